@@ -15,10 +15,11 @@ use log4rs::{
   Config,
 };
 use rustdds::{
-  DomainParticipant, Keyed, QosPolicyBuilder, StatusEvented, TopicDescription, TopicKind,
+  DomainParticipantBuilder, Keyed, QosPolicyBuilder, StatusEvented, TopicDescription, TopicKind,
+  TransportKind,
 };
 use rustdds::policy::{Deadline, Durability, History, Reliability}; /* import all QoS
-                                                                     * policies directly */
+                                                                    * policies directly */
 use serde::{Deserialize, Serialize};
 use clap::{Arg, ArgMatches, Command}; // command line argument processing
 use mio::{Events, Poll, PollOpt, Ready, Token}; // polling
@@ -62,7 +63,19 @@ fn main() {
     .unwrap_or(0);
   let color = matches.value_of("color").unwrap_or("BLUE");
 
-  let domain_participant = DomainParticipant::new(domain_id)
+  let transports = match matches.value_of("ipv6") {
+    Some("prefer") => vec![
+      TransportKind::SharedMemory,
+      TransportKind::UdpV6,
+      TransportKind::UdpV4,
+    ],
+    Some("only") => vec![TransportKind::UdpV6],
+    _ => TransportKind::DEFAULT.to_vec(),
+  };
+
+  let domain_participant = DomainParticipantBuilder::new(domain_id)
+    .transports(transports)
+    .build()
     .unwrap_or_else(|e| panic!("DomainParticipant construction failed: {:?}", e));
 
   let mut qos_b = QosPolicyBuilder::new()
@@ -340,6 +353,7 @@ fn configure_logging() {
   });
 }
 
+#[allow(clippy::too_many_lines)]
 fn get_matches() -> ArgMatches {
   Command::new("RustDDS-interop")
     .version("0.2.2")
@@ -434,6 +448,14 @@ fn get_matches() -> ArgMatches {
         .takes_value(true)
         .value_name("strength"),
     )
+    .arg(
+      Arg::new("ipv6")
+        .help("Use IPv6, either preferring it or only it")
+        .short('6')
+        .takes_value(true)
+        .value_name("usage")
+        .possible_values(&["prefer", "only"]),
+    )
     .get_matches()
 }
 
@@ -0,0 +1,11 @@
+// Feeds arbitrary bytes to the RTPS message parser, as if they were received
+// from the network. Malformed input must be rejected with an error, not a
+// panic.
+//
+// Run with `cargo +nightly fuzz run rtps_message` in the crate root.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+  let _ = rustdds::serialization::parse_rtps_message(data);
+});
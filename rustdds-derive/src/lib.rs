@@ -0,0 +1,901 @@
+//! Derive macros for [RustDDS](https://crates.io/crates/rustdds).
+//!
+//! Use these through the re-exports in `rustdds`. The generated code refers
+//! to items in `rustdds`, so this crate is not useful on its own.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+  parse_macro_input, punctuated::Punctuated, Attribute, Data, DeriveInput, Expr, Field, Fields,
+  Ident, Index, Lit, Member, Meta, NestedMeta, Token, Type,
+};
+
+/// Derive macro for the `DdsType` trait, and `Keyed` if the type has key
+/// fields.
+///
+/// * `#[dds_type(name = "...")]` on the type sets the DDS type name. The
+///   default is the Rust type name.
+/// * `#[dds_key]` on a field makes it a key field. If there is exactly one
+///   key field, its type is the key type, and it must implement `Key`. If
+///   there are several, a key struct named `<Type>Key` is generated. It
+///   contains the key fields in declaration order and implements `Key`.
+///
+/// Types without key fields get no `Keyed` implementation, so they can be
+/// used only in NO_KEY topics.
+#[proc_macro_derive(DdsType, attributes(dds_type, dds_key))]
+pub fn derive_dds_type(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  expand(&input)
+    .unwrap_or_else(syn::Error::into_compile_error)
+    .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+  let name = &input.ident;
+  let type_name = type_name_attribute(&input.attrs)?.unwrap_or_else(|| name.to_string());
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+  let dds_type_impl = quote! {
+    impl #impl_generics ::rustdds::__derive_support::DdsType for #name #ty_generics #where_clause {
+      fn type_name() -> &'static str {
+        #type_name
+      }
+    }
+  };
+
+  let key_fields = key_fields(input)?;
+  let keyed_impl = match key_fields.as_slice() {
+    [] => quote! {},
+    [(member, field)] => {
+      let key_type = &field.ty;
+      quote! {
+        impl #impl_generics ::rustdds::__derive_support::Keyed for #name #ty_generics #where_clause {
+          type K = #key_type;
+          fn key(&self) -> Self::K {
+            ::std::clone::Clone::clone(&self.#member)
+          }
+        }
+      }
+    }
+    _ => {
+      if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+          &input.generics,
+          "DdsType: A generic type may have only one #[dds_key] field",
+        ));
+      }
+      key_struct(input, &key_fields)
+    }
+  };
+
+  Ok(quote! {
+    #dds_type_impl
+    #keyed_impl
+  })
+}
+
+fn type_name_attribute(attrs: &[Attribute]) -> syn::Result<Option<String>> {
+  let mut type_name = None;
+  for attr in attrs.iter().filter(|a| a.path.is_ident("dds_type")) {
+    let list = match attr.parse_meta()? {
+      Meta::List(list) => list,
+      other => {
+        return Err(syn::Error::new_spanned(
+          other,
+          "expected #[dds_type(name = \"...\")]",
+        ))
+      }
+    };
+    for nested in list.nested {
+      match nested {
+        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => match nv.lit {
+          Lit::Str(s) => type_name = Some(s.value()),
+          other => return Err(syn::Error::new_spanned(other, "type name must be a string")),
+        },
+        other => {
+          return Err(syn::Error::new_spanned(
+            other,
+            "unknown dds_type attribute, expected `name = \"...\"`",
+          ))
+        }
+      }
+    }
+  }
+  Ok(type_name)
+}
+
+fn key_fields(input: &DeriveInput) -> syn::Result<Vec<(Member, &Field)>> {
+  let fields = match &input.data {
+    Data::Struct(data) => &data.fields,
+    Data::Enum(data) => {
+      // Enums may be DDS types, but keys are only supported for structs.
+      for variant in &data.variants {
+        for field in &variant.fields {
+          if let Some(attr) = field.attrs.iter().find(|a| a.path.is_ident("dds_key")) {
+            return Err(syn::Error::new_spanned(
+              attr,
+              "DdsType: #[dds_key] is supported only in structs",
+            ));
+          }
+        }
+      }
+      return Ok(Vec::new());
+    }
+    Data::Union(data) => {
+      return Err(syn::Error::new_spanned(
+        data.union_token,
+        "DdsType: Rust unions are not supported",
+      ))
+    }
+  };
+  Ok(
+    fields
+      .iter()
+      .enumerate()
+      .filter(|(_, f)| f.attrs.iter().any(|a| a.path.is_ident("dds_key")))
+      .map(|(i, f)| {
+        let member = match &f.ident {
+          Some(ident) => Member::Named(ident.clone()),
+          None => Member::Unnamed(Index::from(i)),
+        };
+        (member, f)
+      })
+      .collect(),
+  )
+}
+
+// Generate a struct of several key fields, and Key + Keyed impls for it.
+fn key_struct(input: &DeriveInput, key_fields: &[(Member, &Field)]) -> TokenStream {
+  let name = &input.ident;
+  let vis = &input.vis;
+  let key_name = format_ident!("{}Key", name);
+  let doc = format!("Key of [`{}`], generated by `#[derive(DdsType)]`.", name);
+  let types: Vec<_> = key_fields.iter().map(|(_, f)| &f.ty).collect();
+  let members: Vec<_> = key_fields.iter().map(|(m, _)| m).collect();
+
+  let (definition, construction) = match &input.data {
+    Data::Struct(data) if matches!(data.fields, Fields::Named(_)) => {
+      let field_vis: Vec<_> = key_fields.iter().map(|(_, f)| &f.vis).collect();
+      (
+        quote! { #vis struct #key_name { #( #field_vis #members: #types ),* } },
+        quote! { #key_name { #( #members: ::std::clone::Clone::clone(&self.#members) ),* } },
+      )
+    }
+    _ => {
+      let field_vis: Vec<_> = key_fields.iter().map(|(_, f)| &f.vis).collect();
+      (
+        quote! { #vis struct #key_name ( #( #field_vis #types ),* ); },
+        quote! { #key_name ( #( ::std::clone::Clone::clone(&self.#members) ),* ) },
+      )
+    }
+  };
+
+  quote! {
+    #[doc = #doc]
+    #[derive(
+      ::std::fmt::Debug, ::std::clone::Clone, ::std::cmp::PartialEq, ::std::cmp::Eq,
+      ::std::cmp::PartialOrd, ::std::cmp::Ord, ::std::hash::Hash,
+      ::rustdds::__derive_support::serde::Serialize,
+      ::rustdds::__derive_support::serde::Deserialize,
+    )]
+    #[serde(crate = "::rustdds::__derive_support::serde")]
+    #definition
+
+    impl ::rustdds::__derive_support::CdrEncodingSize for #key_name {
+      fn cdr_encoding_max_size() -> ::rustdds::__derive_support::CdrEncodingMaxSize {
+        ::rustdds::__derive_support::CdrEncodingMaxSize::Bytes(0)
+          #( + <#types as ::rustdds::__derive_support::CdrEncodingSize>::cdr_encoding_max_size() )*
+      }
+    }
+
+    impl ::rustdds::__derive_support::Key for #key_name {}
+
+    impl ::rustdds::__derive_support::Keyed for #name {
+      type K = #key_name;
+      fn key(&self) -> Self::K {
+        #construction
+      }
+    }
+  }
+}
+
+/// Derive macro for serializing a fieldless enum as an IDL `enum`.
+///
+/// IDL enums are encoded as their numeric value, an unsigned 32-bit integer.
+/// The values are taken from the Rust discriminants, so explicit and
+/// non-contiguous values such as `Red = 1, Blue = 4` are supported. Unlike
+/// the plain Serde derive, this does not depend on the variant index.
+/// Deserializing an unknown value is an error.
+#[proc_macro_derive(DdsEnum)]
+pub fn derive_dds_enum(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  expand_enum(&input)
+    .unwrap_or_else(syn::Error::into_compile_error)
+    .into()
+}
+
+fn expand_enum(input: &DeriveInput) -> syn::Result<TokenStream> {
+  let name = &input.ident;
+  let data = match &input.data {
+    Data::Enum(data) => data,
+    _ => {
+      return Err(syn::Error::new_spanned(
+        name,
+        "DdsEnum: Only enums are supported",
+      ))
+    }
+  };
+  if !input.generics.params.is_empty() {
+    return Err(syn::Error::new_spanned(
+      &input.generics,
+      "DdsEnum: Generic enums are not supported",
+    ));
+  }
+
+  // Values as i64 constant expressions. A variant without a discriminant is
+  // one more than the previous one, like in Rust and IDL.
+  let mut values = Vec::new();
+  let mut previous: Option<TokenStream> = None;
+  for variant in &data.variants {
+    if !matches!(variant.fields, Fields::Unit) {
+      return Err(syn::Error::new_spanned(
+        variant,
+        "DdsEnum: Variants must not have fields, use DdsUnion for those",
+      ));
+    }
+    let value = match (&variant.discriminant, &previous) {
+      (Some((_, expr)), _) => quote! { (#expr) },
+      (None, Some(previous)) => quote! { (#previous + 1) },
+      (None, None) => quote! { 0 },
+    };
+    previous = Some(value.clone());
+    values.push(value);
+  }
+  let variants: Vec<_> = data.variants.iter().map(|v| &v.ident).collect();
+  let indices: Vec<_> = (0..variants.len()).collect();
+  let count = variants.len();
+  let expecting = format!("a value of enum {}", name);
+
+  Ok(quote! {
+    const _: () = {
+      use ::rustdds::__derive_support::serde;
+
+      const VALUES: [i64; #count] = [ #( #values ),* ];
+
+      impl serde::Serialize for #name {
+        fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where
+          S: serde::Serializer,
+        {
+          let index: usize = match self {
+            #( Self::#variants => #indices, )*
+          };
+          serializer.serialize_u32(VALUES[index] as u32)
+        }
+      }
+
+      impl<'de> serde::Deserialize<'de> for #name {
+        fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+        where
+          D: serde::Deserializer<'de>,
+        {
+          let value = <u32 as serde::Deserialize>::deserialize(deserializer)?;
+          match VALUES.iter().position(|v| *v as u32 == value) {
+            #( ::std::option::Option::Some(#indices) => ::std::result::Result::Ok(Self::#variants), )*
+            _ => ::std::result::Result::Err(<D::Error as serde::de::Error>::invalid_value(
+              serde::de::Unexpected::Unsigned(u64::from(value)),
+              &#expecting,
+            )),
+          }
+        }
+      }
+    };
+  })
+}
+
+/// Derive macro for serializing an enum as an IDL `union`.
+///
+/// A union is encoded as its discriminator followed by the active member,
+/// if the selected case has one. Each variant is a union case and has at
+/// most one field, which is the member.
+///
+/// * `#[dds_union(discriminator = "i16")]` on the type sets the discriminator
+///   type. The default is `i32`. Other possible types are the other integer
+///   types, `char`, `bool` and enums deriving `DdsEnum`. The discriminator
+///   type must implement `PartialEq`.
+/// * `#[dds_case(label, ...)]` on a variant lists the discriminator values
+///   that select it. The first one is used when serializing.
+/// * `#[dds_default(label)]` marks the `default` case, which is selected by
+///   any value not listed in other cases. The label is the discriminator
+///   value written when serializing it.
+///
+/// Every variant must have either `dds_case` or `dds_default`. If there is no
+/// default case, deserializing an unlisted discriminator is an error.
+#[proc_macro_derive(DdsUnion, attributes(dds_union, dds_case, dds_default))]
+pub fn derive_dds_union(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  expand_union(&input)
+    .unwrap_or_else(syn::Error::into_compile_error)
+    .into()
+}
+
+struct UnionCase<'a> {
+  variant: &'a Ident,
+  // Type of the member, or None for a case without one
+  member: Option<&'a Type>,
+  labels: Vec<Expr>,
+  default: bool,
+}
+
+fn expand_union(input: &DeriveInput) -> syn::Result<TokenStream> {
+  let name = &input.ident;
+  let data = match &input.data {
+    Data::Enum(data) => data,
+    _ => {
+      return Err(syn::Error::new_spanned(
+        name,
+        "DdsUnion: Only enums are supported",
+      ))
+    }
+  };
+  if !input.generics.params.is_empty() {
+    return Err(syn::Error::new_spanned(
+      &input.generics,
+      "DdsUnion: Generic enums are not supported",
+    ));
+  }
+  let discriminator = discriminator_attribute(&input.attrs)?;
+
+  let mut cases = Vec::new();
+  for variant in &data.variants {
+    let member = match &variant.fields {
+      Fields::Unit => None,
+      Fields::Unnamed(fields) if fields.unnamed.len() == 1 => Some(&fields.unnamed[0].ty),
+      _ => {
+        return Err(syn::Error::new_spanned(
+          variant,
+          "DdsUnion: Variants must be unit or have exactly one unnamed field",
+        ))
+      }
+    };
+    let mut labels = Vec::new();
+    let mut default = false;
+    for attr in &variant.attrs {
+      if attr.path.is_ident("dds_case") {
+        labels.extend(attr.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated)?);
+      } else if attr.path.is_ident("dds_default") {
+        if default || cases.iter().any(|c: &UnionCase| c.default) {
+          return Err(syn::Error::new_spanned(
+            attr,
+            "DdsUnion: There can be only one default case",
+          ));
+        }
+        default = true;
+        labels.insert(0, attr.parse_args::<Expr>()?);
+      }
+    }
+    if labels.is_empty() {
+      return Err(syn::Error::new_spanned(
+        variant,
+        "DdsUnion: Variant needs #[dds_case(...)] or #[dds_default(...)]",
+      ));
+    }
+    cases.push(UnionCase {
+      variant: &variant.ident,
+      member,
+      labels,
+      default,
+    });
+  }
+
+  let serialize_arms = cases.iter().map(|case| {
+    let variant = case.variant;
+    let label = &case.labels[0];
+    match case.member {
+      Some(_) => quote! {
+        Self::#variant(member) => {
+          let discriminator: #discriminator = #label;
+          let mut tuple = serializer.serialize_tuple(2)?;
+          tuple.serialize_element(&discriminator)?;
+          tuple.serialize_element(member)?;
+          tuple.end()
+        }
+      },
+      None => quote! {
+        Self::#variant => {
+          let discriminator: #discriminator = #label;
+          let mut tuple = serializer.serialize_tuple(1)?;
+          tuple.serialize_element(&discriminator)?;
+          tuple.end()
+        }
+      },
+    }
+  });
+
+  let construct = |case: &UnionCase| {
+    let variant = case.variant;
+    match case.member {
+      Some(member) => quote! {
+        seq
+          .next_element::<#member>()?
+          .map(#name::#variant)
+          .ok_or_else(|| serde::de::Error::invalid_length(1, &self))
+      },
+      None => quote! { ::std::result::Result::Ok(#name::#variant) },
+    }
+  };
+  let case_checks = cases.iter().filter(|c| !c.default).map(|case| {
+    let labels = &case.labels;
+    let construction = construct(case);
+    quote! {
+      if #( discriminator == #labels )||* {
+        return #construction;
+      }
+    }
+  });
+  let fallback = match cases.iter().find(|c| c.default) {
+    Some(case) => construct(case),
+    None => {
+      let message = format!("union {}: No case for the discriminator", name);
+      quote! { ::std::result::Result::Err(serde::de::Error::custom(#message)) }
+    }
+  };
+  let expecting = format!("union {}", name);
+
+  Ok(quote! {
+    const _: () = {
+      use ::rustdds::__derive_support::serde;
+
+      impl serde::Serialize for #name {
+        fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where
+          S: serde::Serializer,
+        {
+          use serde::ser::SerializeTuple;
+          match self {
+            #( #serialize_arms )*
+          }
+        }
+      }
+
+      impl<'de> serde::Deserialize<'de> for #name {
+        fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+        where
+          D: serde::Deserializer<'de>,
+        {
+          struct UnionVisitor;
+
+          impl<'de> serde::de::Visitor<'de> for UnionVisitor {
+            type Value = #name;
+
+            fn expecting(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+              formatter.write_str(#expecting)
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> ::std::result::Result<Self::Value, A::Error>
+            where
+              A: serde::de::SeqAccess<'de>,
+            {
+              let discriminator: #discriminator = seq
+                .next_element()?
+                .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+              #( #case_checks )*
+              #fallback
+            }
+          }
+
+          deserializer.deserialize_tuple(2, UnionVisitor)
+        }
+      }
+    };
+  })
+}
+
+fn discriminator_attribute(attrs: &[Attribute]) -> syn::Result<Type> {
+  let mut discriminator = syn::parse_quote! { i32 };
+  for attr in attrs.iter().filter(|a| a.path.is_ident("dds_union")) {
+    let list = match attr.parse_meta()? {
+      Meta::List(list) => list,
+      other => {
+        return Err(syn::Error::new_spanned(
+          other,
+          "expected #[dds_union(discriminator = \"...\")]",
+        ))
+      }
+    };
+    for nested in list.nested {
+      match nested {
+        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("discriminator") => {
+          match nv.lit {
+            Lit::Str(s) => discriminator = s.parse()?,
+            other => {
+              return Err(syn::Error::new_spanned(
+                other,
+                "discriminator type must be a string",
+              ))
+            }
+          }
+        }
+        other => {
+          return Err(syn::Error::new_spanned(
+            other,
+            "unknown dds_union attribute, expected `discriminator = \"...\"`",
+          ))
+        }
+      }
+    }
+  }
+  Ok(discriminator)
+}
+
+/// Derive macro for serializing a newtype struct as an IDL `bitmask`.
+///
+/// A bitmask is encoded as an unsigned integer of the smallest size that
+/// holds its bits: `u8` for a bit bound of up to 8, `u16` for up to 16, `u32`
+/// for up to 32 and `u64` for up to 64. The only field of the struct is of
+/// that type, and holds the bits.
+///
+/// * `#[dds_bitmask(bit_bound = 12)]` on the type sets the number of bits.
+///   The default is 32, like in IDL.
+///
+/// The derive also implements the `|`, `&` and `^` operators and a
+/// `contains` method. Serializing or deserializing a value with bits set at
+/// or above the bit bound is an error.
+#[proc_macro_derive(DdsBitmask, attributes(dds_bitmask))]
+pub fn derive_dds_bitmask(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  expand_bitmask(&input)
+    .unwrap_or_else(syn::Error::into_compile_error)
+    .into()
+}
+
+fn expand_bitmask(input: &DeriveInput) -> syn::Result<TokenStream> {
+  let name = &input.ident;
+  let newtype = match &input.data {
+    Data::Struct(data) => matches!(&data.fields, Fields::Unnamed(f) if f.unnamed.len() == 1),
+    _ => false,
+  };
+  if !newtype {
+    return Err(syn::Error::new_spanned(
+      name,
+      "DdsBitmask: Only newtype structs, e.g. `struct Flags(u16);`, are supported",
+    ));
+  }
+  if !input.generics.params.is_empty() {
+    return Err(syn::Error::new_spanned(
+      &input.generics,
+      "DdsBitmask: Generic structs are not supported",
+    ));
+  }
+  let bit_bound = integer_attribute(&input.attrs, "dds_bitmask", "bit_bound")?.unwrap_or(32);
+  let wire = wire_type(name, bit_bound)?;
+  let serialize_wire = format_ident!("serialize_{}", wire);
+
+  // Bits at or above the bound, within the wire type. There are none, if the
+  // bound is the size of the wire type.
+  let wire_max = u64::MAX >> (64 - wire_bits(bit_bound));
+  let invalid_bits = (u64::MAX.checked_shl(bit_bound as u32).unwrap_or(0)) & wire_max;
+  let invalid_bits = proc_macro2::Literal::u64_unsuffixed(invalid_bits);
+  let serialize_check = if bit_bound < wire_bits(bit_bound) {
+    let message = format!(
+      "bitmask {}: Bits at or above bit_bound {} are set",
+      name, bit_bound
+    );
+    quote! {
+      if self.0 & #invalid_bits != 0 {
+        return ::std::result::Result::Err(<S::Error as serde::ser::Error>::custom(#message));
+      }
+    }
+  } else {
+    quote! {}
+  };
+  let deserialize_check = if bit_bound < wire_bits(bit_bound) {
+    let expecting = format!("a value of bitmask {}", name);
+    quote! {
+      if bits & #invalid_bits != 0 {
+        return ::std::result::Result::Err(<D::Error as serde::de::Error>::invalid_value(
+          serde::de::Unexpected::Unsigned(u64::from(bits)),
+          &#expecting,
+        ));
+      }
+    }
+  } else {
+    quote! {}
+  };
+
+  Ok(quote! {
+    const _: () = {
+      use ::rustdds::__derive_support::serde;
+
+      impl #name {
+        /// True if all the bits that are set in `other` are set also in
+        /// `self`.
+        pub fn contains(&self, other: Self) -> bool {
+          self.0 & other.0 == other.0
+        }
+      }
+
+      impl ::std::ops::BitOr for #name {
+        type Output = Self;
+        fn bitor(self, other: Self) -> Self {
+          Self(self.0 | other.0)
+        }
+      }
+
+      impl ::std::ops::BitAnd for #name {
+        type Output = Self;
+        fn bitand(self, other: Self) -> Self {
+          Self(self.0 & other.0)
+        }
+      }
+
+      impl ::std::ops::BitXor for #name {
+        type Output = Self;
+        fn bitxor(self, other: Self) -> Self {
+          Self(self.0 ^ other.0)
+        }
+      }
+
+      impl serde::Serialize for #name {
+        fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where
+          S: serde::Serializer,
+        {
+          #serialize_check
+          serializer.#serialize_wire(self.0)
+        }
+      }
+
+      impl<'de> serde::Deserialize<'de> for #name {
+        fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+        where
+          D: serde::Deserializer<'de>,
+        {
+          let bits = <#wire as serde::Deserialize>::deserialize(deserializer)?;
+          #deserialize_check
+          ::std::result::Result::Ok(Self(bits))
+        }
+      }
+    };
+  })
+}
+
+/// Derive macro for serializing a struct as an IDL `bitset`.
+///
+/// The fields are bit fields, packed from the least significant bit up, in
+/// declaration order. A bitset is encoded as an unsigned integer of the
+/// smallest size that holds all the bits, like a bitmask.
+///
+/// * `#[dds_bitfield(width)]` on each field sets its width in bits. The field
+///   type is `bool` or an integer type. Signed values are sign extended when
+///   deserialized.
+/// * `#[dds_bitfield(width, skip = n)]` leaves `n` unused bits before the
+///   field, for anonymous IDL bit fields.
+/// * `#[dds_bitset(bits = n)]` on the type sets the total number of bits, if
+///   there are unused bits after the last field.
+///
+/// Unused bits are written as zero and ignored when reading. Serializing a
+/// value that does not fit in its bit field is an error.
+#[proc_macro_derive(DdsBitset, attributes(dds_bitset, dds_bitfield))]
+pub fn derive_dds_bitset(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  expand_bitset(&input)
+    .unwrap_or_else(syn::Error::into_compile_error)
+    .into()
+}
+
+enum BitfieldKind {
+  Bool,
+  Unsigned,
+  Signed,
+}
+
+fn expand_bitset(input: &DeriveInput) -> syn::Result<TokenStream> {
+  let name = &input.ident;
+  let fields = match &input.data {
+    Data::Struct(data) if matches!(data.fields, Fields::Named(_)) => &data.fields,
+    _ => {
+      return Err(syn::Error::new_spanned(
+        name,
+        "DdsBitset: Only structs with named fields are supported",
+      ))
+    }
+  };
+  if !input.generics.params.is_empty() {
+    return Err(syn::Error::new_spanned(
+      &input.generics,
+      "DdsBitset: Generic structs are not supported",
+    ));
+  }
+
+  let mut serialize_fields = Vec::new();
+  let mut deserialize_fields = Vec::new();
+  let mut position = 0;
+  for field in fields {
+    let ident = field.ident.as_ref().unwrap();
+    let (width, skip) = bitfield_attribute(field)?;
+    position += skip;
+    if !(1..=64).contains(&width) || position + width > 64 {
+      return Err(syn::Error::new_spanned(
+        field,
+        "DdsBitset: Bit fields must fit in 64 bits",
+      ));
+    }
+    let kind = match &field.ty {
+      Type::Path(path) => match path.path.get_ident().map(|i| i.to_string()).as_deref() {
+        Some("bool") => BitfieldKind::Bool,
+        Some("i8") | Some("i16") | Some("i32") | Some("i64") => BitfieldKind::Signed,
+        _ => BitfieldKind::Unsigned,
+      },
+      _ => BitfieldKind::Unsigned,
+    };
+    let shift = proc_macro2::Literal::u64_unsuffixed(position);
+    let mask = proc_macro2::Literal::u64_unsuffixed(u64::MAX >> (64 - width));
+    let unused = proc_macro2::Literal::u64_unsuffixed(64 - width);
+    let too_large = format!(
+      "bitset {}: Value of {} does not fit in {} bits",
+      name, ident, width
+    );
+    let too_large = quote! {
+      return ::std::result::Result::Err(<S::Error as serde::ser::Error>::custom(#too_large));
+    };
+    let out_of_range = format!("bitset {}: Value of {} is out of range", name, ident);
+    let out_of_range = quote! { |_| <D::Error as serde::de::Error>::custom(#out_of_range) };
+
+    // Any value fits in 64 bits.
+    let check = match kind {
+      _ if width == 64 => quote! {},
+      BitfieldKind::Bool => quote! {},
+      BitfieldKind::Unsigned => quote! {
+        if value & !#mask != 0 {
+          #too_large
+        }
+      },
+      BitfieldKind::Signed => quote! {
+        if (value << #unused) >> #unused != value {
+          #too_large
+        }
+      },
+    };
+    serialize_fields.push(match kind {
+      BitfieldKind::Bool => quote! {
+        bits |= u64::from(self.#ident) << #shift;
+      },
+      BitfieldKind::Unsigned => quote! {
+        let value = u64::from(self.#ident);
+        #check
+        bits |= value << #shift;
+      },
+      BitfieldKind::Signed => quote! {
+        let value = i64::from(self.#ident);
+        #check
+        bits |= (value as u64 & #mask) << #shift;
+      },
+    });
+    deserialize_fields.push(match kind {
+      BitfieldKind::Bool => quote! {
+        #ident: (bits >> #shift) & #mask != 0,
+      },
+      BitfieldKind::Unsigned => quote! {
+        #ident: ::std::convert::TryFrom::try_from((bits >> #shift) & #mask)
+          .map_err(#out_of_range)?,
+      },
+      BitfieldKind::Signed => quote! {
+        #ident: ::std::convert::TryFrom::try_from(
+          ((((bits >> #shift) & #mask) << #unused) as i64) >> #unused,
+        )
+        .map_err(#out_of_range)?,
+      },
+    });
+    position += width;
+  }
+
+  let bits = integer_attribute(&input.attrs, "dds_bitset", "bits")?.unwrap_or(position);
+  if bits < position {
+    return Err(syn::Error::new_spanned(
+      name,
+      "DdsBitset: The bit fields do not fit in `bits`",
+    ));
+  }
+  let wire = wire_type(name, bits)?;
+  let serialize_wire = format_ident!("serialize_{}", wire);
+
+  Ok(quote! {
+    const _: () = {
+      use ::rustdds::__derive_support::serde;
+
+      impl serde::Serialize for #name {
+        fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where
+          S: serde::Serializer,
+        {
+          let mut bits: u64 = 0;
+          #( #serialize_fields )*
+          serializer.#serialize_wire(bits as #wire)
+        }
+      }
+
+      impl<'de> serde::Deserialize<'de> for #name {
+        fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+        where
+          D: serde::Deserializer<'de>,
+        {
+          let bits = u64::from(<#wire as serde::Deserialize>::deserialize(deserializer)?);
+          ::std::result::Result::Ok(Self {
+            #( #deserialize_fields )*
+          })
+        }
+      }
+    };
+  })
+}
+
+// Number of bits in the unsigned integer type that holds `bits` bits
+fn wire_bits(bits: u64) -> u64 {
+  match bits {
+    0..=8 => 8,
+    9..=16 => 16,
+    17..=32 => 32,
+    _ => 64,
+  }
+}
+
+fn wire_type(name: &Ident, bits: u64) -> syn::Result<Ident> {
+  if !(1..=64).contains(&bits) {
+    return Err(syn::Error::new_spanned(
+      name,
+      "The number of bits must be from 1 to 64",
+    ));
+  }
+  Ok(format_ident!("u{}", wire_bits(bits)))
+}
+
+// Value of an integer attribute argument, e.g. `#[dds_bitmask(bit_bound = 12)]`
+fn integer_attribute(attrs: &[Attribute], attr_name: &str, key: &str) -> syn::Result<Option<u64>> {
+  let mut value = None;
+  for attr in attrs.iter().filter(|a| a.path.is_ident(attr_name)) {
+    let expected = format!("expected #[{}({} = ...)]", attr_name, key);
+    let list = match attr.parse_meta()? {
+      Meta::List(list) => list,
+      other => return Err(syn::Error::new_spanned(other, expected)),
+    };
+    for nested in list.nested {
+      match nested {
+        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident(key) => match nv.lit {
+          Lit::Int(i) => value = Some(i.base10_parse()?),
+          other => return Err(syn::Error::new_spanned(other, "expected an integer")),
+        },
+        other => return Err(syn::Error::new_spanned(other, expected)),
+      }
+    }
+  }
+  Ok(value)
+}
+
+// Width and skipped bits of a bit field, from `#[dds_bitfield(3, skip = 2)]`
+fn bitfield_attribute(field: &Field) -> syn::Result<(u64, u64)> {
+  let expected = "DdsBitset: expected #[dds_bitfield(width)] or #[dds_bitfield(width, skip = n)]";
+  let attr = field
+    .attrs
+    .iter()
+    .find(|a| a.path.is_ident("dds_bitfield"))
+    .ok_or_else(|| syn::Error::new_spanned(field, expected))?;
+  let list = match attr.parse_meta()? {
+    Meta::List(list) => list,
+    other => return Err(syn::Error::new_spanned(other, expected)),
+  };
+  let mut width = None;
+  let mut skip = 0;
+  for nested in list.nested {
+    match nested {
+      NestedMeta::Lit(Lit::Int(i)) if width.is_none() => width = Some(i.base10_parse()?),
+      NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("skip") => match nv.lit {
+        Lit::Int(i) => skip = i.base10_parse()?,
+        other => return Err(syn::Error::new_spanned(other, "expected an integer")),
+      },
+      other => return Err(syn::Error::new_spanned(other, expected)),
+    }
+  }
+  let width = width.ok_or_else(|| syn::Error::new_spanned(attr, expected))?;
+  Ok((width, skip))
+}
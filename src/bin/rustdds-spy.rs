@@ -0,0 +1,254 @@
+//! Command line tool for inspecting a DDS domain.
+//!
+//! Lists the participants and topics found by discovery, and prints the
+//! samples written on any topic:
+//!
+//! ```text
+//! rustdds-spy [-d <domain_id>] participants
+//! rustdds-spy [-d <domain_id>] topics
+//! rustdds-spy [-d <domain_id>] echo <topic> [-s <schema>]
+//! ```
+//!
+//! Samples are hex dumped, because their data type is not known. If a schema
+//! is given, CDR samples are decoded and printed as JSON instead. The schema
+//! lists the members of the data type as `name:type`, separated by commas,
+//! e.g. `color:string,x:i32,y:i32,shapesize:i32` for the shapes demo. Types
+//! are `bool`, `i8`, `u8`, `i16`, `u16`, `i32`, `u32`, `i64`, `u64`, `f32`,
+//! `f64`, `char` and `string`, followed by `[]` for a sequence or `[n]` for an
+//! array.
+//!
+//! Build with `cargo build --features spy`.
+
+use std::{thread, time::Duration};
+
+use clap::{Arg, ArgMatches, Command};
+use rustdds::{
+  policy::{Durability, History, Reliability},
+  serialization::CdrSchema,
+  DomainParticipant, QosPolicies, QosPolicyBuilder, RawDeserializerAdapter, RawSample, TopicKind,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn main() {
+  let matches = get_matches();
+  let domain_id = matches
+    .value_of("domain_id")
+    .unwrap_or("0")
+    .parse::<u16>()
+    .unwrap_or_else(|e| exit(&format!("Bad domain id: {}", e)));
+  let participant = DomainParticipant::new(domain_id)
+    .unwrap_or_else(|e| exit(&format!("Cannot create DomainParticipant: {}", e)));
+
+  let result = match matches.subcommand() {
+    Some(("participants", _)) => list_participants(&participant),
+    Some(("topics", _)) => list_topics(&participant),
+    Some(("echo", echo_matches)) => echo(&participant, echo_matches),
+    _ => unreachable!("clap requires a subcommand"),
+  };
+  if let Err(e) = result {
+    exit(&e.to_string());
+  }
+}
+
+fn get_matches() -> ArgMatches {
+  Command::new("rustdds-spy")
+    .about("Inspect participants, topics and samples of a DDS domain")
+    .arg(
+      Arg::new("domain_id")
+        .short('d')
+        .value_name("id")
+        .help("DDS domain id, default 0")
+        .takes_value(true),
+    )
+    .subcommand_required(true)
+    .subcommand(Command::new("participants").about("Print participants as they come and go"))
+    .subcommand(Command::new("topics").about("Print the writers and readers of each topic"))
+    .subcommand(
+      Command::new("echo")
+        .about("Print the samples written on a topic")
+        .arg(Arg::new("topic").required(true).help("Topic name"))
+        .arg(
+          Arg::new("schema")
+            .short('s')
+            .long("schema")
+            .value_name("schema")
+            .help("Members of the data type, e.g. \"color:string,x:i32\"")
+            .takes_value(true),
+        ),
+    )
+    .get_matches()
+}
+
+fn exit(message: &str) -> ! {
+  eprintln!("rustdds-spy: {}", message);
+  std::process::exit(1)
+}
+
+fn list_participants(participant: &DomainParticipant) -> rustdds::dds::Result<()> {
+  let mut reader = participant.participant_builtin_reader()?;
+  loop {
+    while let Some(sample) = reader.take_next_sample()? {
+      match sample.value() {
+        Ok(data) => println!(
+          "participant {} name={:?} vendor={:?} locators={:?}",
+          data.participant_guid,
+          data.entity_name.as_deref().unwrap_or(""),
+          data.vendor_id,
+          data.default_unicast_locators,
+        ),
+        Err(guid) => println!("participant {:?} lost", guid),
+      }
+    }
+    thread::sleep(POLL_INTERVAL);
+  }
+}
+
+fn list_topics(participant: &DomainParticipant) -> rustdds::dds::Result<()> {
+  let mut writers = participant.publication_builtin_reader()?;
+  let mut readers = participant.subscription_builtin_reader()?;
+  loop {
+    while let Some(sample) = writers.take_next_sample()? {
+      match sample.value() {
+        Ok(data) => {
+          let topic_data = &data.publication_topic_data;
+          println!(
+            "writer {} topic={:?} type={:?}",
+            topic_data.key, topic_data.topic_name, topic_data.type_name
+          );
+        }
+        Err(guid) => println!("writer {:?} lost", guid),
+      }
+    }
+    while let Some(sample) = readers.take_next_sample()? {
+      match sample.value() {
+        Ok(data) => {
+          let topic_data = &data.subscription_topic_data;
+          println!(
+            "reader {} topic={:?} type={:?}",
+            topic_data.key(),
+            topic_data.topic_name(),
+            topic_data.type_name()
+          );
+        }
+        Err(guid) => println!("reader {:?} lost", guid),
+      }
+    }
+    thread::sleep(POLL_INTERVAL);
+  }
+}
+
+fn echo(participant: &DomainParticipant, matches: &ArgMatches) -> rustdds::dds::Result<()> {
+  let topic_name = matches.value_of("topic").unwrap();
+  let schema = matches
+    .value_of("schema")
+    .map(|s| parse_schema(s).unwrap_or_else(|e| exit(&format!("Bad schema: {}", e))));
+
+  // The type name of the topic is needed to match with its writers.
+  let type_name = loop {
+    if let Some(topic) = participant
+      .discovered_topics()
+      .into_iter()
+      .find(|t| t.topic_name() == topic_name)
+    {
+      break topic.type_name().clone();
+    }
+    thread::sleep(POLL_INTERVAL);
+  };
+  eprintln!("Topic {:?} has type {:?}", topic_name, type_name);
+
+  // Best effort, volatile readers match all writers.
+  let qos = QosPolicyBuilder::new()
+    .reliability(Reliability::BestEffort)
+    .durability(Durability::Volatile)
+    .history(History::KeepAll)
+    .build();
+  let topic =
+    participant.create_topic(topic_name.to_string(), type_name, &qos, TopicKind::NoKey)?;
+  let subscriber = participant.create_subscriber(&QosPolicies::qos_none())?;
+  let mut reader =
+    subscriber.create_datareader_no_key::<RawSample, RawDeserializerAdapter>(&topic, Some(qos))?;
+
+  loop {
+    while let Some(sample) = reader.take_next_sample()? {
+      let info = sample.sample_info();
+      println!(
+        "from {} source_timestamp={:?}",
+        info.writer_guid(),
+        info.source_timestamp()
+      );
+      print_sample(sample.value(), schema.as_ref());
+    }
+    thread::sleep(POLL_INTERVAL);
+  }
+}
+
+// JSON if the schema can decode it, otherwise hex dump.
+fn print_sample(sample: &RawSample, schema: Option<&CdrSchema>) {
+  if let Some(schema) = schema {
+    match schema.to_json(&sample.bytes, sample.encoding) {
+      Ok(json) => {
+        println!("{}", json);
+        return;
+      }
+      Err(e) => eprintln!("Cannot decode with schema: {}", e),
+    }
+  }
+  println!("{:?}, {} bytes", sample.encoding, sample.bytes.len());
+  for (line, chunk) in sample.bytes.chunks(16).enumerate() {
+    let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+    let text: String = chunk
+      .iter()
+      .map(|&b| {
+        if b.is_ascii_graphic() || b == b' ' {
+          b as char
+        } else {
+          '.'
+        }
+      })
+      .collect();
+    println!("{:08x}  {:<47}  {}", line * 16, hex.join(" "), text);
+  }
+}
+
+fn parse_schema(schema: &str) -> Result<CdrSchema, String> {
+  let members = schema
+    .split(',')
+    .map(|member| match member.trim().split_once(':') {
+      Some((name, member_type)) => Ok((name.trim(), parse_type(member_type.trim())?)),
+      None => Err(format!("Expected name:type, found {:?}", member)),
+    })
+    .collect::<Result<Vec<_>, String>>()?;
+  Ok(CdrSchema::structure(members))
+}
+
+fn parse_type(member_type: &str) -> Result<CdrSchema, String> {
+  if let Some(element) = member_type.strip_suffix("[]") {
+    return Ok(CdrSchema::Sequence(Box::new(parse_type(element)?)));
+  }
+  if let (Some(start), Some(element)) = (member_type.rfind('['), member_type.strip_suffix(']')) {
+    let len = element[start + 1..]
+      .parse::<usize>()
+      .map_err(|e| format!("Bad array length in {:?}: {}", member_type, e))?;
+    return Ok(CdrSchema::Array(
+      Box::new(parse_type(&member_type[..start])?),
+      len,
+    ));
+  }
+  Ok(match member_type {
+    "bool" => CdrSchema::Bool,
+    "i8" => CdrSchema::I8,
+    "u8" => CdrSchema::U8,
+    "i16" => CdrSchema::I16,
+    "u16" => CdrSchema::U16,
+    "i32" => CdrSchema::I32,
+    "u32" => CdrSchema::U32,
+    "i64" => CdrSchema::I64,
+    "u64" => CdrSchema::U64,
+    "f32" => CdrSchema::F32,
+    "f64" => CdrSchema::F64,
+    "char" => CdrSchema::Char,
+    "string" => CdrSchema::String,
+    other => return Err(format!("Unknown type {:?}", other)),
+  })
+}
@@ -0,0 +1,705 @@
+use std::{
+  collections::HashMap,
+  fs::{self, File, OpenOptions},
+  io::{self, Read, Seek, SeekFrom, Write},
+  path::{Path, PathBuf},
+  time::Duration as StdDuration,
+};
+
+use mio::{Evented, Poll, PollOpt, Ready, Token};
+use serde::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::{
+  dds::{
+    no_key::{datareader::DataReader, datawriter::DataWriter},
+    pubsub::{Publisher, Subscriber},
+    qos::{policy, HasQoSPolicy, QosPolicies, QosPolicyBuilder},
+    topic::Topic,
+    values::result::{Error, Result},
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+  structure::duration::Duration,
+};
+
+const DEFAULT_CHUNK_SIZE: usize = 16 * 1024;
+
+// Sender waits for acknowledgements after this many chunks, so that the
+// DataWriter does not buffer the whole blob. This must stay below the capacity
+// of the DataWriter command channel.
+const SEND_WINDOW: usize = 8;
+const ACK_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+
+// Blobs being received are stored as "<blob_id>.partial" until complete.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+// Unit of transfer on the data Topic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BlobChunk {
+  blob_id: String,
+  total_size: u64,
+  digest: [u8; 16], // MD5 of the whole blob
+  offset: u64,
+  data: Vec<u8>,
+}
+
+// Sent on the request Topic: receiver asks to (re)send a blob from `offset`
+// onwards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BlobRequest {
+  blob_id: String,
+  offset: u64,
+}
+
+/// Progress of a blob transfer, passed to progress callbacks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobProgress {
+  pub blob_id: String,
+  pub transferred: u64,
+  pub total: u64,
+}
+
+/// A blob that has been completely received and verified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceivedBlob {
+  pub blob_id: String,
+  pub path: PathBuf,
+}
+
+// Blob ids are used as file names on the receiving side.
+fn check_blob_id(blob_id: &str) -> io::Result<()> {
+  let valid = !blob_id.is_empty()
+    && !blob_id.starts_with('.')
+    && blob_id
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+  if valid {
+    Ok(())
+  } else {
+    Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      format!("Invalid blob id {:?}", blob_id),
+    ))
+  }
+}
+
+fn data_qos(topic: &Topic) -> QosPolicies {
+  topic.qos().modify_by(
+    &QosPolicyBuilder::new()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      })
+      .history(policy::History::KeepAll)
+      .build(),
+  )
+}
+
+// Requests are TRANSIENT_LOCAL, so that a sender started after the receiver
+// still gets them.
+fn request_qos(topic: &Topic) -> QosPolicies {
+  data_qos(topic).modify_by(
+    &QosPolicyBuilder::new()
+      .durability(policy::Durability::TransientLocal)
+      .build(),
+  )
+}
+
+fn to_io_error(e: Error) -> io::Error {
+  match e {
+    Error::Io(e) => e,
+    other => io::Error::new(io::ErrorKind::Other, other),
+  }
+}
+
+fn md5_of(reader: &mut impl Read) -> io::Result<(u64, [u8; 16])> {
+  let mut context = md5::Context::new();
+  let mut buffer = vec![0; DEFAULT_CHUNK_SIZE];
+  let mut size = 0;
+  loop {
+    let n = reader.read(&mut buffer)?;
+    if n == 0 {
+      return Ok((size, context.compute().0));
+    }
+    context.consume(&buffer[..n]);
+    size += n as u64;
+  }
+}
+
+trait BlobSource: Read + Seek + Send {}
+impl<T: Read + Seek + Send> BlobSource for T {}
+
+struct OutgoingBlob {
+  source: Box<dyn BlobSource>,
+  total_size: u64,
+  digest: [u8; 16],
+}
+
+/// Sending side of blob transfer.
+///
+/// Transfers large binary objects, e.g. files, over a pair of NoKey Topics:
+/// the data Topic carries the blob in chunks, and the request Topic carries
+/// resume requests from receivers to the sender. The receiving side is a
+/// [`BlobReceiver`] on the same Topics.
+///
+/// Chunks are sent with RELIABLE Reliability, and DDS fragments them as
+/// needed. Every chunk carries the MD5 digest of the whole blob, which the
+/// receiver checks when the blob is complete.
+///
+/// A sent blob stays available until [`remove`](Self::remove)d, so that
+/// receivers that were restarted, or joined late, can request the rest of it.
+/// Requests are served by [`handle_requests`](Self::handle_requests), which
+/// should be called when the `BlobSender` is readable in a [`mio::Poll`], or
+/// periodically.
+pub struct BlobSender {
+  writer: DataWriter<BlobChunk, CDRSerializerAdapter<BlobChunk>>,
+  request_reader: DataReader<BlobRequest, CDRDeserializerAdapter<BlobRequest>>,
+  blobs: HashMap<String, OutgoingBlob>,
+  chunk_size: usize,
+  unacknowledged: usize, // chunks written since last wait for acknowledgements
+}
+
+impl BlobSender {
+  pub fn new(
+    publisher: &Publisher,
+    subscriber: &Subscriber,
+    data_topic: &Topic,
+    request_topic: &Topic,
+  ) -> Result<Self> {
+    let writer = publisher.create_datawriter_no_key_cdr(data_topic, Some(data_qos(data_topic)))?;
+    let request_reader =
+      subscriber.create_datareader_no_key_cdr(request_topic, Some(request_qos(request_topic)))?;
+    Ok(Self {
+      writer,
+      request_reader,
+      blobs: HashMap::new(),
+      chunk_size: DEFAULT_CHUNK_SIZE,
+      unacknowledged: 0,
+    })
+  }
+
+  /// Size of the chunks the blob is split into. Default is 16 KiB.
+  pub fn set_chunk_size(&mut self, chunk_size: usize) {
+    self.chunk_size = chunk_size.max(1);
+  }
+
+  /// Send a blob to all matched receivers.
+  ///
+  /// `blob_id` is used as a file name by receivers, so it may contain only
+  /// ASCII letters, digits, `-`, `_` and `.`, and must not start with `.`.
+  /// Sending again with the same `blob_id` replaces the blob.
+  ///
+  /// `progress` is called after each chunk. This call blocks while waiting for
+  /// receivers to acknowledge chunks.
+  pub fn send<S, F>(&mut self, blob_id: &str, source: S, progress: F) -> io::Result<()>
+  where
+    S: Read + Seek + Send + 'static,
+    F: FnMut(&BlobProgress),
+  {
+    check_blob_id(blob_id)?;
+    let mut source: Box<dyn BlobSource> = Box::new(source);
+    let (total_size, digest) = md5_of(&mut source)?;
+    self.blobs.insert(
+      blob_id.to_string(),
+      OutgoingBlob {
+        source,
+        total_size,
+        digest,
+      },
+    );
+    self.send_from(blob_id, 0, progress)
+  }
+
+  /// Stop serving requests for a blob. Returns false if there was no such
+  /// blob.
+  pub fn remove(&mut self, blob_id: &str) -> bool {
+    self.blobs.remove(blob_id).is_some()
+  }
+
+  /// Serve resume requests received from `BlobReceiver`s. Returns the number
+  /// of requests served. Requests for unknown blobs are ignored.
+  pub fn handle_requests(&mut self) -> io::Result<usize> {
+    let mut served = 0;
+    while let Some(sample) = self
+      .request_reader
+      .take_next_sample()
+      .map_err(to_io_error)?
+    {
+      let request = sample.into_value();
+      if self.blobs.contains_key(&request.blob_id) {
+        info!(
+          "Resending blob {:?} from offset {}",
+          request.blob_id, request.offset
+        );
+        self.send_from(&request.blob_id, request.offset, |_| ())?;
+        served += 1;
+      } else {
+        debug!("Request for unknown blob {:?}", request.blob_id);
+      }
+    }
+    Ok(served)
+  }
+
+  fn send_from(
+    &mut self,
+    blob_id: &str,
+    offset: u64,
+    mut progress: impl FnMut(&BlobProgress),
+  ) -> io::Result<()> {
+    let blob = match self.blobs.get_mut(blob_id) {
+      Some(blob) => blob,
+      None => return Err(io::ErrorKind::NotFound.into()),
+    };
+    let mut offset = offset.min(blob.total_size);
+    blob.source.seek(SeekFrom::Start(offset))?;
+
+    loop {
+      let len = (blob.total_size - offset).min(self.chunk_size as u64) as usize;
+      let mut data = vec![0; len];
+      blob.source.read_exact(&mut data)?;
+      self
+        .writer
+        .write(
+          BlobChunk {
+            blob_id: blob_id.to_string(),
+            total_size: blob.total_size,
+            digest: blob.digest,
+            offset,
+            data,
+          },
+          None,
+        )
+        .map_err(to_io_error)?;
+      offset += len as u64;
+      progress(&BlobProgress {
+        blob_id: blob_id.to_string(),
+        transferred: offset,
+        total: blob.total_size,
+      });
+      self.unacknowledged += 1;
+      if self.unacknowledged >= SEND_WINDOW {
+        // If this times out, a receiver has probably gone away. Receivers
+        // that missed chunks will ask for them, so just carry on.
+        if !self
+          .writer
+          .wait_for_acknowledgments(ACK_TIMEOUT)
+          .map_err(to_io_error)?
+        {
+          warn!("Blob {:?}: chunks not acknowledged in time", blob_id);
+        }
+        self.unacknowledged = 0;
+      }
+      if offset >= blob.total_size {
+        return Ok(());
+      }
+    }
+  }
+}
+
+impl Evented for BlobSender {
+  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    self.request_reader.register(poll, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &Poll,
+    token: Token,
+    interest: Ready,
+    opts: PollOpt,
+  ) -> io::Result<()> {
+    self.request_reader.reregister(poll, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &Poll) -> io::Result<()> {
+    self.request_reader.deregister(poll)
+  }
+}
+
+/// Receiving side of blob transfer. See [`BlobSender`].
+///
+/// Blobs are written into a directory. While a blob is being received, it is
+/// stored as `<blob_id>.partial`, and renamed to `<blob_id>` once it is
+/// complete and its digest has been verified.
+///
+/// If the receiver is restarted, it finds the partial files and asks the
+/// sender to resume from where they end. If chunks are missed, e.g. because
+/// the receiver joined in the middle of a transfer, it asks for them also.
+pub struct BlobReceiver {
+  reader: DataReader<BlobChunk, CDRDeserializerAdapter<BlobChunk>>,
+  request_writer: DataWriter<BlobRequest, CDRSerializerAdapter<BlobRequest>>,
+  directory: PathBuf,
+  incoming: HashMap<String, IncomingBlob>,
+  completed: HashMap<String, [u8; 16]>, // blob_id -> digest
+}
+
+impl BlobReceiver {
+  /// Creates the `directory` if it does not exist, and requests the rest of
+  /// any partially received blobs found there.
+  pub fn new(
+    publisher: &Publisher,
+    subscriber: &Subscriber,
+    data_topic: &Topic,
+    request_topic: &Topic,
+    directory: impl Into<PathBuf>,
+  ) -> Result<Self> {
+    let directory = directory.into();
+    fs::create_dir_all(&directory)?;
+    let reader = subscriber.create_datareader_no_key_cdr(data_topic, Some(data_qos(data_topic)))?;
+    let request_writer =
+      publisher.create_datawriter_no_key_cdr(request_topic, Some(request_qos(request_topic)))?;
+    let mut receiver = Self {
+      reader,
+      request_writer,
+      directory,
+      incoming: HashMap::new(),
+      completed: HashMap::new(),
+    };
+
+    for entry in fs::read_dir(&receiver.directory)? {
+      let file_name = entry?.file_name();
+      let blob_id = file_name
+        .to_str()
+        .and_then(|name| name.strip_suffix(PARTIAL_SUFFIX))
+        .filter(|blob_id| check_blob_id(blob_id).is_ok());
+      if let Some(blob_id) = blob_id {
+        info!("Resuming partially received blob {:?}", blob_id);
+        receiver.request(blob_id)?;
+      }
+    }
+    Ok(receiver)
+  }
+
+  /// Ask the sender to send a blob, starting from the part that has not been
+  /// received yet.
+  pub fn request(&mut self, blob_id: &str) -> io::Result<()> {
+    check_blob_id(blob_id)?;
+    let offset = match self.incoming.get(blob_id) {
+      Some(blob) => blob.received,
+      None => fs::metadata(self.partial_path(blob_id)).map_or(0, |m| m.len()),
+    };
+    self.send_request(blob_id, offset)
+  }
+
+  /// Process received chunks. `progress` is called after each chunk that
+  /// advanced a transfer. Returns the blobs that were completed.
+  ///
+  /// This does not block. It should be called when the `BlobReceiver` is
+  /// readable in a [`mio::Poll`], or periodically.
+  pub fn receive(
+    &mut self,
+    mut progress: impl FnMut(&BlobProgress),
+  ) -> io::Result<Vec<ReceivedBlob>> {
+    let mut completed = Vec::new();
+    while let Some(sample) = self.reader.take_next_sample().map_err(to_io_error)? {
+      let chunk = sample.into_value();
+      if let Err(e) = check_blob_id(&chunk.blob_id) {
+        warn!("Ignoring blob chunk: {}", e);
+        continue;
+      }
+      if self.completed.get(&chunk.blob_id) == Some(&chunk.digest) {
+        continue; // resent for some other receiver
+      }
+      if !self.incoming.contains_key(&chunk.blob_id) {
+        let blob = IncomingBlob::open(&self.partial_path(&chunk.blob_id), &chunk)?;
+        self.incoming.insert(chunk.blob_id.clone(), blob);
+      }
+      if let Some(received) = self.add_chunk(&chunk, &mut progress)? {
+        completed.push(received);
+      }
+    }
+    Ok(completed)
+  }
+
+  fn add_chunk(
+    &mut self,
+    chunk: &BlobChunk,
+    progress: &mut impl FnMut(&BlobProgress),
+  ) -> io::Result<Option<ReceivedBlob>> {
+    let blob = match self.incoming.get_mut(&chunk.blob_id) {
+      Some(blob) => blob,
+      None => return Ok(None),
+    };
+    match blob.add(chunk)? {
+      Added::Nothing => Ok(None),
+      Added::Gap(offset) => {
+        debug!("Blob {:?}: missing data from {}", chunk.blob_id, offset);
+        self.send_request(&chunk.blob_id, offset)?;
+        Ok(None)
+      }
+      Added::Progress => {
+        progress(&blob.progress(&chunk.blob_id));
+        Ok(None)
+      }
+      Added::Complete => {
+        progress(&blob.progress(&chunk.blob_id));
+        let partial_path = self.partial_path(&chunk.blob_id);
+        self.incoming.remove(&chunk.blob_id);
+        let (_, digest) = md5_of(&mut File::open(&partial_path)?)?;
+        if digest != chunk.digest {
+          warn!("Blob {:?}: digest mismatch, receiving again", chunk.blob_id);
+          fs::remove_file(&partial_path)?;
+          self.send_request(&chunk.blob_id, 0)?;
+          return Ok(None);
+        }
+        let path = self.directory.join(&chunk.blob_id);
+        fs::rename(&partial_path, &path)?;
+        self.completed.insert(chunk.blob_id.clone(), digest);
+        info!("Received blob {:?}", chunk.blob_id);
+        Ok(Some(ReceivedBlob {
+          blob_id: chunk.blob_id.clone(),
+          path,
+        }))
+      }
+    }
+  }
+
+  fn send_request(&self, blob_id: &str, offset: u64) -> io::Result<()> {
+    self
+      .request_writer
+      .write(
+        BlobRequest {
+          blob_id: blob_id.to_string(),
+          offset,
+        },
+        None,
+      )
+      .map_err(to_io_error)
+  }
+
+  fn partial_path(&self, blob_id: &str) -> PathBuf {
+    self
+      .directory
+      .join(format!("{}{}", blob_id, PARTIAL_SUFFIX))
+  }
+}
+
+impl Evented for BlobReceiver {
+  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    self.reader.register(poll, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &Poll,
+    token: Token,
+    interest: Ready,
+    opts: PollOpt,
+  ) -> io::Result<()> {
+    self.reader.reregister(poll, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &Poll) -> io::Result<()> {
+    self.reader.deregister(poll)
+  }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Added {
+  Nothing,
+  Gap(u64), // data is missing from this offset on
+  Progress,
+  Complete,
+}
+
+// A blob being received into a partial file.
+struct IncomingBlob {
+  file: File,
+  received: u64, // bytes in the file
+  total_size: u64,
+  digest: [u8; 16],
+  requested_from: Option<u64>, // avoid repeating the same request
+}
+
+impl IncomingBlob {
+  // Continue writing an existing partial file, if there is one.
+  fn open(path: &Path, first_chunk: &BlobChunk) -> io::Result<Self> {
+    let file = OpenOptions::new()
+      .create(true)
+      .truncate(false)
+      .read(true)
+      .write(true)
+      .open(path)?;
+    let received = file.metadata()?.len();
+    Ok(Self {
+      file,
+      received,
+      total_size: first_chunk.total_size,
+      digest: first_chunk.digest,
+      requested_from: None,
+    })
+  }
+
+  fn progress(&self, blob_id: &str) -> BlobProgress {
+    BlobProgress {
+      blob_id: blob_id.to_string(),
+      transferred: self.received,
+      total: self.total_size,
+    }
+  }
+
+  fn add(&mut self, chunk: &BlobChunk) -> io::Result<Added> {
+    if chunk.digest != self.digest || chunk.total_size != self.total_size {
+      // The sender has replaced the blob. Start over.
+      info!("Blob {:?} changed, receiving again", chunk.blob_id);
+      self.file.set_len(0)?;
+      self.received = 0;
+      self.total_size = chunk.total_size;
+      self.digest = chunk.digest;
+      self.requested_from = None;
+    }
+    if self.received > self.total_size {
+      // Partial file is longer than the blob, so it cannot be from this blob.
+      self.file.set_len(0)?;
+      self.received = 0;
+    }
+
+    if chunk.offset > self.received {
+      if self.requested_from == Some(self.received) {
+        return Ok(Added::Nothing);
+      }
+      self.requested_from = Some(self.received);
+      return Ok(Added::Gap(self.received));
+    }
+
+    let end = chunk.offset + chunk.data.len() as u64;
+    let is_complete = end >= self.total_size;
+    if end <= self.received && !(is_complete && self.received == self.total_size) {
+      return Ok(Added::Nothing); // already have this
+    }
+    if end > self.received {
+      let new_data = &chunk.data[(self.received - chunk.offset) as usize..];
+      self.file.seek(SeekFrom::Start(self.received))?;
+      self.file.write_all(new_data)?;
+      self.received = end;
+    }
+    if is_complete {
+      self.file.flush()?;
+      Ok(Added::Complete)
+    } else {
+      Ok(Added::Progress)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  #[cfg(feature = "discovery")]
+  use std::{io::Cursor, thread};
+
+  use super::*;
+  #[cfg(feature = "discovery")]
+  use crate::{dds::topic::TopicKind, test::loopback::loopback_participant};
+
+  fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rustdds_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  #[test]
+  fn blob_incoming_chunks() {
+    let dir = test_dir("blob_incoming_chunks");
+    let blob: Vec<u8> = (0..100u8).collect();
+    let chunk = |offset: usize, len: usize| BlobChunk {
+      blob_id: "b".to_string(),
+      total_size: blob.len() as u64,
+      digest: md5::compute(&blob).0,
+      offset: offset as u64,
+      data: blob[offset..offset + len].to_vec(),
+    };
+
+    // Resume from an existing partial file
+    let path = dir.join("b.partial");
+    fs::write(&path, &blob[..30]).unwrap();
+    let mut incoming = IncomingBlob::open(&path, &chunk(50, 10)).unwrap();
+    assert_eq!(incoming.received, 30);
+
+    // Gap is reported once
+    assert_eq!(incoming.add(&chunk(50, 10)).unwrap(), Added::Gap(30));
+    assert_eq!(incoming.add(&chunk(60, 10)).unwrap(), Added::Nothing);
+
+    // duplicates and overlap
+    assert_eq!(incoming.add(&chunk(0, 20)).unwrap(), Added::Nothing);
+    assert_eq!(incoming.add(&chunk(20, 40)).unwrap(), Added::Progress);
+    assert_eq!(incoming.received, 60);
+    assert_eq!(incoming.add(&chunk(60, 40)).unwrap(), Added::Complete);
+    drop(incoming);
+    assert_eq!(fs::read(&path).unwrap(), blob);
+
+    assert!(check_blob_id("file-1.bin").is_ok());
+    assert!(check_blob_id("../etc/passwd").is_err());
+    assert!(check_blob_id(".hidden").is_err());
+    assert!(check_blob_id("").is_err());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn blob_transfer_with_resume() {
+    let qos = QosPolicyBuilder::new().build();
+    let dir = test_dir("blob_transfer_with_resume");
+    let blob: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+
+    // Participants, as in ByteStream tests
+    let endpoints = || {
+      let participant = loopback_participant(216);
+      let topic = |name: &str| {
+        participant
+          .create_topic(
+            name.to_string(),
+            "BlobTransfer".to_string(),
+            &qos,
+            TopicKind::NoKey,
+          )
+          .unwrap()
+      };
+      let publisher = participant.create_publisher(&qos).unwrap();
+      let subscriber = participant.create_subscriber(&qos).unwrap();
+      let data = topic("blob_test_data");
+      let request = topic("blob_test_request");
+      (participant, publisher, subscriber, data, request)
+    };
+
+    // Send before there is any receiver: everything is lost.
+    let (_p1, publisher, subscriber, data, request) = endpoints();
+    let mut sender = BlobSender::new(&publisher, &subscriber, &data, &request).unwrap();
+    let mut sent = 0;
+    sender
+      .send("test.bin", Cursor::new(blob.clone()), |p| {
+        sent = p.transferred;
+      })
+      .unwrap();
+    assert_eq!(sent, blob.len() as u64);
+
+    // Receiver "restarts" with part of the blob already received.
+    fs::write(dir.join("test.bin.partial"), &blob[..80_000]).unwrap();
+    let (_p2, publisher, subscriber, data, request) = endpoints();
+    let mut receiver =
+      BlobReceiver::new(&publisher, &subscriber, &data, &request, dir.clone()).unwrap();
+
+    let mut received = Vec::new();
+    let mut first_progress = None;
+    for _ in 0..400 {
+      sender.handle_requests().unwrap();
+      received.extend(
+        receiver
+          .receive(|p| {
+            first_progress.get_or_insert(p.transferred);
+          })
+          .unwrap(),
+      );
+      if !received.is_empty() {
+        break;
+      }
+      thread::sleep(StdDuration::from_millis(50));
+    }
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].path, dir.join("test.bin"));
+    assert_eq!(fs::read(&received[0].path).unwrap(), blob);
+    // Transfer continued from the partial file.
+    assert!(first_progress.unwrap() > 80_000);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,494 @@
+#[cfg(feature = "tokio")]
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{self, Context},
+};
+use std::{
+  collections::{BTreeMap, VecDeque},
+  io,
+  time::{Duration as StdDuration, Instant},
+};
+
+use mio::{Events, Poll, PollOpt, Ready, Token};
+use serde::{Deserialize, Serialize};
+use log::warn;
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{
+  dds::{
+    no_key::{datareader::DataReader, datawriter::DataWriter},
+    pubsub::{Publisher, Subscriber},
+    qos::{policy, HasQoSPolicy, QosPolicyBuilder},
+    topic::Topic,
+    values::result::{Error, Result},
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+  structure::duration::Duration,
+};
+#[cfg(feature = "tokio")]
+use crate::dds::with_key::datawriter::AllAcknowledged;
+
+// Chunks larger than this are split. DDS will still fragment them into
+// several DATA_FRAG submessages.
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+// How long flush() waits for the remote reader(s) to acknowledge.
+const FLUSH_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+
+// Unit of transfer on the stream Topics. An empty chunk marks the end of the
+// stream.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StreamChunk {
+  offset: u64,
+  data: Vec<u8>,
+}
+
+/// An ordered, reliable byte stream over a pair of DDS Topics.
+///
+/// Bytes written to the stream are published on the `outgoing` Topic, and bytes
+/// read are taken from the `incoming` Topic. The other end of the stream uses
+/// the same Topics the other way around. This can be used e.g. for file
+/// transfer or tunneling stream-oriented protocols over DDS.
+///
+/// The stream uses RELIABLE Reliability and KEEP_ALL History. Every chunk
+/// carries its byte offset in the stream, so bytes are delivered exactly once
+/// and in order. Large chunks are fragmented by DDS as usual.
+///
+/// The Topics must be of kind `NoKey`. Durability is taken from the Topic QoS:
+/// with VOLATILE durability the reading side must be matched before writing
+/// starts, or the beginning of the stream is lost.
+///
+/// The stream implements [`std::io::Read`] and [`std::io::Write`]. Reads block
+/// until data is available, unless non-blocking mode or a read timeout is set.
+/// Writes do not block.
+///
+/// With the `tokio` feature, the stream also implements
+/// `tokio::io::AsyncRead` and `tokio::io::AsyncWrite`. Async reads wait
+/// for data without blocking the executor, and ignore the non-blocking mode
+/// and read timeout. Async flushes wait for acknowledgments without a timeout,
+/// so use the timeout facility of the runtime to limit them. Shutdown finishes
+/// the stream and then flushes it.
+pub struct ByteStream {
+  writer: DataWriter<StreamChunk, CDRSerializerAdapter<StreamChunk>>,
+  reader: DataReader<StreamChunk, CDRDeserializerAdapter<StreamChunk>>,
+  poll: Poll,
+  write_offset: u64,
+  finished: bool,
+  received: Reassembly,
+  nonblocking: bool,
+  read_timeout: Option<StdDuration>,
+  #[cfg(feature = "tokio")]
+  flushing: Option<AllAcknowledged>,
+}
+
+impl ByteStream {
+  pub fn new(
+    publisher: &Publisher,
+    subscriber: &Subscriber,
+    outgoing: &Topic,
+    incoming: &Topic,
+  ) -> Result<Self> {
+    let stream_qos = QosPolicyBuilder::new()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      })
+      .history(policy::History::KeepAll)
+      .build();
+
+    let writer = publisher
+      .create_datawriter_no_key_cdr(outgoing, Some(outgoing.qos().modify_by(&stream_qos)))?;
+    let reader = subscriber
+      .create_datareader_no_key_cdr(incoming, Some(incoming.qos().modify_by(&stream_qos)))?;
+    let poll = Poll::new()?;
+    poll.register(&reader, Token(0), Ready::readable(), PollOpt::edge())?;
+
+    Ok(Self {
+      writer,
+      reader,
+      poll,
+      write_offset: 0,
+      finished: false,
+      received: Reassembly::new(),
+      nonblocking: false,
+      read_timeout: None,
+      #[cfg(feature = "tokio")]
+      flushing: None,
+    })
+  }
+
+  /// In non-blocking mode, `read` returns
+  /// [`WouldBlock`](std::io::ErrorKind::WouldBlock) instead of waiting for
+  /// data.
+  pub fn set_nonblocking(&mut self, nonblocking: bool) {
+    self.nonblocking = nonblocking;
+  }
+
+  /// Maximum time `read` waits for data, after which it returns
+  /// [`TimedOut`](std::io::ErrorKind::TimedOut). `None` waits indefinitely.
+  pub fn set_read_timeout(&mut self, timeout: Option<StdDuration>) {
+    self.read_timeout = timeout;
+  }
+
+  /// Signal end of stream to the reading side. After this, writing fails, and
+  /// the other end reads end-of-file once it has received all bytes.
+  pub fn finish(&mut self) -> io::Result<()> {
+    if !self.finished {
+      self.publish(Vec::new())?;
+      self.finished = true;
+    }
+    Ok(())
+  }
+
+  /// Number of bytes written to the stream so far.
+  pub fn bytes_written(&self) -> u64 {
+    self.write_offset
+  }
+
+  /// Number of bytes read from the stream so far.
+  pub fn bytes_read(&self) -> u64 {
+    self.received.bytes_read()
+  }
+
+  fn publish(&mut self, data: Vec<u8>) -> io::Result<()> {
+    let len = data.len() as u64;
+    self
+      .writer
+      .write(
+        StreamChunk {
+          offset: self.write_offset,
+          data,
+        },
+        None,
+      )
+      .map_err(to_io_error)?;
+    self.write_offset += len;
+    Ok(())
+  }
+
+  fn write_chunk(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if self.finished {
+      return Err(io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        "ByteStream already finished",
+      ));
+    }
+    if buf.is_empty() {
+      return Ok(0); // empty chunk would mean end of stream
+    }
+    let n = buf.len().min(MAX_CHUNK_SIZE);
+    self.publish(buf[..n].to_vec())?;
+    Ok(n)
+  }
+
+  // Returns true if something was received.
+  fn receive(&mut self) -> io::Result<bool> {
+    let mut received_any = false;
+    while let Some(sample) = self.reader.take_next_sample().map_err(to_io_error)? {
+      self.received.add(sample.into_value());
+      received_any = true;
+    }
+    Ok(received_any)
+  }
+}
+
+// Puts received chunks back into order.
+struct Reassembly {
+  ready: VecDeque<u8>,                  // in-order bytes not yet read
+  next_offset: u64,                     // stream offset after `ready`
+  early_chunks: BTreeMap<u64, Vec<u8>>, // chunks beyond next_offset
+  eof_offset: Option<u64>,
+}
+
+impl Reassembly {
+  fn new() -> Self {
+    Self {
+      ready: VecDeque::new(),
+      next_offset: 0,
+      early_chunks: BTreeMap::new(),
+      eof_offset: None,
+    }
+  }
+
+  fn bytes_read(&self) -> u64 {
+    self.next_offset - self.ready.len() as u64
+  }
+
+  fn add(&mut self, chunk: StreamChunk) {
+    if chunk.data.is_empty() {
+      self.eof_offset = Some(chunk.offset);
+      return;
+    }
+    self.early_chunks.insert(chunk.offset, chunk.data);
+
+    while let Some((&offset, _)) = self.early_chunks.range(..=self.next_offset).next() {
+      let data = self.early_chunks.remove(&offset).unwrap_or_default();
+      let chunk_end = offset + data.len() as u64;
+      if chunk_end > self.next_offset {
+        // Skip the part that was already received, e.g. when the same chunk
+        // arrives twice.
+        let new_data = &data[(self.next_offset - offset) as usize..];
+        self.ready.extend(new_data);
+        self.next_offset = chunk_end;
+      }
+    }
+  }
+
+  // None = nothing to read yet. Some(0) = end of stream.
+  fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+    if self.ready.is_empty() {
+      return if self.eof_offset == Some(self.next_offset) {
+        Some(0)
+      } else {
+        None
+      };
+    }
+    let n = self.ready.len().min(buf.len());
+    for (dst, src) in buf.iter_mut().zip(self.ready.drain(..n)) {
+      *dst = src;
+    }
+    Some(n)
+  }
+}
+
+fn to_io_error(e: Error) -> io::Error {
+  match e {
+    Error::Io(e) => e,
+    other => io::Error::new(io::ErrorKind::Other, other),
+  }
+}
+
+impl io::Read for ByteStream {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let deadline = self.read_timeout.map(|t| Instant::now() + t);
+    let mut events = Events::with_capacity(1);
+    loop {
+      if let Some(n) = self.received.read(buf) {
+        return Ok(n);
+      }
+      if self.receive()? {
+        continue;
+      }
+      if self.nonblocking {
+        return Err(io::ErrorKind::WouldBlock.into());
+      }
+      let timeout = match deadline {
+        None => None,
+        Some(deadline) => {
+          let now = Instant::now();
+          if now >= deadline {
+            return Err(io::ErrorKind::TimedOut.into());
+          }
+          Some(deadline - now)
+        }
+      };
+      self.poll.poll(&mut events, timeout)?;
+    }
+  }
+}
+
+impl io::Write for ByteStream {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.write_chunk(buf)
+  }
+
+  /// Waits until matched readers have acknowledged all written data.
+  fn flush(&mut self) -> io::Result<()> {
+    match self.writer.wait_for_acknowledgments(FLUSH_TIMEOUT) {
+      Ok(true) => Ok(()),
+      Ok(false) => {
+        warn!(
+          "ByteStream flush: not acknowledged within {:?}",
+          FLUSH_TIMEOUT
+        );
+        Err(io::ErrorKind::TimedOut.into())
+      }
+      Err(e) => Err(to_io_error(e)),
+    }
+  }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncRead for ByteStream {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> task::Poll<io::Result<()>> {
+    let this = self.get_mut();
+    let received = &mut this.received;
+    this
+      .reader
+      .poll_when_available(cx, |reader| loop {
+        if let Some(n) = received.read(buf.initialize_unfilled()) {
+          buf.advance(n);
+          return Ok(Some(()));
+        }
+        match reader.take_next_sample()? {
+          Some(sample) => received.add(sample.into_value()),
+          None => return Ok(None),
+        }
+      })
+      .map_err(to_io_error)
+  }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncWrite for ByteStream {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> task::Poll<io::Result<usize>> {
+    task::Poll::Ready(self.get_mut().write_chunk(buf))
+  }
+
+  fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<io::Result<()>> {
+    let this = self.get_mut();
+    let flushing = match &mut this.flushing {
+      Some(flushing) => flushing,
+      None => match this.writer.all_acknowledged().map_err(to_io_error)? {
+        Some(all_acknowledged) => this.flushing.insert(all_acknowledged),
+        None => return task::Poll::Ready(Ok(())),
+      },
+    };
+    match Pin::new(flushing).poll(cx) {
+      task::Poll::Pending => task::Poll::Pending,
+      task::Poll::Ready(result) => {
+        this.flushing = None;
+        task::Poll::Ready(result.map_err(to_io_error))
+      }
+    }
+  }
+
+  fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<io::Result<()>> {
+    let this = self.get_mut();
+    this.finish()?;
+    Pin::new(this).poll_flush(cx)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  #[cfg(feature = "discovery")]
+  use std::io::{Read, Write};
+
+  use super::*;
+  #[cfg(feature = "discovery")]
+  use crate::{
+    dds::{participant::DomainParticipant, topic::TopicKind},
+    test::loopback::loopback_participant,
+  };
+
+  fn chunk(offset: u64, data: &[u8]) -> StreamChunk {
+    StreamChunk {
+      offset,
+      data: data.to_vec(),
+    }
+  }
+
+  #[test]
+  fn byte_stream_reassembly() {
+    let mut r = Reassembly::new();
+    let mut buf = [0; 16];
+    assert_eq!(r.read(&mut buf), None);
+
+    // out of order, duplicates, and overlap
+    r.add(chunk(3, b"def"));
+    assert_eq!(r.read(&mut buf), None);
+    r.add(chunk(0, b"abc"));
+    r.add(chunk(0, b"abc"));
+    r.add(chunk(9, b""));
+    r.add(chunk(4, b"efghi"));
+    assert_eq!(r.read(&mut buf[..4]), Some(4));
+    assert_eq!(&buf[..4], b"abcd");
+    assert_eq!(r.bytes_read(), 4);
+    assert_eq!(r.read(&mut buf), Some(5));
+    assert_eq!(&buf[..5], b"efghi");
+    assert_eq!(r.read(&mut buf), Some(0));
+  }
+
+  // Endpoints in the same DomainParticipant share a DDSCache, and do not
+  // communicate, so each end of a stream needs a participant of its own.
+  #[cfg(feature = "discovery")]
+  fn stream_end(domain_id: u16, outgoing: &str, incoming: &str) -> (DomainParticipant, ByteStream) {
+    let qos = QosPolicyBuilder::new()
+      .durability(policy::Durability::TransientLocal)
+      .build();
+    let participant = loopback_participant(domain_id);
+    let topic = |name: &str| {
+      participant
+        .create_topic(
+          name.to_string(),
+          "ByteStreamChunk".to_string(),
+          &qos,
+          TopicKind::NoKey,
+        )
+        .unwrap()
+    };
+    let stream = ByteStream::new(
+      &participant.create_publisher(&qos).unwrap(),
+      &participant.create_subscriber(&qos).unwrap(),
+      &topic(outgoing),
+      &topic(incoming),
+    )
+    .unwrap();
+    (participant, stream)
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn byte_stream_between_participants() {
+    let (_pa, mut a) = stream_end(217, "byte_stream_test_a_to_b", "byte_stream_test_b_to_a");
+    let (_pb, mut b) = stream_end(217, "byte_stream_test_b_to_a", "byte_stream_test_a_to_b");
+    b.set_read_timeout(Some(StdDuration::from_secs(20)));
+
+    let message: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    a.write_all(&message).unwrap();
+    a.finish().unwrap();
+    assert!(a.write(b"more").is_err());
+
+    let mut received = Vec::new();
+    b.read_to_end(&mut received).unwrap();
+    assert_eq!(received, message);
+    assert_eq!(b.bytes_read(), message.len() as u64);
+  }
+
+  #[test]
+  #[cfg(all(feature = "discovery", feature = "tokio"))]
+  fn byte_stream_async_between_participants() {
+    // Qualified calls, as std::io::{Read, Write} are in scope, too.
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (_pa, mut a) = stream_end(226, "byte_stream_async_a_to_b", "byte_stream_async_b_to_a");
+    let (_pb, mut b) = stream_end(226, "byte_stream_async_b_to_a", "byte_stream_async_a_to_b");
+
+    let message: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+      let reading = tokio::spawn(async move {
+        let mut received = Vec::new();
+        AsyncReadExt::read_to_end(&mut b, &mut received)
+          .await
+          .unwrap();
+        received
+      });
+      // Give discovery time to match the endpoints, so that flushing waits
+      // for the reader.
+      tokio::time::sleep(StdDuration::from_secs(2)).await;
+      AsyncWriteExt::write_all(&mut a, &message).await.unwrap();
+      tokio::time::timeout(StdDuration::from_secs(20), a.shutdown())
+        .await
+        .expect("ByteStream was not acknowledged")
+        .unwrap();
+      assert!(AsyncWriteExt::write(&mut a, b"more").await.is_err());
+
+      let received = tokio::time::timeout(StdDuration::from_secs(20), reading)
+        .await
+        .expect("ByteStream was not received")
+        .unwrap();
+      assert_eq!(received, message);
+    });
+  }
+}
@@ -0,0 +1,204 @@
+// Clocks of DomainParticipants
+
+use std::{any::Any, fmt, sync::Arc, time::Instant};
+
+use crate::{
+  dds::{simulation::SimulatedClock, timer::Timer},
+  structure::time::Timestamp,
+};
+
+/// A source of time for a DomainParticipant, see
+/// [`DomainParticipantBuilder::clock`](crate::DomainParticipantBuilder::clock).
+///
+/// The clock gives the source timestamps that the participant puts on written
+/// samples and protocol messages, and the time for lease, liveliness, lifespan
+/// and deadline checks. A clock derived from PTP, for example, gives
+/// timestamps that agree across hosts better than system clocks synchronized
+/// with NTP.
+pub trait Clock: Send + Sync + 'static {
+  /// The current time.
+  fn now(&self) -> Timestamp;
+
+  /// The current time on a monotonic clock, for measuring intervals, such as
+  /// lease durations. The default is the monotonic system clock, which suits
+  /// clocks that advance at the rate of real time.
+  fn instant(&self) -> Instant {
+    Instant::now()
+  }
+}
+
+/// The system clock. This is the default clock of a DomainParticipant.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> Timestamp {
+    Timestamp::now()
+  }
+}
+
+impl Clock for SimulatedClock {
+  fn now(&self) -> Timestamp {
+    SimulatedClock::now(self)
+  }
+
+  fn instant(&self) -> Instant {
+    SimulatedClock::instant(self)
+  }
+}
+
+// The clock of a participant, and the timers that go with it
+#[derive(Clone)]
+pub(crate) enum TimeSource {
+  System,
+  // Timers expire by the clock
+  Simulated(SimulatedClock),
+  // Timers run on the system clock
+  Custom(Arc<dyn Clock>),
+}
+
+impl Default for TimeSource {
+  fn default() -> Self {
+    Self::System
+  }
+}
+
+impl fmt::Debug for TimeSource {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::System => write!(f, "System"),
+      Self::Simulated(clock) => f.debug_tuple("Simulated").field(clock).finish(),
+      Self::Custom(_) => write!(f, "Custom"),
+    }
+  }
+}
+
+impl TimeSource {
+  pub fn from_clock<C: Clock>(clock: C) -> Self {
+    let any: &dyn Any = &clock;
+    if let Some(clock) = any.downcast_ref::<SimulatedClock>() {
+      Self::Simulated(clock.clone())
+    } else if any.is::<SystemClock>() {
+      Self::System
+    } else {
+      Self::Custom(Arc::new(clock))
+    }
+  }
+
+  pub fn now(&self) -> Timestamp {
+    match self {
+      Self::System => Timestamp::now(),
+      Self::Simulated(clock) => clock.now(),
+      Self::Custom(clock) => clock.now(),
+    }
+  }
+
+  pub fn instant(&self) -> Instant {
+    match self {
+      Self::System => Instant::now(),
+      Self::Simulated(clock) => clock.instant(),
+      Self::Custom(clock) => clock.instant(),
+    }
+  }
+
+  pub fn timer<T>(&self) -> Timer<T> {
+    Timer::new(self, None)
+  }
+
+  // A timer with fewer slots than the default, for timers that seldom have
+  // many timeouts pending
+  pub fn small_timer<T>(&self, num_slots: usize) -> Timer<T> {
+    Timer::new(self, Some(num_slots))
+  }
+}
+
+// A clock for tests, whose wall clock can be stepped without the monotonic
+// clock, like NTP or a user setting the time would do.
+#[cfg(test)]
+#[derive(Clone)]
+pub(crate) struct SteppingClock {
+  start: Timestamp,
+  start_instant: Instant,
+  // Offsets of the wall clock and the monotonic clock from the start
+  offsets: Arc<std::sync::Mutex<(crate::Duration, std::time::Duration)>>,
+}
+
+#[cfg(test)]
+impl SteppingClock {
+  pub fn new() -> Self {
+    Self {
+      start: Timestamp::now(),
+      start_instant: Instant::now(),
+      offsets: Arc::new(std::sync::Mutex::new((
+        crate::Duration::DURATION_ZERO,
+        std::time::Duration::ZERO,
+      ))),
+    }
+  }
+
+  // Lets time pass on both clocks
+  pub fn advance(&self, duration: std::time::Duration) {
+    let mut offsets = self.offsets.lock().unwrap();
+    offsets.0 = offsets.0 + crate::Duration::from_std(duration);
+    offsets.1 += duration;
+  }
+
+  // Steps the wall clock only. The step may be negative.
+  pub fn step_wall_clock(&self, step: crate::Duration) {
+    let mut offsets = self.offsets.lock().unwrap();
+    offsets.0 = offsets.0 + step;
+  }
+}
+
+#[cfg(test)]
+impl Clock for SteppingClock {
+  fn now(&self) -> Timestamp {
+    // Subtracting, because adding does not handle negative durations
+    let wall_offset = self.offsets.lock().unwrap().0;
+    self.start - crate::Duration::from_ticks(-wall_offset.to_ticks())
+  }
+
+  fn instant(&self) -> Instant {
+    self.start_instant + self.offsets.lock().unwrap().1
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::*;
+  use crate::structure::duration::Duration as DdsDuration;
+
+  struct FixedClock(Timestamp);
+
+  impl Clock for FixedClock {
+    fn now(&self) -> Timestamp {
+      self.0
+    }
+  }
+
+  #[test]
+  fn time_source_from_clock() {
+    assert!(matches!(
+      TimeSource::from_clock(SystemClock),
+      TimeSource::System
+    ));
+
+    let simulated = SimulatedClock::new(Timestamp::ZERO);
+    let time_source = TimeSource::from_clock(simulated.clone());
+    assert!(matches!(time_source, TimeSource::Simulated(_)));
+    let start = time_source.instant();
+    simulated.advance(Duration::from_secs(10));
+    assert_eq!(
+      time_source.now(),
+      Timestamp::ZERO + DdsDuration::from_secs(10)
+    );
+    assert_eq!(time_source.instant() - start, Duration::from_secs(10));
+
+    let fixed = Timestamp::ZERO + DdsDuration::from_secs(1234);
+    let time_source = TimeSource::from_clock(FixedClock(fixed));
+    assert!(matches!(time_source, TimeSource::Custom(_)));
+    assert_eq!(time_source.now(), fixed);
+  }
+}
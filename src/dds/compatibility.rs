@@ -0,0 +1,499 @@
+// Adaptation to remote participants that implement other versions of the
+// RTPS protocol, or deviate from it.
+//
+// Remote participants announce their protocol version and vendor in the
+// header of each message and in SPDP. Where the protocol changed between
+// versions, we interpret what an older participant sends the way it meant it.
+// Known deviations of other implementations are worked around by vendor id.
+// Each adaptation can be turned off, in case an implementation announces an
+// old version but follows the newer rules, or has fixed its deviation.
+
+use log::debug;
+use bytes::Bytes;
+use enumflags2::{bitflags, BitFlags};
+use speedy::{Endianness, Readable, Writable};
+
+use crate::{
+  messages::{
+    protocol_version::ProtocolVersion,
+    submessages::{
+      submessage_elements::{parameter_list::ParameterList, serialized_payload::SerializedPayload},
+      submessages::{Gap, RepresentationIdentifier},
+    },
+    vendor_id::VendorId,
+  },
+  structure::{
+    builtin_endpoint::BuiltinEndpointSet,
+    parameter_id::ParameterId,
+    sequence_number::{SequenceNumber, SequenceNumberSet},
+  },
+};
+
+/// How to adapt to remote participants that announce an older RTPS protocol
+/// version than this implementation. Given to
+/// [`DomainParticipantBuilder::protocol_compatibility`](crate::DomainParticipantBuilder::protocol_compatibility).
+///
+/// By default all adaptations are enabled. Each applies only to participants
+/// that announce a version where the rule was different.
+///
+/// ```
+/// use rustdds::{DomainParticipantBuilder, ProtocolCompatibility};
+///
+/// let participant = DomainParticipantBuilder::new(0)
+///   .protocol_compatibility(ProtocolCompatibility {
+///     legacy_gap: false,
+///     ..ProtocolCompatibility::default()
+///   })
+///   .build()
+///   .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolCompatibility {
+  /// GAP from a participant older than RTPS 2.2: a GAP with an empty
+  /// `gapList` whose base equals `gapStart` marks `gapStart` irrelevant.
+  ///
+  /// Older implementations read `gapList.base` as the last irrelevant
+  /// sequence number, so they send a GAP of a single sequence number this
+  /// way. From version 2.2 on `gapList.base` is the first sequence number
+  /// after the gap, and such a GAP would mark nothing.
+  pub legacy_gap: bool,
+
+  /// Builtin endpoints of a participant older than RTPS 2.4: the topic
+  /// discovery endpoints in its `BuiltinEndpointSet` are ignored.
+  ///
+  /// The bits of the topic announcer and detector were assigned in version
+  /// 2.4. Before that, implementations could use them for endpoints of their
+  /// own, and matching our topic discovery endpoints to those would fail.
+  pub legacy_builtin_endpoints: bool,
+
+  /// Workarounds for deviations of other implementations, by vendor.
+  pub vendor_workarounds: VendorWorkarounds,
+}
+
+impl ProtocolCompatibility {
+  /// No adaptations: all participants are taken to follow the rules of the
+  /// version this implementation implements.
+  pub const NONE: Self = Self {
+    legacy_gap: false,
+    legacy_builtin_endpoints: false,
+    vendor_workarounds: VendorWorkarounds::NONE,
+  };
+
+  // Rewrites a GAP from a participant of the given version to the meaning it
+  // has in the current version.
+  pub(crate) fn adapt_gap(&self, source_version: ProtocolVersion, gap: &mut Gap) {
+    if self.legacy_gap
+      && source_version < ProtocolVersion::PROTOCOLVERSION_2_2
+      && gap.gap_list.base() == gap.gap_start
+      && gap.gap_list.iter().next().is_none()
+    {
+      debug!(
+        "GAP from RTPS {:?} marks {:?} irrelevant",
+        source_version, gap.gap_start
+      );
+      gap.gap_list = SequenceNumberSet::new_empty(gap.gap_start + SequenceNumber::new(1));
+    }
+  }
+
+  // The builtin endpoints a participant of the given version has, as this
+  // implementation numbers them.
+  pub(crate) fn builtin_endpoints(
+    &self,
+    version: ProtocolVersion,
+    endpoints: BuiltinEndpointSet,
+  ) -> BuiltinEndpointSet {
+    if self.legacy_builtin_endpoints && version < ProtocolVersion::PROTOCOLVERSION_2_4 {
+      endpoints.without(
+        BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_TOPICS_ANNOUNCER
+          | BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_TOPICS_DETECTOR,
+      )
+    } else {
+      endpoints
+    }
+  }
+}
+
+impl Default for ProtocolCompatibility {
+  fn default() -> Self {
+    Self {
+      legacy_gap: true,
+      legacy_builtin_endpoints: true,
+      vendor_workarounds: VendorWorkarounds::default(),
+    }
+  }
+}
+
+/// A workaround for a known deviation of another DDS implementation from the
+/// RTPS specification.
+#[bitflags]
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VendorWorkaround {
+  /// Inline QoS parameter `0x800f` is the related sample identity of a
+  /// request-reply sample, instead of the `0x0083` of the DDS-RPC
+  /// specification. Parameters from `0x8000` up are vendor-specific, so
+  /// `0x800f` is ignored from other vendors.
+  ///
+  /// RTI Connext and eProsima Fast DDS. RustDDS sends `0x800f` too, to
+  /// interoperate with them.
+  RelatedSampleIdentityPid = 0x01,
+
+  /// A HEARTBEAT may repeat the count of the previous one, e.g. a heartbeat
+  /// piggybacked on DATA and the periodic one that follows. Such heartbeats
+  /// are processed instead of being dropped as duplicates.
+  ///
+  /// eProsima Fast DDS.
+  RepeatedHeartbeatCount = 0x02,
+
+  /// SPDP data that has no `PID_BUILTIN_ENDPOINT_SET` gives the builtin
+  /// endpoints in the deprecated `PID_PARTICIPANT_BUILTIN_ENDPOINTS`.
+  ///
+  /// RTI Connext and Twin Oaks CoreDX.
+  ParticipantBuiltinEndpoints = 0x04,
+}
+
+/// Registry of the [`VendorWorkaround`]s that apply to each vendor.
+///
+/// The default registry has the workarounds for known deviations. Each can be
+/// disabled, and a workaround can be enabled for other vendors, e.g. one
+/// whose implementation is derived from another.
+///
+/// ```
+/// use rustdds::{ProtocolCompatibility, VendorId, VendorWorkaround, VendorWorkarounds};
+///
+/// let compatibility = ProtocolCompatibility {
+///   vendor_workarounds: VendorWorkarounds::default()
+///     .disable(VendorId::RTI_CONNEXT, VendorWorkaround::RelatedSampleIdentityPid),
+///   ..ProtocolCompatibility::default()
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VendorWorkarounds {
+  enabled: Vec<(VendorId, VendorWorkaround)>,
+}
+
+impl VendorWorkarounds {
+  /// No workarounds for any vendor.
+  pub const NONE: Self = Self {
+    enabled: Vec::new(),
+  };
+
+  const KNOWN: &'static [(VendorId, VendorWorkaround)] = &[
+    (
+      VendorId::RTI_CONNEXT,
+      VendorWorkaround::RelatedSampleIdentityPid,
+    ),
+    (
+      VendorId::EPROSIMA_FAST_DDS,
+      VendorWorkaround::RelatedSampleIdentityPid,
+    ),
+    (
+      VendorId::THIS_IMPLEMENTATION,
+      VendorWorkaround::RelatedSampleIdentityPid,
+    ),
+    (
+      VendorId::EPROSIMA_FAST_DDS,
+      VendorWorkaround::RepeatedHeartbeatCount,
+    ),
+    (
+      VendorId::RTI_CONNEXT,
+      VendorWorkaround::ParticipantBuiltinEndpoints,
+    ),
+    (
+      VendorId::TWINOAKS_COREDX,
+      VendorWorkaround::ParticipantBuiltinEndpoints,
+    ),
+  ];
+
+  /// Applies `workaround` to participants of `vendor`.
+  #[must_use]
+  pub fn enable(mut self, vendor: VendorId, workaround: VendorWorkaround) -> Self {
+    if !self.applies(vendor, workaround) {
+      self.enabled.push((vendor, workaround));
+    }
+    self
+  }
+
+  /// Stops applying `workaround` to participants of `vendor`.
+  #[must_use]
+  pub fn disable(mut self, vendor: VendorId, workaround: VendorWorkaround) -> Self {
+    self.enabled.retain(|entry| *entry != (vendor, workaround));
+    self
+  }
+
+  /// Stops applying `workaround` to any vendor.
+  #[must_use]
+  pub fn disable_everywhere(mut self, workaround: VendorWorkaround) -> Self {
+    self.enabled.retain(|(_, w)| *w != workaround);
+    self
+  }
+
+  pub fn applies(&self, vendor: VendorId, workaround: VendorWorkaround) -> bool {
+    self.enabled.contains(&(vendor, workaround))
+  }
+
+  // All the workarounds for a vendor
+  pub(crate) fn of_vendor(&self, vendor: VendorId) -> BitFlags<VendorWorkaround> {
+    self
+      .enabled
+      .iter()
+      .filter(|(v, _)| *v == vendor)
+      .map(|(_, w)| *w)
+      .collect()
+  }
+}
+
+impl Default for VendorWorkarounds {
+  fn default() -> Self {
+    Self {
+      enabled: Self::KNOWN.to_vec(),
+    }
+  }
+}
+
+// Inline QoS of DATA or DATA_FRAG, as this implementation reads it. The
+// related sample identity goes to PID_RELATED_SAMPLE_IDENTITY.
+pub(crate) fn adapt_inline_qos(
+  workarounds: BitFlags<VendorWorkaround>,
+  inline_qos: &mut Option<ParameterList>,
+) {
+  let parameters = match inline_qos {
+    Some(inline_qos) => &mut inline_qos.parameters,
+    None => return,
+  };
+  if !workarounds.contains(VendorWorkaround::RelatedSampleIdentityPid) {
+    parameters.retain(|p| p.parameter_id != ParameterId::PID_RELATED_SAMPLE_IDENTITY);
+  }
+  if !parameters
+    .iter()
+    .any(|p| p.parameter_id == ParameterId::PID_RELATED_SAMPLE_IDENTITY)
+  {
+    for p in parameters.iter_mut() {
+      if p.parameter_id == ParameterId::PID_RELATED_SAMPLE_IDENTITY_STANDARD {
+        p.parameter_id = ParameterId::PID_RELATED_SAMPLE_IDENTITY;
+      }
+    }
+  }
+}
+
+// SPDP data, as Discovery parses it.
+pub(crate) fn adapt_spdp_payload(
+  workarounds: BitFlags<VendorWorkaround>,
+  payload: &mut SerializedPayload,
+) {
+  if !workarounds.contains(VendorWorkaround::ParticipantBuiltinEndpoints) {
+    return;
+  }
+  let endianness = match payload.representation_identifier {
+    RepresentationIdentifier::PL_CDR_LE => Endianness::LittleEndian,
+    RepresentationIdentifier::PL_CDR_BE => Endianness::BigEndian,
+    _ => return,
+  };
+  let bytes = &payload.value;
+  let read_u16 = |pos: usize| u16::read_from_buffer_with_ctx(endianness, &bytes[pos..pos + 2]);
+
+  // Find the parameters in the list
+  let mut participant_builtin_endpoints = None;
+  let mut pos = 0;
+  while pos + 4 <= bytes.len() {
+    let (parameter_id, length) = match (
+      ParameterId::read_from_buffer_with_ctx(endianness, &bytes[pos..pos + 2]),
+      read_u16(pos + 2),
+    ) {
+      (Ok(parameter_id), Ok(length)) => (parameter_id, usize::from(length)),
+      _ => return,
+    };
+    match parameter_id {
+      ParameterId::PID_SENTINEL => break,
+      ParameterId::PID_BUILTIN_ENDPOINT_SET => return, // No need for a workaround
+      ParameterId::PID_PARTICIPANT_BUILTIN_ENDPOINTS => participant_builtin_endpoints = Some(pos),
+      _ => (),
+    }
+    pos += 4 + length;
+  }
+
+  if let Some(pos) = participant_builtin_endpoints {
+    debug!("SPDP data has PID_PARTICIPANT_BUILTIN_ENDPOINTS instead of PID_BUILTIN_ENDPOINT_SET");
+    let mut value = bytes.to_vec();
+    if let Ok(id) = ParameterId::PID_BUILTIN_ENDPOINT_SET.write_to_vec_with_ctx(endianness) {
+      value[pos..pos + 2].copy_from_slice(&id);
+      payload.value = Bytes::from(value);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    messages::submessages::submessage_elements::parameter::Parameter, structure::guid::EntityId,
+  };
+
+  fn gap(start: i64, base: i64) -> Gap {
+    Gap {
+      reader_id: EntityId::UNKNOWN,
+      writer_id: EntityId::UNKNOWN,
+      gap_start: SequenceNumber::new(start),
+      gap_list: SequenceNumberSet::new_empty(SequenceNumber::new(base)),
+    }
+  }
+
+  #[test]
+  fn legacy_gap() {
+    let compatibility = ProtocolCompatibility::default();
+
+    let mut single = gap(5, 5);
+    compatibility.adapt_gap(ProtocolVersion::PROTOCOLVERSION_2_1, &mut single);
+    assert_eq!(single, gap(5, 6));
+
+    // Current rules
+    let mut single = gap(5, 5);
+    compatibility.adapt_gap(ProtocolVersion::PROTOCOLVERSION_2_2, &mut single);
+    assert_eq!(single, gap(5, 5));
+
+    // A range is the same in all versions.
+    let mut range = gap(5, 8);
+    compatibility.adapt_gap(ProtocolVersion::PROTOCOLVERSION_2_1, &mut range);
+    assert_eq!(range, gap(5, 8));
+
+    let mut single = gap(5, 5);
+    ProtocolCompatibility::NONE.adapt_gap(ProtocolVersion::PROTOCOLVERSION_2_1, &mut single);
+    assert_eq!(single, gap(5, 5));
+  }
+
+  #[test]
+  fn legacy_builtin_endpoints() {
+    let endpoints = BuiltinEndpointSet::from_u32(
+      BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_PARTICIPANT_ANNOUNCER
+        | BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_TOPICS_DETECTOR,
+    );
+    let compatibility = ProtocolCompatibility::default();
+
+    let adapted = compatibility.builtin_endpoints(ProtocolVersion::PROTOCOLVERSION_2_3, endpoints);
+    assert!(adapted.contains(BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_PARTICIPANT_ANNOUNCER));
+    assert!(!adapted.contains(BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_TOPICS_DETECTOR));
+
+    let adapted = compatibility.builtin_endpoints(ProtocolVersion::PROTOCOLVERSION_2_4, endpoints);
+    assert_eq!(adapted, endpoints);
+    let adapted = ProtocolCompatibility::NONE
+      .builtin_endpoints(ProtocolVersion::PROTOCOLVERSION_2_3, endpoints);
+    assert_eq!(adapted, endpoints);
+  }
+
+  #[test]
+  fn vendor_workarounds_registry() {
+    let workarounds = VendorWorkarounds::default();
+    assert!(workarounds.applies(
+      VendorId::EPROSIMA_FAST_DDS,
+      VendorWorkaround::RepeatedHeartbeatCount
+    ));
+    assert!(!workarounds.applies(
+      VendorId::ECLIPSE_CYCLONE_DDS,
+      VendorWorkaround::RepeatedHeartbeatCount
+    ));
+
+    let workarounds = workarounds
+      .disable(
+        VendorId::EPROSIMA_FAST_DDS,
+        VendorWorkaround::RepeatedHeartbeatCount,
+      )
+      .enable(
+        VendorId::ECLIPSE_CYCLONE_DDS,
+        VendorWorkaround::RepeatedHeartbeatCount,
+      )
+      .disable_everywhere(VendorWorkaround::RelatedSampleIdentityPid);
+    assert_eq!(
+      workarounds.of_vendor(VendorId::EPROSIMA_FAST_DDS),
+      BitFlags::empty()
+    );
+    assert_eq!(
+      workarounds.of_vendor(VendorId::ECLIPSE_CYCLONE_DDS),
+      VendorWorkaround::RepeatedHeartbeatCount
+    );
+    assert_eq!(
+      workarounds.of_vendor(VendorId::RTI_CONNEXT),
+      VendorWorkaround::ParticipantBuiltinEndpoints
+    );
+  }
+
+  fn inline_qos(ids: &[ParameterId]) -> Option<ParameterList> {
+    Some(ParameterList {
+      parameters: ids
+        .iter()
+        .map(|id| Parameter {
+          parameter_id: *id,
+          value: vec![0; 24],
+        })
+        .collect(),
+    })
+  }
+
+  #[test]
+  fn related_sample_identity_pid() {
+    let vendor_pid = ParameterId::PID_RELATED_SAMPLE_IDENTITY;
+    let standard_pid = ParameterId::PID_RELATED_SAMPLE_IDENTITY_STANDARD;
+
+    // The vendor-specific PID from a vendor that uses it
+    let mut qos = inline_qos(&[vendor_pid]);
+    adapt_inline_qos(VendorWorkaround::RelatedSampleIdentityPid.into(), &mut qos);
+    assert_eq!(qos, inline_qos(&[vendor_pid]));
+
+    // A vendor-specific PID of some other vendor
+    let mut qos = inline_qos(&[vendor_pid]);
+    adapt_inline_qos(BitFlags::empty(), &mut qos);
+    assert_eq!(qos, inline_qos(&[]));
+
+    // The standard PID
+    let mut qos = inline_qos(&[standard_pid]);
+    adapt_inline_qos(BitFlags::empty(), &mut qos);
+    assert_eq!(qos, inline_qos(&[vendor_pid]));
+  }
+
+  fn spdp_payload(
+    representation_identifier: RepresentationIdentifier,
+    endianness: Endianness,
+    ids: &[ParameterId],
+  ) -> SerializedPayload {
+    let mut value = Vec::new();
+    for id in ids.iter().chain(&[ParameterId::PID_SENTINEL]) {
+      let length: u16 = if *id == ParameterId::PID_SENTINEL {
+        0
+      } else {
+        4
+      };
+      value.extend(id.write_to_vec_with_ctx(endianness).unwrap());
+      value.extend(length.write_to_vec_with_ctx(endianness).unwrap());
+      value.extend(vec![0xAB; usize::from(length)]);
+    }
+    SerializedPayload::new(representation_identifier, value)
+  }
+
+  #[test]
+  fn participant_builtin_endpoints() {
+    let old = ParameterId::PID_PARTICIPANT_BUILTIN_ENDPOINTS;
+    let new = ParameterId::PID_BUILTIN_ENDPOINT_SET;
+    let other = ParameterId::PID_PARTICIPANT_GUID;
+    let workarounds = VendorWorkaround::ParticipantBuiltinEndpoints.into();
+
+    for (rep_id, endianness) in [
+      (
+        RepresentationIdentifier::PL_CDR_LE,
+        Endianness::LittleEndian,
+      ),
+      (RepresentationIdentifier::PL_CDR_BE, Endianness::BigEndian),
+    ] {
+      let mut payload = spdp_payload(rep_id, endianness, &[other, old]);
+      adapt_spdp_payload(workarounds, &mut payload);
+      assert_eq!(payload, spdp_payload(rep_id, endianness, &[other, new]));
+
+      // Both are given, so PID_BUILTIN_ENDPOINT_SET is already there.
+      let mut payload = spdp_payload(rep_id, endianness, &[old, new]);
+      adapt_spdp_payload(workarounds, &mut payload);
+      assert_eq!(payload, spdp_payload(rep_id, endianness, &[old, new]));
+
+      let mut payload = spdp_payload(rep_id, endianness, &[other, old]);
+      adapt_spdp_payload(BitFlags::empty(), &mut payload);
+      assert_eq!(payload, spdp_payload(rep_id, endianness, &[other, old]));
+    }
+  }
+}
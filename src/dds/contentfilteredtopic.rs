@@ -0,0 +1,192 @@
+use std::fmt::Debug;
+
+use serde::Serialize;
+
+use crate::{
+  dds::{
+    dynamic_data::DynamicType,
+    participant::DomainParticipant,
+    query_expression::SampleFilter,
+    topic::{validate_topic_name, Topic, TopicDescription},
+    typedesc::TypeDesc,
+    values::result::Result,
+  },
+  discovery::content_filter_property::ContentFilterProperty,
+};
+
+/// A Topic whose samples are restricted to those that match a filter
+/// expression on the sample contents.
+///
+/// A DataReader created on a ContentFilteredTopic receives only the matching
+/// samples of the related [`Topic`]. The filter is announced to remote
+/// DataWriters in discovery, so that those which support writer-side
+/// filtering do not even send the other samples. RustDDS DataWriters do
+/// this. Samples that carry only a key, e.g. due to dispose, are not filtered.
+///
+/// If the [`DynamicType`] of the Topic type is set in the
+/// [`TypeRegistry`](crate::TypeRegistry::set_dynamic_type), the filter is
+/// evaluated on serialized samples, by decoding only the members that the
+/// expression refers to. Samples that do not match are then never
+/// deserialized.
+///
+/// The expression syntax is the same as for a
+/// [`QueryCondition`](crate::QueryCondition).
+///
+/// Create with
+/// [`DomainParticipant::create_contentfilteredtopic`](crate::DomainParticipant::create_contentfilteredtopic)
+/// and read with
+/// [`Subscriber::create_filtered_datareader`](crate::Subscriber::create_filtered_datareader).
+///
+/// See DDS Specification 1.4 Section "2.2.2.3.3 ContentFilteredTopic Class".
+///
+/// # Examples
+///
+/// ```
+/// # use rustdds::{DomainParticipant, QosPolicyBuilder, TopicKind};
+/// #
+/// let domain_participant = DomainParticipant::new(0).unwrap();
+/// let qos = QosPolicyBuilder::new().build();
+/// let topic = domain_participant
+///   .create_topic("shapes".to_string(), "ShapeType".to_string(), &qos, TopicKind::WithKey)
+///   .unwrap();
+///
+/// let red_shapes = domain_participant
+///   .create_contentfilteredtopic(
+///     "red_shapes".to_string(),
+///     &topic,
+///     "color = %0 AND x < 100",
+///     vec!["'RED'".to_string()],
+///   )
+///   .unwrap();
+/// assert_eq!(red_shapes.related_topic(), &topic);
+/// ```
+#[derive(Clone)]
+pub struct ContentFilteredTopic {
+  name: String,
+  related_topic: Topic,
+  expression_parameters: Vec<String>,
+  filter: SampleFilter,
+}
+
+impl ContentFilteredTopic {
+  // The filter class of DDS SQL expressions in discovery data.
+  pub(crate) const FILTER_CLASS_NAME: &'static str = "DDSSQL";
+
+  pub(crate) fn new(
+    name: String,
+    related_topic: &Topic,
+    filter_expression: &str,
+    expression_parameters: Vec<String>,
+  ) -> Result<Self> {
+    validate_topic_name(&name)?;
+    Ok(Self {
+      name,
+      related_topic: related_topic.clone(),
+      filter: SampleFilter::new(filter_expression, &expression_parameters)?,
+      expression_parameters,
+    })
+  }
+
+  pub fn related_topic(&self) -> &Topic {
+    &self.related_topic
+  }
+
+  pub fn filter_expression(&self) -> &str {
+    self.filter.expression()
+  }
+
+  pub fn expression_parameters(&self) -> &[String] {
+    &self.expression_parameters
+  }
+
+  /// Evaluates the filter expression on a sample.
+  /// Samples that cannot be evaluated do not match.
+  pub fn matches<T: Serialize + ?Sized>(&self, sample: &T) -> bool {
+    self.filter.matches(sample)
+  }
+
+  // The filter for serialized samples of `data_type`, if the members the
+  // expression refers to can be projected out of them.
+  pub(crate) fn serialized_filter(&self, data_type: &DynamicType) -> Option<SampleFilter> {
+    let mut filter = self.filter.clone();
+    filter.set_dynamic_type(data_type);
+    Some(filter).filter(SampleFilter::has_projection)
+  }
+
+  pub(crate) fn content_filter_property(&self) -> ContentFilterProperty {
+    ContentFilterProperty {
+      content_filtered_topic_name: self.name.clone(),
+      related_topic_name: self.related_topic.name(),
+      filter_class_name: Self::FILTER_CLASS_NAME.to_string(),
+      filter_expression: self.filter_expression().to_string(),
+      expression_parameters: self.expression_parameters.clone(),
+    }
+  }
+}
+
+impl TopicDescription for ContentFilteredTopic {
+  fn participant(&self) -> Option<DomainParticipant> {
+    self.related_topic.participant()
+  }
+
+  /// The type of the related Topic
+  fn get_type(&self) -> TypeDesc {
+    self.related_topic.get_type()
+  }
+
+  /// The name of this ContentFilteredTopic, not of the related Topic
+  fn name(&self) -> String {
+    self.name.clone()
+  }
+}
+
+impl Debug for ContentFilteredTopic {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ContentFilteredTopic")
+      .field("name", &self.name)
+      .field("related_topic", &self.related_topic.name())
+      .field("filter_expression", &self.filter_expression())
+      .field("expression_parameters", &self.expression_parameters)
+      .finish()
+  }
+}
+
+// Filter announced by a remote DataReader, as evaluated by our DataWriter.
+// Filters of unknown classes or with invalid expressions are not evaluated,
+// i.e. all samples are sent.
+pub(crate) fn filter_from_property(property: &ContentFilterProperty) -> Option<SampleFilter> {
+  if property.filter_class_name != ContentFilteredTopic::FILTER_CLASS_NAME {
+    return None;
+  }
+  SampleFilter::new(&property.filter_expression, &property.expression_parameters).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Serialize)]
+  struct Sample {
+    a: i32,
+  }
+
+  #[test]
+  fn filter_from_content_filter_property() {
+    let mut property = ContentFilterProperty {
+      content_filtered_topic_name: "cft".to_string(),
+      related_topic_name: "topic".to_string(),
+      filter_class_name: ContentFilteredTopic::FILTER_CLASS_NAME.to_string(),
+      filter_expression: "a > %0".to_string(),
+      expression_parameters: vec!["3".to_string()],
+    };
+    let filter = filter_from_property(&property).unwrap();
+    assert!(filter.matches(&Sample { a: 4 }));
+    assert!(!filter.matches(&Sample { a: 2 }));
+
+    property.filter_class_name = "REGEX".to_string();
+    assert!(filter_from_property(&property).is_none());
+    property.filter_class_name = ContentFilteredTopic::FILTER_CLASS_NAME.to_string();
+    property.expression_parameters.clear();
+    assert!(filter_from_property(&property).is_none());
+  }
+}
@@ -13,7 +13,9 @@ use crate::{
     traits::key::{Key, KeyHash, Keyed},
     with_key::datasample::DataSample,
   },
-  structure::{guid::GUID, sequence_number::SequenceNumber, time::Timestamp},
+  structure::{
+    cache_change::ChangeKind, guid::GUID, sequence_number::SequenceNumber, time::Timestamp,
+  },
   with_key::WriteOptions,
 };
 
@@ -50,6 +52,11 @@ pub(crate) struct InstanceMetaData {
   instance_state: InstanceState,         // latest known alive/not_alive state for this instance
   latest_generation_available: NotAliveGenerationCounts, // in this instance
   last_generation_accessed: NotAliveGenerationCounts, // in this instance
+  // Source timestamp and writer of the latest accepted sample. This is used only
+  // with DESTINATION_ORDER BY_SOURCE_TIMESTAMP.
+  latest_source_stamp: Option<(Timestamp, GUID)>,
+  // Writers that have written to this instance and have not been lost since.
+  live_writers: BTreeSet<GUID>,
 }
 
 struct SampleWithMetaData<D: Keyed> {
@@ -92,6 +99,7 @@ where
     }
   }
 
+  // Adds a data sample (Ok) or a dispose (Err).
   pub fn add_sample(
     &mut self,
     new_sample: Result<D, D::K>,
@@ -99,16 +107,132 @@ where
     sequence_number: SequenceNumber,
     receive_timestamp: Timestamp,
     write_options: WriteOptions,
+  ) {
+    let new_instance_state = match new_sample {
+      Ok(_) => InstanceState::Alive,
+      Err(_) => InstanceState::NotAliveDisposed,
+    };
+    self.add_sample_with_state(
+      new_sample,
+      new_instance_state,
+      writer_guid,
+      sequence_number,
+      receive_timestamp,
+      write_options,
+    );
+  }
+
+  // Adds a sample without data, i.e. a dispose or unregister.
+  pub fn add_not_alive_sample(
+    &mut self,
+    key: D::K,
+    change_kind: ChangeKind,
+    writer_guid: GUID,
+    sequence_number: SequenceNumber,
+    receive_timestamp: Timestamp,
+    write_options: WriteOptions,
+  ) {
+    match change_kind {
+      ChangeKind::NotAliveUnregistered => self.unregister_instance(
+        key,
+        writer_guid,
+        sequence_number,
+        receive_timestamp,
+        write_options,
+      ),
+      // Alive should not happen without data. Treat it as dispose, as before.
+      ChangeKind::NotAliveDisposed | ChangeKind::Alive => self.add_sample(
+        Err(key),
+        writer_guid,
+        sequence_number,
+        receive_timestamp,
+        write_options,
+      ),
+    }
+  }
+
+  // DDS spec v1.4 Section 2.2.2.5.1.3: An unregistered instance becomes
+  // NOT_ALIVE_NO_WRITERS only if no other writer is writing it. Otherwise the
+  // unregister is not visible to the application. A disposed instance stays
+  // disposed.
+  fn unregister_instance(
+    &mut self,
+    key: D::K,
+    writer_guid: GUID,
+    sequence_number: SequenceNumber,
+    receive_timestamp: Timestamp,
+    write_options: WriteOptions,
+  ) {
+    let no_writers_left = match self.instance_map.get_mut(&key) {
+      Some(imd) => {
+        imd.live_writers.remove(&writer_guid);
+        imd.live_writers.is_empty() && imd.instance_state == InstanceState::Alive
+      }
+      None => false, // never seen this instance, nothing to unregister
+    };
+    if no_writers_left {
+      self.add_sample_with_state(
+        Err(key),
+        InstanceState::NotAliveNoWriters,
+        writer_guid,
+        sequence_number,
+        receive_timestamp,
+        write_options,
+      );
+    } else {
+      debug!(
+        "unregister_instance: {:?} from {:?} does not change instance state",
+        key.hash_key(),
+        writer_guid
+      );
+    }
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn add_sample_with_state(
+    &mut self,
+    new_sample: Result<D, D::K>,
+    new_instance_state: InstanceState,
+    writer_guid: GUID,
+    sequence_number: SequenceNumber,
+    receive_timestamp: Timestamp,
+    write_options: WriteOptions,
   ) {
     let instance_key = match &new_sample {
       Ok(d) => d.key(),
       Err(k) => k.clone(),
     };
 
-    let new_instance_state = match new_sample {
-      Ok(_) => InstanceState::Alive,
-      Err(_) => InstanceState::NotAliveDisposed,
-    };
+    // DDS spec v1.4 Section 2.2.3.17 DESTINATION_ORDER:
+    // With BY_SOURCE_TIMESTAMP, the final value of an instance must be the same
+    // on all DataReaders regardless of reception order. We reach this by
+    // rejecting any sample that is older than the latest one already accepted
+    // into the same instance. Equal timestamps are tie-broken by writer GUID,
+    // so that the outcome is deterministic on every reader.
+    // Samples without a source timestamp are stamped with the reception time.
+    let source_stamp = (
+      write_options.source_timestamp.unwrap_or(receive_timestamp),
+      writer_guid,
+    );
+    let by_source_timestamp = matches!(
+      self.qos.destination_order(),
+      Some(policy::DestinationOrder::BySourceTimeStamp)
+    );
+    if by_source_timestamp {
+      if let Some(latest) = self
+        .instance_map
+        .get(&instance_key)
+        .and_then(|imd| imd.latest_source_stamp)
+      {
+        if source_stamp < latest {
+          debug!(
+            "add_sample: BY_SOURCE_TIMESTAMP rejects {:?} from {:?}, as it is older than {:?}",
+            sequence_number, writer_guid, latest
+          );
+          return;
+        }
+      }
+    }
 
     // find or create metadata record
     let instance_metadata = if let Some(imd) = self.instance_map.get_mut(&instance_key) {
@@ -121,6 +245,8 @@ where
         latest_generation_available: NotAliveGenerationCounts::zero(), /* this is new instance,
                                                                         * so start from zero */
         last_generation_accessed: NotAliveGenerationCounts::sub_zero(), // never accessed
+        latest_source_stamp: None,
+        live_writers: BTreeSet::new(),
       };
       self.instance_map.insert(instance_key.clone(), imd);
       self
@@ -135,6 +261,9 @@ where
 
     // update instance metadata
     instance_metadata.instance_samples.insert(receive_timestamp);
+    if new_instance_state != InstanceState::NotAliveNoWriters {
+      instance_metadata.live_writers.insert(writer_guid);
+    }
 
     match (instance_metadata.instance_state, new_instance_state) {
       (InstanceState::Alive, _) => (), // was Alive, does not change counts
@@ -160,6 +289,9 @@ where
       (InstanceState::NotAliveNoWriters, _) => (), // you can only die once
     }
     instance_metadata.instance_state = new_instance_state;
+    if by_source_timestamp {
+      instance_metadata.latest_source_stamp = Some(source_stamp);
+    }
 
     // insert new_sample to main table
     self
@@ -228,12 +360,24 @@ where
   // Samples are marked read or viewed only when "read" or "take" methods (below)
   // are called.
   pub fn select_keys_for_access(&self, rc: ReadCondition) -> Vec<(Timestamp, D::K)> {
+    self.select_keys_for_access_filtered(rc, |_| true)
+  }
+
+  // Like select_keys_for_access, but samples must also pass the filter, e.g. a
+  // QueryCondition. Key-only samples are given as Err.
+  pub fn select_keys_for_access_filtered(
+    &self,
+    rc: ReadCondition,
+    filter: impl Fn(std::result::Result<&D, &D::K>) -> bool,
+  ) -> Vec<(Timestamp, D::K)> {
     self
       .datasamples
       .iter()
       .filter_map(|(ts, dsm)| {
         let key = dsm.key();
-        if self.sample_selector(&rc, self.instance_map.get(&key).unwrap(), dsm) {
+        if self.sample_selector(&rc, self.instance_map.get(&key).unwrap(), dsm)
+          && filter(dsm.sample.as_ref())
+        {
           Some((*ts, key))
         } else {
           None
@@ -267,6 +411,26 @@ where
     }
   }
 
+  // Selects the samples of the first instance after `key`, in key order, that
+  // has any samples matching the condition. Without a key, starts from the
+  // smallest instance.
+  pub fn select_next_instance_keys_for_access(
+    &self,
+    key: Option<&D::K>,
+    rc: ReadCondition,
+  ) -> Vec<(Timestamp, D::K)> {
+    let lower_bound = match key {
+      Some(k) => Bound::Excluded(k),
+      None => Bound::Unbounded,
+    };
+    self
+      .instance_map
+      .range((lower_bound, Bound::Unbounded))
+      .map(|(k, _)| self.select_instance_keys_for_access(k, rc))
+      .find(|selected| !selected.is_empty())
+      .unwrap_or_default()
+  }
+
   // select helper
   fn sample_selector(
     &self,
@@ -297,13 +461,36 @@ where
     )
   }
 
+  // DDS spec v1.4 Section 2.2.2.5.5 SampleInfo: sample_rank and
+  // generation_rank are relative to the samples of the same instance in the
+  // returned collection. For each of the `keys`, this returns the number of
+  // samples of the same instance that follow it, and the generation total of
+  // the last of them, the Most Recent Sample In the Collection (MRSIC).
+  fn collection_ranks(&self, keys: &[(Timestamp, D::K)]) -> Vec<(usize, i32)> {
+    let mut following: HashMap<&D::K, (usize, i32)> = HashMap::new();
+    let mut ranks = vec![(0, 0); keys.len()];
+    for (index, (ts, key)) in keys.iter().enumerate().rev() {
+      let generations = self
+        .datasamples
+        .get(ts)
+        .map_or(0, |dswm| dswm.generation_counts.total());
+      // Going backwards, the first sample seen of each instance is its MRSIC.
+      let (count, mrsic_generations) = following.entry(key).or_insert((0, generations));
+      ranks[index] = (*count, *mrsic_generations);
+      *count += 1;
+    }
+    ranks
+  }
+
   fn make_sample_info(
     dswm: &SampleWithMetaData<D>,
     imd: &InstanceMetaData,
     sample_rank: usize,
-    mrs_generations: i32,
     mrsic_generations: i32,
   ) -> SampleInfo {
+    // The Most Recent Sample (MRS) of the instance has the latest generation
+    // counts.
+    let mrs_generations = imd.latest_generation_available.total();
     SampleInfo {
       sample_state: if dswm.sample_has_been_read {
         SampleState::Read
@@ -317,7 +504,8 @@ where
       },
       instance_state: imd.instance_state,
       generation_counts: dswm.generation_counts,
-      sample_rank: sample_rank as i32, // how many samples follow this one
+      valid_data: dswm.sample.is_ok(),
+      sample_rank: sample_rank as i32, // how many samples of the instance follow this one
       generation_rank: mrsic_generations - dswm.generation_counts.total(),
       absolute_generation_rank: mrs_generations - dswm.generation_counts.total(),
       write_options: dswm.write_options.clone(),
@@ -369,27 +557,15 @@ where
     }
 
     let mut instance_generations: HashMap<D::K, NotAliveGenerationCounts> = HashMap::new();
-    let mrsic_total = self
-      .instance_map
-      .get(&keys.last().unwrap().1)
-      .unwrap()
-      .latest_generation_available
-      .total();
-    let mrs_total = self
-      .datasamples
-      .iter()
-      .next_back()
-      .unwrap()
-      .1
-      .generation_counts
-      .total();
+    let ranks = self.collection_ranks(keys);
     let mut sample_infos = VecDeque::with_capacity(len);
     // construct SampleInfos and record read/viewed
     for (index, (ts, key)) in keys.iter().enumerate() {
       let dswm = self.datasamples.get_mut(ts).unwrap();
       let imd = self.instance_map.get(key).unwrap();
 
-      let sample_info = Self::make_sample_info(dswm, imd, len - index - 1, mrs_total, mrsic_total);
+      let (sample_rank, mrsic_total) = ranks[index];
+      let sample_info = Self::make_sample_info(dswm, imd, sample_rank, mrsic_total);
       dswm.sample_has_been_read = true; // mark as read
       Self::record_instance_generation_viewed(
         &mut instance_generations,
@@ -431,25 +607,13 @@ where
     }
 
     let mut instance_generations: HashMap<D::K, NotAliveGenerationCounts> = HashMap::new();
-    let mrsic_total = self
-      .instance_map
-      .get(&keys.last().unwrap().1)
-      .unwrap()
-      .latest_generation_available
-      .total();
-    let mrs_total = self
-      .datasamples
-      .iter()
-      .next_back()
-      .unwrap()
-      .1
-      .generation_counts
-      .total();
+    let ranks = self.collection_ranks(keys);
     // collect result
     for (index, (ts, key)) in keys.iter().enumerate() {
       let dswm = self.datasamples.remove(ts).unwrap();
       let imd = self.instance_map.get(key).unwrap();
-      let sample_info = Self::make_sample_info(&dswm, imd, len - index - 1, mrs_total, mrsic_total);
+      let (sample_rank, mrsic_total) = ranks[index];
+      let sample_info = Self::make_sample_info(&dswm, imd, sample_rank, mrsic_total);
       //dwsm.sample_has_been_read = true; // no need to mark read, as the dswm is
       // about to be destroyed
       Self::record_instance_generation_viewed(
@@ -531,6 +695,87 @@ where
     result
   }
 
+  // Takes all samples into `result`, like take_by_keys with the keys of all
+  // samples, but without collecting the keys or other intermediate results.
+  // Returns the number of samples taken.
+  pub fn take_all_into(&mut self, result: &mut Vec<DataSample<D>>) -> usize {
+    let len = self.datasamples.len();
+    result.reserve(len);
+
+    // Earlier takes may have left samples in instance_samples. Those that
+    // remain are exactly the samples of the instance about to be taken.
+    let datasamples = &self.datasamples;
+    for imd in self.instance_map.values_mut() {
+      imd
+        .instance_samples
+        .retain(|ts| datasamples.contains_key(ts));
+    }
+
+    while let Some(ts) = self.datasamples.keys().next().copied() {
+      let dswm = self.datasamples.remove(&ts).unwrap();
+      let datasamples = &self.datasamples;
+      let imd = self.instance_map.get_mut(&dswm.key()).unwrap();
+      imd.instance_samples.remove(&ts);
+      // The samples of the instance that follow this one, and the last of
+      // them is the MRSIC.
+      let sample_rank = imd.instance_samples.len();
+      let mrsic_generations = imd
+        .instance_samples
+        .iter()
+        .next_back()
+        .and_then(|mrsic_ts| datasamples.get(mrsic_ts))
+        .unwrap_or(&dswm)
+        .generation_counts
+        .total();
+      let sample_info = Self::make_sample_info(&dswm, imd, sample_rank, mrsic_generations);
+      // The instance is viewed once its last sample is taken, so that its
+      // other samples in the collection have the same view state.
+      if sample_rank == 0 {
+        imd.last_generation_accessed = dswm.generation_counts;
+      }
+      result.push(DataSample::new(sample_info, dswm.sample));
+    }
+    len
+  }
+
+  // A matched writer was lost, e.g. its participant lease expired.
+  // DDS spec v1.4 Section 2.2.2.5.1.3: Alive instances that have no other
+  // live writers become NOT_ALIVE_NO_WRITERS. If the writer later comes back
+  // (e.g. after a network partition heals), its next sample causes the
+  // no_writers_generation_count of the instance to be incremented.
+  pub fn writer_lost(&mut self, writer_guid: GUID) {
+    for (key, imd) in self.instance_map.iter_mut() {
+      if imd.live_writers.remove(&writer_guid)
+        && imd.live_writers.is_empty()
+        && imd.instance_state == InstanceState::Alive
+      {
+        debug!(
+          "writer_lost: instance {:?} has no writers after losing {:?}",
+          key.hash_key(),
+          writer_guid
+        );
+        imd.instance_state = InstanceState::NotAliveNoWriters;
+      }
+    }
+  }
+
+  // Remember a key hash received from a writer. Normally it is the same as the
+  // one we compute, but the writer may compute it differently, e.g. with
+  // different alignment padding.
+  // The lifespan of the sample received at this instant has expired, so it
+  // is removed without being accessed.
+  pub fn remove_expired(&mut self, instant: Timestamp) {
+    if let Some(dswm) = self.datasamples.remove(&instant) {
+      if let Some(imd) = self.instance_map.get_mut(&dswm.key()) {
+        imd.instance_samples.remove(&instant);
+      }
+    }
+  }
+
+  pub fn add_key_hash(&mut self, key_hash: KeyHash, key: D::K) {
+    self.hash_to_key_map.entry(key_hash).or_insert(key);
+  }
+
   pub fn key_by_hash(&self, key_hash: KeyHash) -> Option<D::K> {
     if let Some(k) = self.hash_to_key_map.get(&key_hash) {
       Some(k.clone())
@@ -544,17 +789,6 @@ where
     }
   }
 
-  pub fn next_key(&self, key: &D::K) -> Option<D::K> {
-    self
-      .instance_map
-      .range((Bound::Excluded(key), Bound::Unbounded))
-      .map(|(k, _)| k.clone())
-      .next()
-  }
-
-  // This is currently unused, as HasQosPolicy trait does not allow setting QoS,
-  // but a placeholder if it is ever implemented.
-  #[allow(dead_code)]
   pub fn set_qos_policy(&mut self, qos: QosPolicies) {
     self.qos = qos;
   }
@@ -562,7 +796,10 @@ where
 
 #[cfg(test)]
 mod tests {
-  // use super::*;
+  use super::*;
+  use crate::{
+    dds::qos::QosPolicyBuilder, structure::duration::Duration, test::random_data::RandomData,
+  };
   // use crate::{
   //   structure::{time::Timestamp},
   // };
@@ -599,4 +836,453 @@ mod tests {
     }
     */
   }
+
+  #[test]
+  fn dsc_destination_order_by_source_timestamp() {
+    let qos = QosPolicyBuilder::new()
+      .destination_order(policy::DestinationOrder::BySourceTimeStamp)
+      .history(policy::History::KeepAll)
+      .build();
+    let mut datasample_cache = DataSampleCache::<RandomData>::new(qos);
+
+    let writer_a = GUID::from_bytes([1; 16]);
+    let writer_b = GUID::from_bytes([2; 16]);
+    let source_time = Timestamp::now();
+    let sample = |b: &str| RandomData {
+      a: 4,
+      b: b.to_string(),
+    };
+
+    // newest source timestamp arrives first
+    datasample_cache.add_sample(
+      Ok(sample("new")),
+      writer_a,
+      SequenceNumber::from(2),
+      Timestamp::now(),
+      WriteOptions::from(Some(source_time)),
+    );
+    // older source timestamp arrives later and must be dropped
+    datasample_cache.add_sample(
+      Ok(sample("old")),
+      writer_a,
+      SequenceNumber::from(1),
+      Timestamp::now() + Duration::from_millis(1),
+      WriteOptions::from(Some(source_time - Duration::from_millis(10))),
+    );
+    assert_eq!(datasample_cache.datasamples.len(), 1);
+
+    // equal source timestamp is tie-broken by writer GUID
+    datasample_cache.add_sample(
+      Ok(sample("tie-lost")),
+      GUID::from_bytes([0; 16]),
+      SequenceNumber::from(1),
+      Timestamp::now() + Duration::from_millis(2),
+      WriteOptions::from(Some(source_time)),
+    );
+    assert_eq!(datasample_cache.datasamples.len(), 1);
+    datasample_cache.add_sample(
+      Ok(sample("tie-won")),
+      writer_b,
+      SequenceNumber::from(1),
+      Timestamp::now() + Duration::from_millis(3),
+      WriteOptions::from(Some(source_time)),
+    );
+    assert_eq!(datasample_cache.datasamples.len(), 2);
+
+    let values: Vec<String> = datasample_cache
+      .datasamples
+      .values()
+      .map(|s| s.sample.as_ref().unwrap().b.clone())
+      .collect();
+    assert_eq!(values, vec!["new".to_string(), "tie-won".to_string()]);
+  }
+
+  #[test]
+  fn dsc_writer_lost_and_rediscovered() {
+    let qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepAll)
+      .build();
+    let mut datasample_cache = DataSampleCache::<RandomData>::new(qos);
+
+    let writer_a = GUID::from_bytes([1; 16]);
+    let writer_b = GUID::from_bytes([2; 16]);
+    let sample = |a: i64| RandomData {
+      a,
+      b: "foo".to_string(),
+    };
+    let instance_state = |dsc: &DataSampleCache<RandomData>, a: i64| {
+      dsc
+        .instance_map
+        .get(&sample(a).key())
+        .unwrap()
+        .instance_state
+    };
+    let now = Timestamp::now();
+
+    // instance 1 is written by A only, instance 2 by both A and B
+    datasample_cache.add_sample(
+      Ok(sample(1)),
+      writer_a,
+      SequenceNumber::from(1),
+      now,
+      WriteOptions::default(),
+    );
+    datasample_cache.add_sample(
+      Ok(sample(2)),
+      writer_a,
+      SequenceNumber::from(2),
+      now + Duration::from_millis(1),
+      WriteOptions::default(),
+    );
+    datasample_cache.add_sample(
+      Ok(sample(2)),
+      writer_b,
+      SequenceNumber::from(1),
+      now + Duration::from_millis(2),
+      WriteOptions::default(),
+    );
+
+    // Network partition: A is lost.
+    datasample_cache.writer_lost(writer_a);
+    assert_eq!(
+      instance_state(&datasample_cache, 1),
+      InstanceState::NotAliveNoWriters
+    );
+    assert_eq!(instance_state(&datasample_cache, 2), InstanceState::Alive);
+
+    // Losing A again (e.g. reported by another path) changes nothing.
+    datasample_cache.writer_lost(writer_a);
+    assert_eq!(
+      instance_state(&datasample_cache, 1),
+      InstanceState::NotAliveNoWriters
+    );
+
+    // Partition heals and A writes instance 1 again.
+    datasample_cache.add_sample(
+      Ok(sample(1)),
+      writer_a,
+      SequenceNumber::from(3),
+      now + Duration::from_millis(3),
+      WriteOptions::default(),
+    );
+    let imd = datasample_cache.instance_map.get(&sample(1).key()).unwrap();
+    assert_eq!(imd.instance_state, InstanceState::Alive);
+    assert_eq!(
+      imd.latest_generation_available.no_writers_generation_count,
+      1
+    );
+    assert_eq!(imd.latest_generation_available.disposed_generation_count, 0);
+
+    // Instance 2 never lost all its writers, so its counts are unchanged.
+    let imd = datasample_cache.instance_map.get(&sample(2).key()).unwrap();
+    assert_eq!(
+      imd.latest_generation_available.no_writers_generation_count,
+      0
+    );
+  }
+
+  #[test]
+  fn dsc_unregister_and_dispose() {
+    let qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepAll)
+      .build();
+    let mut datasample_cache = DataSampleCache::<RandomData>::new(qos);
+
+    let writer_a = GUID::from_bytes([1; 16]);
+    let writer_b = GUID::from_bytes([2; 16]);
+    let sample = |a: i64| RandomData {
+      a,
+      b: "foo".to_string(),
+    };
+    let instance_state = |dsc: &DataSampleCache<RandomData>, a: i64| {
+      dsc
+        .instance_map
+        .get(&sample(a).key())
+        .unwrap()
+        .instance_state
+    };
+    let now = Timestamp::now();
+    let at = |ms: i64| now + Duration::from_millis(ms);
+
+    // Instance 1 is written by A and B.
+    datasample_cache.add_sample(
+      Ok(sample(1)),
+      writer_a,
+      SequenceNumber::from(1),
+      at(0),
+      WriteOptions::default(),
+    );
+    datasample_cache.add_sample(
+      Ok(sample(1)),
+      writer_b,
+      SequenceNumber::from(1),
+      at(1),
+      WriteOptions::default(),
+    );
+
+    // A unregisters, but B still writes the instance.
+    datasample_cache.add_not_alive_sample(
+      sample(1).key(),
+      ChangeKind::NotAliveUnregistered,
+      writer_a,
+      SequenceNumber::from(2),
+      at(2),
+      WriteOptions::default(),
+    );
+    assert_eq!(instance_state(&datasample_cache, 1), InstanceState::Alive);
+    assert_eq!(datasample_cache.datasamples.len(), 2);
+
+    // B unregisters, so there are no writers left.
+    datasample_cache.add_not_alive_sample(
+      sample(1).key(),
+      ChangeKind::NotAliveUnregistered,
+      writer_b,
+      SequenceNumber::from(2),
+      at(3),
+      WriteOptions::default(),
+    );
+    assert_eq!(
+      instance_state(&datasample_cache, 1),
+      InstanceState::NotAliveNoWriters
+    );
+    assert_eq!(datasample_cache.datasamples.len(), 3);
+
+    // Instance 2 is disposed, and unregistering does not revive it as
+    // NotAliveNoWriters.
+    datasample_cache.add_sample(
+      Ok(sample(2)),
+      writer_a,
+      SequenceNumber::from(3),
+      at(4),
+      WriteOptions::default(),
+    );
+    datasample_cache.add_not_alive_sample(
+      sample(2).key(),
+      ChangeKind::NotAliveDisposed,
+      writer_a,
+      SequenceNumber::from(4),
+      at(5),
+      WriteOptions::default(),
+    );
+    datasample_cache.add_not_alive_sample(
+      sample(2).key(),
+      ChangeKind::NotAliveUnregistered,
+      writer_a,
+      SequenceNumber::from(5),
+      at(6),
+      WriteOptions::default(),
+    );
+    assert_eq!(
+      instance_state(&datasample_cache, 2),
+      InstanceState::NotAliveDisposed
+    );
+  }
+
+  #[test]
+  fn dsc_select_next_instance_skips_non_matching() {
+    let qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepAll)
+      .build();
+    let mut datasample_cache = DataSampleCache::<RandomData>::new(qos);
+
+    let writer = GUID::from_bytes([1; 16]);
+    let sample = |a: i64| RandomData {
+      a,
+      b: "foo".to_string(),
+    };
+    let now = Timestamp::now();
+    for a in 1..=3 {
+      datasample_cache.add_sample(
+        Ok(sample(a)),
+        writer,
+        SequenceNumber::from(a),
+        now + Duration::from_millis(a),
+        WriteOptions::default(),
+      );
+    }
+    // Instance 2 has been read already.
+    let instance_2 =
+      datasample_cache.select_instance_keys_for_access(&sample(2).key(), ReadCondition::any());
+    datasample_cache.read_by_keys(&instance_2);
+
+    let next_unread = |dsc: &DataSampleCache<RandomData>, key: Option<i64>| {
+      dsc
+        .select_next_instance_keys_for_access(
+          key.map(|a| sample(a).key()).as_ref(),
+          ReadCondition::not_read(),
+        )
+        .into_iter()
+        .map(|(_ts, key)| key)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(next_unread(&datasample_cache, None), vec![sample(1).key()]);
+    assert_eq!(
+      next_unread(&datasample_cache, Some(1)),
+      vec![sample(3).key()]
+    );
+    assert!(next_unread(&datasample_cache, Some(3)).is_empty());
+  }
+
+  #[test]
+  fn dsc_sample_info_ranks() {
+    let qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepAll)
+      .build();
+    let mut datasample_cache = DataSampleCache::<RandomData>::new(qos);
+
+    let writer = GUID::from_bytes([1; 16]);
+    let sample = |a: i64| RandomData {
+      a,
+      b: "foo".to_string(),
+    };
+    let now = Timestamp::now();
+    let at = |ms: i64| now + Duration::from_millis(ms);
+    let mut sn = 0;
+    let mut next_sn = || {
+      sn += 1;
+      SequenceNumber::from(sn)
+    };
+
+    // Instance 1: alive, disposed, alive again. Instance 2 is interleaved.
+    datasample_cache.add_sample(
+      Ok(sample(1)),
+      writer,
+      next_sn(),
+      at(0),
+      WriteOptions::default(),
+    );
+    datasample_cache.add_sample(
+      Ok(sample(2)),
+      writer,
+      next_sn(),
+      at(1),
+      WriteOptions::default(),
+    );
+    datasample_cache.add_sample(Err(1), writer, next_sn(), at(2), WriteOptions::default());
+    datasample_cache.add_sample(
+      Ok(sample(1)),
+      writer,
+      next_sn(),
+      at(3),
+      WriteOptions::default(),
+    );
+
+    // Read all but the last sample, so that the MRS of instance 1 is not in
+    // the collection.
+    let mut selected = datasample_cache.select_keys_for_access(ReadCondition::any());
+    selected.pop();
+    let infos: Vec<(i64, bool, i32, i32, i32)> = datasample_cache
+      .read_by_keys(&selected)
+      .iter()
+      .map(|ds| {
+        let si = ds.sample_info();
+        (
+          ds.key(),
+          si.valid_data(),
+          si.sample_rank(),
+          si.generation_rank(),
+          si.absolute_generation_rank(),
+        )
+      })
+      .collect();
+    assert_eq!(
+      infos,
+      vec![(1, true, 1, 0, 1), (2, true, 0, 0, 0), (1, false, 0, 0, 1),]
+    );
+  }
+
+  #[test]
+  fn dsc_remove_expired() {
+    let qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepAll)
+      .build();
+    let writer = GUID::from_bytes([1; 16]);
+    let now = Timestamp::now();
+    let mut datasample_cache = DataSampleCache::<RandomData>::new(qos);
+    for n in 1..=3 {
+      datasample_cache.add_sample(
+        Ok(RandomData {
+          a: 1,
+          b: n.to_string(),
+        }),
+        writer,
+        SequenceNumber::from(n),
+        now + Duration::from_millis(n),
+        WriteOptions::default(),
+      );
+    }
+
+    datasample_cache.remove_expired(now + Duration::from_millis(2));
+    // Not in the cache
+    datasample_cache.remove_expired(now);
+
+    let keys = datasample_cache.select_instance_keys_for_access(&1, ReadCondition::any());
+    let samples: Vec<String> = datasample_cache
+      .take_bare_by_keys(&keys)
+      .into_iter()
+      .map(|s| s.unwrap().b)
+      .collect();
+    assert_eq!(samples, ["1", "3"]);
+  }
+
+  #[test]
+  fn dsc_take_all_into() {
+    let qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepAll)
+      .build();
+    let writer = GUID::from_bytes([1; 16]);
+    let now = Timestamp::now();
+    let new_cache = || {
+      let mut datasample_cache = DataSampleCache::<RandomData>::new(qos.clone());
+      let samples = [Ok(1), Ok(2), Err(1), Ok(1), Ok(3), Ok(2)];
+      for (n, sample) in (1..).zip(samples) {
+        datasample_cache.add_sample(
+          sample.map(|a| RandomData {
+            a,
+            b: "foo".to_string(),
+          }),
+          writer,
+          SequenceNumber::from(n),
+          now + Duration::from_millis(n),
+          WriteOptions::default(),
+        );
+      }
+      // Earlier accesses: instance 3 has been viewed, and a sample of
+      // instance 2 taken.
+      let instance_3 = datasample_cache.select_instance_keys_for_access(&3, ReadCondition::any());
+      datasample_cache.read_by_keys(&instance_3);
+      let instance_2 = datasample_cache.select_instance_keys_for_access(&2, ReadCondition::any());
+      datasample_cache.take_by_keys(&instance_2[..1]);
+      datasample_cache
+    };
+
+    // Same as taking all samples by their keys
+    let mut by_keys = new_cache();
+    let all = by_keys.select_keys_for_access(ReadCondition::any());
+    let expected = by_keys.take_by_keys(&all);
+    assert_eq!(expected.len(), 5);
+
+    let mut datasample_cache = new_cache();
+    let mut taken = Vec::with_capacity(10);
+    assert_eq!(datasample_cache.take_all_into(&mut taken), 5);
+    assert_eq!(taken, expected);
+    assert_eq!(taken.capacity(), 10);
+
+    // The buffer is appended to, and instances stay viewed.
+    datasample_cache.add_sample(
+      Ok(RandomData {
+        a: 1,
+        b: "bar".to_string(),
+      }),
+      writer,
+      SequenceNumber::from(10),
+      now + Duration::from_millis(10),
+      WriteOptions::default(),
+    );
+    assert_eq!(datasample_cache.take_all_into(&mut taken), 1);
+    assert_eq!(taken.len(), 6);
+    assert_eq!(taken[5].sample_info().view_state(), ViewState::NotNew);
+    assert_eq!(taken[5].sample_info().sample_rank(), 0);
+    assert_eq!(datasample_cache.take_all_into(&mut taken), 0);
+  }
 }
@@ -1,3 +1,5 @@
+use std::{any::Any, fmt, sync::Arc};
+
 use bytes::Bytes;
 
 use crate::{
@@ -14,8 +16,13 @@ use crate::structure::cache_change::ChangeKind;
 // a new sample, or key, or a key hash. The latter two are used to indicate
 // dispose or unregister.
 pub enum DDSData {
+  // key_hash is sent and received as inline QoS, if the topic is keyed.
+  // local_sample is the sample itself, if the writer shares it with local
+  // readers. It is never sent.
   Data {
     serialized_payload: SerializedPayload,
+    key_hash: Option<KeyHash>,
+    local_sample: Option<LocalSample>,
   },
   // DataFrags {
   //   // Each DATAFRAG specifies RepresentationIdentifier, but we assume they are the same.
@@ -28,6 +35,7 @@ pub enum DDSData {
   DisposeByKey {
     change_kind: ChangeKind,
     key: SerializedPayload,
+    key_hash: Option<KeyHash>,
   },
   DisposeByKeyHash {
     change_kind: ChangeKind,
@@ -37,10 +45,18 @@ pub enum DDSData {
 
 impl DDSData {
   pub fn new(serialized_payload: SerializedPayload) -> Self {
-    Self::Data { serialized_payload }
+    Self::Data {
+      serialized_payload,
+      key_hash: None,
+      local_sample: None,
+    }
   }
   pub fn new_disposed_by_key(change_kind: ChangeKind, key: SerializedPayload) -> Self {
-    Self::DisposeByKey { change_kind, key }
+    Self::DisposeByKey {
+      change_kind,
+      key,
+      key_hash: None,
+    }
   }
 
   pub fn new_disposed_by_key_hash(change_kind: ChangeKind, key_hash: KeyHash) -> Self {
@@ -50,7 +66,36 @@ impl DDSData {
     }
   }
 
-  #[allow(dead_code)] // Why is this not used?
+  // Attach the key hash of a keyed sample. Has no effect on
+  // DisposeByKeyHash, which already is a key hash.
+  #[must_use]
+  pub fn with_key_hash(mut self, new_key_hash: Option<KeyHash>) -> Self {
+    match &mut self {
+      DDSData::Data { key_hash, .. } | DDSData::DisposeByKey { key_hash, .. } => {
+        *key_hash = new_key_hash;
+      }
+      DDSData::DisposeByKeyHash { .. } => (),
+    }
+    self
+  }
+
+  // Attach the unserialized sample for DataReaders in the same participant.
+  // Has no effect on disposes.
+  #[must_use]
+  pub fn with_local_sample(mut self, new_local_sample: Option<LocalSample>) -> Self {
+    if let DDSData::Data { local_sample, .. } = &mut self {
+      *local_sample = new_local_sample;
+    }
+    self
+  }
+
+  pub fn key_hash(&self) -> Option<KeyHash> {
+    match self {
+      DDSData::Data { key_hash, .. } | DDSData::DisposeByKey { key_hash, .. } => *key_hash,
+      DDSData::DisposeByKeyHash { key_hash, .. } => Some(*key_hash),
+    }
+  }
+
   pub fn change_kind(&self) -> ChangeKind {
     match self {
       DDSData::Data {..} /*| DDSData::DataFrags {..}*/ => ChangeKind::Alive,
@@ -61,7 +106,9 @@ impl DDSData {
   // What is the serialized size of this?
   pub fn payload_size(&self) -> usize {
     match self {
-      DDSData::Data { serialized_payload } => serialized_payload.len_serialized(),
+      DDSData::Data {
+        serialized_payload, ..
+      } => serialized_payload.len_serialized(),
       DDSData::DisposeByKey { key, .. } => key.len_serialized(),
       DDSData::DisposeByKeyHash { .. } => 16,
       // This is a fundamental constant of the RTPS
@@ -77,7 +124,9 @@ impl DDSData {
   #[cfg(test)]
   fn payload_bytes(&self) -> Bytes {
     match &self {
-      DDSData::Data { serialized_payload } => serialized_payload.value.clone(),
+      DDSData::Data {
+        serialized_payload, ..
+      } => serialized_payload.value.clone(),
       DDSData::DisposeByKey { key, .. } => key.value.clone(),
       DDSData::DisposeByKeyHash { key_hash, .. } => Bytes::from(key_hash.to_vec()),
     }
@@ -85,7 +134,9 @@ impl DDSData {
 
   pub fn bytes_slice(&self, from: usize, to: usize) -> Bytes {
     match &self {
-      DDSData::Data { serialized_payload } => serialized_payload.bytes_slice(from, to),
+      DDSData::Data {
+        serialized_payload, ..
+      } => serialized_payload.bytes_slice(from, to),
       DDSData::DisposeByKey { key, .. } => key.bytes_slice(from, to),
       DDSData::DisposeByKeyHash { key_hash, .. } => {
         Bytes::from(key_hash.to_vec().into_boxed_slice()).slice(from..to)
@@ -93,3 +144,65 @@ impl DDSData {
     }
   }
 }
+
+// A sample shared between a DataWriter and the DataReaders of the same
+// participant through the DDSCache, so that the readers can clone it instead
+// of deserializing. The DataWriter knows that the type is Clone, so it
+// provides the clone function.
+#[derive(Clone)]
+pub struct LocalSample {
+  sample: Arc<dyn Any + Send + Sync>,
+  clone_sample: fn(&(dyn Any + Send + Sync)) -> Option<Box<dyn Any>>,
+}
+
+impl LocalSample {
+  pub fn new<D: Clone + Send + Sync + 'static>(sample: D) -> Self {
+    fn clone_sample<D: Clone + 'static>(sample: &(dyn Any + Send + Sync)) -> Option<Box<dyn Any>> {
+      sample
+        .downcast_ref::<D>()
+        .map(|d| Box::new(d.clone()) as Box<dyn Any>)
+    }
+    Self {
+      sample: Arc::new(sample),
+      clone_sample: clone_sample::<D>,
+    }
+  }
+
+  // A copy of the sample, or None if the sample is not of type D. Readers may
+  // have a different Rust type for the same topic type.
+  pub fn get<D: 'static>(&self) -> Option<D> {
+    (self.clone_sample)(&*self.sample)
+      .and_then(|sample| sample.downcast::<D>().ok())
+      .map(|sample| *sample)
+  }
+}
+
+impl fmt::Debug for LocalSample {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("LocalSample")
+  }
+}
+
+// The sample is the same, if it is the very same sample.
+impl PartialEq for LocalSample {
+  fn eq(&self, other: &Self) -> bool {
+    // Compare only addresses. Vtable pointers may differ for the same type.
+    Arc::as_ptr(&self.sample) as *const () == Arc::as_ptr(&other.sample) as *const ()
+  }
+}
+
+impl Eq for LocalSample {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn local_sample_of_other_type_is_not_got() {
+    let sample = LocalSample::new(String::from("hello"));
+    assert_eq!(sample.get::<String>(), Some(String::from("hello")));
+    assert_eq!(sample.get::<Vec<u8>>(), None);
+    assert_eq!(sample.clone(), sample);
+    assert_ne!(LocalSample::new(String::from("hello")), sample);
+  }
+}
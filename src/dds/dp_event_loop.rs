@@ -1,36 +1,55 @@
 use std::{
-  collections::HashMap,
+  collections::{BTreeSet, HashMap},
+  net::IpAddr,
   rc::Rc,
   sync::{Arc, RwLock},
   time::{Duration, Instant},
 };
 
+use bytes::Bytes;
 use log::{debug, error, info, trace, warn};
 use mio::{Event, Events, Poll, PollOpt, Ready, Token};
 use mio_extras::channel as mio_channel;
 
 use crate::{
   dds::{
-    message_receiver::MessageReceiver,
-    qos::policy,
+    clock::TimeSource,
+    compatibility::ProtocolCompatibility,
+    health::{HealthMonitor, ProtocolThread},
+    message_receiver::{AckSubmessageFrom, MessageReceiver},
+    qos::{policy, HasQoSPolicy, QosPolicies},
     reader::{Reader, ReaderIngredients},
+    receive_loop::{ReceiveCommand, ReceiveEvent, ReceiveLoops},
+    statistics::StatisticsMonitor,
+    threads::BackgroundLoop,
+    timer::Timer,
     writer::{Writer, WriterIngredients},
   },
   discovery::{
     data_types::topic_data::{DiscoveredReaderData, DiscoveredWriterData},
     discovery_db::DiscoveryDB,
   },
-  messages::submessages::submessages::AckSubmessage,
-  network::{constant::*, udp_listener::UDPListener, udp_sender::UDPSender},
+  network::{
+    constant::*,
+    interface_filter::InterfaceFilter,
+    packet_capture::PacketCapture,
+    socket_options::SocketOptions,
+    transport::{self, TransportKind, TransportListener, Transports},
+    util::get_local_ip_addrs,
+  },
+  security::SecurityConfig,
   structure::{
     builtin_endpoint::BuiltinEndpointSet,
     dds_cache::DDSCache,
     entity::RTPSEntity,
     guid::{EntityId, GuidPrefix, TokenDecode, GUID},
+    locator::Locator,
   },
 };
 //use crate::discovery::data_types::spdp_participant_data::SpdpDiscoveredParticipantData;
 use crate::discovery::discovery::Discovery;
+#[cfg(feature = "security")]
+use crate::security::plugins::CryptographyHandle;
 use super::{
   rtps_reader_proxy::RtpsReaderProxy, rtps_writer_proxy::RtpsWriterProxy, typedesc::TypeDesc,
 };
@@ -43,6 +62,9 @@ pub struct DomainInfo {
 
 pub const PREEMPTIVE_ACKNACK_PERIOD: Duration = Duration::from_secs(5);
 
+// How often to check if network interfaces have come or gone
+pub const NETWORK_INTERFACES_CHECK_PERIOD: Duration = Duration::from_secs(5);
+
 // RTPS spec Section 8.4.7.1.1  "Default Timing-Related Values"
 pub const NACK_RESPONSE_DELAY: Duration = Duration::from_millis(200);
 pub const NACK_SUPPRESSION_DURATION: Duration = Duration::from_millis(0);
@@ -52,7 +74,9 @@ pub struct DPEventLoop {
   poll: Poll,
   ddscache: Arc<RwLock<DDSCache>>,
   discovery_db: Arc<RwLock<DiscoveryDB>>,
-  udp_listeners: HashMap<Token, UDPListener>,
+  listeners: HashMap<Token, Box<dyn TransportListener>>,
+  // Receive loops that read the other listeners, if the participant has any
+  receive_loops: ReceiveLoops,
   message_receiver: MessageReceiver, // This contains our Readers
 
   // Adding readers
@@ -65,20 +89,52 @@ pub struct DPEventLoop {
   stop_poll_receiver: mio_channel::Receiver<()>,
   // GuidPrefix sent in this channel needs to be RTPSMessage source_guid_prefix. Writer needs this
   // to locate RTPSReaderProxy if negative acknack.
-  ack_nack_receiver: mio_channel::Receiver<(GuidPrefix, AckSubmessage)>,
+  ack_nack_receiver: mio_channel::Receiver<AckSubmessageFrom>,
 
   writers: HashMap<EntityId, Writer>,
-  udp_sender: Rc<UDPSender>,
+  transport_kinds: Vec<TransportKind>,
+  transports: Rc<Transports>,
+  // Senders for Writers with TRANSPORT_PRIORITY QoS, one per priority value
+  prioritized_transports: HashMap<i32, Rc<Transports>>,
+
+  // Network interfaces that we use, and their addresses when last checked
+  interfaces: InterfaceFilter,
+  interface_addrs: BTreeSet<IpAddr>,
+  socket_options: SocketOptions,
+  // Locators of each listener, also those of the receive loops
+  listener_locators: HashMap<Token, Vec<Locator>>,
+  // Shared with DomainParticipant, from where Discovery reads them
+  self_locators: Arc<RwLock<HashMap<Token, Vec<Locator>>>>,
 
   discovery_update_notification_receiver: mio_channel::Receiver<DiscoveryNotificationType>,
+  health_monitor: HealthMonitor,
+  statistics: StatisticsMonitor,
+  // Shared with DomainParticipant, which starts and stops it
+  packet_capture: PacketCapture,
+  compatibility: ProtocolCompatibility,
+  // Creates the timers of Readers and Writers
+  time_source: TimeSource,
+  acknack_timer: Timer<()>,
+  network_interfaces_timer: Timer<()>,
+
+  // DDS Security protection of messages, shared with all Readers and Writers
+  #[cfg(feature = "security")]
+  cryptography: Option<CryptographyHandle>,
 }
 
 impl DPEventLoop {
   // This pub(crate) , because it should be constructed only by DomainParticipant.
   #[allow(clippy::too_many_arguments)]
+  #[cfg_attr(not(feature = "security"), allow(clippy::needless_pass_by_value))]
   pub(crate) fn new(
     domain_info: DomainInfo,
-    udp_listeners: HashMap<Token, UDPListener>,
+    mut listeners: HashMap<Token, Box<dyn TransportListener>>,
+    receive_loops: ReceiveLoops,
+    transport_kinds: Vec<TransportKind>,
+    interfaces: InterfaceFilter,
+    socket_options: SocketOptions,
+    listener_locators: HashMap<Token, Vec<Locator>>,
+    self_locators: Arc<RwLock<HashMap<Token, Vec<Locator>>>>,
     ddscache: Arc<RwLock<DDSCache>>,
     discovery_db: Arc<RwLock<DiscoveryDB>>,
     participant_guid_prefix: GuidPrefix,
@@ -89,21 +145,35 @@ impl DPEventLoop {
     stop_poll_receiver: mio_channel::Receiver<()>,
     discovery_update_notification_receiver: mio_channel::Receiver<DiscoveryNotificationType>,
     spdp_liveness_sender: mio_channel::SyncSender<GuidPrefix>,
+    health_monitor: HealthMonitor,
+    statistics: StatisticsMonitor,
+    packet_capture: PacketCapture,
+    compatibility: ProtocolCompatibility,
+    time_source: TimeSource,
+    security: SecurityConfig,
   ) -> Self {
     let poll = Poll::new().expect("Unable to create new poll.");
-    let (acknack_sender, acknack_receiver) =
-      mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(100);
-    let mut udp_listeners = udp_listeners;
-    for (token, listener) in &mut udp_listeners {
+    let (acknack_sender, acknack_receiver) = mio_channel::sync_channel::<AckSubmessageFrom>(100);
+    for (token, listener) in &mut listeners {
+      debug!("Listening on {:?} with {:?}", listener.kind(), token);
+      listener.set_packet_capture(packet_capture.clone());
       poll
         .register(
-          listener.mio_socket(),
+          listener.evented(),
           *token,
           Ready::readable(),
           PollOpt::edge(),
         )
         .expect("Failed to register listener.");
     }
+    poll
+      .register(
+        &receive_loops.events,
+        DPEV_RECEIVED_MESSAGES_TOKEN,
+        Ready::readable(),
+        PollOpt::edge(),
+      )
+      .expect("Failed to register receive loop events.");
 
     poll
       .register(
@@ -167,21 +237,63 @@ impl DPEventLoop {
       )
       .expect("Failed to register reader update notification.");
 
-    // port number 0 means OS chooses an available port number.
-    let udp_sender = UDPSender::new(0).expect("UDPSender construction fail"); // TODO
+    let mut acknack_timer = time_source.timer();
+    acknack_timer.set_timeout(PREEMPTIVE_ACKNACK_PERIOD, ());
+    poll
+      .register(
+        &acknack_timer,
+        DPEV_ACKNACK_TIMER_TOKEN,
+        Ready::readable(),
+        PollOpt::edge(),
+      )
+      .expect("Failed to register preemptive AckNack timer");
+    let mut network_interfaces_timer = time_source.timer();
+    network_interfaces_timer.set_timeout(NETWORK_INTERFACES_CHECK_PERIOD, ());
+    poll
+      .register(
+        &network_interfaces_timer,
+        DPEV_NETWORK_INTERFACES_TIMER_TOKEN,
+        Ready::readable(),
+        PollOpt::edge(),
+      )
+      .expect("Failed to register network interfaces timer");
+
+    let transports = Transports::new(&transport_kinds, &interfaces, &socket_options)
+      .expect("Transport construction fail") // TODO
+      .with_traffic_counters(statistics.traffic())
+      .with_packet_capture(&packet_capture);
+    let interface_addrs = get_local_ip_addrs(&interfaces);
+
+    let mut message_receiver = MessageReceiver::new(
+      participant_guid_prefix,
+      acknack_sender,
+      spdp_liveness_sender,
+    );
+    message_receiver.set_protocol_compatibility(compatibility.clone());
+    if security.validate_source_addresses {
+      message_receiver.validate_source_addresses();
+    }
+    #[cfg(feature = "security")]
+    if let Some(cryptography) = &security.cryptography {
+      message_receiver.set_cryptography(cryptography.clone());
+    }
 
     Self {
       domain_info,
       poll,
       ddscache,
       discovery_db,
-      udp_listeners,
-      udp_sender: Rc::new(udp_sender),
-      message_receiver: MessageReceiver::new(
-        participant_guid_prefix,
-        acknack_sender,
-        spdp_liveness_sender,
-      ),
+      listeners,
+      receive_loops,
+      transport_kinds,
+      transports: Rc::new(transports),
+      prioritized_transports: HashMap::new(),
+      interfaces,
+      interface_addrs,
+      socket_options,
+      listener_locators,
+      self_locators,
+      message_receiver,
       add_reader_receiver,
       remove_reader_receiver,
       add_writer_receiver,
@@ -190,179 +302,222 @@ impl DPEventLoop {
       writers: HashMap::new(),
       ack_nack_receiver: acknack_receiver,
       discovery_update_notification_receiver,
+      health_monitor,
+      statistics,
+      packet_capture,
+      compatibility,
+      time_source,
+      acknack_timer,
+      network_interfaces_timer,
+      #[cfg(feature = "security")]
+      cryptography: security.cryptography,
     }
   }
 
-  pub fn event_loop(self) {
+  pub fn event_loop(mut self) {
     let mut events = Events::with_capacity(16); // too small capacity just delays events to next poll
-    let mut acknack_timer = mio_extras::timer::Timer::default();
-    acknack_timer.set_timeout(PREEMPTIVE_ACKNACK_PERIOD, ());
+    while self.run_round(&mut events, Some(Duration::from_millis(2000))) {}
+  }
 
+  // Waits for events for at most `timeout`, and handles them. Returns false
+  // when the event loop has been stopped.
+  pub(crate) fn run_round(&mut self, events: &mut Events, timeout: Option<Duration>) -> bool {
     self
       .poll
-      .register(
-        &acknack_timer,
-        DPEV_ACKNACK_TIMER_TOKEN,
-        Ready::readable(),
-        PollOpt::edge(),
-      )
-      .unwrap();
-    let mut poll_alive = Instant::now();
-    let mut ev_wrapper = self;
-    loop {
-      ev_wrapper
-        .poll
-        .poll(&mut events, Some(Duration::from_millis(2000)))
-        .expect("Failed in waiting of poll.");
+      .poll(events, timeout)
+      .expect("Failed in waiting of poll.");
+    let round_start = Instant::now();
 
-      // liveness watchdog
-      let now = Instant::now();
-      if now > poll_alive + Duration::from_secs(2) {
-        debug!("Poll loop alive");
-        poll_alive = now;
-      }
-
-      if events.is_empty() {
-        debug!("dp_event_loop idling.");
-      } else {
-        for event in events.iter() {
-          match EntityId::from_token(event.token()) {
-            TokenDecode::FixedToken(fixed_token) => match fixed_token {
-              STOP_POLL_TOKEN => {
-                let _ = ev_wrapper.stop_poll_receiver.try_recv();
-                // we are not really interested in the content
-                info!("Stopping dp_event_loop");
-                return;
-              }
-              DISCOVERY_LISTENER_TOKEN
-              | DISCOVERY_MUL_LISTENER_TOKEN
-              | USER_TRAFFIC_LISTENER_TOKEN
-              | USER_TRAFFIC_MUL_LISTENER_TOKEN => {
-                let udp_messages = ev_wrapper
-                  .udp_listeners
-                  .get_mut(&event.token())
-                  .map_or_else(
-                    || {
-                      error!("No listener with token {:?}", &event.token());
-                      vec![]
-                    },
-                    UDPListener::messages,
-                  );
-                for packet in udp_messages {
-                  ev_wrapper.message_receiver.handle_received_packet(&packet);
-                }
-              }
-              ADD_READER_TOKEN | REMOVE_READER_TOKEN => {
-                ev_wrapper.handle_reader_action(&event);
-              }
-              ADD_WRITER_TOKEN | REMOVE_WRITER_TOKEN => {
-                ev_wrapper.handle_writer_action(&event);
-              }
-              ACKNACK_MESSGAGE_TO_LOCAL_WRITER_TOKEN => {
-                ev_wrapper.handle_writer_acknack_action(&event);
-              }
-              DISCOVERY_UPDATE_NOTIFICATION_TOKEN => {
-                while let Ok(dnt) = ev_wrapper.discovery_update_notification_receiver.try_recv() {
-                  use DiscoveryNotificationType::*;
-                  match dnt {
-                    WriterUpdated {
-                      discovered_writer_data,
-                    } => ev_wrapper.remote_writer_discovered(&discovered_writer_data),
-
-                    WriterLost { writer_guid } => ev_wrapper.remote_writer_lost(writer_guid),
-
-                    ReaderUpdated {
-                      discovered_reader_data,
-                    } => ev_wrapper.remote_reader_discovered(&discovered_reader_data),
-
-                    ReaderLost { reader_guid } => ev_wrapper.remote_reader_lost(reader_guid),
-
-                    ParticipantUpdated { guid_prefix } => {
-                      ev_wrapper.update_participant(guid_prefix);
-                    }
-
-                    ParticipantLost { guid_prefix } => {
-                      ev_wrapper.remote_participant_lost(guid_prefix);
-                    }
-
-                    AssertTopicLiveliness {
-                      writer_guid,
-                      manual_assertion,
-                    } => {
-                      ev_wrapper
-                        .writers
-                        .get_mut(&writer_guid.entity_id)
-                        .map(|w| w.handle_heartbeat_tick(manual_assertion));
-                    }
+    if events.is_empty() {
+      debug!("dp_event_loop idling.");
+    } else {
+      for event in events.iter() {
+        match EntityId::from_token(event.token()) {
+          TokenDecode::FixedToken(fixed_token) => match fixed_token {
+            STOP_POLL_TOKEN => {
+              let _ = self.stop_poll_receiver.try_recv();
+              // we are not really interested in the content
+              info!("Stopping dp_event_loop");
+              self.receive_loops.send(ReceiveCommand::Stop);
+              self.shut_down_writers();
+              return false;
+            }
+            DISCOVERY_LISTENER_TOKEN
+            | DISCOVERY_MUL_LISTENER_TOKEN
+            | USER_TRAFFIC_LISTENER_TOKEN
+            | USER_TRAFFIC_MUL_LISTENER_TOKEN
+            | DISCOVERY_V6_LISTENER_TOKEN
+            | DISCOVERY_MUL_V6_LISTENER_TOKEN
+            | USER_TRAFFIC_V6_LISTENER_TOKEN
+            | USER_TRAFFIC_MUL_V6_LISTENER_TOKEN
+            | USER_TRAFFIC_SHM_LISTENER_TOKEN
+            | USER_TRAFFIC_TCP_LISTENER_TOKEN => {
+              let messages = self.listeners.get_mut(&event.token()).map_or_else(
+                || {
+                  error!("No listener with token {:?}", &event.token());
+                  vec![]
+                },
+                |listener| listener.messages_with_source(),
+              );
+              self.handle_received_messages(messages);
+            }
+            DPEV_RECEIVED_MESSAGES_TOKEN => {
+              while let Ok(receive_event) = self.receive_loops.events.try_recv() {
+                match receive_event {
+                  ReceiveEvent::Messages(messages) => self.handle_received_messages(messages),
+                  ReceiveEvent::Locators(locators) => {
+                    self.listener_locators.extend(locators);
+                    *self.self_locators.write().unwrap() =
+                      transport::self_locators(&self.listener_locators);
                   }
                 }
               }
-              DPEV_ACKNACK_TIMER_TOKEN => {
-                ev_wrapper.message_receiver.send_preemptive_acknacks();
-                acknack_timer.set_timeout(PREEMPTIVE_ACKNACK_PERIOD, ());
-              }
+            }
+            ADD_READER_TOKEN | REMOVE_READER_TOKEN => {
+              self.handle_reader_action(&event);
+            }
+            ADD_WRITER_TOKEN | REMOVE_WRITER_TOKEN => {
+              self.handle_writer_action(&event);
+            }
+            ACKNACK_MESSGAGE_TO_LOCAL_WRITER_TOKEN => {
+              self.handle_writer_acknack_action(&event);
+            }
+            DISCOVERY_UPDATE_NOTIFICATION_TOKEN => {
+              while let Ok(dnt) = self.discovery_update_notification_receiver.try_recv() {
+                use DiscoveryNotificationType::*;
+                match dnt {
+                  WriterUpdated {
+                    discovered_writer_data,
+                  } => self.remote_writer_discovered(&discovered_writer_data),
 
-              fixed_unknown => {
-                error!(
-                  "Unknown event.token {:?} = 0x{:x?} , decoded as {:?}",
-                  event.token(),
-                  event.token().0,
-                  fixed_unknown
-                );
-              }
-            },
-
-            // Commands/actions
-            TokenDecode::Entity(eid) => {
-              if eid.kind().is_reader() {
-                ev_wrapper.message_receiver.reader_mut(eid).map_or_else(
-                  || error!("Event for unknown reader {:?}", eid),
-                  Reader::process_command,
-                );
-              } else if eid.kind().is_writer() {
-                let local_readers = match ev_wrapper.writers.get_mut(&eid) {
-                  None => {
-                    error!("Event for unknown writer {:?}", eid);
-                    vec![]
+                  WriterLost { writer_guid } => self.remote_writer_lost(writer_guid),
+
+                  ReaderUpdated {
+                    discovered_reader_data,
+                  } => self.remote_reader_discovered(&discovered_reader_data),
+
+                  ReaderLost { reader_guid } => self.remote_reader_lost(reader_guid),
+
+                  ParticipantUpdated { guid_prefix } => {
+                    self.update_participant(guid_prefix);
                   }
-                  Some(writer) => {
-                    // Writer will record data to DDSCache and send it out.
-                    writer.process_writer_command();
-                    writer.local_readers()
+
+                  ParticipantLost { guid_prefix } => {
+                    self.remote_participant_lost(guid_prefix);
                   }
-                };
-                // Notify local (same participant) readers that new data is available in the
-                // cache.
-                ev_wrapper
-                  .message_receiver
-                  .notify_data_to_readers(local_readers);
-              } else {
-                error!("Entity Event for unknown EntityKind {:?}", eid);
+
+                  AssertTopicLiveliness {
+                    writer_guid,
+                    manual_assertion,
+                  } => {
+                    self
+                      .writers
+                      .get_mut(&writer_guid.entity_id)
+                      .map(|w| w.handle_heartbeat_tick(manual_assertion));
+                  }
+
+                  LocalWriterUpdated { writer_guid, qos } => {
+                    self.local_writer_updated(writer_guid, qos);
+                  }
+
+                  LocalReaderUpdated { reader_guid, qos } => {
+                    self.local_reader_updated(reader_guid, qos);
+                  }
+
+                  ClockJump => self.clock_jump(),
+                }
               }
             }
+            DPEV_ACKNACK_TIMER_TOKEN => {
+              self.message_receiver.send_preemptive_acknacks();
+              self
+                .acknack_timer
+                .set_timeout(PREEMPTIVE_ACKNACK_PERIOD, ());
+            }
+            DPEV_NETWORK_INTERFACES_TIMER_TOKEN => {
+              self.check_network_interfaces();
+              self
+                .network_interfaces_timer
+                .set_timeout(NETWORK_INTERFACES_CHECK_PERIOD, ());
+            }
 
-            // Timed Actions
-            TokenDecode::AltEntity(eid) => {
-              if eid.kind().is_reader() {
-                ev_wrapper.handle_reader_timed_event(eid);
-              } else if eid.kind().is_writer() {
-                ev_wrapper.handle_writer_timed_event(eid);
-              } else {
-                error!("AltEntity Event for unknown EntityKind {:?}", eid);
-              }
+            fixed_unknown => {
+              error!(
+                "Unknown event.token {:?} = 0x{:x?} , decoded as {:?}",
+                event.token(),
+                event.token().0,
+                fixed_unknown
+              );
+            }
+          },
+
+          // Commands/actions
+          TokenDecode::Entity(eid) => {
+            if eid.kind().is_reader() {
+              self.message_receiver.reader_mut(eid).map_or_else(
+                || error!("Event for unknown reader {:?}", eid),
+                Reader::process_command,
+              );
+            } else if eid.kind().is_writer() {
+              let local_readers = match self.writers.get_mut(&eid) {
+                None => {
+                  error!("Event for unknown writer {:?}", eid);
+                  vec![]
+                }
+                Some(writer) => {
+                  // Writer will record data to DDSCache and send it out.
+                  writer.process_writer_command();
+                  writer.local_readers()
+                }
+              };
+              // Notify local (same participant) readers that new data is available in the
+              // cache.
+              self.message_receiver.notify_data_to_readers(local_readers);
+            } else {
+              error!("Entity Event for unknown EntityKind {:?}", eid);
             }
           }
-        } // for
-      } // if
-    } // loop
-  } // fn
+
+          // Timed Actions
+          TokenDecode::AltEntity(eid) => {
+            if eid.kind().is_reader() {
+              self.handle_reader_timed_event(eid);
+            } else if eid.kind().is_writer() {
+              self.handle_writer_timed_event(eid);
+            } else {
+              error!("AltEntity Event for unknown EntityKind {:?}", eid);
+            }
+          }
+        }
+      } // for
+    } // if
+      // liveness watchdog
+    self
+      .health_monitor
+      .round_done(ProtocolThread::EventLoop, round_start.elapsed());
+    true
+  }
+
+  fn handle_received_messages(&mut self, messages: Vec<(Bytes, Option<IpAddr>)>) {
+    let traffic = self.statistics.traffic();
+    for (packet, source_address) in messages {
+      traffic.messages_received.increment();
+      traffic.bytes_received.add(packet.len() as u64);
+      self
+        .message_receiver
+        .handle_received_packet(&packet, source_address);
+    }
+    traffic
+      .messages_dropped
+      .set(self.message_receiver.dropped_messages.total());
+  }
 
   fn handle_reader_action(&mut self, event: &Event) {
     match event.token() {
       ADD_READER_TOKEN => {
         trace!("add reader(s)");
         while let Ok(new_reader_ing) = self.add_reader_receiver.receiver.try_recv() {
-          let timer = mio_extras::timer::Builder::default().num_slots(8).build();
+          let timer = self.time_source.small_timer(8);
           self
             .poll
             .register(
@@ -375,7 +530,7 @@ impl DPEventLoop {
           let mut new_reader = Reader::new(
             new_reader_ing,
             self.ddscache.clone(),
-            self.udp_sender.clone(),
+            self.transports.clone(),
             timer,
           );
 
@@ -390,13 +545,27 @@ impl DPEventLoop {
             )
             .expect("Reader command channel registration failed!!!");
 
+          #[cfg(feature = "security")]
+          if let Some(cryptography) = &self.cryptography {
+            new_reader.set_cryptography(cryptography.clone());
+          }
           new_reader.set_requested_deadline_check_timer();
+          self.statistics.add_reader(
+            new_reader.guid(),
+            new_reader.topic_name(),
+            new_reader.counters.clone(),
+          );
           trace!("Add reader: {:?}", new_reader);
           self.message_receiver.add_reader(new_reader);
         }
       }
       REMOVE_READER_TOKEN => {
         while let Ok(old_reader_guid) = self.remove_reader_receiver.receiver.try_recv() {
+          self.statistics.remove_endpoint(old_reader_guid);
+          #[cfg(feature = "security")]
+          if let Some(cryptography) = &self.cryptography {
+            cryptography.unregister_local_endpoint(old_reader_guid.entity_id);
+          }
           if let Some(old_reader) = self.message_receiver.remove_reader(old_reader_guid) {
             self
               .poll
@@ -421,7 +590,7 @@ impl DPEventLoop {
     match event.token() {
       ADD_WRITER_TOKEN => {
         while let Ok(new_writer_ingredients) = self.add_writer_receiver.receiver.try_recv() {
-          let timer = mio_extras::timer::Builder::default().num_slots(8).build();
+          let timer = self.time_source.small_timer(8);
           self
             .poll
             .register(
@@ -431,12 +600,18 @@ impl DPEventLoop {
               PollOpt::edge(),
             )
             .expect("Writer heartbeat timer channel registration failed!!");
-          let new_writer = Writer::new(
+          let transports = self.transports_for(&new_writer_ingredients.qos_policies);
+          #[cfg_attr(not(feature = "security"), allow(unused_mut))]
+          let mut new_writer = Writer::new(
             new_writer_ingredients,
             self.ddscache.clone(),
-            self.udp_sender.clone(),
+            transports,
             timer,
           );
+          #[cfg(feature = "security")]
+          if let Some(cryptography) = &self.cryptography {
+            new_writer.set_cryptography(cryptography.clone());
+          }
 
           self
             .poll
@@ -447,12 +622,25 @@ impl DPEventLoop {
               PollOpt::edge(),
             )
             .expect("Writer command channel registration failed!!");
+          self.statistics.add_writer(
+            new_writer.guid(),
+            new_writer.topic_name(),
+            new_writer.counters.clone(),
+          );
           self.writers.insert(new_writer.guid().entity_id, new_writer);
         }
       }
       REMOVE_WRITER_TOKEN => {
         while let Ok(writer_guid) = &self.remove_writer_receiver.receiver.try_recv() {
-          if let Some(w) = self.writers.remove(&writer_guid.entity_id) {
+          self.statistics.remove_endpoint(*writer_guid);
+          #[cfg(feature = "security")]
+          if let Some(cryptography) = &self.cryptography {
+            cryptography.unregister_local_endpoint(writer_guid.entity_id);
+          }
+          if let Some(mut w) = self.writers.remove(&writer_guid.entity_id) {
+            // Send what the DataWriter wrote before it was dropped, e.g. the
+            // last announcements of Discovery.
+            w.process_writer_command();
             self
               .poll
               .deregister(&w.writer_command_receiver)
@@ -468,6 +656,19 @@ impl DPEventLoop {
     }
   }
 
+  // Last words of the Writers before the event loop stops: Discovery has
+  // stopped, and its Writers have announcements of the participant and its
+  // endpoints being disposed. User Writers unregister their instances, so that
+  // remote readers need not wait for lease timeouts.
+  fn shut_down_writers(&mut self) {
+    for writer in self.writers.values_mut() {
+      writer.process_writer_command();
+      if writer.guid().entity_id.kind().is_user_defined() {
+        writer.unregister_all_instances();
+      }
+    }
+  }
+
   /// Writer timed events can be heatrbeats or cache cleaning events.
   /// events are distinguished by TimerMessageType which is send via mio
   /// channel. Channel token in
@@ -488,14 +689,16 @@ impl DPEventLoop {
   }
 
   fn handle_writer_acknack_action(&mut self, _event: &Event) {
-    while let Ok((acknack_sender_prefix, acknack_submessage)) = self.ack_nack_receiver.try_recv() {
+    while let Ok((acknack_sender_prefix, acknack_submessage, reply_locators)) =
+      self.ack_nack_receiver.try_recv()
+    {
       let writer_guid = GUID::new_with_prefix_and_id(
         self.domain_info.domain_participant_guid.prefix,
         acknack_submessage.writer_id(),
       );
       if let Some(found_writer) = self.writers.get_mut(&writer_guid.entity_id) {
         if found_writer.is_reliable() {
-          found_writer.handle_ack_nack(acknack_sender_prefix, &acknack_submessage);
+          found_writer.handle_ack_nack(acknack_sender_prefix, &acknack_submessage, &reply_locators);
         }
       } else {
         warn!(
@@ -524,6 +727,30 @@ impl DPEventLoop {
           error!("Participant was updated, but DB does not have it. Strange.");
           return;
         };
+      let available_builtin_endpoints = self.compatibility.builtin_endpoints(
+        discovered_participant.protocol_version,
+        discovered_participant.available_builtin_endpoints,
+      );
+      self.message_receiver.set_participant_addresses(
+        participant_guid_prefix,
+        discovered_participant
+          .metatraffic_unicast_locators
+          .iter()
+          .chain(discovered_participant.default_unicast_locators.iter()),
+      );
+      // Until DDS Security authentication is completed, only discovery and
+      // the handshake itself are allowed. Crypto tokens are exchanged only
+      // with remote participants.
+      let authenticated = db.is_authenticated(participant_guid_prefix);
+      let myself = participant_guid_prefix == self.domain_info.domain_participant_guid.prefix;
+      let allowed = |writer_eid: &EntityId| {
+        if *writer_eid == EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER {
+          return authenticated && !myself;
+        }
+        authenticated
+          || *writer_eid == EntityId::SPDP_BUILTIN_PARTICIPANT_WRITER
+          || *writer_eid == EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_WRITER
+      };
 
       for (writer_eid, reader_eid, endpoint) in &[
         (
@@ -551,7 +778,30 @@ impl DPEventLoop {
           EntityId::P2P_BUILTIN_PARTICIPANT_MESSAGE_READER,
           BuiltinEndpointSet::BUILTIN_ENDPOINT_PARTICIPANT_MESSAGE_DATA_READER,
         ),
+        (
+          EntityId::TL_SVC_REQ_WRITER, // TypeLookup
+          EntityId::TL_SVC_REQ_READER,
+          BuiltinEndpointSet::BUILTIN_ENDPOINT_TL_SVC_REQUEST_DATA_READER,
+        ),
+        (
+          EntityId::TL_SVC_REPLY_WRITER,
+          EntityId::TL_SVC_REPLY_READER,
+          BuiltinEndpointSet::BUILTIN_ENDPOINT_TL_SVC_REPLY_DATA_READER,
+        ),
+        (
+          EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_WRITER, // DDS Security
+          EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_READER,
+          BuiltinEndpointSet::BUILTIN_PARTICIPANT_STATELESS_MESSAGE_READER,
+        ),
+        (
+          EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER,
+          EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_READER,
+          BuiltinEndpointSet::BUILTIN_PARTICIPANT_VOLATILE_MESSAGE_SECURE_READER,
+        ),
       ] {
+        if !allowed(writer_eid) {
+          continue;
+        }
         if let Some(writer) = self.writers.get_mut(writer_eid) {
           debug!("update_discovery_writer - {:?}", writer.topic_name());
           let mut qos = Discovery::subscriber_qos();
@@ -565,22 +815,35 @@ impl DPEventLoop {
           {
             qos.reliability = Some(policy::Reliability::BestEffort);
           };
-
-          if discovered_participant
-            .available_builtin_endpoints
-            .contains(*endpoint)
+          if *writer_eid == EntityId::TL_SVC_REQ_WRITER
+            || *writer_eid == EntityId::TL_SVC_REPLY_WRITER
           {
+            qos = qos.modify_by(&Discovery::TYPE_LOOKUP_QOS);
+          }
+          if *writer_eid == EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_WRITER {
+            qos = qos.modify_by(&Discovery::STATELESS_MESSAGE_QOS);
+          }
+          if *writer_eid == EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER {
+            qos = qos.modify_by(&Discovery::VOLATILE_MESSAGE_QOS);
+          }
+
+          if available_builtin_endpoints.contains(*endpoint) {
             let mut reader_proxy = discovered_participant.as_reader_proxy(true, Some(*reader_eid));
 
             if *writer_eid == EntityId::SPDP_BUILTIN_PARTICIPANT_WRITER {
               // Simple Particiapnt Discovery Protocol (SPDP) writer is special,
               // different from SEDP writers
               qos = Discovery::create_spdp_patricipant_qos(); // different QoS
-                                                              // adding a multicast reader
-              reader_proxy.remote_reader_guid = GUID::new_with_prefix_and_id(
-                GuidPrefix::UNKNOWN,
-                EntityId::SPDP_BUILTIN_PARTICIPANT_READER,
-              );
+              if participant_guid_prefix == self.domain_info.domain_participant_guid.prefix {
+                // Our own locators stand for the multicast reader proxy.
+                reader_proxy.remote_reader_guid = GUID::new_with_prefix_and_id(
+                  GuidPrefix::UNKNOWN,
+                  EntityId::SPDP_BUILTIN_PARTICIPANT_READER,
+                );
+              }
+              // Remote participants keep their own reader proxy, so that
+              // unicast-only participants also receive our announcements. When
+              // multicast is used, sending deduplicates the identical locators.
 
               // reader_proxy.multicast_locator_list =
               // get_local_multicast_locators(
@@ -627,20 +890,51 @@ impl DPEventLoop {
           EntityId::P2P_BUILTIN_PARTICIPANT_MESSAGE_READER,
           BuiltinEndpointSet::BUILTIN_ENDPOINT_PARTICIPANT_MESSAGE_DATA_WRITER,
         ),
+        (
+          EntityId::TL_SVC_REQ_WRITER, // TypeLookup
+          EntityId::TL_SVC_REQ_READER,
+          BuiltinEndpointSet::BUILTIN_ENDPOINT_TL_SVC_REQUEST_DATA_WRITER,
+        ),
+        (
+          EntityId::TL_SVC_REPLY_WRITER,
+          EntityId::TL_SVC_REPLY_READER,
+          BuiltinEndpointSet::BUILTIN_ENDPOINT_TL_SVC_REPLY_DATA_WRITER,
+        ),
+        (
+          EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_WRITER, // DDS Security
+          EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_READER,
+          BuiltinEndpointSet::BUILTIN_PARTICIPANT_STATELESS_MESSAGE_WRITER,
+        ),
+        (
+          EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER,
+          EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_READER,
+          BuiltinEndpointSet::BUILTIN_PARTICIPANT_VOLATILE_MESSAGE_SECURE_WRITER,
+        ),
       ] {
+        if !allowed(writer_eid) {
+          continue;
+        }
         if let Some(reader) = self.message_receiver.available_readers.get_mut(reader_eid) {
           debug!("try update_discovery_reader - {:?}", reader.topic_name());
           let qos = if *reader_eid == EntityId::SPDP_BUILTIN_PARTICIPANT_READER {
-            Discovery::create_spdp_patricipant_qos()
+            // SPDP writer is BestEffort, but otherwise offers the same as
+            // other builtin writers.
+            Discovery::publisher_qos().modify_by(&Discovery::create_spdp_patricipant_qos())
+          } else if *reader_eid == EntityId::TL_SVC_REQ_READER
+            || *reader_eid == EntityId::TL_SVC_REPLY_READER
+          {
+            // TypeLookup writers are volatile
+            Discovery::publisher_qos().modify_by(&Discovery::TYPE_LOOKUP_QOS)
+          } else if *reader_eid == EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_READER {
+            Discovery::publisher_qos().modify_by(&Discovery::STATELESS_MESSAGE_QOS)
+          } else if *reader_eid == EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_READER {
+            Discovery::publisher_qos().modify_by(&Discovery::VOLATILE_MESSAGE_QOS)
           } else {
             Discovery::publisher_qos()
           };
           let wp = discovered_participant.as_writer_proxy(true, Some(*writer_eid));
 
-          if discovered_participant
-            .available_builtin_endpoints
-            .contains(*endpoint)
-          {
+          if available_builtin_endpoints.contains(*endpoint) {
             reader.update_writer_proxy(wp, &qos);
             debug!(
               "update_discovery_reader - endpoint {:?} - {:?}",
@@ -672,9 +966,201 @@ impl DPEventLoop {
     for reader in self.message_receiver.available_readers.values_mut() {
       reader.participant_lost(participant_guid_prefix);
     }
+    self
+      .message_receiver
+      .remove_participant_addresses(participant_guid_prefix);
+  }
+
+  // TRANSPORT_PRIORITY is a socket option, so Writers with a priority need
+  // their own sender sockets. These are shared between Writers with equal
+  // priority.
+  fn transports_for(&mut self, qos: &QosPolicies) -> Rc<Transports> {
+    let priority = match qos.transport_priority() {
+      Some(policy::TransportPriority { value }) if value != 0 => value,
+      _ => return self.transports.clone(),
+    };
+    if let Some(transports) = self.prioritized_transports.get(&priority) {
+      return transports.clone();
+    }
+    let transports = Transports::new(
+      &self.transport_kinds,
+      &self.interfaces,
+      &self.socket_options,
+    )
+    .and_then(|transports| {
+      transports.set_transport_priority(priority)?;
+      Ok(
+        transports
+          .with_traffic_counters(self.statistics.traffic())
+          .with_packet_capture(&self.packet_capture),
+      )
+    });
+    match transports {
+      Ok(transports) => {
+        let transports = Rc::new(transports);
+        self
+          .prioritized_transports
+          .insert(priority, transports.clone());
+        transports
+      }
+      Err(e) => {
+        warn!(
+          "Cannot apply transport priority {}: {:?}. Using default sender.",
+          priority, e
+        );
+        self.transports.clone()
+      }
+    }
+  }
+
+  // Interfaces may come and go at runtime, e.g. on laptops or when VPNs
+  // connect. Listeners join multicast groups on new interfaces, senders are
+  // recreated to send multicast to the current interfaces, and Discovery
+  // announces the new self locators.
+  fn check_network_interfaces(&mut self) {
+    let interface_addrs = get_local_ip_addrs(&self.interfaces);
+    if interface_addrs == self.interface_addrs {
+      return;
+    }
+    info!(
+      "Network interfaces changed: {:?} -> {:?}",
+      self.interface_addrs, interface_addrs
+    );
+    self.interface_addrs = interface_addrs;
+
+    for listener in self.listeners.values_mut() {
+      listener.interfaces_changed();
+    }
+    // The receive loops update their locators later.
+    self.receive_loops.send(ReceiveCommand::InterfacesChanged);
+
+    match Transports::new(
+      &self.transport_kinds,
+      &self.interfaces,
+      &self.socket_options,
+    ) {
+      Ok(transports) => {
+        self.transports = Rc::new(
+          transports
+            .with_traffic_counters(self.statistics.traffic())
+            .with_packet_capture(&self.packet_capture),
+        );
+        self.prioritized_transports.clear();
+        let writer_qos: Vec<(EntityId, QosPolicies)> = self
+          .writers
+          .iter()
+          .map(|(eid, writer)| (*eid, writer.qos()))
+          .collect();
+        for (eid, qos) in writer_qos {
+          let transports = self.transports_for(&qos);
+          if let Some(writer) = self.writers.get_mut(&eid) {
+            writer.set_qos(qos, transports);
+          }
+        }
+        for reader in self.message_receiver.available_readers.values_mut() {
+          reader.set_transports(self.transports.clone());
+        }
+      }
+      Err(e) => error!("Cannot recreate senders after network change: {:?}", e),
+    }
+
+    self
+      .listener_locators
+      .extend(transport::listener_locators(&self.listeners));
+    *self.self_locators.write().unwrap() = transport::self_locators(&self.listener_locators);
+  }
+
+  fn clock_jump(&mut self) {
+    // Time measured before the jump is unreliable, so restart deadline
+    // monitoring from now, and resynchronize lifespan timers.
+    for reader in self.message_receiver.available_readers.values_mut() {
+      reader.clock_jump();
+    }
+    for writer in self.writers.values_mut() {
+      writer.clock_jump();
+    }
+  }
+
+  // QoS of a local Writer was changed. Matching must be re-evaluated against
+  // all known remote Readers, as they may have become (in)compatible.
+  fn local_writer_updated(&mut self, writer_guid: GUID, qos: QosPolicies) {
+    let transports = self.transports_for(&qos);
+    let writer = match self.writers.get_mut(&writer_guid.entity_id) {
+      Some(writer) => writer,
+      None => {
+        warn!("QoS update for unknown writer {:?}", writer_guid);
+        return;
+      }
+    };
+    writer.set_qos(qos, transports);
+
+    let readers = self
+      .discovery_db
+      .read()
+      .unwrap()
+      .external_readers_on_topic(writer.topic_name());
+    for drd in readers {
+      writer.update_reader_proxy(
+        &RtpsReaderProxy::from_discovered_reader_data(&drd, &[], &[]),
+        &drd.subscription_topic_data.qos(),
+      );
+    }
+  }
+
+  // Same as above, but for a local Reader
+  fn local_reader_updated(&mut self, reader_guid: GUID, qos: QosPolicies) {
+    let reader = match self
+      .message_receiver
+      .available_readers
+      .get_mut(&reader_guid.entity_id)
+    {
+      Some(reader) => reader,
+      None => {
+        warn!("QoS update for unknown reader {:?}", reader_guid);
+        return;
+      }
+    };
+    reader.set_qos(qos);
+
+    let writers = self
+      .discovery_db
+      .read()
+      .unwrap()
+      .external_writers_on_topic(reader.topic_name());
+    for dwd in writers {
+      reader.update_writer_proxy(
+        RtpsWriterProxy::from_discovered_writer_data(&dwd, &[], &[]),
+        &dwd.publication_topic_data.qos(),
+      );
+    }
   }
 
   fn remote_reader_discovered(&mut self, drd: &DiscoveredReaderData) {
+    if drd
+      .reader_proxy
+      .remote_reader_guid
+      .entity_id
+      .entity_kind
+      .is_built_in()
+    {
+      // Builtin endpoints are matched in update_participant. The exception is
+      // extra SPDP reader proxies, which Discovery uses to announce us to
+      // configured locators, e.g. initial peers.
+      if drd.reader_proxy.remote_reader_guid.entity_id == EntityId::SPDP_BUILTIN_PARTICIPANT_READER
+      {
+        if let Some(writer) = self
+          .writers
+          .get_mut(&EntityId::SPDP_BUILTIN_PARTICIPANT_WRITER)
+        {
+          writer.update_reader_proxy(
+            &RtpsReaderProxy::from_discovered_reader_data(drd, &[], &[]),
+            &drd.subscription_topic_data.qos(),
+          );
+          writer.notify_new_data_to_all_readers();
+        }
+      }
+      return;
+    }
     for writer in self.writers.values_mut() {
       if drd.subscription_topic_data.topic_name() == writer.topic_name() {
         // // see if the participant has published a QoS for the topic
@@ -702,6 +1188,16 @@ impl DPEventLoop {
   }
 
   fn remote_writer_discovered(&mut self, dwd: &DiscoveredWriterData) {
+    if dwd
+      .writer_proxy
+      .remote_writer_guid
+      .entity_id
+      .entity_kind
+      .is_built_in()
+    {
+      // Builtin endpoints are matched in update_participant.
+      return;
+    }
     // update writer proxies in local readers
     for reader in self.message_receiver.available_readers.values_mut() {
       if &dwd.publication_topic_data.topic_name == reader.topic_name() {
@@ -740,6 +1236,16 @@ impl DPEventLoop {
   }
 }
 
+impl BackgroundLoop for DPEventLoop {
+  fn run(self: Box<Self>) {
+    self.event_loop();
+  }
+
+  fn run_round(&mut self, events: &mut Events, timeout: Option<Duration>) -> bool {
+    DPEventLoop::run_round(self, events, timeout)
+  }
+}
+
 // -----------------------------------------------------------
 // -----------------------------------------------------------
 // -----------------------------------------------------------
@@ -752,7 +1258,10 @@ mod tests {
 
   use super::*;
   use crate::{
-    dds::{qos::QosPolicies, statusevents::DataReaderStatus, with_key::datareader::ReaderCommand},
+    dds::{
+      helpers::WakerSlot, qos::QosPolicies, statusevents::DataReaderStatus,
+      with_key::datareader::ReaderCommand,
+    },
     structure::dds_cache::DDSCache,
   };
 
@@ -772,12 +1281,14 @@ mod tests {
     let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
 
     let ddshc = Arc::new(RwLock::new(DDSCache::new()));
+    let statistics = StatisticsMonitor::new(ddshc.clone());
     let (discovery_db_event_sender, _discovery_db_event_receiver) =
       mio_channel::sync_channel::<()>(4);
 
     let discovery_db = Arc::new(RwLock::new(DiscoveryDB::new(
       GUID::new_participant_guid(),
       discovery_db_event_sender,
+      TimeSource::System,
     )));
 
     let domain_info = DomainInfo {
@@ -792,6 +1303,12 @@ mod tests {
       let dp_event_loop = DPEventLoop::new(
         domain_info,
         HashMap::new(),
+        ReceiveLoops::none(),
+        TransportKind::DEFAULT.to_vec(),
+        InterfaceFilter::default(),
+        SocketOptions::default(),
+        HashMap::new(),
+        Arc::new(RwLock::new(HashMap::new())),
         ddshc,
         discovery_db,
         GuidPrefix::default(),
@@ -814,6 +1331,12 @@ mod tests {
         stop_poll_receiver,
         discovery_update_notification_receiver,
         spdp_liveness_sender,
+        HealthMonitor::new(),
+        statistics,
+        PacketCapture::new(),
+        ProtocolCompatibility::default(),
+        TimeSource::System,
+        SecurityConfig::default(),
       );
       dp_event_loop
         .poll
@@ -842,6 +1365,7 @@ mod tests {
       let new_reader_ing = ReaderIngredients {
         guid: new_guid,
         notification_sender: send,
+        data_reader_waker: WakerSlot::default(),
         status_sender,
         topic_name: "test".to_string(),
         qos_policy: QosPolicies::qos_none(),
@@ -0,0 +1,900 @@
+//! Data types defined at run time.
+//!
+//! A [`DynamicType`] describes a data type built at run time from structs,
+//! sequences, arrays, enums and unions. [`DynamicData`] is a sample of such a
+//! type, whose members are accessed by name.
+//!
+//! DynamicData is serialized as CDR exactly like the corresponding compiled
+//! type would be, so it interoperates with statically typed readers and
+//! writers. Writers use the normal
+//! [`CDRSerializerAdapter`](crate::CDRSerializerAdapter), readers use
+//! [`DynamicDataDeserializerAdapter`].
+//!
+//! # Example
+//! ```
+//! use std::sync::Arc;
+//! use rustdds::*;
+//!
+//! let shape_type = Arc::new(DynamicType::structure(
+//!   "ShapeType",
+//!   vec![
+//!     DynamicMember::new("color", DynamicType::String),
+//!     DynamicMember::new("x", DynamicType::I32),
+//!     DynamicMember::new("y", DynamicType::I32),
+//!     DynamicMember::new("shapesize", DynamicType::I32),
+//!   ],
+//! ));
+//! let mut shape = DynamicData::new(shape_type);
+//! shape.set("color", "BLUE").unwrap();
+//! shape.set("x", 10).unwrap();
+//! assert_eq!(shape.get_as::<i32>("x").unwrap(), 10);
+//! assert_eq!(shape.get_as::<i32>("y").unwrap(), 0);
+//! assert!(shape.set("x", "not a number").is_err());
+//! ```
+use std::{convert::TryFrom, fmt, marker::PhantomData, sync::Arc};
+
+use byteorder::{BigEndian, LittleEndian};
+use serde::{
+  de::{self, DeserializeSeed, Deserializer, SeqAccess, Visitor},
+  ser::{SerializeSeq, SerializeTuple, Serializer},
+  Deserialize, Serialize,
+};
+
+use crate::{
+  dds::{
+    traits::serde_adapters::no_key,
+    values::result::{Error, Result},
+  },
+  messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
+  serialization::{
+    cdr_deserializer::CdrDeserializer,
+    error::{Error as SerError, Result as SerResult},
+  },
+};
+
+/// Named member of a struct [`DynamicType`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynamicMember {
+  pub name: String,
+  pub member_type: DynamicType,
+}
+
+impl DynamicMember {
+  pub fn new(name: impl Into<String>, member_type: DynamicType) -> Self {
+    Self {
+      name: name.into(),
+      member_type,
+    }
+  }
+}
+
+/// Case of a union [`DynamicType`]. The case is selected when the union
+/// discriminator equals `label`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynamicUnionCase {
+  pub label: i32,
+  pub name: String,
+  pub member_type: DynamicType,
+}
+
+impl DynamicUnionCase {
+  pub fn new(label: i32, name: impl Into<String>, member_type: DynamicType) -> Self {
+    Self {
+      label,
+      name: name.into(),
+      member_type,
+    }
+  }
+}
+
+/// Description of a data type constructed at run time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DynamicType {
+  Bool,
+  I8,
+  U8,
+  I16,
+  U16,
+  I32,
+  U32,
+  I64,
+  U64,
+  F32,
+  F64,
+  /// Encoded as 4 octets, like Rust `char`.
+  Char,
+  String,
+  Struct {
+    name: String,
+    members: Vec<DynamicMember>,
+  },
+  /// Fixed-length array
+  Array(Box<DynamicType>, usize),
+  /// Variable-length sequence
+  Sequence(Box<DynamicType>),
+  /// Enumeration, encoded as u32
+  Enum {
+    name: String,
+    enumerators: Vec<String>,
+  },
+  /// Union with an i32 discriminator
+  Union {
+    name: String,
+    cases: Vec<DynamicUnionCase>,
+  },
+}
+
+impl DynamicType {
+  pub fn structure(name: impl Into<String>, members: Vec<DynamicMember>) -> Self {
+    Self::Struct {
+      name: name.into(),
+      members,
+    }
+  }
+
+  pub fn enumeration<S: Into<String>>(
+    name: impl Into<String>,
+    enumerators: impl IntoIterator<Item = S>,
+  ) -> Self {
+    Self::Enum {
+      name: name.into(),
+      enumerators: enumerators.into_iter().map(Into::into).collect(),
+    }
+  }
+
+  pub fn union(name: impl Into<String>, cases: Vec<DynamicUnionCase>) -> Self {
+    Self::Union {
+      name: name.into(),
+      cases,
+    }
+  }
+
+  pub fn sequence(element: DynamicType) -> Self {
+    Self::Sequence(Box::new(element))
+  }
+
+  pub fn array(element: DynamicType, len: usize) -> Self {
+    Self::Array(Box::new(element), len)
+  }
+
+  /// Type name, to be used when creating a Topic for this type. Primitive
+  /// types are named like in IDL.
+  pub fn type_name(&self) -> String {
+    match self {
+      Self::Bool => "boolean".to_string(),
+      Self::I8 => "int8".to_string(),
+      Self::U8 => "uint8".to_string(),
+      Self::I16 => "short".to_string(),
+      Self::U16 => "unsigned short".to_string(),
+      Self::I32 => "long".to_string(),
+      Self::U32 => "unsigned long".to_string(),
+      Self::I64 => "long long".to_string(),
+      Self::U64 => "unsigned long long".to_string(),
+      Self::F32 => "float".to_string(),
+      Self::F64 => "double".to_string(),
+      Self::Char => "wchar".to_string(),
+      Self::String => "string".to_string(),
+      Self::Struct { name, .. } | Self::Enum { name, .. } | Self::Union { name, .. } => {
+        name.clone()
+      }
+      Self::Array(elem, len) => format!("{}[{}]", elem.type_name(), len),
+      Self::Sequence(elem) => format!("sequence<{}>", elem.type_name()),
+    }
+  }
+
+  /// The value a new [`DynamicData`] of this type starts with: zero, empty,
+  /// the first enumerator or the first union case.
+  pub fn default_value(&self) -> DynamicValue {
+    match self {
+      Self::Bool => DynamicValue::Bool(false),
+      Self::I8 => DynamicValue::I8(0),
+      Self::U8 => DynamicValue::U8(0),
+      Self::I16 => DynamicValue::I16(0),
+      Self::U16 => DynamicValue::U16(0),
+      Self::I32 => DynamicValue::I32(0),
+      Self::U32 => DynamicValue::U32(0),
+      Self::I64 => DynamicValue::I64(0),
+      Self::U64 => DynamicValue::U64(0),
+      Self::F32 => DynamicValue::F32(0.0),
+      Self::F64 => DynamicValue::F64(0.0),
+      Self::Char => DynamicValue::Char('\0'),
+      Self::String => DynamicValue::String(String::new()),
+      Self::Struct { members, .. } => DynamicValue::Struct(
+        members
+          .iter()
+          .map(|m| m.member_type.default_value())
+          .collect(),
+      ),
+      Self::Array(elem, len) => {
+        DynamicValue::Sequence((0..*len).map(|_| elem.default_value()).collect())
+      }
+      Self::Sequence(_) => DynamicValue::Sequence(Vec::new()),
+      Self::Enum { .. } => DynamicValue::Enum(0),
+      Self::Union { cases, .. } => match cases.first() {
+        Some(case) => DynamicValue::Union {
+          discriminator: case.label,
+          value: Some(Box::new(case.member_type.default_value())),
+        },
+        None => DynamicValue::Union {
+          discriminator: 0,
+          value: None,
+        },
+      },
+    }
+  }
+
+  /// Does the value conform to this type?
+  pub fn accepts(&self, value: &DynamicValue) -> bool {
+    match (self, value) {
+      (Self::Bool, DynamicValue::Bool(_))
+      | (Self::I8, DynamicValue::I8(_))
+      | (Self::U8, DynamicValue::U8(_))
+      | (Self::I16, DynamicValue::I16(_))
+      | (Self::U16, DynamicValue::U16(_))
+      | (Self::I32, DynamicValue::I32(_))
+      | (Self::U32, DynamicValue::U32(_))
+      | (Self::I64, DynamicValue::I64(_))
+      | (Self::U64, DynamicValue::U64(_))
+      | (Self::F32, DynamicValue::F32(_))
+      | (Self::F64, DynamicValue::F64(_))
+      | (Self::Char, DynamicValue::Char(_))
+      | (Self::String, DynamicValue::String(_)) => true,
+      (Self::Struct { members, .. }, DynamicValue::Struct(values)) => {
+        members.len() == values.len()
+          && members
+            .iter()
+            .zip(values)
+            .all(|(m, v)| m.member_type.accepts(v))
+      }
+      (Self::Array(elem, len), DynamicValue::Sequence(values)) => {
+        values.len() == *len && values.iter().all(|v| elem.accepts(v))
+      }
+      (Self::Sequence(elem), DynamicValue::Sequence(values)) => {
+        values.iter().all(|v| elem.accepts(v))
+      }
+      (Self::Enum { enumerators, .. }, DynamicValue::Enum(e)) => (*e as usize) < enumerators.len(),
+      (
+        Self::Union { cases, .. },
+        DynamicValue::Union {
+          discriminator,
+          value,
+        },
+      ) => match (cases.iter().find(|c| c.label == *discriminator), value) {
+        (Some(case), Some(value)) => case.member_type.accepts(value),
+        (None, None) => true,
+        _ => false,
+      },
+      _ => false,
+    }
+  }
+}
+
+/// Value of a [`DynamicType`].
+///
+/// Both arrays and sequences are represented as `Sequence`. Enums are
+/// represented by the index of the enumerator.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicValue {
+  Bool(bool),
+  I8(i8),
+  U8(u8),
+  I16(i16),
+  U16(u16),
+  I32(i32),
+  U32(u32),
+  I64(i64),
+  U64(u64),
+  F32(f32),
+  F64(f64),
+  Char(char),
+  String(String),
+  Struct(Vec<DynamicValue>),
+  Sequence(Vec<DynamicValue>),
+  Enum(u32),
+  Union {
+    discriminator: i32,
+    /// `None` if no case matches the discriminator.
+    value: Option<Box<DynamicValue>>,
+  },
+}
+
+macro_rules! dynamic_value_conversions {
+  ($($variant:ident $t:ty),*) => {
+    $(
+      impl From<$t> for DynamicValue {
+        fn from(v: $t) -> Self {
+          Self::$variant(v)
+        }
+      }
+
+      impl TryFrom<&DynamicValue> for $t {
+        type Error = Error;
+
+        fn try_from(value: &DynamicValue) -> Result<Self> {
+          match value {
+            DynamicValue::$variant(v) => Ok(v.to_owned()),
+            other => Error::bad_parameter(format!(
+              "Expected {}, found {:?}",
+              stringify!($t),
+              other
+            )),
+          }
+        }
+      }
+    )*
+  };
+}
+
+dynamic_value_conversions!(Bool bool, I8 i8, U8 u8, I16 i16, U16 u16, I32 i32, U32 u32,
+  I64 i64, U64 u64, F32 f32, F64 f64, Char char, String String);
+
+impl From<&str> for DynamicValue {
+  fn from(s: &str) -> Self {
+    Self::String(s.to_string())
+  }
+}
+
+/// A sample of a [`DynamicType`].
+///
+/// Members are addressed by name. Members of nested structs and unions are
+/// addressed with dotted paths, e.g. `"position.x"`. Setting a union member
+/// also sets the discriminator to select it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicData {
+  dynamic_type: Arc<DynamicType>,
+  value: DynamicValue,
+}
+
+impl DynamicData {
+  /// Creates a sample with default values.
+  pub fn new(dynamic_type: Arc<DynamicType>) -> Self {
+    let value = dynamic_type.default_value();
+    Self {
+      dynamic_type,
+      value,
+    }
+  }
+
+  /// Creates a sample from a value. Fails if the value does not conform to
+  /// the type.
+  pub fn from_value(dynamic_type: Arc<DynamicType>, value: DynamicValue) -> Result<Self> {
+    if dynamic_type.accepts(&value) {
+      Ok(Self {
+        dynamic_type,
+        value,
+      })
+    } else {
+      Error::bad_parameter(format!(
+        "Value does not conform to type {}",
+        dynamic_type.type_name()
+      ))
+    }
+  }
+
+  pub fn dynamic_type(&self) -> &Arc<DynamicType> {
+    &self.dynamic_type
+  }
+
+  pub fn value(&self) -> &DynamicValue {
+    &self.value
+  }
+
+  pub fn into_value(self) -> DynamicValue {
+    self.value
+  }
+
+  /// Gets a member by name or dotted path.
+  pub fn get(&self, path: &str) -> Result<&DynamicValue> {
+    let mut current_type = &*self.dynamic_type;
+    let mut current_value = &self.value;
+    for name in path.split('.') {
+      match (current_type, current_value) {
+        (DynamicType::Struct { members, .. }, DynamicValue::Struct(values)) => {
+          let index = member_index(members, name)?;
+          current_type = &members[index].member_type;
+          current_value = &values[index];
+        }
+        (
+          DynamicType::Union { cases, .. },
+          DynamicValue::Union {
+            discriminator,
+            value,
+          },
+        ) => {
+          let case = union_case(cases, name)?;
+          match value {
+            Some(value) if case.label == *discriminator => {
+              current_type = &case.member_type;
+              current_value = &**value;
+            }
+            _ => {
+              return Error::precondition_not_met(format!("Union member {} is not selected", name))
+            }
+          }
+        }
+        _ => return no_members(name),
+      }
+    }
+    Ok(current_value)
+  }
+
+  /// Gets a member by name or dotted path, converted to a Rust type.
+  pub fn get_as<T>(&self, path: &str) -> Result<T>
+  where
+    T: for<'a> TryFrom<&'a DynamicValue, Error = Error>,
+  {
+    T::try_from(self.get(path)?)
+  }
+
+  /// Sets a member by name or dotted path. Fails if the value does not
+  /// conform to the type of the member.
+  pub fn set(&mut self, path: &str, value: impl Into<DynamicValue>) -> Result<()> {
+    let value = value.into();
+    let mut current_type = &*self.dynamic_type;
+    let mut current_value = &mut self.value;
+    for name in path.split('.') {
+      match (current_type, current_value) {
+        (DynamicType::Struct { members, .. }, DynamicValue::Struct(values)) => {
+          let index = member_index(members, name)?;
+          current_type = &members[index].member_type;
+          current_value = &mut values[index];
+        }
+        (
+          DynamicType::Union { cases, .. },
+          DynamicValue::Union {
+            discriminator,
+            value: case_value,
+          },
+        ) => {
+          let case = union_case(cases, name)?;
+          if case.label != *discriminator || case_value.is_none() {
+            *discriminator = case.label;
+            *case_value = Some(Box::new(case.member_type.default_value()));
+          }
+          current_type = &case.member_type;
+          current_value = case_value.as_deref_mut().unwrap();
+        }
+        _ => return no_members(name),
+      }
+    }
+    if current_type.accepts(&value) {
+      *current_value = value;
+      Ok(())
+    } else {
+      Error::bad_parameter(format!(
+        "Member {} is of type {}, cannot set to {:?}",
+        path,
+        current_type.type_name(),
+        value
+      ))
+    }
+  }
+}
+
+fn member_index(members: &[DynamicMember], name: &str) -> Result<usize> {
+  members
+    .iter()
+    .position(|m| m.name == name)
+    .map_or_else(|| Error::bad_parameter(format!("No member {}", name)), Ok)
+}
+
+fn union_case<'a>(cases: &'a [DynamicUnionCase], name: &str) -> Result<&'a DynamicUnionCase> {
+  cases.iter().find(|c| c.name == name).map_or_else(
+    || Error::bad_parameter(format!("No union member {}", name)),
+    Ok,
+  )
+}
+
+fn no_members<T>(name: &str) -> Result<T> {
+  Error::bad_parameter(format!(
+    "Cannot access member {} of a type that has no members",
+    name
+  ))
+}
+
+// ----------------------------------------------------------
+// Serialization
+
+// Value together with its type. Arrays and sequences have the same value
+// representation, so the type is needed to serialize them correctly.
+struct TypedValue<'a>(&'a DynamicType, &'a DynamicValue);
+
+impl Serialize for TypedValue<'_> {
+  fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    match (self.0, self.1) {
+      (_, DynamicValue::Bool(v)) => serializer.serialize_bool(*v),
+      (_, DynamicValue::I8(v)) => serializer.serialize_i8(*v),
+      (_, DynamicValue::U8(v)) => serializer.serialize_u8(*v),
+      (_, DynamicValue::I16(v)) => serializer.serialize_i16(*v),
+      (_, DynamicValue::U16(v)) => serializer.serialize_u16(*v),
+      (_, DynamicValue::I32(v)) => serializer.serialize_i32(*v),
+      (_, DynamicValue::U32(v)) => serializer.serialize_u32(*v),
+      (_, DynamicValue::I64(v)) => serializer.serialize_i64(*v),
+      (_, DynamicValue::U64(v)) => serializer.serialize_u64(*v),
+      (_, DynamicValue::F32(v)) => serializer.serialize_f32(*v),
+      (_, DynamicValue::F64(v)) => serializer.serialize_f64(*v),
+      (_, DynamicValue::Char(v)) => serializer.serialize_char(*v),
+      (_, DynamicValue::String(v)) => serializer.serialize_str(v),
+      (_, DynamicValue::Enum(v)) => serializer.serialize_u32(*v),
+      (DynamicType::Struct { members, .. }, DynamicValue::Struct(values)) => {
+        let mut tuple = serializer.serialize_tuple(values.len())?;
+        for (member, value) in members.iter().zip(values) {
+          tuple.serialize_element(&TypedValue(&member.member_type, value))?;
+        }
+        tuple.end()
+      }
+      (DynamicType::Array(elem, _), DynamicValue::Sequence(values)) => {
+        let mut tuple = serializer.serialize_tuple(values.len())?;
+        for value in values {
+          tuple.serialize_element(&TypedValue(elem, value))?;
+        }
+        tuple.end()
+      }
+      (DynamicType::Sequence(elem), DynamicValue::Sequence(values)) => {
+        let mut seq = serializer.serialize_seq(Some(values.len()))?;
+        for value in values {
+          seq.serialize_element(&TypedValue(elem, value))?;
+        }
+        seq.end()
+      }
+      (
+        DynamicType::Union { cases, .. },
+        DynamicValue::Union {
+          discriminator,
+          value,
+        },
+      ) => {
+        let case = cases.iter().find(|c| c.label == *discriminator);
+        match (case, value) {
+          (Some(case), Some(value)) => {
+            let mut tuple = serializer.serialize_tuple(2)?;
+            tuple.serialize_element(discriminator)?;
+            tuple.serialize_element(&TypedValue(&case.member_type, value))?;
+            tuple.end()
+          }
+          _ => serializer.serialize_i32(*discriminator),
+        }
+      }
+      (t, v) => Err(serde::ser::Error::custom(format!(
+        "Value {:?} does not conform to type {}",
+        v,
+        t.type_name()
+      ))),
+    }
+  }
+}
+
+impl Serialize for DynamicData {
+  fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    TypedValue(&self.dynamic_type, &self.value).serialize(serializer)
+  }
+}
+
+// DataReaders require their data type to be Deserialize, but DynamicData
+// cannot be deserialized without knowing its type. The type is supplied by
+// DynamicDataDeserializerAdapter, which does not use this.
+impl<'de> Deserialize<'de> for DynamicData {
+  fn deserialize<D>(_deserializer: D) -> std::result::Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Err(de::Error::custom(
+      "DynamicData can only be deserialized with DynamicDataDeserializerAdapter",
+    ))
+  }
+}
+
+impl<'de> DeserializeSeed<'de> for &DynamicType {
+  type Value = DynamicValue;
+
+  fn deserialize<D>(self, deserializer: D) -> std::result::Result<DynamicValue, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    match self {
+      DynamicType::Bool => bool::deserialize(deserializer).map(DynamicValue::Bool),
+      DynamicType::I8 => i8::deserialize(deserializer).map(DynamicValue::I8),
+      DynamicType::U8 => u8::deserialize(deserializer).map(DynamicValue::U8),
+      DynamicType::I16 => i16::deserialize(deserializer).map(DynamicValue::I16),
+      DynamicType::U16 => u16::deserialize(deserializer).map(DynamicValue::U16),
+      DynamicType::I32 => i32::deserialize(deserializer).map(DynamicValue::I32),
+      DynamicType::U32 => u32::deserialize(deserializer).map(DynamicValue::U32),
+      DynamicType::I64 => i64::deserialize(deserializer).map(DynamicValue::I64),
+      DynamicType::U64 => u64::deserialize(deserializer).map(DynamicValue::U64),
+      DynamicType::F32 => f32::deserialize(deserializer).map(DynamicValue::F32),
+      DynamicType::F64 => f64::deserialize(deserializer).map(DynamicValue::F64),
+      DynamicType::Char => char::deserialize(deserializer).map(DynamicValue::Char),
+      DynamicType::String => String::deserialize(deserializer).map(DynamicValue::String),
+      DynamicType::Struct { members, .. } => {
+        deserializer.deserialize_tuple(members.len(), StructVisitor { members })
+      }
+      DynamicType::Array(elem, len) => {
+        deserializer.deserialize_tuple(*len, SequenceVisitor { elem })
+      }
+      DynamicType::Sequence(elem) => deserializer.deserialize_seq(SequenceVisitor { elem }),
+      DynamicType::Enum { enumerators, .. } => {
+        let e = u32::deserialize(deserializer)?;
+        if (e as usize) < enumerators.len() {
+          Ok(DynamicValue::Enum(e))
+        } else {
+          Err(de::Error::custom(format!("Enumerator {} out of range", e)))
+        }
+      }
+      DynamicType::Union { cases, .. } => deserializer.deserialize_tuple(2, UnionVisitor { cases }),
+    }
+  }
+}
+
+struct StructVisitor<'a> {
+  members: &'a [DynamicMember],
+}
+
+impl<'de> Visitor<'de> for StructVisitor<'_> {
+  type Value = DynamicValue;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    write!(formatter, "struct with {} members", self.members.len())
+  }
+
+  fn visit_seq<A>(self, mut seq: A) -> std::result::Result<DynamicValue, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let mut values = Vec::with_capacity(self.members.len());
+    for member in self.members {
+      let value = seq
+        .next_element_seed(&member.member_type)?
+        .ok_or_else(|| de::Error::custom(format!("Missing struct member {}", member.name)))?;
+      values.push(value);
+    }
+    Ok(DynamicValue::Struct(values))
+  }
+}
+
+struct SequenceVisitor<'a> {
+  elem: &'a DynamicType,
+}
+
+impl<'de> Visitor<'de> for SequenceVisitor<'_> {
+  type Value = DynamicValue;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.write_str("array or sequence")
+  }
+
+  fn visit_seq<A>(self, mut seq: A) -> std::result::Result<DynamicValue, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+    while let Some(value) = seq.next_element_seed(self.elem)? {
+      values.push(value);
+    }
+    Ok(DynamicValue::Sequence(values))
+  }
+}
+
+struct UnionVisitor<'a> {
+  cases: &'a [DynamicUnionCase],
+}
+
+impl<'de> Visitor<'de> for UnionVisitor<'_> {
+  type Value = DynamicValue;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.write_str("union")
+  }
+
+  fn visit_seq<A>(self, mut seq: A) -> std::result::Result<DynamicValue, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let discriminator: i32 = seq
+      .next_element()?
+      .ok_or_else(|| de::Error::custom("Missing union discriminator"))?;
+    let value = match self.cases.iter().find(|c| c.label == discriminator) {
+      Some(case) => Some(Box::new(
+        seq
+          .next_element_seed(&case.member_type)?
+          .ok_or_else(|| de::Error::custom(format!("Missing union member {}", case.name)))?,
+      )),
+      None => None,
+    };
+    Ok(DynamicValue::Union {
+      discriminator,
+      value,
+    })
+  }
+}
+
+/// Supplies the [`DynamicType`] for a [`DynamicDataDeserializerAdapter`].
+///
+/// DeserializerAdapters have no state, so the type is given through a Rust
+/// type. The DynamicType may still be decided at run time, e.g. by returning
+/// one stored in a global variable.
+pub trait DynamicTypeSource {
+  fn dynamic_type() -> Arc<DynamicType>;
+}
+
+/// DeserializerAdapter for receiving [`DynamicData`] of the type given by
+/// `S`, encoded in CDR.
+pub struct DynamicDataDeserializerAdapter<S> {
+  phantom: PhantomData<S>,
+}
+
+const REPR_IDS: [RepresentationIdentifier; 3] = [
+  RepresentationIdentifier::CDR_BE,
+  RepresentationIdentifier::CDR_LE,
+  RepresentationIdentifier::PL_CDR_LE,
+];
+
+impl DynamicData {
+  /// Decodes a CDR sample of the given type.
+  pub fn from_cdr_bytes(
+    dynamic_type: Arc<DynamicType>,
+    input_bytes: &[u8],
+    encoding: RepresentationIdentifier,
+  ) -> SerResult<Self> {
+    let value = match encoding {
+      RepresentationIdentifier::CDR_LE | RepresentationIdentifier::PL_CDR_LE => {
+        (&*dynamic_type).deserialize(&mut CdrDeserializer::<LittleEndian>::new(input_bytes))?
+      }
+      RepresentationIdentifier::CDR_BE | RepresentationIdentifier::PL_CDR_BE => {
+        (&*dynamic_type).deserialize(&mut CdrDeserializer::<BigEndian>::new(input_bytes))?
+      }
+      repr_id => {
+        return Err(SerError::Message(format!(
+          "Unknown representaiton identifier {:?}.",
+          repr_id
+        )))
+      }
+    };
+    Ok(Self {
+      dynamic_type,
+      value,
+    })
+  }
+}
+
+impl<S> no_key::DeserializerAdapter<DynamicData> for DynamicDataDeserializerAdapter<S>
+where
+  S: DynamicTypeSource,
+{
+  fn supported_encodings() -> &'static [RepresentationIdentifier] {
+    &REPR_IDS
+  }
+
+  fn from_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> SerResult<DynamicData> {
+    DynamicData::from_cdr_bytes(S::dynamic_type(), input_bytes, encoding)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::serialization::cdr_serializer::to_bytes;
+
+  #[derive(Serialize)]
+  #[allow(dead_code)] // Circle is not constructed
+  enum Shape {
+    Circle(f32),
+    Label(String),
+  }
+
+  #[derive(Serialize)]
+  struct Point {
+    x: i32,
+    y: i32,
+  }
+
+  #[derive(Serialize)]
+  struct Sample {
+    id: u16,
+    position: Point,
+    path: Vec<Point>,
+    rgb: [u8; 3],
+    name: String,
+    shape: Shape,
+  }
+
+  fn point_type() -> DynamicType {
+    DynamicType::structure(
+      "Point",
+      vec![
+        DynamicMember::new("x", DynamicType::I32),
+        DynamicMember::new("y", DynamicType::I32),
+      ],
+    )
+  }
+
+  fn sample_type() -> Arc<DynamicType> {
+    Arc::new(DynamicType::structure(
+      "Sample",
+      vec![
+        DynamicMember::new("id", DynamicType::U16),
+        DynamicMember::new("position", point_type()),
+        DynamicMember::new("path", DynamicType::sequence(point_type())),
+        DynamicMember::new("rgb", DynamicType::array(DynamicType::U8, 3)),
+        DynamicMember::new("name", DynamicType::String),
+        DynamicMember::new(
+          "shape",
+          DynamicType::union(
+            "Shape",
+            vec![
+              DynamicUnionCase::new(0, "circle", DynamicType::F32),
+              DynamicUnionCase::new(1, "label", DynamicType::String),
+            ],
+          ),
+        ),
+      ],
+    ))
+  }
+
+  #[test]
+  fn dynamic_data_matches_static_cdr() {
+    let sample = Sample {
+      id: 3,
+      position: Point { x: -1, y: 2 },
+      path: vec![Point { x: 5, y: 6 }],
+      rgb: [1, 2, 3],
+      name: "dyn".to_string(),
+      shape: Shape::Label("hello".to_string()),
+    };
+    let static_bytes = to_bytes::<Sample, LittleEndian>(&sample).unwrap();
+
+    let mut data = DynamicData::new(sample_type());
+    data.set("id", 3u16).unwrap();
+    data.set("position.x", -1).unwrap();
+    data.set("position.y", 2).unwrap();
+    data
+      .set(
+        "path",
+        DynamicValue::Sequence(vec![DynamicValue::Struct(vec![
+          DynamicValue::I32(5),
+          DynamicValue::I32(6),
+        ])]),
+      )
+      .unwrap();
+    data
+      .set(
+        "rgb",
+        DynamicValue::Sequence(vec![1u8.into(), 2u8.into(), 3u8.into()]),
+      )
+      .unwrap();
+    data.set("name", "dyn").unwrap();
+    data.set("shape.circle", 1.0f32).unwrap();
+    data.set("shape.label", "hello").unwrap();
+
+    let dynamic_bytes = to_bytes::<DynamicData, LittleEndian>(&data).unwrap();
+    assert_eq!(dynamic_bytes, static_bytes);
+
+    let decoded = DynamicData::from_cdr_bytes(
+      sample_type(),
+      &static_bytes,
+      RepresentationIdentifier::CDR_LE,
+    )
+    .unwrap();
+    assert_eq!(decoded, data);
+    assert_eq!(decoded.get_as::<String>("shape.label").unwrap(), "hello");
+    assert!(decoded.get("shape.circle").is_err());
+  }
+
+  #[test]
+  fn dynamic_data_type_checks() {
+    let mut data = DynamicData::new(sample_type());
+    assert!(data.set("id", 3i32).is_err());
+    assert!(data.set("nonexistent", 3u16).is_err());
+    assert!(data.set("id.x", 3u16).is_err());
+    assert!(data
+      .set("rgb", DynamicValue::Sequence(vec![1u8.into()]))
+      .is_err());
+    assert!(data.get_as::<i32>("name").is_err());
+    assert!(DynamicData::from_value(sample_type(), DynamicValue::I32(0)).is_err());
+  }
+}
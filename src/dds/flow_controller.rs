@@ -0,0 +1,129 @@
+use std::{
+  fmt,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+use crate::dds::values::result::{Error, Result};
+
+/// Limits the rate at which DataWriters send, e.g. over a constrained link.
+///
+/// This is a token bucket: sending takes one token per byte, and tokens are
+/// added at the configured rate, up to the burst size. Messages that would
+/// exceed the rate wait in the DataWriter until there are enough tokens, so
+/// the configured rate is never exceeded over time, but writing does not
+/// block. A message larger than the burst size is sent when the bucket is
+/// full, and the bucket then stays empty for correspondingly longer.
+///
+/// Clones share the same bucket. Attach a FlowController to a single
+/// DataWriter with
+/// [`DataWriter::set_flow_controller`](crate::with_key::DataWriter::set_flow_controller),
+/// or to all DataWriters that a Publisher creates with
+/// [`Publisher::set_flow_controller`](crate::Publisher::set_flow_controller).
+/// Then the rate is shared by the DataWriters together.
+///
+/// # Examples
+///
+/// ```
+/// # use rustdds::FlowController;
+/// // 1 Mbit/s, with bursts of up to 16 KiB
+/// let flow_controller = FlowController::new(125_000, 16 * 1024).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct FlowController {
+  bucket: Arc<Mutex<TokenBucket>>,
+}
+
+struct TokenBucket {
+  bytes_per_second: u64,
+  max_burst: u64,
+  // Negative after sending a message larger than the burst size
+  tokens: f64,
+  refilled: Instant,
+}
+
+impl TokenBucket {
+  fn refill(&mut self, now: Instant) {
+    let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+    self.tokens = (self.tokens + elapsed * self.bytes_per_second as f64).min(self.max_burst as f64);
+    self.refilled = now;
+  }
+}
+
+impl FlowController {
+  /// Rate in bytes per second, and the largest burst in bytes. The rate must
+  /// be positive. The bucket starts full.
+  pub fn new(bytes_per_second: u64, max_burst_bytes: u64) -> Result<Self> {
+    if bytes_per_second == 0 {
+      return Error::bad_parameter("Flow controller rate must be positive");
+    }
+    Ok(Self {
+      bucket: Arc::new(Mutex::new(TokenBucket {
+        bytes_per_second,
+        max_burst: max_burst_bytes,
+        tokens: max_burst_bytes as f64,
+        refilled: Instant::now(),
+      })),
+    })
+  }
+
+  pub fn bytes_per_second(&self) -> u64 {
+    self.bucket.lock().unwrap().bytes_per_second
+  }
+
+  pub fn max_burst_bytes(&self) -> u64 {
+    self.bucket.lock().unwrap().max_burst
+  }
+
+  // Take tokens for sending this many bytes now, or tell how long to wait
+  // until there are enough.
+  pub(crate) fn try_send(&self, bytes: usize, now: Instant) -> std::result::Result<(), Duration> {
+    let mut bucket = self.bucket.lock().unwrap();
+    bucket.refill(now);
+    let needed = (bytes as f64).min(bucket.max_burst as f64);
+    if bucket.tokens >= needed {
+      bucket.tokens -= bytes as f64;
+      Ok(())
+    } else {
+      Err(Duration::from_secs_f64(
+        (needed - bucket.tokens) / bucket.bytes_per_second as f64,
+      ))
+    }
+  }
+}
+
+impl fmt::Debug for FlowController {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("FlowController")
+      .field("bytes_per_second", &self.bytes_per_second())
+      .field("max_burst_bytes", &self.max_burst_bytes())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn flow_controller_limits_rate() {
+    let fc = FlowController::new(1000, 500).unwrap();
+    let start = fc.bucket.lock().unwrap().refilled;
+    let wait_ms = |result: std::result::Result<(), Duration>| result.unwrap_err().as_millis();
+
+    assert_eq!(fc.try_send(400, start), Ok(()));
+    // 100 tokens left, 300 more needed
+    assert!((299..=300).contains(&wait_ms(fc.try_send(400, start))));
+    assert_eq!(fc.try_send(400, start + Duration::from_millis(301)), Ok(()));
+
+    // Larger than burst: sent when the bucket is full, and leaves a debt.
+    let later = start + Duration::from_secs(10);
+    assert_eq!(fc.try_send(1500, later), Ok(()));
+    assert!((1000..=1001).contains(&wait_ms(fc.try_send(1, later))));
+  }
+
+  #[test]
+  fn flow_controller_needs_rate() {
+    assert!(FlowController::new(0, 1000).is_err());
+  }
+}
@@ -8,7 +8,7 @@ use bytes::BytesMut;
 use log::{debug, error, info, trace, warn};
 
 use crate::{
-  dds::ddsdata::DDSData,
+  dds::{ddsdata::DDSData, runtime_metrics},
   messages::submessages::{
     submessage_elements::serialized_payload::SerializedPayload,
     submessages::{DATAFRAG_Flags, DataFrag},
@@ -44,6 +44,7 @@ impl AssemblyBuffer {
     // we have unwrap here, but it will succeed as long as usize >= u32
 
     let mut buffer_bytes = BytesMut::with_capacity(data_size);
+    runtime_metrics::reassembly_buffer_bytes(data_size as i64);
     buffer_bytes.resize(data_size, 0); //TODO: Can we replace this with faster (and unsafer) .set_len and live with
                                        // uninitialized data?
 
@@ -138,6 +139,18 @@ impl fmt::Debug for FragmentAssembler {
   }
 }
 
+// Incomplete samples are abandoned, e.g. when the remote writer is lost.
+impl Drop for FragmentAssembler {
+  fn drop(&mut self) {
+    let abandoned: usize = self
+      .assembly_buffers
+      .values()
+      .map(|abuf| abuf.buffer_bytes.len())
+      .sum();
+    runtime_metrics::reassembly_buffer_bytes(-(abandoned as i64));
+  }
+}
+
 impl FragmentAssembler {
   pub fn new(fragment_size: u16) -> Self {
     debug!("new FragmentAssember. frag_size = {}", fragment_size);
@@ -166,6 +179,7 @@ impl FragmentAssembler {
     if abuf.is_complete() {
       debug!("new_datafrag: COMPLETED FRAGMENT");
       if let Some(abuf) = self.assembly_buffers.remove(&writer_sn) {
+        runtime_metrics::reassembly_buffer_bytes(-(abuf.buffer_bytes.len() as i64));
         // Return what we have assembled.
         let ser_data_or_key = SerializedPayload::from_bytes(&abuf.buffer_bytes.freeze())
           .map_err(|e| {
@@ -0,0 +1,238 @@
+// Participant self-health monitoring.
+//
+// The protocol threads (dp_event_loop and Discovery) report each completed
+// round of their event loops to a shared HealthMonitor. Health is computed
+// on demand from the time of the latest round and how long rounds take, so a
+// wedged thread shows up even though it cannot report anything itself.
+
+use std::{
+  sync::{Arc, Mutex},
+  time::{Duration as StdDuration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  dds::traits::key::Keyed,
+  structure::{duration::Duration, guid::GUID},
+};
+
+/// Health of one protocol thread of a [`DomainParticipant`](crate::DomainParticipant).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+// Note: bool fields are last, so that the CDR encoding has no padding in the
+// middle.
+pub struct ThreadHealth {
+  /// Time since the thread last completed an event loop round.
+  pub since_last_round: Duration,
+  /// Time spent processing events in the latest round.
+  pub last_round_time: Duration,
+  /// Longest time spent processing events in a single round so far.
+  pub max_round_time: Duration,
+  /// The thread has completed an event loop round within
+  /// [`ParticipantHealth::THREAD_STALL_LIMIT`].
+  pub alive: bool,
+}
+
+impl ThreadHealth {
+  /// Alive, and the latest round was within
+  /// [`ParticipantHealth::ROUND_TIME_LIMIT`].
+  pub fn is_healthy(&self) -> bool {
+    self.alive && self.last_round_time <= Duration::from(ParticipantHealth::ROUND_TIME_LIMIT)
+  }
+}
+
+/// Self-health report of a [`DomainParticipant`](crate::DomainParticipant).
+///
+/// Available locally from
+/// [`DomainParticipant::health`](crate::DomainParticipant::health), and
+/// optionally published on the vendor topic
+/// [`HEALTH_TOPIC_NAME`](Self::HEALTH_TOPIC_NAME), keyed by participant GUID.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParticipantHealth {
+  pub participant_guid: GUID,
+  /// Thread receiving and sending RTPS messages.
+  pub event_loop: ThreadHealth,
+  /// Thread processing SPDP and SEDP discovery.
+  pub discovery: ThreadHealth,
+  /// All protocol threads are healthy.
+  pub healthy: bool,
+}
+
+impl ParticipantHealth {
+  /// Topic where health samples are published, if enabled by
+  /// [`DomainParticipantBuilder::publish_health`](crate::DomainParticipantBuilder::publish_health).
+  pub const HEALTH_TOPIC_NAME: &'static str = "RustDDSParticipantHealth";
+  pub const HEALTH_TYPE_NAME: &'static str = "ParticipantHealth";
+
+  /// A thread that has not completed a round for this long is considered
+  /// dead. Both threads wake up at least every two seconds even when idle.
+  pub const THREAD_STALL_LIMIT: StdDuration = StdDuration::from_secs(10);
+
+  /// Processing one round of events should not take longer than this.
+  pub const ROUND_TIME_LIMIT: StdDuration = StdDuration::from_secs(1);
+}
+
+impl Keyed for ParticipantHealth {
+  type K = GUID;
+  fn key(&self) -> GUID {
+    self.participant_guid
+  }
+}
+
+/// A protocol thread of a [`DomainParticipant`](crate::DomainParticipant).
+///
+/// There are no separate send or timer threads. Sending RTPS messages and the
+/// timers of Readers and Writers run on the [`EventLoop`](Self::EventLoop)
+/// thread, and cannot be configured apart from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProtocolThread {
+  /// Receives and sends RTPS messages, and runs the timers of Readers and
+  /// Writers.
+  EventLoop,
+  /// Processes SPDP and SEDP discovery.
+  Discovery,
+  /// Reads RTPS messages from the transports and passes them to the event
+  /// loop. These threads exist only if configured with
+  /// [`DomainParticipantBuilder::thread_config`](crate::DomainParticipantBuilder::thread_config),
+  /// otherwise the event loop reads the messages itself.
+  Receive,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ThreadState {
+  last_round: Instant,
+  last_round_time: StdDuration,
+  max_round_time: StdDuration,
+}
+
+impl ThreadState {
+  fn new(now: Instant) -> Self {
+    Self {
+      last_round: now,
+      last_round_time: StdDuration::ZERO,
+      max_round_time: StdDuration::ZERO,
+    }
+  }
+
+  fn health(&self, now: Instant) -> ThreadHealth {
+    let since_last_round = now.saturating_duration_since(self.last_round);
+    ThreadHealth {
+      since_last_round: since_last_round.into(),
+      last_round_time: self.last_round_time.into(),
+      max_round_time: self.max_round_time.into(),
+      alive: since_last_round <= ParticipantHealth::THREAD_STALL_LIMIT,
+    }
+  }
+}
+
+#[derive(Debug)]
+struct HealthState {
+  event_loop: ThreadState,
+  discovery: ThreadState,
+}
+
+// Cloning gives another handle to the same state.
+#[derive(Debug, Clone)]
+pub(crate) struct HealthMonitor {
+  state: Arc<Mutex<HealthState>>,
+}
+
+impl HealthMonitor {
+  pub fn new() -> Self {
+    let now = Instant::now();
+    Self {
+      state: Arc::new(Mutex::new(HealthState {
+        event_loop: ThreadState::new(now),
+        discovery: ThreadState::new(now),
+      })),
+    }
+  }
+
+  // Called by a protocol thread each time it has processed a batch of events
+  // and is about to wait for more.
+  pub fn round_done(&self, thread: ProtocolThread, round_time: StdDuration) {
+    let mut state = self.state.lock().unwrap();
+    let thread_state = match thread {
+      ProtocolThread::EventLoop => &mut state.event_loop,
+      ProtocolThread::Discovery => &mut state.discovery,
+      // Receive threads only wait for messages. If they stop, the event loop
+      // stops getting messages, which does not show here.
+      ProtocolThread::Receive => return,
+    };
+    thread_state.last_round = Instant::now();
+    thread_state.last_round_time = round_time;
+    thread_state.max_round_time = thread_state.max_round_time.max(round_time);
+  }
+
+  pub fn health(&self, participant_guid: GUID) -> ParticipantHealth {
+    self.health_at(participant_guid, Instant::now())
+  }
+
+  fn health_at(&self, participant_guid: GUID, now: Instant) -> ParticipantHealth {
+    let state = self.state.lock().unwrap();
+    let event_loop = state.event_loop.health(now);
+    let discovery = state.discovery.health(now);
+    ParticipantHealth {
+      participant_guid,
+      healthy: event_loop.is_healthy() && discovery.is_healthy(),
+      event_loop,
+      discovery,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use byteorder::LittleEndian;
+
+  use super::*;
+  use crate::{
+    serialization::{cdr_deserializer::deserialize_from_little_endian, cdr_serializer::to_bytes},
+    structure::guid::EntityKind,
+  };
+
+  #[test]
+  fn health_monitor_rounds() {
+    let monitor = HealthMonitor::new();
+    let guid = GUID::dummy_test_guid(EntityKind::PARTICIPANT_BUILT_IN);
+
+    let health = monitor.health(guid);
+    assert!(health.healthy);
+    assert_eq!(health.participant_guid, guid);
+
+    // slow round in dp_event_loop
+    monitor.round_done(ProtocolThread::EventLoop, StdDuration::from_secs(2));
+    let health = monitor.health(guid);
+    assert!(!health.healthy);
+    assert!(health.event_loop.alive);
+    assert!(!health.event_loop.is_healthy());
+    assert!(health.discovery.is_healthy());
+
+    // recovered, but the maximum is remembered
+    monitor.round_done(ProtocolThread::EventLoop, StdDuration::from_millis(1));
+    let health = monitor.health(guid);
+    assert!(health.healthy);
+    assert_eq!(
+      health.event_loop.max_round_time,
+      Duration::from(StdDuration::from_secs(2))
+    );
+
+    // no rounds from either thread for a while
+    let later = Instant::now() + ParticipantHealth::THREAD_STALL_LIMIT * 2;
+    let health = monitor.health_at(guid, later);
+    assert!(!health.healthy);
+    assert!(!health.event_loop.alive);
+    assert!(!health.discovery.alive);
+  }
+
+  #[test]
+  fn health_cdr_roundtrip() {
+    let monitor = HealthMonitor::new();
+    monitor.round_done(ProtocolThread::Discovery, StdDuration::from_millis(3));
+    let health = monitor.health(GUID::dummy_test_guid(EntityKind::PARTICIPANT_BUILT_IN));
+
+    let bytes = to_bytes::<ParticipantHealth, LittleEndian>(&health).unwrap();
+    let decoded: ParticipantHealth = deserialize_from_little_endian(&bytes).unwrap();
+    assert_eq!(decoded, health);
+  }
+}
@@ -1,8 +1,18 @@
-use std::thread;
+use std::{
+  sync::{Arc, Mutex},
+  task::Waker,
+  thread,
+};
 
 use mio_extras::channel::{SyncSender, TrySendError};
 
-use crate::structure::duration::Duration;
+use crate::{
+  dds::{
+    qos::QosPolicies,
+    values::result::{Error, Result},
+  },
+  structure::duration::Duration,
+};
 
 const TIMEOUT_EPSILON: Duration = Duration::from_nanos(1000);
 
@@ -13,7 +23,7 @@ pub fn try_send_timeout<T>(
   sender: &SyncSender<T>,
   t: T,
   timeout_opt: Option<Duration>,
-) -> Result<(), TrySendError<T>> {
+) -> std::result::Result<(), TrySendError<T>> {
   // TODO: Write a more optimized fast path, where send succeeds on first try.
 
   let timeout = timeout_opt.unwrap_or(TIMEOUT_FALLBACK);
@@ -40,3 +50,222 @@ pub fn try_send_timeout<T>(
   }
   Err(TrySendError::Full(mt))
 }
+
+// Waits until `ready` returns true, checking it with increasing intervals,
+// like try_send_timeout. Returns false if the timeout expired first.
+pub(crate) fn wait_until_ready(timeout: Duration, mut ready: impl FnMut() -> bool) -> bool {
+  let mut delays = Vec::with_capacity(20);
+  let mut to = timeout;
+  while to > TIMEOUT_EPSILON {
+    to = to / 2;
+    delays.push(to);
+  }
+  loop {
+    if ready() {
+      return true;
+    }
+    match delays.pop() {
+      Some(delay) => thread::sleep(std::time::Duration::from(delay)),
+      None => return false,
+    }
+  }
+}
+
+// Holds the Waker of an async task that waits for a notification from the
+// event loop thread, e.g. new data for a DataReader. Clones share the slot.
+// This is used in addition to the mio notification channels, so that async
+// tasks need no thread to poll the channels.
+#[derive(Clone, Default)]
+pub(crate) struct WakerSlot {
+  waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl WakerSlot {
+  pub fn register(&self, waker: &Waker) {
+    let mut slot = self.waker.lock().unwrap();
+    if !slot.as_ref().map_or(false, |w| w.will_wake(waker)) {
+      *slot = Some(waker.clone());
+    }
+  }
+
+  // The task must register again, if it needs another wakeup.
+  pub fn wake(&self) {
+    let waker = self.waker.lock().unwrap().take();
+    if let Some(waker) = waker {
+      waker.wake();
+    }
+  }
+}
+
+type EnableAction = Box<dyn FnOnce(QosPolicies) -> Result<()> + Send>;
+
+#[allow(clippy::large_enum_variant)] // Entities have only one each
+enum LifecycleState {
+  // The action makes the entity live, with the QoS it has by then.
+  Disabled {
+    qos: QosPolicies,
+    enable: EnableAction,
+  },
+  Enabled,
+  Deleted,
+}
+
+// Lifecycle of a Publisher, Subscriber, DataWriter or DataReader. An entity
+// is created disabled, if the EntityFactory policy of its factory says so, and
+// may be deleted by delete_contained_entities of its factory, while the
+// application still holds it. Clones share the state, so that the factory can
+// enable and delete the entities it has created.
+#[derive(Clone)]
+pub(crate) struct EntityLifecycle {
+  state: Arc<Mutex<LifecycleState>>,
+}
+
+impl EntityLifecycle {
+  pub fn enabled() -> Self {
+    Self {
+      state: Arc::new(Mutex::new(LifecycleState::Enabled)),
+    }
+  }
+
+  pub fn disabled(
+    qos: QosPolicies,
+    enable: impl FnOnce(QosPolicies) -> Result<()> + Send + 'static,
+  ) -> Self {
+    Self {
+      state: Arc::new(Mutex::new(LifecycleState::Disabled {
+        qos,
+        enable: Box::new(enable),
+      })),
+    }
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, LifecycleState> {
+    self.state.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  // Enabling an enabled entity does nothing. If the enable action fails, the
+  // entity cannot be enabled any more, so it is regarded as deleted.
+  pub fn enable(&self) -> Result<()> {
+    let mut state = self.lock();
+    match std::mem::replace(&mut *state, LifecycleState::Deleted) {
+      LifecycleState::Disabled { qos, enable } => {
+        enable(qos)?;
+        *state = LifecycleState::Enabled;
+        Ok(())
+      }
+      LifecycleState::Enabled => {
+        *state = LifecycleState::Enabled;
+        Ok(())
+      }
+      LifecycleState::Deleted => Err(Error::AlreadyDeleted),
+    }
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    matches!(*self.lock(), LifecycleState::Enabled)
+  }
+
+  pub fn is_deleted(&self) -> bool {
+    matches!(*self.lock(), LifecycleState::Deleted)
+  }
+
+  // For operations that are not allowed before enable or after delete.
+  pub fn check_enabled(&self) -> Result<()> {
+    match *self.lock() {
+      LifecycleState::Disabled { .. } => Err(Error::NotEnabled),
+      LifecycleState::Enabled => Ok(()),
+      LifecycleState::Deleted => Err(Error::AlreadyDeleted),
+    }
+  }
+
+  // Before the entity is enabled, its QoS can be changed freely, and the
+  // change is used when enabling. Returns false if the entity is not disabled.
+  pub fn set_qos_if_disabled(&self, new_qos: &QosPolicies) -> bool {
+    match &mut *self.lock() {
+      LifecycleState::Disabled { qos, .. } => {
+        *qos = new_qos.clone();
+        true
+      }
+      _ => false,
+    }
+  }
+
+  // Returns true if the entity was enabled, i.e. it has been live and its
+  // RTPS entity and discovery data must be removed.
+  pub fn delete(&self) -> bool {
+    let previous = std::mem::replace(&mut *self.lock(), LifecycleState::Deleted);
+    matches!(previous, LifecycleState::Enabled)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    task::Wake,
+  };
+
+  use super::*;
+
+  struct CountingWaker(AtomicUsize);
+
+  impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+      self.0.fetch_add(1, Ordering::SeqCst);
+    }
+  }
+
+  #[test]
+  fn waker_slot_wakes_once_per_registration() {
+    let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let slot = WakerSlot::default();
+    let other_end = slot.clone();
+
+    // nothing registered yet
+    other_end.wake();
+    assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+
+    slot.register(&waker);
+    slot.register(&waker);
+    other_end.wake();
+    other_end.wake();
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+    slot.register(&waker);
+    other_end.wake();
+    assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+  }
+
+  #[test]
+  fn entity_lifecycle_enable_and_delete() {
+    let enabled_with = Arc::new(Mutex::new(None));
+    let recorder = enabled_with.clone();
+    let lifecycle = EntityLifecycle::disabled(QosPolicies::qos_none(), move |qos| {
+      *recorder.lock().unwrap() = Some(qos);
+      Ok(())
+    });
+    let factory_end = lifecycle.clone();
+    assert!(matches!(lifecycle.check_enabled(), Err(Error::NotEnabled)));
+
+    let qos = QosPolicies::builder()
+      .durability(crate::dds::qos::policy::Durability::TransientLocal)
+      .build();
+    assert!(lifecycle.set_qos_if_disabled(&qos));
+    factory_end.enable().unwrap();
+    lifecycle.enable().unwrap();
+    assert_eq!(enabled_with.lock().unwrap().as_ref(), Some(&qos));
+    assert!(lifecycle.check_enabled().is_ok());
+    assert!(!lifecycle.set_qos_if_disabled(&qos));
+
+    assert!(factory_end.delete());
+    assert!(!lifecycle.delete());
+    assert!(matches!(
+      lifecycle.check_enabled(),
+      Err(Error::AlreadyDeleted)
+    ));
+    assert!(matches!(lifecycle.enable(), Err(Error::AlreadyDeleted)));
+    // never enabled, so nothing to remove
+    assert!(!EntityLifecycle::disabled(QosPolicies::qos_none(), |_| Ok(())).delete());
+  }
+}
@@ -0,0 +1,368 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::{
+  dds::{
+    no_key::{datareader::DataReader as NoKeyDataReader, datawriter::DataWriter},
+    participant::DomainParticipant,
+    pubsub::{Publisher, Subscriber},
+    qos::{HasQoSPolicy, QosPolicies},
+    sampleinfo::SampleInfo,
+    topic::{Topic, TopicDescription, TopicKind},
+    traits::{
+      key::{Key, Keyed},
+      serde_adapters::{no_key, with_key},
+    },
+    values::result::{Error, Result},
+    with_key::datareader::DataReader as WithKeyDataReader,
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+};
+
+/// Suffix appended to the name of a mirrored Topic to get the name of its JSON
+/// mirror Topic.
+pub const JSON_MIRROR_SUFFIX: &str = "__json";
+
+/// Type name of JSON mirror Topics.
+pub const JSON_SAMPLE_TYPE_NAME: &str = "JsonSample";
+
+/// A sample on a JSON mirror Topic.
+///
+/// `json` is a JSON object with the following members:
+/// * `"writer_guid"`: GUID of the DataWriter that wrote the original sample
+/// * `"source_timestamp"`: source timestamp of the sample, or `null`
+/// * `"instance_state"`: `"Alive"`, `"NotAliveDisposed"` or
+///   `"NotAliveNoWriters"`
+/// * `"value"`: the sample, if it carries data
+/// * `"key"`: the instance key, if the sample only announces a state change of
+///   a keyed instance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonSample {
+  pub json: String,
+}
+
+/// Mirrors selected Topics onto JSON mirror Topics for debugging.
+///
+/// Samples received on a mirrored Topic `T` are rendered as JSON and published
+/// on the NoKey Topic `T__json` of type [`JsonSample`]. This way generic tools,
+/// or a simple subscriber, can inspect traffic without knowing the data types
+/// of the mirrored Topics. Only the mirror needs to know them.
+///
+/// The mirror uses its own Publisher and Subscriber in the given
+/// DomainParticipant. Samples written by other DomainParticipants are
+/// mirrored. Mirror Topics use the QoS of the mirrored Topic.
+///
+/// Call [`forward`](Self::forward) periodically to mirror the samples received
+/// since the previous call.
+pub struct JsonMirror {
+  participant: DomainParticipant,
+  publisher: Publisher,
+  subscriber: Subscriber,
+  mirrors: Vec<Box<dyn Mirror>>,
+}
+
+impl JsonMirror {
+  pub fn new(participant: &DomainParticipant) -> Result<Self> {
+    let qos = QosPolicies::qos_none();
+    Ok(Self {
+      participant: participant.clone(),
+      publisher: participant.create_publisher(&qos)?,
+      subscriber: participant.create_subscriber(&qos)?,
+      mirrors: Vec::new(),
+    })
+  }
+
+  /// Mirror a WithKey Topic.
+  pub fn mirror<D, SA>(&mut self, topic: &Topic) -> Result<()>
+  where
+    D: DeserializeOwned + Serialize + Keyed + 'static,
+    <D as Keyed>::K: Key,
+    SA: with_key::DeserializerAdapter<D> + 'static,
+  {
+    let writer = self.create_mirror_writer(topic)?;
+    let reader = self
+      .subscriber
+      .create_datareader::<D, SA>(topic, Some(topic.qos()))?;
+    self
+      .mirrors
+      .push(Box::new(WithKeyMirror { reader, writer }));
+    Ok(())
+  }
+
+  pub fn mirror_cdr<D>(&mut self, topic: &Topic) -> Result<()>
+  where
+    D: DeserializeOwned + Serialize + Keyed + 'static,
+    <D as Keyed>::K: Key,
+  {
+    self.mirror::<D, CDRDeserializerAdapter<D>>(topic)
+  }
+
+  /// Mirror a NoKey Topic.
+  pub fn mirror_no_key<D, SA>(&mut self, topic: &Topic) -> Result<()>
+  where
+    D: DeserializeOwned + Serialize + 'static,
+    SA: no_key::DeserializerAdapter<D> + 'static,
+  {
+    let writer = self.create_mirror_writer(topic)?;
+    let reader = self
+      .subscriber
+      .create_datareader_no_key::<D, SA>(topic, Some(topic.qos()))?;
+    self.mirrors.push(Box::new(NoKeyMirror { reader, writer }));
+    Ok(())
+  }
+
+  pub fn mirror_no_key_cdr<D>(&mut self, topic: &Topic) -> Result<()>
+  where
+    D: DeserializeOwned + Serialize + 'static,
+  {
+    self.mirror_no_key::<D, CDRDeserializerAdapter<D>>(topic)
+  }
+
+  /// Mirror all samples received since the previous call. Returns the number
+  /// of samples mirrored.
+  pub fn forward(&mut self) -> Result<usize> {
+    let mut count = 0;
+    for mirror in self.mirrors.iter_mut() {
+      count += mirror.forward()?;
+    }
+    Ok(count)
+  }
+
+  fn create_mirror_writer(&self, topic: &Topic) -> Result<JsonWriter> {
+    let name = topic.name();
+    if name.ends_with(JSON_MIRROR_SUFFIX) {
+      return Error::bad_parameter(format!("Topic {} is already a JSON mirror", name));
+    }
+    let mirror_topic = self.participant.create_topic(
+      format!("{}{}", name, JSON_MIRROR_SUFFIX),
+      JSON_SAMPLE_TYPE_NAME.to_string(),
+      &topic.qos(),
+      TopicKind::NoKey,
+    )?;
+    self
+      .publisher
+      .create_datawriter_no_key_cdr(&mirror_topic, None)
+  }
+}
+
+type JsonWriter = DataWriter<JsonSample, CDRSerializerAdapter<JsonSample>>;
+
+trait Mirror {
+  fn forward(&mut self) -> Result<usize>;
+}
+
+struct NoKeyMirror<D: DeserializeOwned, SA: no_key::DeserializerAdapter<D>> {
+  reader: NoKeyDataReader<D, SA>,
+  writer: JsonWriter,
+}
+
+impl<D, SA> Mirror for NoKeyMirror<D, SA>
+where
+  D: DeserializeOwned + Serialize + 'static,
+  SA: no_key::DeserializerAdapter<D>,
+{
+  fn forward(&mut self) -> Result<usize> {
+    let mut count = 0;
+    while let Some(sample) = self.reader.take_next_sample()? {
+      count += write_json(&self.writer, sample.sample_info(), "value", sample.value())?;
+    }
+    Ok(count)
+  }
+}
+
+struct WithKeyMirror<D: DeserializeOwned + Keyed, SA: with_key::DeserializerAdapter<D>> {
+  reader: WithKeyDataReader<D, SA>,
+  writer: JsonWriter,
+}
+
+impl<D, SA> Mirror for WithKeyMirror<D, SA>
+where
+  D: DeserializeOwned + Serialize + Keyed + 'static,
+  <D as Keyed>::K: Key,
+  SA: with_key::DeserializerAdapter<D>,
+{
+  fn forward(&mut self) -> Result<usize> {
+    let mut count = 0;
+    while let Some(sample) = self.reader.take_next_sample()? {
+      count += match sample.value() {
+        Ok(value) => write_json(&self.writer, sample.sample_info(), "value", value)?,
+        Err(key) => write_json(&self.writer, sample.sample_info(), "key", key)?,
+      };
+    }
+    Ok(count)
+  }
+}
+
+// Returns the number of samples written, i.e. 0 if the sample cannot be
+// rendered as JSON.
+fn write_json<T: Serialize>(
+  writer: &JsonWriter,
+  sample_info: &SampleInfo,
+  member: &str,
+  value: &T,
+) -> Result<usize> {
+  match render_json(sample_info, member, value) {
+    Ok(json) => {
+      writer.write(JsonSample { json }, None)?;
+      Ok(1)
+    }
+    Err(e) => {
+      warn!(
+        "Cannot mirror sample on topic {}: {}",
+        writer.topic().name(),
+        e
+      );
+      Ok(0)
+    }
+  }
+}
+
+fn render_json<T: Serialize>(
+  sample_info: &SampleInfo,
+  member: &str,
+  value: &T,
+) -> serde_json::Result<String> {
+  let mut object = json!({
+    "writer_guid": sample_info.writer_guid().to_string(),
+    "source_timestamp": sample_info.source_timestamp(),
+    "instance_state": format!("{:?}", sample_info.instance_state()),
+  });
+  object[member] = serde_json::to_value(value)?;
+  serde_json::to_string(&object)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{thread, time::Duration as StdDuration};
+
+  use super::*;
+  use crate::{
+    dds::{
+      qos::QosPolicyBuilder,
+      sampleinfo::{InstanceState, NotAliveGenerationCounts, SampleState, ViewState},
+      with_key::datawriter::WriteOptions,
+    },
+    structure::{
+      guid::{EntityKind, GUID},
+      sequence_number::SequenceNumber,
+      time::Timestamp,
+    },
+    test::loopback::loopback_participant,
+  };
+
+  #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+  struct Shape {
+    color: String,
+    x: i32,
+    y: i32,
+  }
+
+  impl Keyed for Shape {
+    type K = String;
+    fn key(&self) -> String {
+      self.color.clone()
+    }
+  }
+
+  #[test]
+  fn json_mirror_render() {
+    let guid = GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED);
+    let sample_info = SampleInfo {
+      sample_state: SampleState::NotRead,
+      view_state: ViewState::New,
+      instance_state: InstanceState::Alive,
+      generation_counts: NotAliveGenerationCounts::zero(),
+      valid_data: true,
+      sample_rank: 0,
+      generation_rank: 0,
+      absolute_generation_rank: 0,
+      write_options: WriteOptions {
+        source_timestamp: Some(Timestamp::ZERO),
+        ..WriteOptions::default()
+      },
+      publication_handle: guid,
+      sequence_number: SequenceNumber::new(1),
+    };
+    let shape = Shape {
+      color: "RED".to_string(),
+      x: 1,
+      y: -2,
+    };
+    let json: serde_json::Value =
+      serde_json::from_str(&render_json(&sample_info, "value", &shape).unwrap()).unwrap();
+    assert_eq!(json["writer_guid"], guid.to_string());
+    assert_eq!(json["source_timestamp"]["seconds"], 0);
+    assert_eq!(json["instance_state"], "Alive");
+    assert_eq!(json["value"]["color"], "RED");
+    assert_eq!(json["value"]["y"], -2);
+    assert!(json.get("key").is_none());
+  }
+
+  #[test]
+  fn json_mirror_between_participants() {
+    let qos = QosPolicyBuilder::new()
+      .reliability(crate::policy::Reliability::Reliable {
+        max_blocking_time: crate::structure::duration::Duration::DURATION_ZERO,
+      })
+      .durability(crate::policy::Durability::TransientLocal)
+      .build();
+    let shape_topic = |participant: &DomainParticipant| {
+      participant
+        .create_topic(
+          "json_mirror_test".to_string(),
+          "Shape".to_string(),
+          &qos,
+          TopicKind::WithKey,
+        )
+        .unwrap()
+    };
+
+    // Application writing Shapes
+    let app = loopback_participant(218);
+    let publisher = app.create_publisher(&qos).unwrap();
+    let writer = publisher
+      .create_datawriter_cdr::<Shape>(&shape_topic(&app), None)
+      .unwrap();
+
+    // Mirror and a JSON subscriber in another participant
+    let debugger = loopback_participant(218);
+    let mut mirror = JsonMirror::new(&debugger).unwrap();
+    let topic = shape_topic(&debugger);
+    mirror.mirror_cdr::<Shape>(&topic).unwrap();
+    let json_topic = debugger
+      .create_topic(
+        "json_mirror_test__json".to_string(),
+        JSON_SAMPLE_TYPE_NAME.to_string(),
+        &qos,
+        TopicKind::NoKey,
+      )
+      .unwrap();
+    let mut json_reader = debugger
+      .create_subscriber(&qos)
+      .unwrap()
+      .create_datareader_no_key_cdr::<JsonSample>(&json_topic, None)
+      .unwrap();
+
+    let shape = Shape {
+      color: "BLUE".to_string(),
+      x: 10,
+      y: 20,
+    };
+    writer.write(shape, None).unwrap();
+
+    let mut received = None;
+    for _ in 0..100 {
+      mirror.forward().unwrap();
+      if let Some(sample) = json_reader.take_next_sample().unwrap() {
+        received = Some(sample.into_value());
+        break;
+      }
+      thread::sleep(StdDuration::from_millis(100));
+    }
+    let json: serde_json::Value = serde_json::from_str(&received.unwrap().json).unwrap();
+    assert_eq!(json["value"]["color"], "BLUE");
+    assert_eq!(json["value"]["x"], 10);
+  }
+}
@@ -0,0 +1,330 @@
+use enumflags2::{bitflags, BitFlags};
+
+use crate::{
+  dds::{
+    qos::QosPolicyId,
+    statusevents::{
+      ClockJumpKind, CountWithChange, DataReaderStatus, DataWriterStatus, DomainParticipantStatus,
+      ParticipantLostReason, QosPolicyCount, SampleRejectedStatusKind, SubscriberStatus,
+      TopicStatus,
+    },
+  },
+  structure::{duration::Duration, guid::GuidPrefix},
+};
+
+/// Kinds of communication status, used in a [`StatusMask`] to select which
+/// listener callbacks are invoked.
+///
+/// The bit values are those of the DDS Spec 1.4 Section 2.3.3 DCPS PSM.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[bitflags]
+#[repr(u32)]
+pub enum StatusKind {
+  InconsistentTopic = 0x0001,
+  OfferedDeadlineMissed = 0x0002,
+  RequestedDeadlineMissed = 0x0004,
+  OfferedIncompatibleQos = 0x0020,
+  RequestedIncompatibleQos = 0x0040,
+  SampleLost = 0x0080,
+  SampleRejected = 0x0100,
+  DataOnReaders = 0x0200,
+  DataAvailable = 0x0400,
+  LivelinessLost = 0x0800,
+  LivelinessChanged = 0x1000,
+  PublicationMatched = 0x2000,
+  SubscriptionMatched = 0x4000,
+}
+
+/// Set of [`StatusKind`]s. `StatusMask::all()` enables every callback.
+pub type StatusMask = BitFlags<StatusKind>;
+
+/// Callbacks for the statuses of a DataReader of type `R`.
+///
+/// All methods have empty default implementations, so implement only those
+/// that are of interest. The callbacks are invoked by `dispatch_to_listener`
+/// of the DataReader, in the thread that calls it. This is an alternative to
+/// matching on the statuses received with
+/// [`try_recv_status`](crate::StatusEvented::try_recv_status).
+///
+/// See DDS Spec 1.4 Section 2.2.4.4 DataReaderListener Interface.
+pub trait DataReaderListener<R: ?Sized = ()> {
+  /// New samples have arrived since the previous dispatch.
+  fn on_data_available(&mut self, _reader: &mut R) {}
+
+  fn on_sample_rejected(
+    &mut self,
+    _count: CountWithChange,
+    _last_reason: SampleRejectedStatusKind,
+  ) {
+  }
+
+  fn on_liveliness_changed(
+    &mut self,
+    _alive_total: CountWithChange,
+    _not_alive_total: CountWithChange,
+  ) {
+  }
+
+  fn on_requested_deadline_missed(&mut self, _count: CountWithChange) {}
+
+  fn on_requested_incompatible_qos(
+    &mut self,
+    _count: CountWithChange,
+    _last_policy_id: QosPolicyId,
+    _policies: &[QosPolicyCount],
+  ) {
+  }
+
+  fn on_sample_lost(&mut self, _count: CountWithChange) {}
+
+  fn on_subscription_matched(&mut self, _total: CountWithChange, _current: CountWithChange) {}
+}
+
+/// Callbacks for the statuses of a DataWriter.
+///
+/// See [`DataReaderListener`] on how the callbacks are invoked, and DDS Spec
+/// 1.4 Section 2.2.4.4 DataWriterListener Interface.
+pub trait DataWriterListener {
+  fn on_liveliness_lost(&mut self, _count: CountWithChange) {}
+
+  fn on_offered_deadline_missed(&mut self, _count: CountWithChange) {}
+
+  fn on_offered_incompatible_qos(
+    &mut self,
+    _count: CountWithChange,
+    _last_policy_id: QosPolicyId,
+    _policies: &[QosPolicyCount],
+  ) {
+  }
+
+  fn on_publication_matched(&mut self, _total: CountWithChange, _current: CountWithChange) {}
+}
+
+/// Callbacks for the statuses of a Topic.
+pub trait TopicListener {
+  fn on_inconsistent_topic(&mut self, _count: CountWithChange) {}
+}
+
+/// Callbacks for the statuses reported to a DomainParticipant, see
+/// [`DomainParticipantStatusListener::dispatch_to_listener`](crate::DomainParticipantStatusListener::dispatch_to_listener).
+///
+/// This includes the DataReader, DataWriter and Topic callbacks, except
+/// [`on_data_available`](DataReaderListener::on_data_available), which is
+/// replaced by [`on_data_on_readers`](Self::on_data_on_readers) at this
+/// level.
+pub trait ParticipantListener: DataReaderListener + DataWriterListener + TopicListener {
+  /// Some DataReader of a Subscriber has new samples.
+  fn on_data_on_readers(&mut self) {}
+
+  /// Not selected by the [`StatusMask`], as this is not a DDS standard status.
+  /// See [`DomainParticipantStatus::ClockJumpHandled`].
+  fn on_clock_jump_handled(&mut self, _kind: ClockJumpKind, _amount: Duration) {}
+
+  /// Not selected by the [`StatusMask`], as this is not a DDS standard status.
+  /// See [`DomainParticipantStatus::ParticipantLost`].
+  fn on_participant_lost(&mut self, _guid_prefix: GuidPrefix, _reason: ParticipantLostReason) {}
+}
+
+// Invokes the callback corresponding to the status, if enabled in the mask.
+// Returns true if a callback was invoked.
+pub(crate) fn dispatch_reader_status<R, L>(
+  listener: &mut L,
+  status: DataReaderStatus,
+  mask: StatusMask,
+) -> bool
+where
+  R: ?Sized,
+  L: DataReaderListener<R> + ?Sized,
+{
+  let kind = match status {
+    DataReaderStatus::SampleRejected { .. } => StatusKind::SampleRejected,
+    DataReaderStatus::LivelinessChanged { .. } => StatusKind::LivelinessChanged,
+    DataReaderStatus::RequestedDeadlineMissed { .. } => StatusKind::RequestedDeadlineMissed,
+    DataReaderStatus::RequestedIncompatibleQos { .. } => StatusKind::RequestedIncompatibleQos,
+    DataReaderStatus::SampleLost { .. } => StatusKind::SampleLost,
+    DataReaderStatus::SubscriptionMatched { .. } => StatusKind::SubscriptionMatched,
+  };
+  if !mask.contains(kind) {
+    return false;
+  }
+  match status {
+    DataReaderStatus::SampleRejected { count, last_reason } => {
+      listener.on_sample_rejected(count, last_reason);
+    }
+    DataReaderStatus::LivelinessChanged {
+      alive_total,
+      not_alive_total,
+    } => listener.on_liveliness_changed(alive_total, not_alive_total),
+    DataReaderStatus::RequestedDeadlineMissed { count } => {
+      listener.on_requested_deadline_missed(count);
+    }
+    DataReaderStatus::RequestedIncompatibleQos {
+      count,
+      last_policy_id,
+      policies,
+    } => listener.on_requested_incompatible_qos(count, last_policy_id, &policies),
+    DataReaderStatus::SampleLost { count } => listener.on_sample_lost(count),
+    DataReaderStatus::SubscriptionMatched { total, current } => {
+      listener.on_subscription_matched(total, current);
+    }
+  }
+  true
+}
+
+pub(crate) fn dispatch_writer_status<L>(
+  listener: &mut L,
+  status: DataWriterStatus,
+  mask: StatusMask,
+) -> bool
+where
+  L: DataWriterListener + ?Sized,
+{
+  let kind = match status {
+    DataWriterStatus::LivelinessLost { .. } => StatusKind::LivelinessLost,
+    DataWriterStatus::OfferedDeadlineMissed { .. } => StatusKind::OfferedDeadlineMissed,
+    DataWriterStatus::OfferedIncompatibleQos { .. } => StatusKind::OfferedIncompatibleQos,
+    DataWriterStatus::PublicationMatched { .. } => StatusKind::PublicationMatched,
+  };
+  if !mask.contains(kind) {
+    return false;
+  }
+  match status {
+    DataWriterStatus::LivelinessLost { count } => listener.on_liveliness_lost(count),
+    DataWriterStatus::OfferedDeadlineMissed { count } => {
+      listener.on_offered_deadline_missed(count);
+    }
+    DataWriterStatus::OfferedIncompatibleQos {
+      count,
+      last_policy_id,
+      policies,
+    } => listener.on_offered_incompatible_qos(count, last_policy_id, &policies),
+    DataWriterStatus::PublicationMatched { total, current } => {
+      listener.on_publication_matched(total, current);
+    }
+  }
+  true
+}
+
+pub(crate) fn dispatch_participant_status<L>(
+  listener: &mut L,
+  status: DomainParticipantStatus,
+  mask: StatusMask,
+) -> bool
+where
+  L: ParticipantListener + ?Sized,
+{
+  match status {
+    DomainParticipantStatus::PublisherStatus(status) => {
+      dispatch_writer_status(listener, status, mask)
+    }
+    DomainParticipantStatus::SubscriberStatus(SubscriberStatus::DataOnReaders) => {
+      if !mask.contains(StatusKind::DataOnReaders) {
+        return false;
+      }
+      listener.on_data_on_readers();
+      true
+    }
+    DomainParticipantStatus::SubscriberStatus(SubscriberStatus::DataReaderStatus(status)) => {
+      dispatch_reader_status::<(), _>(listener, status, mask)
+    }
+    DomainParticipantStatus::TopicStatus(TopicStatus::InconsistentTopic { count }) => {
+      if !mask.contains(StatusKind::InconsistentTopic) {
+        return false;
+      }
+      listener.on_inconsistent_topic(count);
+      true
+    }
+    DomainParticipantStatus::ClockJumpHandled { kind, amount } => {
+      listener.on_clock_jump_handled(kind, amount);
+      true
+    }
+    DomainParticipantStatus::ParticipantLost {
+      guid_prefix,
+      reason,
+    } => {
+      listener.on_participant_lost(guid_prefix, reason);
+      true
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Default)]
+  struct Recorder {
+    calls: Vec<&'static str>,
+  }
+
+  impl DataReaderListener for Recorder {
+    fn on_subscription_matched(&mut self, _total: CountWithChange, current: CountWithChange) {
+      assert_eq!(current.count(), 1);
+      self.calls.push("subscription_matched");
+    }
+  }
+
+  impl DataWriterListener for Recorder {
+    fn on_publication_matched(&mut self, _total: CountWithChange, _current: CountWithChange) {
+      self.calls.push("publication_matched");
+    }
+  }
+
+  impl TopicListener for Recorder {}
+
+  impl ParticipantListener for Recorder {
+    fn on_data_on_readers(&mut self) {
+      self.calls.push("data_on_readers");
+    }
+  }
+
+  #[test]
+  fn listener_dispatch_with_mask() {
+    let matched = || DataReaderStatus::SubscriptionMatched {
+      total: CountWithChange::new(1, 1),
+      current: CountWithChange::new(1, 1),
+    };
+    let mut recorder = Recorder::default();
+
+    assert!(dispatch_reader_status::<(), _>(
+      &mut recorder,
+      matched(),
+      StatusMask::all()
+    ));
+    // masked out
+    assert!(!dispatch_reader_status::<(), _>(
+      &mut recorder,
+      matched(),
+      StatusKind::DataAvailable | StatusKind::SampleLost
+    ));
+    // default implementations do nothing
+    assert!(dispatch_reader_status::<(), _>(
+      &mut recorder,
+      DataReaderStatus::SampleLost {
+        count: CountWithChange::new(1, 1)
+      },
+      StatusMask::all()
+    ));
+
+    let mask = StatusKind::DataOnReaders | StatusKind::PublicationMatched;
+    for status in [
+      DomainParticipantStatus::SubscriberStatus(SubscriberStatus::DataOnReaders),
+      DomainParticipantStatus::SubscriberStatus(SubscriberStatus::DataReaderStatus(matched())),
+      DomainParticipantStatus::PublisherStatus(DataWriterStatus::PublicationMatched {
+        total: CountWithChange::new(1, 1),
+        current: CountWithChange::new(1, 1),
+      }),
+    ] {
+      dispatch_participant_status(&mut recorder, status, mask);
+    }
+
+    assert_eq!(
+      recorder.calls,
+      vec![
+        "subscription_matched",
+        "data_on_readers",
+        "publication_matched"
+      ]
+    );
+  }
+}
@@ -0,0 +1,386 @@
+use std::{
+  fmt,
+  marker::PhantomData,
+  mem::{align_of, size_of},
+  ops::{Deref, DerefMut},
+  ptr,
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+
+use crate::{
+  dds::traits::{
+    key::Keyed,
+    serde_adapters::{no_key, with_key},
+  },
+  messages::submessages::submessage_elements::serialized_payload::{
+    RepresentationIdentifier, SerializedPayload,
+  },
+  serialization::{
+    error::{Error, Result},
+    CDRDeserializerAdapter,
+  },
+  structure::guid::GUID,
+};
+
+/// Plain data types, whose values can be used directly from received message
+/// buffers. See [`Loaned`].
+///
+/// This is implemented for the primitive integer and floating point types,
+/// and arrays of `Pod` types.
+///
+/// # Safety
+///
+/// Implement this only for types that
+/// * are `#[repr(C)]` and have no padding bytes, so that their memory layout
+///   is the same as their CDR encoding, and
+/// * are valid for any bit pattern, i.e. consist only of integers, floating
+///   point numbers, and arrays and `Pod` structs of those. E.g. `bool`,
+///   `char`, enums and references are not allowed.
+pub unsafe trait Pod: Copy + Send + Sync + 'static {}
+
+macro_rules! impl_pod {
+  ($($t:ty),*) => {
+    $( unsafe impl Pod for $t {} )*
+  };
+}
+
+impl_pod!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// A received sample of a [`Pod`] type, loaned from the received message
+/// buffer. Dereferences to `T`.
+///
+/// If the sample is suitably aligned in the buffer, it is used in place: the
+/// buffer is shared with the topic cache of the DomainParticipant, and the
+/// loan is returned, i.e. the buffer released, when the last `Loaned`
+/// referring to it is dropped. Otherwise, the sample is copied once on
+/// reception. In neither case is it deserialized.
+///
+/// Use [`LoanAdapter`] to read `Loaned` samples:
+///
+/// ```
+/// # use rustdds::*;
+/// # use serde::{Deserialize, Serialize};
+/// #[repr(C)]
+/// #[derive(Clone, Copy, Serialize, Deserialize)]
+/// struct Spectrum {
+///   sensor: u32,
+///   count: u32,
+///   bins: [f32; 32],
+/// }
+/// // Safety: repr(C) without padding, and any bit pattern is valid.
+/// unsafe impl Pod for Spectrum {}
+///
+/// # let participant = DomainParticipant::new(0).unwrap();
+/// # let qos = QosPolicyBuilder::new().build();
+/// # let subscriber = participant.create_subscriber(&qos).unwrap();
+/// # let topic = participant
+/// #   .create_topic("spectra".to_string(), "Spectrum".to_string(), &qos, TopicKind::NoKey)
+/// #   .unwrap();
+/// let mut reader = subscriber
+///   .create_datareader_no_key::<Loaned<Spectrum>, LoanAdapter<Spectrum>>(&topic, None)
+///   .unwrap();
+/// for sample in reader.take_loaned(10).unwrap() {
+///   let spectrum: &Spectrum = sample.value();
+///   println!("{}: {:?}", spectrum.sensor, &spectrum.bins[..spectrum.count as usize]);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Loaned<T: Pod> {
+  inner: LoanedInner<T>,
+}
+
+#[derive(Clone)]
+enum LoanedInner<T> {
+  // Exactly size_of::<T>() bytes, aligned for T
+  Shared(Bytes),
+  Copied(T),
+}
+
+impl<T: Pod> Loaned<T> {
+  /// A `Loaned` that holds its own copy of `value`.
+  pub fn new(value: T) -> Self {
+    Self {
+      inner: LoanedInner::Copied(value),
+    }
+  }
+
+  // `payload` is the CDR encoding of a T in the byte order of this host,
+  // without the encapsulation header.
+  fn from_payload(payload: &Bytes) -> Result<Self> {
+    let size = size_of::<T>();
+    // The end of the payload may be padded to a multiple of 4 bytes.
+    if payload.len() < size || payload.len() > size + 3 {
+      return Err(Error::Message(format!(
+        "Payload of {} bytes does not match {} bytes of {}",
+        payload.len(),
+        size,
+        std::any::type_name::<T>()
+      )));
+    }
+    let bytes = payload.slice(..size);
+    let inner = if size > 0 && bytes.as_ptr() as usize % align_of::<T>() == 0 {
+      LoanedInner::Shared(bytes)
+    } else {
+      // Safety: There are size_of::<T>() bytes, and T is valid for any bit
+      // pattern, as required by Pod.
+      LoanedInner::Copied(unsafe { ptr::read_unaligned(bytes.as_ptr() as *const T) })
+    };
+    Ok(Self { inner })
+  }
+
+  /// Is the sample used in place in the received message buffer, i.e. was it
+  /// not copied.
+  pub fn is_zero_copy(&self) -> bool {
+    matches!(self.inner, LoanedInner::Shared(_))
+  }
+}
+
+impl<T: Pod> Deref for Loaned<T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    match &self.inner {
+      // Safety: The bytes are aligned for T and there are size_of::<T>() of
+      // them, see from_payload(). T is valid for any bit pattern, as required
+      // by Pod. Bytes are immutable, and live as long as self.
+      LoanedInner::Shared(bytes) => unsafe { &*(bytes.as_ptr() as *const T) },
+      LoanedInner::Copied(value) => value,
+    }
+  }
+}
+
+impl<T: Pod + fmt::Debug> fmt::Debug for Loaned<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("Loaned").field(&**self).finish()
+  }
+}
+
+// Deserialized samples, e.g. those from other DeserializerAdapters, are
+// copies.
+impl<'de, T: Pod + Deserialize<'de>> Deserialize<'de> for Loaned<T> {
+  fn deserialize<DE: Deserializer<'de>>(deserializer: DE) -> std::result::Result<Self, DE::Error> {
+    T::deserialize(deserializer).map(Self::new)
+  }
+}
+
+impl<T: Pod + Keyed> Keyed for Loaned<T> {
+  type K = T::K;
+
+  fn key(&self) -> T::K {
+    (**self).key()
+  }
+}
+
+/// DeserializerAdapter for reading [`Loaned`] samples in CDR encoding.
+///
+/// Only samples in the byte order of this host can be loaned. Samples in the
+/// other byte order are not supported, so DataReaders ignore them.
+pub struct LoanAdapter<T> {
+  phantom: PhantomData<T>,
+}
+
+const NATIVE_CDR: [RepresentationIdentifier; 1] = [if cfg!(target_endian = "little") {
+  RepresentationIdentifier::CDR_LE
+} else {
+  RepresentationIdentifier::CDR_BE
+}];
+
+impl<T> no_key::DeserializerAdapter<Loaned<T>> for LoanAdapter<T>
+where
+  T: Pod + DeserializeOwned,
+{
+  fn supported_encodings() -> &'static [RepresentationIdentifier] {
+    &NATIVE_CDR
+  }
+
+  fn from_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<Loaned<T>> {
+    Self::from_payload(&Bytes::copy_from_slice(input_bytes), encoding)
+  }
+
+  fn from_payload(payload: &Bytes, encoding: RepresentationIdentifier) -> Result<Loaned<T>> {
+    if encoding == NATIVE_CDR[0] {
+      Loaned::from_payload(payload)
+    } else {
+      Err(Error::Message(format!(
+        "Cannot loan a sample in encoding {:?}",
+        encoding
+      )))
+    }
+  }
+}
+
+impl<T> with_key::DeserializerAdapter<Loaned<T>> for LoanAdapter<T>
+where
+  T: Pod + Keyed + DeserializeOwned,
+  <T as Keyed>::K: DeserializeOwned,
+{
+  fn key_from_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<T::K> {
+    <CDRDeserializerAdapter<T> as with_key::DeserializerAdapter<T>>::key_from_bytes(
+      input_bytes,
+      encoding,
+    )
+  }
+}
+
+// Alignment of loaned buffers. This is enough for the primitive types.
+const LOAN_ALIGN: usize = 8;
+
+/// A buffer loaned from a DataWriter for the serialized payload of one
+/// sample, see [`DataWriter::loan_sample`](crate::with_key::DataWriter::loan_sample).
+/// Dereferences to the bytes of the buffer.
+pub struct SampleLoan {
+  writer: GUID,
+  encoding: RepresentationIdentifier,
+  buffer: BytesMut,
+}
+
+impl SampleLoan {
+  pub(crate) fn new(writer: GUID, size: usize, encoding: RepresentationIdentifier) -> Self {
+    let mut buffer = BytesMut::zeroed(size + LOAN_ALIGN - 1);
+    // Skipping the beginning does not reallocate.
+    buffer.advance((LOAN_ALIGN - buffer.as_ptr() as usize % LOAN_ALIGN) % LOAN_ALIGN);
+    buffer.truncate(size);
+    Self {
+      writer,
+      encoding,
+      buffer,
+    }
+  }
+
+  /// The data representation in which the payload is to be written.
+  pub fn encoding(&self) -> RepresentationIdentifier {
+    self.encoding
+  }
+
+  pub(crate) fn writer(&self) -> GUID {
+    self.writer
+  }
+
+  // The buffer becomes the payload without copying.
+  pub(crate) fn into_payload(self) -> SerializedPayload {
+    SerializedPayload::new_from_bytes(self.encoding, self.buffer.freeze())
+  }
+}
+
+impl Deref for SampleLoan {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    &self.buffer
+  }
+}
+
+impl DerefMut for SampleLoan {
+  fn deref_mut(&mut self) -> &mut [u8] {
+    &mut self.buffer
+  }
+}
+
+impl fmt::Debug for SampleLoan {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("SampleLoan")
+      .field("writer", &self.writer)
+      .field("encoding", &self.encoding)
+      .field("len", &self.buffer.len())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde::Serialize;
+
+  use super::*;
+  use crate::{
+    dds::traits::serde_adapters::no_key::{DeserializerAdapter, SerializerAdapter},
+    serialization::CDRSerializerAdapter,
+  };
+
+  #[repr(C)]
+  #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+  struct Point {
+    id: u32,
+    flags: [u8; 4],
+    x: f64,
+    y: f64,
+  }
+
+  unsafe impl Pod for Point {}
+
+  const POINT: Point = Point {
+    id: 7,
+    flags: [1, 2, 3, 4],
+    x: 1.5,
+    y: -2.0,
+  };
+
+  // The CDR payload of POINT, at the given offset from 8-byte alignment
+  fn payload_at(offset: usize) -> Bytes {
+    let cdr = <CDRSerializerAdapter<Point> as SerializerAdapter<Point>>::to_bytes(&POINT).unwrap();
+    let mut buffer = vec![0; cdr.len() + 16];
+    let start = (8 - buffer.as_ptr() as usize % 8) % 8 + offset;
+    buffer[start..start + cdr.len()].copy_from_slice(&cdr);
+    // No reallocation, because the length is the capacity.
+    Bytes::from(buffer).slice(start..start + cdr.len())
+  }
+
+  #[test]
+  fn loaned_pod_samples() {
+    assert_eq!(size_of::<Point>(), 24);
+
+    let aligned = payload_at(0);
+    assert_eq!(aligned.as_ptr() as usize % 8, 0);
+    let loaned = LoanAdapter::<Point>::from_payload(&aligned, NATIVE_CDR[0]).unwrap();
+    assert!(loaned.is_zero_copy());
+    assert_eq!(*loaned, POINT);
+    // The loan points into the payload.
+    assert_eq!(&*loaned as *const Point as *const u8, aligned.as_ptr());
+
+    let misaligned = payload_at(4);
+    let loaned = LoanAdapter::<Point>::from_payload(&misaligned, NATIVE_CDR[0]).unwrap();
+    assert!(!loaned.is_zero_copy());
+    assert_eq!(*loaned, POINT);
+
+    // Wrong size or byte order
+    let short = aligned.slice(..20);
+    assert!(LoanAdapter::<Point>::from_payload(&short, NATIVE_CDR[0]).is_err());
+    let other_order = if NATIVE_CDR[0] == RepresentationIdentifier::CDR_LE {
+      RepresentationIdentifier::CDR_BE
+    } else {
+      RepresentationIdentifier::CDR_LE
+    };
+    assert!(LoanAdapter::<Point>::from_payload(&aligned, other_order).is_err());
+
+    // Deserialized by another adapter, it is a copy.
+    let copied =
+      CDRDeserializerAdapter::<Loaned<Point>>::from_bytes(&aligned, NATIVE_CDR[0]).unwrap();
+    assert!(!copied.is_zero_copy());
+    assert_eq!(*copied, POINT);
+  }
+
+  #[test]
+  fn sample_loan_to_loaned() {
+    let cdr = <CDRSerializerAdapter<Point> as SerializerAdapter<Point>>::to_bytes(&POINT).unwrap();
+    let mut loan = SampleLoan::new(GUID::GUID_UNKNOWN, cdr.len(), NATIVE_CDR[0]);
+    assert_eq!(loan.as_ptr() as usize % LOAN_ALIGN, 0);
+    assert!(loan.iter().all(|b| *b == 0));
+    loan.copy_from_slice(&cdr);
+    let buffer = loan.as_ptr();
+
+    // The reader side gets the same buffer.
+    let payload = loan.into_payload();
+    assert_eq!(payload.value.as_ptr(), buffer);
+    let loaned =
+      LoanAdapter::<Point>::from_payload(&payload.value, payload.representation_identifier)
+        .unwrap();
+    assert!(loaned.is_zero_copy());
+    assert_eq!(*loaned, POINT);
+
+    let empty = SampleLoan::new(GUID::GUID_UNKNOWN, 0, NATIVE_CDR[0]);
+    assert!(empty.is_empty());
+  }
+}
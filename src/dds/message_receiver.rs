@@ -1,17 +1,25 @@
-use std::collections::{btree_map::Entry, BTreeMap};
+use std::{
+  collections::{btree_map::Entry, BTreeMap, BTreeSet},
+  net::{IpAddr, SocketAddr},
+};
 
 use mio_extras::{channel as mio_channel, channel::TrySendError};
 use log::{debug, info, trace, warn};
 use bytes::Bytes;
+use enumflags2::BitFlags;
 
 use crate::{
-  dds::reader::Reader,
+  dds::{
+    compatibility::{self, ProtocolCompatibility, VendorWorkaround},
+    pipeline_tracing,
+    reader::Reader,
+  },
   messages::{
     protocol_version::ProtocolVersion,
     submessages::submessages::{EntitySubmessage, *},
     vendor_id::VendorId,
   },
-  serialization::{submessage::SubmessageBody, Message},
+  serialization::{submessage::SubmessageBody, Message, MessageError, RTPS_MESSAGE_HEADER_SIZE},
   structure::{
     entity::RTPSEntity,
     guid::{EntityId, GuidPrefix, GUID},
@@ -19,6 +27,8 @@ use crate::{
     time::Timestamp,
   },
 };
+#[cfg(feature = "security")]
+use crate::security::plugins::CryptographyHandle;
 #[cfg(test)]
 use crate::dds::ddsdata::DDSData;
 #[cfg(test)]
@@ -26,7 +36,10 @@ use crate::structure::cache_change::CacheChange;
 #[cfg(test)]
 use crate::structure::sequence_number::SequenceNumber;
 
-const RTPS_MESSAGE_HEADER_SIZE: usize = 20;
+// ACKNACK or NACK_FRAG for a local Writer, with the GuidPrefix of the sending
+// participant, and the unicast locators where the Reader wants replies, if it
+// told them with INFO_REPLY.
+pub(crate) type AckSubmessageFrom = (GuidPrefix, AckSubmessage, Vec<Locator>);
 
 /// [`MessageReceiver`] is the submessage sequence interpreter described in
 /// RTPS spec v2.3 Section 8.3.4 "The RTPS Message Receiver".
@@ -39,7 +52,7 @@ pub(crate) struct MessageReceiver {
   pub available_readers: BTreeMap<EntityId, Reader>,
   // GuidPrefix sent in this channel needs to be RTPSMessage source_guid_prefix. Writer needs this
   // to locate RTPSReaderProxy if negative acknack.
-  acknack_sender: mio_channel::SyncSender<(GuidPrefix, AckSubmessage)>,
+  acknack_sender: mio_channel::SyncSender<AckSubmessageFrom>,
   // We send notification of remote DomainPArticiapnt liveness to Discovery to
   // bypass Reader. DDSCache, DatasampleCache, and DataReader, because thse will drop
   // reperated messages with duplicate SequenceNumbers, but Discovery needs to see them.
@@ -56,12 +69,60 @@ pub(crate) struct MessageReceiver {
 
   pos: usize,
   pub submessage_count: usize,
+
+  pub dropped_messages: DroppedMessages,
+
+  // Addresses that known remote participants announced in SPDP, if submessages
+  // are accepted only from those. See validate_source_addresses().
+  participant_addresses: Option<BTreeMap<GuidPrefix, BTreeSet<IpAddr>>>,
+  // Where the message being handled came from, if the transport knows it
+  source_address: Option<IpAddr>,
+
+  compatibility: ProtocolCompatibility,
+
+  // DDS Security protection of incoming messages
+  #[cfg(feature = "security")]
+  cryptography: Option<CryptographyHandle>,
+}
+
+// Counts of received messages that were dropped, because they could not be
+// parsed, or DDS Security could not decode or verify them. A rising count
+// means a broken or hostile sender.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DroppedMessages {
+  pub too_short: u64,
+  pub invalid_header: u64,
+  pub malformed_submessage: u64,
+  pub undecodable: u64,
+  // Submessages, rather than messages, from an address that their participant
+  // has not announced
+  pub spoofed: u64,
+}
+
+impl DroppedMessages {
+  fn count(&mut self, error: &MessageError) {
+    match error {
+      MessageError::TooShort(_) => self.too_short += 1,
+      MessageError::Header(_) | MessageError::InvalidHeader(_) => self.invalid_header += 1,
+      MessageError::TruncatedSubmessageHeader { .. }
+      | MessageError::SubmessageLength { .. }
+      | MessageError::Submessage { .. } => self.malformed_submessage += 1,
+    }
+  }
+
+  pub fn total(&self) -> u64 {
+    self.too_short
+      + self.invalid_header
+      + self.malformed_submessage
+      + self.undecodable
+      + self.spoofed
+  }
 }
 
 impl MessageReceiver {
   pub fn new(
     participant_guid_prefix: GuidPrefix,
-    acknack_sender: mio_channel::SyncSender<(GuidPrefix, AckSubmessage)>,
+    acknack_sender: mio_channel::SyncSender<AckSubmessageFrom>,
     spdp_liveness_sender: mio_channel::SyncSender<GuidPrefix>,
   ) -> Self {
     Self {
@@ -74,15 +135,77 @@ impl MessageReceiver {
       source_vendor_id: VendorId::VENDOR_UNKNOWN,
       source_guid_prefix: GuidPrefix::UNKNOWN,
       dest_guid_prefix: GuidPrefix::UNKNOWN,
-      unicast_reply_locator_list: vec![Locator::Invalid],
-      multicast_reply_locator_list: vec![Locator::Invalid],
+      unicast_reply_locator_list: Vec::new(),
+      multicast_reply_locator_list: Vec::new(),
       source_timestamp: None,
 
       pos: 0,
       submessage_count: 0,
+
+      dropped_messages: DroppedMessages::default(),
+
+      participant_addresses: None,
+      source_address: None,
+
+      compatibility: ProtocolCompatibility::default(),
+
+      #[cfg(feature = "security")]
+      cryptography: None,
+    }
+  }
+
+  // Anti-spoofing: from now on, submessages that claim to come from a known
+  // remote participant are dropped, unless they come from an address that the
+  // participant announced. Messages from the local host, and those whose
+  // source the transport does not know, are accepted.
+  pub fn validate_source_addresses(&mut self) {
+    self.participant_addresses = Some(BTreeMap::new());
+  }
+
+  // The participant announced these locators in SPDP.
+  pub fn set_participant_addresses<'a>(
+    &mut self,
+    guid_prefix: GuidPrefix,
+    locators: impl IntoIterator<Item = &'a Locator>,
+  ) {
+    if let Some(participant_addresses) = &mut self.participant_addresses {
+      let addresses = locators
+        .into_iter()
+        .filter(|locator| locator.is_udp())
+        .map(|locator| SocketAddr::from(*locator).ip())
+        .collect();
+      participant_addresses.insert(guid_prefix, addresses);
+    }
+  }
+
+  pub fn remove_participant_addresses(&mut self, guid_prefix: GuidPrefix) {
+    if let Some(participant_addresses) = &mut self.participant_addresses {
+      participant_addresses.remove(&guid_prefix);
+    }
+  }
+
+  fn source_address_is_valid(&self) -> bool {
+    let source_address = match self.source_address {
+      Some(address) if !address.is_loopback() => address,
+      _ => return true,
+    };
+    match &self.participant_addresses {
+      Some(participant_addresses) => participant_addresses
+        .get(&self.source_guid_prefix)
+        .map_or(true, |addresses| addresses.contains(&source_address)),
+      None => true,
     }
   }
 
+  pub fn set_protocol_compatibility(&mut self, compatibility: ProtocolCompatibility) {
+    self.compatibility = compatibility;
+  }
+
+  #[cfg(feature = "security")]
+  pub fn set_cryptography(&mut self, cryptography: CryptographyHandle) {
+    self.cryptography = Some(cryptography);
+  }
+
   pub fn reset(&mut self) {
     self.source_version = ProtocolVersion::THIS_IMPLEMENTATION;
     self.source_vendor_id = VendorId::VENDOR_UNKNOWN;
@@ -91,6 +214,7 @@ impl MessageReceiver {
     self.unicast_reply_locator_list.clear();
     self.multicast_reply_locator_list.clear();
     self.source_timestamp = None;
+    self.source_address = None;
 
     self.pos = 0;
     self.submessage_count = 0;
@@ -103,9 +227,17 @@ impl MessageReceiver {
       unicast_reply_locator_list: self.unicast_reply_locator_list.clone(),
       multicast_reply_locator_list: self.multicast_reply_locator_list.clone(),
       source_timestamp: self.source_timestamp,
+      source_workarounds: self.source_workarounds(),
     }
   }
 
+  fn source_workarounds(&self) -> BitFlags<VendorWorkaround> {
+    self
+      .compatibility
+      .vendor_workarounds
+      .of_vendor(self.source_vendor_id)
+  }
+
   pub fn add_reader(&mut self, new_reader: Reader) {
     let eid = new_reader.guid().entity_id;
     match self.available_readers.entry(eid) {
@@ -175,44 +307,75 @@ impl MessageReceiver {
   //   self.handle_user_msg(msg);
   // }
 
-  pub fn handle_received_packet(&mut self, msg_bytes: &Bytes) {
+  // `source_address` is where the packet came from, if the transport knows it.
+  pub fn handle_received_packet(&mut self, msg_bytes: &Bytes, source_address: Option<IpAddr>) {
+    let _span = pipeline_tracing::receive(msg_bytes.len(), source_address);
     // Check for RTPS ping message. At least RTI implementation sends these.
     // What should we do with them? The spec does not say.
-    if msg_bytes.len() < RTPS_MESSAGE_HEADER_SIZE {
-      if msg_bytes.len() >= 16
-        && msg_bytes[0..4] == b"RTPS"[..]
-        && msg_bytes[9..16] == b"DDSPING"[..]
-      {
-        // TODO: Add some sensible ping message handling here.
-        info!("Received RTPS PING. Do not know how to respond.");
-        debug!("Data was {:?}", &msg_bytes);
-      } else {
-        warn!("Message is shorter than header. Cannot deserialize.");
-        debug!("Data was {:?}", &msg_bytes);
-      }
+    if msg_bytes.len() < RTPS_MESSAGE_HEADER_SIZE
+      && msg_bytes.len() >= 16
+      && msg_bytes[0..4] == b"RTPS"[..]
+      && msg_bytes[9..16] == b"DDSPING"[..]
+    {
+      // TODO: Add some sensible ping message handling here.
+      info!("Received RTPS PING. Do not know how to respond.");
+      debug!("Data was {:?}", &msg_bytes);
       return;
     }
 
+    #[cfg(feature = "security")]
+    let decoded;
+    #[cfg(feature = "security")]
+    let msg_bytes = match &self.cryptography {
+      Some(cryptography) => match cryptography.decode_message(msg_bytes) {
+        Ok(bytes) => {
+          decoded = Bytes::from(bytes);
+          &decoded
+        }
+        Err(e) => {
+          self.dropped_messages.undecodable += 1;
+          debug!(
+            "Dropped RTPS message that cannot be decoded ({} so far): {}",
+            self.dropped_messages.total(),
+            e
+          );
+          return;
+        }
+      },
+      None => msg_bytes,
+    };
+
     // call Speedy reader
     // Bytes .clone() is cheap, so no worries
-    let rtps_message = match Message::read_from_buffer(msg_bytes) {
+    let parsed = {
+      let _span = pipeline_tracing::parse();
+      Message::read_from_buffer(msg_bytes)
+    };
+    let rtps_message = match parsed {
       Ok(m) => m,
-      Err(speedy_err) => {
-        warn!("RTPS deserialize error {:?}", speedy_err);
+      Err(e) => {
+        self.dropped_messages.count(&e);
+        warn!(
+          "Dropped malformed RTPS message ({} so far): {}",
+          self.dropped_messages.total(),
+          e
+        );
         debug!("Data was {:?}", msg_bytes);
         return;
       }
     };
 
     // And process message
-    self.handle_parsed_message(rtps_message);
+    self.handle_parsed_message(rtps_message, source_address);
   }
 
-  // This is also called directly from dp_event_loop in case of loopback messages.
-  pub fn handle_parsed_message(&mut self, rtps_message: Message) {
+  pub fn handle_parsed_message(&mut self, rtps_message: Message, source_address: Option<IpAddr>) {
     self.reset();
+    self.source_address = source_address;
     self.dest_guid_prefix = self.own_guid_prefix;
     self.source_guid_prefix = rtps_message.header.guid_prefix;
+    self.source_version = rtps_message.header.protocol_version;
+    self.source_vendor_id = rtps_message.header.vendor_id;
 
     for submessage in rtps_message.submessages {
       match submessage.body {
@@ -230,10 +393,24 @@ impl MessageReceiver {
         self.dest_guid_prefix, self.own_guid_prefix);
       return;
     }
+    if !self.source_address_is_valid() {
+      self.dropped_messages.spoofed += 1;
+      debug!(
+        "Dropped submessage from {:?} that came from unannounced address {:?}",
+        self.source_guid_prefix, self.source_address
+      );
+      return;
+    }
 
     let mr_state = self.give_message_receiver_info();
     match submessage {
-      EntitySubmessage::Data(data, data_flags) => {
+      EntitySubmessage::Data(mut data, data_flags) => {
+        compatibility::adapt_inline_qos(mr_state.source_workarounds, &mut data.inline_qos);
+        if data.writer_id == EntityId::SPDP_BUILTIN_PARTICIPANT_WRITER {
+          if let Some(payload) = &mut data.serialized_payload {
+            compatibility::adapt_spdp_payload(mr_state.source_workarounds, payload);
+          }
+        }
         let writer_entity_id = data.writer_id;
         let source_guid_prefix = mr_state.source_guid_prefix;
         // If reader_id == UNKNOWN, message should be sent to all matched
@@ -300,7 +477,8 @@ impl MessageReceiver {
           );
         }
       }
-      EntitySubmessage::Gap(gap, _flags) => {
+      EntitySubmessage::Gap(mut gap, _flags) => {
+        self.compatibility.adapt_gap(self.source_version, &mut gap);
         if let Some(target_reader) = self.reader_mut(gap.reader_id) {
           target_reader.handle_gap_msg(&gap, &mr_state);
         }
@@ -308,10 +486,11 @@ impl MessageReceiver {
       EntitySubmessage::AckNack(acknack, _) => {
         // Note: This must not block, because the receiving end is the same thread,
         // i.e. blocking here is an instant deadlock.
-        match self
-          .acknack_sender
-          .try_send((self.source_guid_prefix, AckSubmessage::AckNack(acknack)))
-        {
+        match self.acknack_sender.try_send((
+          self.source_guid_prefix,
+          AckSubmessage::AckNack(acknack),
+          self.unicast_reply_locator_list.clone(),
+        )) {
           Ok(_) => (),
           Err(TrySendError::Full(_)) => {
             info!("AckNack pipe full. Looks like I am very busy. Discarding submessage.");
@@ -319,8 +498,19 @@ impl MessageReceiver {
           Err(e) => warn!("AckNack pipe fail: {:?}", e),
         }
       }
-      EntitySubmessage::DataFrag(datafrag, flags) => {
-        if let Some(target_reader) = self.reader_mut(datafrag.reader_id) {
+      EntitySubmessage::DataFrag(mut datafrag, flags) => {
+        compatibility::adapt_inline_qos(mr_state.source_workarounds, &mut datafrag.inline_qos);
+        // If reader_id == UNKNOWN, message should be sent to all matched
+        // readers
+        if datafrag.reader_id == EntityId::UNKNOWN {
+          for reader in self
+            .available_readers
+            .values_mut()
+            .filter(|p| p.contains_writer(datafrag.writer_id))
+          {
+            reader.handle_datafrag_msg(&datafrag, flags, &mr_state);
+          }
+        } else if let Some(target_reader) = self.reader_mut(datafrag.reader_id) {
           target_reader.handle_datafrag_msg(&datafrag, flags, &mr_state);
         }
       }
@@ -352,23 +542,29 @@ impl MessageReceiver {
         self.source_timestamp = ts_struct.timestamp;
       }
       InterpreterSubmessage::InfoSource(info_src, _flags) => {
+        // The following submessages come from another participant, e.g. relayed
+        // by a router. RTPS spec v2.5 Section "8.3.7.9.4 InfoSource": This
+        // resets what was known about the source.
         self.source_guid_prefix = info_src.guid_prefix;
         self.source_version = info_src.protocol_version;
         self.source_vendor_id = info_src.vendor_id;
-        self.unicast_reply_locator_list.clear(); // Or invalid?
-        self.multicast_reply_locator_list.clear(); // Or invalid?
+        self.unicast_reply_locator_list.clear();
+        self.multicast_reply_locator_list.clear();
         self.source_timestamp = None;
       }
-      InterpreterSubmessage::InfoReply(info_reply, flags) => {
-        self.unicast_reply_locator_list = info_reply.unicast_locator_list;
-        if flags.contains(INFOREPLY_Flags::Multicast) {
-          self.multicast_reply_locator_list = info_reply
-            .multicast_locator_list
-            .expect("InfoReply flag indicates multicast locator is present but none found.");
-        // TODO: Convert the above error to warning only.
-        } else {
-          self.multicast_reply_locator_list.clear();
-        }
+      InterpreterSubmessage::InfoReply(info_reply, _flags) => {
+        // Replies to the following submessages go here, instead of the
+        // locators known from Discovery. Empty lists mean that those are
+        // used again. The flags were already used when parsing.
+        let valid = |locators: Vec<Locator>| -> Vec<Locator> {
+          locators
+            .into_iter()
+            .filter(|loc| !matches!(loc, Locator::Invalid | Locator::Reserved))
+            .collect()
+        };
+        self.unicast_reply_locator_list = valid(info_reply.unicast_locator_list);
+        self.multicast_reply_locator_list =
+          valid(info_reply.multicast_locator_list.unwrap_or_default());
       }
       InterpreterSubmessage::InfoDestination(info_dest, _flags) => {
         if info_dest.guid_prefix == GUID::GUID_UNKNOWN.prefix {
@@ -403,6 +599,8 @@ pub struct MessageReceiverState {
   pub unicast_reply_locator_list: Vec<Locator>,
   pub multicast_reply_locator_list: Vec<Locator>,
   pub source_timestamp: Option<Timestamp>,
+  // Workarounds for the vendor of the source
+  pub source_workarounds: BitFlags<VendorWorkaround>,
 }
 
 impl Default for MessageReceiverState {
@@ -412,6 +610,7 @@ impl Default for MessageReceiverState {
       unicast_reply_locator_list: Vec::default(),
       multicast_reply_locator_list: Vec::default(),
       source_timestamp: Some(Timestamp::INVALID),
+      source_workarounds: BitFlags::empty(),
     }
   }
 }
@@ -420,7 +619,7 @@ impl Default for MessageReceiverState {
 mod tests {
   use std::{
     rc::Rc,
-    sync::{Arc, RwLock},
+    sync::{atomic::AtomicI64, Arc, RwLock},
   };
 
   use speedy::{Readable, Writable};
@@ -431,6 +630,7 @@ mod tests {
 
   use crate::{
     dds::{
+      helpers::WakerSlot,
       qos::QosPolicies,
       reader::ReaderIngredients,
       statusevents::DataReaderStatus,
@@ -439,7 +639,7 @@ mod tests {
       writer::{Writer, WriterCommand, WriterIngredients},
     },
     messages::header::Header,
-    network::udp_sender::UDPSender,
+    network::{transport::Transports, udp_sender::UDPSender},
     serialization::{cdr_deserializer::deserialize_from_little_endian, cdr_serializer::to_bytes},
     structure::{dds_cache::DDSCache, guid::EntityKind, sequence_number::SequenceNumber},
   };
@@ -468,8 +668,7 @@ mod tests {
       0x01, 0x03, 0x00, 0x0c, 0x29, 0x2d, 0x31, 0xa2, 0x28, 0x20, 0x02, 0x8,
     ]);
 
-    let (acknack_sender, _acknack_receiver) =
-      mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(10);
+    let (acknack_sender, _acknack_receiver) = mio_channel::sync_channel::<AckSubmessageFrom>(10);
     let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
     let mut message_receiver =
       MessageReceiver::new(gui_prefix, acknack_sender, spdp_liveness_sender);
@@ -492,6 +691,7 @@ mod tests {
     let reader_ing = ReaderIngredients {
       guid: new_guid,
       notification_sender: send,
+      data_reader_waker: WakerSlot::default(),
       status_sender,
       topic_name: "test".to_string(),
       qos_policy: QosPolicies::qos_none(),
@@ -501,15 +701,15 @@ mod tests {
     let new_reader = Reader::new(
       reader_ing,
       dds_cache,
-      Rc::new(UDPSender::new_with_random_port().unwrap()),
-      mio_extras::timer::Builder::default().build(),
+      Rc::new(Transports::from(UDPSender::new_with_random_port().unwrap())),
+      crate::dds::timer::Timer::default(),
     );
 
     // Skip for now+
     //new_reader.matched_writer_add(remote_writer_guid, mr_state);
     message_receiver.add_reader(new_reader);
 
-    message_receiver.handle_received_packet(&udp_bits1);
+    message_receiver.handle_received_packet(&udp_bits1, None);
 
     assert_eq!(message_receiver.submessage_count, 4);
 
@@ -553,13 +753,19 @@ mod tests {
       topic_name: String::from("topicName1"),
       qos_policies: QosPolicies::qos_none(),
       status_sender,
+      acked_by_all_before: Arc::new(AtomicI64::new(i64::MAX)),
+      ack_readiness: mio::Registration::new2().1,
+      ack_waker: WakerSlot::default(),
+      reader_filters: Arc::new(RwLock::new(BTreeMap::new())),
+      dynamic_type: None,
+      flow_controller: None,
     };
 
     let mut _writer_object = Writer::new(
       writer_ing,
       Arc::new(RwLock::new(DDSCache::new())),
-      Rc::new(UDPSender::new_with_random_port().unwrap()),
-      mio_extras::timer::Builder::default().build(),
+      Rc::new(Transports::from(UDPSender::new_with_random_port().unwrap())),
+      crate::dds::timer::Timer::default(),
     );
     let mut change = message_receiver.get_reader_and_history_cache_change_object(
       new_guid.entity_id,
@@ -592,19 +798,160 @@ mod tests {
     ]);
 
     let guid_new = GUID::default();
-    let (acknack_sender, _acknack_receiver) =
-      mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(10);
+    let (acknack_sender, _acknack_receiver) = mio_channel::sync_channel::<AckSubmessageFrom>(10);
     let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
     let mut message_receiver =
       MessageReceiver::new(guid_new.prefix, acknack_sender, spdp_liveness_sender);
 
-    message_receiver.handle_received_packet(&udp_bits1);
+    message_receiver.handle_received_packet(&udp_bits1, None);
     assert_eq!(message_receiver.submessage_count, 4);
 
-    message_receiver.handle_received_packet(&udp_bits2);
+    message_receiver.handle_received_packet(&udp_bits2, None);
     assert_eq!(message_receiver.submessage_count, 2);
   }
 
+  #[test]
+  fn mr_test_dropped_messages() {
+    let (acknack_sender, _acknack_receiver) = mio_channel::sync_channel::<AckSubmessageFrom>(10);
+    let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
+    let mut message_receiver =
+      MessageReceiver::new(GuidPrefix::UNKNOWN, acknack_sender, spdp_liveness_sender);
+
+    let header = Header::new(GuidPrefix::UNKNOWN).write_to_vec().unwrap();
+    let mut not_rtps = header.clone();
+    not_rtps[0] = b'X';
+    // ACKNACK with octetsToNextHeader pointing past the end
+    let mut too_long = header.clone();
+    too_long.extend_from_slice(&[0x06, 0x01, 0x18, 0x00, 0x00, 0x00]);
+
+    message_receiver.handle_received_packet(&Bytes::from_static(b"RTPS"), None);
+    message_receiver.handle_received_packet(&Bytes::from(not_rtps), None);
+    message_receiver.handle_received_packet(&Bytes::from(too_long), None);
+    // A valid message without submessages
+    message_receiver.handle_received_packet(&Bytes::from(header), None);
+
+    assert_eq!(
+      message_receiver.dropped_messages,
+      DroppedMessages {
+        too_short: 1,
+        invalid_header: 1,
+        malformed_submessage: 1,
+        undecodable: 0,
+        spoofed: 0,
+      }
+    );
+    assert_eq!(message_receiver.dropped_messages.total(), 3);
+  }
+
+  #[test]
+  fn mr_test_info_source_and_reply() {
+    use enumflags2::BitFlags;
+    use speedy::Endianness;
+
+    use crate::structure::sequence_number::SequenceNumberSet;
+
+    let (acknack_sender, acknack_receiver) = mio_channel::sync_channel::<AckSubmessageFrom>(10);
+    let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
+    let mut message_receiver =
+      MessageReceiver::new(GuidPrefix::UNKNOWN, acknack_sender, spdp_liveness_sender);
+
+    // A router relays an ACKNACK from another participant, which wants replies
+    // to a specific address.
+    let router = GuidPrefix::new(&[1; 12]);
+    let reader = GuidPrefix::new(&[2; 12]);
+    let reply_locator = Locator::from("10.0.0.2:7411".parse::<std::net::SocketAddr>().unwrap());
+    let acknack = AckNack {
+      reader_id: EntityId::UNKNOWN,
+      writer_id: EntityId::UNKNOWN,
+      reader_sn_state: SequenceNumberSet::new_empty(SequenceNumber::new(1)),
+      count: 1,
+    };
+    let mut message = Message::new(Header::new(router));
+    message.add_submessage(
+      InfoSource {
+        protocol_version: ProtocolVersion::THIS_IMPLEMENTATION,
+        vendor_id: VendorId::VENDOR_UNKNOWN,
+        guid_prefix: reader,
+      }
+      .create_submessage(BitFlags::<INFOSOURCE_Flags>::from_endianness(
+        Endianness::LittleEndian,
+      )),
+    );
+    message.add_submessage(
+      InfoReply {
+        unicast_locator_list: vec![reply_locator, Locator::Invalid],
+        multicast_locator_list: None,
+      }
+      .create_submessage(Endianness::LittleEndian),
+    );
+    message.add_submessage(acknack.clone().create_submessage(
+      BitFlags::<ACKNACK_Flags>::from_endianness(Endianness::LittleEndian),
+    ));
+    let bytes = message
+      .write_to_vec_with_ctx(Endianness::LittleEndian)
+      .unwrap();
+
+    message_receiver.handle_received_packet(&Bytes::from(bytes), None);
+
+    let (source, submessage, reply_locators) = acknack_receiver.try_recv().unwrap();
+    assert_eq!(source, reader);
+    assert!(matches!(submessage, AckSubmessage::AckNack(an) if an == acknack));
+    assert_eq!(reply_locators, vec![reply_locator]);
+  }
+
+  #[test]
+  fn mr_test_validate_source_addresses() {
+    use enumflags2::BitFlags;
+    use speedy::Endianness;
+
+    use crate::structure::sequence_number::SequenceNumberSet;
+
+    let (acknack_sender, acknack_receiver) = mio_channel::sync_channel::<AckSubmessageFrom>(10);
+    let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
+    let mut message_receiver =
+      MessageReceiver::new(GuidPrefix::UNKNOWN, acknack_sender, spdp_liveness_sender);
+    message_receiver.validate_source_addresses();
+
+    let remote = GuidPrefix::new(&[3; 12]);
+    let announced = Locator::from("10.0.0.2:7411".parse::<std::net::SocketAddr>().unwrap());
+    message_receiver.set_participant_addresses(remote, &[announced]);
+
+    let mut message = Message::new(Header::new(remote));
+    message.add_submessage(
+      AckNack {
+        reader_id: EntityId::UNKNOWN,
+        writer_id: EntityId::UNKNOWN,
+        reader_sn_state: SequenceNumberSet::new_empty(SequenceNumber::new(1)),
+        count: 1,
+      }
+      .create_submessage(BitFlags::<ACKNACK_Flags>::from_endianness(
+        Endianness::LittleEndian,
+      )),
+    );
+    let bytes = Bytes::from(
+      message
+        .write_to_vec_with_ctx(Endianness::LittleEndian)
+        .unwrap(),
+    );
+
+    // Forged source address
+    message_receiver.handle_received_packet(&bytes, "10.0.0.66".parse().ok());
+    assert!(acknack_receiver.try_recv().is_err());
+    assert_eq!(message_receiver.dropped_messages.spoofed, 1);
+
+    // Announced address, local host, and unknown source are accepted
+    for source in &["10.0.0.2".parse().ok(), "127.0.0.1".parse().ok(), None] {
+      message_receiver.handle_received_packet(&bytes, *source);
+      assert!(acknack_receiver.try_recv().is_ok());
+    }
+
+    // A participant that has not been discovered may send from anywhere
+    message_receiver.remove_participant_addresses(remote);
+    message_receiver.handle_received_packet(&bytes, "10.0.0.66".parse().ok());
+    assert!(acknack_receiver.try_recv().is_ok());
+    assert_eq!(message_receiver.dropped_messages.spoofed, 1);
+  }
+
   #[test]
   fn mr_test_header() {
     let guid_new = GUID::default();
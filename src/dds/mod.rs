@@ -2,12 +2,26 @@
 //! crate top level and modules [`no_key`](crate::no_key) and
 //! [`with_key`](crate::with_key).
 
+pub(crate) mod blob_transfer;
+pub(crate) mod byte_stream;
+pub(crate) mod clock;
+pub(crate) mod compatibility;
+pub(crate) mod contentfilteredtopic;
 mod datasample_cache;
 pub(crate) mod ddsdata;
 mod dp_event_loop;
+pub(crate) mod dynamic_data;
+pub(crate) mod flow_controller;
+#[cfg(feature = "fragmentation")]
 mod fragment_assembler;
-mod helpers;
+pub(crate) mod health;
+pub(crate) mod helpers;
+#[cfg(feature = "json")]
+pub(crate) mod json_mirror;
+pub(crate) mod listener;
+pub(crate) mod loan;
 mod message_receiver;
+pub(crate) mod multitopic;
 pub mod sampleinfo;
 
 /// Participating in NoKey topics.
@@ -16,15 +30,33 @@ pub mod no_key;
 pub mod with_key;
 
 pub(crate) mod participant;
+pub(crate) mod participant_factory;
+pub(crate) mod pipeline_tracing;
 pub(crate) mod pubsub;
+#[cfg(feature = "qos_provider")]
+pub(crate) mod qos_provider;
+pub(crate) mod query_expression;
+pub(crate) mod querycondition;
 pub(crate) mod readcondition;
 pub(crate) mod reader;
+mod receive_loop;
+pub(crate) mod recording;
+pub(crate) mod request_reply;
 pub(crate) mod rtps_reader_proxy;
 pub(crate) mod rtps_writer_proxy;
+pub(crate) mod runtime_metrics;
+pub(crate) mod simulation;
+pub(crate) mod statistics;
+pub(crate) mod threads;
+pub(crate) mod timer;
+#[cfg(feature = "tokio")]
+pub(crate) mod tokio_adapters;
 pub(crate) mod topic;
 pub mod traits;
+pub(crate) mod type_registry;
 pub(crate) mod typedesc;
 pub(crate) mod values;
+pub(crate) mod waitset;
 pub(crate) mod writer;
 
 // Public interface
@@ -59,12 +91,31 @@ pub mod data_types {
 }
 
 // DDS Error and Result types
-pub use participant::DomainParticipant;
+pub use health::{ParticipantHealth, ProtocolThread, ThreadHealth};
+#[cfg(feature = "statistics")]
+pub use statistics::{ParticipantStatistics, ReaderStatistics, WriterStatistics};
+pub use clock::{Clock, SystemClock};
+pub use simulation::SimulatedClock;
+pub use threads::{ParticipantDriver, ThreadConfig};
+pub use participant::{
+  DiscoveryListener, DomainParticipant, DomainParticipantBuilder, DomainParticipantStatusListener,
+  ParticipantBuiltinTopicReader, PublicationBuiltinTopicReader, SubscriptionBuiltinTopicReader,
+};
+pub use participant_factory::DomainParticipantFactory;
 pub use topic::{Topic, TopicKind};
-pub use pubsub::{Publisher, Subscriber};
+pub use pubsub::{GroupAccessReader, Publisher, Subscriber};
 
 pub use crate::dds::values::result::*;
 // Discovery results
-pub use crate::discovery::data_types::topic_data::{
-  DiscoveredTopicData, SubscriptionBuiltinTopicData,
+pub use crate::discovery::data_types::{
+  spdp_participant_data::SpdpDiscoveredParticipantData,
+  topic_data::{
+    DiscoveredReaderData, DiscoveredTopicData, DiscoveredWriterData, PublicationBuiltinTopicData,
+    ReaderProxy, SubscriptionBuiltinTopicData, WriterProxy,
+  },
+};
+pub use crate::discovery::{
+  authentication::ParticipantAuthenticator,
+  static_endpoint::StaticEndpoint,
+  type_lookup::{EquivalenceHash, TypeIdentifier, TypeObject},
 };
@@ -0,0 +1,597 @@
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  fmt::Debug,
+  io,
+  marker::PhantomData,
+};
+
+use mio::{Evented, Poll, PollOpt, Ready, Token};
+use serde::{de::DeserializeOwned, Serialize};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::{
+  dds::{
+    participant::{DomainParticipant, DomainParticipantWeak},
+    pubsub::Subscriber,
+    qos::QosPolicies,
+    query_expression::{FieldValue, SubscriptionExpression},
+    readcondition::ReadCondition,
+    topic::{validate_topic_name, validate_type_name, Topic, TopicDescription},
+    traits::{
+      key::{InstanceHandle, Key, Keyed},
+      serde_adapters::with_key::DeserializerAdapter,
+    },
+    typedesc::TypeDesc,
+    values::result::{Error, Result},
+    with_key::datareader::DataReader,
+  },
+  serialization::CDRDeserializerAdapter,
+};
+
+/// A virtual Topic, whose samples are combined from the samples of several
+/// Topics.
+///
+/// The combination is given by a subscription expression, e.g.
+/// `"SELECT flight_id, x, y, destination FROM Position NATURAL JOIN Plan
+/// WHERE x > %0"`. The samples of the Topics after `FROM` are joined by the
+/// members of the same name, usually the key members. That is, a combined
+/// sample is formed of each set of samples, one from each Topic, that agree
+/// on the values of their common members. The optional `WHERE` clause filters
+/// the combined samples, with the same syntax as a
+/// [`QueryCondition`](crate::QueryCondition). Finally, the members listed
+/// after `SELECT` form the resulting sample. A member can be renamed with
+/// `AS`, and `*` selects all members.
+///
+/// A MultiTopic is not announced in discovery. It is read with a
+/// [`MultiTopicDataReader`], which subscribes to each of the Topics and keeps
+/// the latest sample of each of their instances.
+///
+/// Create with
+/// [`DomainParticipant::create_multitopic`](crate::DomainParticipant::create_multitopic).
+///
+/// See DDS Specification 1.4 Section "2.2.2.3.4 MultiTopic Class".
+#[derive(Clone)]
+pub struct MultiTopic {
+  participant: DomainParticipantWeak,
+  name: String,
+  type_desc: TypeDesc,
+  subscription_expression: String,
+  expression: SubscriptionExpression,
+  expression_parameters: Vec<String>,
+  parameters: Vec<FieldValue>,
+}
+
+impl MultiTopic {
+  pub(crate) fn new(
+    participant: DomainParticipantWeak,
+    name: String,
+    type_name: String,
+    subscription_expression: &str,
+    expression_parameters: Vec<String>,
+  ) -> Result<Self> {
+    validate_topic_name(&name)?;
+    validate_type_name(&type_name)?;
+    let expression = SubscriptionExpression::parse(subscription_expression)?;
+    let needed = expression.parameter_count();
+    if expression_parameters.len() < needed {
+      return Error::bad_parameter(format!(
+        "Subscription expression {:?} needs {} parameters, got {}",
+        subscription_expression,
+        needed,
+        expression_parameters.len()
+      ));
+    }
+    Ok(Self {
+      participant,
+      name,
+      type_desc: TypeDesc::new(type_name),
+      subscription_expression: subscription_expression.to_string(),
+      expression,
+      parameters: expression_parameters
+        .iter()
+        .map(|p| FieldValue::from_parameter(p))
+        .collect(),
+      expression_parameters,
+    })
+  }
+
+  pub fn subscription_expression(&self) -> &str {
+    &self.subscription_expression
+  }
+
+  pub fn expression_parameters(&self) -> &[String] {
+    &self.expression_parameters
+  }
+
+  /// Names of the Topics that are combined, in the order of the subscription
+  /// expression.
+  pub fn related_topic_names(&self) -> &[String] {
+    &self.expression.topics
+  }
+
+  // Applies the WHERE and SELECT clauses to joined samples.
+  fn select(&self, joined: &FieldValue) -> Option<FieldValue> {
+    if let Some(filter) = &self.expression.filter {
+      if !filter.evaluate(joined, &self.parameters) {
+        return None;
+      }
+    }
+    match &self.expression.selection {
+      None => Some(joined.clone()),
+      Some(selection) => selection
+        .iter()
+        .map(|(path, alias)| {
+          joined
+            .member(path)
+            .map(|value| (alias.clone(), value.clone()))
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(FieldValue::Struct),
+    }
+  }
+}
+
+// Natural join of two samples: The members that both have must be equal. The
+// result has the members of both.
+fn natural_join(left: &FieldValue, right: &FieldValue) -> Option<FieldValue> {
+  match (left, right) {
+    (FieldValue::Struct(left_members), FieldValue::Struct(right_members)) => {
+      let mut members = left_members.clone();
+      for (name, value) in right_members {
+        match left_members.iter().find(|(left_name, _)| left_name == name) {
+          Some((_, left_value)) if left_value != value => return None,
+          Some(_) => {}
+          None => members.push((name.clone(), value.clone())),
+        }
+      }
+      Some(FieldValue::Struct(members))
+    }
+    _ => None,
+  }
+}
+
+impl TopicDescription for MultiTopic {
+  fn participant(&self) -> Option<DomainParticipant> {
+    self.participant.clone().upgrade()
+  }
+
+  fn get_type(&self) -> TypeDesc {
+    self.type_desc.clone()
+  }
+
+  fn name(&self) -> String {
+    self.name.clone()
+  }
+}
+
+impl Debug for MultiTopic {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("MultiTopic")
+      .field("name", &self.name)
+      .field("type_desc", &self.type_desc)
+      .field("subscription_expression", &self.subscription_expression)
+      .field("expression_parameters", &self.expression_parameters)
+      .finish()
+  }
+}
+
+// One of the Topics of a MultiTopic, read by a DataReader of its own type.
+trait JoinSource: Evented {
+  // Takes the newly received samples, and returns the instances that changed.
+  fn update(&mut self) -> Result<Vec<InstanceHandle>>;
+
+  // The latest sample of each alive instance
+  fn instances(&self) -> &BTreeMap<InstanceHandle, FieldValue>;
+
+  fn enable(&self) -> Result<()>;
+}
+
+struct KeyedSource<S, SA>
+where
+  S: Keyed + DeserializeOwned,
+  SA: DeserializerAdapter<S>,
+{
+  topic_name: String,
+  reader: DataReader<S, SA>,
+  instances: BTreeMap<InstanceHandle, FieldValue>,
+}
+
+impl<S, SA> JoinSource for KeyedSource<S, SA>
+where
+  S: Keyed + DeserializeOwned + Serialize + 'static,
+  <S as Keyed>::K: Key,
+  SA: DeserializerAdapter<S>,
+{
+  fn update(&mut self) -> Result<Vec<InstanceHandle>> {
+    let mut changed = Vec::new();
+    for sample in self.reader.take(usize::MAX, ReadCondition::any())? {
+      let handle = InstanceHandle::from(sample.key().hash_key());
+      match sample.value() {
+        Ok(data) => match FieldValue::from_sample(data) {
+          Ok(value) => {
+            self.instances.insert(handle, value);
+          }
+          Err(e) => {
+            warn!(
+              "MultiTopic source {:?}: cannot join sample: {}",
+              self.topic_name, e
+            );
+            continue;
+          }
+        },
+        // Disposed or unregistered
+        Err(_key) => {
+          self.instances.remove(&handle);
+        }
+      }
+      changed.push(handle);
+    }
+    Ok(changed)
+  }
+
+  fn instances(&self) -> &BTreeMap<InstanceHandle, FieldValue> {
+    &self.instances
+  }
+
+  fn enable(&self) -> Result<()> {
+    self.reader.enable()
+  }
+}
+
+impl<S, SA> Evented for KeyedSource<S, SA>
+where
+  S: Keyed + DeserializeOwned,
+  SA: DeserializerAdapter<S>,
+{
+  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    self.reader.register(poll, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &Poll,
+    token: Token,
+    interest: Ready,
+    opts: PollOpt,
+  ) -> io::Result<()> {
+    self.reader.reregister(poll, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &Poll) -> io::Result<()> {
+    self.reader.deregister(poll)
+  }
+}
+
+/// Reads combined samples of type `D` from a [`MultiTopic`].
+///
+/// Before any samples are produced, a source must be added with
+/// [`add_source`](Self::add_source) for each Topic of the subscription
+/// expression. This gives the type of the samples of that Topic.
+///
+/// `D` is deserialized from the selected members by their names, so it is a
+/// struct that has a member for each of them, or e.g. a map.
+///
+/// The MultiTopicDataReader can be registered to a mio `Poll`, to get
+/// notified of new data in any of the Topics. Add all sources before
+/// registering.
+///
+/// Create with
+/// [`Subscriber::create_multitopic_datareader`](crate::Subscriber::create_multitopic_datareader).
+///
+/// # Examples
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// # use rustdds::*;
+/// #
+/// let domain_participant = DomainParticipant::new(0).unwrap();
+/// let qos = QosPolicyBuilder::new().build();
+/// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Position { flight_id: i32, x: i32, y: i32 }
+/// impl Keyed for Position {
+///   type K = i32;
+///   fn key(&self) -> i32 { self.flight_id }
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Plan { flight_id: i32, destination: String }
+/// impl Keyed for Plan {
+///   type K = i32;
+///   fn key(&self) -> i32 { self.flight_id }
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Arrival { flight_id: i32, destination: String, distance: i32 }
+///
+/// let position_topic = domain_participant
+///   .create_topic("Position".to_string(), "Position".to_string(), &qos, TopicKind::WithKey)
+///   .unwrap();
+/// let plan_topic = domain_participant
+///   .create_topic("Plan".to_string(), "Plan".to_string(), &qos, TopicKind::WithKey)
+///   .unwrap();
+/// let arrivals = domain_participant
+///   .create_multitopic(
+///     "Arrivals".to_string(),
+///     "Arrival".to_string(),
+///     "SELECT flight_id, destination, x AS distance FROM Position NATURAL JOIN Plan \
+///      WHERE x < %0",
+///     vec!["100".to_string()],
+///   )
+///   .unwrap();
+///
+/// let mut reader = subscriber
+///   .create_multitopic_datareader::<Arrival>(&arrivals, None)
+///   .unwrap();
+/// reader.add_source::<Position, CDRDeserializerAdapter<_>>(&position_topic).unwrap();
+/// reader.add_source::<Plan, CDRDeserializerAdapter<_>>(&plan_topic).unwrap();
+///
+/// for arrival in reader.take().unwrap() {
+///   println!("{} is {} away from {}", arrival.flight_id, arrival.distance, arrival.destination);
+/// }
+/// ```
+pub struct MultiTopicDataReader<D> {
+  subscriber: Subscriber,
+  multitopic: MultiTopic,
+  qos: Option<QosPolicies>,
+  // Indexed as the Topics of the subscription expression
+  sources: Vec<Option<Box<dyn JoinSource>>>,
+  // Instances of each source that have changed since the previous take
+  changed: BTreeSet<(usize, InstanceHandle)>,
+  phantom: PhantomData<D>,
+}
+
+impl<D> MultiTopicDataReader<D>
+where
+  D: DeserializeOwned,
+{
+  pub(crate) fn new(
+    subscriber: &Subscriber,
+    multitopic: &MultiTopic,
+    qos: Option<QosPolicies>,
+  ) -> Self {
+    Self {
+      subscriber: subscriber.clone(),
+      multitopic: multitopic.clone(),
+      qos,
+      sources: multitopic
+        .related_topic_names()
+        .iter()
+        .map(|_| None)
+        .collect(),
+      changed: BTreeSet::new(),
+      phantom: PhantomData,
+    }
+  }
+
+  pub fn multitopic(&self) -> &MultiTopic {
+    &self.multitopic
+  }
+
+  /// Subscribes to one of the Topics of the subscription expression, whose
+  /// samples are of type `S`. The Topic must be WithKey.
+  ///
+  /// Fails with
+  /// [`BadParameter`](crate::dds::values::result::Error::BadParameter) if the
+  /// Topic is not in the subscription expression, and with
+  /// [`PreconditionNotMet`](crate::dds::values::result::Error::PreconditionNotMet)
+  /// if a source for the Topic has already been added.
+  pub fn add_source<S, SA>(&mut self, topic: &Topic) -> Result<()>
+  where
+    S: Keyed + DeserializeOwned + Serialize + 'static,
+    <S as Keyed>::K: Key,
+    SA: DeserializerAdapter<S> + 'static,
+  {
+    let topic_name = topic.name();
+    let index = match self
+      .multitopic
+      .related_topic_names()
+      .iter()
+      .position(|name| *name == topic_name)
+    {
+      Some(index) => index,
+      None => {
+        return Error::bad_parameter(format!(
+          "Topic {:?} is not in the subscription expression of MultiTopic {:?}",
+          topic_name, self.multitopic.name
+        ))
+      }
+    };
+    if self.sources[index].is_some() {
+      return Error::precondition_not_met(format!(
+        "MultiTopicDataReader already has a source for Topic {:?}",
+        topic_name
+      ));
+    }
+    let reader = self
+      .subscriber
+      .create_datareader::<S, SA>(topic, self.qos.clone())?;
+    self.sources[index] = Some(Box::new(KeyedSource {
+      topic_name,
+      reader,
+      instances: BTreeMap::new(),
+    }));
+    Ok(())
+  }
+
+  /// Enables the DataReaders of the sources added so far, if the Subscriber
+  /// did not enable them on creation.
+  /// See [`DataReader::enable`](crate::with_key::DataReader::enable).
+  pub fn enable(&self) -> Result<()> {
+    self
+      .sources
+      .iter()
+      .flatten()
+      .try_for_each(|source| source.enable())
+  }
+
+  /// Same as [`add_source`](Self::add_source), using CDR deserialization.
+  pub fn add_source_cdr<S>(&mut self, topic: &Topic) -> Result<()>
+  where
+    S: Keyed + DeserializeOwned + Serialize + 'static,
+    <S as Keyed>::K: Key,
+  {
+    self.add_source::<S, CDRDeserializerAdapter<S>>(topic)
+  }
+
+  /// Returns the combined samples that involve a sample received since the
+  /// previous `take`.
+  ///
+  /// Combinations that cease to exist, e.g. due to a dispose, are not
+  /// reported.
+  pub fn take(&mut self) -> Result<Vec<D>> {
+    let samples = self.combine(true)?;
+    self.changed.clear();
+    Ok(samples)
+  }
+
+  /// Returns all the combined samples of the latest samples of each instance.
+  pub fn read(&mut self) -> Result<Vec<D>> {
+    self.combine(false)
+  }
+
+  fn combine(&mut self, only_changed: bool) -> Result<Vec<D>> {
+    for (index, source) in self.sources.iter_mut().enumerate() {
+      if let Some(source) = source {
+        for handle in source.update()? {
+          self.changed.insert((index, handle));
+        }
+      }
+    }
+
+    let sources = match self
+      .sources
+      .iter()
+      .map(Option::as_deref)
+      .collect::<Option<Vec<&dyn JoinSource>>>()
+    {
+      Some(sources) => sources,
+      None => return Ok(Vec::new()), // not all sources added yet
+    };
+
+    // Join the sources one by one. The flag tells if the combination involves
+    // a changed instance.
+    let mut combinations = vec![(FieldValue::Struct(Vec::new()), false)];
+    for (index, source) in sources.iter().enumerate() {
+      let mut joined = Vec::new();
+      for (left, left_changed) in &combinations {
+        for (handle, sample) in source.instances() {
+          if let Some(value) = natural_join(left, sample) {
+            let changed = *left_changed || self.changed.contains(&(index, *handle));
+            joined.push((value, changed));
+          }
+        }
+      }
+      combinations = joined;
+    }
+
+    Ok(
+      combinations
+        .into_iter()
+        .filter(|(_, changed)| *changed || !only_changed)
+        .filter_map(|(joined, _)| self.multitopic.select(&joined))
+        .filter_map(|value| match value.into_sample() {
+          Ok(sample) => Some(sample),
+          Err(e) => {
+            warn!(
+              "MultiTopic {:?}: cannot form a sample of type {:?}: {}",
+              self.multitopic.name,
+              self.multitopic.type_desc.name(),
+              e
+            );
+            None
+          }
+        })
+        .collect(),
+    )
+  }
+}
+
+impl<D> Evented for MultiTopicDataReader<D> {
+  // All the source DataReaders are registered with the same token.
+  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    for source in self.sources.iter().flatten() {
+      source.register(poll, token, interest, opts)?;
+    }
+    Ok(())
+  }
+
+  fn reregister(
+    &self,
+    poll: &Poll,
+    token: Token,
+    interest: Ready,
+    opts: PollOpt,
+  ) -> io::Result<()> {
+    for source in self.sources.iter().flatten() {
+      source.reregister(poll, token, interest, opts)?;
+    }
+    Ok(())
+  }
+
+  fn deregister(&self, poll: &Poll) -> io::Result<()> {
+    for source in self.sources.iter().flatten() {
+      source.deregister(poll)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sample(members: &[(&str, FieldValue)]) -> FieldValue {
+    FieldValue::Struct(
+      members
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect(),
+    )
+  }
+
+  #[test]
+  fn multitopic_join_and_select() {
+    let dp = DomainParticipant::new(0).unwrap();
+    let multitopic = dp
+      .create_multitopic(
+        "Arrivals".to_string(),
+        "Arrival".to_string(),
+        "SELECT id, x AS distance, destination FROM Position NATURAL JOIN Plan WHERE x < %0",
+        vec!["100".to_string()],
+      )
+      .unwrap();
+    assert_eq!(multitopic.related_topic_names(), &["Position", "Plan"]);
+
+    let position = |id, x| sample(&[("id", FieldValue::Int(id)), ("x", FieldValue::Int(x))]);
+    let plan = sample(&[
+      ("id", FieldValue::Int(1)),
+      ("destination", FieldValue::Str("HEL".to_string())),
+    ]);
+
+    assert_eq!(natural_join(&position(2, 50), &plan), None);
+    let joined = natural_join(&position(1, 50), &plan).unwrap();
+    assert_eq!(
+      multitopic.select(&joined),
+      Some(sample(&[
+        ("id", FieldValue::Int(1)),
+        ("distance", FieldValue::Int(50)),
+        ("destination", FieldValue::Str("HEL".to_string())),
+      ]))
+    );
+    // filtered out by WHERE
+    let far = natural_join(&position(1, 500), &plan).unwrap();
+    assert_eq!(multitopic.select(&far), None);
+
+    assert!(dp
+      .create_multitopic(
+        "Bad".to_string(),
+        "Arrival".to_string(),
+        "SELECT * FROM Position WHERE x < %0",
+        vec![],
+      )
+      .is_err());
+  }
+}
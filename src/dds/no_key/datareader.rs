@@ -1,23 +1,38 @@
-use std::io;
+use std::{
+  io,
+  pin::Pin,
+  task::{self, Context},
+};
 
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Serialize};
+use futures_core::Stream;
 use mio::{Evented, Poll, PollOpt, Ready, Token};
 
 use crate::{
   dds::{
     data_types::GUID,
+    listener::{dispatch_reader_status, DataReaderListener, StatusMask},
+    loan::{LoanAdapter, Loaned, Pod},
     no_key::datasample::DataSample,
-    qos::{HasQoSPolicy, QosPolicies},
+    pubsub::GroupAccessReader,
+    qos::{HasQoSPolicy, MutQosPolicy, QosPolicies},
+    querycondition::QueryCondition,
     readcondition::ReadCondition,
     traits::serde_adapters::no_key::DeserializerAdapter,
-    values::result::Result,
-    with_key::{datareader as datareader_with_key, datasample::DataSample as WithKeyDataSample},
+    values::result::{Error, Result},
+    with_key::{
+      datareader::{self as datareader_with_key, WhenAvailable},
+      datasample::DataSample as WithKeyDataSample,
+    },
   },
-  serialization::CDRDeserializerAdapter,
-  structure::entity::RTPSEntity,
+  discovery::data_types::topic_data::PublicationBuiltinTopicData,
+  serialization::{CDRDeserializerAdapter, SerializedSample},
+  structure::{duration::Duration, entity::RTPSEntity, time::Timestamp},
 };
 use super::wrappers::{DAWrapper, NoKeyWrapper};
 
+type KeyedDataReader<D, DA> = datareader_with_key::DataReader<NoKeyWrapper<D>, DAWrapper<DA>>;
+
 /// Simplified type for CDR encoding
 pub type DataReaderCdr<D> = DataReader<D, CDRDeserializerAdapter<D>>;
 
@@ -48,6 +63,8 @@ pub type DataReaderCdr<D> = DataReader<D, CDRDeserializerAdapter<D>>;
 /// ```
 pub struct DataReader<D: DeserializeOwned, DA: DeserializerAdapter<D> = CDRDeserializerAdapter<D>> {
   keyed_datareader: datareader_with_key::DataReader<NoKeyWrapper<D>, DAWrapper<DA>>,
+  // Reused by take_all_into
+  keyed_samples: Vec<WithKeyDataSample<NoKeyWrapper<D>>>,
 }
 
 // TODO: rewrite DataSample so it can use current Keyed version (and send back
@@ -62,9 +79,19 @@ where
   ) -> Self {
     Self {
       keyed_datareader: keyed,
+      keyed_samples: Vec::new(),
     }
   }
 
+  /// See [`enable`](crate::with_key::DataReader::enable) of the keyed DataReader.
+  pub fn enable(&self) -> Result<()> {
+    self.keyed_datareader.enable()
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.keyed_datareader.is_enabled()
+  }
+
   /// Reads amount of samples found with `max_samples` and `read_condition`
   /// parameters.
   ///
@@ -161,6 +188,92 @@ where
     Ok(result)
   }
 
+  /// Takes all available samples, and appends them to `samples`, see
+  /// [`with_key::DataReader::take_all_into`](datareader_with_key::DataReader::take_all_into).
+  /// Returns the number of samples appended.
+  pub fn take_all_into(&mut self, samples: &mut Vec<DataSample<D>>) -> Result<usize> {
+    self
+      .keyed_datareader
+      .take_all_into(&mut self.keyed_samples)?;
+    let len_before = samples.len();
+    samples.extend(
+      self
+        .keyed_samples
+        .drain(..)
+        .filter_map(DataSample::<D>::from_with_key),
+    );
+    Ok(samples.len() - len_before)
+  }
+
+  /// Takes all samples received since the previous read or take, without
+  /// deserializing them, see
+  /// [`with_key::DataReader::take_serialized`](datareader_with_key::DataReader::take_serialized).
+  pub fn take_serialized(&mut self) -> Result<Vec<SerializedSample>> {
+    // Only samples with data are shown for no_key topics.
+    Ok(
+      self
+        .keyed_datareader
+        .take_serialized()?
+        .into_iter()
+        .filter(|s| s.sample_info().valid_data())
+        .collect(),
+    )
+  }
+
+  /// Deserializes a sample from [`take_serialized`](Self::take_serialized).
+  pub fn deserialize_sample(&self, sample: &SerializedSample) -> Result<D> {
+    match self.keyed_datareader.deserialize_sample(sample)? {
+      Ok(kv) => Ok(kv.d),
+      Err(()) => Err(Error::Serialization {
+        reason: "Sample has no data".to_string(),
+      }),
+    }
+  }
+
+  /// Reads samples that match a [`QueryCondition`], i.e. both its
+  /// [`ReadCondition`] and its filter expression.
+  pub fn read_w_condition(
+    &mut self,
+    max_samples: usize,
+    query_condition: &QueryCondition,
+  ) -> Result<Vec<DataSample<&D>>>
+  where
+    D: Serialize,
+  {
+    let values: Vec<WithKeyDataSample<&NoKeyWrapper<D>>> = self
+      .keyed_datareader
+      .read_w_condition(max_samples, query_condition)?;
+    let mut result = Vec::with_capacity(values.len());
+    for ks in values {
+      if let Some(s) = DataSample::<D>::from_with_key_ref(ks) {
+        result.push(s);
+      }
+    }
+    Ok(result)
+  }
+
+  /// Like [`read_w_condition`](Self::read_w_condition), but takes the
+  /// samples.
+  pub fn take_w_condition(
+    &mut self,
+    max_samples: usize,
+    query_condition: &QueryCondition,
+  ) -> Result<Vec<DataSample<D>>>
+  where
+    D: Serialize,
+  {
+    let values: Vec<WithKeyDataSample<NoKeyWrapper<D>>> = self
+      .keyed_datareader
+      .take_w_condition(max_samples, query_condition)?;
+    let mut result = Vec::with_capacity(values.len());
+    for ks in values {
+      if let Some(s) = DataSample::<D>::from_with_key(ks) {
+        result.push(s);
+      }
+    }
+    Ok(result)
+  }
+
   /// Reads next unread sample
   ///
   /// # Examples
@@ -225,6 +338,70 @@ where
     Ok(ds.pop())
   }
 
+  // Async interface
+
+  /// Takes the next NOT_READ sample, waiting for one to arrive if there is
+  /// none yet. See
+  /// [`with_key::DataReader::async_take_next_sample`](crate::with_key::DataReader::async_take_next_sample).
+  pub async fn async_take_next_sample(&mut self) -> Result<DataSample<D>> {
+    WhenAvailable::new(&mut self.keyed_datareader, Self::take_next_valid).await
+  }
+
+  /// Takes samples like [`take`](Self::take), but waits until at least one
+  /// sample matches `read_condition`.
+  pub async fn async_take(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+  ) -> Result<Vec<DataSample<D>>> {
+    WhenAvailable::new(&mut self.keyed_datareader, move |keyed| {
+      let samples: Vec<DataSample<D>> = keyed
+        .take(max_samples, read_condition)?
+        .into_iter()
+        .filter_map(DataSample::<D>::from_with_key)
+        .collect();
+      Ok(Some(samples).filter(|s| !s.is_empty()))
+    })
+    .await
+  }
+
+  /// Produces a [`Stream`](futures_core::Stream) of NOT_READ samples, which
+  /// are taken from this DataReader as they arrive. The stream does not end.
+  pub fn async_sample_stream(&mut self) -> DataReaderStream<'_, D, DA> {
+    DataReaderStream { reader: self }
+  }
+
+  // Like take_next_sample, but skips over samples without data, so that None
+  // means that there is nothing left to take.
+  fn take_next_valid(keyed: &mut KeyedDataReader<D, DA>) -> Result<Option<DataSample<D>>> {
+    while let Some(keyed_sample) = keyed.take_next_sample()? {
+      if let Some(sample) = DataSample::<D>::from_with_key(keyed_sample) {
+        return Ok(Some(sample));
+      }
+    }
+    Ok(None)
+  }
+
+  // Repeats `attempt` until it produces something, waiting for new data in
+  // between. See the keyed DataReader.
+  pub(crate) fn poll_when_available<T>(
+    &mut self,
+    cx: &mut Context<'_>,
+    mut attempt: impl FnMut(&mut Self) -> Result<Option<T>>,
+  ) -> task::Poll<Result<T>> {
+    loop {
+      match attempt(self) {
+        Err(e) => return task::Poll::Ready(Err(e)),
+        Ok(Some(result)) => return task::Poll::Ready(Ok(result)),
+        Ok(None) => {
+          if !self.keyed_datareader.register_data_waker(cx) {
+            return task::Poll::Pending;
+          }
+        }
+      }
+    }
+  }
+
   // Iterator interface
 
   /// Produces an interator over the currently available NOT_READ samples.
@@ -428,10 +605,93 @@ where
     self.keyed_datareader.get_requested_deadline_missed_status()
   }
   */
+
+  /// Invokes the callbacks of `listener` for the status changes since the
+  /// previous call, and `on_data_available` if new samples have arrived.
+  /// See
+  /// [`with_key::DataReader::dispatch_to_listener`](crate::with_key::DataReader::dispatch_to_listener).
+  pub fn dispatch_to_listener<L>(&mut self, listener: &mut L, mask: StatusMask) -> usize
+  where
+    L: DataReaderListener<Self> + ?Sized,
+  {
+    let (statuses, data_available) = self.keyed_datareader.drain_listener_events(mask);
+    let mut invoked = statuses
+      .into_iter()
+      .map(|status| dispatch_reader_status::<Self, _>(listener, status, mask))
+      .filter(|&invoked| invoked)
+      .count();
+    if data_available {
+      listener.on_data_available(self);
+      invoked += 1;
+    }
+    invoked
+  }
+
+  /// See [`set_heartbeat_response_delay`](crate::with_key::DataReader::set_heartbeat_response_delay)
+  /// of the keyed DataReader.
+  pub fn set_heartbeat_response_delay(&self, delay: Duration, jitter: Duration) -> Result<()> {
+    self
+      .keyed_datareader
+      .set_heartbeat_response_delay(delay, jitter)
+  }
+
+  /// See [`set_heartbeat_suppression_duration`](crate::with_key::DataReader::set_heartbeat_suppression_duration)
+  /// of the keyed DataReader.
+  pub fn set_heartbeat_suppression_duration(&self, duration: Duration) -> Result<()> {
+    self
+      .keyed_datareader
+      .set_heartbeat_suppression_duration(duration)
+  }
+
+  /// Gets the discovery data of the remote DataWriters on this Topic that
+  /// have QoS compatible with this DataReader.
+  pub fn get_matched_publications(&self) -> impl Iterator<Item = PublicationBuiltinTopicData> {
+    self.keyed_datareader.get_matched_publications()
+  }
+}
+
+impl<T> DataReader<Loaned<T>, LoanAdapter<T>>
+where
+  T: Pod + DeserializeOwned,
+{
+  /// Takes up to `max_samples` not yet read samples, loaned from the
+  /// received message buffers instead of deserialized. See [`Loaned`].
+  pub fn take_loaned(&mut self, max_samples: usize) -> Result<Vec<DataSample<Loaned<T>>>> {
+    self.take(max_samples, ReadCondition::not_read())
+  }
+}
+
+/// Stream of samples taken from a DataReader, see
+/// [`DataReader::async_sample_stream`].
+pub struct DataReaderStream<'a, D, DA>
+where
+  D: DeserializeOwned,
+  DA: DeserializerAdapter<D>,
+{
+  reader: &'a mut DataReader<D, DA>,
+}
+
+impl<D, DA> Stream for DataReaderStream<'_, D, DA>
+where
+  D: DeserializeOwned + 'static,
+  DA: DeserializerAdapter<D>,
+{
+  type Item = Result<DataSample<D>>;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<Option<Self::Item>> {
+    self
+      .get_mut()
+      .reader
+      .keyed_datareader
+      .poll_when_available(cx, DataReader::<D, DA>::take_next_valid)
+      .map(Some)
+  }
 }
 
 // This is  not part of DDS spec. We implement mio Eventd so that the
 // application can asynchronously poll DataReader(s).
+/// See the `Evented` implementation of the
+/// [`with_key::DataReader`](datareader_with_key::DataReader).
 impl<D, DA> Evented for DataReader<D, DA>
 where
   D: DeserializeOwned,
@@ -469,15 +729,22 @@ where
   D: DeserializeOwned,
   DA: DeserializerAdapter<D>,
 {
-  // fn set_qos(&mut self, policy: &QosPolicies) -> Result<()> {
-  //   self.keyed_datareader.set_qos(policy)
-  // }
-
   fn qos(&self) -> QosPolicies {
     self.keyed_datareader.qos()
   }
 }
 
+impl<D, DA> MutQosPolicy for DataReader<D, DA>
+where
+  D: DeserializeOwned,
+  DA: DeserializerAdapter<D>,
+{
+  /// See [`set_qos`](MutQosPolicy::set_qos) of the WITH_KEY DataReader.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<()> {
+    self.keyed_datareader.set_qos(new_qos)
+  }
+}
+
 impl<D, DA> RTPSEntity for DataReader<D, DA>
 where
   D: DeserializeOwned,
@@ -487,3 +754,13 @@ where
     self.keyed_datareader.guid()
   }
 }
+
+impl<D, DA> GroupAccessReader for DataReader<D, DA>
+where
+  D: DeserializeOwned + 'static,
+  DA: DeserializerAdapter<D>,
+{
+  fn not_read_sample_timestamps(&mut self) -> Result<Vec<Timestamp>> {
+    self.keyed_datareader.not_read_sample_timestamps()
+  }
+}
@@ -1,12 +1,16 @@
-use std::time::Duration;
+use std::{io, time::Duration};
 
+use mio::{Evented, Poll, PollOpt, Ready, Token};
 use serde::Serialize;
 
 use crate::{
   dds::{
     data_types::GUID,
+    flow_controller::FlowController,
+    listener::{DataWriterListener, StatusMask},
+    loan::SampleLoan,
     pubsub::Publisher,
-    qos::{HasQoSPolicy, QosPolicies},
+    qos::{HasQoSPolicy, MutQosPolicy, QosPolicies},
     topic::Topic,
     traits::{dds_entity::DDSEntity, serde_adapters::no_key::SerializerAdapter},
     values::result::Result,
@@ -14,7 +18,9 @@ use crate::{
   },
   discovery::data_types::topic_data::SubscriptionBuiltinTopicData,
   serialization::CDRSerializerAdapter,
-  structure::{entity::RTPSEntity, rpc::SampleIdentity, time::Timestamp},
+  structure::{
+    entity::RTPSEntity, rpc::SampleIdentity, sequence_number::SequenceNumber, time::Timestamp,
+  },
 };
 use super::wrappers::{NoKeyWrapper, SAWrapper};
 
@@ -61,6 +67,15 @@ where
     }
   }
 
+  /// See [`enable`](crate::with_key::DataWriter::enable) of the keyed DataWriter.
+  pub fn enable(&self) -> Result<()> {
+    self.keyed_datawriter.enable()
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.keyed_datawriter.is_enabled()
+  }
+
   /// Writes single data instance to a topic.
   ///
   /// # Examples
@@ -93,6 +108,12 @@ where
       .write(NoKeyWrapper::<D> { d: data }, source_timestamp)
   }
 
+  /// Writes a sample with the given source timestamp, see
+  /// [`with_key::DataWriter::write_w_timestamp`](datawriter_with_key::DataWriter::write_w_timestamp).
+  pub fn write_w_timestamp(&self, data: D, source_timestamp: Timestamp) -> Result<()> {
+    self.write(data, Some(source_timestamp))
+  }
+
   pub fn write_with_options(
     &self,
     data: D,
@@ -103,6 +124,61 @@ where
       .write_with_options(NoKeyWrapper::<D> { d: data }, write_options)
   }
 
+  /// Writes a sample without blocking, see
+  /// [`with_key::DataWriter::try_write`](datawriter_with_key::DataWriter::try_write).
+  pub fn try_write(&self, data: D, source_timestamp: Option<Timestamp>) -> Result<()> {
+    self
+      .keyed_datawriter
+      .try_write(NoKeyWrapper::<D> { d: data }, source_timestamp)
+  }
+
+  /// Source of only the writable readiness of the DataWriter, see
+  /// [`with_key::DataWriter::as_writable_evented`](datawriter_with_key::DataWriter::as_writable_evented).
+  pub fn as_writable_evented(&self) -> &dyn Evented {
+    self.keyed_datawriter.as_writable_evented()
+  }
+
+  /// See [`with_key::DataWriter::acknowledged_before`](datawriter_with_key::DataWriter::acknowledged_before).
+  pub fn acknowledged_before(&self) -> Result<Option<SequenceNumber>> {
+    self.keyed_datawriter.acknowledged_before()
+  }
+
+  /// Loans a buffer for the serialized payload of a sample, see
+  /// [`with_key::DataWriter::loan_sample`](datawriter_with_key::DataWriter::loan_sample).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::*;
+  /// # let participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let publisher = participant.create_publisher(&qos).unwrap();
+  /// # let topic = participant
+  /// #   .create_topic("bins".to_string(), "Bins".to_string(), &qos, TopicKind::NoKey)
+  /// #   .unwrap();
+  /// let writer = publisher
+  ///   .create_datawriter_no_key::<[f32; 32], CDRSerializerAdapter<_>>(&topic, None)
+  ///   .unwrap();
+  /// let mut loan = writer.loan_sample(32 * 4).unwrap();
+  /// for (i, bin) in loan.chunks_exact_mut(4).enumerate() {
+  ///   bin.copy_from_slice(&(i as f32).to_le_bytes());
+  /// }
+  /// writer.write_loan(loan, WriteOptions::default()).unwrap();
+  /// ```
+  pub fn loan_sample(&self, size: usize) -> Result<SampleLoan> {
+    self.keyed_datawriter.loan_sample(size)
+  }
+
+  /// Publishes a buffer loaned with [`loan_sample`](Self::loan_sample), see
+  /// [`with_key::DataWriter::write_loan`](datawriter_with_key::DataWriter::write_loan).
+  pub fn write_loan(
+    &self,
+    loan: SampleLoan,
+    write_options: datawriter_with_key::WriteOptions,
+  ) -> Result<SampleIdentity> {
+    self.keyed_datawriter.write_loan(loan, (), write_options)
+  }
+
   /// Waits for all acknowledgements to finish
   ///
   /// # Examples
@@ -132,6 +208,101 @@ where
   pub fn wait_for_acknowledgments(&self, max_wait: Duration) -> Result<bool> {
     self.keyed_datawriter.wait_for_acknowledgments(max_wait)
   }
+
+  /// Waits for acknowledgments, and on timeout tells which samples are not
+  /// yet acknowledged. See
+  /// [`with_key::DataWriter::wait_for_acknowledgments_detailed`](crate::with_key::DataWriter::wait_for_acknowledgments_detailed).
+  pub fn wait_for_acknowledgments_detailed(
+    &self,
+    max_wait: Duration,
+  ) -> Result<datawriter_with_key::PendingAcknowledgments> {
+    self
+      .keyed_datawriter
+      .wait_for_acknowledgments_detailed(max_wait)
+  }
+
+  /// Writes a sample, and then waits until all matched reliable DataReaders
+  /// have acknowledged it. See
+  /// [`with_key::DataWriter::async_write`](crate::with_key::DataWriter::async_write).
+  pub async fn async_write(&self, data: D, source_timestamp: Option<Timestamp>) -> Result<()> {
+    self
+      .keyed_datawriter
+      .async_write(NoKeyWrapper::<D> { d: data }, source_timestamp)
+      .await
+  }
+
+  /// Async version of [`wait_for_acknowledgments`](Self::wait_for_acknowledgments),
+  /// without a timeout.
+  pub async fn async_wait_for_acknowledgments(&self) -> Result<()> {
+    self.keyed_datawriter.async_wait_for_acknowledgments().await
+  }
+
+  #[cfg(feature = "tokio")]
+  pub(crate) fn all_acknowledged(&self) -> Result<Option<datawriter_with_key::AllAcknowledged>> {
+    self.keyed_datawriter.all_acknowledged()
+  }
+
+  /// Waits until there is space in the history. See
+  /// [`with_key::DataWriter::async_wait_for_history_space`](crate::with_key::DataWriter::async_wait_for_history_space).
+  pub async fn async_wait_for_history_space(&self) -> Result<()> {
+    self.keyed_datawriter.async_wait_for_history_space().await
+  }
+
+  /// See [`set_batch_max_size`](crate::with_key::DataWriter::set_batch_max_size)
+  /// of the keyed DataWriter.
+  pub fn set_batch_max_size(&self, max_size: usize) -> Result<()> {
+    self.keyed_datawriter.set_batch_max_size(max_size)
+  }
+
+  /// Send out the batched samples now, without waiting for the latency budget
+  /// to pass.
+  pub fn flush(&self) -> Result<()> {
+    self.keyed_datawriter.flush()
+  }
+
+  /// See [`set_flow_controller`](crate::with_key::DataWriter::set_flow_controller)
+  /// of the keyed DataWriter.
+  pub fn set_flow_controller(&self, flow_controller: Option<FlowController>) -> Result<()> {
+    self.keyed_datawriter.set_flow_controller(flow_controller)
+  }
+
+  /// See [`set_heartbeat_period`](crate::with_key::DataWriter::set_heartbeat_period)
+  /// of the keyed DataWriter.
+  pub fn set_heartbeat_period(&self, period: Duration) -> Result<()> {
+    self.keyed_datawriter.set_heartbeat_period(period)
+  }
+
+  /// See [`set_nack_response_delay`](crate::with_key::DataWriter::set_nack_response_delay)
+  /// of the keyed DataWriter.
+  pub fn set_nack_response_delay(&self, delay: Duration) -> Result<()> {
+    self.keyed_datawriter.set_nack_response_delay(delay)
+  }
+
+  /// See [`set_piggyback_heartbeat_every`](crate::with_key::DataWriter::set_piggyback_heartbeat_every)
+  /// of the keyed DataWriter.
+  pub fn set_piggyback_heartbeat_every(&self, count: u32) -> Result<()> {
+    self.keyed_datawriter.set_piggyback_heartbeat_every(count)
+  }
+
+  /// Start sequence numbering from `sequence_number` instead of 1. This must
+  /// be called before writing anything.
+  ///
+  /// For protocol robustness testing only. Requires feature `testing`.
+  #[cfg(feature = "testing")]
+  pub fn set_initial_sequence_number(&self, sequence_number: SequenceNumber) -> Result<()> {
+    self
+      .keyed_datawriter
+      .set_initial_sequence_number(sequence_number)
+  }
+
+  /// Do not use the next `count` sequence numbers for any sample. Readers are
+  /// sent a GAP for them.
+  ///
+  /// For protocol robustness testing only. Requires feature `testing`.
+  #[cfg(feature = "testing")]
+  pub fn skip_sequence_numbers(&self, count: i64) -> Result<()> {
+    self.keyed_datawriter.skip_sequence_numbers(count)
+  }
   /*
   // status queries
   /// Unimplemented. <b>Do not use</b>.
@@ -349,11 +520,12 @@ where
     self.keyed_datawriter.assert_liveliness()
   }
 
-  /// Unimplemented. <b>Do not use</b>.
+  /// Gets the discovery data of the remote DataReaders on this Topic that
+  /// have QoS compatible with this DataWriter.
   ///
   /// # Examples
-  // TODO: enable run when implemented
-  /// ```no_run ignore
+  ///
+  /// ```
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
@@ -370,7 +542,7 @@ where
   /// #
   /// // NoKey is important
   /// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::NoKey).unwrap();
-  /// let data_writer = publisher.create_datawriter_no_key::<SomeType, CDRSerializerAdapter<_>>(topic, None).unwrap();
+  /// let data_writer = publisher.create_datawriter_no_key::<SomeType, CDRSerializerAdapter<_>>(&topic, None).unwrap();
   ///
   /// for sub in data_writer.get_matched_subscriptions().iter() {
   ///   // handle subscriptions
@@ -379,6 +551,16 @@ where
   pub fn get_matched_subscriptions(&self) -> Vec<SubscriptionBuiltinTopicData> {
     self.keyed_datawriter.get_matched_subscriptions()
   }
+
+  /// Invokes the callbacks of `listener` for the status changes since the
+  /// previous call. See
+  /// [`with_key::DataWriter::dispatch_to_listener`](crate::with_key::DataWriter::dispatch_to_listener).
+  pub fn dispatch_to_listener<L>(&mut self, listener: &mut L, mask: StatusMask) -> usize
+  where
+    L: DataWriterListener + ?Sized,
+  {
+    self.keyed_datawriter.dispatch_to_listener(listener, mask)
+  }
   /*
   /// Gets mio receiver for all implemented Status changes
   ///
@@ -414,6 +596,18 @@ where
   } */
 }
 
+impl<D, SA> DataWriter<D, SA>
+where
+  D: Serialize + Clone + Send + Sync + 'static,
+  SA: SerializerAdapter<D>,
+{
+  /// See [`set_local_zero_copy`](crate::with_key::DataWriter::set_local_zero_copy)
+  /// of the keyed DataWriter.
+  pub fn set_local_zero_copy(&self, enabled: bool) -> Result<()> {
+    self.keyed_datawriter.set_local_zero_copy(enabled)
+  }
+}
+
 impl<D: Serialize, SA: SerializerAdapter<D>> RTPSEntity for DataWriter<D, SA> {
   fn guid(&self) -> GUID {
     self.keyed_datawriter.guid()
@@ -421,17 +615,44 @@ impl<D: Serialize, SA: SerializerAdapter<D>> RTPSEntity for DataWriter<D, SA> {
 }
 
 impl<D: Serialize, SA: SerializerAdapter<D>> HasQoSPolicy for DataWriter<D, SA> {
-  // fn set_qos(&mut self, policy: &QosPolicies) -> Result<()> {
-  //   self.keyed_datawriter.set_qos(policy)
-  // }
-
   fn qos(&self) -> QosPolicies {
     self.keyed_datawriter.qos()
   }
 }
 
+impl<D: Serialize, SA: SerializerAdapter<D>> MutQosPolicy for DataWriter<D, SA> {
+  /// See [`set_qos`](MutQosPolicy::set_qos) of the WITH_KEY DataWriter.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<()> {
+    self.keyed_datawriter.set_qos(new_qos)
+  }
+}
+
 impl<D: Serialize, SA: SerializerAdapter<D>> DDSEntity for DataWriter<D, SA> {}
 
+/// See the `Evented` implementation of the
+/// [`with_key::DataWriter`](datawriter_with_key::DataWriter).
+impl<D: Serialize, SA: SerializerAdapter<D>> Evented for DataWriter<D, SA> {
+  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    self.keyed_datawriter.register(poll, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &Poll,
+    token: Token,
+    interest: Ready,
+    opts: PollOpt,
+  ) -> io::Result<()> {
+    self
+      .keyed_datawriter
+      .reregister(poll, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &Poll) -> io::Result<()> {
+    self.keyed_datawriter.deregister(poll)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use byteorder::LittleEndian;
@@ -14,6 +14,7 @@ use crate::{
 // * the wrapper is good for WITH_KEY
 // The wrapper introduces a dummy key of type (), which of course has an always
 // known value ()
+#[derive(Clone)]
 pub(crate) struct NoKeyWrapper<D> {
   pub(crate) d: D,
 }
@@ -84,6 +85,10 @@ where
     SA::output_encoding()
   }
 
+  fn output_encoding_of(value: &NoKeyWrapper<D>) -> RepresentationIdentifier {
+    SA::output_encoding_of(&value.d)
+  }
+
   fn to_bytes(value: &NoKeyWrapper<D>) -> Result<Bytes> {
     SA::to_bytes(&value.d)
   }
@@ -121,6 +126,10 @@ where
   fn from_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<NoKeyWrapper<D>> {
     DA::from_bytes(input_bytes, encoding).map(|d| NoKeyWrapper::<D> { d })
   }
+
+  fn from_payload(payload: &Bytes, encoding: RepresentationIdentifier) -> Result<NoKeyWrapper<D>> {
+    DA::from_payload(payload, encoding).map(|d| NoKeyWrapper::<D> { d })
+  }
 }
 
 // then, implement with_key DA
@@ -1,35 +1,667 @@
 //use mio::Token;
 use std::{
-  collections::HashMap,
-  io::ErrorKind,
-  net::Ipv4Addr,
+  collections::{BTreeMap, HashMap},
+  fs::File,
+  io,
+  io::{BufWriter, ErrorKind, Write},
+  net::{IpAddr, SocketAddr},
+  ops::RangeInclusive,
+  path::Path,
   sync::{atomic, Arc, Mutex, RwLock, Weak},
-  thread,
-  thread::JoinHandle,
   time::{Duration, Instant},
 };
 
 use mio_extras::channel as mio_channel;
 use mio::Token;
+use serde::de::DeserializeOwned;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
 use crate::{
   dds::{
-    dp_event_loop::DPEventLoop, pubsub::*, qos::*, reader::*, topic::*, typedesc::TypeDesc,
-    values::result::*, writer::WriterIngredients,
+    clock::{Clock, TimeSource},
+    compatibility::ProtocolCompatibility,
+    contentfilteredtopic::ContentFilteredTopic,
+    multitopic::MultiTopic,
+    dp_event_loop::DPEventLoop,
+    health::{HealthMonitor, ParticipantHealth, ProtocolThread},
+    listener::{dispatch_participant_status, ParticipantListener, StatusMask},
+    pubsub::*,
+    qos::*,
+    reader::*,
+    receive_loop::ReceiveLoops,
+    runtime_metrics,
+    simulation::SimulatedClock,
+    statistics::StatisticsMonitor,
+    statusevents::{
+      DiscoveryEvent, DomainParticipantStatus, StatusBroadcaster, StatusEvented, StatusReceiver,
+    },
+    threads::{BackgroundSpawner, BackgroundTask, ParticipantDriver, ThreadConfig, ThreadSettings},
+    topic::*,
+    traits::{
+      key::{Key, Keyed},
+      serde_adapters::with_key,
+    },
+    type_registry::TypeRegistry,
+    typedesc::TypeDesc,
+    values::result::*,
+    with_key::datareader::DataReader as WithKeyDataReader,
+    writer::WriterIngredients,
   },
   discovery::{
-    data_types::topic_data::DiscoveredTopicData,
+    data_types::{
+      spdp_participant_data::SpdpDiscoveredParticipantData,
+      topic_data::{DiscoveredReaderData, DiscoveredTopicData, DiscoveredWriterData},
+    },
+    authentication::{AuthenticatorHandle, ParticipantAuthenticator},
     discovery::{Discovery, DiscoveryCommand},
     discovery_db::DiscoveryDB,
+    static_endpoint::StaticEndpoint,
+    type_lookup::{TypeIdentifier, TypeObject},
+  },
+  log_and_err_internal, log_and_err_precondition_not_met,
+  network::{
+    constant::*,
+    interface_filter::{InterfaceFilter, InterfaceSelector},
+    packet_capture::PacketCapture,
+    port_mapping::PortMapping,
+    shared_memory::ShmListener,
+    socket_options::SocketOptions,
+    tcp::TCPListener,
+    transport::{self, TransportKind, TransportListener},
+    udp_listener::UDPListener,
+  },
+  security::SecurityConfig,
+  serialization::pl_cdr_deserializer::PlCdrDeserializerAdapter,
+  structure::{
+    dds_cache::DDSCache, entity::RTPSEntity, guid::*, locator::Locator, time::Timestamp,
+  },
+};
+#[cfg(feature = "statistics")]
+use crate::dds::statistics::ParticipantStatistics;
+#[cfg(feature = "security")]
+use crate::security::{
+  access_control::{PermissionsCredentials, TopicAccess},
+  authentication::IdentityCredentials,
+  cryptographic::KeyRotationPolicy,
+  plugins::{
+    AccessControlHandle, AccessControlPlugin, AuthenticationHandle, AuthenticationPlugin,
+    CryptographicPlugin, CryptographyHandle,
   },
-  log_and_err_internal,
-  network::{constant::*, udp_listener::UDPListener},
-  structure::{dds_cache::DDSCache, entity::RTPSEntity, guid::*, locator::Locator},
 };
 use super::dp_event_loop::DomainInfo;
 
+/// Builder for [`DomainParticipant`], for when the defaults used by
+/// [`DomainParticipant::new`] are not suitable.
+///
+/// By default, participants discover each other by sending SPDP announcements
+/// to a well-known multicast address. On networks where multicast is blocked,
+/// remote participants can be listed as initial peers. Announcements are then
+/// periodically sent by unicast to each of them, in addition to (or, with
+/// `multicast_discovery(false)`, instead of) multicast.
+///
+/// # Examples
+///
+/// ```
+/// use std::net::{IpAddr, Ipv4Addr};
+/// # use rustdds::DomainParticipantBuilder;
+///
+/// let domain_participant = DomainParticipantBuilder::new(0)
+///   .initial_peer(IpAddr::V4(Ipv4Addr::LOCALHOST))
+///   .multicast_discovery(false)
+///   .build()
+///   .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct DomainParticipantBuilder {
+  domain_id: u16,
+  initial_peer_addresses: Vec<(IpAddr, RangeInclusive<u16>)>,
+  initial_peer_socket_addresses: Vec<SocketAddr>,
+  multicast_discovery: bool,
+  port_mapping: PortMapping,
+  transports: Vec<TransportKind>,
+  interfaces: InterfaceFilter,
+  socket_options: SocketOptions,
+  publish_health: bool,
+  publish_statistics: Option<Duration>,
+  guid_prefix: Option<GuidPrefix>,
+  static_endpoint_discovery: bool,
+  static_endpoints: Vec<StaticEndpoint>,
+  domain_tag: String,
+  participant_authenticator: Option<AuthenticatorHandle>,
+  compatibility: ProtocolCompatibility,
+  security: SecurityConfig,
+  threads: ThreadSettings,
+  time_source: TimeSource,
+}
+
+impl DomainParticipantBuilder {
+  /// Participant ids probed at an initial peer address, if not specified
+  /// otherwise.
+  pub const DEFAULT_INITIAL_PEER_PARTICIPANT_IDS: RangeInclusive<u16> = 0..=4;
+
+  pub fn new(domain_id: u16) -> Self {
+    Self {
+      domain_id,
+      initial_peer_addresses: Vec::new(),
+      initial_peer_socket_addresses: Vec::new(),
+      multicast_discovery: true,
+      port_mapping: PortMapping::default(),
+      transports: TransportKind::DEFAULT.to_vec(),
+      interfaces: InterfaceFilter::default(),
+      socket_options: SocketOptions::default(),
+      publish_health: false,
+      publish_statistics: None,
+      guid_prefix: None,
+      static_endpoint_discovery: false,
+      static_endpoints: Vec::new(),
+      domain_tag: String::new(),
+      participant_authenticator: None,
+      compatibility: ProtocolCompatibility::default(),
+      security: SecurityConfig::default(),
+      threads: ThreadSettings::default(),
+      time_source: TimeSource::System,
+    }
+  }
+
+  /// Add an initial peer host. Announcements are sent to the SPDP unicast
+  /// ports of participant ids
+  /// [`DEFAULT_INITIAL_PEER_PARTICIPANT_IDS`](Self::DEFAULT_INITIAL_PEER_PARTICIPANT_IDS)
+  /// at that address.
+  #[must_use]
+  pub fn initial_peer(self, address: IpAddr) -> Self {
+    self.initial_peer_with_participant_ids(address, Self::DEFAULT_INITIAL_PEER_PARTICIPANT_IDS)
+  }
+
+  /// Add an initial peer host, probing the given range of participant ids.
+  /// The port numbers are computed from the domain id and participant id
+  /// with the [`port_mapping`](Self::port_mapping) of this participant.
+  /// Participant ids whose port number would be out of range are skipped.
+  #[must_use]
+  pub fn initial_peer_with_participant_ids(
+    mut self,
+    address: IpAddr,
+    participant_ids: RangeInclusive<u16>,
+  ) -> Self {
+    self.initial_peer_addresses.push((address, participant_ids));
+    self
+  }
+
+  /// Add an initial peer by exact address and port, e.g. when the remote end
+  /// uses a non-default port mapping.
+  #[must_use]
+  pub fn initial_peer_socket_address(mut self, socket_address: SocketAddr) -> Self {
+    self.initial_peer_socket_addresses.push(socket_address);
+    self
+  }
+
+  /// Enable or disable discovery over multicast. Default is enabled.
+  /// If disabled, remote participants can only be discovered if they are
+  /// listed as initial peers, or if they have us as their initial peer.
+  #[must_use]
+  pub fn multicast_discovery(mut self, multicast_discovery: bool) -> Self {
+    self.multicast_discovery = multicast_discovery;
+    self
+  }
+
+  /// Compute the UDP port numbers from the domain id and participant id with
+  /// the given [`PortMapping`], instead of the default of RTPS spec v2.5
+  /// Section "9.6.2.3 Default Port Numbers". Use this to communicate with
+  /// deployments that use a non-default port layout. All participants of a
+  /// domain must use the same mapping, and participants of different domains
+  /// in the same process or host need mappings that give them different
+  /// ports.
+  ///
+  /// Building fails with [`BadParameter`](Error::BadParameter) if the ports
+  /// of this domain do not fit in 16 bits.
+  #[must_use]
+  pub fn port_mapping(mut self, port_mapping: PortMapping) -> Self {
+    self.port_mapping = port_mapping;
+    self
+  }
+
+  /// Select the network transports to send and receive with, in order of
+  /// preference. Default is [`TransportKind::DEFAULT`]. Building fails with
+  /// [`BadParameter`](Error::BadParameter) if no UDP transport is selected,
+  /// because Discovery needs one.
+  ///
+  /// E.g. `[TransportKind::UdpV6, TransportKind::UdpV4]` prefers IPv6 with
+  /// remote participants that announce both, and `[TransportKind::UdpV6]`
+  /// restricts the participant to IPv6. Note that remote participants using
+  /// only IPv4 are then not discovered. Neither uses shared memory.
+  /// `[TransportKind::TcpV4, TransportKind::UdpV4]` sends user traffic over
+  /// TCP to the remote participants that have it as well.
+  #[must_use]
+  pub fn transports(mut self, transports: impl IntoIterator<Item = TransportKind>) -> Self {
+    self.transports.clear();
+    for kind in transports {
+      if !self.transports.contains(&kind) {
+        self.transports.push(kind);
+      }
+    }
+    self
+  }
+
+  /// Use only the network interfaces that match an allowed selector, if any
+  /// are given. This applies to the unicast addresses that we advertise, and
+  /// to the interfaces where multicast is sent and received. Loopback
+  /// interfaces are used only if allowed explicitly.
+  ///
+  /// Interfaces that come up or go down later are noticed and taken into use
+  /// or out of use. IPv6 multicast always uses the default interface of the
+  /// system.
+  ///
+  /// ```
+  /// # use rustdds::DomainParticipantBuilder;
+  /// let builder = DomainParticipantBuilder::new(0)
+  ///   .allow_interface("eth0".parse().unwrap())
+  ///   .allow_interface("192.168.1.0/24".parse().unwrap());
+  /// ```
+  #[must_use]
+  pub fn allow_interface(mut self, selector: InterfaceSelector) -> Self {
+    self.interfaces.allow.push(selector);
+    self
+  }
+
+  /// Never use the network interfaces that match this selector, even if they
+  /// are allowed by [`allow_interface`](Self::allow_interface).
+  #[must_use]
+  pub fn deny_interface(mut self, selector: InterfaceSelector) -> Self {
+    self.interfaces.deny.push(selector);
+    self
+  }
+
+  /// Set the largest RTPS message to send with a transport, e.g. to fit a
+  /// small path MTU or to use jumbo frames. Samples that do not fit in one
+  /// message are fragmented, with fragments that fit all selected transports.
+  ///
+  /// By default, UDP messages fit in the smallest MTU of the network
+  /// interfaces in use, on Linux, and otherwise in an Ethernet frame. The size
+  /// is limited to what the transport can carry, e.g. 65507 bytes for UDP
+  /// over IPv4.
+  #[must_use]
+  pub fn max_message_size(mut self, kind: TransportKind, size: usize) -> Self {
+    self.socket_options.max_message_size.insert(kind, size);
+    self
+  }
+
+  /// Set the size of the send buffers of the UDP sockets. Default is the
+  /// operating system default, which may limit throughput with large or
+  /// high-rate samples. The operating system may cap the size.
+  #[must_use]
+  pub fn send_buffer_size(mut self, size: usize) -> Self {
+    self.socket_options.send_buffer_size = Some(size);
+    self
+  }
+
+  /// Set the size of the receive buffers of the UDP sockets. A larger buffer
+  /// avoids dropping packets in bursts. The operating system may cap the
+  /// size, e.g. Linux to `net.core.rmem_max`.
+  #[must_use]
+  pub fn receive_buffer_size(mut self, size: usize) -> Self {
+    self.socket_options.receive_buffer_size = Some(size);
+    self
+  }
+
+  /// Set the time-to-live (IPv6: hop limit) of sent multicast packets, i.e.
+  /// how many routers they may pass. Must be from 1 to 255, otherwise
+  /// building fails with [`BadParameter`](Error::BadParameter). The operating
+  /// system default is usually 1, which keeps multicast in the local network.
+  #[must_use]
+  pub fn multicast_ttl(mut self, ttl: u32) -> Self {
+    self.socket_options.multicast_ttl = Some(ttl);
+    self
+  }
+
+  /// Enable or disable receiving our own multicast on this host. Default is
+  /// enabled. Participants on the same host discover each other by multicast
+  /// only if this is enabled.
+  #[must_use]
+  pub fn multicast_loopback(mut self, multicast_loopback: bool) -> Self {
+    self.socket_options.multicast_loopback = multicast_loopback;
+    self
+  }
+
+  /// Mark sent IPv4 packets with this Differentiated Services Code Point,
+  /// clamped to 0..=63. DataWriters with a non-zero TRANSPORT_PRIORITY QoS
+  /// use the priority as DSCP instead.
+  #[must_use]
+  pub fn dscp(mut self, dscp: u8) -> Self {
+    self.socket_options.dscp = Some(dscp);
+    self
+  }
+
+  /// Periodically publish the [`ParticipantHealth`] of this participant on
+  /// the vendor topic
+  /// [`HEALTH_TOPIC_NAME`](ParticipantHealth::HEALTH_TOPIC_NAME), so that
+  /// supervisors elsewhere in the domain can detect a wedged participant.
+  /// Default is disabled. The health is always available locally from
+  /// [`DomainParticipant::health`].
+  #[must_use]
+  pub fn publish_health(mut self, publish_health: bool) -> Self {
+    self.publish_health = publish_health;
+    self
+  }
+
+  /// Publish the [`ParticipantStatistics`] of this participant with the given
+  /// period on the vendor topic
+  /// [`STATISTICS_TOPIC_NAME`](ParticipantStatistics::STATISTICS_TOPIC_NAME).
+  /// Default is not to publish. The statistics are always available locally
+  /// from [`DomainParticipant::statistics`].
+  #[cfg(feature = "statistics")]
+  #[must_use]
+  pub fn publish_statistics(mut self, period: Duration) -> Self {
+    self.publish_statistics = Some(period);
+    self
+  }
+
+  /// Use a fixed GUID prefix instead of a random one. The prefix must be
+  /// unique in the domain. Together with static endpoint discovery, this
+  /// makes the GUIDs of our endpoints known in advance, because EntityIds are
+  /// assigned in creation order.
+  #[must_use]
+  pub fn guid_prefix(mut self, guid_prefix: GuidPrefix) -> Self {
+    self.guid_prefix = Some(guid_prefix);
+    self
+  }
+
+  /// Disable SEDP, the discovery of remote DataReaders and DataWriters.
+  /// Remote endpoints are then known only if declared with
+  /// [`static_endpoint`](Self::static_endpoint) or
+  /// [`DomainParticipant::add_static_endpoint`]. Participants are still
+  /// discovered via SPDP, but SEDP endpoints are not advertised. Default is
+  /// disabled, i.e. SEDP is used.
+  ///
+  /// Without the `discovery` feature there is no SPDP or SEDP at all, and
+  /// static endpoint discovery is always used.
+  #[must_use]
+  pub fn static_endpoint_discovery(mut self, static_endpoint_discovery: bool) -> Self {
+    self.static_endpoint_discovery = static_endpoint_discovery;
+    self
+  }
+
+  /// Declare a remote DataReader or DataWriter. Static endpoints are matched
+  /// with local endpoints as if they had been discovered, also when SEDP is
+  /// in use.
+  #[must_use]
+  pub fn static_endpoint(mut self, endpoint: StaticEndpoint) -> Self {
+    self.static_endpoints.push(endpoint);
+    self
+  }
+
+  /// Set the domain tag, as specified in RTPS spec v2.4 Section
+  /// "8.5.3.2 Domain tag". Participants with different domain tags ignore
+  /// each other, so several logical domains can share a domain id and its
+  /// ports. Default is the empty tag.
+  #[must_use]
+  pub fn domain_tag(mut self, domain_tag: impl Into<String>) -> Self {
+    self.domain_tag = domain_tag.into();
+    self
+  }
+
+  /// Accept or reject remote participants as they are discovered, before
+  /// any endpoint information is exchanged with them. See
+  /// [`ParticipantAuthenticator`]. By default, all participants are accepted.
+  #[must_use]
+  pub fn participant_authenticator(
+    mut self,
+    authenticator: impl ParticipantAuthenticator + 'static,
+  ) -> Self {
+    self.participant_authenticator = Some(AuthenticatorHandle::new(authenticator));
+    self
+  }
+
+  /// How to adapt to remote participants that announce an older RTPS
+  /// protocol version. By default all the adaptations of
+  /// [`ProtocolCompatibility`] are enabled.
+  #[must_use]
+  pub fn protocol_compatibility(mut self, compatibility: ProtocolCompatibility) -> Self {
+    self.compatibility = compatibility;
+    self
+  }
+
+  /// Accept submessages from a discovered remote participant only if they
+  /// come from an IP address that the participant announced in its unicast
+  /// locators. This defends against trivial spoofing of GUIDs with forged UDP
+  /// packets on an untrusted network, but it also rejects participants that
+  /// are not reachable at the addresses they announce, e.g. behind NAT.
+  /// Messages from the local host are always accepted. Default is disabled.
+  #[must_use]
+  pub fn validate_source_addresses(mut self, validate_source_addresses: bool) -> Self {
+    self.security.validate_source_addresses = validate_source_addresses;
+    self
+  }
+
+  /// Authenticate remote participants with DDS Security PKI-DH, as specified
+  /// in DDS Security spec v1.1 Section "9.3 Builtin Authentication". Only
+  /// remote participants that have a certificate from the same identity CA,
+  /// and complete the authentication handshake, are matched beyond SPDP.
+  /// Participants without DDS Security are ignored.
+  ///
+  /// The GUID prefix of the participant is derived from the certificate
+  /// subject, as required by the specification. Data is protected only if
+  /// access control is enabled with [`permissions`](Self::permissions) and
+  /// its governance asks for it.
+  #[cfg(feature = "security")]
+  #[must_use]
+  pub fn identity(mut self, identity: IdentityCredentials) -> Self {
+    self.security.identity = Some(identity);
+    self
+  }
+
+  /// Enforce DDS Security access control, as specified in DDS Security spec
+  /// v1.1 Section "9.4 Builtin Access Control". The governance document tells
+  /// which domains and topics are access controlled, and the permissions
+  /// document what each participant may do with them. Requires an
+  /// [`identity`](Self::identity), since grants are given to certificate
+  /// subjects.
+  ///
+  /// Building the participant fails with [`Error::PermissionDenied`] if it may
+  /// not join the domain, and so does creating a DataWriter or DataReader on a
+  /// topic that it may not publish or subscribe. Remote DataWriters and
+  /// DataReaders are not matched unless the permissions document that their
+  /// participant sent in the authentication handshake allows them.
+  #[cfg(feature = "security")]
+  #[must_use]
+  pub fn permissions(mut self, permissions: PermissionsCredentials) -> Self {
+    self.security.permissions = Some(permissions);
+    self
+  }
+
+  /// When the builtin cryptographic plugin replaces the master keys that
+  /// protect messages and data, e.g. for long-lived sessions. By default keys
+  /// are not replaced. Building fails with
+  /// [`BadParameter`](Error::BadParameter) if the policy is not valid.
+  #[cfg(feature = "security")]
+  #[must_use]
+  pub fn key_rotation(mut self, policy: KeyRotationPolicy) -> Self {
+    self.security.key_rotation = policy;
+    self
+  }
+
+  /// Authenticate remote participants with a custom
+  /// [`AuthenticationPlugin`] instead of the builtin PKI-DH, which
+  /// [`identity`](Self::identity) enables.
+  #[cfg(feature = "security")]
+  #[must_use]
+  pub fn authentication_plugin(mut self, plugin: impl AuthenticationPlugin + 'static) -> Self {
+    self.security.authentication = Some(AuthenticationHandle::new(plugin));
+    self
+  }
+
+  /// Enforce access control with a custom [`AccessControlPlugin`] instead of
+  /// the builtin one, which [`permissions`](Self::permissions) enables.
+  /// Requires authentication.
+  #[cfg(feature = "security")]
+  #[must_use]
+  pub fn access_control_plugin(mut self, plugin: impl AccessControlPlugin + 'static) -> Self {
+    self.security.access_control = Some(AccessControlHandle::new(plugin));
+    self
+  }
+
+  /// Protect messages and data with a custom [`CryptographicPlugin`] instead
+  /// of the builtin AES-GCM-GMAC, which is used when access control asks for
+  /// protection. Requires authentication, since keys are exchanged with
+  /// authenticated participants only.
+  #[cfg(feature = "security")]
+  #[must_use]
+  pub fn cryptographic_plugin(mut self, plugin: impl CryptographicPlugin + 'static) -> Self {
+    self.security.cryptography = Some(CryptographyHandle::new(plugin));
+    self
+  }
+
+  /// Run the event loop and Discovery of the participant as tasks on the
+  /// blocking thread pool of a tokio runtime, instead of starting two threads
+  /// of their own. The runtime must not be shut down before the participant
+  /// is closed or dropped, which waits for the tasks to finish.
+  ///
+  /// ```
+  /// # use rustdds::DomainParticipantBuilder;
+  /// let runtime = tokio::runtime::Runtime::new().unwrap();
+  /// let participant = DomainParticipantBuilder::new(0)
+  ///   .tokio_runtime(runtime.handle().clone())
+  ///   .build()
+  ///   .unwrap();
+  /// participant.close().unwrap();
+  /// ```
+  #[cfg(feature = "tokio")]
+  #[must_use]
+  pub fn tokio_runtime(mut self, runtime: tokio::runtime::Handle) -> Self {
+    self.threads.tokio_runtime = Some(runtime);
+    self
+  }
+
+  /// Use `clock` instead of the system clock for the timestamps of samples
+  /// and protocol messages, and for lease, liveliness and deadline checks.
+  ///
+  /// A [`SimulatedClock`] also drives the protocol timers, see
+  /// [`build_simulated`](Self::build_simulated). Other clocks are expected to
+  /// advance at the rate of real time, so timers run on the system clock.
+  ///
+  /// ```
+  /// # use rustdds::{Clock, DomainParticipantBuilder, Timestamp};
+  /// struct PtpClock;
+  ///
+  /// impl Clock for PtpClock {
+  ///   fn now(&self) -> Timestamp {
+  ///     // Read the PTP hardware clock here
+  ///     Timestamp::now()
+  ///   }
+  /// }
+  ///
+  /// let participant = DomainParticipantBuilder::new(0)
+  ///   .clock(PtpClock)
+  ///   .build()
+  ///   .unwrap();
+  /// let now = participant.current_time();
+  /// ```
+  #[must_use]
+  pub fn clock(mut self, clock: impl Clock) -> Self {
+    self.time_source = TimeSource::from_clock(clock);
+    self
+  }
+
+  /// Configure the name, stack size, CPU affinity or count of the threads of
+  /// a protocol loop. The configuration is checked by
+  /// [`build`](Self::build).
+  ///
+  /// Configuring [`ProtocolThread::Receive`] moves reading messages from the
+  /// event loop to receive threads, as many as the
+  /// [`count`](ThreadConfig::count).
+  ///
+  /// Send and timer threads cannot be configured, because there are none:
+  /// sending and the timers of Readers and Writers always stay on the event
+  /// loop thread, and follow the configuration of
+  /// [`ProtocolThread::EventLoop`].
+  ///
+  /// ```
+  /// # use rustdds::{DomainParticipantBuilder, ProtocolThread, ThreadConfig};
+  /// let participant = DomainParticipantBuilder::new(0)
+  ///   .thread_config(
+  ///     ProtocolThread::EventLoop,
+  ///     ThreadConfig::new().name("dds-io").cpu_affinity([0]),
+  ///   )
+  ///   .thread_config(
+  ///     ProtocolThread::Receive,
+  ///     ThreadConfig::new().name("dds-rx").count(2),
+  ///   )
+  ///   .build()
+  ///   .unwrap();
+  /// ```
+  #[must_use]
+  pub fn thread_config(mut self, thread: ProtocolThread, config: ThreadConfig) -> Self {
+    self.threads.configs.insert(thread, config);
+    self
+  }
+
+  pub fn build(self) -> Result<DomainParticipant> {
+    DomainParticipant::from_builder(&self, false).map(|(dp, _driver)| dp)
+  }
+
+  /// Build a participant that starts no threads. Its protocol loops are run
+  /// by calling [`ParticipantDriver::poll`] on the returned driver instead.
+  /// Thread configurations and a tokio runtime do not apply.
+  ///
+  /// ```
+  /// # use std::time::Duration;
+  /// # use rustdds::DomainParticipantBuilder;
+  /// let (participant, mut driver) = DomainParticipantBuilder::new(0)
+  ///   .build_driven()
+  ///   .unwrap();
+  /// driver.poll(Duration::from_millis(10));
+  /// participant.close().unwrap();
+  /// while driver.poll(Duration::from_millis(10)) {}
+  /// ```
+  pub fn build_driven(self) -> Result<(DomainParticipant, ParticipantDriver)> {
+    let (dp, driver) = DomainParticipant::from_builder(&self, true)?;
+    match driver {
+      Some(driver) => Ok((dp, driver)),
+      None => log_and_err_internal!("Driven participant has no driver"),
+    }
+  }
+
+  /// Build a participant for a simulation or a test, which runs its protocol
+  /// timers on `clock` instead of the system clock. Like
+  /// [`build_driven`](Self::build_driven), it starts no threads, so
+  /// everything happens in
+  /// [`ParticipantDriver::step`](ParticipantDriver::step) calls, and timers
+  /// expire only when the clock is advanced. See [`SimulatedClock`].
+  ///
+  /// Messages still travel over the network transports. Use a domain of the
+  /// simulation's own, so that it does not hear from other participants.
+  pub fn build_simulated(
+    self,
+    clock: &SimulatedClock,
+  ) -> Result<(DomainParticipant, ParticipantDriver)> {
+    self.clock(clock.clone()).build_driven()
+  }
+
+  fn initial_peer_locators(&self) -> Vec<Locator> {
+    let mut locators: Vec<Locator> = self
+      .initial_peer_addresses
+      .iter()
+      .flat_map(|(address, participant_ids)| {
+        participant_ids.clone().filter_map(move |participant_id| {
+          self
+            .port_mapping
+            .spdp_unicast_port(self.domain_id, participant_id)
+            .ok()
+            .map(|port| Locator::from(SocketAddr::new(*address, port)))
+        })
+      })
+      .chain(
+        self
+          .initial_peer_socket_addresses
+          .iter()
+          .map(|sa| Locator::from(*sa)),
+      )
+      .collect();
+    locators.sort();
+    locators.dedup();
+    locators
+  }
+}
+
 /// DDS DomainParticipant
 ///
 /// It is recommended that only one DomainParticipant per OS process is created,
@@ -50,7 +682,28 @@ pub struct DomainParticipant {
 }
 
 #[allow(clippy::new_without_default)]
+/// DataReader for the builtin "DCPSParticipant" topic, see
+/// [`DomainParticipant::participant_builtin_reader`].
+pub type ParticipantBuiltinTopicReader = WithKeyDataReader<
+  SpdpDiscoveredParticipantData,
+  PlCdrDeserializerAdapter<SpdpDiscoveredParticipantData>,
+>;
+
+/// DataReader for the builtin "DCPSPublication" topic, see
+/// [`DomainParticipant::publication_builtin_reader`].
+pub type PublicationBuiltinTopicReader =
+  WithKeyDataReader<DiscoveredWriterData, PlCdrDeserializerAdapter<DiscoveredWriterData>>;
+
+/// DataReader for the builtin "DCPSSubscription" topic, see
+/// [`DomainParticipant::subscription_builtin_reader`].
+pub type SubscriptionBuiltinTopicReader =
+  WithKeyDataReader<DiscoveredReaderData, PlCdrDeserializerAdapter<DiscoveredReaderData>>;
+
 impl DomainParticipant {
+  // How many unread events a discovery listener may have before new ones are
+  // dropped.
+  const DISCOVERY_LISTENER_CAPACITY: usize = 256;
+
   /// # Examples
   /// ```
   /// # use rustdds::DomainParticipant;
@@ -58,13 +711,59 @@ impl DomainParticipant {
   /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// ```
   pub fn new(domain_id: u16) -> Result<Self> {
+    DomainParticipantBuilder::new(domain_id).build()
+  }
+
+  fn from_builder(
+    builder: &DomainParticipantBuilder,
+    driven: bool,
+  ) -> Result<(Self, Option<ParticipantDriver>)> {
     trace!("DomainParticipant construct start");
+    runtime_metrics::describe();
+    let domain_id = builder.domain_id;
+    let initial_peers = builder.initial_peer_locators();
+    // Discovery needs UDP.
+    if !builder
+      .transports
+      .iter()
+      .any(|&kind| listener_tokens(kind).is_some())
+    {
+      return Error::bad_parameter("At least one UDP transport must be selected");
+    }
+    if let Some(ttl) = builder.socket_options.multicast_ttl {
+      if !(1..=255).contains(&ttl) {
+        return Error::bad_parameter("Multicast TTL must be from 1 to 255");
+      }
+    }
+    for endpoint in &builder.static_endpoints {
+      endpoint.validate()?;
+    }
+    for (thread, config) in &builder.threads.configs {
+      config.validate(*thread)?;
+    }
+    let participant_guid = builder
+      .guid_prefix
+      .map_or_else(GUID::new_participant_guid, |prefix| {
+        GUID::new(prefix, EntityId::PARTICIPANT)
+      });
+    #[cfg(feature = "security")]
+    let participant_guid = GUID::new(
+      builder.security.participant_guid_prefix(participant_guid),
+      EntityId::PARTICIPANT,
+    );
+    #[cfg_attr(not(feature = "security"), allow(unused_mut))]
+    let mut security = builder.security.clone();
+    #[cfg(feature = "security")]
+    security.enable_plugins(domain_id, participant_guid)?;
 
     // Discovery join channel is used to just send a join handle into the inner
     // participant, so its .drop() can wait until discovery has had a chance to
     // stop.
     let (djh_sender, djh_receiver) = mio_channel::channel();
 
+    // Starts the event loop and Discovery
+    let spawner = BackgroundSpawner::new(builder.threads.clone(), driven);
+
     // Channel is used to notify Discovery of (duplicate) SPDP messages from the
     // wire.
     let (spdp_liveness_sender, spdp_liveness_receiver) = mio_channel::sync_channel(8);
@@ -84,13 +783,40 @@ impl DomainParticipant {
     let (discovery_command_sender, discovery_command_receiver) =
       mio_channel::sync_channel::<DiscoveryCommand>(64);
 
+    // Participant-level status events are sent to all listeners created by
+    // .status_listener()
+    let status_broadcaster = StatusBroadcaster::new();
+
+    // Discovery events are sent to all listeners created by
+    // .discovery_listener(). These come in bursts when a large participant
+    // is discovered, so the listeners have more room.
+    let discovery_event_broadcaster =
+      StatusBroadcaster::with_listener_capacity(Self::DISCOVERY_LISTENER_CAPACITY);
+
+    // Protocol threads report their event loop rounds here.
+    let health_monitor = HealthMonitor::new();
+
     // intermediate DP wrapper
     let dp = DomainParticipantDisc::new(
       domain_id,
+      participant_guid,
+      builder.multicast_discovery,
+      builder.port_mapping,
+      builder.transports.clone(),
+      builder.interfaces.clone(),
+      builder.socket_options.clone(),
       djh_receiver,
       discovery_update_notification_receiver,
       discovery_command_sender,
       spdp_liveness_sender,
+      status_broadcaster.clone(),
+      discovery_event_broadcaster.clone(),
+      health_monitor.clone(),
+      builder.domain_tag.clone(),
+      builder.compatibility.clone(),
+      builder.time_source.clone(),
+      security.clone(),
+      &spawner,
     )?;
     let self_locators = dp.self_locators();
 
@@ -105,10 +831,19 @@ impl DomainParticipant {
     // Construct and start background thread
     let dp_clone = dp.weak_clone();
     let disc_db_clone = dp.discovery_db();
-    let discovery_handle = thread::Builder::new()
-      .name("RustDDS discovery thread".to_string())
-      .spawn(move || {
-        if let Ok(mut discovery) = Discovery::new(
+    let statistics_monitor = dp.statistics_monitor();
+    let publish_health = builder.publish_health;
+    let publish_statistics = builder.publish_statistics;
+    let sedp_enabled = cfg!(feature = "discovery") && !builder.static_endpoint_discovery;
+    let static_endpoints = builder.static_endpoints.clone();
+    let domain_tag = builder.domain_tag.clone();
+    let participant_authenticator = builder.participant_authenticator.clone();
+    let time_source = builder.time_source.clone();
+    let discovery_handle = spawner.spawn(
+      ProtocolThread::Discovery,
+      "RustDDS discovery thread".to_string(),
+      move || {
+        let mut discovery = Discovery::new(
           dp_clone,
           disc_db_clone,
           discovery_started_sender,
@@ -116,10 +851,28 @@ impl DomainParticipant {
           discovery_command_receiver,
           spdp_liveness_receiver,
           self_locators,
-        ) {
-          discovery.discovery_event_loop(); // run the event loop
+          initial_peers,
+          status_broadcaster,
+          discovery_event_broadcaster,
+          health_monitor,
+          publish_health,
+          statistics_monitor,
+          publish_statistics,
+          sedp_enabled,
+          static_endpoints,
+          domain_tag,
+          participant_authenticator,
+          time_source,
+          security,
+        )
+        .ok()?;
+        if discovery.start() {
+          Some(discovery)
+        } else {
+          None
         }
-      })?;
+      },
+    )?;
 
     djh_sender.send(discovery_handle).unwrap_or(()); // send join handle to inner participant
 
@@ -128,7 +881,7 @@ impl DomainParticipant {
       Ok(Ok(())) => {
         // normal case
         info!("Discovery started. Participant constructed.");
-        Ok(dp)
+        Ok((dp, spawner.into_driver()))
       }
       Ok(Err(e)) => {
         std::mem::drop(dp);
@@ -145,6 +898,10 @@ impl DomainParticipant {
   /// * `qos` - Takes [qos policies](qos/struct.QosPolicies.html) for publisher
   ///   and given to DataWriter as default.
   ///
+  /// The Publisher is enabled, unless the
+  /// [`EntityFactory`](crate::policy::EntityFactory) policy of this
+  /// DomainParticipant says otherwise.
+  ///
   /// # Examples
   ///
   /// ```
@@ -166,6 +923,10 @@ impl DomainParticipant {
   /// * `qos` - Takes [qos policies](qos/struct.QosPolicies.html) for subscriber
   ///   and given to DataReader as default.
   ///
+  /// The Subscriber is enabled, unless the
+  /// [`EntityFactory`](crate::policy::EntityFactory) policy of this
+  /// DomainParticipant says otherwise.
+  ///
   /// # Examples
   ///
   /// ```
@@ -190,6 +951,20 @@ impl DomainParticipant {
   /// * `qos` - Takes [qos policies](qos/struct.QosPolicies.html) that are
   ///   distributed to DataReaders and DataWriters.
   ///
+  /// A topic name starts with a letter, `_` or `/`, and the rest of it are
+  /// letters, digits or one of `_-/.`. Letters and digits may be non-ASCII,
+  /// but names are compared as UTF-8 bytes, so e.g. differently normalized
+  /// forms of the same name are different topics. A type name may contain
+  /// anything but control characters. Both names are at most
+  /// [`Topic::MAX_NAME_LENGTH`] bytes long.
+  ///
+  /// Fails with
+  /// [`BadParameter`](crate::dds::values::result::Error::BadParameter) if a
+  /// name is not valid, and with
+  /// [`PreconditionNotMet`](crate::dds::values::result::Error::PreconditionNotMet)
+  /// if this participant already has a topic of the same name, but another
+  /// type. Creating a topic of the same name and type again is allowed.
+  ///
   /// # Examples
   ///
   /// ```
@@ -198,6 +973,12 @@ impl DomainParticipant {
   /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey);
+  /// assert!(topic.is_ok());
+  ///
+  /// let bad_name = domain_participant.create_topic("some topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey);
+  /// assert!(bad_name.is_err());
+  /// let other_type = domain_participant.create_topic("some_topic".to_string(), "OtherType".to_string(), &qos, TopicKind::WithKey);
+  /// assert!(other_type.is_err());
   /// ```
   pub fn create_topic(
     &self,
@@ -215,36 +996,202 @@ impl DomainParticipant {
       .create_topic(&w, name, type_desc, qos, topic_kind)
   }
 
-  pub fn find_topic(&self, name: &str, timeout: Duration) -> Result<Option<Topic>> {
-    let w = self.weak_clone();
-    self.dpi.lock().unwrap().find_topic(&w, name, timeout)
+  /// Create a [`ContentFilteredTopic`], which selects samples of the
+  /// `related_topic` by the filter expression.
+  ///
+  /// Fails with
+  /// [`BadParameter`](crate::dds::values::result::Error::BadParameter) if the
+  /// expression is malformed, or there are fewer parameters than the
+  /// expression refers to, and with
+  /// [`PreconditionNotMet`](crate::dds::values::result::Error::PreconditionNotMet)
+  /// if `related_topic` belongs to another DomainParticipant.
+  pub fn create_contentfilteredtopic(
+    &self,
+    name: String,
+    related_topic: &Topic,
+    filter_expression: &str,
+    expression_parameters: Vec<String>,
+  ) -> Result<ContentFilteredTopic> {
+    if related_topic.participant().as_ref() != Some(self) {
+      return Error::precondition_not_met("Related Topic belongs to another DomainParticipant");
+    }
+    ContentFilteredTopic::new(
+      name,
+      related_topic,
+      filter_expression,
+      expression_parameters,
+    )
   }
 
+  /// Create a [`MultiTopic`], which combines samples of several Topics as
+  /// given by the subscription expression. The result type is named
+  /// `type_name`.
+  ///
+  /// Fails with
+  /// [`BadParameter`](crate::dds::values::result::Error::BadParameter) if the
+  /// expression is malformed, or there are fewer parameters than the
+  /// expression refers to.
+  pub fn create_multitopic(
+    &self,
+    name: String,
+    type_name: String,
+    subscription_expression: &str,
+    expression_parameters: Vec<String>,
+  ) -> Result<MultiTopic> {
+    MultiTopic::new(
+      self.weak_clone(),
+      name,
+      type_name,
+      subscription_expression,
+      expression_parameters,
+    )
+  }
+
+  /// Finds a Topic by name, waiting up to `timeout` for it to appear.
+  ///
+  /// Topics created locally with [`create_topic`](Self::create_topic) are
+  /// found immediately. Otherwise the Topic is looked up from discovery data,
+  /// i.e. it must be announced by a remote participant, or used by one of its
+  /// DataReaders or DataWriters. The returned Topic has the type name and QoS
+  /// announced by the remote participant, so endpoints can be created without
+  /// knowing them beforehand.
+  ///
+  /// Returns `None` if the Topic was not found before the timeout.
+  ///
   /// # Examples
   ///
   /// ```
-  /// # use rustdds::DomainParticipant;
+  /// # use std::time::Duration;
+  /// # use rustdds::{DomainParticipant, TopicKind, QosPolicyBuilder};
   ///
   /// let domain_participant = DomainParticipant::new(0).unwrap();
-  /// let domain_id = domain_participant.domain_id();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let topic = domain_participant
+  ///   .create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey)
+  ///   .unwrap();
+  ///
+  /// let found = domain_participant
+  ///   .find_topic("some_topic", Duration::from_millis(100))
+  ///   .unwrap();
+  /// assert_eq!(found, Some(topic));
   /// ```
-  pub fn domain_id(&self) -> u16 {
-    self.dpi.lock().unwrap().domain_id()
+  pub fn find_topic(&self, name: &str, timeout: Duration) -> Result<Option<Topic>> {
+    if let Some(topic) = self.lookup_topicdescription(name) {
+      return Ok(Some(topic));
+    }
+
+    let w = self.weak_clone();
+    let poll = mio::Poll::new()?;
+    let mut events = mio::Events::with_capacity(1);
+    // Register before the first check, and use level trigger, so that no update
+    // is missed. The participant is not locked while waiting, because
+    // Discovery needs it to process the updates.
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .register_discovery_db_events(&poll)?;
+
+    let find_end = Instant::now() + timeout;
+    let found = loop {
+      match self
+        .dpi
+        .lock()
+        .unwrap()
+        .find_topic_in_discovery_db(&w, name)
+      {
+        Ok(None) => (),
+        other => break other,
+      }
+      // Give up only after a check made at or past the deadline.
+      let now = Instant::now();
+      if now >= find_end {
+        break Ok(None);
+      }
+      if let Err(e) = poll.poll(&mut events, Some(find_end - now)) {
+        break Err(e.into());
+      }
+      if events.iter().next().is_some() {
+        self.dpi.lock().unwrap().discovery_db_updated();
+      }
+    };
+
+    // The receiver can be registered to only one Poll at a time, so release it
+    // for the next call.
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .deregister_discovery_db_events(&poll)?;
+    found
+  }
+
+  /// Looks up a Topic by name among those created locally, or returned earlier
+  /// by [`find_topic`](Self::find_topic). Does not wait, and does not look at
+  /// discovery data.
+  ///
+  /// If several Topics have been created with the same name, the latest one
+  /// is returned.
+  pub fn lookup_topicdescription(&self, name: &str) -> Option<Topic> {
+    self.dpi.lock().unwrap().lookup_topicdescription(name)
+  }
+
+  /// The [`TypeRegistry`] of this participant. DataWriters and DataReaders
+  /// created on Topics of registered types are checked against it.
+  pub fn type_registry(&self) -> TypeRegistry {
+    self.dpi.lock().unwrap().type_registry()
   }
 
+  /// Deletes all Publishers and Subscribers created from this
+  /// DomainParticipant, along with their DataWriters and DataReaders, and
+  /// forgets the Topics created so far. Built-in entities, e.g. those of
+  /// Discovery, are not affected.
+  ///
+  /// Operations on the deleted entities fail with
+  /// [`AlreadyDeleted`](Error::AlreadyDeleted). The DomainParticipant itself
+  /// can still be used to create new ones.
+  ///
   /// # Examples
   ///
   /// ```
-  /// # use rustdds::DomainParticipant;
-  ///
+  /// # use rustdds::{DomainParticipant, QosPolicyBuilder, TopicKind};
+  /// # use serde::{Serialize, Deserialize};
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType {}
+  /// #
   /// let domain_participant = DomainParticipant::new(0).unwrap();
-  /// let participant_id = domain_participant.participant_id();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  /// let topic = domain_participant
+  ///   .create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::NoKey)
+  ///   .unwrap();
+  /// let data_writer = publisher.create_datawriter_no_key_cdr::<SomeType>(&topic, None).unwrap();
+  ///
+  /// domain_participant.delete_contained_entities().unwrap();
+  /// assert!(data_writer.write(SomeType {}, None).is_err());
+  /// assert!(domain_participant.lookup_topicdescription("some_topic").is_none());
   /// ```
-  pub fn participant_id(&self) -> u16 {
-    self.dpi.lock().unwrap().participant_id()
+  pub fn delete_contained_entities(&self) -> Result<()> {
+    // Take the entities out first: deleting them must not happen while holding
+    // the participant lock.
+    let (publishers, subscribers) = self.dpi.lock().unwrap().take_contained_entities();
+    for publisher in publishers.iter().filter_map(PublisherWeak::upgrade) {
+      publisher.delete()?;
+    }
+    for subscriber in subscribers.iter().filter_map(SubscriberWeak::upgrade) {
+      subscriber.delete()?;
+    }
+    Ok(())
   }
 
-  /// Gets all DiscoveredTopics from DDS network
+  /// Shuts down the participant in an orderly way: remote participants are
+  /// told that this participant and its readers and writers are gone, and
+  /// all instances of the writers are unregistered. Remote peers thus learn
+  /// about it immediately, rather than when the lease duration runs out.
+  ///
+  /// This is done also when the last clone of the participant is dropped.
+  /// After closing, the participant and its entities no longer communicate.
+  /// Closing again does nothing.
   ///
   /// # Examples
   ///
@@ -252,13 +1199,219 @@ impl DomainParticipant {
   /// # use rustdds::DomainParticipant;
   ///
   /// let domain_participant = DomainParticipant::new(0).unwrap();
-  /// let discovered_topics = domain_participant.discovered_topics();
-  /// for dtopic in discovered_topics.iter() {
-  ///   // do something
-  /// }
+  /// domain_participant.close().unwrap();
   /// ```
-  pub fn discovered_topics(&self) -> Vec<DiscoveredTopicData> {
-    self.dpi.lock().unwrap().discovered_topics()
+  pub fn close(&self) -> Result<()> {
+    // Discovery may need the participant lock to finish, so it must not be held
+    // while joining.
+    let discovery_handle = self.dpi.lock()?.stop_discovery();
+    if let Some(handle) = discovery_handle {
+      handle
+        .join()
+        .or_else(|_| log_and_err_internal!("Discovery thread panicked"))?;
+    }
+    self.dpi.lock()?.stop_event_loop();
+    Ok(())
+  }
+
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::DomainParticipant;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let domain_id = domain_participant.domain_id();
+  /// ```
+  pub fn domain_id(&self) -> u16 {
+    self.dpi.lock().unwrap().domain_id()
+  }
+
+  /// The participant id, which selects the unicast port numbers of this
+  /// participant, see [`PortMapping`](crate::PortMapping). It is the smallest
+  /// id, whose discovery and user traffic unicast ports were both free when
+  /// the participant was created. Participants of the same domain on the same
+  /// host thus get different ids.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::DomainParticipant;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let participant_id = domain_participant.participant_id();
+  /// ```
+  pub fn participant_id(&self) -> u16 {
+    self.dpi.lock().unwrap().participant_id()
+  }
+
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::DomainParticipantBuilder;
+  ///
+  /// let domain_participant = DomainParticipantBuilder::new(0)
+  ///   .domain_tag("test bench")
+  ///   .build()
+  ///   .unwrap();
+  /// assert_eq!(domain_participant.domain_tag(), "test bench");
+  /// ```
+  pub fn domain_tag(&self) -> String {
+    self.dpi.lock().unwrap().domain_tag.clone()
+  }
+
+  // DDS Security access control of local DataWriters and DataReaders
+  #[cfg(feature = "security")]
+  pub(crate) fn check_topic_access(&self, topic_name: &str, access: TopicAccess) -> Result<()> {
+    match &self.dpi.lock().unwrap().access_control {
+      Some(access_control) => access_control
+        .check_local_endpoint(topic_name, access)
+        .or_else(|e| Error::permission_denied(e.to_string())),
+      None => Ok(()),
+    }
+  }
+
+  /// Gets all DiscoveredTopics from DDS network
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::DomainParticipant;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let discovered_topics = domain_participant.discovered_topics();
+  /// for dtopic in discovered_topics.iter() {
+  ///   // do something
+  /// }
+  /// ```
+  pub fn discovered_topics(&self) -> Vec<DiscoveredTopicData> {
+    self.dpi.lock().unwrap().discovered_topics()
+  }
+
+  /// Gets the SPDP data of all remote DomainParticipants currently known
+  /// through Discovery. This includes e.g. their USER_DATA QoS.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::DomainParticipant;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// for participant in domain_participant.discovered_participants() {
+  ///   println!("{:?} {:?}", participant.participant_guid, participant.user_data);
+  /// }
+  /// ```
+  pub fn discovered_participants(&self) -> Vec<SpdpDiscoveredParticipantData> {
+    self.dpi.lock().unwrap().discovered_participants()
+  }
+
+  /// Creates a read-only DataReader for the builtin "DCPSParticipant" topic.
+  /// Each instance is a DomainParticipant known through Discovery, including
+  /// this one. An instance becomes NOT_ALIVE when its participant is lost.
+  ///
+  /// Builtin DataReaders also see data that Discovery received before they
+  /// were created. They are not announced to other participants. Without the
+  /// `discovery` feature nothing is received on the builtin topics, so these
+  /// DataReaders stay empty.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::DomainParticipant;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let mut reader = domain_participant.participant_builtin_reader().unwrap();
+  /// while let Ok(Some(sample)) = reader.take_next_sample() {
+  ///   if let Ok(participant) = sample.value() {
+  ///     println!("{:?}", participant.participant_guid);
+  ///   }
+  /// }
+  /// ```
+  pub fn participant_builtin_reader(&self) -> Result<ParticipantBuiltinTopicReader> {
+    self.create_builtin_reader(
+      "DCPSParticipant",
+      "SPDPDiscoveredParticipantData",
+      &Discovery::create_spdp_patricipant_qos(),
+    )
+  }
+
+  /// Creates a read-only DataReader for the builtin "DCPSPublication" topic.
+  /// Each instance is a DataWriter, local or remote, with its topic name, type
+  /// name and QoS. See
+  /// [`participant_builtin_reader`](Self::participant_builtin_reader).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::DomainParticipant;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let mut reader = domain_participant.publication_builtin_reader().unwrap();
+  /// while let Ok(Some(sample)) = reader.take_next_sample() {
+  ///   if let Ok(writer) = sample.value() {
+  ///     let data = &writer.publication_topic_data;
+  ///     println!("{} {} {:?}", data.topic_name, data.type_name, data.reliability);
+  ///   }
+  /// }
+  /// ```
+  pub fn publication_builtin_reader(&self) -> Result<PublicationBuiltinTopicReader> {
+    self.create_builtin_reader(
+      "DCPSPublication",
+      "DiscoveredWriterData",
+      &Discovery::publisher_qos(),
+    )
+  }
+
+  /// Creates a read-only DataReader for the builtin "DCPSSubscription" topic.
+  /// Each instance is a DataReader, local or remote, with its topic name, type
+  /// name and QoS. See
+  /// [`participant_builtin_reader`](Self::participant_builtin_reader).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::DomainParticipant;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let mut reader = domain_participant.subscription_builtin_reader().unwrap();
+  /// while let Ok(Some(sample)) = reader.take_next_sample() {
+  ///   if let Ok(reader) = sample.value() {
+  ///     let data = &reader.subscription_topic_data;
+  ///     println!("{} {} {:?}", data.topic_name(), data.type_name(), data.qos());
+  ///   }
+  /// }
+  /// ```
+  pub fn subscription_builtin_reader(&self) -> Result<SubscriptionBuiltinTopicReader> {
+    self.create_builtin_reader(
+      "DCPSSubscription",
+      "DiscoveredReaderData",
+      &Discovery::subscriber_qos(),
+    )
+  }
+
+  // The topics must match the ones created by Discovery, because the builtin
+  // DataReaders read the same DDSCache topics.
+  fn create_builtin_reader<D>(
+    &self,
+    topic_name: &str,
+    type_name: &str,
+    topic_qos: &QosPolicies,
+  ) -> Result<WithKeyDataReader<D, PlCdrDeserializerAdapter<D>>>
+  where
+    D: DeserializeOwned + Keyed + 'static,
+    <D as Keyed>::K: Key,
+    PlCdrDeserializerAdapter<D>: with_key::DeserializerAdapter<D>,
+  {
+    // Like the ones of Discovery, this is not a user-created Subscriber.
+    let subscriber = self
+      .weak_clone()
+      .create_subscriber(&Discovery::subscriber_qos())?;
+    let topic = self.create_topic(
+      topic_name.to_string(),
+      type_name.to_string(),
+      topic_qos,
+      TopicKind::WithKey,
+    )?;
+    subscriber.create_builtin_datareader(&topic)
   }
 
   /// Manually asserts liveliness, affecting all writers with
@@ -277,6 +1430,292 @@ impl DomainParticipant {
     self.dpi.lock().unwrap().assert_liveliness()
   }
 
+  /// Ignores a remote DomainParticipant, as in DDS `ignore_participant`.
+  ///
+  /// Its discovery data is dropped from now on, and local readers and writers
+  /// are unmatched from its writers and readers. The participant is not
+  /// reported in [`discovered_participants`](Self::discovered_participants).
+  /// This cannot be undone. Builtin topic DataReaders are not affected.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::{DomainParticipant, RTPSEntity};
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// for participant in domain_participant.discovered_participants() {
+  ///   let guid_prefix = participant.participant_guid.prefix;
+  ///   if guid_prefix != domain_participant.guid_prefix() {
+  ///     domain_participant.ignore_participant(guid_prefix).unwrap();
+  ///   }
+  /// }
+  /// ```
+  pub fn ignore_participant(&self, guid_prefix: GuidPrefix) -> Result<()> {
+    if guid_prefix == self.guid_prefix() {
+      return log_and_err_precondition_not_met!("Cannot ignore the local DomainParticipant.");
+    }
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .send_discovery_command(DiscoveryCommand::IgnoreParticipant { guid_prefix })
+  }
+
+  /// Ignores a remote DataWriter, as in DDS `ignore_publication`. Local
+  /// DataReaders are unmatched from it and will not match it again. See
+  /// [`ignore_participant`](Self::ignore_participant).
+  pub fn ignore_publication(&self, writer_guid: GUID) -> Result<()> {
+    if writer_guid.prefix == self.guid_prefix() {
+      return log_and_err_precondition_not_met!("Cannot ignore a local DataWriter.");
+    }
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .send_discovery_command(DiscoveryCommand::IgnorePublication { guid: writer_guid })
+  }
+
+  /// Ignores a remote DataReader, as in DDS `ignore_subscription`. Local
+  /// DataWriters are unmatched from it and will not match it again. See
+  /// [`ignore_participant`](Self::ignore_participant).
+  pub fn ignore_subscription(&self, reader_guid: GUID) -> Result<()> {
+    if reader_guid.prefix == self.guid_prefix() {
+      return log_and_err_precondition_not_met!("Cannot ignore a local DataReader.");
+    }
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .send_discovery_command(DiscoveryCommand::IgnoreSubscription { guid: reader_guid })
+  }
+
+  /// Declares a remote DataReader or DataWriter at run time, see
+  /// [`DomainParticipantBuilder::static_endpoint_discovery`]. Declaring the
+  /// same GUID again replaces the previous declaration.
+  pub fn add_static_endpoint(&self, endpoint: StaticEndpoint) -> Result<()> {
+    endpoint.validate()?;
+    if endpoint.guid.prefix == self.guid_prefix() {
+      return log_and_err_precondition_not_met!("Static endpoint cannot be local.");
+    }
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .send_discovery_command(DiscoveryCommand::AddStaticEndpoint { endpoint })
+  }
+
+  /// Makes an XTypes [`TypeObject`] available to remote participants through
+  /// the TypeLookup service. Returns the TypeIdentifier by which it is
+  /// requested.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::{DomainParticipant, TypeObject};
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// // DHEADER and EK_MINIMAL discriminator, followed by the MinimalTypeObject
+  /// let type_object = TypeObject::from_xcdr2_bytes(vec![4, 0, 0, 0, 0xF1, 0x51, 0, 0]).unwrap();
+  /// let type_id = domain_participant.register_type_object(type_object.clone());
+  /// assert_eq!(domain_participant.type_object(&type_id), Some(type_object));
+  /// ```
+  pub fn register_type_object(&self, type_object: TypeObject) -> TypeIdentifier {
+    self
+      .discovery_db()
+      .write()
+      .unwrap_or_else(|e| panic!("DiscoveryDB is poisoned. {:?}", e))
+      .add_type_object(type_object)
+  }
+
+  /// Asks remote participants for the TypeObjects of the given
+  /// TypeIdentifiers, using the XTypes TypeLookup service. Replies arrive in
+  /// the background, and are then available from
+  /// [`type_object`](Self::type_object).
+  pub fn request_type_objects(&self, type_ids: Vec<TypeIdentifier>) -> Result<()> {
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .send_discovery_command(DiscoveryCommand::RequestTypeObjects { type_ids })
+  }
+
+  /// Returns a TypeObject registered with
+  /// [`register_type_object`](Self::register_type_object) or received from a
+  /// remote participant.
+  pub fn type_object(&self, type_id: &TypeIdentifier) -> Option<TypeObject> {
+    self
+      .discovery_db()
+      .read()
+      .unwrap_or_else(|e| panic!("DiscoveryDB is poisoned. {:?}", e))
+      .find_type_object(type_id)
+      .cloned()
+  }
+
+  /// Creates a listener for participant-level status events, such as
+  /// [`DomainParticipantStatus::ClockJumpHandled`].
+  ///
+  /// Each listener receives its own copy of every event. Register the
+  /// listener to a `mio::Poll` using [`StatusEvented::as_status_evented`]
+  /// before expecting events from it.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// # use rustdds::{DomainParticipant, StatusEvented};
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let mut listener = domain_participant.status_listener();
+  /// let _evented = listener.as_status_evented();
+  /// assert!(listener.try_recv_status().is_none());
+  /// ```
+  pub fn status_listener(&self) -> DomainParticipantStatusListener {
+    DomainParticipantStatusListener {
+      status_receiver: self.dpi.lock().unwrap().status_broadcaster.new_listener(),
+    }
+  }
+
+  /// Creates a listener for changes in remote participants, writers and
+  /// readers, as seen by Discovery. This is an alternative to polling the
+  /// builtin topic readers, e.g. for building a topology monitor.
+  ///
+  /// Our own participant and its endpoints are not reported. Each listener
+  /// receives its own copy of every event. Register the listener to a
+  /// `mio::Poll` using [`StatusEvented::as_status_evented`] before expecting
+  /// events from it.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// # use rustdds::{DomainParticipant, StatusEvented};
+  /// use rustdds::dds::statusevents::DiscoveryEvent;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let mut listener = domain_participant.discovery_listener();
+  /// let _evented = listener.as_status_evented();
+  /// while let Some(event) = listener.try_recv_status() {
+  ///   if let DiscoveryEvent::WriterDiscovered { writer } = event {
+  ///     println!("{}", writer.publication_topic_data.topic_name);
+  ///   }
+  /// }
+  /// ```
+  pub fn discovery_listener(&self) -> DiscoveryListener {
+    DiscoveryListener {
+      event_receiver: self
+        .dpi
+        .lock()
+        .unwrap()
+        .discovery_event_broadcaster
+        .new_listener(),
+    }
+  }
+
+  /// Reports whether the background protocol threads of this participant are
+  /// alive and keeping up with their events. Unhealthy state usually means
+  /// that the participant is wedged and should be restarted.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// # use rustdds::DomainParticipant;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let health = domain_participant.health();
+  /// assert!(health.event_loop.alive && health.discovery.alive);
+  /// ```
+  pub fn health(&self) -> ParticipantHealth {
+    let guid = self.guid();
+    self.dpi.lock().unwrap().health_monitor.health(guid)
+  }
+
+  /// The current time on the clock of this participant, see
+  /// [`DomainParticipantBuilder::clock`]. Source timestamps for
+  /// [`WriteOptions`](crate::WriteOptions) can be taken from this.
+  pub fn current_time(&self) -> Timestamp {
+    self.time_source().now()
+  }
+
+  /// Internal statistics of this participant and its DataReaders and
+  /// DataWriters: messages and bytes sent and received, heartbeats,
+  /// acknowledgments, retransmissions, dropped samples, and the size of the
+  /// sample cache. Counters are cumulative since the participant was created,
+  /// so rates are obtained by comparing successive snapshots.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// # use rustdds::{DomainParticipant, RTPSEntity};
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let statistics = domain_participant.statistics();
+  /// assert_eq!(statistics.participant_guid, domain_participant.guid());
+  /// ```
+  #[cfg(feature = "statistics")]
+  pub fn statistics(&self) -> ParticipantStatistics {
+    let guid = self.guid();
+    self.statistics_monitor().statistics(guid)
+  }
+
+  /// Starts writing the RTPS messages that this participant sends and
+  /// receives over UDP to a pcapng file, for offline analysis with e.g.
+  /// Wireshark. An existing file is overwritten. If a capture is already going
+  /// on, it is finished and the new one starts.
+  ///
+  /// Sockets do not give the IP and UDP headers of the messages, so they are
+  /// made up from the socket addresses. Sent messages have the unspecified
+  /// address as source, because that is what the sockets are bound to.
+  ///
+  /// # Example
+  ///
+  /// ```no_run
+  /// # use rustdds::DomainParticipant;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// domain_participant.start_packet_capture("rtps.pcapng").unwrap();
+  /// // ... communicate ...
+  /// domain_participant.stop_packet_capture().unwrap();
+  /// ```
+  pub fn start_packet_capture<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+    let file = File::create(path)?;
+    self.start_packet_capture_to(BufWriter::new(file))
+  }
+
+  /// Same as [`start_packet_capture`](Self::start_packet_capture), but writes
+  /// the pcapng data to the given output.
+  pub fn start_packet_capture_to<W: Write + Send + 'static>(&self, output: W) -> Result<()> {
+    self.packet_capture().start(Box::new(output))?;
+    Ok(())
+  }
+
+  /// Finishes the packet capture, if one is going on.
+  pub fn stop_packet_capture(&self) -> Result<()> {
+    self.packet_capture().stop()?;
+    Ok(())
+  }
+
+  fn packet_capture(&self) -> PacketCapture {
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .dpi
+      .lock()
+      .unwrap()
+      .packet_capture
+      .clone()
+  }
+
+  pub(crate) fn statistics_monitor(&self) -> StatisticsMonitor {
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .dpi
+      .lock()
+      .unwrap()
+      .statistics
+      .clone()
+  }
+
   pub(crate) fn weak_clone(&self) -> DomainParticipantWeak {
     DomainParticipantWeak::new(self, self.guid())
   }
@@ -285,6 +1724,10 @@ impl DomainParticipant {
     self.dpi.lock().unwrap().dds_cache()
   }
 
+  pub(crate) fn time_source(&self) -> TimeSource {
+    self.dpi.lock().unwrap().time_source()
+  }
+
   pub(crate) fn discovery_db(&self) -> Arc<RwLock<DiscoveryDB>> {
     self
       .dpi
@@ -314,27 +1757,78 @@ impl PartialEq for DomainParticipant {
   }
 }
 
-#[derive(Clone)]
-pub struct DomainParticipantWeak {
-  dpi: Weak<Mutex<DomainParticipantDisc>>,
-  // This struct caches the GUID to avoid construction deadlocks
-  guid: GUID,
+/// Receives participant-level status events. See
+/// [`DomainParticipant::status_listener`].
+pub struct DomainParticipantStatusListener {
+  status_receiver: StatusReceiver<DomainParticipantStatus>,
 }
 
-impl DomainParticipantWeak {
-  pub fn new(dp: &DomainParticipant, guid: GUID) -> Self {
-    Self {
-      dpi: Arc::downgrade(&dp.dpi),
-      guid,
-    }
+impl StatusEvented<DomainParticipantStatus> for DomainParticipantStatusListener {
+  fn as_status_evented(&mut self) -> &dyn mio::Evented {
+    self.status_receiver.as_status_evented()
   }
 
-  pub fn create_publisher(&self, qos: &QosPolicies) -> Result<Publisher> {
-    self
-      .dpi
-      .upgrade()
+  fn try_recv_status(&self) -> Option<DomainParticipantStatus> {
+    self.status_receiver.try_recv_status()
+  }
+}
+
+impl DomainParticipantStatusListener {
+  /// Invokes the callbacks of `listener` for the status events since the
+  /// previous call. Only the callbacks selected by `mask` are invoked, except
+  /// for the ones that are not DDS standard statuses, which are always
+  /// invoked.
+  ///
+  /// The callbacks run in the calling thread. Returns the number of callbacks
+  /// invoked.
+  pub fn dispatch_to_listener<L>(&mut self, listener: &mut L, mask: StatusMask) -> usize
+  where
+    L: ParticipantListener + ?Sized,
+  {
+    self.status_receiver.enable();
+    std::iter::from_fn(|| self.status_receiver.try_recv_status())
+      .map(|status| dispatch_participant_status(listener, status, mask))
+      .filter(|&invoked| invoked)
+      .count()
+  }
+}
+
+/// Receives discovery events. See [`DomainParticipant::discovery_listener`].
+pub struct DiscoveryListener {
+  event_receiver: StatusReceiver<DiscoveryEvent>,
+}
+
+impl StatusEvented<DiscoveryEvent> for DiscoveryListener {
+  fn as_status_evented(&mut self) -> &dyn mio::Evented {
+    self.event_receiver.as_status_evented()
+  }
+
+  fn try_recv_status(&self) -> Option<DiscoveryEvent> {
+    self.event_receiver.try_recv_status()
+  }
+}
+
+#[derive(Clone)]
+pub struct DomainParticipantWeak {
+  dpi: Weak<Mutex<DomainParticipantDisc>>,
+  // This struct caches the GUID to avoid construction deadlocks
+  guid: GUID,
+}
+
+impl DomainParticipantWeak {
+  pub fn new(dp: &DomainParticipant, guid: GUID) -> Self {
+    Self {
+      dpi: Arc::downgrade(&dp.dpi),
+      guid,
+    }
+  }
+
+  pub fn create_publisher(&self, qos: &QosPolicies) -> Result<Publisher> {
+    self
+      .dpi
+      .upgrade()
       .ok_or(Error::OutOfResources)
-      .and_then(|dpi| dpi.lock().unwrap().create_publisher(self, qos))
+      .and_then(|dpi| dpi.lock().unwrap().create_builtin_publisher(self, qos))
   }
 
   pub fn create_subscriber(&self, qos: &QosPolicies) -> Result<Subscriber> {
@@ -342,7 +1836,7 @@ impl DomainParticipantWeak {
       .dpi
       .upgrade()
       .ok_or(Error::OutOfResources)
-      .and_then(|dpi| dpi.lock().unwrap().create_subscriber(self, qos))
+      .and_then(|dpi| dpi.lock().unwrap().create_builtin_subscriber(self, qos))
   }
 
   pub fn create_topic(
@@ -417,23 +1911,64 @@ pub(crate) struct DomainParticipantDisc {
   dpi: Arc<Mutex<DomainParticipantInner>>,
   // Discovery control
   discovery_command_sender: mio_channel::SyncSender<DiscoveryCommand>,
-  discovery_join_handle: mio_channel::Receiver<JoinHandle<()>>,
+  discovery_join_handle: mio_channel::Receiver<BackgroundTask>,
   // This allows deterministic generation of EntityIds for DataReader, DataWriter, etc.
   entity_id_generator: atomic::AtomicU32,
+  status_broadcaster: StatusBroadcaster<DomainParticipantStatus>,
+  discovery_event_broadcaster: StatusBroadcaster<DiscoveryEvent>,
+  health_monitor: HealthMonitor,
+  domain_tag: String,
+  #[cfg(feature = "security")]
+  access_control: Option<AccessControlHandle>,
+  qos: QosPolicies,
+  // Publishers and Subscribers created by the application, for
+  // delete_contained_entities
+  publishers: Vec<PublisherWeak>,
+  subscribers: Vec<SubscriberWeak>,
+  type_registry: TypeRegistry,
+  // Set by close()
+  closed: bool,
 }
 
 impl DomainParticipantDisc {
+  #[allow(clippy::too_many_arguments)]
+  #[cfg_attr(not(feature = "security"), allow(clippy::needless_pass_by_value))]
   pub fn new(
     domain_id: u16,
-    discovery_join_handle: mio_channel::Receiver<JoinHandle<()>>,
+    participant_guid: GUID,
+    multicast_discovery: bool,
+    port_mapping: PortMapping,
+    transports: Vec<TransportKind>,
+    interfaces: InterfaceFilter,
+    socket_options: SocketOptions,
+    discovery_join_handle: mio_channel::Receiver<BackgroundTask>,
     discovery_update_notification_receiver: mio_channel::Receiver<DiscoveryNotificationType>,
     discovery_command_sender: mio_channel::SyncSender<DiscoveryCommand>,
     spdp_liveness_sender: mio_channel::SyncSender<GuidPrefix>,
+    status_broadcaster: StatusBroadcaster<DomainParticipantStatus>,
+    discovery_event_broadcaster: StatusBroadcaster<DiscoveryEvent>,
+    health_monitor: HealthMonitor,
+    domain_tag: String,
+    compatibility: ProtocolCompatibility,
+    time_source: TimeSource,
+    security: SecurityConfig,
+    spawner: &BackgroundSpawner,
   ) -> Result<Self> {
     let dpi = DomainParticipantInner::new(
       domain_id,
+      participant_guid,
+      multicast_discovery,
+      port_mapping,
+      transports,
+      interfaces,
+      socket_options,
       discovery_update_notification_receiver,
       spdp_liveness_sender,
+      health_monitor.clone(),
+      compatibility,
+      time_source,
+      security.clone(),
+      spawner,
     )?;
 
     Ok(Self {
@@ -441,9 +1976,49 @@ impl DomainParticipantDisc {
       discovery_command_sender,
       discovery_join_handle,
       entity_id_generator: atomic::AtomicU32::new(0),
+      status_broadcaster,
+      discovery_event_broadcaster,
+      health_monitor,
+      domain_tag,
+      #[cfg(feature = "security")]
+      access_control: security.access_control,
+      qos: QosPolicies::qos_none(),
+      publishers: Vec::new(),
+      subscribers: Vec::new(),
+      type_registry: TypeRegistry::new(),
+      closed: false,
     })
   }
 
+  // Tells Discovery to announce that our participant and endpoints are gone,
+  // and to stop. The caller should join the returned thread, but not while
+  // holding the lock of this participant, because Discovery may need it to
+  // get there.
+  fn stop_discovery(&mut self) -> Option<BackgroundTask> {
+    if self.closed {
+      return None;
+    }
+    self.closed = true;
+    debug!("Sending Discovery Stop signal.");
+    if self
+      .discovery_command_sender
+      .send(DiscoveryCommand::StopDiscovery)
+      .is_err()
+    {
+      warn!("Failed to send stop signal to Discovery");
+      return None;
+    }
+    self.discovery_join_handle.try_recv().ok()
+  }
+
+  fn stop_event_loop(&self) {
+    self.dpi.lock().unwrap().stop_event_loop();
+  }
+
+  pub fn type_registry(&self) -> TypeRegistry {
+    self.type_registry.clone()
+  }
+
   // This generates identifiers that consist of given EntityKind and arbitrary,
   // unique identifier.
   pub(crate) fn new_entity_id(&self, entity_kind: EntityKind) -> EntityId {
@@ -455,6 +2030,40 @@ impl DomainParticipantDisc {
   }
 
   pub fn create_publisher(
+    &mut self,
+    dp: &DomainParticipantWeak,
+    qos: &QosPolicies,
+  ) -> Result<Publisher> {
+    let publisher = self.dpi.lock().unwrap().create_publisher(
+      dp,
+      qos,
+      self.discovery_command_sender.clone(),
+      self.qos.autoenable_created_entities(),
+    )?;
+    self.publishers.retain(|p| p.upgrade().is_some());
+    self.publishers.push(publisher.downgrade());
+    Ok(publisher)
+  }
+
+  pub fn create_subscriber(
+    &mut self,
+    dp: &DomainParticipantWeak,
+    qos: &QosPolicies,
+  ) -> Result<Subscriber> {
+    let subscriber = self.dpi.lock().unwrap().create_subscriber(
+      dp,
+      qos,
+      self.discovery_command_sender.clone(),
+      self.qos.autoenable_created_entities(),
+    )?;
+    self.subscribers.retain(|s| s.upgrade().is_some());
+    self.subscribers.push(subscriber.downgrade());
+    Ok(subscriber)
+  }
+
+  // Built-in Publishers and Subscribers, e.g. those of Discovery, are always
+  // enabled, and are not deleted by delete_contained_entities.
+  pub(crate) fn create_builtin_publisher(
     &self,
     dp: &DomainParticipantWeak,
     qos: &QosPolicies,
@@ -463,10 +2072,10 @@ impl DomainParticipantDisc {
       .dpi
       .lock()
       .unwrap()
-      .create_publisher(dp, qos, self.discovery_command_sender.clone())
+      .create_publisher(dp, qos, self.discovery_command_sender.clone(), true)
   }
 
-  pub fn create_subscriber(
+  pub(crate) fn create_builtin_subscriber(
     &self,
     dp: &DomainParticipantWeak,
     qos: &QosPolicies,
@@ -475,7 +2084,16 @@ impl DomainParticipantDisc {
       .dpi
       .lock()
       .unwrap()
-      .create_subscriber(dp, qos, self.discovery_command_sender.clone())
+      .create_subscriber(dp, qos, self.discovery_command_sender.clone(), true)
+  }
+
+  // Also forgets the Topics, which are deleted along with the other entities.
+  pub(crate) fn take_contained_entities(&mut self) -> (Vec<PublisherWeak>, Vec<SubscriberWeak>) {
+    self.dpi.lock().unwrap().topics.clear();
+    (
+      std::mem::take(&mut self.publishers),
+      std::mem::take(&mut self.subscribers),
+    )
   }
 
   pub fn create_topic(
@@ -494,13 +2112,36 @@ impl DomainParticipantDisc {
       .create_topic(dp, name, type_desc, qos, topic_kind)
   }
 
-  pub fn find_topic(
+  pub fn register_discovery_db_events(&self, poll: &mio::Poll) -> Result<()> {
+    self.dpi.lock().unwrap().register_discovery_db_events(poll)
+  }
+
+  pub fn deregister_discovery_db_events(&self, poll: &mio::Poll) -> Result<()> {
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .deregister_discovery_db_events(poll)
+  }
+
+  pub fn discovery_db_updated(&self) -> bool {
+    self.dpi.lock().unwrap().discovery_db_updated()
+  }
+
+  pub fn find_topic_in_discovery_db(
     &self,
     dp: &DomainParticipantWeak,
     name: &str,
-    timeout: Duration,
   ) -> Result<Option<Topic>> {
-    self.dpi.lock().unwrap().find_topic(dp, name, timeout)
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .find_topic_in_discovery_db(dp, name)
+  }
+
+  pub fn lookup_topicdescription(&self, name: &str) -> Option<Topic> {
+    self.dpi.lock().unwrap().lookup_topicdescription(name)
   }
 
   pub fn domain_id(&self) -> u16 {
@@ -515,10 +2156,18 @@ impl DomainParticipantDisc {
     self.dpi.lock().unwrap().discovered_topics()
   }
 
+  pub fn discovered_participants(&self) -> Vec<SpdpDiscoveredParticipantData> {
+    self.dpi.lock().unwrap().discovered_participants()
+  }
+
   pub(crate) fn dds_cache(&self) -> Arc<RwLock<DDSCache>> {
     self.dpi.lock().unwrap().dds_cache()
   }
 
+  pub(crate) fn time_source(&self) -> TimeSource {
+    self.dpi.lock().unwrap().time_source.clone()
+  }
+
   // pub(crate) fn discovery_db(&self) -> Arc<RwLock<DiscoveryDB>> {
   //   self.dpi.lock().unwrap().discovery_db.clone()
   // }
@@ -535,29 +2184,34 @@ impl DomainParticipantDisc {
       })
   }
 
+  pub(crate) fn send_discovery_command(&self, command: DiscoveryCommand) -> Result<()> {
+    self
+      .discovery_command_sender
+      .send(command)
+      .or_else(|e| log_and_err_internal!("Failed to send DiscoveryCommand. {:?}", e))
+  }
+
   pub(crate) fn self_locators(&self) -> HashMap<Token, Vec<Locator>> {
-    self.dpi.lock().unwrap().self_locators.clone()
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .self_locators
+      .read()
+      .unwrap()
+      .clone()
   }
 }
 
 impl Drop for DomainParticipantDisc {
   fn drop(&mut self) {
     info!("===== RustDDS shutting down ===== .drop() DomainParticipantDisc");
-    debug!("Sending Discovery Stop signal.");
-    if self
-      .discovery_command_sender
-      .send(DiscoveryCommand::StopDiscovery)
-      .is_err()
-    {
-      warn!("Failed to send stop signal to Discovery");
-      return;
-    }
-
-    debug!("Waiting for Discovery join.");
-    if let Ok(handle) = self.discovery_join_handle.try_recv() {
+    if let Some(handle) = self.stop_discovery() {
+      debug!("Waiting for Discovery join.");
       handle.join().unwrap();
       debug!("Joined Discovery.");
     }
+    self.stop_event_loop();
   }
 }
 
@@ -574,8 +2228,10 @@ pub(crate) struct DomainParticipantInner {
 
   // dp_event_loop control
   stop_poll_sender: mio_channel::Sender<()>,
-  ev_loop_handle: Option<JoinHandle<()>>, // this is Option, because it needs to be extracted
+  ev_loop_handle: Option<BackgroundTask>, // this is Option, because it needs to be extracted
   // out of the struct (take) in order to .join() on the handle.
+  // Receive threads, which the event loop stops
+  receive_loop_handles: Vec<BackgroundTask>,
 
   // Writers
   add_writer_sender: mio_channel::SyncSender<WriterIngredients>,
@@ -584,13 +2240,40 @@ pub(crate) struct DomainParticipantInner {
   dds_cache: Arc<RwLock<DDSCache>>,
   discovery_db: Arc<RwLock<DiscoveryDB>>,
   discovery_db_event_receiver: mio_channel::Receiver<()>,
-
-  // RTPS locators describing how to reach this DP
-  self_locators: HashMap<Token, Vec<Locator>>,
+  // Shared with the event loop, which updates the counters
+  statistics: StatisticsMonitor,
+  // Shared with the UDP senders and listeners of the event loop
+  packet_capture: PacketCapture,
+
+  // Topics created by this DP, or found from discovery data, by name
+  topics: BTreeMap<String, Topic>,
+
+  // RTPS locators describing how to reach this DP. The event loop updates
+  // these when network interfaces change.
+  self_locators: Arc<RwLock<HashMap<Token, Vec<Locator>>>>,
+  // The clock of the participant
+  time_source: TimeSource,
 }
 
 impl Drop for DomainParticipantInner {
   fn drop(&mut self) {
+    self.stop_event_loop();
+  }
+}
+
+#[allow(clippy::new_without_default)]
+impl DomainParticipantInner {
+  // ParticipantIds are probed below this, as suggested by RTPS spec v2.5
+  // Section "9.6.2.3 Default Port Numbers".
+  const PARTICIPANT_ID_LIMIT: u16 = 120;
+
+  // Before stopping, the event loop lets the Writers send their last
+  // messages. Does nothing if already stopped.
+  fn stop_event_loop(&mut self) {
+    let join_handle = match self.ev_loop_handle.take() {
+      Some(join_handle) => join_handle,
+      None => return,
+    };
     // if send has an error simply leave as we have lost control of the
     // ev_loop_thread anyways
     if self.stop_poll_sender.send(()).is_err() {
@@ -598,111 +2281,215 @@ impl Drop for DomainParticipantInner {
     }
 
     debug!("Waiting for dp_event_loop join");
-    match self.ev_loop_handle.take() {
-      Some(join_handle) => {
-        join_handle
-          .join()
-          .unwrap_or_else(|e| warn!("Failed to join dp_event_loop: {:?}", e));
-      }
-      None => {
-        error!("Someone managed to steal dp_event_loop join handle from DomainParticipantInner.");
+    join_handle
+      .join()
+      .unwrap_or_else(|e| warn!("Failed to join dp_event_loop: {:?}", e));
+    debug!("Joined dp_event_loop");
+    for handle in self.receive_loop_handles.drain(..) {
+      handle
+        .join()
+        .unwrap_or_else(|e| warn!("Failed to join receive loop: {:?}", e));
+    }
+  }
+
+  // If we do not get the preferred listening port, try again, with "any" port
+  // number.
+  fn new_unicast_listener(
+    host: &str,
+    port: u16,
+    interfaces: &InterfaceFilter,
+  ) -> io::Result<UDPListener> {
+    UDPListener::new_unicast(host, port)
+      .or_else(|e| {
+        if matches!(e.kind(), ErrorKind::AddrInUse) {
+          UDPListener::new_unicast(host, 0)
+        } else {
+          Err(e)
+        }
+      })
+      .map(|l| l.with_interfaces(interfaces.clone()))
+  }
+
+  // Selects the ParticipantId: the first one, whose discovery and user traffic
+  // unicast ports are both free. Another participant may hold just one of
+  // them, e.g. if it has chosen a different port for the other one.
+  fn new_unicast_listener_pair(
+    host: &str,
+    domain_id: u16,
+    port_mapping: &PortMapping,
+  ) -> Result<(u16, UDPListener, UDPListener)> {
+    // A port in a range reserved by the OS gives PermissionDenied on Windows.
+    let is_taken =
+      |e: &io::Error| matches!(e.kind(), ErrorKind::AddrInUse | ErrorKind::PermissionDenied);
+
+    for pid in 0..Self::PARTICIPANT_ID_LIMIT {
+      let discovery_listener =
+        match UDPListener::new_unicast(host, port_mapping.spdp_unicast_port(domain_id, pid)?) {
+          Ok(l) => l,
+          Err(e) if is_taken(&e) => continue,
+          Err(e) => return log_and_err_internal!("Could not open discovery listener: {:?}", e),
+        };
+      match UDPListener::new_unicast(
+        host,
+        port_mapping.user_traffic_unicast_port(domain_id, pid)?,
+      ) {
+        Ok(l) => return Ok((pid, discovery_listener, l)),
+        Err(e) if is_taken(&e) => {
+          debug!("ParticipantId {} has its user traffic port taken", pid);
+        }
+        Err(e) => {
+          return log_and_err_internal!("Could not open unicast user traffic listener: {:?}", e)
+        }
       }
     }
-    debug!("Joined dp_event_loop");
+    log_and_err_internal!(
+      "Could not find free ParticipantId in domain {}. Ports of ids up to {} are taken.",
+      domain_id,
+      Self::PARTICIPANT_ID_LIMIT - 1
+    )
   }
-}
 
-#[allow(clippy::new_without_default)]
-impl DomainParticipantInner {
+  #[allow(clippy::too_many_arguments)]
   fn new(
     domain_id: u16,
+    new_guid: GUID,
+    multicast_discovery: bool,
+    port_mapping: PortMapping,
+    transports: Vec<TransportKind>,
+    interfaces: InterfaceFilter,
+    socket_options: SocketOptions,
     discovery_update_notification_receiver: mio_channel::Receiver<DiscoveryNotificationType>,
     spdp_liveness_sender: mio_channel::SyncSender<GuidPrefix>,
+    health_monitor: HealthMonitor,
+    compatibility: ProtocolCompatibility,
+    time_source: TimeSource,
+    security: SecurityConfig,
+    spawner: &BackgroundSpawner,
   ) -> Result<Self> {
-    let mut listeners = HashMap::new();
-
-    match UDPListener::new_multicast(
-      "0.0.0.0",
-      spdp_well_known_multicast_port(domain_id),
-      Ipv4Addr::new(239, 255, 0, 1),
-    ) {
-      Ok(l) => {
-        listeners.insert(DISCOVERY_MUL_LISTENER_TOKEN, l);
+    let mut listeners: HashMap<Token, Box<dyn TransportListener>> = HashMap::new();
+    // ParticipantId is selected by the first transport, and the others use
+    // the same port numbers, if they can.
+    let mut participant_id = None;
+
+    for &kind in &transports {
+      let (host, multicast_group, tokens) = match (
+        kind.unspecified_address(),
+        kind.multicast_group(),
+        listener_tokens(kind),
+      ) {
+        (Some(host), Some(multicast_group), Some(tokens)) => (host, multicast_group, tokens),
+        // TCP listens on the user traffic port of the ParticipantId, which is
+        // not known yet.
+        _ if kind == TransportKind::TcpV4 => continue,
+        // Shared memory carries only user traffic, to participants on the same
+        // host. We still work without it.
+        _ => {
+          match ShmListener::new() {
+            Ok(l) => {
+              listeners.insert(USER_TRAFFIC_SHM_LISTENER_TOKEN, Box::new(l));
+            }
+            Err(e) => warn!("Cannot get {:?} listener: {:?}", kind, e),
+          }
+          continue;
+        }
+      };
+      let [discovery_token, discovery_mul_token, user_traffic_token, user_traffic_mul_token] =
+        tokens;
+
+      // Without the multicast listener we also do not advertise a multicast
+      // metatraffic locator, so remote participants will reach us by unicast
+      // only.
+      if multicast_discovery {
+        match UDPListener::new_multicast(
+          host,
+          port_mapping.spdp_multicast_port(domain_id)?,
+          multicast_group,
+          interfaces.clone(),
+        ) {
+          Ok(l) => {
+            listeners.insert(discovery_mul_token, Box::new(l));
+          }
+          Err(e) => warn!("Cannot get multicast discovery listener: {:?}", e),
+        }
       }
-      Err(e) => warn!("Cannot get multicast discovery listener: {:?}", e),
-    }
-
-    let mut participant_id = 0;
 
-    let mut discovery_listener = None;
-
-    // Magic value 120 below is from RTPS spec 2.5 Section "9.6.2.3 Default Port
-    // Numbers"
-    while discovery_listener.is_none() && participant_id < 120 {
-      discovery_listener = UDPListener::new_unicast(
-        "0.0.0.0",
-        spdp_well_known_unicast_port(domain_id, participant_id),
-      )
-      .ok();
-      if discovery_listener.is_none() {
-        participant_id += 1;
+      let (pid, discovery_listener, user_traffic_listener) = match participant_id {
+        None => {
+          let (pid, discovery_listener, user_traffic_listener) =
+            Self::new_unicast_listener_pair(host, domain_id, &port_mapping)?;
+          info!("ParticipantId {} selected.", pid);
+          (
+            pid,
+            discovery_listener.with_interfaces(interfaces.clone()),
+            user_traffic_listener.with_interfaces(interfaces.clone()),
+          )
+        }
+        Some(pid) => (
+          pid,
+          Self::new_unicast_listener(
+            host,
+            port_mapping.spdp_unicast_port(domain_id, pid)?,
+            &interfaces,
+          )
+          .or_else(|e| log_and_err_internal!("Could not open discovery listener: {:?}", e))?,
+          Self::new_unicast_listener(
+            host,
+            port_mapping.user_traffic_unicast_port(domain_id, pid)?,
+            &interfaces,
+          )
+          .or_else(|e| {
+            log_and_err_internal!("Could not open unicast user traffic listener: {:?}", e)
+          })?,
+        ),
+      };
+      participant_id = Some(pid);
+      listeners.insert(discovery_token, Box::new(discovery_listener));
+
+      // Now the user traffic listeners
+
+      match UDPListener::new_multicast(
+        host,
+        port_mapping.user_traffic_multicast_port(domain_id)?,
+        multicast_group,
+        interfaces.clone(),
+      ) {
+        Ok(l) => {
+          listeners.insert(user_traffic_mul_token, Box::new(l));
+        }
+        Err(e) => warn!("Cannot get multicast user traffic listener: {:?}", e),
       }
-    }
-
-    info!("ParticipantId {} selected.", participant_id);
 
-    // here discovery_listener is redefined (shadowed)
-    let discovery_listener = match discovery_listener {
-      Some(dl) => dl,
-      None => return log_and_err_internal!("Could not find free ParticipantId"),
-    };
-    listeners.insert(DISCOVERY_LISTENER_TOKEN, discovery_listener);
-
-    // Now the user traffic listeners
-
-    match UDPListener::new_multicast(
-      "0.0.0.0",
-      user_traffic_multicast_port(domain_id),
-      Ipv4Addr::new(239, 255, 0, 1),
-    ) {
-      Ok(l) => {
-        listeners.insert(USER_TRAFFIC_MUL_LISTENER_TOKEN, l);
-      }
-      Err(e) => warn!("Cannot get multicast user traffic listener: {:?}", e),
+      listeners.insert(user_traffic_token, Box::new(user_traffic_listener));
     }
 
-    let user_traffic_listener = UDPListener::new_unicast(
-      "0.0.0.0",
-      user_traffic_unicast_port(domain_id, participant_id),
-    )
-    .or_else(|e| {
-      if matches!(e.kind(), ErrorKind::AddrInUse) {
-        // If we do not get the preferred listening port,
-        // try again, with "any" port number.
-        UDPListener::new_unicast("0.0.0.0", 0).or_else(|e| {
-          log_and_err_internal!(
-            "Could not open unicast user traffic listener, any port number: {:?}",
-            e
-          )
-        })
-      } else {
-        log_and_err_internal!("Could not open unicast user traffic listener: {:?}", e)
+    // The builder checks that some UDP transport is selected.
+    let participant_id = participant_id.unwrap_or_default();
+
+    // Like shared memory, TCP carries only user traffic, and we work without
+    // it.
+    if transports.contains(&TransportKind::TcpV4) {
+      let port = port_mapping.user_traffic_unicast_port(domain_id, participant_id)?;
+      match TCPListener::new(port).or_else(|_| TCPListener::new(0)) {
+        Ok(l) => {
+          listeners.insert(
+            USER_TRAFFIC_TCP_LISTENER_TOKEN,
+            Box::new(l.with_interfaces(interfaces.clone())),
+          );
+        }
+        Err(e) => warn!("Cannot get {:?} listener: {:?}", TransportKind::TcpV4, e),
       }
-    })?;
+    }
 
-    listeners.insert(USER_TRAFFIC_LISTENER_TOKEN, user_traffic_listener);
+    for (token, listener) in &listeners {
+      listener
+        .set_socket_options(&socket_options)
+        .unwrap_or_else(|e| warn!("Cannot set socket options of {:?}: {:?}", token, e));
+    }
 
-    // construct our own Locators
-    let self_locators: HashMap<Token, Vec<Locator>> = listeners
-      .iter()
-      .map(|(t, l)| match l.to_locator_address() {
-        Ok(locs) => (*t, locs),
-        Err(e) => {
-          error!("No local network address for token {:?}: {:?}", t, e);
-          (*t, vec![])
-        }
-      })
-      .collect();
+    // construct our own Locators. These are updated by the event loop, if
+    // network interfaces change.
+    let listener_locators = transport::listener_locators(&listeners);
+    let self_locators = Arc::new(RwLock::new(transport::self_locators(&listener_locators)));
 
     // Adding readers
     let (sender_add_reader, receiver_add_reader) =
@@ -714,7 +2501,6 @@ impl DomainParticipantInner {
       mio_channel::sync_channel::<WriterIngredients>(10);
     let (remove_writer_sender, remove_writer_receiver) = mio_channel::sync_channel::<GUID>(10);
 
-    let new_guid = GUID::new_participant_guid();
     let domain_info = DomainInfo {
       domain_participant_guid: new_guid,
       domain_id,
@@ -722,25 +2508,49 @@ impl DomainParticipantInner {
     };
 
     let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    let statistics = StatisticsMonitor::new(dds_cache.clone());
+    let packet_capture = PacketCapture::new();
 
     let (discovery_db_event_sender, discovery_db_event_receiver) =
       mio_channel::sync_channel::<()>(1);
     let discovery_db = Arc::new(RwLock::new(DiscoveryDB::new(
       new_guid,
       discovery_db_event_sender,
+      time_source.clone(),
     )));
 
     let (stop_poll_sender, stop_poll_receiver) = mio_channel::channel::<()>();
 
+    // Receive threads take over the listeners from the event loop, if
+    // configured.
+    let (receive_loops, receive_loop_handles) = ReceiveLoops::spawn(
+      spawner,
+      spawner.thread_count(ProtocolThread::Receive).unwrap_or(0),
+      &mut listeners,
+      &packet_capture,
+      participant_id,
+    )?;
+
     // Launch the background thread for DomainParticipant
     let dds_cache_clone = dds_cache.clone();
     let disc_db_clone = discovery_db.clone();
-    let ev_loop_handle = thread::Builder::new()
-      .name(format!("RustDDS Participant {} event loop", participant_id))
-      .spawn(move || {
-        let dp_event_loop = DPEventLoop::new(
+    let self_locators_clone = self_locators.clone();
+    let statistics_clone = statistics.clone();
+    let packet_capture_clone = packet_capture.clone();
+    let time_source_clone = time_source.clone();
+    let ev_loop_handle = spawner.spawn(
+      ProtocolThread::EventLoop,
+      format!("RustDDS Participant {} event loop", participant_id),
+      move || {
+        Some(DPEventLoop::new(
           domain_info,
           listeners,
+          receive_loops,
+          transports,
+          interfaces,
+          socket_options,
+          listener_locators,
+          self_locators_clone,
           dds_cache_clone,
           disc_db_clone,
           new_guid.prefix,
@@ -763,9 +2573,15 @@ impl DomainParticipantInner {
           stop_poll_receiver,
           discovery_update_notification_receiver,
           spdp_liveness_sender,
-        );
-        dp_event_loop.event_loop();
-      })?;
+          health_monitor,
+          statistics_clone,
+          packet_capture_clone,
+          compatibility,
+          time_source_clone,
+          security,
+        ))
+      },
+    )?;
 
     info!(
       "New DomainParticipantInner: domain_id={:?} participant_id={:?} GUID={:?}",
@@ -779,12 +2595,17 @@ impl DomainParticipantInner {
       sender_remove_reader,
       stop_poll_sender,
       ev_loop_handle: Some(ev_loop_handle),
+      receive_loop_handles,
       add_writer_sender,
       remove_writer_sender,
       dds_cache,
       discovery_db,
       discovery_db_event_receiver,
+      statistics,
+      packet_capture,
+      topics: BTreeMap::new(),
       self_locators,
+      time_source,
     })
   }
 
@@ -811,6 +2632,7 @@ impl DomainParticipantInner {
     domain_participant: &DomainParticipantWeak,
     qos: &QosPolicies,
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
+    enabled: bool,
   ) -> Result<Publisher> {
     Ok(Publisher::new(
       domain_participant.clone(),
@@ -820,6 +2642,7 @@ impl DomainParticipantInner {
       self.add_writer_sender.clone(),
       self.remove_writer_sender.clone(),
       discovery_command,
+      enabled,
     ))
   }
 
@@ -828,6 +2651,7 @@ impl DomainParticipantInner {
     domain_participant: &DomainParticipantWeak,
     qos: &QosPolicies,
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
+    enabled: bool,
   ) -> Result<Subscriber> {
     Ok(Subscriber::new(
       domain_participant.clone(),
@@ -836,23 +2660,48 @@ impl DomainParticipantInner {
       self.sender_add_reader.clone(),
       self.sender_remove_reader.clone(),
       discovery_command,
+      enabled,
     ))
   }
 
   // Topic creation. Data types should be handled as something (potentially) more
   // structured than a String. NOTE: Here we are using &str for topic name. &str
   // is Unicode string, whereas DDS specifies topic name to be a sequence of
-  // octets, which would be &[u8] in Rust. This may cause problems if there are
-  // topic names with non-ASCII characters. On the other hand, string handling
-  // with &str is easier in Rust.
+  // octets, which would be &[u8] in Rust. Names are sent and compared as UTF-8
+  // bytes, see validate_topic_name.
   pub fn create_topic(
-    &self,
+    &mut self,
     domain_participant_weak: &DomainParticipantWeak,
     name: String,
     type_desc: String,
     qos: &QosPolicies,
     topic_kind: TopicKind,
   ) -> Result<Topic> {
+    validate_topic_name(&name)?;
+    validate_type_name(&type_desc)?;
+    if let Some(existing) = self.topics.get(&name) {
+      if existing.get_type().name() != type_desc {
+        return Error::precondition_not_met(format!(
+          "Topic {:?} already exists with type {:?}, not {:?}",
+          name,
+          existing.get_type().name(),
+          type_desc
+        ));
+      }
+    }
+    Ok(self.insert_topic(domain_participant_weak, name, type_desc, qos, topic_kind))
+  }
+
+  // Remote participants may use names that we would not accept locally, so
+  // topics found from discovery data are not validated.
+  fn insert_topic(
+    &mut self,
+    domain_participant_weak: &DomainParticipantWeak,
+    name: String,
+    type_desc: String,
+    qos: &QosPolicies,
+    topic_kind: TopicKind,
+  ) -> Topic {
     let topic = Topic::new(
       domain_participant_weak,
       name,
@@ -860,60 +2709,40 @@ impl DomainParticipantInner {
       qos,
       topic_kind,
     );
-    Ok(topic)
-
-    // TODO: refine
+    self.topics.insert(topic.name(), topic.clone());
+    topic
   }
 
-  // Do not implement content filtered topics or multi-topics (yet)
+  pub fn lookup_topicdescription(&self, name: &str) -> Option<Topic> {
+    self.topics.get(name).cloned()
+  }
 
-  pub fn find_topic(
-    &self,
-    domain_participant_weak: &DomainParticipantWeak,
-    name: &str,
-    timeout: Duration,
-  ) -> Result<Option<Topic>> {
-    let poll = mio::Poll::new()?;
-    let mut events = mio::Events::with_capacity(1);
-    // Should be register before the check and use level trigger to avoid missing
-    // event
+  pub fn register_discovery_db_events(&self, poll: &mio::Poll) -> Result<()> {
     poll.register(
       &self.discovery_db_event_receiver,
       mio::Token(0),
       mio::Ready::readable(),
       mio::PollOpt::level(),
     )?;
+    Ok(())
+  }
 
-    let find_end = Instant::now() + timeout;
-    loop {
-      if let Some(topic) = self.find_topic_in_discovery_db(domain_participant_weak, name)? {
-        return Ok(Some(topic));
-      }
-      let timeout = find_end - Instant::now();
-      poll.poll(&mut events, Some(timeout))?;
-
-      if let Some(_event) = events.iter().next() {
-        if self.discovery_db_event_receiver.try_recv().is_ok() {
-          continue;
-        }
-      }
-
-      if Instant::now() > find_end {
-        break;
-      }
-    }
+  pub fn deregister_discovery_db_events(&self, poll: &mio::Poll) -> Result<()> {
+    poll.deregister(&self.discovery_db_event_receiver)?;
+    Ok(())
+  }
 
-    Ok(None)
+  // Consumes one update notification, if there is one
+  pub fn discovery_db_updated(&self) -> bool {
+    self.discovery_db_event_receiver.try_recv().is_ok()
   }
 
-  fn find_topic_in_discovery_db(
-    &self,
+  pub fn find_topic_in_discovery_db(
+    &mut self,
     domain_participant_weak: &DomainParticipantWeak,
     name: &str,
   ) -> Result<Option<Topic>> {
-    let db = self.discovery_db.read().map_err(|_| Error::LockPoisoned)?;
-
-    let build_topic_fn = |d: &DiscoveredTopicData| {
+    let build_topic_params = |d: &DiscoveredTopicData| {
       let qos = d.topic_data.qos();
       let topic_kind = match d.topic_data.key {
         Some(_) => TopicKind::WithKey,
@@ -921,24 +2750,33 @@ impl DomainParticipantInner {
       };
       let name = d.topic_name().clone();
       let type_desc = d.topic_data.type_name.clone();
-      self.create_topic(domain_participant_weak, name, type_desc, &qos, topic_kind)
+      (name, type_desc, qos, topic_kind)
     };
 
-    if let Some(d) = db.get_topic(name) {
+    // Copy the data out, as insert_topic needs self mutably.
+    let params = self
+      .discovery_db
+      .read()
+      .map_err(|_| Error::LockPoisoned)?
+      .get_topic(name)
+      .map(build_topic_params);
+
+    match params {
       // build a Topic from DiscoveredTopicData
-      build_topic_fn(d).map(Some)
-    } else {
-      Ok(None)
+      Some((name, type_desc, qos, topic_kind)) => Ok(Some(self.insert_topic(
+        domain_participant_weak,
+        name,
+        type_desc,
+        &qos,
+        topic_kind,
+      ))),
+      None => Ok(None),
     }
   }
   // get_builtin_subscriber (why would we need this?)
 
   // ignore_* operations. TODO: Do we need any of those?
 
-  // delete_contained_entities is not needed. Data structures should be designed
-  // so that lifetime of all created objects is within the lifetime of
-  // DomainParticipant. Then such deletion is implicit.
-
   // The following methods are not for application use.
 
   // pub(crate) fn get_add_reader_sender(&self) ->
@@ -973,6 +2811,19 @@ impl DomainParticipantInner {
 
     db.all_user_topics().cloned().collect()
   }
+
+  pub fn discovered_participants(&self) -> Vec<SpdpDiscoveredParticipantData> {
+    let db = self
+      .discovery_db
+      .read()
+      .unwrap_or_else(|e| panic!("DiscoveryDB is poisoned. {:?}", e));
+
+    db.all_participants()
+      .filter(|p| p.participant_guid != self.my_guid)
+      .filter(|p| db.is_authenticated(p.participant_guid.prefix))
+      .cloned()
+      .collect()
+  }
 } // impl
 
 impl RTPSEntity for DomainParticipant {
@@ -981,6 +2832,24 @@ impl RTPSEntity for DomainParticipant {
   }
 }
 
+impl HasQoSPolicy for DomainParticipant {
+  fn qos(&self) -> QosPolicies {
+    self.dpi.lock().unwrap().qos.clone()
+  }
+}
+
+impl MutQosPolicy for DomainParticipant {
+  /// Changes the QoS of this DomainParticipant.
+  ///
+  /// Policies defined in `new_qos` replace the current ones, and the rest are
+  /// kept. Contained entities are not affected.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<()> {
+    let mut dpd = self.dpi.lock().unwrap();
+    dpd.qos = dpd.qos.changed_by(new_qos)?;
+    Ok(())
+  }
+}
+
 impl RTPSEntity for DomainParticipantDisc {
   fn guid(&self) -> GUID {
     self.dpi.lock().unwrap().guid()
@@ -1022,14 +2891,17 @@ mod tests {
       submessages::submessages::{AckNack, EntitySubmessage, SubmessageHeader, SubmessageKind, *},
       vendor_id::VendorId,
     },
-    network::{constant::user_traffic_unicast_port, udp_sender::UDPSender},
+    network::{port_mapping::PortMapping, transport::Transports, udp_sender::UDPSender},
     serialization::{cdr_serializer::CDRSerializerAdapter, submessage::*, Message, SubMessage},
     structure::{
       guid::{EntityId, GUID},
       locator::Locator,
       sequence_number::{SequenceNumber, SequenceNumberSet},
     },
-    test::random_data::RandomData,
+    test::{
+      loopback::{loopback_participant, loopback_participant_builder},
+      random_data::RandomData,
+    },
   };
   use super::DomainParticipant;
 
@@ -1094,8 +2966,10 @@ mod tests {
       .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(&topic, None)
       .expect("Failed to create datawriter");
 
-    let port_number: u16 = user_traffic_unicast_port(5, 0);
-    let sender = UDPSender::new(1234).unwrap();
+    let port_number: u16 = PortMapping::default()
+      .user_traffic_unicast_port(5, 0)
+      .unwrap();
+    let sender = Transports::from(UDPSender::new(1234).unwrap());
     let mut m: Message = Message::default();
 
     let a: AckNack = AckNack {
@@ -1133,4 +3007,1942 @@ mod tests {
     let locators = vec![Locator::UdpV4(socket_address)];
     sender.send_to_locator_list(&_data, &locators);
   }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_user_data_in_discovery() {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+      dds::qos::{policy, HasQoSPolicy, MutQosPolicy},
+      serialization::CDRDeserializerAdapter,
+      structure::entity::RTPSEntity,
+    };
+
+    let mut writer_participant = loopback_participant(200);
+    writer_participant
+      .set_qos(
+        &QosPolicies::builder()
+          .user_data(policy::UserData {
+            value: b"participant".to_vec(),
+          })
+          .build(),
+      )
+      .unwrap();
+    let topic_qos = QosPolicies::builder()
+      .topic_data(policy::TopicData {
+        value: b"topic".to_vec(),
+      })
+      .build();
+    let topic = writer_participant
+      .create_topic(
+        "user_data_discovery_test".to_string(),
+        "RandomData".to_string(),
+        &topic_qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let publisher = writer_participant
+      .create_publisher(
+        &QosPolicies::builder()
+          .group_data(policy::GroupData {
+            value: b"group".to_vec(),
+          })
+          .build(),
+      )
+      .unwrap();
+    let writer_qos = QosPolicies::builder()
+      .user_data(policy::UserData {
+        value: b"writer".to_vec(),
+      })
+      .build();
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        &topic,
+        Some(writer_qos),
+      )
+      .unwrap();
+    assert_eq!(
+      data_writer.qos().group_data().unwrap().value,
+      b"group".to_vec()
+    );
+
+    let reader_participant = loopback_participant(200);
+    let reader_topic = reader_participant
+      .create_topic(
+        "user_data_discovery_test".to_string(),
+        "RandomData".to_string(),
+        &QosPolicies::qos_none(),
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let data_reader = reader_participant
+      .create_subscriber(&QosPolicies::qos_none())
+      .unwrap()
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&reader_topic, None)
+      .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let publication = loop {
+      if let Some(p) = data_reader.get_matched_publications().next() {
+        break p;
+      }
+      assert!(Instant::now() < deadline, "Writer was not discovered");
+      std::thread::sleep(Duration::from_millis(100));
+    };
+    assert_eq!(publication.user_data.unwrap().value, b"writer".to_vec());
+    assert_eq!(publication.topic_data.unwrap().value, b"topic".to_vec());
+    assert_eq!(publication.group_data.unwrap().value, b"group".to_vec());
+
+    let remote = reader_participant
+      .discovered_participants()
+      .into_iter()
+      .find(|p| p.participant_guid == writer_participant.guid())
+      .unwrap();
+    assert_eq!(remote.user_data.unwrap().value, b"participant".to_vec());
+  }
+
+  #[test]
+  fn dp_lookup_and_find_local_topic() {
+    use std::time::Duration;
+
+    let dp = DomainParticipant::new(0).unwrap();
+    assert!(dp.lookup_topicdescription("lookup_test").is_none());
+    assert!(dp
+      .find_topic("lookup_test", Duration::from_millis(10))
+      .unwrap()
+      .is_none());
+
+    let topic = dp
+      .create_topic(
+        "lookup_test".to_string(),
+        "RandomData".to_string(),
+        &QosPolicies::qos_none(),
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    assert_eq!(
+      dp.lookup_topicdescription("lookup_test"),
+      Some(topic.clone())
+    );
+    assert_eq!(
+      dp.find_topic("lookup_test", Duration::from_millis(0))
+        .unwrap(),
+      Some(topic)
+    );
+  }
+
+  #[test]
+  fn dp_entity_factory_and_delete_contained_entities() {
+    use crate::dds::{
+      qos::{policy, MutQosPolicy},
+      values::result::Error,
+    };
+
+    let mut dp = DomainParticipant::new(0).unwrap();
+    dp.set_qos(
+      &QosPolicies::builder()
+        .entity_factory(policy::EntityFactory {
+          autoenable_created_entities: false,
+        })
+        .build(),
+    )
+    .unwrap();
+    let publisher = dp.create_publisher(&QosPolicies::qos_none()).unwrap();
+    assert!(!publisher.is_enabled());
+    let topic = dp
+      .create_topic(
+        "entity_factory_test".to_string(),
+        "RandomData".to_string(),
+        &QosPolicies::qos_none(),
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(&topic, None)
+      .unwrap();
+    let sample = RandomData {
+      a: 1,
+      b: "one".to_string(),
+    };
+
+    assert!(!data_writer.is_enabled());
+    assert!(matches!(
+      data_writer.write(sample.clone(), None),
+      Err(Error::NotEnabled)
+    ));
+    // The Publisher must be enabled first.
+    assert!(data_writer.enable().is_err());
+    // The Publisher itself has the default EntityFactory policy, so it enables
+    // its DataWriters.
+    publisher.enable().unwrap();
+    assert!(data_writer.is_enabled());
+    data_writer.write(sample.clone(), None).unwrap();
+
+    dp.delete_contained_entities().unwrap();
+    assert!(matches!(
+      data_writer.write(sample, None),
+      Err(Error::AlreadyDeleted)
+    ));
+    assert!(matches!(
+      publisher.create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        &topic, None
+      ),
+      Err(Error::AlreadyDeleted)
+    ));
+    assert!(dp.lookup_topicdescription("entity_factory_test").is_none());
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_find_remote_topic() {
+    use std::time::Duration;
+
+    use crate::{
+      dds::{
+        qos::{policy, HasQoSPolicy},
+        topic::TopicDescription,
+      },
+      serialization::CDRSerializerAdapter,
+    };
+
+    let writer_participant = loopback_participant(201);
+    let topic_qos = QosPolicies::builder()
+      .durability(policy::Durability::TransientLocal)
+      .build();
+    let topic = writer_participant
+      .create_topic(
+        "find_topic_test".to_string(),
+        "RandomData".to_string(),
+        &topic_qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let _data_writer = writer_participant
+      .create_publisher(&QosPolicies::qos_none())
+      .unwrap()
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(&topic, None)
+      .unwrap();
+
+    let reader_participant = loopback_participant(201);
+    let found = reader_participant
+      .find_topic("find_topic_test", Duration::from_secs(10))
+      .unwrap()
+      .expect("Topic was not discovered");
+    assert_eq!(found.get_type().name(), "RandomData");
+    assert_eq!(found.qos().durability, topic_qos.durability);
+    assert_eq!(
+      reader_participant.lookup_topicdescription("find_topic_test"),
+      Some(found)
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_builtin_topic_readers() {
+    use std::time::{Duration, Instant};
+
+    use crate::structure::entity::RTPSEntity;
+
+    let writer_participant = loopback_participant(202);
+    let topic = writer_participant
+      .create_topic(
+        "builtin_reader_test".to_string(),
+        "RandomData".to_string(),
+        &QosPolicies::qos_none(),
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let data_writer = writer_participant
+      .create_publisher(&QosPolicies::qos_none())
+      .unwrap()
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(&topic, None)
+      .unwrap();
+
+    let reader_participant = loopback_participant(202);
+    let mut participant_reader = reader_participant.participant_builtin_reader().unwrap();
+    let mut publication_reader = reader_participant.publication_builtin_reader().unwrap();
+    // Created only after the remote writer may already have been discovered.
+    std::thread::sleep(Duration::from_millis(500));
+    let mut subscription_reader = reader_participant.subscription_builtin_reader().unwrap();
+
+    let mut participants = BTreeSet::new();
+    let mut writer_seen = false;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !(writer_seen && participants.contains(&writer_participant.guid())) {
+      assert!(Instant::now() < deadline, "Builtin topics were not read");
+      while let Ok(Some(sample)) = participant_reader.take_next_sample() {
+        if let Ok(p) = sample.value() {
+          participants.insert(p.participant_guid);
+        }
+      }
+      while let Ok(Some(sample)) = publication_reader.take_next_sample() {
+        if let Ok(w) = sample.value() {
+          if w.writer_proxy.remote_writer_guid == data_writer.guid() {
+            assert_eq!(w.publication_topic_data.topic_name, "builtin_reader_test");
+            assert_eq!(w.publication_topic_data.type_name, "RandomData");
+            writer_seen = true;
+          }
+        }
+      }
+      std::thread::sleep(Duration::from_millis(100));
+    }
+    // Our own participant is there, too.
+    assert!(participants.contains(&reader_participant.guid()));
+
+    // Builtin readers are not announced.
+    let guid = subscription_reader.guid();
+    while let Ok(Some(sample)) = subscription_reader.take_next_sample() {
+      if let Ok(r) = sample.value() {
+        assert_ne!(r.reader_proxy.remote_reader_guid, guid);
+      }
+    }
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_ignore_participant() {
+    use std::time::{Duration, Instant};
+
+    use crate::{serialization::CDRDeserializerAdapter, structure::entity::RTPSEntity};
+
+    let topic_name = "ignore_participant_test".to_string();
+    let writer_participant = loopback_participant(203);
+    let topic = writer_participant
+      .create_topic(
+        topic_name.clone(),
+        "RandomData".to_string(),
+        &QosPolicies::qos_none(),
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let _data_writer = writer_participant
+      .create_publisher(&QosPolicies::qos_none())
+      .unwrap()
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(&topic, None)
+      .unwrap();
+
+    let reader_participant = loopback_participant(203);
+    let reader_topic = reader_participant
+      .create_topic(
+        topic_name,
+        "RandomData".to_string(),
+        &QosPolicies::qos_none(),
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let data_reader = reader_participant
+      .create_subscriber(&QosPolicies::qos_none())
+      .unwrap()
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&reader_topic, None)
+      .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while data_reader.get_matched_publications().next().is_none() {
+      assert!(Instant::now() < deadline, "Writer was not discovered");
+      std::thread::sleep(Duration::from_millis(100));
+    }
+
+    assert!(reader_participant
+      .ignore_participant(reader_participant.guid_prefix())
+      .is_err());
+    reader_participant
+      .ignore_participant(writer_participant.guid_prefix())
+      .unwrap();
+
+    // Unmatched, and the periodic announcements do not bring it back.
+    std::thread::sleep(Duration::from_secs(3));
+    assert!(data_reader.get_matched_publications().next().is_none());
+    assert!(!reader_participant
+      .discovered_participants()
+      .iter()
+      .any(|p| p.participant_guid == writer_participant.guid()));
+  }
+
+  #[test]
+  fn dp_static_endpoint_discovery() {
+    use std::time::{Duration, Instant};
+
+    use super::StaticEndpoint;
+    use crate::{
+      dds::qos::{policy::Reliability, QosPolicyBuilder},
+      network::port_mapping::PortMapping,
+      serialization::CDRDeserializerAdapter,
+      structure::entity::RTPSEntity,
+    };
+
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: crate::Duration::DURATION_ZERO,
+      })
+      .build();
+    let topic_name = "static_discovery_test".to_string();
+    let locator = |dp: &DomainParticipant| {
+      SocketAddr::new(
+        Ipv4Addr::LOCALHOST.into(),
+        PortMapping::default()
+          .user_traffic_unicast_port(dp.domain_id(), dp.participant_id())
+          .unwrap(),
+      )
+    };
+
+    let writer_participant = loopback_participant_builder(204)
+      .static_endpoint_discovery(true)
+      .build()
+      .unwrap();
+    let topic = writer_participant
+      .create_topic(
+        topic_name.clone(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let data_writer = writer_participant
+      .create_publisher(&qos)
+      .unwrap()
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(&topic, None)
+      .unwrap();
+    // Local matching works without SEDP also.
+    let local_reader = writer_participant
+      .create_subscriber(&qos)
+      .unwrap()
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+
+    let reader_participant = loopback_participant_builder(204)
+      .static_endpoint_discovery(true)
+      .build()
+      .unwrap();
+    let reader_topic = reader_participant
+      .create_topic(
+        topic_name.clone(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let mut data_reader = reader_participant
+      .create_subscriber(&qos)
+      .unwrap()
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&reader_topic, None)
+      .unwrap();
+
+    let wait_for = |what: &str, condition: &dyn Fn() -> bool| {
+      let deadline = Instant::now() + Duration::from_secs(10);
+      while !condition() {
+        assert!(Instant::now() < deadline, "Timeout waiting for {}", what);
+        std::thread::sleep(Duration::from_millis(100));
+      }
+    };
+    wait_for("local match", &|| {
+      local_reader.get_matched_publications().next().is_some()
+    });
+    // No SEDP, so the remote writer is not discovered by itself.
+    std::thread::sleep(Duration::from_secs(3));
+    assert!(data_reader.get_matched_publications().next().is_none());
+
+    reader_participant
+      .add_static_endpoint(StaticEndpoint::new(
+        data_writer.guid(),
+        topic_name.clone(),
+        "RandomData".to_string(),
+        qos.clone(),
+        vec![locator(&writer_participant)],
+      ))
+      .unwrap();
+    writer_participant
+      .add_static_endpoint(StaticEndpoint::new(
+        data_reader.guid(),
+        topic_name,
+        "RandomData".to_string(),
+        qos,
+        vec![locator(&reader_participant)],
+      ))
+      .unwrap();
+    assert!(writer_participant
+      .add_static_endpoint(StaticEndpoint::new(
+        local_reader.guid(),
+        "x".to_string(),
+        "y".to_string(),
+        QosPolicies::qos_none(),
+        vec![locator(&writer_participant)],
+      ))
+      .is_err());
+    wait_for("static match", &|| {
+      data_reader.get_matched_publications().next().is_some()
+    });
+
+    let data = RandomData {
+      a: 1,
+      b: "static".to_string(),
+    };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+      data_writer.write(data.clone(), None).unwrap();
+      if let Ok(Some(sample)) = data_reader.take_next_sample() {
+        assert_eq!(sample.into_value().unwrap(), data);
+        break;
+      }
+      assert!(Instant::now() < deadline, "No data received");
+      std::thread::sleep(Duration::from_millis(200));
+    }
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_publish_health() {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+      dds::health::ParticipantHealth, discovery::discovery::Discovery,
+      serialization::CDRDeserializerAdapter, structure::entity::RTPSEntity,
+    };
+
+    let monitored = loopback_participant_builder(205)
+      .publish_health(true)
+      .build()
+      .unwrap();
+    assert!(monitored.health().healthy);
+
+    let supervisor = loopback_participant(205);
+    let topic = supervisor
+      .create_topic(
+        ParticipantHealth::HEALTH_TOPIC_NAME.to_string(),
+        ParticipantHealth::HEALTH_TYPE_NAME.to_string(),
+        &Discovery::PARTICIPANT_MESSAGE_QOS,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let mut health_reader = supervisor
+      .create_subscriber(&QosPolicies::qos_none())
+      .unwrap()
+      .create_datareader::<ParticipantHealth, CDRDeserializerAdapter<ParticipantHealth>>(
+        &topic, None,
+      )
+      .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(15);
+    loop {
+      if let Ok(Some(sample)) = health_reader.take_next_sample() {
+        let health = sample.into_value().unwrap();
+        assert_eq!(health.participant_guid, monitored.guid());
+        assert!(health.healthy);
+        break;
+      }
+      assert!(Instant::now() < deadline, "No health sample received");
+      std::thread::sleep(Duration::from_millis(100));
+    }
+  }
+
+  #[test]
+  #[cfg(all(feature = "discovery", feature = "statistics"))]
+  fn dp_publish_statistics() {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+      dds::statistics::ParticipantStatistics, discovery::discovery::Discovery,
+      serialization::CDRDeserializerAdapter, structure::entity::RTPSEntity,
+    };
+
+    let monitored = loopback_participant_builder(206)
+      .publish_statistics(Duration::from_millis(500))
+      .build()
+      .unwrap();
+
+    let supervisor = loopback_participant(206);
+    let topic = supervisor
+      .create_topic(
+        ParticipantStatistics::STATISTICS_TOPIC_NAME.to_string(),
+        ParticipantStatistics::STATISTICS_TYPE_NAME.to_string(),
+        &Discovery::PARTICIPANT_MESSAGE_QOS,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let mut statistics_reader = supervisor
+      .create_subscriber(&QosPolicies::qos_none())
+      .unwrap()
+      .create_datareader::<ParticipantStatistics, CDRDeserializerAdapter<ParticipantStatistics>>(
+        &topic, None,
+      )
+      .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(15);
+    loop {
+      if let Ok(Some(sample)) = statistics_reader.take_next_sample() {
+        let statistics = sample.into_value().unwrap();
+        assert_eq!(statistics.participant_guid, monitored.guid());
+        assert!(statistics.messages_sent > 0);
+        // The statistics are published by a writer of the participant itself.
+        assert!(statistics
+          .writers
+          .iter()
+          .any(|w| w.topic_name == ParticipantStatistics::STATISTICS_TOPIC_NAME));
+        break;
+      }
+      assert!(Instant::now() < deadline, "No statistics sample received");
+      std::thread::sleep(Duration::from_millis(100));
+    }
+
+    // The supervisor has received the samples of the monitored participant.
+    let statistics = supervisor.statistics();
+    assert!(statistics.messages_received > 0);
+    assert!(statistics.readers.iter().any(|r| r.topic_name
+      == ParticipantStatistics::STATISTICS_TOPIC_NAME
+      && r.samples_received > 0));
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_discovery_listener() {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+      dds::statusevents::{DiscoveryEvent, StatusEvented},
+      structure::entity::RTPSEntity,
+    };
+
+    let monitor = loopback_participant(207);
+    let mut listener = monitor.discovery_listener();
+    let _evented = listener.as_status_evented();
+
+    let remote = loopback_participant(207);
+    let qos = QosPolicies::qos_none();
+    let topic = remote
+      .create_topic(
+        "discovery_listener_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let data_writer = remote
+      .create_publisher(&qos)
+      .unwrap()
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(&topic, None)
+      .unwrap();
+    let writer_guid = data_writer.guid();
+
+    let mut events = Vec::new();
+    let mut wait_for = |what: &str, matches: &dyn Fn(&DiscoveryEvent) -> bool| {
+      let deadline = Instant::now() + Duration::from_secs(15);
+      loop {
+        while let Some(event) = listener.try_recv_status() {
+          events.push(event);
+        }
+        if events.iter().any(matches) {
+          return;
+        }
+        assert!(Instant::now() < deadline, "Timeout waiting for {}", what);
+        std::thread::sleep(Duration::from_millis(100));
+      }
+    };
+
+    wait_for("participant", &|e| {
+      matches!(e, DiscoveryEvent::ParticipantDiscovered { participant }
+        if participant.participant_guid == remote.guid())
+    });
+    wait_for("writer", &|e| {
+      matches!(e, DiscoveryEvent::WriterDiscovered { writer }
+        if writer.writer_proxy.remote_writer_guid == writer_guid)
+    });
+    drop(data_writer);
+    wait_for(
+      "writer lost",
+      &|e| matches!(e, DiscoveryEvent::WriterLost { writer_guid: g } if *g == writer_guid),
+    );
+    // The same writer is reported only once, although it is announced
+    // repeatedly, and our own participant is not reported at all.
+    let count =
+      |matches: &dyn Fn(&DiscoveryEvent) -> bool| events.iter().filter(|e| matches(e)).count();
+    assert_eq!(
+      count(&|e| matches!(e, DiscoveryEvent::WriterDiscovered { writer }
+        if writer.writer_proxy.remote_writer_guid == writer_guid)),
+      1
+    );
+    assert_eq!(
+      count(
+        &|e| matches!(e, DiscoveryEvent::ParticipantDiscovered { participant }
+        if participant.participant_guid == monitor.guid())
+      ),
+      0
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_remote_qos_update() {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+      dds::{
+        qos::{
+          policy::{Deadline, Reliability},
+          MutQosPolicy, QosPolicyBuilder, QosPolicyId,
+        },
+        statusevents::{DataReaderStatus, StatusEvented},
+      },
+      serialization::CDRDeserializerAdapter,
+    };
+
+    let deadline = |millis| Deadline(crate::Duration::from_millis(millis));
+    let reliable = Reliability::Reliable {
+      max_blocking_time: crate::Duration::DURATION_ZERO,
+    };
+    let reader_qos = QosPolicyBuilder::new()
+      .reliability(reliable)
+      .deadline(deadline(1000))
+      .build();
+    let writer_qos = QosPolicyBuilder::new()
+      .reliability(reliable)
+      .deadline(deadline(2000))
+      .build();
+    let topic_name = "remote_qos_update_test".to_string();
+
+    let reader_participant = loopback_participant(208);
+    let topic = reader_participant
+      .create_topic(
+        topic_name.clone(),
+        "RandomData".to_string(),
+        &reader_qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let mut data_reader = reader_participant
+      .create_subscriber(&reader_qos)
+      .unwrap()
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+    let _evented = data_reader.as_status_evented();
+
+    let writer_participant = loopback_participant(208);
+    let writer_topic = writer_participant
+      .create_topic(
+        topic_name,
+        "RandomData".to_string(),
+        &writer_qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let mut data_writer = writer_participant
+      .create_publisher(&writer_qos)
+      .unwrap()
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        &writer_topic,
+        None,
+      )
+      .unwrap();
+
+    let wait_for = |what: &str, matches: &dyn Fn(&DataReaderStatus) -> bool| {
+      let timeout = Instant::now() + Duration::from_secs(15);
+      loop {
+        if let Some(status) = data_reader.try_recv_status() {
+          if matches(&status) {
+            return;
+          }
+        }
+        assert!(Instant::now() < timeout, "Timeout waiting for {}", what);
+        std::thread::sleep(Duration::from_millis(50));
+      }
+    };
+
+    // Offered deadline is longer than requested.
+    wait_for("incompatible QoS", &|s| {
+      matches!(s, DataReaderStatus::RequestedIncompatibleQos { last_policy_id, .. }
+        if *last_policy_id == QosPolicyId::Deadline)
+    });
+
+    // The writer tightens its deadline, and is matched.
+    data_writer
+      .set_qos(&QosPolicyBuilder::new().deadline(deadline(500)).build())
+      .unwrap();
+    wait_for("match", &|s| {
+      matches!(s, DataReaderStatus::SubscriptionMatched { current, .. }
+        if current.count_change() == 1)
+    });
+
+    // ...and relaxes it again, so it is unmatched.
+    data_writer
+      .set_qos(&QosPolicyBuilder::new().deadline(deadline(2000)).build())
+      .unwrap();
+    wait_for("unmatch", &|s| {
+      matches!(s, DataReaderStatus::SubscriptionMatched { current, .. }
+        if current.count_change() == -1)
+    });
+    wait_for("incompatible QoS again", &|s| {
+      matches!(s, DataReaderStatus::RequestedIncompatibleQos { .. })
+    });
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_domain_tag_isolation() {
+    use std::time::{Duration, Instant};
+
+    use crate::structure::entity::RTPSEntity;
+
+    let tagged = |tag: &str| {
+      loopback_participant_builder(209)
+        .domain_tag(tag)
+        .build()
+        .unwrap()
+    };
+    let lab_a = tagged("lab");
+    let lab_b = tagged("lab");
+    let production = tagged("");
+    let knows = |dp: &DomainParticipant, other: &DomainParticipant| {
+      dp.discovered_participants()
+        .iter()
+        .any(|p| p.participant_guid == other.guid())
+    };
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !(knows(&lab_a, &lab_b) && knows(&lab_b, &lab_a)) {
+      assert!(Instant::now() < deadline, "Same domain tag not discovered");
+      std::thread::sleep(Duration::from_millis(100));
+    }
+    // Give the untagged participant a few announcement rounds also.
+    std::thread::sleep(Duration::from_secs(3));
+    assert!(!knows(&lab_a, &production));
+    assert!(!knows(&production, &lab_a));
+    assert!(!knows(&production, &lab_b));
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_participant_authenticator() {
+    use std::{
+      collections::BTreeMap,
+      sync::{Arc, Mutex},
+      time::{Duration, Instant},
+    };
+
+    use crate::{
+      discovery::data_types::spdp_participant_data::SpdpDiscoveredParticipantData,
+      structure::{entity::RTPSEntity, guid::GuidPrefix},
+    };
+
+    let trusted_prefix = GuidPrefix::new(b"trustedPeer1");
+    // How many times each participant was asked about
+    let asked = Arc::new(Mutex::new(BTreeMap::<GuidPrefix, usize>::new()));
+    let asked_clone = asked.clone();
+    let gatekeeper = loopback_participant_builder(210)
+      .participant_authenticator(move |p: &SpdpDiscoveredParticipantData| {
+        let prefix = p.participant_guid.prefix;
+        *asked_clone.lock().unwrap().entry(prefix).or_insert(0) += 1;
+        prefix == trusted_prefix
+      })
+      .build()
+      .unwrap();
+    let trusted = loopback_participant_builder(210)
+      .guid_prefix(trusted_prefix)
+      .build()
+      .unwrap();
+    let stranger = loopback_participant(210);
+
+    let knows = |prefix: GuidPrefix| {
+      gatekeeper
+        .discovered_participants()
+        .iter()
+        .any(|p| p.participant_guid.prefix == prefix)
+    };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !knows(trusted.guid_prefix()) {
+      assert!(
+        Instant::now() < deadline,
+        "Trusted participant not accepted"
+      );
+      std::thread::sleep(Duration::from_millis(100));
+    }
+    // Let both send a few more announcements.
+    std::thread::sleep(Duration::from_secs(3));
+    assert!(!knows(stranger.guid_prefix()));
+
+    let asked = asked.lock().unwrap();
+    assert_eq!(asked.get(&trusted.guid_prefix()), Some(&1));
+    assert_eq!(asked.get(&stranger.guid_prefix()), Some(&1));
+  }
+
+  #[cfg(feature = "security")]
+  #[test]
+  fn dp_security_authentication() {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+      security::authentication::IdentityCredentials,
+      structure::{entity::RTPSEntity, guid::GuidPrefix},
+    };
+
+    let identity_ca = include_str!("../test/security/identity_ca.pem");
+    let identity = |certificate: &str, key: &str| {
+      IdentityCredentials::from_pem(identity_ca, certificate, key).unwrap()
+    };
+    let secure_a = loopback_participant_builder(211)
+      .identity(identity(
+        include_str!("../test/security/participant_a.pem"),
+        include_str!("../test/security/participant_a_key.pem"),
+      ))
+      .build()
+      .unwrap();
+    let secure_b = loopback_participant_builder(211)
+      .identity(identity(
+        include_str!("../test/security/participant_b.pem"),
+        include_str!("../test/security/participant_b_key.pem"),
+      ))
+      .build()
+      .unwrap();
+    let plain = loopback_participant(211);
+    // The GUID prefix is derived from the certificate.
+    assert_eq!(secure_a.guid_prefix().bytes[0] & 0x80, 0x80);
+
+    let knows = |a: &DomainParticipant, prefix: GuidPrefix| {
+      a.discovered_participants()
+        .iter()
+        .any(|p| p.participant_guid.prefix == prefix)
+    };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !(knows(&secure_a, secure_b.guid_prefix()) && knows(&secure_b, secure_a.guid_prefix())) {
+      assert!(
+        Instant::now() < deadline,
+        "Secure participants did not authenticate"
+      );
+      std::thread::sleep(Duration::from_millis(100));
+    }
+    // Let the plain participant send a few more announcements.
+    std::thread::sleep(Duration::from_secs(3));
+    assert!(!knows(&secure_a, plain.guid_prefix()));
+    assert!(!knows(&secure_b, plain.guid_prefix()));
+  }
+
+  #[cfg(feature = "security")]
+  #[test]
+  fn dp_custom_authentication_plugin() {
+    use std::{
+      collections::BTreeSet,
+      sync::Mutex,
+      time::{Duration, Instant},
+    };
+
+    use crate::{
+      discovery::data_types::spdp_participant_data::SpdpDiscoveredParticipantData,
+      security::{
+        authentication::{HandshakeProgress, RemoteCredentials, SharedSecret},
+        generic_message::{MessageIdentity, ParticipantGenericMessage},
+        plugins::AuthenticationPlugin,
+        types::DataHolder,
+        SecurityError, SecurityResult,
+      },
+      structure::{entity::RTPSEntity, guid::GuidPrefix},
+    };
+
+    // Participants that know the same password accept each other. Each sends
+    // the password to the other, until it has received the other's. Passwords
+    // that are not replies are answered, in case the first answer was lost.
+    struct PasswordAuthentication {
+      password: &'static str,
+      guid: Mutex<GUID>,
+      pending: Mutex<BTreeSet<GuidPrefix>>,
+      authenticated: Mutex<BTreeSet<GuidPrefix>>,
+    }
+
+    impl PasswordAuthentication {
+      fn new(password: &'static str) -> Self {
+        Self {
+          password,
+          guid: Mutex::new(GUID::GUID_UNKNOWN),
+          pending: Mutex::new(BTreeSet::new()),
+          authenticated: Mutex::new(BTreeSet::new()),
+        }
+      }
+
+      fn message(&self, remote: GuidPrefix, class_id: &str) -> ParticipantGenericMessage {
+        let guid = *self.guid.lock().unwrap();
+        ParticipantGenericMessage {
+          message_identity: MessageIdentity {
+            source_guid: guid.from_prefix(EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_WRITER),
+            sequence_number: 1,
+          },
+          related_message_identity: MessageIdentity::default(),
+          destination_participant_guid: GUID::new(remote, EntityId::PARTICIPANT),
+          destination_endpoint_guid: GUID::GUID_UNKNOWN,
+          source_endpoint_guid: GUID::GUID_UNKNOWN,
+          message_class_id: ParticipantGenericMessage::AUTH_HANDSHAKE.to_string(),
+          message_data: vec![DataHolder::new(class_id).with_property("password", self.password)],
+        }
+      }
+    }
+
+    impl AuthenticationPlugin for PasswordAuthentication {
+      fn identity_token(&self) -> DataHolder {
+        DataHolder::new("Password")
+      }
+
+      fn set_participant_data(&self, data: &SpdpDiscoveredParticipantData) {
+        *self.guid.lock().unwrap() = data.participant_guid;
+      }
+
+      fn set_permissions_document(&self, _document: Vec<u8>) {}
+
+      fn begin(
+        &self,
+        remote_guid: GUID,
+        identity_token: Option<&DataHolder>,
+      ) -> SecurityResult<Option<ParticipantGenericMessage>> {
+        match identity_token {
+          Some(token) if token.class_id == "Password" => {
+            self.pending.lock().unwrap().insert(remote_guid.prefix);
+            Ok(Some(self.message(remote_guid.prefix, "Password")))
+          }
+          _ => Err(SecurityError::new("No password")),
+        }
+      }
+
+      fn process(&self, message: &ParticipantGenericMessage) -> SecurityResult<HandshakeProgress> {
+        let source = message.message_identity.source_guid.prefix;
+        // Wait for SPDP to tell about the participant before accepting it.
+        if message.destination_participant_guid.prefix != self.guid.lock().unwrap().prefix
+          || !self.pending.lock().unwrap().contains(&source)
+        {
+          return Ok(HandshakeProgress::Nothing);
+        }
+        let token = match message.message_data.first() {
+          Some(token) if token.property("password") == Some(self.password) => token,
+          _ => return Err(SecurityError::new("Wrong password")),
+        };
+        let reply = (token.class_id == "Password").then(|| self.message(source, "Password+Reply"));
+        if self.authenticated.lock().unwrap().insert(source) {
+          Ok(HandshakeProgress::Completed(reply))
+        } else {
+          Ok(reply.map_or(HandshakeProgress::Nothing, HandshakeProgress::Send))
+        }
+      }
+
+      fn resend(&self) -> Vec<ParticipantGenericMessage> {
+        let authenticated = self.authenticated.lock().unwrap();
+        let pending = self.pending.lock().unwrap();
+        pending
+          .difference(&authenticated)
+          .map(|prefix| self.message(*prefix, "Password"))
+          .collect()
+      }
+
+      fn remote_credentials(&self, guid_prefix: GuidPrefix) -> Option<RemoteCredentials> {
+        self.authenticated.lock().unwrap().get(&guid_prefix)?;
+        Some(RemoteCredentials {
+          subject_name: format!("{:?}", guid_prefix),
+          permissions: Vec::new(),
+        })
+      }
+
+      fn shared_secret(&self, _guid_prefix: GuidPrefix) -> Option<SharedSecret> {
+        None
+      }
+
+      fn remove(&self, guid_prefix: GuidPrefix) {
+        self.pending.lock().unwrap().remove(&guid_prefix);
+        self.authenticated.lock().unwrap().remove(&guid_prefix);
+      }
+    }
+
+    let participant = |password| {
+      loopback_participant_builder(212)
+        .authentication_plugin(PasswordAuthentication::new(password))
+        .build()
+        .unwrap()
+    };
+    let a = participant("open sesame");
+    let b = participant("open sesame");
+    let intruder = participant("abracadabra");
+    let plain = loopback_participant(212);
+
+    let knows = |a: &DomainParticipant, prefix: GuidPrefix| {
+      a.discovered_participants()
+        .iter()
+        .any(|p| p.participant_guid.prefix == prefix)
+    };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !(knows(&a, b.guid_prefix()) && knows(&b, a.guid_prefix())) {
+      assert!(
+        Instant::now() < deadline,
+        "Participants did not authenticate"
+      );
+      std::thread::sleep(Duration::from_millis(100));
+    }
+    // Let the others send a few more announcements and handshakes.
+    std::thread::sleep(Duration::from_secs(3));
+    assert!(!knows(&a, intruder.guid_prefix()));
+    assert!(!knows(&intruder, a.guid_prefix()));
+    assert!(!knows(&a, plain.guid_prefix()));
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_type_lookup() {
+    use std::time::{Duration, Instant};
+
+    use crate::discovery::type_lookup::TypeObject;
+
+    let server = loopback_participant(213);
+    let client = loopback_participant(213);
+
+    let type_object =
+      TypeObject::from_xcdr2_bytes(vec![8, 0, 0, 0, 0xF2, 0x51, 0, 0, 1, 2, 3, 4]).unwrap();
+    let type_id = server.register_type_object(type_object.clone());
+    assert_eq!(client.type_object(&type_id), None);
+
+    // Requests are lost until the TypeLookup endpoints are matched, so repeat.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while client.type_object(&type_id).is_none() {
+      assert!(Instant::now() < deadline, "TypeObject not received");
+      client.request_type_objects(vec![type_id]).unwrap();
+      std::thread::sleep(Duration::from_millis(500));
+    }
+    assert_eq!(client.type_object(&type_id), Some(type_object));
+  }
+
+  #[test]
+  fn dp_builder_initial_peer_locators() {
+    use std::net::IpAddr;
+
+    use super::DomainParticipantBuilder;
+    use crate::network::port_mapping::PortMapping;
+
+    let peer = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let explicit = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 12345);
+    let builder = DomainParticipantBuilder::new(3)
+      .initial_peer_with_participant_ids(peer, 1..=2)
+      // overlapping range must not produce duplicates
+      .initial_peer_with_participant_ids(peer, 2..=2)
+      .initial_peer_socket_address(explicit);
+
+    let port = |pid| PortMapping::default().spdp_unicast_port(3, pid).unwrap();
+    let expected: BTreeSet<Locator> = vec![
+      Locator::from(SocketAddr::new(peer, port(1))),
+      Locator::from(SocketAddr::new(peer, port(2))),
+      Locator::from(explicit),
+    ]
+    .into_iter()
+    .collect();
+    let locators = builder.initial_peer_locators();
+    assert_eq!(locators.len(), expected.len());
+    assert_eq!(locators.into_iter().collect::<BTreeSet<_>>(), expected);
+
+    assert_eq!(
+      DomainParticipantBuilder::new(0)
+        .initial_peer(peer)
+        .initial_peer_locators()
+        .len(),
+      DomainParticipantBuilder::DEFAULT_INITIAL_PEER_PARTICIPANT_IDS.count()
+    );
+
+    // Participant ids beyond the port range are skipped.
+    let locators = DomainParticipantBuilder::new(1)
+      .port_mapping(PortMapping {
+        port_base: 65000,
+        ..PortMapping::default()
+      })
+      .initial_peer_with_participant_ids(peer, 0..=200)
+      .initial_peer_locators();
+    assert_eq!(
+      locators.last(),
+      Some(&Locator::from(SocketAddr::new(peer, 65534)))
+    );
+    assert_eq!(locators.len(), 138);
+  }
+
+  #[test]
+  fn dp_port_mapping() {
+    use super::DomainParticipantBuilder;
+    use crate::{
+      dds::values::result::Error,
+      network::{constant::*, port_mapping::PortMapping, transport::TransportKind},
+    };
+
+    let mapping = PortMapping {
+      port_base: 20400,
+      domain_id_gain: 100,
+      participant_id_gain: 4,
+      offset_d0: 0,
+      offset_d1: 20,
+      offset_d2: 1,
+      offset_d3: 22,
+    };
+    let make_participant = |domain_id| {
+      DomainParticipantBuilder::new(domain_id)
+        .transports([TransportKind::UdpV4])
+        .port_mapping(mapping)
+        .build()
+        .unwrap()
+    };
+    // Participants of several domains in the same process
+    let participants = [
+      make_participant(3),
+      make_participant(3),
+      make_participant(4),
+    ];
+    assert_ne!(
+      participants[0].participant_id(),
+      participants[1].participant_id()
+    );
+
+    for participant in &participants {
+      let domain_id = participant.domain_id();
+      let participant_id = participant.participant_id();
+      let self_locators = participant.self_locators();
+      let ports = |token| {
+        self_locators
+          .get(&token)
+          .into_iter()
+          .flatten()
+          .filter(|locator| locator.is_udp())
+          .map(|locator| SocketAddr::from(*locator).port())
+          .collect::<BTreeSet<u16>>()
+      };
+      assert_eq!(
+        ports(DISCOVERY_LISTENER_TOKEN),
+        [mapping
+          .spdp_unicast_port(domain_id, participant_id)
+          .unwrap()]
+        .into()
+      );
+      assert_eq!(
+        ports(USER_TRAFFIC_LISTENER_TOKEN),
+        [mapping
+          .user_traffic_unicast_port(domain_id, participant_id)
+          .unwrap()]
+        .into()
+      );
+      assert_eq!(
+        ports(DISCOVERY_MUL_LISTENER_TOKEN),
+        [mapping.spdp_multicast_port(domain_id).unwrap()].into()
+      );
+    }
+
+    let out_of_range = DomainParticipantBuilder::new(700)
+      .port_mapping(mapping)
+      .build();
+    assert!(matches!(out_of_range, Err(Error::BadParameter { .. })));
+  }
+
+  #[test]
+  fn dp_participant_id_skips_taken_ports() {
+    use std::net::UdpSocket;
+
+    use crate::network::constant::USER_TRAFFIC_LISTENER_TOKEN;
+
+    let domain_id = 27;
+    let mapping = PortMapping::default();
+    // Another process holds the user traffic port of the first free
+    // ParticipantId.
+    let first = DomainParticipant::new(domain_id).unwrap();
+    let free_id = first.participant_id() + 1;
+    let _socket = UdpSocket::bind((
+      Ipv4Addr::UNSPECIFIED,
+      mapping
+        .user_traffic_unicast_port(domain_id, free_id)
+        .unwrap(),
+    ))
+    .unwrap();
+
+    let second = DomainParticipant::new(domain_id).unwrap();
+    assert!(second.participant_id() > free_id);
+    let user_traffic_ports: BTreeSet<u16> = second
+      .self_locators()
+      .get(&USER_TRAFFIC_LISTENER_TOKEN)
+      .into_iter()
+      .flatten()
+      .filter(|locator| matches!(locator, Locator::UdpV4(_)))
+      .map(|locator| SocketAddr::from(*locator).port())
+      .collect();
+    assert_eq!(
+      user_traffic_ports,
+      [mapping
+        .user_traffic_unicast_port(domain_id, second.participant_id())
+        .unwrap()]
+      .into()
+    );
+  }
+
+  #[test]
+  fn dp_close() {
+    let domain_id = 28;
+    let qos = QosPolicies::qos_none();
+    let participant = DomainParticipant::new(domain_id).unwrap();
+    let participant_id = participant.participant_id();
+    let topic = participant
+      .create_topic(
+        "closing".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let writer = participant
+      .create_publisher(&qos)
+      .unwrap()
+      .create_datawriter_cdr::<RandomData>(&topic, None)
+      .unwrap();
+    writer
+      .write(
+        RandomData {
+          a: 1,
+          b: "live".to_string(),
+        },
+        None,
+      )
+      .unwrap();
+
+    participant.close().unwrap();
+    participant.close().unwrap();
+    // The listening ports are free again.
+    let next = DomainParticipant::new(domain_id).unwrap();
+    assert_eq!(next.participant_id(), participant_id);
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_unicast_only_discovery() {
+    use std::time::{Duration, Instant};
+
+    use crate::structure::entity::RTPSEntity;
+
+    let participant_a = loopback_participant(214);
+    let participant_b = loopback_participant(214);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let remote_b = loop {
+      if let Some(p) = participant_a
+        .discovered_participants()
+        .into_iter()
+        .find(|p| p.participant_guid == participant_b.guid())
+      {
+        break p;
+      }
+      assert!(Instant::now() < deadline, "Participant was not discovered");
+      std::thread::sleep(Duration::from_millis(100));
+    };
+    assert!(remote_b.metatraffic_multicast_locators.is_empty());
+    assert!(participant_b
+      .discovered_participants()
+      .iter()
+      .any(|p| p.participant_guid == participant_a.guid()));
+  }
+
+  #[test]
+  #[cfg(not(feature = "discovery"))]
+  fn dp_without_discovery() {
+    use std::time::Duration;
+
+    use crate::structure::entity::RTPSEntity;
+
+    // Initial peers are set, but there is no SPDP to contact them.
+    let participant_a = loopback_participant(223);
+    let participant_b = loopback_participant(223);
+
+    std::thread::sleep(Duration::from_secs(3));
+    assert!(!participant_a
+      .discovered_participants()
+      .iter()
+      .any(|p| p.participant_guid == participant_b.guid()));
+    assert!(!participant_b
+      .discovered_participants()
+      .iter()
+      .any(|p| p.participant_guid == participant_a.guid()));
+  }
+
+  #[test]
+  fn dp_builder_needs_a_transport() {
+    use super::DomainParticipantBuilder;
+    use crate::{dds::values::result::Error, network::transport::TransportKind};
+
+    assert!(matches!(
+      DomainParticipantBuilder::new(0).transports(vec![]).build(),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      DomainParticipantBuilder::new(0)
+        .transports([TransportKind::SharedMemory])
+        .build(),
+      Err(Error::BadParameter { .. })
+    ));
+  }
+
+  #[test]
+  fn dp_builder_checks_multicast_ttl() {
+    use super::DomainParticipantBuilder;
+    use crate::dds::values::result::Error;
+
+    assert!(matches!(
+      DomainParticipantBuilder::new(0).multicast_ttl(0).build(),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      DomainParticipantBuilder::new(0).multicast_ttl(256).build(),
+      Err(Error::BadParameter { .. })
+    ));
+  }
+
+  #[test]
+  fn dp_builder_checks_thread_config() {
+    use super::DomainParticipantBuilder;
+    use crate::dds::{health::ProtocolThread, threads::ThreadConfig, values::result::Error};
+
+    assert!(matches!(
+      DomainParticipantBuilder::new(0)
+        .thread_config(
+          ProtocolThread::EventLoop,
+          ThreadConfig::new().cpu_affinity(Vec::new())
+        )
+        .build(),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      DomainParticipantBuilder::new(0)
+        .thread_config(
+          ProtocolThread::Discovery,
+          ThreadConfig::new().cpu_affinity([ThreadConfig::MAX_CPUS])
+        )
+        .build(),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      DomainParticipantBuilder::new(0)
+        .thread_config(ProtocolThread::Discovery, ThreadConfig::new().stack_size(0))
+        .build(),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      DomainParticipantBuilder::new(0)
+        .thread_config(ProtocolThread::Receive, ThreadConfig::new().count(0))
+        .build(),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      DomainParticipantBuilder::new(0)
+        .thread_config(ProtocolThread::EventLoop, ThreadConfig::new().count(2))
+        .build(),
+      Err(Error::BadParameter { .. })
+    ));
+  }
+
+  #[test]
+  #[cfg(target_os = "linux")]
+  fn dp_thread_config() {
+    use super::DomainParticipantBuilder;
+    use crate::dds::{health::ProtocolThread, threads::ThreadConfig};
+
+    let thread_names = || {
+      std::fs::read_dir("/proc/self/task")
+        .unwrap()
+        .filter_map(|task| std::fs::read_to_string(task.ok()?.path().join("comm")).ok())
+        .map(|name| name.trim_end().to_string())
+        .collect::<Vec<_>>()
+    };
+
+    let participant = DomainParticipantBuilder::new(0)
+      .thread_config(
+        ProtocolThread::EventLoop,
+        ThreadConfig::new()
+          .name("dp-test-io")
+          .stack_size(4 << 20)
+          .cpu_affinity([0]),
+      )
+      .thread_config(
+        ProtocolThread::Discovery,
+        ThreadConfig::new().name("dp-test-disc"),
+      )
+      .thread_config(
+        ProtocolThread::Receive,
+        ThreadConfig::new().name("dp-test-rx").count(2),
+      )
+      .build()
+      .unwrap();
+    let names = thread_names();
+    assert!(names.iter().any(|name| name == "dp-test-io"));
+    assert!(names.iter().any(|name| name == "dp-test-disc"));
+    assert!(names.iter().any(|name| name == "dp-test-rx-0"));
+    assert!(names.iter().any(|name| name == "dp-test-rx-1"));
+
+    participant.close().unwrap();
+    let names = thread_names();
+    assert!(!names.iter().any(|name| name.starts_with("dp-test-")));
+  }
+
+  #[test]
+  fn dp_build_driven() {
+    use std::time::{Duration, Instant};
+
+    use super::DomainParticipantBuilder;
+
+    let (participant, mut driver) = DomainParticipantBuilder::new(0).build_driven().unwrap();
+    for _ in 0..5 {
+      assert!(driver.poll(Duration::from_millis(10)));
+    }
+    let health = participant.health();
+    assert!(health.event_loop.alive && health.discovery.alive);
+
+    participant.close().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while driver.poll(Duration::from_millis(10)) {
+      assert!(Instant::now() < deadline, "Driver did not finish");
+    }
+    assert!(!driver.poll(Duration::ZERO));
+  }
+
+  #[test]
+  fn dp_build_simulated() {
+    use std::time::{Duration, Instant};
+
+    use super::DomainParticipantBuilder;
+    use crate::dds::simulation::SimulatedClock;
+
+    let clock = SimulatedClock::default();
+    let (participant, mut driver) = DomainParticipantBuilder::new(0)
+      .build_simulated(&clock)
+      .unwrap();
+    assert!(driver.step());
+
+    // An hour of protocol timers runs without waiting for them.
+    let started = Instant::now();
+    while clock.elapsed() < Duration::from_secs(3600) {
+      let idle = clock.time_to_next_timer().unwrap();
+      assert!(idle <= Duration::from_secs(60));
+      clock.advance(idle);
+      assert!(driver.step());
+    }
+    assert!(started.elapsed() < Duration::from_secs(60));
+
+    participant.close().unwrap();
+    assert!(!driver.step());
+  }
+
+  #[test]
+  fn dp_custom_clock() {
+    use super::DomainParticipantBuilder;
+    use crate::{
+      dds::clock::Clock,
+      structure::{duration::Duration, time::Timestamp},
+    };
+
+    struct FixedClock(Timestamp);
+
+    impl Clock for FixedClock {
+      fn now(&self) -> Timestamp {
+        self.0
+      }
+    }
+
+    let fixed = Timestamp::ZERO + Duration::from_secs(1234);
+    let participant = DomainParticipantBuilder::new(0)
+      .clock(FixedClock(fixed))
+      .build()
+      .unwrap();
+    assert_eq!(participant.current_time(), fixed);
+  }
+
+  #[test]
+  #[ignore] // Requires working IPv6 multicast on the local host.
+  fn dp_ipv6_only_discovery() {
+    use std::time::{Duration, Instant};
+
+    use super::DomainParticipantBuilder;
+    use crate::{
+      network::{port_mapping::PortMapping, transport::TransportKind},
+      structure::entity::RTPSEntity,
+    };
+
+    let make_participant = || {
+      DomainParticipantBuilder::new(18)
+        .transports([TransportKind::UdpV6])
+        .build()
+        .unwrap()
+    };
+    let participant_a = make_participant();
+    let participant_b = make_participant();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let remote_b = loop {
+      if let Some(p) = participant_a
+        .discovered_participants()
+        .into_iter()
+        .find(|p| p.participant_guid == participant_b.guid())
+      {
+        break p;
+      }
+      assert!(Instant::now() < deadline, "Participant was not discovered");
+      std::thread::sleep(Duration::from_millis(100));
+    };
+    assert!(remote_b
+      .metatraffic_unicast_locators
+      .iter()
+      .chain(remote_b.metatraffic_multicast_locators.iter())
+      .all(|loc| matches!(loc, Locator::UdpV6(_))));
+    assert!(remote_b
+      .metatraffic_multicast_locators
+      .contains(&Locator::from(SocketAddr::new(
+        TransportKind::UdpV6.multicast_group().unwrap(),
+        PortMapping::default().spdp_multicast_port(18).unwrap()
+      ))));
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_tcp_user_traffic() {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+      dds::qos::{policy::Reliability, QosPolicyBuilder},
+      network::transport::TransportKind,
+      serialization::CDRDeserializerAdapter,
+      structure::entity::RTPSEntity,
+    };
+
+    let make_participant = || {
+      loopback_participant_builder(224)
+        .transports([TransportKind::TcpV4, TransportKind::UdpV4])
+        .build()
+        .unwrap()
+    };
+    let writer_participant = make_participant();
+    let reader_participant = make_participant();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let remote_reader_participant = loop {
+      if let Some(p) = writer_participant
+        .discovered_participants()
+        .into_iter()
+        .find(|p| p.participant_guid == reader_participant.guid())
+      {
+        break p;
+      }
+      assert!(Instant::now() < deadline, "Participant was not discovered");
+      std::thread::sleep(Duration::from_millis(100));
+    };
+    // Discovery stays on UDP.
+    assert!(remote_reader_participant
+      .default_unicast_locators
+      .iter()
+      .any(|loc| matches!(loc, Locator::TcpV4(_))));
+    assert!(remote_reader_participant
+      .metatraffic_unicast_locators
+      .iter()
+      .all(|loc| matches!(loc, Locator::UdpV4(_))));
+
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: crate::Duration::DURATION_ZERO,
+      })
+      .build();
+    let topic = writer_participant
+      .create_topic(
+        "tcp_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let data_writer = writer_participant
+      .create_publisher(&qos)
+      .unwrap()
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(&topic, None)
+      .unwrap();
+    let reader_topic = reader_participant
+      .create_topic(
+        "tcp_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let mut data_reader = reader_participant
+      .create_subscriber(&qos)
+      .unwrap()
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&reader_topic, None)
+      .unwrap();
+
+    let data = RandomData {
+      a: 1,
+      b: "tcp".to_string(),
+    };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+      data_writer.write(data.clone(), None).unwrap();
+      if let Ok(Some(sample)) = data_reader.take_next_sample() {
+        assert_eq!(sample.into_value().unwrap(), data);
+        break;
+      }
+      assert!(Instant::now() < deadline, "No data received");
+      std::thread::sleep(Duration::from_millis(200));
+    }
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_receive_threads() {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+      dds::{
+        health::ProtocolThread,
+        qos::{policy::Reliability, QosPolicyBuilder},
+        threads::ThreadConfig,
+      },
+      serialization::CDRDeserializerAdapter,
+    };
+
+    // Discovery and user traffic are received by receive threads, two in the
+    // writer participant, one in the reader participant.
+    let writer_participant = loopback_participant_builder(225)
+      .thread_config(ProtocolThread::Receive, ThreadConfig::new().count(2))
+      .build()
+      .unwrap();
+    let reader_participant = loopback_participant_builder(225)
+      .thread_config(ProtocolThread::Receive, ThreadConfig::new())
+      .build()
+      .unwrap();
+
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: crate::Duration::DURATION_ZERO,
+      })
+      .build();
+    let topic = writer_participant
+      .create_topic(
+        "receive_threads_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let data_writer = writer_participant
+      .create_publisher(&qos)
+      .unwrap()
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(&topic, None)
+      .unwrap();
+    let reader_topic = reader_participant
+      .create_topic(
+        "receive_threads_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let mut data_reader = reader_participant
+      .create_subscriber(&qos)
+      .unwrap()
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&reader_topic, None)
+      .unwrap();
+
+    let data = RandomData {
+      a: 1,
+      b: "receive threads".to_string(),
+    };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+      data_writer.write(data.clone(), None).unwrap();
+      if let Ok(Some(sample)) = data_reader.take_next_sample() {
+        assert_eq!(sample.into_value().unwrap(), data);
+        break;
+      }
+      assert!(Instant::now() < deadline, "No data received");
+      std::thread::sleep(Duration::from_millis(200));
+    }
+
+    writer_participant.close().unwrap();
+    reader_participant.close().unwrap();
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_participant_lost_status() {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+      dds::statusevents::{DomainParticipantStatus, ParticipantLostReason, StatusEvented},
+      structure::entity::RTPSEntity,
+    };
+
+    let participant_a = loopback_participant(215);
+    let mut status_listener = participant_a.status_listener();
+    // Statuses are delivered only to listeners that are registered to a poll.
+    let _ = status_listener.as_status_evented();
+    let participant_b = loopback_participant(215);
+    let prefix_b = participant_b.guid().prefix;
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !participant_a
+      .discovered_participants()
+      .iter()
+      .any(|p| p.participant_guid.prefix == prefix_b)
+    {
+      assert!(Instant::now() < deadline, "Participant was not discovered");
+      std::thread::sleep(Duration::from_millis(100));
+      // do not let the listener fill up
+      while status_listener.try_recv_status().is_some() {}
+    }
+
+    // Dropping announces disposal of the participant. If that announcement is
+    // lost, then the lease of B expires in 5 * 2 seconds.
+    drop(participant_b);
+
+    let deadline = Instant::now() + Duration::from_secs(20);
+    loop {
+      match status_listener.try_recv_status() {
+        Some(DomainParticipantStatus::ParticipantLost {
+          guid_prefix,
+          reason,
+        }) if guid_prefix == prefix_b => {
+          assert!(matches!(
+            reason,
+            ParticipantLostReason::Disposed | ParticipantLostReason::LeaseExpired
+          ));
+          break;
+        }
+        Some(_other) => (),
+        None => {
+          assert!(Instant::now() < deadline, "No ParticipantLost status");
+          std::thread::sleep(Duration::from_millis(100));
+        }
+      }
+    }
+    assert!(!participant_a
+      .discovered_participants()
+      .iter()
+      .any(|p| p.participant_guid.prefix == prefix_b));
+  }
+
+  #[test]
+  #[cfg(feature = "discovery")]
+  fn dp_writer_lost_and_rediscovered() {
+    use std::{thread, time::Duration};
+
+    use super::ParticipantDriver;
+    use crate::{
+      dds::{
+        qos::{policy, QosPolicyBuilder},
+        readcondition::ReadCondition,
+        sampleinfo::InstanceState,
+        simulation::SimulatedClock,
+      },
+      serialization::CDRDeserializerAdapter,
+      structure::entity::RTPSEntity,
+    };
+
+    // Advances the shared clock in small steps, so that the stepped
+    // participants exchange messages and run their timers in between.
+    fn run(clock: &SimulatedClock, drivers: &mut [&mut ParticipantDriver], duration: Duration) {
+      let end = clock.elapsed() + duration;
+      while clock.elapsed() < end {
+        clock.advance(Duration::from_millis(100));
+        for driver in drivers.iter_mut() {
+          driver.step();
+        }
+        // Let the UDP messages arrive
+        thread::sleep(Duration::from_millis(2));
+      }
+    }
+
+    let qos = QosPolicyBuilder::new()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: crate::Duration::DURATION_ZERO,
+      })
+      .durability(policy::Durability::TransientLocal)
+      .history(policy::History::KeepAll)
+      .build();
+    let clock = SimulatedClock::default();
+    let (writer_participant, mut writer_driver) = loopback_participant_builder(222)
+      .build_simulated(&clock)
+      .unwrap();
+    let (reader_participant, mut reader_driver) = loopback_participant_builder(222)
+      .build_simulated(&clock)
+      .unwrap();
+    let writer_prefix = writer_participant.guid().prefix;
+
+    let topic = writer_participant
+      .create_topic(
+        "writer_lost_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let data_writer = writer_participant
+      .create_publisher(&qos)
+      .unwrap()
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(&topic, None)
+      .unwrap();
+    let topic = reader_participant
+      .create_topic(
+        "writer_lost_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let mut data_reader = reader_participant
+      .create_subscriber(&qos)
+      .unwrap()
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+    let sample = |b: &str| RandomData {
+      a: 1,
+      b: b.to_string(),
+    };
+
+    data_writer.write(sample("before"), None).unwrap();
+    let mut received = false;
+    for _ in 0..200 {
+      run(
+        &clock,
+        &mut [&mut writer_driver, &mut reader_driver],
+        Duration::from_millis(100),
+      );
+      let samples = data_reader.read(10, ReadCondition::any()).unwrap();
+      if let Some(s) = samples.first() {
+        assert_eq!(s.sample_info().instance_state, InstanceState::Alive);
+        received = true;
+        break;
+      }
+    }
+    assert!(received, "Sample was not received");
+
+    // Network partition: the writer participant goes silent, its lease
+    // expires, and its writer is lost. It keeps writing meanwhile.
+    data_writer.write(sample("during"), None).unwrap();
+    run(&clock, &mut [&mut reader_driver], Duration::from_secs(15));
+    assert!(!reader_participant
+      .discovered_participants()
+      .iter()
+      .any(|p| p.participant_guid.prefix == writer_prefix));
+    let samples = data_reader.read(10, ReadCondition::any()).unwrap();
+    assert_eq!(samples.len(), 1);
+    assert_eq!(
+      samples[0].sample_info().instance_state,
+      InstanceState::NotAliveNoWriters
+    );
+
+    // The partition heals. The writer is rediscovered and its durable history
+    // is evaluated again: only the sample written during the partition is new.
+    let mut samples = Vec::new();
+    for _ in 0..300 {
+      run(
+        &clock,
+        &mut [&mut writer_driver, &mut reader_driver],
+        Duration::from_millis(100),
+      );
+      if data_reader.read(10, ReadCondition::any()).unwrap().len() > 1 {
+        samples = data_reader.take(10, ReadCondition::any()).unwrap();
+        break;
+      }
+    }
+    let values: Vec<String> = samples
+      .iter()
+      .map(|s| s.value().as_ref().unwrap().b.clone())
+      .collect();
+    assert_eq!(values, vec!["before".to_string(), "during".to_string()]);
+    let latest = samples[1].sample_info();
+    assert_eq!(latest.instance_state, InstanceState::Alive);
+    assert_eq!(latest.generation_counts.no_writers_generation_count, 1);
+    assert_eq!(latest.generation_counts.disposed_generation_count, 0);
+  }
 }
@@ -0,0 +1,212 @@
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::{
+  dds::{
+    participant::{DomainParticipant, DomainParticipantBuilder, DomainParticipantWeak},
+    qos::{MutQosPolicy, QosPolicies},
+    values::result::{Error, Result},
+  },
+  structure::entity::RTPSEntity,
+};
+
+static FACTORY: Lazy<DomainParticipantFactory> = Lazy::new(|| DomainParticipantFactory {
+  inner: Mutex::new(FactoryInner {
+    default_participant_qos: QosPolicies::qos_none(),
+    participants: Vec::new(),
+  }),
+});
+
+struct FactoryInner {
+  default_participant_qos: QosPolicies,
+  // The factory does not keep participants alive: a participant is removed
+  // from here, when it is deleted or the application drops it.
+  participants: Vec<(u16, DomainParticipantWeak)>, // (domain_id, participant)
+}
+
+impl FactoryInner {
+  fn live_participants(&mut self) -> Vec<(u16, DomainParticipant)> {
+    let mut live = Vec::new();
+    self
+      .participants
+      .retain(|(domain_id, weak)| match weak.clone().upgrade() {
+        Some(participant) => {
+          live.push((*domain_id, participant));
+          true
+        }
+        None => false,
+      });
+    live
+  }
+
+  fn lookup(&mut self, domain_id: u16) -> Option<DomainParticipant> {
+    self
+      .live_participants()
+      .into_iter()
+      .find(|(d, _)| *d == domain_id)
+      .map(|(_, participant)| participant)
+  }
+
+  fn create(&mut self, builder: DomainParticipantBuilder) -> Result<DomainParticipant> {
+    let mut participant = builder.build()?;
+    participant.set_qos(&self.default_participant_qos)?;
+    let domain_id = participant.domain_id();
+    self
+      .participants
+      .push((domain_id, participant.weak_clone()));
+    info!(
+      "DomainParticipantFactory: created participant {:?} in domain {}",
+      participant.guid(),
+      domain_id
+    );
+    Ok(participant)
+  }
+}
+
+/// Process-wide factory of [`DomainParticipant`]s, as in the DDS
+/// specification (v1.4 Section 2.2.2.2.2 "DomainParticipantFactory Class").
+///
+/// Participants can also be created without the factory, with
+/// [`DomainParticipant::new`] or [`DomainParticipantBuilder`], but those are
+/// not known to the factory.
+///
+/// The factory holds the default QoS for new participants, and keeps track
+/// of the participants it created, so that they can be looked up and reused
+/// by domain id. It does not keep them alive: when the application has
+/// dropped all clones of a participant, it is gone from the factory also.
+///
+/// # Examples
+///
+/// ```
+/// use rustdds::DomainParticipantFactory;
+///
+/// let factory = DomainParticipantFactory::instance();
+/// let participant = factory.get_or_create_participant(0).unwrap();
+/// // Same participant again
+/// let other = factory.lookup_participant(0).unwrap();
+/// assert_eq!(participant, other);
+/// ```
+pub struct DomainParticipantFactory {
+  inner: Mutex<FactoryInner>,
+}
+
+impl DomainParticipantFactory {
+  /// The factory instance of this process.
+  pub fn instance() -> &'static Self {
+    &FACTORY
+  }
+
+  /// Creates a new participant in a domain, with the default participant
+  /// QoS. This creates a new participant even if the factory already has one
+  /// in the same domain.
+  pub fn create_participant(&self, domain_id: u16) -> Result<DomainParticipant> {
+    self.create_participant_with(DomainParticipantBuilder::new(domain_id))
+  }
+
+  /// Like [`create_participant`](Self::create_participant), but with the
+  /// configuration in `builder`.
+  pub fn create_participant_with(
+    &self,
+    builder: DomainParticipantBuilder,
+  ) -> Result<DomainParticipant> {
+    self.inner.lock().unwrap().create(builder)
+  }
+
+  /// Returns a participant in the domain created by this factory, or creates
+  /// one, if there is none.
+  pub fn get_or_create_participant(&self, domain_id: u16) -> Result<DomainParticipant> {
+    let mut inner = self.inner.lock().unwrap();
+    match inner.lookup(domain_id) {
+      Some(participant) => Ok(participant),
+      None => inner.create(DomainParticipantBuilder::new(domain_id)),
+    }
+  }
+
+  /// Finds a participant in the domain created by this factory. If there are
+  /// several, any one of them is returned.
+  pub fn lookup_participant(&self, domain_id: u16) -> Option<DomainParticipant> {
+    self.inner.lock().unwrap().lookup(domain_id)
+  }
+
+  /// All live participants created by this factory.
+  pub fn participants(&self) -> Vec<DomainParticipant> {
+    self
+      .inner
+      .lock()
+      .unwrap()
+      .live_participants()
+      .into_iter()
+      .map(|(_, participant)| participant)
+      .collect()
+  }
+
+  /// Closes a participant created by this factory, see
+  /// [`DomainParticipant::close`], and removes it from the factory.
+  pub fn delete_participant(&self, participant: &DomainParticipant) -> Result<()> {
+    {
+      let mut inner = self.inner.lock().unwrap();
+      let count = inner.participants.len();
+      inner
+        .participants
+        .retain(|(_, weak)| weak.clone().upgrade().as_ref() != Some(participant));
+      if inner.participants.len() == count {
+        return Error::precondition_not_met("Participant was not created by this factory");
+      }
+    }
+    participant.close()
+  }
+
+  /// QoS given to new participants.
+  pub fn default_participant_qos(&self) -> QosPolicies {
+    self.inner.lock().unwrap().default_participant_qos.clone()
+  }
+
+  /// Sets the QoS given to new participants. Existing participants are not
+  /// affected.
+  pub fn set_default_participant_qos(&self, qos: &QosPolicies) {
+    self.inner.lock().unwrap().default_participant_qos = qos.clone();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dds::qos::{policy, HasQoSPolicy, QosPolicyBuilder};
+
+  #[test]
+  fn participant_factory() {
+    // Domain ids not used by other tests, because the factory is shared.
+    let factory = DomainParticipantFactory::instance();
+    assert!(factory.lookup_participant(41).is_none());
+
+    let participant = factory.get_or_create_participant(41).unwrap();
+    assert_eq!(factory.get_or_create_participant(41).unwrap(), participant);
+    assert_eq!(factory.lookup_participant(41).unwrap(), participant);
+    assert!(factory.lookup_participant(42).is_none());
+
+    let qos = QosPolicyBuilder::new()
+      .user_data(policy::UserData {
+        value: b"factory".to_vec(),
+      })
+      .build();
+    factory.set_default_participant_qos(&qos);
+    assert_eq!(factory.default_participant_qos(), qos);
+    let other = factory.create_participant(42).unwrap();
+    assert_ne!(other, participant);
+    assert_eq!(other.qos(), qos);
+    factory.set_default_participant_qos(&QosPolicies::qos_none());
+
+    // Dropped participants are forgotten.
+    drop(other);
+    assert!(factory.lookup_participant(42).is_none());
+
+    factory.delete_participant(&participant).unwrap();
+    assert!(factory.lookup_participant(41).is_none());
+    assert!(factory.delete_participant(&participant).is_err());
+    let unknown = DomainParticipant::new(42).unwrap();
+    assert!(factory.delete_participant(&unknown).is_err());
+  }
+}
@@ -0,0 +1,84 @@
+// Tracing spans of the sample pipeline, reported to the `tracing` facade if
+// the "tracing" feature is enabled:
+//
+// write -> serialize -> send, in the DataWriter and the event loop thread, and
+// receive -> parse -> cache -> notify, in the event loop thread.
+//
+// Spans carry the topic name and the GUIDs involved, so that a subscriber can
+// time each stage of a sample. Without the feature, entering a span does
+// nothing, except for spans that replaced a log line: those write the line.
+
+#![cfg_attr(not(feature = "tracing"), allow(unused_variables, dead_code))]
+
+use std::net::IpAddr;
+
+#[cfg(not(feature = "tracing"))]
+use log::trace;
+
+use crate::structure::{guid::GUID, sequence_number::SequenceNumber};
+
+// Exits the span when dropped.
+pub(crate) struct Entered {
+  #[cfg(feature = "tracing")]
+  _span: tracing::span::EnteredSpan,
+}
+
+macro_rules! enter {
+  ($($span:tt)*) => {
+    Entered {
+      #[cfg(feature = "tracing")]
+      _span: tracing::trace_span!($($span)*).entered(),
+    }
+  };
+}
+
+// DataWriter write call, until the sample is handed over to the event loop.
+pub(crate) fn write(topic_name: &str, writer: GUID) -> Entered {
+  enter!("write", topic = topic_name, writer = ?writer)
+}
+
+pub(crate) fn serialize() -> Entered {
+  enter!("serialize")
+}
+
+// Encoding and sending one RTPS message from a local Writer.
+pub(crate) fn send(topic_name: &str, writer: GUID) -> Entered {
+  enter!("send", topic = topic_name, writer = ?writer)
+}
+
+pub(crate) fn receive(bytes: usize, source: Option<IpAddr>) -> Entered {
+  enter!("receive", bytes, source = ?source)
+}
+
+pub(crate) fn parse() -> Entered {
+  enter!("parse")
+}
+
+// Adding a received sample to the cache of a local Reader.
+pub(crate) fn cache(
+  topic_name: &str,
+  reader: GUID,
+  writer: GUID,
+  sequence_number: SequenceNumber,
+) -> Entered {
+  #[cfg(not(feature = "tracing"))]
+  trace!(
+    "cache sample from {:?} seq={:?} topic={:?} reader={:?}",
+    writer,
+    sequence_number,
+    topic_name,
+    reader,
+  );
+  enter!(
+    "cache",
+    topic = topic_name,
+    reader = ?reader,
+    writer = ?writer,
+    sequence_number = ?sequence_number
+  )
+}
+
+// Waking up the DataReader of a local Reader.
+pub(crate) fn notify(topic_name: &str, reader: GUID) -> Entered {
+  enter!("notify", topic = topic_name, reader = ?reader)
+}
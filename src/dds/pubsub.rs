@@ -1,9 +1,11 @@
 use std::{
+  collections::BTreeMap,
   fmt::Debug,
-  sync::{Arc, Mutex, MutexGuard, RwLock},
+  sync::{atomic::AtomicI64, Arc, Mutex, MutexGuard, RwLock, Weak},
   time::Duration,
 };
 
+use mio::Registration;
 use mio_extras::channel as mio_channel;
 use serde::{de::DeserializeOwned, Serialize};
 use byteorder::LittleEndian;
@@ -12,7 +14,12 @@ use log::{debug, error, info, trace, warn};
 
 use crate::{
   dds::{
+    contentfilteredtopic::ContentFilteredTopic,
     data_types::EntityKind,
+    dynamic_data::DynamicType,
+    flow_controller::FlowController,
+    helpers::{EntityLifecycle, WakerSlot},
+    multitopic::{MultiTopic, MultiTopicDataReader},
     no_key::{
       datareader::DataReader as NoKeyDataReader, datawriter::DataWriter as NoKeyDataWriter,
     },
@@ -36,18 +43,22 @@ use crate::{
     discovery_db::DiscoveryDB,
   },
   log_and_err_internal, log_and_err_precondition_not_met,
+  messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
   serialization::{cdr_deserializer::CDRDeserializerAdapter, cdr_serializer::CDRSerializerAdapter},
   structure::{
     entity::RTPSEntity,
     guid::{EntityId, GUID},
+    time::Timestamp,
     topic_kind::TopicKind,
   },
 };
 use super::{
   no_key::wrappers::{DAWrapper, NoKeyWrapper, SAWrapper},
-  with_key::datareader::ReaderCommand,
+  with_key::datareader::{ContentFilter, ReaderCommand},
   writer::WriterCommand,
 };
+#[cfg(feature = "security")]
+use crate::security::access_control::TopicAccess;
 
 // -------------------------------------------------------------------
 
@@ -85,6 +96,7 @@ pub struct Publisher {
 }
 
 impl Publisher {
+  #[allow(clippy::too_many_arguments)]
   pub(super) fn new(
     dp: DomainParticipantWeak,
     discovery_db: Arc<RwLock<DiscoveryDB>>,
@@ -93,6 +105,7 @@ impl Publisher {
     add_writer_sender: mio_channel::SyncSender<WriterIngredients>,
     remove_writer_sender: mio_channel::SyncSender<GUID>,
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
+    enabled: bool,
   ) -> Self {
     Self {
       inner: Arc::new(Mutex::new(InnerPublisher::new(
@@ -103,6 +116,7 @@ impl Publisher {
         add_writer_sender,
         remove_writer_sender,
         discovery_command,
+        enabled,
       ))),
     }
   }
@@ -161,6 +175,7 @@ impl Publisher {
     <D as Keyed>::K: Key,
     SA: with_key::SerializerAdapter<D>,
   {
+    self.check_registered_type::<D>(topic, TopicKind::WithKey, SA::output_encoding())?;
     self.inner_lock().create_datawriter(self, None, topic, qos)
   }
 
@@ -249,6 +264,7 @@ impl Publisher {
     D: Serialize,
     SA: no_key::SerializerAdapter<D>,
   {
+    self.check_registered_type::<D>(topic, TopicKind::NoKey, SA::output_encoding())?;
     self
       .inner_lock()
       .create_datawriter_no_key(self, None, topic, qos)
@@ -266,22 +282,22 @@ impl Publisher {
   }
 
   // Versions with callee-specified EntityId. These are for Discovery use only.
-  // ... except that Discovery has no use for no_key versions.
+  // Only the TypeLookup service uses no_key versions.
 
-  // pub(crate) fn create_datawriter_no_key_with_entityid<D, SA>(
-  //   &self,
-  //   entity_id: EntityId,
-  //   topic: &Topic,
-  //   qos: Option<QosPolicies>,
-  // ) -> Result<NoKeyDataWriter<D, SA>>
-  // where
-  //   D: Serialize,
-  //   SA: no_key::SerializerAdapter<D>,
-  // {
-  //   self
-  //     .inner_lock()
-  //     .create_datawriter_no_key(self, Some(entity_id), topic, qos)
-  // }
+  pub(crate) fn create_datawriter_no_key_with_entityid<D, SA>(
+    &self,
+    entity_id: EntityId,
+    topic: &Topic,
+    qos: Option<QosPolicies>,
+  ) -> Result<NoKeyDataWriter<D, SA>>
+  where
+    D: Serialize,
+    SA: no_key::SerializerAdapter<D>,
+  {
+    self
+      .inner_lock()
+      .create_datawriter_no_key(self, Some(entity_id), topic, qos)
+  }
 
   // pub(crate) fn create_datawriter_no_key_cdr_with_entityid<D>(
   //   &self,
@@ -304,16 +320,27 @@ impl Publisher {
   // has created.
 
   // Suspend and resume publications are preformance optimization methods.
-  // The minimal correct implementation is to do nothing. See DDS spec 2.2.2.4.1.8
-  // and .9
-  /// Currently does nothing
+  // See DDS spec 2.2.2.4.1.8 and .9
+  /// Holds back samples written by the DataWriters of this Publisher, until
+  /// [`resume_publications`](Self::resume_publications). Then they are
+  /// handed to the RTPS Writers all at once, in the order they were written.
+  ///
+  /// Calls may be nested: publications are resumed by the `resume` call
+  /// matching the first `suspend`. While suspended,
+  /// [`DataWriter::wait_for_acknowledgments`](crate::with_key::DataWriter::wait_for_acknowledgments)
+  /// does not wait for the held samples.
   pub fn suspend_publications(&self) -> Result<()> {
-    self.inner_lock().suspend_publications()
+    self.inner_lock().suspended_writes.suspend();
+    Ok(())
   }
 
-  /// Currently does nothing
+  /// Ends a suspension started with
+  /// [`suspend_publications`](Self::suspend_publications). It is an error to
+  /// call this when publications are not suspended.
   pub fn resume_publications(&self) -> Result<()> {
-    self.inner_lock().resume_publications()
+    // Sending may block, so do not hold the Publisher lock.
+    let suspended_writes = self.inner_lock().suspended_writes.clone();
+    suspended_writes.resume()
   }
 
   // coherent change set
@@ -358,8 +385,59 @@ impl Publisher {
     self.inner_lock().domain_participant.clone().upgrade()
   }
 
-  // delete_contained_entities: We should not need this. Contained DataWriters
-  // should dispose themselves and notify publisher.
+  // A DataWriter of a registered type must be able to write for the
+  // registered DataReaders, see TypeRegistry.
+  fn check_registered_type<D: ?Sized>(
+    &self,
+    topic: &Topic,
+    topic_kind: TopicKind,
+    output_encoding: RepresentationIdentifier,
+  ) -> Result<()> {
+    match self.participant() {
+      Some(dp) => dp
+        .type_registry()
+        .check_writer::<D>(topic, topic_kind, output_encoding),
+      None => Ok(()),
+    }
+  }
+
+  /// Enables this Publisher, if it was created disabled due to the
+  /// [`EntityFactory`](policy::EntityFactory) policy of its
+  /// DomainParticipant. If the EntityFactory policy of this Publisher allows,
+  /// this enables also the DataWriters created so far.
+  ///
+  /// DataWriters created from a disabled Publisher are disabled, and cannot be
+  /// enabled before the Publisher.
+  pub fn enable(&self) -> Result<()> {
+    self.inner_lock().enable()
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.inner_lock().lifecycle.is_enabled()
+  }
+
+  /// Deletes all DataWriters created from this Publisher. They stop
+  /// communicating, and are no longer announced to remote participants.
+  /// Operations on the deleted DataWriters fail with
+  /// [`AlreadyDeleted`](Error::AlreadyDeleted).
+  pub fn delete_contained_entities(&self) -> Result<()> {
+    self.inner_lock().delete_contained_entities()
+  }
+
+  // Deletes the DataWriters and this Publisher itself. Used by
+  // DomainParticipant::delete_contained_entities.
+  pub(crate) fn delete(&self) -> Result<()> {
+    let mut inner = self.inner_lock();
+    inner.delete_contained_entities()?;
+    inner.lifecycle.delete();
+    Ok(())
+  }
+
+  pub(crate) fn downgrade(&self) -> PublisherWeak {
+    PublisherWeak {
+      inner: Arc::downgrade(&self.inner),
+    }
+  }
 
   /// Returns default DataWriter qos.
   ///
@@ -404,12 +482,45 @@ impl Publisher {
     self.inner_lock().set_default_datawriter_qos(q);
   }
 
+  /// Limits the send rate of the DataWriters that are created from this
+  /// Publisher afterwards. They share the given [`FlowController`], so the
+  /// rate applies to them together. DataWriters that exist already are not
+  /// affected.
+  ///
+  /// `None` removes the limit from DataWriters created afterwards.
+  pub fn set_flow_controller(&self, flow_controller: Option<FlowController>) {
+    self.inner_lock().flow_controller = flow_controller;
+  }
+
   // This is used on DataWriter .drop()
   pub(crate) fn remove_writer(&self, guid: GUID) {
     self.inner_lock().remove_writer(guid);
   }
+
+  pub(crate) fn discovery_db(&self) -> Arc<RwLock<DiscoveryDB>> {
+    self.inner_lock().discovery_db.clone()
+  }
 } // impl
 
+impl HasQoSPolicy for Publisher {
+  fn qos(&self) -> QosPolicies {
+    self.inner_lock().my_qos_policies.clone()
+  }
+}
+
+impl MutQosPolicy for Publisher {
+  /// Changes the QoS of this Publisher.
+  ///
+  /// Policies defined in `new_qos` replace the current ones, and the rest are
+  /// kept. Presentation cannot be changed after creation. Existing DataWriters
+  /// are not affected.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<()> {
+    let mut inner = self.inner_lock();
+    inner.my_qos_policies = inner.my_qos_policies.changed_by(new_qos)?;
+    Ok(())
+  }
+}
+
 impl PartialEq for Publisher {
   fn eq(&self, other: &Self) -> bool {
     let id_self = { self.inner_lock().identity() };
@@ -424,6 +535,19 @@ impl Debug for Publisher {
   }
 }
 
+// The DomainParticipant keeps track of its Publishers without keeping them
+// alive.
+#[derive(Clone)]
+pub(crate) struct PublisherWeak {
+  inner: Weak<Mutex<InnerPublisher>>,
+}
+
+impl PublisherWeak {
+  pub fn upgrade(&self) -> Option<Publisher> {
+    self.inner.upgrade().map(|inner| Publisher { inner })
+  }
+}
+
 // "Inner" struct
 
 #[derive(Clone)]
@@ -436,10 +560,81 @@ struct InnerPublisher {
   add_writer_sender: mio_channel::SyncSender<WriterIngredients>,
   remove_writer_sender: mio_channel::SyncSender<GUID>,
   discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
+  lifecycle: EntityLifecycle,
+  // User-defined DataWriters created from this Publisher and not dropped yet
+  writers: BTreeMap<GUID, EntityLifecycle>,
+  // Given to new user-defined DataWriters
+  flow_controller: Option<FlowController>,
+  // Shared with the user-defined DataWriters
+  suspended_writes: SuspendedWrites,
+}
+
+// Writes of the DataWriters of a Publisher, held back while publications are
+// suspended.
+#[derive(Clone, Default)]
+pub(crate) struct SuspendedWrites {
+  inner: Arc<Mutex<SuspendedWritesInner>>,
+}
+
+#[derive(Default)]
+struct SuspendedWritesInner {
+  depth: usize, // suspend calls not resumed yet
+  held: Vec<(mio_channel::SyncSender<WriterCommand>, WriterCommand)>,
+}
+
+impl SuspendedWrites {
+  fn lock(&self) -> MutexGuard<'_, SuspendedWritesInner> {
+    self
+      .inner
+      .lock()
+      .unwrap_or_else(|e| panic!("Suspended writes lock fail! {:?}", e))
+  }
+
+  fn suspend(&self) {
+    self.lock().depth += 1;
+  }
+
+  fn resume(&self) -> Result<()> {
+    // The lock is held while sending, so that new writes cannot overtake the
+    // held ones.
+    let mut inner = self.lock();
+    match inner.depth {
+      0 => return Error::precondition_not_met("Publications are not suspended"),
+      1 => (),
+      _ => {
+        inner.depth -= 1;
+        return Ok(());
+      }
+    }
+    inner.depth = 0;
+    for (cc_upload, command) in inner.held.drain(..) {
+      if cc_upload.send(command).is_err() {
+        debug!("resume_publications: DataWriter is gone, dropping its held write");
+      }
+    }
+    Ok(())
+  }
+
+  // Holds `command` for sending on resume, if publications are suspended.
+  // Otherwise, returns it to be sent now.
+  pub(crate) fn try_hold(
+    &self,
+    cc_upload: &mio_channel::SyncSender<WriterCommand>,
+    command: WriterCommand,
+  ) -> Option<WriterCommand> {
+    let mut inner = self.lock();
+    if inner.depth == 0 {
+      Some(command)
+    } else {
+      inner.held.push((cc_upload.clone(), command));
+      None
+    }
+  }
 }
 
 // public interface for Publisher
 impl InnerPublisher {
+  #[allow(clippy::too_many_arguments)]
   fn new(
     dp: DomainParticipantWeak,
     discovery_db: Arc<RwLock<DiscoveryDB>>,
@@ -448,12 +643,19 @@ impl InnerPublisher {
     add_writer_sender: mio_channel::SyncSender<WriterIngredients>,
     remove_writer_sender: mio_channel::SyncSender<GUID>,
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
+    enabled: bool,
   ) -> Self {
     // We generate an arbitrary but unique id to distiguish Publishers from each
     // other. EntityKind is just some value, since we do not show it to anyone.
     let id = EntityId::MAX;
     //dp.clone().upgrade().unwrap().new_entity_id(EntityKind::UNKNOWN_BUILT_IN);
 
+    let lifecycle = if enabled {
+      EntityLifecycle::enabled()
+    } else {
+      EntityLifecycle::disabled(qos.clone(), |_| Ok(()))
+    };
+
     Self {
       id,
       domain_participant: dp,
@@ -463,11 +665,15 @@ impl InnerPublisher {
       add_writer_sender,
       remove_writer_sender,
       discovery_command,
+      lifecycle,
+      writers: BTreeMap::new(),
+      flow_controller: None,
+      suspended_writes: SuspendedWrites::default(),
     }
   }
 
   pub fn create_datawriter<D, SA>(
-    &self,
+    &mut self,
     outer: &Publisher,
     entity_id_opt: Option<EntityId>,
     topic: &Topic,
@@ -478,6 +684,10 @@ impl InnerPublisher {
     <D as Keyed>::K: Key,
     SA: with_key::SerializerAdapter<D>,
   {
+    if self.lifecycle.is_deleted() {
+      return Err(Error::AlreadyDeleted);
+    }
+
     // Data samples from DataWriter to HistoryCache
     let (dwcc_upload, hccc_download) = mio_channel::sync_channel::<WriterCommand>(16);
 
@@ -502,43 +712,106 @@ impl InnerPublisher {
       .participant()
       .ok_or("upgrade fail")
       .or_else(|e| log_and_err_internal!("Where is my DomainParticipant? {}", e))?;
+    // Builtin writers are not subject to access control.
+    #[cfg(feature = "security")]
+    if !entity_id.kind().is_built_in() {
+      dp.check_topic_access(&topic.name(), TopicAccess::Publish)?;
+    }
 
     let guid = GUID::new_with_prefix_and_id(dp.guid().prefix, entity_id);
 
+    // No readers yet, so nothing is waiting for acknowledgments.
+    let acked_by_all_before = Arc::new(AtomicI64::new(i64::MAX));
+    let (ack_registration, ack_readiness) = Registration::new2();
+    let ack_waker = WakerSlot::default();
+    let reader_filters = Arc::new(RwLock::new(BTreeMap::new()));
+
     let new_writer = WriterIngredients {
       guid,
       writer_command_receiver: hccc_download,
       topic_name: topic.name(),
-      qos_policies: writer_qos,
+      qos_policies: writer_qos.clone(),
       status_sender,
+      acked_by_all_before: acked_by_all_before.clone(),
+      ack_readiness: ack_readiness.clone(),
+      ack_waker: ack_waker.clone(),
+      reader_filters: reader_filters.clone(),
+      dynamic_type: registered_dynamic_type(Some(dp.clone()), topic),
+      // Discovery must not be slowed down by user traffic.
+      flow_controller: if entity_id.kind().is_built_in() {
+        None
+      } else {
+        self.flow_controller.clone()
+      },
     };
 
-    self
-      .add_writer_sender
-      .send(new_writer)
-      .or_else(|e| log_and_err_internal!("Adding a new writer failed: {}", e))?;
+    // Enabling adds the RTPS Writer to the event loop, and announces the
+    // DataWriter, with the QoS it has by then.
+    let add_writer_sender = self.add_writer_sender.clone();
+    let discovery_db = self.discovery_db.clone();
+    let dp_weak = dp.weak_clone();
+    let writer_topic = topic.clone();
+    let lifecycle = EntityLifecycle::disabled(writer_qos.clone(), move |qos| {
+      let dp = dp_weak
+        .upgrade()
+        .ok_or("upgrade fail")
+        .or_else(|e| log_and_err_internal!("Where is my DomainParticipant? {}", e))?;
+      add_writer_sender
+        .send(WriterIngredients {
+          qos_policies: qos.clone(),
+          ..new_writer
+        })
+        .or_else(|e| log_and_err_internal!("Adding a new writer failed: {}", e))?;
+
+      // notify Discovery DB
+      let mut db = discovery_db.write()?;
+      let dwd = DiscoveredWriterData::from_local_writer(guid, &qos, &writer_topic, &dp);
+      db.update_local_topic_writer(dwd);
+      db.update_topic_data_p(&writer_topic);
+      Ok(())
+    });
+
+    // Built-in DataWriters, i.e. those of Discovery, are always enabled, and
+    // are not deleted with the user-defined ones.
+    let built_in = entity_id.kind().is_built_in();
+    if built_in
+      || (self.lifecycle.is_enabled() && self.my_qos_policies.autoenable_created_entities())
+    {
+      lifecycle.enable()?;
+    }
 
     let data_writer = WithKeyDataWriter::<D, SA>::new(
       outer.clone(),
       topic.clone(),
+      writer_qos.clone(),
       guid,
       dwcc_upload,
       self.discovery_command.clone(),
       &dp.dds_cache(),
       status_receiver,
+      acked_by_all_before,
+      ack_registration,
+      ack_readiness,
+      ack_waker,
+      reader_filters,
+      lifecycle.clone(),
+      // Discovery keeps publishing, whatever the user does.
+      if built_in {
+        SuspendedWrites::default()
+      } else {
+        self.suspended_writes.clone()
+      },
+      dp.time_source(),
     )?;
-
-    // notify Discovery DB
-    let mut db = self.discovery_db.write()?;
-    let dwd = DiscoveredWriterData::new(&data_writer, topic, &dp);
-    db.update_local_topic_writer(dwd);
-    db.update_topic_data_p(topic);
+    if !built_in {
+      self.writers.insert(guid, lifecycle);
+    }
 
     Ok(data_writer)
   }
 
   pub fn create_datawriter_no_key<D, SA>(
-    &self,
+    &mut self,
     outer: &Publisher,
     entity_id_opt: Option<EntityId>,
     topic: &Topic,
@@ -559,14 +832,6 @@ impl InnerPublisher {
     Ok(NoKeyDataWriter::<D, SA>::from_keyed(d))
   }
 
-  pub fn suspend_publications(&self) -> Result<()> {
-    Ok(())
-  }
-
-  pub fn resume_publications(&self) -> Result<()> {
-    Ok(())
-  }
-
   pub fn begin_coherent_changes(&self) -> Result<()> {
     Ok(())
   }
@@ -601,7 +866,34 @@ impl InnerPublisher {
     entity_id_opt.unwrap_or_else(|| self.participant().unwrap().new_entity_id(entity_kind))
   }
 
-  pub(crate) fn remove_writer(&self, guid: GUID) {
+  fn enable(&self) -> Result<()> {
+    if self.lifecycle.is_enabled() {
+      return Ok(());
+    }
+    self.lifecycle.enable()?;
+    if self.my_qos_policies.autoenable_created_entities() {
+      for writer in self.writers.values() {
+        writer.enable()?;
+      }
+    }
+    Ok(())
+  }
+
+  fn delete_contained_entities(&mut self) -> Result<()> {
+    for (guid, writer) in std::mem::take(&mut self.writers) {
+      if writer.delete() {
+        self.remove_writer(guid);
+        self
+          .discovery_command
+          .send(DiscoveryCommand::RemoveLocalWriter { guid })
+          .or_else(|e| log_and_err_internal!("Cannot send RemoveLocalWriter: {:?}", e))?;
+      }
+    }
+    Ok(())
+  }
+
+  pub(crate) fn remove_writer(&mut self, guid: GUID) {
+    self.writers.remove(&guid);
     self
       .remove_writer_sender
       .try_send(guid)
@@ -664,6 +956,7 @@ impl Subscriber {
     sender_add_reader: mio_channel::SyncSender<ReaderIngredients>,
     sender_remove_reader: mio_channel::SyncSender<GUID>,
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
+    enabled: bool,
   ) -> Self {
     Self {
       inner: Arc::new(InnerSubscriber::new(
@@ -673,6 +966,7 @@ impl Subscriber {
         sender_add_reader,
         sender_remove_reader,
         discovery_command,
+        enabled,
       )),
     }
   }
@@ -727,6 +1021,7 @@ impl Subscriber {
     <D as Keyed>::K: Key,
     SA: with_key::DeserializerAdapter<D>,
   {
+    self.check_registered_type::<D>(topic, TopicKind::WithKey, SA::supported_encodings())?;
     self.inner.create_datareader(self, topic, None, qos)
   }
 
@@ -742,6 +1037,94 @@ impl Subscriber {
     self.create_datareader::<D, CDRDeserializerAdapter<D>>(topic, qos)
   }
 
+  /// Creates a DataReader for a [`ContentFilteredTopic`] of a keyed Topic.
+  /// The DataReader receives only the samples that match the filter.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::{DomainParticipant, Keyed, QosPolicyBuilder, TopicKind};
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  ///
+  /// #[derive(Serialize, Deserialize)]
+  /// struct SomeType { a: i32 }
+  /// impl Keyed for SomeType {
+  ///   type K = i32;
+  ///
+  ///   fn key(&self) -> Self::K {
+  ///     self.a
+  ///   }
+  /// }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey).unwrap();
+  /// let positive = domain_participant
+  ///   .create_contentfilteredtopic("positive".to_string(), &topic, "a > 0", vec![])
+  ///   .unwrap();
+  /// let data_reader = subscriber
+  ///   .create_filtered_datareader::<SomeType, CDRDeserializerAdapter<_>>(&positive, None);
+  /// ```
+  pub fn create_filtered_datareader<D, SA>(
+    &self,
+    topic: &ContentFilteredTopic,
+    qos: Option<QosPolicies>,
+  ) -> Result<WithKeyDataReader<D, SA>>
+  where
+    D: DeserializeOwned + Keyed + Serialize + 'static,
+    <D as Keyed>::K: Key,
+    SA: with_key::DeserializerAdapter<D>,
+  {
+    self.check_registered_type::<D>(
+      topic.related_topic(),
+      TopicKind::WithKey,
+      SA::supported_encodings(),
+    )?;
+    self.inner.create_filtered_datareader(self, topic, qos)
+  }
+
+  /// Creates a DataReader for a [`ContentFilteredTopic`] of a NoKey Topic.
+  /// See [`create_filtered_datareader`](Self::create_filtered_datareader).
+  pub fn create_filtered_datareader_no_key<D, SA>(
+    &self,
+    topic: &ContentFilteredTopic,
+    qos: Option<QosPolicies>,
+  ) -> Result<NoKeyDataReader<D, SA>>
+  where
+    D: DeserializeOwned + Serialize + 'static,
+    SA: no_key::DeserializerAdapter<D>,
+  {
+    self.check_registered_type::<D>(
+      topic.related_topic(),
+      TopicKind::NoKey,
+      SA::supported_encodings(),
+    )?;
+    self
+      .inner
+      .create_filtered_datareader_no_key(self, topic, qos)
+  }
+
+  /// Creates a reader of the combined samples of a [`MultiTopic`]. See
+  /// [`MultiTopicDataReader`] on how to add the Topics to read.
+  ///
+  /// The QoS policies apply to the DataReaders of each of the Topics.
+  pub fn create_multitopic_datareader<D>(
+    &self,
+    multitopic: &MultiTopic,
+    qos: Option<QosPolicies>,
+  ) -> Result<MultiTopicDataReader<D>>
+  where
+    D: DeserializeOwned,
+  {
+    if multitopic.participant() != self.participant() {
+      return Error::precondition_not_met("MultiTopic belongs to another DomainParticipant");
+    }
+    Ok(MultiTopicDataReader::new(self, multitopic, qos))
+  }
+
   // versions with callee-specified EntityId. These are for Discovery use only.
 
   pub(crate) fn create_datareader_with_entityid<D: 'static, SA>(
@@ -773,6 +1156,21 @@ impl Subscriber {
     self.create_datareader_with_entityid::<D, CDRDeserializerAdapter<D>>(topic, entity_id, qos)
   }
 
+  // DataReader for a builtin (discovery) topic. It reads what Discovery has
+  // already received into the DDSCache, so it has no RTPS Reader of its own and
+  // is not announced to remote participants.
+  pub(crate) fn create_builtin_datareader<D, SA>(
+    &self,
+    topic: &Topic,
+  ) -> Result<WithKeyDataReader<D, SA>>
+  where
+    D: DeserializeOwned + Keyed + 'static,
+    <D as Keyed>::K: Key,
+    SA: with_key::DeserializerAdapter<D>,
+  {
+    self.inner.create_builtin_datareader(self, topic)
+  }
+
   /// Create DDS DataReader for non keyed Topics
   ///
   /// # Arguments
@@ -814,6 +1212,7 @@ impl Subscriber {
     D: DeserializeOwned,
     SA: no_key::DeserializerAdapter<D>,
   {
+    self.check_registered_type::<D>(topic, TopicKind::NoKey, SA::supported_encodings())?;
     self.inner.create_datareader_no_key(self, topic, None, qos)
   }
 
@@ -828,22 +1227,21 @@ impl Subscriber {
     self.create_datareader_no_key::<D, CDRDeserializerAdapter<D>>(topic, qos)
   }
 
-  // Exists for symmetry, but not really neeeded,
-  // as the only user is Discovery.
-  // pub(crate) fn create_datareader_no_key_with_entityid<D: 'static, SA>(
-  //   &self,
-  //   topic: &Topic,
-  //   entity_id: EntityId,
-  //   qos: Option<QosPolicies>,
-  // ) -> Result<NoKeyDataReader<D, SA>>
-  // where
-  //   D: DeserializeOwned,
-  //   SA: no_key::DeserializerAdapter<D>,
-  // {
-  //   self
-  //     .inner
-  //     .create_datareader_no_key(self, topic, Some(entity_id), qos)
-  // }
+  // Used by the TypeLookup service in Discovery.
+  pub(crate) fn create_datareader_no_key_with_entityid<D, SA>(
+    &self,
+    topic: &Topic,
+    entity_id: EntityId,
+    qos: Option<QosPolicies>,
+  ) -> Result<NoKeyDataReader<D, SA>>
+  where
+    D: DeserializeOwned + 'static,
+    SA: no_key::DeserializerAdapter<D>,
+  {
+    self
+      .inner
+      .create_datareader_no_key(self, topic, Some(entity_id), qos)
+  }
 
   // Exists for symmetry, but not really neeeded,
   // as the only user is Discovery.
@@ -898,19 +1296,260 @@ impl Subscriber {
     self.inner.participant()
   }
 
+  // A DataReader of a registered type must be able to read the registered
+  // DataWriters, see TypeRegistry.
+  fn check_registered_type<D: ?Sized>(
+    &self,
+    topic: &Topic,
+    topic_kind: TopicKind,
+    supported_encodings: &[RepresentationIdentifier],
+  ) -> Result<()> {
+    match self.participant() {
+      Some(dp) => dp
+        .type_registry()
+        .check_reader::<D>(topic, topic_kind, supported_encodings),
+      None => Ok(()),
+    }
+  }
+
+  /// Enables this Subscriber, if it was created disabled. See
+  /// [`Publisher::enable`].
+  pub fn enable(&self) -> Result<()> {
+    self.inner.enable()
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.inner.lifecycle.is_enabled()
+  }
+
+  /// Deletes all DataReaders created from this Subscriber. See
+  /// [`Publisher::delete_contained_entities`].
+  pub fn delete_contained_entities(&self) -> Result<()> {
+    self.inner.delete_contained_entities()
+  }
+
+  pub(crate) fn delete(&self) -> Result<()> {
+    self.inner.delete_contained_entities()?;
+    self.inner.lifecycle.delete();
+    Ok(())
+  }
+
+  /// Begins accessing the DataReaders of this Subscriber as a group. See DDS
+  /// spec 2.2.2.5.2.8.
+  ///
+  /// If the PRESENTATION QoS policy of this Subscriber has access scope
+  /// GROUP, then until [`end_access`](Self::end_access) its DataReaders show
+  /// only samples that were received before the access began. This way the
+  /// application sees a consistent state of all the DataReaders. With other
+  /// access scopes, the access has no effect.
+  ///
+  /// Calls may be nested: the access ends with the `end_access` matching the
+  /// first `begin_access`.
+  pub fn begin_access(&self) -> Result<()> {
+    let mut access = self.inner.access_lock();
+    match &mut *access {
+      Some((depth, _)) => *depth += 1,
+      None => *access = Some((1, Timestamp::now())),
+    }
+    Ok(())
+  }
+
+  /// Ends an access started with [`begin_access`](Self::begin_access). It is
+  /// an error to call this when there is no access going on.
+  pub fn end_access(&self) -> Result<()> {
+    let mut access = self.inner.access_lock();
+    match &mut *access {
+      None => Error::precondition_not_met("end_access without begin_access"),
+      Some((1, _)) => {
+        *access = None;
+        Ok(())
+      }
+      Some((depth, _)) => {
+        *depth -= 1;
+        Ok(())
+      }
+    }
+  }
+
+  /// Lists the given DataReaders of this Subscriber that have NOT_READ
+  /// samples. See DDS spec 2.2.2.5.2.10.
+  ///
+  /// If the PRESENTATION QoS policy of this Subscriber has access scope
+  /// GROUP and `ordered_access`, the list has an entry for each NOT_READ
+  /// sample, and a DataReader may be listed many times. The samples are
+  /// listed in the order they were received, which is the order they were
+  /// published for samples from the same remote Publisher, apart from
+  /// retransmitted ones. Taking one sample with `take_next_sample` from each
+  /// listed DataReader in turn processes the samples in that order across
+  /// all the DataReaders. This must be done within
+  /// [`begin_access`](Self::begin_access) and
+  /// [`end_access`](Self::end_access), so that newly received samples do not
+  /// change the order, and fails with
+  /// [`PreconditionNotMet`](Error::PreconditionNotMet) otherwise.
+  ///
+  /// Otherwise each DataReader with NOT_READ samples is listed once.
+  ///
+  /// Fails with [`BadParameter`](Error::BadParameter) if a DataReader does
+  /// not belong to this Subscriber.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Deserialize, Serialize};
+  /// # use rustdds::*;
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct Temperature(f32);
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct Humidity(f32);
+  /// let participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new()
+  ///   .presentation(policy::Presentation {
+  ///     access_scope: policy::PresentationAccessScope::Group,
+  ///     coherent_access: false,
+  ///     ordered_access: true,
+  ///   })
+  ///   .build();
+  /// let subscriber = participant.create_subscriber(&qos).unwrap();
+  /// # let temperature_topic = participant
+  /// #   .create_topic("temperature".to_string(), "Temperature".to_string(), &qos, TopicKind::NoKey)
+  /// #   .unwrap();
+  /// # let humidity_topic = participant
+  /// #   .create_topic("humidity".to_string(), "Humidity".to_string(), &qos, TopicKind::NoKey)
+  /// #   .unwrap();
+  /// let mut temperatures = subscriber
+  ///   .create_datareader_no_key_cdr::<Temperature>(&temperature_topic, None)
+  ///   .unwrap();
+  /// let mut humidities = subscriber
+  ///   .create_datareader_no_key_cdr::<Humidity>(&humidity_topic, None)
+  ///   .unwrap();
+  ///
+  /// subscriber.begin_access().unwrap();
+  /// let order = subscriber
+  ///   .get_datareaders(&mut [&mut temperatures, &mut humidities])
+  ///   .unwrap();
+  /// for guid in order {
+  ///   if guid == temperatures.guid() {
+  ///     let temperature = temperatures.take_next_sample().unwrap();
+  ///   } else {
+  ///     let humidity = humidities.take_next_sample().unwrap();
+  ///   }
+  /// }
+  /// subscriber.end_access().unwrap();
+  /// ```
+  pub fn get_datareaders(&self, readers: &mut [&mut dyn GroupAccessReader]) -> Result<Vec<GUID>> {
+    let ordered = matches!(
+      self.inner.qos().presentation(),
+      Some(policy::Presentation {
+        access_scope: policy::PresentationAccessScope::Group,
+        ordered_access: true,
+        ..
+      })
+    );
+    if ordered && self.inner.access_lock().is_none() {
+      return Error::precondition_not_met("GROUP ordered access requires begin_access");
+    }
+
+    let mut samples = Vec::new();
+    for reader in readers.iter_mut() {
+      let guid = reader.guid();
+      if !self.inner.readers_lock().contains_key(&guid) {
+        return Error::bad_parameter("DataReader does not belong to this Subscriber.");
+      }
+      let timestamps = reader.not_read_sample_timestamps()?;
+      if ordered {
+        samples.extend(timestamps.into_iter().map(|timestamp| (timestamp, guid)));
+      } else if let Some(&first) = timestamps.first() {
+        samples.push((first, guid));
+      }
+    }
+    if ordered {
+      // The timestamps are unique within the participant, so this is a total
+      // order that keeps the order of each DataReader.
+      samples.sort_by_key(|(timestamp, _)| *timestamp);
+    }
+    Ok(samples.into_iter().map(|(_, guid)| guid).collect())
+  }
+
+  // DataReaders must not show samples received after this, if there is a
+  // GROUP access going on.
+  pub(crate) fn group_access_began(&self) -> Option<Timestamp> {
+    let group_scope = matches!(
+      self.inner.qos().presentation(),
+      Some(policy::Presentation {
+        access_scope: policy::PresentationAccessScope::Group,
+        ..
+      })
+    );
+    if group_scope {
+      self.inner.access_lock().map(|(_, began)| began)
+    } else {
+      None
+    }
+  }
+
+  pub(crate) fn downgrade(&self) -> SubscriberWeak {
+    SubscriberWeak {
+      inner: Arc::downgrade(&self.inner),
+    }
+  }
+
   pub(crate) fn remove_reader(&self, guid: GUID) {
     self.inner.remove_reader(guid);
   }
+
+  pub(crate) fn discovery_db(&self) -> Arc<RwLock<DiscoveryDB>> {
+    self.inner.discovery_db.clone()
+  }
+}
+
+impl HasQoSPolicy for Subscriber {
+  fn qos(&self) -> QosPolicies {
+    self.inner.qos()
+  }
+}
+
+/// A DataReader that can be listed in order with the other DataReaders of its
+/// Subscriber, see [`Subscriber::get_datareaders`].
+pub trait GroupAccessReader: RTPSEntity {
+  /// Reception timestamps of the NOT_READ samples of this DataReader, in the
+  /// order `take_next_sample` returns them.
+  fn not_read_sample_timestamps(&mut self) -> Result<Vec<Timestamp>>;
+}
+
+impl MutQosPolicy for Subscriber {
+  /// Changes the QoS of this Subscriber.
+  ///
+  /// Policies defined in `new_qos` replace the current ones, and the rest are
+  /// kept. Presentation cannot be changed after creation. Existing DataReaders
+  /// are not affected.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<()> {
+    self.inner.set_qos(new_qos)
+  }
 }
 
 #[derive(Clone)]
+pub(crate) struct SubscriberWeak {
+  inner: Weak<InnerSubscriber>,
+}
+
+impl SubscriberWeak {
+  pub fn upgrade(&self) -> Option<Subscriber> {
+    self.inner.upgrade().map(|inner| Subscriber { inner })
+  }
+}
+
 pub struct InnerSubscriber {
   domain_participant: DomainParticipantWeak,
   discovery_db: Arc<RwLock<DiscoveryDB>>,
-  qos: QosPolicies,
+  qos: Mutex<QosPolicies>,
   sender_add_reader: mio_channel::SyncSender<ReaderIngredients>,
   sender_remove_reader: mio_channel::SyncSender<GUID>,
   discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
+  lifecycle: EntityLifecycle,
+  // User-defined DataReaders created from this Subscriber and not dropped yet
+  readers: Mutex<BTreeMap<GUID, EntityLifecycle>>,
+  // begin_access nesting depth, and when the outermost access began
+  access: Mutex<Option<(usize, Timestamp)>>,
 }
 
 impl InnerSubscriber {
@@ -921,14 +1560,24 @@ impl InnerSubscriber {
     sender_add_reader: mio_channel::SyncSender<ReaderIngredients>,
     sender_remove_reader: mio_channel::SyncSender<GUID>,
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
+    enabled: bool,
   ) -> Self {
+    let lifecycle = if enabled {
+      EntityLifecycle::enabled()
+    } else {
+      EntityLifecycle::disabled(qos.clone(), |_| Ok(()))
+    };
+
     Self {
       domain_participant,
       discovery_db,
-      qos,
+      qos: Mutex::new(qos),
       sender_add_reader,
       sender_remove_reader,
       discovery_command,
+      lifecycle,
+      readers: Mutex::new(BTreeMap::new()),
+      access: Mutex::new(None),
     }
   }
 
@@ -938,14 +1587,21 @@ impl InnerSubscriber {
     entity_id_opt: Option<EntityId>,
     topic: &Topic,
     optional_qos: Option<QosPolicies>,
+    content_filter: Option<&ContentFilteredTopic>,
   ) -> Result<WithKeyDataReader<D, SA>>
   where
     D: DeserializeOwned + Keyed,
     <D as Keyed>::K: Key,
     SA: with_key::DeserializerAdapter<D>,
   {
+    if self.lifecycle.is_deleted() {
+      return Err(Error::AlreadyDeleted);
+    }
+
     // incoming data notification channel from Reader to DataReader
     let (send, rec) = mio_channel::sync_channel::<()>(4);
+    // wakes async tasks waiting for data, along with the notification channel
+    let data_reader_waker = WakerSlot::default();
     // status change channel from Reader to DataReader
     let (status_sender, status_receiver) = mio_channel::sync_channel::<DataReaderStatus>(4);
     // reader command channel from Datareader to Reader
@@ -955,7 +1611,7 @@ impl InnerSubscriber {
     // Use subscriber QoS as basis, modify by Topic settings, and modify by
     // specified QoS.
     let qos = self
-      .qos
+      .qos()
       .modify_by(&topic.qos())
       .modify_by(&optional_qos.unwrap_or_else(QosPolicies::qos_none));
 
@@ -966,25 +1622,68 @@ impl InnerSubscriber {
       Some(dp) => dp,
       None => return log_and_err_precondition_not_met!("DomainParticipant doesn't exist anymore."),
     };
+    #[cfg(feature = "security")]
+    if !entity_id.kind().is_built_in() {
+      dp.check_topic_access(&topic.name(), TopicAccess::Subscribe)?;
+    }
 
     let reader_guid = GUID::new_with_prefix_and_id(dp.guid_prefix(), entity_id);
 
     let new_reader = ReaderIngredients {
       guid: reader_guid,
-      notification_sender: send,
+      notification_sender: send.clone(),
+      data_reader_waker: data_reader_waker.clone(),
       status_sender,
       topic_name: topic.name(),
       qos_policy: qos.clone(),
       data_reader_command_receiver: reader_command_receiver,
     };
 
-    {
-      let mut db = self
-        .discovery_db
-        .write()
-        .or_else(|e| log_and_err_internal!("Cannot lock discovery_db. {}", e))?;
-      db.update_local_topic_reader(&dp, topic, &new_reader);
-      db.update_topic_data_p(topic);
+    // Enabling announces the DataReader, with the QoS it has by then, and
+    // adds the RTPS Reader to the event loop.
+    let sender_add_reader = self.sender_add_reader.clone();
+    let discovery_db = self.discovery_db.clone();
+    let dp_weak = dp.weak_clone();
+    let reader_topic = topic.clone();
+    let content_filter = content_filter.map(ContentFilteredTopic::content_filter_property);
+    let lifecycle = EntityLifecycle::disabled(qos.clone(), move |qos| {
+      let dp = match dp_weak.upgrade() {
+        Some(dp) => dp,
+        None => {
+          return log_and_err_precondition_not_met!("DomainParticipant doesn't exist anymore.")
+        }
+      };
+      let new_reader = ReaderIngredients {
+        qos_policy: qos,
+        ..new_reader
+      };
+      {
+        let mut db = discovery_db
+          .write()
+          .or_else(|e| log_and_err_internal!("Cannot lock discovery_db. {}", e))?;
+        db.update_local_topic_reader(&dp, &reader_topic, &new_reader, content_filter);
+        db.update_topic_data_p(&reader_topic);
+      }
+
+      // Return the DataReader Reader pairs to where they are used
+      sender_add_reader
+        .try_send(new_reader)
+        .or_else(|e| log_and_err_internal!("Cannot add DataReader. Error: {}", e))
+    });
+
+    // Create new topic to DDScache if one isn't present
+    match dp.dds_cache().write() {
+      Ok(mut dds_cache) => {
+        dds_cache.add_new_topic(topic.name(), topic.get_type());
+      }
+      Err(e) => return log_and_err_internal!("Cannot lock DDScache. Error: {}", e),
+    }
+
+    // Built-in DataReaders, i.e. those of Discovery, are always enabled, and
+    // are not deleted with the user-defined ones.
+    let built_in = entity_id.kind().is_built_in();
+    if built_in || (self.lifecycle.is_enabled() && self.qos().autoenable_created_entities()) {
+      lifecycle.enable()?;
     }
 
     let datareader = WithKeyDataReader::<D, SA>::new(
@@ -993,26 +1692,73 @@ impl InnerSubscriber {
       topic.clone(),
       qos,
       rec,
+      send.clone(),
+      data_reader_waker.clone(),
       dp.dds_cache(),
       self.discovery_command.clone(),
       status_receiver,
       reader_command_sender,
+      lifecycle.clone(),
     )?;
+    if !built_in {
+      self.readers_lock().insert(reader_guid, lifecycle);
+    }
+
+    Ok(datareader)
+  }
+
+  fn create_builtin_datareader<D, SA>(
+    &self,
+    outer: &Subscriber,
+    topic: &Topic,
+  ) -> Result<WithKeyDataReader<D, SA>>
+  where
+    D: DeserializeOwned + Keyed + 'static,
+    <D as Keyed>::K: Key,
+    SA: with_key::DeserializerAdapter<D>,
+  {
+    // The DDSCache notifies us directly, as there is no Reader to do it.
+    let (send, rec) = mio_channel::sync_channel::<()>(4);
+    let data_reader_waker = WakerSlot::default();
+    // Nothing sends statuses or receives commands, since there is no Reader.
+    let (_status_sender, status_receiver) = mio_channel::sync_channel::<DataReaderStatus>(4);
+    let (reader_command_sender, _reader_command_receiver) =
+      mio_channel::sync_channel::<ReaderCommand>(4);
+
+    let qos = self.qos().modify_by(&topic.qos());
+
+    let dp = match self.participant() {
+      Some(dp) => dp,
+      None => return log_and_err_precondition_not_met!("DomainParticipant doesn't exist anymore."),
+    };
+    let entity_id = dp.new_entity_id(EntityKind::READER_WITH_KEY_BUILT_IN);
+
+    let mut datareader = WithKeyDataReader::<D, SA>::new(
+      outer.clone(),
+      entity_id,
+      topic.clone(),
+      qos,
+      rec,
+      send.clone(),
+      data_reader_waker.clone(),
+      dp.dds_cache(),
+      self.discovery_command.clone(),
+      status_receiver,
+      reader_command_sender,
+      EntityLifecycle::enabled(),
+    )?;
+    // Discovery data received before this DataReader was created is still
+    // relevant, so start reading from the beginning of the cache.
+    datareader.read_from_start();
 
-    // Create new topic to DDScache if one isn't present
     match dp.dds_cache().write() {
       Ok(mut dds_cache) => {
         dds_cache.add_new_topic(topic.name(), topic.get_type());
+        dds_cache.topic_add_listener(&topic.name(), send, data_reader_waker);
       }
       Err(e) => return log_and_err_internal!("Cannot lock DDScache. Error: {}", e),
     }
 
-    // Return the DataReader Reader pairs to where they are used
-    self
-      .sender_add_reader
-      .try_send(new_reader)
-      .or_else(|e| log_and_err_internal!("Cannot add DataReader. Error: {}", e))?;
-
     Ok(datareader)
   }
 
@@ -1033,7 +1779,35 @@ impl InnerSubscriber {
         "Topic is NO_KEY, but attempted to create WITH_KEY Datareader",
       );
     }
-    self.create_datareader_internal(outer, entity_id, topic, qos)
+    self.create_datareader_internal(outer, entity_id, topic, qos, None)
+  }
+
+  pub fn create_filtered_datareader<D, SA>(
+    &self,
+    outer: &Subscriber,
+    topic: &ContentFilteredTopic,
+    qos: Option<QosPolicies>,
+  ) -> Result<WithKeyDataReader<D, SA>>
+  where
+    D: DeserializeOwned + Keyed + Serialize + 'static,
+    <D as Keyed>::K: Key,
+    SA: with_key::DeserializerAdapter<D>,
+  {
+    let related_topic = topic.related_topic();
+    if related_topic.kind() != TopicKind::WithKey {
+      return Error::precondition_not_met(
+        "Topic is NO_KEY, but attempted to create WITH_KEY Datareader",
+      );
+    }
+    let mut datareader =
+      self.create_datareader_internal(outer, None, related_topic, qos, Some(topic))?;
+    let filter = topic.clone();
+    datareader.set_content_filter(ContentFilter {
+      sample: Box::new(move |sample: &D| filter.matches(sample)),
+      serialized: registered_dynamic_type(self.participant(), topic)
+        .and_then(|t| topic.serialized_filter(&t)),
+    });
+    Ok(datareader)
   }
 
   pub fn create_datareader_no_key<D: 'static, SA>(
@@ -1061,16 +1835,112 @@ impl InnerSubscriber {
       Some(entity_id),
       topic,
       qos,
+      None,
     )?;
 
     Ok(NoKeyDataReader::<D, SA>::from_keyed(d))
   }
 
+  pub fn create_filtered_datareader_no_key<D, SA>(
+    &self,
+    outer: &Subscriber,
+    topic: &ContentFilteredTopic,
+    qos: Option<QosPolicies>,
+  ) -> Result<NoKeyDataReader<D, SA>>
+  where
+    D: DeserializeOwned + Serialize + 'static,
+    SA: no_key::DeserializerAdapter<D>,
+  {
+    let related_topic = topic.related_topic();
+    if related_topic.kind() != TopicKind::NoKey {
+      return Error::precondition_not_met(
+        "Topic is WITH_KEY, but attempted to create NO_KEY Datareader",
+      );
+    }
+
+    let entity_id = self.unwrap_or_new_entity_id(None, EntityKind::READER_NO_KEY_USER_DEFINED);
+
+    let mut d = self.create_datareader_internal::<NoKeyWrapper<D>, DAWrapper<SA>>(
+      outer,
+      Some(entity_id),
+      related_topic,
+      qos,
+      Some(topic),
+    )?;
+    let filter = topic.clone();
+    d.set_content_filter(ContentFilter {
+      sample: Box::new(move |sample: &NoKeyWrapper<D>| filter.matches(&sample.d)),
+      serialized: registered_dynamic_type(self.participant(), topic)
+        .and_then(|t| topic.serialized_filter(&t)),
+    });
+
+    Ok(NoKeyDataReader::<D, SA>::from_keyed(d))
+  }
+
   pub fn participant(&self) -> Option<DomainParticipant> {
     self.domain_participant.clone().upgrade()
   }
 
+  fn qos_lock(&self) -> MutexGuard<'_, QosPolicies> {
+    self
+      .qos
+      .lock()
+      .unwrap_or_else(|e| panic!("Subscriber QoS lock fail! {:?}", e))
+  }
+
+  pub fn qos(&self) -> QosPolicies {
+    self.qos_lock().clone()
+  }
+
+  pub fn set_qos(&self, new_qos: &QosPolicies) -> Result<()> {
+    let mut qos = self.qos_lock();
+    *qos = qos.changed_by(new_qos)?;
+    Ok(())
+  }
+
+  fn access_lock(&self) -> MutexGuard<'_, Option<(usize, Timestamp)>> {
+    self
+      .access
+      .lock()
+      .unwrap_or_else(|e| panic!("Subscriber access lock fail! {:?}", e))
+  }
+
+  fn readers_lock(&self) -> MutexGuard<'_, BTreeMap<GUID, EntityLifecycle>> {
+    self
+      .readers
+      .lock()
+      .unwrap_or_else(|e| panic!("Subscriber readers lock fail! {:?}", e))
+  }
+
+  fn enable(&self) -> Result<()> {
+    if self.lifecycle.is_enabled() {
+      return Ok(());
+    }
+    self.lifecycle.enable()?;
+    if self.qos().autoenable_created_entities() {
+      for reader in self.readers_lock().values() {
+        reader.enable()?;
+      }
+    }
+    Ok(())
+  }
+
+  fn delete_contained_entities(&self) -> Result<()> {
+    let readers = std::mem::take(&mut *self.readers_lock());
+    for (guid, reader) in readers {
+      if reader.delete() {
+        self.remove_reader(guid);
+        self
+          .discovery_command
+          .send(DiscoveryCommand::RemoveLocalReader { guid })
+          .or_else(|e| log_and_err_internal!("Cannot send RemoveLocalReader: {:?}", e))?;
+      }
+    }
+    Ok(())
+  }
+
   pub(crate) fn remove_reader(&self, guid: GUID) {
+    self.readers_lock().remove(&guid);
     self
       .sender_remove_reader
       .try_send(guid)
@@ -1088,7 +1958,197 @@ impl InnerSubscriber {
   }
 }
 
+// The DynamicType of the type of a Topic, if it is set in the TypeRegistry.
+fn registered_dynamic_type(
+  participant: Option<DomainParticipant>,
+  topic: &dyn TopicDescription,
+) -> Option<Arc<DynamicType>> {
+  let registered = participant?.type_registry().get(topic.get_type().name())?;
+  registered.dynamic_type().cloned()
+}
+
 // -------------------------------------------------------------------
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+  use super::*;
+
+  #[test]
+  fn suspended_writes_nested() {
+    let (cc_upload, cc_download) = mio_channel::sync_channel::<WriterCommand>(10);
+    let suspended_writes = SuspendedWrites::default();
+    assert!(suspended_writes.resume().is_err());
+
+    suspended_writes.suspend();
+    suspended_writes.suspend();
+    assert!(suspended_writes
+      .try_hold(&cc_upload, WriterCommand::FlushDataBatch)
+      .is_none());
+    assert!(suspended_writes
+      .try_hold(&cc_upload, WriterCommand::FlushDataBatch)
+      .is_none());
+    suspended_writes.resume().unwrap();
+    assert!(cc_download.try_recv().is_err());
+
+    suspended_writes.resume().unwrap();
+    assert!(cc_download.try_recv().is_ok());
+    assert!(cc_download.try_recv().is_ok());
+    assert!(cc_download.try_recv().is_err());
+    assert!(suspended_writes
+      .try_hold(&cc_upload, WriterCommand::FlushDataBatch)
+      .is_some());
+    assert!(suspended_writes.resume().is_err());
+  }
+
+  #[test]
+  fn subscriber_group_access() {
+    let participant = DomainParticipant::new(0).unwrap();
+    let group_qos = QosPolicyBuilder::new()
+      .presentation(policy::Presentation {
+        access_scope: policy::PresentationAccessScope::Group,
+        coherent_access: true,
+        ordered_access: false,
+      })
+      .build();
+    let subscriber = participant.create_subscriber(&group_qos).unwrap();
+    assert!(subscriber.end_access().is_err());
+    assert_eq!(subscriber.group_access_began(), None);
+
+    subscriber.begin_access().unwrap();
+    let began = subscriber.group_access_began().unwrap();
+    subscriber.begin_access().unwrap();
+    assert_eq!(subscriber.group_access_began(), Some(began));
+    subscriber.end_access().unwrap();
+    assert_eq!(subscriber.group_access_began(), Some(began));
+    subscriber.end_access().unwrap();
+    assert_eq!(subscriber.group_access_began(), None);
+    assert!(subscriber.end_access().is_err());
+
+    // Other access scopes are not affected.
+    let subscriber = participant
+      .create_subscriber(&QosPolicyBuilder::new().build())
+      .unwrap();
+    subscriber.begin_access().unwrap();
+    assert_eq!(subscriber.group_access_began(), None);
+    subscriber.end_access().unwrap();
+  }
+
+  #[test]
+  fn subscriber_group_ordered_access() {
+    use bytes::Bytes;
+
+    use crate::{
+      dds::ddsdata::DDSData,
+      messages::submessages::submessage_elements::serialized_payload::SerializedPayload,
+      serialization::cdr_serializer::to_bytes,
+      structure::{
+        cache_change::CacheChange, duration::Duration, guid::GuidPrefix,
+        sequence_number::SequenceNumber,
+      },
+      with_key::WriteOptions,
+    };
+
+    let participant = DomainParticipant::new(0).unwrap();
+    let ordered_qos = QosPolicyBuilder::new()
+      .presentation(policy::Presentation {
+        access_scope: policy::PresentationAccessScope::Group,
+        coherent_access: false,
+        ordered_access: true,
+      })
+      .history(policy::History::KeepAll)
+      .build();
+    let subscriber = participant.create_subscriber(&ordered_qos).unwrap();
+    let topic = |name: &str| {
+      participant
+        .create_topic(
+          name.to_string(),
+          "u32".to_string(),
+          &ordered_qos,
+          TopicKind::NoKey,
+        )
+        .unwrap()
+    };
+    let (topic_a, topic_b) = (topic("ordered_a"), topic("ordered_b"));
+    let mut reader_a = subscriber
+      .create_datareader_no_key_cdr::<u32>(&topic_a, None)
+      .unwrap();
+    let mut reader_b = subscriber
+      .create_datareader_no_key_cdr::<u32>(&topic_b, None)
+      .unwrap();
+
+    // Without an access, the order could change under the application.
+    assert!(subscriber
+      .get_datareaders(&mut [&mut reader_a, &mut reader_b])
+      .is_err());
+
+    // Samples received from one writer on each topic, interleaved
+    let writer = |n| {
+      GUID::new_with_prefix_and_id(
+        GuidPrefix::new(&[7; 12]),
+        EntityId::create_custom_entity_id([n; 3], EntityKind::WRITER_NO_KEY_USER_DEFINED),
+      )
+    };
+    let dds_cache = participant.dds_cache();
+    let add = |topic: &Topic, writer_guid, sn: i64, value: u32, received: Timestamp| {
+      let payload = SerializedPayload::new_from_bytes(
+        RepresentationIdentifier::CDR_LE,
+        Bytes::from(to_bytes::<u32, LittleEndian>(&value).unwrap()),
+      );
+      dds_cache.write().unwrap().add_change(
+        &topic.name(),
+        &received,
+        CacheChange::new(
+          writer_guid,
+          SequenceNumber::from(sn),
+          WriteOptions::from(None),
+          DDSData::new(payload),
+        ),
+      );
+    };
+    let start = Timestamp::now();
+    let at = |micros| start + Duration::from_std(std::time::Duration::from_micros(micros));
+    add(&topic_a, writer(1), 1, 1, at(1));
+    add(&topic_b, writer(2), 1, 2, at(2));
+    add(&topic_a, writer(1), 2, 3, at(3));
+    add(&topic_b, writer(2), 2, 4, at(4));
+    add(&topic_b, writer(2), 3, 5, at(5));
+    std::thread::sleep(std::time::Duration::from_millis(10));
+
+    subscriber.begin_access().unwrap();
+    // Received during the access, so not listed until the next one
+    add(&topic_a, writer(1), 3, 6, Timestamp::now());
+
+    let (guid_a, guid_b) = (reader_a.guid(), reader_b.guid());
+    let order = subscriber
+      .get_datareaders(&mut [&mut reader_a, &mut reader_b])
+      .unwrap();
+    assert_eq!(order, [guid_a, guid_b, guid_a, guid_b, guid_b]);
+    let mut values = Vec::new();
+    for guid in order {
+      let reader = if guid == guid_a {
+        &mut reader_a
+      } else {
+        &mut reader_b
+      };
+      values.push(*reader.take_next_sample().unwrap().unwrap().value());
+    }
+    assert_eq!(values, [1, 2, 3, 4, 5]);
+    subscriber.end_access().unwrap();
+
+    subscriber.begin_access().unwrap();
+    assert_eq!(
+      subscriber
+        .get_datareaders(&mut [&mut reader_a, &mut reader_b])
+        .unwrap(),
+      [guid_a]
+    );
+    subscriber.end_access().unwrap();
+
+    // Only DataReaders of this Subscriber can be ordered.
+    let other_subscriber = participant.create_subscriber(&ordered_qos).unwrap();
+    other_subscriber.begin_access().unwrap();
+    assert!(other_subscriber
+      .get_datareaders(&mut [&mut reader_a])
+      .is_err());
+  }
+}
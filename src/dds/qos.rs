@@ -1,14 +1,20 @@
 use std::io;
 
 use speedy::{Endianness, Readable};
-use log::trace;
+use log::{trace, warn};
 
 use crate::{
-  dds::{traits::key::KeyHash, values::result::Result},
+  dds::{
+    traits::key::KeyHash,
+    values::result::{Error, Result},
+  },
   messages::submessages::submessage_elements::{
-    parameter_list::ParameterList, RepresentationIdentifier,
+    parameter::Parameter, parameter_list::ParameterList, RepresentationIdentifier,
+  },
+  structure::{
+    duration::Duration, inline_qos::StatusInfo, parameter_id::ParameterId, rpc::SampleIdentity,
+    sequence_number::SequenceNumber,
   },
-  structure::{inline_qos::StatusInfo, parameter_id::ParameterId, rpc::SampleIdentity},
 };
 
 // This is to be implemented by all DomanParticipant, Publisher, Subscriber,
@@ -31,7 +37,7 @@ pub trait MutQosPolicy {
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum QosPolicyId {
   //Invalid  // We should represent this using Option<QosPolicyId> where needed
-  //UserData,  // 1
+  UserData,     // 1
   Durability,   // 2
   Presentation, // 3
   Deadline,
@@ -48,9 +54,9 @@ pub enum QosPolicyId {
   //EntityFactory, // 15
   //WriterDataLifeCycle,
   //ReaderDataLifeCycle, // 17
-  //TopicData, // 18
-  //GroupData,
-  //TransportPriority, // 20
+  TopicData, // 18
+  GroupData,
+  TransportPriority, // 20
   Lifespan,
   //DurabilityService, // 22
 }
@@ -70,6 +76,11 @@ pub struct QosPolicyBuilder {
   history: Option<policy::History>,
   resource_limits: Option<policy::ResourceLimits>,
   lifespan: Option<policy::Lifespan>,
+  transport_priority: Option<policy::TransportPriority>,
+  user_data: Option<policy::UserData>,
+  topic_data: Option<policy::TopicData>,
+  group_data: Option<policy::GroupData>,
+  entity_factory: Option<policy::EntityFactory>,
 }
 
 impl QosPolicyBuilder {
@@ -149,7 +160,37 @@ impl QosPolicyBuilder {
     self
   }
 
-  pub const fn build(self) -> QosPolicies {
+  #[must_use]
+  pub const fn transport_priority(mut self, transport_priority: policy::TransportPriority) -> Self {
+    self.transport_priority = Some(transport_priority);
+    self
+  }
+
+  #[must_use]
+  pub fn user_data(mut self, user_data: policy::UserData) -> Self {
+    self.user_data = Some(user_data);
+    self
+  }
+
+  #[must_use]
+  pub fn topic_data(mut self, topic_data: policy::TopicData) -> Self {
+    self.topic_data = Some(topic_data);
+    self
+  }
+
+  #[must_use]
+  pub fn group_data(mut self, group_data: policy::GroupData) -> Self {
+    self.group_data = Some(group_data);
+    self
+  }
+
+  #[must_use]
+  pub const fn entity_factory(mut self, entity_factory: policy::EntityFactory) -> Self {
+    self.entity_factory = Some(entity_factory);
+    self
+  }
+
+  pub fn build(self) -> QosPolicies {
     QosPolicies {
       durability: self.durability,
       presentation: self.presentation,
@@ -163,6 +204,11 @@ impl QosPolicyBuilder {
       history: self.history,
       resource_limits: self.resource_limits,
       lifespan: self.lifespan,
+      transport_priority: self.transport_priority,
+      user_data: self.user_data,
+      topic_data: self.topic_data,
+      group_data: self.group_data,
+      entity_factory: self.entity_factory,
     }
   }
 }
@@ -185,6 +231,11 @@ pub struct QosPolicies {
   pub(crate) history: Option<policy::History>,
   pub(crate) resource_limits: Option<policy::ResourceLimits>,
   pub(crate) lifespan: Option<policy::Lifespan>,
+  pub(crate) transport_priority: Option<policy::TransportPriority>,
+  pub(crate) user_data: Option<policy::UserData>,
+  pub(crate) topic_data: Option<policy::TopicData>,
+  pub(crate) group_data: Option<policy::GroupData>,
+  pub(crate) entity_factory: Option<policy::EntityFactory>,
 }
 
 impl QosPolicies {
@@ -245,6 +296,34 @@ impl QosPolicies {
     self.lifespan
   }
 
+  pub const fn transport_priority(&self) -> Option<policy::TransportPriority> {
+    self.transport_priority
+  }
+
+  pub fn user_data(&self) -> Option<policy::UserData> {
+    self.user_data.clone()
+  }
+
+  pub fn topic_data(&self) -> Option<policy::TopicData> {
+    self.topic_data.clone()
+  }
+
+  pub fn group_data(&self) -> Option<policy::GroupData> {
+    self.group_data.clone()
+  }
+
+  pub const fn entity_factory(&self) -> Option<policy::EntityFactory> {
+    self.entity_factory
+  }
+
+  // Entities are enabled on creation, unless the EntityFactory policy of
+  // their factory says otherwise.
+  pub(crate) fn autoenable_created_entities(&self) -> bool {
+    self
+      .entity_factory
+      .map_or(true, |ef| ef.autoenable_created_entities)
+  }
+
   /// Merge two QosPolicies
   ///
   /// Constructs a QosPolicy, where each policy is taken from `self`,
@@ -264,6 +343,11 @@ impl QosPolicies {
       history: other.history.or(self.history),
       resource_limits: other.resource_limits.or(self.resource_limits),
       lifespan: other.lifespan.or(self.lifespan),
+      transport_priority: other.transport_priority.or(self.transport_priority),
+      user_data: other.user_data.clone().or_else(|| self.user_data.clone()),
+      topic_data: other.topic_data.clone().or_else(|| self.topic_data.clone()),
+      group_data: other.group_data.clone().or_else(|| self.group_data.clone()),
+      entity_factory: other.entity_factory.or(self.entity_factory),
     }
   }
 
@@ -281,23 +365,36 @@ impl QosPolicies {
   ///
   /// This is not symmetric.
   pub fn compliance_failure_wrt(&self, other: &Self) -> Option<QosPolicyId> {
+    self.compliance_failures_wrt(other).first().copied()
+  }
+
+  /// Like [`compliance_failure_wrt`](Self::compliance_failure_wrt), but lists
+  /// all policies causing incompliance. Empty means that the policies are
+  /// compatible.
+  pub fn compliance_failures_wrt(&self, other: &Self) -> Vec<QosPolicyId> {
     trace!(
       "QoS compatibility check - offered: {:?} - requested {:?}",
       self,
       other
     );
-    let result = self.compliance_failure_wrt_impl(other);
+    let result = self.compliance_failures_wrt_impl(other);
     trace!("Result: {:?}", result);
     result
   }
 
-  fn compliance_failure_wrt_impl(&self, other: &Self) -> Option<QosPolicyId> {
-    // TODO: Check for cases where policy is requested, but not offered (None)
+  fn compliance_failures_wrt_impl(&self, other: &Self) -> Vec<QosPolicyId> {
+    let mut failures = Vec::new();
+
+    // A policy that is not present has its default value (DDS spec Section
+    // 2.2.3). Where the default is the weakest possible offer, a policy that is
+    // requested but not offered is checked against the default. Policies whose
+    // default is already compatible with any request (e.g. LatencyBudget,
+    // RELIABLE Reliability for writers) are compared only when both are present.
 
     // check Durability: Offered must be better than or equal to Requested.
-    if let (Some(off), Some(req)) = (self.durability, other.durability) {
-      if off < req {
-        return Some(QosPolicyId::Durability);
+    if let Some(req) = other.durability {
+      if self.durability.unwrap_or(policy::Durability::Volatile) < req {
+        failures.push(QosPolicyId::Durability);
       }
     }
 
@@ -305,19 +402,29 @@ impl QosPolicies {
     // * If coherent_access is requsted, it must be offered also. AND
     // * Same for ordered_access. AND
     // * Offered access scope is broader than requested.
-    if let (Some(off), Some(req)) = (self.presentation, other.presentation) {
+    if let Some(req) = other.presentation {
+      let off = self.presentation.unwrap_or(policy::Presentation {
+        access_scope: policy::PresentationAccessScope::Instance,
+        coherent_access: false,
+        ordered_access: false,
+      });
       if (req.coherent_access && !off.coherent_access)
         || (req.ordered_access && !off.ordered_access)
         || (req.access_scope > off.access_scope)
       {
-        return Some(QosPolicyId::Presentation);
+        failures.push(QosPolicyId::Presentation);
       }
     }
 
     // check Deadline: offered period <= requested period
-    if let (Some(off), Some(req)) = (self.deadline, other.deadline) {
-      if off.0 > req.0 {
-        return Some(QosPolicyId::Deadline);
+    // Default period is infinite.
+    if let Some(req) = other.deadline {
+      if self
+        .deadline
+        .map_or(Duration::DURATION_INFINITE, |off| off.0)
+        > req.0
+      {
+        failures.push(QosPolicyId::Deadline);
       }
     }
 
@@ -325,15 +432,19 @@ impl QosPolicies {
     // offered duration <= requested duration
     if let (Some(off), Some(req)) = (self.latency_budget, other.latency_budget) {
       if off.duration > req.duration {
-        return Some(QosPolicyId::LatencyBudget);
+        failures.push(QosPolicyId::LatencyBudget);
       }
     }
 
     // check Ownership:
     // offered kind == requested kind
-    if let (Some(off), Some(req)) = (self.ownership, other.ownership) {
-      if off != req {
-        return Some(QosPolicyId::Ownership);
+    // Default kind is SHARED, on both sides.
+    if self.ownership.is_some() || other.ownership.is_some() {
+      let is_exclusive = |ownership: Option<policy::Ownership>| {
+        matches!(ownership, Some(policy::Ownership::Exclusive { .. }))
+      };
+      if is_exclusive(self.ownership) != is_exclusive(other.ownership) {
+        failures.push(QosPolicyId::Ownership);
       }
     }
 
@@ -343,9 +454,13 @@ impl QosPolicies {
     // AND offered lease_duration <= requested lease_duration
     //
     // See Ord implementation on Liveliness.
-    if let (Some(off), Some(req)) = (self.liveliness, other.liveliness) {
+    // Default is AUTOMATIC with infinite lease_duration.
+    if let Some(req) = other.liveliness {
+      let off = self.liveliness.unwrap_or(policy::Liveliness::Automatic {
+        lease_duration: Duration::DURATION_INFINITE,
+      });
       if off < req {
-        return Some(QosPolicyId::Liveliness);
+        failures.push(QosPolicyId::Liveliness);
       }
     }
 
@@ -354,21 +469,226 @@ impl QosPolicies {
     // kind ranking: BEST_EFFORT < RELIABLE
     if let (Some(off), Some(req)) = (self.reliability, other.reliability) {
       if off < req {
-        return Some(QosPolicyId::Reliability);
+        failures.push(QosPolicyId::Reliability);
       }
     }
 
     // check Destination Order
     // offered kind >= requested kind
     // kind ranking: BY_RECEPTION_TIMESTAMP < BY_SOURCE_TIMESTAMP
-    if let (Some(off), Some(req)) = (self.destination_order, other.destination_order) {
-      if off < req {
-        return Some(QosPolicyId::DestinationOrder);
+    if let Some(req) = other.destination_order {
+      if self
+        .destination_order
+        .unwrap_or(policy::DestinationOrder::ByReceptionTimestamp)
+        < req
+      {
+        failures.push(QosPolicyId::DestinationOrder);
       }
     }
 
-    // default value. no incompatibility detected.
-    None
+    failures
+  }
+
+  /// Check if `new_qos` attempts to change a policy that cannot be changed
+  /// after the Entity has been created.
+  ///
+  /// * None => All changes are allowed
+  /// * Some(policyId) => (any) one of the policies that cannot be changed
+  ///
+  /// A policy that is not defined in `new_qos` is not changed. Changeability is
+  /// defined in the table in DDS spec v1.4 Section "2.2.3 Supported QoS".
+  /// Ownership strength can be changed, but Ownership kind cannot.
+  pub fn immutable_policy_change(&self, new_qos: &Self) -> Option<QosPolicyId> {
+    fn changed<T: PartialEq + Copy>(old: Option<T>, new: Option<T>) -> bool {
+      new.is_some() && old != new
+    }
+
+    let ownership_kind =
+      |o: Option<policy::Ownership>| o.map(|o| matches!(o, policy::Ownership::Exclusive { .. }));
+
+    if changed(self.durability, new_qos.durability) {
+      Some(QosPolicyId::Durability)
+    } else if changed(self.presentation, new_qos.presentation) {
+      Some(QosPolicyId::Presentation)
+    } else if changed(
+      ownership_kind(self.ownership),
+      ownership_kind(new_qos.ownership),
+    ) {
+      Some(QosPolicyId::Ownership)
+    } else if changed(self.liveliness, new_qos.liveliness) {
+      Some(QosPolicyId::Liveliness)
+    } else if changed(self.reliability, new_qos.reliability) {
+      Some(QosPolicyId::Reliability)
+    } else if changed(self.destination_order, new_qos.destination_order) {
+      Some(QosPolicyId::DestinationOrder)
+    } else if changed(self.history, new_qos.history) {
+      Some(QosPolicyId::History)
+    } else if changed(self.resource_limits, new_qos.resource_limits) {
+      Some(QosPolicyId::ResourceLimits)
+    } else {
+      None
+    }
+  }
+
+  /// QoS of an Entity after `set_qos(new_qos)`. The defined policies in
+  /// `new_qos` replace the current ones, and the rest are kept.
+  ///
+  /// Fails with [`Error::ImmutablePolicy`] if `new_qos` would change a policy
+  /// that cannot be changed after the Entity has been created.
+  pub(crate) fn changed_by(&self, new_qos: &Self) -> Result<Self> {
+    match self.immutable_policy_change(new_qos) {
+      None => Ok(self.modify_by(new_qos)),
+      Some(policy_id) => {
+        warn!("set_qos: Cannot change {:?} policy", policy_id);
+        Err(Error::ImmutablePolicy)
+      }
+    }
+  }
+}
+
+// Defines the QoS type of one kind of Entity. It is a QosPolicies that can
+// only be given the policies that apply to that kind of Entity, as listed in the
+// table in DDS spec v1.4 Section "2.2.3 Supported QoS".
+macro_rules! entity_qos {
+  ( $(#[$attr:meta])* $name:ident { $( $policy:ident : $policy_type:ty ),* $(,)? } ) => {
+    $(#[$attr])*
+    #[derive(Clone, Debug, PartialEq, Eq, Default)]
+    pub struct $name {
+      qos: QosPolicies,
+    }
+
+    impl $name {
+      pub fn new() -> Self {
+        Self::default()
+      }
+
+      /// Takes the applicable policies from `qos`, and ignores the rest.
+      pub fn from_qos_policies(qos: &QosPolicies) -> Self {
+        let mut typed = Self::default();
+        $( typed.qos.$policy = qos.$policy.clone(); )*
+        typed
+      }
+
+      $(
+        #[must_use]
+        pub fn $policy(mut self, $policy: $policy_type) -> Self {
+          self.qos.$policy = Some($policy);
+          self
+        }
+      )*
+
+      pub fn qos_policies(&self) -> &QosPolicies {
+        &self.qos
+      }
+    }
+
+    impl From<$name> for QosPolicies {
+      fn from(typed: $name) -> Self {
+        typed.qos
+      }
+    }
+  };
+}
+
+entity_qos!(
+  /// QoS policies of a DataReader, i.e. the "requested" QoS.
+  ///
+  /// Can be used wherever [`QosPolicies`] are expected by converting with
+  /// `into()`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::{policy, DomainParticipant, QosPolicies, ReaderQos, TopicKind};
+  /// # use rustdds::no_key::DataReader;
+  /// # use rustdds::CDRDeserializerAdapter;
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let topic = domain_participant
+  /// #   .create_topic("some_topic".to_string(), "u32".to_string(), &QosPolicies::qos_none(), TopicKind::NoKey)
+  /// #   .unwrap();
+  /// # let subscriber = domain_participant.create_subscriber(&QosPolicies::qos_none()).unwrap();
+  /// let reader_qos = ReaderQos::new()
+  ///   .reliability(policy::Reliability::BestEffort)
+  ///   .history(policy::History::KeepLast { depth: 1 });
+  /// let reader = subscriber
+  ///   .create_datareader_no_key::<u32, CDRDeserializerAdapter<u32>>(&topic, Some(reader_qos.into()))
+  ///   .unwrap();
+  /// ```
+  ReaderQos {
+    durability: policy::Durability,
+    deadline: policy::Deadline,
+    latency_budget: policy::LatencyBudget,
+    ownership: policy::Ownership,
+    liveliness: policy::Liveliness,
+    time_based_filter: policy::TimeBasedFilter,
+    reliability: policy::Reliability,
+    destination_order: policy::DestinationOrder,
+    history: policy::History,
+    resource_limits: policy::ResourceLimits,
+    user_data: policy::UserData,
+  }
+);
+
+entity_qos!(
+  /// QoS policies of a DataWriter, i.e. the "offered" QoS. See [`ReaderQos`].
+  WriterQos {
+    durability: policy::Durability,
+    deadline: policy::Deadline,
+    latency_budget: policy::LatencyBudget,
+    ownership: policy::Ownership,
+    liveliness: policy::Liveliness,
+    reliability: policy::Reliability,
+    transport_priority: policy::TransportPriority,
+    lifespan: policy::Lifespan,
+    destination_order: policy::DestinationOrder,
+    history: policy::History,
+    resource_limits: policy::ResourceLimits,
+    user_data: policy::UserData,
+  }
+);
+
+entity_qos!(
+  /// QoS policies of a Topic. These are the defaults for the DataReaders and
+  /// DataWriters of the Topic. See [`ReaderQos`].
+  TopicQos {
+    topic_data: policy::TopicData,
+    durability: policy::Durability,
+    deadline: policy::Deadline,
+    latency_budget: policy::LatencyBudget,
+    ownership: policy::Ownership,
+    liveliness: policy::Liveliness,
+    reliability: policy::Reliability,
+    transport_priority: policy::TransportPriority,
+    lifespan: policy::Lifespan,
+    destination_order: policy::DestinationOrder,
+    history: policy::History,
+    resource_limits: policy::ResourceLimits,
+  }
+);
+
+entity_qos!(
+  /// QoS policies of a Publisher. See [`ReaderQos`].
+  PublisherQos {
+    presentation: policy::Presentation,
+    group_data: policy::GroupData,
+    entity_factory: policy::EntityFactory,
+  }
+);
+
+entity_qos!(
+  /// QoS policies of a Subscriber. See [`ReaderQos`].
+  SubscriberQos {
+    presentation: policy::Presentation,
+    group_data: policy::GroupData,
+    entity_factory: policy::EntityFactory,
+  }
+);
+
+impl WriterQos {
+  /// All policies in which this offered QoS is incompatible with the
+  /// `requested` one. Empty means that the DataWriter and DataReader match.
+  pub fn compliance_failures_wrt(&self, requested: &ReaderQos) -> Vec<QosPolicyId> {
+    self.qos.compliance_failures_wrt(&requested.qos)
   }
 }
 
@@ -376,29 +696,55 @@ impl QosPolicies {
 // "qospolicy"
 /// Contains all available QoSPolicies
 pub mod policy {
-  use std::cmp::Ordering;
+  use std::{cmp::Ordering, time::Instant};
 
   use serde::{Deserialize, Serialize};
 
-  use crate::structure::{duration::Duration, parameter_id::ParameterId};
+  #[cfg(feature = "discovery")]
+  use crate::structure::parameter_id::ParameterId;
+  use crate::{
+    dds::clock::TimeSource,
+    structure::{duration::Duration, time::Timestamp},
+  };
 
-  /*
+  /// DDS 2.2.3.1 USER_DATA
+  ///
+  /// Application-defined octets attached to a DomainParticipant, DataReader or
+  /// DataWriter. They are not interpreted by RustDDS, but are sent to remote
+  /// participants in discovery.
+  #[derive(Clone, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
   pub struct UserData {
     pub value: Vec<u8>,
   }
 
+  /// DDS 2.2.3.2 TOPIC_DATA
+  ///
+  /// Application-defined octets attached to a Topic. Propagated in discovery
+  /// with the Topic and with the DataReaders and DataWriters of the Topic.
+  #[derive(Clone, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
   pub struct TopicData {
     pub value: Vec<u8>,
   }
 
-  pub struct GropupData {
+  /// DDS 2.2.3.3 GROUP_DATA
+  ///
+  /// Application-defined octets attached to a Publisher or Subscriber.
+  /// Propagated in discovery with the DataReaders and DataWriters created from
+  /// the Publisher or Subscriber.
+  #[derive(Clone, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+  pub struct GroupData {
     pub value: Vec<u8>,
   }
 
+  /// DDS 2.2.3.15 TRANSPORT_PRIORITY
+  ///
+  /// RustDDS maps the value to the DSCP (Differentiated Services Code Point)
+  /// field of outgoing IP packets sent by DataWriters. Values are clamped to
+  /// the DSCP range 0..=63.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
   pub struct TransportPriority {
     pub value: i32,
   }
-  */
 
   /// DDS 2.2.3.16 LIFESPAN
   #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -406,6 +752,29 @@ pub mod policy {
     pub duration: Duration,
   }
 
+  impl Lifespan {
+    // When a sample with the given source timestamp expires, on the monotonic
+    // clock. The time already elapsed since the source timestamp is read from
+    // the wall clock only once, so later clock jumps do not move the expiry.
+    // None means never.
+    pub(crate) fn expiry(
+      &self,
+      source_timestamp: Option<Timestamp>,
+      time_source: &TimeSource,
+    ) -> Option<Instant> {
+      if self.duration == Duration::DURATION_INFINITE {
+        return None;
+      }
+      // A source timestamp in the future, e.g. because our clock has stepped
+      // backwards, counts as no time elapsed.
+      let elapsed = source_timestamp.map_or(Duration::DURATION_ZERO, |ts| {
+        (time_source.now() - ts).max(Duration::DURATION_ZERO)
+      });
+      let remaining = Duration::from_ticks(self.duration.to_ticks() - elapsed.to_ticks());
+      time_source.instant().checked_add(remaining.to_std())
+    }
+  }
+
   /// DDS 2.2.3.4 DURABILITY
   #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
   pub enum Durability {
@@ -555,6 +924,7 @@ pub mod policy {
     pub max_samples_per_instance: i32,
   }
 
+  #[cfg(feature = "discovery")]
   #[derive(Serialize, Deserialize)]
   pub(crate) struct QosData<D>
   where
@@ -565,6 +935,7 @@ pub mod policy {
     qos_param: D,
   }
 
+  #[cfg(feature = "discovery")]
   impl<D> QosData<D>
   where
     D: Serialize + Copy + Clone,
@@ -602,11 +973,16 @@ pub mod policy {
     }
   }
 
-  /*
+  /// DDS 2.2.3.20 ENTITY_FACTORY
+  ///
+  /// Whether a DomainParticipant, Publisher or Subscriber enables the entities
+  /// it creates. If not, they can be configured first, and then enabled
+  /// explicitly with `enable()`. This is a local policy, not sent in discovery.
+  /// The default is to enable.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
   pub struct EntityFactory {
-    autoenable_created_entities: bool,
+    pub autoenable_created_entities: bool,
   }
-  */
   // WriterDataLifecycle
   // ReaderDataLifeCycle
 
@@ -621,6 +997,17 @@ pub mod policy {
 pub(crate) struct InlineQos {}
 
 impl InlineQos {
+  // RTPS spec v2.5 Section 9.6.2.2.1: A DATA or DATA_FRAG with an unknown
+  // inline QoS parameter, which must be understood, must be ignored. None of
+  // the inline QoS parameters that we know has the must-understand flag.
+  pub fn not_understood(params: &ParameterList) -> Option<ParameterId> {
+    params
+      .parameters
+      .iter()
+      .map(|p| p.parameter_id)
+      .find(|pid| pid.must_understand())
+  }
+
   pub fn status_info(
     params: &ParameterList,
     rep_id: RepresentationIdentifier,
@@ -659,6 +1046,37 @@ impl InlineQos {
       .iter()
       .find(|p| p.parameter_id == ParameterId::PID_RELATED_SAMPLE_IDENTITY);
 
+    Self::read_value(rsi, representation_id)
+  }
+
+  pub fn ownership_strength(
+    params: &ParameterList,
+    representation_id: RepresentationIdentifier,
+  ) -> std::result::Result<Option<i32>, crate::serialization::error::Error> {
+    let strength = params
+      .parameters
+      .iter()
+      .find(|p| p.parameter_id == ParameterId::PID_OWNERSHIP_STRENGTH);
+    Self::read_value(strength, representation_id)
+  }
+
+  // RTPS spec v2.5 Section 9.6.4.6: The sequence number of the first sample
+  // in the coherent set
+  pub fn coherent_set(
+    params: &ParameterList,
+    representation_id: RepresentationIdentifier,
+  ) -> std::result::Result<Option<SequenceNumber>, crate::serialization::error::Error> {
+    let coherent_set = params
+      .parameters
+      .iter()
+      .find(|p| p.parameter_id == ParameterId::PID_COHERENT_SET);
+    Self::read_value(coherent_set, representation_id)
+  }
+
+  fn read_value<'a, T: Readable<'a, Endianness>>(
+    param: Option<&'a Parameter>,
+    representation_id: RepresentationIdentifier,
+  ) -> std::result::Result<Option<T>, crate::serialization::error::Error> {
     let endianness = if representation_id == RepresentationIdentifier::CDR_BE
       || representation_id == RepresentationIdentifier::PL_CDR_BE
     {
@@ -667,12 +1085,206 @@ impl InlineQos {
       Endianness::LittleEndian
     };
 
-    Ok(match rsi {
+    Ok(match param {
       Some(p) => Some(
-        SampleIdentity::read_from_buffer_with_ctx(endianness, &p.value)
+        T::read_from_buffer_with_ctx(endianness, &p.value)
           .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?,
       ),
       None => None,
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn qos_compliance_against_defaults() {
+    let none = QosPolicies::qos_none();
+
+    // Requested, but not offered: compared to the default offer.
+    let req = QosPolicies::builder()
+      .durability(policy::Durability::TransientLocal)
+      .build();
+    assert_eq!(
+      none.compliance_failure_wrt(&req),
+      Some(QosPolicyId::Durability)
+    );
+    let req = QosPolicies::builder()
+      .deadline(policy::Deadline(Duration::from_secs(1)))
+      .build();
+    assert_eq!(
+      none.compliance_failure_wrt(&req),
+      Some(QosPolicyId::Deadline)
+    );
+
+    // RELIABLE is the default offer of a DataWriter.
+    let req = QosPolicies::builder()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      })
+      .build();
+    assert_eq!(none.compliance_failure_wrt(&req), None);
+
+    // Ownership kind must match also when only one side specifies it.
+    let exclusive = QosPolicies::builder()
+      .ownership(policy::Ownership::Exclusive { strength: 5 })
+      .build();
+    assert_eq!(
+      exclusive.compliance_failure_wrt(&none),
+      Some(QosPolicyId::Ownership)
+    );
+    // Strength does not matter.
+    let other_strength = QosPolicies::builder()
+      .ownership(policy::Ownership::Exclusive { strength: 1 })
+      .build();
+    assert_eq!(exclusive.compliance_failure_wrt(&other_strength), None);
+
+    assert_eq!(none.compliance_failure_wrt(&none), None);
+  }
+  #[test]
+  fn qos_compliance_lists_all_failures() {
+    let offered = WriterQos::new()
+      .reliability(policy::Reliability::BestEffort)
+      .durability(policy::Durability::Volatile)
+      .deadline(policy::Deadline(Duration::from_secs(1)));
+    let requested = ReaderQos::new()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      })
+      .durability(policy::Durability::TransientLocal)
+      .deadline(policy::Deadline(Duration::from_secs(2)));
+    assert_eq!(
+      offered.compliance_failures_wrt(&requested),
+      vec![QosPolicyId::Durability, QosPolicyId::Reliability]
+    );
+    assert_eq!(
+      offered
+        .qos_policies()
+        .compliance_failure_wrt(requested.qos_policies()),
+      Some(QosPolicyId::Durability)
+    );
+    assert!(offered
+      .compliance_failures_wrt(&ReaderQos::new())
+      .is_empty());
+  }
+
+  #[test]
+  fn qos_typed_entity_qos() {
+    let all = QosPolicies::builder()
+      .lifespan(policy::Lifespan {
+        duration: Duration::from_secs(1),
+      })
+      .time_based_filter(policy::TimeBasedFilter {
+        minimum_separation: Duration::from_secs(1),
+      })
+      .presentation(policy::Presentation {
+        access_scope: policy::PresentationAccessScope::Topic,
+        coherent_access: false,
+        ordered_access: false,
+      })
+      .reliability(policy::Reliability::BestEffort)
+      .build();
+
+    // Only the applicable policies are taken.
+    let reader: QosPolicies = ReaderQos::from_qos_policies(&all).into();
+    assert_eq!(reader.lifespan(), None);
+    assert_eq!(reader.time_based_filter(), all.time_based_filter());
+    assert_eq!(reader.presentation(), None);
+    assert_eq!(reader.reliability(), all.reliability());
+
+    let writer = WriterQos::from_qos_policies(&all);
+    assert_eq!(writer.qos_policies().lifespan(), all.lifespan());
+    assert_eq!(writer.qos_policies().time_based_filter(), None);
+
+    let publisher = PublisherQos::from_qos_policies(&all);
+    assert_eq!(
+      publisher.qos_policies(),
+      &QosPolicies::builder()
+        .presentation(all.presentation().unwrap())
+        .build()
+    );
+  }
+
+  #[test]
+  fn qos_immutable_policy_change() {
+    let current = QosPolicies::builder()
+      .reliability(policy::Reliability::BestEffort)
+      .deadline(policy::Deadline(Duration::from_secs(1)))
+      .ownership(policy::Ownership::Exclusive { strength: 1 })
+      .build();
+
+    // Changeable policies, and policies not mentioned in the new QoS
+    let new_qos = QosPolicies::builder()
+      .deadline(policy::Deadline(Duration::from_secs(2)))
+      .ownership(policy::Ownership::Exclusive { strength: 7 })
+      .build();
+    assert_eq!(current.immutable_policy_change(&new_qos), None);
+    let changed = current.changed_by(&new_qos).unwrap();
+    assert_eq!(changed.deadline, new_qos.deadline);
+    assert_eq!(changed.ownership, new_qos.ownership);
+    assert_eq!(changed.reliability, current.reliability);
+
+    // Setting an immutable policy to its current value is fine.
+    let same = QosPolicies::builder()
+      .reliability(policy::Reliability::BestEffort)
+      .build();
+    assert_eq!(current.immutable_policy_change(&same), None);
+
+    let new_qos = QosPolicies::builder()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      })
+      .build();
+    assert_eq!(
+      current.immutable_policy_change(&new_qos),
+      Some(QosPolicyId::Reliability)
+    );
+    assert!(matches!(
+      current.changed_by(&new_qos),
+      Err(Error::ImmutablePolicy)
+    ));
+
+    let new_qos = QosPolicies::builder()
+      .ownership(policy::Ownership::Shared)
+      .build();
+    assert_eq!(
+      current.immutable_policy_change(&new_qos),
+      Some(QosPolicyId::Ownership)
+    );
+  }
+
+  #[test]
+  fn inline_qos_must_understand() {
+    // PID_PAD, PID_STATUS_INFO, an unknown PID, and a sentinel with a length,
+    // which is ignored.
+    let le = [
+      0x00, 0x00, 0x04, 0x00, 0, 0, 0, 0, //
+      0x71, 0x00, 0x04, 0x00, 0, 0, 0, 1, //
+      0x00, 0x3f, 0x04, 0x00, 1, 2, 3, 4, //
+      0x01, 0x00, 0x04, 0x00,
+    ];
+    let be = [
+      0x00, 0x00, 0x00, 0x04, 0, 0, 0, 0, //
+      0x00, 0x71, 0x00, 0x04, 0, 0, 0, 1, //
+      0x3f, 0x00, 0x00, 0x04, 1, 2, 3, 4, //
+      0x00, 0x01, 0x00, 0x04,
+    ];
+    for (endianness, bytes) in [(Endianness::LittleEndian, le), (Endianness::BigEndian, be)] {
+      let mut params = ParameterList::read_from_buffer_with_ctx(endianness, &bytes).unwrap();
+      assert_eq!(params.parameters.len(), 3);
+      assert!(
+        InlineQos::status_info(&params, RepresentationIdentifier::CDR_LE)
+          .unwrap()
+          .contains(crate::structure::inline_qos::StatusInfoEnum::Disposed)
+      );
+      assert_eq!(InlineQos::not_understood(&params), None);
+
+      // Unknown, and the must-understand flag is set
+      params.parameters[2].parameter_id =
+        ParameterId::read_from_buffer_with_ctx(Endianness::BigEndian, &[0x7f, 0x00]).unwrap();
+      assert!(InlineQos::not_understood(&params).is_some());
+    }
+  }
+}
@@ -0,0 +1,665 @@
+//! Named QoS profiles loaded from configuration files.
+//!
+//! The XML format follows the OMG DDS XML (DDS-XML) QoS profile schema:
+//!
+//! ```xml
+//! <dds xmlns="http://www.omg.org/dds/">
+//!   <qos_library name="MyLibrary">
+//!     <qos_profile name="Reliable">
+//!       <datawriter_qos>
+//!         <reliability>
+//!           <kind>RELIABLE_RELIABILITY_QOS</kind>
+//!           <max_blocking_time><sec>1</sec><nanosec>0</nanosec></max_blocking_time>
+//!         </reliability>
+//!       </datawriter_qos>
+//!     </qos_profile>
+//!     <qos_profile name="KeepAll" base_name="Reliable">
+//!       <datareader_qos>
+//!         <history><kind>KEEP_ALL_HISTORY_QOS</kind></history>
+//!       </datareader_qos>
+//!     </qos_profile>
+//!   </qos_library>
+//! </dds>
+//! ```
+//!
+//! The YAML format has the same structure. Attributes are written as ordinary
+//! mapping entries, and repeated elements as sequences:
+//!
+//! ```yaml
+//! qos_library:
+//!   - name: MyLibrary
+//!     qos_profile:
+//!       - name: Reliable
+//!         datawriter_qos:
+//!           reliability:
+//!             kind: RELIABLE_RELIABILITY_QOS
+//!             max_blocking_time: { sec: 1, nanosec: 0 }
+//! ```
+//!
+//! Profiles are referred to as `"<library>::<profile>"`. Policies that RustDDS
+//! does not implement are ignored.
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use log::warn;
+
+use crate::{
+  dds::qos::{policy, QosPolicies, QosPolicyBuilder},
+  structure::duration::Duration,
+};
+
+pub(crate) mod xml;
+
+/// Errors from loading QoS profiles
+#[derive(Debug, thiserror::Error)]
+pub enum QosProviderError {
+  #[error(transparent)]
+  Io(#[from] io::Error),
+
+  #[error("XML syntax error at byte {position}: {reason}")]
+  Xml { position: usize, reason: String },
+
+  #[error("YAML syntax error: {reason}")]
+  Yaml { reason: String },
+
+  /// File name extension is not one of `xml`, `yaml`, or `yml`.
+  #[error("Unknown QoS profile file format: {path}")]
+  UnknownFormat { path: String },
+
+  #[error("Invalid value {value:?} for {element}")]
+  InvalidValue { element: String, value: String },
+
+  #[error("QoS profile {name:?} not found")]
+  ProfileNotFound { name: String },
+
+  /// A profile inherits (via `base_name`) from itself.
+  #[error("QoS profile {name:?} inherits from itself")]
+  InheritanceCycle { name: String },
+}
+
+type Result<T> = std::result::Result<T, QosProviderError>;
+
+/// A collection of named QoS profiles.
+///
+/// Loaded from an XML or YAML file, this allows tuning QoS of a deployment
+/// without recompiling. Use the `*_qos` methods to get the QosPolicies to pass
+/// to e.g.
+/// [`create_topic`](crate::DomainParticipant::create_topic) or
+/// [`create_datawriter`](crate::Publisher::create_datawriter).
+///
+/// ```no_run
+/// use rustdds::*;
+///
+/// let provider = QosProvider::from_file("qos_profiles.xml").unwrap();
+/// let participant = DomainParticipant::new(0).unwrap();
+/// let topic = participant
+///   .create_topic(
+///     "Square".to_string(),
+///     "ShapeType".to_string(),
+///     &provider.topic_qos("MyLibrary::Reliable").unwrap(),
+///     TopicKind::WithKey,
+///   )
+///   .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QosProvider {
+  profiles: BTreeMap<String, QosProfile>,
+}
+
+// QoS of each entity kind in a profile
+#[derive(Debug, Clone, Default)]
+struct QosProfile {
+  participant: QosPolicies,
+  topic: QosPolicies,
+  publisher: QosPolicies,
+  subscriber: QosPolicies,
+  datawriter: QosPolicies,
+  datareader: QosPolicies,
+}
+
+impl QosProfile {
+  fn modify_by(&self, other: &Self) -> Self {
+    Self {
+      participant: self.participant.modify_by(&other.participant),
+      topic: self.topic.modify_by(&other.topic),
+      publisher: self.publisher.modify_by(&other.publisher),
+      subscriber: self.subscriber.modify_by(&other.subscriber),
+      datawriter: self.datawriter.modify_by(&other.datawriter),
+      datareader: self.datareader.modify_by(&other.datareader),
+    }
+  }
+}
+
+impl QosProvider {
+  /// Load profiles from a file. The format is selected by the file name
+  /// extension: `.xml`, or `.yaml` / `.yml`.
+  pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    match path.extension().and_then(|e| e.to_str()) {
+      Some("xml") | Some("XML") => Self::from_xml_str(&text),
+      Some("yaml") | Some("yml") | Some("YAML") | Some("YML") => Self::from_yaml_str(&text),
+      _ => Err(QosProviderError::UnknownFormat {
+        path: path.display().to_string(),
+      }),
+    }
+  }
+
+  /// Load profiles from an XML document.
+  pub fn from_xml_str(xml: &str) -> Result<Self> {
+    Self::from_root(&xml::parse(xml)?)
+  }
+
+  /// Load profiles from a YAML document.
+  pub fn from_yaml_str(yaml: &str) -> Result<Self> {
+    let value: serde_yaml::Value =
+      serde_yaml::from_str(yaml).map_err(|e| QosProviderError::Yaml {
+        reason: e.to_string(),
+      })?;
+    let mut root = Node::new("dds");
+    Node::push_yaml_children(&mut root, &value);
+    Self::from_root(&root)
+  }
+
+  /// Names of all profiles, in the form `"<library>::<profile>"`.
+  pub fn profile_names(&self) -> impl Iterator<Item = &str> {
+    self.profiles.keys().map(String::as_str)
+  }
+
+  pub fn participant_qos(&self, profile: &str) -> Result<QosPolicies> {
+    self.profile(profile).map(|p| p.participant.clone())
+  }
+
+  pub fn topic_qos(&self, profile: &str) -> Result<QosPolicies> {
+    self.profile(profile).map(|p| p.topic.clone())
+  }
+
+  pub fn publisher_qos(&self, profile: &str) -> Result<QosPolicies> {
+    self.profile(profile).map(|p| p.publisher.clone())
+  }
+
+  pub fn subscriber_qos(&self, profile: &str) -> Result<QosPolicies> {
+    self.profile(profile).map(|p| p.subscriber.clone())
+  }
+
+  pub fn datawriter_qos(&self, profile: &str) -> Result<QosPolicies> {
+    self.profile(profile).map(|p| p.datawriter.clone())
+  }
+
+  pub fn datareader_qos(&self, profile: &str) -> Result<QosPolicies> {
+    self.profile(profile).map(|p| p.datareader.clone())
+  }
+
+  fn profile(&self, name: &str) -> Result<&QosProfile> {
+    self
+      .profiles
+      .get(name)
+      .ok_or_else(|| QosProviderError::ProfileNotFound {
+        name: name.to_string(),
+      })
+  }
+
+  fn from_root(root: &Node) -> Result<Self> {
+    // Collect profile definitions: name -> (base_name, profile)
+    let mut definitions = BTreeMap::new();
+    for library in root.children_named("qos_library") {
+      let library_name = library.child_text("name").unwrap_or("");
+      for profile in library.children_named("qos_profile") {
+        let profile_name = profile.child_text("name").unwrap_or("");
+        let qualify = |name: &str| {
+          if name.contains("::") {
+            name.to_string()
+          } else {
+            format!("{}::{}", library_name, name)
+          }
+        };
+        let base_name = profile.child_text("base_name").map(qualify);
+        definitions.insert(qualify(profile_name), (base_name, parse_profile(profile)?));
+      }
+    }
+
+    let mut profiles = BTreeMap::new();
+    for name in definitions.keys() {
+      let resolved = resolve_profile(name, &definitions, &mut Vec::new())?;
+      profiles.insert(name.clone(), resolved);
+    }
+    Ok(Self { profiles })
+  }
+}
+
+// Apply inheritance chain
+fn resolve_profile(
+  name: &str,
+  definitions: &BTreeMap<String, (Option<String>, QosProfile)>,
+  visiting: &mut Vec<String>,
+) -> Result<QosProfile> {
+  if visiting.iter().any(|v| v == name) {
+    return Err(QosProviderError::InheritanceCycle {
+      name: name.to_string(),
+    });
+  }
+  let (base_name, profile) =
+    definitions
+      .get(name)
+      .ok_or_else(|| QosProviderError::ProfileNotFound {
+        name: name.to_string(),
+      })?;
+  match base_name {
+    None => Ok(profile.clone()),
+    Some(base_name) => {
+      visiting.push(name.to_string());
+      let base = resolve_profile(base_name, definitions, visiting)?;
+      Ok(base.modify_by(profile))
+    }
+  }
+}
+
+fn parse_profile(node: &Node) -> Result<QosProfile> {
+  let qos = |element: &str| {
+    node
+      .child(element)
+      .map_or_else(|| Ok(QosPolicies::default()), parse_qos)
+  };
+  Ok(QosProfile {
+    participant: qos("domainparticipant_qos")?,
+    topic: qos("topic_qos")?,
+    publisher: qos("publisher_qos")?,
+    subscriber: qos("subscriber_qos")?,
+    datawriter: qos("datawriter_qos")?,
+    datareader: qos("datareader_qos")?,
+  })
+}
+
+// Parse the contents of e.g. <datawriter_qos>
+fn parse_qos(node: &Node) -> Result<QosPolicies> {
+  let mut builder = QosPolicyBuilder::new();
+  let mut ownership = None;
+  let mut ownership_strength = 0;
+
+  for policy_node in &node.children {
+    builder = match policy_node.name.as_str() {
+      "durability" => builder.durability(match policy_node.kind()? {
+        "VOLATILE_DURABILITY_QOS" => policy::Durability::Volatile,
+        "TRANSIENT_LOCAL_DURABILITY_QOS" => policy::Durability::TransientLocal,
+        "TRANSIENT_DURABILITY_QOS" => policy::Durability::Transient,
+        "PERSISTENT_DURABILITY_QOS" => policy::Durability::Persistent,
+        other => return Err(policy_node.invalid("kind", other)),
+      }),
+      "presentation" => builder.presentation(policy::Presentation {
+        access_scope: match policy_node
+          .child_text("access_scope")
+          .unwrap_or("INSTANCE_PRESENTATION_QOS")
+        {
+          "INSTANCE_PRESENTATION_QOS" => policy::PresentationAccessScope::Instance,
+          "TOPIC_PRESENTATION_QOS" => policy::PresentationAccessScope::Topic,
+          "GROUP_PRESENTATION_QOS" => policy::PresentationAccessScope::Group,
+          other => return Err(policy_node.invalid("access_scope", other)),
+        },
+        coherent_access: policy_node.bool("coherent_access")?.unwrap_or(false),
+        ordered_access: policy_node.bool("ordered_access")?.unwrap_or(false),
+      }),
+      "deadline" => builder.deadline(policy::Deadline(
+        policy_node
+          .duration("period")?
+          .unwrap_or(Duration::DURATION_INFINITE),
+      )),
+      "latency_budget" => builder.latency_budget(policy::LatencyBudget {
+        duration: policy_node
+          .duration("duration")?
+          .unwrap_or(Duration::DURATION_ZERO),
+      }),
+      "ownership" => {
+        ownership = Some(policy_node.kind()?.to_string());
+        builder
+      }
+      "ownership_strength" => {
+        ownership_strength = policy_node.int("value")?.unwrap_or(0);
+        builder
+      }
+      "liveliness" => {
+        let lease_duration = policy_node
+          .duration("lease_duration")?
+          .unwrap_or(Duration::DURATION_INFINITE);
+        builder.liveliness(match policy_node.kind()? {
+          "AUTOMATIC_LIVELINESS_QOS" => policy::Liveliness::Automatic { lease_duration },
+          "MANUAL_BY_PARTICIPANT_LIVELINESS_QOS" => {
+            policy::Liveliness::ManualByParticipant { lease_duration }
+          }
+          "MANUAL_BY_TOPIC_LIVELINESS_QOS" => policy::Liveliness::ManualByTopic { lease_duration },
+          other => return Err(policy_node.invalid("kind", other)),
+        })
+      }
+      "time_based_filter" => builder.time_based_filter(policy::TimeBasedFilter {
+        minimum_separation: policy_node
+          .duration("minimum_separation")?
+          .unwrap_or(Duration::DURATION_ZERO),
+      }),
+      "reliability" => builder.reliability(match policy_node.kind()? {
+        "BEST_EFFORT_RELIABILITY_QOS" => policy::Reliability::BestEffort,
+        "RELIABLE_RELIABILITY_QOS" => policy::Reliability::Reliable {
+          max_blocking_time: policy_node
+            .duration("max_blocking_time")?
+            .unwrap_or_else(|| Duration::from_millis(100)),
+        },
+        other => return Err(policy_node.invalid("kind", other)),
+      }),
+      "destination_order" => builder.destination_order(match policy_node.kind()? {
+        "BY_RECEPTION_TIMESTAMP_DESTINATIONORDER_QOS" => {
+          policy::DestinationOrder::ByReceptionTimestamp
+        }
+        "BY_SOURCE_TIMESTAMP_DESTINATIONORDER_QOS" => policy::DestinationOrder::BySourceTimeStamp,
+        other => return Err(policy_node.invalid("kind", other)),
+      }),
+      "history" => builder.history(match policy_node.kind()? {
+        "KEEP_LAST_HISTORY_QOS" => policy::History::KeepLast {
+          depth: policy_node.int("depth")?.unwrap_or(1),
+        },
+        "KEEP_ALL_HISTORY_QOS" => policy::History::KeepAll,
+        other => return Err(policy_node.invalid("kind", other)),
+      }),
+      "resource_limits" => builder.resource_limits(policy::ResourceLimits {
+        max_samples: policy_node.int("max_samples")?.unwrap_or(i32::MAX),
+        max_instances: policy_node.int("max_instances")?.unwrap_or(i32::MAX),
+        max_samples_per_instance: policy_node
+          .int("max_samples_per_instance")?
+          .unwrap_or(i32::MAX),
+      }),
+      "lifespan" => builder.lifespan(policy::Lifespan {
+        duration: policy_node
+          .duration("duration")?
+          .unwrap_or(Duration::DURATION_INFINITE),
+      }),
+      "transport_priority" => builder.transport_priority(policy::TransportPriority {
+        value: policy_node.int("value")?.unwrap_or(0),
+      }),
+      other => {
+        warn!("QoS profile: Ignoring unsupported policy {:?}", other);
+        builder
+      }
+    }
+  }
+
+  if let Some(kind) = ownership {
+    builder = builder.ownership(match kind.as_str() {
+      "SHARED_OWNERSHIP_QOS" => policy::Ownership::Shared,
+      "EXCLUSIVE_OWNERSHIP_QOS" => policy::Ownership::Exclusive {
+        strength: ownership_strength,
+      },
+      other => {
+        return Err(QosProviderError::InvalidValue {
+          element: "ownership/kind".to_string(),
+          value: other.to_string(),
+        })
+      }
+    });
+  }
+
+  Ok(builder.build())
+}
+
+// Element tree, common to both XML and YAML input. XML attributes are
+// represented as child elements. The security plugins read their XML documents
+// into this, too.
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+  pub name: String,
+  pub text: String,
+  pub children: Vec<Node>,
+}
+
+impl Node {
+  fn new(name: &str) -> Self {
+    Self::with_text(name, String::new())
+  }
+
+  fn with_text(name: &str, text: String) -> Self {
+    Self {
+      name: name.to_string(),
+      text,
+      children: Vec::new(),
+    }
+  }
+
+  fn push_yaml_children(parent: &mut Self, value: &serde_yaml::Value) {
+    use serde_yaml::Value;
+    if let Value::Mapping(mapping) = value {
+      for (key, value) in mapping {
+        let name = match key {
+          Value::String(s) => s.clone(),
+          other => Self::yaml_scalar_text(other),
+        };
+        match value {
+          Value::Sequence(items) => {
+            for item in items {
+              parent.children.push(Self::from_yaml(&name, item));
+            }
+          }
+          _ => parent.children.push(Self::from_yaml(&name, value)),
+        }
+      }
+    }
+  }
+
+  fn from_yaml(name: &str, value: &serde_yaml::Value) -> Self {
+    let mut node = Self::with_text(name, Self::yaml_scalar_text(value));
+    Self::push_yaml_children(&mut node, value);
+    node
+  }
+
+  fn yaml_scalar_text(value: &serde_yaml::Value) -> String {
+    use serde_yaml::Value;
+    match value {
+      Value::String(s) => s.clone(),
+      Value::Number(n) => n.to_string(),
+      Value::Bool(b) => b.to_string(),
+      _ => String::new(),
+    }
+  }
+
+  pub fn child(&self, name: &str) -> Option<&Node> {
+    self.children.iter().find(|c| c.name == name)
+  }
+
+  pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> {
+    self.children.iter().filter(move |c| c.name == name)
+  }
+
+  pub fn child_text(&self, name: &str) -> Option<&str> {
+    self.child(name).map(|c| c.text.as_str())
+  }
+
+  fn invalid(&self, child: &str, value: &str) -> QosProviderError {
+    QosProviderError::InvalidValue {
+      element: format!("{}/{}", self.name, child),
+      value: value.to_string(),
+    }
+  }
+
+  fn kind(&self) -> Result<&str> {
+    self
+      .child_text("kind")
+      .ok_or_else(|| self.invalid("kind", ""))
+  }
+
+  fn int(&self, child: &str) -> Result<Option<i32>> {
+    match self.child_text(child) {
+      None => Ok(None),
+      Some("LENGTH_UNLIMITED") => Ok(Some(i32::MAX)),
+      Some(text) => text
+        .parse()
+        .map(Some)
+        .map_err(|_| self.invalid(child, text)),
+    }
+  }
+
+  pub fn bool(&self, child: &str) -> Result<Option<bool>> {
+    match self.child_text(child) {
+      None => Ok(None),
+      Some("true") | Some("1") => Ok(Some(true)),
+      Some("false") | Some("0") => Ok(Some(false)),
+      Some(text) => Err(self.invalid(child, text)),
+    }
+  }
+
+  // DDS-XML duration: <sec> and <nanosec> children, either of which may be
+  // omitted.
+  fn duration(&self, child: &str) -> Result<Option<Duration>> {
+    let node = match self.child(child) {
+      None => return Ok(None),
+      Some(node) => node,
+    };
+    if node.text == "DURATION_INFINITY" {
+      return Ok(Some(Duration::DURATION_INFINITE));
+    }
+    let sec = match node.child_text("sec") {
+      None => 0,
+      Some("DURATION_INFINITY") | Some("DURATION_INFINITE_SEC") => {
+        return Ok(Some(Duration::DURATION_INFINITE))
+      }
+      Some(text) => text.parse::<i64>().map_err(|_| node.invalid("sec", text))?,
+    };
+    let nanosec = match node.child_text("nanosec") {
+      None => 0,
+      Some("DURATION_INFINITY") | Some("DURATION_INFINITE_NSEC") => {
+        return Ok(Some(Duration::DURATION_INFINITE))
+      }
+      Some(text) => text
+        .parse::<i64>()
+        .map_err(|_| node.invalid("nanosec", text))?,
+    };
+    Ok(Some(Duration::from_nanos(sec * 1_000_000_000 + nanosec)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+    <dds xmlns="http://www.omg.org/dds/">
+      <qos_library name="Lib">
+        <qos_profile name="Base">
+          <datawriter_qos>
+            <reliability>
+              <kind>RELIABLE_RELIABILITY_QOS</kind>
+              <max_blocking_time><sec>1</sec><nanosec>500000000</nanosec></max_blocking_time>
+            </reliability>
+            <history><kind>KEEP_LAST_HISTORY_QOS</kind><depth>5</depth></history>
+          </datawriter_qos>
+          <topic_qos>
+            <durability><kind>TRANSIENT_LOCAL_DURABILITY_QOS</kind></durability>
+          </topic_qos>
+        </qos_profile>
+        <qos_profile name="Derived" base_name="Base">
+          <datawriter_qos>
+            <history><kind>KEEP_ALL_HISTORY_QOS</kind></history>
+            <ownership><kind>EXCLUSIVE_OWNERSHIP_QOS</kind></ownership>
+            <ownership_strength><value>7</value></ownership_strength>
+            <deadline><period><sec>DURATION_INFINITE_SEC</sec></period></deadline>
+            <user_data><value>ignored</value></user_data>
+          </datawriter_qos>
+        </qos_profile>
+      </qos_library>
+    </dds>"#;
+
+  #[test]
+  fn qos_provider_xml_with_inheritance() {
+    let provider = QosProvider::from_xml_str(XML).unwrap();
+    assert_eq!(
+      provider.profile_names().collect::<Vec<_>>(),
+      vec!["Lib::Base", "Lib::Derived"]
+    );
+
+    let base = provider.datawriter_qos("Lib::Base").unwrap();
+    assert_eq!(
+      base.reliability(),
+      Some(policy::Reliability::Reliable {
+        max_blocking_time: Duration::from_millis(1500)
+      })
+    );
+    assert_eq!(base.history(), Some(policy::History::KeepLast { depth: 5 }));
+
+    let derived = provider.datawriter_qos("Lib::Derived").unwrap();
+    assert_eq!(derived.reliability(), base.reliability());
+    assert_eq!(derived.history(), Some(policy::History::KeepAll));
+    assert_eq!(
+      derived.ownership(),
+      Some(policy::Ownership::Exclusive { strength: 7 })
+    );
+    assert_eq!(
+      derived.deadline(),
+      Some(policy::Deadline(Duration::DURATION_INFINITE))
+    );
+    assert_eq!(
+      provider.topic_qos("Lib::Derived").unwrap().durability(),
+      Some(policy::Durability::TransientLocal)
+    );
+    assert_eq!(
+      provider.datareader_qos("Lib::Derived").unwrap(),
+      QosPolicies::default()
+    );
+    assert!(matches!(
+      provider.datawriter_qos("Lib::Missing"),
+      Err(QosProviderError::ProfileNotFound { .. })
+    ));
+  }
+
+  #[test]
+  fn qos_provider_yaml() {
+    let yaml = r#"
+qos_library:
+  - name: Lib
+    qos_profile:
+      - name: Base
+        datareader_qos:
+          reliability:
+            kind: BEST_EFFORT_RELIABILITY_QOS
+          resource_limits:
+            max_samples: 100
+            max_instances: LENGTH_UNLIMITED
+      - name: Derived
+        base_name: Lib::Base
+        datareader_qos:
+          presentation: { access_scope: TOPIC_PRESENTATION_QOS, ordered_access: true }
+"#;
+    let provider = QosProvider::from_yaml_str(yaml).unwrap();
+    let qos = provider.datareader_qos("Lib::Derived").unwrap();
+    assert_eq!(qos.reliability(), Some(policy::Reliability::BestEffort));
+    assert_eq!(
+      qos.resource_limits(),
+      Some(policy::ResourceLimits {
+        max_samples: 100,
+        max_instances: i32::MAX,
+        max_samples_per_instance: i32::MAX,
+      })
+    );
+    assert_eq!(
+      qos.presentation(),
+      Some(policy::Presentation {
+        access_scope: policy::PresentationAccessScope::Topic,
+        coherent_access: false,
+        ordered_access: true,
+      })
+    );
+  }
+
+  #[test]
+  fn qos_provider_errors() {
+    let bad_kind = r#"<dds><qos_library name="L"><qos_profile name="P"><datawriter_qos>
+      <reliability><kind>SOMETIMES</kind></reliability>
+      </datawriter_qos></qos_profile></qos_library></dds>"#;
+    assert!(matches!(
+      QosProvider::from_xml_str(bad_kind),
+      Err(QosProviderError::InvalidValue { .. })
+    ));
+
+    let cycle = r#"<dds><qos_library name="L">
+      <qos_profile name="A" base_name="B"/>
+      <qos_profile name="B" base_name="L::A"/>
+      </qos_library></dds>"#;
+    assert!(matches!(
+      QosProvider::from_xml_str(cycle),
+      Err(QosProviderError::InheritanceCycle { .. })
+    ));
+  }
+}
@@ -0,0 +1,226 @@
+// A minimal XML reader, sufficient for DDS XML QoS profile files and DDS Security
+// governance and permissions documents.
+//
+// Supports elements, attributes, character data, character/entity references,
+// CDATA sections, comments, processing instructions and DOCTYPE declarations
+// (the latter three are skipped). Namespace prefixes are stripped from element
+// and attribute names. DTD validation and custom entities are not supported.
+
+use super::{Node, QosProviderError};
+
+type Result<T> = std::result::Result<T, QosProviderError>;
+
+/// Parse an XML document and return its root element.
+pub(crate) fn parse(input: &str) -> Result<Node> {
+  let mut reader = XmlReader { input, pos: 0 };
+  reader.skip_misc()?;
+  let root = reader.element()?;
+  reader.skip_misc()?;
+  if reader.pos < input.len() {
+    return Err(reader.error("Unexpected content after root element"));
+  }
+  Ok(root)
+}
+
+struct XmlReader<'a> {
+  input: &'a str,
+  pos: usize,
+}
+
+impl<'a> XmlReader<'a> {
+  fn rest(&self) -> &'a str {
+    &self.input[self.pos..]
+  }
+
+  fn error(&self, reason: &str) -> QosProviderError {
+    QosProviderError::Xml {
+      position: self.pos,
+      reason: reason.to_string(),
+    }
+  }
+
+  fn skip_whitespace(&mut self) {
+    let rest = self.rest();
+    self.pos += rest.len() - rest.trim_start().len();
+  }
+
+  // Skip everything up to and including `terminator`.
+  fn skip_past(&mut self, terminator: &str) -> Result<&'a str> {
+    match self.rest().find(terminator) {
+      Some(i) => {
+        let skipped = &self.rest()[..i];
+        self.pos += i + terminator.len();
+        Ok(skipped)
+      }
+      None => Err(self.error(&format!("Missing {:?}", terminator))),
+    }
+  }
+
+  // Skip whitespace, comments, processing instructions and DOCTYPE.
+  fn skip_misc(&mut self) -> Result<()> {
+    loop {
+      self.skip_whitespace();
+      if self.rest().starts_with("<?") {
+        self.skip_past("?>")?;
+      } else if self.rest().starts_with("<!--") {
+        self.skip_past("-->")?;
+      } else if self.rest().starts_with("<!DOCTYPE") {
+        self.skip_past(">")?;
+      } else {
+        return Ok(());
+      }
+    }
+  }
+
+  fn name(&mut self) -> Result<&'a str> {
+    let rest = self.rest();
+    let len = rest
+      .find(|c: char| c.is_whitespace() || c == '>' || c == '/' || c == '=')
+      .unwrap_or(rest.len());
+    if len == 0 {
+      return Err(self.error("Expected a name"));
+    }
+    self.pos += len;
+    let name = &rest[..len];
+    // strip namespace prefix
+    Ok(name.rsplit(':').next().unwrap_or(name))
+  }
+
+  fn expect(&mut self, s: &str) -> Result<()> {
+    if self.rest().starts_with(s) {
+      self.pos += s.len();
+      Ok(())
+    } else {
+      Err(self.error(&format!("Expected {:?}", s)))
+    }
+  }
+
+  fn element(&mut self) -> Result<Node> {
+    self.expect("<")?;
+    let mut node = Node::new(self.name()?);
+
+    // attributes
+    loop {
+      self.skip_whitespace();
+      if self.rest().starts_with("/>") {
+        self.pos += 2;
+        return Ok(node);
+      } else if self.rest().starts_with('>') {
+        self.pos += 1;
+        break;
+      }
+      let name = self.name()?;
+      self.skip_whitespace();
+      self.expect("=")?;
+      self.skip_whitespace();
+      let quote = match self.rest().chars().next() {
+        Some(q) if q == '"' || q == '\'' => q,
+        _ => return Err(self.error("Expected quoted attribute value")),
+      };
+      self.pos += 1;
+      let value = self.skip_past(&quote.to_string())?;
+      let value = self.unescape(value)?;
+      node.children.push(Node::with_text(name, value));
+    }
+
+    // content
+    loop {
+      let rest = self.rest();
+      if rest.starts_with("</") {
+        self.pos += 2;
+        let end_name = self.name()?;
+        if end_name != node.name {
+          return Err(self.error(&format!(
+            "End tag {:?} does not match {:?}",
+            end_name, node.name
+          )));
+        }
+        self.skip_whitespace();
+        self.expect(">")?;
+        node.text = node.text.trim().to_string();
+        return Ok(node);
+      } else if rest.starts_with("<!--") || rest.starts_with("<?") {
+        self.skip_misc()?;
+      } else if rest.starts_with("<![CDATA[") {
+        self.pos += "<![CDATA[".len();
+        let data = self.skip_past("]]>")?;
+        node.text.push_str(data);
+      } else if rest.starts_with('<') {
+        let child = self.element()?;
+        node.children.push(child);
+      } else if rest.is_empty() {
+        return Err(self.error(&format!("Unterminated element {:?}", node.name)));
+      } else {
+        let len = rest.find('<').unwrap_or(rest.len());
+        let text = self.unescape(&rest[..len])?;
+        node.text.push_str(&text);
+        self.pos += len;
+      }
+    }
+  }
+
+  fn unescape(&self, s: &str) -> Result<String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+      result.push_str(&rest[..amp]);
+      let semi = rest[amp..]
+        .find(';')
+        .ok_or_else(|| self.error("Unterminated entity reference"))?;
+      let entity = &rest[amp + 1..amp + semi];
+      let c = match entity {
+        "lt" => '<',
+        "gt" => '>',
+        "amp" => '&',
+        "quot" => '"',
+        "apos" => '\'',
+        _ => {
+          let code = if let Some(hex) = entity.strip_prefix("#x") {
+            u32::from_str_radix(hex, 16).ok()
+          } else if let Some(dec) = entity.strip_prefix('#') {
+            dec.parse().ok()
+          } else {
+            None
+          };
+          code
+            .and_then(std::char::from_u32)
+            .ok_or_else(|| self.error(&format!("Unknown entity &{};", entity)))?
+        }
+      };
+      result.push(c);
+      rest = &rest[amp + semi + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn xml_reader_basics() {
+    let doc = r#"<?xml version="1.0" encoding="UTF-8"?>
+      <!-- comment -->
+      <dds:dds xmlns:dds="http://www.omg.org/dds/">
+        <a name='x &amp; y'>
+          <b>  1 &lt; 2 </b>
+          <!-- another comment -->
+          <c/>
+          <d><![CDATA[<raw>]]></d>
+        </a>
+      </dds:dds>"#;
+    let root = parse(doc).unwrap();
+    assert_eq!(root.name, "dds");
+    let a = root.child("a").unwrap();
+    assert_eq!(a.child_text("name"), Some("x & y"));
+    assert_eq!(a.child_text("b"), Some("1 < 2"));
+    assert!(a.child("c").is_some());
+    assert_eq!(a.child_text("d"), Some("<raw>"));
+
+    assert!(parse("<a><b></a>").is_err());
+    assert!(parse("<a>").is_err());
+    assert!(parse("<a></a><b/>").is_err());
+  }
+}
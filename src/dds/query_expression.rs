@@ -0,0 +1,1417 @@
+// Parsing and evaluation of DDS SQL filter expressions, as used by
+// QueryCondition and ContentFilteredTopic, and of MultiTopic subscription
+// expressions.
+//
+// The grammar is a subset of DDS spec v1.4 Annex B "Syntax for DCPS Queries and
+// Filters": comparisons, BETWEEN, LIKE, AND, OR, NOT and parentheses.
+// Operands are member names (dotted for nested members), literals and
+// parameters %0, %1, ... Member values are obtained by serializing the sample
+// with serde, so any `Serialize` type can be filtered by its member names.
+// When the DynamicType of the samples is known, the members can instead be
+// projected out of serialized samples, without deserializing them.
+
+use std::{cmp::Ordering, convert::TryFrom};
+
+use serde::{
+  de::{
+    self,
+    value::{MapDeserializer, SeqDeserializer},
+    DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor,
+  },
+  forward_to_deserialize_any, ser, Serialize,
+};
+
+use crate::{
+  dds::{
+    dynamic_data::DynamicType,
+    values::result::{Error, Result},
+  },
+  messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
+  serialization::{
+    cdr_projection::{CdrProjection, CdrValue},
+    error::{Error as SerializationError, Result as SerializationResult},
+  },
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expression {
+  And(Box<Expression>, Box<Expression>),
+  Or(Box<Expression>, Box<Expression>),
+  Not(Box<Expression>),
+  Compare(Operand, CompareOp, Operand),
+  Between(Operand, Operand, Operand),
+  Like(Operand, Operand),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CompareOp {
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Operand {
+  Field(Vec<String>),
+  Literal(FieldValue),
+  Parameter(usize),
+}
+
+impl Expression {
+  pub fn parse(expression: &str) -> Result<Self> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let parsed = parser.or_expression()?;
+    match parser.peek() {
+      None => Ok(parsed),
+      Some(token) => Error::bad_parameter(format!(
+        "Unexpected {:?} in filter expression {:?}",
+        token, expression
+      )),
+    }
+  }
+
+  // Number of parameters the expression refers to, i.e. highest index + 1.
+  pub fn parameter_count(&self) -> usize {
+    let operand_count = |op: &Operand| match op {
+      Operand::Parameter(i) => i + 1,
+      _ => 0,
+    };
+    match self {
+      Self::And(a, b) | Self::Or(a, b) => a.parameter_count().max(b.parameter_count()),
+      Self::Not(e) => e.parameter_count(),
+      Self::Compare(a, _, b) | Self::Like(a, b) => operand_count(a).max(operand_count(b)),
+      Self::Between(a, b, c) => operand_count(a).max(operand_count(b)).max(operand_count(c)),
+    }
+  }
+
+  // The distinct member paths the expression refers to
+  pub fn field_paths(&self) -> Vec<Vec<String>> {
+    let mut paths = Vec::new();
+    self.collect_field_paths(&mut paths);
+    paths
+  }
+
+  fn collect_field_paths(&self, paths: &mut Vec<Vec<String>>) {
+    let mut add = |op: &Operand| {
+      if let Operand::Field(path) = op {
+        if !paths.contains(path) {
+          paths.push(path.clone());
+        }
+      }
+    };
+    match self {
+      Self::And(a, b) | Self::Or(a, b) => {
+        a.collect_field_paths(paths);
+        b.collect_field_paths(paths);
+      }
+      Self::Not(e) => e.collect_field_paths(paths),
+      Self::Compare(a, _, b) | Self::Like(a, b) => {
+        add(a);
+        add(b);
+      }
+      Self::Between(a, b, c) => {
+        add(a);
+        add(b);
+        add(c);
+      }
+    }
+  }
+
+  // Comparisons of values of different kinds, or of missing members, are
+  // false, also when negated with <>.
+  pub fn evaluate(&self, sample: &FieldValue, parameters: &[FieldValue]) -> bool {
+    let resolve = |op: &Operand| -> Option<FieldValue> {
+      match op {
+        Operand::Field(path) => sample.member(path).cloned(),
+        Operand::Literal(v) => Some(v.clone()),
+        Operand::Parameter(i) => parameters.get(*i).cloned(),
+      }
+    };
+    let ordering = |a: &Operand, b: &Operand| -> Option<Ordering> {
+      FieldValue::compare(&resolve(a)?, &resolve(b)?)
+    };
+    match self {
+      Self::And(a, b) => a.evaluate(sample, parameters) && b.evaluate(sample, parameters),
+      Self::Or(a, b) => a.evaluate(sample, parameters) || b.evaluate(sample, parameters),
+      Self::Not(e) => !e.evaluate(sample, parameters),
+      Self::Compare(a, op, b) => ordering(a, b).map_or(false, |ord| match op {
+        CompareOp::Eq => ord == Ordering::Equal,
+        CompareOp::Ne => ord != Ordering::Equal,
+        CompareOp::Lt => ord == Ordering::Less,
+        CompareOp::Le => ord != Ordering::Greater,
+        CompareOp::Gt => ord == Ordering::Greater,
+        CompareOp::Ge => ord != Ordering::Less,
+      }),
+      Self::Between(a, low, high) => {
+        ordering(a, low).map_or(false, |ord| ord != Ordering::Less)
+          && ordering(a, high).map_or(false, |ord| ord != Ordering::Greater)
+      }
+      Self::Like(a, pattern) => match (resolve(a), resolve(pattern)) {
+        (Some(FieldValue::Str(s)), Some(FieldValue::Str(p))) => like(
+          &s.chars().collect::<Vec<_>>(),
+          &p.chars().collect::<Vec<_>>(),
+        ),
+        _ => false,
+      },
+    }
+  }
+}
+
+// A parsed filter expression together with the values of its parameters.
+#[derive(Debug, Clone)]
+pub(crate) struct SampleFilter {
+  expression_text: String,
+  expression: Expression,
+  parameters: Vec<FieldValue>,
+  projection: Option<FilterProjection>,
+}
+
+// The members that a filter expression refers to, and how to project them out
+// of serialized samples.
+#[derive(Debug, Clone)]
+struct FilterProjection {
+  paths: Vec<Vec<String>>,
+  projection: CdrProjection,
+}
+
+impl SampleFilter {
+  pub fn new(expression: &str, parameters: &[String]) -> Result<Self> {
+    let mut filter = Self {
+      expression_text: expression.to_string(),
+      expression: Expression::parse(expression)?,
+      parameters: Vec::new(),
+      projection: None,
+    };
+    filter.set_parameters(parameters)?;
+    Ok(filter)
+  }
+
+  pub fn expression(&self) -> &str {
+    &self.expression_text
+  }
+
+  // Fails if there are fewer parameters than the expression refers to.
+  pub fn set_parameters(&mut self, parameters: &[String]) -> Result<()> {
+    let needed = self.expression.parameter_count();
+    if parameters.len() < needed {
+      return Error::bad_parameter(format!(
+        "Filter expression {:?} needs {} parameters, got {}",
+        self.expression_text,
+        needed,
+        parameters.len()
+      ));
+    }
+    self.parameters = parameters
+      .iter()
+      .map(|p| FieldValue::from_parameter(p))
+      .collect();
+    Ok(())
+  }
+
+  // Samples that cannot be evaluated do not match.
+  pub fn matches<T: Serialize + ?Sized>(&self, sample: &T) -> bool {
+    FieldValue::from_sample(sample).map_or(false, |value| self.matches_value(&value))
+  }
+
+  // For evaluating several filters on the same sample, which is then
+  // serialized only once.
+  pub fn matches_value(&self, sample: &FieldValue) -> bool {
+    self.expression.evaluate(sample, &self.parameters)
+  }
+
+  // Lets the filter evaluate serialized samples of `data_type` by decoding
+  // only the members the expression refers to. If some of them cannot be
+  // projected, e.g. because they are sequences or union members, the filter
+  // is left without a projection, and samples must be deserialized.
+  pub fn set_dynamic_type(&mut self, data_type: &DynamicType) {
+    let paths = self.expression.field_paths();
+    // Array elements are not members in filter expressions.
+    if paths
+      .iter()
+      .flatten()
+      .any(|name| name.parse::<usize>().is_ok())
+    {
+      self.projection = None;
+      return;
+    }
+    let dotted: Vec<String> = paths.iter().map(|path| path.join(".")).collect();
+    let dotted: Vec<&str> = dotted.iter().map(String::as_str).collect();
+    self.projection = CdrProjection::new(data_type, &dotted)
+      .ok()
+      .map(|projection| FilterProjection { paths, projection });
+  }
+
+  pub fn has_projection(&self) -> bool {
+    self.projection.is_some()
+  }
+
+  // Evaluates the filter on a serialized sample. None if the filter has no
+  // projection, or the sample cannot be decoded.
+  pub fn matches_serialized(
+    &self,
+    payload: &[u8],
+    encoding: RepresentationIdentifier,
+  ) -> Option<bool> {
+    let projected = self.projection.as_ref()?;
+    let values = projected.projection.project(payload, encoding).ok()?;
+    let mut sample = FieldValue::Struct(Vec::new());
+    for (path, value) in projected.paths.iter().zip(values) {
+      sample.insert(path, FieldValue::from(value));
+    }
+    Some(self.matches_value(&sample))
+  }
+}
+
+// A MultiTopic subscription expression:
+// SELECT members FROM Topic [NATURAL JOIN Topic ...] [WHERE filter]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SubscriptionExpression {
+  // Selected members, and their names in the result. None selects all, i.e. *.
+  pub selection: Option<Vec<(Vec<String>, String)>>,
+  pub topics: Vec<String>,
+  pub filter: Option<Expression>,
+}
+
+impl SubscriptionExpression {
+  pub fn parse(expression: &str) -> Result<Self> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser { tokens, pos: 0 };
+
+    parser.expect_keyword("SELECT")?;
+    let selection = if parser.accept(&Token::Star) {
+      None
+    } else {
+      let mut selection = Vec::new();
+      loop {
+        let member = parser.name()?;
+        let path: Vec<String> = member.split('.').map(String::from).collect();
+        // The name in the result is given by "member AS name" or
+        // "member name", and defaults to the last part of the member name.
+        let alias = if parser.accept_keyword("AS")
+          || (matches!(parser.peek(), Some(Token::Ident(_))) && !parser.peek_keyword("FROM"))
+        {
+          parser.name()?
+        } else {
+          path.last().cloned().unwrap_or_default()
+        };
+        selection.push((path, alias));
+        if !parser.accept(&Token::Comma) {
+          break;
+        }
+      }
+      Some(selection)
+    };
+
+    parser.expect_keyword("FROM")?;
+    let mut topics = vec![parser.name()?];
+    // Joins may be written as NATURAL JOIN, INNER NATURAL JOIN or
+    // NATURAL INNER JOIN.
+    loop {
+      let inner = parser.accept_keyword("INNER");
+      if !parser.accept_keyword("NATURAL") {
+        if inner {
+          return Error::bad_parameter(format!(
+            "Expected NATURAL after INNER in subscription expression {:?}",
+            expression
+          ));
+        }
+        break;
+      }
+      if !inner {
+        parser.accept_keyword("INNER");
+      }
+      parser.expect_keyword("JOIN")?;
+      topics.push(parser.name()?);
+    }
+
+    let filter = if parser.accept_keyword("WHERE") {
+      Some(parser.or_expression()?)
+    } else {
+      None
+    };
+    match parser.peek() {
+      None => Ok(Self {
+        selection,
+        topics,
+        filter,
+      }),
+      Some(token) => Error::bad_parameter(format!(
+        "Unexpected {:?} in subscription expression {:?}",
+        token, expression
+      )),
+    }
+  }
+
+  pub fn parameter_count(&self) -> usize {
+    self.filter.as_ref().map_or(0, Expression::parameter_count)
+  }
+}
+
+// SQL LIKE: '%' matches any sequence of characters, '_' any single character.
+fn like(s: &[char], pattern: &[char]) -> bool {
+  match pattern.split_first() {
+    None => s.is_empty(),
+    Some((&'%', rest)) => (0..=s.len()).any(|skip| like(&s[skip..], rest)),
+    Some((p, rest)) => match s.split_first() {
+      Some((c, s_rest)) if *p == '_' || p == c => like(s_rest, rest),
+      _ => false,
+    },
+  }
+}
+
+// ---------------------------------------------------------------------------
+// Tokenizer and parser
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Ident(String),
+  Literal(FieldValue),
+  Parameter(usize),
+  Compare(CompareOp),
+  LParen,
+  RParen,
+  Comma,
+  Star,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+  let chars: Vec<char> = input.chars().collect();
+  let mut tokens = Vec::new();
+  let mut i = 0;
+  let take_while = |i: &mut usize, pred: &dyn Fn(char) -> bool| -> String {
+    let start = *i;
+    while *i < chars.len() && pred(chars[*i]) {
+      *i += 1;
+    }
+    chars[start..*i].iter().collect()
+  };
+
+  while i < chars.len() {
+    let c = chars[i];
+    let next = chars.get(i + 1).copied();
+    match c {
+      c if c.is_whitespace() => i += 1,
+      '(' => {
+        tokens.push(Token::LParen);
+        i += 1;
+      }
+      ')' => {
+        tokens.push(Token::RParen);
+        i += 1;
+      }
+      ',' => {
+        tokens.push(Token::Comma);
+        i += 1;
+      }
+      '*' => {
+        tokens.push(Token::Star);
+        i += 1;
+      }
+      '%' => {
+        i += 1;
+        let digits = take_while(&mut i, &|c| c.is_ascii_digit());
+        let index = digits
+          .parse()
+          .map_err(|_| bad_expression(input, "parameter number expected after '%'"))?;
+        tokens.push(Token::Parameter(index));
+      }
+      '\'' | '`' | '"' => {
+        // DDS spec quotes strings as `string' or 'string'. Accept also "string".
+        let quote = if c == '"' { '"' } else { '\'' };
+        i += 1;
+        let s = take_while(&mut i, &|c| c != quote);
+        if i >= chars.len() {
+          return Err(bad_expression(input, "unterminated string"));
+        }
+        i += 1;
+        tokens.push(Token::Literal(FieldValue::Str(s)));
+      }
+      '=' | '<' | '>' | '!' => {
+        let (op, len) = match (c, next) {
+          ('=', Some('=')) => (CompareOp::Eq, 2),
+          ('=', _) => (CompareOp::Eq, 1),
+          ('<', Some('>')) | ('!', Some('=')) => (CompareOp::Ne, 2),
+          ('<', Some('=')) => (CompareOp::Le, 2),
+          ('<', _) => (CompareOp::Lt, 1),
+          ('>', Some('=')) => (CompareOp::Ge, 2),
+          ('>', _) => (CompareOp::Gt, 1),
+          _ => return Err(bad_expression(input, "unknown operator")),
+        };
+        tokens.push(Token::Compare(op));
+        i += len;
+      }
+      c if c.is_ascii_digit()
+        || ((c == '-' || c == '+') && next.map_or(false, |n| n.is_ascii_digit())) =>
+      {
+        i += 1;
+        let rest = take_while(&mut i, &|c| {
+          c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+'
+        });
+        let number = format!("{}{}", c, rest);
+        let value = FieldValue::parse_number(&number)
+          .ok_or_else(|| bad_expression(input, &format!("bad number {:?}", number)))?;
+        tokens.push(Token::Literal(value));
+      }
+      c if c.is_alphabetic() || c == '_' => {
+        let ident = take_while(&mut i, &|c| c.is_alphanumeric() || c == '_' || c == '.');
+        tokens.push(Token::Ident(ident));
+      }
+      _ => {
+        return Err(bad_expression(
+          input,
+          &format!("unexpected character {:?}", c),
+        ))
+      }
+    }
+  }
+  Ok(tokens)
+}
+
+fn bad_expression(expression: &str, reason: &str) -> Error {
+  Error::BadParameter {
+    reason: format!("Filter expression {:?}: {}", expression, reason),
+  }
+}
+
+struct Parser {
+  tokens: Vec<Token>,
+  pos: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.pos)
+  }
+
+  fn next_token(&mut self) -> Option<Token> {
+    let token = self.tokens.get(self.pos).cloned();
+    self.pos += 1;
+    token
+  }
+
+  fn accept(&mut self, token: &Token) -> bool {
+    let found = self.peek() == Some(token);
+    if found {
+      self.pos += 1;
+    }
+    found
+  }
+
+  fn name(&mut self) -> Result<String> {
+    match self.next_token() {
+      Some(Token::Ident(s)) => Ok(s),
+      other => Error::bad_parameter(format!("Expected a name in expression, found {:?}", other)),
+    }
+  }
+
+  fn peek_keyword(&self, keyword: &str) -> bool {
+    matches!(self.peek(), Some(Token::Ident(s)) if s.eq_ignore_ascii_case(keyword))
+  }
+
+  fn accept_keyword(&mut self, keyword: &str) -> bool {
+    let found = self.peek_keyword(keyword);
+    if found {
+      self.pos += 1;
+    }
+    found
+  }
+
+  fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+    if self.accept_keyword(keyword) {
+      Ok(())
+    } else {
+      Error::bad_parameter(format!(
+        "Expected {} in filter expression, found {:?}",
+        keyword,
+        self.peek()
+      ))
+    }
+  }
+
+  fn or_expression(&mut self) -> Result<Expression> {
+    let mut expr = self.and_expression()?;
+    while self.accept_keyword("OR") {
+      expr = Expression::Or(Box::new(expr), Box::new(self.and_expression()?));
+    }
+    Ok(expr)
+  }
+
+  fn and_expression(&mut self) -> Result<Expression> {
+    let mut expr = self.not_expression()?;
+    while self.accept_keyword("AND") {
+      expr = Expression::And(Box::new(expr), Box::new(self.not_expression()?));
+    }
+    Ok(expr)
+  }
+
+  fn not_expression(&mut self) -> Result<Expression> {
+    if self.accept_keyword("NOT") {
+      Ok(Expression::Not(Box::new(self.not_expression()?)))
+    } else if self.peek() == Some(&Token::LParen) {
+      self.pos += 1;
+      let expr = self.or_expression()?;
+      match self.next_token() {
+        Some(Token::RParen) => Ok(expr),
+        other => Error::bad_parameter(format!(
+          "Expected ')' in filter expression, found {:?}",
+          other
+        )),
+      }
+    } else {
+      self.predicate()
+    }
+  }
+
+  fn predicate(&mut self) -> Result<Expression> {
+    let left = self.operand()?;
+    let negated = self.accept_keyword("NOT");
+    let expr = if self.accept_keyword("BETWEEN") {
+      let low = self.operand()?;
+      self.expect_keyword("AND")?;
+      let high = self.operand()?;
+      Expression::Between(left, low, high)
+    } else if self.accept_keyword("LIKE") {
+      Expression::Like(left, self.operand()?)
+    } else if negated {
+      return Error::bad_parameter("Expected BETWEEN or LIKE after NOT in filter expression");
+    } else {
+      match self.next_token() {
+        Some(Token::Compare(op)) => Expression::Compare(left, op, self.operand()?),
+        other => {
+          return Error::bad_parameter(format!(
+            "Expected comparison operator in filter expression, found {:?}",
+            other
+          ))
+        }
+      }
+    };
+    Ok(if negated {
+      Expression::Not(Box::new(expr))
+    } else {
+      expr
+    })
+  }
+
+  fn operand(&mut self) -> Result<Operand> {
+    match self.next_token() {
+      Some(Token::Ident(s)) if s.eq_ignore_ascii_case("TRUE") => {
+        Ok(Operand::Literal(FieldValue::Bool(true)))
+      }
+      Some(Token::Ident(s)) if s.eq_ignore_ascii_case("FALSE") => {
+        Ok(Operand::Literal(FieldValue::Bool(false)))
+      }
+      Some(Token::Ident(s)) => Ok(Operand::Field(s.split('.').map(String::from).collect())),
+      Some(Token::Literal(v)) => Ok(Operand::Literal(v)),
+      Some(Token::Parameter(i)) => Ok(Operand::Parameter(i)),
+      other => Error::bad_parameter(format!(
+        "Expected member name, value or parameter in filter expression, found {:?}",
+        other
+      )),
+    }
+  }
+}
+
+// ---------------------------------------------------------------------------
+// Sample values
+
+// Generic representation of a serialized sample. Enumerations without data are
+// represented by the name of the variant, so they can be compared to strings.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum FieldValue {
+  Null,
+  Bool(bool),
+  Int(i128),
+  Float(f64),
+  Str(String),
+  Seq(Vec<FieldValue>),
+  Struct(Vec<(String, FieldValue)>),
+}
+
+impl FieldValue {
+  pub fn from_sample<T: Serialize + ?Sized>(sample: &T) -> SerializationResult<Self> {
+    sample.serialize(ValueSerializer)
+  }
+
+  // Query parameters are strings, but may stand for any kind of value.
+  pub fn from_parameter(parameter: &str) -> Self {
+    let trimmed = parameter.trim();
+    if let Some(number) = Self::parse_number(trimmed) {
+      return number;
+    }
+    if trimmed.eq_ignore_ascii_case("TRUE") {
+      return Self::Bool(true);
+    }
+    if trimmed.eq_ignore_ascii_case("FALSE") {
+      return Self::Bool(false);
+    }
+    let unquoted = trimmed
+      .strip_prefix('\'')
+      .and_then(|s| s.strip_suffix('\''))
+      .unwrap_or(trimmed);
+    Self::Str(unquoted.to_string())
+  }
+
+  fn parse_number(s: &str) -> Option<Self> {
+    let unsigned = s.trim_start_matches(|c| c == '-' || c == '+');
+    if let Some(hex) = unsigned
+      .strip_prefix("0x")
+      .or_else(|| unsigned.strip_prefix("0X"))
+    {
+      let value = i128::from_str_radix(hex, 16).ok()?;
+      return Some(Self::Int(if s.starts_with('-') { -value } else { value }));
+    }
+    s.parse::<i128>()
+      .map(Self::Int)
+      .ok()
+      .or_else(|| s.parse::<f64>().ok().map(Self::Float))
+  }
+
+  pub fn member(&self, path: &[String]) -> Option<&FieldValue> {
+    match path.split_first() {
+      None => Some(self),
+      Some((name, rest)) => match self {
+        Self::Struct(members) => members
+          .iter()
+          .find(|(member_name, _)| member_name == name)
+          .and_then(|(_, value)| value.member(rest)),
+        _ => None,
+      },
+    }
+  }
+
+  // Sets a member of a struct, adding the structs along the path as needed.
+  fn insert(&mut self, path: &[String], value: FieldValue) {
+    match path.split_first() {
+      None => *self = value,
+      Some((name, rest)) => {
+        if let Self::Struct(members) = self {
+          let index = match members
+            .iter()
+            .position(|(member_name, _)| member_name == name)
+          {
+            Some(index) => index,
+            None => {
+              members.push((name.clone(), Self::Struct(Vec::new())));
+              members.len() - 1
+            }
+          };
+          members[index].1.insert(rest, value);
+        }
+      }
+    }
+  }
+
+  // Converts back to a sample, which may be of another type than the one the
+  // value was made from, as long as the member names and kinds agree.
+  pub fn into_sample<D: DeserializeOwned>(self) -> SerializationResult<D> {
+    D::deserialize(self)
+  }
+
+  fn compare(a: &FieldValue, b: &FieldValue) -> Option<Ordering> {
+    match (a, b) {
+      (Self::Int(a), Self::Int(b)) => Some(a.cmp(b)),
+      (Self::Int(a), Self::Float(b)) => (*a as f64).partial_cmp(b),
+      (Self::Float(a), Self::Int(b)) => a.partial_cmp(&(*b as f64)),
+      (Self::Float(a), Self::Float(b)) => a.partial_cmp(b),
+      (Self::Str(a), Self::Str(b)) => Some(a.cmp(b)),
+      (Self::Bool(a), Self::Bool(b)) => Some(a.cmp(b)),
+      _ => None,
+    }
+  }
+}
+
+// Projected members get the same values as when serializing the sample.
+impl From<CdrValue> for FieldValue {
+  fn from(value: CdrValue) -> Self {
+    match value {
+      CdrValue::Bool(v) => Self::Bool(v),
+      CdrValue::I8(v) => Self::Int(v.into()),
+      CdrValue::U8(v) => Self::Int(v.into()),
+      CdrValue::I16(v) => Self::Int(v.into()),
+      CdrValue::U16(v) => Self::Int(v.into()),
+      CdrValue::I32(v) => Self::Int(v.into()),
+      CdrValue::U32(v) => Self::Int(v.into()),
+      CdrValue::I64(v) => Self::Int(v.into()),
+      CdrValue::U64(v) => Self::Int(v.into()),
+      CdrValue::F32(v) => Self::Float(v.into()),
+      CdrValue::F64(v) => Self::Float(v),
+      CdrValue::Char(v) => Self::Str(v.to_string()),
+      CdrValue::String(v) => Self::Str(v),
+      CdrValue::Enum { name, .. } => Self::Str(name),
+    }
+  }
+}
+
+struct ValueSerializer;
+
+struct SeqBuilder {
+  variant: Option<&'static str>,
+  items: Vec<FieldValue>,
+}
+
+struct StructBuilder {
+  variant: Option<&'static str>,
+  members: Vec<(String, FieldValue)>,
+  pending_key: Option<String>,
+}
+
+fn wrap_variant(variant: Option<&'static str>, value: FieldValue) -> FieldValue {
+  match variant {
+    Some(name) => FieldValue::Struct(vec![(name.to_string(), value)]),
+    None => value,
+  }
+}
+
+impl ser::Serializer for ValueSerializer {
+  type Ok = FieldValue;
+  type Error = SerializationError;
+
+  type SerializeSeq = SeqBuilder;
+  type SerializeTuple = SeqBuilder;
+  type SerializeTupleStruct = SeqBuilder;
+  type SerializeTupleVariant = SeqBuilder;
+  type SerializeMap = StructBuilder;
+  type SerializeStruct = StructBuilder;
+  type SerializeStructVariant = StructBuilder;
+
+  fn serialize_bool(self, v: bool) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Bool(v))
+  }
+  fn serialize_i8(self, v: i8) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Int(v.into()))
+  }
+  fn serialize_i16(self, v: i16) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Int(v.into()))
+  }
+  fn serialize_i32(self, v: i32) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Int(v.into()))
+  }
+  fn serialize_i64(self, v: i64) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Int(v.into()))
+  }
+  fn serialize_i128(self, v: i128) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Int(v))
+  }
+  fn serialize_u8(self, v: u8) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Int(v.into()))
+  }
+  fn serialize_u16(self, v: u16) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Int(v.into()))
+  }
+  fn serialize_u32(self, v: u32) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Int(v.into()))
+  }
+  fn serialize_u64(self, v: u64) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Int(v.into()))
+  }
+  fn serialize_f32(self, v: f32) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Float(v.into()))
+  }
+  fn serialize_f64(self, v: f64) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Float(v))
+  }
+  fn serialize_char(self, v: char) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Str(v.to_string()))
+  }
+  fn serialize_str(self, v: &str) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Str(v.to_string()))
+  }
+  fn serialize_bytes(self, v: &[u8]) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Seq(
+      v.iter().map(|b| FieldValue::Int((*b).into())).collect(),
+    ))
+  }
+  fn serialize_none(self) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Null)
+  }
+  fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> SerializationResult<FieldValue> {
+    value.serialize(self)
+  }
+  fn serialize_unit(self) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Null)
+  }
+  fn serialize_unit_struct(self, _name: &'static str) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Null)
+  }
+  fn serialize_unit_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+  ) -> SerializationResult<FieldValue> {
+    Ok(FieldValue::Str(variant.to_string()))
+  }
+  fn serialize_newtype_struct<T: ?Sized + Serialize>(
+    self,
+    _name: &'static str,
+    value: &T,
+  ) -> SerializationResult<FieldValue> {
+    value.serialize(self)
+  }
+  fn serialize_newtype_variant<T: ?Sized + Serialize>(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    value: &T,
+  ) -> SerializationResult<FieldValue> {
+    Ok(wrap_variant(Some(variant), value.serialize(self)?))
+  }
+  fn serialize_seq(self, len: Option<usize>) -> SerializationResult<SeqBuilder> {
+    Ok(SeqBuilder {
+      variant: None,
+      items: Vec::with_capacity(len.unwrap_or(0)),
+    })
+  }
+  fn serialize_tuple(self, len: usize) -> SerializationResult<SeqBuilder> {
+    self.serialize_seq(Some(len))
+  }
+  fn serialize_tuple_struct(
+    self,
+    _name: &'static str,
+    len: usize,
+  ) -> SerializationResult<SeqBuilder> {
+    self.serialize_seq(Some(len))
+  }
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    len: usize,
+  ) -> SerializationResult<SeqBuilder> {
+    Ok(SeqBuilder {
+      variant: Some(variant),
+      items: Vec::with_capacity(len),
+    })
+  }
+  fn serialize_map(self, len: Option<usize>) -> SerializationResult<StructBuilder> {
+    Ok(StructBuilder {
+      variant: None,
+      members: Vec::with_capacity(len.unwrap_or(0)),
+      pending_key: None,
+    })
+  }
+  fn serialize_struct(self, _name: &'static str, len: usize) -> SerializationResult<StructBuilder> {
+    self.serialize_map(Some(len))
+  }
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    len: usize,
+  ) -> SerializationResult<StructBuilder> {
+    Ok(StructBuilder {
+      variant: Some(variant),
+      members: Vec::with_capacity(len),
+      pending_key: None,
+    })
+  }
+}
+
+impl SeqBuilder {
+  fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> SerializationResult<()> {
+    self.items.push(value.serialize(ValueSerializer)?);
+    Ok(())
+  }
+
+  fn finish(self) -> SerializationResult<FieldValue> {
+    Ok(wrap_variant(self.variant, FieldValue::Seq(self.items)))
+  }
+}
+
+impl ser::SerializeSeq for SeqBuilder {
+  type Ok = FieldValue;
+  type Error = SerializationError;
+  fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> SerializationResult<()> {
+    self.push(value)
+  }
+  fn end(self) -> SerializationResult<FieldValue> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeTuple for SeqBuilder {
+  type Ok = FieldValue;
+  type Error = SerializationError;
+  fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> SerializationResult<()> {
+    self.push(value)
+  }
+  fn end(self) -> SerializationResult<FieldValue> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeTupleStruct for SeqBuilder {
+  type Ok = FieldValue;
+  type Error = SerializationError;
+  fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> SerializationResult<()> {
+    self.push(value)
+  }
+  fn end(self) -> SerializationResult<FieldValue> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeTupleVariant for SeqBuilder {
+  type Ok = FieldValue;
+  type Error = SerializationError;
+  fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> SerializationResult<()> {
+    self.push(value)
+  }
+  fn end(self) -> SerializationResult<FieldValue> {
+    self.finish()
+  }
+}
+
+impl StructBuilder {
+  fn push<T: ?Sized + Serialize>(&mut self, name: String, value: &T) -> SerializationResult<()> {
+    self.members.push((name, value.serialize(ValueSerializer)?));
+    Ok(())
+  }
+
+  fn finish(self) -> SerializationResult<FieldValue> {
+    Ok(wrap_variant(self.variant, FieldValue::Struct(self.members)))
+  }
+}
+
+impl ser::SerializeMap for StructBuilder {
+  type Ok = FieldValue;
+  type Error = SerializationError;
+  fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> SerializationResult<()> {
+    // Map entries become members, so that they can be referred to by key.
+    let name = match key.serialize(ValueSerializer)? {
+      FieldValue::Str(s) => s,
+      FieldValue::Int(i) => i.to_string(),
+      FieldValue::Bool(b) => b.to_string(),
+      other => format!("{:?}", other),
+    };
+    self.pending_key = Some(name);
+    Ok(())
+  }
+  fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> SerializationResult<()> {
+    let name = self.pending_key.take().unwrap_or_default();
+    self.push(name, value)
+  }
+  fn end(self) -> SerializationResult<FieldValue> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeStruct for StructBuilder {
+  type Ok = FieldValue;
+  type Error = SerializationError;
+  fn serialize_field<T: ?Sized + Serialize>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> SerializationResult<()> {
+    self.push(key.to_string(), value)
+  }
+  fn end(self) -> SerializationResult<FieldValue> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeStructVariant for StructBuilder {
+  type Ok = FieldValue;
+  type Error = SerializationError;
+  fn serialize_field<T: ?Sized + Serialize>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> SerializationResult<()> {
+    self.push(key.to_string(), value)
+  }
+  fn end(self) -> SerializationResult<FieldValue> {
+    self.finish()
+  }
+}
+
+// ---------------------------------------------------------------------------
+// Sample values back to samples
+
+impl<'de> de::Deserializer<'de> for FieldValue {
+  type Error = SerializationError;
+
+  fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> SerializationResult<V::Value> {
+    match self {
+      Self::Null => visitor.visit_unit(),
+      Self::Bool(b) => visitor.visit_bool(b),
+      Self::Int(i) => match (i64::try_from(i), u64::try_from(i)) {
+        (Ok(i), _) => visitor.visit_i64(i),
+        (_, Ok(u)) => visitor.visit_u64(u),
+        _ => visitor.visit_i128(i),
+      },
+      Self::Float(f) => visitor.visit_f64(f),
+      Self::Str(s) => visitor.visit_string(s),
+      Self::Seq(items) => {
+        let mut seq = SeqDeserializer::new(items.into_iter());
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+      }
+      Self::Struct(members) => {
+        let mut map = MapDeserializer::new(members.into_iter());
+        let value = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(value)
+      }
+    }
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> SerializationResult<V::Value> {
+    match self {
+      Self::Null => visitor.visit_none(),
+      other => visitor.visit_some(other),
+    }
+  }
+
+  fn deserialize_newtype_struct<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    visitor: V,
+  ) -> SerializationResult<V::Value> {
+    visitor.visit_newtype_struct(self)
+  }
+
+  // Enumerators without data are strings, others a struct of one member,
+  // named by the variant.
+  fn deserialize_enum<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    _variants: &'static [&'static str],
+    visitor: V,
+  ) -> SerializationResult<V::Value> {
+    match self {
+      Self::Str(variant) => visitor.visit_enum(VariantValue {
+        variant,
+        value: Self::Null,
+      }),
+      Self::Struct(mut members) if members.len() == 1 => {
+        let (variant, value) = members.remove(0);
+        visitor.visit_enum(VariantValue { variant, value })
+      }
+      other => Err(de::Error::custom(format!(
+        "Expected enumeration, found {:?}",
+        other
+      ))),
+    }
+  }
+
+  forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+    unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+  }
+}
+
+impl IntoDeserializer<'_, SerializationError> for FieldValue {
+  type Deserializer = Self;
+
+  fn into_deserializer(self) -> Self {
+    self
+  }
+}
+
+struct VariantValue {
+  variant: String,
+  value: FieldValue,
+}
+
+impl<'de> de::EnumAccess<'de> for VariantValue {
+  type Error = SerializationError;
+  type Variant = FieldValue;
+
+  fn variant_seed<V: DeserializeSeed<'de>>(
+    self,
+    seed: V,
+  ) -> SerializationResult<(V::Value, FieldValue)> {
+    let variant = seed.deserialize(FieldValue::Str(self.variant))?;
+    Ok((variant, self.value))
+  }
+}
+
+impl<'de> de::VariantAccess<'de> for FieldValue {
+  type Error = SerializationError;
+
+  fn unit_variant(self) -> SerializationResult<()> {
+    Ok(())
+  }
+
+  fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> SerializationResult<T::Value> {
+    seed.deserialize(self)
+  }
+
+  fn tuple_variant<V: Visitor<'de>>(
+    self,
+    _len: usize,
+    visitor: V,
+  ) -> SerializationResult<V::Value> {
+    de::Deserializer::deserialize_any(self, visitor)
+  }
+
+  fn struct_variant<V: Visitor<'de>>(
+    self,
+    _fields: &'static [&'static str],
+    visitor: V,
+  ) -> SerializationResult<V::Value> {
+    de::Deserializer::deserialize_any(self, visitor)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde::Deserialize;
+
+  use super::*;
+  use crate::{
+    dds::dynamic_data::{DynamicMember, DynamicUnionCase},
+    serialization::{cdr_serializer::to_bytes, LittleEndian},
+  };
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq)]
+  enum Color {
+    Red,
+    #[allow(dead_code)] // not constructed
+    Blue,
+    Custom(u8, u8, u8),
+  }
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq)]
+  struct Point {
+    x: i32,
+    y: f64,
+  }
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq)]
+  struct Shape {
+    name: String,
+    color: Color,
+    position: Point,
+    size: u32,
+    filled: bool,
+  }
+
+  #[test]
+  fn query_expression_evaluation() {
+    let shape = FieldValue::from_sample(&Shape {
+      name: "Square".to_string(),
+      color: Color::Red,
+      position: Point { x: 10, y: -2.5 },
+      size: 30,
+      filled: true,
+    })
+    .unwrap();
+    let parameters = vec![
+      FieldValue::from_parameter("25"),
+      FieldValue::from_parameter("'Red'"),
+    ];
+    let eval = |expression: &str| {
+      Expression::parse(expression)
+        .unwrap()
+        .evaluate(&shape, &parameters)
+    };
+
+    assert!(eval("size > 20"));
+    assert!(eval("size >= %0 AND color = %1"));
+    assert!(eval(
+      "color = 'Red' AND NOT (position.x < 0 OR filled = FALSE)"
+    ));
+    assert!(eval("position.y BETWEEN -3 AND -2.0"));
+    assert!(eval("size NOT BETWEEN 0 AND %0"));
+    assert!(eval("name LIKE 'Sq%e'"));
+    assert!(eval("name LIKE '_quare'"));
+    assert!(!eval("name LIKE 'Sq'"));
+    assert!(eval("position.x <> 11"));
+    assert!(eval("size = 0x1E"));
+    // unknown members and mismatched kinds never match
+    assert!(!eval("missing = 1"));
+    assert!(!eval("missing <> 1"));
+    assert!(!eval("name > 1"));
+
+    for bad in &[
+      "size >",
+      "size 20",
+      "(size > 20",
+      "size > 20 20",
+      "size ~ 2",
+      "%x = 1",
+    ] {
+      assert!(Expression::parse(bad).is_err(), "{}", bad);
+    }
+    assert_eq!(
+      Expression::parse("a = %0 OR b BETWEEN %3 AND 1")
+        .unwrap()
+        .parameter_count(),
+      4
+    );
+  }
+
+  fn shape_type() -> DynamicType {
+    DynamicType::structure(
+      "Shape",
+      vec![
+        DynamicMember::new("name", DynamicType::String),
+        DynamicMember::new(
+          "color",
+          DynamicType::union(
+            "Color",
+            vec![DynamicUnionCase::new(
+              2,
+              "Custom",
+              DynamicType::array(DynamicType::U8, 3),
+            )],
+          ),
+        ),
+        DynamicMember::new(
+          "position",
+          DynamicType::structure(
+            "Point",
+            vec![
+              DynamicMember::new("x", DynamicType::I32),
+              DynamicMember::new("y", DynamicType::F64),
+            ],
+          ),
+        ),
+        DynamicMember::new("size", DynamicType::U32),
+        DynamicMember::new("filled", DynamicType::Bool),
+      ],
+    )
+  }
+
+  #[test]
+  fn sample_filter_on_serialized_samples() {
+    let shapes = [
+      Shape {
+        name: "Square".to_string(),
+        color: Color::Custom(1, 2, 3),
+        position: Point { x: 10, y: -2.5 },
+        size: 30,
+        filled: true,
+      },
+      Shape {
+        name: "Circle".to_string(),
+        color: Color::Red,
+        position: Point { x: -1, y: 4.0 },
+        size: 10,
+        filled: false,
+      },
+    ];
+    let mut filter = SampleFilter::new(
+      "size > %0 AND position.y < 0 OR name LIKE 'Ci%' AND filled = FALSE",
+      &["20".to_string()],
+    )
+    .unwrap();
+    assert!(filter
+      .matches_serialized(&[], RepresentationIdentifier::CDR_LE)
+      .is_none());
+    filter.set_dynamic_type(&shape_type());
+    assert!(filter.has_projection());
+
+    for (shape, parameter) in shapes.iter().zip(&["20", "40"]) {
+      filter.set_parameters(&[parameter.to_string()]).unwrap();
+      let bytes = to_bytes::<Shape, LittleEndian>(shape).unwrap();
+      assert!(filter.matches(shape));
+      assert_eq!(
+        filter.matches_serialized(&bytes, RepresentationIdentifier::CDR_LE),
+        Some(true)
+      );
+      filter.set_parameters(&["100".to_string()]).unwrap();
+      assert_eq!(
+        filter.matches_serialized(&bytes, RepresentationIdentifier::CDR_LE),
+        Some(filter.matches(shape))
+      );
+      assert_eq!(
+        filter.matches_serialized(&bytes[..8], RepresentationIdentifier::CDR_LE),
+        None
+      );
+    }
+
+    // union members cannot be projected
+    let mut filter = SampleFilter::new("color = 'Red'", &[]).unwrap();
+    filter.set_dynamic_type(&shape_type());
+    assert!(!filter.has_projection());
+  }
+
+  #[test]
+  fn subscription_expression_parsing() {
+    let parsed = SubscriptionExpression::parse(
+      "SELECT flight_id, position.x AS x, position.y y FROM Flight NATURAL JOIN \
+       Position INNER NATURAL JOIN Plan WHERE x > %1",
+    )
+    .unwrap();
+    assert_eq!(
+      parsed.selection,
+      Some(vec![
+        (vec!["flight_id".to_string()], "flight_id".to_string()),
+        (
+          vec!["position".to_string(), "x".to_string()],
+          "x".to_string()
+        ),
+        (
+          vec!["position".to_string(), "y".to_string()],
+          "y".to_string()
+        ),
+      ])
+    );
+    assert_eq!(parsed.topics, vec!["Flight", "Position", "Plan"]);
+    assert_eq!(parsed.parameter_count(), 2);
+
+    let all = SubscriptionExpression::parse("select * from A").unwrap();
+    assert_eq!(all.selection, None);
+    assert_eq!(all.topics, vec!["A"]);
+    assert!(all.filter.is_none());
+
+    for bad in &[
+      "SELECT FROM A",
+      "SELECT a FROM",
+      "SELECT a, FROM A",
+      "SELECT * FROM A JOIN B",
+      "SELECT * FROM A INNER JOIN B",
+      "SELECT * FROM A WHERE",
+      "SELECT * FROM A B",
+    ] {
+      assert!(SubscriptionExpression::parse(bad).is_err(), "{}", bad);
+    }
+  }
+
+  #[test]
+  fn field_value_into_sample() {
+    let shape = Shape {
+      name: "Square".to_string(),
+      color: Color::Custom(1, 2, 3),
+      position: Point { x: -10, y: 2.5 },
+      size: 30,
+      filled: true,
+    };
+    let value = FieldValue::from_sample(&shape).unwrap();
+    assert_eq!(value.clone().into_sample::<Shape>().unwrap(), shape);
+
+    // Members are matched by name, extra members are ignored.
+    assert_eq!(
+      value.into_sample::<Point>().ok(),
+      None // no members x and y at top level
+    );
+    let point = FieldValue::Struct(vec![
+      ("y".to_string(), FieldValue::Int(4)),
+      ("extra".to_string(), FieldValue::Null),
+      ("x".to_string(), FieldValue::Int(3)),
+    ]);
+    assert_eq!(
+      point.into_sample::<Point>().unwrap(),
+      Point { x: 3, y: 4.0 }
+    );
+    assert_eq!(
+      FieldValue::Str("Red".to_string())
+        .into_sample::<Color>()
+        .unwrap(),
+      Color::Red
+    );
+    assert!(FieldValue::Int(300).into_sample::<u8>().is_err());
+  }
+}
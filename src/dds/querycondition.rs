@@ -0,0 +1,161 @@
+use serde::Serialize;
+
+use crate::{
+  dds::{
+    dynamic_data::DynamicType, query_expression::SampleFilter, readcondition::ReadCondition,
+    values::result::Result,
+  },
+  serialization::SerializedSample,
+};
+
+/// A [`ReadCondition`] augmented with a filter expression on the sample
+/// contents.
+///
+/// The expression uses the DDS SQL syntax, e.g.
+/// `"x > %0 AND color = 'RED'"`. Supported are comparisons (`=`, `<>`, `<`,
+/// `<=`, `>`, `>=`), `BETWEEN`, `LIKE` (with `%` and `_` wildcards), `AND`,
+/// `OR`, `NOT` and parentheses. Operands are member names, which may be
+/// dotted to refer to nested members, literal numbers, strings and booleans,
+/// and parameters `%0`, `%1`, etc.
+///
+/// The members are those of the serde serialization of the sample. Enumerators
+/// without data compare equal to their names as strings. Samples that carry
+/// only a key, e.g. due to dispose, are evaluated against the key. A
+/// comparison with a missing member, or with a value of a different kind, is
+/// false.
+///
+/// Given the [`DynamicType`] of the samples, the expression can also be
+/// evaluated on serialized samples, e.g. from
+/// [`take_serialized`](crate::with_key::DataReader::take_serialized), by
+/// decoding only the members it refers to. See
+/// [`matches_serialized`](Self::matches_serialized).
+///
+/// Use with
+/// [`DataReader::read_w_condition`](crate::with_key::DataReader::read_w_condition)
+/// and [`take_w_condition`](crate::with_key::DataReader::take_w_condition).
+///
+/// See DDS Specification 1.4 Section "2.2.2.5.9 QueryCondition Class".
+///
+/// # Examples
+///
+/// ```
+/// use rustdds::{QueryCondition, ReadCondition};
+///
+/// let query = QueryCondition::new(
+///   ReadCondition::not_read(),
+///   "x BETWEEN %0 AND %1 AND color = 'RED'",
+///   vec!["0".to_string(), "100".to_string()],
+/// )
+/// .unwrap();
+/// assert_eq!(query.query_parameters(), &["0", "100"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct QueryCondition {
+  read_condition: ReadCondition,
+  query_parameters: Vec<String>,
+  filter: SampleFilter,
+}
+
+impl QueryCondition {
+  /// Fails with
+  /// [`BadParameter`](crate::dds::values::result::Error::BadParameter) if the
+  /// expression is malformed, or there are fewer parameters than the
+  /// expression refers to.
+  pub fn new(
+    read_condition: ReadCondition,
+    query_expression: &str,
+    query_parameters: Vec<String>,
+  ) -> Result<Self> {
+    Ok(Self {
+      read_condition,
+      filter: SampleFilter::new(query_expression, &query_parameters)?,
+      query_parameters,
+    })
+  }
+
+  pub fn read_condition(&self) -> ReadCondition {
+    self.read_condition
+  }
+
+  pub fn query_expression(&self) -> &str {
+    self.filter.expression()
+  }
+
+  pub fn query_parameters(&self) -> &[String] {
+    &self.query_parameters
+  }
+
+  /// Changes the parameters. The expression stays the same.
+  pub fn set_query_parameters(&mut self, query_parameters: Vec<String>) -> Result<()> {
+    self.filter.set_parameters(&query_parameters)?;
+    self.query_parameters = query_parameters;
+    Ok(())
+  }
+
+  /// Evaluates the filter expression on a sample, or on a key.
+  /// Samples that cannot be evaluated do not match.
+  pub fn matches<T: Serialize + ?Sized>(&self, sample: &T) -> bool {
+    self.filter.matches(sample)
+  }
+
+  /// Sets the type of the samples given to
+  /// [`matches_serialized`](Self::matches_serialized).
+  pub fn set_dynamic_type(&mut self, data_type: &DynamicType) {
+    self.filter.set_dynamic_type(data_type);
+  }
+
+  /// Evaluates the filter expression on a serialized sample with data,
+  /// without deserializing the members that the expression does not refer
+  /// to.
+  ///
+  /// The result is `None` if no type was set with
+  /// [`set_dynamic_type`](Self::set_dynamic_type), the expression refers to
+  /// members that cannot be decoded this way, i.e. sequences or union
+  /// members, or the sample has no data or cannot be decoded. Then the
+  /// sample must be deserialized and given to [`matches`](Self::matches).
+  pub fn matches_serialized(&self, sample: &SerializedSample) -> Option<bool> {
+    if !sample.sample_info().valid_data() {
+      return None;
+    }
+    self
+      .filter
+      .matches_serialized(sample.bytes(), sample.encoding())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Serialize)]
+  struct Sample {
+    x: i32,
+    label: String,
+  }
+
+  #[test]
+  fn query_condition_parameters() {
+    let mut query = QueryCondition::new(
+      ReadCondition::any(),
+      "x > %0 AND label LIKE %1",
+      vec!["5".to_string(), "a%".to_string()],
+    )
+    .unwrap();
+    let sample = Sample {
+      x: 7,
+      label: "abc".to_string(),
+    };
+    assert!(query.matches(&sample));
+
+    query
+      .set_query_parameters(vec!["10".to_string(), "a%".to_string()])
+      .unwrap();
+    assert!(!query.matches(&sample));
+
+    // too few parameters
+    assert!(query.set_query_parameters(vec!["1".to_string()]).is_err());
+    assert!(QueryCondition::new(ReadCondition::any(), "x > %2", vec![]).is_err());
+    // key-only samples are matched against the key
+    assert!(!query.matches(&7));
+  }
+}
@@ -13,11 +13,36 @@ pub struct ReadCondition {
   sample_state_mask: BitFlags<SampleState>,
   view_state_mask: BitFlags<ViewState>,
   instance_state_mask: BitFlags<InstanceState>,
-  /* Extension idea: Add a query string and a list of query parameters to upgrade this
-   * to QueryCondition. But that would be a lot of work, especially in DataReader. */
+  // A query string and parameters are added by QueryCondition.
 }
 
 impl ReadCondition {
+  /// Condition selects samples whose states are included in all three masks.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use rustdds::{InstanceState, ReadCondition, SampleState, ViewState};
+  ///
+  /// // Unread samples of instances that have been disposed or lost their writers
+  /// let rc = ReadCondition::new(
+  ///   SampleState::NotRead.into(),
+  ///   ViewState::any(),
+  ///   InstanceState::not_alive(),
+  /// );
+  /// ```
+  pub fn new(
+    sample_state_mask: BitFlags<SampleState>,
+    view_state_mask: BitFlags<ViewState>,
+    instance_state_mask: BitFlags<InstanceState>,
+  ) -> Self {
+    Self {
+      sample_state_mask,
+      view_state_mask,
+      instance_state_mask,
+    }
+  }
+
   /// Condition reads all available samples
   pub fn any() -> Self {
     Self {
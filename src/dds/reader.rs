@@ -1,24 +1,30 @@
 use std::{
-  collections::{BTreeMap, BTreeSet},
+  collections::{btree_map::Entry, BTreeMap, BTreeSet},
   fmt, iter,
   rc::Rc,
   sync::{Arc, RwLock},
-  time::Duration as StdDuration,
+  time::{Duration as StdDuration, Instant},
 };
 
 use mio::Token;
-use mio_extras::{channel as mio_channel, timer::Timer};
+use mio_extras::channel as mio_channel;
 use log::{debug, error, info, trace, warn};
 use enumflags2::BitFlags;
 use speedy::{Endianness, Writable};
 
 use crate::{
   dds::{
+    compatibility::VendorWorkaround,
     ddsdata::DDSData,
+    helpers::WakerSlot,
     message_receiver::MessageReceiverState,
+    pipeline_tracing,
     qos::{policy, HasQoSPolicy, QosPolicies},
     rtps_writer_proxy::RtpsWriterProxy,
-    statusevents::{CountWithChange, DataReaderStatus},
+    runtime_metrics::{self, LocalEndpoint, SampleDirection},
+    statistics::ReaderCounters,
+    statusevents::{CountWithChange, DataReaderStatus, IncompatibleQosTracker},
+    timer::Timer,
     with_key::datawriter::{WriteOptions, WriteOptionsBuilder},
   },
   messages::{
@@ -28,7 +34,7 @@ use crate::{
     submessages::{submessage_elements::parameter_list::ParameterList, submessages::*},
     vendor_id::VendorId,
   },
-  network::udp_sender::UDPSender,
+  network::transport::Transports,
   serialization::message::Message,
   structure::{
     cache_change::{CacheChange, ChangeKind},
@@ -40,11 +46,22 @@ use crate::{
     time::Timestamp,
   },
 };
+#[cfg(feature = "security")]
+use crate::security::plugins::CryptographyHandle;
 use super::{qos::InlineQos, with_key::datareader::ReaderCommand};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) enum TimedEvent {
   DeadlineMissedCheck,
+  HeartbeatResponse { writer_guid: GUID },
+  LifespanExpiry,
+}
+
+// A HEARTBEAT waiting for the response delay to pass
+struct PendingHeartbeatResponse {
+  heartbeat: Heartbeat,
+  final_flag_set: bool,
+  reply_locators: Vec<Locator>,
 }
 
 // Some pieces necessary to contruct a reader.
@@ -52,6 +69,7 @@ pub(crate) enum TimedEvent {
 pub(crate) struct ReaderIngredients {
   pub guid: GUID,
   pub notification_sender: mio_channel::SyncSender<()>,
+  pub data_reader_waker: WakerSlot,
   pub status_sender: mio_channel::SyncSender<DataReaderStatus>,
   pub topic_name: String,
   pub qos_policy: QosPolicies,
@@ -78,8 +96,10 @@ impl fmt::Debug for ReaderIngredients {
 pub(crate) struct Reader {
   // Should the instant be sent?
   notification_sender: mio_channel::SyncSender<()>,
+  // Wakes an async task waiting for data on the DataReader
+  data_reader_waker: WakerSlot,
   status_sender: mio_channel::SyncSender<DataReaderStatus>,
-  udp_sender: Rc<UDPSender>,
+  transports: Rc<Transports>,
 
   is_stateful: bool, // is this StatefulReader or Statelessreader as per RTPS spec
   // Currently we support only stateful behaviour.
@@ -97,11 +117,14 @@ pub(crate) struct Reader {
 
   my_guid: GUID,
 
+  // ACKNACKs responding to HEARTBEATs are sent after this delay, and a random
+  // part of the jitter, so that many readers do not respond all at once.
   heartbeat_response_delay: StdDuration,
-
-  // TODO: Implement (use) this
-  #[allow(dead_code)]
-  heartbeat_supression_duration: StdDuration,
+  heartbeat_response_jitter: StdDuration,
+  // HEARTBEATs that arrive sooner than this after the previous one from the
+  // same writer are ignored.
+  heartbeat_suppression_duration: StdDuration,
+  pending_heartbeat_responses: BTreeMap<GUID, PendingHeartbeatResponse>,
 
   received_hearbeat_count: i32,
 
@@ -109,23 +132,36 @@ pub(crate) struct Reader {
   writer_match_count_total: i32, // total count, never decreases
 
   requested_deadline_missed_count: i32,
-  offered_incompatible_qos_count: i32,
+  incompatible_qos: IncompatibleQosTracker,
+  // Deadlines are not considered missed before this. Moved forward on clock
+  // jumps.
+  deadline_monitoring_start: Timestamp,
+  // When the lifespan timer fires next, on the monotonic clock
+  lifespan_timeout: Option<Instant>,
 
   pub(crate) timed_event_timer: Timer<TimedEvent>,
   pub(crate) data_reader_command_receiver: mio_channel::Receiver<ReaderCommand>,
+
+  // Participant statistics of this Reader
+  pub(crate) counters: Arc<ReaderCounters>,
+
+  // DDS Security protection of payloads and outgoing messages
+  #[cfg(feature = "security")]
+  cryptography: Option<CryptographyHandle>,
 }
 
 impl Reader {
   pub fn new(
     i: ReaderIngredients,
     dds_cache: Arc<RwLock<DDSCache>>,
-    udp_sender: Rc<UDPSender>,
+    transports: Rc<Transports>,
     timed_event_timer: Timer<TimedEvent>,
   ) -> Self {
     Self {
       notification_sender: i.notification_sender,
+      data_reader_waker: i.data_reader_waker,
       status_sender: i.status_sender,
-      udp_sender,
+      transports,
       is_stateful: true, // Do not change this before stateless functionality is implemented.
 
       reliability: i
@@ -140,17 +176,34 @@ impl Reader {
       seqnum_instant_map: BTreeMap::new(),
       my_guid: i.guid,
 
-      heartbeat_response_delay: StdDuration::new(0, 500_000_000), // 0,5sec
-      heartbeat_supression_duration: StdDuration::new(0, 0),
+      // Respond immediately by default, to repair lost samples quickly.
+      heartbeat_response_delay: StdDuration::ZERO,
+      heartbeat_response_jitter: StdDuration::ZERO,
+      heartbeat_suppression_duration: StdDuration::ZERO,
+      pending_heartbeat_responses: BTreeMap::new(),
       received_hearbeat_count: 0,
       matched_writers: BTreeMap::new(),
       writer_match_count_total: 0,
       requested_deadline_missed_count: 0,
-      offered_incompatible_qos_count: 0,
+      incompatible_qos: IncompatibleQosTracker::new(),
+      deadline_monitoring_start: Timestamp::ZERO,
+      lifespan_timeout: None,
       timed_event_timer,
       data_reader_command_receiver: i.data_reader_command_receiver,
+      counters: Arc::new(ReaderCounters::default()),
+      #[cfg(feature = "security")]
+      cryptography: None,
     }
   }
+
+  // Generates the keys of this Reader, if its topic is protected.
+  #[cfg(feature = "security")]
+  pub fn set_cryptography(&mut self, cryptography: CryptographyHandle) {
+    cryptography
+      .register_local_endpoint(self.my_guid, &self.topic_name)
+      .unwrap_or_else(|e| error!("Cannot generate keys for {:?}: {}", self.my_guid, e));
+    self.cryptography = Some(cryptography);
+  }
   // TODO: check if it's necessary to implement different handlers for discovery
   // and user messages
 
@@ -160,6 +213,11 @@ impl Reader {
     self.guid().entity_id.as_token()
   }
 
+  // The time on the clock of the participant
+  fn now(&self) -> Timestamp {
+    self.timed_event_timer.time_source().now()
+  }
+
   pub fn set_requested_deadline_check_timer(&mut self) {
     if let Some(deadline) = self.qos_policy.deadline {
       debug!(
@@ -212,9 +270,15 @@ impl Reader {
     };
 
     let mut changes: Vec<DataReaderStatus> = vec![];
-    let now = Timestamp::now();
+    let now = self.now();
     for writer_proxy in self.matched_writers.values_mut() {
       if let Some(last_change) = writer_proxy.last_change_timestamp() {
+        // Timestamps from before a backwards clock jump may be in the future.
+        let last_change = if last_change > now {
+          self.deadline_monitoring_start
+        } else {
+          last_change.max(self.deadline_monitoring_start)
+        };
         let since_last = now.duration_since(last_change);
         // if time singe last received message is greater than deadline increase status
         // and return notification.
@@ -245,6 +309,75 @@ impl Reader {
     changes
   } // fn
 
+  // Apply a changed QoS. The caller has checked that only changeable policies
+  // were changed.
+  pub fn set_qos(&mut self, qos: QosPolicies) {
+    let deadline_timer_running = self.qos_policy.deadline.is_some();
+    self.qos_policy = qos;
+    // Deadline may have changed. Do not count missed deadlines over the change.
+    self.restart_deadline_monitoring();
+    if !deadline_timer_running {
+      self.set_requested_deadline_check_timer();
+    }
+  }
+
+  // Sender sockets were replaced, because network interfaces changed.
+  pub fn set_transports(&mut self, transports: Rc<Transports>) {
+    self.transports = transports;
+  }
+
+  // Called after a clock jump. Samples received before the jump do not tell
+  // reliably how long ago they arrived, so deadline periods start again now.
+  pub fn restart_deadline_monitoring(&mut self) {
+    self.deadline_monitoring_start = self.now();
+  }
+
+  // Called after a clock jump. Lifespan expiry is on the monotonic clock, so
+  // wall clock steps do not move it, but the lifespan timer may have been
+  // stalled. Changes that expired meanwhile are removed now.
+  pub fn clock_jump(&mut self) {
+    self.restart_deadline_monitoring();
+    self.remove_expired_changes();
+  }
+
+  fn lifespan_expiry(&self, source_timestamp: Option<Timestamp>) -> Option<Instant> {
+    self
+      .qos_policy
+      .lifespan
+      .and_then(|lifespan| lifespan.expiry(source_timestamp, self.timed_event_timer.time_source()))
+  }
+
+  fn schedule_lifespan_expiry(&mut self, expiry: Instant) {
+    let now = self.timed_event_timer.time_source().instant();
+    if expiry <= now {
+      self.remove_expired_changes();
+    } else if self
+      .lifespan_timeout
+      .map_or(true, |timeout| expiry < timeout)
+    {
+      self.lifespan_timeout = Some(expiry);
+      self
+        .timed_event_timer
+        .set_timeout(expiry - now, TimedEvent::LifespanExpiry);
+    }
+  }
+
+  // Removes changes whose lifespan has expired from the DDSCache, and sets the
+  // timer for the next expiry.
+  fn remove_expired_changes(&mut self) {
+    let now = self.timed_event_timer.time_source().instant();
+    let next_expiry = match self.dds_cache.write() {
+      Ok(mut cache) => cache.topic_remove_expired(&self.topic_name, now),
+      Err(e) => panic!("The DDSCache of is poisoned. Error: {}", e),
+    };
+    if self.lifespan_timeout.map_or(true, |timeout| timeout <= now) {
+      self.lifespan_timeout = None;
+      if let Some(expiry) = next_expiry {
+        self.schedule_lifespan_expiry(expiry);
+      }
+    }
+  }
+
   pub fn handle_timed_event(&mut self) {
     while let Some(e) = self.timed_event_timer.poll() {
       match e {
@@ -252,6 +385,13 @@ impl Reader {
           self.handle_requested_deadline_event();
           self.set_requested_deadline_check_timer(); // re-prime timer
         }
+        TimedEvent::HeartbeatResponse { writer_guid } => {
+          self.send_delayed_heartbeat_response(writer_guid);
+        }
+        TimedEvent::LifespanExpiry => {
+          self.lifespan_timeout = None;
+          self.remove_expired_changes();
+        }
       }
     }
   }
@@ -265,6 +405,13 @@ impl Reader {
           warn!("RESET_REQUESTED_DEADLINE_STATUS not implemented!");
           //TODO: This should be implemented.
         }
+        Ok(ReaderCommand::SetHeartbeatResponseDelay { delay, jitter }) => {
+          self.heartbeat_response_delay = delay;
+          self.heartbeat_response_jitter = jitter;
+        }
+        Ok(ReaderCommand::SetHeartbeatSuppressionDuration { duration }) => {
+          self.heartbeat_suppression_duration = duration;
+        }
 
         // Disconnected is normal when terminating
         Err(TryRecvError::Disconnected) => {
@@ -324,10 +471,12 @@ impl Reader {
   // updates or adds a new writer proxy, doesn't touch changes
   pub fn update_writer_proxy(&mut self, proxy: RtpsWriterProxy, offered_qos: &QosPolicies) {
     debug!("update_writer_proxy topic={:?}", self.topic_name);
-    match offered_qos.compliance_failure_wrt(&self.qos_policy) {
+    let bad_policy_ids = offered_qos.compliance_failures_wrt(&self.qos_policy);
+    match bad_policy_ids.first() {
       None => {
         // success, update or insert
         let writer_id = proxy.remote_writer_guid;
+        self.incompatible_qos.forget(writer_id);
         let count_change = self.matched_writer_update(proxy);
         if count_change > 0 {
           self.writer_match_count_total += count_change;
@@ -341,15 +490,20 @@ impl Reader {
           );
         }
       }
-      Some(bad_policy_id) => {
+      Some(&bad_policy_id) => {
         // no QoS match
-        self.offered_incompatible_qos_count += 1;
+        let writer_id = proxy.remote_writer_guid;
+        // The writer may have been compatible before, but changed its QoS.
+        self.matched_writer_remove(writer_id);
+        if !self.incompatible_qos.record(writer_id, &bad_policy_ids) {
+          return; // already reported
+        }
         self.send_status_change(DataReaderStatus::RequestedIncompatibleQos {
-          count: CountWithChange::new(self.offered_incompatible_qos_count, 1),
+          count: CountWithChange::new(self.incompatible_qos.total_count(), 1),
           last_policy_id: bad_policy_id,
-          policies: Vec::new(), // TODO. implementation missing
+          policies: self.incompatible_qos.policies(),
         });
-        warn!("update_writer_proxy - QoS mismatch {:?}", bad_policy_id);
+        warn!("update_writer_proxy - QoS mismatch {:?}", bad_policy_ids);
         info!(
           "update_writer_proxy - QoS mismatch: topic={:?} requested={:?}  offered={:?}",
           self.topic_name, &self.qos_policy, offered_qos
@@ -365,17 +519,31 @@ impl Reader {
       0
     } else {
       self.matched_writers.insert(proxy.remote_writer_guid, proxy);
+      runtime_metrics::matched_endpoints(&self.topic_name, LocalEndpoint::Reader, 1);
       1
     }
   }
 
   pub fn remove_writer_proxy(&mut self, writer_guid: GUID) {
+    self.incompatible_qos.forget(writer_guid);
+    self.matched_writer_remove(writer_guid);
+  }
+
+  fn matched_writer_remove(&mut self, writer_guid: GUID) {
     if self.matched_writers.contains_key(&writer_guid) {
       self.matched_writers.remove(&writer_guid);
+      runtime_metrics::matched_endpoints(&self.topic_name, LocalEndpoint::Reader, -1);
       self.send_status_change(DataReaderStatus::SubscriptionMatched {
         total: CountWithChange::new(self.writer_match_count_total, 0),
         current: CountWithChange::new(self.matched_writers.len() as i32, -1),
       });
+      // Let the DataReader know, so that it can update instance states and
+      // accept the writer's history again, if it is later re-discovered.
+      match self.dds_cache.write() {
+        Ok(mut cache) => cache.topic_writer_lost(&self.topic_name, Timestamp::now(), writer_guid),
+        Err(e) => panic!("The DDSCache of is poisoned. Error: {}", e),
+      }
+      self.notify_cache_change();
     }
   }
 
@@ -431,23 +599,22 @@ impl Reader {
     //trace!("handle_data_msg entry");
     let receive_timestamp = Timestamp::now();
 
-    // parse write_options out of the message
-    let mut write_options_b = WriteOptionsBuilder::new();
-    // Check if we have s source timestamp
-    if let Some(source_timestamp) = mr_state.source_timestamp {
-      write_options_b = write_options_b.source_timestamp(source_timestamp);
-    }
-    // Check if the message specifies a related_sample_identity
-    let ri = DATA_Flags::cdr_representation_identifier(data_flags);
-    if let Some(related_sample_identity) = data
-      .inline_qos
-      .as_ref()
-      .and_then(|iqos| InlineQos::related_sample_identity(iqos, ri).ok())
-      .flatten()
-    {
-      write_options_b = write_options_b.related_sample_identity(related_sample_identity);
+    if let Some(pid) = data.inline_qos.as_ref().and_then(InlineQos::not_understood) {
+      debug!(
+        "Dropping DATA with inline QoS parameter {:?} not understood",
+        pid
+      );
+      self.counters.samples_dropped.increment();
+      return;
     }
 
+    // parse write_options out of the message
+    let write_options = Self::write_options(
+      &data.inline_qos,
+      DATA_Flags::cdr_representation_identifier(data_flags),
+      mr_state,
+    );
+
     let writer_guid = GUID::new_with_prefix_and_id(mr_state.source_guid_prefix, data.writer_id);
     let writer_seq_num = data.writer_sn; // for borrow checker
 
@@ -455,11 +622,14 @@ impl Reader {
       Ok(ddsdata) => self.process_received_data(
         ddsdata,
         receive_timestamp,
-        write_options_b.build(),
+        write_options,
         writer_guid,
         writer_seq_num,
       ),
-      Err(e) => debug!("Parsing DATA to DDSData failed: {}", e),
+      Err(e) => {
+        debug!("Parsing DATA to DDSData failed: {}", e);
+        self.counters.samples_dropped.increment();
+      }
     }
   }
 
@@ -473,49 +643,60 @@ impl Reader {
     let seq_num = datafrag.writer_sn;
     let receive_timestamp = Timestamp::now();
 
-    // check if this submessage is expired already
-    // TODO: Maybe this check is in the wrong place altogether? It should be
-    // done when Datareader fetches data for the application.
-    if let (Some(source_timestamp), Some(lifespan)) =
-      (mr_state.source_timestamp, self.qos().lifespan)
+    if let Some(pid) = datafrag
+      .inline_qos
+      .as_ref()
+      .and_then(InlineQos::not_understood)
     {
-      let elapsed = receive_timestamp.duration_since(source_timestamp);
-      if lifespan.duration < elapsed {
+      debug!(
+        "Dropping DATA_FRAG with inline QoS parameter {:?} not understood",
+        pid
+      );
+      self.counters.samples_dropped.increment();
+      return;
+    }
+
+    // check if this submessage is expired already, so that we need not
+    // reassemble it
+    if let Some(expiry) = self.lifespan_expiry(mr_state.source_timestamp) {
+      if expiry <= self.timed_event_timer.time_source().instant() {
         info!(
-          "DataFrag {:?} from {:?} lifespan exeeded. duration={:?} elapsed={:?}",
-          seq_num, writer_guid, lifespan.duration, elapsed
+          "DataFrag {:?} from {:?} lifespan exeeded. source_timestamp={:?}",
+          seq_num, writer_guid, mr_state.source_timestamp
         );
+        self.counters.samples_dropped.increment();
         return;
       }
     }
 
     // parse write_options out of the message
-    // TODO: This is almost duplicate code from DATA processing
-    let mut write_options_b = WriteOptionsBuilder::new();
-    // Check if we have s source timestamp
-    if let Some(source_timestamp) = mr_state.source_timestamp {
-      write_options_b = write_options_b.source_timestamp(source_timestamp);
-    }
-    // Check if the message specifies a related_sample_identity
-    let ri = DATAFRAG_Flags::cdr_representation_identifier(datafrag_flags);
-    if let Some(related_sample_identity) = datafrag
+    let write_options = Self::write_options(
+      &datafrag.inline_qos,
+      DATAFRAG_Flags::cdr_representation_identifier(datafrag_flags),
+      mr_state,
+    );
+
+    let key_hash = datafrag
       .inline_qos
       .as_ref()
-      .and_then(|iqos| InlineQos::related_sample_identity(iqos, ri).ok())
-      .flatten()
-    {
-      write_options_b = write_options_b.related_sample_identity(related_sample_identity);
-    }
+      .and_then(|iqos| InlineQos::key_hash(iqos).ok())
+      .flatten();
 
     let writer_seq_num = datafrag.writer_sn; // for borrow checker
     if let Some(writer_proxy) = self.matched_writer_lookup(writer_guid) {
       if let Some(complete_ddsdata) = writer_proxy.handle_datafrag(datafrag, datafrag_flags) {
+        // Key hash may be present in any of the fragments. We take it from the
+        // last one, if present there.
+        let complete_ddsdata = match key_hash {
+          Some(_) => complete_ddsdata.with_key_hash(key_hash),
+          None => complete_ddsdata,
+        };
         // Source timestamp (if any) will be the timestamp of the last fragment (that
         // completes the sample).
         self.process_received_data(
           complete_ddsdata,
           receive_timestamp,
-          write_options_b.build(),
+          write_options,
           writer_guid,
           writer_seq_num,
         );
@@ -540,14 +721,21 @@ impl Reader {
     writer_guid: GUID,
     writer_sn: SequenceNumber,
   ) {
-    trace!(
-      "handle_data_msg from {:?} seq={:?} topic={:?} reliability={:?} stateful={:?}",
-      &writer_guid,
-      writer_sn,
-      self.topic_name,
-      self.reliability,
-      self.is_stateful,
-    );
+    let _span = pipeline_tracing::cache(&self.topic_name, self.my_guid, writer_guid, writer_sn);
+    #[cfg(feature = "security")]
+    let ddsdata = match &self.cryptography {
+      Some(cryptography) => match cryptography.decode_data(writer_guid, ddsdata) {
+        Ok(ddsdata) => ddsdata,
+        Err(e) => {
+          // Not marked as received, so a reliable Writer will send it again.
+          debug!("Dropped sample from {:?}: {}", writer_guid, e);
+          self.counters.samples_dropped.increment();
+          return;
+        }
+      },
+      None => ddsdata,
+    };
+    let now = self.now();
     if self.is_stateful {
       let my_entityid = self.my_guid.entity_id; // to please borrow checker
       if let Some(writer_proxy) = self.matched_writer_lookup(writer_guid) {
@@ -560,11 +748,12 @@ impl Reader {
             // incrementing sequence numbers. (eProsime shapes demo 2.1.0 from
             // 2021)
           } else {
+            self.counters.samples_dropped.increment();
             return;
           }
         }
         // Add the change and get the instant
-        writer_proxy.received_changes_add(writer_sn, receive_timestamp);
+        writer_proxy.received_changes_add(writer_sn, receive_timestamp, now);
       } else {
         // no writer proxy found
         info!(
@@ -577,6 +766,11 @@ impl Reader {
       todo!()
     }
 
+    runtime_metrics::sample_size(
+      &self.topic_name,
+      SampleDirection::Received,
+      ddsdata.payload_size(),
+    );
     self.make_cache_change(
       ddsdata,
       receive_timestamp,
@@ -584,6 +778,7 @@ impl Reader {
       writer_guid,
       writer_sn,
     );
+    self.counters.samples_received.increment();
 
     // Add to own track-keeping datastructure
     #[cfg(test)]
@@ -598,6 +793,11 @@ impl Reader {
     data_flags: BitFlags<DATA_Flags>,
   ) -> Result<DDSData, String> {
     let representation_identifier = DATA_Flags::cdr_representation_identifier(data_flags);
+    let received_key_hash = data
+      .inline_qos
+      .as_ref()
+      .and_then(|iqos| InlineQos::key_hash(iqos).ok())
+      .flatten();
 
     match (
       data.serialized_payload,
@@ -606,27 +806,25 @@ impl Reader {
     ) {
       (Some(sp), true, false) => {
         // data
-        Ok(DDSData::new(sp))
+        Ok(DDSData::new(sp).with_key_hash(received_key_hash))
       }
 
       (Some(sp), false, true) => {
         // key
-        Ok(DDSData::new_disposed_by_key(
-          Self::deduce_change_kind(&data.inline_qos, false, representation_identifier),
-          sp,
-        ))
+        Ok(
+          DDSData::new_disposed_by_key(
+            Self::deduce_change_kind(&data.inline_qos, false, representation_identifier),
+            sp,
+          )
+          .with_key_hash(received_key_hash),
+        )
       }
 
       (None, false, false) => {
         // no data, no key. Maybe there is inline QoS?
         // At least we should find key hash, or we do not know WTF the writer is talking
         // about
-        let key_hash = if let Some(h) = data
-          .inline_qos
-          .as_ref()
-          .and_then(|iqos| InlineQos::key_hash(iqos).ok())
-          .flatten()
-        {
+        let key_hash = if let Some(h) = received_key_hash {
           Ok(h)
         } else {
           info!("Received DATA that has no payload and no key_hash inline QoS - discarding");
@@ -676,6 +874,7 @@ impl Reader {
   ) -> bool {
     let writer_guid =
       GUID::new_with_prefix_and_id(mr_state.source_guid_prefix, heartbeat.writer_id);
+    self.counters.heartbeats_received.increment();
 
     if self.reliability == policy::Reliability::BestEffort {
       debug!(
@@ -705,6 +904,7 @@ impl Reader {
       );
     }
 
+    let heartbeat_suppression_duration = self.heartbeat_suppression_duration;
     let writer_proxy = if let Some(wp) = self.matched_writer_lookup(writer_guid) {
       wp
     } else {
@@ -712,15 +912,36 @@ impl Reader {
       return false;
     };
 
+    // Reply where the writer asked with INFO_REPLY, or else where Discovery
+    // told.
     let mut mr_state = mr_state;
-    mr_state.unicast_reply_locator_list = writer_proxy.unicast_locator_list.clone();
+    if mr_state.unicast_reply_locator_list.is_empty() {
+      mr_state.unicast_reply_locator_list = writer_proxy.unicast_locator_list.clone();
+    }
 
-    if heartbeat.count <= writer_proxy.received_heartbeat_count {
+    let repeated_count_allowed = mr_state
+      .source_workarounds
+      .contains(VendorWorkaround::RepeatedHeartbeatCount);
+    if heartbeat.count < writer_proxy.received_heartbeat_count
+      || (heartbeat.count == writer_proxy.received_heartbeat_count && !repeated_count_allowed)
+    {
       // This heartbeat was already seen an processed.
       return false;
     }
     writer_proxy.received_heartbeat_count = heartbeat.count;
 
+    let now = Instant::now();
+    if let Some(previous) = writer_proxy.heartbeat_accepted_at {
+      if now.duration_since(previous) < heartbeat_suppression_duration {
+        trace!(
+          "HEARTBEAT from {:?} too soon after the previous one. Ignoring.",
+          writer_guid
+        );
+        return false;
+      }
+    }
+    writer_proxy.heartbeat_accepted_at = Some(now);
+
     // remove fragmented changes until first_sn.
     let removed_instances = writer_proxy.irrelevant_changes_up_to(heartbeat.first_sn);
 
@@ -743,19 +964,76 @@ impl Reader {
       }
     }
 
+    // Respond now, or after the delay. Data that arrives meanwhile need not be
+    // requested.
+    let delay =
+      self.heartbeat_response_delay + self.heartbeat_response_jitter.mul_f64(rand::random());
+    if delay.is_zero() {
+      return self.respond_to_heartbeat(
+        writer_guid,
+        heartbeat,
+        final_flag_set,
+        &mr_state.unicast_reply_locator_list,
+      );
+    }
+    match self.pending_heartbeat_responses.entry(writer_guid) {
+      Entry::Occupied(mut entry) => {
+        // Respond to the latest, but a HEARTBEAT without the final flag must
+        // get a response.
+        let pending = entry.get_mut();
+        pending.heartbeat = heartbeat.clone();
+        pending.final_flag_set &= final_flag_set;
+        pending.reply_locators = mr_state.unicast_reply_locator_list;
+      }
+      Entry::Vacant(entry) => {
+        entry.insert(PendingHeartbeatResponse {
+          heartbeat: heartbeat.clone(),
+          final_flag_set,
+          reply_locators: mr_state.unicast_reply_locator_list,
+        });
+        self
+          .timed_event_timer
+          .set_timeout(delay, TimedEvent::HeartbeatResponse { writer_guid });
+      }
+    }
+    false
+  } // fn
+
+  fn send_delayed_heartbeat_response(&mut self, writer_guid: GUID) -> bool {
+    match self.pending_heartbeat_responses.remove(&writer_guid) {
+      Some(pending) => self.respond_to_heartbeat(
+        writer_guid,
+        &pending.heartbeat,
+        pending.final_flag_set,
+        &pending.reply_locators,
+      ),
+      None => false,
+    }
+  }
+
+  // Sends ACKNACK, and NACK_FRAGs if needed. Returns true if sent.
+  fn respond_to_heartbeat(
+    &mut self,
+    writer_guid: GUID,
+    heartbeat: &Heartbeat,
+    final_flag_set: bool,
+    reply_locators: &[Locator],
+  ) -> bool {
     let reader_id = self.entity_id();
 
-    // this is duplicate code from above, but needed, because we need another
-    // mutable borrow. TODO: Maybe could be written in some sensible way.
     let writer_proxy = if let Some(wp) = self.matched_writer_lookup(writer_guid) {
       wp
     } else {
-      error!("Writer proxy disappeared 2!");
+      debug!(
+        "Writer proxy {:?} disappeared before HEARTBEAT response",
+        writer_guid
+      );
       return false;
     };
 
     // See if ACKNACK is needed, and generate one.
-    let missing_seqnums = writer_proxy.missing_seqnums(heartbeat.first_sn, heartbeat.last_sn);
+    let (reader_sn_state, partially_received) =
+      writer_proxy.acknack_request(heartbeat.first_sn, heartbeat.last_sn);
 
     // Interpretation of final flag in RTPS spec
     // 8.4.2.3.1 Readers must respond eventually after receiving a HEARTBEAT with
@@ -766,38 +1044,7 @@ impl Reader {
     // having received all the data samples or may indicate that some data
     // samples are missing. The response may be delayed to avoid message storms.
 
-    if !missing_seqnums.is_empty() || !final_flag_set {
-      let mut partially_received = Vec::new();
-      // report of what we have.
-      // We claim to have received all SNs before "base" and produce a set of missing
-      // sequence numbers that are >= base.
-      let reader_sn_state = match missing_seqnums.get(0) {
-        Some(&first_missing) => {
-          // Here we assume missing_seqnums are returned in order.
-          // Limit the set to maximum that can be sent in acknack submessage.
-
-          SequenceNumberSet::from_base_and_set(
-            first_missing,
-            &missing_seqnums
-              .iter()
-              .copied()
-              .take_while(|sn| sn < &(first_missing + SequenceNumber::new(256)))
-              .filter(|sn| {
-                if writer_proxy.is_partially_received(*sn) {
-                  partially_received.push(*sn);
-                  false
-                } else {
-                  true
-                }
-              })
-              .collect(),
-          )
-        }
-
-        // Nothing missing. Report that we have all we have.
-        None => SequenceNumberSet::new_empty(writer_proxy.all_ackable_before()),
-      };
-
+    if !reader_sn_state.is_empty() || !partially_received.is_empty() || !final_flag_set {
       let response_ack_nack = AckNack {
         reader_id,
         writer_id: heartbeat.writer_id,
@@ -857,9 +1104,9 @@ impl Reader {
           fflags,
           nackfrags,
           InfoDestination {
-            guid_prefix: mr_state.source_guid_prefix,
+            guid_prefix: writer_guid.prefix,
           },
-          &mr_state.unicast_reply_locator_list,
+          reply_locators,
         );
       }
 
@@ -867,9 +1114,9 @@ impl Reader {
         flags,
         response_ack_nack,
         InfoDestination {
-          guid_prefix: mr_state.source_guid_prefix,
+          guid_prefix: writer_guid.prefix,
         },
-        &mr_state.unicast_reply_locator_list,
+        reply_locators,
       );
 
       return true;
@@ -971,6 +1218,32 @@ impl Reader {
     }
   }
 
+  // Source timestamp and the inline QoS that is stored with the sample
+  fn write_options(
+    inline_qos: &Option<ParameterList>,
+    ri: RepresentationIdentifier,
+    mr_state: &MessageReceiverState,
+  ) -> WriteOptions {
+    let mut write_options_b = WriteOptionsBuilder::new();
+    // Check if we have s source timestamp
+    if let Some(source_timestamp) = mr_state.source_timestamp {
+      write_options_b = write_options_b.source_timestamp(source_timestamp);
+    }
+    if let Some(iqos) = inline_qos {
+      // Check if the message specifies a related_sample_identity
+      if let Ok(Some(related_sample_identity)) = InlineQos::related_sample_identity(iqos, ri) {
+        write_options_b = write_options_b.related_sample_identity(related_sample_identity);
+      }
+      if let Ok(Some(strength)) = InlineQos::ownership_strength(iqos, ri) {
+        write_options_b = write_options_b.ownership_strength(strength);
+      }
+      if let Ok(Some(coherent_set)) = InlineQos::coherent_set(iqos, ri) {
+        write_options_b = write_options_b.coherent_set(coherent_set);
+      }
+    }
+    write_options_b.build()
+  }
+
   // Convert DATA submessage into a CacheChange and update history cache
   fn make_cache_change(
     &mut self,
@@ -980,18 +1253,24 @@ impl Reader {
     writer_guid: GUID,
     writer_sn: SequenceNumber,
   ) {
+    let expiry = self.lifespan_expiry(write_options.source_timestamp);
     let cache_change = CacheChange::new(writer_guid, writer_sn, write_options, data);
-    let mut cache = match self.dds_cache.write() {
-      Ok(rwlock) => rwlock,
+    match self.dds_cache.write() {
+      Ok(mut cache) => {
+        cache.add_expiring_change(&self.topic_name, &receive_timestamp, cache_change, expiry);
+      }
       // TODO: Should we panic here? Are we allowed to continue with poisoned DDSCache?
       Err(e) => panic!("The DDSCache of is poisoned. Error: {}", e),
-    };
-    cache.add_change(&self.topic_name, &receive_timestamp, cache_change);
+    }
+    if let Some(expiry) = expiry {
+      self.schedule_lifespan_expiry(expiry);
+    }
   }
 
   // notifies DataReaders (or any listeners that history cache has changed for
   // this reader) likely use of mio channel
   pub fn notify_cache_change(&self) {
+    let _span = pipeline_tracing::notify(&self.topic_name, self.my_guid);
     match self.notification_sender.try_send(()) {
       Ok(()) => (),
       Err(mio_channel::TrySendError::Full(_)) => (), /* This is harmless. There is a */
@@ -1004,6 +1283,7 @@ impl Reader {
         // TODO: What does this mean? Can we ever get here?
       }
     }
+    self.data_reader_waker.wake();
   }
 
   fn send_acknack_to(
@@ -1023,16 +1303,13 @@ impl Reader {
       guid_prefix: self.my_guid.prefix,
     });
 
+    let destination = info_dst.guid_prefix;
     message.add_submessage(info_dst.create_submessage(infodst_flags));
 
     message.add_submessage(acknack.create_submessage(flags));
 
-    let bytes = message
-      .write_to_vec_with_ctx(Endianness::LittleEndian)
-      .unwrap();
-    self
-      .udp_sender
-      .send_to_locator_list(&bytes, dst_localtor_list);
+    self.send_message(&message, destination, dst_localtor_list);
+    self.counters.acknacks_sent.increment();
   }
 
   fn send_nackfrags_to(
@@ -1052,17 +1329,39 @@ impl Reader {
       guid_prefix: self.my_guid.prefix,
     });
 
+    let destination = info_dst.guid_prefix;
     message.add_submessage(info_dst.create_submessage(infodst_flags));
 
     for nf in nackfrags {
       message.add_submessage(nf.create_submessage(flags));
     }
 
+    self.send_message(&message, destination, dst_locator_list);
+  }
+
+  // With DDS Security, the message is protected as the governance requires.
+  fn send_message(
+    &self,
+    message: &Message,
+    #[cfg_attr(not(feature = "security"), allow(unused_variables))] destination: GuidPrefix,
+    dst_locator_list: &[Locator],
+  ) {
     let bytes = message
       .write_to_vec_with_ctx(Endianness::LittleEndian)
       .unwrap();
+    #[cfg(feature = "security")]
+    let bytes = match &self.cryptography {
+      Some(cryptography) => match cryptography.encode_message(bytes, Some(destination)) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+          error!("Cannot protect message topic={:?}: {}", self.topic_name, e);
+          return;
+        }
+      },
+      None => bytes,
+    };
     self
-      .udp_sender
+      .transports
       .send_to_locator_list(&bytes, dst_locator_list);
   }
 
@@ -1108,6 +1407,17 @@ impl HasQoSPolicy for Reader {
   }
 }
 
+// The matches of a deleted Reader are gone, too.
+impl Drop for Reader {
+  fn drop(&mut self) {
+    runtime_metrics::matched_endpoints(
+      &self.topic_name,
+      LocalEndpoint::Reader,
+      -(self.matched_writers.len() as i32),
+    );
+  }
+}
+
 impl RTPSEntity for Reader {
   fn guid(&self) -> GUID {
     self.my_guid
@@ -1130,10 +1440,14 @@ impl fmt::Debug for Reader {
 mod tests {
   use crate::{
     dds::{
-      qos::policy::Reliability, statusevents::DataReaderStatus, typedesc::TypeDesc,
+      clock::{Clock, SteppingClock, TimeSource},
+      qos::policy::{Lifespan, Reliability},
+      statusevents::DataReaderStatus,
+      typedesc::TypeDesc,
       with_key::datawriter::WriteOptions,
     },
     messages::submessages::submessage_elements::serialized_payload::SerializedPayload,
+    network::udp_sender::UDPSender,
     structure::guid::{EntityId, EntityKind, GuidPrefix, GUID},
     Duration, QosPolicyBuilder,
   };
@@ -1160,6 +1474,7 @@ mod tests {
     let reader_ing = ReaderIngredients {
       guid,
       notification_sender: send,
+      data_reader_waker: WakerSlot::default(),
       status_sender,
       topic_name: "test".to_string(),
       qos_policy: QosPolicies::qos_none(),
@@ -1168,8 +1483,8 @@ mod tests {
     let mut reader = Reader::new(
       reader_ing,
       dds_cache,
-      Rc::new(UDPSender::new(0).unwrap()),
-      mio_extras::timer::Builder::default().build(),
+      Rc::new(Transports::from(UDPSender::new(0).unwrap())),
+      Timer::default(),
     );
 
     let writer_guid = GUID {
@@ -1226,6 +1541,7 @@ mod tests {
     let reader_ing = ReaderIngredients {
       guid: new_guid,
       notification_sender: send,
+      data_reader_waker: WakerSlot::default(),
       status_sender,
       topic_name: "test".to_string(),
       qos_policy: QosPolicies::qos_none(),
@@ -1234,8 +1550,8 @@ mod tests {
     let mut new_reader = Reader::new(
       reader_ing,
       dds_cache.clone(),
-      Rc::new(UDPSender::new(0).unwrap()),
-      mio_extras::timer::Builder::default().build(),
+      Rc::new(Transports::from(UDPSender::new(0).unwrap())),
+      Timer::default(),
     );
 
     let writer_guid = GUID {
@@ -1307,6 +1623,7 @@ mod tests {
     let reader_ing = ReaderIngredients {
       guid: new_guid,
       notification_sender: send,
+      data_reader_waker: WakerSlot::default(),
       status_sender,
       topic_name: "test".to_string(),
       qos_policy: reliable_qos.clone(),
@@ -1315,8 +1632,8 @@ mod tests {
     let mut new_reader = Reader::new(
       reader_ing,
       dds_cache,
-      Rc::new(UDPSender::new(0).unwrap()),
-      mio_extras::timer::Builder::default().build(),
+      Rc::new(Transports::from(UDPSender::new(0).unwrap())),
+      Timer::default(),
     );
 
     let writer_guid = GUID {
@@ -1437,6 +1754,180 @@ mod tests {
     // How to get it from writer proxies?
   }
 
+  #[test]
+  fn rtpsreader_heartbeat_response_delay_and_suppression() {
+    let (send, _rec) = mio_channel::sync_channel::<()>(100);
+    let (status_sender, _status_receiver) =
+      mio_extras::channel::sync_channel::<DataReaderStatus>(100);
+    let (reader_command_sender, reader_command_receiver) =
+      mio_channel::sync_channel::<ReaderCommand>(10);
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    dds_cache
+      .write()
+      .unwrap()
+      .add_new_topic("test".to_string(), TypeDesc::new("testi".to_string()));
+    let reliable_qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::from_millis(100),
+      })
+      .build();
+    let reader_ing = ReaderIngredients {
+      guid: GUID::dummy_test_guid(EntityKind::READER_NO_KEY_USER_DEFINED),
+      notification_sender: send,
+      data_reader_waker: WakerSlot::default(),
+      status_sender,
+      topic_name: "test".to_string(),
+      qos_policy: reliable_qos.clone(),
+      data_reader_command_receiver: reader_command_receiver,
+    };
+    let mut reader = Reader::new(
+      reader_ing,
+      dds_cache,
+      Rc::new(Transports::from(UDPSender::new(0).unwrap())),
+      Timer::default(),
+    );
+
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    let mr_state = MessageReceiverState {
+      source_guid_prefix: writer_guid.prefix,
+      ..Default::default()
+    };
+    reader.matched_writer_add(
+      writer_guid,
+      EntityId::UNKNOWN,
+      vec![],
+      vec![],
+      &reliable_qos,
+    );
+    let heartbeat = |count| Heartbeat {
+      reader_id: reader.entity_id(),
+      writer_id: writer_guid.entity_id,
+      first_sn: SequenceNumber::new(1),
+      last_sn: SequenceNumber::new(2),
+      count,
+    };
+    let (hb_1, hb_2, hb_3) = (heartbeat(1), heartbeat(2), heartbeat(3));
+
+    reader_command_sender
+      .try_send(ReaderCommand::SetHeartbeatResponseDelay {
+        delay: StdDuration::from_secs(10),
+        jitter: StdDuration::from_secs(1),
+      })
+      .unwrap();
+    reader_command_sender
+      .try_send(ReaderCommand::SetHeartbeatSuppressionDuration {
+        duration: StdDuration::from_secs(10),
+      })
+      .unwrap();
+    reader.process_command();
+
+    // The response waits for the delay.
+    assert!(!reader.handle_heartbeat_msg(&hb_1, true, mr_state.clone()));
+    assert!(reader
+      .pending_heartbeat_responses
+      .contains_key(&writer_guid));
+    // Too soon after the previous one
+    assert!(!reader.handle_heartbeat_msg(&hb_2, false, mr_state.clone()));
+    assert!(reader.pending_heartbeat_responses[&writer_guid].final_flag_set);
+    assert!(reader.send_delayed_heartbeat_response(writer_guid));
+    assert!(reader.pending_heartbeat_responses.is_empty());
+
+    // Without delay and suppression, responses are immediate.
+    reader_command_sender
+      .try_send(ReaderCommand::SetHeartbeatResponseDelay {
+        delay: StdDuration::ZERO,
+        jitter: StdDuration::ZERO,
+      })
+      .unwrap();
+    reader_command_sender
+      .try_send(ReaderCommand::SetHeartbeatSuppressionDuration {
+        duration: StdDuration::ZERO,
+      })
+      .unwrap();
+    reader.process_command();
+    assert!(reader.handle_heartbeat_msg(&hb_3, true, mr_state));
+  }
+
+  #[test]
+  fn rtpsreader_lifespan_ignores_clock_jumps() {
+    let (send, _rec) = mio_channel::sync_channel::<()>(100);
+    let (status_sender, _status_receiver) =
+      mio_extras::channel::sync_channel::<DataReaderStatus>(100);
+    let (_reader_command_sender, reader_command_receiver) =
+      mio_channel::sync_channel::<ReaderCommand>(10);
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    dds_cache
+      .write()
+      .unwrap()
+      .add_new_topic("test".to_string(), TypeDesc::new("testi".to_string()));
+    let reader_ing = ReaderIngredients {
+      guid: GUID::dummy_test_guid(EntityKind::READER_NO_KEY_USER_DEFINED),
+      notification_sender: send,
+      data_reader_waker: WakerSlot::default(),
+      status_sender,
+      topic_name: "test".to_string(),
+      qos_policy: QosPolicyBuilder::new()
+        .lifespan(Lifespan {
+          duration: Duration::from_secs(10),
+        })
+        .build(),
+      data_reader_command_receiver: reader_command_receiver,
+    };
+    let clock = SteppingClock::new();
+    let mut reader = Reader::new(
+      reader_ing,
+      dds_cache.clone(),
+      Rc::new(Transports::from(UDPSender::new(0).unwrap())),
+      Timer::new(&TimeSource::from_clock(clock.clone()), None),
+    );
+
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    let mut receive = |sn: i64, source_timestamp: Timestamp| {
+      let data = DDSData::new(SerializedPayload::default());
+      let write_options = WriteOptions::from(Some(source_timestamp));
+      // Receive timestamps are keys in DDSCache, so they must be unique.
+      let receive_timestamp = Timestamp::now() + Duration::from_nanos(sn);
+      reader.process_received_data(
+        data,
+        receive_timestamp,
+        write_options,
+        writer_guid,
+        SequenceNumber::new(sn),
+      );
+    };
+    let in_cache = || {
+      dds_cache
+        .read()
+        .unwrap()
+        .topic_get_changes_in_range("test", &Timestamp::ZERO, &Timestamp::INFINITE)
+        .map(|(_, cc)| i64::from(cc.sequence_number))
+        .collect::<Vec<_>>()
+    };
+
+    // Sent 4 seconds ago. Sent 20 seconds ago has expired already.
+    receive(1, clock.now() - Duration::from_secs(4));
+    receive(2, clock.now() - Duration::from_secs(20));
+    assert_eq!(in_cache(), [1]);
+
+    // After the wall clock jumps, the lifespan still ends 6 seconds after
+    // reception.
+    clock.step_wall_clock(Duration::from_secs(3600));
+    reader.clock_jump();
+    assert_eq!(in_cache(), [1]);
+    clock.step_wall_clock(Duration::from_secs(-7200));
+    clock.advance(StdDuration::from_secs(5));
+    reader.clock_jump();
+    assert_eq!(in_cache(), [1]);
+    clock.advance(StdDuration::from_secs(2));
+    reader.clock_jump();
+    assert!(in_cache().is_empty());
+
+    // DataReaders learn which samples have expired.
+    let (expired, position) = dds_cache.read().unwrap().topic_get_expired_since("test", 0);
+    assert_eq!(expired.len(), 2);
+    assert_eq!(position, 2);
+  }
+
   #[test]
   #[ignore]
   fn rtpsreader_handle_gap() {
@@ -1458,6 +1949,7 @@ mod tests {
     let reader_ing = ReaderIngredients {
       guid: new_guid,
       notification_sender: send,
+      data_reader_waker: WakerSlot::default(),
       status_sender,
       topic_name: "test".to_string(),
       qos_policy: QosPolicies::qos_none(),
@@ -1466,8 +1958,8 @@ mod tests {
     let mut reader = Reader::new(
       reader_ing,
       dds_cache,
-      Rc::new(UDPSender::new(0).unwrap()),
-      mio_extras::timer::Builder::default().build(),
+      Rc::new(Transports::from(UDPSender::new(0).unwrap())),
+      Timer::default(),
     );
 
     let writer_guid = GUID {
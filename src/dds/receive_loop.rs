@@ -0,0 +1,242 @@
+// Receive threads of a DomainParticipant, see ProtocolThread::Receive.
+//
+// By default the event loop polls the transport listeners itself. When
+// receive threads are configured, the listeners are divided among them
+// instead. A receive loop reads the messages of its listeners and passes them
+// to the event loop, which owns the Readers and Writers, and handles the
+// messages as if it had read them itself. Sending and the timers of Readers
+// and Writers stay on the event loop for the same reason.
+
+use std::{collections::HashMap, io, net::IpAddr, sync::mpsc::TryRecvError, time::Duration};
+
+use bytes::Bytes;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use mio::{Events, Poll, PollOpt, Ready, Token};
+use mio_extras::channel as mio_channel;
+
+use crate::{
+  dds::{
+    health::ProtocolThread,
+    threads::{BackgroundLoop, BackgroundSpawner, BackgroundTask},
+  },
+  network::{
+    constant::STOP_POLL_TOKEN,
+    packet_capture::PacketCapture,
+    transport::{self, TransportListener},
+  },
+  structure::locator::Locator,
+};
+
+// Events from the receive loops to the event loop. When the event loop falls
+// this far behind, receive loops wait, and the transports buffer or drop
+// messages as if the event loop read them itself.
+const RECEIVE_QUEUE_SIZE: usize = 64;
+
+pub(crate) enum ReceiveEvent {
+  // Messages read from a listener
+  Messages(Vec<(Bytes, Option<IpAddr>)>),
+  // New locators of the listeners of a receive loop, after network interfaces
+  // changed
+  Locators(HashMap<Token, Vec<Locator>>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ReceiveCommand {
+  InterfacesChanged,
+  Stop,
+}
+
+// The end of the receive loops that the event loop holds
+pub(crate) struct ReceiveLoops {
+  pub events: mio_channel::Receiver<ReceiveEvent>,
+  pub commands: Vec<mio_channel::Sender<ReceiveCommand>>,
+}
+
+impl ReceiveLoops {
+  // No receive loops: the event loop reads all listeners
+  pub fn none() -> Self {
+    let (_sender, events) = mio_channel::sync_channel(1);
+    Self {
+      events,
+      commands: Vec::new(),
+    }
+  }
+
+  pub fn send(&self, command: ReceiveCommand) {
+    for sender in &self.commands {
+      // The loop has stopped already if this fails.
+      sender.send(command).unwrap_or(());
+    }
+  }
+
+  // Moves the `listeners` to `count` receive loops, which are started with
+  // `spawner`. The listeners are divided in the order of their Tokens, so that
+  // with two loops, the discovery and user traffic unicast listeners of UDPv4
+  // go to different ones.
+  pub fn spawn(
+    spawner: &BackgroundSpawner,
+    count: usize,
+    listeners: &mut HashMap<Token, Box<dyn TransportListener>>,
+    packet_capture: &PacketCapture,
+    participant_id: u16,
+  ) -> io::Result<(Self, Vec<BackgroundTask>)> {
+    let count = count.min(listeners.len());
+    if count == 0 {
+      return Ok((Self::none(), Vec::new()));
+    }
+    let mut tokens: Vec<Token> = listeners.keys().copied().collect();
+    tokens.sort();
+    let mut groups: Vec<HashMap<Token, Box<dyn TransportListener>>> =
+      (0..count).map(|_| HashMap::new()).collect();
+    for (i, token) in tokens.into_iter().enumerate() {
+      if let Some(listener) = listeners.remove(&token) {
+        groups[i % count].insert(token, listener);
+      }
+    }
+
+    let (event_sender, events) = mio_channel::sync_channel(RECEIVE_QUEUE_SIZE);
+    let mut receive_loops = Self {
+      events,
+      commands: Vec::new(),
+    };
+    let mut tasks = Vec::new();
+    for (i, listeners) in groups.into_iter().enumerate() {
+      let (command_sender, command_receiver) = mio_channel::channel();
+      let event_sender = event_sender.clone();
+      let packet_capture = packet_capture.clone();
+      let task = spawner.spawn_instance(
+        ProtocolThread::Receive,
+        if count > 1 { Some(i) } else { None },
+        format!("RustDDS Participant {} receive {}", participant_id, i),
+        move || ReceiveLoop::new(listeners, event_sender, command_receiver, &packet_capture),
+      );
+      match task {
+        Ok(task) => {
+          receive_loops.commands.push(command_sender);
+          tasks.push(task);
+        }
+        Err(e) => {
+          // Stop the loops that have started already
+          receive_loops.send(ReceiveCommand::Stop);
+          for task in tasks {
+            task.join().unwrap_or(());
+          }
+          return Err(e);
+        }
+      }
+    }
+    Ok((receive_loops, tasks))
+  }
+}
+
+pub(crate) struct ReceiveLoop {
+  poll: Poll,
+  listeners: HashMap<Token, Box<dyn TransportListener>>,
+  event_sender: mio_channel::SyncSender<ReceiveEvent>,
+  command_receiver: mio_channel::Receiver<ReceiveCommand>,
+}
+
+impl ReceiveLoop {
+  fn new(
+    mut listeners: HashMap<Token, Box<dyn TransportListener>>,
+    event_sender: mio_channel::SyncSender<ReceiveEvent>,
+    command_receiver: mio_channel::Receiver<ReceiveCommand>,
+    packet_capture: &PacketCapture,
+  ) -> Option<Self> {
+    let poll = Poll::new()
+      .map_err(|e| error!("Cannot create poll of a receive loop: {:?}", e))
+      .ok()?;
+    for (token, listener) in &mut listeners {
+      debug!("Receiving {:?} with {:?}", listener.kind(), token);
+      listener.set_packet_capture(packet_capture.clone());
+      poll
+        .register(
+          listener.evented(),
+          *token,
+          Ready::readable(),
+          PollOpt::edge(),
+        )
+        .map_err(|e| error!("Cannot register listener {:?}: {:?}", token, e))
+        .ok()?;
+    }
+    poll
+      .register(
+        &command_receiver,
+        STOP_POLL_TOKEN,
+        Ready::readable(),
+        PollOpt::edge(),
+      )
+      .map_err(|e| error!("Cannot register receive loop commands: {:?}", e))
+      .ok()?;
+    Some(Self {
+      poll,
+      listeners,
+      event_sender,
+      command_receiver,
+    })
+  }
+
+  // Returns false when the loop should stop
+  fn handle_commands(&mut self) -> bool {
+    loop {
+      match self.command_receiver.try_recv() {
+        Ok(ReceiveCommand::InterfacesChanged) => {
+          for listener in self.listeners.values_mut() {
+            listener.interfaces_changed();
+          }
+          let locators = transport::listener_locators(&self.listeners);
+          if self
+            .event_sender
+            .send(ReceiveEvent::Locators(locators))
+            .is_err()
+          {
+            return false;
+          }
+        }
+        Ok(ReceiveCommand::Stop) | Err(TryRecvError::Disconnected) => return false,
+        Err(TryRecvError::Empty) => return true,
+      }
+    }
+  }
+}
+
+impl BackgroundLoop for ReceiveLoop {
+  fn run(mut self: Box<Self>) {
+    let mut events = Events::with_capacity(16);
+    while self.run_round(&mut events, None) {}
+    debug!("Receive loop stopped");
+  }
+
+  fn run_round(&mut self, events: &mut Events, timeout: Option<Duration>) -> bool {
+    if let Err(e) = self.poll.poll(events, timeout) {
+      error!("Receive loop poll failed: {:?}", e);
+      return false;
+    }
+    for event in events.iter() {
+      if event.token() == STOP_POLL_TOKEN {
+        if !self.handle_commands() {
+          return false;
+        }
+        continue;
+      }
+      let messages = match self.listeners.get_mut(&event.token()) {
+        Some(listener) => listener.messages_with_source(),
+        None => {
+          error!("No listener with token {:?}", event.token());
+          continue;
+        }
+      };
+      // An error means that the event loop has stopped.
+      if !messages.is_empty()
+        && self
+          .event_sender
+          .send(ReceiveEvent::Messages(messages))
+          .is_err()
+      {
+        return false;
+      }
+    }
+    true
+  }
+}
@@ -0,0 +1,741 @@
+use std::{
+  collections::BTreeMap,
+  fs::File,
+  io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+  path::Path,
+  thread,
+  time::Instant,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::{
+  dds::{
+    no_key::{datareader::DataReader, datawriter::DataWriter},
+    participant::DomainParticipant,
+    pubsub::{Publisher, Subscriber},
+    qos::{HasQoSPolicy, QosPolicies},
+    topic::{Topic, TopicDescription, TopicKind},
+    typedesc::TypeDesc,
+    values::result::{Error, Result},
+  },
+  messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
+  serialization::{RawDeserializerAdapter, RawSample, RawSerializerAdapter},
+  structure::{duration::Duration, guid::GUID, time::Timestamp},
+};
+
+// Recording file format. All integers are little-endian.
+//
+// file   = MAGIC record* [index record, index offset: u64, INDEX_MAGIC]
+// record = record kind: u8, body length: u32, body
+//
+// TOPIC body:  topic id: u32, topic kind: u8, name: string, type name: string
+// SAMPLE body: topic id: u32, recorded at: time, source timestamp present: u8,
+//              source timestamp: time, writer GUID: [u8; 16],
+//              representation identifier: [u8; 2], serialized payload
+// INDEX body:  count: u32, TOPIC record offset: u64 (count times),
+//              count: u32, (recorded at: time, SAMPLE record offset: u64)
+//              (count times)
+//
+// string is a u32 length followed by UTF-8, time is a Timestamp as 32.32 fixed
+// point seconds, u64. A TOPIC record precedes the samples of the topic.
+//
+// The index lists the TOPIC records, and seek points: SAMPLE records at least
+// SEEK_POINT_INTERVAL apart. It is written when recording is finished. A
+// recording without one, e.g. after a crash, is indexed by scanning it.
+
+const MAGIC: &[u8; 8] = b"RDDSREC1";
+const INDEX_MAGIC: &[u8; 8] = b"RDDSIDX1";
+
+const TOPIC_RECORD: u8 = 1;
+const SAMPLE_RECORD: u8 = 2;
+const INDEX_RECORD: u8 = 3;
+
+const RECORD_HEADER_SIZE: u64 = 5;
+const TRAILER_SIZE: u64 = 16;
+
+const SEEK_POINT_INTERVAL: Duration = Duration::from_secs(1);
+
+type RawReader = DataReader<RawSample, RawDeserializerAdapter>;
+type RawWriter = DataWriter<RawSample, RawSerializerAdapter>;
+
+/// A Topic in a recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedTopic {
+  pub name: String,
+  pub type_name: String,
+  pub kind: TopicKind,
+}
+
+// Raw samples are read and written through a NoKey Topic, which matches
+// endpoints of both kinds. The Topic is not registered to the participant, so
+// that it does not replace an application Topic of the same name.
+fn raw_topic(
+  participant: &DomainParticipant,
+  name: &str,
+  type_name: &str,
+  qos: &QosPolicies,
+) -> Topic {
+  Topic::new(
+    &participant.weak_clone(),
+    name.to_string(),
+    TypeDesc::new(type_name.to_string()),
+    qos,
+    TopicKind::NoKey,
+  )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecordedSample {
+  topic_id: u32,
+  recorded_at: Timestamp,
+  source_timestamp: Option<Timestamp>,
+  writer_guid: GUID,
+  data: RawSample,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Index {
+  topics: Vec<u64>,
+  seek_points: Vec<(Timestamp, u64)>,
+}
+
+/// Records the samples of selected Topics to a file, for replay with a
+/// [`Player`].
+///
+/// The recorder reads each recorded Topic with a DataReader of [`RawSample`],
+/// so it needs no knowledge of the data types. A sample is stored serialized,
+/// with the time it was recorded, its source timestamp and the GUID of its
+/// writer. Only samples carrying data are recorded, not dispose or unregister
+/// notifications.
+///
+/// Call [`record_received`](Self::record_received) periodically to store the
+/// samples received since the previous call. The recording time of a sample is
+/// the time of that call, so replay timing is only as accurate as the calling
+/// interval. Call [`finish`](Self::finish) at the end to write the index of the
+/// recording.
+pub struct Recorder<W: Write> {
+  participant: DomainParticipant,
+  subscriber: Subscriber,
+  readers: Vec<(u32, RawReader)>,
+  output: RecordWriter<W>,
+}
+
+impl Recorder<BufWriter<File>> {
+  /// Creates a recording file, replacing an existing one.
+  pub fn create(participant: &DomainParticipant, path: impl AsRef<Path>) -> Result<Self> {
+    Self::new(participant, BufWriter::new(File::create(path)?))
+  }
+}
+
+impl<W: Write> Recorder<W> {
+  pub fn new(participant: &DomainParticipant, output: W) -> Result<Self> {
+    Ok(Self {
+      participant: participant.clone(),
+      subscriber: participant.create_subscriber(&QosPolicies::qos_none())?,
+      readers: Vec::new(),
+      output: RecordWriter::new(output)?,
+    })
+  }
+
+  /// Starts recording a Topic. The DataReader of the recorder uses the QoS of
+  /// the Topic.
+  pub fn record(&mut self, topic: &Topic) -> Result<()> {
+    let name = topic.name();
+    let type_name = topic.get_type().name().to_string();
+    let qos = topic.qos();
+    let reader = self
+      .subscriber
+      .create_datareader_no_key::<RawSample, RawDeserializerAdapter>(
+        &raw_topic(&self.participant, &name, &type_name, &qos),
+        Some(qos),
+      )?;
+    let topic_id = self.output.write_topic(&RecordedTopic {
+      name,
+      type_name,
+      kind: topic.kind(),
+    })?;
+    self.readers.push((topic_id, reader));
+    Ok(())
+  }
+
+  /// Stores the samples received since the previous call. Returns the number
+  /// of samples stored.
+  pub fn record_received(&mut self) -> Result<usize> {
+    let recorded_at = Timestamp::now();
+    let mut count = 0;
+    for (topic_id, reader) in self.readers.iter_mut() {
+      while let Some(sample) = reader.take_next_sample()? {
+        let info = sample.sample_info();
+        self.output.write_sample(&RecordedSample {
+          topic_id: *topic_id,
+          recorded_at,
+          source_timestamp: info.source_timestamp(),
+          writer_guid: info.writer_guid(),
+          data: sample.into_value(),
+        })?;
+        count += 1;
+      }
+    }
+    Ok(count)
+  }
+
+  /// Records the remaining received samples and writes the index. Returns the
+  /// output, flushed.
+  pub fn finish(mut self) -> Result<W> {
+    self.record_received()?;
+    Ok(self.output.finish()?)
+  }
+}
+
+// Writes records and collects the index.
+struct RecordWriter<W: Write> {
+  output: W,
+  position: u64,
+  index: Index,
+}
+
+impl<W: Write> RecordWriter<W> {
+  fn new(mut output: W) -> io::Result<Self> {
+    output.write_all(MAGIC)?;
+    Ok(Self {
+      output,
+      position: MAGIC.len() as u64,
+      index: Index::default(),
+    })
+  }
+
+  fn write_record(&mut self, kind: u8, body: &[u8]) -> io::Result<u64> {
+    let offset = self.position;
+    self.output.write_u8(kind)?;
+    self.output.write_u32::<LittleEndian>(body.len() as u32)?;
+    self.output.write_all(body)?;
+    self.position += RECORD_HEADER_SIZE + body.len() as u64;
+    Ok(offset)
+  }
+
+  fn write_topic(&mut self, topic: &RecordedTopic) -> io::Result<u32> {
+    let topic_id = self.index.topics.len() as u32;
+    let mut body = Vec::new();
+    body.write_u32::<LittleEndian>(topic_id)?;
+    body.write_u8(match topic.kind {
+      TopicKind::NoKey => 0,
+      TopicKind::WithKey => 1,
+    })?;
+    write_string(&mut body, &topic.name)?;
+    write_string(&mut body, &topic.type_name)?;
+    let offset = self.write_record(TOPIC_RECORD, &body)?;
+    self.index.topics.push(offset);
+    Ok(topic_id)
+  }
+
+  fn write_sample(&mut self, sample: &RecordedSample) -> io::Result<()> {
+    let mut body = Vec::with_capacity(40 + sample.data.bytes.len());
+    body.write_u32::<LittleEndian>(sample.topic_id)?;
+    body.write_u64::<LittleEndian>(sample.recorded_at.to_ticks())?;
+    body.write_u8(sample.source_timestamp.is_some() as u8)?;
+    body.write_u64::<LittleEndian>(sample.source_timestamp.map_or(0, Timestamp::to_ticks))?;
+    body.write_all(&sample.writer_guid.to_bytes())?;
+    body.write_all(&sample.data.encoding.to_bytes())?;
+    body.write_all(&sample.data.bytes)?;
+    let offset = self.write_record(SAMPLE_RECORD, &body)?;
+    self.index.add_sample(sample.recorded_at, offset);
+    Ok(())
+  }
+
+  fn finish(mut self) -> io::Result<W> {
+    let mut body = Vec::new();
+    body.write_u32::<LittleEndian>(self.index.topics.len() as u32)?;
+    for offset in &self.index.topics {
+      body.write_u64::<LittleEndian>(*offset)?;
+    }
+    body.write_u32::<LittleEndian>(self.index.seek_points.len() as u32)?;
+    for (time, offset) in &self.index.seek_points {
+      body.write_u64::<LittleEndian>(time.to_ticks())?;
+      body.write_u64::<LittleEndian>(*offset)?;
+    }
+    let index_offset = self.write_record(INDEX_RECORD, &body)?;
+    self.output.write_u64::<LittleEndian>(index_offset)?;
+    self.output.write_all(INDEX_MAGIC)?;
+    self.output.flush()?;
+    Ok(self.output)
+  }
+}
+
+impl Index {
+  fn add_sample(&mut self, recorded_at: Timestamp, offset: u64) {
+    let is_seek_point = match self.seek_points.last() {
+      Some((previous, _)) => recorded_at - *previous >= SEEK_POINT_INTERVAL,
+      None => true,
+    };
+    if is_seek_point {
+      self.seek_points.push((recorded_at, offset));
+    }
+  }
+}
+
+/// Replays a recording made with a [`Recorder`].
+///
+/// Samples are written with a DataWriter of [`RawSample`] per recorded Topic,
+/// in the data representation they were recorded in. Samples of WithKey Topics
+/// are written without a key hash, so readers compute the instance from the
+/// data.
+///
+/// [`play`](Self::play) writes the samples with their original timing,
+/// optionally sped up or slowed down with [`set_rate`](Self::set_rate).
+/// [`play_next`](Self::play_next) writes one sample at a time, for stepping
+/// through a recording.
+pub struct Player<R: Read + Seek> {
+  participant: DomainParticipant,
+  publisher: Publisher,
+  qos: QosPolicies,
+  rate: f64,
+  input: R,
+  position: u64,
+  end: u64,
+  topics: BTreeMap<u32, RecordedTopic>,
+  writers: BTreeMap<u32, RawWriter>,
+  index: Index,
+}
+
+impl Player<BufReader<File>> {
+  /// Opens a recording file. DataWriters use the given QoS.
+  pub fn open(
+    participant: &DomainParticipant,
+    path: impl AsRef<Path>,
+    qos: &QosPolicies,
+  ) -> Result<Self> {
+    Self::new(participant, BufReader::new(File::open(path)?), qos)
+  }
+}
+
+impl<R: Read + Seek> Player<R> {
+  pub fn new(participant: &DomainParticipant, input: R, qos: &QosPolicies) -> Result<Self> {
+    let mut player = Self {
+      participant: participant.clone(),
+      publisher: participant.create_publisher(&QosPolicies::qos_none())?,
+      qos: qos.clone(),
+      rate: 1.0,
+      input,
+      position: 0,
+      end: 0,
+      topics: BTreeMap::new(),
+      writers: BTreeMap::new(),
+      index: Index::default(),
+    };
+    player.read_index()?;
+    for offset in player.index.topics.clone() {
+      player.seek_to(offset)?;
+      if let Some((TOPIC_RECORD, body)) = player.read_record()? {
+        let (topic_id, topic) = parse_topic(&body)?;
+        player.topics.insert(topic_id, topic);
+      }
+    }
+    player.seek_to(MAGIC.len() as u64)?;
+    Ok(player)
+  }
+
+  /// Topics in the recording.
+  pub fn topics(&self) -> Vec<RecordedTopic> {
+    self.topics.values().cloned().collect()
+  }
+
+  /// Recording time of the first sample, or `None` if there are no samples.
+  pub fn start_time(&self) -> Option<Timestamp> {
+    self.index.seek_points.first().map(|(time, _)| *time)
+  }
+
+  /// Sets the playback speed of [`play`](Self::play) relative to the original
+  /// timing, e.g. 2.0 for double speed.
+  pub fn set_rate(&mut self, rate: f64) -> Result<()> {
+    if !(rate.is_finite() && rate > 0.0) {
+      return Error::bad_parameter(format!("Playback rate must be positive, not {}", rate));
+    }
+    self.rate = rate;
+    Ok(())
+  }
+
+  /// Continues playback from the first sample recorded at or after `time`.
+  pub fn seek(&mut self, time: Timestamp) -> Result<()> {
+    let offset = self
+      .index
+      .seek_points
+      .iter()
+      .take_while(|(seek_time, _)| *seek_time <= time)
+      .last()
+      .map_or(MAGIC.len() as u64, |(_, offset)| *offset);
+    self.seek_to(offset)?;
+    loop {
+      let record_start = self.position;
+      match self.next_sample()? {
+        Some(sample) if sample.recorded_at < time => {}
+        Some(_) => return Ok(self.seek_to(record_start)?),
+        None => return Ok(()),
+      }
+    }
+  }
+
+  /// Writes the next sample right away. Returns its recording time, or `None`
+  /// at the end of the recording.
+  pub fn play_next(&mut self) -> Result<Option<Timestamp>> {
+    match self.next_sample()? {
+      Some(sample) => {
+        let recorded_at = sample.recorded_at;
+        self.write(sample)?;
+        Ok(Some(recorded_at))
+      }
+      None => Ok(None),
+    }
+  }
+
+  /// Writes the rest of the recording with the original timing, scaled by the
+  /// playback rate. Blocks until the end of the recording, and returns the
+  /// number of samples written.
+  pub fn play(&mut self) -> Result<usize> {
+    let started = Instant::now();
+    let mut first_recorded_at = None;
+    let mut count = 0;
+    while let Some(sample) = self.next_sample()? {
+      let first = *first_recorded_at.get_or_insert(sample.recorded_at);
+      let due = (sample.recorded_at - first).to_std().div_f64(self.rate);
+      if let Some(wait) = due.checked_sub(started.elapsed()) {
+        thread::sleep(wait);
+      }
+      self.write(sample)?;
+      count += 1;
+    }
+    Ok(count)
+  }
+
+  fn write(&mut self, sample: RecordedSample) -> Result<()> {
+    let topic_id = sample.topic_id;
+    let topic = match self.topics.get(&topic_id) {
+      Some(topic) => topic,
+      None => {
+        warn!("Recorded sample of unknown topic id {}", topic_id);
+        return Ok(());
+      }
+    };
+    if !self.writers.contains_key(&topic_id) {
+      let writer = self
+        .publisher
+        .create_datawriter_no_key::<RawSample, RawSerializerAdapter>(
+          &raw_topic(&self.participant, &topic.name, &topic.type_name, &self.qos),
+          Some(self.qos.clone()),
+        )?;
+      self.writers.insert(topic_id, writer);
+    }
+    self.writers[&topic_id].write(sample.data, None)
+  }
+
+  fn seek_to(&mut self, offset: u64) -> io::Result<()> {
+    self.position = self.input.seek(SeekFrom::Start(offset))?;
+    Ok(())
+  }
+
+  // Reads the next record, or returns None at the end of the recording.
+  fn read_record(&mut self) -> io::Result<Option<(u8, Vec<u8>)>> {
+    if self.position + RECORD_HEADER_SIZE > self.end {
+      return Ok(None);
+    }
+    let kind = self.input.read_u8()?;
+    let length = self.input.read_u32::<LittleEndian>()?;
+    if self.position + RECORD_HEADER_SIZE + u64::from(length) > self.end {
+      return Ok(None);
+    }
+    let mut body = vec![0; length as usize];
+    self.input.read_exact(&mut body)?;
+    self.position += RECORD_HEADER_SIZE + u64::from(length);
+    Ok(Some((kind, body)))
+  }
+
+  fn next_sample(&mut self) -> io::Result<Option<RecordedSample>> {
+    while let Some((kind, body)) = self.read_record()? {
+      if kind == SAMPLE_RECORD {
+        return parse_sample(&body).map(Some);
+      }
+    }
+    Ok(None)
+  }
+
+  fn read_index(&mut self) -> io::Result<()> {
+    let mut magic = [0; 8];
+    self.input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+      return Err(invalid_data("Not a RustDDS recording"));
+    }
+    let file_size = self.input.seek(SeekFrom::End(0))?;
+
+    if file_size >= MAGIC.len() as u64 + TRAILER_SIZE {
+      self.input.seek(SeekFrom::Start(file_size - TRAILER_SIZE))?;
+      let index_offset = self.input.read_u64::<LittleEndian>()?;
+      self.input.read_exact(&mut magic)?;
+      if &magic == INDEX_MAGIC && index_offset < file_size - TRAILER_SIZE {
+        self.end = file_size - TRAILER_SIZE;
+        self.seek_to(index_offset)?;
+        if let Some((INDEX_RECORD, body)) = self.read_record()? {
+          self.index = parse_index(&body)?;
+          self.end = index_offset;
+          return Ok(());
+        }
+      }
+    }
+
+    // No index: scan the records. A record cut short at the end is ignored.
+    debug!("Recording has no index, scanning it");
+    self.end = file_size;
+    self.seek_to(MAGIC.len() as u64)?;
+    self.index = Index::default();
+    loop {
+      let offset = self.position;
+      match self.read_record()? {
+        Some((TOPIC_RECORD, _)) => self.index.topics.push(offset),
+        Some((SAMPLE_RECORD, body)) => {
+          let sample = parse_sample(&body)?;
+          self.index.add_sample(sample.recorded_at, offset);
+        }
+        Some(_) => {}
+        None => break,
+      }
+    }
+    self.end = self.position;
+    Ok(())
+  }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_string(output: &mut Vec<u8>, string: &str) -> io::Result<()> {
+  output.write_u32::<LittleEndian>(string.len() as u32)?;
+  output.write_all(string.as_bytes())
+}
+
+fn read_string(input: &mut &[u8]) -> io::Result<String> {
+  let length = input.read_u32::<LittleEndian>()? as usize;
+  if length > input.len() {
+    return Err(invalid_data("String longer than its record"));
+  }
+  let (string, rest) = input.split_at(length);
+  *input = rest;
+  String::from_utf8(string.to_vec()).map_err(|_| invalid_data("String is not UTF-8"))
+}
+
+fn parse_topic(mut body: &[u8]) -> io::Result<(u32, RecordedTopic)> {
+  let topic_id = body.read_u32::<LittleEndian>()?;
+  let kind = match body.read_u8()? {
+    0 => TopicKind::NoKey,
+    1 => TopicKind::WithKey,
+    _ => return Err(invalid_data("Unknown topic kind")),
+  };
+  let name = read_string(&mut body)?;
+  let type_name = read_string(&mut body)?;
+  Ok((
+    topic_id,
+    RecordedTopic {
+      name,
+      type_name,
+      kind,
+    },
+  ))
+}
+
+fn parse_sample(mut body: &[u8]) -> io::Result<RecordedSample> {
+  let topic_id = body.read_u32::<LittleEndian>()?;
+  let recorded_at = Timestamp::from_ticks(body.read_u64::<LittleEndian>()?);
+  let has_source_timestamp = body.read_u8()? != 0;
+  let source_timestamp = Timestamp::from_ticks(body.read_u64::<LittleEndian>()?);
+  let mut guid = [0; 16];
+  body.read_exact(&mut guid)?;
+  let mut encoding = [0; 2];
+  body.read_exact(&mut encoding)?;
+  Ok(RecordedSample {
+    topic_id,
+    recorded_at,
+    source_timestamp: if has_source_timestamp {
+      Some(source_timestamp)
+    } else {
+      None
+    },
+    writer_guid: GUID::from_bytes(guid),
+    data: RawSample {
+      encoding: RepresentationIdentifier::from_bytes(&encoding)?,
+      bytes: body.to_vec(),
+    },
+  })
+}
+
+fn parse_index(mut body: &[u8]) -> io::Result<Index> {
+  let mut index = Index::default();
+  for _ in 0..body.read_u32::<LittleEndian>()? {
+    index.topics.push(body.read_u64::<LittleEndian>()?);
+  }
+  for _ in 0..body.read_u32::<LittleEndian>()? {
+    let time = Timestamp::from_ticks(body.read_u64::<LittleEndian>()?);
+    index
+      .seek_points
+      .push((time, body.read_u64::<LittleEndian>()?));
+  }
+  Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{convert::TryInto, io::Cursor, time::Duration as StdDuration};
+
+  use super::*;
+  use crate::{
+    dds::qos::{policy, QosPolicyBuilder},
+    structure::guid::EntityKind,
+  };
+
+  fn sample(topic_id: u32, recorded_at: Timestamp, byte: u8) -> RecordedSample {
+    RecordedSample {
+      topic_id,
+      recorded_at,
+      source_timestamp: Some(recorded_at - Duration::from_millis(1)),
+      writer_guid: GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED),
+      data: RawSample {
+        encoding: RepresentationIdentifier::CDR_BE,
+        bytes: vec![byte; 4],
+      },
+    }
+  }
+
+  #[test]
+  fn recording_index_and_seek() {
+    let start = Timestamp::now();
+    let mut writer = RecordWriter::new(Cursor::new(Vec::new())).unwrap();
+    let topic = RecordedTopic {
+      name: "recorded".to_string(),
+      type_name: "Recorded".to_string(),
+      kind: TopicKind::WithKey,
+    };
+    assert_eq!(writer.write_topic(&topic).unwrap(), 0);
+    // Samples every 400 ms
+    let samples: Vec<RecordedSample> = (0..10u8)
+      .map(|i| sample(0, start + Duration::from_millis(400 * i64::from(i)), i))
+      .collect();
+    for s in &samples {
+      writer.write_sample(s).unwrap();
+    }
+    let index = writer.index.clone();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let participant = DomainParticipant::new(0).unwrap();
+    let qos = QosPolicies::qos_none();
+    let mut player = Player::new(&participant, Cursor::new(bytes.clone()), &qos).unwrap();
+    assert_eq!(player.topics(), vec![topic.clone()]);
+    assert_eq!(player.start_time(), Some(start));
+    assert_eq!(player.index, index);
+    assert_eq!(
+      index
+        .seek_points
+        .iter()
+        .map(|(t, _)| *t)
+        .collect::<Vec<_>>(),
+      vec![
+        samples[0].recorded_at,
+        samples[3].recorded_at,
+        samples[6].recorded_at,
+        samples[9].recorded_at
+      ]
+    );
+    assert_eq!(player.next_sample().unwrap(), Some(samples[0].clone()));
+
+    // Between seek points, and at a sample
+    player.seek(start + Duration::from_millis(1500)).unwrap();
+    assert_eq!(player.next_sample().unwrap(), Some(samples[4].clone()));
+    player.seek(samples[8].recorded_at).unwrap();
+    assert_eq!(player.next_sample().unwrap(), Some(samples[8].clone()));
+    assert_eq!(player.next_sample().unwrap(), Some(samples[9].clone()));
+    assert_eq!(player.next_sample().unwrap(), None);
+
+    // Without the index and with the last record cut short, the recording is
+    // scanned up to the last complete record.
+    let index_offset =
+      u64::from_le_bytes(bytes[bytes.len() - 16..bytes.len() - 8].try_into().unwrap()) as usize;
+    let truncated = bytes[..index_offset - 2].to_vec();
+    let mut player = Player::new(&participant, Cursor::new(truncated), &qos).unwrap();
+    assert_eq!(player.topics(), vec![topic]);
+    player.seek(samples[8].recorded_at).unwrap();
+    assert_eq!(player.next_sample().unwrap(), Some(samples[8].clone()));
+    assert_eq!(player.next_sample().unwrap(), None);
+
+    assert!(Player::new(&participant, Cursor::new(b"not a recording".to_vec()), &qos).is_err());
+  }
+
+  #[test]
+  fn record_and_replay() {
+    let qos = QosPolicyBuilder::new()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      })
+      .durability(policy::Durability::TransientLocal)
+      .history(policy::History::KeepAll)
+      .build();
+    let topic_of = |participant: &DomainParticipant| {
+      participant
+        .create_topic(
+          "recording_test".to_string(),
+          "RecordingTest".to_string(),
+          &qos,
+          TopicKind::NoKey,
+        )
+        .unwrap()
+    };
+
+    // Record
+    let recording = {
+      let participant = DomainParticipant::new(0).unwrap();
+      let topic = topic_of(&participant);
+      let writer = participant
+        .create_publisher(&qos)
+        .unwrap()
+        .create_datawriter_no_key_cdr::<String>(&topic, None)
+        .unwrap();
+      let mut recorder = Recorder::new(&participant, Vec::new()).unwrap();
+      recorder.record(&topic).unwrap();
+      for text in ["first", "second", "third"] {
+        writer.write(text.to_string(), None).unwrap();
+      }
+      let mut recorded = 0;
+      for _ in 0..100 {
+        recorded += recorder.record_received().unwrap();
+        if recorded == 3 {
+          break;
+        }
+        thread::sleep(StdDuration::from_millis(50));
+      }
+      assert_eq!(recorded, 3);
+      recorder.finish().unwrap()
+    };
+
+    // Replay
+    let participant = DomainParticipant::new(0).unwrap();
+    let mut reader = participant
+      .create_subscriber(&qos)
+      .unwrap()
+      .create_datareader_no_key_cdr::<String>(&topic_of(&participant), None)
+      .unwrap();
+    let mut player = Player::new(&participant, Cursor::new(recording), &qos).unwrap();
+    player.set_rate(10.0).unwrap();
+    assert!(player.set_rate(0.0).is_err());
+    assert_eq!(player.play().unwrap(), 3);
+
+    let mut received = Vec::new();
+    for _ in 0..100 {
+      while let Some(sample) = reader.take_next_sample().unwrap() {
+        received.push(sample.into_value());
+      }
+      if received.len() == 3 {
+        break;
+      }
+      thread::sleep(StdDuration::from_millis(50));
+    }
+    assert_eq!(received, vec!["first", "second", "third"]);
+  }
+}
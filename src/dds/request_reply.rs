@@ -0,0 +1,535 @@
+use std::{
+  collections::HashMap,
+  future::Future,
+  io,
+  pin::Pin,
+  task::{self, Context},
+  thread,
+  time::{Duration as StdDuration, Instant},
+};
+
+use mio::{Evented, Poll, PollOpt, Ready, Token};
+use serde::{de::DeserializeOwned, Serialize};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::{
+  dds::{
+    helpers::WakerSlot,
+    no_key::{datareader::DataReader, datawriter::DataWriter},
+    pubsub::{Publisher, Subscriber},
+    qos::{policy, HasQoSPolicy, QosPolicies, QosPolicyBuilder},
+    topic::Topic,
+    values::result::{Error, Result},
+    with_key::datawriter::{WriteOptions, WriteOptionsBuilder},
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+  structure::{duration::Duration, rpc::SampleIdentity},
+};
+
+fn rpc_qos(topic: &Topic) -> QosPolicies {
+  topic.qos().modify_by(
+    &QosPolicyBuilder::new()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      })
+      .history(policy::History::KeepAll)
+      .build(),
+  )
+}
+
+// Requests waiting for a reply, and the replies that have arrived, but have
+// not been taken yet. Only the first reply to a request is kept.
+struct PendingReplies<P> {
+  replies: HashMap<SampleIdentity, Option<P>>,
+}
+
+impl<P> PendingReplies<P> {
+  fn new() -> Self {
+    Self {
+      replies: HashMap::new(),
+    }
+  }
+
+  fn expect(&mut self, request_id: SampleIdentity) {
+    self.replies.insert(request_id, None);
+  }
+
+  fn is_expected(&self, request_id: &SampleIdentity) -> bool {
+    self.replies.contains_key(request_id)
+  }
+
+  // Returns false, if the reply is not for any pending request.
+  fn add(&mut self, related_request: Option<SampleIdentity>, reply: P) -> bool {
+    match related_request.and_then(|id| self.replies.get_mut(&id)) {
+      Some(slot @ None) => {
+        *slot = Some(reply);
+        true
+      }
+      _ => false,
+    }
+  }
+
+  fn take(&mut self, request_id: &SampleIdentity) -> Option<P> {
+    match self.replies.get(request_id) {
+      Some(Some(_)) => self.replies.remove(request_id).flatten(),
+      _ => None,
+    }
+  }
+
+  fn forget(&mut self, request_id: &SampleIdentity) {
+    self.replies.remove(request_id);
+  }
+}
+
+type ReplyReader<P> = DataReader<P, CDRDeserializerAdapter<P>>;
+
+// Takes all received replies and stores the ones that are for pending
+// requests.
+fn receive_replies<P>(reader: &mut ReplyReader<P>, pending: &mut PendingReplies<P>) -> Result<()>
+where
+  P: DeserializeOwned + 'static,
+{
+  while let Some(sample) = reader.take_next_sample()? {
+    let related_request = sample.sample_info().related_sample_identity();
+    if !pending.add(related_request, sample.into_value()) {
+      debug!("Discarding reply to {:?}", related_request);
+    }
+  }
+  Ok(())
+}
+
+/// Client side of request/reply over DDS.
+///
+/// A `Requester` writes requests of type `Q` to a request Topic and reads
+/// replies of type `P` from a reply Topic. The other side is a [`Replier`] on
+/// the same Topics. Both Topics are NoKey Topics.
+///
+/// Each request is identified by the [`SampleIdentity`] of the request sample,
+/// and the `Replier` sends it back with the reply as the related sample
+/// identity (in inline QoS), as in the DDS-RPC specification. Therefore
+/// several `Requester`s can share the Topics: each one takes only the replies
+/// to its own requests.
+///
+/// Requests and replies are sent with RELIABLE Reliability and KEEP_ALL
+/// History. Only the first reply to each request is returned.
+///
+/// # Example
+///
+/// ```no_run
+/// # use rustdds::*;
+/// # async fn example() -> rustdds::dds::Result<()> {
+/// # let participant = DomainParticipant::new(0)?;
+/// # let qos = QosPolicyBuilder::new().build();
+/// # let publisher = participant.create_publisher(&qos)?;
+/// # let subscriber = participant.create_subscriber(&qos)?;
+/// let request_topic = participant.create_topic(
+///   "add_request".to_string(), "AddRequest".to_string(), &qos, TopicKind::NoKey)?;
+/// let reply_topic = participant.create_topic(
+///   "add_reply".to_string(), "AddReply".to_string(), &qos, TopicKind::NoKey)?;
+/// let mut requester =
+///   Requester::<(i32, i32), i32>::new(&publisher, &subscriber, &request_topic, &reply_topic)?;
+/// let sum = requester.call((1, 2), std::time::Duration::from_secs(1)).await?;
+/// assert_eq!(sum, 3);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Requester<Q, P>
+where
+  Q: Serialize,
+  P: DeserializeOwned + 'static,
+{
+  request_writer: DataWriter<Q, CDRSerializerAdapter<Q>>,
+  reply_reader: ReplyReader<P>,
+  pending: PendingReplies<P>,
+}
+
+impl<Q, P> Requester<Q, P>
+where
+  Q: Serialize,
+  P: DeserializeOwned + 'static,
+{
+  pub fn new(
+    publisher: &Publisher,
+    subscriber: &Subscriber,
+    request_topic: &Topic,
+    reply_topic: &Topic,
+  ) -> Result<Self> {
+    let request_writer =
+      publisher.create_datawriter_no_key_cdr(request_topic, Some(rpc_qos(request_topic)))?;
+    let reply_reader =
+      subscriber.create_datareader_no_key_cdr(reply_topic, Some(rpc_qos(reply_topic)))?;
+    Ok(Self {
+      request_writer,
+      reply_reader,
+      pending: PendingReplies::new(),
+    })
+  }
+
+  /// Sends a request. The returned identity is used to get the reply with
+  /// [`take_reply`](Self::take_reply) or
+  /// [`receive_reply`](Self::receive_reply).
+  pub fn send_request(&mut self, request: Q) -> Result<SampleIdentity> {
+    let request_id = self
+      .request_writer
+      .write_with_options(request, WriteOptions::default())?;
+    self.pending.expect(request_id);
+    Ok(request_id)
+  }
+
+  /// Takes the reply to a request, if it has arrived. This does not block.
+  ///
+  /// Replies to other requests of this `Requester` are kept until they are
+  /// taken. It is an error to ask for a request that was not sent, or
+  /// whose reply was already taken.
+  pub fn take_reply(&mut self, request_id: SampleIdentity) -> Result<Option<P>> {
+    self.check_pending(&request_id)?;
+    receive_replies(&mut self.reply_reader, &mut self.pending)?;
+    Ok(self.pending.take(&request_id))
+  }
+
+  /// Waits for the reply to a request. If it does not arrive within
+  /// `timeout`, returns [`Error::Timeout`], and a reply arriving later is
+  /// discarded.
+  pub async fn receive_reply(
+    &mut self,
+    request_id: SampleIdentity,
+    timeout: StdDuration,
+  ) -> Result<P> {
+    self.check_pending(&request_id)?;
+    ReplyFuture {
+      requester: self,
+      request_id,
+      deadline: Instant::now() + timeout,
+      timer: None,
+    }
+    .await
+  }
+
+  /// Sends a request and waits for its reply, like
+  /// [`receive_reply`](Self::receive_reply).
+  pub async fn call(&mut self, request: Q, timeout: StdDuration) -> Result<P> {
+    let request_id = self.send_request(request)?;
+    self.receive_reply(request_id, timeout).await
+  }
+
+  /// Stops waiting for the reply to a request.
+  pub fn cancel(&mut self, request_id: SampleIdentity) {
+    self.pending.forget(&request_id);
+  }
+
+  fn check_pending(&self, request_id: &SampleIdentity) -> Result<()> {
+    if self.pending.is_expected(request_id) {
+      Ok(())
+    } else {
+      Error::precondition_not_met(format!("No pending request {:?}", request_id))
+    }
+  }
+}
+
+// Readable, when replies have arrived.
+impl<Q, P> Evented for Requester<Q, P>
+where
+  Q: Serialize,
+  P: DeserializeOwned + 'static,
+{
+  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    self.reply_reader.register(poll, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &Poll,
+    token: Token,
+    interest: Ready,
+    opts: PollOpt,
+  ) -> io::Result<()> {
+    self.reply_reader.reregister(poll, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &Poll) -> io::Result<()> {
+    self.reply_reader.deregister(poll)
+  }
+}
+
+struct ReplyFuture<'a, Q, P>
+where
+  Q: Serialize,
+  P: DeserializeOwned + 'static,
+{
+  requester: &'a mut Requester<Q, P>,
+  request_id: SampleIdentity,
+  deadline: Instant,
+  // Woken by a timer thread at the deadline. Created on first Pending.
+  timer: Option<WakerSlot>,
+}
+
+impl<Q, P> Future for ReplyFuture<'_, Q, P>
+where
+  Q: Serialize,
+  P: DeserializeOwned + 'static,
+{
+  type Output = Result<P>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<Self::Output> {
+    let this = self.get_mut();
+    let request_id = this.request_id;
+    let Requester {
+      reply_reader,
+      pending,
+      ..
+    } = &mut *this.requester;
+
+    let result = reply_reader.poll_when_available(cx, |reader| {
+      receive_replies(reader, pending)?;
+      Ok(pending.take(&request_id))
+    });
+    if result.is_ready() {
+      return result;
+    }
+
+    if Instant::now() >= this.deadline {
+      pending.forget(&request_id);
+      return task::Poll::Ready(Err(Error::Timeout));
+    }
+    let deadline = this.deadline;
+    this
+      .timer
+      .get_or_insert_with(|| {
+        let timer = WakerSlot::default();
+        let wake_at_deadline = timer.clone();
+        thread::spawn(move || {
+          thread::sleep(deadline.saturating_duration_since(Instant::now()));
+          wake_at_deadline.wake();
+        });
+        timer
+      })
+      .register(cx.waker());
+    task::Poll::Pending
+  }
+}
+
+/// Service side of request/reply over DDS. See [`Requester`].
+///
+/// Requests are taken with [`take_request`](Self::take_request) or
+/// [`async_take_request`](Self::async_take_request), and each one is answered
+/// with [`send_reply`](Self::send_reply), using the identity that came with
+/// the request.
+pub struct Replier<Q, P>
+where
+  Q: DeserializeOwned + 'static,
+  P: Serialize,
+{
+  request_reader: DataReader<Q, CDRDeserializerAdapter<Q>>,
+  reply_writer: DataWriter<P, CDRSerializerAdapter<P>>,
+}
+
+impl<Q, P> Replier<Q, P>
+where
+  Q: DeserializeOwned + 'static,
+  P: Serialize,
+{
+  pub fn new(
+    publisher: &Publisher,
+    subscriber: &Subscriber,
+    request_topic: &Topic,
+    reply_topic: &Topic,
+  ) -> Result<Self> {
+    let request_reader =
+      subscriber.create_datareader_no_key_cdr(request_topic, Some(rpc_qos(request_topic)))?;
+    let reply_writer =
+      publisher.create_datawriter_no_key_cdr(reply_topic, Some(rpc_qos(reply_topic)))?;
+    Ok(Self {
+      request_reader,
+      reply_writer,
+    })
+  }
+
+  /// Takes the next received request, and the identity to reply to. This
+  /// does not block.
+  pub fn take_request(&mut self) -> Result<Option<(SampleIdentity, Q)>> {
+    Ok(
+      self
+        .request_reader
+        .take_next_sample()?
+        .map(|sample| (sample.sample_info().sample_identity(), sample.into_value())),
+    )
+  }
+
+  /// Waits for the next request.
+  pub async fn async_take_request(&mut self) -> Result<(SampleIdentity, Q)> {
+    TakeRequest { replier: self }.await
+  }
+
+  /// Sends the reply to the request identified by `request_id`.
+  pub fn send_reply(&self, request_id: SampleIdentity, reply: P) -> Result<()> {
+    self
+      .reply_writer
+      .write_with_options(
+        reply,
+        WriteOptionsBuilder::new()
+          .related_sample_identity(request_id)
+          .build(),
+      )
+      .map(|_| ())
+  }
+}
+
+// Readable, when requests have arrived.
+impl<Q, P> Evented for Replier<Q, P>
+where
+  Q: DeserializeOwned + 'static,
+  P: Serialize,
+{
+  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    self.request_reader.register(poll, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &Poll,
+    token: Token,
+    interest: Ready,
+    opts: PollOpt,
+  ) -> io::Result<()> {
+    self.request_reader.reregister(poll, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &Poll) -> io::Result<()> {
+    self.request_reader.deregister(poll)
+  }
+}
+
+struct TakeRequest<'a, Q, P>
+where
+  Q: DeserializeOwned + 'static,
+  P: Serialize,
+{
+  replier: &'a mut Replier<Q, P>,
+}
+
+impl<Q, P> Future for TakeRequest<'_, Q, P>
+where
+  Q: DeserializeOwned + 'static,
+  P: Serialize,
+{
+  type Output = Result<(SampleIdentity, Q)>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<Self::Output> {
+    self
+      .get_mut()
+      .replier
+      .request_reader
+      .poll_when_available(cx, |reader| {
+        Ok(
+          reader
+            .take_next_sample()?
+            .map(|sample| (sample.sample_info().sample_identity(), sample.into_value())),
+        )
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    sync::Arc,
+    task::{Wake, Waker},
+  };
+
+  use super::*;
+  use crate::{
+    dds::topic::TopicKind,
+    structure::{guid::GUID, sequence_number::SequenceNumber},
+    test::loopback::loopback_participant,
+  };
+
+  fn request_id(sequence_number: i64) -> SampleIdentity {
+    SampleIdentity {
+      writer_guid: GUID::GUID_UNKNOWN,
+      sequence_number: SequenceNumber::from(sequence_number),
+    }
+  }
+
+  #[test]
+  fn pending_replies() {
+    let mut pending = PendingReplies::new();
+    pending.expect(request_id(1));
+    pending.expect(request_id(2));
+
+    assert!(!pending.add(None, "no related sample identity"));
+    assert!(!pending.add(Some(request_id(3)), "not ours"));
+    assert!(pending.add(Some(request_id(2)), "two"));
+    assert!(!pending.add(Some(request_id(2)), "second reply"));
+    assert_eq!(pending.take(&request_id(1)), None);
+    assert!(pending.is_expected(&request_id(1)));
+    assert_eq!(pending.take(&request_id(2)), Some("two"));
+    assert!(!pending.is_expected(&request_id(2)));
+
+    pending.forget(&request_id(1));
+    assert!(!pending.add(Some(request_id(1)), "too late"));
+    assert!(!pending.is_expected(&request_id(1)));
+  }
+
+  struct ThreadWaker(thread::Thread);
+
+  impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+      self.0.unpark();
+    }
+  }
+
+  fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+      match future.as_mut().poll(&mut cx) {
+        task::Poll::Ready(output) => return output,
+        task::Poll::Pending => thread::park(),
+      }
+    }
+  }
+
+  #[test]
+  fn request_reply_call() {
+    let qos = QosPolicyBuilder::new().build();
+    let participant = loopback_participant(219);
+    let topic = |name: &str| {
+      participant
+        .create_topic(name.to_string(), name.to_string(), &qos, TopicKind::NoKey)
+        .unwrap()
+    };
+    let request_topic = topic("rpc_test_request");
+    let reply_topic = topic("rpc_test_reply");
+    let publisher = participant.create_publisher(&qos).unwrap();
+    let subscriber = participant.create_subscriber(&qos).unwrap();
+
+    let mut replier =
+      Replier::<(i32, i32), i32>::new(&publisher, &subscriber, &request_topic, &reply_topic)
+        .unwrap();
+    let service = thread::spawn(move || {
+      // Answer two requests, in reverse order
+      let (id_1, (a, b)) = block_on(replier.async_take_request()).unwrap();
+      let (id_2, (c, d)) = block_on(replier.async_take_request()).unwrap();
+      replier.send_reply(id_2, c + d).unwrap();
+      replier.send_reply(id_1, a + b).unwrap();
+    });
+
+    let mut requester =
+      Requester::<(i32, i32), i32>::new(&publisher, &subscriber, &request_topic, &reply_topic)
+        .unwrap();
+    thread::sleep(StdDuration::from_secs(1)); // for matching
+    let timeout = StdDuration::from_secs(5);
+    let id_1 = requester.send_request((1, 2)).unwrap();
+    let id_2 = requester.send_request((10, 20)).unwrap();
+    assert_eq!(block_on(requester.receive_reply(id_1, timeout)).unwrap(), 3);
+    assert_eq!(requester.take_reply(id_2).unwrap(), Some(30));
+    assert!(requester.take_reply(id_2).is_err());
+    service.join().unwrap();
+
+    // Nobody answers now.
+    let result = block_on(requester.call((1, 1), StdDuration::from_millis(200)));
+    assert!(matches!(result, Err(Error::Timeout)));
+  }
+}
@@ -6,7 +6,9 @@ use log::{debug, error, trace, warn};
 
 use crate::{
   dds::{participant::DomainParticipant, qos::QosPolicies},
-  discovery::data_types::topic_data::DiscoveredReaderData,
+  discovery::{
+    content_filter_property::ContentFilterProperty, data_types::topic_data::DiscoveredReaderData,
+  },
   messages::submessages::submessage::AckSubmessage,
   network::constant::*,
   structure::{
@@ -53,6 +55,8 @@ pub(crate) struct RtpsReaderProxy {
   pub repair_mode: bool,
   pub qos: QosPolicies,
   pub frags_requested: BTreeMap<SequenceNumber, BitVec>,
+  // Filter announced by the reader, if it reads a ContentFilteredTopic.
+  pub content_filter: Option<ContentFilterProperty>,
 }
 
 impl RtpsReaderProxy {
@@ -69,6 +73,7 @@ impl RtpsReaderProxy {
       repair_mode: false,
       qos,
       frags_requested: BTreeMap::new(),
+      content_filter: None,
     }
   }
 
@@ -97,6 +102,7 @@ impl RtpsReaderProxy {
       repair_mode: false,
       qos: reader.qos_policy.clone(),
       frags_requested: BTreeMap::new(),
+      content_filter: None,
     }
   }
 
@@ -134,6 +140,7 @@ impl RtpsReaderProxy {
       repair_mode: false,
       qos: discovered_reader_data.subscription_topic_data.qos(),
       frags_requested: BTreeMap::new(),
+      content_filter: discovered_reader_data.content_filter.clone(),
     }
   }
 
@@ -301,3 +308,70 @@ impl Iterator for FragBitVecIterator {
 //     }
 //   }
 // }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    dds::rtps_writer_proxy::RtpsWriterProxy,
+    messages::submessages::submessages::AckNack,
+    structure::{guid::EntityKind, sequence_number::SequenceNumberSet, time::Timestamp},
+  };
+
+  #[test]
+  fn acknack_base_is_never_past_a_missing_sn() {
+    // The writer side and the reader side of the same match
+    let mut reader_proxy = RtpsReaderProxy::new(
+      GUID::dummy_test_guid(EntityKind::READER_WITH_KEY_USER_DEFINED),
+      QosPolicies::qos_none(),
+    );
+    let mut writer_proxy = RtpsWriterProxy::new(
+      GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED),
+      vec![],
+      vec![],
+      EntityId::UNKNOWN,
+    );
+    let last_sn = SequenceNumber::new(600);
+    // The reader has only every third sample.
+    for sn in (3..=600).step_by(3) {
+      writer_proxy.received_changes_add(
+        SequenceNumber::new(sn),
+        Timestamp::now(),
+        Timestamp::now(),
+      );
+    }
+
+    let mut heartbeats = 0;
+    while writer_proxy.all_ackable_before() <= last_sn {
+      heartbeats += 1;
+      assert!(heartbeats < 10, "Reader does not catch up");
+      let (reader_sn_state, _) = writer_proxy.acknack_request(SequenceNumber::new(1), last_sn);
+      assert!(reader_sn_state.iter().count() <= SequenceNumberSet::MAX_BITS as usize);
+      let acknack = AckNack {
+        reader_id: EntityId::UNKNOWN,
+        writer_id: EntityId::UNKNOWN,
+        reader_sn_state,
+        count: heartbeats,
+      };
+      reader_proxy.handle_ack_nack(&AckSubmessage::AckNack(acknack), last_sn);
+
+      // Everything that the writer takes as acknowledged has been received.
+      let mut sn = SequenceNumber::new(1);
+      while sn < reader_proxy.acked_up_to_before() {
+        assert!(
+          writer_proxy.should_ignore_change(sn),
+          "{:?} is not received",
+          sn
+        );
+        sn += 1;
+      }
+
+      // The writer resends what was requested.
+      for sn in std::mem::take(&mut reader_proxy.unsent_changes) {
+        writer_proxy.received_changes_add(sn, Timestamp::now(), Timestamp::now());
+      }
+    }
+    // 400 missing SNs do not fit in one ACKNACK.
+    assert!(heartbeats > 1);
+  }
+}
@@ -1,18 +1,25 @@
 use core::ops::Bound::{Excluded, Unbounded};
-use std::{cmp::max, collections::BTreeMap, iter};
+use std::{
+  cmp::{max, min},
+  collections::BTreeMap,
+  iter,
+  time::Instant,
+};
 
 use enumflags2::BitFlags;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
+#[cfg(feature = "fragmentation")]
+use crate::dds::fragment_assembler::FragmentAssembler;
 use crate::{
-  dds::{ddsdata::DDSData, fragment_assembler::FragmentAssembler},
+  dds::ddsdata::DDSData,
   discovery::data_types::topic_data::DiscoveredWriterData,
   messages::submessages::submessages::{DATAFRAG_Flags, DataFrag},
   structure::{
     guid::{EntityId, GUID},
     locator::Locator,
-    sequence_number::{FragmentNumber, SequenceNumber},
+    sequence_number::{FragmentNumber, SequenceNumber, SequenceNumberSet},
     time::Timestamp,
   },
 };
@@ -66,6 +73,8 @@ pub(crate) struct RtpsWriterProxy {
 
   // The changes map is cleaned on heartbeat messages. The changes no longer available are dropped.
   pub received_heartbeat_count: i32,
+  // When the last HEARTBEAT was processed, for heartbeat suppression
+  pub heartbeat_accepted_at: Option<Instant>,
 
   pub sent_ack_nack_count: i32,
 
@@ -78,6 +87,7 @@ pub(crate) struct RtpsWriterProxy {
   last_received_sequence_number: SequenceNumber,
   last_received_timestamp: Timestamp,
 
+  #[cfg(feature = "fragmentation")]
   fragment_assembler: Option<FragmentAssembler>,
 }
 
@@ -95,12 +105,14 @@ impl RtpsWriterProxy {
       remote_group_entity_id,
       changes: BTreeMap::new(),
       received_heartbeat_count: 0,
+      heartbeat_accepted_at: None,
       sent_ack_nack_count: 0,
       // Sequence numbering must start at 1.
       // Therefore, we can ACK all sequence numbers below 1 even before receiving anything.
       ack_base: SequenceNumber::new(1),
       last_received_sequence_number: SequenceNumber::new(0),
       last_received_timestamp: Timestamp::INVALID,
+      #[cfg(feature = "fragmentation")]
       fragment_assembler: None,
     }
   }
@@ -203,20 +215,58 @@ impl RtpsWriterProxy {
     missing_seqnums
   }
 
+  // What to request in the ACKNACK that answers a HEARTBEAT: the readerSNState,
+  // and the partially received SNs, which are requested with NACK_FRAG instead.
+  //
+  // The writer takes the base of the readerSNState to acknowledge all SNs
+  // before it, so the base is the first missing SN, and there is only one
+  // readerSNState per HEARTBEAT. The set spans at most
+  // SequenceNumberSet::MAX_BITS SNs. The rest are requested after later
+  // HEARTBEATs.
+  pub fn acknack_request(
+    &self,
+    hb_first_sn: SequenceNumber,
+    hb_last_sn: SequenceNumber,
+  ) -> (SequenceNumberSet, Vec<SequenceNumber>) {
+    let request_last_sn = min(
+      hb_last_sn,
+      max(hb_first_sn, self.ack_base) + (i64::from(SequenceNumberSet::MAX_BITS) - 1),
+    );
+    let missing_seqnums = self.missing_seqnums(hb_first_sn, request_last_sn);
+    let (partially_received, missing_sns): (Vec<SequenceNumber>, Vec<SequenceNumber>) =
+      missing_seqnums
+        .iter()
+        .partition(|sn| self.is_partially_received(**sn));
+    let reader_sn_state = match missing_seqnums.first() {
+      Some(&first_missing) => {
+        SequenceNumberSet::from_base_and_set(first_missing, &missing_sns.into_iter().collect())
+      }
+      // Nothing missing. Report that we have all we have.
+      None => SequenceNumberSet::new_empty(self.all_ackable_before()),
+    };
+    (reader_sn_state, partially_received)
+  }
+
   // Check if we have already received this sequence number
   // or it has been marked as not_available
   pub fn should_ignore_change(&self, seqnum: SequenceNumber) -> bool {
     seqnum < self.ack_base || self.changes.contains_key(&seqnum)
   }
 
-  // This is used to mark DATA as received.
-  pub fn received_changes_add(&mut self, seq_num: SequenceNumber, receive_timestamp: Timestamp) {
+  // This is used to mark DATA as received. receive_timestamp refers to the
+  // change in DDSCache, and now is the time on the participant clock.
+  pub fn received_changes_add(
+    &mut self,
+    seq_num: SequenceNumber,
+    receive_timestamp: Timestamp,
+    now: Timestamp,
+  ) {
     self.changes.insert(seq_num, Some(receive_timestamp));
 
     // Update deadline tracker
     if seq_num > self.last_received_sequence_number {
       self.last_received_sequence_number = seq_num;
-      self.last_received_timestamp = receive_timestamp;
+      self.last_received_timestamp = now;
     }
 
     // We get to advance ack_base if it was equal to seq_num
@@ -249,14 +299,27 @@ impl RtpsWriterProxy {
     if seq_num >= self.ack_base {
       // if this is still in the relevant range
       // insert not_available marker.
-      self.changes.insert(seq_num, None).flatten() // this will return the
-                                                   // Timestamp, if there was a
-                                                   // recived change
+      // This returns the Timestamp, if there was a received change.
+      let removed = self.changes.insert(seq_num, None).flatten();
+      self.advance_ack_base();
+      removed
     } else {
       None
     }
+  }
 
-    // TODO: Update ack_base
+  // Move ack_base past any received/not_available changes that follow it.
+  fn advance_ack_base(&mut self) {
+    let old_ack_base = self.ack_base;
+    while self.changes.contains_key(&self.ack_base) {
+      self.ack_base = self.ack_base + SequenceNumber::new(1);
+    }
+    if self.ack_base > old_ack_base {
+      debug!(
+        "ack_base increased to {:?} writer={:?}",
+        self.ack_base, self.remote_writer_guid
+      );
+    }
   }
 
   // Used to add range of irrelevant changes from GAP message
@@ -289,6 +352,7 @@ impl RtpsWriterProxy {
       self.changes.append(&mut after);
 
       self.ack_base = max(remove_until_before, self.ack_base);
+      self.advance_ack_base();
       debug!(
         "ack_base increased to {:?} by irrelevant_changes_range {:?} to {:?}. writer={:?}",
         self.ack_base, remove_from, remove_until_before, self.remote_writer_guid
@@ -355,14 +419,17 @@ impl RtpsWriterProxy {
       multicast_locator_list,
       changes: BTreeMap::new(),
       received_heartbeat_count: 0,
+      heartbeat_accepted_at: None,
       sent_ack_nack_count: 0,
       ack_base: SequenceNumber::default(),
       last_received_sequence_number: SequenceNumber::new(0),
       last_received_timestamp: Timestamp::INVALID,
+      #[cfg(feature = "fragmentation")]
       fragment_assembler: None,
     }
   } // fn
 
+  #[cfg(feature = "fragmentation")]
   pub fn handle_datafrag(
     &mut self,
     datafrag: &DataFrag,
@@ -379,6 +446,7 @@ impl RtpsWriterProxy {
     }
   } // fn
 
+  #[cfg(feature = "fragmentation")]
   pub fn missing_frags_for<'a>(
     &'a self,
     seq: SequenceNumber,
@@ -389,6 +457,7 @@ impl RtpsWriterProxy {
       Box::new(iter::empty())
     }
   }
+  #[cfg(feature = "fragmentation")]
   pub fn is_partially_received(&self, seq: SequenceNumber) -> bool {
     if let Some(ref fa) = self.fragment_assembler {
       fa.is_partially_received(seq)
@@ -396,4 +465,59 @@ impl RtpsWriterProxy {
       false
     }
   }
+
+  #[cfg(not(feature = "fragmentation"))]
+  pub fn handle_datafrag(
+    &mut self,
+    datafrag: &DataFrag,
+    _flags: BitFlags<DATAFRAG_Flags>,
+  ) -> Option<DDSData> {
+    debug!(
+      "Fragmentation support is not enabled. Discarding DATA_FRAG {:?} from {:?}",
+      datafrag.writer_sn, self.remote_writer_guid
+    );
+    None
+  }
+
+  #[cfg(not(feature = "fragmentation"))]
+  pub fn missing_frags_for<'a>(
+    &'a self,
+    _seq: SequenceNumber,
+  ) -> Box<dyn 'a + Iterator<Item = FragmentNumber>> {
+    Box::new(iter::empty())
+  }
+
+  #[cfg(not(feature = "fragmentation"))]
+  pub fn is_partially_received(&self, _seq: SequenceNumber) -> bool {
+    false
+  }
 } // impl
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::structure::guid::EntityKind;
+
+  #[test]
+  fn writer_proxy_gap_advances_ack_base() {
+    let mut wp = RtpsWriterProxy::new(
+      GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED),
+      vec![],
+      vec![],
+      EntityId::UNKNOWN,
+    );
+    let sn = SequenceNumber::new;
+
+    wp.received_changes_add(sn(1), Timestamp::now(), Timestamp::now());
+    wp.received_changes_add(sn(4), Timestamp::now(), Timestamp::now());
+    assert_eq!(wp.all_ackable_before(), sn(2));
+
+    // GAP for a single SN
+    wp.set_irrelevant_change(sn(2));
+    assert_eq!(wp.all_ackable_before(), sn(3));
+
+    // GAP for a range, followed by an already received change
+    wp.irrelevant_changes_range(sn(3), sn(4));
+    assert_eq!(wp.all_ackable_before(), sn(5));
+  }
+}
@@ -0,0 +1,123 @@
+// Runtime metrics, reported to the `metrics` facade if the "metrics" feature
+// is enabled. The application installs a recorder, e.g. a Prometheus
+// exporter, to collect them. Without the feature these functions do nothing.
+//
+// Labels are topic names, so the number of series grows with the number of
+// topics, not with the number of samples or endpoints.
+
+#![cfg_attr(not(feature = "metrics"), allow(unused_variables, dead_code))]
+
+use std::time::Duration;
+
+use crate::dds::statusevents::DiscoveryEvent;
+
+pub(crate) const WRITE_DURATION: &str = "rustdds_write_duration_seconds";
+pub(crate) const SAMPLE_SIZE: &str = "rustdds_sample_size_bytes";
+pub(crate) const MATCHED_ENDPOINTS: &str = "rustdds_matched_endpoints";
+pub(crate) const REASSEMBLY_BUFFER_BYTES: &str = "rustdds_reassembly_buffer_bytes";
+pub(crate) const DISCOVERY_EVENTS: &str = "rustdds_discovery_events_total";
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum SampleDirection {
+  Written,
+  Received,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum LocalEndpoint {
+  Writer,
+  Reader,
+}
+
+// Descriptions are shown by exporters that support them. Describing again is
+// harmless, so this is done for each participant.
+pub(crate) fn describe() {
+  #[cfg(feature = "metrics")]
+  {
+    use metrics::{describe_counter, describe_gauge, describe_histogram, Unit};
+
+    describe_histogram!(
+      WRITE_DURATION,
+      Unit::Seconds,
+      "Time spent in DataWriter write calls"
+    );
+    describe_histogram!(
+      SAMPLE_SIZE,
+      Unit::Bytes,
+      "Serialized size of samples written and received"
+    );
+    describe_gauge!(
+      MATCHED_ENDPOINTS,
+      "Remote endpoints matched with local writers and readers"
+    );
+    describe_gauge!(
+      REASSEMBLY_BUFFER_BYTES,
+      Unit::Bytes,
+      "Memory held by partially received fragmented samples"
+    );
+    describe_counter!(DISCOVERY_EVENTS, "Discovery events by kind");
+  }
+}
+
+pub(crate) fn write_duration(topic_name: &str, duration: Duration) {
+  #[cfg(feature = "metrics")]
+  metrics::histogram!(WRITE_DURATION, "topic" => topic_name.to_string()).record(duration);
+}
+
+pub(crate) fn sample_size(topic_name: &str, direction: SampleDirection, size: usize) {
+  #[cfg(feature = "metrics")]
+  {
+    let direction = match direction {
+      SampleDirection::Written => "written",
+      SampleDirection::Received => "received",
+    };
+    metrics::histogram!(
+      SAMPLE_SIZE,
+      "topic" => topic_name.to_string(),
+      "direction" => direction
+    )
+    .record(size as f64);
+  }
+}
+
+// Count of matched remote endpoints changes by `change`.
+pub(crate) fn matched_endpoints(topic_name: &str, local: LocalEndpoint, change: i32) {
+  #[cfg(feature = "metrics")]
+  {
+    let local = match local {
+      LocalEndpoint::Writer => "writer",
+      LocalEndpoint::Reader => "reader",
+    };
+    metrics::gauge!(
+      MATCHED_ENDPOINTS,
+      "topic" => topic_name.to_string(),
+      "local" => local
+    )
+    .increment(f64::from(change));
+  }
+}
+
+// Reassembly buffers of `size` bytes were allocated (positive) or released
+// (negative).
+pub(crate) fn reassembly_buffer_bytes(size: i64) {
+  #[cfg(feature = "metrics")]
+  metrics::gauge!(REASSEMBLY_BUFFER_BYTES).increment(size as f64);
+}
+
+pub(crate) fn discovery_event(event: &DiscoveryEvent) {
+  #[cfg(feature = "metrics")]
+  {
+    let kind = match event {
+      DiscoveryEvent::ParticipantDiscovered { .. } => "participant_discovered",
+      DiscoveryEvent::ParticipantUpdated { .. } => "participant_updated",
+      DiscoveryEvent::ParticipantLost { .. } => "participant_lost",
+      DiscoveryEvent::WriterDiscovered { .. } => "writer_discovered",
+      DiscoveryEvent::WriterUpdated { .. } => "writer_updated",
+      DiscoveryEvent::WriterLost { .. } => "writer_lost",
+      DiscoveryEvent::ReaderDiscovered { .. } => "reader_discovered",
+      DiscoveryEvent::ReaderUpdated { .. } => "reader_updated",
+      DiscoveryEvent::ReaderLost { .. } => "reader_lost",
+    };
+    metrics::counter!(DISCOVERY_EVENTS, "event" => kind).increment(1);
+  }
+}
@@ -196,6 +196,10 @@ pub struct SampleInfo {
   /// corresponding counters at the time the sample was received.
   pub(crate) generation_counts: NotAliveGenerationCounts,
 
+  /// Indicates whether the DataSample contains data, or only the key of an
+  /// instance whose instance_state changed, e.g. by dispose.
+  pub(crate) valid_data: bool,
+
   /// The ranks are are computed based solely on the actual samples in the
   /// ordered collection returned by the read or take.
   /// The sample_rank indicates the number of samples of the same instance that
@@ -261,6 +265,13 @@ impl SampleInfo {
   //   self.instance_state = instance_state;
   // }
 
+  /// `false` if the sample has no data, but only reports a change in the
+  /// instance_state. The key of the instance is still available from the
+  /// [`DataSample`](crate::with_key::DataSample).
+  pub fn valid_data(&self) -> bool {
+    self.valid_data
+  }
+
   pub fn disposed_generation_count(&self) -> i32 {
     self.generation_counts.disposed_generation_count
   }
@@ -295,6 +306,18 @@ impl SampleInfo {
     self.write_options.related_sample_identity
   }
 
+  /// Ownership strength of the DataWriter, if it was sent with the sample.
+  /// Writers with exclusive ownership send it.
+  pub fn ownership_strength(&self) -> Option<i32> {
+    self.write_options.ownership_strength
+  }
+
+  /// Sequence number of the first sample in the coherent set that this
+  /// sample belongs to, if the DataWriter sent one.
+  pub fn coherent_set(&self) -> Option<SequenceNumber> {
+    self.write_options.coherent_set
+  }
+
   pub fn sample_identity(&self) -> SampleIdentity {
     SampleIdentity {
       writer_guid: self.publication_handle,
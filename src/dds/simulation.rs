@@ -0,0 +1,254 @@
+// Simulated time, for running participants deterministically and faster
+// than real time.
+
+use std::{
+  fmt,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+use mio::{Ready, Registration, SetReadiness};
+
+use crate::structure::{duration::Duration as DdsDuration, time::Timestamp};
+
+/// A clock that advances only when told to, for simulations and tests.
+///
+/// Participants built with
+/// [`DomainParticipantBuilder::build_simulated`](crate::DomainParticipantBuilder::build_simulated)
+/// run their protocol timers, such as heartbeats, announcements and
+/// deadline checks, on this clock. Together with
+/// [`ParticipantDriver::step`](crate::ParticipantDriver::step), this lets a
+/// test or a discrete-event simulation decide exactly when each timer
+/// expires, and skip over idle time without sleeping:
+///
+/// ```
+/// # use std::time::Duration;
+/// # use rustdds::{DomainParticipantBuilder, SimulatedClock};
+/// let clock = SimulatedClock::default();
+/// let (participant, mut driver) = DomainParticipantBuilder::new(0)
+///   .build_simulated(&clock)
+///   .unwrap();
+/// for _ in 0..10 {
+///   let idle = clock.time_to_next_timer().unwrap_or(Duration::from_millis(100));
+///   clock.advance(idle);
+///   driver.step();
+/// }
+/// ```
+///
+/// Clones refer to the same clock. Any number of participants can share it.
+#[derive(Clone)]
+pub struct SimulatedClock {
+  inner: Arc<Mutex<ClockInner>>,
+}
+
+struct ClockInner {
+  start: Timestamp,
+  start_instant: Instant,
+  elapsed: Duration,
+  // Timers waiting for the clock, by the elapsed time when they expire
+  scheduled: Vec<(Duration, SetReadiness)>,
+}
+
+impl SimulatedClock {
+  /// A clock that starts at `start`.
+  pub fn new(start: Timestamp) -> Self {
+    Self {
+      inner: Arc::new(Mutex::new(ClockInner {
+        start,
+        start_instant: Instant::now(),
+        elapsed: Duration::ZERO,
+        scheduled: Vec::new(),
+      })),
+    }
+  }
+
+  /// The current time of the clock.
+  pub fn now(&self) -> Timestamp {
+    let inner = self.inner.lock().unwrap();
+    inner.start + DdsDuration::from_std(inner.elapsed)
+  }
+
+  /// The current time of the clock as an `Instant`, which advances with the
+  /// clock from the time the clock was created.
+  pub fn instant(&self) -> Instant {
+    let inner = self.inner.lock().unwrap();
+    inner.start_instant + inner.elapsed
+  }
+
+  /// How much the clock has been advanced since it was created.
+  pub fn elapsed(&self) -> Duration {
+    self.inner.lock().unwrap().elapsed
+  }
+
+  /// Advances the clock. Timers that expire by the new time become ready,
+  /// and are handled on the next
+  /// [`ParticipantDriver::step`](crate::ParticipantDriver::step).
+  pub fn advance(&self, duration: Duration) {
+    let mut inner = self.inner.lock().unwrap();
+    inner.elapsed += duration;
+    let elapsed = inner.elapsed;
+    inner.scheduled.retain(|(expires, set_readiness)| {
+      if *expires <= elapsed {
+        set_readiness.set_readiness(Ready::readable()).unwrap_or(());
+        false
+      } else {
+        true
+      }
+    });
+  }
+
+  /// How far the clock must be advanced for the next timer to expire, or
+  /// None if no timers are pending. A discrete-event simulation can advance
+  /// the clock by this much, as nothing happens in between. Timers that have
+  /// been cancelled may still be counted.
+  pub fn time_to_next_timer(&self) -> Option<Duration> {
+    let inner = self.inner.lock().unwrap();
+    inner
+      .scheduled
+      .iter()
+      .map(|(expires, _)| *expires - inner.elapsed)
+      .min()
+  }
+
+  fn schedule(&self, expires: Duration, set_readiness: &SetReadiness) {
+    let mut inner = self.inner.lock().unwrap();
+    if expires <= inner.elapsed {
+      set_readiness.set_readiness(Ready::readable()).unwrap_or(());
+    } else {
+      inner.scheduled.push((expires, set_readiness.clone()));
+    }
+  }
+}
+
+impl Default for SimulatedClock {
+  /// A clock that starts at the current system time.
+  fn default() -> Self {
+    Self::new(Timestamp::now())
+  }
+}
+
+impl fmt::Debug for SimulatedClock {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("SimulatedClock")
+      .field("now", &self.now())
+      .finish()
+  }
+}
+
+// A timer that expires by a SimulatedClock. It is readable when it has
+// expired timeouts.
+pub(crate) struct SimulatedTimer<T> {
+  clock: SimulatedClock,
+  // Pending timeouts: expiry time on the clock, id and state
+  timeouts: Vec<(Duration, u64, T)>,
+  next_id: u64,
+  registration: Registration,
+  set_readiness: SetReadiness,
+}
+
+impl<T> SimulatedTimer<T> {
+  pub fn new(clock: SimulatedClock) -> Self {
+    let (registration, set_readiness) = Registration::new2();
+    Self {
+      clock,
+      timeouts: Vec::new(),
+      next_id: 0,
+      registration,
+      set_readiness,
+    }
+  }
+
+  pub fn set_timeout(&mut self, delay_from_now: Duration, state: T) -> u64 {
+    let id = self.next_id;
+    self.next_id += 1;
+    let expires = self.clock.elapsed() + delay_from_now;
+    self.timeouts.push((expires, id, state));
+    self.clock.schedule(expires, &self.set_readiness);
+    id
+  }
+
+  pub fn cancel_timeout(&mut self, id: u64) -> Option<T> {
+    let index = self.timeouts.iter().position(|(_, i, _)| *i == id)?;
+    Some(self.timeouts.remove(index).2)
+  }
+
+  // The earliest expired timeout, in the order they were set if they expire
+  // at the same time
+  pub fn poll(&mut self) -> Option<T> {
+    let now = self.clock.elapsed();
+    let expired = self
+      .timeouts
+      .iter()
+      .enumerate()
+      .filter(|(_, (expires, _, _))| *expires <= now)
+      .min_by_key(|(_, (expires, id, _))| (*expires, *id))
+      .map(|(index, _)| index);
+    match expired {
+      Some(index) => Some(self.timeouts.remove(index).2),
+      None => {
+        self
+          .set_readiness
+          .set_readiness(Ready::empty())
+          .unwrap_or(());
+        None
+      }
+    }
+  }
+
+  pub fn registration(&self) -> &Registration {
+    &self.registration
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use mio::{Events, Poll, PollOpt, Ready, Token};
+
+  use super::*;
+  use crate::dds::{clock::TimeSource, timer::Timer};
+
+  fn ready_tokens(poll: &Poll) -> Vec<Token> {
+    let mut events = Events::with_capacity(8);
+    poll.poll(&mut events, Some(Duration::ZERO)).unwrap();
+    events.iter().map(|e| e.token()).collect()
+  }
+
+  #[test]
+  fn simulated_timers_expire_by_the_clock() {
+    let clock = SimulatedClock::new(Timestamp::ZERO);
+    let mut timer: Timer<&str> = TimeSource::Simulated(clock.clone()).timer();
+    let poll = Poll::new().unwrap();
+    poll
+      .register(&timer, Token(1), Ready::readable(), PollOpt::edge())
+      .unwrap();
+
+    timer.set_timeout(Duration::from_secs(2), "second");
+    timer.set_timeout(Duration::from_secs(1), "first");
+    let cancelled = timer.set_timeout(Duration::from_secs(1), "cancelled");
+    assert_eq!(timer.cancel_timeout(&cancelled), Some("cancelled"));
+    assert_eq!(clock.time_to_next_timer(), Some(Duration::from_secs(1)));
+
+    clock.advance(Duration::from_millis(999));
+    assert!(ready_tokens(&poll).is_empty());
+    assert_eq!(timer.poll(), None);
+
+    clock.advance(Duration::from_millis(1));
+    assert_eq!(ready_tokens(&poll), vec![Token(1)]);
+    assert_eq!(timer.poll(), Some("first"));
+    assert_eq!(timer.poll(), None);
+
+    // Advancing past several timeouts at once delivers them in order.
+    timer.set_timeout(Duration::from_millis(500), "third");
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(ready_tokens(&poll), vec![Token(1)]);
+    assert_eq!(timer.poll(), Some("third"));
+    assert_eq!(timer.poll(), Some("second"));
+    assert_eq!(timer.poll(), None);
+    assert_eq!(clock.time_to_next_timer(), None);
+
+    assert_eq!(
+      clock.now(),
+      Timestamp::ZERO + DdsDuration::from_std(Duration::from_secs(6))
+    );
+  }
+}
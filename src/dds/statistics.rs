@@ -0,0 +1,329 @@
+// Participant and endpoint statistics.
+//
+// The protocol threads update atomic counters as they go. Each Writer and
+// Reader owns the counters of its own, and they are registered in the shared
+// StatisticsMonitor for as long as the endpoint exists. Statistics are
+// snapshots of the counters, taken on demand.
+//
+// Without the "statistics" feature the counters are empty and read as zero,
+// so the protocol threads can update them unconditionally.
+
+use std::{
+  collections::BTreeMap,
+  sync::{Arc, Mutex, RwLock},
+};
+#[cfg(feature = "statistics")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "statistics")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "statistics")]
+use crate::dds::traits::key::Keyed;
+use crate::structure::{dds_cache::DDSCache, guid::GUID};
+
+#[cfg(feature = "statistics")]
+/// Statistics of a local DataWriter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriterStatistics {
+  pub guid: GUID,
+  pub topic_name: String,
+  /// Samples written by the application, including disposes and unregisters.
+  pub samples_written: u64,
+  /// Rounds of HEARTBEATs sent to the matched readers.
+  pub heartbeats_sent: u64,
+  /// ACKNACKs and NACK_FRAGs received from reliable readers.
+  pub acknacks_received: u64,
+  /// DATA and DATA_FRAG submessages resent, because a reader asked for them.
+  pub retransmissions: u64,
+}
+
+#[cfg(feature = "statistics")]
+/// Statistics of a local DataReader.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReaderStatistics {
+  pub guid: GUID,
+  pub topic_name: String,
+  /// Samples accepted into the cache.
+  pub samples_received: u64,
+  /// Samples discarded, because they were duplicates, had expired, or could
+  /// not be decoded.
+  pub samples_dropped: u64,
+  pub heartbeats_received: u64,
+  pub acknacks_sent: u64,
+}
+
+#[cfg(feature = "statistics")]
+/// Internal statistics of a [`DomainParticipant`](crate::DomainParticipant)
+/// and its endpoints.
+///
+/// Available locally from
+/// [`DomainParticipant::statistics`](crate::DomainParticipant::statistics),
+/// and optionally published on the vendor topic
+/// [`STATISTICS_TOPIC_NAME`](Self::STATISTICS_TOPIC_NAME), keyed by
+/// participant GUID. All counters start from zero when the participant is
+/// created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParticipantStatistics {
+  pub participant_guid: GUID,
+  /// RTPS messages sent, counting each destination locator separately.
+  pub messages_sent: u64,
+  pub bytes_sent: u64,
+  pub messages_received: u64,
+  pub bytes_received: u64,
+  /// Received messages or submessages that were discarded, because they were
+  /// malformed, could not be decoded, or came from an unexpected address.
+  pub messages_dropped: u64,
+  /// Samples currently held in the cache of the participant, for all topics.
+  pub cache_samples: u64,
+  pub writers: Vec<WriterStatistics>,
+  pub readers: Vec<ReaderStatistics>,
+}
+
+#[cfg(feature = "statistics")]
+impl ParticipantStatistics {
+  /// Topic where statistics samples are published, if enabled by
+  /// [`DomainParticipantBuilder::publish_statistics`](crate::DomainParticipantBuilder::publish_statistics).
+  pub const STATISTICS_TOPIC_NAME: &'static str = "RustDDSParticipantStatistics";
+  pub const STATISTICS_TYPE_NAME: &'static str = "ParticipantStatistics";
+}
+
+#[cfg(feature = "statistics")]
+impl Keyed for ParticipantStatistics {
+  type K = GUID;
+  fn key(&self) -> GUID {
+    self.participant_guid
+  }
+}
+
+#[cfg(feature = "statistics")]
+#[derive(Debug, Default)]
+pub(crate) struct Counter(AtomicU64);
+
+#[cfg(feature = "statistics")]
+impl Counter {
+  pub fn increment(&self) {
+    self.add(1);
+  }
+
+  pub fn add(&self, amount: u64) {
+    self.0.fetch_add(amount, Ordering::Relaxed);
+  }
+
+  // For counts that are kept elsewhere, and only mirrored here.
+  pub fn set(&self, value: u64) {
+    self.0.store(value, Ordering::Relaxed);
+  }
+
+  pub fn get(&self) -> u64 {
+    self.0.load(Ordering::Relaxed)
+  }
+}
+
+#[cfg(not(feature = "statistics"))]
+#[derive(Debug, Default)]
+pub(crate) struct Counter;
+
+#[cfg(not(feature = "statistics"))]
+impl Counter {
+  pub fn increment(&self) {}
+
+  pub fn add(&self, _amount: u64) {}
+
+  pub fn set(&self, _value: u64) {}
+
+  #[allow(dead_code)]
+  pub fn get(&self) -> u64 {
+    0
+  }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct TrafficCounters {
+  pub messages_sent: Counter,
+  pub bytes_sent: Counter,
+  pub messages_received: Counter,
+  pub bytes_received: Counter,
+  pub messages_dropped: Counter,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct WriterCounters {
+  pub samples_written: Counter,
+  pub heartbeats_sent: Counter,
+  pub acknacks_received: Counter,
+  pub retransmissions: Counter,
+}
+
+#[cfg(feature = "statistics")]
+impl WriterCounters {
+  fn statistics(&self, guid: GUID, topic_name: &str) -> WriterStatistics {
+    WriterStatistics {
+      guid,
+      topic_name: topic_name.to_string(),
+      samples_written: self.samples_written.get(),
+      heartbeats_sent: self.heartbeats_sent.get(),
+      acknacks_received: self.acknacks_received.get(),
+      retransmissions: self.retransmissions.get(),
+    }
+  }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ReaderCounters {
+  pub samples_received: Counter,
+  pub samples_dropped: Counter,
+  pub heartbeats_received: Counter,
+  pub acknacks_sent: Counter,
+}
+
+#[cfg(feature = "statistics")]
+impl ReaderCounters {
+  fn statistics(&self, guid: GUID, topic_name: &str) -> ReaderStatistics {
+    ReaderStatistics {
+      guid,
+      topic_name: topic_name.to_string(),
+      samples_received: self.samples_received.get(),
+      samples_dropped: self.samples_dropped.get(),
+      heartbeats_received: self.heartbeats_received.get(),
+      acknacks_sent: self.acknacks_sent.get(),
+    }
+  }
+}
+
+#[derive(Debug, Default)]
+#[cfg_attr(not(feature = "statistics"), allow(dead_code))]
+struct Endpoints {
+  writers: BTreeMap<GUID, (String, Arc<WriterCounters>)>,
+  readers: BTreeMap<GUID, (String, Arc<ReaderCounters>)>,
+}
+
+// Cloning gives another handle to the same counters.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "statistics"), allow(dead_code))]
+pub(crate) struct StatisticsMonitor {
+  traffic: Arc<TrafficCounters>,
+  endpoints: Arc<Mutex<Endpoints>>,
+  dds_cache: Arc<RwLock<DDSCache>>,
+}
+
+impl StatisticsMonitor {
+  pub fn new(dds_cache: Arc<RwLock<DDSCache>>) -> Self {
+    Self {
+      traffic: Arc::new(TrafficCounters::default()),
+      endpoints: Arc::new(Mutex::new(Endpoints::default())),
+      dds_cache,
+    }
+  }
+
+  pub fn traffic(&self) -> Arc<TrafficCounters> {
+    self.traffic.clone()
+  }
+
+  pub fn add_writer(&self, guid: GUID, topic_name: &str, counters: Arc<WriterCounters>) {
+    let mut endpoints = self.endpoints.lock().unwrap();
+    endpoints
+      .writers
+      .insert(guid, (topic_name.to_string(), counters));
+  }
+
+  pub fn add_reader(&self, guid: GUID, topic_name: &str, counters: Arc<ReaderCounters>) {
+    let mut endpoints = self.endpoints.lock().unwrap();
+    endpoints
+      .readers
+      .insert(guid, (topic_name.to_string(), counters));
+  }
+
+  pub fn remove_endpoint(&self, guid: GUID) {
+    let mut endpoints = self.endpoints.lock().unwrap();
+    endpoints.writers.remove(&guid);
+    endpoints.readers.remove(&guid);
+  }
+
+  #[cfg(feature = "statistics")]
+  pub fn statistics(&self, participant_guid: GUID) -> ParticipantStatistics {
+    let cache_samples = self.dds_cache.read().unwrap().change_count() as u64;
+    let endpoints = self.endpoints.lock().unwrap();
+    ParticipantStatistics {
+      participant_guid,
+      messages_sent: self.traffic.messages_sent.get(),
+      bytes_sent: self.traffic.bytes_sent.get(),
+      messages_received: self.traffic.messages_received.get(),
+      bytes_received: self.traffic.bytes_received.get(),
+      messages_dropped: self.traffic.messages_dropped.get(),
+      cache_samples,
+      writers: endpoints
+        .writers
+        .iter()
+        .map(|(guid, (topic_name, counters))| counters.statistics(*guid, topic_name))
+        .collect(),
+      readers: endpoints
+        .readers
+        .iter()
+        .map(|(guid, (topic_name, counters))| counters.statistics(*guid, topic_name))
+        .collect(),
+    }
+  }
+}
+
+#[cfg(all(test, feature = "statistics"))]
+mod tests {
+  use byteorder::LittleEndian;
+
+  use super::*;
+  use crate::{
+    serialization::{cdr_deserializer::deserialize_from_little_endian, cdr_serializer::to_bytes},
+    structure::guid::EntityKind,
+  };
+
+  #[test]
+  fn statistics_monitor_endpoints() {
+    let monitor = StatisticsMonitor::new(Arc::new(RwLock::new(DDSCache::new())));
+    let participant_guid = GUID::dummy_test_guid(EntityKind::PARTICIPANT_BUILT_IN);
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED);
+    let reader_guid = GUID::dummy_test_guid(EntityKind::READER_WITH_KEY_USER_DEFINED);
+
+    let writer_counters = Arc::new(WriterCounters::default());
+    let reader_counters = Arc::new(ReaderCounters::default());
+    monitor.add_writer(writer_guid, "topic", writer_counters.clone());
+    monitor.add_reader(reader_guid, "topic", reader_counters.clone());
+
+    writer_counters.samples_written.add(3);
+    reader_counters.heartbeats_received.increment();
+    monitor.traffic().bytes_sent.add(100);
+
+    let statistics = monitor.statistics(participant_guid);
+    assert_eq!(statistics.participant_guid, participant_guid);
+    assert_eq!(statistics.bytes_sent, 100);
+    assert_eq!(statistics.cache_samples, 0);
+    assert_eq!(statistics.writers.len(), 1);
+    assert_eq!(statistics.writers[0].guid, writer_guid);
+    assert_eq!(statistics.writers[0].topic_name, "topic");
+    assert_eq!(statistics.writers[0].samples_written, 3);
+    assert_eq!(statistics.readers[0].heartbeats_received, 1);
+
+    // Removed endpoints are no longer reported.
+    monitor.remove_endpoint(writer_guid);
+    let statistics = monitor.statistics(participant_guid);
+    assert!(statistics.writers.is_empty());
+    assert_eq!(statistics.readers.len(), 1);
+  }
+
+  #[test]
+  fn statistics_cdr_roundtrip() {
+    let monitor = StatisticsMonitor::new(Arc::new(RwLock::new(DDSCache::new())));
+    let reader_counters = Arc::new(ReaderCounters::default());
+    reader_counters.samples_received.add(7);
+    monitor.add_reader(
+      GUID::dummy_test_guid(EntityKind::READER_NO_KEY_USER_DEFINED),
+      "some topic",
+      reader_counters,
+    );
+    monitor.traffic().messages_received.add(12);
+    let statistics = monitor.statistics(GUID::dummy_test_guid(EntityKind::PARTICIPANT_BUILT_IN));
+
+    let bytes = to_bytes::<ParticipantStatistics, LittleEndian>(&statistics).unwrap();
+    let decoded: ParticipantStatistics = deserialize_from_little_endian(&bytes).unwrap();
+    assert_eq!(decoded, statistics);
+  }
+}
@@ -7,10 +7,26 @@
 // Communcation statues are detailed in Figure 2.13 and tables in Section
 // 2.2.4.1 in DDS Specification v1.4
 
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  sync::{Arc, Mutex},
+};
+
 use mio::Evented;
 use mio_extras::channel as mio_channel;
+use log::warn;
 
-use crate::dds::qos::QosPolicyId;
+use crate::{
+  dds::qos::QosPolicyId,
+  discovery::data_types::{
+    spdp_participant_data::SpdpDiscoveredParticipantData,
+    topic_data::{DiscoveredReaderData, DiscoveredWriterData},
+  },
+  structure::{
+    duration::Duration,
+    guid::{GuidPrefix, GUID},
+  },
+};
 
 /// This trait corresponds to set_listener() of the Entity class in DDS spec.
 /// Types implementing this trait can be registered to a poll and
@@ -33,11 +49,15 @@ impl<E> StatusReceiver<E> {
       enabled: false,
     }
   }
+
+  pub fn enable(&mut self) {
+    self.enabled = true;
+  }
 }
 
 impl<E> StatusEvented<E> for StatusReceiver<E> {
   fn as_status_evented(&mut self) -> &dyn Evented {
-    self.enabled = true;
+    self.enable();
     &self.channel_receiver
   }
 
@@ -50,11 +70,145 @@ impl<E> StatusEvented<E> for StatusReceiver<E> {
   }
 }
 
+// Helper for Entities that can have several status listeners, e.g.
+// DomainParticipant. Each listener gets a copy of each status event.
+#[derive(Clone)]
+pub(crate) struct StatusBroadcaster<E> {
+  senders: Arc<Mutex<Vec<mio_channel::SyncSender<E>>>>,
+  listener_capacity: usize,
+}
+
+impl<E: Clone> StatusBroadcaster<E> {
+  // How many unread events a listener may have before new ones are dropped.
+  const LISTENER_CAPACITY: usize = 16;
+
+  pub fn new() -> Self {
+    Self::with_listener_capacity(Self::LISTENER_CAPACITY)
+  }
+
+  pub fn with_listener_capacity(listener_capacity: usize) -> Self {
+    Self {
+      senders: Arc::new(Mutex::new(Vec::new())),
+      listener_capacity,
+    }
+  }
+
+  pub fn new_listener(&self) -> StatusReceiver<E> {
+    let (sender, receiver) = mio_channel::sync_channel(self.listener_capacity);
+    self.senders.lock().unwrap().push(sender);
+    StatusReceiver::new(receiver)
+  }
+
+  pub fn send(&self, event: &E) {
+    // Listeners that have been dropped are removed here.
+    self
+      .senders
+      .lock()
+      .unwrap()
+      .retain(|sender| match sender.try_send(event.clone()) {
+        Ok(()) => true,
+        Err(mio_channel::TrySendError::Full(_)) => {
+          warn!("Status listener is full. Dropping status event.");
+          true
+        }
+        Err(mio_channel::TrySendError::Disconnected(_)) => false,
+        Err(mio_channel::TrySendError::Io(e)) => {
+          warn!("Cannot send status event: {:?}", e);
+          true
+        }
+      });
+  }
+}
+
 #[derive(Debug, Clone)]
 pub enum DomainParticipantStatus {
   PublisherStatus(PublisherStatus),
   SubscriberStatus(SubscriberStatus),
   TopicStatus(TopicStatus),
+  /// A clock anomaly was detected, and timers (e.g. remote participant
+  /// leases and reader deadlines) were restarted instead of letting them all
+  /// expire at once.
+  ClockJumpHandled {
+    kind: ClockJumpKind,
+    /// Size of the jump. Negative means that the wall clock jumped backwards.
+    amount: Duration,
+  },
+  /// A remote DomainParticipant is no longer considered present. Its readers
+  /// and writers have been removed from discovery, and are no longer matched
+  /// with local ones.
+  ParticipantLost {
+    guid_prefix: GuidPrefix,
+    reason: ParticipantLostReason,
+  },
+}
+
+/// Changes in the set of remote DomainParticipants, DataWriters and
+/// DataReaders known to Discovery, see
+/// [`DomainParticipant::discovery_listener`](crate::DomainParticipant::discovery_listener).
+///
+/// Whether remote endpoints match local ones is reported separately, by the
+/// `PublicationMatched` and `SubscriptionMatched` statuses of the local
+/// DataWriters and DataReaders.
+#[derive(Debug, Clone)]
+pub enum DiscoveryEvent {
+  ParticipantDiscovered {
+    participant: SpdpDiscoveredParticipantData,
+  },
+  /// The participant announced different data than before, e.g. new
+  /// locators.
+  ParticipantUpdated {
+    participant: SpdpDiscoveredParticipantData,
+  },
+  /// The participant is gone, and so are all of its writers and readers.
+  /// No separate `WriterLost` or `ReaderLost` events are sent for them.
+  ParticipantLost {
+    guid_prefix: GuidPrefix,
+    reason: ParticipantLostReason,
+  },
+  WriterDiscovered {
+    writer: DiscoveredWriterData,
+  },
+  /// The writer announced different data than before, e.g. changed QoS.
+  WriterUpdated {
+    writer: DiscoveredWriterData,
+  },
+  WriterLost {
+    writer_guid: GUID,
+  },
+  ReaderDiscovered {
+    reader: DiscoveredReaderData,
+  },
+  /// The reader announced different data than before, e.g. changed QoS.
+  ReaderUpdated {
+    reader: DiscoveredReaderData,
+  },
+  ReaderLost {
+    reader_guid: GUID,
+  },
+}
+
+/// Why a remote participant was lost, see
+/// [`DomainParticipantStatus::ParticipantLost`] and
+/// [`DiscoveryEvent::ParticipantLost`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParticipantLostReason {
+  /// Nothing was heard from the participant within its lease duration.
+  LeaseExpired,
+  /// The participant announced that it is going away.
+  Disposed,
+}
+
+/// Kind of clock anomaly reported in
+/// [`DomainParticipantStatus::ClockJumpHandled`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockJumpKind {
+  /// Wall clock (RTPS Timestamp) and monotonic clock disagree, e.g. due to a
+  /// time step by NTP or manual setting. On most platforms system suspend
+  /// also appears like this.
+  WallClockStep,
+  /// Monotonic clock advanced much more than expected, i.e. the process was
+  /// not running for a while, e.g. due to suspend or being stopped.
+  Stall,
 }
 
 #[derive(Debug, Clone)]
@@ -188,3 +342,62 @@ pub struct QosPolicyCount {
   policy_id: QosPolicyId,
   count: i32,
 }
+
+impl QosPolicyCount {
+  pub fn policy_id(&self) -> QosPolicyId {
+    self.policy_id
+  }
+
+  /// Number of times this policy was found incompatible.
+  pub fn count(&self) -> i32 {
+    self.count
+  }
+}
+
+// Bookkeeping for the RequestedIncompatibleQos / OfferedIncompatibleQos
+// statuses of a Reader / Writer.
+//
+// An incompatibility is counted once per remote endpoint, even though
+// Discovery keeps reporting the same remote endpoint repeatedly.
+#[derive(Debug, Default)]
+pub(crate) struct IncompatibleQosTracker {
+  total_count: i32,
+  policy_counts: BTreeMap<QosPolicyId, i32>,
+  incompatible_endpoints: BTreeSet<GUID>,
+}
+
+impl IncompatibleQosTracker {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // Returns true if this is a new incompatibility, i.e. a status should be
+  // sent. Each of the incompatible policies is counted.
+  pub fn record(&mut self, remote_guid: GUID, policy_ids: &[QosPolicyId]) -> bool {
+    if !self.incompatible_endpoints.insert(remote_guid) {
+      return false;
+    }
+    self.total_count += 1;
+    for policy_id in policy_ids {
+      *self.policy_counts.entry(*policy_id).or_insert(0) += 1;
+    }
+    true
+  }
+
+  // The remote endpoint is now compatible or gone.
+  pub fn forget(&mut self, remote_guid: GUID) {
+    self.incompatible_endpoints.remove(&remote_guid);
+  }
+
+  pub fn total_count(&self) -> i32 {
+    self.total_count
+  }
+
+  pub fn policies(&self) -> Vec<QosPolicyCount> {
+    self
+      .policy_counts
+      .iter()
+      .map(|(&policy_id, &count)| QosPolicyCount { policy_id, count })
+      .collect()
+  }
+}
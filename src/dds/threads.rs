@@ -0,0 +1,382 @@
+// Background threads of a DomainParticipant, and driving them from an
+// application thread instead.
+//
+// A DomainParticipant has two protocol loops, see ProtocolThread: the event
+// loop, which sends and receives all RTPS messages and runs the timers of
+// Readers and Writers, and Discovery. Each runs on a thread of its own,
+// unless the participant uses a tokio runtime or a ParticipantDriver.
+// Optionally, receive loops read the messages from the transports and pass
+// them to the event loop, see receive_loop.rs.
+
+use std::{cell::RefCell, collections::HashMap, io, thread, time::Duration};
+
+use mio::Events;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::dds::{
+  health::ProtocolThread,
+  values::result::{Error, Result},
+};
+
+/// Configuration of a protocol thread of a DomainParticipant, see
+/// [`DomainParticipantBuilder::thread_config`](crate::DomainParticipantBuilder::thread_config).
+///
+/// This applies only to threads that the participant starts itself, i.e. not
+/// when running on a tokio runtime or a [`ParticipantDriver`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadConfig {
+  name: Option<String>,
+  stack_size: Option<usize>,
+  cpu_affinity: Option<Vec<usize>>,
+  count: Option<usize>,
+}
+
+impl ThreadConfig {
+  /// CPU numbers must be below this.
+  pub const MAX_CPUS: usize = 1024;
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Name of the thread, instead of the default "RustDDS ..." name.
+  #[must_use]
+  pub fn name(mut self, name: impl Into<String>) -> Self {
+    self.name = Some(name.into());
+    self
+  }
+
+  /// Stack size of the thread in bytes, instead of the Rust default.
+  #[must_use]
+  pub fn stack_size(mut self, stack_size: usize) -> Self {
+    self.stack_size = Some(stack_size);
+    self
+  }
+
+  /// Run the thread only on these CPUs. This is supported on Linux only.
+  /// Elsewhere, and if the operating system refuses, the thread runs on any
+  /// CPU, and a warning is logged.
+  #[must_use]
+  pub fn cpu_affinity(mut self, cpus: impl IntoIterator<Item = usize>) -> Self {
+    self.cpu_affinity = Some(cpus.into_iter().collect());
+    self
+  }
+
+  /// Number of threads, by default one. Only
+  /// [`ProtocolThread::Receive`] can have more than one. With several
+  /// threads, a configured name gets the thread number as a suffix, e.g.
+  /// "dds-rx-0".
+  #[must_use]
+  pub fn count(mut self, count: usize) -> Self {
+    self.count = Some(count);
+    self
+  }
+
+  pub(crate) fn validate(&self, protocol_thread: ProtocolThread) -> Result<()> {
+    if let Some(cpus) = &self.cpu_affinity {
+      if cpus.is_empty() {
+        return Error::bad_parameter("CPU affinity must allow at least one CPU");
+      }
+      if let Some(cpu) = cpus.iter().find(|&&cpu| cpu >= Self::MAX_CPUS) {
+        return Error::bad_parameter(format!(
+          "CPU {} is out of range. CPU numbers must be below {}",
+          cpu,
+          Self::MAX_CPUS
+        ));
+      }
+    }
+    if self.stack_size == Some(0) {
+      return Error::bad_parameter("Thread stack size must not be zero");
+    }
+    match self.count {
+      Some(0) => Error::bad_parameter("Thread count must not be zero"),
+      Some(count) if count > 1 && protocol_thread != ProtocolThread::Receive => {
+        Error::bad_parameter(format!(
+          "{:?} cannot have {} threads, only Receive can have more than one",
+          protocol_thread, count
+        ))
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+#[cfg(target_os = "linux")]
+fn set_cpu_affinity(cpus: &[usize]) -> io::Result<()> {
+  // Safety: cpu_set_t is plain data, and the CPU numbers have been checked
+  // to fit in it.
+  unsafe {
+    let mut set: libc::cpu_set_t = std::mem::zeroed();
+    for &cpu in cpus {
+      libc::CPU_SET(cpu, &mut set);
+    }
+    if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0 {
+      Ok(())
+    } else {
+      Err(io::Error::last_os_error())
+    }
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_cpu_affinity(_cpus: &[usize]) -> io::Result<()> {
+  Err(io::Error::new(
+    io::ErrorKind::Other,
+    "CPU affinity is not supported on this platform",
+  ))
+}
+
+// How the protocol loops are run, as configured in DomainParticipantBuilder
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ThreadSettings {
+  pub configs: HashMap<ProtocolThread, ThreadConfig>,
+  #[cfg(feature = "tokio")]
+  pub tokio_runtime: Option<tokio::runtime::Handle>,
+}
+
+// A protocol loop, i.e. the event loop or Discovery
+pub(crate) trait BackgroundLoop {
+  // Runs until stopped.
+  fn run(self: Box<Self>);
+
+  // Waits for events for at most `timeout`, and handles them. Returns false
+  // when the loop has been stopped.
+  fn run_round(&mut self, events: &mut Events, timeout: Option<Duration>) -> bool;
+}
+
+type DrivenLoops = Vec<(ProtocolThread, Box<dyn BackgroundLoop>)>;
+
+// Starts the protocol loops of a DomainParticipant under construction: on
+// threads of their own, with the "tokio" feature on the blocking thread pool
+// of a tokio runtime, or for a ParticipantDriver, not at all.
+pub(crate) struct BackgroundSpawner {
+  settings: ThreadSettings,
+  // Loops for the ParticipantDriver, if the participant has one
+  driven: Option<RefCell<DrivenLoops>>,
+}
+
+impl BackgroundSpawner {
+  pub fn new(settings: ThreadSettings, driven: bool) -> Self {
+    Self {
+      settings,
+      driven: if driven {
+        Some(RefCell::new(Vec::new()))
+      } else {
+        None
+      },
+    }
+  }
+
+  // How many threads of `protocol_thread` are configured, if the loops run on
+  // threads of the participant at all
+  pub fn thread_count(&self, protocol_thread: ProtocolThread) -> Option<usize> {
+    if self.driven.is_some() {
+      return None;
+    }
+    #[cfg(feature = "tokio")]
+    {
+      if self.settings.tokio_runtime.is_some() {
+        return None;
+      }
+    }
+    self
+      .settings
+      .configs
+      .get(&protocol_thread)
+      .map(|config| config.count.unwrap_or(1))
+  }
+
+  // Creates the loop with `make`, on the thread where it runs. `make` returns
+  // None if the loop cannot start.
+  pub fn spawn<L, F>(
+    &self,
+    protocol_thread: ProtocolThread,
+    default_name: String,
+    make: F,
+  ) -> io::Result<BackgroundTask>
+  where
+    L: BackgroundLoop + 'static,
+    F: FnOnce() -> Option<L> + Send + 'static,
+  {
+    self.spawn_instance(protocol_thread, None, default_name, make)
+  }
+
+  // Like spawn, for one of several threads of `protocol_thread`. The
+  // `instance` number is appended to the configured thread name.
+  pub fn spawn_instance<L, F>(
+    &self,
+    protocol_thread: ProtocolThread,
+    instance: Option<usize>,
+    default_name: String,
+    make: F,
+  ) -> io::Result<BackgroundTask>
+  where
+    L: BackgroundLoop + 'static,
+    F: FnOnce() -> Option<L> + Send + 'static,
+  {
+    if let Some(driven) = &self.driven {
+      if let Some(background_loop) = make() {
+        driven
+          .borrow_mut()
+          .push((protocol_thread, Box::new(background_loop)));
+      }
+      return Ok(BackgroundTask::Driven);
+    }
+    let run = move || {
+      if let Some(background_loop) = make() {
+        Box::new(background_loop).run();
+      }
+    };
+
+    #[cfg(feature = "tokio")]
+    {
+      if let Some(runtime) = &self.settings.tokio_runtime {
+        let (done_sender, done_receiver) = std::sync::mpsc::sync_channel(1);
+        runtime.spawn_blocking(move || {
+          let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(run));
+          done_sender.send(result).unwrap_or(());
+        });
+        return Ok(BackgroundTask::Tokio(done_receiver));
+      }
+    }
+
+    let config = self
+      .settings
+      .configs
+      .get(&protocol_thread)
+      .cloned()
+      .unwrap_or_default();
+    let name = match (config.name, instance) {
+      (Some(name), Some(instance)) => format!("{}-{}", name, instance),
+      (Some(name), None) => name,
+      (None, _) => default_name,
+    };
+    let mut builder = thread::Builder::new().name(name);
+    if let Some(stack_size) = config.stack_size {
+      builder = builder.stack_size(stack_size);
+    }
+    let cpu_affinity = config.cpu_affinity;
+    builder
+      .spawn(move || {
+        if let Some(cpus) = cpu_affinity {
+          if let Err(e) = set_cpu_affinity(&cpus) {
+            warn!(
+              "Cannot set CPU affinity of {:?} thread: {}",
+              protocol_thread, e
+            );
+          }
+        }
+        run();
+      })
+      .map(BackgroundTask::Thread)
+  }
+
+  // The driver of the loops, if they are not run by threads
+  pub fn into_driver(self) -> Option<ParticipantDriver> {
+    self.driven.map(|driven| ParticipantDriver {
+      loops: driven
+        .into_inner()
+        .into_iter()
+        .map(|(thread, background_loop)| (thread, background_loop, Events::with_capacity(32)))
+        .collect(),
+    })
+  }
+}
+
+pub(crate) enum BackgroundTask {
+  Thread(thread::JoinHandle<()>),
+  // Receives the result when the task finishes. If the runtime is shut down
+  // before the task runs, the sender is dropped.
+  #[cfg(feature = "tokio")]
+  Tokio(std::sync::mpsc::Receiver<thread::Result<()>>),
+  // Run by a ParticipantDriver, which finishes the loop on its next poll
+  Driven,
+}
+
+impl BackgroundTask {
+  // Blocks until the loop has finished, like JoinHandle::join
+  pub fn join(self) -> thread::Result<()> {
+    match self {
+      Self::Thread(handle) => handle.join(),
+      #[cfg(feature = "tokio")]
+      Self::Tokio(done_receiver) => done_receiver
+        .recv()
+        .unwrap_or_else(|_| Err(Box::new("Task was dropped by the tokio runtime"))),
+      Self::Driven => Ok(()),
+    }
+  }
+}
+
+/// Runs the protocol loops of a DomainParticipant on an application thread,
+/// instead of threads of the participant. This is for embedded and real-time
+/// systems, where the application controls all threads. See
+/// [`DomainParticipantBuilder::build_driven`](crate::DomainParticipantBuilder::build_driven).
+///
+/// The participant does not communicate unless the application calls
+/// [`poll`](Self::poll) regularly, at least every 100 ms or so, because
+/// the protocol timers are also handled there. The driver is not `Send`: it
+/// stays on the thread that built the participant.
+///
+/// The [`TcpV4`](crate::TransportKind::TcpV4) transport, if selected, still
+/// receives on a thread of its own.
+pub struct ParticipantDriver {
+  loops: Vec<(ProtocolThread, Box<dyn BackgroundLoop>, Events)>,
+}
+
+impl ParticipantDriver {
+  // Limits step() when events keep coming in
+  const MAX_STEP_ROUNDS: usize = 64;
+
+  /// Handles the pending protocol events, waiting for them for at most
+  /// `timeout`. Returns false when the participant has been closed or
+  /// dropped, and the driver has nothing more to do.
+  pub fn poll(&mut self, timeout: Duration) -> bool {
+    self.run_round(timeout);
+    !self.loops.is_empty()
+  }
+
+  /// Handles all events that are due now, without waiting: received
+  /// messages, requests from the application, and expired timers. Handling
+  /// them may cause more events, such as a Writer sending to a Reader of the
+  /// same participant, so this repeats until there are none.
+  ///
+  /// With a [`SimulatedClock`](crate::SimulatedClock), call this after each
+  /// advance of the clock. Returns false like [`poll`](Self::poll).
+  pub fn step(&mut self) -> bool {
+    for _ in 0..Self::MAX_STEP_ROUNDS {
+      if !self.run_round(Duration::ZERO) {
+        break;
+      }
+    }
+    !self.loops.is_empty()
+  }
+
+  // Returns true if there were events
+  fn run_round(&mut self, timeout: Duration) -> bool {
+    // Discovery does not wait, so that the event loop can. Discovery runs
+    // again after the event loop, to handle what it received without delay.
+    let discovery_events = self.run_rounds(ProtocolThread::Discovery, Duration::ZERO);
+    let event_loop_events = self.run_rounds(ProtocolThread::EventLoop, timeout);
+    let more_discovery_events = self.run_rounds(ProtocolThread::Discovery, Duration::ZERO);
+    discovery_events || event_loop_events || more_discovery_events
+  }
+
+  fn run_rounds(&mut self, thread: ProtocolThread, timeout: Duration) -> bool {
+    let mut had_events = false;
+    let mut i = 0;
+    while i < self.loops.len() {
+      let (t, background_loop, events) = &mut self.loops[i];
+      if *t != thread {
+        i += 1;
+      } else if background_loop.run_round(events, Some(timeout)) {
+        had_events |= !events.is_empty();
+        i += 1;
+      } else {
+        self.loops.remove(i);
+        had_events = true;
+      }
+    }
+    had_events
+  }
+}
@@ -0,0 +1,118 @@
+// Timers of the protocol loops.
+//
+// Timers run on the system clock, using mio_extras timers, unless the
+// participant has a SimulatedClock. Then they expire only when the simulated
+// clock is advanced.
+
+use std::{io, time::Duration};
+
+use mio::{Evented, Poll, PollOpt, Ready, Token};
+
+use crate::dds::{clock::TimeSource, simulation::SimulatedTimer};
+
+// Like mio_extras::timer::Timer, which it wraps for the system clock. A timer
+// also tells the time of the participant that created it.
+pub(crate) struct Timer<T> {
+  time_source: TimeSource,
+  kind: TimerKind<T>,
+}
+
+enum TimerKind<T> {
+  System(mio_extras::timer::Timer<T>),
+  Simulated(SimulatedTimer<T>),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Timeout {
+  System(mio_extras::timer::Timeout),
+  Simulated(u64),
+}
+
+impl<T> Default for Timer<T> {
+  fn default() -> Self {
+    Self::new(&TimeSource::System, None)
+  }
+}
+
+impl<T> Timer<T> {
+  // A system timer can have a different number of slots than the default,
+  // e.g. fewer for timers that seldom have many timeouts pending.
+  pub fn new(time_source: &TimeSource, num_slots: Option<usize>) -> Self {
+    let kind = match time_source {
+      TimeSource::Simulated(clock) => TimerKind::Simulated(SimulatedTimer::new(clock.clone())),
+      TimeSource::System | TimeSource::Custom(_) => {
+        let mut builder = mio_extras::timer::Builder::default();
+        if let Some(num_slots) = num_slots {
+          builder = builder.num_slots(num_slots);
+        }
+        TimerKind::System(builder.build())
+      }
+    };
+    Self {
+      time_source: time_source.clone(),
+      kind,
+    }
+  }
+
+  pub fn time_source(&self) -> &TimeSource {
+    &self.time_source
+  }
+
+  pub fn set_timeout(&mut self, delay_from_now: Duration, state: T) -> Timeout {
+    match &mut self.kind {
+      TimerKind::System(timer) => Timeout::System(timer.set_timeout(delay_from_now, state)),
+      TimerKind::Simulated(timer) => Timeout::Simulated(timer.set_timeout(delay_from_now, state)),
+    }
+  }
+
+  pub fn cancel_timeout(&mut self, timeout: &Timeout) -> Option<T> {
+    match (&mut self.kind, timeout) {
+      (TimerKind::System(timer), Timeout::System(timeout)) => timer.cancel_timeout(timeout),
+      (TimerKind::Simulated(timer), Timeout::Simulated(id)) => timer.cancel_timeout(*id),
+      _ => None,
+    }
+  }
+
+  // The state of an expired timeout, if any
+  pub fn poll(&mut self) -> Option<T> {
+    match &mut self.kind {
+      TimerKind::System(timer) => timer.poll(),
+      TimerKind::Simulated(timer) => timer.poll(),
+    }
+  }
+}
+
+// The inherent registration methods of mio::Registration are deprecated, so
+// its Evented methods are called explicitly.
+impl<T> Evented for Timer<T> {
+  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    match &self.kind {
+      TimerKind::System(timer) => timer.register(poll, token, interest, opts),
+      TimerKind::Simulated(timer) => {
+        Evented::register(timer.registration(), poll, token, interest, opts)
+      }
+    }
+  }
+
+  fn reregister(
+    &self,
+    poll: &Poll,
+    token: Token,
+    interest: Ready,
+    opts: PollOpt,
+  ) -> io::Result<()> {
+    match &self.kind {
+      TimerKind::System(timer) => timer.reregister(poll, token, interest, opts),
+      TimerKind::Simulated(timer) => {
+        Evented::reregister(timer.registration(), poll, token, interest, opts)
+      }
+    }
+  }
+
+  fn deregister(&self, poll: &Poll) -> io::Result<()> {
+    match &self.kind {
+      TimerKind::System(timer) => timer.deregister(poll),
+      TimerKind::Simulated(timer) => Evented::deregister(timer.registration(), poll),
+    }
+  }
+}
@@ -0,0 +1,402 @@
+use std::{
+  ops::Deref,
+  pin::Pin,
+  task::{self, Context},
+};
+
+use futures_core::Stream;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+  dds::{
+    no_key,
+    sampleinfo::SampleInfo,
+    traits::{
+      key::{Key, Keyed},
+      serde_adapters::{no_key as no_key_adapters, with_key as with_key_adapters},
+    },
+    values::result::Result,
+    with_key,
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+  structure::{rpc::SampleIdentity, time::Timestamp},
+};
+
+/// Stream of samples taken from an owned no_key DataReader, together with
+/// their [`SampleInfo`]. The stream does not end, but it yields an error if
+/// taking samples fails.
+///
+/// This is like
+/// [`no_key::DataReader::async_sample_stream`](crate::no_key::DataReader::async_sample_stream),
+/// but owns the DataReader, so that the stream is `'static`.
+pub struct DataReaderStream<D, DA = CDRDeserializerAdapter<D>>
+where
+  D: DeserializeOwned,
+  DA: no_key_adapters::DeserializerAdapter<D>,
+{
+  reader: no_key::DataReader<D, DA>,
+}
+
+impl<D, DA> DataReaderStream<D, DA>
+where
+  D: DeserializeOwned + 'static,
+  DA: no_key_adapters::DeserializerAdapter<D>,
+{
+  pub fn new(reader: no_key::DataReader<D, DA>) -> Self {
+    Self { reader }
+  }
+
+  /// Gives the DataReader back.
+  pub fn into_inner(self) -> no_key::DataReader<D, DA> {
+    self.reader
+  }
+}
+
+impl<D, DA> Deref for DataReaderStream<D, DA>
+where
+  D: DeserializeOwned,
+  DA: no_key_adapters::DeserializerAdapter<D>,
+{
+  type Target = no_key::DataReader<D, DA>;
+
+  fn deref(&self) -> &Self::Target {
+    &self.reader
+  }
+}
+
+// The DataReader is not pinned, so the stream can be moved while polled.
+impl<D, DA> Unpin for DataReaderStream<D, DA>
+where
+  D: DeserializeOwned,
+  DA: no_key_adapters::DeserializerAdapter<D>,
+{
+}
+
+impl<D, DA> Stream for DataReaderStream<D, DA>
+where
+  D: DeserializeOwned + 'static,
+  DA: no_key_adapters::DeserializerAdapter<D>,
+{
+  type Item = Result<(D, SampleInfo)>;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<Option<Self::Item>> {
+    let mut samples = self.get_mut().reader.async_sample_stream();
+    Pin::new(&mut samples)
+      .poll_next(cx)
+      .map(|item| item.map(|sample| sample.map(|sample| (sample.value, sample.sample_info))))
+  }
+}
+
+/// Stream of samples taken from an owned with_key DataReader, together with
+/// their [`SampleInfo`]. The value is the key of the instance, if it was
+/// disposed or unregistered. Like [`DataReaderStream`], but for WITH_KEY
+/// topics.
+pub struct KeyedDataReaderStream<D, DA = CDRDeserializerAdapter<D>>
+where
+  D: Keyed + DeserializeOwned,
+  DA: with_key_adapters::DeserializerAdapter<D>,
+{
+  reader: with_key::DataReader<D, DA>,
+}
+
+impl<D, DA> KeyedDataReaderStream<D, DA>
+where
+  D: Keyed + DeserializeOwned + 'static,
+  <D as Keyed>::K: Key,
+  DA: with_key_adapters::DeserializerAdapter<D>,
+{
+  pub fn new(reader: with_key::DataReader<D, DA>) -> Self {
+    Self { reader }
+  }
+
+  /// Gives the DataReader back.
+  pub fn into_inner(self) -> with_key::DataReader<D, DA> {
+    self.reader
+  }
+}
+
+impl<D, DA> Deref for KeyedDataReaderStream<D, DA>
+where
+  D: Keyed + DeserializeOwned,
+  DA: with_key_adapters::DeserializerAdapter<D>,
+{
+  type Target = with_key::DataReader<D, DA>;
+
+  fn deref(&self) -> &Self::Target {
+    &self.reader
+  }
+}
+
+impl<D, DA> Unpin for KeyedDataReaderStream<D, DA>
+where
+  D: Keyed + DeserializeOwned,
+  DA: with_key_adapters::DeserializerAdapter<D>,
+{
+}
+
+impl<D, DA> Stream for KeyedDataReaderStream<D, DA>
+where
+  D: Keyed + DeserializeOwned + 'static,
+  <D as Keyed>::K: Key,
+  DA: with_key_adapters::DeserializerAdapter<D>,
+{
+  type Item = Result<(std::result::Result<D, D::K>, SampleInfo)>;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<Option<Self::Item>> {
+    let mut samples = self.get_mut().reader.async_sample_stream();
+    Pin::new(&mut samples)
+      .poll_next(cx)
+      .map(|item| item.map(|sample| sample.map(|sample| (sample.value, sample.sample_info))))
+  }
+}
+
+/// Owned no_key DataWriter with async writes. Dereferences to the DataWriter
+/// for its other operations.
+pub struct AsyncDataWriter<D, SA = CDRSerializerAdapter<D>>
+where
+  D: Serialize,
+  SA: no_key_adapters::SerializerAdapter<D>,
+{
+  writer: no_key::DataWriter<D, SA>,
+}
+
+impl<D, SA> AsyncDataWriter<D, SA>
+where
+  D: Serialize,
+  SA: no_key_adapters::SerializerAdapter<D>,
+{
+  pub fn new(writer: no_key::DataWriter<D, SA>) -> Self {
+    Self { writer }
+  }
+
+  /// Gives the DataWriter back.
+  pub fn into_inner(self) -> no_key::DataWriter<D, SA> {
+    self.writer
+  }
+
+  /// Writes a sample. If the history is full, this waits for space without
+  /// blocking the executor, see
+  /// [`async_wait_for_history_space`](crate::no_key::DataWriter::async_wait_for_history_space).
+  /// This does not wait for acknowledgments.
+  pub async fn write(
+    &self,
+    data: D,
+    source_timestamp: Option<Timestamp>,
+  ) -> Result<SampleIdentity> {
+    self.writer.async_wait_for_history_space().await?;
+    self
+      .writer
+      .write_with_options(data, source_timestamp.into())
+  }
+
+  /// Writes a sample like [`write`](Self::write), and then waits until all
+  /// matched reliable DataReaders have acknowledged it.
+  pub async fn write_reliably(&self, data: D, source_timestamp: Option<Timestamp>) -> Result<()> {
+    self.writer.async_write(data, source_timestamp).await
+  }
+}
+
+impl<D, SA> Deref for AsyncDataWriter<D, SA>
+where
+  D: Serialize,
+  SA: no_key_adapters::SerializerAdapter<D>,
+{
+  type Target = no_key::DataWriter<D, SA>;
+
+  fn deref(&self) -> &Self::Target {
+    &self.writer
+  }
+}
+
+/// Owned with_key DataWriter with async writes. Like [`AsyncDataWriter`],
+/// but for WITH_KEY topics.
+pub struct KeyedAsyncDataWriter<D, SA = CDRSerializerAdapter<D>>
+where
+  D: Keyed + Serialize,
+  SA: with_key_adapters::SerializerAdapter<D>,
+{
+  writer: with_key::DataWriter<D, SA>,
+}
+
+impl<D, SA> KeyedAsyncDataWriter<D, SA>
+where
+  D: Keyed + Serialize,
+  <D as Keyed>::K: Key,
+  SA: with_key_adapters::SerializerAdapter<D>,
+{
+  pub fn new(writer: with_key::DataWriter<D, SA>) -> Self {
+    Self { writer }
+  }
+
+  /// Gives the DataWriter back.
+  pub fn into_inner(self) -> with_key::DataWriter<D, SA> {
+    self.writer
+  }
+
+  /// Writes a sample, waiting for space in the history if necessary. See
+  /// [`AsyncDataWriter::write`].
+  pub async fn write(
+    &self,
+    data: D,
+    source_timestamp: Option<Timestamp>,
+  ) -> Result<SampleIdentity> {
+    self.writer.async_wait_for_history_space().await?;
+    self
+      .writer
+      .write_with_options(data, source_timestamp.into())
+  }
+
+  /// Writes a sample, and then waits until all matched reliable DataReaders
+  /// have acknowledged it. See [`AsyncDataWriter::write_reliably`].
+  pub async fn write_reliably(&self, data: D, source_timestamp: Option<Timestamp>) -> Result<()> {
+    self.writer.async_write(data, source_timestamp).await
+  }
+}
+
+impl<D, SA> Deref for KeyedAsyncDataWriter<D, SA>
+where
+  D: Keyed + Serialize,
+  SA: with_key_adapters::SerializerAdapter<D>,
+{
+  type Target = with_key::DataWriter<D, SA>;
+
+  fn deref(&self) -> &Self::Target {
+    &self.writer
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration as StdDuration;
+
+  use futures::StreamExt;
+  use serde::Deserialize;
+
+  use super::*;
+  use crate::{
+    dds::{
+      participant::DomainParticipantBuilder,
+      qos::{policy, QosPolicyBuilder},
+      topic::TopicKind,
+    },
+    structure::{duration::Duration, entity::RTPSEntity},
+    test::loopback::loopback_participant_builder,
+  };
+
+  #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+  struct Reading {
+    sensor: u32,
+    value: f64,
+  }
+
+  fn reliable_qos() -> crate::dds::qos::QosPolicies {
+    QosPolicyBuilder::new()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: Duration::from_secs(1),
+      })
+      .history(policy::History::KeepAll)
+      .build()
+  }
+
+  #[test]
+  fn participant_on_tokio_runtime() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let participant = DomainParticipantBuilder::new(0)
+      .tokio_runtime(runtime.handle().clone())
+      .build()
+      .unwrap();
+    let qos = reliable_qos();
+    let topic = participant
+      .create_topic(
+        "tokio_readings".to_string(),
+        "Reading".to_string(),
+        &qos,
+        TopicKind::NoKey,
+      )
+      .unwrap();
+    let publisher = participant.create_publisher(&qos).unwrap();
+    let writer = AsyncDataWriter::new(
+      publisher
+        .create_datawriter_no_key_cdr::<Reading>(&topic, None)
+        .unwrap(),
+    );
+
+    runtime.block_on(async {
+      let reading = Reading {
+        sensor: 1,
+        value: 0.5,
+      };
+      writer.write(reading.clone(), None).await.unwrap();
+      // No matched readers to wait for
+      tokio::time::timeout(
+        StdDuration::from_secs(5),
+        writer.write_reliably(reading, None),
+      )
+      .await
+      .expect("Acknowledgment wait timed out")
+      .unwrap();
+    });
+    drop(writer);
+    participant.close().unwrap();
+  }
+
+  #[test]
+  fn stream_samples_on_tokio_runtime() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let participant = loopback_participant_builder(220)
+      .tokio_runtime(runtime.handle().clone())
+      .build()
+      .unwrap();
+    let qos = reliable_qos();
+    let topic = participant
+      .create_topic(
+        "tokio_stream".to_string(),
+        "Reading".to_string(),
+        &qos,
+        TopicKind::NoKey,
+      )
+      .unwrap();
+    let subscriber = participant.create_subscriber(&qos).unwrap();
+    let mut samples = DataReaderStream::new(
+      subscriber
+        .create_datareader_no_key_cdr::<Reading>(&topic, None)
+        .unwrap(),
+    );
+    let publisher = participant.create_publisher(&qos).unwrap();
+    let writer = AsyncDataWriter::new(
+      publisher
+        .create_datawriter_no_key_cdr::<Reading>(&topic, None)
+        .unwrap(),
+    );
+
+    let writer_guid = writer.guid();
+
+    runtime.block_on(async {
+      let received = tokio::spawn(async move {
+        let mut values = Vec::new();
+        while values.len() < 3 {
+          let (reading, info) = samples.next().await.unwrap().unwrap();
+          assert_eq!(info.writer_guid(), writer_guid);
+          values.push(reading);
+        }
+        values
+      });
+      // Give discovery time to match the endpoints.
+      tokio::time::sleep(StdDuration::from_secs(2)).await;
+      let sent: Vec<Reading> = (0..3)
+        .map(|i| Reading {
+          sensor: i,
+          value: f64::from(i) / 2.0,
+        })
+        .collect();
+      for reading in &sent {
+        writer.write_reliably(reading.clone(), None).await.unwrap();
+      }
+      let values = tokio::time::timeout(StdDuration::from_secs(5), received)
+        .await
+        .expect("Samples were not received")
+        .unwrap();
+      assert_eq!(values, sent);
+    });
+  }
+}
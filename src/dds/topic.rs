@@ -1,17 +1,75 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{
+  fmt::Debug,
+  sync::{Arc, RwLock},
+};
 
 use crate::dds::{
   participant::{DomainParticipant, DomainParticipantWeak},
-  qos::{HasQoSPolicy, QosPolicies},
+  qos::{HasQoSPolicy, MutQosPolicy, QosPolicies},
   traits::dds_entity::DDSEntity,
   typedesc::TypeDesc,
+  values::result::{Error, Result},
 };
 pub use crate::structure::topic_kind::TopicKind;
 
+// Topic names are sent to other participants as a sequence of octets. They
+// must match exactly, so names are compared as UTF-8 bytes, without any
+// Unicode normalization.
+//
+// A topic name starts with a letter, '_' or '/', and the rest are letters,
+// digits or one of "_-/.". Letters and digits may be non-ASCII. These cover
+// the names used by e.g. ROS 2 ("rt/chatter"), and exclude characters that
+// have a meaning in content filter expressions and partition patterns.
+pub(crate) fn validate_topic_name(name: &str) -> Result<()> {
+  validate_name_length("Topic", name)?;
+  let mut chars = name.chars();
+  match chars.next() {
+    Some(c) if c.is_alphabetic() || c == '_' || c == '/' => (),
+    Some(c) => {
+      return Error::bad_parameter(format!("Topic name {:?} must not start with {:?}", name, c))
+    }
+    None => unreachable!(), // checked above
+  }
+  match chars.find(|c| !(c.is_alphanumeric() || "_-/.".contains(*c))) {
+    Some(c) => Error::bad_parameter(format!("Topic name {:?} contains character {:?}", name, c)),
+    None => Ok(()),
+  }
+}
+
+// Type names are more varied, e.g. "std_msgs::msg::dds_::String_" from IDL
+// modules, so only control characters are rejected.
+pub(crate) fn validate_type_name(name: &str) -> Result<()> {
+  validate_name_length("Type", name)?;
+  match name.chars().find(|c| c.is_control()) {
+    Some(c) => Error::bad_parameter(format!(
+      "Type name {:?} contains control character {:?}",
+      name, c
+    )),
+    None => Ok(()),
+  }
+}
+
+fn validate_name_length(what: &str, name: &str) -> Result<()> {
+  if name.is_empty() {
+    Error::bad_parameter(format!("{} name must not be empty", what))
+  } else if name.len() > Topic::MAX_NAME_LENGTH {
+    Error::bad_parameter(format!(
+      "{} name {:?} is {} bytes long, but at most {} are allowed",
+      what,
+      name,
+      name.len(),
+      Topic::MAX_NAME_LENGTH
+    ))
+  } else {
+    Ok(())
+  }
+}
+
 /// Trait approximation of DDS 2.2.2.3.1 TopicDescription Class
 ///
-/// Now it is utterly useless, but if we ever add ContentFilteredTopic or
-/// MultiTopic, then it may turn out to be useful.
+/// Implemented by [`Topic`],
+/// [`ContentFilteredTopic`](crate::ContentFilteredTopic) and
+/// [`MultiTopic`](crate::MultiTopic).
 pub trait TopicDescription {
   fn participant(&self) -> Option<DomainParticipant>;
   fn get_type(&self) -> TypeDesc; // This replaces type_name() from spec
@@ -45,12 +103,13 @@ pub trait TopicDescription {
 /// ```
 #[derive(Clone)]
 pub struct Topic {
-  //TODO: Do we really need set_qos operation?
-  // Maybe not. Let's make Topic immutable.
   inner: Arc<InnerTopic>,
 }
 
 impl Topic {
+  /// Maximum length of topic and type names, in bytes of UTF-8.
+  pub const MAX_NAME_LENGTH: usize = 256;
+
   pub(crate) fn new(
     my_domainparticipant: &DomainParticipantWeak,
     my_name: String,
@@ -148,16 +207,34 @@ impl HasQoSPolicy for Topic {
   }
 }
 
+impl MutQosPolicy for Topic {
+  /// Changes the QoS of this Topic.
+  ///
+  /// Policies defined in `new_qos` replace the current ones, and the rest are
+  /// kept. Attempting to change a policy that cannot be changed after
+  /// creation, e.g. Reliability, fails with
+  /// [`ImmutablePolicy`](crate::dds::values::result::Error::ImmutablePolicy).
+  ///
+  /// The new QoS is announced to remote participants. Existing DataWriters and
+  /// DataReaders of the Topic are not affected.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<()> {
+    self.inner.set_qos(new_qos)?;
+    if let Some(dp) = self.participant() {
+      dp.discovery_db().write()?.update_topic_data_p(self);
+    }
+    Ok(())
+  }
+}
+
 //impl DDSEntity for Topic {}
 
 // -------------------------------- InnerTopic -----------------------------
 
-#[derive(Clone)]
 pub struct InnerTopic {
   my_domainparticipant: DomainParticipantWeak,
   my_name: String,
   my_typedesc: TypeDesc,
-  my_qos_policies: QosPolicies,
+  my_qos_policies: RwLock<QosPolicies>,
   topic_kind: TopicKind, // WITH_KEY or NO_KEY
 }
 
@@ -175,7 +252,7 @@ impl InnerTopic {
       my_domainparticipant: my_domainparticipant.clone(),
       my_name,
       my_typedesc,
-      my_qos_policies: my_qos_policies.clone(),
+      my_qos_policies: RwLock::new(my_qos_policies.clone()),
       topic_kind,
     }
   }
@@ -195,6 +272,12 @@ impl InnerTopic {
   pub fn kind(&self) -> TopicKind {
     self.topic_kind
   }
+
+  fn set_qos(&self, new_qos: &QosPolicies) -> Result<()> {
+    let mut qos = self.my_qos_policies.write()?;
+    *qos = qos.changed_by(new_qos)?;
+    Ok(())
+  }
   /*
   pub(crate) fn get_inconsistent_topic_status() -> Result<TopicStatus> {
     unimplemented!()
@@ -240,15 +323,49 @@ impl TopicDescription for InnerTopic {
 }
 
 impl HasQoSPolicy for InnerTopic {
-  // fn set_qos(&mut self, policy: &QosPolicies) -> Result<()> {
-  //   // TODO: check liveliness of qos_polic
-  //   self.my_qos_policies = policy.clone();
-  //   Ok(())
-  // }
-
   fn qos(&self) -> QosPolicies {
-    self.my_qos_policies.clone()
+    self
+      .my_qos_policies
+      .read()
+      .unwrap_or_else(|e| panic!("Topic QoS lock fail! {:?}", e))
+      .clone()
   }
 }
 
 impl DDSEntity for InnerTopic {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn topic_names() {
+    for name in [
+      "Square",
+      "rt/chatter",
+      "_private",
+      "/a-b.c_d",
+      "Päivä",
+      "數據",
+    ] {
+      assert!(validate_topic_name(name).is_ok(), "{:?}", name);
+    }
+    for name in ["", "1st", "-x", "some topic", "what?", "a*", "tab\t", "a,b"] {
+      assert!(validate_topic_name(name).is_err(), "{:?}", name);
+    }
+    // The limit is in bytes, not characters.
+    assert!(validate_topic_name(&"a".repeat(Topic::MAX_NAME_LENGTH)).is_ok());
+    assert!(validate_topic_name(&"a".repeat(Topic::MAX_NAME_LENGTH + 1)).is_err());
+    assert!(validate_topic_name(&"ä".repeat(Topic::MAX_NAME_LENGTH / 2 + 1)).is_err());
+  }
+
+  #[test]
+  fn type_names() {
+    for name in ["ShapeType", "std_msgs::msg::dds_::String_", "Huh?"] {
+      assert!(validate_type_name(name).is_ok(), "{:?}", name);
+    }
+    for name in ["", "new\nline", "nul\0"] {
+      assert!(validate_type_name(name).is_err(), "{:?}", name);
+    }
+  }
+}
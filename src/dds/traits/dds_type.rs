@@ -0,0 +1,107 @@
+/// Name of a data type, as communicated in DDS discovery.
+///
+/// Writers and readers match only if their topics have the same type name, so
+/// the name should be the one other DDS implementations use for the same
+/// type, usually its fully qualified IDL name, e.g. `"geometry::Point"`.
+///
+/// This can be derived with `#[derive(DdsType)]`, which also implements
+/// [`Keyed`](crate::Keyed) from fields marked `#[dds_key]`. The derive
+/// macro requires the `derive` feature, which is on by default.
+///
+/// # Example
+/// ```
+/// use rustdds::*;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, DdsType)]
+/// #[dds_type(name = "ShapeType")]
+/// struct Shape {
+///   #[dds_key]
+///   color: String,
+///   x: i32,
+///   y: i32,
+///   shapesize: i32,
+/// }
+///
+/// assert_eq!(Shape::type_name(), "ShapeType");
+/// let shape = Shape {
+///   color: "BLUE".to_string(),
+///   x: 1,
+///   y: 2,
+///   shapesize: 3,
+/// };
+/// assert_eq!(shape.key(), "BLUE");
+/// ```
+pub trait DdsType {
+  fn type_name() -> &'static str;
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+  use serde::{Deserialize, Serialize};
+
+  use crate::{DdsType, Key, Keyed};
+
+  #[derive(Serialize, Deserialize, DdsType)]
+  struct NoKey {
+    a: i32,
+  }
+
+  #[derive(Serialize, Deserialize, DdsType)]
+  #[dds_type(name = "test::Reading")]
+  struct Reading {
+    #[dds_key]
+    sensor: u32,
+    value: f64,
+    #[dds_key]
+    channel: u16,
+  }
+
+  #[derive(Serialize, Deserialize, DdsType)]
+  struct Tuple(#[dds_key] String, u8);
+
+  #[derive(Serialize, Deserialize, DdsType)]
+  struct Wrapper<T> {
+    #[dds_key]
+    id: u64,
+    payload: T,
+  }
+
+  #[test]
+  fn type_names() {
+    assert_eq!(NoKey::type_name(), "NoKey");
+    assert_eq!(Reading::type_name(), "test::Reading");
+    assert_eq!(Wrapper::<u8>::type_name(), "Wrapper");
+  }
+
+  #[test]
+  fn generated_keys() {
+    let reading = Reading {
+      sensor: 7,
+      value: 1.5,
+      channel: 2,
+    };
+    let key = reading.key();
+    assert_eq!(
+      key,
+      ReadingKey {
+        sensor: 7,
+        channel: 2
+      }
+    );
+    // Fits in 16 bytes: Big-endian CDR, zero-padded
+    assert_eq!(
+      key.hash_key().to_vec(),
+      vec![0, 0, 0, 7, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+
+    let tuple = Tuple("abc".to_string(), 1);
+    assert_eq!(tuple.key(), "abc");
+
+    let wrapper = Wrapper {
+      id: 3,
+      payload: "x",
+    };
+    assert_eq!(wrapper.key(), 3);
+  }
+}
@@ -59,6 +59,37 @@ impl KeyHash {
     let a = <[u8; 16]>::try_from(bytes).map_err(|_e| Error::Eof)?;
     Ok(Self(a))
   }
+
+  // The key hash of a key, given its CDR Big-Endian representation and the
+  // maximum size of that for the key type. See Key::hash_key.
+  pub(crate) fn from_key_cdr_bytes(cdr_bytes: &[u8], max_size: CdrEncodingMaxSize) -> Self {
+    Self(if max_size > CdrEncodingMaxSize::Bytes(16) {
+      // use MD5 hash to get the hash. The MD5 hash is always exactly
+      // 16 bytes, so just deref it to [u8;16]
+      *md5::compute(cdr_bytes)
+    } else {
+      // pad with zeros to get 16 bytes
+      let mut hash = [0x00; 16];
+      let len = cdr_bytes.len().min(16);
+      hash[..len].copy_from_slice(&cdr_bytes[..len]);
+      hash
+    })
+  }
+}
+
+/// Handle to an instance registered with a DataWriter.
+///
+/// RustDDS mostly uses keys directly in place of instance handles (see the
+/// README), but [`register_instance`](crate::with_key::DataWriter::register_instance)
+/// returns one, so that later writes can be checked to refer to the same
+/// instance. The handle is derived from the key hash of the instance.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
+pub struct InstanceHandle(KeyHash);
+
+impl From<KeyHash> for InstanceHandle {
+  fn from(key_hash: KeyHash) -> Self {
+    Self(key_hash)
+  }
 }
 
 /// Trait for instance lookup key in a WITH_KEY topic.
@@ -135,7 +166,7 @@ pub trait Key:
     // (Does it include CDR-specified alignment padding too?)
     //
 
-    let mut cdr_bytes = to_bytes::<Self, BigEndian>(self).unwrap_or_else(|e| {
+    let cdr_bytes = to_bytes::<Self, BigEndian>(self).unwrap_or_else(|e| {
       error!("Hashing key {:?} failed!", e);
       // This would cause a lot of hash collisions, but wht else we could do
       // if the key cannot be serialized? Are there any realistic conditions
@@ -143,17 +174,7 @@ pub trait Key:
       vec![0; 16]
     });
 
-    KeyHash(
-      if Self::cdr_encoding_max_size() > CdrEncodingMaxSize::Bytes(16) {
-        // use MD5 hash to get the hash. The MD5 hash is always exactly
-        // 16 bytes, so just deref it to [u8;16]
-        *md5::compute(&cdr_bytes)
-      } else {
-        cdr_bytes.resize(16, 0x00); // pad with zeros to get 16 bytes
-        <[u8; 16]>::try_from(cdr_bytes).unwrap() // this succeeds, because of
-                                                 // the resize above
-      },
-    )
+    KeyHash::from_key_cdr_bytes(&cdr_bytes, Self::cdr_encoding_max_size())
   }
 }
 
@@ -2,11 +2,13 @@
 //! [EntityId](../data_types/struct.EntityId.html)
 
 pub(crate) mod dds_entity;
+pub(crate) mod dds_type;
 pub(crate) mod key;
 pub mod serde_adapters;
 
 pub use dds_entity::DDSEntity;
-pub use key::{Key, Keyed};
+pub use dds_type::DdsType;
+pub use key::{InstanceHandle, Key, Keyed};
 
 pub use crate::structure::entity::RTPSEntity;
 pub use super::topic::TopicDescription;
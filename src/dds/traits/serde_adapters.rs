@@ -38,6 +38,14 @@ pub mod no_key {
       }
       Self::from_bytes(&total_payload, encoding)
     }
+
+    /// Deserializes from a received message buffer. The default
+    /// implementation calls from_bytes(). Adapters that keep a reference to
+    /// the buffer instead of copying from it, like
+    /// [`LoanAdapter`](crate::LoanAdapter), override this.
+    fn from_payload(payload: &Bytes, encoding: RepresentationIdentifier) -> Result<D> {
+      Self::from_bytes(payload, encoding)
+    }
   }
 
   /// trait for connecting Serde Serializer implementation and DataWriter
@@ -49,6 +57,13 @@ pub mod no_key {
     // what encoding do we produce?
     fn output_encoding() -> RepresentationIdentifier;
 
+    /// Encoding of the bytes produced from `value`. Adapters that pass through
+    /// already serialized samples override this, because their encoding varies
+    /// per sample.
+    fn output_encoding_of(_value: &D) -> RepresentationIdentifier {
+      Self::output_encoding()
+    }
+
     fn to_bytes(value: &D) -> Result<Bytes>;
   }
 }
@@ -0,0 +1,769 @@
+use std::{
+  any::Any,
+  collections::BTreeMap,
+  fmt::Debug,
+  marker::PhantomData,
+  sync::{Arc, RwLock},
+};
+
+use byteorder::BigEndian;
+use bytes::Bytes;
+use serde::{de::DeserializeOwned, ser::SerializeTuple, Serialize, Serializer};
+
+use crate::{
+  dds::{
+    dynamic_data::DynamicType,
+    topic::{validate_type_name, Topic, TopicDescription, TopicKind},
+    traits::{
+      key::{Key, KeyHash, Keyed},
+      serde_adapters::{no_key, with_key},
+    },
+    values::result::{Error, Result},
+  },
+  messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
+  serialization::{
+    cdr_projection::{CdrProjection, CdrValue},
+    cdr_serializer::to_bytes,
+    CDRDeserializerAdapter, CDRSerializerAdapter,
+  },
+};
+
+/// (De)serialization of a registered type, for tools that handle samples
+/// without knowing their type at compile time.
+///
+/// Decoded samples are of the registered Rust type, boxed as [`Any`].
+pub trait TypeCodec: Send + Sync {
+  /// Deserializes a sample with the deserializer adapter of the type.
+  fn decode(&self, payload: &[u8], encoding: RepresentationIdentifier) -> Result<Box<dyn Any>>;
+
+  /// Serializes a sample with the serializer adapter of the type. Fails with
+  /// [`BadParameter`](Error::BadParameter) if `sample` is not of the
+  /// registered type.
+  fn encode(&self, sample: &dyn Any) -> Result<(RepresentationIdentifier, Bytes)>;
+
+  /// The instance key hash of a serialized sample, or `None` if the type has
+  /// no key.
+  fn key_hash(&self, payload: &[u8], encoding: RepresentationIdentifier)
+    -> Result<Option<KeyHash>>;
+
+  /// Deserializes a sample and renders it as JSON.
+  #[cfg(feature = "json")]
+  fn decode_json(
+    &self,
+    payload: &[u8],
+    encoding: RepresentationIdentifier,
+  ) -> Result<serde_json::Value>;
+
+  /// Builds a sample from JSON and serializes it.
+  #[cfg(feature = "json")]
+  fn encode_json(&self, json: serde_json::Value) -> Result<(RepresentationIdentifier, Bytes)>;
+}
+
+/// A type in a [`TypeRegistry`].
+#[derive(Clone)]
+pub struct RegisteredType {
+  type_name: String,
+  topic_kind: TopicKind,
+  rust_type_name: &'static str,
+  output_encoding: RepresentationIdentifier,
+  supported_encodings: &'static [RepresentationIdentifier],
+  codec: Arc<dyn TypeCodec>,
+  // Set with TypeRegistry::set_dynamic_type
+  dynamic_type: Option<Arc<DynamicType>>,
+  key_projection: Option<CdrProjection>,
+}
+
+impl RegisteredType {
+  /// The DDS type name, under which this was registered.
+  pub fn type_name(&self) -> &str {
+    &self.type_name
+  }
+
+  pub fn topic_kind(&self) -> TopicKind {
+    self.topic_kind
+  }
+
+  /// Name of the Rust type, as given by [`std::any::type_name`].
+  pub fn rust_type_name(&self) -> &'static str {
+    self.rust_type_name
+  }
+
+  /// The representation written by the serializer adapter.
+  pub fn output_encoding(&self) -> RepresentationIdentifier {
+    self.output_encoding
+  }
+
+  /// The representations read by the deserializer adapter.
+  pub fn supported_encodings(&self) -> &'static [RepresentationIdentifier] {
+    self.supported_encodings
+  }
+
+  pub fn codec(&self) -> &dyn TypeCodec {
+    self.codec.as_ref()
+  }
+
+  /// The DynamicType set with
+  /// [`TypeRegistry::set_dynamic_type`].
+  pub fn dynamic_type(&self) -> Option<&Arc<DynamicType>> {
+    self.dynamic_type.as_ref()
+  }
+
+  /// The instance key hash of a serialized sample, or `None` if the type has
+  /// no key.
+  ///
+  /// If key members were given to [`TypeRegistry::set_dynamic_type`], only
+  /// they are decoded from the sample. Otherwise this is
+  /// [`TypeCodec::key_hash`], which deserializes the whole sample.
+  pub fn key_hash(
+    &self,
+    payload: &[u8],
+    encoding: RepresentationIdentifier,
+  ) -> Result<Option<KeyHash>> {
+    let projection = match &self.key_projection {
+      Some(projection) => projection,
+      None => return self.codec.key_hash(payload, encoding),
+    };
+    let key = projection.project(payload, encoding)?;
+    let cdr_bytes = to_bytes::<KeyMembers, BigEndian>(&KeyMembers(&key))?;
+    Ok(Some(KeyHash::from_key_cdr_bytes(
+      &cdr_bytes,
+      projection.max_size(),
+    )))
+  }
+}
+
+// Key members serialized in sequence, like the members of a key struct.
+struct KeyMembers<'a>(&'a [CdrValue]);
+
+impl Serialize for KeyMembers<'_> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    let mut tuple = serializer.serialize_tuple(self.0.len())?;
+    for member in self.0 {
+      tuple.serialize_element(member)?;
+    }
+    tuple.end()
+  }
+}
+
+impl Debug for RegisteredType {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("RegisteredType")
+      .field("type_name", &self.type_name)
+      .field("topic_kind", &self.topic_kind)
+      .field("rust_type_name", &self.rust_type_name)
+      .field("output_encoding", &self.output_encoding)
+      .field("supported_encodings", &self.supported_encodings)
+      .field("dynamic_type", &self.dynamic_type)
+      .finish()
+  }
+}
+
+/// Maps DDS type names to Rust types and their (de)serializer adapters.
+///
+/// Each [`DomainParticipant`](crate::DomainParticipant) has one, see
+/// [`DomainParticipant::type_registry`](crate::DomainParticipant::type_registry).
+/// When a DataWriter or DataReader is created on a Topic whose type name is
+/// registered, its Rust type must be the registered one, its Topic kind must
+/// match, and it must be able to exchange data with the registered adapters.
+/// Otherwise the creation fails with
+/// [`PreconditionNotMet`](Error::PreconditionNotMet). Topics of unregistered
+/// types are not checked.
+///
+/// Tools can also look up the [`TypeCodec`] of a type name, to handle samples
+/// of types they do not know at compile time.
+///
+/// # Example
+///
+/// ```
+/// use rustdds::*;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Shape {
+///   color: String,
+///   x: i32,
+/// }
+/// impl Keyed for Shape {
+///   type K = String;
+///   fn key(&self) -> String {
+///     self.color.clone()
+///   }
+/// }
+///
+/// let participant = DomainParticipant::new(0).unwrap();
+/// let registry = participant.type_registry();
+/// registry.register_cdr::<Shape>("ShapeType").unwrap();
+/// assert!(registry.get("ShapeType").is_some());
+///
+/// let qos = QosPolicyBuilder::new().build();
+/// let topic = participant
+///   .create_topic("Square".to_string(), "ShapeType".to_string(), &qos, TopicKind::WithKey)
+///   .unwrap();
+/// let publisher = participant.create_publisher(&qos).unwrap();
+/// assert!(publisher.create_datawriter_cdr::<Shape>(&topic, None).is_ok());
+/// // Not the registered type
+/// assert!(publisher.create_datawriter_no_key_cdr::<i32>(&topic, None).is_err());
+/// ```
+#[derive(Clone, Default)]
+pub struct TypeRegistry {
+  types: Arc<RwLock<BTreeMap<String, RegisteredType>>>,
+}
+
+impl TypeRegistry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Registers a keyed type. Registering the same Rust type again under the
+  /// same name replaces the adapters. Fails with
+  /// [`PreconditionNotMet`](Error::PreconditionNotMet) if the name is
+  /// registered for another Rust type.
+  pub fn register<D, SA, DA>(&self, type_name: &str) -> Result<()>
+  where
+    D: Keyed + Serialize + DeserializeOwned + 'static,
+    <D as Keyed>::K: Key,
+    SA: with_key::SerializerAdapter<D> + 'static,
+    DA: with_key::DeserializerAdapter<D> + 'static,
+  {
+    self.insert(RegisteredType {
+      type_name: type_name.to_string(),
+      topic_kind: TopicKind::WithKey,
+      rust_type_name: std::any::type_name::<D>(),
+      output_encoding: SA::output_encoding(),
+      supported_encodings: DA::supported_encodings(),
+      codec: Arc::new(KeyedCodec::<D, SA, DA>(PhantomData)),
+      dynamic_type: None,
+      key_projection: None,
+    })
+  }
+
+  /// Registers a keyed type with the CDR adapters, which the `_cdr` variants
+  /// of DataWriter and DataReader creation use.
+  pub fn register_cdr<D>(&self, type_name: &str) -> Result<()>
+  where
+    D: Keyed + Serialize + DeserializeOwned + 'static,
+    <D as Keyed>::K: Key,
+  {
+    self.register::<D, CDRSerializerAdapter<D>, CDRDeserializerAdapter<D>>(type_name)
+  }
+
+  /// Registers a NoKey type, see [`register`](Self::register).
+  pub fn register_no_key<D, SA, DA>(&self, type_name: &str) -> Result<()>
+  where
+    D: Serialize + DeserializeOwned + 'static,
+    SA: no_key::SerializerAdapter<D> + 'static,
+    DA: no_key::DeserializerAdapter<D> + 'static,
+  {
+    self.insert(RegisteredType {
+      type_name: type_name.to_string(),
+      topic_kind: TopicKind::NoKey,
+      rust_type_name: std::any::type_name::<D>(),
+      output_encoding: SA::output_encoding(),
+      supported_encodings: DA::supported_encodings(),
+      codec: Arc::new(NoKeyCodec::<D, SA, DA>(PhantomData)),
+      dynamic_type: None,
+      key_projection: None,
+    })
+  }
+
+  /// Registers a NoKey type with the CDR adapters.
+  pub fn register_no_key_cdr<D>(&self, type_name: &str) -> Result<()>
+  where
+    D: Serialize + DeserializeOwned + 'static,
+  {
+    self.register_no_key::<D, CDRSerializerAdapter<D>, CDRDeserializerAdapter<D>>(type_name)
+  }
+
+  /// Sets the [`DynamicType`] of a registered type. Then samples of the type
+  /// can be examined without deserializing them:
+  ///
+  /// * Filters of ContentFilteredTopics are evaluated on serialized samples,
+  ///   decoding only the members the filter expression refers to, both in
+  ///   DataReaders of the ContentFilteredTopic and in DataWriters filtering
+  ///   for remote DataReaders. This applies to DataReaders and DataWriters
+  ///   created after this call.
+  /// * [`RegisteredType::key_hash`] decodes only the `key_members`, which are
+  ///   member paths as in [`CdrProjection`](crate::serialization::CdrProjection),
+  ///   in the order in which the key type serializes them. If none are given,
+  ///   the whole sample is deserialized as before.
+  ///
+  /// The DynamicType must describe the CDR encoding of the registered Rust
+  /// type. Registering the type again clears it.
+  ///
+  /// Fails with [`PreconditionNotMet`](Error::PreconditionNotMet) if
+  /// `type_name` is not registered, and with
+  /// [`BadParameter`](Error::BadParameter) if the type is NoKey and key
+  /// members are given, or if a key member is not a primitive, string or enum
+  /// member of `dynamic_type`.
+  pub fn set_dynamic_type(
+    &self,
+    type_name: &str,
+    dynamic_type: DynamicType,
+    key_members: &[&str],
+  ) -> Result<()> {
+    let mut types = self.types.write()?;
+    let registered = match types.get_mut(type_name) {
+      Some(registered) => registered,
+      None => {
+        return Error::precondition_not_met(format!("Type {:?} is not registered", type_name))
+      }
+    };
+    let key_projection = match (registered.topic_kind, key_members.is_empty()) {
+      (_, true) => None,
+      (TopicKind::NoKey, false) => {
+        return Error::bad_parameter(format!("Type {:?} has no key", type_name))
+      }
+      (TopicKind::WithKey, false) => Some(
+        CdrProjection::new(&dynamic_type, key_members)
+          .or_else(|e| Error::bad_parameter(format!("Bad key members: {}", e)))?,
+      ),
+    };
+    registered.dynamic_type = Some(Arc::new(dynamic_type));
+    registered.key_projection = key_projection;
+    Ok(())
+  }
+
+  /// Removes a registration. Returns `false` if the name was not registered.
+  pub fn unregister(&self, type_name: &str) -> bool {
+    self.types.write().unwrap().remove(type_name).is_some()
+  }
+
+  pub fn get(&self, type_name: &str) -> Option<RegisteredType> {
+    self.types.read().unwrap().get(type_name).cloned()
+  }
+
+  pub fn type_names(&self) -> Vec<String> {
+    self.types.read().unwrap().keys().cloned().collect()
+  }
+
+  fn insert(&self, registered: RegisteredType) -> Result<()> {
+    validate_type_name(&registered.type_name)?;
+    let mut types = self.types.write()?;
+    if let Some(existing) = types.get(&registered.type_name) {
+      if existing.rust_type_name != registered.rust_type_name {
+        return Error::precondition_not_met(format!(
+          "Type {:?} is already registered as {}",
+          registered.type_name, existing.rust_type_name
+        ));
+      }
+    }
+    types.insert(registered.type_name.clone(), registered);
+    Ok(())
+  }
+
+  // Checks a DataWriter of Rust type D, which writes `output_encoding`, on
+  // `topic`.
+  pub(crate) fn check_writer<D: ?Sized>(
+    &self,
+    topic: &Topic,
+    topic_kind: TopicKind,
+    output_encoding: RepresentationIdentifier,
+  ) -> Result<()> {
+    self.check::<D>(topic, topic_kind, |registered| {
+      if registered.supported_encodings.contains(&output_encoding) {
+        Ok(())
+      } else {
+        Err(format!(
+          "its deserializer does not read {:?}",
+          output_encoding
+        ))
+      }
+    })
+  }
+
+  // Checks a DataReader of Rust type D, which reads `supported_encodings`, on
+  // `topic`.
+  pub(crate) fn check_reader<D: ?Sized>(
+    &self,
+    topic: &Topic,
+    topic_kind: TopicKind,
+    supported_encodings: &[RepresentationIdentifier],
+  ) -> Result<()> {
+    self.check::<D>(topic, topic_kind, |registered| {
+      if supported_encodings.contains(&registered.output_encoding) {
+        Ok(())
+      } else {
+        Err(format!(
+          "its serializer writes {:?}, which the DataReader does not read",
+          registered.output_encoding
+        ))
+      }
+    })
+  }
+
+  fn check<D: ?Sized>(
+    &self,
+    topic: &Topic,
+    topic_kind: TopicKind,
+    check_encoding: impl FnOnce(&RegisteredType) -> std::result::Result<(), String>,
+  ) -> Result<()> {
+    let registered = match self.get(topic.get_type().name()) {
+      Some(registered) => registered,
+      None => return Ok(()),
+    };
+    let mismatch = if registered.rust_type_name != std::any::type_name::<D>() {
+      Err(format!("it is {}", registered.rust_type_name))
+    } else if registered.topic_kind != topic_kind {
+      Err(format!("it is {:?}", registered.topic_kind))
+    } else {
+      check_encoding(&registered)
+    };
+    mismatch.or_else(|reason| {
+      Error::precondition_not_met(format!(
+        "Topic {:?} has registered type {:?}, but {}",
+        topic.name(),
+        registered.type_name,
+        reason
+      ))
+    })
+  }
+}
+
+// PhantomData of a function pointer, so that the codecs are Send and Sync
+// regardless of the type parameters.
+type CodecMarker<D, SA, DA> = PhantomData<fn() -> (D, SA, DA)>;
+
+struct KeyedCodec<D, SA, DA>(CodecMarker<D, SA, DA>);
+
+impl<D, SA, DA> TypeCodec for KeyedCodec<D, SA, DA>
+where
+  D: Keyed + Serialize + DeserializeOwned + 'static,
+  <D as Keyed>::K: Key,
+  SA: with_key::SerializerAdapter<D>,
+  DA: with_key::DeserializerAdapter<D>,
+{
+  fn decode(&self, payload: &[u8], encoding: RepresentationIdentifier) -> Result<Box<dyn Any>> {
+    Ok(Box::new(DA::from_bytes(payload, encoding)?))
+  }
+
+  fn encode(&self, sample: &dyn Any) -> Result<(RepresentationIdentifier, Bytes)> {
+    encode::<D, SA>(sample)
+  }
+
+  fn key_hash(
+    &self,
+    payload: &[u8],
+    encoding: RepresentationIdentifier,
+  ) -> Result<Option<KeyHash>> {
+    Ok(Some(DA::from_bytes(payload, encoding)?.key().hash_key()))
+  }
+
+  #[cfg(feature = "json")]
+  fn decode_json(
+    &self,
+    payload: &[u8],
+    encoding: RepresentationIdentifier,
+  ) -> Result<serde_json::Value> {
+    to_json(&DA::from_bytes(payload, encoding)?)
+  }
+
+  #[cfg(feature = "json")]
+  fn encode_json(&self, json: serde_json::Value) -> Result<(RepresentationIdentifier, Bytes)> {
+    from_json::<D, SA>(json)
+  }
+}
+
+struct NoKeyCodec<D, SA, DA>(CodecMarker<D, SA, DA>);
+
+impl<D, SA, DA> TypeCodec for NoKeyCodec<D, SA, DA>
+where
+  D: Serialize + DeserializeOwned + 'static,
+  SA: no_key::SerializerAdapter<D>,
+  DA: no_key::DeserializerAdapter<D>,
+{
+  fn decode(&self, payload: &[u8], encoding: RepresentationIdentifier) -> Result<Box<dyn Any>> {
+    Ok(Box::new(DA::from_bytes(payload, encoding)?))
+  }
+
+  fn encode(&self, sample: &dyn Any) -> Result<(RepresentationIdentifier, Bytes)> {
+    encode::<D, SA>(sample)
+  }
+
+  fn key_hash(
+    &self,
+    _payload: &[u8],
+    _encoding: RepresentationIdentifier,
+  ) -> Result<Option<KeyHash>> {
+    Ok(None)
+  }
+
+  #[cfg(feature = "json")]
+  fn decode_json(
+    &self,
+    payload: &[u8],
+    encoding: RepresentationIdentifier,
+  ) -> Result<serde_json::Value> {
+    to_json(&DA::from_bytes(payload, encoding)?)
+  }
+
+  #[cfg(feature = "json")]
+  fn encode_json(&self, json: serde_json::Value) -> Result<(RepresentationIdentifier, Bytes)> {
+    from_json::<D, SA>(json)
+  }
+}
+
+fn encode<D, SA>(sample: &dyn Any) -> Result<(RepresentationIdentifier, Bytes)>
+where
+  D: 'static,
+  SA: no_key::SerializerAdapter<D>,
+  D: Serialize,
+{
+  match sample.downcast_ref::<D>() {
+    Some(sample) => Ok((SA::output_encoding_of(sample), SA::to_bytes(sample)?)),
+    None => Error::bad_parameter(format!("Sample is not a {}", std::any::type_name::<D>())),
+  }
+}
+
+#[cfg(feature = "json")]
+fn to_json<D: Serialize>(sample: &D) -> Result<serde_json::Value> {
+  serde_json::to_value(sample).map_err(|e| Error::Serialization {
+    reason: e.to_string(),
+  })
+}
+
+#[cfg(feature = "json")]
+fn from_json<D, SA>(json: serde_json::Value) -> Result<(RepresentationIdentifier, Bytes)>
+where
+  D: Serialize + DeserializeOwned,
+  SA: no_key::SerializerAdapter<D>,
+{
+  let sample: D = serde_json::from_value(json).map_err(|e| Error::Serialization {
+    reason: e.to_string(),
+  })?;
+  Ok((SA::output_encoding_of(&sample), SA::to_bytes(&sample)?))
+}
+
+#[cfg(test)]
+mod tests {
+  use cdr_encoding_size::CdrEncodingSize;
+  use serde::Deserialize;
+
+  use super::*;
+  use crate::{
+    dds::{dynamic_data::DynamicMember, participant::DomainParticipant, qos::QosPolicies},
+    serialization::{Xcdr2DeserializerAdapter, Xcdr2SerializerAdapter},
+    test::random_data::RandomData,
+  };
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct Reading {
+    sensor: u32,
+    value: f64,
+  }
+
+  impl Keyed for Reading {
+    type K = u32;
+    fn key(&self) -> u32 {
+      self.sensor
+    }
+  }
+
+  #[test]
+  fn registry_rejects_another_rust_type() {
+    let registry = TypeRegistry::new();
+    registry.register_cdr::<Reading>("Reading").unwrap();
+    // Registering again is fine.
+    registry.register_cdr::<Reading>("Reading").unwrap();
+    assert!(matches!(
+      registry.register_no_key_cdr::<RandomData>("Reading"),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+    assert!(registry.register_cdr::<Reading>("").is_err());
+
+    registry
+      .register_no_key_cdr::<RandomData>("RandomData")
+      .unwrap();
+    assert_eq!(registry.type_names(), ["RandomData", "Reading"]);
+    let registered = registry.get("Reading").unwrap();
+    assert_eq!(registered.topic_kind(), TopicKind::WithKey);
+    assert!(registered.rust_type_name().ends_with("Reading"));
+
+    assert!(registry.unregister("Reading"));
+    assert!(!registry.unregister("Reading"));
+    assert!(registry.get("Reading").is_none());
+  }
+
+  #[test]
+  fn registry_codec() {
+    let registry = TypeRegistry::new();
+    registry.register_cdr::<Reading>("Reading").unwrap();
+    let codec = registry.get("Reading").unwrap().codec;
+
+    let reading = Reading {
+      sensor: 7,
+      value: 1.5,
+    };
+    let (encoding, bytes) = codec.encode(&reading).unwrap();
+    assert_eq!(encoding, RepresentationIdentifier::CDR_LE);
+    let decoded = codec.decode(&bytes, encoding).unwrap();
+    assert_eq!(decoded.downcast_ref::<Reading>(), Some(&reading));
+    assert_eq!(
+      codec.key_hash(&bytes, encoding).unwrap(),
+      Some(7u32.hash_key())
+    );
+    assert!(matches!(
+      codec.encode(&"not a Reading"),
+      Err(Error::BadParameter { .. })
+    ));
+
+    #[cfg(feature = "json")]
+    {
+      let json = codec.decode_json(&bytes, encoding).unwrap();
+      assert_eq!(json, serde_json::json!({"sensor": 7, "value": 1.5}));
+      assert_eq!(codec.encode_json(json).unwrap(), (encoding, bytes));
+    }
+  }
+
+  #[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, CdrEncodingSize,
+  )]
+  struct LabelKey {
+    label: String,
+    id: u16,
+  }
+
+  impl Key for LabelKey {}
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct Labeled {
+    value: f64,
+    id: u16,
+    history: Vec<f64>,
+    label: String,
+  }
+
+  impl Keyed for Labeled {
+    type K = LabelKey;
+    fn key(&self) -> LabelKey {
+      LabelKey {
+        label: self.label.clone(),
+        id: self.id,
+      }
+    }
+  }
+
+  #[test]
+  fn registry_projected_key_hash() {
+    let registry = TypeRegistry::new();
+    let labeled_type = DynamicType::structure(
+      "Labeled",
+      vec![
+        DynamicMember::new("value", DynamicType::F64),
+        DynamicMember::new("id", DynamicType::U16),
+        DynamicMember::new("history", DynamicType::sequence(DynamicType::F64)),
+        DynamicMember::new("label", DynamicType::String),
+      ],
+    );
+    assert!(matches!(
+      registry.set_dynamic_type("Labeled", labeled_type.clone(), &[]),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+    registry.register_cdr::<Labeled>("Labeled").unwrap();
+    assert!(matches!(
+      registry.set_dynamic_type("Labeled", labeled_type.clone(), &["history"]),
+      Err(Error::BadParameter { .. })
+    ));
+    registry
+      .set_dynamic_type("Labeled", labeled_type.clone(), &["label", "id"])
+      .unwrap();
+    let registered = registry.get("Labeled").unwrap();
+    assert_eq!(registered.dynamic_type().map(|t| &**t), Some(&labeled_type));
+
+    let sample = Labeled {
+      value: 0.5,
+      id: 3,
+      history: vec![1.0, 2.0],
+      label: "a long label, so the key hash is MD5".to_string(),
+    };
+    let (encoding, bytes) = registered.codec().encode(&sample).unwrap();
+    assert_eq!(
+      registered.key_hash(&bytes, encoding).unwrap(),
+      Some(sample.key().hash_key())
+    );
+    assert!(registered.key_hash(&bytes[..20], encoding).is_err());
+
+    // Keys that fit in the key hash are padded, not hashed.
+    registry.register_cdr::<Reading>("Reading").unwrap();
+    let reading_type = DynamicType::structure(
+      "Reading",
+      vec![
+        DynamicMember::new("sensor", DynamicType::U32),
+        DynamicMember::new("value", DynamicType::F64),
+      ],
+    );
+    registry
+      .set_dynamic_type("Reading", reading_type, &["sensor"])
+      .unwrap();
+    let registered = registry.get("Reading").unwrap();
+    let (encoding, bytes) = registered
+      .codec()
+      .encode(&Reading {
+        sensor: 7,
+        value: 1.5,
+      })
+      .unwrap();
+    assert_eq!(
+      registered.key_hash(&bytes, encoding).unwrap(),
+      Some(7u32.hash_key())
+    );
+
+    // NoKey types have no key members.
+    assert!(matches!(
+      registry.set_dynamic_type("RandomData", DynamicType::I32, &[]),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+    registry
+      .register_no_key_cdr::<RandomData>("RandomData")
+      .unwrap();
+    assert!(matches!(
+      registry.set_dynamic_type("RandomData", labeled_type, &["id"]),
+      Err(Error::BadParameter { .. })
+    ));
+  }
+
+  #[test]
+  fn registry_checks_endpoints() {
+    let participant = DomainParticipant::new(0).unwrap();
+    let qos = QosPolicies::qos_none();
+    participant
+      .type_registry()
+      .register::<Reading, Xcdr2SerializerAdapter<Reading>, Xcdr2DeserializerAdapter<Reading>>(
+        "Reading",
+      )
+      .unwrap();
+    let topic = participant
+      .create_topic(
+        "readings".to_string(),
+        "Reading".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let publisher = participant.create_publisher(&qos).unwrap();
+    let subscriber = participant.create_subscriber(&qos).unwrap();
+
+    assert!(publisher
+      .create_datawriter::<Reading, Xcdr2SerializerAdapter<Reading>>(&topic, None)
+      .is_ok());
+    assert!(subscriber
+      .create_datareader::<Reading, Xcdr2DeserializerAdapter<Reading>>(&topic, None)
+      .is_ok());
+
+    // XCDR1 cannot be read by the registered deserializer, and vice versa.
+    assert!(matches!(
+      publisher.create_datawriter_cdr::<Reading>(&topic, None),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+    assert!(matches!(
+      subscriber.create_datareader_cdr::<Reading>(&topic, None),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+    // Wrong kind and type
+    assert!(publisher
+      .create_datawriter_no_key::<Reading, Xcdr2SerializerAdapter<Reading>>(&topic, None)
+      .is_err());
+    assert!(subscriber
+      .create_datareader_cdr::<RandomData>(&topic, None)
+      .is_err());
+  }
+}
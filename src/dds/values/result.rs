@@ -10,10 +10,9 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// * `OK` is not included. It is not an error. Ok/Error should be distinguished
 ///   with the `Result` type.
 /// * `Error` is too unspecific.
-/// * `AlreadyDeleted` We should use Rust type system to avoid these, so no need
-///   for run-time error.
 /// * `Timeout`  This is normal operation and should be encoded as `Option` or
-///   `Result`
+///   `Result`, except when the operation cannot complete, e.g. a blocking
+///   write.
 /// * `NoData`  This should be encoded as `Option<SomeData>`, not an error code.
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -30,6 +29,11 @@ pub enum Error {
   #[error("Out of resources")]
   OutOfResources,
 
+  /// The operation could not complete within its time limit, e.g. the
+  /// `max_blocking_time` of a write.
+  #[error("Timeout")]
+  Timeout,
+
   /// Operation invoked on an Entity that is not yet enabled.
   #[error("Entity not yet enabled")]
   NotEnabled,
@@ -43,6 +47,11 @@ pub enum Error {
   #[error("Inconsistent policies: {reason}")]
   InconsistentPolicy { reason: String },
 
+  /// Operation invoked on an Entity that has been deleted by
+  /// `delete_contained_entities` of its factory.
+  #[error("Entity already deleted")]
+  AlreadyDeleted,
+
   /// A pre-condition for the operation was not met.
   #[error("Precondition not met: {precondition}")]
   PreconditionNotMet { precondition: String },
@@ -62,6 +71,12 @@ pub enum Error {
   #[error("Lock poisoned")]
   LockPoisoned,
 
+  /// The operation cannot complete now without blocking, and it was asked
+  /// not to block, e.g. a `try_write` when the history of the DataWriter is
+  /// full. Does not exist in the DDS spec.
+  #[error("Operation would block")]
+  WouldBlock,
+
   /// Something that should not go wrong went wrong anyway.
   /// This is usually a bug in RustDDS
   #[error("Internal error: {reason}")]
@@ -75,6 +90,11 @@ pub enum Error {
 
   #[error("Discovery error: {reason}")]
   Discovery { reason: String },
+
+  /// DDS Security access control does not permit the operation, e.g.
+  /// publishing a topic that the permissions document does not grant.
+  #[error("Permission denied: {reason}")]
+  PermissionDenied { reason: String },
 }
 
 impl Error {
@@ -89,6 +109,12 @@ impl Error {
       precondition: precondition.into(),
     })
   }
+
+  pub fn permission_denied<T>(reason: impl Into<String>) -> Result<T> {
+    Err(Self::PermissionDenied {
+      reason: reason.into(),
+    })
+  }
 }
 
 #[doc(hidden)]
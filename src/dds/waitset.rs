@@ -0,0 +1,237 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use mio::{Evented, Events, Poll, PollOpt, Ready, Registration, SetReadiness, Token};
+
+use crate::dds::{statusevents::StatusEvented, values::result::Result};
+
+/// Identifies a condition attached to a [`WaitSet`].
+///
+/// [`WaitSet::wait`] returns the ids of the conditions that triggered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConditionId(usize);
+
+/// A condition whose trigger value is set by the application.
+///
+/// This can be used to wake up a thread blocked in [`WaitSet::wait`], e.g.
+/// to tell it to shut down. Share it between threads with an `Arc`.
+///
+/// See DDS Spec 1.4 Section 2.2.2.1.8 GuardCondition Class.
+pub struct GuardCondition {
+  registration: Registration,
+  set_readiness: SetReadiness,
+}
+
+impl GuardCondition {
+  pub fn new() -> Self {
+    let (registration, set_readiness) = Registration::new2();
+    Self {
+      registration,
+      set_readiness,
+    }
+  }
+
+  /// The condition stays triggered until it is set to `false` again.
+  pub fn set_trigger_value(&self, value: bool) -> Result<()> {
+    let readiness = if value {
+      Ready::readable()
+    } else {
+      Ready::empty()
+    };
+    self.set_readiness.set_readiness(readiness)?;
+    Ok(())
+  }
+
+  pub fn get_trigger_value(&self) -> bool {
+    self.set_readiness.readiness().is_readable()
+  }
+}
+
+impl Default for GuardCondition {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Blocks a thread until one of the attached conditions triggers.
+///
+/// This is an alternative to registering entities with a mio `Poll` directly,
+/// which is what the WaitSet does internally. The conditions are
+/// * a [`GuardCondition`], triggered by the application,
+/// * a read condition of a DataReader, which triggers when new samples have
+///   arrived and stays triggered until the DataReader is read or taken from,
+/// * a status condition of an entity, which triggers when the entity has a
+///   status change to be received with `try_recv_status`.
+///
+/// A source can be attached to only one WaitSet or `Poll` at a time.
+///
+/// See DDS Spec 1.4 Section 2.2.2.1.6 WaitSet Class.
+///
+/// # Examples
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// # use std::time::Duration;
+/// # use rustdds::{DomainParticipant, QosPolicyBuilder, TopicKind, WaitSet};
+/// # use rustdds::no_key::DataReader;
+/// # use rustdds::serialization::CDRDeserializerAdapter;
+/// #
+/// let domain_participant = DomainParticipant::new(0).unwrap();
+/// let qos = QosPolicyBuilder::new().build();
+/// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct SomeType { a: i32 }
+///
+/// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::NoKey).unwrap();
+/// let mut data_reader = subscriber.create_datareader_no_key::<SomeType, CDRDeserializerAdapter<_>>(&topic, None).unwrap();
+///
+/// let mut wait_set = WaitSet::new().unwrap();
+/// let data_available = wait_set.attach_read_condition(&data_reader).unwrap();
+///
+/// for triggered in wait_set.wait(Some(Duration::from_millis(10))).unwrap() {
+///   if triggered == data_available {
+///     while let Ok(Some(sample)) = data_reader.take_next_sample() {
+///       // do something
+///     }
+///   }
+/// }
+/// ```
+pub struct WaitSet {
+  poll: Poll,
+  events: Events,
+  attached: BTreeSet<ConditionId>,
+  next_id: usize,
+}
+
+impl WaitSet {
+  pub fn new() -> Result<Self> {
+    Ok(Self {
+      poll: Poll::new()?,
+      events: Events::with_capacity(16),
+      attached: BTreeSet::new(),
+      next_id: 0,
+    })
+  }
+
+  pub fn attach_guard_condition(
+    &mut self,
+    guard_condition: &GuardCondition,
+  ) -> Result<ConditionId> {
+    self.attach(&guard_condition.registration)
+  }
+
+  /// Attaches a DataReader, so that the WaitSet wakes up when new samples
+  /// arrive. Which samples are then read, e.g. using a
+  /// [`ReadCondition`](crate::ReadCondition), is up to the application.
+  pub fn attach_read_condition(&mut self, reader: &impl Evented) -> Result<ConditionId> {
+    self.attach(reader)
+  }
+
+  /// Attaches the status changes of an entity, e.g. a DataReader or
+  /// DataWriter.
+  pub fn attach_status_condition<E>(
+    &mut self,
+    entity: &mut impl StatusEvented<E>,
+  ) -> Result<ConditionId> {
+    self.attach(entity.as_status_evented())
+  }
+
+  fn attach(&mut self, source: &dyn Evented) -> Result<ConditionId> {
+    let id = ConditionId(self.next_id);
+    // Level-triggered, so that a condition stays triggered until handled.
+    self
+      .poll
+      .register(source, Token(id.0), Ready::readable(), PollOpt::level())?;
+    self.next_id += 1;
+    self.attached.insert(id);
+    Ok(id)
+  }
+
+  /// Detaches a condition. It is no longer reported by [`wait`](Self::wait).
+  /// Returns `false` if the condition was not attached.
+  pub fn detach_condition(&mut self, condition: ConditionId) -> bool {
+    // The source stays registered with our Poll, but its events are ignored.
+    self.attached.remove(&condition)
+  }
+
+  pub fn conditions(&self) -> impl Iterator<Item = ConditionId> + '_ {
+    self.attached.iter().copied()
+  }
+
+  /// Blocks until at least one attached condition has triggered, or the
+  /// timeout expires. Returns the triggered conditions, or an empty Vec on
+  /// timeout. With `None` as timeout, waits indefinitely.
+  pub fn wait(&mut self, timeout: Option<Duration>) -> Result<Vec<ConditionId>> {
+    let deadline = timeout.map(|t| std::time::Instant::now() + t);
+    loop {
+      let remaining = deadline.map(|d| d.saturating_duration_since(std::time::Instant::now()));
+      self.poll.poll(&mut self.events, remaining)?;
+      let mut triggered: Vec<ConditionId> = self
+        .events
+        .iter()
+        .map(|event| ConditionId(event.token().0))
+        .filter(|id| self.attached.contains(id))
+        .collect();
+      if !triggered.is_empty() {
+        triggered.sort();
+        triggered.dedup();
+        return Ok(triggered);
+      }
+      // Nothing attached triggered: either timeout, or an event from a
+      // detached condition.
+      if remaining.map_or(false, |r| r == Duration::from_secs(0)) || self.events.is_empty() {
+        return Ok(Vec::new());
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{sync::Arc, thread};
+
+  use super::*;
+
+  #[test]
+  fn waitset_guard_condition() {
+    let mut wait_set = WaitSet::new().unwrap();
+    let guard = Arc::new(GuardCondition::new());
+    let other = GuardCondition::new();
+    let guard_id = wait_set.attach_guard_condition(&guard).unwrap();
+    let other_id = wait_set.attach_guard_condition(&other).unwrap();
+
+    // nothing triggered
+    assert!(wait_set
+      .wait(Some(Duration::from_millis(10)))
+      .unwrap()
+      .is_empty());
+
+    let remote = guard.clone();
+    let handle = thread::spawn(move || remote.set_trigger_value(true).unwrap());
+    assert_eq!(
+      wait_set.wait(Some(Duration::from_secs(5))).unwrap(),
+      vec![guard_id]
+    );
+    handle.join().unwrap();
+
+    // stays triggered until reset
+    assert!(guard.get_trigger_value());
+    assert_eq!(
+      wait_set.wait(Some(Duration::from_millis(10))).unwrap(),
+      vec![guard_id]
+    );
+    guard.set_trigger_value(false).unwrap();
+    other.set_trigger_value(true).unwrap();
+    assert_eq!(
+      wait_set.wait(Some(Duration::from_millis(10))).unwrap(),
+      vec![other_id]
+    );
+
+    // detached conditions are not reported
+    assert!(wait_set.detach_condition(other_id));
+    assert!(wait_set
+      .wait(Some(Duration::from_millis(10)))
+      .unwrap()
+      .is_empty());
+  }
+}
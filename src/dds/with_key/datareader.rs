@@ -1,13 +1,18 @@
 use std::{
   cmp::max,
   collections::BTreeMap,
+  future::Future,
   io,
   marker::PhantomData,
+  pin::Pin,
   sync::{Arc, RwLock},
+  task::{self, Context},
 };
 
 //use itertools::Itertools;
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Serialize};
+use bytes::Bytes;
+use futures_core::Stream;
 use mio_extras::channel as mio_channel;
 #[allow(unused_imports)]
 use log::{debug, error, info, warn};
@@ -17,9 +22,15 @@ use crate::{
   dds::{
     datasample_cache::DataSampleCache,
     ddsdata::DDSData,
-    pubsub::Subscriber,
+    helpers::{EntityLifecycle, WakerSlot},
+    listener::{dispatch_reader_status, DataReaderListener, StatusKind, StatusMask},
+    loan::{LoanAdapter, Loaned, Pod},
+    pubsub::{GroupAccessReader, Subscriber},
     qos::*,
+    query_expression::SampleFilter,
+    querycondition::QueryCondition,
     readcondition::*,
+    sampleinfo::{InstanceState, NotAliveGenerationCounts, SampleInfo, SampleState, ViewState},
     statusevents::*,
     topic::Topic,
     traits::{key::*, serde_adapters::with_key::*, TopicDescription},
@@ -27,10 +38,13 @@ use crate::{
     with_key::datasample::*,
   },
   discovery::{data_types::topic_data::PublicationBuiltinTopicData, discovery::DiscoveryCommand},
-  log_and_err_precondition_not_met,
-  serialization::CDRDeserializerAdapter,
+  log_and_err_internal, log_and_err_precondition_not_met,
+  messages::submessages::submessage_elements::serialized_payload::{
+    RepresentationIdentifier, SerializedPayload,
+  },
+  serialization::{CDRDeserializerAdapter, SerializedSample},
   structure::{
-    cache_change::CacheChange,
+    cache_change::{CacheChange, ChangeKind},
     dds_cache::DDSCache,
     duration::Duration,
     entity::RTPSEntity,
@@ -43,6 +57,14 @@ use crate::{
 /// Simplified type for CDR encoding
 pub type DataReaderCdr<D> = DataReader<D, CDRDeserializerAdapter<D>>;
 
+// Decides whether a received sample is kept
+pub(crate) struct ContentFilter<D> {
+  pub sample: Box<dyn Fn(&D) -> bool + Send>,
+  // The same filter for serialized samples, if the TypeRegistry has the
+  // DynamicType of the Topic. Then rejected samples are not deserialized.
+  pub serialized: Option<SampleFilter>,
+}
+
 /// Parameter for reading [Readers](../struct.With_Key_DataReader.html) data
 /// with key or with next from current key.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -55,6 +77,13 @@ pub enum SelectByKey {
 pub(crate) enum ReaderCommand {
   #[allow(dead_code)] // TODO: Implement this (resetting) feature
   ResetRequestedDeadlineStatus,
+  SetHeartbeatResponseDelay {
+    delay: std::time::Duration,
+    jitter: std::time::Duration,
+  },
+  SetHeartbeatSuppressionDuration {
+    duration: std::time::Duration,
+  },
 }
 /*
 struct CurrentStatusChanges {
@@ -116,29 +145,38 @@ pub struct DataReader<
   D: Keyed + DeserializeOwned,
   DA: DeserializerAdapter<D> = CDRDeserializerAdapter<D>,
 > {
-  #[allow(dead_code)] // TODO: This is currently unused, because we do not implement
-  // any subscriber-wide QoS policies, such as ordered or coherent access.
-  // Remove this attribute when/if such things are implemented.
+  // For subscriber-wide access, see Subscriber::begin_access.
   my_subscriber: Subscriber,
 
   my_topic: Topic,
   qos_policy: QosPolicies,
   my_guid: GUID,
   pub(crate) notification_receiver: mio_channel::Receiver<()>,
+  // For notifying ourselves of samples left over by an access
+  notification_sender: mio_channel::SyncSender<()>,
+  // Woken along with each notification, for async tasks.
+  data_reader_waker: WakerSlot,
 
   dds_cache: Arc<RwLock<DDSCache>>,
 
   datasample_cache: DataSampleCache<D>,
   latest_instant: Timestamp,
   latest_sequence_number: BTreeMap<GUID, SequenceNumber>,
+  // How far we have removed changes whose lifespan expired in DDSCache
+  expired_position: u64,
   deserializer_type: PhantomData<DA>, // This is to provide use for DA
 
   discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
   status_receiver: StatusReceiver<DataReaderStatus>,
 
-  #[allow(dead_code)] // TODO: This is currently unused, because we do not implement
-  // resetting deadline missed status. Remove attribute when it is supported.
   reader_command: mio_channel::SyncSender<ReaderCommand>,
+
+  // Samples that do not pass this are dropped on arrival.
+  // Set for DataReaders of a ContentFilteredTopic.
+  content_filter: Option<ContentFilter<D>>,
+
+  // Shared with the Subscriber, which can enable and delete this DataReader.
+  lifecycle: EntityLifecycle,
 }
 
 impl<D, DA> Drop for DataReader<D, DA>
@@ -147,9 +185,17 @@ where
   DA: DeserializerAdapter<D>,
 {
   fn drop(&mut self) {
+    let was_enabled = self.lifecycle.delete();
+
     // Tell dp_event_loop
     self.my_subscriber.remove_reader(self.my_guid);
 
+    // Discovery does not know a DataReader that was never enabled, and it has
+    // already been told about a DataReader deleted by the Subscriber.
+    if !was_enabled {
+      return;
+    }
+
     // Tell discovery
     match self
       .discovery_command
@@ -181,10 +227,13 @@ where
     qos_policy: QosPolicies,
     // Each notification sent to this channel must be try_recv'd
     notification_receiver: mio_channel::Receiver<()>,
+    notification_sender: mio_channel::SyncSender<()>,
+    data_reader_waker: WakerSlot,
     dds_cache: Arc<RwLock<DDSCache>>,
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
     status_channel_rec: mio_channel::Receiver<DataReaderStatus>,
     reader_command: mio_channel::SyncSender<ReaderCommand>,
+    lifecycle: EntityLifecycle,
   ) -> Result<Self> {
     let dp = match subscriber.participant() {
       Some(dp) => dp,
@@ -199,25 +248,70 @@ where
 
     Ok(Self {
       my_subscriber: subscriber,
-      qos_policy,
+      qos_policy: qos_policy.clone(),
       my_guid,
       notification_receiver,
+      notification_sender,
+      data_reader_waker,
       dds_cache,
-      datasample_cache: DataSampleCache::new(topic.qos()),
+      datasample_cache: DataSampleCache::new(qos_policy),
       // The reader is created before the datareader, hence initializing the
       // latest_instant to now should be fine. There should be no smaller instants
       // added by the reader.
       my_topic: topic,
       latest_instant: Timestamp::now(),
       latest_sequence_number: BTreeMap::new(),
+      expired_position: 0,
       deserializer_type: PhantomData,
       discovery_command,
       status_receiver: StatusReceiver::new(status_channel_rec),
       //current_status: CurrentStatusChanges::new(),
       reader_command,
+      content_filter: None,
+      lifecycle,
     })
   }
 
+  /// Enables this DataReader, if it was created disabled due to the
+  /// [`EntityFactory`](crate::policy::EntityFactory) policy of its Subscriber.
+  /// Only then is the DataReader announced to remote participants, and it
+  /// starts receiving samples. Before that, any QoS policy can be changed with
+  /// `set_qos`, and reading fails with [`NotEnabled`](Error::NotEnabled).
+  ///
+  /// Fails with [`PreconditionNotMet`](Error::PreconditionNotMet) if the
+  /// Subscriber is not enabled.
+  pub fn enable(&self) -> Result<()> {
+    if !self.my_subscriber.is_enabled() {
+      return Error::precondition_not_met("Subscriber is not enabled");
+    }
+    self.lifecycle.enable()
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.lifecycle.is_enabled()
+  }
+
+  pub(crate) fn set_content_filter(&mut self, filter: ContentFilter<D>) {
+    self.content_filter = Some(filter);
+  }
+
+  // Evaluates the content filter on a serialized sample, if that can be done
+  // without deserializing it.
+  fn content_filter_serialized(&self, payload: &SerializedPayload) -> Option<bool> {
+    self
+      .content_filter
+      .as_ref()?
+      .serialized
+      .as_ref()?
+      .matches_serialized(&payload.value, payload.representation_identifier)
+  }
+
+  // Make the next read/take consider also cache changes that were added
+  // before this DataReader was created.
+  pub(crate) fn read_from_start(&mut self) {
+    self.latest_instant = Timestamp::ZERO;
+  }
+
   /// Reads amount of samples found with `max_samples` and `read_condition`
   /// parameters.
   ///
@@ -272,10 +366,10 @@ where
     // Clear notification buffer. This must be done first to avoid race conditions.
     while self.notification_receiver.try_recv().is_ok() {}
 
-    self.fill_local_datasample_cache();
+    self.fill_local_datasample_cache()?;
 
     let mut selected = self.datasample_cache.select_keys_for_access(read_condition);
-    selected.truncate(max_samples);
+    self.limit_selection(&mut selected, max_samples, &read_condition);
 
     let result = self.datasample_cache.read_by_keys(&selected);
 
@@ -336,10 +430,10 @@ where
     // Clear notification buffer. This must be done first to avoid race conditions.
     while self.notification_receiver.try_recv().is_ok() {}
 
-    self.fill_local_datasample_cache();
+    self.fill_local_datasample_cache()?;
     let mut selected = self.datasample_cache.select_keys_for_access(read_condition);
     debug!("take selected count = {}", selected.len());
-    selected.truncate(max_samples);
+    self.limit_selection(&mut selected, max_samples, &read_condition);
 
     let result = self.datasample_cache.take_by_keys(&selected);
     debug!("take taken count = {}", result.len());
@@ -347,6 +441,320 @@ where
     Ok(result)
   }
 
+  /// Takes all available samples, and appends them to `samples`. Returns the
+  /// number of samples taken.
+  ///
+  /// The samples are the same as from
+  /// `take(usize::MAX, ReadCondition::any())`, but this does less work
+  /// per sample, and the caller can reuse the same buffer for each call. This
+  /// suits topics with high sample rates.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::*;
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #   fn key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  /// # let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader_cdr::<SomeType>(&topic, None).unwrap();
+  ///
+  /// let mut samples = Vec::with_capacity(1000);
+  /// // Each time data arrives...
+  /// data_reader.take_all_into(&mut samples).unwrap();
+  /// for sample in samples.drain(..) {
+  ///   // do something
+  /// }
+  /// ```
+  pub fn take_all_into(&mut self, samples: &mut Vec<DataSample<D>>) -> Result<usize> {
+    // Clear notification buffer. This must be done first to avoid race conditions.
+    while self.notification_receiver.try_recv().is_ok() {}
+
+    self.fill_local_datasample_cache()?;
+    let count = self.datasample_cache.take_all_into(samples);
+    debug!("take_all_into taken count = {}", count);
+    Ok(count)
+  }
+
+  /// Takes all samples received since the previous read or take, without
+  /// deserializing them.
+  ///
+  /// Gateways and bridges can forward the serialized data as it is, e.g. with
+  /// a DataWriter of [`RawSample`](crate::RawSample), and deserialize with
+  /// [`deserialize_sample`](Self::deserialize_sample) only the samples they
+  /// need to look into.
+  ///
+  /// Without deserializing a sample, the DataReader cannot tell which
+  /// instance it belongs to. Therefore the [`SampleInfo`] describes the sample
+  /// alone: the instance state is `Alive` for samples with data, and
+  /// `NotAliveDisposed` or `NotAliveNoWriters` for disposes and unregisters,
+  /// the view state is `New`, and the generation counts and ranks are zero.
+  ///
+  /// Samples that an earlier read has already deserialized are not included.
+  /// A DataReader of a ContentFilteredTopic still deserializes samples to
+  /// filter them, unless the DynamicType of the Topic type is set in the
+  /// [`TypeRegistry`](crate::TypeRegistry::set_dynamic_type).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::*;
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #   fn key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  /// # let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader_cdr::<SomeType>(&topic, None).unwrap();
+  ///
+  /// for sample in data_reader.take_serialized().unwrap() {
+  ///   // forward sample.bytes() as they are, or
+  ///   if let Ok(Ok(data)) = data_reader.deserialize_sample(&sample) {
+  ///     // do something
+  ///   }
+  /// }
+  /// ```
+  pub fn take_serialized(&mut self) -> Result<Vec<SerializedSample>> {
+    // Clear notification buffer. This must be done first to avoid race conditions.
+    while self.notification_receiver.try_recv().is_ok() {}
+
+    self.lifecycle.check_enabled()?;
+    let dds_cache_arc = Arc::clone(&self.dds_cache);
+    let dds_cache = dds_cache_arc.read()?;
+    let now = self
+      .my_subscriber
+      .group_access_began()
+      .unwrap_or_else(Timestamp::now);
+
+    // The samples do not enter datasample_cache, but instances there may have
+    // lost their writers.
+    let lost_writers =
+      dds_cache.topic_get_writers_lost_in_range(&self.my_topic.name(), &self.latest_instant, &now);
+    let cache_changes = self.new_cache_changes(&dds_cache, now);
+    for (_lost_instant, writer_guid) in lost_writers {
+      self.datasample_cache.writer_lost(writer_guid);
+    }
+
+    let mut result = Vec::with_capacity(cache_changes.len());
+    for (_instant, cache_change) in cache_changes {
+      let (instance_state, valid_data, encoding, bytes) = match &cache_change.data_value {
+        DDSData::Data {
+          serialized_payload, ..
+        } => {
+          match (
+            self.content_filter_serialized(serialized_payload),
+            &self.content_filter,
+          ) {
+            (Some(true), _) | (None, None) => {}
+            (Some(false), _) => continue, // filtered out
+            (None, Some(filter)) => match self.deserialize_data(
+              serialized_payload.representation_identifier,
+              &serialized_payload.value,
+            ) {
+              Ok(data) if (filter.sample)(&data) => {}
+              Ok(_) => continue, // filtered out
+              Err(e) => {
+                warn!("take_serialized: {}, Topic = {}", e, self.my_topic.name());
+                continue; // cannot be filtered
+              }
+            },
+          }
+          (
+            InstanceState::Alive,
+            true,
+            serialized_payload.representation_identifier,
+            serialized_payload.value.clone(),
+          )
+        }
+        DDSData::DisposeByKey {
+          change_kind, key, ..
+        } => (
+          Self::not_alive_instance_state(*change_kind),
+          false,
+          key.representation_identifier,
+          key.value.clone(),
+        ),
+        DDSData::DisposeByKeyHash { change_kind, .. } => (
+          Self::not_alive_instance_state(*change_kind),
+          false,
+          RepresentationIdentifier::CDR_LE,
+          Bytes::new(),
+        ),
+      };
+      result.push(SerializedSample {
+        sample_info: SampleInfo {
+          sample_state: SampleState::NotRead,
+          view_state: ViewState::New,
+          instance_state,
+          generation_counts: NotAliveGenerationCounts::zero(),
+          valid_data,
+          sample_rank: 0,
+          generation_rank: 0,
+          absolute_generation_rank: 0,
+          write_options: cache_change.write_options.clone(),
+          publication_handle: cache_change.writer_guid,
+          sequence_number: cache_change.sequence_number,
+        },
+        encoding,
+        bytes,
+      });
+    }
+    debug!("take_serialized taken count = {}", result.len());
+    Ok(result)
+  }
+
+  /// Deserializes a sample from [`take_serialized`](Self::take_serialized).
+  ///
+  /// The result is `Ok(data)` for a sample with data, and `Err(key)` for a
+  /// sample without data, as in [`DataSample::value`].
+  pub fn deserialize_sample(
+    &self,
+    sample: &SerializedSample,
+  ) -> Result<std::result::Result<D, D::K>> {
+    if sample.sample_info.valid_data {
+      self
+        .deserialize_data(sample.encoding, &sample.bytes)
+        .map(Ok)
+    } else if sample.bytes.is_empty() {
+      Err(Error::Serialization {
+        reason: "Sample identifies its instance only by key hash".to_string(),
+      })
+    } else {
+      DA::key_from_bytes(&sample.bytes, sample.encoding)
+        .map(Err)
+        .map_err(|e| Error::Serialization {
+          reason: e.to_string(),
+        })
+    }
+  }
+
+  fn deserialize_data(&self, encoding: RepresentationIdentifier, bytes: &Bytes) -> Result<D> {
+    if !DA::supported_encodings().contains(&encoding) {
+      return Err(Error::Serialization {
+        reason: format!("Unknown representation id {:?}", encoding),
+      });
+    }
+    DA::from_payload(bytes, encoding).map_err(|e| Error::Serialization {
+      reason: e.to_string(),
+    })
+  }
+
+  // Without the key, an unregister cannot be checked against the other
+  // writers of the instance, so it is reported as is.
+  fn not_alive_instance_state(change_kind: ChangeKind) -> InstanceState {
+    match change_kind {
+      ChangeKind::NotAliveUnregistered => InstanceState::NotAliveNoWriters,
+      ChangeKind::NotAliveDisposed | ChangeKind::Alive => InstanceState::NotAliveDisposed,
+    }
+  }
+
+  /// Reads samples that match a [`QueryCondition`], i.e. both its
+  /// [`ReadCondition`] and its filter expression.
+  ///
+  /// This corresponds to DDS read_w_condition with a QueryCondition.
+  /// Samples that carry only a key are matched against the key.
+  pub fn read_w_condition(
+    &mut self,
+    max_samples: usize,
+    query_condition: &QueryCondition,
+  ) -> Result<Vec<DataSample<&D>>>
+  where
+    D: Serialize,
+  {
+    // Clear notification buffer. This must be done first to avoid race conditions.
+    while self.notification_receiver.try_recv().is_ok() {}
+
+    self.fill_local_datasample_cache()?;
+
+    let mut selected = self.select_keys_for_query(query_condition);
+    self.limit_selection(
+      &mut selected,
+      max_samples,
+      &query_condition.read_condition(),
+    );
+
+    let result = self.datasample_cache.read_by_keys(&selected);
+
+    Ok(result)
+  }
+
+  /// Like [`read_w_condition`](Self::read_w_condition), but takes the
+  /// samples.
+  pub fn take_w_condition(
+    &mut self,
+    max_samples: usize,
+    query_condition: &QueryCondition,
+  ) -> Result<Vec<DataSample<D>>>
+  where
+    D: Serialize,
+  {
+    // Clear notification buffer. This must be done first to avoid race conditions.
+    while self.notification_receiver.try_recv().is_ok() {}
+
+    self.fill_local_datasample_cache()?;
+
+    let mut selected = self.select_keys_for_query(query_condition);
+    self.limit_selection(
+      &mut selected,
+      max_samples,
+      &query_condition.read_condition(),
+    );
+
+    let result = self.datasample_cache.take_by_keys(&selected);
+
+    Ok(result)
+  }
+
+  // Only max_samples of the selected samples are accessed. If unread samples
+  // are left over, the DataReader notifies itself, so that it stays readable
+  // until they have been accessed.
+  fn limit_selection(
+    &self,
+    selected: &mut Vec<(Timestamp, <D as Keyed>::K)>,
+    max_samples: usize,
+    read_condition: &ReadCondition,
+  ) {
+    if selected.len() > max_samples {
+      selected.truncate(max_samples);
+      if *read_condition.sample_state_mask() == SampleState::NotRead {
+        // If the channel is full, there is a notification already.
+        let _ = self.notification_sender.try_send(());
+      }
+    }
+  }
+
+  fn select_keys_for_query(
+    &self,
+    query_condition: &QueryCondition,
+  ) -> Vec<(Timestamp, <D as Keyed>::K)>
+  where
+    D: Serialize,
+  {
+    self.datasample_cache.select_keys_for_access_filtered(
+      query_condition.read_condition(),
+      |sample| match sample {
+        Ok(d) => query_condition.matches(d),
+        Err(k) => query_condition.matches(k),
+      },
+    )
+  }
+
   /// Reads next unread sample
   ///
   /// # Examples
@@ -431,6 +839,95 @@ where
     Ok(ds.pop())
   }
 
+  // Async interface
+
+  /// Takes the next NOT_READ sample, waiting for one to arrive if there is
+  /// none yet. This is the async version of
+  /// [`take_next_sample`](Self::take_next_sample).
+  ///
+  /// The returned future is woken by the DomainParticipant event loop thread
+  /// when new data arrives, so it works with any async executor.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::*;
+  /// # use rustdds::with_key::DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  /// #
+  /// async fn print_next(reader: &mut DataReader<SomeType, CDRDeserializerAdapter<SomeType>>) {
+  ///   match reader.async_take_next_sample().await {
+  ///     Ok(sample) => println!("got {:?}", sample.value().as_ref().map(|s| s.a)),
+  ///     Err(e) => println!("error {:?}", e),
+  ///   }
+  /// }
+  /// ```
+  pub async fn async_take_next_sample(&mut self) -> Result<DataSample<D>> {
+    WhenAvailable::new(self, |reader| reader.take_next_sample()).await
+  }
+
+  /// Takes samples like [`take`](Self::take), but waits until at least one
+  /// sample matches `read_condition`.
+  pub async fn async_take(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+  ) -> Result<Vec<DataSample<D>>> {
+    WhenAvailable::new(self, move |reader| {
+      reader
+        .take(max_samples, read_condition)
+        .map(|samples| Some(samples).filter(|s| !s.is_empty()))
+    })
+    .await
+  }
+
+  /// Produces a [`Stream`](futures_core::Stream) of NOT_READ samples, which
+  /// are taken from this DataReader as they arrive. The stream does not end.
+  pub fn async_sample_stream(&mut self) -> DataReaderStream<'_, D, DA> {
+    DataReaderStream { reader: self }
+  }
+
+  // Repeats `attempt` until it produces something. In between, waits for new
+  // data to arrive.
+  pub(crate) fn poll_when_available<T>(
+    &mut self,
+    cx: &mut Context<'_>,
+    mut attempt: impl FnMut(&mut Self) -> Result<Option<T>>,
+  ) -> task::Poll<Result<T>> {
+    loop {
+      match attempt(self) {
+        Err(e) => return task::Poll::Ready(Err(e)),
+        Ok(Some(result)) => return task::Poll::Ready(Ok(result)),
+        Ok(None) => {
+          if !self.register_data_waker(cx) {
+            return task::Poll::Pending;
+          }
+        }
+      }
+    }
+  }
+
+  // Makes the task of `cx` wake up when new data arrives. Returns true, if
+  // there was a notification of new data already, so that the caller should
+  // try again before waiting.
+  pub(crate) fn register_data_waker(&mut self, cx: &mut Context<'_>) -> bool {
+    // Register before checking, so that a notification arriving in
+    // between is not missed.
+    self.data_reader_waker.register(cx.waker());
+    self.notification_receiver.try_recv().is_ok()
+  }
+
   // Iterator interface
 
   // Iterator helpers: _bare versions do not fetch or even construct metadata.
@@ -442,10 +939,10 @@ where
     // Clear notification buffer. This must be done first to avoid race conditions.
     while self.notification_receiver.try_recv().is_ok() {}
 
-    self.fill_local_datasample_cache();
+    self.fill_local_datasample_cache()?;
 
     let mut selected = self.datasample_cache.select_keys_for_access(read_condition);
-    selected.truncate(max_samples);
+    self.limit_selection(&mut selected, max_samples, &read_condition);
 
     let result = self.datasample_cache.read_bare_by_keys(&selected);
 
@@ -460,10 +957,10 @@ where
     // Clear notification buffer. This must be done first to avoid race conditions.
     while self.notification_receiver.try_recv().is_ok() {}
 
-    self.fill_local_datasample_cache();
+    self.fill_local_datasample_cache()?;
     let mut selected = self.datasample_cache.select_keys_for_access(read_condition);
     debug!("take bare selected count = {}", selected.len());
-    selected.truncate(max_samples);
+    self.limit_selection(&mut selected, max_samples, &read_condition);
 
     let result = self.datasample_cache.take_bare_by_keys(&selected);
     debug!("take bare taken count = {}", result.len());
@@ -672,13 +1169,10 @@ where
   // Gets all unseen cache_changes from the TopicCache. Deserializes
   // the serialized payload and stores the DataSamples (the actual data and the
   // samplestate) to local container, datasample_cache.
-  fn fill_local_datasample_cache(&mut self) {
-    let is_reliable = matches!(
-      self.qos_policy.reliability(),
-      Some(policy::Reliability::Reliable { .. })
-    );
-
-    let dds_cache = match self.dds_cache.read() {
+  fn fill_local_datasample_cache(&mut self) -> Result<()> {
+    self.lifecycle.check_enabled()?;
+    let dds_cache_arc = Arc::clone(&self.dds_cache);
+    let dds_cache = match dds_cache_arc.read() {
       Ok(rwlock) => rwlock,
       // TODO: Should we panic here? Are we allowed to continue with poisoned DDSCache?
       Err(e) => panic!(
@@ -686,21 +1180,39 @@ where
         e
       ),
     };
+    // During a GROUP access, samples received after it began are not shown.
+    let now = self
+      .my_subscriber
+      .group_access_began()
+      .unwrap_or_else(Timestamp::now);
+
+    // Remote writers may have been lost (e.g. lease expired due to network
+    // partition) in between the cache changes. Process changes up to each such
+    // event before applying it, so that instance states follow the actual order
+    // of events.
+    let lost_writers =
+      dds_cache.topic_get_writers_lost_in_range(&self.my_topic.name(), &self.latest_instant, &now);
+    for (lost_instant, writer_guid) in lost_writers {
+      self.add_cache_changes_until(&dds_cache, lost_instant);
+      self.latest_instant = max(self.latest_instant, lost_instant);
+      // We keep latest_sequence_number of the lost writer. If it is re-discovered,
+      // it keeps its GUID and sequence numbering, and the history it may resend
+      // to us after re-matching is discarded as already seen.
+      self.datasample_cache.writer_lost(writer_guid);
+    }
+    self.add_cache_changes_until(&dds_cache, now);
 
-    let cache_changes = dds_cache.topic_get_changes_in_range(
-      &self.my_topic.name(),
-      &self.latest_instant,
-      &Timestamp::now(),
-    );
-
-    let mut cache_changes_vec: Vec<(Timestamp, &CacheChange)> = cache_changes.collect();
-
-    // We sort by sequence number so that earlier SNs (from the same writer) are
-    // forced to appear earlier. This way we do not lose any CacheChanges even if
-    // they were received out of order.
-    // The next loop will discard any CacheChanges that appear out of sequence.
-    cache_changes_vec.sort_by_key(|(_ts, cc)| cc.sequence_number);
+    // Samples whose lifespan has expired are removed, whether read or not.
+    let (expired, expired_position) =
+      dds_cache.topic_get_expired_since(&self.my_topic.name(), self.expired_position);
+    for instant in expired {
+      self.datasample_cache.remove_expired(instant);
+    }
+    self.expired_position = expired_position;
+    Ok(())
+  }
 
+  fn add_cache_changes_until(&mut self, dds_cache: &DDSCache, end_instant: Timestamp) {
     for (
       instant,
       CacheChange {
@@ -709,161 +1221,240 @@ where
         write_options,
         data_value,
       },
-    ) in cache_changes_vec
+    ) in self.new_cache_changes(dds_cache, end_instant)
     {
-      self.latest_instant = max(self.latest_instant, instant); // update our time pointer
-                                                               // what was the latest
-      let latest_sequence_number_have_already = self.latest_sequence_number.get(writer_guid);
-
-      // Check that the sequence numbers proceed in correct order.
-      // The reliable mode gets stuck if DDSCache is not able to produce
-      // all the SNs withot gaps. (TODO: Ensure that DDSCache satisfies this.)
-      //
-      // If no previous SN is known, then any SN is acceptable, as we may be
-      // joining the data stream at any time.
-      //
-      if (! is_reliable &&
-            // Check that SequenceNumber always goes forward.
-            // Getting the same SN means duplicate packet, which we must drop.
-            latest_sequence_number_have_already
-              .map_or(true,  |latest| sequence_number > latest))
-        || (is_reliable &&
-            // Check that we get all the sequence numbers in order
-            latest_sequence_number_have_already
-              .map_or(true, |latest| *latest + SequenceNumber::from(1) == *sequence_number))
-      {
-        // normal case: sequence_number not seen before
-        // first, update our last-seen-pointer
-        self
-          .latest_sequence_number
-          .insert(*writer_guid, *sequence_number);
-
-        // deserialize into datasample cache
-        match data_value {
-          DDSData::Data { serialized_payload } => {
+      // deserialize into datasample cache
+      match data_value {
+        DDSData::Data {
+          serialized_payload,
+          key_hash,
+          local_sample,
+        } => {
+          // A DataWriter of this process may have shared the sample itself,
+          // so that it need not be deserialized.
+          let local_payload = local_sample.as_ref().and_then(|s| s.get::<D>());
+          let serialized_match = match local_payload {
+            Some(_) => None,
+            None => self.content_filter_serialized(serialized_payload),
+          };
+          if serialized_match == Some(false) {
+            continue; // filtered out without deserializing
+          }
+          let payload = if let Some(payload) = local_payload {
+            payload
+          } else if let Some(recognized_rep_id) = DA::supported_encodings()
+            .iter()
+            .find(|r| **r == serialized_payload.representation_identifier)
+          {
             // what is our data serialization format (representation identifier) ?
-            if let Some(recognized_rep_id) = DA::supported_encodings()
-              .iter()
-              .find(|r| **r == serialized_payload.representation_identifier)
-            {
-              match DA::from_bytes(&serialized_payload.value, *recognized_rep_id) {
-                Ok(payload) => self.datasample_cache.add_sample(
-                  Ok(payload),
-                  *writer_guid,
-                  *sequence_number,
-                  instant,
-                  write_options.clone(),
-                ),
-                Err(e) => {
-                  error!(
-                    "Failed to deserialize bytes: {}, Topic = {}, Type = {:?}",
-                    e,
-                    self.my_topic.name(),
-                    self.my_topic.get_type()
-                  );
-                  info!("Bytes were {:?}", &serialized_payload.value);
-                  continue; // skip this sample
-                }
+            match DA::from_payload(&serialized_payload.value, *recognized_rep_id) {
+              Ok(payload) => payload,
+              Err(e) => {
+                error!(
+                  "Failed to deserialize bytes: {}, Topic = {}, Type = {:?}",
+                  e,
+                  self.my_topic.name(),
+                  self.my_topic.get_type()
+                );
+                info!("Bytes were {:?}", &serialized_payload.value);
+                continue; // skip this sample
               }
-            } else {
-              warn!(
-                "Unknown representation id {:?}.",
-                serialized_payload.representation_identifier
-              );
-              info!("Serialized payload was {:?}", &serialized_payload);
-              continue; // skip this sample, as we cannot decode it
             }
+          } else {
+            warn!(
+              "Unknown representation id {:?}.",
+              serialized_payload.representation_identifier
+            );
+            info!("Serialized payload was {:?}", &serialized_payload);
+            continue; // skip this sample, as we cannot decode it
+          };
+          // Remember the key hash the writer computed, so that later
+          // disposes carrying only that hash find the instance.
+          if let Some(key_hash) = key_hash {
+            self.datasample_cache.add_key_hash(*key_hash, payload.key());
           }
+          match (&self.content_filter, serialized_match) {
+            (Some(filter), None) if !(filter.sample)(&payload) => continue, // filtered out
+            _ => {}
+          }
+          self.datasample_cache.add_sample(
+            Ok(payload),
+            *writer_guid,
+            *sequence_number,
+            instant,
+            write_options.clone(),
+          );
+        }
 
-          DDSData::DisposeByKey {
-            key: serialized_key,
-            ..
-          } => {
-            match DA::key_from_bytes(
-              &serialized_key.value,
-              serialized_key.representation_identifier,
-            ) {
-              Ok(key) => {
-                self.datasample_cache.add_sample(
-                  Err(key),
+        DDSData::DisposeByKey {
+          change_kind,
+          key: serialized_key,
+          key_hash,
+        } => {
+          match DA::key_from_bytes(
+            &serialized_key.value,
+            serialized_key.representation_identifier,
+          ) {
+            Ok(key) => {
+              self.datasample_cache.add_not_alive_sample(
+                key,
+                *change_kind,
+                *writer_guid,
+                *sequence_number,
+                instant,
+                write_options.clone(),
+              );
+            }
+            Err(e) => {
+              // The key could not be decoded, but the key hash identifies
+              // the instance, if we have seen it.
+              if let Some(key) = key_hash.and_then(|h| self.datasample_cache.key_by_hash(h)) {
+                debug!("Failed to deserialize key {}, using key hash instead.", e);
+                self.datasample_cache.add_not_alive_sample(
+                  key,
+                  *change_kind,
                   *writer_guid,
                   *sequence_number,
                   instant,
                   write_options.clone(),
                 );
+                continue;
               }
-              Err(e) => {
-                warn!(
-                  "Failed to deserialize key {}, Topic = {}, Type = {:?}",
-                  e,
-                  self.my_topic.name(),
-                  self.my_topic.get_type()
-                );
-                debug!("Bytes were {:?}", &serialized_key.value);
-                continue; // skip this sample
-              }
+              warn!(
+                "Failed to deserialize key {}, Topic = {}, Type = {:?}",
+                e,
+                self.my_topic.name(),
+                self.my_topic.get_type()
+              );
+              debug!("Bytes were {:?}", &serialized_key.value);
+              continue; // skip this sample
             }
           }
+        }
+
+        DDSData::DisposeByKeyHash {
+          change_kind,
+          key_hash,
+        } => {
+          if let Some(key) = self.datasample_cache.key_by_hash(*key_hash) {
+            self.datasample_cache.add_not_alive_sample(
+              key,
+              *change_kind,
+              *writer_guid,
+              *sequence_number,
+              instant,
+              write_options.clone(),
+            );
+          } else {
+            warn!("Tried to dispose with unkonwn key hash: {:x?}", key_hash);
+            // The cache should know hash -> key mapping even if the sample
+            // has been disposed or .take()n
+          }
+        } /*
+          DDSData::DataFrags { representation_identifier, bytes_frags } => {
+            // what is our data serialization format (representation identifier) ?
+            if let Some(recognized_rep_id) =
+                DA::supported_encodings().iter().find(|r| *r == representation_identifier)
+            {
+              match DA::from_vec_bytes(bytes_frags, *recognized_rep_id) {
+                Ok(payload) => {
+                  self
+                  .datasample_cache
+                  .add_sample(Ok(payload), *writer_guid, instant, None)
+                }
+                Err(e) => {
+                  error!("Failed to deserialize (DATAFRAG) bytes: {}, Topic = {}, Type = {:?}",
+                          e, self.my_topic.name(), self.my_topic.get_type() );
+                  //debug!("Bytes were {:?}",&serialized_payload.value);
+                  continue // skip this sample
+                }
+              }
+            } else {
+                warn!("Unknown representation id {:?}.", representation_identifier);
+                //debug!("Serialized payload was {:?}", &serialized_payload);
+                continue // skip this sample, as we cannot decode it
+            }
+          } */
+      } // match
+    } // for loop
+  } // fn
+
+  // Gets the cache changes received after latest_instant, up to end_instant,
+  // and advances latest_instant past them. Changes whose sequence numbers have
+  // been seen already are dropped.
+  fn new_cache_changes<'a>(
+    &mut self,
+    dds_cache: &'a DDSCache,
+    end_instant: Timestamp,
+  ) -> Vec<(Timestamp, &'a CacheChange)> {
+    let cache_changes = dds_cache.topic_get_changes_in_range(
+      &self.my_topic.name(),
+      &self.latest_instant,
+      &end_instant,
+    );
+
+    let mut cache_changes_vec: Vec<(Timestamp, &CacheChange)> = cache_changes.collect();
+
+    // We sort by sequence number so that earlier SNs (from the same writer) are
+    // forced to appear earlier. This way we do not lose any CacheChanges even if
+    // they were received out of order.
+    // The next loop will discard any CacheChanges that appear out of sequence.
+    cache_changes_vec.sort_by_key(|(_ts, cc)| cc.sequence_number);
 
-          DDSData::DisposeByKeyHash { key_hash, .. } => {
-            if let Some(key) = self.datasample_cache.key_by_hash(*key_hash) {
-              self.datasample_cache.add_sample(
-                Err(key),
-                *writer_guid,
-                *sequence_number,
-                instant,
-                write_options.clone(),
-              );
-            } else {
-              warn!("Tried to dispose with unkonwn key hash: {:x?}", key_hash);
-              // The cache should know hash -> key mapping even if the sample
-              // has been disposed or .take()n
-            }
-          } /*
-            DDSData::DataFrags { representation_identifier, bytes_frags } => {
-              // what is our data serialization format (representation identifier) ?
-              if let Some(recognized_rep_id) =
-                  DA::supported_encodings().iter().find(|r| *r == representation_identifier)
-              {
-                match DA::from_vec_bytes(bytes_frags, *recognized_rep_id) {
-                  Ok(payload) => {
-                    self
-                    .datasample_cache
-                    .add_sample(Ok(payload), *writer_guid, instant, None)
-                  }
-                  Err(e) => {
-                    error!("Failed to deserialize (DATAFRAG) bytes: {}, Topic = {}, Type = {:?}",
-                            e, self.my_topic.name(), self.my_topic.get_type() );
-                    //debug!("Bytes were {:?}",&serialized_payload.value);
-                    continue // skip this sample
-                  }
-                }
-              } else {
-                  warn!("Unknown representation id {:?}.", representation_identifier);
-                  //debug!("Serialized payload was {:?}", &serialized_payload);
-                  continue // skip this sample, as we cannot decode it
-              }
-            } */
-        } // match
-      }
-      // if (acceptable SN)
-      else {
-        // sequence naumber is not acceptable
+    let mut new_changes = Vec::with_capacity(cache_changes_vec.len());
+    for (instant, cache_change) in cache_changes_vec {
+      self.latest_instant = max(self.latest_instant, instant); // update our time pointer
+                                                               // what was the latest
+      let latest_sequence_number_have_already =
+        self.latest_sequence_number.get(&cache_change.writer_guid);
+
+      // Check that SequenceNumber always goes forward.
+      // Getting the same SN means duplicate packet, which we must drop.
+      //
+      // The SNs may skip forward also in reliable mode, because the writer may
+      // announce with GAP that some SNs are not relevant. Those never appear in
+      // the DDSCache, so waiting for them would stall the reader.
+      //
+      // If no previous SN is known, then any SN is acceptable, as we may be
+      // joining the data stream at any time.
+      //
+      if latest_sequence_number_have_already
+        .map_or(true, |latest| cache_change.sequence_number > *latest)
+      {
+        // normal case: sequence_number not seen before
+        // first, update our last-seen-pointer
+        self
+          .latest_sequence_number
+          .insert(cache_change.writer_guid, cache_change.sequence_number);
+        new_changes.push((instant, cache_change));
       }
-    } // for loop
-  } // fn
+      // otherwise the sequence number is not acceptable
+    }
+    new_changes
+  }
 
-  fn infer_key(
+  // Selects the samples of one instance for read_instance and take_instance.
+  fn select_instance_keys(
     &self,
     instance_key: Option<<D as Keyed>::K>,
     this_or_next: SelectByKey,
-  ) -> Option<<D as Keyed>::K> {
-    match instance_key {
-      Some(k) => match this_or_next {
-        SelectByKey::This => Some(k),
-        SelectByKey::Next => self.datasample_cache.next_key(&k),
+    read_condition: ReadCondition,
+  ) -> Vec<(Timestamp, <D as Keyed>::K)> {
+    match (instance_key, this_or_next) {
+      (Some(k), SelectByKey::This) => self
+        .datasample_cache
+        .select_instance_keys_for_access(&k, read_condition),
+      (None, SelectByKey::This) => match self.datasample_cache.instance_map.keys().next() {
+        Some(k) => self
+          .datasample_cache
+          .select_instance_keys_for_access(k, read_condition),
+        None => Vec::new(),
       },
-      None => self.datasample_cache.instance_map.keys().next().cloned(),
+      // DDS spec v1.4 Section 2.2.2.5.3.16 read_next_instance: instances that
+      // have no samples matching the condition are skipped.
+      (k, SelectByKey::Next) => self
+        .datasample_cache
+        .select_next_instance_keys_for_access(k.as_ref(), read_condition),
     }
   }
 
@@ -872,7 +1463,9 @@ where
   /// is not specified, the smallest (in key order) instance is selected.
   /// If a key is specified, then the parameter this_or_next specifies whether
   /// to access the instance with specified key or the following one, in key
-  /// order.
+  /// order. With `SelectByKey::Next`, instances that have no samples matching
+  /// `read_condition` are skipped, and a missing key starts from the
+  /// smallest instance.
   ///
   /// This should cover DDS DataReader methods read_instance,
   /// read_next_instance, read_next_instance_w_condition.
@@ -929,16 +1522,9 @@ where
     // Clear notification buffer. This must be done first to avoid race conditions.
     while self.notification_receiver.try_recv().is_ok() {}
 
-    self.fill_local_datasample_cache();
-
-    let key = match self.infer_key(instance_key, this_or_next) {
-      Some(k) => k,
-      None => return Ok(Vec::new()),
-    };
+    self.fill_local_datasample_cache()?;
 
-    let mut selected = self
-      .datasample_cache
-      .select_instance_keys_for_access(&key, read_condition);
+    let mut selected = self.select_instance_keys(instance_key, this_or_next, read_condition);
     selected.truncate(max_samples);
 
     let result = self.datasample_cache.read_by_keys(&selected);
@@ -1002,16 +1588,9 @@ where
     // Clear notification buffer. This must be done first to avoid race conditions.
     while self.notification_receiver.try_recv().is_ok() {}
 
-    self.fill_local_datasample_cache();
-
-    let key = match self.infer_key(instance_key, this_or_next) {
-      Some(k) => k,
-      None => return Ok(Vec::new()),
-    };
+    self.fill_local_datasample_cache()?;
 
-    let mut selected = self
-      .datasample_cache
-      .select_instance_keys_for_access(&key, read_condition);
+    let mut selected = self.select_instance_keys(instance_key, this_or_next, read_condition);
     selected.truncate(max_samples);
 
     let result = self.datasample_cache.take_by_keys(&selected);
@@ -1207,6 +1786,131 @@ where
     return Ok(value_before_reset);
   } */
 
+  /// Invokes the callbacks of `listener` for the status changes since the
+  /// previous call, and `on_data_available` if new samples have arrived.
+  /// Only the callbacks selected by `mask` are invoked. Status changes not in
+  /// the mask are discarded.
+  ///
+  /// The callbacks run in the calling thread, e.g. after
+  /// [`WaitSet::wait`](crate::WaitSet::wait) has returned. Returns the number
+  /// of callbacks invoked.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::*;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// # use rustdds::dds::statusevents::CountWithChange;
+  /// # use rustdds::with_key::DataReader;
+  /// #
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  /// #
+  /// type Reader = DataReader<SomeType, CDRDeserializerAdapter<SomeType>>;
+  ///
+  /// struct Printer;
+  ///
+  /// impl DataReaderListener<Reader> for Printer {
+  ///   fn on_data_available(&mut self, reader: &mut Reader) {
+  ///     while let Ok(Some(sample)) = reader.take_next_sample() {
+  ///       // do something
+  ///     }
+  ///   }
+  ///
+  ///   fn on_subscription_matched(&mut self, _total: CountWithChange, current: CountWithChange) {
+  ///     println!("{} matched writers", current.count());
+  ///   }
+  /// }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader: Reader = subscriber.create_datareader(&topic, None).unwrap();
+  /// let mask = StatusKind::DataAvailable | StatusKind::SubscriptionMatched;
+  /// data_reader.dispatch_to_listener(&mut Printer, mask);
+  /// ```
+  pub fn dispatch_to_listener<L>(&mut self, listener: &mut L, mask: StatusMask) -> usize
+  where
+    L: DataReaderListener<Self> + ?Sized,
+  {
+    let (statuses, data_available) = self.drain_listener_events(mask);
+    let mut invoked = statuses
+      .into_iter()
+      .map(|status| dispatch_reader_status::<Self, _>(listener, status, mask))
+      .filter(|&invoked| invoked)
+      .count();
+    if data_available {
+      listener.on_data_available(self);
+      invoked += 1;
+    }
+    invoked
+  }
+
+  // Takes the pending status changes, and tells if new samples have arrived.
+  // The data notifications are consumed only if DataAvailable is in the mask,
+  // so that they still wake up a Poll otherwise.
+  pub(crate) fn drain_listener_events(
+    &mut self,
+    mask: StatusMask,
+  ) -> (Vec<DataReaderStatus>, bool) {
+    self.status_receiver.enable();
+    let statuses = std::iter::from_fn(|| self.status_receiver.try_recv_status()).collect();
+    let mut data_available = false;
+    if mask.contains(StatusKind::DataAvailable) {
+      while self.notification_receiver.try_recv().is_ok() {
+        data_available = true;
+      }
+    }
+    (statuses, data_available)
+  }
+
+  /// Delay the responses to HEARTBEATs from reliable DataWriters by `delay`,
+  /// and a random part of `jitter`.
+  ///
+  /// By default, a HEARTBEAT that tells of missing samples, or asks for a
+  /// response, is responded to immediately. With many DataReaders, the
+  /// responses to a HEARTBEAT arrive at the DataWriter all at once, and
+  /// samples that were merely late are requested again. RTPS calls this
+  /// `heartbeatResponseDelay`, with a suggested value of 500 ms.
+  pub fn set_heartbeat_response_delay(&self, delay: Duration, jitter: Duration) -> Result<()> {
+    if delay < Duration::DURATION_ZERO || jitter < Duration::DURATION_ZERO {
+      return Error::bad_parameter("Heartbeat response delay and jitter must not be negative");
+    }
+    self
+      .reader_command
+      .try_send(ReaderCommand::SetHeartbeatResponseDelay {
+        delay: delay.to_std(),
+        jitter: jitter.to_std(),
+      })
+      .or_else(|e| log_and_err_internal!("Cannot send SetHeartbeatResponseDelay: {:?}", e))
+  }
+
+  /// Ignore HEARTBEATs that arrive sooner than `duration` after the previous
+  /// one from the same DataWriter. RTPS calls this
+  /// `heartbeatSuppressionDuration`. The default is zero, i.e. no HEARTBEAT is
+  /// ignored.
+  pub fn set_heartbeat_suppression_duration(&self, duration: Duration) -> Result<()> {
+    if duration < Duration::DURATION_ZERO {
+      return Error::bad_parameter("Heartbeat suppression duration must not be negative");
+    }
+    self
+      .reader_command
+      .try_send(ReaderCommand::SetHeartbeatSuppressionDuration {
+        duration: duration.to_std(),
+      })
+      .or_else(|e| log_and_err_internal!("Cannot send SetHeartbeatSuppressionDuration: {:?}", e))
+  }
+
   /// Return values:
   /// true - got all historical data
   /// false - timeout before all historical data was received
@@ -1223,12 +1927,117 @@ where
   // we got.
 
   pub fn get_matched_publications(&self) -> impl Iterator<Item = PublicationBuiltinTopicData> {
-    vec![].into_iter()
+    let db = self.my_subscriber.discovery_db();
+    let db = db
+      .read()
+      .unwrap_or_else(|e| panic!("DiscoveryDB is poisoned. {:?}", e));
+    db.external_writers_on_topic(&self.my_topic.name())
+      .into_iter()
+      .map(|dwd| dwd.publication_topic_data)
+      .filter(|publ| {
+        publ
+          .qos()
+          .compliance_failure_wrt(&self.qos_policy)
+          .is_none()
+      })
+      .collect::<Vec<_>>()
+      .into_iter()
   }
 } // impl
 
+impl<T> DataReader<Loaned<T>, LoanAdapter<T>>
+where
+  T: Pod + Keyed + DeserializeOwned,
+  <T as Keyed>::K: Key,
+{
+  /// Takes up to `max_samples` not yet read samples, loaned from the
+  /// received message buffers instead of deserialized. See [`Loaned`].
+  pub fn take_loaned(&mut self, max_samples: usize) -> Result<Vec<DataSample<Loaned<T>>>> {
+    self.take(max_samples, ReadCondition::not_read())
+  }
+}
+
 // This is  not part of DDS spec. We implement mio Eventd so that the
 // application can asynchronously poll DataReader(s).
+// Future of async_take_next_sample and async_take
+pub(crate) struct WhenAvailable<'a, D, DA, T, F>
+where
+  D: Keyed + DeserializeOwned,
+  DA: DeserializerAdapter<D>,
+{
+  reader: &'a mut DataReader<D, DA>,
+  attempt: F,
+  result_type: PhantomData<fn() -> T>,
+}
+
+impl<'a, D, DA, T, F> WhenAvailable<'a, D, DA, T, F>
+where
+  D: Keyed + DeserializeOwned,
+  DA: DeserializerAdapter<D>,
+  F: FnMut(&mut DataReader<D, DA>) -> Result<Option<T>>,
+{
+  pub(crate) fn new(reader: &'a mut DataReader<D, DA>, attempt: F) -> Self {
+    Self {
+      reader,
+      attempt,
+      result_type: PhantomData,
+    }
+  }
+}
+
+impl<D, DA, T, F> Future for WhenAvailable<'_, D, DA, T, F>
+where
+  D: Keyed + DeserializeOwned + 'static,
+  <D as Keyed>::K: Key,
+  DA: DeserializerAdapter<D>,
+  F: FnMut(&mut DataReader<D, DA>) -> Result<Option<T>> + Unpin,
+{
+  type Output = Result<T>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<Self::Output> {
+    let this = self.get_mut();
+    this.reader.poll_when_available(cx, &mut this.attempt)
+  }
+}
+
+/// Stream of samples taken from a DataReader, see
+/// [`DataReader::async_sample_stream`].
+pub struct DataReaderStream<'a, D, DA>
+where
+  D: Keyed + DeserializeOwned,
+  DA: DeserializerAdapter<D>,
+{
+  reader: &'a mut DataReader<D, DA>,
+}
+
+impl<D, DA> Stream for DataReaderStream<'_, D, DA>
+where
+  D: Keyed + DeserializeOwned + 'static,
+  <D as Keyed>::K: Key,
+  DA: DeserializerAdapter<D>,
+{
+  type Item = Result<DataSample<D>>;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<Option<Self::Item>> {
+    self
+      .get_mut()
+      .reader
+      .poll_when_available(cx, |reader| reader.take_next_sample())
+      .map(Some)
+  }
+}
+
+/// A DataReader can be registered with a mio `Poll` for `Ready::readable()`.
+/// It becomes readable when new samples arrive, and stays readable until the
+/// application reads or takes samples. If an access with
+/// [`ReadCondition::not_read()`] leaves unread samples over, because of
+/// `max_samples`, the DataReader becomes readable again.
+///
+/// With `PollOpt::level()`, the DataReader is polled ready until it is
+/// accessed. With `PollOpt::edge()`, there is an event when samples arrive
+/// or are left over, so access it until there is nothing more to read or
+/// take. Status changes of the DataReader have a separate source, see
+/// [`StatusEvented`].
 impl<D, DA> Evented for DataReader<D, DA>
 where
   D: Keyed + DeserializeOwned,
@@ -1278,18 +2087,52 @@ where
   D: Keyed + DeserializeOwned,
   DA: DeserializerAdapter<D>,
 {
-  // fn set_qos(&mut self, policy: &QosPolicies) -> Result<()> {
-  //   // TODO: check liveliness of qos_policy
-  //   self.qos_policy = policy.clone();
-  //   self.datasample_cache.set_qos(policy.clone());
-  //   Ok(())
-  // }
-
   fn qos(&self) -> QosPolicies {
     self.qos_policy.clone()
   }
 }
 
+impl<D, DA> MutQosPolicy for DataReader<D, DA>
+where
+  D: Keyed + DeserializeOwned,
+  <D as Keyed>::K: Key,
+  DA: DeserializerAdapter<D>,
+{
+  /// Changes the QoS of this DataReader.
+  ///
+  /// Policies defined in `new_qos` replace the current ones, and the rest are
+  /// kept. Attempting to change a policy that cannot be changed after
+  /// creation, e.g. Reliability, fails with
+  /// [`ImmutablePolicy`](Error::ImmutablePolicy).
+  ///
+  /// The new QoS is announced to remote participants, and matching with
+  /// DataWriters is re-evaluated. A DataReader that is not yet enabled can
+  /// change any policy.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<()> {
+    // Nothing is announced before enabling, so anything can be changed.
+    let unannounced_qos = self.qos_policy.modify_by(new_qos);
+    if self.lifecycle.set_qos_if_disabled(&unannounced_qos) {
+      self
+        .datasample_cache
+        .set_qos_policy(unannounced_qos.clone());
+      self.qos_policy = unannounced_qos;
+      return Ok(());
+    }
+
+    let qos = self.qos_policy.changed_by(new_qos)?;
+    self
+      .discovery_command
+      .send(DiscoveryCommand::UpdateLocalReader {
+        guid: self.my_guid,
+        qos: qos.clone(),
+      })
+      .or_else(|e| log_and_err_internal!("Cannot send QoS update to Discovery: {:?}", e))?;
+    self.datasample_cache.set_qos_policy(qos.clone());
+    self.qos_policy = qos;
+    Ok(())
+  }
+}
+
 impl<D, DA> RTPSEntity for DataReader<D, DA>
 where
   D: Keyed + DeserializeOwned,
@@ -1300,6 +2143,25 @@ where
   }
 }
 
+impl<D, DA> GroupAccessReader for DataReader<D, DA>
+where
+  D: Keyed + DeserializeOwned + 'static,
+  <D as Keyed>::K: Key,
+  DA: DeserializerAdapter<D>,
+{
+  fn not_read_sample_timestamps(&mut self) -> Result<Vec<Timestamp>> {
+    self.fill_local_datasample_cache()?;
+    Ok(
+      self
+        .datasample_cache
+        .select_keys_for_access(ReadCondition::not_read())
+        .into_iter()
+        .map(|(timestamp, _key)| timestamp)
+        .collect(),
+    )
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use std::rc::Rc;
@@ -1315,14 +2177,16 @@ mod tests {
       message_receiver::*,
       participant::DomainParticipant,
       reader::{Reader, ReaderIngredients},
+      sampleinfo::InstanceState,
       topic::TopicKind,
       traits::key::Keyed,
+      with_key::WriteOptions,
     },
     messages::submessages::{
       data::Data,
       submessage_elements::serialized_payload::{RepresentationIdentifier, SerializedPayload},
     },
-    network::udp_sender::UDPSender,
+    network::{transport::Transports, udp_sender::UDPSender},
     serialization::{cdr_deserializer::CDRDeserializerAdapter, cdr_serializer::to_bytes},
     structure::{
       guid::{EntityKind, GuidPrefix},
@@ -1362,6 +2226,7 @@ mod tests {
     let reader_ing = ReaderIngredients {
       guid: reader_guid,
       notification_sender: send,
+      data_reader_waker: WakerSlot::default(),
       status_sender,
       topic_name: topic.name(),
       qos_policy: QosPolicies::qos_none(),
@@ -1371,8 +2236,8 @@ mod tests {
     let mut new_reader = Reader::new(
       reader_ing,
       dp.dds_cache(),
-      Rc::new(UDPSender::new_with_random_port().unwrap()),
-      mio_extras::timer::Builder::default().build(),
+      Rc::new(Transports::from(UDPSender::new_with_random_port().unwrap())),
+      crate::dds::timer::Timer::default(),
     );
 
     let mut matching_datareader = sub
@@ -1423,7 +2288,7 @@ mod tests {
 
     std::thread::sleep(std::time::Duration::from_millis(100));
 
-    matching_datareader.fill_local_datasample_cache();
+    matching_datareader.fill_local_datasample_cache().unwrap();
     let deserialized_random_data = matching_datareader.read(1, ReadCondition::any()).unwrap()[0]
       .value()
       .unwrap()
@@ -1467,13 +2332,220 @@ mod tests {
     new_reader.handle_data_msg(data2, data_flags, &mr_state);
     new_reader.handle_data_msg(data3, data_flags, &mr_state);
 
-    matching_datareader.fill_local_datasample_cache();
+    matching_datareader.fill_local_datasample_cache().unwrap();
     let random_data_vec = matching_datareader
       .read_instance(100, ReadCondition::any(), Some(data_key), SelectByKey::This)
       .unwrap();
     assert_eq!(random_data_vec.len(), 3);
   }
 
+  #[test]
+  fn dr_take_serialized() {
+    let dp = DomainParticipant::new(0).expect("Participant creation failed");
+    let qos = QosPolicies::qos_none();
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr_serialized".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let mut reader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+
+    let writer_guid = GUID {
+      prefix: GuidPrefix::new(&[1; 12]),
+      entity_id: EntityId::create_custom_entity_id(
+        [1; 3],
+        EntityKind::WRITER_WITH_KEY_USER_DEFINED,
+      ),
+    };
+    let random_data = RandomData {
+      a: 1,
+      b: "serialized".to_string(),
+    };
+    let data_bytes = Bytes::from(to_bytes::<RandomData, LittleEndian>(&random_data).unwrap());
+    let key_bytes = Bytes::from(to_bytes::<i64, LittleEndian>(&random_data.a).unwrap());
+
+    let dds_cache = dp.dds_cache();
+    let add = |sn: i64, data_value: DDSData| {
+      dds_cache.write().unwrap().add_change(
+        &topic.name(),
+        &Timestamp::now(),
+        CacheChange::new(
+          writer_guid,
+          SequenceNumber::from(sn),
+          WriteOptions::from(None),
+          data_value,
+        ),
+      );
+    };
+    add(
+      1,
+      DDSData::new(SerializedPayload::new_from_bytes(
+        RepresentationIdentifier::CDR_LE,
+        data_bytes.clone(),
+      )),
+    );
+    add(
+      2,
+      DDSData::DisposeByKey {
+        change_kind: ChangeKind::NotAliveDisposed,
+        key: SerializedPayload::new_from_bytes(RepresentationIdentifier::CDR_LE, key_bytes.clone()),
+        key_hash: None,
+      },
+    );
+
+    let samples = reader.take_serialized().unwrap();
+    assert_eq!(samples.len(), 2);
+
+    let data_sample = &samples[0];
+    assert_eq!(data_sample.bytes(), &data_bytes);
+    assert_eq!(data_sample.encoding(), RepresentationIdentifier::CDR_LE);
+    let info = data_sample.sample_info();
+    assert!(info.valid_data());
+    assert_eq!(info.instance_state(), InstanceState::Alive);
+    assert_eq!(info.writer_guid(), writer_guid);
+    assert_eq!(
+      reader.deserialize_sample(data_sample).unwrap(),
+      Ok(random_data.clone())
+    );
+
+    let dispose_sample = &samples[1];
+    assert_eq!(dispose_sample.bytes(), &key_bytes);
+    let info = dispose_sample.sample_info();
+    assert!(!info.valid_data());
+    assert_eq!(info.instance_state(), InstanceState::NotAliveDisposed);
+    assert_eq!(
+      reader.deserialize_sample(dispose_sample).unwrap(),
+      Err(random_data.a)
+    );
+
+    // The samples have been taken.
+    assert!(reader.take_serialized().unwrap().is_empty());
+    assert!(reader.take(10, ReadCondition::any()).unwrap().is_empty());
+  }
+
+  #[test]
+  fn dr_writer_lost_and_rediscovered() {
+    let dp = DomainParticipant::new(0).expect("Participant creation failed");
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll);
+
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr_lost".to_string(),
+        "drtest?".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+
+    let (send, _rec) = mio_channel::sync_channel::<()>(10);
+    let (status_sender, _status_receiver) =
+      mio_extras::channel::sync_channel::<DataReaderStatus>(100);
+    let (_reader_commander, reader_command_receiver) =
+      mio_extras::channel::sync_channel::<ReaderCommand>(100);
+
+    let reader_ing = ReaderIngredients {
+      guid: GUID::new_with_prefix_and_id(dp.guid_prefix(), EntityId::default()),
+      notification_sender: send,
+      data_reader_waker: WakerSlot::default(),
+      status_sender,
+      topic_name: topic.name(),
+      qos_policy: QosPolicies::qos_none(),
+      data_reader_command_receiver: reader_command_receiver,
+    };
+    let mut new_reader = Reader::new(
+      reader_ing,
+      dp.dds_cache(),
+      Rc::new(Transports::from(UDPSender::new_with_random_port().unwrap())),
+      crate::dds::timer::Timer::default(),
+    );
+
+    let mut matching_datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+
+    let writer_guid = GUID {
+      prefix: GuidPrefix::new(&[1; 12]),
+      entity_id: EntityId::create_custom_entity_id(
+        [1; 3],
+        EntityKind::WRITER_WITH_KEY_USER_DEFINED,
+      ),
+    };
+    let mr_state = MessageReceiverState {
+      source_guid_prefix: writer_guid.prefix,
+      ..Default::default()
+    };
+    let data_flags = DATA_Flags::Endianness | DATA_Flags::Data;
+    let make_data = |sn: i64, b: &str| Data {
+      reader_id: EntityId::UNKNOWN,
+      writer_id: writer_guid.entity_id,
+      writer_sn: SequenceNumber::from(sn),
+      serialized_payload: Some(SerializedPayload {
+        representation_identifier: RepresentationIdentifier::CDR_LE,
+        representation_options: [0, 0],
+        value: Bytes::from(
+          to_bytes::<RandomData, LittleEndian>(&RandomData {
+            a: 1,
+            b: b.to_string(),
+          })
+          .unwrap(),
+        ),
+      }),
+      ..Default::default()
+    };
+    let match_writer = |reader: &mut Reader| {
+      reader.matched_writer_add(
+        writer_guid,
+        EntityId::UNKNOWN,
+        mr_state.unicast_reply_locator_list.clone(),
+        mr_state.multicast_reply_locator_list.clone(),
+        &QosPolicies::qos_none(),
+      );
+    };
+
+    match_writer(&mut new_reader);
+    new_reader.handle_data_msg(make_data(1, "before"), data_flags, &mr_state);
+
+    // Network partition: writer lease expires and it is unmatched.
+    new_reader.remove_writer_proxy(writer_guid);
+    matching_datareader.fill_local_datasample_cache().unwrap();
+    let samples = matching_datareader.read(10, ReadCondition::any()).unwrap();
+    assert_eq!(samples.len(), 1);
+    assert_eq!(
+      samples[0].sample_info().instance_state,
+      InstanceState::NotAliveNoWriters
+    );
+
+    // Partition heals: writer is re-discovered and resends its history
+    // followed by new data.
+    match_writer(&mut new_reader);
+    new_reader.handle_data_msg(make_data(1, "before"), data_flags, &mr_state);
+    new_reader.handle_data_msg(make_data(2, "after"), data_flags, &mr_state);
+    matching_datareader.fill_local_datasample_cache().unwrap();
+
+    let samples = matching_datareader.take(10, ReadCondition::any()).unwrap();
+    // History already seen must not be delivered twice.
+    assert_eq!(samples.len(), 2);
+    let latest = samples[1].sample_info();
+    assert_eq!(latest.instance_state, InstanceState::Alive);
+    assert_eq!(latest.generation_counts.no_writers_generation_count, 1);
+    assert_eq!(latest.generation_counts.disposed_generation_count, 0);
+    assert_eq!(
+      samples[0]
+        .sample_info()
+        .generation_counts
+        .no_writers_generation_count,
+      0
+    );
+  }
+
   #[test]
   #[ignore]
   fn dr_read_and_take() {
@@ -1486,7 +2558,7 @@ mod tests {
     let sub = dp.create_subscriber(&qos).unwrap();
     let topic = dp
       .create_topic(
-        "dr read".to_string(),
+        "dr_read".to_string(),
         "read fn test?".to_string(),
         &qos,
         TopicKind::WithKey,
@@ -1505,6 +2577,7 @@ mod tests {
     let reader_ing = ReaderIngredients {
       guid: reader_guid,
       notification_sender: send,
+      data_reader_waker: WakerSlot::default(),
       status_sender,
       topic_name: topic.name(),
       qos_policy: QosPolicies::qos_none(),
@@ -1514,8 +2587,8 @@ mod tests {
     let mut reader = Reader::new(
       reader_ing,
       dp.dds_cache(),
-      Rc::new(UDPSender::new_with_random_port().unwrap()),
-      mio_extras::timer::Builder::default().build(),
+      Rc::new(Transports::from(UDPSender::new_with_random_port().unwrap())),
+      crate::dds::timer::Timer::default(),
     );
 
     let mut datareader = sub
@@ -1726,6 +2799,47 @@ mod tests {
     assert!(results.unwrap().is_empty());
   }
 
+  #[test]
+  fn dr_stays_readable_with_samples_left_over() {
+    let dp = DomainParticipant::new(0).expect("Participant creation failed");
+    let qos = QosPolicies::qos_none();
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "left_over".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+    let selection = || {
+      (1..=3)
+        .map(|a| (Timestamp::now(), a))
+        .collect::<Vec<(Timestamp, i64)>>()
+    };
+
+    // Everything selected is accessed.
+    let mut selected = selection();
+    datareader.limit_selection(&mut selected, 3, &ReadCondition::not_read());
+    assert_eq!(selected.len(), 3);
+    assert!(datareader.notification_receiver.try_recv().is_err());
+
+    // Unread samples are left over.
+    let mut selected = selection();
+    datareader.limit_selection(&mut selected, 2, &ReadCondition::not_read());
+    assert_eq!(selected.len(), 2);
+    assert!(datareader.notification_receiver.try_recv().is_ok());
+
+    // Samples left over may have been read already.
+    let mut selected = selection();
+    datareader.limit_selection(&mut selected, 2, &ReadCondition::any());
+    assert_eq!(selected.len(), 2);
+    assert!(datareader.notification_receiver.try_recv().is_err());
+  }
+
   /* removing this test case, because UDPSender cannot be moved across threads.
   #[test]
   fn dr_wake_up() {
@@ -1751,6 +2865,7 @@ mod tests {
     let mut reader_ing = ReaderIngredients {
       guid: reader_guid,
       notification_sender: send,
+      data_reader_waker: WakerSlot::default(),
       status_sender,
       topic_name: topic.name().to_string(),
       qos_policy: QosPolicies::qos_none(),
@@ -16,6 +16,9 @@ use crate::dds::{sampleinfo::*, traits::key::*};
 /// * `Err(k)` means `valid_data == false`, no sample exists, but only a Key `k`
 ///   and instance_state has changed.
 ///
+/// The same information is available as
+/// [`SampleInfo::valid_data`](crate::SampleInfo::valid_data).
+///
 /// See also DDS spec v1.4 Section 2.2.2.5.4.
 #[derive(PartialEq, Debug)]
 pub struct DataSample<D: Keyed> {
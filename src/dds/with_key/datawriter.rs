@@ -1,29 +1,43 @@
 use std::{
+  cmp::max,
+  collections::{BTreeMap, BTreeSet},
+  future::Future,
+  io,
   marker::PhantomData,
+  ops::RangeBounds,
+  pin::Pin,
   sync::{
     atomic::{AtomicI64, Ordering},
-    Arc, RwLock,
+    mpsc::TryRecvError,
+    Arc, Mutex, RwLock,
   },
+  task::{self, Context},
   time::Duration,
 };
 
-use mio::{Evented, Events, Poll, PollOpt, Ready, Token};
-use mio_extras::channel::{self as mio_channel, Receiver, SendError};
+use mio::{Evented, Events, Poll, PollOpt, Ready, Registration, SetReadiness, Token};
+use mio_extras::channel::{self as mio_channel, Receiver, SendError, TrySendError};
 use serde::Serialize;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
 use crate::{
   dds::{
-    ddsdata::DDSData,
+    clock::TimeSource,
+    ddsdata::{DDSData, LocalSample},
+    flow_controller::FlowController,
     helpers::*,
-    pubsub::Publisher,
+    listener::{dispatch_writer_status, DataWriterListener, StatusMask},
+    loan::SampleLoan,
+    pipeline_tracing,
+    pubsub::{Publisher, SuspendedWrites},
     qos::{
-      policy::{Liveliness, Reliability},
-      HasQoSPolicy, QosPolicies,
+      policy::{DestinationOrder, History, Liveliness, Reliability, ResourceLimits},
+      HasQoSPolicy, MutQosPolicy, QosPolicies,
     },
+    query_expression::{FieldValue, SampleFilter},
     statusevents::*,
-    topic::Topic,
+    topic::{Topic, TopicKind},
     traits::{
       dds_entity::DDSEntity, key::*, serde_adapters::with_key::SerializerAdapter, TopicDescription,
     },
@@ -31,14 +45,24 @@ use crate::{
   },
   discovery::{data_types::topic_data::SubscriptionBuiltinTopicData, discovery::DiscoveryCommand},
   log_and_err_internal,
-  messages::submessages::submessage_elements::serialized_payload::SerializedPayload,
+  messages::submessages::submessage_elements::serialized_payload::{
+    RepresentationIdentifier, SerializedPayload,
+  },
   serialization::CDRSerializerAdapter,
   structure::{
-    cache_change::ChangeKind, dds_cache::DDSCache, entity::RTPSEntity, guid::GUID,
-    rpc::SampleIdentity, sequence_number::SequenceNumber, time::Timestamp,
+    cache_change::ChangeKind,
+    dds_cache::DDSCache,
+    duration::Duration as DDSDuration,
+    entity::RTPSEntity,
+    guid::GUID,
+    rpc::SampleIdentity,
+    sequence_number::{SequenceNumber, SequenceNumberRange},
+    time::Timestamp,
   },
 };
-use super::super::writer::WriterCommand;
+use super::super::writer::{AckProgress, Writer, WriterCommand};
+#[cfg(feature = "metrics")]
+use crate::dds::runtime_metrics;
 
 // It is a bit overkill to use a builder for such a simple struct, but
 // it may be expanded in future versions of RustDDS or even the spec.
@@ -48,6 +72,8 @@ use super::super::writer::WriterCommand;
 pub struct WriteOptionsBuilder {
   related_sample_identity: Option<SampleIdentity>,
   source_timestamp: Option<Timestamp>,
+  ownership_strength: Option<i32>,
+  coherent_set: Option<SequenceNumber>,
 }
 
 impl WriteOptionsBuilder {
@@ -59,6 +85,8 @@ impl WriteOptionsBuilder {
     WriteOptions {
       related_sample_identity: self.related_sample_identity,
       source_timestamp: self.source_timestamp,
+      ownership_strength: self.ownership_strength,
+      coherent_set: self.coherent_set,
     }
   }
 
@@ -82,6 +110,19 @@ impl WriteOptionsBuilder {
     self.source_timestamp = Some(source_timestamp);
     self
   }
+
+  // These are set by the RTPS layer, not by the application.
+  #[must_use]
+  pub(crate) fn ownership_strength(mut self, ownership_strength: i32) -> Self {
+    self.ownership_strength = Some(ownership_strength);
+    self
+  }
+
+  #[must_use]
+  pub(crate) fn coherent_set(mut self, coherent_set: SequenceNumber) -> Self {
+    self.coherent_set = Some(coherent_set);
+    self
+  }
 }
 
 /// Type to be used with write_with_options.
@@ -90,14 +131,18 @@ impl WriteOptionsBuilder {
 pub struct WriteOptions {
   pub(crate) related_sample_identity: Option<SampleIdentity>,
   pub(crate) source_timestamp: Option<Timestamp>,
+  // Inline QoS: strength of a writer with exclusive ownership, and the first
+  // sequence number of the coherent set that the sample belongs to.
+  pub(crate) ownership_strength: Option<i32>,
+  pub(crate) coherent_set: Option<SequenceNumber>,
   // future extension room fo other fields.
 }
 
 impl From<Option<Timestamp>> for WriteOptions {
   fn from(source_timestamp: Option<Timestamp>) -> Self {
     Self {
-      related_sample_identity: None,
       source_timestamp,
+      ..Self::default()
     }
   }
 }
@@ -105,6 +150,13 @@ impl From<Option<Timestamp>> for WriteOptions {
 /// Simplified type for CDR encoding
 pub type DataWriterCdr<D> = DataWriter<D, CDRSerializerAdapter<D>>;
 
+// Turns a written sample into one that local DataReaders can share
+type LocalSharing<D> = fn(D) -> LocalSample;
+
+// A sample ready to be written: its payload and key, the DataReaders that
+// filter it out, and the sample for local sharing.
+type OutgoingSample<K> = (SerializedPayload, K, BTreeSet<GUID>, Option<LocalSample>);
+
 /// DDS DataWriter for keyed topics
 ///
 /// # Examples
@@ -147,6 +199,31 @@ pub struct DataWriter<D: Keyed + Serialize, SA: SerializerAdapter<D> = CDRSerial
   discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
   status_receiver: StatusReceiver<DataWriterStatus>,
   available_sequence_number: AtomicI64,
+  // Updated by the Writer, see WriterIngredients.
+  acked_by_all_before: Arc<AtomicI64>,
+  // Readiness for application event loops, see the Evented implementation.
+  // The Writer sets it when samples are acknowledged. try_write() clears
+  // writable when the history is full, and acknowledged_before() clears
+  // readable.
+  ack_registration: Registration,
+  ack_readiness: SetReadiness,
+  // Woken by the Writer along with ack_readiness.
+  ack_waker: WakerSlot,
+  // Content filters of matched readers, maintained by the Writer.
+  reader_filters: Arc<RwLock<BTreeMap<GUID, SampleFilter>>>,
+  // Instances registered explicitly or implicitly by writing, and not
+  // unregistered since.
+  registered_instances: Mutex<BTreeSet<InstanceHandle>>,
+  // Shared with the Publisher, which can enable and delete this DataWriter.
+  lifecycle: EntityLifecycle,
+  // Set if samples are shared with DataReaders of the same participant. See
+  // set_local_zero_copy().
+  local_sharing: Mutex<Option<LocalSharing<D>>>,
+  // Shared with the Publisher, which holds writes back while publications are
+  // suspended.
+  suspended_writes: SuspendedWrites,
+  // The clock of the participant, for source timestamps
+  time_source: TimeSource,
 }
 
 impl<D, SA> Drop for DataWriter<D, SA>
@@ -155,9 +232,17 @@ where
   SA: SerializerAdapter<D>,
 {
   fn drop(&mut self) {
+    let was_enabled = self.lifecycle.delete();
+
     // Tell Publisher to drop the corresponding RTPS Writer
     self.my_publisher.remove_writer(self.my_guid);
 
+    // Discovery does not know a DataWriter that was never enabled, and it has
+    // already been told about a DataWriter deleted by the Publisher.
+    if !was_enabled {
+      return;
+    }
+
     // Notify Discovery that we are no longer
     match self
       .discovery_command
@@ -184,21 +269,31 @@ where
   <D as Keyed>::K: Key,
   SA: SerializerAdapter<D>,
 {
+  #[allow(clippy::too_many_arguments)]
   pub(crate) fn new(
     publisher: Publisher,
     topic: Topic,
+    qos: QosPolicies,
     guid: GUID,
     cc_upload: mio_channel::SyncSender<WriterCommand>,
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
     dds_cache: &Arc<RwLock<DDSCache>>, // Apparently, this is only needed for our Topic creation
     status_receiver_rec: Receiver<DataWriterStatus>,
+    acked_by_all_before: Arc<AtomicI64>,
+    ack_registration: Registration,
+    ack_readiness: SetReadiness,
+    ack_waker: WakerSlot,
+    reader_filters: Arc<RwLock<BTreeMap<GUID, SampleFilter>>>,
+    lifecycle: EntityLifecycle,
+    suspended_writes: SuspendedWrites,
+    time_source: TimeSource,
   ) -> Result<Self> {
     match dds_cache.write() {
       Ok(mut cache) => cache.add_new_topic(topic.name(), topic.get_type()),
       Err(e) => panic!("DDSCache is poisoned. {:?}", e),
     };
 
-    if let Some(lv) = topic.qos().liveliness {
+    if let Some(lv) = qos.liveliness {
       match lv {
         Liveliness::Automatic { .. } | Liveliness::ManualByTopic { .. } => (),
         Liveliness::ManualByParticipant { .. } => {
@@ -208,7 +303,7 @@ where
         }
       }
     };
-    let qos = topic.qos();
+    ack_readiness.set_readiness(Ready::writable())?;
     Ok(Self {
       data_phantom: PhantomData,
       ser_phantom: PhantomData,
@@ -220,9 +315,38 @@ where
       discovery_command,
       status_receiver: StatusReceiver::new(status_receiver_rec),
       available_sequence_number: AtomicI64::new(1), // valid numbering starts from 1
+      acked_by_all_before,
+      ack_registration,
+      ack_readiness,
+      ack_waker,
+      reader_filters,
+      registered_instances: Mutex::new(BTreeSet::new()),
+      lifecycle,
+      local_sharing: Mutex::new(None),
+      suspended_writes,
+      time_source,
     })
   }
 
+  /// Enables this DataWriter, if it was created disabled due to the
+  /// [`EntityFactory`](crate::policy::EntityFactory) policy of its Publisher.
+  /// Only then is the DataWriter announced to remote participants, and it can
+  /// write. Before that, any QoS policy can be changed with `set_qos`, and
+  /// most operations fail with [`NotEnabled`](Error::NotEnabled).
+  ///
+  /// Fails with [`PreconditionNotMet`](Error::PreconditionNotMet) if the
+  /// Publisher is not enabled.
+  pub fn enable(&self) -> Result<()> {
+    if !self.my_publisher.is_enabled() {
+      return Error::precondition_not_met("Publisher is not enabled");
+    }
+    self.lifecycle.enable()
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.lifecycle.is_enabled()
+  }
+
   fn next_sequence_number(&self) -> SequenceNumber {
     SequenceNumber::from(
       self
@@ -237,6 +361,68 @@ where
       .fetch_sub(1, Ordering::Relaxed);
   }
 
+  // Maximum number of unacknowledged samples, if the history is limited
+  fn history_limit(&self) -> Option<i64> {
+    match (self.qos_policy.history, self.qos_policy.resource_limits) {
+      (Some(History::KeepAll), Some(ResourceLimits { max_samples, .. })) if max_samples > 0 => {
+        Some(i64::from(max_samples))
+      }
+      _ => None, // unlimited
+    }
+  }
+
+  fn has_history_space(&self, max_samples: i64) -> bool {
+    let unacknowledged = self.available_sequence_number.load(Ordering::Relaxed)
+      - self.acked_by_all_before.load(Ordering::Relaxed);
+    unacknowledged < max_samples
+  }
+
+  // With KEEP_ALL history and a max_samples resource limit, a reliable
+  // DataWriter may hold at most max_samples samples that some matched
+  // reliable DataReader has not yet acknowledged. Waits up to
+  // max_blocking_time for acknowledgments to make room for one more.
+  fn wait_for_history_space(&self, max_blocking_time: DDSDuration) -> Result<()> {
+    let max_samples = match self.history_limit() {
+      Some(max_samples) => max_samples,
+      None => return Ok(()),
+    };
+    if wait_until_ready(max_blocking_time, || self.has_history_space(max_samples)) {
+      Ok(())
+    } else {
+      warn!(
+        "Write timed out: history of {} samples is full. topic={:?}",
+        max_samples,
+        self.my_topic.name()
+      );
+      Err(Error::Timeout)
+    }
+  }
+
+  // Non-blocking version of wait_for_history_space(). When the history is
+  // full, this clears the writable readiness, and the Writer sets it again
+  // when DataReaders acknowledge samples.
+  fn check_history_space(&self) -> Result<()> {
+    let max_samples = match self.history_limit() {
+      Some(max_samples) => max_samples,
+      None => return Ok(()),
+    };
+    if self.has_history_space(max_samples) {
+      return Ok(());
+    }
+    self
+      .ack_readiness
+      .set_readiness(self.ack_readiness.readiness() - Ready::writable())?;
+    // Samples may have been acknowledged before the readiness was cleared.
+    if self.has_history_space(max_samples) {
+      self
+        .ack_readiness
+        .set_readiness(self.ack_readiness.readiness() | Ready::writable())?;
+      Ok(())
+    } else {
+      Err(Error::WouldBlock)
+    }
+  }
+
   // This one function provides both get_matched_subscrptions and
   // get_matched_subscription_data TODO: Maybe we could return references to the
   // subscription data to avoid copying? But then what if the result set changes
@@ -295,6 +481,12 @@ where
 
   /// Writes single data instance to a topic.
   ///
+  /// A Reliable DataWriter with KEEP_ALL History and a `max_samples`
+  /// ResourceLimit keeps at most that many samples that are not yet
+  /// acknowledged by all matched reliable DataReaders. When its history is
+  /// full, `write` blocks for up to the `max_blocking_time` of the Reliability
+  /// policy, and then fails with [`Error::Timeout`].
+  ///
   /// # Examples
   ///
   /// ```
@@ -333,32 +525,190 @@ where
   }
 
   pub fn write_with_options(&self, data: D, write_options: WriteOptions) -> Result<SampleIdentity> {
-    let send_buffer = SA::to_bytes(&data)?; // serialize
+    self.write_sample(write_options, true, || self.serialize_sample(data))
+  }
 
-    let ddsdata = DDSData::new(SerializedPayload::new_from_bytes(
-      SA::output_encoding(),
-      send_buffer,
-    ));
+  /// Writes a sample with the given source timestamp, e.g. the time when
+  /// the data was measured, instead of the time of writing.
+  ///
+  /// The timestamp is sent to DataReaders, and becomes the
+  /// [`source_timestamp`](crate::SampleInfo::source_timestamp) of the
+  /// sample there. With DESTINATION_ORDER BY_SOURCE_TIMESTAMP, DataReaders
+  /// also order the samples of an instance by it.
+  ///
+  /// See DDS Spec 1.4 Section 2.2.2.4.2.12 write_w_timestamp.
+  pub fn write_w_timestamp(&self, data: D, source_timestamp: Timestamp) -> Result<()> {
+    self.write(data, Some(source_timestamp))
+  }
+
+  /// Writes a sample like [`write`](Self::write), but never blocks. If a
+  /// Reliable DataWriter with KEEP_ALL History and a `max_samples`
+  /// ResourceLimit has a full history, this fails with
+  /// [`Error::WouldBlock`] instead of waiting, and the sample is not written.
+  ///
+  /// To find out when to try again, register the DataWriter with a mio
+  /// `Poll` for `Ready::writable()`, see its `Evented` implementation.
+  pub fn try_write(&self, data: D, source_timestamp: Option<Timestamp>) -> Result<()> {
+    self.write_sample(WriteOptions::from(source_timestamp), false, || {
+      self.serialize_sample(data)
+    })?;
+    Ok(())
+  }
+
+  /// Source of only the writable readiness of the DataWriter. This is the
+  /// same as registering the DataWriter itself for `Ready::writable()`.
+  pub fn as_writable_evented(&self) -> &dyn Evented {
+    &self.ack_registration
+  }
+
+  /// All matched reliable DataReaders have acknowledged the samples before
+  /// this sequence number. `None` if there are no matched reliable
+  /// DataReaders.
+  ///
+  /// This clears the readable readiness of the DataWriter, see its `Evented`
+  /// implementation.
+  pub fn acknowledged_before(&self) -> Result<Option<SequenceNumber>> {
+    // Clear first, so that a later acknowledgment sets it again.
+    self
+      .ack_readiness
+      .set_readiness(self.ack_readiness.readiness() - Ready::readable())?;
+    Ok(match self.acked_by_all_before.load(Ordering::Relaxed) {
+      i64::MAX => None,
+      acked_before => Some(SequenceNumber::from(acked_before)),
+    })
+  }
+
+  fn serialize_sample(&self, data: D) -> Result<OutgoingSample<D::K>> {
+    let send_buffer = {
+      let _span = pipeline_tracing::serialize();
+      SA::to_bytes(&data)?
+    };
+    let encoding = SA::output_encoding_of(&data);
+    let filtered_out = self.readers_filtering_out(&data, encoding, &send_buffer);
+    let key = data.key();
+    let local_sharing = *self.local_sharing.lock()?;
+    let local_sample = local_sharing.map(|share| share(data));
+    Ok((
+      SerializedPayload::new_from_bytes(encoding, send_buffer),
+      key,
+      filtered_out,
+      local_sample,
+    ))
+  }
+
+  /// Loans a buffer of `size` bytes for the serialized payload of a sample,
+  /// i.e. its data without the encapsulation header, in the data
+  /// representation [`SampleLoan::encoding`]. The buffer is filled with zeros,
+  /// and aligned to 8 bytes.
+  ///
+  /// When the application has filled the buffer, it publishes it with
+  /// [`write_loan`](Self::write_loan), and the buffer is used as the payload
+  /// without copying or serializing it. DataReaders in the same process that
+  /// read [`Loaned`](crate::Loaned) samples get a loan of the same buffer.
+  /// Transports still copy the payload into the messages they send, also the
+  /// shared memory transport.
+  pub fn loan_sample(&self, size: usize) -> Result<SampleLoan> {
+    self.lifecycle.check_enabled()?;
+    Ok(SampleLoan::new(self.my_guid, size, SA::output_encoding()))
+  }
+
+  /// Publishes a buffer loaned with [`loan_sample`](Self::loan_sample) as a
+  /// sample of instance `key`. As in [`write`](Self::write), this may block,
+  /// if the history of a Reliable DataWriter is full.
+  ///
+  /// The sample cannot be checked against content filters of the matched
+  /// DataReaders, so they filter it themselves.
+  pub fn write_loan(
+    &self,
+    loan: SampleLoan,
+    key: D::K,
+    write_options: WriteOptions,
+  ) -> Result<SampleIdentity> {
+    if loan.writer() != self.my_guid {
+      return Error::bad_parameter("The buffer was loaned from another DataWriter");
+    }
+    self.write_sample(write_options, true, || {
+      Ok((loan.into_payload(), key, BTreeSet::new(), None))
+    })
+  }
+
+  // Common part of writing a sample: serialize() produces it. If not allowed
+  // to block, this fails with WouldBlock instead.
+  fn write_sample<F>(
+    &self,
+    mut write_options: WriteOptions,
+    block: bool,
+    serialize: F,
+  ) -> Result<SampleIdentity>
+  where
+    F: FnOnce() -> Result<OutgoingSample<D::K>>,
+  {
+    self.lifecycle.check_enabled()?;
+    #[cfg(feature = "tracing")]
+    let _span = pipeline_tracing::write(&self.my_topic.name(), self.my_guid);
+    // Topic name is a String, so metrics are only collected if enabled.
+    #[cfg(feature = "metrics")]
+    let write_start = std::time::Instant::now();
+    let timeout = match self.qos().reliability() {
+      Some(Reliability::Reliable { max_blocking_time }) => {
+        if block {
+          self.wait_for_history_space(max_blocking_time)?;
+        } else {
+          self.check_history_space()?;
+        }
+        Some(max_blocking_time)
+      }
+      _ => None,
+    };
+
+    write_options.source_timestamp = self.stamp_source_timestamp(write_options.source_timestamp);
+    let (serialized_payload, key, filtered_out, local_sample) = serialize()?;
+    #[cfg(feature = "metrics")]
+    runtime_metrics::sample_size(
+      &self.my_topic.name(),
+      runtime_metrics::SampleDirection::Written,
+      serialized_payload.value.len(),
+    );
+
+    let ddsdata = DDSData::new(serialized_payload)
+      .with_key_hash(self.key_hash(&key))
+      .with_local_sample(local_sample);
+    // Writing implicitly registers the instance.
+    self
+      .registered_instances
+      .lock()?
+      .insert(key.hash_key().into());
     let sequence_number = self.next_sequence_number();
     let writer_command = WriterCommand::DDSData {
       ddsdata,
       write_options,
       sequence_number,
+      filtered_out,
     };
 
-    let timeout = match self.qos().reliability() {
-      Some(Reliability::Reliable { max_blocking_time }) => Some(max_blocking_time),
-      _ => None,
+    let sent = match self
+      .suspended_writes
+      .try_hold(&self.cc_upload, writer_command)
+    {
+      None => Ok(()), // sent when the Publisher resumes publications
+      Some(writer_command) if block => try_send_timeout(&self.cc_upload, writer_command, timeout),
+      Some(writer_command) => self.cc_upload.try_send(writer_command),
     };
-
-    match try_send_timeout(&self.cc_upload, writer_command, timeout) {
+    match sent {
       Ok(_) => {
         self.refresh_manual_liveliness();
+        #[cfg(feature = "metrics")]
+        runtime_metrics::write_duration(&self.my_topic.name(), write_start.elapsed());
         Ok(SampleIdentity {
           writer_guid: self.my_guid,
           sequence_number,
         })
       }
+      // The event loop has not yet taken earlier samples.
+      Err(TrySendError::Full(_)) if !block => {
+        self.undo_sequence_number();
+        Err(Error::WouldBlock)
+      }
       Err(e) => {
         warn!(
           "Failed to write new data: topic={:?}  reason={:?}  timeout={:?}",
@@ -372,6 +722,120 @@ where
     }
   }
 
+  /// Registers an instance, i.e. a key value, with this DataWriter, and
+  /// returns a handle to it.
+  ///
+  /// Registration is not required before writing, as writing implicitly
+  /// registers the instance. Registering again returns the same handle.
+  /// Registration is local, and does not send anything to DataReaders.
+  ///
+  /// See DDS Spec 1.4 Section 2.2.2.4.2.5 register_instance.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::with_key::DataWriter;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::serialization::CDRSerializerAdapter;
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  ///
+  /// #[derive(Serialize, Deserialize)]
+  /// struct SomeType { a: i32, val: usize }
+  /// impl Keyed for SomeType {
+  ///   type K = i32;
+  ///
+  ///   fn key(&self) -> Self::K {
+  ///     self.a
+  ///   }
+  /// }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey).unwrap();
+  /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(&topic, None).unwrap();
+  ///
+  /// let handle = data_writer.register_instance(&1).unwrap();
+  /// data_writer.write_with_handle(SomeType { a: 1, val: 3 }, handle, None).unwrap();
+  /// // A handle does not fit samples of other instances.
+  /// assert!(data_writer.write_with_handle(SomeType { a: 2, val: 3 }, handle, None).is_err());
+  ///
+  /// // This writer no longer updates instance 1, but it stays alive for others.
+  /// data_writer.unregister_instance(&1, None).unwrap();
+  /// assert_eq!(data_writer.lookup_instance(&1), None);
+  /// ```
+  pub fn register_instance(&self, key: &<D as Keyed>::K) -> Result<InstanceHandle> {
+    self.lifecycle.check_enabled()?;
+    let handle = InstanceHandle::from(key.hash_key());
+    self.registered_instances.lock()?.insert(handle);
+    Ok(handle)
+  }
+
+  /// Returns the handle of an instance, if it is currently registered with
+  /// this DataWriter.
+  ///
+  /// See DDS Spec 1.4 Section 2.2.2.4.2.14 lookup_instance.
+  pub fn lookup_instance(&self, key: &<D as Keyed>::K) -> Option<InstanceHandle> {
+    let handle = InstanceHandle::from(key.hash_key());
+    self
+      .registered_instances
+      .lock()
+      .ok()
+      .filter(|instances| instances.contains(&handle))
+      .map(|_| handle)
+  }
+
+  /// Like [`write`](Self::write), but checks that `data` belongs to the
+  /// instance given by `handle`.
+  ///
+  /// Fails with [`BadParameter`](Error::BadParameter) if the key of `data`
+  /// does not match the handle, and with
+  /// [`PreconditionNotMet`](Error::PreconditionNotMet) if the instance is
+  /// not registered.
+  pub fn write_with_handle(
+    &self,
+    data: D,
+    handle: InstanceHandle,
+    source_timestamp: Option<Timestamp>,
+  ) -> Result<()> {
+    self.check_instance_handle(&data.key(), handle)?;
+    self.write(data, source_timestamp)
+  }
+
+  fn check_instance_handle(&self, key: &<D as Keyed>::K, handle: InstanceHandle) -> Result<()> {
+    if InstanceHandle::from(key.hash_key()) != handle {
+      return Error::bad_parameter("Instance handle does not match the key of the data.");
+    }
+    if !self.registered_instances.lock()?.contains(&handle) {
+      return Error::precondition_not_met("Instance is not registered with this DataWriter.");
+    }
+    Ok(())
+  }
+
+  // With DESTINATION_ORDER BY_SOURCE_TIMESTAMP, readers order samples by their
+  // source timestamp, so every sample we send must carry one.
+  fn stamp_source_timestamp(&self, source_timestamp: Option<Timestamp>) -> Option<Timestamp> {
+    match self.qos().destination_order() {
+      Some(DestinationOrder::BySourceTimeStamp) => {
+        source_timestamp.or_else(|| Some(self.time_source.now()))
+      }
+      _ => source_timestamp,
+    }
+  }
+
+  // Key hash sent as inline QoS with each sample, so that readers can identify
+  // the instance without deserializing. NoKey topics have no key hash.
+  fn key_hash(&self, key: &D::K) -> Option<KeyHash> {
+    match self.my_topic.kind() {
+      TopicKind::WithKey => Some(key.hash_key()),
+      TopicKind::NoKey => None,
+    }
+  }
+
   /// This operation blocks the calling thread until either all data written by
   /// the reliable DataWriter entities is acknowledged by all
   /// matched reliable DataReader entities, or else the duration specified by
@@ -421,8 +885,21 @@ where
   /// data_writer.wait_for_acknowledgments(std::time::Duration::from_millis(100));
   /// ```
   pub fn wait_for_acknowledgments(&self, max_wait: Duration) -> Result<bool> {
+    self
+      .wait_for_acknowledgments_detailed(max_wait)
+      .map(|pending| pending.all_acknowledged())
+  }
+
+  /// Like [`wait_for_acknowledgments`](Self::wait_for_acknowledgments), but
+  /// on timeout also tells which matched reliable DataReaders have not yet
+  /// acknowledged which samples.
+  pub fn wait_for_acknowledgments_detailed(
+    &self,
+    max_wait: Duration,
+  ) -> Result<PendingAcknowledgments> {
+    self.lifecycle.check_enabled()?;
     match &self.qos_policy.reliability {
-      None | Some(Reliability::BestEffort) => Ok(true),
+      None | Some(Reliability::BestEffort) => Ok(PendingAcknowledgments::none(self.my_guid)),
       Some(Reliability::Reliable { .. }) => {
         let (acked_sender, acked_receiver) = mio_channel::sync_channel::<()>(1);
         let poll = Poll::new()?;
@@ -432,10 +909,13 @@ where
           Ready::readable(),
           PollOpt::edge(),
         )?;
+        let progress = Arc::new(Mutex::new(AckProgress::default()));
         self
           .cc_upload
           .try_send(WriterCommand::WaitForAcknowledgments {
             all_acked: acked_sender,
+            all_acked_waker: WakerSlot::default(),
+            progress: progress.clone(),
           })?;
         let mut events = Events::with_capacity(1);
         poll.poll(&mut events, Some(max_wait))?;
@@ -444,14 +924,216 @@ where
             .try_recv()
             .or_else(|_e| log_and_err_internal!("wait_for_acknowledgments - Spurious poll event?"));
           // got reply
-          Ok(true)
+          Ok(PendingAcknowledgments::none(self.my_guid))
+        } else if acked_receiver.try_recv().is_ok() {
+          // acknowledged just after the timeout
+          Ok(PendingAcknowledgments::none(self.my_guid))
         } else {
           // no token, so presumably timed out
-          Ok(false)
+          let progress = progress.lock().unwrap();
+          Ok(PendingAcknowledgments::from_progress(
+            self.my_guid,
+            &progress,
+          ))
         }
       }
     } // match
   }
+
+  /// Writes a sample like [`write`](Self::write), and then waits until all
+  /// matched reliable DataReaders have acknowledged it.
+  ///
+  /// If the history is full, this first waits for space like
+  /// [`async_wait_for_history_space`](Self::async_wait_for_history_space),
+  /// so that writing does not block the executor. A BestEffort DataWriter
+  /// does not wait for acknowledgments. To limit the wait, use the timeout
+  /// facility of the async runtime.
+  pub async fn async_write(&self, data: D, source_timestamp: Option<Timestamp>) -> Result<()> {
+    self.async_wait_for_history_space().await?;
+    self.write(data, source_timestamp)?;
+    self.async_wait_for_acknowledgments().await
+  }
+
+  /// Waits until there is space in the history of a Reliable DataWriter with
+  /// KEEP_ALL History and a `max_samples` ResourceLimit, so that
+  /// [`try_write`](Self::try_write) would not fail with
+  /// [`Error::WouldBlock`] for that reason. Completes immediately for other
+  /// DataWriters.
+  ///
+  /// This is the async counterpart of the writable readiness, see the
+  /// `Evented` implementation of the DataWriter. The returned future is woken
+  /// by the DomainParticipant event loop thread, so it works with any async
+  /// executor.
+  pub async fn async_wait_for_history_space(&self) -> Result<()> {
+    self.lifecycle.check_enabled()?;
+    HistorySpace { writer: self }.await
+  }
+
+  /// Async version of [`wait_for_acknowledgments`](Self::wait_for_acknowledgments),
+  /// without a timeout. The returned future is woken by the DomainParticipant
+  /// event loop thread, so it works with any async executor.
+  pub async fn async_wait_for_acknowledgments(&self) -> Result<()> {
+    match self.all_acknowledged()? {
+      None => Ok(()),
+      Some(all_acknowledged) => all_acknowledged.await,
+    }
+  }
+
+  // Starts waiting for acknowledgments. The returned future does not borrow
+  // the DataWriter, so it can be kept across polls, e.g. by ByteStream. None
+  // means that there is nothing to wait for.
+  pub(crate) fn all_acknowledged(&self) -> Result<Option<AllAcknowledged>> {
+    self.lifecycle.check_enabled()?;
+    match &self.qos_policy.reliability {
+      None | Some(Reliability::BestEffort) => Ok(None),
+      Some(Reliability::Reliable { .. }) => {
+        let (acked_sender, acked_receiver) = mio_channel::sync_channel::<()>(1);
+        let acked_waker = WakerSlot::default();
+        let progress = Arc::new(Mutex::new(AckProgress::default()));
+        self
+          .cc_upload
+          .try_send(WriterCommand::WaitForAcknowledgments {
+            all_acked: acked_sender,
+            all_acked_waker: acked_waker.clone(),
+            progress: progress.clone(),
+          })?;
+        Ok(Some(AllAcknowledged {
+          acked_receiver,
+          acked_waker,
+          _progress: progress,
+        }))
+      }
+    }
+  }
+
+  /// Set the size in bytes at which batched samples are sent out, even if the
+  /// latency budget would allow waiting longer.
+  ///
+  /// Samples are batched into one RTPS message, if the QoS policy
+  /// LATENCY_BUDGET is greater than zero. The first sample of a batch is sent
+  /// out at the latest when the latency budget has passed. Default size is 16
+  /// KiB, and the limit is 60 KiB, so that a batch fits in a UDP datagram.
+  pub fn set_batch_max_size(&self, max_size: usize) -> Result<()> {
+    if max_size == 0 || max_size > Writer::DATA_BATCH_MAX_SIZE_LIMIT {
+      return Error::bad_parameter(format!(
+        "Batch size must be between 1 and {} bytes",
+        Writer::DATA_BATCH_MAX_SIZE_LIMIT
+      ));
+    }
+    self
+      .cc_upload
+      .try_send(WriterCommand::SetDataBatchMaxSize { max_size })
+      .or_else(|e| log_and_err_internal!("Cannot send SetDataBatchMaxSize: {:?}", e))
+  }
+
+  /// Send out the batched samples now, without waiting for the latency budget
+  /// to pass. See [`set_batch_max_size`](Self::set_batch_max_size).
+  pub fn flush(&self) -> Result<()> {
+    self
+      .cc_upload
+      .try_send(WriterCommand::FlushDataBatch)
+      .or_else(|e| log_and_err_internal!("Cannot send FlushDataBatch: {:?}", e))
+  }
+
+  /// Limit the send rate of this DataWriter with a [`FlowController`], or
+  /// remove the limit with `None`. Messages that exceed the rate are queued
+  /// and sent later, so writing does not block because of this. A queue
+  /// that keeps growing means that the rate is too low for the writes.
+  ///
+  /// This replaces a FlowController given by
+  /// [`Publisher::set_flow_controller`](crate::Publisher::set_flow_controller).
+  pub fn set_flow_controller(&self, flow_controller: Option<FlowController>) -> Result<()> {
+    self
+      .cc_upload
+      .try_send(WriterCommand::SetFlowController { flow_controller })
+      .or_else(|e| log_and_err_internal!("Cannot send SetFlowController: {:?}", e))
+  }
+
+  /// Set the period of the HEARTBEATs that announce the available samples to
+  /// reliable DataReaders. The default is 1 second, or a third of the lease
+  /// duration of QoS policy LIVELINESS MANUAL_BY_TOPIC.
+  ///
+  /// A shorter period repairs lost samples sooner, at the cost of more
+  /// messages. Best effort DataWriters send no HEARTBEATs, so this has no
+  /// effect on them.
+  pub fn set_heartbeat_period(&self, period: Duration) -> Result<()> {
+    if period.is_zero() {
+      return Error::bad_parameter("Heartbeat period must be greater than zero");
+    }
+    self
+      .cc_upload
+      .try_send(WriterCommand::SetHeartbeatPeriod { period })
+      .or_else(|e| log_and_err_internal!("Cannot send SetHeartbeatPeriod: {:?}", e))
+  }
+
+  /// Set how long to wait after an ACKNACK or NACK_FRAG requests samples,
+  /// before sending them. RTPS calls this `nackResponseDelay`. The default is
+  /// 200 ms.
+  pub fn set_nack_response_delay(&self, delay: Duration) -> Result<()> {
+    self
+      .cc_upload
+      .try_send(WriterCommand::SetNackResponseDelay { delay })
+      .or_else(|e| log_and_err_internal!("Cannot send SetNackResponseDelay: {:?}", e))
+  }
+
+  /// Piggyback a HEARTBEAT to every `count`th message of DATA, instead of
+  /// every one, which is the default. With `count` zero, no HEARTBEATs are
+  /// piggybacked, and new samples are announced only by the periodic
+  /// HEARTBEAT, see [`set_heartbeat_period`](Self::set_heartbeat_period).
+  ///
+  /// Fewer HEARTBEATs mean fewer ACKNACK responses from reliable DataReaders,
+  /// but lost samples are noticed later.
+  pub fn set_piggyback_heartbeat_every(&self, count: u32) -> Result<()> {
+    self
+      .cc_upload
+      .try_send(WriterCommand::SetPiggybackHeartbeatEvery { count })
+      .or_else(|e| log_and_err_internal!("Cannot send SetPiggybackHeartbeatEvery: {:?}", e))
+  }
+
+  /// Start sequence numbering from `sequence_number` instead of 1. This must
+  /// be called before writing anything.
+  ///
+  /// For protocol robustness testing only. Requires feature `testing`.
+  #[cfg(feature = "testing")]
+  pub fn set_initial_sequence_number(&self, sequence_number: SequenceNumber) -> Result<()> {
+    if self.available_sequence_number.load(Ordering::Relaxed) != 1 {
+      return Error::precondition_not_met("DataWriter has already written samples");
+    }
+    if sequence_number < SequenceNumber::from(1) {
+      return Error::bad_parameter("Sequence numbers start from 1");
+    }
+    match i64::from(sequence_number) - 1 {
+      0 => Ok(()),
+      count => self.skip_sequence_numbers(count),
+    }
+  }
+
+  /// Do not use the next `count` sequence numbers for any sample. Readers are
+  /// sent a GAP for them, and reliable readers should not wait for them.
+  ///
+  /// For protocol robustness testing only. Requires feature `testing`.
+  #[cfg(feature = "testing")]
+  pub fn skip_sequence_numbers(&self, count: i64) -> Result<()> {
+    if count < 1 {
+      return Error::bad_parameter("Must skip at least one sequence number");
+    }
+    let begin = self
+      .available_sequence_number
+      .fetch_add(count, Ordering::Relaxed);
+    let range = SequenceNumberRange::new(
+      SequenceNumber::from(begin),
+      SequenceNumber::from(begin + count - 1),
+    );
+    self
+      .cc_upload
+      .try_send(WriterCommand::SkipSequenceNumbers { range })
+      .or_else(|e| {
+        self
+          .available_sequence_number
+          .fetch_sub(count, Ordering::Relaxed);
+        log_and_err_internal!("Cannot send SkipSequenceNumbers: {:?}", e)
+      })
+  }
   /*
   /// Gets mio Receiver for all status changes
   ///
@@ -800,6 +1482,7 @@ where
   // make send error visible) TODO: Better make send failure visible, so
   // application can see if Discovery has failed.
   pub fn assert_liveliness(&self) -> Result<()> {
+    self.lifecycle.check_enabled()?;
     self.refresh_manual_liveliness();
 
     match self.qos().liveliness {
@@ -817,12 +1500,13 @@ where
     Ok(())
   }
 
-  /// Unimplemented. <b>Do not use</b>.
+  /// Gets the discovery data of the remote DataReaders on this Topic that
+  /// have QoS compatible with this DataWriter. This includes e.g. their
+  /// USER_DATA, TOPIC_DATA and GROUP_DATA QoS.
   ///
   /// # Examples
   ///
-  /// ```no_run
-  // TODO: enable when available
+  /// ```
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
@@ -855,7 +1539,32 @@ where
   ///   // do something
   /// }
   pub fn get_matched_subscriptions(&self) -> Vec<SubscriptionBuiltinTopicData> {
-    todo!()
+    let db = self.my_publisher.discovery_db();
+    let db = db
+      .read()
+      .unwrap_or_else(|e| panic!("DiscoveryDB is poisoned. {:?}", e));
+    db.external_readers_on_topic(&self.my_topic.name())
+      .into_iter()
+      .map(|drd| drd.subscription_topic_data)
+      .filter(|sub| self.qos_policy.compliance_failure_wrt(&sub.qos()).is_none())
+      .collect()
+  }
+
+  /// Invokes the callbacks of `listener` for the status changes since the
+  /// previous call. Only the callbacks selected by `mask` are invoked. Status
+  /// changes not in the mask are discarded.
+  ///
+  /// The callbacks run in the calling thread. Returns the number of callbacks
+  /// invoked.
+  pub fn dispatch_to_listener<L>(&mut self, listener: &mut L, mask: StatusMask) -> usize
+  where
+    L: DataWriterListener + ?Sized,
+  {
+    self.status_receiver.enable();
+    std::iter::from_fn(|| self.status_receiver.try_recv_status())
+      .map(|status| dispatch_writer_status(listener, status, mask))
+      .filter(|&invoked| invoked)
+      .count()
   }
 
   /// Disposes data instance with specified key
@@ -910,69 +1619,391 @@ where
   /// data_writer.dispose(&1, None).unwrap();
   /// ```
   pub fn dispose(&self, key: &<D as Keyed>::K, source_timestamp: Option<Timestamp>) -> Result<()> {
+    self.send_key_only(ChangeKind::NotAliveDisposed, key, source_timestamp)
+  }
+
+  /// Disposes an instance like [`dispose`](Self::dispose), with the given
+  /// source timestamp, see [`write_w_timestamp`](Self::write_w_timestamp).
+  ///
+  /// See DDS Spec 1.4 Section 2.2.2.4.2.14 dispose_w_timestamp.
+  pub fn dispose_w_timestamp(
+    &self,
+    key: &<D as Keyed>::K,
+    source_timestamp: Timestamp,
+  ) -> Result<()> {
+    self.dispose(key, Some(source_timestamp))
+  }
+
+  /// Tells DataReaders that this DataWriter will no longer update the
+  /// instance with the given key.
+  ///
+  /// Unlike [`dispose`](Self::dispose), this does not end the instance.
+  /// The instance becomes `NotAliveNoWriters` on a DataReader only when no
+  /// other DataWriter is updating it.
+  ///
+  /// Fails with [`PreconditionNotMet`](Error::PreconditionNotMet) if the
+  /// instance has not been registered or written by this DataWriter.
+  ///
+  /// See DDS Spec 1.4 Section 2.2.2.4.2.7 unregister_instance. An example is
+  /// at [`register_instance`](Self::register_instance).
+  pub fn unregister_instance(
+    &self,
+    key: &<D as Keyed>::K,
+    source_timestamp: Option<Timestamp>,
+  ) -> Result<()> {
+    self.lifecycle.check_enabled()?;
+    let handle = InstanceHandle::from(key.hash_key());
+    if !self.registered_instances.lock()?.remove(&handle) {
+      return Error::precondition_not_met("Instance is not registered with this DataWriter.");
+    }
+    self.send_key_only(ChangeKind::NotAliveUnregistered, key, source_timestamp)
+  }
+
+  /// Like [`unregister_instance`](Self::unregister_instance), but also checks
+  /// that `key` belongs to the instance given by `handle`.
+  pub fn unregister_instance_with_handle(
+    &self,
+    key: &<D as Keyed>::K,
+    handle: InstanceHandle,
+    source_timestamp: Option<Timestamp>,
+  ) -> Result<()> {
+    self.check_instance_handle(key, handle)?;
+    self.unregister_instance(key, source_timestamp)
+  }
+
+  // Matched readers whose content filter rejects the sample. Filters that
+  // can be evaluated on the serialized sample are, the others on the sample
+  // serialized with serde. If the sample cannot be evaluated, it is sent to
+  // everyone.
+  fn readers_filtering_out(
+    &self,
+    data: &D,
+    encoding: RepresentationIdentifier,
+    serialized: &[u8],
+  ) -> BTreeSet<GUID> {
+    let reader_filters = match self.reader_filters.read() {
+      Ok(reader_filters) if !reader_filters.is_empty() => reader_filters,
+      _ => return BTreeSet::new(),
+    };
+    // Made only if some filter needs it
+    let mut sample_value: Option<Option<FieldValue>> = None;
+    let mut filtering_out = BTreeSet::new();
+    for (guid, filter) in reader_filters.iter() {
+      let matches = match filter.matches_serialized(serialized, encoding) {
+        Some(matches) => matches,
+        None => match sample_value.get_or_insert_with(|| {
+          FieldValue::from_sample(data)
+            .map_err(|e| debug!("Cannot evaluate content filters on sample: {}", e))
+            .ok()
+        }) {
+          Some(value) => filter.matches_value(value),
+          None => true,
+        },
+      };
+      if !matches {
+        filtering_out.insert(*guid);
+      }
+    }
+    filtering_out
+  }
+
+  // Sends a sample that has only the key, no data, i.e. a dispose or an
+  // unregister.
+  fn send_key_only(
+    &self,
+    change_kind: ChangeKind,
+    key: &<D as Keyed>::K,
+    source_timestamp: Option<Timestamp>,
+  ) -> Result<()> {
+    self.lifecycle.check_enabled()?;
     let send_buffer = SA::key_to_bytes(key)?; // serialize key
 
     let ddsdata = DDSData::new_disposed_by_key(
-      ChangeKind::NotAliveDisposed,
+      change_kind,
       SerializedPayload::new_from_bytes(SA::output_encoding(), send_buffer),
-    );
-    self
-      .cc_upload
-      .send(WriterCommand::DDSData {
-        ddsdata,
-        write_options: WriteOptions::from(source_timestamp),
-        sequence_number: self.next_sequence_number(),
-      })
-      .or_else(|huh| {
+    )
+    .with_key_hash(self.key_hash(key));
+    let writer_command = WriterCommand::DDSData {
+      ddsdata,
+      write_options: WriteOptions::from(self.stamp_source_timestamp(source_timestamp)),
+      sequence_number: self.next_sequence_number(),
+      filtered_out: BTreeSet::new(), // key-only samples are not filtered
+    };
+    if let Some(writer_command) = self
+      .suspended_writes
+      .try_hold(&self.cc_upload, writer_command)
+    {
+      self.cc_upload.send(writer_command).or_else(|huh| {
         self.undo_sequence_number();
-        log_and_err_internal!("Cannot send dispose command: {:?}", huh)
+        log_and_err_internal!("Cannot send {:?} command: {:?}", change_kind, huh)
       })?;
+    }
 
     self.refresh_manual_liveliness();
     Ok(())
   }
 }
 
-impl<D, SA> StatusEvented<DataWriterStatus> for DataWriter<D, SA>
+impl<D, SA> DataWriter<D, SA>
 where
-  D: Keyed + Serialize,
+  D: Keyed + Serialize + Clone + Send + Sync + 'static,
+  <D as Keyed>::K: Key,
   SA: SerializerAdapter<D>,
 {
-  fn as_status_evented(&mut self) -> &dyn Evented {
-    self.status_receiver.as_status_evented()
+  /// Share written samples with the DataReaders of the same
+  /// DomainParticipant, so that they get a clone of the sample instead of
+  /// deserializing it. Local DataReaders get the samples through the topic
+  /// cache of the participant, so this avoids the remaining decoding cost.
+  /// Default is disabled.
+  ///
+  /// Samples are still serialized for remote DataReaders. DataReaders whose
+  /// sample type is a different Rust type deserialize as usual.
+  ///
+  /// This is set per DataWriter, rather than by QoS, because it requires the
+  /// sample type to be `Clone`.
+  pub fn set_local_zero_copy(&self, enabled: bool) -> Result<()> {
+    *self.local_sharing.lock()? = if enabled {
+      Some(LocalSample::new::<D>)
+    } else {
+      None
+    };
+    Ok(())
   }
+}
 
-  fn try_recv_status(&self) -> Option<DataWriterStatus> {
-    self.status_receiver.try_recv_status()
-  }
+/// Result of
+/// [`wait_for_acknowledgments_detailed`](DataWriter::wait_for_acknowledgments_detailed):
+/// which samples of the DataWriter each matched reliable DataReader has not
+/// yet acknowledged.
+///
+/// If the DomainParticipant event loop did not get to process the wait
+/// before the timeout, it is not known which DataReaders are lagging. Then
+/// `all_acknowledged()` is `false`, but `readers()` is empty.
+#[derive(Debug, Clone)]
+pub struct PendingAcknowledgments {
+  writer_guid: GUID,
+  all_acknowledged: bool,
+  pending: BTreeMap<GUID, SequenceNumberRange>,
 }
 
-impl<D, SA> RTPSEntity for DataWriter<D, SA>
-where
-  D: Keyed + Serialize,
-  SA: SerializerAdapter<D>,
-{
-  fn guid(&self) -> GUID {
-    self.my_guid
+impl PendingAcknowledgments {
+  fn none(writer_guid: GUID) -> Self {
+    Self {
+      writer_guid,
+      all_acknowledged: true,
+      pending: BTreeMap::new(),
+    }
   }
-}
 
-impl<D, SA> HasQoSPolicy for DataWriter<D, SA>
-where
-  D: Keyed + Serialize,
-  SA: SerializerAdapter<D>,
+  fn from_progress(writer_guid: GUID, progress: &AckProgress) -> Self {
+    let pending = progress
+      .readers_pending
+      .iter()
+      .map(|(reader, acked_before)| {
+        let first_unacked = max(*acked_before, SequenceNumber::from(1));
+        (
+          *reader,
+          SequenceNumberRange::new(first_unacked, progress.wait_until),
+        )
+      })
+      .collect();
+    Self {
+      writer_guid,
+      all_acknowledged: false,
+      pending,
+    }
+  }
+
+  pub fn all_acknowledged(&self) -> bool {
+    self.all_acknowledged
+  }
+
+  /// DataReaders that have not acknowledged all samples.
+  pub fn readers(&self) -> impl Iterator<Item = GUID> + '_ {
+    self.pending.keys().copied()
+  }
+
+  /// Sequence numbers of the samples not acknowledged by `reader`, or `None`
+  /// if it has acknowledged everything, or is not matched.
+  pub fn unacknowledged_by(&self, reader: GUID) -> Option<SequenceNumberRange> {
+    self.pending.get(&reader).copied()
+  }
+
+  /// Is the sample, as identified by [`write_with_options`](DataWriter::write_with_options),
+  /// still unacknowledged by some DataReader?
+  pub fn is_unacknowledged(&self, sample: &SampleIdentity) -> bool {
+    sample.writer_guid == self.writer_guid
+      && self
+        .pending
+        .values()
+        .any(|range| range.contains(&sample.sequence_number))
+  }
+}
+
+// Future of async_wait_for_acknowledgments
+pub(crate) struct AllAcknowledged {
+  acked_receiver: Receiver<()>,
+  acked_waker: WakerSlot,
+  // Keeps the Writer waiting for as long as this future exists.
+  _progress: Arc<Mutex<AckProgress>>,
+}
+
+impl Future for AllAcknowledged {
+  type Output = Result<()>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<Self::Output> {
+    // Register before checking, so that a notification arriving in between is
+    // not missed.
+    self.acked_waker.register(cx.waker());
+    match self.acked_receiver.try_recv() {
+      Ok(()) => task::Poll::Ready(Ok(())),
+      Err(TryRecvError::Empty) => task::Poll::Pending,
+      Err(TryRecvError::Disconnected) => task::Poll::Ready(log_and_err_internal!(
+        "Acknowledgment wait was cancelled by the Writer"
+      )),
+    }
+  }
+}
+
+// Future of async_wait_for_history_space
+struct HistorySpace<'a, D, SA>
+where
+  D: Keyed + Serialize,
+  SA: SerializerAdapter<D>,
+{
+  writer: &'a DataWriter<D, SA>,
+}
+
+impl<D, SA> Future for HistorySpace<'_, D, SA>
+where
+  D: Keyed + Serialize,
+  <D as Keyed>::K: Key,
+  SA: SerializerAdapter<D>,
+{
+  type Output = Result<()>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<Self::Output> {
+    // Register before checking, so that an acknowledgment arriving in between
+    // is not missed.
+    self.writer.ack_waker.register(cx.waker());
+    match self.writer.check_history_space() {
+      Err(Error::WouldBlock) => task::Poll::Pending,
+      result => task::Poll::Ready(result),
+    }
+  }
+}
+
+/// A DataWriter can be registered with a mio `Poll` for
+/// * `Ready::writable()`: There may be space in the history, so that
+///   [`try_write`](DataWriter::try_write) would not fail with
+///   [`Error::WouldBlock`]. This is set from the start, and is cleared only
+///   when `try_write` fails.
+/// * `Ready::readable()`: Matched reliable DataReaders have acknowledged more
+///   samples. This is cleared by
+///   [`acknowledged_before`](DataWriter::acknowledged_before).
+///
+/// Register for both to get both kinds of events with the same token, and
+/// tell them apart by `Event::readiness()`. Status changes of the DataWriter
+/// have a separate source, see [`StatusEvented`].
+///
+/// With `PollOpt::level()`, the DataWriter is polled ready for as long as
+/// the readiness is set, so clear it as described above. With
+/// `PollOpt::edge()`, there is one event each time the readiness is set,
+/// including when acknowledgments arrive while it is set already.
+impl<D, SA> Evented for DataWriter<D, SA>
+where
+  D: Keyed + Serialize,
+  SA: SerializerAdapter<D>,
 {
-  // fn set_qos(&mut self, policy: &QosPolicies) -> Result<()> {
-  //   // TODO: check liveliness of qos_policy
-  //   self.qos_policy = policy.clone();
-  //   Ok(())
-  // }
+  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    Evented::register(&self.ack_registration, poll, token, interest, opts)
+  }
 
+  fn reregister(
+    &self,
+    poll: &Poll,
+    token: Token,
+    interest: Ready,
+    opts: PollOpt,
+  ) -> io::Result<()> {
+    Evented::reregister(&self.ack_registration, poll, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &Poll) -> io::Result<()> {
+    Evented::deregister(&self.ack_registration, poll)
+  }
+}
+
+impl<D, SA> StatusEvented<DataWriterStatus> for DataWriter<D, SA>
+where
+  D: Keyed + Serialize,
+  SA: SerializerAdapter<D>,
+{
+  fn as_status_evented(&mut self) -> &dyn Evented {
+    self.status_receiver.as_status_evented()
+  }
+
+  fn try_recv_status(&self) -> Option<DataWriterStatus> {
+    self.status_receiver.try_recv_status()
+  }
+}
+
+impl<D, SA> RTPSEntity for DataWriter<D, SA>
+where
+  D: Keyed + Serialize,
+  SA: SerializerAdapter<D>,
+{
+  fn guid(&self) -> GUID {
+    self.my_guid
+  }
+}
+
+impl<D, SA> HasQoSPolicy for DataWriter<D, SA>
+where
+  D: Keyed + Serialize,
+  SA: SerializerAdapter<D>,
+{
   fn qos(&self) -> QosPolicies {
     self.qos_policy.clone()
   }
 }
 
+impl<D, SA> MutQosPolicy for DataWriter<D, SA>
+where
+  D: Keyed + Serialize,
+  SA: SerializerAdapter<D>,
+{
+  /// Changes the QoS of this DataWriter.
+  ///
+  /// Policies defined in `new_qos` replace the current ones, and the rest are
+  /// kept. Attempting to change a policy that cannot be changed after
+  /// creation, e.g. Reliability, fails with
+  /// [`ImmutablePolicy`](Error::ImmutablePolicy).
+  ///
+  /// The new QoS is announced to remote participants, and matching with
+  /// DataReaders is re-evaluated. A DataWriter that is not yet enabled can
+  /// change any policy.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<()> {
+    // Nothing is announced before enabling, so anything can be changed.
+    let unannounced_qos = self.qos_policy.modify_by(new_qos);
+    if self.lifecycle.set_qos_if_disabled(&unannounced_qos) {
+      self.qos_policy = unannounced_qos;
+      return Ok(());
+    }
+
+    let qos = self.qos_policy.changed_by(new_qos)?;
+    self
+      .discovery_command
+      .send(DiscoveryCommand::UpdateLocalWriter {
+        guid: self.my_guid,
+        qos: qos.clone(),
+      })
+      .or_else(|e| log_and_err_internal!("Cannot send QoS update to Discovery: {:?}", e))?;
+    self.qos_policy = qos;
+    Ok(())
+  }
+}
+
 impl<D, SA> DDSEntity for DataWriter<D, SA>
 where
   D: Keyed + Serialize,
@@ -989,9 +2020,14 @@ mod tests {
 
   use super::*;
   use crate::{
-    dds::{participant::DomainParticipant, traits::key::Keyed},
-    serialization::cdr_serializer::CDRSerializerAdapter,
-    structure::topic_kind::TopicKind,
+    dds::{
+      dynamic_data::{DynamicMember, DynamicType},
+      participant::DomainParticipant,
+      qos::policy::Deadline,
+      traits::key::Keyed,
+    },
+    serialization::cdr_serializer::{to_bytes, CDRSerializerAdapter},
+    structure::{duration::Duration as DDSDuration, guid::EntityKind, topic_kind::TopicKind},
     test::random_data::*,
   };
 
@@ -1036,6 +2072,360 @@ mod tests {
     // TODO: write also with timestamp
   }
 
+  #[test]
+  fn dw_write_blocks_when_history_full() {
+    use crate::dds::qos::{policy, QosPolicyBuilder};
+
+    let domain_participant = DomainParticipant::new(0).expect("Publisher creation failed!");
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: DDSDuration::from_millis(50),
+      })
+      .history(policy::History::KeepAll)
+      .resource_limits(policy::ResourceLimits {
+        max_samples: 2,
+        max_instances: 2,
+        max_samples_per_instance: 2,
+      })
+      .build();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic(
+        "history_full_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+    let data_writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(&topic, None)
+        .expect("Failed to create datawriter");
+    let data = RandomData {
+      a: 4,
+      b: "Fobar".to_string(),
+    };
+
+    // Pretend that a reliable reader has acknowledged nothing.
+    data_writer.acked_by_all_before.store(1, Ordering::Relaxed);
+    data_writer.write(data.clone(), None).unwrap();
+    data_writer.write(data.clone(), None).unwrap();
+    assert!(matches!(
+      data_writer.write(data.clone(), None),
+      Err(Error::Timeout)
+    ));
+
+    // The first sample is acknowledged, which makes room for one more.
+    data_writer.acked_by_all_before.store(2, Ordering::Relaxed);
+    data_writer.write(data, None).unwrap();
+  }
+
+  #[test]
+  fn dw_try_write_would_block() {
+    use crate::dds::qos::{policy, QosPolicyBuilder};
+
+    let domain_participant = DomainParticipant::new(0).expect("Publisher creation failed!");
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: DDSDuration::from_secs(10),
+      })
+      .history(policy::History::KeepAll)
+      .resource_limits(policy::ResourceLimits {
+        max_samples: 2,
+        max_instances: 2,
+        max_samples_per_instance: 2,
+      })
+      .build();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic(
+        "try_write_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+    let data_writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(&topic, None)
+        .expect("Failed to create datawriter");
+    let data = RandomData {
+      a: 4,
+      b: "Fobar".to_string(),
+    };
+    assert!(data_writer.ack_readiness.readiness().is_writable());
+
+    // Pretend that a reliable reader has acknowledged nothing. The full
+    // history does not block for max_blocking_time.
+    data_writer.acked_by_all_before.store(1, Ordering::Relaxed);
+    data_writer.try_write(data.clone(), None).unwrap();
+    data_writer.try_write(data.clone(), None).unwrap();
+    assert!(matches!(
+      data_writer.try_write(data.clone(), None),
+      Err(Error::WouldBlock)
+    ));
+    assert!(data_writer.ack_readiness.readiness().is_empty());
+
+    // The Writer signals when the first sample is acknowledged.
+    data_writer.acked_by_all_before.store(2, Ordering::Relaxed);
+    data_writer
+      .ack_readiness
+      .set_readiness(Ready::writable())
+      .unwrap();
+    let poll = Poll::new().unwrap();
+    poll
+      .register(
+        data_writer.as_writable_evented(),
+        Token(0),
+        Ready::writable(),
+        PollOpt::edge(),
+      )
+      .unwrap();
+    let mut events = Events::with_capacity(4);
+    poll
+      .poll(&mut events, Some(std::time::Duration::from_millis(100)))
+      .unwrap();
+    assert!(events.iter().any(|e| e.readiness().is_writable()));
+    data_writer.try_write(data, None).unwrap();
+  }
+
+  #[test]
+  fn dw_async_wait_for_history_space() {
+    use std::{
+      sync::atomic::AtomicUsize,
+      task::{Wake, Waker},
+    };
+
+    use crate::dds::qos::{policy, QosPolicyBuilder};
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+      fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+      }
+    }
+
+    let domain_participant = DomainParticipant::new(0).expect("Publisher creation failed!");
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: DDSDuration::from_secs(10),
+      })
+      .history(policy::History::KeepAll)
+      .resource_limits(policy::ResourceLimits {
+        max_samples: 1,
+        max_instances: 1,
+        max_samples_per_instance: 1,
+      })
+      .build();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic(
+        "history_space_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+    let data_writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(&topic, None)
+        .expect("Failed to create datawriter");
+    let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    // Pretend that a reliable reader has acknowledged nothing.
+    data_writer.acked_by_all_before.store(1, Ordering::Relaxed);
+    let mut space = Box::pin(data_writer.async_wait_for_history_space());
+    assert!(matches!(
+      space.as_mut().poll(&mut cx),
+      task::Poll::Ready(Ok(()))
+    ));
+    data_writer
+      .try_write(
+        RandomData {
+          a: 4,
+          b: "Fobar".to_string(),
+        },
+        None,
+      )
+      .unwrap();
+
+    let mut space = Box::pin(data_writer.async_wait_for_history_space());
+    assert!(space.as_mut().poll(&mut cx).is_pending());
+    // The Writer wakes the task when the sample is acknowledged.
+    data_writer.acked_by_all_before.store(2, Ordering::Relaxed);
+    data_writer.ack_waker.wake();
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    assert!(matches!(
+      space.as_mut().poll(&mut cx),
+      task::Poll::Ready(Ok(()))
+    ));
+  }
+
+  #[test]
+  fn dw_evented_acknowledgments() {
+    let domain_participant = DomainParticipant::new(0).expect("Publisher creation failed!");
+    let qos = QosPolicies::qos_none();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic(
+        "evented_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+    let data_writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(&topic, None)
+        .expect("Failed to create datawriter");
+
+    let poll = Poll::new().unwrap();
+    poll
+      .register(
+        &data_writer,
+        Token(0),
+        Ready::readable() | Ready::writable(),
+        PollOpt::level(),
+      )
+      .unwrap();
+    let mut events = Events::with_capacity(4);
+    let mut poll_readiness = || {
+      poll
+        .poll(&mut events, Some(std::time::Duration::from_millis(100)))
+        .unwrap();
+      events
+        .iter()
+        .fold(Ready::empty(), |ready, e| ready | e.readiness())
+    };
+    assert_eq!(poll_readiness(), Ready::writable());
+    assert_eq!(data_writer.acknowledged_before().unwrap(), None);
+
+    // The Writer signals acknowledgments.
+    data_writer.acked_by_all_before.store(3, Ordering::Relaxed);
+    data_writer
+      .ack_readiness
+      .set_readiness(Ready::readable() | Ready::writable())
+      .unwrap();
+    assert_eq!(poll_readiness(), Ready::readable() | Ready::writable());
+    assert_eq!(
+      data_writer.acknowledged_before().unwrap(),
+      Some(SequenceNumber::from(3))
+    );
+    assert_eq!(poll_readiness(), Ready::writable());
+  }
+
+  #[test]
+  fn dw_batch_max_size() {
+    use crate::dds::qos::{policy, QosPolicyBuilder};
+
+    let domain_participant = DomainParticipant::new(0).expect("Publisher creation failed!");
+    let qos = QosPolicyBuilder::new()
+      .latency_budget(policy::LatencyBudget {
+        duration: DDSDuration::from_millis(100),
+      })
+      .build();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic(
+        "batch_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+    let data_writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(&topic, None)
+        .expect("Failed to create datawriter");
+
+    assert!(data_writer.set_batch_max_size(0).is_err());
+    assert!(data_writer.set_batch_max_size(1024 * 1024).is_err());
+    data_writer.set_batch_max_size(1024).unwrap();
+    data_writer
+      .write(
+        RandomData {
+          a: 4,
+          b: "Fobar".to_string(),
+        },
+        None,
+      )
+      .unwrap();
+    data_writer.flush().unwrap();
+  }
+
+  #[test]
+  fn dw_readers_filtering_out() {
+    let domain_participant = DomainParticipant::new(0).expect("Publisher creation failed!");
+    let qos = QosPolicies::qos_none();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic(
+        "reader_filter_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+    let data_writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(&topic, None)
+        .expect("Failed to create datawriter");
+    let data = |a| RandomData {
+      a,
+      b: "Fobar".to_string(),
+    };
+    let filtering_out = |sample: &RandomData, serialized: &RandomData| {
+      let bytes = to_bytes::<RandomData, LittleEndian>(serialized).unwrap();
+      data_writer
+        .readers_filtering_out(sample, RepresentationIdentifier::CDR_LE, &bytes)
+        .into_iter()
+        .collect::<Vec<_>>()
+    };
+    assert!(filtering_out(&data(1), &data(1)).is_empty());
+
+    // As if the Writer had matched a reader of a ContentFilteredTopic
+    let reader = GUID::dummy_test_guid(EntityKind::READER_WITH_KEY_USER_DEFINED);
+    data_writer.reader_filters.write().unwrap().insert(
+      reader,
+      SampleFilter::new("a > %0", &["3".to_string()]).unwrap(),
+    );
+    assert!(filtering_out(&data(4), &data(4)).is_empty());
+    assert_eq!(filtering_out(&data(2), &data(2)), vec![reader]);
+
+    // With the DynamicType of the Topic, only the serialized sample is looked
+    // at.
+    let mut projected = SampleFilter::new("a > %0", &["3".to_string()]).unwrap();
+    projected.set_dynamic_type(&DynamicType::structure(
+      "RandomData",
+      vec![
+        DynamicMember::new("a", DynamicType::I64),
+        DynamicMember::new("b", DynamicType::String),
+      ],
+    ));
+    data_writer
+      .reader_filters
+      .write()
+      .unwrap()
+      .insert(reader, projected);
+    assert!(filtering_out(&data(2), &data(4)).is_empty());
+    assert_eq!(filtering_out(&data(4), &data(2)), vec![reader]);
+  }
+
   #[test]
   fn dw_dispose_test() {
     let domain_participant = DomainParticipant::new(0).expect("Publisher creation failed!");
@@ -1111,4 +2501,214 @@ mod tests {
     assert!(res); // we should get "true" immediately, because we have
                   // no Reliable QoS
   }
+  #[test]
+  fn dw_set_qos_test() {
+    let domain_participant = DomainParticipant::new(0).expect("Publisher creation failed!");
+    let qos = QosPolicies::qos_none();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic(
+        "SetQosTopic".to_string(),
+        "Huh?".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+
+    let writer_qos = QosPolicies::builder()
+      .reliability(Reliability::BestEffort)
+      .build();
+    let mut data_writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(&topic, Some(writer_qos))
+        .expect("Failed to create datawriter");
+    assert_eq!(data_writer.qos().reliability, Some(Reliability::BestEffort));
+
+    // Deadline can be changed.
+    let deadline = Deadline(DDSDuration::from_secs(1));
+    data_writer
+      .set_qos(&QosPolicies::builder().deadline(deadline).build())
+      .expect("Deadline change failed");
+    assert_eq!(data_writer.qos().deadline, Some(deadline));
+    assert_eq!(data_writer.qos().reliability, Some(Reliability::BestEffort));
+
+    // Reliability cannot.
+    let reliable = QosPolicies::builder()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: DDSDuration::DURATION_ZERO,
+      })
+      .build();
+    assert!(matches!(
+      data_writer.set_qos(&reliable),
+      Err(Error::ImmutablePolicy)
+    ));
+    assert_eq!(data_writer.qos().reliability, Some(Reliability::BestEffort));
+
+    // Discovery announces the new QoS.
+    let announced = |dp: &DomainParticipant| {
+      dp.discovery_db()
+        .read()
+        .unwrap()
+        .get_all_local_topic_writers()
+        .find(|dwd| dwd.writer_proxy.remote_writer_guid == data_writer.guid())
+        .and_then(|dwd| dwd.publication_topic_data.deadline)
+    };
+    for _ in 0..20 {
+      if announced(&domain_participant).is_some() {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(announced(&domain_participant), Some(deadline));
+  }
+
+  #[cfg(feature = "testing")]
+  #[test]
+  fn dw_skip_sequence_numbers_test() {
+    let domain_participant = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicies::qos_none();
+    let publisher = domain_participant.create_publisher(&qos).unwrap();
+    let topic = domain_participant
+      .create_topic(
+        "skip_sn_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let data_writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher.create_datawriter(&topic, None).unwrap();
+    let data = RandomData {
+      a: 4,
+      b: "Fobar".to_string(),
+    };
+
+    assert!(data_writer
+      .set_initial_sequence_number(SequenceNumber::from(0))
+      .is_err());
+    data_writer
+      .set_initial_sequence_number(SequenceNumber::from(100))
+      .unwrap();
+    let sample_id = data_writer
+      .write_with_options(data.clone(), WriteOptions::default())
+      .unwrap();
+    assert_eq!(sample_id.sequence_number, SequenceNumber::from(100));
+
+    assert!(data_writer
+      .set_initial_sequence_number(SequenceNumber::from(1))
+      .is_err());
+    assert!(data_writer.skip_sequence_numbers(0).is_err());
+    data_writer.skip_sequence_numbers(5).unwrap();
+    let sample_id = data_writer
+      .write_with_options(data, WriteOptions::default())
+      .unwrap();
+    assert_eq!(sample_id.sequence_number, SequenceNumber::from(106));
+  }
+
+  #[cfg(feature = "testing")]
+  #[test]
+  fn dw_skip_sequence_numbers_reliable_test() {
+    use crate::{
+      dds::qos::{policy, QosPolicyBuilder},
+      test::loopback::loopback_participant,
+    };
+
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: DDSDuration::DURATION_ZERO,
+      })
+      .durability(policy::Durability::TransientLocal)
+      .history(policy::History::KeepAll)
+      .build();
+    let topic = |participant: &DomainParticipant| {
+      participant
+        .create_topic(
+          "skip_sn_reliable_test".to_string(),
+          "RandomData".to_string(),
+          &qos,
+          TopicKind::WithKey,
+        )
+        .unwrap()
+    };
+    let writer_participant = loopback_participant(221);
+    let data_writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      writer_participant
+        .create_publisher(&qos)
+        .unwrap()
+        .create_datawriter(&topic(&writer_participant), None)
+        .unwrap();
+    let reader_participant = loopback_participant(221);
+    let mut data_reader = reader_participant
+      .create_subscriber(&qos)
+      .unwrap()
+      .create_datareader_cdr::<RandomData>(&topic(&reader_participant), None)
+      .unwrap();
+
+    data_writer
+      .set_initial_sequence_number(SequenceNumber::from(1000))
+      .unwrap();
+    for a in 0..3 {
+      data_writer.skip_sequence_numbers(3).unwrap();
+      data_writer
+        .write(
+          RandomData {
+            a,
+            b: "skip".to_string(),
+          },
+          None,
+        )
+        .unwrap();
+    }
+
+    let mut received = Vec::new();
+    for _ in 0..100 {
+      while let Some(sample) = data_reader.take_next_sample().unwrap() {
+        received.push(sample.into_value().unwrap().a);
+      }
+      if received.len() == 3 {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(received, vec![0, 1, 2]);
+    assert!(data_writer
+      .wait_for_acknowledgments(Duration::from_secs(5))
+      .unwrap());
+  }
+
+  #[test]
+  fn pending_acknowledgments_from_progress() {
+    let writer = GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED);
+    let reader = GUID::dummy_test_guid(EntityKind::READER_WITH_KEY_USER_DEFINED);
+    let mut progress = AckProgress {
+      wait_until: SequenceNumber::from(5),
+      readers_pending: BTreeMap::new(),
+    };
+    progress
+      .readers_pending
+      .insert(reader, SequenceNumber::from(3));
+
+    let pending = PendingAcknowledgments::from_progress(writer, &progress);
+    assert!(!pending.all_acknowledged());
+    assert_eq!(pending.readers().collect::<Vec<_>>(), vec![reader]);
+    let range = pending.unacknowledged_by(reader).unwrap();
+    assert_eq!(
+      (range.begin(), range.end()),
+      (SequenceNumber::from(3), SequenceNumber::from(5))
+    );
+    assert!(pending.unacknowledged_by(writer).is_none());
+
+    let sample = |sn: i64| SampleIdentity {
+      writer_guid: writer,
+      sequence_number: SequenceNumber::from(sn),
+    };
+    assert!(!pending.is_unacknowledged(&sample(2)));
+    assert!(pending.is_unacknowledged(&sample(3)));
+    assert!(pending.is_unacknowledged(&sample(5)));
+    assert!(!pending.is_unacknowledged(&sample(6)));
+
+    assert!(PendingAcknowledgments::none(writer).all_acknowledged());
+  }
 }
@@ -1,47 +1,62 @@
 use std::{
+  borrow::Cow,
+  cell::{Cell, RefCell},
   cmp::max,
-  collections::{BTreeMap, BTreeSet, HashSet},
+  collections::{BTreeMap, BTreeSet, HashSet, VecDeque},
   iter::FromIterator,
   ops::Bound::Included,
   rc::Rc,
-  sync::{Arc, RwLock},
+  sync::{
+    atomic::{AtomicI64, Ordering},
+    Arc, Mutex, RwLock,
+  },
+  time::Instant,
 };
 
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 use speedy::{Endianness, Writable};
-use mio_extras::{
-  channel::{self as mio_channel, SyncSender, TrySendError},
-  timer::Timer,
-};
-use mio::Token;
+use mio_extras::channel::{self as mio_channel, SyncSender, TrySendError};
+use mio::{Ready, SetReadiness, Token};
 use policy::{History, Reliability};
 
 use crate::{
   dds::{
+    contentfilteredtopic::filter_from_property,
     ddsdata::DDSData,
     dp_event_loop::{NACK_RESPONSE_DELAY, NACK_SUPPRESSION_DURATION},
+    dynamic_data::DynamicType,
+    flow_controller::FlowController,
+    helpers::WakerSlot,
+    pipeline_tracing,
     qos::HasQoSPolicy,
+    query_expression::SampleFilter,
+    runtime_metrics::{self, LocalEndpoint},
+    statistics::WriterCounters,
+    timer::{Timeout, Timer},
+    traits::key::KeyHash,
     with_key::datawriter::WriteOptions,
   },
   messages::submessages::submessages::AckSubmessage,
-  network::udp_sender::UDPSender,
+  network::transport::Transports,
   serialization::{Message, MessageBuilder},
   structure::{
-    cache_change::CacheChange,
+    cache_change::{CacheChange, ChangeKind},
     dds_cache::DDSCache,
     duration::Duration,
     entity::RTPSEntity,
     guid::{EntityId, GuidPrefix, GUID},
     locator::Locator,
-    sequence_number::{FragmentNumber, SequenceNumber},
+    sequence_number::{FragmentNumber, SequenceNumber, SequenceNumberRange},
     time::Timestamp,
   },
 };
+#[cfg(feature = "security")]
+use crate::security::plugins::CryptographyHandle;
 use super::{
   qos::{policy, QosPolicies},
   rtps_reader_proxy::RtpsReaderProxy,
-  statusevents::{CountWithChange, DataWriterStatus},
+  statusevents::{CountWithChange, DataWriterStatus, IncompatibleQosTracker},
 };
 
 #[derive(PartialEq, Eq, Clone, Copy)]
@@ -56,6 +71,9 @@ pub(crate) enum TimedEvent {
   CacheCleaning,
   SendRepairData { to_reader: GUID },
   SendRepairFrags { to_reader: GUID },
+  FlushDataBatch,
+  FlowControl,
+  LifespanExpiry,
 }
 
 // This is used to construct an actual Writer.
@@ -66,6 +84,22 @@ pub(crate) struct WriterIngredients {
   pub topic_name: String,
   pub qos_policies: QosPolicies,
   pub status_sender: SyncSender<DataWriterStatus>,
+  // Sequence number before which all matched reliable readers have
+  // acknowledged everything, or i64::MAX if there are none. The DataWriter
+  // uses this to block while its KEEP_ALL history is full.
+  pub acked_by_all_before: Arc<AtomicI64>,
+  // Set readable and writable when acked_by_all_before advances, for
+  // DataWriters registered in an application event loop.
+  pub ack_readiness: SetReadiness,
+  // Woken at the same time, for async writes waiting for history space.
+  pub ack_waker: WakerSlot,
+  // Content filters of the matched readers that have one. The DataWriter
+  // evaluates these on each sample.
+  pub reader_filters: Arc<RwLock<BTreeMap<GUID, SampleFilter>>>,
+  // Type of the Topic from the TypeRegistry. With this, reader_filters are
+  // evaluated on serialized samples.
+  pub dynamic_type: Option<Arc<DynamicType>>,
+  pub flow_controller: Option<FlowController>,
 }
 
 impl WriterIngredients {
@@ -93,6 +127,13 @@ pub(crate) struct Writer {
   ///availability of data by sending a
   ///Heartbeat Message.
   pub heartbeat_period: Option<Duration>,
+  // The pending periodic Heartbeat, so that it can be rescheduled when the
+  // period changes
+  heartbeat_timeout: Option<Timeout>,
+  // HEARTBEAT is piggybacked to every this many DATA messages. Zero means
+  // never, and then only the periodic Heartbeat announces new data.
+  piggyback_heartbeat_every: u32,
+  data_messages_since_heartbeat: u32,
   /// duration to launch cahche change remove from DDSCache
   pub cache_cleaning_period: Duration,
   ///Protocol tuning parameter that
@@ -137,9 +178,9 @@ pub(crate) struct Writer {
   /// StatefulWriter maintains on each matched RTPS Reader
   readers: BTreeMap<GUID, RtpsReaderProxy>, // TODO: Convert to BTreeMap for faster finds.
   matched_readers_count_total: i32, // all matches, never decremented
-  requested_incompatible_qos_count: i32, // how many times a Reader requested incompatible QoS
+  incompatible_qos: IncompatibleQosTracker, // Readers that requested incompatible QoS
   //message: Option<Message>,
-  udp_sender: Rc<UDPSender>,
+  transports: Rc<Transports>,
 
   // This writer can read/write to only one of this DDSCache topic caches identified with
   // my_topic_name
@@ -151,6 +192,12 @@ pub(crate) struct Writer {
   /// Useful when negative acknack is received.
   sequence_number_to_instant: BTreeMap<SequenceNumber, Timestamp>,
 
+  /// Lifespan expiry of samples in the history, on the monotonic clock, so
+  /// that wall clock jumps do not expire them early or keep them too long.
+  lifespan_expiries: BTreeMap<SequenceNumber, Instant>,
+  /// When the lifespan timer fires next
+  lifespan_timeout: Option<Instant>,
+
   /// Maps this writers local sequence numbers to DDSHistodyCache instants.
   /// Useful when datawriter dispose is received.
   //key_to_instant: HashMap<u128, Timestamp>,  // unused?
@@ -160,6 +207,14 @@ pub(crate) struct Writer {
   // TODO: Apparently, this is never updated.
   disposed_sequence_numbers: HashSet<SequenceNumber>,
 
+  /// Sequence numbers skipped on DataWriter request. Readers asking for these
+  /// get a GAP.
+  skipped_sequence_numbers: Vec<SequenceNumberRange>,
+
+  // Instances of a keyed topic that have been written, but not disposed or
+  // unregistered. These are unregistered when the participant shuts down.
+  live_instances: BTreeSet<KeyHash>,
+
   //When dataWriter sends cacheChange message with cacheKind is NotAliveDisposed
   //this is set true. If Datawriter after disposing sends new cahceChanges this falg is then
   //turned true.
@@ -176,7 +231,42 @@ pub(crate) struct Writer {
   // Used for sending status info about messages sent
   status_sender: SyncSender<DataWriterStatus>,
   //offered_deadline_status: OfferedDeadlineMissedStatus,
-  ack_waiter: Option<AckWaiter>,
+  ack_waiters: Vec<AckWaiter>,
+  acked_by_all_before: Arc<AtomicI64>,
+  ack_readiness: SetReadiness,
+  ack_waker: WakerSlot,
+  reader_filters: Arc<RwLock<BTreeMap<GUID, SampleFilter>>>,
+  dynamic_type: Option<Arc<DynamicType>>,
+  // Samples that are not sent to some readers due to their content filter.
+  // Such readers get a GAP instead.
+  filtered_out: BTreeMap<SequenceNumber, BTreeSet<GUID>>,
+
+  // DATA waiting to be sent in one message, as allowed by LATENCY_BUDGET.
+  // These are DDSCache keys.
+  data_batch: Vec<Timestamp>,
+  data_batch_size: usize, // sum of payload sizes in data_batch
+  // The batch is sent out when it reaches this size.
+  data_batch_max_size: usize,
+
+  // Messages that wait for the flow controller, with their destinations.
+  // These are queued while sending, so they are behind a RefCell.
+  flow_controller: Option<FlowController>,
+  flow_queue: RefCell<VecDeque<(Vec<u8>, Vec<Locator>)>>,
+  // How long the first message in flow_queue must wait, if no FlowControl
+  // timed event has been set for it yet
+  flow_wait: Cell<Option<std::time::Duration>>,
+  flow_timer_set: bool,
+
+  // Participant statistics of this Writer
+  pub(crate) counters: Arc<WriterCounters>,
+
+  // DDS Security protection of outgoing messages and payloads. Protected
+  // payloads are kept by sequence number, so that repairs and all fragments
+  // of a sample are sent from the same encoding.
+  #[cfg(feature = "security")]
+  cryptography: Option<CryptographyHandle>,
+  #[cfg(feature = "security")]
+  protected_payloads: RefCell<BTreeMap<SequenceNumber, DDSData>>,
 }
 
 pub(crate) enum WriterCommand {
@@ -184,24 +274,104 @@ pub(crate) enum WriterCommand {
     ddsdata: DDSData,
     write_options: WriteOptions,
     sequence_number: SequenceNumber,
+    // Readers whose content filter rejects this sample
+    filtered_out: BTreeSet<GUID>,
   },
   WaitForAcknowledgments {
     all_acked: mio_channel::SyncSender<()>,
+    // Woken along with the all_acked notification, for async tasks.
+    all_acked_waker: WakerSlot,
+    progress: Arc<Mutex<AckProgress>>,
+  },
+  // Sequence numbers are not used for any sample, and are announced with GAP.
+  // For protocol testing only.
+  #[cfg_attr(not(feature = "testing"), allow(dead_code))]
+  SkipSequenceNumbers {
+    range: SequenceNumberRange,
+  },
+  SetDataBatchMaxSize {
+    max_size: usize,
+  },
+  SetFlowController {
+    flow_controller: Option<FlowController>,
+  },
+  // Send out batched DATA now.
+  FlushDataBatch,
+  SetHeartbeatPeriod {
+    period: std::time::Duration,
+  },
+  SetNackResponseDelay {
+    delay: std::time::Duration,
+  },
+  SetPiggybackHeartbeatEvery {
+    count: u32,
   },
   //ResetOfferedDeadlineMissedStatus { writer_guid: GUID },
 }
 
+// Progress of a wait for acknowledgments. This is shared with the waiting
+// DataWriter, which reports it on timeout.
+#[derive(Debug, Default)]
+pub(crate) struct AckProgress {
+  pub wait_until: SequenceNumber,
+  // Reliable readers that have not acknowledged everything up to wait_until,
+  // and the sequence number before which each has acknowledged everything.
+  pub readers_pending: BTreeMap<GUID, SequenceNumber>,
+}
+
 struct AckWaiter {
-  wait_until: SequenceNumber,
+  progress: Arc<Mutex<AckProgress>>,
   complete_channel: SyncSender<()>,
-  readers_pending: BTreeSet<GUID>,
+  complete_waker: WakerSlot,
+}
+
+impl AckWaiter {
+  fn is_complete(&self) -> bool {
+    self.progress.lock().unwrap().readers_pending.is_empty()
+  }
+
+  // If the DataWriter no longer holds the progress, it has stopped waiting.
+  fn is_awaited(&self) -> bool {
+    Arc::strong_count(&self.progress) > 1
+  }
+
+  fn notify_complete(&self) {
+    // it is normal for the send to fail, because receiver may have timed out
+    let _ = self.complete_channel.try_send(());
+    self.complete_waker.wake();
+  }
 }
 
 impl Writer {
+  // Batched DATA is sent out at the latest when it reaches this size, so that
+  // messages stay well below the UDP datagram limit.
+  pub const DEFAULT_DATA_BATCH_MAX_SIZE: usize = 16 * 1024;
+  // Larger batches would not fit in a UDP datagram.
+  pub const DATA_BATCH_MAX_SIZE_LIMIT: usize = 60 * 1024;
+  // Unregister DATA submessages with a key hash are less than 100 bytes
+  // each, so this many fit in a message.
+  const UNREGISTER_BATCH_SIZE: usize = 100;
+
+  // Room for everything else in a message with a DATA_FRAG: RTPS header,
+  // INFO_DST, INFO_TS, DATA_FRAG submessage header and fixed fields, inline
+  // QoS, and encapsulation header. Rounded up.
+  const MESSAGE_OVERHEAD: usize = 128;
+  // Smaller fragments would only waste messages.
+  const MIN_FRAGMENT_SIZE: usize = 256;
+
+  // Fragments must fit in a message of any of our transports. The size is
+  // fixed for the lifetime of the Writer.
+  fn fragment_size(transports: &Transports) -> usize {
+    transports
+      .max_message_size()
+      .saturating_sub(Self::MESSAGE_OVERHEAD)
+      .clamp(Self::MIN_FRAGMENT_SIZE, u16::MAX.into())
+  }
+
   pub fn new(
     i: WriterIngredients,
     dds_cache: Arc<RwLock<DDSCache>>,
-    udp_sender: Rc<UDPSender>,
+    transports: Rc<Transports>,
     mut timed_event_timer: Timer<TimedEvent>,
   ) -> Self {
     let heartbeat_period = i
@@ -230,9 +400,9 @@ impl Writer {
     let cache_cleaning_period = Duration::from_secs(2 * 60);
 
     // Start periodic Heartbeat
-    if let Some(period) = heartbeat_period {
-      timed_event_timer.set_timeout(std::time::Duration::from(period), TimedEvent::Heartbeat);
-    }
+    let heartbeat_timeout = heartbeat_period.map(|period| {
+      timed_event_timer.set_timeout(std::time::Duration::from(period), TimedEvent::Heartbeat)
+    });
     // start periodic cache cleaning
     timed_event_timer.set_timeout(
       std::time::Duration::from(cache_cleaning_period),
@@ -244,33 +414,70 @@ impl Writer {
       heartbeat_message_counter: 1,
       push_mode: true,
       heartbeat_period,
+      heartbeat_timeout,
+      piggyback_heartbeat_every: 1,
+      data_messages_since_heartbeat: 0,
       cache_cleaning_period,
       nack_response_delay: NACK_RESPONSE_DELAY, // default value from dp_event_loop
       nackfrag_response_delay: NACK_RESPONSE_DELAY, // default value from dp_event_loop
       nack_suppression_duration: NACK_SUPPRESSION_DURATION,
       first_change_sequence_number: SequenceNumber::from(1), // first = 1, last = 0
       last_change_sequence_number: SequenceNumber::from(0),  // means we have nothing to write
-      data_max_size_serialized: 1024,
-      // ^^ TODO: Maybe a smarter selection would be in order.
-      // We should get the minimum over all outgoing interfaces.
+      data_max_size_serialized: Self::fragment_size(&transports),
       my_guid: i.guid,
       writer_command_receiver: i.writer_command_receiver,
       readers: BTreeMap::new(),
       matched_readers_count_total: 0,
-      requested_incompatible_qos_count: 0,
-      udp_sender,
+      incompatible_qos: IncompatibleQosTracker::new(),
+      transports,
       dds_cache,
       my_topic_name: i.topic_name,
       sequence_number_to_instant: BTreeMap::new(),
+      lifespan_expiries: BTreeMap::new(),
+      lifespan_timeout: None,
       disposed_sequence_numbers: HashSet::new(),
+      skipped_sequence_numbers: Vec::new(),
+      live_instances: BTreeSet::new(),
       timed_event_timer,
       qos_policies: i.qos_policies,
       status_sender: i.status_sender,
       //offered_deadline_status: OfferedDeadlineMissedStatus::new(),
-      ack_waiter: None,
+      ack_waiters: Vec::new(),
+      acked_by_all_before: i.acked_by_all_before,
+      ack_readiness: i.ack_readiness,
+      ack_waker: i.ack_waker,
+      reader_filters: i.reader_filters,
+      dynamic_type: i.dynamic_type,
+      filtered_out: BTreeMap::new(),
+      data_batch: Vec::new(),
+      data_batch_size: 0,
+      data_batch_max_size: Self::DEFAULT_DATA_BATCH_MAX_SIZE,
+      flow_controller: i.flow_controller,
+      flow_queue: RefCell::new(VecDeque::new()),
+      flow_wait: Cell::new(None),
+      flow_timer_set: false,
+      counters: Arc::new(WriterCounters::default()),
+      #[cfg(feature = "security")]
+      cryptography: None,
+      #[cfg(feature = "security")]
+      protected_payloads: RefCell::new(BTreeMap::new()),
     }
   }
 
+  // Generates the keys of this Writer, if its topic is protected.
+  #[cfg(feature = "security")]
+  pub fn set_cryptography(&mut self, cryptography: CryptographyHandle) {
+    cryptography
+      .register_local_endpoint(self.my_guid, &self.my_topic_name)
+      .unwrap_or_else(|e| error!("Cannot generate keys for {:?}: {}", self.my_guid, e));
+    // Leave room for the protection of payloads and submessages.
+    self.data_max_size_serialized = self
+      .data_max_size_serialized
+      .saturating_sub(cryptography.protection_overhead())
+      .max(Self::MIN_FRAGMENT_SIZE);
+    self.cryptography = Some(cryptography);
+  }
+
   /// To know when token represents a writer we should look entity attribute
   /// kind this entity token can be used in DataWriter -> Writer mio::channel.
   pub fn entity_token(&self) -> Token {
@@ -319,11 +526,17 @@ impl Writer {
           self.handle_heartbeat_tick(false);
           // ^^ false = This is automatic heartbeat by timer, not manual by application
           // call.
-          if let Some(period) = self.heartbeat_period {
-            self
-              .timed_event_timer
-              .set_timeout(std::time::Duration::from(period), TimedEvent::Heartbeat);
-          }
+          self.heartbeat_timeout = None; // fired already, nothing to cancel
+          self.schedule_heartbeat();
+        }
+        TimedEvent::FlushDataBatch => self.flush_data_batch(),
+        TimedEvent::FlowControl => {
+          self.flow_timer_set = false;
+          self.send_flow_queue();
+        }
+        TimedEvent::LifespanExpiry => {
+          self.lifespan_timeout = None;
+          self.remove_expired_changes();
         }
         TimedEvent::CacheCleaning => {
           self.handle_cache_cleaning();
@@ -370,8 +583,207 @@ impl Writer {
         } // SendRepairFrags
       } // match
     } // while
+    self.schedule_flow_control();
   } // fn
 
+  // Without the "fragmentation" feature, large samples are sent as a single
+  // DATA submessage, and it is up to the transport whether that fits.
+  fn fragmentation_needed(&self, payload_size: usize) -> bool {
+    cfg!(feature = "fragmentation") && payload_size > self.data_max_size_serialized
+  }
+
+  // LATENCY_BUDGET > 0 means that the application tolerates some delay in
+  // delivery. We use that to collect several DATA submessages into one RTPS
+  // message. In pull mode there is nothing to batch.
+  fn batching_delay(&self) -> Option<std::time::Duration> {
+    match self.qos_policies.latency_budget {
+      Some(policy::LatencyBudget { duration })
+        if self.push_mode && duration > Duration::DURATION_ZERO =>
+      {
+        Some(duration.to_std())
+      }
+      _ => None,
+    }
+  }
+
+  fn add_to_data_batch(&mut self, timestamp: Timestamp, delay: std::time::Duration) {
+    if self.data_batch.is_empty() {
+      // First sample in batch: it must go out within the latency budget.
+      self
+        .timed_event_timer
+        .set_timeout(delay, TimedEvent::FlushDataBatch);
+    }
+    self.data_batch.push(timestamp);
+    self.data_batch_size += self
+      .dds_cache
+      .read()
+      .unwrap()
+      .topic_get_change(&self.my_topic_name, &timestamp)
+      .map_or(0, |cc| cc.data_value.payload_size());
+
+    if self.data_batch_size >= self.data_batch_max_size {
+      self.flush_data_batch();
+    }
+  }
+
+  fn flush_data_batch(&mut self) {
+    if !self.data_batch.is_empty() {
+      let batch = std::mem::take(&mut self.data_batch);
+      self.data_batch_size = 0;
+      self.send_data_and_heartbeat(&batch);
+    }
+  }
+
+  // Sends DATA submessages for the given changes (if we are in push mode)
+  // followed by one HEARTBEAT, all in one RTPS message. The HEARTBEAT is left
+  // out, if it is not yet due according to piggyback_heartbeat_every.
+  //
+  // Readers whose content filter rejects some of the changes get a message of
+  // their own, where those changes are replaced by a GAP.
+  fn send_data_and_heartbeat(&mut self, timestamps: &[Timestamp]) {
+    let filtering_readers = if self.push_mode {
+      self.readers_filtering_out(timestamps)
+    } else {
+      BTreeSet::new()
+    };
+    // Without push mode, the HEARTBEAT is all we send.
+    let with_heartbeat = !self.push_mode || self.piggyback_heartbeat_due();
+
+    let final_flag = false; // false = request that readers acknowledge with ACKNACK.
+    let liveliness_flag = false; // This is not a manual liveliness assertion (DDS API call), but side-effect of
+                                 // writing new data.
+    let mut message_builder = self.data_msgs(MessageBuilder::new(), timestamps, None);
+    if with_heartbeat {
+      message_builder =
+        message_builder.heartbeat_msg(self, EntityId::UNKNOWN, final_flag, liveliness_flag);
+    }
+    let data_hb_message = message_builder.add_header_and_build(self.my_guid.prefix);
+    self.send_message_to_readers(
+      DeliveryMode::Multicast,
+      &data_hb_message,
+      &mut self
+        .readers
+        .values()
+        .filter(|r| !filtering_readers.contains(&r.remote_reader_guid)),
+    );
+
+    for reader_guid in filtering_readers {
+      if let Some(reader_proxy) = self.readers.get(&reader_guid) {
+        let partial_message =
+          MessageBuilder::new().dst_submessage(self.endianness, reader_guid.prefix);
+        let mut message_builder = self.data_msgs(partial_message, timestamps, Some(reader_guid));
+        if with_heartbeat {
+          message_builder =
+            message_builder.heartbeat_msg(self, reader_guid.entity_id, final_flag, liveliness_flag);
+        }
+        let reader_message = message_builder.add_header_and_build(self.my_guid.prefix);
+        self.send_message_to_readers(
+          DeliveryMode::Unicast,
+          &reader_message,
+          &mut std::iter::once(reader_proxy),
+        );
+      }
+    }
+  }
+
+  // Counts DATA messages, and tells if this one should carry a HEARTBEAT.
+  fn piggyback_heartbeat_due(&mut self) -> bool {
+    if self.piggyback_heartbeat_every == 0 {
+      return false;
+    }
+    self.data_messages_since_heartbeat += 1;
+    if self.data_messages_since_heartbeat >= self.piggyback_heartbeat_every {
+      self.data_messages_since_heartbeat = 0;
+      true
+    } else {
+      false
+    }
+  }
+
+  // Adds DATA submessages for the given changes, if we are in push mode.
+  // If a reader is given, the changes its content filter rejects are
+  // announced with GAP instead.
+  fn data_msgs(
+    &self,
+    mut message_builder: MessageBuilder,
+    timestamps: &[Timestamp],
+    reader_guid: Option<GUID>,
+  ) -> MessageBuilder {
+    if !self.push_mode {
+      // Not pushing: Send only HEARTBEAT. Send DATA only after readers
+      // ACKNACK asking for it.
+      return message_builder;
+    }
+    // If we are in push mode, proactively send DATA submessage along with
+    // HEARTBEAT.
+    let mut filtered_out = BTreeSet::new();
+    // The source timestamp in effect in the message, as the reader sees it
+    let mut message_timestamp = None;
+    let dds_cache = self.dds_cache.read().unwrap();
+    for timestamp in timestamps {
+      if let Some(cache_change) = dds_cache.topic_get_change(&self.my_topic_name, timestamp) {
+        if let Some(guid) = reader_guid {
+          if self.is_filtered_out(cache_change.sequence_number, guid) {
+            filtered_out.insert(cache_change.sequence_number);
+            continue;
+          }
+        }
+        let cache_change = match self.outgoing_change(cache_change) {
+          Some(cache_change) => cache_change,
+          None => continue,
+        };
+        // If DataWriter sent us a source timestamp, then add that.
+        // Timestamp has to go before Data to have effect on Data. It also
+        // applies to the Data that follow, so a change without a source
+        // timestamp invalidates it.
+        let source_timestamp = cache_change.write_options.source_timestamp;
+        if source_timestamp != message_timestamp {
+          message_builder = message_builder.ts_msg(self.endianness, source_timestamp);
+          message_timestamp = source_timestamp;
+        }
+        message_builder = message_builder.data_msg(
+          &cache_change,
+          reader_guid.map_or(EntityId::UNKNOWN, |guid| guid.entity_id), // reader
+          self.my_guid.entity_id,                                       // writer
+          self.endianness,
+        );
+      } else {
+        // We just did .insert_to_history_cache but nothing was found?
+        error!(
+          "send_data_and_heartbeat: The dog ate my CacheChange {:?} topic={:?}",
+          timestamp,
+          self.topic_name(),
+        );
+      }
+    }
+    match reader_guid {
+      Some(guid) if !filtered_out.is_empty() => message_builder.gap_msg(&filtered_out, self, guid),
+      _ => message_builder,
+    }
+  }
+
+  // Readers whose content filter rejects some of the given changes.
+  fn readers_filtering_out(&self, timestamps: &[Timestamp]) -> BTreeSet<GUID> {
+    if self.filtered_out.is_empty() {
+      return BTreeSet::new();
+    }
+    let dds_cache = self.dds_cache.read().unwrap();
+    timestamps
+      .iter()
+      .filter_map(|ts| dds_cache.topic_get_change(&self.my_topic_name, ts))
+      .filter_map(|cc| self.filtered_out.get(&cc.sequence_number))
+      .flatten()
+      .copied()
+      .collect()
+  }
+
+  fn is_filtered_out(&self, sequence_number: SequenceNumber, reader_guid: GUID) -> bool {
+    self
+      .filtered_out
+      .get(&sequence_number)
+      .map_or(false, |readers| readers.contains(&reader_guid))
+  }
+
   /// This is called by dp_wrapper everytime cacheCleaning message is received.
   fn handle_cache_cleaning(&mut self) {
     let resource_limit = 32; // TODO: This limit should be obtained
@@ -404,6 +816,7 @@ impl Writer {
           ddsdata,
           write_options,
           sequence_number,
+          filtered_out,
         } => {
           // We have a new sample here. Things to do:
           // 1. Insert it to history cache and get it sequence numbered
@@ -411,66 +824,31 @@ impl Writer {
           //    If we are pushing data, send the DATA submessage and HEARTBEAT.
           //    If we are not pushing, send out HEARTBEAT only. Readers will then ask for
           // the DATA with ACKNACK, if they are interested.
-          let fragmentation_needed = ddsdata.payload_size() > self.data_max_size_serialized;
+          self.counters.samples_written.increment();
+          let fragmentation_needed = self.fragmentation_needed(ddsdata.payload_size());
+          if !filtered_out.is_empty() {
+            self.filtered_out.insert(sequence_number, filtered_out);
+          }
           let timestamp =
             self.insert_to_history_cache(ddsdata, write_options.clone(), sequence_number);
 
           self.increase_heartbeat_counter();
 
           if !fragmentation_needed {
-            let mut message_builder = MessageBuilder::new();
-            // the beef: DATA submessage
-            if self.push_mode {
-              // If we are in push mode, proactively send DATA submessage along with
-              // HEARTBEAT.
-              if let Some(cache_change) = self
-                .dds_cache
-                .read()
-                .unwrap()
-                .topic_get_change(&self.my_topic_name, &timestamp)
-              {
-                // If DataWriter sent us a source timestamp, then add that.
-                // Timestamp has to go before Data to have effect on Data.
-                if let Some(src_ts) = cache_change.write_options.source_timestamp {
-                  message_builder = message_builder.ts_msg(self.endianness, Some(src_ts));
-                }
-                message_builder = message_builder.data_msg(
-                  cache_change,
-                  EntityId::UNKNOWN,      // reader
-                  self.my_guid.entity_id, // writer
-                  self.endianness,
-                );
-              } else {
-                // We just did .insert_to_history_cache but nothing was found?
-                error!(
-                  "process_writer_command: The dog ate my CacheChange {:?} topic={:?}",
-                  sequence_number,
-                  self.topic_name(),
-                );
-              }
-            } else {
-              // Not pushing: Send only HEARTBEAT. Send DATA only after readers
-              // ACKNACK asking for it.
-            };
-
-            let final_flag = false; // false = request that readers acknowledge with ACKNACK.
-            let liveliness_flag = false; // This is not a manual liveliness assertion (DDS API call), but side-effect of
-                                         // writing new data.
-            let data_hb_message = message_builder
-              .heartbeat_msg(self, EntityId::UNKNOWN, final_flag, liveliness_flag)
-              .add_header_and_build(self.my_guid.prefix);
-            self.send_message_to_readers(
-              DeliveryMode::Multicast,
-              &data_hb_message,
-              &mut self.readers.values(),
-            );
+            match self.batching_delay() {
+              Some(delay) => self.add_to_data_batch(timestamp, delay),
+              None => self.send_data_and_heartbeat(&[timestamp]),
+            }
           } else {
             // Large payload, must fragment.
+            // Send out any batched data first, to keep sending order.
+            self.flush_data_batch();
             if let Some(cache_change) = self
               .dds_cache
               .read()
               .unwrap()
               .topic_get_change(&self.my_topic_name, &timestamp)
+              .and_then(|cc| self.outgoing_change(cc))
             {
               let fragment_size: u32 = self.data_max_size_serialized as u32; //TODO: overflow check
               let data_size: u32 = cache_change.data_value.payload_size() as u32; //TODO: overflow check
@@ -489,7 +867,7 @@ impl Writer {
                   }
 
                   message_builder = message_builder.data_frag_msg(
-                    cache_change,
+                    &cache_change,
                     EntityId::UNKNOWN,      // reader
                     self.my_guid.entity_id, // writer
                     frag_num,
@@ -499,10 +877,15 @@ impl Writer {
                   );
 
                   // TODO: some sort of queuing is needed
+                  // Readers that filter this sample out are sent a GAP when
+                  // they ask for it.
                   self.send_message_to_readers(
                     DeliveryMode::Multicast,
                     &message_builder.add_header_and_build(self.my_guid.prefix),
-                    &mut self.readers.values(),
+                    &mut self
+                      .readers
+                      .values()
+                      .filter(|r| !self.is_filtered_out(sequence_number, r.remote_reader_guid)),
                   );
                 } // end for
               }
@@ -531,15 +914,19 @@ impl Writer {
         // WriterCommand::ResetOfferedDeadlineMissedStatus { writer_guid: _, } => {
         //   self.reset_offered_deadline_missed_status();
         // }
-        WriterCommand::WaitForAcknowledgments { all_acked } => {
+        WriterCommand::WaitForAcknowledgments {
+          all_acked,
+          all_acked_waker,
+          progress,
+        } => {
           let wait_until = self.last_change_sequence_number;
-          let readers_pending: BTreeSet<_> = self
+          let readers_pending: BTreeMap<_, _> = self
             .readers
             .iter()
             .filter_map(|(guid, rp)| {
-              if rp.qos().reliability().is_some() {
+              if matches!(rp.qos().reliability(), Some(Reliability::Reliable { .. })) {
                 if rp.all_acked_before <= wait_until {
-                  Some(*guid)
+                  Some((*guid, rp.all_acked_before))
                 } else {
                   None // already acked
                 }
@@ -548,26 +935,135 @@ impl Writer {
               }
             })
             .collect();
-          if readers_pending.is_empty() {
+          *progress.lock().unwrap() = AckProgress {
+            wait_until,
+            readers_pending,
+          };
+          let ack_waiter = AckWaiter {
+            progress,
+            complete_channel: all_acked,
+            complete_waker: all_acked_waker,
+          };
+          // Forget waits that the DataWriter has given up on.
+          self.ack_waiters.retain(AckWaiter::is_awaited);
+          if ack_waiter.is_complete() {
             // all acked already
-            let _ = all_acked.try_send(()); // may fail, if receiver has timeouted
-            self.ack_waiter = None;
+            ack_waiter.notify_complete();
           } else {
-            self.ack_waiter = Some(AckWaiter {
-              wait_until,
-              complete_channel: all_acked,
-              readers_pending,
-            });
+            self.ack_waiters.push(ack_waiter);
           }
         }
+        WriterCommand::SkipSequenceNumbers { range } => self.skip_sequence_numbers(range),
+        WriterCommand::SetDataBatchMaxSize { max_size } => {
+          self.data_batch_max_size = max_size;
+          if self.data_batch_size >= max_size {
+            self.flush_data_batch();
+          }
+        }
+        WriterCommand::FlushDataBatch => self.flush_data_batch(),
+        WriterCommand::SetHeartbeatPeriod { period } => {
+          // Best effort Writers do not send Heartbeats.
+          if self.heartbeat_period.is_some() {
+            self.heartbeat_period = Some(Duration::from_std(period));
+            self.schedule_heartbeat();
+          }
+        }
+        WriterCommand::SetNackResponseDelay { delay } => {
+          self.nack_response_delay = delay;
+          self.nackfrag_response_delay = delay;
+        }
+        WriterCommand::SetPiggybackHeartbeatEvery { count } => {
+          self.piggyback_heartbeat_every = count;
+          self.data_messages_since_heartbeat = 0;
+        }
+        WriterCommand::SetFlowController { flow_controller } => {
+          self.flow_controller = flow_controller;
+          // Without a flow controller, this sends out everything.
+          self.send_flow_queue();
+        }
       }
     }
+    self.schedule_flow_control();
+  }
+
+  // Tells readers that this Writer no longer updates its live instances, as
+  // if the DataWriter had unregistered each of them. This is done at
+  // participant shutdown, when the DataWriter cannot write anymore, so the
+  // next sequence numbers are ours to take.
+  pub fn unregister_all_instances(&mut self) {
+    if self.live_instances.is_empty() {
+      return;
+    }
+    // Keep sending order
+    self.flush_data_batch();
+    info!(
+      "Unregistering {} instances topic={:?}",
+      self.live_instances.len(),
+      self.my_topic_name
+    );
+    let timestamps: Vec<Timestamp> = std::mem::take(&mut self.live_instances)
+      .into_iter()
+      .map(|key_hash| {
+        let sequence_number = self.last_change_sequence_number + SequenceNumber::from(1);
+        self.insert_to_history_cache(
+          DDSData::new_disposed_by_key_hash(ChangeKind::NotAliveUnregistered, key_hash),
+          WriteOptions::from(Some(self.now())),
+          sequence_number,
+        )
+      })
+      .collect();
+    // Key hashes are small, but there may be many of them.
+    for batch in timestamps.chunks(Self::UNREGISTER_BATCH_SIZE) {
+      self.increase_heartbeat_counter();
+      self.send_data_and_heartbeat(batch);
+    }
+  }
+
+  // The skipped sequence numbers look like samples that have already been
+  // removed from history.
+  fn skip_sequence_numbers(&mut self, range: SequenceNumberRange) {
+    if range.is_empty() {
+      return;
+    }
+    // Keep sending order
+    self.flush_data_batch();
+
+    if self.last_change_sequence_number == SequenceNumber::zero() {
+      // Nothing written yet: just start numbering later.
+      self.first_change_sequence_number = range.end() + SequenceNumber::from(1);
+    } else {
+      self.skipped_sequence_numbers.push(range);
+    }
+    self.last_change_sequence_number = range.end();
+    info!(
+      "Skipping sequence numbers {:?}..={:?} topic={:?}",
+      range.begin(),
+      range.end(),
+      self.my_topic_name
+    );
+
+    let final_flag = false;
+    let liveliness_flag = false;
+    let gap_hb_message = MessageBuilder::new()
+      .gap_range_msg(range, self, EntityId::UNKNOWN)
+      .heartbeat_msg(self, EntityId::UNKNOWN, final_flag, liveliness_flag)
+      .add_header_and_build(self.my_guid.prefix);
+    self.send_message_to_readers(
+      DeliveryMode::Multicast,
+      &gap_hb_message,
+      &mut self.readers.values(),
+    );
+  }
+
+  // The time on the clock of the participant
+  fn now(&self) -> Timestamp {
+    self.timed_event_timer.time_source().now()
   }
 
   fn insert_to_history_cache(
     &mut self,
     data: DDSData,
-    write_options: WriteOptions,
+    mut write_options: WriteOptions,
     sequence_number: SequenceNumber,
   ) -> Timestamp {
     // first increasing last SequenceNumber
@@ -585,14 +1081,37 @@ impl Writer {
         max(self.first_change_sequence_number, SequenceNumber::from(1))
       }
 
+      // Samples removed due to lifespan expiry are not offered again.
       Some(History::KeepLast { depth }) => max(
         self.last_change_sequence_number - SequenceNumber::from(i64::from(depth - 1)),
-        SequenceNumber::from(1),
+        max(self.first_change_sequence_number, SequenceNumber::from(1)),
       ),
     };
     assert!(self.first_change_sequence_number > SequenceNumber::zero());
     assert!(self.last_change_sequence_number > SequenceNumber::zero());
 
+    if let Some(key_hash) = data.key_hash() {
+      match data.change_kind() {
+        ChangeKind::Alive => self.live_instances.insert(key_hash),
+        ChangeKind::NotAliveDisposed | ChangeKind::NotAliveUnregistered => {
+          self.live_instances.remove(&key_hash)
+        }
+      };
+    }
+
+    // With exclusive ownership, our strength goes to inline QoS, so that
+    // readers see changes to it without waiting for discovery.
+    if let Some(policy::Ownership::Exclusive { strength }) = self.qos_policies.ownership {
+      write_options.ownership_strength = Some(strength);
+    }
+
+    let expiry = self.qos_policies.lifespan.and_then(|lifespan| {
+      lifespan.expiry(
+        write_options.source_timestamp,
+        self.timed_event_timer.time_source(),
+      )
+    });
+
     // create new CacheChange from DDSData
     let new_cache_change = CacheChange::new(self.guid(), new_sequence_number, write_options, data);
 
@@ -609,6 +1128,10 @@ impl Writer {
     self
       .sequence_number_to_instant
       .insert(new_sequence_number, timestamp);
+    if let Some(expiry) = expiry {
+      self.lifespan_expiries.insert(new_sequence_number, expiry);
+      self.schedule_lifespan_expiry(expiry);
+    }
 
     // update key to timestamp mapping
     //self.key_to_instant.insert(data_key, timestamp);
@@ -624,8 +1147,25 @@ impl Writer {
   // --------------------------------------------------------------
   // --------------------------------------------------------------
 
+  // (Re)starts the periodic Heartbeat timer, which fires after
+  // heartbeat_period.
+  fn schedule_heartbeat(&mut self) {
+    if let Some(timeout) = self.heartbeat_timeout.take() {
+      self.timed_event_timer.cancel_timeout(&timeout);
+    }
+    if let Some(period) = self.heartbeat_period {
+      self.heartbeat_timeout = Some(
+        self
+          .timed_event_timer
+          .set_timeout(std::time::Duration::from(period), TimedEvent::Heartbeat),
+      );
+    }
+  }
+
   /// This is called periodically.
   pub fn handle_heartbeat_tick(&mut self, is_manual_assertion: bool) {
+    // Heartbeat announces all our changes, so do not keep any of them waiting.
+    self.flush_data_batch();
     // Reliable Stateless Writer will set the final flag.
     // Reliable Stateful Writer (that tracks Readers by ReaderProxy) will not set
     // the final flag.
@@ -650,7 +1190,7 @@ impl Writer {
       trace!("heartbeat tick: all readers have all available data.");
     } else {
       let hb_message = MessageBuilder::new()
-        .ts_msg(self.endianness, Some(Timestamp::now()))
+        .ts_msg(self.endianness, Some(self.now()))
         .heartbeat_msg(self, EntityId::UNKNOWN, final_flag, liveliness_flag)
         .add_header_and_build(self.my_guid.prefix);
       debug!(
@@ -665,16 +1205,20 @@ impl Writer {
         &mut self.readers.values(),
       );
     }
+    self.schedule_flow_control();
   }
 
   /// When receiving an ACKNACK Message indicating a Reader is missing some data
   /// samples, the Writer must respond by either sending the missing data
   /// samples, sending a GAP message when the sample is not relevant, or
   /// sending a HEARTBEAT message when the sample is no longer available
+  // reply_locators are from INFO_REPLY, if the reader sent one. Then we reply
+  // there from now on, instead of the locators known from Discovery.
   pub fn handle_ack_nack(
     &mut self,
     reader_guid_prefix: GuidPrefix,
     ack_submessage: &AckSubmessage,
+    reply_locators: &[Locator],
   ) {
     // sanity check
     if !self.is_reliable() {
@@ -684,6 +1228,7 @@ impl Writer {
       );
       return;
     }
+    self.counters.acknacks_received.increment();
 
     match ack_submessage {
       AckSubmessage::AckNack(ref an) => {
@@ -701,6 +1246,13 @@ impl Writer {
         self.update_ack_waiters(reader_guid, Some(an.reader_sn_state.base()));
 
         if let Some(reader_proxy) = self.lookup_readerproxy_mut(reader_guid) {
+          if !reply_locators.is_empty() && reader_proxy.unicast_locator_list != reply_locators {
+            info!(
+              "Reader {:?} asks for replies to {:?}",
+              reader_proxy.remote_reader_guid, reply_locators
+            );
+            reader_proxy.unicast_locator_list = reply_locators.to_vec();
+          }
           // Mark requested SNs as "unsent changes"
           reader_proxy.handle_ack_nack(ack_submessage, last_seq);
 
@@ -751,6 +1303,7 @@ impl Writer {
             );
           }
         }
+        self.update_acked_by_all_before();
       }
       AckSubmessage::NackFrag(ref nackfrag) => {
         // NackFrag is negative acknowledgement only, i.e. requesting missing fragments.
@@ -767,30 +1320,52 @@ impl Writer {
         );
       }
     }
+    self.schedule_flow_control();
   }
 
   fn update_ack_waiters(&mut self, guid: GUID, acked_before: Option<SequenceNumber>) {
-    let mut completed = false;
-    match &mut self.ack_waiter {
-      Some(aw) => match acked_before {
-        None => {
-          aw.readers_pending.remove(&guid);
-        }
-        Some(acked_before) => {
-          if aw.wait_until < acked_before {
-            aw.readers_pending.remove(&guid);
+    self.ack_waiters.retain(|aw| {
+      {
+        let mut progress = aw.progress.lock().unwrap();
+        match acked_before {
+          None => {
+            progress.readers_pending.remove(&guid);
           }
-          if aw.readers_pending.is_empty() {
-            // it is normal for the send to fail, because receiver may have timed out
-            let _ = aw.complete_channel.try_send(());
-            completed = true;
+          Some(acked_before) => {
+            if progress.wait_until < acked_before {
+              progress.readers_pending.remove(&guid);
+            } else if let Some(reader_acked_before) = progress.readers_pending.get_mut(&guid) {
+              *reader_acked_before = max(*reader_acked_before, acked_before);
+            }
           }
         }
-      },
-      None => (),
-    }
-    if completed {
-      self.ack_waiter = None;
+      }
+      if aw.is_complete() {
+        aw.notify_complete();
+        false
+      } else {
+        true
+      }
+    });
+  }
+
+  fn update_acked_by_all_before(&self) {
+    let acked_by_all_before = self
+      .readers
+      .values()
+      .filter(|rp| matches!(rp.qos().reliability(), Some(Reliability::Reliable { .. })))
+      .map(|rp| max(i64::from(rp.all_acked_before), 1))
+      .min()
+      .unwrap_or(i64::MAX);
+    let previous = self
+      .acked_by_all_before
+      .swap(acked_by_all_before, Ordering::Relaxed);
+    if acked_by_all_before > previous {
+      let readiness = self.ack_readiness.readiness() | Ready::readable() | Ready::writable();
+      if let Err(e) = self.ack_readiness.set_readiness(readiness) {
+        warn!("Cannot signal acknowledgments: {:?}", e);
+      }
+      self.ack_waker.wake();
     }
   }
 
@@ -832,12 +1407,8 @@ impl Writer {
   fn handle_repair_data_send_worker(&mut self, reader_proxy: &mut RtpsReaderProxy) {
     // Note: The reader_proxy is now removed from readers map
     let reader_guid = reader_proxy.remote_reader_guid;
-    let mut partial_message = MessageBuilder::new()
-      .dst_submessage(self.endianness, reader_guid.prefix)
-      .ts_msg(self.endianness, Some(Timestamp::now()));
-    // TODO: This timestamp should probably not be
-    // the current (retransmit) time, but the initial sample production timestamp,
-    // i.e. should be read from DDSCache (and be implemented there)
+    let mut partial_message =
+      MessageBuilder::new().dst_submessage(self.endianness, reader_guid.prefix);
     debug!(
       "Repair data send due to ACKNACK. ReaderProxy Unsent changes: {:?}",
       reader_proxy.unsent_changes
@@ -847,7 +1418,10 @@ impl Writer {
     let mut found_data = false;
     if let Some(&unsent_sn) = reader_proxy.unsent_changes.iter().next() {
       // There are unsent changes.
-      if let Some(timestamp) = self.sequence_number_to_instant(unsent_sn) {
+      if self.is_filtered_out(unsent_sn, reader_guid) {
+        // The content filter of the reader rejects this change.
+        no_longer_relevant.push(unsent_sn);
+      } else if let Some(timestamp) = self.sequence_number_to_instant(unsent_sn) {
         // Try to find the cache change from DDSCache
         if let Some(cache_change) = self
           .dds_cache
@@ -855,13 +1429,60 @@ impl Writer {
           .unwrap()
           .topic_get_change(&self.my_topic_name, &timestamp)
         {
-          // CacheChange found, construct DATA submessage
-          partial_message = partial_message.data_msg(
-            cache_change,
-            reader_guid.entity_id,  // reader
-            self.my_guid.entity_id, // writer
-            self.endianness,
-          );
+          // Decide on the size of the sample itself, as when it was first sent.
+          let fragmentation_needed =
+            self.fragmentation_needed(cache_change.data_value.payload_size());
+          match self.outgoing_change(cache_change) {
+            Some(cache_change) if fragmentation_needed => {
+              // Too large for a DATA submessage. Resend as fragments.
+              let fragment_size: u32 = self.data_max_size_serialized as u32;
+              let data_size: u32 = cache_change.data_value.payload_size() as u32;
+              let num_frags = (data_size + fragment_size - 1) / fragment_size;
+              for frag_num in FragmentNumber::range_inclusive(
+                FragmentNumber::new(1),
+                FragmentNumber::new(num_frags),
+              ) {
+                let mut frag_message =
+                  MessageBuilder::new().dst_submessage(self.endianness, reader_guid.prefix);
+                // A retransmitted sample keeps its original source timestamp.
+                if let Some(src_ts) = cache_change.write_options.source_timestamp {
+                  frag_message = frag_message.ts_msg(self.endianness, Some(src_ts));
+                }
+                let frag_message = frag_message
+                  .data_frag_msg(
+                    &cache_change,
+                    reader_guid.entity_id,  // reader
+                    self.my_guid.entity_id, // writer
+                    frag_num,
+                    fragment_size as u16,
+                    data_size,
+                    self.endianness,
+                  )
+                  .add_header_and_build(self.my_guid.prefix);
+                self.send_message_to_readers(
+                  DeliveryMode::Unicast,
+                  &frag_message,
+                  &mut std::iter::once(&*reader_proxy),
+                );
+                self.counters.retransmissions.increment();
+              }
+            }
+            Some(cache_change) => {
+              // CacheChange found, construct DATA submessage
+              self.counters.retransmissions.increment();
+              // A retransmitted sample keeps its original source timestamp.
+              if let Some(src_ts) = cache_change.write_options.source_timestamp {
+                partial_message = partial_message.ts_msg(self.endianness, Some(src_ts));
+              }
+              partial_message = partial_message.data_msg(
+                &cache_change,
+                reader_guid.entity_id,  // reader
+                self.my_guid.entity_id, // writer
+                self.endianness,
+              );
+            }
+            None => (), // Could not be protected. This has been logged.
+          }
           // TODO: Here we are cloning the entire payload. We need to rewrite
           // the transmit path to avoid copying.
 
@@ -884,6 +1505,16 @@ impl Writer {
             &reader_proxy, unsent_sn, &self.my_topic_name
           );
           // nothing to do
+        } else if self
+          .skipped_sequence_numbers
+          .iter()
+          .any(|r| r.begin() <= unsent_sn && unsent_sn <= r.end())
+        {
+          debug!(
+            "Reader {:?} requested skipped {:?}. Topic {:?}",
+            &reader_proxy, unsent_sn, &self.my_topic_name
+          );
+          // nothing to do
         } else {
           // we are running out of excuses
           error!(
@@ -942,6 +1573,7 @@ impl Writer {
           .read()
           .unwrap()
           .topic_get_change(&self.my_topic_name, &timestamp)
+          .and_then(|cc| self.outgoing_change(cc))
         {
           // Generate datafrag message
           let mut message_builder = MessageBuilder::new();
@@ -953,7 +1585,7 @@ impl Writer {
           let data_size: u32 = cache_change.data_value.payload_size() as u32; //TODO: overflow check
 
           message_builder = message_builder.data_frag_msg(
-            cache_change,
+            &cache_change,
             reader_proxy.remote_reader_guid.entity_id, // reader
             self.my_guid.entity_id,                    // writer
             frag_num,
@@ -968,6 +1600,7 @@ impl Writer {
             &message_builder.add_header_and_build(self.my_guid.prefix),
             &mut self.readers.values(),
           );
+          self.counters.retransmissions.increment();
         } else {
           error!(
             "handle_repair_frags_send_worker: {:?} missing from DDSCache. topic={:?}",
@@ -1014,15 +1647,129 @@ impl Writer {
         .write()
         .unwrap()
         .topic_remove_before(&self.my_topic_name, keep_instant);
-    } else {
+    } else if first_keeper <= self.last_change_sequence_number {
+      // Otherwise all changes have expired and been removed already.
       warn!("{:?} missing from instant map", first_keeper);
     }
     self.first_change_sequence_number = first_keeper;
     self.sequence_number_to_instant = self.sequence_number_to_instant.split_off(&first_keeper);
+    self.lifespan_expiries = self.lifespan_expiries.split_off(&first_keeper);
+    self.filtered_out = self.filtered_out.split_off(&first_keeper);
+    #[cfg(feature = "security")]
+    {
+      let protected_payloads = self.protected_payloads.get_mut();
+      *protected_payloads = protected_payloads.split_off(&first_keeper);
+    }
+  }
+
+  // Called after a clock jump. Lifespan expiry is on the monotonic clock, so
+  // wall clock steps do not move it, but the lifespan timer may have been
+  // stalled. Samples that expired meanwhile are removed now.
+  pub fn clock_jump(&mut self) {
+    self.remove_expired_changes();
+  }
+
+  fn schedule_lifespan_expiry(&mut self, expiry: Instant) {
+    let now = self.timed_event_timer.time_source().instant();
+    if expiry <= now {
+      self.remove_expired_changes();
+    } else if self
+      .lifespan_timeout
+      .map_or(true, |timeout| expiry < timeout)
+    {
+      self.lifespan_timeout = Some(expiry);
+      self
+        .timed_event_timer
+        .set_timeout(expiry - now, TimedEvent::LifespanExpiry);
+    }
+  }
+
+  /// Removes samples whose lifespan has expired from the history, whether or
+  /// not all readers have acknowledged them. Readers asking for them get a
+  /// GAP. Samples before an expired one are removed, too, so that the history
+  /// stays contiguous.
+  fn remove_expired_changes(&mut self) {
+    let now = self.timed_event_timer.time_source().instant();
+    let last_expired = self
+      .lifespan_expiries
+      .iter()
+      .take_while(|(_sn, expiry)| **expiry <= now)
+      .map(|(sn, _expiry)| *sn)
+      .last();
+
+    if let Some(last_expired) = last_expired {
+      let first_kept = last_expired + SequenceNumber::from(1);
+      let kept = self.sequence_number_to_instant.split_off(&first_kept);
+      let expired = std::mem::replace(&mut self.sequence_number_to_instant, kept);
+      {
+        let mut cache = self.dds_cache.write().unwrap();
+        for instant in expired.values() {
+          cache.topic_remove_change(&self.my_topic_name, instant);
+        }
+      }
+      debug!(
+        "Lifespan of {} samples expired on topic {:?}",
+        expired.len(),
+        self.my_topic_name
+      );
+      self.first_change_sequence_number = max(self.first_change_sequence_number, first_kept);
+      self.lifespan_expiries = self.lifespan_expiries.split_off(&first_kept);
+      self.filtered_out = self.filtered_out.split_off(&first_kept);
+      #[cfg(feature = "security")]
+      {
+        let protected_payloads = self.protected_payloads.get_mut();
+        *protected_payloads = protected_payloads.split_off(&first_kept);
+      }
+    }
+
+    if self.lifespan_timeout.map_or(true, |timeout| timeout <= now) {
+      self.lifespan_timeout = None;
+      if let Some(&expiry) = self.lifespan_expiries.values().next() {
+        self.schedule_lifespan_expiry(expiry);
+      }
+    }
   }
 
   fn increase_heartbeat_counter(&mut self) {
     self.heartbeat_message_counter += 1;
+    self.counters.heartbeats_sent.increment();
+  }
+
+  // The change as it is sent out. With DDS Security, its payload is protected,
+  // if the topic requires it. None if that fails.
+  #[cfg(feature = "security")]
+  fn outgoing_change<'a>(&self, cache_change: &'a CacheChange) -> Option<Cow<'a, CacheChange>> {
+    use std::collections::btree_map::Entry;
+
+    let cryptography = match &self.cryptography {
+      Some(cryptography) => cryptography,
+      None => return Some(Cow::Borrowed(cache_change)),
+    };
+    let mut protected_payloads = self.protected_payloads.borrow_mut();
+    let data_value = match protected_payloads.entry(cache_change.sequence_number) {
+      Entry::Occupied(entry) => entry.get().clone(),
+      Entry::Vacant(entry) => {
+        match cryptography.encode_data(self.my_guid.entity_id, &cache_change.data_value) {
+          Ok(Some(data_value)) => entry.insert(data_value).clone(),
+          Ok(None) => return Some(Cow::Borrowed(cache_change)),
+          Err(e) => {
+            error!(
+              "Cannot protect {:?} topic={:?}: {}",
+              cache_change.sequence_number, self.my_topic_name, e
+            );
+            return None;
+          }
+        }
+      }
+    };
+    let mut protected_change = cache_change.clone();
+    protected_change.data_value = data_value;
+    Some(Cow::Owned(protected_change))
+  }
+
+  #[cfg(not(feature = "security"))]
+  fn outgoing_change<'a>(&self, cache_change: &'a CacheChange) -> Option<Cow<'a, CacheChange>> {
+    Some(Cow::Borrowed(cache_change))
   }
 
   fn send_message_to_readers(
@@ -1030,21 +1777,64 @@ impl Writer {
     preferred_mode: DeliveryMode,
     message: &Message,
     readers: &mut dyn Iterator<Item = &RtpsReaderProxy>,
+  ) {
+    let _span = pipeline_tracing::send(&self.my_topic_name, self.my_guid);
+    let buffer = message.write_to_vec_with_ctx(self.endianness).unwrap();
+    #[cfg(feature = "security")]
+    let buffer = match &self.cryptography {
+      Some(cryptography)
+        if self.my_guid.entity_id == EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER =>
+      {
+        // Crypto tokens are protected with the key exchange keys of each
+        // destination participant separately.
+        for reader in readers {
+          let destination = reader.remote_reader_guid.prefix;
+          match cryptography.encode_message(buffer.clone(), Some(destination)) {
+            Ok(encoded) => {
+              self.queue_message(preferred_mode, encoded, &mut std::iter::once(reader));
+            }
+            Err(e) => debug!("Cannot send crypto tokens to {:?}: {}", destination, e),
+          }
+        }
+        return;
+      }
+      Some(cryptography) => match cryptography.encode_message(buffer, None) {
+        Ok(encoded) => encoded,
+        Err(e) => {
+          error!(
+            "Cannot protect message topic={:?}: {}",
+            self.my_topic_name, e
+          );
+          return;
+        }
+      },
+      None => buffer,
+    };
+    self.queue_message(preferred_mode, buffer, readers);
+  }
+
+  // Queue an RTPS message to the preferred locators of the readers, and send
+  // it as far as flow control allows.
+  fn queue_message(
+    &self,
+    preferred_mode: DeliveryMode,
+    buffer: Vec<u8>,
+    readers: &mut dyn Iterator<Item = &RtpsReaderProxy>,
   ) {
     // TODO: This is a stupid transmit algorithm. We should compute a preferred
     // unicast and multicast locators for each reader only on every reader update,
     // and not find it dynamically on every message.
-    let buffer = message.write_to_vec_with_ctx(self.endianness).unwrap();
     let mut already_sent_to = BTreeSet::new();
+    let mut locators = Vec::new();
 
     macro_rules! send_unless_sent_and_mark {
       ($locs:expr) => {
-        for loc in $locs.iter() {
-          if already_sent_to.contains(loc) {
+        for loc in self.transports.preferred_locators(&$locs) {
+          if already_sent_to.contains(&loc) {
             trace!("Already sent to {:?}", loc);
           } else {
-            self.udp_sender.send_to_locator(&buffer, loc);
-            already_sent_to.insert(loc.clone());
+            locators.push(loc);
+            already_sent_to.insert(loc);
           }
         }
       };
@@ -1079,6 +1869,41 @@ impl Writer {
         }
       } // match
     }
+
+    if !locators.is_empty() {
+      self.flow_queue.borrow_mut().push_back((buffer, locators));
+      self.send_flow_queue();
+    }
+  }
+
+  // Send queued messages, as far as the flow controller allows. Without one,
+  // everything is sent right away.
+  fn send_flow_queue(&self) {
+    let mut queue = self.flow_queue.borrow_mut();
+    while let Some((buffer, locators)) = queue.front() {
+      if let Some(flow_controller) = &self.flow_controller {
+        if let Err(wait) = flow_controller.try_send(buffer.len() * locators.len(), Instant::now()) {
+          self.flow_wait.set(Some(wait));
+          return;
+        }
+      }
+      for loc in locators {
+        self.transports.send_to_locator(buffer, loc);
+      }
+      queue.pop_front();
+    }
+  }
+
+  // Wake up to send the queued messages, when the flow controller allows.
+  fn schedule_flow_control(&mut self) {
+    if let Some(wait) = self.flow_wait.take() {
+      if !self.flow_timer_set {
+        self
+          .timed_event_timer
+          .set_timeout(wait, TimedEvent::FlowControl);
+        self.flow_timer_set = true;
+      }
+    }
   }
 
   // Send status to DataWriter or however is listening
@@ -1097,15 +1922,27 @@ impl Writer {
       });
   }
 
+  // Apply a changed QoS. The caller has checked that only changeable policies
+  // were changed, and selected sender sockets according to
+  // TRANSPORT_PRIORITY.
+  pub fn set_qos(&mut self, qos: QosPolicies, transports: Rc<Transports>) {
+    self.qos_policies = qos;
+    self.transports = transports;
+  }
+
   pub fn update_reader_proxy(
     &mut self,
     reader_proxy: &RtpsReaderProxy,
     requested_qos: &QosPolicies,
   ) {
     debug!("update_reader_proxy topic={:?}", self.my_topic_name);
-    match self.qos_policies.compliance_failure_wrt(requested_qos) {
+    let bad_policy_ids = self.qos_policies.compliance_failures_wrt(requested_qos);
+    match bad_policy_ids.first() {
       // matched QoS
       None => {
+        self
+          .incompatible_qos
+          .forget(reader_proxy.remote_reader_guid);
         let change = self.matched_reader_update(reader_proxy.clone());
         if change > 0 {
           self.matched_readers_count_total += change;
@@ -1125,19 +1962,23 @@ impl Writer {
           debug!("Reader details: {:?}", &reader_proxy);
         }
       }
-      Some(bad_policy_id) => {
+      Some(&bad_policy_id) => {
         // QoS not compliant :(
         warn!(
           "update_reader_proxy - QoS mismatch {:?} topic={:?}",
-          bad_policy_id,
+          bad_policy_ids,
           self.topic_name()
         );
-        self.requested_incompatible_qos_count += 1;
-        self.send_status(DataWriterStatus::OfferedIncompatibleQos {
-          count: CountWithChange::new(self.requested_incompatible_qos_count, 1),
-          last_policy_id: bad_policy_id,
-          policies: Vec::new(), // TODO: implement this
-        });
+        let reader_guid = reader_proxy.remote_reader_guid;
+        // The reader may have been compatible before, but changed its QoS.
+        self.matched_reader_lost(reader_guid);
+        if self.incompatible_qos.record(reader_guid, &bad_policy_ids) {
+          self.send_status(DataWriterStatus::OfferedIncompatibleQos {
+            count: CountWithChange::new(self.incompatible_qos.total_count(), 1),
+            last_policy_id: bad_policy_id,
+            policies: self.incompatible_qos.policies(),
+          });
+        }
       }
     } // match
   }
@@ -1159,13 +2000,38 @@ impl Writer {
         0,
       ),
     };
+    self.update_reader_filter(&to_insert);
     self.readers.insert(to_insert.remote_reader_guid, to_insert);
+    self.update_acked_by_all_before();
+    runtime_metrics::matched_endpoints(&self.my_topic_name, LocalEndpoint::Writer, count_change);
     count_change
   }
 
+  // Keeps the reader filters evaluated by the DataWriter up to date.
+  fn update_reader_filter(&self, reader_proxy: &RtpsReaderProxy) {
+    let mut reader_filters = self.reader_filters.write().unwrap();
+    match reader_proxy
+      .content_filter
+      .as_ref()
+      .and_then(filter_from_property)
+    {
+      Some(mut filter) => {
+        if let Some(dynamic_type) = &self.dynamic_type {
+          filter.set_dynamic_type(dynamic_type);
+        }
+        reader_filters.insert(reader_proxy.remote_reader_guid, filter);
+      }
+      None => {
+        reader_filters.remove(&reader_proxy.remote_reader_guid);
+      }
+    }
+  }
+
   fn matched_reader_remove(&mut self, guid: GUID) -> Option<RtpsReaderProxy> {
+    self.reader_filters.write().unwrap().remove(&guid);
     let removed = self.readers.remove(&guid);
     if let Some(ref removed_reader) = removed {
+      runtime_metrics::matched_endpoints(&self.my_topic_name, LocalEndpoint::Writer, -1);
       info!(
         "Removed reader proxy. topic={:?} reader={:?}",
         self.topic_name(),
@@ -1177,6 +2043,11 @@ impl Writer {
   }
 
   pub fn reader_lost(&mut self, guid: GUID) {
+    self.incompatible_qos.forget(guid);
+    self.matched_reader_lost(guid);
+  }
+
+  fn matched_reader_lost(&mut self, guid: GUID) {
     if self.readers.contains_key(&guid) {
       info!(
         "reader_lost topic={:?} reader={:?}",
@@ -1190,8 +2061,9 @@ impl Writer {
         current: CountWithChange::new(self.readers.len() as i32, -1),
       });
     }
-    // also remember to remove reader from ack_waiter
+    // also remember to remove reader from ack_waiters
     self.update_ack_waiters(guid, None);
+    self.update_acked_by_all_before();
   }
 
   // Entire remote participant was lost.
@@ -1227,6 +2099,17 @@ impl Writer {
   // }
 }
 
+// The matches of a deleted Writer are gone, too.
+impl Drop for Writer {
+  fn drop(&mut self) {
+    runtime_metrics::matched_endpoints(
+      &self.my_topic_name,
+      LocalEndpoint::Writer,
+      -(self.readers.len() as i32),
+    );
+  }
+}
+
 impl RTPSEntity for Writer {
   fn guid(&self) -> GUID {
     self.my_guid
@@ -1245,20 +2128,363 @@ impl HasQoSPolicy for Writer {
 
 #[cfg(test)]
 mod tests {
-  use std::thread;
+  use std::{
+    collections::BTreeMap,
+    rc::Rc,
+    sync::{atomic::AtomicI64, Arc, RwLock},
+    thread,
+  };
 
+  use bytes::Bytes;
   use byteorder::LittleEndian;
   use log::info;
+  use mio_extras::channel as mio_channel;
 
+  use super::{MessageBuilder, WakerSlot, Writer, WriterCommand, WriterIngredients};
   use crate::{
     dds::{
-      participant::DomainParticipant, qos::QosPolicies, topic::TopicKind,
-      with_key::datawriter::DataWriter,
+      clock::{Clock, SteppingClock, TimeSource},
+      ddsdata::DDSData,
+      participant::DomainParticipant,
+      qos::{policy, QosPolicies, QosPolicyBuilder},
+      timer::Timer,
+      topic::TopicKind,
+      traits::key::KeyHash,
+      typedesc::TypeDesc,
+      with_key::datawriter::{DataWriter, WriteOptions},
+    },
+    messages::submessages::{
+      submessage::{EntitySubmessage, InterpreterSubmessage},
+      submessage_elements::serialized_payload::SerializedPayload,
+    },
+    network::{transport::Transports, udp_sender::UDPSender},
+    serialization::{cdr_serializer::CDRSerializerAdapter, submessage::SubmessageBody, Message},
+    structure::{
+      cache_change::ChangeKind,
+      dds_cache::DDSCache,
+      duration::Duration,
+      guid::{EntityKind, GUID},
+      sequence_number::SequenceNumber,
+      time::Timestamp,
     },
-    serialization::cdr_serializer::CDRSerializerAdapter,
     test::random_data::*,
+    RepresentationIdentifier,
   };
 
+  // A Writer of topic "test_topic" that is not part of a DomainParticipant.
+  // The topic is added to dds_cache. WriterCommands are given to the writer
+  // with the returned sender.
+  fn test_writer(
+    qos_policies: QosPolicies,
+    entity_kind: EntityKind,
+    dds_cache: &Arc<RwLock<DDSCache>>,
+  ) -> (Writer, mio_channel::Sender<WriterCommand>) {
+    dds_cache
+      .write()
+      .unwrap()
+      .add_new_topic("test_topic".to_string(), TypeDesc::new("Foo".to_string()));
+    let (command_sender, writer_command_receiver) = mio_channel::channel();
+    // Status events are not received.
+    let (status_sender, _status_receiver) = mio_channel::sync_channel(10);
+    let writer = Writer::new(
+      WriterIngredients {
+        guid: GUID::dummy_test_guid(entity_kind),
+        writer_command_receiver,
+        topic_name: "test_topic".to_string(),
+        qos_policies,
+        status_sender,
+        acked_by_all_before: Arc::new(AtomicI64::new(i64::MAX)),
+        ack_readiness: mio::Registration::new2().1,
+        ack_waker: WakerSlot::default(),
+        reader_filters: Arc::new(RwLock::new(BTreeMap::new())),
+        dynamic_type: None,
+        flow_controller: None,
+      },
+      Arc::clone(dds_cache),
+      Rc::new(Transports::from(UDPSender::new_with_random_port().unwrap())),
+      crate::dds::timer::Timer::default(),
+    );
+    (writer, command_sender)
+  }
+
+  // A sample whose payload is four bytes of n
+  fn test_sample(n: u8) -> DDSData {
+    DDSData::new(SerializedPayload::new_from_bytes(
+      RepresentationIdentifier::CDR_LE,
+      Bytes::from(vec![n; 4]),
+    ))
+  }
+
+  #[test]
+  fn writer_fragment_size_follows_max_message_size() {
+    use crate::network::{
+      interface_filter::InterfaceFilter, socket_options::SocketOptions, transport::TransportKind,
+    };
+
+    let fragment_size = |max_message_size| {
+      let mut options = SocketOptions::default();
+      options
+        .max_message_size
+        .insert(TransportKind::UdpV4, max_message_size);
+      let transports = Transports::new(
+        &[TransportKind::UdpV4],
+        &InterfaceFilter::default(),
+        &options,
+      )
+      .unwrap();
+      Writer::fragment_size(&transports)
+    };
+    assert_eq!(fragment_size(1000), 1000 - Writer::MESSAGE_OVERHEAD);
+    assert_eq!(fragment_size(9000), 9000 - Writer::MESSAGE_OVERHEAD);
+    assert_eq!(fragment_size(100), Writer::MIN_FRAGMENT_SIZE);
+  }
+
+  #[test]
+  fn writer_heartbeat_tuning() {
+    let qos = QosPolicyBuilder::new()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      })
+      .build();
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    let (mut writer, command_sender) =
+      test_writer(qos, EntityKind::WRITER_NO_KEY_USER_DEFINED, &dds_cache);
+    assert_eq!(writer.heartbeat_period, Some(Duration::from_secs(1)));
+    // By default, every DATA message carries a HEARTBEAT.
+    assert!((0..3).all(|_| writer.piggyback_heartbeat_due()));
+
+    let period = std::time::Duration::from_millis(100);
+    let delay = std::time::Duration::from_millis(20);
+    for command in [
+      WriterCommand::SetHeartbeatPeriod { period },
+      WriterCommand::SetNackResponseDelay { delay },
+      WriterCommand::SetPiggybackHeartbeatEvery { count: 3 },
+    ] {
+      command_sender.send(command).unwrap();
+    }
+    writer.process_writer_command();
+
+    assert_eq!(writer.heartbeat_period, Some(Duration::from_std(period)));
+    assert_eq!(writer.nack_response_delay, delay);
+    assert_eq!(writer.nackfrag_response_delay, delay);
+    let due: Vec<bool> = (0..6).map(|_| writer.piggyback_heartbeat_due()).collect();
+    assert_eq!(due, [false, false, true, false, false, true]);
+
+    command_sender
+      .send(WriterCommand::SetPiggybackHeartbeatEvery { count: 0 })
+      .unwrap();
+    writer.process_writer_command();
+    assert!((0..10).all(|_| !writer.piggyback_heartbeat_due()));
+  }
+
+  #[test]
+  fn writer_unregisters_live_instances() {
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    let (mut writer, _command_sender) = test_writer(
+      QosPolicyBuilder::new().build(),
+      EntityKind::WRITER_WITH_KEY_USER_DEFINED,
+      &dds_cache,
+    );
+
+    let key_hash = |n: u8| KeyHash::from_cdr_bytes(vec![n; 16]).unwrap();
+    let sample = |n: u8| test_sample(n).with_key_hash(Some(key_hash(n)));
+    let changes = vec![
+      sample(1),
+      sample(2),
+      sample(3),
+      sample(1),
+      DDSData::new_disposed_by_key_hash(ChangeKind::NotAliveDisposed, key_hash(2)),
+    ];
+    for (sn, ddsdata) in (1..).zip(changes) {
+      writer.insert_to_history_cache(ddsdata, WriteOptions::from(None), SequenceNumber::from(sn));
+    }
+    let live: Vec<KeyHash> = writer.live_instances.iter().copied().collect();
+    assert_eq!(live, [key_hash(1), key_hash(3)]);
+
+    writer.unregister_all_instances();
+    assert!(writer.live_instances.is_empty());
+    assert_eq!(writer.last_change_sequence_number, SequenceNumber::from(7));
+    let unregistered = dds_cache
+      .read()
+      .unwrap()
+      .topic_get_changes_in_range("test_topic", &Timestamp::ZERO, &Timestamp::now())
+      .filter(|(_, cc)| cc.data_value.change_kind() == ChangeKind::NotAliveUnregistered)
+      .count();
+    assert_eq!(unregistered, 2);
+
+    // Nothing left to unregister
+    writer.unregister_all_instances();
+    assert_eq!(writer.last_change_sequence_number, SequenceNumber::from(7));
+  }
+
+  #[test]
+  fn writer_lifespan_ignores_clock_jumps() {
+    let qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepAll)
+      .lifespan(policy::Lifespan {
+        duration: Duration::from_secs(10),
+      })
+      .build();
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    let (mut writer, _command_sender) =
+      test_writer(qos, EntityKind::WRITER_NO_KEY_USER_DEFINED, &dds_cache);
+    let clock = SteppingClock::new();
+    writer.timed_event_timer = Timer::new(&TimeSource::from_clock(clock.clone()), None);
+    let in_history = |writer: &Writer| -> Vec<i64> {
+      writer
+        .sequence_number_to_instant
+        .keys()
+        .map(|sn| i64::from(*sn))
+        .collect()
+    };
+
+    let write = |writer: &mut Writer, n: u8| {
+      let write_options = WriteOptions::from(Some(clock.now()));
+      writer.insert_to_history_cache(
+        test_sample(n),
+        write_options,
+        SequenceNumber::from(i64::from(n)),
+      );
+    };
+    write(&mut writer, 1);
+    clock.advance(std::time::Duration::from_secs(5));
+    write(&mut writer, 2);
+
+    // The wall clock jumps an hour forwards, and then two hours backwards.
+    // Lifespan is measured on the monotonic clock, so nothing expires.
+    clock.step_wall_clock(Duration::from_secs(3600));
+    writer.clock_jump();
+    assert_eq!(in_history(&writer), [1, 2]);
+    clock.step_wall_clock(Duration::from_secs(-7200));
+    writer.clock_jump();
+    assert_eq!(in_history(&writer), [1, 2]);
+
+    // 11 and 6 seconds after writing, only the first one has expired.
+    clock.advance(std::time::Duration::from_secs(6));
+    writer.clock_jump();
+    assert_eq!(in_history(&writer), [2]);
+    assert_eq!(writer.first_change_sequence_number, SequenceNumber::from(2));
+    assert_eq!(dds_cache.read().unwrap().change_count(), 1);
+
+    // A source timestamp in the future counts as written now.
+    let write_options = WriteOptions::from(Some(clock.now() + Duration::from_secs(3600)));
+    writer.insert_to_history_cache(test_sample(3), write_options, SequenceNumber::from(3));
+    clock.advance(std::time::Duration::from_secs(5));
+    writer.clock_jump();
+    assert_eq!(in_history(&writer), [3]);
+    clock.advance(std::time::Duration::from_secs(6));
+    writer.clock_jump();
+    assert!(in_history(&writer).is_empty());
+    assert_eq!(writer.first_change_sequence_number, SequenceNumber::from(4));
+    assert_eq!(dds_cache.read().unwrap().change_count(), 0);
+  }
+
+  #[test]
+  fn writer_sends_source_timestamps() {
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    let (mut writer, _command_sender) = test_writer(
+      QosPolicyBuilder::new().build(),
+      EntityKind::WRITER_NO_KEY_USER_DEFINED,
+      &dds_cache,
+    );
+
+    let measured = Timestamp::ZERO + Duration::from_secs(1000);
+    let later = measured + Duration::from_secs(1);
+    let source_timestamps = [
+      Some(measured),
+      None,
+      Some(measured),
+      Some(measured),
+      Some(later),
+    ];
+    let changes: Vec<Timestamp> = (1..)
+      .zip(source_timestamps)
+      .map(|(sn, source_timestamp)| {
+        writer.insert_to_history_cache(
+          test_sample(sn as u8),
+          WriteOptions::from(source_timestamp),
+          SequenceNumber::from(sn),
+        )
+      })
+      .collect();
+
+    // Interpret the message like a reader does: INFO_TS gives the source
+    // timestamp of the DATA submessages that follow it.
+    let message = writer
+      .data_msgs(MessageBuilder::new(), &changes, None)
+      .add_header_and_build(writer.my_guid.prefix);
+    let mut in_effect = None;
+    let mut received = Vec::new();
+    for submessage in message.submessages {
+      match submessage.body {
+        SubmessageBody::Interpreter(InterpreterSubmessage::InfoTimestamp(info_ts, _)) => {
+          in_effect = info_ts.timestamp;
+        }
+        SubmessageBody::Entity(EntitySubmessage::Data(..)) => received.push(in_effect),
+        _ => (),
+      }
+    }
+    assert_eq!(received, source_timestamps);
+  }
+
+  #[test]
+  fn writer_batch_invalidates_source_timestamps() {
+    use std::net::UdpSocket;
+
+    use crate::{dds::rtps_reader_proxy::RtpsReaderProxy, structure::locator::Locator};
+
+    // A LATENCY_BUDGET makes the writer collect DATA into batches.
+    let qos = QosPolicyBuilder::new()
+      .latency_budget(policy::LatencyBudget {
+        duration: Duration::from_secs(10),
+      })
+      .build();
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    let (mut writer, _command_sender) =
+      test_writer(qos, EntityKind::WRITER_NO_KEY_USER_DEFINED, &dds_cache);
+    let delay = writer.batching_delay().unwrap();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+      .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+      .unwrap();
+    let mut reader_proxy = RtpsReaderProxy::new(
+      GUID::dummy_test_guid(EntityKind::READER_NO_KEY_USER_DEFINED),
+      QosPolicies::qos_none(),
+    );
+    reader_proxy.unicast_locator_list = vec![Locator::from(socket.local_addr().unwrap())];
+    writer.update_reader_proxy(&reader_proxy, &QosPolicies::qos_none());
+
+    // A sample without a timestamp follows one with a timestamp in the batch.
+    let measured = Timestamp::ZERO + Duration::from_secs(1000);
+    let source_timestamps = [Some(measured), None, None, Some(measured)];
+    for (sn, source_timestamp) in (1..).zip(source_timestamps) {
+      let timestamp = writer.insert_to_history_cache(
+        test_sample(sn as u8),
+        WriteOptions::from(source_timestamp),
+        SequenceNumber::from(sn),
+      );
+      writer.add_to_data_batch(timestamp, delay);
+    }
+    writer.flush_data_batch();
+
+    let mut buffer = vec![0; 65536];
+    let len = socket.recv(&mut buffer).unwrap();
+    let message = Message::read_from_buffer(&Bytes::copy_from_slice(&buffer[..len])).unwrap();
+    let mut in_effect = None;
+    let mut received = Vec::new();
+    for submessage in message.submessages {
+      match submessage.body {
+        SubmessageBody::Interpreter(InterpreterSubmessage::InfoTimestamp(info_ts, _)) => {
+          in_effect = info_ts.timestamp;
+        }
+        SubmessageBody::Entity(EntitySubmessage::Data(..)) => received.push(in_effect),
+        _ => (),
+      }
+    }
+    assert_eq!(received, source_timestamps);
+  }
+
   #[test]
   fn test_writer_receives_datawriter_cache_change_notifications() {
     let domain_participant = DomainParticipant::new(0).expect("Failed to create participant");
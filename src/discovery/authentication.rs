@@ -0,0 +1,66 @@
+use std::{fmt, sync::Arc};
+
+use crate::discovery::data_types::spdp_participant_data::SpdpDiscoveredParticipantData;
+
+/// Decides whether a newly discovered remote DomainParticipant is accepted.
+///
+/// Set with
+/// [`DomainParticipantBuilder::participant_authenticator`](crate::DomainParticipantBuilder::participant_authenticator).
+/// It is consulted once per remote participant, when its first SPDP
+/// announcement arrives, before any SEDP data is exchanged with it. A rejected
+/// participant is ignored for the lifetime of the local participant, as if by
+/// [`DomainParticipant::ignore_participant`](crate::DomainParticipant::ignore_participant),
+/// so no endpoints are matched and no user data flows.
+///
+/// The decision is made in the discovery thread, so it should be quick.
+///
+/// Closures of type `Fn(&SpdpDiscoveredParticipantData) -> bool` implement
+/// this trait.
+///
+/// # Example
+///
+/// ```
+/// use rustdds::{dds::SpdpDiscoveredParticipantData, DomainParticipantBuilder};
+///
+/// let domain_participant = DomainParticipantBuilder::new(0)
+///   .participant_authenticator(|participant: &SpdpDiscoveredParticipantData| {
+///     participant.user_data.as_ref().map(|ud| ud.value.as_slice()) == Some(b"secret")
+///   })
+///   .build()
+///   .unwrap();
+/// ```
+pub trait ParticipantAuthenticator: Send + Sync {
+  /// Returns `true` to accept the participant.
+  fn authenticate(&self, participant: &SpdpDiscoveredParticipantData) -> bool;
+}
+
+impl<F> ParticipantAuthenticator for F
+where
+  F: Fn(&SpdpDiscoveredParticipantData) -> bool + Send + Sync,
+{
+  fn authenticate(&self, participant: &SpdpDiscoveredParticipantData) -> bool {
+    self(participant)
+  }
+}
+
+// Shareable handle, so that the participant builder stays Clone and Debug.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "discovery"), allow(dead_code))]
+pub(crate) struct AuthenticatorHandle(Arc<dyn ParticipantAuthenticator>);
+
+impl AuthenticatorHandle {
+  pub fn new(authenticator: impl ParticipantAuthenticator + 'static) -> Self {
+    Self(Arc::new(authenticator))
+  }
+
+  #[cfg(feature = "discovery")]
+  pub fn authenticate(&self, participant: &SpdpDiscoveredParticipantData) -> bool {
+    self.0.authenticate(participant)
+  }
+}
+
+impl fmt::Debug for AuthenticatorHandle {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("ParticipantAuthenticator")
+  }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "discovery")]
 use crate::structure::parameter_id::ParameterId;
 
 /// The ContentFilterProperty field provides all the required information to
@@ -40,6 +41,7 @@ pub struct ContentFilterProperty {
   pub expression_parameters: Vec<String>,
 }
 
+#[cfg(feature = "discovery")]
 #[derive(Serialize, Deserialize)]
 pub struct ContentFilterPropertyData {
   parameter_id: ParameterId,
@@ -47,6 +49,7 @@ pub struct ContentFilterPropertyData {
   content_filter_property: ContentFilterProperty,
 }
 
+#[cfg(feature = "discovery")]
 impl ContentFilterPropertyData {
   pub fn new(content_filter_property: &ContentFilterProperty) -> Self {
     let len_cftn = content_filter_property.content_filtered_topic_name.len();
@@ -3,13 +3,14 @@ use std::collections::HashMap;
 use mio::Token;
 use serde::{de::Error, Deserialize, Serialize};
 use chrono::Utc;
+#[cfg(feature = "discovery")]
 use bytes::Bytes;
 use cdr_encoding_size::CdrEncodingSize;
 
 use crate::{
   dds::{
     participant::DomainParticipant,
-    qos::QosPolicies,
+    qos::{policy::UserData, HasQoSPolicy, QosPolicies},
     rtps_reader_proxy::RtpsReaderProxy,
     rtps_writer_proxy::RtpsWriterProxy,
     traits::key::{Key, Keyed},
@@ -20,10 +21,9 @@ use crate::{
     vendor_id::VendorId,
   },
   network::constant::*,
+  security::types::DataHolder,
   serialization::{
-    builtin_data_deserializer::BuiltinDataDeserializer,
-    builtin_data_serializer::BuiltinDataSerializer, error::Result, pl_cdr_deserializer::*,
-    pl_cdr_serializer::*,
+    builtin_data_deserializer::BuiltinDataDeserializer, error::Result, pl_cdr_deserializer::*,
   },
   structure::{
     builtin_endpoint::{BuiltinEndpointQos, BuiltinEndpointSet},
@@ -33,6 +33,8 @@ use crate::{
     locator::Locator,
   },
 };
+#[cfg(feature = "discovery")]
+use crate::serialization::{builtin_data_serializer::BuiltinDataSerializer, pl_cdr_serializer::*};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SpdpDiscoveredParticipantData {
@@ -50,6 +52,14 @@ pub struct SpdpDiscoveredParticipantData {
   pub manual_liveliness_count: i32,
   pub builtin_endpoint_qos: Option<BuiltinEndpointQos>,
   pub entity_name: Option<String>,
+  pub user_data: Option<UserData>,
+  /// Participants with different domain tags ignore each other, although
+  /// they use the same domain id. Empty is the default tag.
+  pub domain_tag: String,
+  /// Identity of a participant that uses DDS Security authentication
+  pub identity_token: Option<DataHolder>,
+  /// Permissions CA of a participant that uses DDS Security access control
+  pub permissions_token: Option<DataHolder>,
 }
 
 impl SpdpDiscoveredParticipantData {
@@ -148,7 +158,11 @@ impl SpdpDiscoveredParticipantData {
       | BuiltinEndpointSet::BUILTIN_ENDPOINT_PARTICIPANT_MESSAGE_DATA_WRITER
       | BuiltinEndpointSet::BUILTIN_ENDPOINT_PARTICIPANT_MESSAGE_DATA_READER
       | BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_TOPICS_ANNOUNCER
-      | BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_TOPICS_DETECTOR;
+      | BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_TOPICS_DETECTOR
+      | BuiltinEndpointSet::BUILTIN_ENDPOINT_TL_SVC_REQUEST_DATA_WRITER
+      | BuiltinEndpointSet::BUILTIN_ENDPOINT_TL_SVC_REQUEST_DATA_READER
+      | BuiltinEndpointSet::BUILTIN_ENDPOINT_TL_SVC_REPLY_DATA_WRITER
+      | BuiltinEndpointSet::BUILTIN_ENDPOINT_TL_SVC_REPLY_DATA_READER;
 
     Self {
       updated_time: Utc::now(),
@@ -165,6 +179,10 @@ impl SpdpDiscoveredParticipantData {
       manual_liveliness_count: 0,
       builtin_endpoint_qos: None,
       entity_name: None,
+      user_data: participant.qos().user_data(),
+      domain_tag: participant.domain_tag(),
+      identity_token: None,
+      permissions_token: None,
     }
   }
 }
@@ -173,7 +191,7 @@ impl PlCdrDeserialize for SpdpDiscoveredParticipantData {
   fn from_pl_cdr_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<Self> {
     BuiltinDataDeserializer::new()
       .parse_data(input_bytes, encoding)
-      .generate_spdp_participant_data()
+      .and_then(|data| data.generate_spdp_participant_data())
       .map_err(|e| {
         Error::custom(format!(
           "SpdpDiscoveredParticipantData::deserialize - {:?} - data was {:?}",
@@ -183,6 +201,7 @@ impl PlCdrDeserialize for SpdpDiscoveredParticipantData {
   }
 }
 
+#[cfg(feature = "discovery")]
 impl PlCdrSerialize for SpdpDiscoveredParticipantData {
   fn to_pl_cdr_bytes(&self, encoding: RepresentationIdentifier) -> Result<Bytes> {
     BuiltinDataSerializer::from_participant_data(self).serialize_pl_cdr_to_Bytes(encoding)
@@ -209,7 +228,7 @@ impl PlCdrDeserialize for Participant_GUID {
   fn from_pl_cdr_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<Self> {
     BuiltinDataDeserializer::new()
       .parse_data(input_bytes, encoding)
-      .generate_participant_guid()
+      .and_then(|data| data.generate_participant_guid())
       .map_err(|e| {
         Error::custom(format!(
           "deserialize Participant_GUID - {:?} - data was {:?}",
@@ -219,13 +238,14 @@ impl PlCdrDeserialize for Participant_GUID {
   }
 }
 
+#[cfg(feature = "discovery")]
 impl PlCdrSerialize for Participant_GUID {
   fn to_pl_cdr_bytes(&self, encoding: RepresentationIdentifier) -> Result<Bytes> {
     BuiltinDataSerializer::from_participant_guid(*self).serialize_pl_cdr_to_Bytes(encoding)
   }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "discovery"))]
 mod tests {
   use byteorder::LittleEndian;
 
@@ -296,4 +316,196 @@ mod tests {
       }
     }
   }
+
+  #[test]
+  fn pdata_user_data_ser_deser() {
+    let data = spdp_participant_data_raw();
+    let rtpsmsg = Message::read_from_buffer(&data).unwrap();
+
+    for submsg in &rtpsmsg.submessages() {
+      if let SubmessageBody::Entity(EntitySubmessage::Data(d, _)) = &submsg.body {
+        let mut participant_data: SpdpDiscoveredParticipantData =
+          PlCdrDeserializerAdapter::from_bytes(
+            &d.serialized_payload.as_ref().unwrap().value,
+            RepresentationIdentifier::PL_CDR_LE,
+          )
+          .unwrap();
+        assert_eq!(participant_data.user_data, None);
+
+        // odd length to exercise padding
+        participant_data.user_data = Some(UserData {
+          value: b"hello".to_vec(),
+        });
+        let sdata = participant_data
+          .to_pl_cdr_bytes(RepresentationIdentifier::PL_CDR_LE)
+          .unwrap();
+        let mut participant_data_2: SpdpDiscoveredParticipantData =
+          PlCdrDeserializerAdapter::from_bytes(&sdata, RepresentationIdentifier::PL_CDR_LE)
+            .unwrap();
+        participant_data_2.updated_time = participant_data.updated_time;
+        assert_eq!(participant_data_2, participant_data);
+      }
+    }
+  }
+
+  #[test]
+  fn pdata_domain_tag_ser_deser() {
+    let data = spdp_participant_data_raw();
+    let rtpsmsg = Message::read_from_buffer(&data).unwrap();
+
+    for submsg in &rtpsmsg.submessages() {
+      if let SubmessageBody::Entity(EntitySubmessage::Data(d, _)) = &submsg.body {
+        let mut participant_data: SpdpDiscoveredParticipantData =
+          PlCdrDeserializerAdapter::from_bytes(
+            &d.serialized_payload.as_ref().unwrap().value,
+            RepresentationIdentifier::PL_CDR_LE,
+          )
+          .unwrap();
+        // No tag on the wire is the default tag.
+        assert_eq!(participant_data.domain_tag, "");
+        let default_size = participant_data
+          .to_pl_cdr_bytes(RepresentationIdentifier::PL_CDR_LE)
+          .unwrap()
+          .len();
+
+        // odd length to exercise padding, and another parameter after it
+        participant_data.domain_tag = "lab".to_string();
+        participant_data.user_data = Some(UserData {
+          value: b"hello".to_vec(),
+        });
+        let sdata = participant_data
+          .to_pl_cdr_bytes(RepresentationIdentifier::PL_CDR_LE)
+          .unwrap();
+        let mut participant_data_2: SpdpDiscoveredParticipantData =
+          PlCdrDeserializerAdapter::from_bytes(&sdata, RepresentationIdentifier::PL_CDR_LE)
+            .unwrap();
+        participant_data_2.updated_time = participant_data.updated_time;
+        assert_eq!(participant_data_2, participant_data);
+
+        // The default tag is not sent at all.
+        participant_data.domain_tag.clear();
+        participant_data.user_data = None;
+        assert_eq!(
+          participant_data
+            .to_pl_cdr_bytes(RepresentationIdentifier::PL_CDR_LE)
+            .unwrap()
+            .len(),
+          default_size
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn pdata_identity_token_ser_deser() {
+    let data = spdp_participant_data_raw();
+    let rtpsmsg = Message::read_from_buffer(&data).unwrap();
+
+    for submsg in &rtpsmsg.submessages() {
+      if let SubmessageBody::Entity(EntitySubmessage::Data(d, _)) = &submsg.body {
+        let mut participant_data: SpdpDiscoveredParticipantData =
+          PlCdrDeserializerAdapter::from_bytes(
+            &d.serialized_payload.as_ref().unwrap().value,
+            RepresentationIdentifier::PL_CDR_LE,
+          )
+          .unwrap();
+        assert_eq!(participant_data.identity_token, None);
+
+        // odd lengths to exercise padding
+        participant_data.identity_token = Some(
+          DataHolder::new("DDS:Auth:PKI-DH:1.0")
+            .with_property("dds.cert.sn", "CN=x")
+            .with_binary_property("b", vec![1, 2, 3]),
+        );
+        participant_data.permissions_token = Some(
+          DataHolder::new("DDS:Access:Permissions:1.0").with_property("dds.perm_ca.sn", "CN=y"),
+        );
+        participant_data.domain_tag = "lab".to_string();
+        for encoding in [
+          RepresentationIdentifier::PL_CDR_LE,
+          RepresentationIdentifier::PL_CDR_BE,
+        ] {
+          let sdata = participant_data.to_pl_cdr_bytes(encoding).unwrap();
+          let sentinel: &[u8] = if encoding == RepresentationIdentifier::PL_CDR_LE {
+            &[1, 0, 0, 0]
+          } else {
+            &[0, 1, 0, 0]
+          };
+          assert!(sdata.ends_with(sentinel));
+          let mut participant_data_2: SpdpDiscoveredParticipantData =
+            PlCdrDeserializerAdapter::from_bytes(&sdata, encoding).unwrap();
+          participant_data_2.updated_time = participant_data.updated_time;
+          assert_eq!(participant_data_2, participant_data);
+        }
+      }
+    }
+  }
+
+  // Parameter list with the given parameters, in either byte order. Values
+  // are given as (little endian, big endian).
+  fn parameter_list(parameters: &[(u16, &[u8], &[u8])], big_endian: bool) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (pid, le, be) in parameters {
+      let value = if big_endian { be } else { le };
+      let header = if big_endian {
+        [pid.to_be_bytes(), (value.len() as u16).to_be_bytes()]
+      } else {
+        [pid.to_le_bytes(), (value.len() as u16).to_le_bytes()]
+      };
+      bytes.extend_from_slice(&header.concat());
+      bytes.extend_from_slice(value);
+    }
+    bytes
+  }
+
+  #[test]
+  fn pdata_parameter_list_both_endians() {
+    let guid: &[u8] = &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 0, 0, 1, 0xc1];
+    let parameters: Vec<(u16, &[u8], &[u8])> = vec![
+      (0x0015, &[2, 4, 0, 0], &[2, 4, 0, 0]), // PID_PROTOCOL_VERSION
+      (0x0016, &[1, 0x12, 0, 0], &[1, 0x12, 0, 0]), // PID_VENDOR_ID
+      (0x0000, &[0; 4], &[0; 4]),             // PID_PAD
+      (0x0050, guid, guid),                   // PID_PARTICIPANT_GUID
+      (0x3f00, &[1, 2, 3, 4], &[1, 2, 3, 4]), // unknown, skipped
+      (0xc000, &[1, 2, 3, 4], &[1, 2, 3, 4]), // vendor-specific, ignored
+      (0x0058, &[0x3f, 0, 0, 0], &[0, 0, 0, 0x3f]), // PID_BUILTIN_ENDPOINT_SET
+      (
+        0x4014, // PID_DOMAIN_TAG, must be understood
+        &[4, 0, 0, 0, b'l', b'a', b'b', 0],
+        &[0, 0, 0, 4, b'l', b'a', b'b', 0],
+      ),
+      (0x0001, &[], &[]), // PID_SENTINEL
+      (
+        0x0062, // PID_ENTITY_NAME, after the sentinel, so ignored
+        &[2, 0, 0, 0, b'x', 0, 0, 0],
+        &[0, 0, 0, 2, b'x', 0, 0, 0],
+      ),
+    ];
+
+    for (encoding, big_endian) in [
+      (RepresentationIdentifier::PL_CDR_LE, false),
+      (RepresentationIdentifier::PL_CDR_BE, true),
+    ] {
+      let bytes = parameter_list(&parameters, big_endian);
+      let pdata = SpdpDiscoveredParticipantData::from_pl_cdr_bytes(&bytes, encoding).unwrap();
+      assert_eq!(pdata.protocol_version, ProtocolVersion::PROTOCOLVERSION_2_4);
+      assert_eq!(pdata.participant_guid.to_bytes(), guid);
+      assert!(pdata
+        .available_builtin_endpoints
+        .contains(BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_SUBSCRIPTIONS_DETECTOR));
+      assert_eq!(pdata.domain_tag, "lab");
+      assert_eq!(pdata.entity_name, None);
+
+      // A missing sentinel is tolerated.
+      let guid_key = Participant_GUID::from_pl_cdr_bytes(&bytes[..bytes.len() - 16], encoding);
+      assert_eq!(guid_key.unwrap().0, pdata.participant_guid);
+
+      // An unknown parameter that must be understood rejects the whole list.
+      let mut rejected = parameters.clone();
+      rejected.insert(4, (0x4f00, &[1, 2, 3, 4], &[1, 2, 3, 4]));
+      let bytes = parameter_list(&rejected, big_endian);
+      assert!(SpdpDiscoveredParticipantData::from_pl_cdr_bytes(&bytes, encoding).is_err());
+      assert!(Participant_GUID::from_pl_cdr_bytes(&bytes, encoding).is_err());
+    }
+  }
 }
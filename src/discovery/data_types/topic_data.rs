@@ -1,5 +1,6 @@
 use std::time::Instant;
 
+#[cfg(feature = "discovery")]
 use bytes::Bytes;
 use serde::{ser::Error, Deserialize, Serialize};
 use chrono::{DateTime, Utc};
@@ -11,7 +12,8 @@ use crate::{
     qos::{
       policy::{
         Deadline, DestinationOrder, Durability, History, LatencyBudget, Lifespan, Liveliness,
-        Ownership, Presentation, Reliability, ResourceLimits, TimeBasedFilter,
+        Ownership, Presentation, Reliability, ResourceLimits, TimeBasedFilter, GroupData,
+        TopicData, UserData,
       },
       HasQoSPolicy, QosPolicies,
     },
@@ -27,11 +29,10 @@ use crate::{
   },
   discovery::content_filter_property::ContentFilterProperty,
   messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
-  network::{constant::user_traffic_unicast_port, util::get_local_unicast_locators},
+  network::constant::USER_TRAFFIC_LISTENER_TOKEN,
   serialization::{
-    builtin_data_deserializer::BuiltinDataDeserializer,
-    builtin_data_serializer::BuiltinDataSerializer, error as ser,
-    pl_cdr_deserializer::PlCdrDeserialize, pl_cdr_serializer::PlCdrSerialize,
+    builtin_data_deserializer::BuiltinDataDeserializer, error as ser,
+    pl_cdr_deserializer::PlCdrDeserialize,
   },
   structure::{
     entity::RTPSEntity,
@@ -39,6 +40,10 @@ use crate::{
     locator::Locator,
   },
 };
+#[cfg(feature = "discovery")]
+use crate::serialization::{
+  builtin_data_serializer::BuiltinDataSerializer, pl_cdr_serializer::PlCdrSerialize,
+};
 #[cfg(test)]
 use crate::structure::guid::EntityKind;
 
@@ -60,7 +65,7 @@ impl PlCdrDeserialize for Endpoint_GUID {
   ) -> ser::Result<Self> {
     BuiltinDataDeserializer::new()
       .parse_data(input_bytes, encoding)
-      .generate_endpoint_guid()
+      .and_then(|data| data.generate_endpoint_guid())
       .map_err(|e| {
         ser::Error::custom(format!(
           "deserialize Endpoint_GUID - {:?} - data was {:?}",
@@ -70,6 +75,7 @@ impl PlCdrDeserialize for Endpoint_GUID {
   }
 }
 
+#[cfg(feature = "discovery")]
 impl PlCdrSerialize for Endpoint_GUID {
   fn to_pl_cdr_bytes(&self, encoding: RepresentationIdentifier) -> ser::Result<Bytes> {
     BuiltinDataSerializer::from_endpoint_guid(*self).serialize_pl_cdr_to_Bytes(encoding)
@@ -134,12 +140,12 @@ pub struct SubscriptionBuiltinTopicData {
   reliability: Option<Reliability>,
   ownership: Option<Ownership>,
   destination_order: Option<DestinationOrder>,
-  // pub user_data: Option<UserData>,
+  user_data: Option<UserData>,
   time_based_filter: Option<TimeBasedFilter>,
   presentation: Option<Presentation>,
   // pub partition: Option<Partition>,
-  // pub topic_data: Option<TopicData>,
-  // pub group_data: Option<GroupData>,
+  topic_data: Option<TopicData>,
+  group_data: Option<GroupData>,
   // pub durability_service: Option<DurabilityService>,
   lifespan: Option<Lifespan>,
 
@@ -170,8 +176,11 @@ impl SubscriptionBuiltinTopicData {
       reliability: None,
       ownership: None,
       destination_order: None,
+      user_data: None,
       time_based_filter: None,
       presentation: None,
+      topic_data: None,
+      group_data: None,
       lifespan: None,
       service_instance_name: None,
       related_datawriter_key: None,
@@ -198,6 +207,18 @@ impl SubscriptionBuiltinTopicData {
     &self.type_name
   }
 
+  pub fn user_data(&self) -> Option<&UserData> {
+    self.user_data.as_ref()
+  }
+
+  pub fn topic_data(&self) -> Option<&TopicData> {
+    self.topic_data.as_ref()
+  }
+
+  pub fn group_data(&self) -> Option<&GroupData> {
+    self.group_data.as_ref()
+  }
+
   pub fn set_qos(&mut self, qos: &QosPolicies) {
     self.durability = qos.durability;
     self.deadline = qos.deadline;
@@ -206,8 +227,11 @@ impl SubscriptionBuiltinTopicData {
     self.reliability = qos.reliability;
     self.ownership = qos.ownership;
     self.destination_order = qos.destination_order;
+    self.user_data = qos.user_data.clone();
     self.time_based_filter = qos.time_based_filter;
     self.presentation = qos.presentation;
+    self.topic_data = qos.topic_data.clone();
+    self.group_data = qos.group_data.clone();
     self.lifespan = qos.lifespan;
   }
 
@@ -225,6 +249,11 @@ impl SubscriptionBuiltinTopicData {
       history: None, // SubscriptionBuiltinTopicData does not contain History QoS
       resource_limits: None, // nor Resource Limits, see Figure 8.30 in RTPS spec 2.5
       lifespan: self.lifespan,
+      transport_priority: None,
+      user_data: self.user_data.clone(),
+      topic_data: self.topic_data.clone(),
+      group_data: self.group_data.clone(),
+      entity_factory: None,
     }
   }
 
@@ -286,7 +315,7 @@ impl PlCdrDeserialize for DiscoveredReaderData {
   ) -> ser::Result<Self> {
     BuiltinDataDeserializer::new()
       .parse_data(input_bytes, encoding)
-      .generate_discovered_reader_data()
+      .and_then(|data| data.generate_discovered_reader_data())
       .map_err(|e| {
         ser::Error::custom(format!(
           "DiscoveredReaderData::deserialize - {:?} - data was {:?}",
@@ -296,6 +325,7 @@ impl PlCdrDeserialize for DiscoveredReaderData {
   }
 }
 
+#[cfg(feature = "discovery")]
 impl PlCdrSerialize for DiscoveredReaderData {
   fn to_pl_cdr_bytes(&self, encoding: RepresentationIdentifier) -> ser::Result<Bytes> {
     BuiltinDataSerializer::from_discovered_reader_data(self).serialize_pl_cdr_to_Bytes(encoding)
@@ -362,6 +392,9 @@ pub struct PublicationBuiltinTopicData {
   pub ownership: Option<Ownership>,
   pub destination_order: Option<DestinationOrder>,
   pub presentation: Option<Presentation>,
+  pub user_data: Option<UserData>,
+  pub topic_data: Option<TopicData>,
+  pub group_data: Option<GroupData>,
 
   // From Remote Procedure Call over DDS:
   pub service_instance_name: Option<String>,
@@ -387,6 +420,9 @@ impl PublicationBuiltinTopicData {
       ownership: None,
       destination_order: None,
       presentation: None,
+      user_data: None,
+      topic_data: None,
+      group_data: None,
 
       service_instance_name: None,
       related_datareader_key: None,
@@ -405,6 +441,9 @@ impl PublicationBuiltinTopicData {
     self.ownership = qos.ownership;
     self.destination_order = qos.destination_order;
     self.presentation = qos.presentation;
+    self.user_data = qos.user_data.clone();
+    self.topic_data = qos.topic_data.clone();
+    self.group_data = qos.group_data.clone();
   }
 
   pub fn qos(&self) -> QosPolicies {
@@ -421,6 +460,11 @@ impl PublicationBuiltinTopicData {
       history: None,         // PublicationBuiltinTopicData does not contain History QoS
       resource_limits: None, // nor Resource Limits, see Figure 8.30 in RTPS spec 2.5
       lifespan: self.lifespan,
+      transport_priority: None,
+      user_data: self.user_data.clone(),
+      topic_data: self.topic_data.clone(),
+      group_data: self.group_data.clone(),
+      entity_factory: None,
     }
   }
 
@@ -465,18 +509,30 @@ impl DiscoveredWriterData {
     topic: &Topic,
     dp: &DomainParticipant,
   ) -> Self {
-    let unicast_port = user_traffic_unicast_port(dp.domain_id(), dp.participant_id());
-    let unicast_addresses = get_local_unicast_locators(unicast_port);
+    Self::from_local_writer(writer.guid(), &writer.qos(), topic, dp)
+  }
 
-    let writer_proxy = WriterProxy::new(writer.guid(), vec![], unicast_addresses);
+  // For a DataWriter that is being enabled, and may not exist as such yet.
+  pub(crate) fn from_local_writer(
+    writer_guid: GUID,
+    qos: &QosPolicies,
+    topic: &Topic,
+    dp: &DomainParticipant,
+  ) -> Self {
+    let unicast_addresses = dp
+      .self_locators()
+      .remove(&USER_TRAFFIC_LISTENER_TOKEN)
+      .unwrap_or_default();
+
+    let writer_proxy = WriterProxy::new(writer_guid, vec![], unicast_addresses);
     let mut publication_topic_data = PublicationBuiltinTopicData::new(
-      writer.guid(),
+      writer_guid,
       dp.guid(),
       topic.name(),
       topic.get_type().name().to_string(),
     );
 
-    publication_topic_data.set_qos(&writer.qos());
+    publication_topic_data.set_qos(qos);
 
     Self {
       last_updated: Instant::now(),
@@ -493,7 +549,7 @@ impl PlCdrDeserialize for DiscoveredWriterData {
   ) -> ser::Result<Self> {
     BuiltinDataDeserializer::new()
       .parse_data(input_bytes, encoding)
-      .generate_discovered_writer_data()
+      .and_then(|data| data.generate_discovered_writer_data())
       .map_err(|e| {
         ser::Error::custom(format!(
           "DiscoveredWriterData::deserialize - {:?} - data was {:?}",
@@ -503,6 +559,7 @@ impl PlCdrDeserialize for DiscoveredWriterData {
   }
 }
 
+#[cfg(feature = "discovery")]
 impl PlCdrSerialize for DiscoveredWriterData {
   fn to_pl_cdr_bytes(&self, encoding: RepresentationIdentifier) -> ser::Result<Bytes> {
     BuiltinDataSerializer::from_discovered_writer_data(self).serialize_pl_cdr_to_Bytes(encoding)
@@ -530,6 +587,7 @@ pub struct TopicBuiltinTopicData {
   pub history: Option<History>,
   pub resource_limits: Option<ResourceLimits>,
   pub ownership: Option<Ownership>,
+  pub topic_data: Option<TopicData>,
 }
 
 impl TopicBuiltinTopicData {
@@ -549,6 +607,7 @@ impl TopicBuiltinTopicData {
       history: qos.history(),
       resource_limits: qos.resource_limits(),
       ownership: qos.ownership(),
+      topic_data: qos.topic_data(),
     }
   }
 }
@@ -568,6 +627,11 @@ impl HasQoSPolicy for TopicBuiltinTopicData {
       history: self.history,
       resource_limits: self.resource_limits,
       lifespan: self.lifespan,
+      transport_priority: None,
+      user_data: None,
+      topic_data: self.topic_data.clone(),
+      group_data: None,
+      entity_factory: None,
     }
   }
 }
@@ -623,7 +687,7 @@ impl PlCdrDeserialize for DiscoveredTopicData {
   ) -> ser::Result<Self> {
     BuiltinDataDeserializer::new()
       .parse_data(input_bytes, encoding)
-      .generate_topic_data()
+      .and_then(|data| data.generate_topic_data())
       .map_err(|e| {
         ser::Error::custom(format!(
           "DiscoveredTopicData::deserialize - {:?} - data was {:?}",
@@ -634,6 +698,7 @@ impl PlCdrDeserialize for DiscoveredTopicData {
   }
 }
 
+#[cfg(feature = "discovery")]
 impl PlCdrSerialize for DiscoveredTopicData {
   fn to_pl_cdr_bytes(&self, encoding: RepresentationIdentifier) -> ser::Result<Bytes> {
     BuiltinDataSerializer::from_topic_data(&self.topic_data).serialize_pl_cdr_to_Bytes(encoding)
@@ -696,7 +761,7 @@ impl Key for ParticipantMessageDataKey {}
 // =======================================================================
 // =======================================================================
 
-#[cfg(test)]
+#[cfg(all(test, feature = "discovery"))]
 mod tests {
   use byteorder::LittleEndian;
   use bytes::Bytes;
@@ -1,3 +1,5 @@
+#[cfg(feature = "discovery")]
+use std::collections::BTreeMap;
 use std::{
   collections::HashMap,
   sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
@@ -7,10 +9,13 @@ use std::{
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 use mio::{Events, Poll, PollOpt, Ready, Token};
-use mio_extras::{channel as mio_channel, timer::Timer};
+use mio_extras::channel as mio_channel;
 
 use crate::{
   dds::{
+    clock::TimeSource,
+    participant::DomainParticipant,
+    health::{HealthMonitor, ParticipantHealth, ProtocolThread},
     participant::DomainParticipantWeak,
     qos::{
       policy::{
@@ -20,41 +25,85 @@ use crate::{
       QosPolicies, QosPolicyBuilder,
     },
     readcondition::ReadCondition,
+    runtime_metrics,
+    statistics::StatisticsMonitor,
+    statusevents::{
+      DiscoveryEvent, DomainParticipantStatus, ParticipantLostReason, StatusBroadcaster,
+    },
+    no_key::{
+      datareader::DataReader as NoKeyDataReader, datawriter::DataWriter as NoKeyDataWriter,
+    },
+    threads::BackgroundLoop,
+    timer::Timer,
     topic::*,
     values::result::{Error, Result},
-    with_key::{
-      datareader::{DataReader, DataReaderCdr},
-      datawriter::{DataWriter, DataWriterCdr},
-    },
-    SubscriptionBuiltinTopicData,
+    with_key::{datareader::DataReaderCdr, datawriter::DataWriterCdr},
   },
   discovery::{
     data_types::{
-      spdp_participant_data::{Participant_GUID, SpdpDiscoveredParticipantData},
-      topic_data::{
-        DiscoveredReaderData, DiscoveredWriterData, Endpoint_GUID, PublicationBuiltinTopicData,
-        ReaderProxy, WriterProxy,
-      },
+      spdp_participant_data::SpdpDiscoveredParticipantData,
+      topic_data::{DiscoveredReaderData, DiscoveredWriterData},
+    },
+    authentication::AuthenticatorHandle,
+    discovery_db::DiscoveryDB,
+    static_endpoint::StaticEndpoint,
+    type_lookup::{
+      TypeIdentifier, TypeLookupAdapter, TypeLookupCall, TypeLookupReply, TypeLookupRequest,
+      TypeLookupReturn,
     },
-    discovery_db::{DiscoveredVia, DiscoveryDB},
   },
   network::constant::*,
-  serialization::{
-    pl_cdr_deserializer::PlCdrDeserializerAdapter, pl_cdr_serializer::PlCdrSerializerAdapter,
-  },
+  security::SecurityConfig,
   structure::{
+    builtin_endpoint::BuiltinEndpointSet,
+    clock_monitor::ClockMonitor,
     duration::Duration,
     entity::RTPSEntity,
     guid::{EntityId, GuidPrefix, GUID},
     locator::Locator,
+    rpc::{RemoteExceptionCode, ReplyHeader, RequestHeader, SampleIdentity},
+    sequence_number::SequenceNumber,
     time::Timestamp,
   },
 };
-use super::data_types::topic_data::{
-  DiscoveredTopicData, ParticipantMessageData, ParticipantMessageDataKind,
+use super::data_types::topic_data::{ParticipantMessageData, ParticipantMessageDataKind};
+#[cfg(feature = "discovery")]
+use crate::{
+  dds::{
+    with_key::{datareader::DataReader, datawriter::DataWriter},
+    SubscriptionBuiltinTopicData,
+  },
+  discovery::{
+    data_types::{
+      spdp_participant_data::Participant_GUID,
+      topic_data::{
+        DiscoveredTopicData, Endpoint_GUID, PublicationBuiltinTopicData, ReaderProxy, WriterProxy,
+      },
+    },
+    discovery_db::DiscoveredVia,
+  },
+  serialization::{
+    pl_cdr_deserializer::PlCdrDeserializerAdapter, pl_cdr_serializer::PlCdrSerializerAdapter,
+  },
+};
+#[cfg(feature = "statistics")]
+use crate::dds::statistics::ParticipantStatistics;
+#[cfg(feature = "security")]
+use std::cell::Cell;
+
+#[cfg(feature = "security")]
+use crate::{
+  security::{
+    access_control::TopicAccess,
+    authentication::HandshakeProgress,
+    generic_message::{MessageIdentity, ParticipantGenericMessage},
+    plugins::{AccessControlHandle, AuthenticationHandle, CryptographyHandle},
+    types::DataHolder,
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
 };
 
-#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Clone, PartialEq, Eq)]
 pub enum DiscoveryCommand {
   StopDiscovery,
   RemoveLocalWriter {
@@ -63,11 +112,38 @@ pub enum DiscoveryCommand {
   RemoveLocalReader {
     guid: GUID,
   },
+  // QoS of a local DataWriter or DataReader has been changed with set_qos.
+  UpdateLocalWriter {
+    guid: GUID,
+    qos: QosPolicies,
+  },
+  UpdateLocalReader {
+    guid: GUID,
+    qos: QosPolicies,
+  },
   ManualAssertLiveliness,
   AssertTopicLiveliness {
     writer_guid: GUID,
     manual_assertion: bool,
   },
+  // Application does not want to communicate with these.
+  IgnoreParticipant {
+    guid_prefix: GuidPrefix,
+  },
+  IgnorePublication {
+    guid: GUID,
+  },
+  IgnoreSubscription {
+    guid: GUID,
+  },
+  // Remote endpoint declared by the application, not discovered.
+  AddStaticEndpoint {
+    endpoint: StaticEndpoint,
+  },
+  // Ask remote participants for TypeObjects via TypeLookup service.
+  RequestTypeObjects {
+    type_ids: Vec<TypeIdentifier>,
+  },
 }
 
 pub struct LivelinessState {
@@ -76,10 +152,10 @@ pub struct LivelinessState {
 }
 
 impl LivelinessState {
-  pub fn new() -> Self {
+  pub fn new(now: Timestamp) -> Self {
     Self {
-      last_auto_update: Timestamp::now(),
-      last_manual_participant_update: Timestamp::now(),
+      last_auto_update: now,
+      last_manual_participant_update: now,
     }
   }
 }
@@ -98,13 +174,92 @@ pub(crate) struct Discovery {
   spdp_liveness_receiver: mio_channel::Receiver<GuidPrefix>,
 
   liveliness_state: LivelinessState,
+  // The clock of the participant, for liveliness and leases
+  time_source: TimeSource,
+  // Detects clock jumps, so that we can restart lease timers
+  clock_monitor: ClockMonitor,
+  // Participant-level status events to application
+  participant_status_broadcaster: StatusBroadcaster<DomainParticipantStatus>,
+  // Changes in remote participants and endpoints to application
+  discovery_event_broadcaster: StatusBroadcaster<DiscoveryEvent>,
   self_locators: HashMap<Token, Vec<Locator>>,
+  // Configured SPDP unicast locators of remote participants. We announce
+  // ourselves to these also, in case multicast does not get through.
+  #[cfg(feature = "discovery")]
+  initial_peers: Vec<Locator>,
+  // If SEDP is disabled, endpoints are known only from static configuration.
+  sedp_enabled: bool,
+  static_endpoints: Vec<StaticEndpoint>,
+  // Participants with a different domain tag are ignored.
+  #[cfg(feature = "discovery")]
+  domain_tag: String,
+  // Decides if newly discovered participants are accepted
+  #[cfg(feature = "discovery")]
+  participant_authenticator: Option<AuthenticatorHandle>,
 
   // DDS Subsciber and Publisher for Discovery
   // ...but these are not actually used after initialization
   //discovery_subscriber: Subscriber,
   //discovery_publisher: Publisher,
 
+  // SPDP and SEDP. Without the "discovery" feature, remote endpoints are known
+  // only from static configuration.
+  #[cfg(feature = "discovery")]
+  spdp_sedp: SpdpSedp,
+
+  participant_cleanup_timer: Timer<()>, // garbage collection timer for dead remote particiapnts
+  // Our readers and writers, and the static endpoints, are updated on these.
+  readers_send_info_timer: Timer<()>,
+  writers_send_info_timer: Timer<()>,
+  topic_cleanup_timer: Timer<()>,
+
+  // DCPSParticipantMessage - used by participants to communicate liveness
+  #[allow(dead_code)] // Technically, the topic is not accesssed after initialization
+  participant_message_topic: Topic,
+  dcps_participant_message_reader: DataReaderCdr<ParticipantMessageData>,
+  dcps_participant_message_writer: DataWriterCdr<ParticipantMessageData>,
+  dcps_participant_message_timer: Timer<()>,
+
+  // XTypes TypeLookup service. We answer getTypes requests from the
+  // TypeObjects in DiscoveryDB, and store TypeObjects received in replies.
+  #[allow(dead_code)] // Technically, the topic is not accesssed after initialization
+  type_lookup_request_topic: Topic,
+  type_lookup_request_reader:
+    NoKeyDataReader<TypeLookupRequest, TypeLookupAdapter<TypeLookupRequest>>,
+  type_lookup_request_writer:
+    NoKeyDataWriter<TypeLookupRequest, TypeLookupAdapter<TypeLookupRequest>>,
+  #[allow(dead_code)] // Technically, the topic is not accesssed after initialization
+  type_lookup_reply_topic: Topic,
+  type_lookup_reply_reader: NoKeyDataReader<TypeLookupReply, TypeLookupAdapter<TypeLookupReply>>,
+  type_lookup_reply_writer: NoKeyDataWriter<TypeLookupReply, TypeLookupAdapter<TypeLookupReply>>,
+  // Sequence number for request ids
+  type_lookup_request_count: i64,
+
+  // Participant self-health. Checked periodically, and published on
+  // the vendor topic if so configured.
+  health_monitor: HealthMonitor,
+  health_writer: Option<DataWriterCdr<ParticipantHealth>>,
+  health_timer: Timer<()>,
+
+  // Participant statistics, published on the vendor topic with the given
+  // period, if so configured.
+  #[cfg(feature = "statistics")]
+  statistics_monitor: StatisticsMonitor,
+  #[cfg(feature = "statistics")]
+  statistics_writer: Option<(DataWriterCdr<ParticipantStatistics>, StdDuration)>,
+  #[cfg(feature = "statistics")]
+  statistics_timer: Timer<()>,
+
+  // DDS Security authentication and access control. Present if the participant
+  // has an identity.
+  #[cfg(feature = "security")]
+  authentication: Option<AuthenticationService>,
+}
+
+// Builtin endpoints of SPDP and SEDP, where participants announce themselves,
+// their DataReaders and DataWriters, and their topics.
+#[cfg(feature = "discovery")]
+struct SpdpSedp {
   // Handling of "DCPSParticipant" topic. This is the mother of all topics
   // where participants announce their presence and built-in readers and writers.
   #[allow(dead_code)] // Technically, the topic is not accesssed after initialization
@@ -117,7 +272,6 @@ pub(crate) struct Discovery {
     SpdpDiscoveredParticipantData,
     PlCdrSerializerAdapter<SpdpDiscoveredParticipantData>,
   >,
-  participant_cleanup_timer: Timer<()>, // garbage collection timer for dead remote particiapnts
   participant_send_info_timer: Timer<()>, // timer to periodically announce our presence
 
   // Topic "DCPSSubscription" - announcing and detecting Readers
@@ -127,7 +281,6 @@ pub(crate) struct Discovery {
     DataReader<DiscoveredReaderData, PlCdrDeserializerAdapter<DiscoveredReaderData>>,
   dcps_subscription_writer:
     DataWriter<DiscoveredReaderData, PlCdrSerializerAdapter<DiscoveredReaderData>>,
-  readers_send_info_timer: Timer<()>,
 
   // Topic "DCPSPublication" - announcing and detecting Writers
   #[allow(dead_code)] // Technically, the topic is not accesssed after initialization
@@ -136,7 +289,6 @@ pub(crate) struct Discovery {
     DataReader<DiscoveredWriterData, PlCdrDeserializerAdapter<DiscoveredWriterData>>,
   dcps_publication_writer:
     DataWriter<DiscoveredWriterData, PlCdrSerializerAdapter<DiscoveredWriterData>>,
-  writers_send_info_timer: Timer<()>,
 
   // Topic "DCPSTopic" - annoncing and detecting topics
   #[allow(dead_code)] // Technically, the topic is not accesssed after initialization
@@ -144,24 +296,118 @@ pub(crate) struct Discovery {
   dcps_topic_reader: DataReader<DiscoveredTopicData, PlCdrDeserializerAdapter<DiscoveredTopicData>>,
   dcps_topic_writer: DataWriter<DiscoveredTopicData, PlCdrSerializerAdapter<DiscoveredTopicData>>,
   topic_info_send_timer: Timer<()>,
-  topic_cleanup_timer: Timer<()>,
+}
 
-  // DCPSParticipantMessage - used by participants to communicate liveness
+// Builtin endpoints of the authentication handshake, and the permissions of
+// authenticated participants
+#[cfg(feature = "security")]
+struct AuthenticationService {
+  authentication: AuthenticationHandle,
+  access_control: Option<AccessControlHandle>,
   #[allow(dead_code)] // Technically, the topic is not accesssed after initialization
-  participant_message_topic: Topic,
-  dcps_participant_message_reader: DataReaderCdr<ParticipantMessageData>,
-  dcps_participant_message_writer: DataWriterCdr<ParticipantMessageData>,
-  dcps_participant_message_timer: Timer<()>,
+  topic: Topic,
+  reader:
+    NoKeyDataReader<ParticipantGenericMessage, CDRDeserializerAdapter<ParticipantGenericMessage>>,
+  writer:
+    NoKeyDataWriter<ParticipantGenericMessage, CDRSerializerAdapter<ParticipantGenericMessage>>,
+  resend_timer: Timer<()>,
+  // Present if the governance protects anything
+  key_exchange: Option<KeyExchange>,
+}
+
+// Builtin endpoints of the crypto token exchange with authenticated
+// participants
+#[cfg(feature = "security")]
+struct KeyExchange {
+  cryptography: CryptographyHandle,
+  #[allow(dead_code)] // Technically, the topic is not accesssed after initialization
+  topic: Topic,
+  reader:
+    NoKeyDataReader<ParticipantGenericMessage, CDRDeserializerAdapter<ParticipantGenericMessage>>,
+  writer:
+    NoKeyDataWriter<ParticipantGenericMessage, CDRSerializerAdapter<ParticipantGenericMessage>>,
+  sequence_number: Cell<i64>,
+}
+
+#[cfg(feature = "security")]
+impl KeyExchange {
+  fn send(
+    &self,
+    message_class_id: &str,
+    source_endpoint_guid: GUID,
+    destination_endpoint_guid: GUID,
+    message_data: Vec<DataHolder>,
+  ) {
+    let sequence_number = self.sequence_number.get() + 1;
+    self.sequence_number.set(sequence_number);
+    let message = ParticipantGenericMessage {
+      message_identity: MessageIdentity {
+        source_guid: self.writer.guid(),
+        sequence_number,
+      },
+      related_message_identity: MessageIdentity::default(),
+      destination_participant_guid: GUID::new(
+        destination_endpoint_guid.prefix,
+        EntityId::PARTICIPANT,
+      ),
+      destination_endpoint_guid,
+      source_endpoint_guid,
+      message_class_id: message_class_id.to_string(),
+      message_data,
+    };
+    self
+      .writer
+      .write(message, None)
+      .unwrap_or_else(|e| error!("Cannot send crypto tokens: {:?}", e));
+  }
+
+  // The participant tokens are sent to the participant itself, without
+  // endpoints.
+  fn send_participant_tokens(&self, guid_prefix: GuidPrefix) {
+    match self.cryptography.participant_crypto_tokens() {
+      Ok(tokens) => self.send(
+        ParticipantGenericMessage::PARTICIPANT_CRYPTO_TOKENS,
+        GUID::GUID_UNKNOWN,
+        GUID::new(guid_prefix, EntityId::UNKNOWN),
+        tokens,
+      ),
+      Err(e) => error!("Cannot send participant crypto tokens: {}", e),
+    }
+  }
+
+  // Tokens of a local endpoint for a remote one, unless they have been sent
+  // already.
+  fn send_endpoint_tokens(&self, local: GUID, remote: GUID, message_class_id: &str) {
+    match self
+      .cryptography
+      .endpoint_tokens_to_send(local.entity_id, remote)
+    {
+      Ok(Some(tokens)) => self.send(message_class_id, local, remote, tokens),
+      Ok(None) => (),
+      Err(e) => error!("Cannot send crypto tokens of {:?}: {}", local, e),
+    }
+  }
 }
 
 impl Discovery {
   const PARTICIPANT_CLEANUP_PERIOD: StdDuration = StdDuration::from_secs(2);
+  const PARTICIPANT_CLEANUP_MIN_DELAY: StdDuration = StdDuration::from_millis(10);
   const TOPIC_CLEANUP_PERIOD: StdDuration = StdDuration::from_secs(60); // timer for cleaning up inactive topics
+  #[cfg(feature = "discovery")]
   const SEND_PARTICIPANT_INFO_PERIOD: StdDuration = StdDuration::from_secs(2);
   const SEND_READERS_INFO_PERIOD: StdDuration = StdDuration::from_secs(2);
   const SEND_WRITERS_INFO_PERIOD: StdDuration = StdDuration::from_secs(2);
+  #[cfg(feature = "discovery")]
   const SEND_TOPIC_INFO_PERIOD: StdDuration = StdDuration::from_secs(10);
   const CHECK_PARTICIPANT_MESSAGES: StdDuration = StdDuration::from_secs(1);
+  const CHECK_HEALTH_PERIOD: StdDuration = StdDuration::from_secs(5);
+  #[cfg(feature = "security")]
+  const HANDSHAKE_RESEND_PERIOD: StdDuration = StdDuration::from_secs(1);
+  #[cfg(feature = "security")]
+  const VOLATILE_MESSAGE_TOPIC: &'static str = "DCPSParticipantVolatileMessageSecure";
+  // How much clocks may disagree, or cleanup timer may be late, before we
+  // consider it a clock jump.
+  const CLOCK_JUMP_TOLERANCE: StdDuration = StdDuration::from_secs(5);
 
   pub(crate) const PARTICIPANT_MESSAGE_QOS: QosPolicies = QosPolicies {
     durability: Some(Durability::TransientLocal),
@@ -178,8 +424,84 @@ impl Discovery {
     history: Some(History::KeepLast { depth: 1 }),
     resource_limits: None,
     lifespan: None,
+    transport_priority: None,
+    user_data: None,
+    topic_data: None,
+    group_data: None,
+    entity_factory: None,
   };
 
+  // TypeLookup requests and replies are not of interest to late joiners.
+  pub(crate) const TYPE_LOOKUP_QOS: QosPolicies = QosPolicies {
+    durability: Some(Durability::Volatile),
+    presentation: None,
+    deadline: None,
+    latency_budget: None,
+    ownership: None,
+    liveliness: None,
+    time_based_filter: None,
+    reliability: Some(Reliability::Reliable {
+      max_blocking_time: Duration::DURATION_ZERO,
+    }),
+    destination_order: None,
+    history: Some(History::KeepLast { depth: 10 }),
+    resource_limits: None,
+    lifespan: None,
+    transport_priority: None,
+    user_data: None,
+    topic_data: None,
+    group_data: None,
+    entity_factory: None,
+  };
+
+  // DDS Security stateless messages (the authentication handshake) are sent
+  // best-effort. Lost messages are resent by the handshake itself.
+  pub(crate) const STATELESS_MESSAGE_QOS: QosPolicies = QosPolicies {
+    durability: Some(Durability::Volatile),
+    presentation: None,
+    deadline: None,
+    latency_budget: None,
+    ownership: None,
+    liveliness: None,
+    time_based_filter: None,
+    reliability: Some(Reliability::BestEffort),
+    destination_order: None,
+    history: Some(History::KeepLast { depth: 10 }),
+    resource_limits: None,
+    lifespan: None,
+    transport_priority: None,
+    user_data: None,
+    topic_data: None,
+    group_data: None,
+    entity_factory: None,
+  };
+
+  // Crypto tokens are sent reliably, and only to participants that have
+  // completed authentication, so there is no history for late joiners.
+  pub(crate) const VOLATILE_MESSAGE_QOS: QosPolicies = QosPolicies {
+    durability: Some(Durability::Volatile),
+    presentation: None,
+    deadline: None,
+    latency_budget: None,
+    ownership: None,
+    liveliness: None,
+    time_based_filter: None,
+    reliability: Some(Reliability::Reliable {
+      max_blocking_time: Duration::DURATION_ZERO,
+    }),
+    destination_order: None,
+    history: Some(History::KeepAll),
+    resource_limits: None,
+    lifespan: None,
+    transport_priority: None,
+    user_data: None,
+    topic_data: None,
+    group_data: None,
+    entity_factory: None,
+  };
+
+  #[allow(clippy::too_many_arguments)]
+  #[cfg_attr(not(feature = "security"), allow(clippy::needless_pass_by_value))]
   pub fn new(
     domain_participant: DomainParticipantWeak,
     discovery_db: Arc<RwLock<DiscoveryDB>>,
@@ -188,6 +510,23 @@ impl Discovery {
     discovery_command_receiver: mio_channel::Receiver<DiscoveryCommand>,
     spdp_liveness_receiver: mio_channel::Receiver<GuidPrefix>,
     self_locators: HashMap<Token, Vec<Locator>>,
+    #[cfg_attr(not(feature = "discovery"), allow(unused_variables))] initial_peers: Vec<Locator>,
+    participant_status_broadcaster: StatusBroadcaster<DomainParticipantStatus>,
+    discovery_event_broadcaster: StatusBroadcaster<DiscoveryEvent>,
+    health_monitor: HealthMonitor,
+    publish_health: bool,
+    #[cfg_attr(not(feature = "statistics"), allow(unused_variables))]
+    statistics_monitor: StatisticsMonitor,
+    #[cfg_attr(not(feature = "statistics"), allow(unused_variables))] publish_statistics: Option<
+      StdDuration,
+    >,
+    sedp_enabled: bool,
+    static_endpoints: Vec<StaticEndpoint>,
+    #[cfg_attr(not(feature = "discovery"), allow(unused_variables))] domain_tag: String,
+    #[cfg_attr(not(feature = "discovery"), allow(unused_variables))]
+    participant_authenticator: Option<AuthenticatorHandle>,
+    time_source: TimeSource,
+    #[cfg_attr(not(feature = "security"), allow(unused_variables))] security: SecurityConfig,
   ) -> Result<Self> {
     // helper macro to handle initialization failures.
     macro_rules! try_construct {
@@ -241,48 +580,8 @@ impl Discovery {
       "Unable to create Discovery Publisher. {:?}"
     );
 
-    // Participant
-    let dcps_participant_topic = try_construct!(
-      domain_participant.create_topic(
-        "DCPSParticipant".to_string(),
-        "SPDPDiscoveredParticipantData".to_string(),
-        &Self::create_spdp_patricipant_qos(),
-        TopicKind::WithKey,
-      ),
-      "Unable to create DCPSParticipant topic. {:?}"
-    );
-
-    let dcps_participant_reader = try_construct!( discovery_subscriber
-      .create_datareader_with_entityid
-        ::<SpdpDiscoveredParticipantData,PlCdrDeserializerAdapter<SpdpDiscoveredParticipantData>>(
-        &dcps_participant_topic,
-        EntityId::SPDP_BUILTIN_PARTICIPANT_READER,
-        None,
-      ) ,"Unable to create DataReader for DCPSParticipant. {:?}");
-
-    let dcps_participant_writer = try_construct!(
-      discovery_publisher.create_datawriter_with_entityid
-        ::<SpdpDiscoveredParticipantData,PlCdrSerializerAdapter<SpdpDiscoveredParticipantData>>(
-        EntityId::SPDP_BUILTIN_PARTICIPANT_WRITER,
-        &dcps_participant_topic,
-        None,
-      ),
-      "Unable to create DataWriter for DCPSParticipant. {:?}"
-    );
-
-    // register participant reader
-    try_construct!(
-      poll.register(
-        &dcps_participant_reader,
-        DISCOVERY_PARTICIPANT_DATA_TOKEN,
-        Ready::readable(),
-        PollOpt::edge(),
-      ),
-      "Failed to register participant reader to poll. {:?}"
-    );
-
     // create lease duration check timer
-    let mut participant_cleanup_timer: Timer<()> = Timer::default();
+    let mut participant_cleanup_timer = time_source.timer();
     participant_cleanup_timer.set_timeout(Self::PARTICIPANT_CLEANUP_PERIOD, ());
     try_construct!(
       poll.register(
@@ -294,245 +593,593 @@ impl Discovery {
       "Unable to create participant cleanup timer. {:?}"
     );
 
-    // creating timer for sending out own participant data
-    let mut participant_send_info_timer: Timer<()> = Timer::default();
-    participant_send_info_timer.set_timeout(Self::SEND_PARTICIPANT_INFO_PERIOD, ());
+    let mut readers_send_info_timer = time_source.timer();
+    readers_send_info_timer.set_timeout(Self::SEND_READERS_INFO_PERIOD, ());
 
     try_construct!(
       poll.register(
-        &participant_send_info_timer,
-        DISCOVERY_SEND_PARTICIPANT_INFO_TOKEN,
+        &readers_send_info_timer,
+        DISCOVERY_SEND_READERS_INFO_TOKEN,
         Ready::readable(),
         PollOpt::edge(),
       ),
-      "Unable to register participant info sender. {:?}"
-    );
-
-    // Subscriptions: What are the Readers on the network and what are they
-    // subscribing to?
-
-    let dcps_subscription_topic = try_construct!(
-      domain_participant.create_topic(
-        "DCPSSubscription".to_string(),
-        "DiscoveredReaderData".to_string(),
-        &discovery_subscriber_qos,
-        TopicKind::WithKey,
-      ),
-      "Unable to create DCPSSubscription topic. {:?}"
+      "Unable to register readers info sender. {:?}"
     );
 
-    let dcps_subscription_reader = try_construct!( discovery_subscriber
-      .create_datareader_with_entityid::<DiscoveredReaderData, PlCdrDeserializerAdapter<DiscoveredReaderData>>(
-        &dcps_subscription_topic,
-        EntityId::SEDP_BUILTIN_SUBSCRIPTIONS_READER,
-        None,
-      ) ,"Unable to create DataReader for DCPSSubscription. {:?}");
+    let mut writers_send_info_timer = time_source.timer();
+    writers_send_info_timer.set_timeout(Self::SEND_WRITERS_INFO_PERIOD, ());
 
     try_construct!(
       poll.register(
-        &dcps_subscription_reader,
-        DISCOVERY_READER_DATA_TOKEN,
+        &writers_send_info_timer,
+        DISCOVERY_SEND_WRITERS_INFO_TOKEN,
         Ready::readable(),
         PollOpt::edge(),
       ),
-      "Unable to register subscription reader. {:?}"
-    );
-
-    let dcps_subscription_writer = try_construct!(
-      discovery_publisher.create_datawriter_with_entityid
-        ::<DiscoveredReaderData,PlCdrSerializerAdapter<DiscoveredReaderData>>(
-        EntityId::SEDP_BUILTIN_SUBSCRIPTIONS_WRITER,
-        &dcps_subscription_topic,
-        None,
-      ),
-      "Unable to create DataWriter for DCPSSubscription. {:?}"
+      "Unable to register readers info sender. {:?}"
     );
 
-    let mut readers_send_info_timer: Timer<()> = Timer::default();
-
-    readers_send_info_timer.set_timeout(Self::SEND_READERS_INFO_PERIOD, ());
-
+    // create lease duration check timer
+    let mut topic_cleanup_timer = time_source.timer();
+    topic_cleanup_timer.set_timeout(Self::TOPIC_CLEANUP_PERIOD, ());
     try_construct!(
       poll.register(
-        &readers_send_info_timer,
-        DISCOVERY_SEND_READERS_INFO_TOKEN,
+        &topic_cleanup_timer,
+        DISCOVERY_TOPIC_CLEANUP_TOKEN,
         Ready::readable(),
         PollOpt::edge(),
       ),
-      "Unable to register readers info sender. {:?}"
+      "Unable to register topic cleanup timer. {:?}"
     );
 
-    // Publication : Who are the Writers here and elsewhere
+    // SPDP and SEDP
+    #[cfg(feature = "discovery")]
+    let spdp_sedp = {
+      // Participant
+      let dcps_participant_topic = try_construct!(
+        domain_participant.create_topic(
+          "DCPSParticipant".to_string(),
+          "SPDPDiscoveredParticipantData".to_string(),
+          &Self::create_spdp_patricipant_qos(),
+          TopicKind::WithKey,
+        ),
+        "Unable to create DCPSParticipant topic. {:?}"
+      );
+
+      let dcps_participant_reader = try_construct!( discovery_subscriber
+        .create_datareader_with_entityid
+          ::<SpdpDiscoveredParticipantData,PlCdrDeserializerAdapter<SpdpDiscoveredParticipantData>>(
+          &dcps_participant_topic,
+          EntityId::SPDP_BUILTIN_PARTICIPANT_READER,
+          None,
+        ) ,"Unable to create DataReader for DCPSParticipant. {:?}");
+
+      let dcps_participant_writer = try_construct!(
+        discovery_publisher.create_datawriter_with_entityid
+          ::<SpdpDiscoveredParticipantData,PlCdrSerializerAdapter<SpdpDiscoveredParticipantData>>(
+          EntityId::SPDP_BUILTIN_PARTICIPANT_WRITER,
+          &dcps_participant_topic,
+          None,
+        ),
+        "Unable to create DataWriter for DCPSParticipant. {:?}"
+      );
+
+      // register participant reader
+      try_construct!(
+        poll.register(
+          &dcps_participant_reader,
+          DISCOVERY_PARTICIPANT_DATA_TOKEN,
+          Ready::readable(),
+          PollOpt::edge(),
+        ),
+        "Failed to register participant reader to poll. {:?}"
+      );
+
+      // creating timer for sending out own participant data
+      let mut participant_send_info_timer = time_source.timer();
+      participant_send_info_timer.set_timeout(Self::SEND_PARTICIPANT_INFO_PERIOD, ());
+
+      try_construct!(
+        poll.register(
+          &participant_send_info_timer,
+          DISCOVERY_SEND_PARTICIPANT_INFO_TOKEN,
+          Ready::readable(),
+          PollOpt::edge(),
+        ),
+        "Unable to register participant info sender. {:?}"
+      );
+
+      // Subscriptions: What are the Readers on the network and what are they
+      // subscribing to?
+
+      let dcps_subscription_topic = try_construct!(
+        domain_participant.create_topic(
+          "DCPSSubscription".to_string(),
+          "DiscoveredReaderData".to_string(),
+          &discovery_subscriber_qos,
+          TopicKind::WithKey,
+        ),
+        "Unable to create DCPSSubscription topic. {:?}"
+      );
+
+      let dcps_subscription_reader = try_construct!( discovery_subscriber
+        .create_datareader_with_entityid::<DiscoveredReaderData, PlCdrDeserializerAdapter<DiscoveredReaderData>>(
+          &dcps_subscription_topic,
+          EntityId::SEDP_BUILTIN_SUBSCRIPTIONS_READER,
+          None,
+        ) ,"Unable to create DataReader for DCPSSubscription. {:?}");
+
+      try_construct!(
+        poll.register(
+          &dcps_subscription_reader,
+          DISCOVERY_READER_DATA_TOKEN,
+          Ready::readable(),
+          PollOpt::edge(),
+        ),
+        "Unable to register subscription reader. {:?}"
+      );
+
+      let dcps_subscription_writer = try_construct!(
+        discovery_publisher.create_datawriter_with_entityid
+          ::<DiscoveredReaderData,PlCdrSerializerAdapter<DiscoveredReaderData>>(
+          EntityId::SEDP_BUILTIN_SUBSCRIPTIONS_WRITER,
+          &dcps_subscription_topic,
+          None,
+        ),
+        "Unable to create DataWriter for DCPSSubscription. {:?}"
+      );
+
+      // Publication : Who are the Writers here and elsewhere
+
+      let dcps_publication_topic = try_construct!(
+        domain_participant.create_topic(
+          "DCPSPublication".to_string(),
+          "DiscoveredWriterData".to_string(),
+          &discovery_publisher_qos,
+          TopicKind::WithKey,
+        ),
+        "Unable to create DCPSPublication topic. {:?}"
+      );
+
+      let dcps_publication_reader = try_construct!( discovery_subscriber
+        .create_datareader_with_entityid
+          ::<DiscoveredWriterData, PlCdrDeserializerAdapter<DiscoveredWriterData>>(
+          &dcps_publication_topic,
+          EntityId::SEDP_BUILTIN_PUBLICATIONS_READER,
+          None,
+        ) ,"Unable to create DataReader for DCPSPublication. {:?}");
+
+      try_construct!(
+        poll.register(
+          &dcps_publication_reader,
+          DISCOVERY_WRITER_DATA_TOKEN,
+          Ready::readable(),
+          PollOpt::edge(),
+        ),
+        "Unable to register writers info sender. {:?}"
+      );
+
+      let dcps_publication_writer = try_construct!(
+        discovery_publisher.create_datawriter_with_entityid
+          ::<DiscoveredWriterData,PlCdrSerializerAdapter<DiscoveredWriterData>>(
+          EntityId::SEDP_BUILTIN_PUBLICATIONS_WRITER,
+          &dcps_publication_topic,
+          None,
+        ),
+        "Unable to create DataWriter for DCPSPublication. {:?}"
+      );
+
+      // Topic topic (not a typo)
+
+      let dcps_topic_topic = try_construct!(
+        domain_participant.create_topic(
+          "DCPSTopic".to_string(),
+          "DiscoveredTopicData".to_string(),
+          &QosPolicyBuilder::new().build(), //TODO: check what this should be
+          TopicKind::WithKey,
+        ),
+        "Unable to create DCPSTopic topic. {:?}"
+      );
+
+      let dcps_topic_reader = try_construct!( discovery_subscriber
+        .create_datareader_with_entityid
+          ::<DiscoveredTopicData, PlCdrDeserializerAdapter<DiscoveredTopicData>>(
+          &dcps_topic_topic,
+          EntityId::SEDP_BUILTIN_TOPIC_READER,
+          None,
+        ) ,"Unable to create DataReader for DCPSTopic. {:?}");
+
+      try_construct!(
+        poll.register(
+          &dcps_topic_reader,
+          DISCOVERY_TOPIC_DATA_TOKEN,
+          Ready::readable(),
+          PollOpt::edge(),
+        ),
+        "Unable to register topic reader. {:?}"
+      );
+
+      let dcps_topic_writer = try_construct!(
+        discovery_publisher.create_datawriter_with_entityid
+          ::<DiscoveredTopicData,PlCdrSerializerAdapter<DiscoveredTopicData>>(
+          EntityId::SEDP_BUILTIN_TOPIC_WRITER,
+          &dcps_topic_topic,
+          None,
+        ),
+        "Unable to create DataWriter for DCPSTopic. {:?}"
+      );
+
+      let mut topic_info_send_timer = time_source.timer();
+      topic_info_send_timer.set_timeout(Self::SEND_TOPIC_INFO_PERIOD, ());
+      try_construct!(
+        poll.register(
+          &topic_info_send_timer,
+          DISCOVERY_SEND_TOPIC_INFO_TOKEN,
+          Ready::readable(),
+          PollOpt::edge(),
+        ),
+        "Unable to register topic info sender. {:?}"
+      );
 
-    let dcps_publication_topic = try_construct!(
+      SpdpSedp {
+        dcps_participant_topic,
+        dcps_participant_reader,
+        dcps_participant_writer,
+        participant_send_info_timer,
+        dcps_subscription_topic,
+        dcps_subscription_reader,
+        dcps_subscription_writer,
+        dcps_publication_topic,
+        dcps_publication_reader,
+        dcps_publication_writer,
+        dcps_topic_topic,
+        dcps_topic_reader,
+        dcps_topic_writer,
+        topic_info_send_timer,
+      }
+    };
+
+    // Participant Message Data 8.4.13
+
+    let participant_message_topic = try_construct!(
       domain_participant.create_topic(
-        "DCPSPublication".to_string(),
-        "DiscoveredWriterData".to_string(),
-        &discovery_publisher_qos,
+        "DCPSParticipantMessage".to_string(),
+        "ParticipantMessageData".to_string(),
+        &Self::PARTICIPANT_MESSAGE_QOS,
         TopicKind::WithKey,
       ),
-      "Unable to create DCPSPublication topic. {:?}"
+      "Unable to create DCPSParticipantMessage topic. {:?}"
     );
 
-    let dcps_publication_reader = try_construct!( discovery_subscriber
-      .create_datareader_with_entityid
-        ::<DiscoveredWriterData, PlCdrDeserializerAdapter<DiscoveredWriterData>>(
-        &dcps_publication_topic,
-        EntityId::SEDP_BUILTIN_PUBLICATIONS_READER,
+    let dcps_participant_message_reader = try_construct!(
+      discovery_subscriber.create_datareader_cdr_with_entityid::<ParticipantMessageData>(
+        &participant_message_topic,
+        EntityId::P2P_BUILTIN_PARTICIPANT_MESSAGE_READER,
         None,
-      ) ,"Unable to create DataReader for DCPSPublication. {:?}");
+      ),
+      "Unable to create DCPSParticipantMessage reader. {:?}"
+    );
 
     try_construct!(
       poll.register(
-        &dcps_publication_reader,
-        DISCOVERY_WRITER_DATA_TOKEN,
+        &dcps_participant_message_reader,
+        DISCOVERY_PARTICIPANT_MESSAGE_TOKEN,
         Ready::readable(),
         PollOpt::edge(),
       ),
-      "Unable to register writers info sender. {:?}"
+      "Unable to register DCPSParticipantMessage reader. {:?}"
     );
 
-    let dcps_publication_writer = try_construct!(
-      discovery_publisher.create_datawriter_with_entityid
-        ::<DiscoveredWriterData,PlCdrSerializerAdapter<DiscoveredWriterData>>(
-        EntityId::SEDP_BUILTIN_PUBLICATIONS_WRITER,
-        &dcps_publication_topic,
+    let dcps_participant_message_writer = try_construct!(
+      discovery_publisher.create_datawriter_cdr_with_entityid::<ParticipantMessageData>(
+        EntityId::P2P_BUILTIN_PARTICIPANT_MESSAGE_WRITER,
+        &participant_message_topic,
         None,
       ),
-      "Unable to create DataWriter for DCPSPublication. {:?}"
+      "Unable to create DCPSParticipantMessage writer. {:?}"
     );
 
-    let mut writers_send_info_timer: Timer<()> = Timer::default();
-
-    writers_send_info_timer.set_timeout(Self::SEND_WRITERS_INFO_PERIOD, ());
-
+    let mut dcps_participant_message_timer = time_source.timer();
+    dcps_participant_message_timer.set_timeout(Self::CHECK_PARTICIPANT_MESSAGES, ());
     try_construct!(
       poll.register(
-        &writers_send_info_timer,
-        DISCOVERY_SEND_WRITERS_INFO_TOKEN,
+        &dcps_participant_message_timer,
+        DISCOVERY_PARTICIPANT_MESSAGE_TIMER_TOKEN,
         Ready::readable(),
         PollOpt::edge(),
       ),
-      "Unable to register readers info sender. {:?}"
+      "Unable to register DCPSParticipantMessage timer. {:?}"
     );
 
-    // Topic topic (not a typo)
+    // XTypes TypeLookup service, 7.6.3.3.4
 
-    let dcps_topic_topic = try_construct!(
+    let type_lookup_request_topic = try_construct!(
       domain_participant.create_topic(
-        "DCPSTopic".to_string(),
-        "DiscoveredTopicData".to_string(),
-        &QosPolicyBuilder::new().build(), //TODO: check what this should be
-        TopicKind::WithKey,
+        "DCPSTypeLookupRequest".to_string(),
+        "TypeLookup_Request".to_string(),
+        &Self::TYPE_LOOKUP_QOS,
+        TopicKind::NoKey,
       ),
-      "Unable to create DCPSTopic topic. {:?}"
+      "Unable to create TypeLookup request topic. {:?}"
     );
 
-    let dcps_topic_reader = try_construct!( discovery_subscriber
-      .create_datareader_with_entityid
-        ::<DiscoveredTopicData, PlCdrDeserializerAdapter<DiscoveredTopicData>>(
-        &dcps_topic_topic,
-        EntityId::SEDP_BUILTIN_TOPIC_READER,
-        None,
-      ) ,"Unable to create DataReader for DCPSTopic. {:?}");
-
-    try_construct!(
-      poll.register(
-        &dcps_topic_reader,
-        DISCOVERY_TOPIC_DATA_TOKEN,
-        Ready::readable(),
-        PollOpt::edge(),
+    let type_lookup_request_reader = try_construct!(
+      discovery_subscriber.create_datareader_no_key_with_entityid::<
+        TypeLookupRequest,
+        TypeLookupAdapter<TypeLookupRequest>,
+      >(
+        &type_lookup_request_topic,
+        EntityId::TL_SVC_REQ_READER,
+        Some(Self::TYPE_LOOKUP_QOS),
       ),
-      "Unable to register topic reader. {:?}"
+      "Unable to create TypeLookup request reader. {:?}"
     );
 
-    let dcps_topic_writer = try_construct!(
-      discovery_publisher.create_datawriter_with_entityid
-        ::<DiscoveredTopicData,PlCdrSerializerAdapter<DiscoveredTopicData>>(
-        EntityId::SEDP_BUILTIN_TOPIC_WRITER,
-        &dcps_topic_topic,
-        None,
-      ),
-      "Unable to create DataWriter for DCPSTopic. {:?}"
-    );
-
-    let mut topic_info_send_timer: Timer<()> = Timer::default();
-    topic_info_send_timer.set_timeout(Self::SEND_TOPIC_INFO_PERIOD, ());
     try_construct!(
       poll.register(
-        &topic_info_send_timer,
-        DISCOVERY_SEND_TOPIC_INFO_TOKEN,
+        &type_lookup_request_reader,
+        DISCOVERY_TYPE_LOOKUP_REQUEST_TOKEN,
         Ready::readable(),
         PollOpt::edge(),
       ),
-      "Unable to register topic info sender. {:?}"
+      "Unable to register TypeLookup request reader. {:?}"
     );
 
-    // create lease duration check timer
-    let mut topic_cleanup_timer: Timer<()> = Timer::default();
-    topic_cleanup_timer.set_timeout(Self::TOPIC_CLEANUP_PERIOD, ());
-    try_construct!(
-      poll.register(
-        &topic_cleanup_timer,
-        DISCOVERY_TOPIC_CLEANUP_TOKEN,
-        Ready::readable(),
-        PollOpt::edge(),
+    let type_lookup_request_writer = try_construct!(
+      discovery_publisher.create_datawriter_no_key_with_entityid::<
+        TypeLookupRequest,
+        TypeLookupAdapter<TypeLookupRequest>,
+      >(
+        EntityId::TL_SVC_REQ_WRITER,
+        &type_lookup_request_topic,
+        Some(Self::TYPE_LOOKUP_QOS),
       ),
-      "Unable to register topic cleanup timer. {:?}"
+      "Unable to create TypeLookup request writer. {:?}"
     );
 
-    // Participant Message Data 8.4.13
-
-    let participant_message_topic = try_construct!(
+    let type_lookup_reply_topic = try_construct!(
       domain_participant.create_topic(
-        "DCPSParticipantMessage".to_string(),
-        "ParticipantMessageData".to_string(),
-        &Self::PARTICIPANT_MESSAGE_QOS,
-        TopicKind::WithKey,
+        "DCPSTypeLookupReply".to_string(),
+        "TypeLookup_Reply".to_string(),
+        &Self::TYPE_LOOKUP_QOS,
+        TopicKind::NoKey,
       ),
-      "Unable to create DCPSParticipantMessage topic. {:?}"
+      "Unable to create TypeLookup reply topic. {:?}"
     );
 
-    let dcps_participant_message_reader = try_construct!(
-      discovery_subscriber.create_datareader_cdr_with_entityid::<ParticipantMessageData>(
-        &participant_message_topic,
-        EntityId::P2P_BUILTIN_PARTICIPANT_MESSAGE_READER,
-        None,
+    let type_lookup_reply_reader = try_construct!(
+      discovery_subscriber.create_datareader_no_key_with_entityid::<
+        TypeLookupReply,
+        TypeLookupAdapter<TypeLookupReply>,
+      >(
+        &type_lookup_reply_topic,
+        EntityId::TL_SVC_REPLY_READER,
+        Some(Self::TYPE_LOOKUP_QOS),
       ),
-      "Unable to create DCPSParticipantMessage reader. {:?}"
+      "Unable to create TypeLookup reply reader. {:?}"
     );
 
     try_construct!(
       poll.register(
-        &dcps_participant_message_reader,
-        DISCOVERY_PARTICIPANT_MESSAGE_TOKEN,
+        &type_lookup_reply_reader,
+        DISCOVERY_TYPE_LOOKUP_REPLY_TOKEN,
         Ready::readable(),
         PollOpt::edge(),
       ),
-      "Unable to register DCPSParticipantMessage reader. {:?}"
+      "Unable to register TypeLookup reply reader. {:?}"
     );
 
-    let dcps_participant_message_writer = try_construct!(
-      discovery_publisher.create_datawriter_cdr_with_entityid::<ParticipantMessageData>(
-        EntityId::P2P_BUILTIN_PARTICIPANT_MESSAGE_WRITER,
-        &participant_message_topic,
-        None,
+    let type_lookup_reply_writer = try_construct!(
+      discovery_publisher.create_datawriter_no_key_with_entityid::<
+        TypeLookupReply,
+        TypeLookupAdapter<TypeLookupReply>,
+      >(
+        EntityId::TL_SVC_REPLY_WRITER,
+        &type_lookup_reply_topic,
+        Some(Self::TYPE_LOOKUP_QOS),
       ),
-      "Unable to create DCPSParticipantMessage writer. {:?}"
+      "Unable to create TypeLookup reply writer. {:?}"
     );
 
-    let mut dcps_participant_message_timer = Timer::default();
-    dcps_participant_message_timer.set_timeout(Self::CHECK_PARTICIPANT_MESSAGES, ());
+    // Vendor-specific participant health topic. This is an ordinary
+    // (non-builtin) topic, so that it is announced via SEDP as usual.
+    let health_writer = if publish_health {
+      let health_topic = try_construct!(
+        domain_participant.create_topic(
+          ParticipantHealth::HEALTH_TOPIC_NAME.to_string(),
+          ParticipantHealth::HEALTH_TYPE_NAME.to_string(),
+          &Self::PARTICIPANT_MESSAGE_QOS,
+          TopicKind::WithKey,
+        ),
+        "Unable to create participant health topic. {:?}"
+      );
+      Some(try_construct!(
+        discovery_publisher.create_datawriter_cdr::<ParticipantHealth>(&health_topic, None),
+        "Unable to create participant health writer. {:?}"
+      ))
+    } else {
+      None
+    };
+
+    let mut health_timer = time_source.timer();
+    health_timer.set_timeout(Self::CHECK_HEALTH_PERIOD, ());
     try_construct!(
       poll.register(
-        &dcps_participant_message_timer,
-        DISCOVERY_PARTICIPANT_MESSAGE_TIMER_TOKEN,
+        &health_timer,
+        DISCOVERY_HEALTH_TIMER_TOKEN,
         Ready::readable(),
         PollOpt::edge(),
       ),
-      "Unable to register DCPSParticipantMessage timer. {:?}"
+      "Unable to register health timer. {:?}"
     );
 
+    // Vendor-specific participant statistics topic, like the health topic
+    #[cfg(feature = "statistics")]
+    let (statistics_writer, statistics_timer) = {
+      let statistics_writer = match publish_statistics {
+        Some(period) => {
+          let statistics_topic = try_construct!(
+            domain_participant.create_topic(
+              ParticipantStatistics::STATISTICS_TOPIC_NAME.to_string(),
+              ParticipantStatistics::STATISTICS_TYPE_NAME.to_string(),
+              &Self::PARTICIPANT_MESSAGE_QOS,
+              TopicKind::WithKey,
+            ),
+            "Unable to create participant statistics topic. {:?}"
+          );
+          let writer = try_construct!(
+            discovery_publisher
+              .create_datawriter_cdr::<ParticipantStatistics>(&statistics_topic, None),
+            "Unable to create participant statistics writer. {:?}"
+          );
+          Some((writer, period))
+        }
+        None => None,
+      };
+
+      let mut statistics_timer = time_source.timer();
+      if let Some(period) = publish_statistics {
+        statistics_timer.set_timeout(period, ());
+      }
+      try_construct!(
+        poll.register(
+          &statistics_timer,
+          DISCOVERY_STATISTICS_TIMER_TOKEN,
+          Ready::readable(),
+          PollOpt::edge(),
+        ),
+        "Unable to register statistics timer. {:?}"
+      );
+      (statistics_writer, statistics_timer)
+    };
+
+    // DDS Security authentication handshake, on the builtin
+    // ParticipantStatelessMessage topic
+    #[cfg(feature = "security")]
+    let authentication = match security.authentication {
+      Some(authentication) => {
+        let topic = try_construct!(
+          domain_participant.create_topic(
+            "DCPSParticipantStatelessMessage".to_string(),
+            "ParticipantStatelessMessage".to_string(),
+            &Self::STATELESS_MESSAGE_QOS,
+            TopicKind::NoKey,
+          ),
+          "Unable to create ParticipantStatelessMessage topic. {:?}"
+        );
+        let reader = try_construct!(
+          discovery_subscriber.create_datareader_no_key_with_entityid::<
+            ParticipantGenericMessage,
+            CDRDeserializerAdapter<ParticipantGenericMessage>,
+          >(
+            &topic,
+            EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_READER,
+            Some(Self::STATELESS_MESSAGE_QOS),
+          ),
+          "Unable to create ParticipantStatelessMessage reader. {:?}"
+        );
+        try_construct!(
+          poll.register(
+            &reader,
+            DISCOVERY_STATELESS_MESSAGE_TOKEN,
+            Ready::readable(),
+            PollOpt::edge(),
+          ),
+          "Unable to register ParticipantStatelessMessage reader. {:?}"
+        );
+        let writer = try_construct!(
+          discovery_publisher.create_datawriter_no_key_with_entityid::<
+            ParticipantGenericMessage,
+            CDRSerializerAdapter<ParticipantGenericMessage>,
+          >(
+            EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_WRITER,
+            &topic,
+            Some(Self::STATELESS_MESSAGE_QOS),
+          ),
+          "Unable to create ParticipantStatelessMessage writer. {:?}"
+        );
+        let mut resend_timer = time_source.timer();
+        resend_timer.set_timeout(Self::HANDSHAKE_RESEND_PERIOD, ());
+        try_construct!(
+          poll.register(
+            &resend_timer,
+            DISCOVERY_HANDSHAKE_TIMER_TOKEN,
+            Ready::readable(),
+            PollOpt::edge(),
+          ),
+          "Unable to register handshake timer. {:?}"
+        );
+        if let Some(access_control) = &security.access_control {
+          authentication.set_permissions_document(access_control.permissions_document());
+        }
+
+        // Crypto tokens on the builtin ParticipantVolatileMessageSecure topic
+        let key_exchange = match security.cryptography {
+          Some(cryptography) => {
+            let topic = try_construct!(
+              domain_participant.create_topic(
+                Self::VOLATILE_MESSAGE_TOPIC.to_string(),
+                "ParticipantVolatileMessageSecure".to_string(),
+                &Self::VOLATILE_MESSAGE_QOS,
+                TopicKind::NoKey,
+              ),
+              "Unable to create ParticipantVolatileMessageSecure topic. {:?}"
+            );
+            let reader = try_construct!(
+              discovery_subscriber.create_datareader_no_key_with_entityid::<
+                ParticipantGenericMessage,
+                CDRDeserializerAdapter<ParticipantGenericMessage>,
+              >(
+                &topic,
+                EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_READER,
+                Some(Self::VOLATILE_MESSAGE_QOS),
+              ),
+              "Unable to create ParticipantVolatileMessageSecure reader. {:?}"
+            );
+            try_construct!(
+              poll.register(
+                &reader,
+                DISCOVERY_VOLATILE_MESSAGE_TOKEN,
+                Ready::readable(),
+                PollOpt::edge(),
+              ),
+              "Unable to register ParticipantVolatileMessageSecure reader. {:?}"
+            );
+            let writer = try_construct!(
+              discovery_publisher.create_datawriter_no_key_with_entityid::<
+                ParticipantGenericMessage,
+                CDRSerializerAdapter<ParticipantGenericMessage>,
+              >(
+                EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER,
+                &topic,
+                Some(Self::VOLATILE_MESSAGE_QOS),
+              ),
+              "Unable to create ParticipantVolatileMessageSecure writer. {:?}"
+            );
+            Some(KeyExchange {
+              cryptography,
+              topic,
+              reader,
+              writer,
+              sequence_number: Cell::new(0),
+            })
+          }
+          None => None,
+        };
+
+        Some(AuthenticationService {
+          authentication,
+          access_control: security.access_control,
+          topic,
+          reader,
+          writer,
+          resend_timer,
+          key_exchange,
+        })
+      }
+      None => None,
+    };
+
     Ok(Self {
       poll,
       domain_participant,
@@ -542,73 +1189,109 @@ impl Discovery {
       discovery_command_receiver,
       spdp_liveness_receiver,
       self_locators,
-
-      liveliness_state: LivelinessState::new(),
+      #[cfg(feature = "discovery")]
+      initial_peers,
+      sedp_enabled,
+      static_endpoints,
+      #[cfg(feature = "discovery")]
+      domain_tag,
+      #[cfg(feature = "discovery")]
+      participant_authenticator,
+
+      liveliness_state: LivelinessState::new(time_source.now()),
+      time_source,
+      clock_monitor: ClockMonitor::new(
+        Self::PARTICIPANT_CLEANUP_PERIOD,
+        Self::CLOCK_JUMP_TOLERANCE,
+      ),
+      participant_status_broadcaster,
+      discovery_event_broadcaster,
 
       // discovery_subscriber,
       // discovery_publisher,
-      dcps_participant_topic,
-      dcps_participant_reader,
-      dcps_participant_writer,
-      participant_cleanup_timer,
-      participant_send_info_timer,
+      #[cfg(feature = "discovery")]
+      spdp_sedp,
 
-      dcps_subscription_topic,
-      dcps_subscription_reader,
-      dcps_subscription_writer,
+      participant_cleanup_timer,
       readers_send_info_timer,
-
-      dcps_publication_topic,
-      dcps_publication_reader,
-      dcps_publication_writer,
       writers_send_info_timer,
-
-      dcps_topic_topic,
-      dcps_topic_reader,
-      dcps_topic_writer,
-      topic_info_send_timer,
       topic_cleanup_timer,
 
       participant_message_topic,
       dcps_participant_message_reader,
       dcps_participant_message_writer,
       dcps_participant_message_timer,
+
+      type_lookup_request_topic,
+      type_lookup_request_reader,
+      type_lookup_request_writer,
+      type_lookup_reply_topic,
+      type_lookup_reply_reader,
+      type_lookup_reply_writer,
+      type_lookup_request_count: 0,
+
+      health_monitor,
+      health_writer,
+      health_timer,
+
+      #[cfg(feature = "statistics")]
+      statistics_monitor,
+      #[cfg(feature = "statistics")]
+      statistics_writer,
+      #[cfg(feature = "statistics")]
+      statistics_timer,
+
+      #[cfg(feature = "security")]
+      authentication,
     })
   }
 
-  pub fn discovery_event_loop(&mut self) {
+  // Announces the participant and its endpoints, and tells the
+  // DomainParticipant that Discovery has started. Returns false if the
+  // participant is gone already.
+  pub(crate) fn start(&mut self) -> bool {
     self.initialize_participant();
 
     // send out info about non-built-in Writers and Readers that we have.
     self.write_writers_info();
     self.write_readers_info();
+    self.update_static_writers();
+    self.update_static_readers();
 
-    match self.discovery_started_sender.send(Ok(())) {
-      Ok(_) => (),
-      _ => return, // Participant has probably crashed at this point
-    };
+    // Fails if the participant has probably crashed at this point
+    self.discovery_started_sender.send(Ok(())).is_ok()
+  }
 
-    loop {
-      let mut events = Events::with_capacity(32); // Should this be outside of the loop?
-      match self.poll.poll(&mut events, None) {
-        Ok(_) => (),
-        Err(e) => {
-          error!("Failed in waiting of poll in discovery. {:?}", e);
-          return;
-        }
+  // Waits for events for at most `timeout`, and handles them. Returns false
+  // when Discovery has stopped.
+  pub(crate) fn run_round(
+    &mut self,
+    events: &mut Events,
+    timeout: Option<std::time::Duration>,
+  ) -> bool {
+    match self.poll.poll(events, timeout) {
+      Ok(_) => (),
+      Err(e) => {
+        error!("Failed in waiting of poll in discovery. {:?}", e);
+        return false;
       }
-
-      for event in events.into_iter() {
-        match event.token() {
-          DISCOVERY_COMMAND_TOKEN => {
-            while let Ok(command) = self.discovery_command_receiver.try_recv() {
-              match command {
-                DiscoveryCommand::StopDiscovery => {
-                  info!("Stopping Discovery");
+    }
+    let round_start = Instant::now();
+
+    for event in events.iter() {
+      match event.token() {
+        DISCOVERY_COMMAND_TOKEN => {
+          while let Ok(command) = self.discovery_command_receiver.try_recv() {
+            match command {
+              DiscoveryCommand::StopDiscovery => {
+                info!("Stopping Discovery");
+                #[cfg(feature = "discovery")]
+                {
                   // disposing readers
                   let db = self.discovery_db_read();
                   for reader in db.get_all_local_topic_readers() {
                     self
+                      .spdp_sedp
                       .dcps_subscription_writer
                       .dispose(&Endpoint_GUID(reader.reader_proxy.remote_reader_guid), None)
                       .unwrap_or(());
@@ -616,167 +1299,301 @@ impl Discovery {
 
                   for writer in db.get_all_local_topic_writers() {
                     self
+                      .spdp_sedp
                       .dcps_publication_writer
                       .dispose(&Endpoint_GUID(writer.writer_proxy.remote_writer_guid), None)
                       .unwrap_or(());
                   }
                   // finally disposing the participant we have
                   self
+                    .spdp_sedp
                     .dcps_participant_writer
                     .dispose(&Participant_GUID(self.domain_participant.guid()), None)
                     .unwrap_or(());
-                  info!("Stopped Discovery");
-                  return; // terminate event loop
                 }
-                DiscoveryCommand::RemoveLocalWriter { guid } => {
-                  if guid == self.dcps_publication_writer.guid() {
-                    continue;
-                  }
-                  self
-                    .dcps_publication_writer
-                    .dispose(&Endpoint_GUID(guid), None)
-                    .unwrap_or(());
-
-                  match self.discovery_db.write() {
-                    Ok(mut db) => db.remove_local_topic_writer(guid),
-                    Err(e) => {
-                      error!("DiscoveryDB is poisoned. {:?}", e);
-                      return;
-                    }
-                  }
+                info!("Stopped Discovery");
+                return false; // terminate event loop
+              }
+              DiscoveryCommand::RemoveLocalWriter { guid } => {
+                // Builtin endpoints are not announced, so no dispose either.
+                if guid.entity_id.entity_kind.is_built_in() {
+                  continue;
                 }
-                DiscoveryCommand::RemoveLocalReader { guid } => {
-                  if guid == self.dcps_subscription_writer.guid() {
-                    continue;
+                #[cfg(feature = "discovery")]
+                self
+                  .spdp_sedp
+                  .dcps_publication_writer
+                  .dispose(&Endpoint_GUID(guid), None)
+                  .unwrap_or(());
+
+                match self.discovery_db.write() {
+                  Ok(mut db) => db.remove_local_topic_writer(guid),
+                  Err(e) => {
+                    error!("DiscoveryDB is poisoned. {:?}", e);
+                    return false;
                   }
+                }
+              }
+              DiscoveryCommand::RemoveLocalReader { guid } => {
+                // Builtin endpoints are not announced, so no dispose either.
+                if guid.entity_id.entity_kind.is_built_in() {
+                  continue;
+                }
 
-                  self
-                    .dcps_subscription_writer
-                    .dispose(&Endpoint_GUID(guid), None)
-                    .unwrap_or(());
-
-                  match self.discovery_db.write() {
-                    Ok(mut db) => db.remove_local_topic_reader(guid),
-                    Err(e) => {
-                      error!("DiscoveryDB is poisoned. {:?}", e);
-                      return;
-                    }
+                #[cfg(feature = "discovery")]
+                self
+                  .spdp_sedp
+                  .dcps_subscription_writer
+                  .dispose(&Endpoint_GUID(guid), None)
+                  .unwrap_or(());
+
+                match self.discovery_db.write() {
+                  Ok(mut db) => db.remove_local_topic_reader(guid),
+                  Err(e) => {
+                    error!("DiscoveryDB is poisoned. {:?}", e);
+                    return false;
                   }
                 }
-                DiscoveryCommand::ManualAssertLiveliness => {
-                  self.liveliness_state.last_manual_participant_update = Timestamp::now();
+              }
+              DiscoveryCommand::UpdateLocalWriter { guid, qos } => {
+                self.update_local_writer(guid, qos);
+              }
+              DiscoveryCommand::UpdateLocalReader { guid, qos } => {
+                self.update_local_reader(guid, qos);
+              }
+              DiscoveryCommand::ManualAssertLiveliness => {
+                self.liveliness_state.last_manual_participant_update = self.time_source.now();
+              }
+              DiscoveryCommand::AssertTopicLiveliness {
+                writer_guid,
+                manual_assertion,
+              } => {
+                self.send_discovery_notification(
+                  DiscoveryNotificationType::AssertTopicLiveliness {
+                    writer_guid,
+                    manual_assertion,
+                  },
+                );
+              }
+              DiscoveryCommand::IgnoreParticipant { guid_prefix } => {
+                info!("Ignoring participant {:?}", guid_prefix);
+                self.discovery_db_write().ignore_participant(guid_prefix);
+                self.send_discovery_notification(DiscoveryNotificationType::ParticipantLost {
+                  guid_prefix,
+                });
+              }
+              DiscoveryCommand::IgnorePublication { guid } => {
+                info!("Ignoring writer {:?}", guid);
+                self.discovery_db_write().ignore_endpoint(guid);
+                self.send_discovery_notification(DiscoveryNotificationType::WriterLost {
+                  writer_guid: guid,
+                });
+              }
+              DiscoveryCommand::IgnoreSubscription { guid } => {
+                info!("Ignoring reader {:?}", guid);
+                self.discovery_db_write().ignore_endpoint(guid);
+                self.send_discovery_notification(DiscoveryNotificationType::ReaderLost {
+                  reader_guid: guid,
+                });
+              }
+              DiscoveryCommand::AddStaticEndpoint { endpoint } => {
+                info!("Adding static endpoint {:?}", endpoint.guid);
+                if let Some(dwd) = endpoint.discovered_writer_data() {
+                  self.writer_discovered(&dwd);
                 }
-                DiscoveryCommand::AssertTopicLiveliness {
-                  writer_guid,
-                  manual_assertion,
-                } => {
-                  self.send_discovery_notification(
-                    DiscoveryNotificationType::AssertTopicLiveliness {
-                      writer_guid,
-                      manual_assertion,
-                    },
-                  );
+                if let Some(drd) = endpoint.discovered_reader_data() {
+                  self.reader_discovered(&drd);
                 }
-              };
-            }
+                self.static_endpoints.retain(|e| e.guid != endpoint.guid);
+                self.static_endpoints.push(endpoint);
+              }
+              DiscoveryCommand::RequestTypeObjects { type_ids } => {
+                self.request_type_objects(type_ids);
+              }
+            };
           }
+        }
 
-          DISCOVERY_PARTICIPANT_DATA_TOKEN => {
-            debug!("triggered participant reader");
-            self.handle_participant_reader();
-          }
+        #[cfg(feature = "discovery")]
+        DISCOVERY_PARTICIPANT_DATA_TOKEN => {
+          debug!("triggered participant reader");
+          self.handle_participant_reader();
+        }
 
-          DISCOVERY_PARTICIPANT_CLEANUP_TOKEN => {
-            self.participant_cleanup();
-            // setting next cleanup timeout
-            self
-              .participant_cleanup_timer
-              .set_timeout(Self::PARTICIPANT_CLEANUP_PERIOD, ());
-          }
+        DISCOVERY_PARTICIPANT_CLEANUP_TOKEN => {
+          // Check clocks first, so that a jump does not expire all leases.
+          self.handle_clock_jump();
+          self.participant_cleanup();
+          // setting next cleanup timeout
+          let delay = self.next_participant_cleanup_delay();
+          self.participant_cleanup_timer.set_timeout(delay, ());
+        }
 
-          DISCOVERY_SEND_PARTICIPANT_INFO_TOKEN => {
-            let strong_dp = if let Some(dp) = self.domain_participant.clone().upgrade() {
-              dp
-            } else {
-              error!("DomainParticipant doesn't exist anymore, exiting Discovery.");
-              return;
-            };
+        #[cfg(feature = "discovery")]
+        DISCOVERY_SEND_PARTICIPANT_INFO_TOKEN => {
+          let strong_dp = if let Some(dp) = self.domain_participant.clone().upgrade() {
+            dp
+          } else {
+            error!("DomainParticipant doesn't exist anymore, exiting Discovery.");
+            return false;
+          };
+          self.update_self_locators(&strong_dp);
 
-            // setting 5 times the duration so lease doesn't break if update fails once or
-            // twice
-            let data = SpdpDiscoveredParticipantData::from_local_participant(
-              &strong_dp,
-              &self.self_locators,
-              5.0 * Duration::from(Self::SEND_PARTICIPANT_INFO_PERIOD),
-            );
+          // setting 5 times the duration so lease doesn't break if update fails once or
+          // twice
+          let data = self.local_participant_data(
+            &strong_dp,
+            5.0 * Duration::from(Self::SEND_PARTICIPANT_INFO_PERIOD),
+          );
+          #[cfg(feature = "security")]
+          self.set_handshake_participant_data(&data);
 
-            self.dcps_participant_writer.write(data, None).unwrap_or(());
-            // reschedule timer
-            self
-              .participant_send_info_timer
-              .set_timeout(Self::SEND_PARTICIPANT_INFO_PERIOD, ());
-          }
-          DISCOVERY_READER_DATA_TOKEN => {
-            self.handle_subscription_reader(None);
-          }
-          DISCOVERY_SEND_READERS_INFO_TOKEN => {
-            self.write_readers_info();
-            self
-              .readers_send_info_timer
-              .set_timeout(Self::SEND_READERS_INFO_PERIOD, ());
-          }
-          DISCOVERY_WRITER_DATA_TOKEN => {
-            self.handle_publication_reader(None);
-          }
-          DISCOVERY_SEND_WRITERS_INFO_TOKEN => {
-            self.write_writers_info();
-            self
-              .writers_send_info_timer
-              .set_timeout(Self::SEND_WRITERS_INFO_PERIOD, ());
+          self
+            .spdp_sedp
+            .dcps_participant_writer
+            .write(data, None)
+            .unwrap_or(());
+          // reschedule timer
+          self
+            .spdp_sedp
+            .participant_send_info_timer
+            .set_timeout(Self::SEND_PARTICIPANT_INFO_PERIOD, ());
+        }
+        #[cfg(feature = "discovery")]
+        DISCOVERY_READER_DATA_TOKEN => {
+          self.handle_subscription_reader(None);
+        }
+        DISCOVERY_SEND_READERS_INFO_TOKEN => {
+          // Without SPDP, network interface changes are noticed here.
+          #[cfg(not(feature = "discovery"))]
+          if let Some(dp) = self.domain_participant.clone().upgrade() {
+            self.update_self_locators(&dp);
           }
-          DISCOVERY_TOPIC_DATA_TOKEN => {
-            self.handle_topic_reader(None);
+          self.write_readers_info();
+          self.update_static_readers();
+          #[cfg(feature = "security")]
+          {
+            self.rotate_crypto_keys();
+            self.send_endpoint_crypto_tokens();
           }
-          DISCOVERY_TOPIC_CLEANUP_TOKEN => {
-            self.topic_cleanup();
+          self
+            .readers_send_info_timer
+            .set_timeout(Self::SEND_READERS_INFO_PERIOD, ());
+        }
+        #[cfg(feature = "discovery")]
+        DISCOVERY_WRITER_DATA_TOKEN => {
+          self.handle_publication_reader(None);
+        }
+        DISCOVERY_SEND_WRITERS_INFO_TOKEN => {
+          self.write_writers_info();
+          self.update_static_writers();
+          self
+            .writers_send_info_timer
+            .set_timeout(Self::SEND_WRITERS_INFO_PERIOD, ());
+        }
+        #[cfg(feature = "discovery")]
+        DISCOVERY_TOPIC_DATA_TOKEN => {
+          self.handle_topic_reader(None);
+        }
+        DISCOVERY_TOPIC_CLEANUP_TOKEN => {
+          self.topic_cleanup();
 
-            self
-              .topic_cleanup_timer
-              .set_timeout(Self::TOPIC_CLEANUP_PERIOD, ());
-          }
-          DISCOVERY_SEND_TOPIC_INFO_TOKEN => {
-            self.write_topic_info();
-            self
-              .topic_info_send_timer
-              .set_timeout(Self::SEND_TOPIC_INFO_PERIOD, ());
-          }
-          DISCOVERY_PARTICIPANT_MESSAGE_TOKEN => {
-            self.handle_participant_message_reader();
-          }
-          DISCOVERY_PARTICIPANT_MESSAGE_TIMER_TOKEN => {
-            self.write_participant_message();
-            self
-              .dcps_participant_message_timer
-              .set_timeout(Self::CHECK_PARTICIPANT_MESSAGES, ());
-          }
-          SPDP_LIVENESS_TOKEN => {
-            while let Ok(guid_prefix) = self.spdp_liveness_receiver.try_recv() {
-              match self.discovery_db.write() {
-                Ok(mut db) => db.participant_is_alive(guid_prefix),
-                Err(e) => {
-                  error!("DiscoveryDB is poisoned. {:?}", e);
-                  return;
-                }
+          self
+            .topic_cleanup_timer
+            .set_timeout(Self::TOPIC_CLEANUP_PERIOD, ());
+        }
+        #[cfg(feature = "discovery")]
+        DISCOVERY_SEND_TOPIC_INFO_TOKEN => {
+          self.write_topic_info();
+          self
+            .spdp_sedp
+            .topic_info_send_timer
+            .set_timeout(Self::SEND_TOPIC_INFO_PERIOD, ());
+        }
+        DISCOVERY_PARTICIPANT_MESSAGE_TOKEN => {
+          self.handle_participant_message_reader();
+        }
+        DISCOVERY_PARTICIPANT_MESSAGE_TIMER_TOKEN => {
+          self.write_participant_message();
+          self
+            .dcps_participant_message_timer
+            .set_timeout(Self::CHECK_PARTICIPANT_MESSAGES, ());
+        }
+        DISCOVERY_TYPE_LOOKUP_REQUEST_TOKEN => {
+          self.handle_type_lookup_request_reader();
+        }
+        DISCOVERY_TYPE_LOOKUP_REPLY_TOKEN => {
+          self.handle_type_lookup_reply_reader();
+        }
+        DISCOVERY_HEALTH_TIMER_TOKEN => {
+          self.check_health();
+          self.health_timer.set_timeout(Self::CHECK_HEALTH_PERIOD, ());
+        }
+        #[cfg(feature = "statistics")]
+        DISCOVERY_STATISTICS_TIMER_TOKEN => {
+          self.publish_statistics();
+        }
+        #[cfg(feature = "security")]
+        DISCOVERY_STATELESS_MESSAGE_TOKEN => {
+          self.handle_stateless_message_reader();
+        }
+        #[cfg(feature = "security")]
+        DISCOVERY_HANDSHAKE_TIMER_TOKEN => {
+          self.resend_handshake_messages();
+        }
+        #[cfg(feature = "security")]
+        DISCOVERY_VOLATILE_MESSAGE_TOKEN => {
+          self.handle_volatile_message_reader();
+        }
+        SPDP_LIVENESS_TOKEN => {
+          while let Ok(guid_prefix) = self.spdp_liveness_receiver.try_recv() {
+            match self.discovery_db.write() {
+              Ok(mut db) => db.participant_is_alive(guid_prefix),
+              Err(e) => {
+                error!("DiscoveryDB is poisoned. {:?}", e);
+                return false;
               }
             }
           }
-          other_token => {
-            error!("discovery event loop got token: {:?}", other_token);
-          }
-        } // match
-      } // for
-    } // loop
-  } // fn
+        }
+        other_token => {
+          error!("discovery event loop got token: {:?}", other_token);
+        }
+      } // match
+    } // for
+    self
+      .health_monitor
+      .round_done(ProtocolThread::Discovery, round_start.elapsed());
+    true
+  }
+
+  // Watchdog: warn about unhealthy protocol threads and publish the
+  // health sample, if enabled. A wedged Discovery thread cannot do this, but
+  // then the missing samples tell the story.
+  fn check_health(&self) {
+    let health = self.health_monitor.health(self.domain_participant.guid());
+    if !health.healthy {
+      warn!("Participant is not healthy: {:?}", health);
+    }
+    if let Some(writer) = &self.health_writer {
+      writer
+        .write(health, None)
+        .unwrap_or_else(|e| error!("Cannot publish participant health: {:?}", e));
+    }
+  }
+
+  #[cfg(feature = "statistics")]
+  fn publish_statistics(&mut self) {
+    if let Some((writer, period)) = &self.statistics_writer {
+      let statistics = self
+        .statistics_monitor
+        .statistics(self.domain_participant.guid());
+      writer
+        .write(statistics, None)
+        .unwrap_or_else(|e| error!("Cannot publish participant statistics: {:?}", e));
+      self.statistics_timer.set_timeout(*period, ());
+    }
+  }
 
   // Initialize our own particiapnt data into the Discovery DB.
   // That causes ReaderProxies and WriterProxies to be constructed and
@@ -784,7 +1601,7 @@ impl Discovery {
   // built-in and user-defined.
   // If we did not do this, the Readers and Writers in this participant could not
   // find each other.
-  fn initialize_participant(&self) {
+  fn initialize_participant(&mut self) {
     let dp = if let Some(dp) = self.domain_participant.clone().upgrade() {
       dp
     } else {
@@ -792,11 +1609,9 @@ impl Discovery {
       return;
     };
 
-    let participant_data = SpdpDiscoveredParticipantData::from_local_participant(
-      &dp,
-      &self.self_locators,
-      Duration::DURATION_INFINITE,
-    );
+    let participant_data = self.local_participant_data(&dp, Duration::DURATION_INFINITE);
+    #[cfg(feature = "security")]
+    self.set_handshake_participant_data(&participant_data);
 
     // Initialize our own particiapnt data into the Discovery DB, so we can talk to
     // ourself.
@@ -810,6 +1625,14 @@ impl Discovery {
       guid_prefix: dp.guid().prefix,
     });
 
+    #[cfg(feature = "discovery")]
+    self.create_spdp_proxies(&dp);
+  }
+
+  // Proxies for the builtin participant reader and writer, so that SPDP
+  // announcements are sent to the multicast group and the initial peers.
+  #[cfg(feature = "discovery")]
+  fn create_spdp_proxies(&self, dp: &DomainParticipant) {
     // insert a (fake) reader proxy as multicast address, so discovery notifications
     // are sent somewhere
     let reader_guid = GUID::new(
@@ -883,11 +1706,40 @@ impl Discovery {
     self.send_discovery_notification(DiscoveryNotificationType::WriterUpdated {
       discovered_writer_data: dwd,
     });
+
+    // Similarly, a (fake) reader proxy for the configured initial peers. It has
+    // only unicast locators, so that the participant writer sends to each of
+    // them on every periodic announcement. Its GUID does not belong to any real
+    // participant, so it is never removed as lost.
+    if !self.initial_peers.is_empty() {
+      let peers_reader_guid = GUID::new(
+        GuidPrefix::new(b"InitialPeers"),
+        EntityId::SPDP_BUILTIN_PARTICIPANT_READER,
+      );
+      info!(
+        "Creating DCPSParticipant reader proxy for initial peers {:?}",
+        self.initial_peers
+      );
+      self.send_discovery_notification(DiscoveryNotificationType::ReaderUpdated {
+        discovered_reader_data: DiscoveredReaderData {
+          reader_proxy: ReaderProxy::new(peers_reader_guid, self.initial_peers.clone(), vec![]),
+          subscription_topic_data: SubscriptionBuiltinTopicData::new(
+            peers_reader_guid,
+            Some(dp.guid()),
+            String::from("DCPSParticipant"),
+            String::from("SPDPDiscoveredParticipantData"),
+            &Self::create_spdp_patricipant_qos(),
+          ),
+          content_filter: None,
+        },
+      });
+    }
   }
 
+  #[cfg(feature = "discovery")]
   pub fn handle_participant_reader(&mut self) {
     loop {
-      let s = self.dcps_participant_reader.read_next_sample();
+      let s = self.spdp_sedp.dcps_participant_reader.read_next_sample();
       debug!("handle_participant_reader read {:?}", &s);
       match s {
         Ok(Some(d)) => match d.value {
@@ -899,13 +1751,71 @@ impl Discovery {
               "handle_participant_reader discovered {:?}",
               &participant_data
             );
-            let was_new = self
-              .discovery_db_write()
-              .update_participant(&participant_data);
+            if self
+              .discovery_db_read()
+              .is_ignored(participant_data.participant_guid)
+            {
+              continue;
+            }
+            if participant_data.domain_tag != self.domain_tag {
+              debug!(
+                "Ignoring participant {:?} with domain tag {:?}",
+                participant_data.participant_guid, participant_data.domain_tag
+              );
+              continue;
+            }
+            if !self.participant_accepted(&participant_data) {
+              continue;
+            }
+            #[cfg(feature = "security")]
+            if !self.begin_authentication(&participant_data) {
+              continue;
+            }
             let guid_prefix = participant_data.participant_guid.prefix;
+            let (was_new, was_changed, authenticated) = {
+              let mut db = self.discovery_db_write();
+              // updated_time changes on every announcement, so it does not count.
+              let was_changed = db.find_participant_proxy(guid_prefix).map_or(true, |old| {
+                SpdpDiscoveredParticipantData {
+                  updated_time: participant_data.updated_time,
+                  ..old.clone()
+                } != participant_data
+              });
+              let was_new = db.update_participant(&participant_data);
+              // Participants without an IdentityToken were let in without
+              // authentication.
+              #[cfg(feature = "security")]
+              if was_new
+                && self.authentication.is_some()
+                && participant_data.identity_token.is_some()
+              {
+                db.set_authenticated(guid_prefix, false);
+              }
+              (was_new, was_changed, db.is_authenticated(guid_prefix))
+            };
             self.send_discovery_notification(DiscoveryNotificationType::ParticipantUpdated {
               guid_prefix,
             });
+            if !authenticated {
+              // The application hears of the participant once it is
+              // authenticated.
+              continue;
+            }
+            if was_new {
+              self.send_discovery_event(
+                guid_prefix,
+                &DiscoveryEvent::ParticipantDiscovered {
+                  participant: participant_data.clone(),
+                },
+              );
+            } else if was_changed {
+              self.send_discovery_event(
+                guid_prefix,
+                &DiscoveryEvent::ParticipantUpdated {
+                  participant: participant_data.clone(),
+                },
+              );
+            }
             if was_new {
               // This may be a rediscovery of a previously seen participant that
               // was temporarily lost due to network outage. Check if we already know
@@ -919,12 +1829,19 @@ impl Discovery {
           }
           // Err means that DomainParticipant was disposed
           Err(participant_guid) => {
-            self
-              .discovery_db_write()
-              .remove_participant(participant_guid.0.prefix);
+            let guid_prefix = participant_guid.0.prefix;
+            let was_known = {
+              let mut db = self.discovery_db_write();
+              let was_known = db.find_participant_proxy(guid_prefix).is_some();
+              db.remove_participant(guid_prefix);
+              was_known
+            };
             self.send_discovery_notification(DiscoveryNotificationType::ParticipantLost {
-              guid_prefix: participant_guid.0.prefix,
+              guid_prefix,
             });
+            if was_known {
+              self.participant_lost(guid_prefix, ParticipantLostReason::Disposed);
+            }
           }
         },
         Ok(None) => {
@@ -937,9 +1854,13 @@ impl Discovery {
   }
 
   // Check if there are messages about new Readers
+  #[cfg(feature = "discovery")]
   pub fn handle_subscription_reader(&mut self, read_history: Option<GuidPrefix>) {
+    if !self.sedp_enabled {
+      return;
+    }
     let drds: Vec<std::result::Result<DiscoveredReaderData, GUID>> =
-      match self.dcps_subscription_reader.read(
+      match self.spdp_sedp.dcps_subscription_reader.read(
         usize::MAX,
         if read_history.is_some() {
           ReadCondition::any()
@@ -969,17 +1890,12 @@ impl Discovery {
         }
       };
 
+    let drds = latest_per_endpoint(drds, |drd| drd.reader_proxy.remote_reader_guid);
     for d in drds {
       match d {
         Ok(d) => {
-          let drd = self.discovery_db_write().update_subscription(&d);
-          debug!(
-            "handle_subscription_reader - send_discovery_notification ReaderUpdated  {:?}",
-            &drd
-          );
-          self.send_discovery_notification(DiscoveryNotificationType::ReaderUpdated {
-            discovered_reader_data: drd,
-          });
+          debug!("handle_subscription_reader discovered {:?}", &d);
+          self.reader_discovered(&d);
           if read_history.is_some() {
             info!(
               "Rediscovered reader {:?} topic={:?}",
@@ -990,18 +1906,35 @@ impl Discovery {
         }
         Err(reader_key) => {
           info!("Dispose Reader {:?}", reader_key);
-          self.discovery_db_write().remove_topic_reader(reader_key);
+          let was_known = {
+            let mut db = self.discovery_db_write();
+            let was_known = db.find_external_reader(reader_key).is_some();
+            db.remove_topic_reader(reader_key);
+            was_known
+          };
           self.send_discovery_notification(DiscoveryNotificationType::ReaderLost {
             reader_guid: reader_key,
           });
+          if was_known {
+            self.send_discovery_event(
+              reader_key.prefix,
+              &DiscoveryEvent::ReaderLost {
+                reader_guid: reader_key,
+              },
+            );
+          }
         }
       }
     } // loop
   }
 
+  #[cfg(feature = "discovery")]
   pub fn handle_publication_reader(&mut self, read_history: Option<GuidPrefix>) {
+    if !self.sedp_enabled {
+      return;
+    }
     let dwds: Vec<std::result::Result<DiscoveredWriterData, GUID>> =
-      match self.dcps_publication_reader.read(
+      match self.spdp_sedp.dcps_publication_reader.read(
         usize::MAX,
         if read_history.is_some() {
           ReadCondition::any()
@@ -1030,30 +1963,44 @@ impl Discovery {
         }
       };
 
+    let dwds = latest_per_endpoint(dwds, |dwd| dwd.writer_proxy.remote_writer_guid);
     for d in dwds {
       match d {
         Ok(dwd) => {
           trace!("handle_publication_reader discovered {:?}", &dwd);
-          let discovered_writer_data = self.discovery_db_write().update_publication(&dwd);
-          self.send_discovery_notification(DiscoveryNotificationType::WriterUpdated {
-            discovered_writer_data,
-          });
-          debug!("Discovered Writer {:?}", &dwd);
+          self.writer_discovered(&dwd);
         }
         Err(writer_key) => {
-          self.discovery_db_write().remove_topic_writer(writer_key);
+          let was_known = {
+            let mut db = self.discovery_db_write();
+            let was_known = db.find_external_writer(writer_key).is_some();
+            db.remove_topic_writer(writer_key);
+            was_known
+          };
           self.send_discovery_notification(DiscoveryNotificationType::WriterLost {
             writer_guid: writer_key,
           });
+          if was_known {
+            self.send_discovery_event(
+              writer_key.prefix,
+              &DiscoveryEvent::WriterLost {
+                writer_guid: writer_key,
+              },
+            );
+          }
           debug!("Disposed Writer {:?}", writer_key);
         }
       }
     } // loop
   }
 
+  #[cfg(feature = "discovery")]
   pub fn handle_topic_reader(&mut self, read_history: Option<GuidPrefix>) {
+    if !self.sedp_enabled {
+      return;
+    }
     let ts: Vec<std::result::Result<(DiscoveredTopicData, GUID), GUID>> =
-      match self.dcps_topic_reader.read(
+      match self.spdp_sedp.dcps_topic_reader.read(
         usize::MAX,
         if read_history.is_some() {
           ReadCondition::any()
@@ -1082,6 +2029,9 @@ impl Discovery {
       match t {
         Ok((topic_data, writer)) => {
           info!("handle_topic_reader discovered {:?}", &topic_data);
+          if self.discovery_db_read().is_ignored(writer) {
+            continue;
+          }
           self
             .discovery_db_write()
             .update_topic_data(&topic_data, writer, DiscoveredVia::Topic);
@@ -1176,7 +2126,7 @@ impl Discovery {
         Liveliness::ManualByTopic { lease_duration: _ } => false,
       });
 
-    let inow = Timestamp::now();
+    let inow = self.time_source.now();
 
     // Automatic
     {
@@ -1243,11 +2193,512 @@ impl Discovery {
     }
   }
 
-  pub fn participant_cleanup(&self) {
+  pub fn handle_type_lookup_request_reader(&mut self) {
+    let requests = match self
+      .type_lookup_request_reader
+      .take(100, ReadCondition::any())
+    {
+      Ok(samples) => samples,
+      Err(e) => {
+        error!("Failed to read TypeLookup requests. {:?}", e);
+        return;
+      }
+    };
+
+    for request in requests.into_iter().map(|s| s.into_value()) {
+      // Our own requests are matched to our own reader, too.
+      if request.header.request_id.writer_guid.prefix == self.domain_participant.guid().prefix {
+        continue;
+      }
+      let (remote_ex, return_data) = match request.call {
+        TypeLookupCall::GetTypes { type_ids } => {
+          let db = self.discovery_db_read();
+          let types = type_ids
+            .into_iter()
+            .filter_map(|type_id| {
+              db.find_type_object(&type_id)
+                .map(|type_object| (type_id, type_object.clone()))
+            })
+            .collect();
+          (
+            RemoteExceptionCode::Ok,
+            TypeLookupReturn::GetTypes { types },
+          )
+        }
+        TypeLookupCall::Unsupported { operation_id } => {
+          debug!("Unsupported TypeLookup operation {:x?}", operation_id);
+          (
+            RemoteExceptionCode::UnknownOperation,
+            TypeLookupReturn::Unsupported { operation_id },
+          )
+        }
+      };
+      let reply = TypeLookupReply {
+        header: ReplyHeader {
+          related_request_id: request.header.request_id,
+          remote_ex,
+        },
+        return_data,
+      };
+      self
+        .type_lookup_reply_writer
+        .write(reply, None)
+        .unwrap_or_else(|e| error!("Failed to write TypeLookup reply. {:?}", e));
+    }
+  }
+
+  pub fn handle_type_lookup_reply_reader(&mut self) {
+    let replies = match self
+      .type_lookup_reply_reader
+      .take(100, ReadCondition::any())
+    {
+      Ok(samples) => samples,
+      Err(e) => {
+        error!("Failed to read TypeLookup replies. {:?}", e);
+        return;
+      }
+    };
+
+    let mut db = self.discovery_db_write();
+    for reply in replies.into_iter().map(|s| s.into_value()) {
+      // Replies go to all requesters, so we may also see answers to others.
+      // They are just as good.
+      if let TypeLookupReturn::GetTypes { types } = reply.return_data {
+        for (type_id, type_object) in types {
+          if type_object.type_identifier() == type_id {
+            db.add_type_object(type_object);
+          } else {
+            warn!("TypeLookup reply has TypeObject not matching {:?}", type_id);
+          }
+        }
+      }
+    }
+  }
+
+  fn request_type_objects(&mut self, type_ids: Vec<TypeIdentifier>) {
+    self.type_lookup_request_count += 1;
+    let request = TypeLookupRequest {
+      header: RequestHeader {
+        request_id: SampleIdentity {
+          writer_guid: self.type_lookup_request_writer.guid(),
+          sequence_number: SequenceNumber::from(self.type_lookup_request_count),
+        },
+        instance_name: String::new(),
+      },
+      call: TypeLookupCall::GetTypes { type_ids },
+    };
+    self
+      .type_lookup_request_writer
+      .write(request, None)
+      .unwrap_or_else(|e| error!("Failed to write TypeLookup request. {:?}", e));
+  }
+
+  pub fn participant_cleanup(&mut self) {
     let removed_guid_prefixes = self.discovery_db_write().participant_cleanup();
     for guid_prefix in removed_guid_prefixes {
       debug!("participant cleanup - timeout for {:?}", guid_prefix);
       self.send_discovery_notification(DiscoveryNotificationType::ParticipantLost { guid_prefix });
+      self.participant_lost(guid_prefix, ParticipantLostReason::LeaseExpired);
+    }
+  }
+
+  // Consult the authenticator about participants that we have not seen
+  // before. Rejected ones are ignored from now on, so that nothing is
+  // exchanged with them.
+  #[cfg(feature = "discovery")]
+  fn participant_accepted(&self, participant_data: &SpdpDiscoveredParticipantData) -> bool {
+    let authenticator = match &self.participant_authenticator {
+      Some(authenticator) => authenticator,
+      None => return true,
+    };
+    let guid_prefix = participant_data.participant_guid.prefix;
+    if guid_prefix == self.domain_participant.guid().prefix
+      || self
+        .discovery_db_read()
+        .find_participant_proxy(guid_prefix)
+        .is_some()
+    {
+      return true; // already accepted
+    }
+    if authenticator.authenticate(participant_data) {
+      info!("Accepted participant {:?}", guid_prefix);
+      true
+    } else {
+      info!("Rejected participant {:?}", guid_prefix);
+      self.discovery_db_write().ignore_participant(guid_prefix);
+      false
+    }
+  }
+
+  // With DDS Security, a new participant must authenticate itself before
+  // anything but SPDP is exchanged with it. Participants that cannot
+  // authenticate are ignored, unless the governance allows unauthenticated
+  // participants and the participant does not use DDS Security at all.
+  #[cfg(feature = "security")]
+  fn begin_authentication(&mut self, participant_data: &SpdpDiscoveredParticipantData) -> bool {
+    let guid = participant_data.participant_guid;
+    let service = match &mut self.authentication {
+      Some(service) => service,
+      None => return true,
+    };
+    if guid.prefix == self.domain_participant.guid().prefix {
+      return true;
+    }
+    if participant_data.identity_token.is_none()
+      && matches!(&service.access_control, Some(ac) if ac.allows_unauthenticated_participants())
+    {
+      return true;
+    }
+    match service
+      .authentication
+      .begin(guid, participant_data.identity_token.as_ref())
+    {
+      Ok(request) => {
+        if let Some(request) = request {
+          Self::write_stateless_message(service, request);
+        }
+        true
+      }
+      Err(e) => {
+        info!("Ignoring participant {:?}: {}", guid.prefix, e);
+        self.discovery_db_write().ignore_participant(guid.prefix);
+        false
+      }
+    }
+  }
+
+  #[cfg(feature = "security")]
+  fn handle_stateless_message_reader(&mut self) {
+    let service = match &mut self.authentication {
+      Some(service) => service,
+      None => return,
+    };
+    let messages = match service.reader.take(100, ReadCondition::any()) {
+      Ok(samples) => samples,
+      Err(e) => {
+        error!("Failed to read stateless messages. {:?}", e);
+        return;
+      }
+    };
+
+    let mut authenticated = Vec::new();
+    for message in messages.into_iter().map(|s| s.into_value()) {
+      let source = message.message_identity.source_guid.prefix;
+      match service.authentication.process(&message) {
+        Ok(HandshakeProgress::Send(reply)) => Self::write_stateless_message(service, reply),
+        Ok(HandshakeProgress::Completed(reply)) => {
+          if let Some(reply) = reply {
+            Self::write_stateless_message(service, reply);
+          }
+          authenticated.push(source);
+        }
+        Ok(HandshakeProgress::Nothing) => (),
+        Err(e) => warn!("Dropped handshake message from {:?}: {}", source, e),
+      }
+    }
+    for guid_prefix in authenticated {
+      self.participant_authenticated(guid_prefix);
+    }
+  }
+
+  // Now the participant gets matched with all our builtin endpoints, and the
+  // application hears of it. With access control, its permissions must allow
+  // it to join the domain.
+  #[cfg(feature = "security")]
+  fn participant_authenticated(&mut self, guid_prefix: GuidPrefix) {
+    if let Some(service) = &mut self.authentication {
+      if let (Some(access_control), Some(remote)) = (
+        &service.access_control,
+        service.authentication.remote_credentials(guid_prefix),
+      ) {
+        if let Err(e) = access_control.validate_remote_permissions(guid_prefix, &remote) {
+          info!("Ignoring participant {:?}: {}", guid_prefix, e);
+          service.authentication.remove(guid_prefix);
+          self.discovery_db_write().ignore_participant(guid_prefix);
+          return;
+        }
+      }
+    }
+    self.begin_key_exchange(guid_prefix);
+    let participant = {
+      let mut db = self.discovery_db_write();
+      db.set_authenticated(guid_prefix, true);
+      db.find_participant_proxy(guid_prefix).cloned()
+    };
+    self.send_discovery_notification(DiscoveryNotificationType::ParticipantUpdated { guid_prefix });
+    if let Some(participant) = participant {
+      self.send_discovery_event(
+        guid_prefix,
+        &DiscoveryEvent::ParticipantDiscovered { participant },
+      );
+    }
+  }
+
+  // Derive the key exchange keys from the handshake, and send our participant
+  // crypto tokens. The endpoint tokens follow when SEDP has matched endpoints.
+  #[cfg(feature = "security")]
+  fn begin_key_exchange(&self, guid_prefix: GuidPrefix) {
+    let (authentication, key_exchange) = match &self.authentication {
+      Some(AuthenticationService {
+        authentication,
+        key_exchange: Some(key_exchange),
+        ..
+      }) => (authentication, key_exchange),
+      _ => return,
+    };
+    let shared_secret = match authentication.shared_secret(guid_prefix) {
+      Some(shared_secret) => shared_secret,
+      None => return,
+    };
+    match key_exchange
+      .cryptography
+      .register_remote_participant(guid_prefix, &shared_secret)
+    {
+      Ok(()) => key_exchange.send_participant_tokens(guid_prefix),
+      Err(e) => error!("Cannot exchange keys with {:?}: {}", guid_prefix, e),
+    }
+  }
+
+  // Crypto tokens from authenticated participants. The ParticipantGenericMessage
+  // must come from the participant whose tokens it carries.
+  #[cfg(feature = "security")]
+  fn handle_volatile_message_reader(&mut self) {
+    let my_prefix = self.domain_participant.guid().prefix;
+    let key_exchange = match &mut self.authentication {
+      Some(AuthenticationService {
+        key_exchange: Some(key_exchange),
+        ..
+      }) => key_exchange,
+      _ => return,
+    };
+    let samples = match key_exchange.reader.take(100, ReadCondition::any()) {
+      Ok(samples) => samples,
+      Err(e) => {
+        error!("Failed to read crypto token messages. {:?}", e);
+        return;
+      }
+    };
+
+    for sample in samples {
+      let source = sample.sample_info().writer_guid().prefix;
+      let message = sample.into_value();
+      if message.destination_participant_guid.prefix != my_prefix {
+        continue; // For some other participant
+      }
+      let result = match message.message_class_id.as_str() {
+        ParticipantGenericMessage::PARTICIPANT_CRYPTO_TOKENS => key_exchange
+          .cryptography
+          .set_remote_participant_tokens(source, &message.message_data),
+        ParticipantGenericMessage::DATAWRITER_CRYPTO_TOKENS
+        | ParticipantGenericMessage::DATAREADER_CRYPTO_TOKENS
+          if message.source_endpoint_guid.prefix == source =>
+        {
+          key_exchange
+            .cryptography
+            .set_remote_endpoint_tokens(message.source_endpoint_guid, &message.message_data)
+        }
+        other => {
+          debug!(
+            "Ignoring crypto token message {:?} from {:?}",
+            other, message.source_endpoint_guid
+          );
+          Ok(())
+        }
+      };
+      if let Err(e) = result {
+        warn!("Dropped crypto tokens from {:?}: {}", source, e);
+      }
+    }
+  }
+
+  // Send the crypto tokens of local endpoints to the matching remote endpoints
+  // that do not have them yet. Local endpoints get their keys asynchronously,
+  // so this is repeated.
+  #[cfg(feature = "security")]
+  fn send_endpoint_crypto_tokens(&self) {
+    let key_exchange = match &self.authentication {
+      Some(AuthenticationService {
+        key_exchange: Some(key_exchange),
+        ..
+      }) => key_exchange,
+      _ => return,
+    };
+    let mut pairs = Vec::new();
+    {
+      let db = self.discovery_db_read();
+      for writer in db.get_all_local_topic_writers() {
+        let topic_name = &writer.publication_topic_data.topic_name;
+        for reader in db.external_readers_on_topic(topic_name) {
+          pairs.push((
+            writer.writer_proxy.remote_writer_guid,
+            reader.reader_proxy.remote_reader_guid,
+            ParticipantGenericMessage::DATAWRITER_CRYPTO_TOKENS,
+          ));
+        }
+      }
+      for reader in db.get_all_local_topic_readers() {
+        let topic_name = reader.subscription_topic_data.topic_name();
+        for writer in db.external_writers_on_topic(topic_name) {
+          pairs.push((
+            reader.reader_proxy.remote_reader_guid,
+            writer.writer_proxy.remote_writer_guid,
+            ParticipantGenericMessage::DATAREADER_CRYPTO_TOKENS,
+          ));
+        }
+      }
+    }
+    for (local, remote, message_class_id) in pairs {
+      key_exchange.send_endpoint_tokens(local, remote, message_class_id);
+    }
+  }
+
+  // Replace the keys that are due for rotation. New participant keys are sent
+  // here, and new endpoint keys by send_endpoint_crypto_tokens.
+  #[cfg(feature = "security")]
+  fn rotate_crypto_keys(&self) {
+    if let Some(AuthenticationService {
+      key_exchange: Some(key_exchange),
+      ..
+    }) = &self.authentication
+    {
+      match key_exchange.cryptography.rotate_keys() {
+        Ok(participants) => {
+          for guid_prefix in participants {
+            key_exchange.send_participant_tokens(guid_prefix);
+          }
+        }
+        Err(e) => error!("Cannot rotate crypto keys: {}", e),
+      }
+    }
+  }
+
+  // Remote endpoints on protected topics need our keys, and we need theirs.
+  #[cfg(feature = "security")]
+  fn remote_endpoint_discovered(&self, guid: GUID, topic_name: &str) {
+    if let Some(AuthenticationService {
+      key_exchange: Some(key_exchange),
+      ..
+    }) = &self.authentication
+    {
+      key_exchange
+        .cryptography
+        .register_remote_endpoint(guid, topic_name);
+      self.send_endpoint_crypto_tokens();
+    }
+  }
+
+  #[cfg(feature = "security")]
+  fn resend_handshake_messages(&mut self) {
+    if let Some(service) = &mut self.authentication {
+      for message in service.authentication.resend() {
+        Self::write_stateless_message(service, message);
+      }
+      service
+        .resend_timer
+        .set_timeout(Self::HANDSHAKE_RESEND_PERIOD, ());
+    }
+  }
+
+  #[cfg(feature = "security")]
+  fn write_stateless_message(service: &AuthenticationService, message: ParticipantGenericMessage) {
+    service
+      .writer
+      .write(message, None)
+      .unwrap_or_else(|e| error!("Cannot send handshake message: {:?}", e));
+  }
+
+  // With access control, remote DataWriters and DataReaders are matched only if
+  // the governance and the permissions of their participant allow it.
+  #[cfg(feature = "security")]
+  fn remote_endpoint_permitted(&self, guid: GUID, topic_name: &str, access: TopicAccess) -> bool {
+    let access_control = match &self.authentication {
+      Some(AuthenticationService {
+        access_control: Some(access_control),
+        ..
+      }) => access_control,
+      _ => return true,
+    };
+    match access_control.check_remote_endpoint(guid, topic_name, access) {
+      Ok(()) => true,
+      Err(e) => {
+        debug!("Ignoring remote endpoint {:?}: {}", guid, e);
+        false
+      }
+    }
+  }
+
+  // Our SPDP data is sent in the handshake messages.
+  #[cfg(feature = "security")]
+  fn set_handshake_participant_data(&mut self, data: &SpdpDiscoveredParticipantData) {
+    if let Some(service) = &mut self.authentication {
+      service.authentication.set_participant_data(data);
+    }
+  }
+
+  fn participant_lost(&mut self, guid_prefix: GuidPrefix, reason: ParticipantLostReason) {
+    #[cfg(feature = "security")]
+    if let Some(service) = &mut self.authentication {
+      service.authentication.remove(guid_prefix);
+      if let Some(access_control) = &service.access_control {
+        access_control.remove_remote_participant(guid_prefix);
+      }
+      if let Some(key_exchange) = &service.key_exchange {
+        key_exchange
+          .cryptography
+          .remove_remote_participant(guid_prefix);
+      }
+    }
+    self
+      .participant_status_broadcaster
+      .send(&DomainParticipantStatus::ParticipantLost {
+        guid_prefix,
+        reason,
+      });
+    self.send_discovery_event(
+      guid_prefix,
+      &DiscoveryEvent::ParticipantLost {
+        guid_prefix,
+        reason,
+      },
+    );
+  }
+
+  // Events about our own participant and endpoints are not sent to the
+  // application.
+  fn send_discovery_event(&self, guid_prefix: GuidPrefix, event: &DiscoveryEvent) {
+    if guid_prefix != self.domain_participant.guid().prefix {
+      runtime_metrics::discovery_event(event);
+      self.discovery_event_broadcaster.send(event);
+    }
+  }
+
+  // Leases may be shorter than our regular cleanup period, so check again
+  // at the next expiry, if that comes sooner.
+  fn next_participant_cleanup_delay(&self) -> StdDuration {
+    match self.discovery_db_read().next_lease_expiry() {
+      Some(expiry) => expiry
+        .saturating_duration_since(self.time_source.instant())
+        .max(Self::PARTICIPANT_CLEANUP_MIN_DELAY)
+        .min(Self::PARTICIPANT_CLEANUP_PERIOD),
+      None => Self::PARTICIPANT_CLEANUP_PERIOD,
+    }
+  }
+
+  // If clocks have jumped, e.g. due to system suspend or NTP time step, then
+  // our idea of elapsed time is wrong. Instead of expiring all leases and
+  // deadlines at once (or stalling timers), we restart them from now.
+  fn handle_clock_jump(&mut self) {
+    if let Some((kind, amount)) = self.clock_monitor.check() {
+      warn!(
+        "Clock jump detected: {:?} by {:?}. Restarting lease and liveliness timers.",
+        kind, amount
+      );
+      self.discovery_db_write().restart_leases();
+      self.liveliness_state = LivelinessState::new(self.time_source.now());
+      self.send_discovery_notification(DiscoveryNotificationType::ClockJump);
+      self
+        .participant_status_broadcaster
+        .send(&DomainParticipantStatus::ClockJumpHandled { kind, amount });
     }
   }
 
@@ -1255,56 +2706,325 @@ impl Discovery {
     self.discovery_db_write().topic_cleanup();
   }
 
+  #[cfg_attr(not(feature = "discovery"), allow(clippy::needless_return))]
   pub fn write_readers_info(&self) {
-    let db = self.discovery_db_read();
-    let local_user_readers = db.get_all_local_topic_readers().filter(|p| {
-      p.reader_proxy
-        .remote_reader_guid
-        .entity_id
-        .kind()
-        .is_user_defined()
-    });
-    let mut count = 0;
-    for data in local_user_readers {
-      match self.dcps_subscription_writer.write(data.clone(), None) {
-        Ok(_) => {
-          count += 1;
+    if !self.sedp_enabled {
+      // Our own readers do not come back to us via SEDP, so they must be
+      // matched with our writers directly.
+      let local_user_readers: Vec<DiscoveredReaderData> = self
+        .discovery_db_read()
+        .get_all_local_topic_readers()
+        .filter(|p| {
+          p.reader_proxy
+            .remote_reader_guid
+            .entity_id
+            .kind()
+            .is_user_defined()
+        })
+        .cloned()
+        .collect();
+      for drd in &local_user_readers {
+        self.reader_discovered(drd);
+      }
+      return;
+    }
+    #[cfg(feature = "discovery")]
+    {
+      let db = self.discovery_db_read();
+      let local_user_readers = db.get_all_local_topic_readers().filter(|p| {
+        p.reader_proxy
+          .remote_reader_guid
+          .entity_id
+          .kind()
+          .is_user_defined()
+      });
+      let mut count = 0;
+      for data in local_user_readers {
+        match self
+          .spdp_sedp
+          .dcps_subscription_writer
+          .write(data.clone(), None)
+        {
+          Ok(_) => {
+            count += 1;
+          }
+          Err(e) => error!("Unable to write new readers info. {:?}", e),
         }
-        Err(e) => error!("Unable to write new readers info. {:?}", e),
       }
+      debug!("Announced {} readers", count);
     }
-    debug!("Announced {} readers", count);
   }
 
+  #[cfg_attr(not(feature = "discovery"), allow(clippy::needless_return))]
   pub fn write_writers_info(&self) {
-    let db = self.discovery_db_read();
-    let local_user_writers = db.get_all_local_topic_writers().filter(|p| {
-      p.writer_proxy
-        .remote_writer_guid
-        .entity_id
-        .kind()
-        .is_user_defined()
+    if !self.sedp_enabled {
+      let local_user_writers: Vec<DiscoveredWriterData> = self
+        .discovery_db_read()
+        .get_all_local_topic_writers()
+        .filter(|p| {
+          p.writer_proxy
+            .remote_writer_guid
+            .entity_id
+            .kind()
+            .is_user_defined()
+        })
+        .cloned()
+        .collect();
+      for dwd in &local_user_writers {
+        self.writer_discovered(dwd);
+      }
+      return;
+    }
+    #[cfg(feature = "discovery")]
+    {
+      let db = self.discovery_db_read();
+      let local_user_writers = db.get_all_local_topic_writers().filter(|p| {
+        p.writer_proxy
+          .remote_writer_guid
+          .entity_id
+          .kind()
+          .is_user_defined()
+      });
+      let mut count = 0;
+      for data in local_user_writers {
+        if self
+          .spdp_sedp
+          .dcps_publication_writer
+          .write(data.clone(), None)
+          .is_err()
+        {
+          error!("Unable to write new writers info.");
+        } else {
+          count += 1;
+        }
+      }
+      debug!("Announced {} writers", count);
+    }
+  }
+
+  // Statically configured endpoints are refreshed at the same rate as SEDP
+  // announcements would be, so that they come back after e.g. the remote
+  // participant has been lost and rediscovered.
+  fn update_static_readers(&self) {
+    for drd in self
+      .static_endpoints
+      .iter()
+      .filter_map(StaticEndpoint::discovered_reader_data)
+    {
+      self.reader_discovered(&drd);
+    }
+  }
+
+  fn update_static_writers(&self) {
+    for dwd in self
+      .static_endpoints
+      .iter()
+      .filter_map(StaticEndpoint::discovered_writer_data)
+    {
+      self.writer_discovered(&dwd);
+    }
+  }
+
+  // Reader data received via SEDP, or fed in directly if SEDP is not used.
+  fn reader_discovered(&self, drd: &DiscoveredReaderData) {
+    let guid = drd.reader_proxy.remote_reader_guid;
+    if self.discovery_db_read().is_ignored(guid) {
+      return;
+    }
+    #[cfg(feature = "security")]
+    if !self.remote_endpoint_permitted(
+      guid,
+      drd.subscription_topic_data.topic_name(),
+      TopicAccess::Subscribe,
+    ) {
+      return;
+    }
+    let (previous, discovered_reader_data) = {
+      let mut db = self.discovery_db_write();
+      let previous = db.find_external_reader(guid).cloned();
+      (previous, db.update_subscription(drd))
+    };
+    #[cfg(feature = "security")]
+    self.remote_endpoint_discovered(guid, drd.subscription_topic_data.topic_name());
+    // Readers are announced repeatedly, so report only actual changes.
+    match previous {
+      None => self.send_discovery_event(
+        guid.prefix,
+        &DiscoveryEvent::ReaderDiscovered {
+          reader: discovered_reader_data.clone(),
+        },
+      ),
+      Some(previous) if previous != *drd => self.send_discovery_event(
+        guid.prefix,
+        &DiscoveryEvent::ReaderUpdated {
+          reader: discovered_reader_data.clone(),
+        },
+      ),
+      Some(_) => (),
+    }
+    self.send_discovery_notification(DiscoveryNotificationType::ReaderUpdated {
+      discovered_reader_data,
     });
-    let mut count = 0;
-    for data in local_user_writers {
-      if self
-        .dcps_publication_writer
-        .write(data.clone(), None)
-        .is_err()
+  }
+
+  // Writer data received via SEDP, or fed in directly if SEDP is not used.
+  fn writer_discovered(&self, dwd: &DiscoveredWriterData) {
+    let guid = dwd.writer_proxy.remote_writer_guid;
+    if self.discovery_db_read().is_ignored(guid) {
+      return;
+    }
+    #[cfg(feature = "security")]
+    if !self.remote_endpoint_permitted(
+      guid,
+      &dwd.publication_topic_data.topic_name,
+      TopicAccess::Publish,
+    ) {
+      return;
+    }
+    let (previous, discovered_writer_data) = {
+      let mut db = self.discovery_db_write();
+      let previous = db.find_external_writer(guid).cloned();
+      (previous, db.update_publication(dwd))
+    };
+    #[cfg(feature = "security")]
+    self.remote_endpoint_discovered(guid, &dwd.publication_topic_data.topic_name);
+    // last_updated changes on every announcement, so it does not count.
+    match previous {
+      None => self.send_discovery_event(
+        guid.prefix,
+        &DiscoveryEvent::WriterDiscovered {
+          writer: discovered_writer_data.clone(),
+        },
+      ),
+      Some(previous)
+        if previous.writer_proxy != dwd.writer_proxy
+          || previous.publication_topic_data != dwd.publication_topic_data =>
       {
-        error!("Unable to write new writers info.");
-      } else {
-        count += 1;
+        self.send_discovery_event(
+          guid.prefix,
+          &DiscoveryEvent::WriterUpdated {
+            writer: discovered_writer_data.clone(),
+          },
+        );
+      }
+      Some(_) => (),
+    }
+    self.send_discovery_notification(DiscoveryNotificationType::WriterUpdated {
+      discovered_writer_data,
+    });
+  }
+
+  // The event loop updates the self locators of the participant when network
+  // interfaces change. Then our endpoints are announced again with the new
+  // locators.
+  fn update_self_locators(&mut self, dp: &DomainParticipant) {
+    let self_locators = dp.self_locators();
+    if self_locators == self.self_locators {
+      return;
+    }
+    info!("Self locators changed. Announcing new locators.");
+    let locators = |token| self_locators.get(&token).cloned().unwrap_or_default();
+    self.discovery_db_write().update_local_locators(
+      &locators(USER_TRAFFIC_LISTENER_TOKEN),
+      &locators(USER_TRAFFIC_MUL_LISTENER_TOKEN),
+    );
+    self.self_locators = self_locators;
+    self.write_readers_info();
+    self.write_writers_info();
+  }
+
+  // Our SPDP announcement. Without SEDP, we do not advertise the SEDP
+  // endpoints, so that remote participants do not try to use them.
+  fn local_participant_data(
+    &self,
+    dp: &DomainParticipant,
+    lease_duration: Duration,
+  ) -> SpdpDiscoveredParticipantData {
+    let mut data = SpdpDiscoveredParticipantData::from_local_participant(
+      dp,
+      &self.self_locators,
+      lease_duration,
+    );
+    if !self.sedp_enabled {
+      data.available_builtin_endpoints = data.available_builtin_endpoints.without(
+        BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_PUBLICATIONS_ANNOUNCER
+          | BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_PUBLICATIONS_DETECTOR
+          | BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_SUBSCRIPTIONS_ANNOUNCER
+          | BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_SUBSCRIPTIONS_DETECTOR
+          | BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_TOPICS_ANNOUNCER
+          | BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_TOPICS_DETECTOR,
+      );
+    }
+    #[cfg(feature = "security")]
+    if let Some(service) = &self.authentication {
+      data.available_builtin_endpoints = data.available_builtin_endpoints.with(
+        BuiltinEndpointSet::BUILTIN_PARTICIPANT_STATELESS_MESSAGE_WRITER
+          | BuiltinEndpointSet::BUILTIN_PARTICIPANT_STATELESS_MESSAGE_READER,
+      );
+      if service.key_exchange.is_some() {
+        data.available_builtin_endpoints = data.available_builtin_endpoints.with(
+          BuiltinEndpointSet::BUILTIN_PARTICIPANT_VOLATILE_MESSAGE_SECURE_WRITER
+            | BuiltinEndpointSet::BUILTIN_PARTICIPANT_VOLATILE_MESSAGE_SECURE_READER,
+        );
+      }
+      data.identity_token = Some(service.authentication.identity_token());
+      data.permissions_token = service
+        .access_control
+        .as_ref()
+        .map(|access_control| access_control.permissions_token());
+    }
+    data
+  }
+
+  // Announce the new QoS of a local Writer and let dp_event_loop re-evaluate
+  // matching with it.
+  #[cfg_attr(not(feature = "discovery"), allow(unused_variables))]
+  fn update_local_writer(&self, guid: GUID, qos: QosPolicies) {
+    let updated = self
+      .discovery_db_write()
+      .update_local_topic_writer_qos(guid, &qos);
+    match updated {
+      Some(data) => {
+        #[cfg(feature = "discovery")]
+        if let Err(e) = self.spdp_sedp.dcps_publication_writer.write(data, None) {
+          error!("Unable to write updated writer info. {:?}", e);
+        }
+        self.send_discovery_notification(DiscoveryNotificationType::LocalWriterUpdated {
+          writer_guid: guid,
+          qos,
+        });
+      }
+      None => warn!("QoS update for unknown local writer {:?}", guid),
+    }
+  }
+
+  // Same as above, but for a local Reader
+  #[cfg_attr(not(feature = "discovery"), allow(unused_variables))]
+  fn update_local_reader(&self, guid: GUID, qos: QosPolicies) {
+    let updated = self
+      .discovery_db_write()
+      .update_local_topic_reader_qos(guid, &qos);
+    match updated {
+      Some(data) => {
+        #[cfg(feature = "discovery")]
+        if let Err(e) = self.spdp_sedp.dcps_subscription_writer.write(data, None) {
+          error!("Unable to write updated reader info. {:?}", e);
+        }
+        self.send_discovery_notification(DiscoveryNotificationType::LocalReaderUpdated {
+          reader_guid: guid,
+          qos,
+        });
       }
+      None => warn!("QoS update for unknown local reader {:?}", guid),
     }
-    debug!("Announced {} writers", count);
   }
 
+  #[cfg(feature = "discovery")]
   pub fn write_topic_info(&self) {
     let db = self.discovery_db_read();
     let datas = db.local_user_topics();
     for data in datas {
-      if let Err(e) = self.dcps_topic_writer.write(data.clone(), None) {
+      if let Err(e) = self.spdp_sedp.dcps_topic_writer.write(data.clone(), None) {
         error!("Unable to write new topic info: {:?}", e);
       }
     }
@@ -1398,12 +3118,52 @@ impl Discovery {
   }
 }
 
+// Discovery has been started already, when it is handed over to a thread or
+// a ParticipantDriver.
+impl BackgroundLoop for Discovery {
+  fn run(mut self: Box<Self>) {
+    let mut events = Events::with_capacity(32);
+    while self.run_round(&mut events, None) {}
+  }
+
+  fn run_round(&mut self, events: &mut Events, timeout: Option<StdDuration>) -> bool {
+    Discovery::run_round(self, events, timeout)
+  }
+}
+
+// A remote endpoint may have been announced several times before we read
+// the announcements, e.g. with updated QoS, and rediscovery reads the whole
+// history again. Only the latest announcement or disposal of each endpoint is
+// relevant. Applying the older ones also would cause spurious unmatching and
+// rematching, if the QoS has changed in between.
+#[cfg(feature = "discovery")]
+fn latest_per_endpoint<D>(
+  samples: Vec<std::result::Result<D, GUID>>,
+  guid_of: impl Fn(&D) -> GUID,
+) -> Vec<std::result::Result<D, GUID>> {
+  let guid = |sample: &std::result::Result<D, GUID>| match sample {
+    Ok(d) => guid_of(d),
+    Err(guid) => *guid,
+  };
+  let latest_index: BTreeMap<GUID, usize> = samples
+    .iter()
+    .enumerate()
+    .map(|(i, sample)| (guid(sample), i))
+    .collect();
+  samples
+    .into_iter()
+    .enumerate()
+    .filter(|(i, sample)| latest_index.get(&guid(sample)) == Some(i))
+    .map(|(_, sample)| sample)
+    .collect()
+}
+
 // -----------------------------------------------------------------------
 // -----------------------------------------------------------------------
 // -----------------------------------------------------------------------
 // -----------------------------------------------------------------------
 
-#[cfg(test)]
+#[cfg(all(test, feature = "discovery"))]
 mod tests {
   use std::net::SocketAddr;
 
@@ -1420,11 +3180,11 @@ mod tests {
       submessage_elements::serialized_payload::RepresentationIdentifier,
       submessages::{EntitySubmessage, InterpreterSubmessage},
     },
-    network::{udp_listener::UDPListener, udp_sender::UDPSender},
+    network::{port_mapping::PortMapping, udp_listener::UDPListener, udp_sender::UDPSender},
     serialization::{
       cdr_deserializer::CDRDeserializerAdapter, cdr_serializer::to_bytes, submessage::*,
     },
-    structure::{entity::RTPSEntity, locator::Locator},
+    structure::{entity::RTPSEntity, guid::EntityKind, locator::Locator},
     test::{
       shape_type::ShapeType,
       test_data::{
@@ -1434,6 +3194,18 @@ mod tests {
     },
   };
 
+  #[test]
+  fn discovery_latest_per_endpoint() {
+    let a = GUID::dummy_test_guid(EntityKind::READER_WITH_KEY_USER_DEFINED);
+    let b = GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED);
+    // (endpoint, QoS version)
+    let samples = vec![Ok((a, 1)), Ok((b, 1)), Ok((a, 2)), Err(b), Ok((a, 3))];
+    assert_eq!(
+      latest_per_endpoint(samples, |(guid, _)| *guid),
+      vec![Err(b), Ok((a, 3))]
+    );
+  }
+
   #[test]
   fn discovery_participant_data_test() {
     let poll = Poll::new().unwrap();
@@ -1451,7 +3223,7 @@ mod tests {
     let udp_sender = UDPSender::new_with_random_port().expect("failed to create UDPSender");
     let addresses = vec![SocketAddr::new(
       "127.0.0.1".parse().unwrap(),
-      spdp_well_known_unicast_port(0, 0),
+      PortMapping::default().spdp_unicast_port(0, 0).unwrap(),
     )];
 
     let tdata = spdp_participant_msg_mod(11000);
@@ -1511,7 +3283,7 @@ mod tests {
     let udp_sender = UDPSender::new_with_random_port().expect("failed to create UDPSender");
     let addresses = vec![SocketAddr::new(
       "127.0.0.1".parse().unwrap(),
-      spdp_well_known_unicast_port(14, 0),
+      PortMapping::default().spdp_unicast_port(14, 0).unwrap(),
     )];
 
     let mut tdata = spdp_subscription_msg();
@@ -1599,7 +3371,7 @@ mod tests {
     let udp_sender = UDPSender::new_with_random_port().expect("failed to create UDPSender");
     let addresses = vec![SocketAddr::new(
       "127.0.0.1".parse().unwrap(),
-      spdp_well_known_unicast_port(15, 0),
+      PortMapping::default().spdp_unicast_port(15, 0).unwrap(),
     )];
 
     let mut tdata = spdp_publication_msg();
@@ -1657,6 +3429,7 @@ mod tests {
         history: None,
         resource_limits: None,
         ownership: None,
+        topic_data: None,
       },
     );
 
@@ -1669,7 +3442,7 @@ mod tests {
     let udp_sender = UDPSender::new_with_random_port().expect("failed to create UDPSender");
     let addresses = vec![SocketAddr::new(
       "127.0.0.1".parse().unwrap(),
-      spdp_well_known_unicast_port(16, 0),
+      PortMapping::default().spdp_unicast_port(16, 0).unwrap(),
     )];
 
     let rr = rtps_message
@@ -1,4 +1,7 @@
-use std::{collections::BTreeMap, time::Instant};
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  time::Instant,
+};
 
 use chrono::Utc;
 #[allow(unused_imports)]
@@ -6,22 +9,32 @@ use log::{debug, error, info, trace, warn};
 
 use crate::{
   dds::{
-    participant::DomainParticipant, qos::HasQoSPolicy, reader::ReaderIngredients,
-    rtps_reader_proxy::RtpsReaderProxy, rtps_writer_proxy::RtpsWriterProxy, topic::Topic,
+    clock::TimeSource,
+    participant::DomainParticipant,
+    qos::{HasQoSPolicy, QosPolicies},
+    reader::ReaderIngredients,
+    rtps_reader_proxy::RtpsReaderProxy,
+    rtps_writer_proxy::RtpsWriterProxy,
+    topic::Topic,
     traits::TopicDescription,
   },
   structure::{
     duration::Duration,
     entity::RTPSEntity,
     guid::{EntityId, GuidPrefix, GUID},
+    locator::Locator,
   },
 };
-use super::data_types::{
-  spdp_participant_data::SpdpDiscoveredParticipantData,
-  topic_data::{
-    DiscoveredReaderData, DiscoveredTopicData, DiscoveredWriterData, ParticipantMessageData,
-    ReaderProxy, SubscriptionBuiltinTopicData, TopicBuiltinTopicData, WriterProxy,
+use super::{
+  content_filter_property::ContentFilterProperty,
+  data_types::{
+    spdp_participant_data::SpdpDiscoveredParticipantData,
+    topic_data::{
+      DiscoveredReaderData, DiscoveredTopicData, DiscoveredWriterData, ParticipantMessageData,
+      ReaderProxy, SubscriptionBuiltinTopicData, TopicBuiltinTopicData, WriterProxy,
+    },
   },
+  type_lookup::{TypeIdentifier, TypeObject},
 };
 
 // If remote participant does not specifiy lease duration, how long silence
@@ -54,6 +67,22 @@ pub(crate) struct DiscoveryDB {
 
   // sender for notifying (potential) waiters in participant.find_topic() call
   topic_updated_sender: mio_extras::channel::SyncSender<()>,
+
+  // Remote participants and endpoints the application has asked to ignore.
+  // This cannot be undone.
+  ignored_participants: BTreeSet<GuidPrefix>,
+  ignored_endpoints: BTreeSet<GUID>,
+
+  // Remote participants that have not completed the DDS Security
+  // authentication handshake. Only the builtin endpoints of SPDP and of the
+  // handshake are matched with them.
+  unauthenticated_participants: BTreeSet<GuidPrefix>,
+
+  // TypeObjects registered locally or received via TypeLookup service
+  type_objects: BTreeMap<TypeIdentifier, TypeObject>,
+
+  // The clock of the participant, for leases
+  time_source: TimeSource,
 }
 
 // How did we discover this topic
@@ -65,7 +94,11 @@ pub(crate) enum DiscoveredVia {
 }
 
 impl DiscoveryDB {
-  pub fn new(my_guid: GUID, topic_updated_sender: mio_extras::channel::SyncSender<()>) -> Self {
+  pub fn new(
+    my_guid: GUID,
+    topic_updated_sender: mio_extras::channel::SyncSender<()>,
+    time_source: TimeSource,
+  ) -> Self {
     Self {
       my_guid,
       participant_proxies: BTreeMap::new(),
@@ -76,6 +109,11 @@ impl DiscoveryDB {
       external_topic_writers: BTreeMap::new(),
       topics: BTreeMap::new(),
       topic_updated_sender,
+      ignored_participants: BTreeSet::new(),
+      ignored_endpoints: BTreeSet::new(),
+      unauthenticated_participants: BTreeSet::new(),
+      type_objects: BTreeMap::new(),
+      time_source,
     }
   }
 
@@ -120,13 +158,13 @@ impl DiscoveryDB {
     self.participant_proxies.insert(guid.prefix, data.clone());
     self
       .participant_last_life_signs
-      .insert(guid.prefix, Instant::now());
+      .insert(guid.prefix, self.time_source.instant());
 
     new_participant
   }
   pub fn participant_is_alive(&mut self, guid_prefix: GuidPrefix) {
     if let Some(ts) = self.participant_last_life_signs.get_mut(&guid_prefix) {
-      let now = Instant::now();
+      let now = self.time_source.instant();
       if now.duration_since(*ts) > std::time::Duration::from_secs(1) {
         debug!(
           "Participant alive update for {:?}, but no full update.",
@@ -143,10 +181,70 @@ impl DiscoveryDB {
     info!("removing participant {:?}", guid_prefix);
     self.participant_proxies.remove(&guid_prefix);
     self.participant_last_life_signs.remove(&guid_prefix);
+    self.unauthenticated_participants.remove(&guid_prefix);
 
     self.remove_topic_reader_with_prefix(guid_prefix);
 
     self.remove_topic_writer_with_prefix(guid_prefix);
+
+    self.remove_topics_with_prefix(guid_prefix);
+  }
+
+  // Forget what the participant told us about topics. Topics that nobody else
+  // knows about are removed entirely.
+  fn remove_topics_with_prefix(&mut self, guid_prefix: GuidPrefix) {
+    self.topics.retain(|_name, sources| {
+      sources.remove(&guid_prefix);
+      !sources.is_empty()
+    });
+  }
+
+  // Forget the participant and everything it has, and keep ignoring it.
+  pub fn ignore_participant(&mut self, guid_prefix: GuidPrefix) {
+    self.ignored_participants.insert(guid_prefix);
+    self.remove_participant(guid_prefix);
+  }
+
+  // Forget the remote reader or writer, and keep ignoring it.
+  pub fn ignore_endpoint(&mut self, guid: GUID) {
+    self.ignored_endpoints.insert(guid);
+    self.remove_topic_reader(guid);
+    self.remove_topic_writer(guid);
+  }
+
+  // Is the participant or endpoint ignored, or does it belong to an ignored
+  // participant?
+  pub fn is_ignored(&self, guid: GUID) -> bool {
+    self.ignored_participants.contains(&guid.prefix) || self.ignored_endpoints.contains(&guid)
+  }
+
+  #[cfg(feature = "security")]
+  pub fn set_authenticated(&mut self, guid_prefix: GuidPrefix, authenticated: bool) {
+    if authenticated {
+      self.unauthenticated_participants.remove(&guid_prefix);
+    } else {
+      self.unauthenticated_participants.insert(guid_prefix);
+    }
+  }
+
+  // Participants are authenticated unless a handshake is pending.
+  pub fn is_authenticated(&self, guid_prefix: GuidPrefix) -> bool {
+    !self.unauthenticated_participants.contains(&guid_prefix)
+  }
+
+  // TypeObjects are stored under their hash-based TypeIdentifier.
+  pub fn add_type_object(&mut self, type_object: TypeObject) -> TypeIdentifier {
+    let type_id = type_object.type_identifier();
+    self.type_objects.insert(type_id, type_object);
+    type_id
+  }
+
+  pub fn find_type_object(&self, type_id: &TypeIdentifier) -> Option<&TypeObject> {
+    self.type_objects.get(type_id)
+  }
+
+  pub fn all_participants(&self) -> impl Iterator<Item = &SpdpDiscoveredParticipantData> {
+    self.participant_proxies.values()
   }
 
   pub fn find_participant_proxy(
@@ -156,6 +254,14 @@ impl DiscoveryDB {
     self.participant_proxies.get(&guid_prefix)
   }
 
+  pub fn find_external_reader(&self, guid: GUID) -> Option<&DiscoveredReaderData> {
+    self.external_topic_readers.get(&guid)
+  }
+
+  pub fn find_external_writer(&self, guid: GUID) -> Option<&DiscoveredWriterData> {
+    self.external_topic_writers.get(&guid)
+  }
+
   fn remove_topic_reader_with_prefix(&mut self, guid_prefix: GuidPrefix) {
     // TODO: Implement this using .drain_filter() in BTreeMap once it lands in
     // stable.
@@ -194,11 +300,9 @@ impl DiscoveryDB {
   // Delete participant proxies, if we have not heard of them within
   // lease_duration
   pub fn participant_cleanup(&mut self) -> Vec<GuidPrefix> {
-    let inow = Instant::now();
+    let inow = self.time_source.instant();
 
     let mut to_remove = Vec::new();
-    // TODO: We are not cleaning up liast_life_signs table, but that should not be a
-    // problem, except for a slight memory leak.
     for (&guid, sp) in &self.participant_proxies {
       let lease_duration = sp
         .lease_duration
@@ -227,6 +331,40 @@ impl DiscoveryDB {
     to_remove
   }
 
+  // When will the next participant lease expire, unless renewed.
+  // This is used to schedule the next participant_cleanup().
+  pub fn next_lease_expiry(&self) -> Option<Instant> {
+    self
+      .participant_proxies
+      .iter()
+      .filter_map(|(guid_prefix, sp)| {
+        let lease_duration = sp
+          .lease_duration
+          .unwrap_or(DEFAULT_PARTICIPANT_LEASE_DURATION)
+          + PARTICIPANT_LEASE_DURATION_TOLREANCE;
+        self
+          .participant_last_life_signs
+          .get(guid_prefix)
+          .and_then(|last_life| last_life.checked_add(lease_duration.to_std()))
+      })
+      .min()
+  }
+
+  // Restart all lease timers from now. This is used after a clock jump,
+  // where we cannot tell how long remote participants have really been silent.
+  // Expiring all of them at once would be worse than giving them a fresh lease.
+  pub fn restart_leases(&mut self) {
+    let now = self.time_source.instant();
+    self
+      .participant_last_life_signs
+      .values_mut()
+      .for_each(|last_life| *last_life = now);
+    self
+      .external_topic_writers
+      .values_mut()
+      .for_each(|p| p.last_updated = now);
+  }
+
   fn topic_has_writers_or_readers(&self, topic_name: &str) -> bool {
     // TODO: This entire function has silly implementation.
     // We should really have a separate map from Topic to Readers & Writers
@@ -289,6 +427,18 @@ impl DiscoveryDB {
     self.local_topic_writers.remove(&guid);
   }
 
+  // Returns the updated data, or None if there is no such local writer.
+  pub fn update_local_topic_writer_qos(
+    &mut self,
+    guid: GUID,
+    qos: &QosPolicies,
+  ) -> Option<DiscoveredWriterData> {
+    self.local_topic_writers.get_mut(&guid).map(|dwd| {
+      dwd.publication_topic_data.set_qos(qos);
+      dwd.clone()
+    })
+  }
+
   // TODO: This is silly. Returns one of the paramters cloned, or None
   // TODO: Why are we here checking if discovery db already has this? What about
   // reader proxies in writers?
@@ -468,6 +618,7 @@ impl DiscoveryDB {
       let mut b = BTreeMap::new();
       b.insert(updater.prefix, (discovered_via, dtd.clone()));
       self.topics.insert(topic_name, b);
+      notify = true;
     };
 
     if notify {
@@ -486,6 +637,7 @@ impl DiscoveryDB {
     domain_participant: &DomainParticipant,
     topic: &Topic,
     reader: &ReaderIngredients,
+    content_filter: Option<ContentFilterProperty>,
   ) {
     let reader_guid = reader.guid;
 
@@ -496,12 +648,9 @@ impl DiscoveryDB {
       Some(domain_participant.guid()),
       topic.name(),
       topic.get_type().name().to_string(),
-      &topic.qos(),
+      &reader.qos_policy,
     );
 
-    // TODO: possibly change content filter to dynamic value
-    let content_filter = None;
-
     let discovered_reader_data = DiscoveredReaderData {
       reader_proxy: ReaderProxy::from(reader_proxy),
       subscription_topic_data: subscription_data,
@@ -517,6 +666,18 @@ impl DiscoveryDB {
     self.local_topic_readers.remove(&guid);
   }
 
+  // Returns the updated data, or None if there is no such local reader.
+  pub fn update_local_topic_reader_qos(
+    &mut self,
+    guid: GUID,
+    qos: &QosPolicies,
+  ) -> Option<DiscoveredReaderData> {
+    self.local_topic_readers.get_mut(&guid).map(|drd| {
+      drd.subscription_topic_data.set_qos(qos);
+      drd.clone()
+    })
+  }
+
   pub fn get_all_local_topic_readers(&self) -> impl Iterator<Item = &DiscoveredReaderData> {
     self.local_topic_readers.iter().map(|(_, p)| p)
   }
@@ -525,6 +686,17 @@ impl DiscoveryDB {
     self.local_topic_writers.iter().map(|(_, p)| p)
   }
 
+  // Our locators have changed, because network interfaces came or went.
+  pub fn update_local_locators(&mut self, unicast: &[Locator], multicast: &[Locator]) {
+    for drd in self.local_topic_readers.values_mut() {
+      drd.reader_proxy.unicast_locator_list = unicast.to_vec();
+      drd.reader_proxy.multicast_locator_list = multicast.to_vec();
+    }
+    for dwd in self.local_topic_writers.values_mut() {
+      dwd.writer_proxy.unicast_locator_list = unicast.to_vec();
+    }
+  }
+
   // Note:
   // If multiple participants announce the same topic, this will
   // return duplicates, one per announcing particiapnt.
@@ -538,6 +710,7 @@ impl DiscoveryDB {
   }
 
   // as above, but only from my GUID
+  #[cfg(feature = "discovery")]
   pub fn local_user_topics(&self) -> impl Iterator<Item = &DiscoveredTopicData> {
     let me = self.my_guid.prefix;
     self
@@ -563,6 +736,7 @@ impl DiscoveryDB {
       .and_then(|m| m.values().next().map(|t| &t.1))
   }
 
+  #[cfg(feature = "discovery")]
   pub fn writers_on_topic_and_participant(
     &self,
     topic_name: &str,
@@ -582,6 +756,7 @@ impl DiscoveryDB {
       .collect()
   }
 
+  #[cfg(feature = "discovery")]
   pub fn readers_on_topic_and_participant(
     &self,
     topic_name: &str,
@@ -596,6 +771,58 @@ impl DiscoveryDB {
       .collect()
   }
 
+  // Remote readers on a topic, with default locators filled in from the
+  // participant, like update_subscription() does.
+  pub fn external_readers_on_topic(&self, topic_name: &str) -> Vec<DiscoveredReaderData> {
+    self
+      .external_topic_readers
+      .values()
+      .filter(|drd| drd.subscription_topic_data.topic_name() == topic_name)
+      .map(|drd| {
+        let (unicast, multicast) =
+          self.participant_default_locators(drd.reader_proxy.remote_reader_guid.prefix);
+        DiscoveredReaderData {
+          reader_proxy: ReaderProxy::from(RtpsReaderProxy::from_discovered_reader_data(
+            drd, &unicast, &multicast,
+          )),
+          ..drd.clone()
+        }
+      })
+      .collect()
+  }
+
+  // Remote writers on a topic, with default locators filled in from the
+  // participant, like update_publication() does.
+  pub fn external_writers_on_topic(&self, topic_name: &str) -> Vec<DiscoveredWriterData> {
+    self
+      .external_topic_writers
+      .values()
+      .filter(|dwd| dwd.publication_topic_data.topic_name == topic_name)
+      .map(|dwd| {
+        let (unicast, multicast) =
+          self.participant_default_locators(dwd.writer_proxy.remote_writer_guid.prefix);
+        DiscoveredWriterData {
+          writer_proxy: WriterProxy::from(RtpsWriterProxy::from_discovered_writer_data(
+            dwd, &unicast, &multicast,
+          )),
+          ..dwd.clone()
+        }
+      })
+      .collect()
+  }
+
+  fn participant_default_locators(&self, guid_prefix: GuidPrefix) -> (Vec<Locator>, Vec<Locator>) {
+    self
+      .find_participant_proxy(guid_prefix)
+      .map(|pp| {
+        (
+          pp.default_unicast_locators.clone(),
+          pp.default_multicast_locators.clone(),
+        )
+      })
+      .unwrap_or_default()
+  }
+
   // // TODO: return iterator somehow?
   #[cfg(test)] // used only for testing
   pub fn get_local_topic_readers<'a, T: TopicDescription>(
@@ -612,7 +839,7 @@ impl DiscoveryDB {
   }
 
   pub fn update_lease_duration(&mut self, data: &ParticipantMessageData) {
-    let now = Instant::now();
+    let now = self.time_source.instant();
     let prefix = data.guid;
     self
       .external_topic_writers
@@ -635,10 +862,10 @@ mod tests {
   use super::*;
   use crate::{
     dds::{
-      qos::QosPolicies, reader::Reader, statusevents::DataReaderStatus, topic::TopicKind,
-      with_key::datareader::ReaderCommand,
+      helpers::WakerSlot, qos::QosPolicies, reader::Reader, simulation::SimulatedClock,
+      statusevents::DataReaderStatus, topic::TopicKind, with_key::datareader::ReaderCommand,
     },
-    network::udp_sender::UDPSender,
+    network::{transport::Transports, udp_sender::UDPSender},
     serialization::cdr_serializer::CDRSerializerAdapter,
     structure::{dds_cache::DDSCache, guid::*},
     test::{
@@ -652,7 +879,11 @@ mod tests {
     let (discovery_db_event_sender, _discovery_db_event_receiver) =
       mio_channel::sync_channel::<()>(4);
 
-    let mut discoverydb = DiscoveryDB::new(GUID::new_participant_guid(), discovery_db_event_sender);
+    let mut discoverydb = DiscoveryDB::new(
+      GUID::new_participant_guid(),
+      discovery_db_event_sender,
+      TimeSource::System,
+    );
     let mut data = spdp_participant_data().unwrap();
     data.lease_duration = Some(Duration::from(StdDuration::from_secs(1)));
 
@@ -669,11 +900,141 @@ mod tests {
     // TODO: more operations tests
   }
 
+  #[test]
+  fn discdb_participant_lease_expiry_purges_endpoints() {
+    let (discovery_db_event_sender, _discovery_db_event_receiver) =
+      mio_channel::sync_channel::<()>(4);
+
+    let mut discoverydb = DiscoveryDB::new(
+      GUID::new_participant_guid(),
+      discovery_db_event_sender,
+      TimeSource::System,
+    );
+    let mut data = spdp_participant_data().unwrap();
+    data.lease_duration = Some(Duration::from(StdDuration::from_millis(100)));
+    let prefix = data.participant_guid.prefix;
+    assert!(discoverydb.next_lease_expiry().is_none());
+    discoverydb.update_participant(&data);
+
+    let mut drd = DiscoveredReaderData::default("lease_topic".to_string(), "Foo".to_string());
+    drd.reader_proxy.remote_reader_guid = GUID::new_with_prefix_and_id(
+      prefix,
+      EntityId::create_custom_entity_id([1, 2, 3], EntityKind::READER_WITH_KEY_USER_DEFINED),
+    );
+    discoverydb.update_subscription(&drd);
+    assert_eq!(
+      discoverydb.external_readers_on_topic("lease_topic").len(),
+      1
+    );
+    assert!(discoverydb.get_topic("lease_topic").is_some());
+
+    let expiry = discoverydb.next_lease_expiry().unwrap();
+    assert!(expiry <= Instant::now() + StdDuration::from_millis(100));
+
+    // not expired yet
+    assert!(discoverydb.participant_cleanup().is_empty());
+
+    std::thread::sleep(StdDuration::from_millis(200));
+    assert_eq!(discoverydb.participant_cleanup(), vec![prefix]);
+    assert!(discoverydb.find_participant_proxy(prefix).is_none());
+    assert!(discoverydb
+      .external_readers_on_topic("lease_topic")
+      .is_empty());
+    assert!(discoverydb.get_topic("lease_topic").is_none());
+    assert!(discoverydb.next_lease_expiry().is_none());
+  }
+
+  #[test]
+  fn discdb_participant_lease_on_simulated_clock() {
+    let (discovery_db_event_sender, _discovery_db_event_receiver) =
+      mio_channel::sync_channel::<()>(4);
+    let clock = SimulatedClock::default();
+    let mut discoverydb = DiscoveryDB::new(
+      GUID::new_participant_guid(),
+      discovery_db_event_sender,
+      TimeSource::Simulated(clock.clone()),
+    );
+    let mut data = spdp_participant_data().unwrap();
+    data.lease_duration = Some(Duration::from(StdDuration::from_secs(30)));
+    let prefix = data.participant_guid.prefix;
+    discoverydb.update_participant(&data);
+    assert_eq!(
+      discoverydb.next_lease_expiry(),
+      Some(clock.instant() + StdDuration::from_secs(30))
+    );
+
+    // The lease runs on the clock, not in real time.
+    clock.advance(StdDuration::from_secs(29));
+    assert!(discoverydb.participant_cleanup().is_empty());
+    discoverydb.participant_is_alive(prefix);
+    clock.advance(StdDuration::from_secs(29));
+    assert!(discoverydb.participant_cleanup().is_empty());
+    clock.advance(StdDuration::from_secs(2));
+    assert_eq!(discoverydb.participant_cleanup(), vec![prefix]);
+  }
+
+  #[test]
+  fn discdb_ignore() {
+    let (discovery_db_event_sender, _discovery_db_event_receiver) =
+      mio_channel::sync_channel::<()>(4);
+
+    let mut discoverydb = DiscoveryDB::new(
+      GUID::new_participant_guid(),
+      discovery_db_event_sender,
+      TimeSource::System,
+    );
+    let data = spdp_participant_data().unwrap();
+    let prefix = data.participant_guid.prefix;
+    discoverydb.update_participant(&data);
+
+    let reader_guid = |prefix, n| {
+      GUID::new_with_prefix_and_id(
+        prefix,
+        EntityId::create_custom_entity_id([1, 2, n], EntityKind::READER_WITH_KEY_USER_DEFINED),
+      )
+    };
+    let other_prefix = GuidPrefix::new(b"other");
+    for guid in [
+      reader_guid(prefix, 1),
+      reader_guid(other_prefix, 1),
+      reader_guid(other_prefix, 2),
+    ] {
+      let mut drd = DiscoveredReaderData::default("ignore_topic".to_string(), "Foo".to_string());
+      drd.reader_proxy.remote_reader_guid = guid;
+      discoverydb.update_subscription(&drd);
+    }
+    assert_eq!(
+      discoverydb.external_readers_on_topic("ignore_topic").len(),
+      3
+    );
+
+    discoverydb.ignore_endpoint(reader_guid(other_prefix, 1));
+    assert!(discoverydb.is_ignored(reader_guid(other_prefix, 1)));
+    assert!(!discoverydb.is_ignored(reader_guid(other_prefix, 2)));
+    assert_eq!(
+      discoverydb.external_readers_on_topic("ignore_topic").len(),
+      2
+    );
+
+    discoverydb.ignore_participant(prefix);
+    assert!(discoverydb.is_ignored(data.participant_guid));
+    assert!(discoverydb.is_ignored(reader_guid(prefix, 1)));
+    assert!(discoverydb.find_participant_proxy(prefix).is_none());
+    assert_eq!(
+      discoverydb.external_readers_on_topic("ignore_topic").len(),
+      1
+    );
+  }
+
   #[test]
   fn discdb_writer_proxies() {
     let (discovery_db_event_sender, _discovery_db_event_receiver) =
       mio_channel::sync_channel::<()>(4);
-    let _discoverydb = DiscoveryDB::new(GUID::new_participant_guid(), discovery_db_event_sender);
+    let _discoverydb = DiscoveryDB::new(
+      GUID::new_participant_guid(),
+      discovery_db_event_sender,
+      TimeSource::System,
+    );
     let topic_name = String::from("some_topic");
     let type_name = String::from("RandomData");
     let _dreader = DiscoveredReaderData::default(topic_name, type_name);
@@ -686,8 +1047,11 @@ mod tests {
     let (discovery_db_event_sender, _discovery_db_event_receiver) =
       mio_channel::sync_channel::<()>(4);
 
-    let mut discovery_db =
-      DiscoveryDB::new(GUID::new_participant_guid(), discovery_db_event_sender);
+    let mut discovery_db = DiscoveryDB::new(
+      GUID::new_participant_guid(),
+      discovery_db_event_sender,
+      TimeSource::System,
+    );
 
     let domain_participant = DomainParticipant::new(0).expect("Failed to create publisher");
     let topic = domain_participant
@@ -774,13 +1138,17 @@ mod tests {
     let dp = DomainParticipant::new(0).expect("Failed to create participant");
     let topic = dp
       .create_topic(
-        "some topic name".to_string(),
+        "some_topic_name".to_string(),
         "Wazzup".to_string(),
         &QosPolicies::qos_none(),
         TopicKind::WithKey,
       )
       .unwrap();
-    let mut discoverydb = DiscoveryDB::new(GUID::new_participant_guid(), discovery_db_event_sender);
+    let mut discoverydb = DiscoveryDB::new(
+      GUID::new_participant_guid(),
+      discovery_db_event_sender,
+      TimeSource::System,
+    );
 
     let (notification_sender, _notification_receiver) = mio_extras::channel::sync_channel(100);
     let (status_sender, _status_receiver) =
@@ -793,25 +1161,26 @@ mod tests {
     let reader_ing = ReaderIngredients {
       guid: GUID::dummy_test_guid(EntityKind::READER_NO_KEY_USER_DEFINED),
       notification_sender: notification_sender.clone(),
+      data_reader_waker: WakerSlot::default(),
       status_sender: status_sender.clone(),
       topic_name: topic.name(),
       qos_policy: QosPolicies::qos_none(),
       data_reader_command_receiver: reader_command_receiver1,
     };
 
-    discoverydb.update_local_topic_reader(&dp, &topic, &reader_ing);
+    discoverydb.update_local_topic_reader(&dp, &topic, &reader_ing, None);
     assert_eq!(discoverydb.local_topic_readers.len(), 1);
     assert_eq!(discoverydb.get_local_topic_readers(&topic).len(), 1);
 
-    discoverydb.update_local_topic_reader(&dp, &topic, &reader_ing);
+    discoverydb.update_local_topic_reader(&dp, &topic, &reader_ing, None);
     assert_eq!(discoverydb.local_topic_readers.len(), 1);
     assert_eq!(discoverydb.get_local_topic_readers(&topic).len(), 1);
 
     let _reader = Reader::new(
       reader_ing,
       Arc::new(RwLock::new(DDSCache::new())),
-      Rc::new(UDPSender::new(0).unwrap()),
-      mio_extras::timer::Builder::default().build(),
+      Rc::new(Transports::from(UDPSender::new(0).unwrap())),
+      crate::dds::timer::Timer::default(),
     );
 
     let reader_ing = ReaderIngredients {
@@ -823,21 +1192,22 @@ mod tests {
         },
       ), // GUID needs to be different in order to be added
       notification_sender,
+      data_reader_waker: WakerSlot::default(),
       status_sender,
       topic_name: topic.name(),
       qos_policy: QosPolicies::qos_none(),
       data_reader_command_receiver: reader_command_receiver2,
     };
 
-    discoverydb.update_local_topic_reader(&dp, &topic, &reader_ing);
+    discoverydb.update_local_topic_reader(&dp, &topic, &reader_ing, None);
     assert_eq!(discoverydb.get_local_topic_readers(&topic).len(), 2);
     assert_eq!(discoverydb.get_all_local_topic_readers().count(), 2);
 
     let _reader = Reader::new(
       reader_ing,
       Arc::new(RwLock::new(DDSCache::new())),
-      Rc::new(UDPSender::new(0).unwrap()),
-      mio_extras::timer::Builder::default().build(),
+      Rc::new(Transports::from(UDPSender::new(0).unwrap())),
+      crate::dds::timer::Timer::default(),
     );
   }
 }
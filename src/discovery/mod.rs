@@ -1,5 +1,8 @@
+pub(crate) mod authentication;
 pub(crate) mod content_filter_property;
 pub(crate) mod data_types;
 #[allow(clippy::module_inception)]
 pub(crate) mod discovery;
 pub(crate) mod discovery_db;
+pub(crate) mod static_endpoint;
+pub(crate) mod type_lookup;
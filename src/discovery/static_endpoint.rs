@@ -0,0 +1,203 @@
+use std::net::SocketAddr;
+
+use crate::{
+  dds::{
+    qos::QosPolicies,
+    values::result::{Error, Result},
+  },
+  discovery::data_types::topic_data::{
+    DiscoveredReaderData, DiscoveredWriterData, PublicationBuiltinTopicData, ReaderProxy,
+    SubscriptionBuiltinTopicData, WriterProxy,
+  },
+  structure::{
+    guid::{EntityId, GUID},
+    locator::Locator,
+  },
+};
+
+/// A remote DataWriter or DataReader declared in advance, instead of being
+/// discovered via SEDP.
+///
+/// Whether this is a writer or a reader is determined by the entity kind in
+/// `guid`. Used with
+/// [`DomainParticipantBuilder::static_endpoint`](crate::DomainParticipantBuilder::static_endpoint)
+/// and [`DomainParticipant::add_static_endpoint`](crate::DomainParticipant::add_static_endpoint).
+///
+/// # Example
+///
+/// ```
+/// use std::net::SocketAddr;
+/// # use rustdds::{EntityId, EntityKind, GuidPrefix, QosPolicyBuilder, StaticEndpoint, GUID};
+///
+/// let remote_writer = StaticEndpoint::new(
+///   GUID::new(
+///     GuidPrefix::new(b"remoteWriter"),
+///     EntityId::new([0, 0, 1], EntityKind::WRITER_WITH_KEY_USER_DEFINED),
+///   ),
+///   "Square".to_string(),
+///   "ShapeType".to_string(),
+///   QosPolicyBuilder::new().build(),
+///   vec!["192.168.1.10:7411".parse::<SocketAddr>().unwrap()],
+/// );
+/// assert!(remote_writer.is_writer());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaticEndpoint {
+  pub guid: GUID,
+  pub topic_name: String,
+  pub type_name: String,
+  pub qos: QosPolicies,
+  /// Where the remote endpoint receives unicast RTPS traffic.
+  pub unicast_locators: Vec<SocketAddr>,
+  pub multicast_locators: Vec<SocketAddr>,
+}
+
+impl StaticEndpoint {
+  pub fn new(
+    guid: GUID,
+    topic_name: String,
+    type_name: String,
+    qos: QosPolicies,
+    unicast_locators: Vec<SocketAddr>,
+  ) -> Self {
+    Self {
+      guid,
+      topic_name,
+      type_name,
+      qos,
+      unicast_locators,
+      multicast_locators: Vec::new(),
+    }
+  }
+
+  pub fn is_writer(&self) -> bool {
+    self.guid.entity_id.entity_kind.is_writer()
+  }
+
+  pub fn is_reader(&self) -> bool {
+    self.guid.entity_id.entity_kind.is_reader()
+  }
+
+  pub(crate) fn validate(&self) -> Result<()> {
+    let kind = self.guid.entity_id.entity_kind;
+    if !kind.is_user_defined() || !(kind.is_writer() || kind.is_reader()) {
+      return Error::bad_parameter(format!(
+        "Static endpoint GUID must be a user-defined reader or writer: {:?}",
+        self.guid
+      ));
+    }
+    if self.unicast_locators.is_empty() && self.multicast_locators.is_empty() {
+      return Error::bad_parameter(format!("Static endpoint {:?} has no locators", self.guid));
+    }
+    Ok(())
+  }
+
+  fn locators(addresses: &[SocketAddr]) -> Vec<Locator> {
+    addresses.iter().map(|a| Locator::from(*a)).collect()
+  }
+
+  // Same data as would have been received via SEDP.
+  pub(crate) fn discovered_writer_data(&self) -> Option<DiscoveredWriterData> {
+    if !self.is_writer() {
+      return None;
+    }
+    let mut publication_topic_data = PublicationBuiltinTopicData::new(
+      self.guid,
+      GUID::new(self.guid.prefix, EntityId::PARTICIPANT),
+      self.topic_name.clone(),
+      self.type_name.clone(),
+    );
+    publication_topic_data.set_qos(&self.qos);
+    Some(DiscoveredWriterData {
+      last_updated: std::time::Instant::now(),
+      writer_proxy: WriterProxy::new(
+        self.guid,
+        Self::locators(&self.multicast_locators),
+        Self::locators(&self.unicast_locators),
+      ),
+      publication_topic_data,
+    })
+  }
+
+  pub(crate) fn discovered_reader_data(&self) -> Option<DiscoveredReaderData> {
+    if !self.is_reader() {
+      return None;
+    }
+    Some(DiscoveredReaderData {
+      reader_proxy: ReaderProxy::new(
+        self.guid,
+        Self::locators(&self.unicast_locators),
+        Self::locators(&self.multicast_locators),
+      ),
+      subscription_topic_data: SubscriptionBuiltinTopicData::new(
+        self.guid,
+        Some(GUID::new(self.guid.prefix, EntityId::PARTICIPANT)),
+        self.topic_name.clone(),
+        self.type_name.clone(),
+        &self.qos,
+      ),
+      content_filter: None,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    dds::qos::{policy::Reliability, QosPolicyBuilder},
+    structure::{duration::Duration, guid::EntityKind},
+  };
+
+  #[test]
+  fn static_endpoint_conversion() {
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      })
+      .build();
+    let addr: SocketAddr = "10.0.0.1:7411".parse().unwrap();
+
+    let writer = StaticEndpoint::new(
+      GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED),
+      "topic".to_string(),
+      "Type".to_string(),
+      qos.clone(),
+      vec![addr],
+    );
+    assert!(writer.validate().is_ok());
+    assert!(writer.discovered_reader_data().is_none());
+    let dwd = writer.discovered_writer_data().unwrap();
+    assert_eq!(dwd.writer_proxy.remote_writer_guid, writer.guid);
+    assert_eq!(
+      dwd.writer_proxy.unicast_locator_list,
+      vec![Locator::from(addr)]
+    );
+    assert!(dwd.writer_proxy.multicast_locator_list.is_empty());
+    assert_eq!(dwd.publication_topic_data.topic_name, "topic");
+    assert_eq!(dwd.publication_topic_data.reliability, qos.reliability);
+
+    let reader = StaticEndpoint::new(
+      GUID::dummy_test_guid(EntityKind::READER_NO_KEY_USER_DEFINED),
+      "topic".to_string(),
+      "Type".to_string(),
+      qos,
+      vec![addr],
+    );
+    assert!(reader.discovered_writer_data().is_none());
+    let drd = reader.discovered_reader_data().unwrap();
+    assert_eq!(
+      drd.reader_proxy.unicast_locator_list,
+      vec![Locator::from(addr)]
+    );
+    assert_eq!(drd.subscription_topic_data.type_name(), "Type");
+
+    // Builtin endpoints and participants cannot be declared.
+    let mut builtin = reader.clone();
+    builtin.guid = GUID::dummy_test_guid(EntityKind::READER_WITH_KEY_BUILT_IN);
+    assert!(builtin.validate().is_err());
+    let mut no_locators = reader;
+    no_locators.unicast_locators.clear();
+    assert!(no_locators.validate().is_err());
+  }
+}
@@ -0,0 +1,733 @@
+// XTypes TypeLookup service, see DDS-XTypes 1.3 Section 7.6.3.3 and Annex C.
+//
+// Only the getTypes operation is implemented. TypeObjects are not interpreted,
+// they are carried as their serialized form, which is also what their
+// TypeIdentifier hash is computed from.
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+use byteorder::{ByteOrder, LittleEndian};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+  dds::{
+    traits::serde_adapters::no_key,
+    values::result::{Error as DdsError, Result as DdsResult},
+  },
+  messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
+  serialization::error::{Error, Result},
+  structure::{
+    guid::GUID,
+    rpc::{RemoteExceptionCode, ReplyHeader, RequestHeader, SampleIdentity},
+    sequence_number::SequenceNumber,
+  },
+};
+
+/// First 14 bytes of the MD5 hash of a serialized [`TypeObject`].
+pub type EquivalenceHash = [u8; 14];
+
+/// XTypes TypeIdentifier
+///
+/// Identifies a type when requesting type information from remote
+/// participants. Only primitive types and hashed TypeObjects are supported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum TypeIdentifier {
+  /// Primitive type, identified by its XTypes TypeKind, e.g. `0x04` for
+  /// `int32`.
+  Primitive(u8),
+  /// Hash of a minimal TypeObject
+  Minimal(EquivalenceHash),
+  /// Hash of a complete TypeObject
+  Complete(EquivalenceHash),
+}
+
+impl TypeIdentifier {
+  const EK_MINIMAL: u8 = 0xF1;
+  const EK_COMPLETE: u8 = 0xF2;
+
+  // TK_BOOLEAN ..= TK_UINT8, TK_CHAR8 and TK_CHAR16
+  fn is_primitive_kind(kind: u8) -> bool {
+    matches!(kind, 0x01..=0x0D | 0x10 | 0x11)
+  }
+}
+
+/// XTypes TypeObject, in serialized form
+///
+/// RustDDS does not interpret TypeObjects. They are stored and exchanged as
+/// the XCDR2 little-endian serialization of the `TypeObject` union, as
+/// produced e.g. by an IDL compiler of another DDS implementation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeObject {
+  serialized: Vec<u8>,
+}
+
+impl TypeObject {
+  /// Wraps a serialized TypeObject. The bytes must start with the DHEADER
+  /// followed by the `EK_MINIMAL` or `EK_COMPLETE` discriminator.
+  pub fn from_xcdr2_bytes(serialized: Vec<u8>) -> DdsResult<Self> {
+    if serialized.len() < 5 {
+      return Err(DdsError::BadParameter {
+        reason: "TypeObject is too short".to_string(),
+      });
+    }
+    let dheader = LittleEndian::read_u32(&serialized[0..4]) as usize;
+    if dheader != serialized.len() - 4 {
+      return Err(DdsError::BadParameter {
+        reason: format!(
+          "TypeObject DHEADER is {} but {} bytes follow",
+          dheader,
+          serialized.len() - 4
+        ),
+      });
+    }
+    match serialized[4] {
+      TypeIdentifier::EK_MINIMAL | TypeIdentifier::EK_COMPLETE => Ok(Self { serialized }),
+      other => Err(DdsError::BadParameter {
+        reason: format!("Unknown TypeObject kind {:#04x}", other),
+      }),
+    }
+  }
+
+  pub fn as_bytes(&self) -> &[u8] {
+    &self.serialized
+  }
+
+  /// Is this a minimal, as opposed to complete, TypeObject?
+  pub fn is_minimal(&self) -> bool {
+    self.serialized[4] == TypeIdentifier::EK_MINIMAL
+  }
+
+  /// The hash-based TypeIdentifier of this TypeObject.
+  pub fn type_identifier(&self) -> TypeIdentifier {
+    let digest = md5::compute(&self.serialized);
+    let mut hash = EquivalenceHash::default();
+    hash.copy_from_slice(&digest[..14]);
+    if self.is_minimal() {
+      TypeIdentifier::Minimal(hash)
+    } else {
+      TypeIdentifier::Complete(hash)
+    }
+  }
+}
+
+// Operation hashes from XTypes 1.3 Annex C
+const GET_TYPES_HASH_ID: i32 = 0x0182_52d3;
+// Member ids in the TypeLookup_Call and TypeLookup_Return unions. The
+// discriminator is member 0.
+const GET_TYPES_MEMBER_ID: u32 = 1;
+const DDS_RETCODE_OK: i32 = 0;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum TypeLookupCall {
+  GetTypes { type_ids: Vec<TypeIdentifier> },
+  // getTypeDependencies or something else we do not implement
+  Unsupported { operation_id: i32 },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct TypeLookupRequest {
+  pub header: RequestHeader,
+  pub call: TypeLookupCall,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum TypeLookupReturn {
+  GetTypes {
+    types: Vec<(TypeIdentifier, TypeObject)>,
+  },
+  Unsupported {
+    operation_id: i32,
+  },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct TypeLookupReply {
+  pub header: ReplyHeader,
+  pub return_data: TypeLookupReturn,
+}
+
+// TypeLookup messages are encoded by hand, because the CDR serializer does not
+// know about XCDR2 headers.
+pub(crate) trait Xcdr2Message: Sized {
+  fn to_xcdr2_bytes(&self) -> Vec<u8>;
+  fn from_xcdr2_bytes(input: &[u8]) -> Result<Self>;
+}
+
+impl Xcdr2Message for TypeLookupRequest {
+  fn to_xcdr2_bytes(&self) -> Vec<u8> {
+    let mut w = Xcdr2Writer::new();
+    w.write_sample_identity(&self.header.request_id);
+    w.write_string(&self.header.instance_name);
+
+    // TypeLookup_Call is a mutable union
+    let call = w.begin_length();
+    match &self.call {
+      TypeLookupCall::GetTypes { type_ids } => {
+        w.write_discriminator(GET_TYPES_HASH_ID);
+        let member = w.begin_member(GET_TYPES_MEMBER_ID);
+        // TypeLookup_getTypes_In is a mutable struct
+        let input = w.begin_length();
+        let type_ids_member = w.begin_member(0);
+        let sequence = w.begin_length();
+        w.write_u32(type_ids.len() as u32);
+        for type_id in type_ids {
+          w.write_type_identifier(type_id);
+        }
+        w.end_length(sequence);
+        w.end_length(type_ids_member);
+        w.end_length(input);
+        w.end_length(member);
+      }
+      TypeLookupCall::Unsupported { operation_id } => w.write_discriminator(*operation_id),
+    }
+    w.end_length(call);
+    w.buffer
+  }
+
+  fn from_xcdr2_bytes(input: &[u8]) -> Result<Self> {
+    let mut r = Xcdr2Reader::new(input);
+    let request_id = r.read_sample_identity()?;
+    let instance_name = r.read_string()?;
+
+    let mut operation_id = None;
+    let mut type_ids = Vec::new();
+    let call_end = r.read_dheader()?;
+    while r.pos < call_end {
+      let (member_id, member_end) = r.read_member_header()?;
+      match member_id {
+        0 => operation_id = Some(r.read_i32()?),
+        GET_TYPES_MEMBER_ID => {
+          let input_end = r.read_dheader()?;
+          while r.pos < input_end {
+            let (member_id, member_end) = r.read_member_header()?;
+            if member_id == 0 {
+              type_ids = r.read_type_identifiers()?;
+            }
+            r.pos = member_end;
+          }
+        }
+        _ => (), // unknown member, skip
+      }
+      r.pos = member_end;
+    }
+
+    let call = match operation_id {
+      Some(GET_TYPES_HASH_ID) => TypeLookupCall::GetTypes { type_ids },
+      Some(operation_id) => TypeLookupCall::Unsupported { operation_id },
+      None => {
+        return Err(Error::Message(
+          "TypeLookup_Call has no discriminator".to_string(),
+        ))
+      }
+    };
+    Ok(Self {
+      header: RequestHeader {
+        request_id,
+        instance_name,
+      },
+      call,
+    })
+  }
+}
+
+impl Xcdr2Message for TypeLookupReply {
+  fn to_xcdr2_bytes(&self) -> Vec<u8> {
+    let mut w = Xcdr2Writer::new();
+    w.write_sample_identity(&self.header.related_request_id);
+    w.write_u32(self.header.remote_ex as u32);
+
+    // TypeLookup_Return is a mutable union
+    let ret = w.begin_length();
+    match &self.return_data {
+      TypeLookupReturn::GetTypes { types } => {
+        w.write_discriminator(GET_TYPES_HASH_ID);
+        let member = w.begin_member(GET_TYPES_MEMBER_ID);
+        // TypeLookup_getTypes_Result is an appendable union
+        let result = w.begin_length();
+        w.write_i32(DDS_RETCODE_OK);
+        // TypeLookup_getTypes_Out is a mutable struct
+        let output = w.begin_length();
+
+        let types_member = w.begin_member(0);
+        let sequence = w.begin_length();
+        w.write_u32(types.len() as u32);
+        for (type_id, type_object) in types {
+          w.write_type_identifier(type_id);
+          // TypeObject starts with its DHEADER, so it is 4-aligned
+          w.align(4);
+          w.write_bytes(type_object.as_bytes());
+        }
+        w.end_length(sequence);
+        w.end_length(types_member);
+
+        // complete_to_minimal is always empty
+        let complete_to_minimal_member = w.begin_member(1);
+        let sequence = w.begin_length();
+        w.write_u32(0);
+        w.end_length(sequence);
+        w.end_length(complete_to_minimal_member);
+
+        w.end_length(output);
+        w.end_length(result);
+        w.end_length(member);
+      }
+      TypeLookupReturn::Unsupported { operation_id } => w.write_discriminator(*operation_id),
+    }
+    w.end_length(ret);
+    w.buffer
+  }
+
+  fn from_xcdr2_bytes(input: &[u8]) -> Result<Self> {
+    let mut r = Xcdr2Reader::new(input);
+    let related_request_id = r.read_sample_identity()?;
+    let remote_ex = match r.read_u32()? {
+      0 => RemoteExceptionCode::Ok,
+      1 => RemoteExceptionCode::Unsupported,
+      2 => RemoteExceptionCode::InvalidArgument,
+      3 => RemoteExceptionCode::OutOfResources,
+      4 => RemoteExceptionCode::UnknownOperation,
+      _ => RemoteExceptionCode::UnknownException,
+    };
+
+    let mut operation_id = None;
+    let mut types = Vec::new();
+    let ret_end = r.read_dheader()?;
+    while r.pos < ret_end {
+      let (member_id, member_end) = r.read_member_header()?;
+      match member_id {
+        0 => operation_id = Some(r.read_i32()?),
+        GET_TYPES_MEMBER_ID => {
+          let result_end = r.read_dheader()?;
+          // Other return codes carry no TypeObjects.
+          if r.read_i32()? == DDS_RETCODE_OK {
+            let output_end = r.read_dheader()?;
+            while r.pos < output_end {
+              let (member_id, member_end) = r.read_member_header()?;
+              if member_id == 0 {
+                types = r.read_type_object_pairs()?;
+              }
+              r.pos = member_end;
+            }
+          }
+          r.pos = result_end;
+        }
+        _ => (), // unknown member, skip
+      }
+      r.pos = member_end;
+    }
+
+    let return_data = match operation_id {
+      Some(GET_TYPES_HASH_ID) => TypeLookupReturn::GetTypes { types },
+      Some(operation_id) => TypeLookupReturn::Unsupported { operation_id },
+      None => {
+        return Err(Error::Message(
+          "TypeLookup_Return has no discriminator".to_string(),
+        ))
+      }
+    };
+    Ok(Self {
+      header: ReplyHeader {
+        related_request_id,
+        remote_ex,
+      },
+      return_data,
+    })
+  }
+}
+
+// XCDR2 little-endian encoder. Maximum alignment in XCDR2 is 4.
+struct Xcdr2Writer {
+  buffer: Vec<u8>,
+}
+
+impl Xcdr2Writer {
+  fn new() -> Self {
+    Self { buffer: Vec::new() }
+  }
+
+  fn align(&mut self, alignment: usize) {
+    while self.buffer.len() % alignment != 0 {
+      self.buffer.push(0);
+    }
+  }
+
+  fn write_bytes(&mut self, bytes: &[u8]) {
+    self.buffer.extend_from_slice(bytes);
+  }
+
+  fn write_u32(&mut self, value: u32) {
+    self.align(4);
+    let mut bytes = [0; 4];
+    LittleEndian::write_u32(&mut bytes, value);
+    self.write_bytes(&bytes);
+  }
+
+  fn write_i32(&mut self, value: i32) {
+    self.write_u32(value as u32);
+  }
+
+  fn write_string(&mut self, s: &str) {
+    self.write_u32(s.len() as u32 + 1);
+    self.write_bytes(s.as_bytes());
+    self.buffer.push(0);
+  }
+
+  fn write_sample_identity(&mut self, sample_identity: &SampleIdentity) {
+    self.write_bytes(&sample_identity.writer_guid.to_bytes());
+    let sn = i64::from(sample_identity.sequence_number);
+    self.write_i32((sn >> 32) as i32);
+    self.write_u32(sn as u32);
+  }
+
+  // TypeIdentifier is a final union
+  fn write_type_identifier(&mut self, type_id: &TypeIdentifier) {
+    match type_id {
+      TypeIdentifier::Primitive(kind) => self.buffer.push(*kind),
+      TypeIdentifier::Minimal(hash) => {
+        self.buffer.push(TypeIdentifier::EK_MINIMAL);
+        self.write_bytes(hash);
+      }
+      TypeIdentifier::Complete(hash) => {
+        self.buffer.push(TypeIdentifier::EK_COMPLETE);
+        self.write_bytes(hash);
+      }
+    }
+  }
+
+  // Reserves space for a DHEADER or NEXTINT. The length is filled in by
+  // end_length().
+  fn begin_length(&mut self) -> usize {
+    self.align(4);
+    let position = self.buffer.len();
+    self.write_u32(0);
+    position
+  }
+
+  fn end_length(&mut self, position: usize) {
+    let length = self.buffer.len() - position - 4;
+    LittleEndian::write_u32(&mut self.buffer[position..position + 4], length as u32);
+  }
+
+  // EMHEADER with length code 4, i.e. member length is given in NEXTINT.
+  fn begin_member(&mut self, member_id: u32) -> usize {
+    self.write_u32((4 << 28) | member_id);
+    self.begin_length()
+  }
+
+  // Discriminator of a mutable union is a must-understand member 0.
+  fn write_discriminator(&mut self, discriminator: i32) {
+    self.write_u32(0x8000_0000 | (2 << 28));
+    self.write_i32(discriminator);
+  }
+}
+
+// XCDR2 little-endian decoder
+struct Xcdr2Reader<'a> {
+  input: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Xcdr2Reader<'a> {
+  fn new(input: &'a [u8]) -> Self {
+    Self { input, pos: 0 }
+  }
+
+  fn align(&mut self, alignment: usize) {
+    self.pos = (self.pos + alignment - 1) / alignment * alignment;
+  }
+
+  fn read_bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+    let end = self.pos + count;
+    if end > self.input.len() {
+      return Err(Error::Eof);
+    }
+    let bytes = &self.input[self.pos..end];
+    self.pos = end;
+    Ok(bytes)
+  }
+
+  fn read_u8(&mut self) -> Result<u8> {
+    Ok(self.read_bytes(1)?[0])
+  }
+
+  fn read_u32(&mut self) -> Result<u32> {
+    self.align(4);
+    Ok(LittleEndian::read_u32(self.read_bytes(4)?))
+  }
+
+  fn read_i32(&mut self) -> Result<i32> {
+    Ok(self.read_u32()? as i32)
+  }
+
+  fn read_string(&mut self) -> Result<String> {
+    let length = self.read_u32()? as usize;
+    let bytes = self.read_bytes(length)?;
+    // drop the terminating NUL
+    let bytes = bytes.split_last().map_or(bytes, |(_, init)| init);
+    std::str::from_utf8(bytes)
+      .map(String::from)
+      .map_err(Error::BadString)
+  }
+
+  fn read_sample_identity(&mut self) -> Result<SampleIdentity> {
+    let mut guid_bytes = [0; 16];
+    guid_bytes.copy_from_slice(self.read_bytes(16)?);
+    let high = self.read_i32()?;
+    let low = self.read_u32()?;
+    Ok(SampleIdentity {
+      writer_guid: GUID::from_bytes(guid_bytes),
+      sequence_number: SequenceNumber::from((i64::from(high) << 32) + i64::from(low)),
+    })
+  }
+
+  // Returns the end position of the delimited data following the DHEADER.
+  fn read_dheader(&mut self) -> Result<usize> {
+    let length = self.read_u32()? as usize;
+    if self.pos + length > self.input.len() {
+      return Err(Error::Eof);
+    }
+    Ok(self.pos + length)
+  }
+
+  // Returns member id and the end position of the member. Position is left at
+  // the start of the member value.
+  fn read_member_header(&mut self) -> Result<(u32, usize)> {
+    let emheader = self.read_u32()?;
+    let member_id = emheader & 0x0FFF_FFFF;
+    let length = match (emheader >> 28) & 0x7 {
+      length_code @ 0..=3 => 1 << length_code,
+      4 => self.read_u32()? as usize,
+      length_code => {
+        // NEXTINT is also the first word of the member value
+        let next_int = self.read_u32()? as usize;
+        self.pos -= 4;
+        match length_code {
+          5 => 4 + next_int,
+          6 => 4 + 4 * next_int,
+          _ => 4 + 8 * next_int,
+        }
+      }
+    };
+    if self.pos + length > self.input.len() {
+      return Err(Error::Eof);
+    }
+    Ok((member_id, self.pos + length))
+  }
+
+  fn read_type_identifier(&mut self) -> Result<TypeIdentifier> {
+    let mut hash = EquivalenceHash::default();
+    match self.read_u8()? {
+      TypeIdentifier::EK_MINIMAL => {
+        hash.copy_from_slice(self.read_bytes(14)?);
+        Ok(TypeIdentifier::Minimal(hash))
+      }
+      TypeIdentifier::EK_COMPLETE => {
+        hash.copy_from_slice(self.read_bytes(14)?);
+        Ok(TypeIdentifier::Complete(hash))
+      }
+      kind if TypeIdentifier::is_primitive_kind(kind) => Ok(TypeIdentifier::Primitive(kind)),
+      kind => Err(Error::Message(format!(
+        "Unsupported TypeIdentifier kind {:#04x}",
+        kind
+      ))),
+    }
+  }
+
+  // Decoding stops at the first TypeIdentifier kind we do not understand,
+  // because we cannot know its length.
+  fn read_type_identifiers(&mut self) -> Result<Vec<TypeIdentifier>> {
+    let sequence_end = self.read_dheader()?;
+    let count = self.read_u32()?;
+    let mut type_ids = Vec::new();
+    for _ in 0..count {
+      match self.read_type_identifier() {
+        Ok(type_id) => type_ids.push(type_id),
+        Err(_) => break,
+      }
+    }
+    self.pos = sequence_end;
+    Ok(type_ids)
+  }
+
+  fn read_type_object_pairs(&mut self) -> Result<Vec<(TypeIdentifier, TypeObject)>> {
+    let sequence_end = self.read_dheader()?;
+    let count = self.read_u32()?;
+    let mut pairs = Vec::new();
+    for _ in 0..count {
+      let type_id = match self.read_type_identifier() {
+        Ok(type_id) => type_id,
+        Err(_) => break,
+      };
+      self.align(4);
+      let start = self.pos;
+      let end = self.read_dheader()?;
+      self.pos = end;
+      if let Ok(type_object) = TypeObject::from_xcdr2_bytes(self.input[start..end].to_vec()) {
+        pairs.push((type_id, type_object));
+      }
+    }
+    self.pos = sequence_end;
+    Ok(pairs)
+  }
+}
+
+// Adapter to use TypeLookup messages with the builtin DataReaders and
+// DataWriters
+pub(crate) struct TypeLookupAdapter<D> {
+  phantom: PhantomData<D>,
+}
+
+const REPR_IDS: [RepresentationIdentifier; 1] = [RepresentationIdentifier::CDR2_LE];
+
+impl<D> no_key::SerializerAdapter<D> for TypeLookupAdapter<D>
+where
+  D: Serialize + Xcdr2Message,
+{
+  fn output_encoding() -> RepresentationIdentifier {
+    RepresentationIdentifier::CDR2_LE
+  }
+
+  fn to_bytes(value: &D) -> Result<Bytes> {
+    Ok(Bytes::from(value.to_xcdr2_bytes()))
+  }
+}
+
+impl<D> no_key::DeserializerAdapter<D> for TypeLookupAdapter<D>
+where
+  D: DeserializeOwned + Xcdr2Message,
+{
+  fn supported_encodings() -> &'static [RepresentationIdentifier] {
+    &REPR_IDS
+  }
+
+  fn from_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<D> {
+    match encoding {
+      RepresentationIdentifier::CDR2_LE => D::from_xcdr2_bytes(input_bytes),
+      repr_id => Err(Error::Message(format!(
+        "Unknown representation identifier {:?}",
+        repr_id
+      ))),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::structure::guid::{EntityId, GuidPrefix};
+
+  // A TypeObject in the shape of a MinimalTypeObject of a struct. The
+  // contents are not interpreted.
+  fn type_object() -> TypeObject {
+    let body = [
+      TypeIdentifier::EK_MINIMAL,
+      0x51, // TK_STRUCTURE
+      0,
+      0,
+      1,
+      2,
+      3,
+      4,
+    ];
+    let mut serialized = vec![0; 4];
+    LittleEndian::write_u32(&mut serialized, body.len() as u32);
+    serialized.extend_from_slice(&body);
+    TypeObject::from_xcdr2_bytes(serialized).unwrap()
+  }
+
+  fn sample_identity() -> SampleIdentity {
+    SampleIdentity {
+      writer_guid: GUID::new(GuidPrefix::new(&[7; 12]), EntityId::TL_SVC_REQ_WRITER),
+      sequence_number: SequenceNumber::from((5_i64 << 32) + 3),
+    }
+  }
+
+  #[test]
+  fn type_lookup_type_object_identifier() {
+    let type_object = type_object();
+    assert!(type_object.is_minimal());
+    let digest = md5::compute(type_object.as_bytes());
+    match type_object.type_identifier() {
+      TypeIdentifier::Minimal(hash) => assert_eq!(hash[..], digest[..14]),
+      other => panic!("Unexpected {:?}", other),
+    }
+
+    assert!(TypeObject::from_xcdr2_bytes(vec![1, 0, 0, 0]).is_err());
+    assert!(TypeObject::from_xcdr2_bytes(vec![2, 0, 0, 0, 0xF1]).is_err());
+    assert!(TypeObject::from_xcdr2_bytes(vec![1, 0, 0, 0, 0x42]).is_err());
+  }
+
+  #[test]
+  fn type_lookup_request_ser_deser() {
+    let request = TypeLookupRequest {
+      header: RequestHeader {
+        request_id: sample_identity(),
+        instance_name: "abc".to_string(),
+      },
+      call: TypeLookupCall::GetTypes {
+        type_ids: vec![
+          TypeIdentifier::Primitive(0x04),
+          type_object().type_identifier(),
+          TypeIdentifier::Complete([9; 14]),
+        ],
+      },
+    };
+    let bytes = request.to_xcdr2_bytes();
+    assert_eq!(
+      TypeLookupRequest::from_xcdr2_bytes(&bytes).unwrap(),
+      request
+    );
+
+    let unsupported = TypeLookupRequest {
+      call: TypeLookupCall::Unsupported {
+        operation_id: 0x05aa_fb31,
+      },
+      ..request
+    };
+    let bytes = unsupported.to_xcdr2_bytes();
+    assert_eq!(
+      TypeLookupRequest::from_xcdr2_bytes(&bytes).unwrap(),
+      unsupported
+    );
+  }
+
+  #[test]
+  fn type_lookup_reply_ser_deser() {
+    let type_object = type_object();
+    let reply = TypeLookupReply {
+      header: ReplyHeader {
+        related_request_id: sample_identity(),
+        remote_ex: RemoteExceptionCode::Ok,
+      },
+      return_data: TypeLookupReturn::GetTypes {
+        types: vec![(type_object.type_identifier(), type_object)],
+      },
+    };
+    let bytes = reply.to_xcdr2_bytes();
+    assert_eq!(TypeLookupReply::from_xcdr2_bytes(&bytes).unwrap(), reply);
+
+    // truncated input
+    assert!(TypeLookupReply::from_xcdr2_bytes(&bytes[..bytes.len() - 2]).is_err());
+  }
+
+  #[test]
+  fn type_lookup_request_unknown_identifier() {
+    // An unknown TypeIdentifier kind ends the sequence, but not decoding.
+    let request = TypeLookupRequest {
+      header: RequestHeader::default(),
+      call: TypeLookupCall::GetTypes {
+        type_ids: vec![
+          TypeIdentifier::Complete([1; 14]),
+          TypeIdentifier::Primitive(0x70),
+        ],
+      },
+    };
+    let bytes = request.to_xcdr2_bytes();
+    assert_eq!(
+      TypeLookupRequest::from_xcdr2_bytes(&bytes).unwrap().call,
+      TypeLookupCall::GetTypes {
+        type_ids: vec![TypeIdentifier::Complete([1; 14])],
+      }
+    );
+  }
+}
@@ -0,0 +1,943 @@
+// Rust code generation from the IDL syntax tree.
+//
+// Modules become Rust modules, and names are resolved to relative paths
+// (`super::...`), so that the generated code can be included anywhere in a
+// crate.
+
+use std::{collections::BTreeMap, convert::TryFrom};
+
+use super::{parser::*, IdlError, Result};
+
+const DERIVE_SERDE: &str = "::rustdds::__derive_support::serde::Serialize, \
+                            ::rustdds::__derive_support::serde::Deserialize";
+const SERDE_CRATE: &str = "#[serde(crate = \"::rustdds::__derive_support::serde\")]";
+
+// Limit for nested constant references, to stop on reference cycles.
+const MAX_CONST_DEPTH: usize = 64;
+
+// Largest array that Serde can (de)serialize
+const MAX_ARRAY_LENGTH: i128 = 32;
+
+// Bitmasks and bitsets are encoded as an integer of at most 64 bits.
+const MAX_BITS: i128 = 64;
+
+#[derive(Clone, Copy)]
+enum Symbol<'a> {
+  Struct(&'a Struct),
+  Union,
+  Enum(&'a Enum),
+  Bitmask,
+  Bitset,
+  Typedef(&'a Typedef),
+  Const(&'a Const),
+  // Enumerators are in the scope of their enum type, not inside it.
+  Enumerator(&'a Enum, &'a str),
+}
+
+struct Entry<'a> {
+  symbol: Symbol<'a>,
+  file: &'a str,
+}
+
+// Definitions of a module. A module may be reopened, also in another file,
+// so definitions of all its occurrences are merged.
+#[derive(Default)]
+struct Module<'a> {
+  modules: Vec<(&'a str, Module<'a>)>,
+  items: Vec<(&'a str, &'a Definition)>,
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+  Integer(i128),
+  Float(String),
+  Str(String),
+  Char(u8),
+  Bool(bool),
+  // Path of the enum type, and the enumerator name
+  Enumerator(Vec<String>, String),
+}
+
+pub(crate) struct Generator<'a> {
+  root: Module<'a>,
+  // Keyed by fully qualified name
+  symbols: BTreeMap<Vec<String>, Entry<'a>>,
+}
+
+impl<'a> Generator<'a> {
+  pub fn new(files: &'a [(String, Vec<Definition>)]) -> Result<Self> {
+    let mut generator = Generator {
+      root: Module::default(),
+      symbols: BTreeMap::new(),
+    };
+    for (file, definitions) in files {
+      let mut root = std::mem::take(&mut generator.root);
+      generator.add_definitions(file, &mut Vec::new(), &mut root, definitions)?;
+      generator.root = root;
+    }
+    Ok(generator)
+  }
+
+  fn add_definitions(
+    &mut self,
+    file: &'a str,
+    scope: &mut Vec<String>,
+    module: &mut Module<'a>,
+    definitions: &'a [Definition],
+  ) -> Result<()> {
+    for definition in definitions {
+      let (name, line, symbol) = match definition {
+        Definition::Module {
+          name,
+          definitions: module_definitions,
+        } => {
+          let index = match module.modules.iter().position(|(n, _)| n == name) {
+            Some(index) => index,
+            None => {
+              module.modules.push((name, Module::default()));
+              module.modules.len() - 1
+            }
+          };
+          scope.push(name.clone());
+          self.add_definitions(
+            file,
+            scope,
+            &mut module.modules[index].1,
+            module_definitions,
+          )?;
+          scope.pop();
+          continue;
+        }
+        Definition::Struct(s) => (&s.name, s.line, Symbol::Struct(s)),
+        Definition::Union(u) => (&u.name, u.line, Symbol::Union),
+        Definition::Bitmask(b) => (&b.name, b.line, Symbol::Bitmask),
+        Definition::Bitset(b) => (&b.name, b.line, Symbol::Bitset),
+        Definition::Enum(e) => {
+          for enumerator in &e.enumerators {
+            self.add_symbol(
+              file,
+              e.line,
+              scope,
+              &enumerator.name,
+              Symbol::Enumerator(e, &enumerator.name),
+            )?;
+          }
+          (&e.name, e.line, Symbol::Enum(e))
+        }
+        Definition::Typedef(t) => (&t.name, t.line, Symbol::Typedef(t)),
+        Definition::Const(c) => (&c.name, c.line, Symbol::Const(c)),
+      };
+      self.add_symbol(file, line, scope, name, symbol)?;
+      module.items.push((file, definition));
+    }
+    Ok(())
+  }
+
+  fn add_symbol(
+    &mut self,
+    file: &'a str,
+    line: usize,
+    scope: &[String],
+    name: &str,
+    symbol: Symbol<'a>,
+  ) -> Result<()> {
+    let mut path = scope.to_vec();
+    path.push(name.to_string());
+    if self.symbols.contains_key(&path) {
+      return Err(IdlError::Invalid {
+        file: file.to_string(),
+        line,
+        reason: format!("{} is defined twice", path.join("::")),
+      });
+    }
+    self.symbols.insert(path, Entry { symbol, file });
+    Ok(())
+  }
+
+  pub fn generate(&self) -> Result<String> {
+    let mut out = Output::default();
+    self.generate_module(&mut out, &mut Vec::new(), &self.root)?;
+    Ok(out.text)
+  }
+
+  fn generate_module(
+    &self,
+    out: &mut Output,
+    scope: &mut Vec<String>,
+    module: &Module<'a>,
+  ) -> Result<()> {
+    for (file, definition) in &module.items {
+      let context = Context {
+        generator: self,
+        file,
+        scope,
+        module: scope,
+      };
+      match definition {
+        Definition::Struct(s) => context.generate_struct(out, s)?,
+        Definition::Union(u) => context.generate_union(out, u)?,
+        Definition::Enum(e) => context.generate_enum(out, e)?,
+        Definition::Bitmask(b) => context.generate_bitmask(out, b)?,
+        Definition::Bitset(b) => context.generate_bitset(out, b)?,
+        Definition::Typedef(t) => context.generate_typedef(out, t)?,
+        Definition::Const(c) => context.generate_const(out, c)?,
+        Definition::Module { .. } => (),
+      }
+    }
+    for (name, submodule) in &module.modules {
+      out.blank_line();
+      out.line(&format!(
+        "pub mod {} {{",
+        rust_identifier(&snake_case(name))
+      ));
+      out.depth += 1;
+      scope.push(name.to_string());
+      self.generate_module(out, scope, submodule)?;
+      scope.pop();
+      out.depth -= 1;
+      out.line("}");
+    }
+    Ok(())
+  }
+}
+
+#[derive(Default)]
+struct Output {
+  text: String,
+  depth: usize,
+}
+
+impl Output {
+  fn line(&mut self, line: &str) {
+    for _ in 0..self.depth {
+      self.text.push_str("    ");
+    }
+    self.text.push_str(line);
+    self.text.push('\n');
+  }
+
+  fn blank_line(&mut self) {
+    if !self.text.is_empty() && !self.text.ends_with("{\n") {
+      self.text.push('\n');
+    }
+  }
+}
+
+struct Context<'g, 'a> {
+  generator: &'g Generator<'a>,
+  file: &'a str,
+  // Where names are looked up
+  scope: &'g [String],
+  // Where the generated code is. This differs from `scope` for members
+  // inherited from a base struct in another module.
+  module: &'g [String],
+}
+
+impl<'g, 'a> Context<'g, 'a> {
+  // Context of a definition elsewhere, for resolving names used in it
+  fn at(&self, path: &'g [String]) -> Self {
+    let entry = &self.generator.symbols[path];
+    Context {
+      generator: self.generator,
+      file: entry.file,
+      scope: &path[..path.len() - 1],
+      module: self.module,
+    }
+  }
+
+  fn invalid(&self, line: usize, reason: String) -> IdlError {
+    IdlError::Invalid {
+      file: self.file.to_string(),
+      line,
+      reason,
+    }
+  }
+
+  fn resolve(&self, name: &ScopedName, line: usize) -> Result<(&'g [String], Symbol<'a>)> {
+    // Look in the current scope first, then in the enclosing ones.
+    let outermost = if name.absolute { 0 } else { self.scope.len() };
+    for depth in (0..=outermost).rev() {
+      let mut path = self.scope[..depth].to_vec();
+      path.extend(name.parts.iter().cloned());
+      if let Some((path, entry)) = self.generator.symbols.get_key_value(&path) {
+        return Ok((path, entry.symbol));
+      }
+    }
+    Err(self.invalid(line, format!("{} is not defined", name)))
+  }
+
+  // Rust path to a definition, relative to the current module.
+  fn rust_path(&self, path: &[String], rust_name: &str) -> String {
+    let modules = &path[..path.len() - 1];
+    let common = self
+      .module
+      .iter()
+      .zip(modules)
+      .take_while(|(a, b)| a == b)
+      .count();
+    let mut rust_path = "super::".repeat(self.module.len() - common);
+    for module in &modules[common..] {
+      rust_path.push_str(&rust_identifier(&snake_case(module)));
+      rust_path.push_str("::");
+    }
+    rust_path.push_str(rust_name);
+    rust_path
+  }
+
+  fn rust_type(&self, type_spec: &TypeSpec, line: usize) -> Result<String> {
+    Ok(match type_spec {
+      TypeSpec::Primitive(primitive) => primitive_type(*primitive).to_string(),
+      TypeSpec::String => "String".to_string(),
+      TypeSpec::Sequence(element) => format!("Vec<{}>", self.rust_type(element, line)?),
+      TypeSpec::Named(name) => match self.resolve(name, line)? {
+        (path, Symbol::Struct(_))
+        | (path, Symbol::Union)
+        | (path, Symbol::Enum(_))
+        | (path, Symbol::Bitmask)
+        | (path, Symbol::Bitset)
+        | (path, Symbol::Typedef(_)) => {
+          self.rust_path(path, &rust_identifier(path.last().unwrap()))
+        }
+        _ => return Err(self.invalid(line, format!("{} is not a type", name))),
+      },
+    })
+  }
+
+  fn array_type(&self, element: String, dimensions: &[ConstExpr], line: usize) -> Result<String> {
+    let mut rust_type = element;
+    for dimension in dimensions.iter().rev() {
+      match self.evaluate(dimension, line, 0)? {
+        Value::Integer(length) if (1..=MAX_ARRAY_LENGTH).contains(&length) => {
+          rust_type = format!("[{}; {}]", rust_type, length);
+        }
+        Value::Integer(length) if length > MAX_ARRAY_LENGTH => {
+          return Err(IdlError::Unsupported {
+            file: self.file.to_string(),
+            line,
+            feature: format!("arrays of more than {} elements", MAX_ARRAY_LENGTH),
+          })
+        }
+        _ => return Err(self.invalid(line, "Invalid array length".to_string())),
+      }
+    }
+    Ok(rust_type)
+  }
+
+  fn member_type(&self, member: &Member) -> Result<String> {
+    let element = self.rust_type(&member.type_spec, member.line)?;
+    self.array_type(element, &member.dimensions, member.line)
+  }
+
+  // Follows typedefs to the underlying type. Returns it with the context it
+  // was defined in.
+  fn base_type(
+    &self,
+    type_spec: &'a TypeSpec,
+    line: usize,
+  ) -> Result<(Context<'g, 'a>, &'a TypeSpec)> {
+    let mut context = self.at_self();
+    let mut type_spec = type_spec;
+    loop {
+      match type_spec {
+        TypeSpec::Named(name) => match context.resolve(name, line)? {
+          (path, Symbol::Typedef(typedef)) if typedef.dimensions.is_empty() => {
+            context = context.at(path);
+            type_spec = &typedef.type_spec;
+          }
+          _ => return Ok((context, type_spec)),
+        },
+        _ => return Ok((context, type_spec)),
+      }
+    }
+  }
+
+  fn at_self(&self) -> Self {
+    Context {
+      generator: self.generator,
+      file: self.file,
+      scope: self.scope,
+      module: self.module,
+    }
+  }
+
+  fn evaluate(&self, expr: &ConstExpr, line: usize, depth: usize) -> Result<Value> {
+    let invalid = || self.invalid(line, "Invalid constant expression".to_string());
+    Ok(match expr {
+      ConstExpr::Integer(value) => Value::Integer(i128::try_from(*value).map_err(|_| invalid())?),
+      ConstExpr::Float(value) => Value::Float(value.clone()),
+      ConstExpr::Str(value) => Value::Str(value.clone()),
+      ConstExpr::Char(value) => Value::Char(*value),
+      ConstExpr::Bool(value) => Value::Bool(*value),
+      ConstExpr::Name(name) => match self.resolve(name, line)? {
+        (path, Symbol::Const(c)) if depth < MAX_CONST_DEPTH => {
+          self.at(path).evaluate(&c.value, c.line, depth + 1)?
+        }
+        (path, Symbol::Enumerator(e, enumerator)) => {
+          let mut enum_path = path[..path.len() - 1].to_vec();
+          enum_path.push(e.name.clone());
+          Value::Enumerator(enum_path, enumerator.to_string())
+        }
+        _ => return Err(invalid()),
+      },
+      ConstExpr::Unary(operator, operand) => {
+        match (*operator, self.evaluate(operand, line, depth)?) {
+          ("-", Value::Integer(value)) => Value::Integer(-value),
+          ("-", Value::Float(value)) => Value::Float(format!("-{}", value)),
+          ("+", value @ Value::Integer(_)) | ("+", value @ Value::Float(_)) => value,
+          ("~", Value::Integer(value)) => Value::Integer(!value),
+          _ => return Err(invalid()),
+        }
+      }
+      ConstExpr::Binary(operator, left, right) => {
+        match (
+          self.evaluate(left, line, depth)?,
+          self.evaluate(right, line, depth)?,
+        ) {
+          (Value::Integer(left), Value::Integer(right)) => {
+            let shift = u32::try_from(right).ok();
+            let result = match *operator {
+              "|" => Some(left | right),
+              "^" => Some(left ^ right),
+              "&" => Some(left & right),
+              "<<" => shift.and_then(|s| left.checked_shl(s)),
+              ">>" => shift.and_then(|s| left.checked_shr(s)),
+              "+" => left.checked_add(right),
+              "-" => left.checked_sub(right),
+              "*" => left.checked_mul(right),
+              "/" => left.checked_div(right),
+              "%" => left.checked_rem(right),
+              _ => None,
+            };
+            Value::Integer(result.ok_or_else(invalid)?)
+          }
+          _ => return Err(invalid()),
+        }
+      }
+    })
+  }
+
+  fn render_value(&self, value: &Value) -> String {
+    match value {
+      Value::Integer(value) => value.to_string(),
+      Value::Float(value) => value.clone(),
+      Value::Str(value) => format!("{:?}", value),
+      Value::Char(value) if value.is_ascii_graphic() || *value == b' ' => {
+        format!("b{:?}", char::from(*value))
+      }
+      Value::Char(value) => value.to_string(),
+      Value::Bool(value) => value.to_string(),
+      Value::Enumerator(enum_path, enumerator) => {
+        let rust_name = format!(
+          "{}::{}",
+          rust_identifier(enum_path.last().unwrap()),
+          rust_identifier(&camel_case(enumerator))
+        );
+        self.rust_path(enum_path, &rust_name)
+      }
+    }
+  }
+
+  fn dds_type_name(&self, name: &str) -> String {
+    let mut parts = self.scope.to_vec();
+    parts.push(name.to_string());
+    parts.join("::")
+  }
+
+  fn generate_struct(&self, out: &mut Output, s: &Struct) -> Result<()> {
+    // Members of base types come first.
+    let mut bases = Vec::new();
+    let mut base = s.base.as_ref().map(|b| (self.at_self(), b, s.line));
+    while let Some((context, name, line)) = base {
+      match context.resolve(name, line)? {
+        (path, Symbol::Struct(base_struct)) => {
+          let base_context = context.at(path);
+          base = base_struct
+            .base
+            .as_ref()
+            .map(|b| (base_context.at_self(), b, base_struct.line));
+          bases.push((base_context, base_struct));
+          if bases.len() > MAX_CONST_DEPTH {
+            return Err(self.invalid(s.line, "Cyclic inheritance".to_string()));
+          }
+        }
+        _ => return Err(self.invalid(line, format!("{} is not a struct", name))),
+      }
+    }
+
+    let mut fields = Vec::new();
+    for (context, base_struct) in bases.iter().rev() {
+      for member in &base_struct.members {
+        fields.push((member, context.member_type(member)?));
+      }
+    }
+    for member in &s.members {
+      fields.push((member, self.member_type(member)?));
+    }
+
+    out.blank_line();
+    out.line(&format!(
+      "#[derive(Debug, Clone, PartialEq, {}, ::rustdds::DdsType)]",
+      DERIVE_SERDE
+    ));
+    out.line(SERDE_CRATE);
+    out.line(&format!(
+      "#[dds_type(name = \"{}\")]",
+      self.dds_type_name(&s.name)
+    ));
+    allow_naming(out, &s.name);
+    out.line(&format!("pub struct {} {{", rust_identifier(&s.name)));
+    out.depth += 1;
+    for (member, rust_type) in fields {
+      let field_name = snake_case(&member.name);
+      if member.key {
+        out.line("#[dds_key]");
+      }
+      if field_name != member.name {
+        out.line(&format!("#[serde(rename = \"{}\")]", member.name));
+      }
+      out.line(&format!(
+        "pub {}: {},",
+        rust_identifier(&field_name),
+        rust_type
+      ));
+    }
+    out.depth -= 1;
+    out.line("}");
+    Ok(())
+  }
+
+  fn generate_union(&self, out: &mut Output, u: &Union) -> Result<()> {
+    let (discriminator_context, discriminator) = self.base_type(&u.discriminator, u.line)?;
+    let invalid_discriminator =
+      || self.invalid(u.line, "Invalid union discriminator type".to_string());
+    let (discriminator_type, enum_type) = match discriminator {
+      TypeSpec::Primitive(Primitive::Float) | TypeSpec::Primitive(Primitive::Double) => {
+        return Err(invalid_discriminator())
+      }
+      TypeSpec::Primitive(primitive) => (primitive_type(*primitive).to_string(), None),
+      TypeSpec::Named(name) => match discriminator_context.resolve(name, u.line)? {
+        (path, Symbol::Enum(e)) => (self.rust_type(&u.discriminator, u.line)?, Some((path, e))),
+        _ => return Err(invalid_discriminator()),
+      },
+      _ => return Err(invalid_discriminator()),
+    };
+
+    let mut used = Vec::new();
+    let mut cases = Vec::new();
+    for case in &u.cases {
+      let mut labels = Vec::new();
+      for label in &case.labels {
+        // Enumerators of the discriminator enum are in scope of the enum.
+        let value = match (label, enum_type) {
+          (ConstExpr::Name(name), Some((enum_path, e)))
+            if !name.absolute
+              && name.parts.len() == 1
+              && e.enumerators.iter().any(|en| en.name == name.parts[0]) =>
+          {
+            Value::Enumerator(enum_path.to_vec(), name.parts[0].clone())
+          }
+          _ => self.evaluate(label, case.member.line, 0)?,
+        };
+        used.push(value.clone());
+        labels.push(self.render_value(&value));
+      }
+      cases.push((case, labels));
+    }
+
+    out.blank_line();
+    out.line("#[derive(Debug, Clone, PartialEq, ::rustdds::DdsUnion, ::rustdds::DdsType)]");
+    out.line(&format!(
+      "#[dds_type(name = \"{}\")]",
+      self.dds_type_name(&u.name)
+    ));
+    out.line(&format!(
+      "#[dds_union(discriminator = \"{}\")]",
+      discriminator_type
+    ));
+    allow_naming(out, &u.name);
+    out.line(&format!("pub enum {} {{", rust_identifier(&u.name)));
+    out.depth += 1;
+    for (case, labels) in cases {
+      if !labels.is_empty() {
+        out.line(&format!("#[dds_case({})]", labels.join(", ")));
+      }
+      if case.default {
+        let label = self.default_label(u, discriminator, enum_type, &used)?;
+        out.line(&format!("#[dds_default({})]", self.render_value(&label)));
+      }
+      out.line(&format!(
+        "{}({}),",
+        rust_identifier(&camel_case(&case.member.name)),
+        self.member_type(&case.member)?
+      ));
+    }
+    out.depth -= 1;
+    out.line("}");
+    Ok(())
+  }
+
+  // A discriminator value that no case label has, to be written for the
+  // default case.
+  fn default_label(
+    &self,
+    u: &Union,
+    discriminator: &TypeSpec,
+    enum_type: Option<(&[String], &Enum)>,
+    used: &[Value],
+  ) -> Result<Value> {
+    let unused = match (discriminator, enum_type) {
+      (_, Some((enum_path, e))) => e
+        .enumerators
+        .iter()
+        .find(|en| {
+          !used
+            .iter()
+            .any(|v| matches!(v, Value::Enumerator(_, name) if *name == en.name))
+        })
+        .map(|en| Value::Enumerator(enum_path.to_vec(), en.name.clone())),
+      (TypeSpec::Primitive(Primitive::Boolean), _) => [false, true]
+        .iter()
+        .find(|b| !used.iter().any(|v| matches!(v, Value::Bool(u) if u == *b)))
+        .map(|b| Value::Bool(*b)),
+      _ => (0..=i128::from(u8::MAX))
+        .chain(256..=i128::from(u32::MAX))
+        .find(|i| {
+          !used.iter().any(|v| match v {
+            Value::Integer(value) => value == i,
+            Value::Char(value) => i128::from(*value) == *i,
+            _ => false,
+          })
+        })
+        .map(Value::Integer),
+    };
+    unused.ok_or_else(|| {
+      self.invalid(
+        u.line,
+        format!(
+          "Union {} has no discriminator value left for the default case",
+          u.name
+        ),
+      )
+    })
+  }
+
+  fn generate_enum(&self, out: &mut Output, e: &Enum) -> Result<()> {
+    out.blank_line();
+    out.line(
+      "#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ::rustdds::DdsEnum)]",
+    );
+    allow_naming(out, &e.name);
+    out.line(&format!("pub enum {} {{", rust_identifier(&e.name)));
+    out.depth += 1;
+    for enumerator in &e.enumerators {
+      let variant = rust_identifier(&camel_case(&enumerator.name));
+      match &enumerator.value {
+        Some(value) => match self.evaluate(value, e.line, 0)? {
+          Value::Integer(value) => out.line(&format!("{} = {},", variant, value)),
+          _ => return Err(self.invalid(e.line, "Invalid enumerator value".to_string())),
+        },
+        None => out.line(&format!("{},", variant)),
+      }
+    }
+    out.depth -= 1;
+    out.line("}");
+    Ok(())
+  }
+
+  fn generate_bitmask(&self, out: &mut Output, b: &Bitmask) -> Result<()> {
+    let bit_bound = match &b.bit_bound {
+      Some(bit_bound) => match self.evaluate(bit_bound, b.line, 0)? {
+        Value::Integer(bits) if (1..=MAX_BITS).contains(&bits) => bits,
+        _ => return Err(self.invalid(b.line, "Invalid bit bound".to_string())),
+      },
+      None => 32,
+    };
+    let mut values = Vec::new();
+    let mut position = 0;
+    for value in &b.values {
+      if let Some(expr) = &value.position {
+        position = match self.evaluate(expr, b.line, 0)? {
+          Value::Integer(position) => position,
+          _ => return Err(self.invalid(b.line, "Invalid bit position".to_string())),
+        };
+      }
+      if !(0..bit_bound).contains(&position) {
+        return Err(self.invalid(
+          b.line,
+          format!("Position of {} is outside the bit bound", value.name),
+        ));
+      }
+      values.push((&value.name, position));
+      position += 1;
+    }
+
+    let name = rust_identifier(&b.name);
+    out.blank_line();
+    out.line(
+      "#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, \
+       ::rustdds::DdsBitmask)]",
+    );
+    out.line(&format!("#[dds_bitmask(bit_bound = {})]", bit_bound));
+    allow_naming(out, &b.name);
+    out.line(&format!(
+      "pub struct {}(pub {});",
+      name,
+      bits_type(bit_bound)
+    ));
+    out.blank_line();
+    out.line(&format!("impl {} {{", name));
+    out.depth += 1;
+    for (value, position) in values {
+      if value.chars().any(|ch| ch.is_ascii_lowercase()) {
+        out.line("#[allow(non_upper_case_globals)]");
+      }
+      out.line(&format!(
+        "pub const {}: Self = Self(1 << {});",
+        rust_identifier(value),
+        position
+      ));
+    }
+    out.depth -= 1;
+    out.line("}");
+    Ok(())
+  }
+
+  fn generate_bitset(&self, out: &mut Output, b: &Bitset) -> Result<()> {
+    // Names, widths, preceding unused bits and types of the bit fields
+    let mut fields = Vec::new();
+    let mut bits = 0;
+    let mut skip = 0;
+    for bitfield in &b.bitfields {
+      let width = match self.evaluate(&bitfield.width, bitfield.line, 0)? {
+        Value::Integer(width) if (1..=MAX_BITS).contains(&width) => width,
+        _ => return Err(self.invalid(bitfield.line, "Invalid bit field width".to_string())),
+      };
+      bits += width;
+      if bits > MAX_BITS {
+        return Err(IdlError::Unsupported {
+          file: self.file.to_string(),
+          line: bitfield.line,
+          feature: format!("bitsets of more than {} bits", MAX_BITS),
+        });
+      }
+      let name = match &bitfield.name {
+        Some(name) => name,
+        None => {
+          skip += width;
+          continue;
+        }
+      };
+      // The default type is the smallest that holds the bits.
+      let rust_type = match &bitfield.type_spec {
+        None if width == 1 => primitive_type(Primitive::Boolean),
+        None => bits_type(width),
+        Some(type_spec) => match self.base_type(type_spec, bitfield.line)?.1 {
+          TypeSpec::Primitive(primitive)
+            if primitive_bits(*primitive).map_or(false, |b| width <= b) =>
+          {
+            primitive_type(*primitive)
+          }
+          _ => {
+            return Err(self.invalid(
+              bitfield.line,
+              format!("Invalid type for bit field {}", name),
+            ))
+          }
+        },
+      };
+      fields.push((name, width, skip, rust_type));
+      skip = 0;
+    }
+    if bits == 0 {
+      return Err(self.invalid(b.line, format!("Bitset {} has no bits", b.name)));
+    }
+
+    out.blank_line();
+    out.line(
+      "#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, \
+       ::rustdds::DdsBitset)]",
+    );
+    // Unused bits at the end count in the size.
+    if skip > 0 {
+      out.line(&format!("#[dds_bitset(bits = {})]", bits));
+    }
+    allow_naming(out, &b.name);
+    out.line(&format!("pub struct {} {{", rust_identifier(&b.name)));
+    out.depth += 1;
+    for (name, width, skip, rust_type) in fields {
+      if skip > 0 {
+        out.line(&format!("#[dds_bitfield({}, skip = {})]", width, skip));
+      } else {
+        out.line(&format!("#[dds_bitfield({})]", width));
+      }
+      out.line(&format!(
+        "pub {}: {},",
+        rust_identifier(&snake_case(name)),
+        rust_type
+      ));
+    }
+    out.depth -= 1;
+    out.line("}");
+    Ok(())
+  }
+
+  fn generate_typedef(&self, out: &mut Output, t: &Typedef) -> Result<()> {
+    let rust_type = self.rust_type(&t.type_spec, t.line)?;
+    let rust_type = self.array_type(rust_type, &t.dimensions, t.line)?;
+    out.blank_line();
+    allow_naming(out, &t.name);
+    out.line(&format!(
+      "pub type {} = {};",
+      rust_identifier(&t.name),
+      rust_type
+    ));
+    Ok(())
+  }
+
+  fn generate_const(&self, out: &mut Output, c: &Const) -> Result<()> {
+    let (context, base_type) = self.base_type(&c.type_spec, c.line)?;
+    let value = self.evaluate(&c.value, c.line, 0)?;
+    let (rust_type, value) = match (base_type, value) {
+      (TypeSpec::String, value @ Value::Str(_)) => ("&str".to_string(), value),
+      (TypeSpec::Primitive(Primitive::Float), Value::Integer(value))
+      | (TypeSpec::Primitive(Primitive::Double), Value::Integer(value)) => (
+        primitive_type(Primitive::Double).to_string(),
+        Value::Float(format!("{}.0", value)),
+      ),
+      (TypeSpec::Primitive(primitive), value) => (primitive_type(*primitive).to_string(), value),
+      (TypeSpec::Named(name), value @ Value::Enumerator(..)) => {
+        let (path, _) = context.resolve(name, c.line)?;
+        (
+          self.rust_path(path, &rust_identifier(path.last().unwrap())),
+          value,
+        )
+      }
+      _ => return Err(self.invalid(c.line, format!("Invalid value for constant {}", c.name))),
+    };
+    // Float constants keep their declared width.
+    let rust_type = match base_type {
+      TypeSpec::Primitive(primitive) => primitive_type(*primitive).to_string(),
+      _ => rust_type,
+    };
+    out.blank_line();
+    if c.name.chars().any(|ch| ch.is_ascii_lowercase()) {
+      out.line("#[allow(non_upper_case_globals)]");
+    }
+    out.line(&format!(
+      "pub const {}: {} = {};",
+      rust_identifier(&c.name),
+      rust_type,
+      self.render_value(&value)
+    ));
+    Ok(())
+  }
+}
+
+fn primitive_type(primitive: Primitive) -> &'static str {
+  match primitive {
+    Primitive::Boolean => "bool",
+    // IDL char is a single octet.
+    Primitive::Octet | Primitive::Char | Primitive::UInt8 => "u8",
+    Primitive::Int8 => "i8",
+    Primitive::Int16 => "i16",
+    Primitive::UInt16 => "u16",
+    Primitive::Int32 => "i32",
+    Primitive::UInt32 => "u32",
+    Primitive::Int64 => "i64",
+    Primitive::UInt64 => "u64",
+    Primitive::Float => "f32",
+    Primitive::Double => "f64",
+  }
+}
+
+// Number of bits in a bit field of the type, or None if the type cannot be
+// used in bit fields
+fn primitive_bits(primitive: Primitive) -> Option<i128> {
+  match primitive {
+    Primitive::Boolean => Some(1),
+    Primitive::Octet | Primitive::UInt8 | Primitive::Int8 => Some(8),
+    Primitive::Int16 | Primitive::UInt16 => Some(16),
+    Primitive::Int32 | Primitive::UInt32 => Some(32),
+    Primitive::Int64 | Primitive::UInt64 => Some(64),
+    Primitive::Char | Primitive::Float | Primitive::Double => None,
+  }
+}
+
+// The smallest unsigned integer type that holds `bits` bits. This is also the
+// CDR encoding of bitmasks and bitsets.
+fn bits_type(bits: i128) -> &'static str {
+  match bits {
+    0..=8 => "u8",
+    9..=16 => "u16",
+    17..=32 => "u32",
+    _ => "u64",
+  }
+}
+
+// Type names are kept as they are, but need an allow if they are not
+// UpperCamelCase.
+fn allow_naming(out: &mut Output, type_name: &str) {
+  if !type_name.starts_with(|c: char| c.is_ascii_uppercase()) || type_name.contains('_') {
+    out.line("#[allow(non_camel_case_types)]");
+  }
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+  "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+  "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+  "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+  "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+  "where", "while", "yield",
+];
+
+fn rust_identifier(name: &str) -> String {
+  if matches!(name, "self" | "Self" | "super" | "crate") {
+    format!("{}_", name)
+  } else if RUST_KEYWORDS.contains(&name) {
+    format!("r#{}", name)
+  } else {
+    name.to_string()
+  }
+}
+
+pub(crate) fn snake_case(name: &str) -> String {
+  let chars: Vec<char> = name.chars().collect();
+  let mut snake = String::new();
+  for (i, c) in chars.iter().enumerate() {
+    if c.is_ascii_uppercase() {
+      let previous = if i > 0 { Some(chars[i - 1]) } else { None };
+      let next_is_lower = chars.get(i + 1).map_or(false, |n| n.is_ascii_lowercase());
+      let word_start = match previous {
+        Some(p) => {
+          p.is_ascii_lowercase() || p.is_ascii_digit() || (p.is_ascii_uppercase() && next_is_lower)
+        }
+        None => false,
+      };
+      if word_start {
+        snake.push('_');
+      }
+      snake.push(c.to_ascii_lowercase());
+    } else {
+      snake.push(*c);
+    }
+  }
+  snake
+}
+
+pub(crate) fn camel_case(name: &str) -> String {
+  name
+    .split('_')
+    .filter(|part| !part.is_empty())
+    .map(|part| {
+      let all_upper = !part.chars().any(|c| c.is_ascii_lowercase());
+      let mut chars = part.chars();
+      let first = chars.next().unwrap().to_ascii_uppercase();
+      let rest: String = if all_upper {
+        chars.as_str().to_ascii_lowercase()
+      } else {
+        chars.as_str().to_string()
+      };
+      format!("{}{}", first, rest)
+    })
+    .collect()
+}
@@ -0,0 +1,369 @@
+//! Rust code generation from OMG IDL, for build scripts.
+
+use std::{
+  env, fs, io,
+  path::{Path, PathBuf},
+};
+
+mod codegen;
+mod parser;
+
+/// Errors from IDL code generation
+#[derive(Debug, thiserror::Error)]
+pub enum IdlError {
+  #[error(transparent)]
+  Io(#[from] io::Error),
+
+  #[error("{file}:{line}: {reason}")]
+  Syntax {
+    file: String,
+    line: usize,
+    reason: String,
+  },
+
+  /// Valid IDL, for which code cannot be generated.
+  #[error("{file}:{line}: Not supported: {feature}")]
+  Unsupported {
+    file: String,
+    line: usize,
+    feature: String,
+  },
+
+  /// E.g. an undefined name or an invalid constant.
+  #[error("{file}:{line}: {reason}")]
+  Invalid {
+    file: String,
+    line: usize,
+    reason: String,
+  },
+
+  /// [`IdlCodegen::write_to_out_dir`] was called outside of a build script.
+  #[error("OUT_DIR is not set. Is this a build script?")]
+  NoOutDir,
+}
+
+type Result<T> = std::result::Result<T, IdlError>;
+
+enum Input {
+  File(PathBuf),
+  Source { name: String, idl: String },
+}
+
+/// Generates Rust code from OMG IDL files, for build scripts.
+///
+/// This reads IDL files and generates Rust types for them: structs
+/// implement Serde traits, [`DdsType`](trait@crate::DdsType) and, if they
+/// have `@key` members, [`Keyed`](crate::Keyed). The generated code uses the
+/// derive macros of RustDDS, so the `derive` feature must be on.
+///
+/// | IDL | Rust |
+/// |-----|------|
+/// | `module` | `pub mod`, name in snake_case |
+/// | `struct`, inheritance | `pub struct`, base members first |
+/// | `enum`, `@value` | `pub enum` with [`DdsEnum`](crate::DdsEnum) |
+/// | `union` | `pub enum` with [`DdsUnion`](crate::DdsUnion) |
+/// | `bitmask`, `@bit_bound`, `@position` | `pub struct` of `u8` ... `u64` with [`DdsBitmask`](crate::DdsBitmask), a constant for each value |
+/// | `bitset`, `bitfield<N>` | `pub struct` with [`DdsBitset`](crate::DdsBitset) |
+/// | `typedef` | `pub type` |
+/// | `const` | `pub const` |
+/// | `boolean`, `octet`, `char`, `int8` ... `uint64`, `short` ... `unsigned long long` | `bool`, `u8`, `u8`, `i8` ... `u64` |
+/// | `float`, `double` | `f32`, `f64` |
+/// | `string`, `string<N>` | `String` |
+/// | `sequence<T>`, `sequence<T, N>` | `Vec<T>` |
+/// | `T name[N]` | `[T; N]`, at most 32 elements |
+///
+/// Member names are converted to snake_case. Bounds of strings and sequences
+/// are not checked. Preprocessor directives, including `#include`, are
+/// ignored, so all files that are needed must be given to the same
+/// `IdlCodegen`, in order of dependency. Other IDL features, e.g.
+/// `wstring`, `map` and `@optional`, are reported as
+/// [`IdlError::Unsupported`].
+///
+/// # Example
+///
+/// In `build.rs`, with `rustdds` also in `[build-dependencies]`:
+///
+/// ```no_run
+/// rustdds::IdlCodegen::new()
+///   .input("idl/shapes.idl")
+///   .write_to_out_dir("shapes.rs")
+///   .unwrap();
+/// ```
+///
+/// and in the crate:
+///
+/// ```ignore
+/// include!(concat!(env!("OUT_DIR"), "/shapes.rs"));
+/// ```
+#[derive(Default)]
+pub struct IdlCodegen {
+  inputs: Vec<Input>,
+}
+
+impl IdlCodegen {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds an IDL file.
+  #[must_use]
+  pub fn input<P: AsRef<Path>>(mut self, path: P) -> Self {
+    self.inputs.push(Input::File(path.as_ref().to_path_buf()));
+    self
+  }
+
+  /// Adds IDL from a string. The name is used in error messages.
+  #[must_use]
+  pub fn source(mut self, name: &str, idl: &str) -> Self {
+    self.inputs.push(Input::Source {
+      name: name.to_string(),
+      idl: idl.to_string(),
+    });
+    self
+  }
+
+  /// Generates Rust code for all the inputs.
+  pub fn generate(&self) -> Result<String> {
+    let mut files = Vec::new();
+    for input in &self.inputs {
+      let (name, idl) = match input {
+        Input::File(path) => (path.display().to_string(), fs::read_to_string(path)?),
+        Input::Source { name, idl } => (name.clone(), idl.clone()),
+      };
+      let definitions = parser::parse(&name, &idl)?;
+      files.push((name, definitions));
+    }
+    let code = codegen::Generator::new(&files)?.generate()?;
+    let names: Vec<_> = files.iter().map(|(name, _)| name.as_str()).collect();
+    Ok(format!(
+      "// Generated by rustdds::IdlCodegen from {}. Do not edit.\n\n{}",
+      names.join(", "),
+      code
+    ))
+  }
+
+  /// Generates Rust code and writes it to `file_name` in the `OUT_DIR` of the
+  /// build script. Tells Cargo to run the build script again, when an input
+  /// file changes.
+  ///
+  /// Returns the path of the written file.
+  pub fn write_to_out_dir(&self, file_name: &str) -> Result<PathBuf> {
+    let out_dir = env::var_os("OUT_DIR").ok_or(IdlError::NoOutDir)?;
+    for input in &self.inputs {
+      if let Input::File(path) = input {
+        println!("cargo:rerun-if-changed={}", path.display());
+      }
+    }
+    let path = Path::new(&out_dir).join(file_name);
+    fs::write(&path, self.generate()?)?;
+    Ok(path)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn generate(idl: &str) -> Result<String> {
+    IdlCodegen::new().source("test.idl", idl).generate()
+  }
+
+  #[test]
+  fn generate_struct() {
+    let code = generate(
+      r#"
+      module geometry {
+        const long MAX_POINTS = 4 * 2;
+        @topic
+        struct Point {
+          @key long id;
+          double x, y;
+          string<16> label;
+          sequence<octet> data;
+          short matrix[2][3];
+        };
+      };
+      "#,
+    )
+    .unwrap();
+    assert_eq!(
+      code,
+      "// Generated by rustdds::IdlCodegen from test.idl. Do not edit.
+
+pub mod geometry {
+    pub const MAX_POINTS: i32 = 8;
+
+    #[derive(Debug, Clone, PartialEq, ::rustdds::__derive_support::serde::Serialize, \
+       ::rustdds::__derive_support::serde::Deserialize, ::rustdds::DdsType)]
+    #[serde(crate = \"::rustdds::__derive_support::serde\")]
+    #[dds_type(name = \"geometry::Point\")]
+    pub struct Point {
+        #[dds_key]
+        pub id: i32,
+        pub x: f64,
+        pub y: f64,
+        pub label: String,
+        pub data: Vec<u8>,
+        pub matrix: [[i16; 3]; 2],
+    }
+}
+"
+    );
+  }
+
+  #[test]
+  fn names_and_scopes() {
+    let code = generate(
+      r#"
+      module Outer {
+        typedef sequence<long, 10> Numbers;
+        enum Color { RED, @value(5) LIGHT_BLUE };
+        module Inner {
+          struct Base { Numbers values; };
+        };
+      };
+      module Outer {
+        struct Derived : Inner::Base {
+          Color color;
+          ::Outer::Inner::Base nested;
+          long type;
+          unsigned long long bigValue;
+        };
+      };
+      "#,
+    )
+    .unwrap();
+    assert!(code.contains("pub mod outer {"));
+    assert!(code.contains("pub type Numbers = Vec<i32>;"));
+    assert!(code.contains("    Red,\n"));
+    assert!(code.contains("    LightBlue = 5,\n"));
+    assert!(code.contains("pub values: super::Numbers,"));
+    // Inherited member first, with the path relative to the derived struct
+    assert!(code.contains(
+      "pub struct Derived {
+        pub values: Numbers,
+        pub color: Color,
+        pub nested: inner::Base,
+        pub r#type: i32,
+        #[serde(rename = \"bigValue\")]
+        pub big_value: u64,
+    }"
+    ));
+    assert!(code.contains("#[dds_type(name = \"Outer::Derived\")]"));
+  }
+
+  #[test]
+  fn generate_union() {
+    let code = generate(
+      r#"
+      enum Kind { POINT, LABEL, NONE };
+      union Value switch (Kind) {
+        case POINT: long point;
+        case LABEL: string label;
+        default: octet other;
+      };
+      union Number switch (short) {
+        case 1: case 2: long small;
+        case -1: double negative;
+        default: boolean unknown;
+      };
+      "#,
+    )
+    .unwrap();
+    assert!(code.contains(
+      "#[dds_union(discriminator = \"Kind\")]
+pub enum Value {
+    #[dds_case(Kind::Point)]
+    Point(i32),
+    #[dds_case(Kind::Label)]
+    Label(String),
+    #[dds_default(Kind::None)]
+    Other(u8),
+}"
+    ));
+    assert!(code.contains(
+      "#[dds_union(discriminator = \"i16\")]
+pub enum Number {
+    #[dds_case(1, 2)]
+    Small(i32),
+    #[dds_case(-1)]
+    Negative(f64),
+    #[dds_default(0)]
+    Unknown(bool),
+}"
+    ));
+  }
+
+  #[test]
+  fn generate_bitmask_and_bitset() {
+    let code = generate(
+      r#"
+      @bit_bound(12)
+      bitmask Permissions { READ, WRITE, @position(11) EXECUTE };
+      bitmask Wide { FLAG };
+      bitset Header {
+        bitfield<3> version;
+        bitfield<1> urgent;
+        bitfield<2>;
+        bitfield<10, short> delta;
+        bitfield<4>;
+      };
+      "#,
+    )
+    .unwrap();
+    assert_eq!(
+      code,
+      "// Generated by rustdds::IdlCodegen from test.idl. Do not edit.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, ::rustdds::DdsBitmask)]
+#[dds_bitmask(bit_bound = 12)]
+pub struct Permissions(pub u16);
+
+impl Permissions {
+    pub const READ: Self = Self(1 << 0);
+    pub const WRITE: Self = Self(1 << 1);
+    pub const EXECUTE: Self = Self(1 << 11);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, ::rustdds::DdsBitmask)]
+#[dds_bitmask(bit_bound = 32)]
+pub struct Wide(pub u32);
+
+impl Wide {
+    pub const FLAG: Self = Self(1 << 0);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, ::rustdds::DdsBitset)]
+#[dds_bitset(bits = 20)]
+pub struct Header {
+    #[dds_bitfield(3)]
+    pub version: u8,
+    #[dds_bitfield(1)]
+    pub urgent: bool,
+    #[dds_bitfield(10, skip = 2)]
+    pub delta: i16,
+}
+"
+    );
+
+    let error = generate("@bit_bound(8) bitmask B { A, @position(8) B };").unwrap_err();
+    assert!(error.to_string().contains("outside the bit bound"));
+    let error = generate("bitset B { bitfield<9, octet> a; };").unwrap_err();
+    assert!(error.to_string().contains("Invalid type for bit field a"));
+  }
+
+  #[test]
+  fn invalid_idl() {
+    let error = generate("struct S {\n  Unknown u;\n};").unwrap_err();
+    assert_eq!(error.to_string(), "test.idl:2: Unknown is not defined");
+    let error = generate("struct S { long a[40]; };").unwrap_err();
+    assert!(matches!(error, IdlError::Unsupported { .. }));
+    let error = generate(
+      "union U switch (boolean) {\n case TRUE: long a;\n case FALSE: long b;\n default: long c; };",
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("no discriminator value left"));
+    let error = generate("struct S { long a; };\nstruct S { long b; };").unwrap_err();
+    assert_eq!(error.to_string(), "test.idl:2: S is defined twice");
+  }
+}
@@ -0,0 +1,957 @@
+// Lexer and recursive-descent parser for the supported subset of OMG IDL 4.
+//
+// The result is a syntax tree, in which names are not yet resolved. That is
+// done by the code generator.
+
+use super::{IdlError, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Ident(String),
+  Integer(u128),
+  Float(String),
+  Str(String),
+  Char(u8),
+  // "::", "<<", ">>", or a single punctuation character
+  Punct(&'static str),
+}
+
+struct Lexed {
+  token: Token,
+  line: usize,
+}
+
+const PUNCTUATION: &[&str] = &[
+  "::", "<<", ">>", "{", "}", "(", ")", "[", "]", "<", ">", ";", ",", ":", "=", "+", "-", "*", "/",
+  "%", "|", "&", "^", "~", "@",
+];
+
+fn tokenize(file: &str, source: &str) -> Result<Vec<Lexed>> {
+  let syntax_error = |line: usize, reason: String| IdlError::Syntax {
+    file: file.to_string(),
+    line,
+    reason,
+  };
+  let chars: Vec<char> = source.chars().collect();
+  let mut tokens = Vec::new();
+  let mut line = 1;
+  let mut at_line_start = true;
+  let mut i = 0;
+
+  while i < chars.len() {
+    let c = chars[i];
+    if c == '\n' {
+      line += 1;
+      at_line_start = true;
+      i += 1;
+      continue;
+    }
+    if c.is_whitespace() {
+      i += 1;
+      continue;
+    }
+    // Preprocessor directives, e.g. include guards and #pragma, are skipped.
+    if c == '#' && at_line_start {
+      while i < chars.len() && chars[i] != '\n' {
+        i += 1;
+      }
+      continue;
+    }
+    at_line_start = false;
+
+    let rest = &chars[i..];
+    if rest.starts_with(&['/', '/']) {
+      while i < chars.len() && chars[i] != '\n' {
+        i += 1;
+      }
+    } else if rest.starts_with(&['/', '*']) {
+      let start_line = line;
+      i += 2;
+      loop {
+        match chars.get(i) {
+          None => return Err(syntax_error(start_line, "Unterminated comment".to_string())),
+          Some('*') if chars.get(i + 1) == Some(&'/') => {
+            i += 2;
+            break;
+          }
+          Some('\n') => line += 1,
+          Some(_) => (),
+        }
+        i += 1;
+      }
+    } else if c.is_ascii_alphabetic() || c == '_' {
+      let start = i;
+      while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+        i += 1;
+      }
+      let ident: String = chars[start..i].iter().collect();
+      // A leading underscore escapes an IDL keyword.
+      let ident = ident.strip_prefix('_').map_or(ident.clone(), String::from);
+      tokens.push(Lexed {
+        token: Token::Ident(ident),
+        line,
+      });
+    } else if c.is_ascii_digit() {
+      let start = i;
+      while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+        // Exponent sign
+        if (chars[i] == 'e' || chars[i] == 'E')
+          && !chars[start..i].iter().any(|c| *c == 'x' || *c == 'X')
+          && matches!(chars.get(i + 1), Some('+') | Some('-'))
+        {
+          i += 1;
+        }
+        i += 1;
+      }
+      let text: String = chars[start..i].iter().collect();
+      let token = parse_number(&text)
+        .ok_or_else(|| syntax_error(line, format!("Invalid number {:?}", text)))?;
+      tokens.push(Lexed { token, line });
+    } else if c == '"' {
+      let mut value = String::new();
+      i += 1;
+      loop {
+        match chars.get(i) {
+          None | Some('\n') => return Err(syntax_error(line, "Unterminated string".to_string())),
+          Some('"') => break,
+          Some('\\') => {
+            let (escaped, length) = unescape(&chars[i..])
+              .ok_or_else(|| syntax_error(line, "Invalid escape sequence".to_string()))?;
+            value.push(escaped);
+            i += length;
+            continue;
+          }
+          Some(other) => value.push(*other),
+        }
+        i += 1;
+      }
+      i += 1;
+      tokens.push(Lexed {
+        token: Token::Str(value),
+        line,
+      });
+    } else if c == '\'' {
+      let (value, length) = match chars.get(i + 1) {
+        Some('\\') => unescape(&chars[i + 1..])
+          .ok_or_else(|| syntax_error(line, "Invalid escape sequence".to_string()))?,
+        Some(other) => (*other, 1),
+        None => return Err(syntax_error(line, "Unterminated character".to_string())),
+      };
+      i += 1 + length;
+      if chars.get(i) != Some(&'\'') || !value.is_ascii() {
+        return Err(syntax_error(line, "Invalid character literal".to_string()));
+      }
+      i += 1;
+      tokens.push(Lexed {
+        token: Token::Char(value as u8),
+        line,
+      });
+    } else {
+      let punct = PUNCTUATION
+        .iter()
+        .find(|p| rest.iter().zip(p.chars()).filter(|(a, b)| *a == b).count() == p.len())
+        .ok_or_else(|| syntax_error(line, format!("Unexpected character {:?}", c)))?;
+      i += punct.len();
+      tokens.push(Lexed {
+        token: Token::Punct(punct),
+        line,
+      });
+    }
+  }
+  Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Option<Token> {
+  if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+    u128::from_str_radix(hex, 16).ok().map(Token::Integer)
+  } else if text.contains(|c| matches!(c, '.' | 'e' | 'E')) {
+    text
+      .parse::<f64>()
+      .ok()
+      .map(|_| Token::Float(text.to_string()))
+  } else if text.len() > 1 && text.starts_with('0') {
+    u128::from_str_radix(&text[1..], 8).ok().map(Token::Integer)
+  } else {
+    text.parse().ok().map(Token::Integer)
+  }
+}
+
+// Escape sequence starting with a backslash. Returns the character and the
+// number of source characters used.
+fn unescape(chars: &[char]) -> Option<(char, usize)> {
+  let c = match chars.get(1)? {
+    'n' => '\n',
+    't' => '\t',
+    'r' => '\r',
+    '0' => '\0',
+    '\\' => '\\',
+    '\'' => '\'',
+    '"' => '"',
+    '?' => '?',
+    'x' => {
+      let digits: String = chars[2..]
+        .iter()
+        .take(2)
+        .take_while(|c| c.is_ascii_hexdigit())
+        .collect();
+      let value = u8::from_str_radix(&digits, 16).ok()?;
+      return Some((char::from(value), 2 + digits.len()));
+    }
+    _ => return None,
+  };
+  Some((c, 2))
+}
+
+// Syntax tree
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ScopedName {
+  // Starts with "::"
+  pub absolute: bool,
+  pub parts: Vec<String>,
+}
+
+impl std::fmt::Display for ScopedName {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    if self.absolute {
+      write!(f, "::")?;
+    }
+    write!(f, "{}", self.parts.join("::"))
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Primitive {
+  Boolean,
+  Octet,
+  Char,
+  Int8,
+  UInt8,
+  Int16,
+  UInt16,
+  Int32,
+  UInt32,
+  Int64,
+  UInt64,
+  Float,
+  Double,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TypeSpec {
+  Primitive(Primitive),
+  // Bounds are parsed, but not enforced.
+  String,
+  Sequence(Box<TypeSpec>),
+  Named(ScopedName),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ConstExpr {
+  Integer(u128),
+  Float(String),
+  Str(String),
+  Char(u8),
+  Bool(bool),
+  Name(ScopedName),
+  // '-', '+' or '~'
+  Unary(&'static str, Box<ConstExpr>),
+  Binary(&'static str, Box<ConstExpr>, Box<ConstExpr>),
+}
+
+#[derive(Debug)]
+pub(crate) struct Member {
+  pub name: String,
+  pub line: usize,
+  pub type_spec: TypeSpec,
+  pub dimensions: Vec<ConstExpr>,
+  pub key: bool,
+}
+
+#[derive(Debug)]
+pub(crate) struct Struct {
+  pub name: String,
+  pub line: usize,
+  pub base: Option<ScopedName>,
+  pub members: Vec<Member>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Enumerator {
+  pub name: String,
+  // From @value
+  pub value: Option<ConstExpr>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Enum {
+  pub name: String,
+  pub line: usize,
+  pub enumerators: Vec<Enumerator>,
+}
+
+#[derive(Debug)]
+pub(crate) struct UnionCase {
+  pub labels: Vec<ConstExpr>,
+  pub default: bool,
+  pub member: Member,
+}
+
+#[derive(Debug)]
+pub(crate) struct Union {
+  pub name: String,
+  pub line: usize,
+  pub discriminator: TypeSpec,
+  pub cases: Vec<UnionCase>,
+}
+
+#[derive(Debug)]
+pub(crate) struct BitValue {
+  pub name: String,
+  // From @position
+  pub position: Option<ConstExpr>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Bitmask {
+  pub name: String,
+  pub line: usize,
+  // From @bit_bound
+  pub bit_bound: Option<ConstExpr>,
+  pub values: Vec<BitValue>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Bitfield {
+  // None for unused bits
+  pub name: Option<String>,
+  pub line: usize,
+  pub width: ConstExpr,
+  pub type_spec: Option<TypeSpec>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Bitset {
+  pub name: String,
+  pub line: usize,
+  pub bitfields: Vec<Bitfield>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Typedef {
+  pub name: String,
+  pub line: usize,
+  pub type_spec: TypeSpec,
+  pub dimensions: Vec<ConstExpr>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Const {
+  pub name: String,
+  pub line: usize,
+  pub type_spec: TypeSpec,
+  pub value: ConstExpr,
+}
+
+#[derive(Debug)]
+pub(crate) enum Definition {
+  Module {
+    name: String,
+    definitions: Vec<Definition>,
+  },
+  Struct(Struct),
+  Union(Union),
+  Enum(Enum),
+  Bitmask(Bitmask),
+  Bitset(Bitset),
+  Typedef(Typedef),
+  Const(Const),
+}
+
+struct Annotation {
+  name: String,
+  arguments: Vec<ConstExpr>,
+}
+
+pub(crate) fn parse(file: &str, source: &str) -> Result<Vec<Definition>> {
+  let mut parser = Parser {
+    file,
+    tokens: tokenize(file, source)?,
+    position: 0,
+  };
+  let mut definitions = Vec::new();
+  while !parser.at_end() {
+    parser.definition(&mut definitions)?;
+  }
+  Ok(definitions)
+}
+
+struct Parser<'a> {
+  file: &'a str,
+  tokens: Vec<Lexed>,
+  position: usize,
+}
+
+impl Parser<'_> {
+  fn at_end(&self) -> bool {
+    self.position >= self.tokens.len()
+  }
+
+  fn line(&self) -> usize {
+    self
+      .tokens
+      .get(self.position)
+      .or_else(|| self.tokens.last())
+      .map_or(1, |t| t.line)
+  }
+
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.position).map(|t| &t.token)
+  }
+
+  fn next(&mut self) -> Result<Token> {
+    let token = self
+      .peek()
+      .cloned()
+      .ok_or_else(|| self.syntax_error("Unexpected end of file"))?;
+    self.position += 1;
+    Ok(token)
+  }
+
+  fn syntax_error(&self, reason: &str) -> IdlError {
+    IdlError::Syntax {
+      file: self.file.to_string(),
+      line: self.line(),
+      reason: reason.to_string(),
+    }
+  }
+
+  fn unsupported(&self, feature: &str) -> IdlError {
+    IdlError::Unsupported {
+      file: self.file.to_string(),
+      line: self.line(),
+      feature: feature.to_string(),
+    }
+  }
+
+  fn is_punct(&self, punct: &str) -> bool {
+    matches!(self.peek(), Some(Token::Punct(p)) if *p == punct)
+  }
+
+  fn is_keyword(&self, keyword: &str) -> bool {
+    matches!(self.peek(), Some(Token::Ident(i)) if i == keyword)
+  }
+
+  fn eat_punct(&mut self, punct: &str) -> bool {
+    let found = self.is_punct(punct);
+    if found {
+      self.position += 1;
+    }
+    found
+  }
+
+  fn eat_keyword(&mut self, keyword: &str) -> bool {
+    let found = self.is_keyword(keyword);
+    if found {
+      self.position += 1;
+    }
+    found
+  }
+
+  fn expect_punct(&mut self, punct: &str) -> Result<()> {
+    if self.eat_punct(punct) {
+      Ok(())
+    } else {
+      Err(self.syntax_error(&format!("Expected \"{}\"", punct)))
+    }
+  }
+
+  fn identifier(&mut self) -> Result<String> {
+    match self.peek() {
+      Some(Token::Ident(ident)) => {
+        let ident = ident.clone();
+        self.position += 1;
+        Ok(ident)
+      }
+      _ => Err(self.syntax_error("Expected an identifier")),
+    }
+  }
+
+  fn scoped_name(&mut self) -> Result<ScopedName> {
+    let absolute = self.eat_punct("::");
+    let mut parts = vec![self.identifier()?];
+    while self.eat_punct("::") {
+      parts.push(self.identifier()?);
+    }
+    Ok(ScopedName { absolute, parts })
+  }
+
+  fn annotations(&mut self) -> Result<Vec<Annotation>> {
+    let mut annotations = Vec::new();
+    while self.eat_punct("@") {
+      let name = self.scoped_name()?.parts.join("::");
+      let mut arguments = Vec::new();
+      if self.eat_punct("(") {
+        while !self.eat_punct(")") {
+          // Named arguments, e.g. @range(min = 0, max = 10), are skipped.
+          if matches!(
+            self.tokens.get(self.position + 1).map(|t| &t.token),
+            Some(Token::Punct("="))
+          ) {
+            self.position += 2;
+          }
+          arguments.push(self.const_expr()?);
+          if !self.is_punct(")") {
+            self.expect_punct(",")?;
+          }
+        }
+      }
+      annotations.push(Annotation { name, arguments });
+    }
+    Ok(annotations)
+  }
+
+  fn definition(&mut self, definitions: &mut Vec<Definition>) -> Result<()> {
+    // Annotations of types, e.g. @topic, @final, do not affect CDR encoding.
+    // @bit_bound of a bitmask does.
+    let annotations = self.annotations()?;
+    let line = self.line();
+    let keyword = self.identifier()?;
+    match keyword.as_str() {
+      "module" => {
+        let name = self.identifier()?;
+        self.expect_punct("{")?;
+        let mut module_definitions = Vec::new();
+        while !self.eat_punct("}") {
+          self.definition(&mut module_definitions)?;
+        }
+        definitions.push(Definition::Module {
+          name,
+          definitions: module_definitions,
+        });
+      }
+      "struct" => {
+        let name = self.identifier()?;
+        // Forward declaration
+        if !self.is_punct(";") {
+          let base = if self.eat_punct(":") {
+            Some(self.scoped_name()?)
+          } else {
+            None
+          };
+          self.expect_punct("{")?;
+          let mut members = Vec::new();
+          while !self.eat_punct("}") {
+            self.members(&mut members)?;
+          }
+          definitions.push(Definition::Struct(Struct {
+            name,
+            line,
+            base,
+            members,
+          }));
+        }
+      }
+      "union" => {
+        let name = self.identifier()?;
+        if !self.is_punct(";") {
+          definitions.push(Definition::Union(self.union_body(name, line)?));
+        }
+      }
+      "enum" => {
+        let name = self.identifier()?;
+        self.expect_punct("{")?;
+        let mut enumerators = Vec::new();
+        loop {
+          let annotations = self.annotations()?;
+          let value = annotations
+            .into_iter()
+            .find(|a| a.name == "value")
+            .and_then(|a| a.arguments.into_iter().next());
+          enumerators.push(Enumerator {
+            name: self.identifier()?,
+            value,
+          });
+          if !self.eat_punct(",") {
+            break;
+          }
+        }
+        self.expect_punct("}")?;
+        definitions.push(Definition::Enum(Enum {
+          name,
+          line,
+          enumerators,
+        }));
+      }
+      "typedef" => {
+        let type_spec = self.type_spec()?;
+        loop {
+          let (name, dimensions) = self.declarator()?;
+          definitions.push(Definition::Typedef(Typedef {
+            name,
+            line,
+            type_spec: type_spec.clone(),
+            dimensions,
+          }));
+          if !self.eat_punct(",") {
+            break;
+          }
+        }
+      }
+      "const" => {
+        let type_spec = self.type_spec()?;
+        let name = self.identifier()?;
+        self.expect_punct("=")?;
+        let value = self.const_expr()?;
+        definitions.push(Definition::Const(Const {
+          name,
+          line,
+          type_spec,
+          value,
+        }));
+      }
+      "bitmask" => {
+        let name = self.identifier()?;
+        self.expect_punct("{")?;
+        let mut values = Vec::new();
+        loop {
+          let position = self
+            .annotations()?
+            .into_iter()
+            .find(|a| a.name == "position")
+            .and_then(|a| a.arguments.into_iter().next());
+          values.push(BitValue {
+            name: self.identifier()?,
+            position,
+          });
+          if !self.eat_punct(",") {
+            break;
+          }
+        }
+        self.expect_punct("}")?;
+        let bit_bound = annotations
+          .into_iter()
+          .find(|a| a.name == "bit_bound")
+          .and_then(|a| a.arguments.into_iter().next());
+        definitions.push(Definition::Bitmask(Bitmask {
+          name,
+          line,
+          bit_bound,
+          values,
+        }));
+      }
+      "bitset" => {
+        let name = self.identifier()?;
+        if self.is_punct(":") {
+          return Err(self.unsupported("bitset inheritance"));
+        }
+        self.expect_punct("{")?;
+        let mut bitfields = Vec::new();
+        while !self.eat_punct("}") {
+          self.bitfields(&mut bitfields)?;
+        }
+        definitions.push(Definition::Bitset(Bitset {
+          name,
+          line,
+          bitfields,
+        }));
+      }
+      "interface" | "valuetype" | "exception" | "native" | "component" | "home" | "eventtype"
+      | "porttype" | "connector" | "abstract" | "local" | "custom" => {
+        return Err(self.unsupported(&format!("\"{}\" definitions", keyword)))
+      }
+      _ => return Err(self.syntax_error(&format!("Unexpected \"{}\"", keyword))),
+    }
+    self.expect_punct(";")
+  }
+
+  fn union_body(&mut self, name: String, line: usize) -> Result<Union> {
+    if !self.eat_keyword("switch") {
+      return Err(self.syntax_error("Expected \"switch\""));
+    }
+    self.expect_punct("(")?;
+    self.annotations()?;
+    let discriminator = self.type_spec()?;
+    self.expect_punct(")")?;
+    self.expect_punct("{")?;
+    let mut cases = Vec::new();
+    while !self.eat_punct("}") {
+      let mut labels = Vec::new();
+      let mut default = false;
+      loop {
+        if self.eat_keyword("case") {
+          labels.push(self.const_expr()?);
+        } else if self.eat_keyword("default") {
+          default = true;
+        } else {
+          break;
+        }
+        self.expect_punct(":")?;
+      }
+      if labels.is_empty() && !default {
+        return Err(self.syntax_error("Expected \"case\" or \"default\""));
+      }
+      let mut members = Vec::new();
+      self.members(&mut members)?;
+      if members.len() != 1 {
+        return Err(self.syntax_error("A union case must have exactly one member"));
+      }
+      cases.push(UnionCase {
+        labels,
+        default,
+        member: members.remove(0),
+      });
+    }
+    Ok(Union {
+      name,
+      line,
+      discriminator,
+      cases,
+    })
+  }
+
+  // Member declaration, possibly with several declarators, e.g. "long x, y;"
+  fn members(&mut self, members: &mut Vec<Member>) -> Result<()> {
+    let annotations = self.annotations()?;
+    let key = annotations
+      .iter()
+      .any(|a| a.name == "key" && !matches!(a.arguments.first(), Some(ConstExpr::Bool(false))));
+    if let Some(annotation) = annotations
+      .iter()
+      .find(|a| a.name == "optional" || a.name == "external")
+    {
+      return Err(self.unsupported(&format!("@{} members", annotation.name)));
+    }
+    let line = self.line();
+    let type_spec = self.type_spec()?;
+    loop {
+      let (name, dimensions) = self.declarator()?;
+      members.push(Member {
+        name,
+        line,
+        type_spec: type_spec.clone(),
+        dimensions,
+        key,
+      });
+      if !self.eat_punct(",") {
+        break;
+      }
+    }
+    self.expect_punct(";")
+  }
+
+  // Bit field declaration, e.g. "bitfield<3> a, b;", "bitfield<4, short> c;"
+  // or "bitfield<2>;" for unused bits
+  fn bitfields(&mut self, bitfields: &mut Vec<Bitfield>) -> Result<()> {
+    self.annotations()?;
+    let line = self.line();
+    if !self.eat_keyword("bitfield") {
+      return Err(self.syntax_error("Expected \"bitfield\""));
+    }
+    self.expect_punct("<")?;
+    let width = self.const_expr()?;
+    let type_spec = if self.eat_punct(",") {
+      Some(self.type_spec()?)
+    } else {
+      None
+    };
+    self.expect_punct(">")?;
+    if self.eat_punct(";") {
+      bitfields.push(Bitfield {
+        name: None,
+        line,
+        width,
+        type_spec,
+      });
+      return Ok(());
+    }
+    loop {
+      bitfields.push(Bitfield {
+        name: Some(self.identifier()?),
+        line,
+        width: width.clone(),
+        type_spec: type_spec.clone(),
+      });
+      if !self.eat_punct(",") {
+        break;
+      }
+    }
+    self.expect_punct(";")
+  }
+
+  // Name with optional array dimensions, e.g. "matrix[3][4]"
+  fn declarator(&mut self) -> Result<(String, Vec<ConstExpr>)> {
+    let name = self.identifier()?;
+    let mut dimensions = Vec::new();
+    while self.eat_punct("[") {
+      dimensions.push(self.const_expr()?);
+      self.expect_punct("]")?;
+    }
+    Ok((name, dimensions))
+  }
+
+  fn type_spec(&mut self) -> Result<TypeSpec> {
+    use Primitive::*;
+
+    if self.is_punct("::") {
+      return Ok(TypeSpec::Named(self.scoped_name()?));
+    }
+    let start = self.position;
+    let word = self.identifier()?;
+    let primitive = match word.as_str() {
+      "boolean" => Boolean,
+      "octet" => Octet,
+      "char" => Char,
+      "int8" => Int8,
+      "uint8" => UInt8,
+      "int16" | "short" => Int16,
+      "uint16" => UInt16,
+      "int32" => Int32,
+      "uint32" => UInt32,
+      "int64" => Int64,
+      "uint64" => UInt64,
+      "float" => Float,
+      "double" => Double,
+      "long" if self.eat_keyword("long") => Int64,
+      "long" if self.is_keyword("double") => return Err(self.unsupported("long double")),
+      "long" => Int32,
+      "unsigned" => match self.identifier()?.as_str() {
+        "short" => UInt16,
+        "long" if self.eat_keyword("long") => UInt64,
+        "long" => UInt32,
+        _ => return Err(self.syntax_error("Expected \"short\" or \"long\"")),
+      },
+      "string" => {
+        self.bound()?;
+        return Ok(TypeSpec::String);
+      }
+      "sequence" => {
+        self.expect_punct("<")?;
+        let element = self.type_spec()?;
+        if self.eat_punct(",") {
+          self.const_expr()?;
+        }
+        self.expect_punct(">")?;
+        return Ok(TypeSpec::Sequence(Box::new(element)));
+      }
+      "wchar" | "wstring" | "any" | "fixed" | "map" | "Object" | "ValueBase" => {
+        return Err(self.unsupported(&format!("type \"{}\"", word)))
+      }
+      _ => {
+        self.position = start;
+        return Ok(TypeSpec::Named(self.scoped_name()?));
+      }
+    };
+    Ok(TypeSpec::Primitive(primitive))
+  }
+
+  // Optional "<bound>" of a string
+  fn bound(&mut self) -> Result<()> {
+    if self.eat_punct("<") {
+      self.const_expr()?;
+      self.expect_punct(">")?;
+    }
+    Ok(())
+  }
+
+  fn const_expr(&mut self) -> Result<ConstExpr> {
+    self.binary_expr(0)
+  }
+
+  // Binary operators by increasing precedence
+  const BINARY_OPERATORS: &'static [&'static [&'static str]] = &[
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+  ];
+
+  fn binary_expr(&mut self, level: usize) -> Result<ConstExpr> {
+    if level == Self::BINARY_OPERATORS.len() {
+      return self.unary_expr();
+    }
+    let mut left = self.binary_expr(level + 1)?;
+    loop {
+      let operator = match self.peek() {
+        Some(Token::Punct(p)) if Self::BINARY_OPERATORS[level].contains(p) => *p,
+        _ => return Ok(left),
+      };
+      self.position += 1;
+      let right = self.binary_expr(level + 1)?;
+      left = ConstExpr::Binary(operator, Box::new(left), Box::new(right));
+    }
+  }
+
+  fn unary_expr(&mut self) -> Result<ConstExpr> {
+    for operator in ["-", "+", "~"] {
+      if self.eat_punct(operator) {
+        return Ok(ConstExpr::Unary(operator, Box::new(self.unary_expr()?)));
+      }
+    }
+    if self.eat_punct("(") {
+      let expr = self.const_expr()?;
+      self.expect_punct(")")?;
+      return Ok(expr);
+    }
+    if self.is_punct("::") {
+      return Ok(ConstExpr::Name(self.scoped_name()?));
+    }
+    Ok(match self.next()? {
+      Token::Integer(value) => ConstExpr::Integer(value),
+      Token::Float(value) => ConstExpr::Float(value),
+      Token::Str(value) => ConstExpr::Str(value),
+      Token::Char(value) => ConstExpr::Char(value),
+      Token::Ident(word) if word == "TRUE" => ConstExpr::Bool(true),
+      Token::Ident(word) if word == "FALSE" => ConstExpr::Bool(false),
+      Token::Ident(_) => {
+        self.position -= 1;
+        ConstExpr::Name(self.scoped_name()?)
+      }
+      Token::Punct(_) => {
+        self.position -= 1;
+        return Err(self.syntax_error("Expected a constant expression"));
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tokens() {
+    let tokens = tokenize(
+      "test",
+      "#ifndef X\n// comment\nconst long _struct = 0x1F << 017; /* multi\nline */ 'a' \"s\\n\" 1.5e-3",
+    )
+    .unwrap();
+    let tokens: Vec<_> = tokens.into_iter().map(|t| (t.token, t.line)).collect();
+    assert_eq!(
+      tokens,
+      vec![
+        (Token::Ident("const".to_string()), 3),
+        (Token::Ident("long".to_string()), 3),
+        (Token::Ident("struct".to_string()), 3),
+        (Token::Punct("="), 3),
+        (Token::Integer(31), 3),
+        (Token::Punct("<<"), 3),
+        (Token::Integer(15), 3),
+        (Token::Punct(";"), 3),
+        (Token::Char(b'a'), 4),
+        (Token::Str("s\n".to_string()), 4),
+        (Token::Float("1.5e-3".to_string()), 4),
+      ]
+    );
+  }
+
+  #[test]
+  fn syntax_errors() {
+    let error = parse("shapes.idl", "module m {\n struct S { long x }; };").unwrap_err();
+    assert_eq!(error.to_string(), "shapes.idl:2: Expected \";\"");
+    let error = parse("shapes.idl", "struct S {\n  wstring name;\n};").unwrap_err();
+    assert!(matches!(error, IdlError::Unsupported { line: 2, .. }));
+  }
+}
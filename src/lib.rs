@@ -53,6 +53,13 @@
 //! serializable/deserializable.
 //! * If your data is to be communicated over a WithKey topic, the payload data
 //!   type must implement [`Keyed`] trait from this crate.
+//! * `#[derive(DdsType)]` implements [`Keyed`] from fields marked `#[dds_key]`
+//!   and gives the type its DDS type name. See [`DdsType`](trait@DdsType).
+//! * IDL enums with explicit values and IDL unions can be mapped to Rust
+//!   enums with `#[derive(DdsEnum)]` and `#[derive(DdsUnion)]`. IDL bitmasks
+//!   and bitsets map to structs with `#[derive(DdsBitmask)]` and
+//!   `#[derive(DdsBitset)]`, which serialize as an unsigned integer sized by
+//!   the bit bound or the bit fields.
 //! * If you are using [CDR serialization](https://en.wikipedia.org/wiki/Common_Data_Representation)
 //!   ([specification, Section 15.3](https://www.omg.org/cgi-bin/doc?formal/02-06-51)) , which
 //!   is the DDS default, then use [`CDRSerializerAdapter`] and
@@ -140,13 +147,18 @@
   // but that may break code flow.
 )]
 
+// Lets derive macros refer to this crate as ::rustdds also internally.
+extern crate self as rustdds;
+
 #[macro_use]
 mod serialization_test;
 #[macro_use]
 mod checked_impl;
 mod discovery;
+mod idl;
 mod messages;
 mod network;
+mod security;
 pub(crate) mod structure;
 
 #[cfg(test)]
@@ -161,30 +173,113 @@ pub mod serialization;
 // Re-exports from crate root to simplify usage
 #[doc(inline)]
 pub use dds::{
-  participant::DomainParticipant,
-  pubsub::{Publisher, Subscriber},
+  blob_transfer::{BlobProgress, BlobReceiver, BlobSender, ReceivedBlob},
+  byte_stream::ByteStream,
+  clock::{Clock, SystemClock},
+  compatibility::{ProtocolCompatibility, VendorWorkaround, VendorWorkarounds},
+  contentfilteredtopic::ContentFilteredTopic,
+  dynamic_data::{
+    DynamicData, DynamicDataDeserializerAdapter, DynamicMember, DynamicType, DynamicTypeSource,
+    DynamicUnionCase, DynamicValue,
+  },
+  flow_controller::FlowController,
+  health::{ParticipantHealth, ProtocolThread, ThreadHealth},
+  listener::{
+    DataReaderListener, DataWriterListener, ParticipantListener, StatusKind, StatusMask,
+    TopicListener,
+  },
+  loan::{LoanAdapter, Loaned, Pod, SampleLoan},
+  multitopic::{MultiTopic, MultiTopicDataReader},
+  participant::{
+    DiscoveryListener, DomainParticipant, DomainParticipantBuilder,
+    DomainParticipantStatusListener, ParticipantBuiltinTopicReader, PublicationBuiltinTopicReader,
+    SubscriptionBuiltinTopicReader,
+  },
+  participant_factory::DomainParticipantFactory,
+  pubsub::{GroupAccessReader, Publisher, Subscriber},
   qos,
-  qos::{policy, QosPolicies, QosPolicyBuilder},
+  qos::{
+    policy, PublisherQos, QosPolicies, QosPolicyBuilder, ReaderQos, SubscriberQos, TopicQos,
+    WriterQos,
+  },
+  querycondition::QueryCondition,
   readcondition::ReadCondition,
+  recording::{Player, RecordedTopic, Recorder},
+  request_reply::{Replier, Requester},
   sampleinfo::{InstanceState, NotAliveGenerationCounts, SampleInfo, SampleState, ViewState},
+  simulation::SimulatedClock,
   statusevents::StatusEvented,
+  threads::{ParticipantDriver, ThreadConfig},
   topic::{Topic, TopicDescription, TopicKind},
-  traits::{Key, Keyed, RTPSEntity},
+  traits::{DdsType, InstanceHandle, Key, Keyed, RTPSEntity},
+  type_registry::{RegisteredType, TypeCodec, TypeRegistry},
   typedesc::TypeDesc,
-  with_key::{WriteOptions, WriteOptionsBuilder},
+  waitset::{ConditionId, GuardCondition, WaitSet},
+  with_key::{PendingAcknowledgments, WriteOptions, WriteOptionsBuilder},
+};
+pub use discovery::{
+  authentication::ParticipantAuthenticator,
+  static_endpoint::StaticEndpoint,
+  type_lookup::{EquivalenceHash, TypeIdentifier, TypeObject},
+};
+pub use network::{
+  interface_filter::{InterfaceSelector, ParseInterfaceSelectorError},
+  port_mapping::PortMapping,
+  transport::{Transport, TransportKind, TransportListener},
 };
+#[cfg(feature = "statistics")]
+#[doc(inline)]
+pub use dds::statistics::{ParticipantStatistics, ReaderStatistics, WriterStatistics};
+pub use security::types::{BinaryProperty, DataHolder, Property};
+#[cfg(feature = "security")]
+pub use security::{
+  access_control::{PermissionsCredentials, ProtectionKind, TopicAccess, TopicProtection},
+  authentication::{HandshakeProgress, IdentityCredentials, RemoteCredentials, SharedSecret},
+  cryptographic::KeyRotationPolicy,
+  generic_message::{MessageIdentity, ParticipantGenericMessage},
+  plugins::{AccessControlPlugin, AuthenticationPlugin, CryptographicPlugin},
+  SecurityError,
+};
+#[cfg(feature = "json")]
+#[doc(inline)]
+pub use dds::json_mirror::{JsonMirror, JsonSample};
+pub use idl::{IdlCodegen, IdlError};
+#[cfg(feature = "qos_provider")]
+#[doc(inline)]
+pub use dds::qos_provider::{QosProvider, QosProviderError};
+pub use messages::{protocol_version::ProtocolVersion, vendor_id::VendorId};
 /// Needed to specify serialized data representation in case it is other than
 /// CDR.
 pub use messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier;
 #[doc(inline)]
 pub use serialization::{
   CDRDeserializerAdapter, CDRSerializerAdapter, CdrDeserializer, CdrSerializer,
+  RawDeserializerAdapter, RawSample, RawSerializerAdapter, SerializedSample,
+  Xcdr2DeserializerAdapter, Xcdr2SerializerAdapter,
 };
 pub use structure::{
-  duration::Duration, guid::GUID, sequence_number::SequenceNumber, time::Timestamp,
+  duration::Duration,
+  guid::{EntityId, EntityKind, GuidPrefix, ParseGuidError, GUID},
+  locator::Locator,
+  sequence_number::{SequenceNumber, SequenceNumberRange},
+  time::Timestamp,
 };
 // re-export from a helper crate
 pub use cdr_encoding_size::CdrEncodingSize;
+/// Derive macro for [`DdsType`](trait@DdsType) and [`Keyed`].
+#[cfg(feature = "derive")]
+pub use rustdds_derive::DdsType;
+#[cfg(feature = "derive")]
+pub use rustdds_derive::{DdsBitmask, DdsBitset, DdsEnum, DdsUnion};
+
+// Items used by code generated with rustdds-derive. Not a public API.
+#[doc(hidden)]
+pub mod __derive_support {
+  pub use serde;
+  pub use cdr_encoding_size::{CdrEncodingMaxSize, CdrEncodingSize};
+
+  pub use crate::dds::traits::{DdsType, Key, Keyed};
+}
 
 /// Components used to access NO_KEY Topics
 pub mod no_key {
@@ -199,3 +294,60 @@ pub mod with_key {
 pub mod rpc {
   pub use crate::structure::rpc::*;
 }
+
+/// Owned async adapters of DataReaders and DataWriters, for tokio
+/// applications.
+///
+/// The adapters own their DataReader or DataWriter, so that they can be moved
+/// into spawned tasks. Their futures are woken by the DomainParticipant event
+/// loop, so they do not need a thread of their own, and they do not block the
+/// executor. To run the event loop itself on a tokio runtime, see
+/// [`DomainParticipantBuilder::tokio_runtime`](crate::DomainParticipantBuilder::tokio_runtime).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use rustdds::*;
+/// # use rustdds::tokio_adapters::{AsyncDataWriter, DataReaderStream};
+/// # use serde::{Deserialize, Serialize};
+/// use futures::StreamExt;
+///
+/// #[derive(Serialize, Deserialize, Debug)]
+/// struct Reading {
+///   value: f64,
+/// }
+///
+/// # async fn run() -> rustdds::dds::Result<()> {
+/// let participant = DomainParticipantBuilder::new(0)
+///   .tokio_runtime(tokio::runtime::Handle::current())
+///   .build()?;
+/// let qos = QosPolicyBuilder::new()
+///   .reliability(policy::Reliability::Reliable {
+///     max_blocking_time: Duration::from_secs(1),
+///   })
+///   .build();
+/// let topic = participant.create_topic(
+///   "readings".to_string(),
+///   "Reading".to_string(),
+///   &qos,
+///   TopicKind::NoKey,
+/// )?;
+/// let subscriber = participant.create_subscriber(&qos)?;
+/// let reader = subscriber.create_datareader_no_key_cdr::<Reading>(&topic, None)?;
+/// let mut samples = DataReaderStream::new(reader);
+/// tokio::spawn(async move {
+///   while let Some(Ok((reading, info))) = samples.next().await {
+///     println!("{:?} from {:?}", reading, info.writer_guid());
+///   }
+/// });
+///
+/// let publisher = participant.create_publisher(&qos)?;
+/// let writer = AsyncDataWriter::new(publisher.create_datawriter_no_key_cdr(&topic, None)?);
+/// writer.write_reliably(Reading { value: 1.5 }, None).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "tokio")]
+pub mod tokio_adapters {
+  pub use crate::dds::tokio_adapters::*;
+}
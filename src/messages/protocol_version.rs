@@ -1,8 +1,11 @@
 use speedy::{Readable, Writable};
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "discovery")]
 use crate::structure::parameter_id::ParameterId;
 
+/// Version of the RTPS protocol, which participants announce in the header of
+/// each message and in SPDP.
 #[derive(
   Debug, PartialOrd, PartialEq, Ord, Eq, Readable, Writable, Serialize, Deserialize, Clone, Copy,
 )]
@@ -22,7 +25,6 @@ impl ProtocolVersion {
   pub const PROTOCOLVERSION_2_0: Self = Self { major: 2, minor: 0 };
   #[allow(dead_code)] // Specification defines this, but not necessarily used.
   pub const PROTOCOLVERSION_2_1: Self = Self { major: 2, minor: 1 };
-  #[allow(dead_code)] // Specification defines this, but not necessarily used.
   pub const PROTOCOLVERSION_2_2: Self = Self { major: 2, minor: 2 };
   #[allow(dead_code)] // Specification defines this, but not necessarily used.
   pub const PROTOCOLVERSION_2_3: Self = Self { major: 2, minor: 3 };
@@ -35,6 +37,7 @@ impl Default for ProtocolVersion {
   }
 }
 
+#[cfg(feature = "discovery")]
 #[derive(Serialize, Deserialize)]
 pub struct ProtocolVersionData {
   parameter_id: ParameterId,
@@ -42,6 +45,7 @@ pub struct ProtocolVersionData {
   protocol_version: ProtocolVersion,
 }
 
+#[cfg(feature = "discovery")]
 impl ProtocolVersionData {
   pub fn from(protocol_version: ProtocolVersion) -> Self {
     Self {
@@ -77,6 +77,12 @@ impl Data {
     // writerSN (8) = 20 bytes
     // of which 16 bytes is after octetsToInlineQos field.
     let rtps_v23_data_header_size: u16 = 16;
+    if octets_to_inline_qos < rtps_v23_data_header_size {
+      return Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Data has too low octetsToInlineQos",
+      ));
+    }
     // There may be some extra data between writerSN and inlineQos, if the header is
     // extended in future versions. But as of RTPS v2.3 , extra_octets should be
     // always zero.
@@ -93,6 +99,13 @@ impl Data {
       None
     };
 
+    if cursor.position() > buffer.len() as u64 {
+      return Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "Data octetsToInlineQos points past the end of submessage",
+      ));
+    }
+
     let payload = if expect_data {
       let p = SerializedPayload::from_bytes(&buffer.clone().split_off(cursor.position() as usize))?;
       Some(p)
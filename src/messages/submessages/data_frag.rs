@@ -1,6 +1,5 @@
 use std::io;
 
-use log::debug;
 use speedy::{Context, Error, Readable, Writable, Writer};
 use enumflags2::BitFlags;
 use bytes::Bytes;
@@ -116,13 +115,13 @@ impl DataFrag {
 
     // Skip any possible fields we do not know about.
     let rtps_v23_header_size: u16 = 7 * 4;
-    let extra_octets = octets_to_inline_qos - rtps_v23_header_size;
     if octets_to_inline_qos < rtps_v23_header_size {
       return Err(io::Error::new(
         io::ErrorKind::Other,
         "DataFrag has too low octetsToInlineQos",
       ));
     }
+    let extra_octets = octets_to_inline_qos - rtps_v23_header_size;
     cursor.set_position(cursor.position() + u64::from(extra_octets));
 
     let inline_qos = if expect_qos {
@@ -134,6 +133,13 @@ impl DataFrag {
       None
     };
 
+    if cursor.position() > buffer.len() as u64 {
+      return Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "DataFrag octetsToInlineQos points past the end of submessage",
+      ));
+    }
+
     // Payload should be always present, be it data or key fragments.
     let serialized_payload = buffer.clone().split_off(cursor.position() as usize);
 
@@ -154,14 +160,10 @@ impl DataFrag {
 impl<C: Context> Writable<C> for DataFrag {
   fn write_to<T: ?Sized + Writer<C>>(&self, writer: &mut T) -> Result<(), C::Error> {
     writer.write_u16(0)?;
-    if self.inline_qos.is_some() && !self.inline_qos.as_ref().unwrap().parameters.is_empty() {
-      debug!("self.inline_qos {:?}", self.inline_qos);
-      todo!()
-    } else if self.inline_qos.is_some() && self.inline_qos.as_ref().unwrap().parameters.is_empty()
-      || self.inline_qos.is_none()
-    {
-      writer.write_u16(24)?;
-    }
+    // octetsToInlineQos: readerId + writerId + writerSN + fragmentStartingNum +
+    // fragmentsInSubmessage + fragmentSize + sampleSize. Inline QoS, if present,
+    // follows them immediately.
+    writer.write_u16(28)?;
     writer.write_value(&self.reader_id)?;
     writer.write_value(&self.writer_id)?;
     writer.write_value(&self.writer_sn)?;
@@ -169,8 +171,8 @@ impl<C: Context> Writable<C> for DataFrag {
     writer.write_value(&self.fragments_in_submessage)?;
     writer.write_value(&self.fragment_size)?;
     writer.write_value(&self.data_size)?;
-    if self.inline_qos.is_some() && !self.inline_qos.as_ref().unwrap().parameters.is_empty() {
-      writer.write_value(&self.inline_qos)?;
+    if let Some(inline_qos) = self.inline_qos.as_ref() {
+      writer.write_value(inline_qos)?;
     }
     writer.write_bytes(&self.serialized_payload)?;
     Ok(())
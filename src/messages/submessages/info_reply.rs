@@ -1,11 +1,23 @@
-use speedy::{Readable, Writable};
+use std::io;
 
-use crate::structure::locator::Locator;
+use enumflags2::BitFlags;
+use speedy::{Context, Endianness, Readable, Writable, Writer};
+
+use crate::{
+  messages::submessages::submessages::SubmessageHeader,
+  serialization::{SubMessage, SubmessageBody},
+  structure::locator::Locator,
+};
+use super::{
+  submessage::InterpreterSubmessage,
+  submessage_flag::{endianness_flag, FromEndianness, INFOREPLY_Flags},
+  submessage_kind::SubmessageKind,
+};
 
 /// This message is sent from an RTPS Reader to an RTPS Writer.
 /// It contains explicit information on where to send a reply
 /// to the Submessages that follow it within the same message.
-#[derive(Debug, PartialEq, Eq, Clone, Readable, Writable)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct InfoReply {
   /// Indicates an alternative set of unicast addresses that
   /// the Writershould use to reach the Readers when
@@ -19,3 +31,111 @@ pub struct InfoReply {
   /// Only present when the MulticastFlag is set.
   pub multicast_locator_list: Option<Vec<Locator>>,
 }
+
+impl InfoReply {
+  // The presence of the multicast list is told by the flags, so this cannot
+  // be Readable.
+  pub fn deserialize(buffer: &[u8], flags: BitFlags<INFOREPLY_Flags>) -> io::Result<Self> {
+    let endianness = endianness_flag(flags.bits());
+    let mut cursor = io::Cursor::new(buffer);
+
+    let unicast_locator_list = read_locator_list(endianness, &mut cursor)?;
+    let multicast_locator_list = if flags.contains(INFOREPLY_Flags::Multicast) {
+      Some(read_locator_list(endianness, &mut cursor)?)
+    } else {
+      None
+    };
+    Ok(Self {
+      unicast_locator_list,
+      multicast_locator_list,
+    })
+  }
+
+  pub fn len_serialized(&self) -> usize {
+    // LocatorList is a count followed by 24-byte locators
+    let list_len = |list: &[Locator]| 4 + 24 * list.len();
+    list_len(&self.unicast_locator_list)
+      + self
+        .multicast_locator_list
+        .as_ref()
+        .map_or(0, |list| list_len(list))
+  }
+
+  #[allow(dead_code)] // RustDDS replies to these, but does not send them.
+  pub fn create_submessage(self, endianness: Endianness) -> SubMessage {
+    let mut flags = BitFlags::<INFOREPLY_Flags>::from_endianness(endianness);
+    if self.multicast_locator_list.is_some() {
+      flags |= INFOREPLY_Flags::Multicast;
+    }
+    SubMessage {
+      header: SubmessageHeader {
+        kind: SubmessageKind::INFO_REPLY,
+        flags: flags.bits(),
+        content_length: self.len_serialized() as u16,
+      },
+      body: SubmessageBody::Interpreter(InterpreterSubmessage::InfoReply(self, flags)),
+    }
+  }
+}
+
+// The count comes from the network, so check it against the data before
+// allocating anything.
+fn read_locator_list(
+  endianness: Endianness,
+  cursor: &mut io::Cursor<&[u8]>,
+) -> io::Result<Vec<Locator>> {
+  let map_speedy_err = |e: speedy::Error| io::Error::new(io::ErrorKind::Other, e);
+  let count = u32::read_from_stream_unbuffered_with_ctx(endianness, &mut *cursor)
+    .map_err(map_speedy_err)? as usize;
+  let remaining =
+    cursor.get_ref().len() as u64 - cursor.position().min(cursor.get_ref().len() as u64);
+  if count as u64 * 24 > remaining {
+    return Err(io::Error::new(
+      io::ErrorKind::UnexpectedEof,
+      "LocatorList is longer than submessage",
+    ));
+  }
+  (0..count)
+    .map(|_| {
+      Locator::read_from_stream_unbuffered_with_ctx(endianness, &mut *cursor)
+        .map_err(map_speedy_err)
+    })
+    .collect()
+}
+
+impl<C: Context> Writable<C> for InfoReply {
+  fn write_to<T: ?Sized + Writer<C>>(&self, writer: &mut T) -> Result<(), C::Error> {
+    writer.write_value(&self.unicast_locator_list)?;
+    if let Some(multicast_locator_list) = &self.multicast_locator_list {
+      writer.write_value(multicast_locator_list)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::net::SocketAddr;
+
+  use super::*;
+
+  #[test]
+  fn info_reply_multicast_list_follows_flag() {
+    let unicast = Locator::from("192.168.1.2:7411".parse::<SocketAddr>().unwrap());
+    let multicast = Locator::from("239.255.0.1:7401".parse::<SocketAddr>().unwrap());
+    for multicast_locator_list in [None, Some(vec![multicast])] {
+      let info_reply = InfoReply {
+        unicast_locator_list: vec![unicast],
+        multicast_locator_list,
+      };
+      let submessage = info_reply.clone().create_submessage(Endianness::BigEndian);
+      let flags = BitFlags::<INFOREPLY_Flags>::from_bits_truncate(submessage.header.flags);
+      let bytes = info_reply
+        .write_to_vec_with_ctx(Endianness::BigEndian)
+        .unwrap();
+
+      assert_eq!(bytes.len(), usize::from(submessage.header.content_length));
+      assert_eq!(InfoReply::deserialize(&bytes, flags).unwrap(), info_reply);
+    }
+  }
+}
@@ -1,13 +1,25 @@
-use speedy::{Readable, Writable};
+use enumflags2::BitFlags;
+use speedy::{Context, Readable, Reader, Writable, Writer};
 
 use crate::{
-  messages::{protocol_version::ProtocolVersion, vendor_id::VendorId},
+  messages::{
+    protocol_version::ProtocolVersion, submessages::submessages::SubmessageHeader,
+    vendor_id::VendorId,
+  },
+  serialization::{SubMessage, SubmessageBody},
   structure::guid::GuidPrefix,
 };
+use super::{
+  submessage::InterpreterSubmessage, submessage_flag::INFOSOURCE_Flags,
+  submessage_kind::SubmessageKind,
+};
 
 /// This message modifies the logical source of the Submessages
 /// that follow.
-#[derive(Debug, PartialEq, Eq, Clone, Readable, Writable)]
+///
+/// E.g. a router that relays messages from another participant puts this in
+/// front of them, so that the receiver knows who actually wrote them.
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct InfoSource {
   /// Indicates the protocol used to encapsulate subsequent Submessages
   pub protocol_version: ProtocolVersion,
@@ -21,6 +33,53 @@ pub struct InfoSource {
   pub guid_prefix: GuidPrefix,
 }
 
+impl InfoSource {
+  pub fn len_serialized(&self) -> usize {
+    4 + // unused
+    std::mem::size_of::<ProtocolVersion>() +
+    std::mem::size_of::<VendorId>() +
+    std::mem::size_of::<GuidPrefix>()
+  }
+
+  #[allow(dead_code)] // RustDDS does not relay messages, but a router would.
+  pub fn create_submessage(self, flags: BitFlags<INFOSOURCE_Flags>) -> SubMessage {
+    SubMessage {
+      header: SubmessageHeader {
+        kind: SubmessageKind::INFO_SRC,
+        flags: flags.bits(),
+        content_length: self.len_serialized() as u16,
+      },
+      body: SubmessageBody::Interpreter(InterpreterSubmessage::InfoSource(self, flags)),
+    }
+  }
+}
+
+// RTPS spec v2.5 Section "9.4.5.10 InfoSource Submessage": The contents start
+// with an unused 32-bit field.
+impl<'a, C: Context> Readable<'a, C> for InfoSource {
+  fn read_from<R: Reader<'a, C>>(reader: &mut R) -> Result<Self, C::Error> {
+    let _unused = reader.read_u32()?;
+    Ok(Self {
+      protocol_version: reader.read_value()?,
+      vendor_id: reader.read_value()?,
+      guid_prefix: reader.read_value()?,
+    })
+  }
+
+  fn minimum_bytes_needed() -> usize {
+    20
+  }
+}
+
+impl<C: Context> Writable<C> for InfoSource {
+  fn write_to<T: ?Sized + Writer<C>>(&self, writer: &mut T) -> Result<(), C::Error> {
+    writer.write_u32(0)?;
+    writer.write_value(&self.protocol_version)?;
+    writer.write_value(&self.vendor_id)?;
+    writer.write_value(&self.guid_prefix)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -39,11 +98,13 @@ mod tests {
                           0x01, 0x00, 0x00, 0x00]
           }
       },
-      le = [0x02, 0x02, 0xFF, 0xAA,
+      le = [0x00, 0x00, 0x00, 0x00,
+            0x02, 0x02, 0xFF, 0xAA,
             0x01, 0x02, 0x6D, 0x3F,
             0x7E, 0x07, 0x00, 0x00,
             0x01, 0x00, 0x00, 0x00],
-      be = [0x02, 0x02, 0xFF, 0xAA,
+      be = [0x00, 0x00, 0x00, 0x00,
+            0x02, 0x02, 0xFF, 0xAA,
             0x01, 0x02, 0x6D, 0x3F,
             0x7E, 0x07, 0x00, 0x00,
             0x01, 0x00, 0x00, 0x00]
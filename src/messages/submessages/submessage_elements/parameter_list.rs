@@ -23,13 +23,17 @@ impl ParameterList {
     self.parameters.is_empty()
   }
 
+  // Includes the sentinel that ends the list
   pub fn len_serialized(&self) -> usize {
-    self.parameters.iter().map(|p| p.len_serialized()).sum()
+    self
+      .parameters
+      .iter()
+      .map(|p| p.len_serialized())
+      .sum::<usize>()
+      + 4
   }
 }
 
-const SENTINEL: u32 = 0x00000001;
-
 impl<C: Context> Writable<C> for ParameterList {
   #[inline]
   fn write_to<T: ?Sized + Writer<C>>(&self, writer: &mut T) -> Result<(), C::Error> {
@@ -37,7 +41,10 @@ impl<C: Context> Writable<C> for ParameterList {
       writer.write_value(param)?;
     }
 
-    writer.write_u32(SENTINEL)?;
+    // PID_SENTINEL with zero length. Not a u32, so that it is right in both
+    // byte orders.
+    writer.write_value(&ParameterId::PID_SENTINEL)?;
+    writer.write_u16(0)?;
 
     Ok(())
   }
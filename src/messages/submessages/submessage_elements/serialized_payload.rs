@@ -27,18 +27,26 @@ impl RepresentationIdentifier {
     bytes: [0x00, 0x03],
   };
 
+  // XCDR2 values are from DDS-XTypes spec v1.3 Section 7.6.3.1.2, Table 60
   pub const CDR2_BE: Self = Self {
-    bytes: [0x00, 0x10],
+    bytes: [0x00, 0x06],
   };
   pub const CDR2_LE: Self = Self {
-    bytes: [0x00, 0x11],
+    bytes: [0x00, 0x07],
+  };
+
+  pub const D_CDR2_BE: Self = Self {
+    bytes: [0x00, 0x08],
+  };
+  pub const D_CDR2_LE: Self = Self {
+    bytes: [0x00, 0x09],
   };
 
   pub const PL_CDR2_BE: Self = Self {
-    bytes: [0x00, 0x12],
+    bytes: [0x00, 0x0a],
   };
   pub const PL_CDR2_LE: Self = Self {
-    bytes: [0x00, 0x13],
+    bytes: [0x00, 0x0b],
   };
 
   pub const D_CDR_BE: Self = Self {
@@ -1,9 +1,14 @@
 use speedy::{Context, Readable, Reader, Writable, Writer};
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "discovery")]
 use crate::structure::parameter_id::ParameterId;
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+/// Identifies the DDS implementation of a participant. Participants announce
+/// it in the header of each message and in SPDP.
+///
+/// The values are assigned by the OMG DDS SIG.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Clone, Copy)]
 pub struct VendorId {
   pub vendor_id: [u8; 2],
 }
@@ -20,6 +25,26 @@ impl VendorId {
 
   pub const THIS_IMPLEMENTATION: Self = Self::ATOSTEK;
 
+  /// RTI Connext DDS
+  pub const RTI_CONNEXT: Self = Self {
+    vendor_id: [0x01, 0x01],
+  };
+
+  /// Twin Oaks Computing CoreDX DDS
+  pub const TWINOAKS_COREDX: Self = Self {
+    vendor_id: [0x01, 0x06],
+  };
+
+  /// eProsima Fast DDS
+  pub const EPROSIMA_FAST_DDS: Self = Self {
+    vendor_id: [0x01, 0x0F],
+  };
+
+  /// Eclipse Cyclone DDS
+  pub const ECLIPSE_CYCLONE_DDS: Self = Self {
+    vendor_id: [0x01, 0x10],
+  };
+
   pub fn as_bytes(&self) -> [u8; 2] {
     self.vendor_id
   }
@@ -31,6 +56,7 @@ impl Default for VendorId {
   }
 }
 
+#[cfg(feature = "discovery")]
 #[derive(Serialize, Deserialize)]
 pub struct VendorIdData {
   parameter_id: ParameterId,
@@ -38,6 +64,7 @@ pub struct VendorIdData {
   vendor_id: VendorId,
 }
 
+#[cfg(feature = "discovery")]
 impl VendorIdData {
   pub fn from(vendor_id: VendorId) -> Self {
     Self {
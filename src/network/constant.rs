@@ -2,8 +2,9 @@ use mio::Token;
 use mio_extras::channel as mio_channel;
 
 use crate::{
-  dds::data_types::GuidPrefix,
+  dds::{data_types::GuidPrefix, qos::QosPolicies},
   discovery::data_types::topic_data::{DiscoveredReaderData, DiscoveredWriterData},
+  network::transport::TransportKind,
   structure::guid::{EntityKind, GUID},
 };
 
@@ -42,50 +43,93 @@ pub const DISCOVERY_UPDATE_NOTIFICATION_TOKEN: Token = Token(21 + PTB);
 pub const DISCOVERY_COMMAND_TOKEN: Token = Token(22 + PTB);
 pub const SPDP_LIVENESS_TOKEN: Token = Token(23 + PTB);
 
+pub const DISCOVERY_V6_LISTENER_TOKEN: Token = Token(24 + PTB);
+pub const DISCOVERY_MUL_V6_LISTENER_TOKEN: Token = Token(25 + PTB);
+pub const USER_TRAFFIC_V6_LISTENER_TOKEN: Token = Token(26 + PTB);
+pub const USER_TRAFFIC_MUL_V6_LISTENER_TOKEN: Token = Token(27 + PTB);
+
+pub const USER_TRAFFIC_SHM_LISTENER_TOKEN: Token = Token(28 + PTB);
+pub const USER_TRAFFIC_TCP_LISTENER_TOKEN: Token = Token(29 + PTB);
+
+#[cfg(feature = "discovery")]
 pub const DISCOVERY_PARTICIPANT_DATA_TOKEN: Token = Token(30 + PTB);
 pub const DISCOVERY_PARTICIPANT_CLEANUP_TOKEN: Token = Token(31 + PTB);
+#[cfg(feature = "discovery")]
 pub const DISCOVERY_SEND_PARTICIPANT_INFO_TOKEN: Token = Token(32 + PTB);
+#[cfg(feature = "discovery")]
 pub const DISCOVERY_READER_DATA_TOKEN: Token = Token(33 + PTB);
 pub const DISCOVERY_SEND_READERS_INFO_TOKEN: Token = Token(34 + PTB);
+#[cfg(feature = "discovery")]
 pub const DISCOVERY_WRITER_DATA_TOKEN: Token = Token(35 + PTB);
 pub const DISCOVERY_SEND_WRITERS_INFO_TOKEN: Token = Token(36 + PTB);
+#[cfg(feature = "discovery")]
 pub const DISCOVERY_TOPIC_DATA_TOKEN: Token = Token(37 + PTB);
 pub const DISCOVERY_TOPIC_CLEANUP_TOKEN: Token = Token(38 + PTB);
+#[cfg(feature = "discovery")]
 pub const DISCOVERY_SEND_TOPIC_INFO_TOKEN: Token = Token(39 + PTB);
 pub const DISCOVERY_PARTICIPANT_MESSAGE_TOKEN: Token = Token(40 + PTB);
 pub const DISCOVERY_PARTICIPANT_MESSAGE_TIMER_TOKEN: Token = Token(41 + PTB);
+pub const DISCOVERY_HEALTH_TIMER_TOKEN: Token = Token(42 + PTB);
+pub const DISCOVERY_TYPE_LOOKUP_REQUEST_TOKEN: Token = Token(43 + PTB);
+pub const DISCOVERY_TYPE_LOOKUP_REPLY_TOKEN: Token = Token(44 + PTB);
+#[cfg(feature = "security")]
+pub const DISCOVERY_STATELESS_MESSAGE_TOKEN: Token = Token(45 + PTB);
+#[cfg(feature = "security")]
+pub const DISCOVERY_HANDSHAKE_TIMER_TOKEN: Token = Token(46 + PTB);
+#[cfg(feature = "security")]
+pub const DISCOVERY_VOLATILE_MESSAGE_TOKEN: Token = Token(47 + PTB);
+#[cfg(feature = "statistics")]
+pub const DISCOVERY_STATISTICS_TIMER_TOKEN: Token = Token(48 + PTB);
 
 pub const DPEV_ACKNACK_TIMER_TOKEN: Token = Token(50 + PTB);
-
-pub struct TokenReceiverPair<T> {
-  pub token: Token,
-  pub receiver: mio_channel::Receiver<T>,
-}
-
-// These constants are from RTPS spec Section 9.6.2.3 Default Port Numbers
-const PB: u16 = 7400;
-const DG: u16 = 250;
-const PG: u16 = 2;
-
-const D0: u16 = 0;
-const D1: u16 = 10;
-const D2: u16 = 1;
-const D3: u16 = 11;
-
-pub fn spdp_well_known_multicast_port(domain_id: u16) -> u16 {
-  PB + DG * domain_id + D0
+pub const DPEV_NETWORK_INTERFACES_TIMER_TOKEN: Token = Token(51 + PTB);
+pub const DPEV_RECEIVED_MESSAGES_TOKEN: Token = Token(52 + PTB);
+
+// Listener Tokens of a UDP transport, in the order discovery unicast,
+// discovery multicast, user traffic unicast, user traffic multicast.
+// Shared memory has only USER_TRAFFIC_SHM_LISTENER_TOKEN, and TCP only
+// USER_TRAFFIC_TCP_LISTENER_TOKEN.
+pub fn listener_tokens(kind: TransportKind) -> Option<[Token; 4]> {
+  match kind {
+    TransportKind::UdpV4 => Some([
+      DISCOVERY_LISTENER_TOKEN,
+      DISCOVERY_MUL_LISTENER_TOKEN,
+      USER_TRAFFIC_LISTENER_TOKEN,
+      USER_TRAFFIC_MUL_LISTENER_TOKEN,
+    ]),
+    TransportKind::UdpV6 => Some([
+      DISCOVERY_V6_LISTENER_TOKEN,
+      DISCOVERY_MUL_V6_LISTENER_TOKEN,
+      USER_TRAFFIC_V6_LISTENER_TOKEN,
+      USER_TRAFFIC_MUL_V6_LISTENER_TOKEN,
+    ]),
+    TransportKind::SharedMemory | TransportKind::TcpV4 => None,
+  }
 }
 
-pub fn spdp_well_known_unicast_port(domain_id: u16, participant_id: u16) -> u16 {
-  PB + DG * domain_id + D1 + PG * participant_id
+// The self locators of a participant are keyed by the UDPv4 listener tokens,
+// and the locators of the other transports are listed together with those.
+// This gives the key for the locators of a listener.
+pub fn self_locators_token(listener_token: Token) -> Option<Token> {
+  match listener_token {
+    DISCOVERY_LISTENER_TOKEN | DISCOVERY_V6_LISTENER_TOKEN => Some(DISCOVERY_LISTENER_TOKEN),
+    DISCOVERY_MUL_LISTENER_TOKEN | DISCOVERY_MUL_V6_LISTENER_TOKEN => {
+      Some(DISCOVERY_MUL_LISTENER_TOKEN)
+    }
+    USER_TRAFFIC_LISTENER_TOKEN
+    | USER_TRAFFIC_V6_LISTENER_TOKEN
+    | USER_TRAFFIC_SHM_LISTENER_TOKEN
+    | USER_TRAFFIC_TCP_LISTENER_TOKEN => Some(USER_TRAFFIC_LISTENER_TOKEN),
+    USER_TRAFFIC_MUL_LISTENER_TOKEN | USER_TRAFFIC_MUL_V6_LISTENER_TOKEN => {
+      Some(USER_TRAFFIC_MUL_LISTENER_TOKEN)
+    }
+    _ => None,
+  }
 }
 
-pub fn user_traffic_multicast_port(domain_id: u16) -> u16 {
-  PB + DG * domain_id + D2
-}
-
-pub fn user_traffic_unicast_port(domain_id: u16, participant_id: u16) -> u16 {
-  PB + DG * domain_id + D3 + PG * participant_id
+pub struct TokenReceiverPair<T> {
+  pub token: Token,
+  pub receiver: mio_channel::Receiver<T>,
 }
 
 #[derive(Debug)]
@@ -113,4 +157,16 @@ pub(crate) enum DiscoveryNotificationType {
     writer_guid: GUID,
     manual_assertion: bool,
   },
+  // QoS of a local Writer or Reader has changed, so matching with remote
+  // endpoints must be re-evaluated.
+  LocalWriterUpdated {
+    writer_guid: GUID,
+    qos: QosPolicies,
+  },
+  LocalReaderUpdated {
+    reader_guid: GUID,
+    qos: QosPolicies,
+  },
+  // Clocks have jumped, and local timers should be restarted.
+  ClockJump,
 }
@@ -0,0 +1,160 @@
+use std::{net::IpAddr, str::FromStr};
+
+use if_addrs::Interface;
+
+/// Selects local network interfaces by name or by address, for
+/// [`DomainParticipantBuilder::allow_interface`](crate::DomainParticipantBuilder::allow_interface)
+/// and
+/// [`DomainParticipantBuilder::deny_interface`](crate::DomainParticipantBuilder::deny_interface).
+///
+/// Parsing from text accepts CIDR notation, e.g. `192.168.1.0/24` or
+/// `fd00::/8`, a single address, or otherwise an interface name, e.g. `eth0`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InterfaceSelector {
+  /// Interface name, as the operating system calls it
+  Name(String),
+  /// Interface addresses within this network
+  Network { address: IpAddr, prefix_len: u8 },
+}
+
+impl InterfaceSelector {
+  fn matches(&self, interface: &Interface) -> bool {
+    match self {
+      Self::Name(name) => interface.name == *name,
+      Self::Network {
+        address,
+        prefix_len,
+      } => in_network(interface.ip(), *address, *prefix_len),
+    }
+  }
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix_len: u8) -> bool {
+  match (ip, network) {
+    (IpAddr::V4(ip), IpAddr::V4(network)) => {
+      (u32::from(ip) ^ u32::from(network))
+        .checked_shr(32 - u32::from(prefix_len.min(32)))
+        .unwrap_or(0)
+        == 0
+    }
+    (IpAddr::V6(ip), IpAddr::V6(network)) => {
+      (u128::from(ip) ^ u128::from(network))
+        .checked_shr(128 - u32::from(prefix_len.min(128)))
+        .unwrap_or(0)
+        == 0
+    }
+    _ => false,
+  }
+}
+
+/// Error from parsing [`InterfaceSelector`] from text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("invalid network interface selector")]
+pub struct ParseInterfaceSelectorError;
+
+impl FromStr for InterfaceSelector {
+  type Err = ParseInterfaceSelectorError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (address, prefix_len) = match s.split_once('/') {
+      Some((address, prefix_len)) => (
+        address
+          .parse::<IpAddr>()
+          .map_err(|_| ParseInterfaceSelectorError)?,
+        Some(
+          prefix_len
+            .parse::<u8>()
+            .map_err(|_| ParseInterfaceSelectorError)?,
+        ),
+      ),
+      None => match s.parse::<IpAddr>() {
+        Ok(address) => (address, None),
+        Err(_) if !s.is_empty() => return Ok(Self::Name(s.to_string())),
+        Err(_) => return Err(ParseInterfaceSelectorError),
+      },
+    };
+    let max_prefix_len = if address.is_ipv4() { 32 } else { 128 };
+    match prefix_len {
+      Some(prefix_len) if prefix_len > max_prefix_len => Err(ParseInterfaceSelectorError),
+      _ => Ok(Self::Network {
+        address,
+        prefix_len: prefix_len.unwrap_or(max_prefix_len),
+      }),
+    }
+  }
+}
+
+// Which local network interfaces a DomainParticipant uses. Loopback
+// interfaces are used only if explicitly allowed, because remote participants
+// cannot reach us by those.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InterfaceFilter {
+  pub allow: Vec<InterfaceSelector>,
+  pub deny: Vec<InterfaceSelector>,
+}
+
+impl InterfaceFilter {
+  pub fn accepts(&self, interface: &Interface) -> bool {
+    let allowed = if self.allow.is_empty() {
+      !interface.is_loopback()
+    } else {
+      self.allow.iter().any(|s| s.matches(interface))
+    };
+    allowed && !self.deny.iter().any(|s| s.matches(interface))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use if_addrs::{IfAddr, Ifv4Addr};
+  use test_case::test_case;
+
+  use super::*;
+
+  fn interface(name: &str, ip: [u8; 4]) -> Interface {
+    Interface {
+      name: name.to_string(),
+      addr: IfAddr::V4(Ifv4Addr {
+        ip: ip.into(),
+        netmask: [255, 255, 255, 0].into(),
+        broadcast: None,
+      }),
+    }
+  }
+
+  #[test_case("eth0" => Ok(InterfaceSelector::Name("eth0".to_string())) ; "name")]
+  #[test_case("10.1.0.0/16" => Ok(InterfaceSelector::Network {
+    address: [10, 1, 0, 0].into(), prefix_len: 16 }) ; "IPv4 network")]
+  #[test_case("10.1.2.3" => Ok(InterfaceSelector::Network {
+    address: [10, 1, 2, 3].into(), prefix_len: 32 }) ; "IPv4 address")]
+  #[test_case("fd00::/8" => Ok(InterfaceSelector::Network {
+    address: "fd00::".parse().unwrap(), prefix_len: 8 }) ; "IPv6 network")]
+  #[test_case("10.1.0.0/33" => Err(ParseInterfaceSelectorError) ; "too long prefix")]
+  #[test_case("eth0/8" => Err(ParseInterfaceSelectorError) ; "name with prefix")]
+  #[test_case("" => Err(ParseInterfaceSelectorError) ; "empty")]
+  fn parse(s: &str) -> Result<InterfaceSelector, ParseInterfaceSelectorError> {
+    s.parse()
+  }
+
+  #[test]
+  fn filter_allow_and_deny() {
+    let eth0 = interface("eth0", [192, 168, 1, 10]);
+    let eth1 = interface("eth1", [10, 1, 2, 3]);
+    let lo = interface("lo", [127, 0, 0, 1]);
+
+    let all = InterfaceFilter::default();
+    assert!(all.accepts(&eth0) && all.accepts(&eth1) && !all.accepts(&lo));
+
+    let filter = InterfaceFilter {
+      allow: vec!["192.168.0.0/16".parse().unwrap(), "lo".parse().unwrap()],
+      deny: vec![],
+    };
+    assert!(filter.accepts(&eth0) && !filter.accepts(&eth1) && filter.accepts(&lo));
+
+    let filter = InterfaceFilter {
+      allow: vec![],
+      deny: vec!["eth0".parse().unwrap()],
+    };
+    assert!(!filter.accepts(&eth0) && filter.accepts(&eth1));
+  }
+}
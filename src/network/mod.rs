@@ -1,4 +1,11 @@
 pub mod constant;
+pub mod interface_filter;
+pub(crate) mod packet_capture;
+pub mod port_mapping;
+pub mod shared_memory;
+pub mod socket_options;
+pub mod tcp;
+pub mod transport;
 pub mod udp_listener;
 pub mod udp_sender;
 pub mod util;
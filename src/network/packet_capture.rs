@@ -0,0 +1,336 @@
+// Capture of the RTPS messages sent and received over UDP, in the pcapng
+// format that Wireshark and tcpdump read.
+//
+// Sockets do not give us the IP and UDP headers, so each message is written
+// with headers made up from the socket addresses, using the "raw IP" link
+// type. The result looks as if it was captured on the wire, except that IP
+// level fragments are already reassembled.
+
+use std::{
+  fmt,
+  io::{self, Write},
+  net::{IpAddr, Ipv6Addr, SocketAddr},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+  },
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+// Packets start with an IPv4 or IPv6 header, told apart by the version field.
+const LINKTYPE_RAW: u16 = 101;
+
+const IPV4_HEADER_SIZE: usize = 20;
+const IPV6_HEADER_SIZE: usize = 40;
+const UDP_HEADER_SIZE: usize = 8;
+const UDP_PROTOCOL: u8 = 17;
+const TTL: u8 = 64;
+
+// Writes pcapng blocks to the output. The timestamps have the default
+// resolution of microseconds.
+struct PcapngWriter {
+  output: Box<dyn Write + Send>,
+}
+
+impl PcapngWriter {
+  fn new(mut output: Box<dyn Write + Send>) -> io::Result<Self> {
+    let mut section_header = Vec::with_capacity(16);
+    section_header.extend_from_slice(&BYTE_ORDER_MAGIC.to_ne_bytes());
+    section_header.extend_from_slice(&1u16.to_ne_bytes()); // major version
+    section_header.extend_from_slice(&0u16.to_ne_bytes()); // minor version
+    section_header.extend_from_slice(&(-1i64).to_ne_bytes()); // section length not known
+    write_block(&mut output, SECTION_HEADER_BLOCK, &section_header)?;
+
+    let mut interface_description = Vec::with_capacity(8);
+    interface_description.extend_from_slice(&LINKTYPE_RAW.to_ne_bytes());
+    interface_description.extend_from_slice(&0u16.to_ne_bytes()); // reserved
+    interface_description.extend_from_slice(&0u32.to_ne_bytes()); // no snap length limit
+    write_block(
+      &mut output,
+      INTERFACE_DESCRIPTION_BLOCK,
+      &interface_description,
+    )?;
+
+    Ok(Self { output })
+  }
+
+  fn write_packet(&mut self, timestamp: SystemTime, packet: &[u8]) -> io::Result<()> {
+    let micros = timestamp
+      .duration_since(UNIX_EPOCH)
+      .map_or(0, |d| d.as_micros() as u64);
+    let mut body = Vec::with_capacity(20 + packet.len() + 3);
+    body.extend_from_slice(&0u32.to_ne_bytes()); // interface id
+    body.extend_from_slice(&((micros >> 32) as u32).to_ne_bytes());
+    body.extend_from_slice(&(micros as u32).to_ne_bytes());
+    body.extend_from_slice(&(packet.len() as u32).to_ne_bytes()); // captured length
+    body.extend_from_slice(&(packet.len() as u32).to_ne_bytes()); // original length
+    body.extend_from_slice(packet);
+    write_block(&mut self.output, ENHANCED_PACKET_BLOCK, &body)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.output.flush()
+  }
+}
+
+// Block body is padded to 32 bits, and framed with the block type and two
+// copies of the total length.
+fn write_block(output: &mut dyn Write, block_type: u32, body: &[u8]) -> io::Result<()> {
+  let padding = (4 - body.len() % 4) % 4;
+  let total_length = (12 + body.len() + padding) as u32;
+  output.write_all(&block_type.to_ne_bytes())?;
+  output.write_all(&total_length.to_ne_bytes())?;
+  output.write_all(body)?;
+  output.write_all(&[0; 3][..padding])?;
+  output.write_all(&total_length.to_ne_bytes())
+}
+
+// Wraps a UDP payload in made-up IP and UDP headers. If one address is IPv6,
+// the other is mapped to IPv6, too.
+fn ip_udp_packet(source: SocketAddr, destination: SocketAddr, payload: &[u8]) -> Vec<u8> {
+  let udp_length = UDP_HEADER_SIZE + payload.len();
+  let mut packet;
+  let pseudo_header_sum;
+  match (source.ip(), destination.ip()) {
+    (IpAddr::V4(src), IpAddr::V4(dst)) => {
+      packet = Vec::with_capacity(IPV4_HEADER_SIZE + udp_length);
+      packet.push(0x45); // version 4, header length 5 words
+      packet.push(0); // DSCP and ECN
+      packet.extend_from_slice(&((IPV4_HEADER_SIZE + udp_length) as u16).to_be_bytes());
+      packet.extend_from_slice(&[0, 0, 0x40, 0]); // identification, don't fragment
+      packet.push(TTL);
+      packet.push(UDP_PROTOCOL);
+      packet.extend_from_slice(&[0, 0]); // header checksum, filled in below
+      packet.extend_from_slice(&src.octets());
+      packet.extend_from_slice(&dst.octets());
+      let header_checksum = !fold(sum_words(&packet));
+      packet[10..12].copy_from_slice(&header_checksum.to_be_bytes());
+      pseudo_header_sum = sum_words(&src.octets())
+        + sum_words(&dst.octets())
+        + u32::from(UDP_PROTOCOL)
+        + udp_length as u32;
+    }
+    (src, dst) => {
+      let src = to_ipv6(src);
+      let dst = to_ipv6(dst);
+      packet = Vec::with_capacity(IPV6_HEADER_SIZE + udp_length);
+      packet.extend_from_slice(&[0x60, 0, 0, 0]); // version 6, no traffic class or flow label
+      packet.extend_from_slice(&(udp_length as u16).to_be_bytes());
+      packet.push(UDP_PROTOCOL); // next header
+      packet.push(TTL); // hop limit
+      packet.extend_from_slice(&src.octets());
+      packet.extend_from_slice(&dst.octets());
+      pseudo_header_sum = sum_words(&src.octets())
+        + sum_words(&dst.octets())
+        + u32::from(UDP_PROTOCOL)
+        + udp_length as u32;
+    }
+  }
+
+  let udp_start = packet.len();
+  packet.extend_from_slice(&source.port().to_be_bytes());
+  packet.extend_from_slice(&destination.port().to_be_bytes());
+  packet.extend_from_slice(&(udp_length as u16).to_be_bytes());
+  packet.extend_from_slice(&[0, 0]); // checksum, filled in below
+  packet.extend_from_slice(payload);
+  let udp_checksum = match !fold(pseudo_header_sum + sum_words(&packet[udp_start..])) {
+    0 => 0xFFFF, // zero would mean "no checksum"
+    checksum => checksum,
+  };
+  packet[udp_start + 6..udp_start + 8].copy_from_slice(&udp_checksum.to_be_bytes());
+  packet
+}
+
+fn to_ipv6(address: IpAddr) -> Ipv6Addr {
+  match address {
+    IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+    IpAddr::V6(v6) => v6,
+  }
+}
+
+// Internet checksum: sum of big-endian 16-bit words, odd byte padded with zero.
+fn sum_words(bytes: &[u8]) -> u32 {
+  bytes
+    .chunks(2)
+    .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+    .fold(0u32, u32::wrapping_add)
+}
+
+fn fold(mut sum: u32) -> u16 {
+  while sum > 0xFFFF {
+    sum = (sum & 0xFFFF) + (sum >> 16);
+  }
+  sum as u16
+}
+
+// Shared by the UDP senders and listeners of a DomainParticipant. Capture can
+// be started and stopped at any time. While it is stopped, recording costs
+// one atomic load.
+//
+// This is pub only because it appears in the hidden methods of the public
+// Transport traits. The module is not public.
+#[derive(Clone, Default)]
+pub struct PacketCapture {
+  active: Arc<AtomicBool>,
+  writer: Arc<Mutex<Option<PcapngWriter>>>,
+}
+
+impl PacketCapture {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // Starts writing a new capture to the output. A capture that was already
+  // going on is finished first.
+  pub fn start(&self, output: Box<dyn Write + Send>) -> io::Result<()> {
+    let new_writer = PcapngWriter::new(output)?;
+    let mut writer = self.writer.lock().unwrap();
+    let old_writer = writer.replace(new_writer);
+    self.active.store(true, Ordering::Relaxed);
+    match old_writer {
+      Some(mut old_writer) => old_writer.flush(),
+      None => Ok(()),
+    }
+  }
+
+  pub fn stop(&self) -> io::Result<()> {
+    let mut writer = self.writer.lock().unwrap();
+    self.active.store(false, Ordering::Relaxed);
+    match writer.take() {
+      Some(mut writer) => writer.flush(),
+      None => Ok(()),
+    }
+  }
+
+  pub fn is_active(&self) -> bool {
+    self.active.load(Ordering::Relaxed)
+  }
+
+  // Records one UDP datagram. If the output fails, capture is stopped, so
+  // that communication goes on.
+  pub fn record(&self, source: SocketAddr, destination: SocketAddr, message: &[u8]) {
+    if !self.is_active() {
+      return;
+    }
+    let timestamp = SystemTime::now();
+    let packet = ip_udp_packet(source, destination, message);
+    let mut writer = self.writer.lock().unwrap();
+    if let Some(w) = writer.as_mut() {
+      if let Err(e) = w.write_packet(timestamp, &packet) {
+        error!(
+          "Packet capture stopped, because it cannot be written: {:?}",
+          e
+        );
+        *writer = None;
+        self.active.store(false, Ordering::Relaxed);
+      }
+    }
+  }
+}
+
+impl fmt::Debug for PacketCapture {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("PacketCapture")
+      .field("active", &self.is_active())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Output that stays readable after it has been given to PacketCapture.
+  #[derive(Clone, Default)]
+  struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+  impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes([
+      bytes[offset],
+      bytes[offset + 1],
+      bytes[offset + 2],
+      bytes[offset + 3],
+    ])
+  }
+
+  #[test]
+  fn packet_capture_pcapng_blocks() {
+    let buffer = SharedBuffer::default();
+    let capture = PacketCapture::new();
+    let source: SocketAddr = "192.168.1.2:7410".parse().unwrap();
+    let destination: SocketAddr = "239.255.0.1:7400".parse().unwrap();
+
+    // Nothing is written before capture is started.
+    capture.record(source, destination, b"RTPS");
+    capture.start(Box::new(buffer.clone())).unwrap();
+    capture.record(source, destination, b"RTPS\x02\x04");
+    capture.stop().unwrap();
+    capture.record(source, destination, b"RTPS");
+
+    let bytes = buffer.0.lock().unwrap().clone();
+    // Section header block, interface description block, one packet block.
+    assert_eq!(u32_at(&bytes, 0), SECTION_HEADER_BLOCK);
+    assert_eq!(u32_at(&bytes, 8), BYTE_ORDER_MAGIC);
+    assert_eq!(u32_at(&bytes, 28), INTERFACE_DESCRIPTION_BLOCK);
+    let packet_block = &bytes[48..];
+    assert_eq!(u32_at(packet_block, 0), ENHANCED_PACKET_BLOCK);
+    let block_length = u32_at(packet_block, 4) as usize;
+    assert_eq!(block_length, packet_block.len());
+    assert_eq!(
+      u32_at(packet_block, block_length - 4) as usize,
+      block_length
+    );
+    let captured_length = u32_at(packet_block, 20) as usize;
+    assert_eq!(captured_length, IPV4_HEADER_SIZE + UDP_HEADER_SIZE + 6);
+    assert_eq!(block_length, 32 + 36); // padded to 32 bits
+  }
+
+  #[test]
+  fn packet_capture_checksums() {
+    let payload = b"RTPS\x02\x04\x01";
+    let v4 = ip_udp_packet(
+      "10.0.0.1:1234".parse().unwrap(),
+      "10.0.0.2:7411".parse().unwrap(),
+      payload,
+    );
+    // A correct checksum makes the header sum to all ones.
+    assert_eq!(fold(sum_words(&v4[..IPV4_HEADER_SIZE])), 0xFFFF);
+    assert_eq!(&v4[12..16], &[10, 0, 0, 1]);
+    assert_eq!(
+      &v4[IPV4_HEADER_SIZE + 2..IPV4_HEADER_SIZE + 4],
+      &7411u16.to_be_bytes()
+    );
+    let udp = &v4[IPV4_HEADER_SIZE..];
+    let pseudo_header = sum_words(&v4[12..20]) + u32::from(UDP_PROTOCOL) + udp.len() as u32;
+    assert_eq!(fold(pseudo_header + sum_words(udp)), 0xFFFF);
+    assert_eq!(&udp[UDP_HEADER_SIZE..], payload);
+
+    // Mixed address families are written as IPv6.
+    let v6 = ip_udp_packet(
+      "[fe80::1]:1234".parse().unwrap(),
+      "10.0.0.2:7411".parse().unwrap(),
+      payload,
+    );
+    assert_eq!(v6[0] >> 4, 6);
+    assert_eq!(v6.len(), IPV6_HEADER_SIZE + UDP_HEADER_SIZE + payload.len());
+    let udp = &v6[IPV6_HEADER_SIZE..];
+    let pseudo_header = sum_words(&v6[8..40]) + u32::from(UDP_PROTOCOL) + udp.len() as u32;
+    assert_eq!(fold(pseudo_header + sum_words(udp)), 0xFFFF);
+  }
+}
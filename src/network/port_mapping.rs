@@ -0,0 +1,123 @@
+use std::convert::TryFrom;
+
+use crate::dds::values::result::{Error, Result};
+
+/// How the UDP port numbers of a DomainParticipant are computed from its
+/// domain id and participant id, as specified in RTPS spec v2.5 Section
+/// "9.6.2.3 Default Port Numbers":
+///
+/// * SPDP multicast: `PB + DG * domain_id + d0`
+/// * SPDP unicast: `PB + DG * domain_id + d1 + PG * participant_id`
+/// * user traffic multicast: `PB + DG * domain_id + d2`
+/// * user traffic unicast: `PB + DG * domain_id + d3 + PG * participant_id`
+///
+/// The [`Default`] has the values suggested by the specification, which
+/// other DDS implementations also use by default. Participants that use a
+/// different mapping do not discover each other over multicast, since they
+/// listen to different ports. See
+/// [`DomainParticipantBuilder::port_mapping`](crate::DomainParticipantBuilder::port_mapping).
+///
+/// # Example
+///
+/// ```
+/// # use rustdds::PortMapping;
+///
+/// let mapping = PortMapping {
+///   port_base: 17400,
+///   ..PortMapping::default()
+/// };
+/// assert_eq!(mapping.spdp_multicast_port(1).unwrap(), 17650);
+/// assert_eq!(mapping.user_traffic_unicast_port(1, 2).unwrap(), 17665);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PortMapping {
+  /// PB
+  pub port_base: u16,
+  /// DG
+  pub domain_id_gain: u16,
+  /// PG
+  pub participant_id_gain: u16,
+  /// d0, added to the SPDP multicast port
+  pub offset_d0: u16,
+  /// d1, added to the SPDP unicast ports
+  pub offset_d1: u16,
+  /// d2, added to the user traffic multicast port
+  pub offset_d2: u16,
+  /// d3, added to the user traffic unicast ports
+  pub offset_d3: u16,
+}
+
+impl Default for PortMapping {
+  fn default() -> Self {
+    Self {
+      port_base: 7400,
+      domain_id_gain: 250,
+      participant_id_gain: 2,
+      offset_d0: 0,
+      offset_d1: 10,
+      offset_d2: 1,
+      offset_d3: 11,
+    }
+  }
+}
+
+impl PortMapping {
+  pub fn spdp_multicast_port(&self, domain_id: u16) -> Result<u16> {
+    self.port(domain_id, self.offset_d0, 0)
+  }
+
+  pub fn spdp_unicast_port(&self, domain_id: u16, participant_id: u16) -> Result<u16> {
+    self.port(domain_id, self.offset_d1, participant_id)
+  }
+
+  pub fn user_traffic_multicast_port(&self, domain_id: u16) -> Result<u16> {
+    self.port(domain_id, self.offset_d2, 0)
+  }
+
+  pub fn user_traffic_unicast_port(&self, domain_id: u16, participant_id: u16) -> Result<u16> {
+    self.port(domain_id, self.offset_d3, participant_id)
+  }
+
+  // Fails with BadParameter, if the port would not fit in 16 bits.
+  fn port(&self, domain_id: u16, offset: u16, participant_id: u16) -> Result<u16> {
+    let port = u32::from(self.port_base)
+      + u32::from(self.domain_id_gain) * u32::from(domain_id)
+      + u32::from(offset)
+      + u32::from(self.participant_id_gain) * u32::from(participant_id);
+    u16::try_from(port).or_else(|_| {
+      Error::bad_parameter(format!(
+        "Port number {} of domain {} participant {} is out of range",
+        port, domain_id, participant_id
+      ))
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn default_ports_follow_rtps_spec() {
+    let mapping = PortMapping::default();
+    assert_eq!(mapping.spdp_multicast_port(0).unwrap(), 7400);
+    assert_eq!(mapping.spdp_unicast_port(0, 0).unwrap(), 7410);
+    assert_eq!(mapping.user_traffic_multicast_port(0).unwrap(), 7401);
+    assert_eq!(mapping.user_traffic_unicast_port(0, 0).unwrap(), 7411);
+    assert_eq!(mapping.spdp_unicast_port(1, 2).unwrap(), 7664);
+    assert_eq!(mapping.user_traffic_unicast_port(1, 2).unwrap(), 7665);
+  }
+
+  #[test]
+  fn port_out_of_range() {
+    let mapping = PortMapping::default();
+    // 7400 + 250 * 232 + 11 fits, one more domain does not.
+    assert_eq!(mapping.user_traffic_unicast_port(232, 0).unwrap(), 65411);
+    assert!(mapping.spdp_multicast_port(233).is_err());
+    assert!(mapping
+      .spdp_unicast_port(232, 100)
+      .unwrap_err()
+      .to_string()
+      .contains("out of range"));
+  }
+}
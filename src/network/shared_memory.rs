@@ -0,0 +1,719 @@
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  convert::TryFrom,
+  env, fmt,
+  fs::{self, File, OpenOptions},
+  io::{self, Read, Write},
+  mem::size_of,
+  net::{Ipv4Addr, SocketAddr},
+  path::{Path, PathBuf},
+  ptr,
+  sync::atomic::{AtomicU32, AtomicU64, Ordering},
+  thread,
+};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+use bytes::Bytes;
+use memmap2::MmapMut;
+use mio::{net::UdpSocket, Evented};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use static_assertions::const_assert;
+
+use crate::{
+  network::transport::{Transport, TransportKind, TransportListener},
+  structure::locator::{self, Locator},
+};
+
+// Shared memory transport between participants on the same host.
+//
+// Each participant creates one segment, a file in shared memory, and maps it.
+// The segment contains a ring buffer, where any number of senders write RTPS
+// messages, and which the owning listener reads. Because the event loop
+// cannot wait for memory, a sender wakes the listener with a datagram to its
+// loopback doorbell socket, if the listener has declared that it is waiting.
+//
+// The handshake is in the segment header: The listener initializes the
+// header, and then sets the state to READY. A sender maps the segment on first
+// use, and checks the magic number, version, capacity and state. When the
+// listener goes away, it sets the state to CLOSED and removes the file, and
+// senders drop their mappings.
+//
+// The locator of a segment is of a RustDDS-specific kind. Its address contains
+// a host id and a segment id, and its port is the doorbell port. The host id is
+// shared by all participants using the same shared memory directory, so a
+// locator with our host id can be reached with shared memory.
+
+const SEGMENT_FILE_PREFIX: &str = "rustdds-shm-";
+const HOST_ID_FILE: &str = "rustdds-shm-host";
+
+const MAGIC: u32 = u32::from_be_bytes(*b"RDSM");
+const VERSION: u32 = 1;
+
+const STATE_READY: u32 = 1;
+const STATE_CLOSED: u32 = 2;
+
+const HEADER_SIZE: usize = 64;
+const DATA_SIZE: usize = 2 * 1024 * 1024;
+// Records are aligned to this, so that length fields are aligned, and a wrap
+// marker always fits at the end of the data area.
+const RECORD_ALIGN: usize = 8;
+const LENGTH_SIZE: usize = size_of::<u32>();
+// Written in place of a record length, when the record did not fit at the end
+// of the data area and was written at the beginning instead.
+const WRAP_MARKER: u32 = u32::MAX;
+
+// A record may take at most half of the data area, see Segment::push().
+pub const MAX_MESSAGE_SIZE: usize = DATA_SIZE / 2 - RECORD_ALIGN;
+
+// How many times a sender tries to get the writer lock before checking that
+// its holder is still alive. The lock is held only for copying one message.
+const WRITER_LOCK_ATTEMPTS: usize = 1000;
+
+#[repr(C)]
+struct Header {
+  magic: AtomicU32,
+  version: AtomicU32,
+  capacity: AtomicU32,
+  state: AtomicU32,
+  // Process id of the sender holding the lock, or 0 if free
+  writer_lock: AtomicU32,
+  // Set by the listener when it has read everything and waits for a doorbell.
+  reader_waiting: AtomicU32,
+  owner_pid: AtomicU32,
+  _reserved: AtomicU32,
+  // Positions only grow. The offset in the data area is position % capacity.
+  head: AtomicU64,
+  tail: AtomicU64,
+}
+
+const_assert!(size_of::<Header>() <= HEADER_SIZE);
+// Offset of owner_pid, for reading it without mapping the segment
+const OWNER_PID_OFFSET: usize = 24;
+
+fn record_size(message_len: usize) -> usize {
+  (LENGTH_SIZE + message_len + RECORD_ALIGN - 1) / RECORD_ALIGN * RECORD_ALIGN
+}
+
+// A mapped segment, either our own or that of a remote participant
+struct Segment {
+  _mmap: MmapMut,
+  base: *mut u8,
+  capacity: usize,
+}
+
+// The raw pointer points into the mapping, which moves with the Segment.
+unsafe impl Send for Segment {}
+
+impl fmt::Debug for Segment {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Segment")
+      .field("capacity", &self.capacity)
+      .finish()
+  }
+}
+
+impl Segment {
+  fn from_file(file: &File) -> io::Result<Self> {
+    let len = usize::try_from(file.metadata()?.len())
+      .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "segment too large"))?;
+    if len <= HEADER_SIZE {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "segment too small",
+      ));
+    }
+    // Safety: Other processes modify the mapped memory concurrently. We access
+    // it only through atomics in the header and raw pointer copies of the data
+    // area, never through references to the data.
+    let mut mmap = unsafe { MmapMut::map_mut(file)? };
+    let base = mmap.as_mut_ptr();
+    Ok(Self {
+      _mmap: mmap,
+      base,
+      capacity: len - HEADER_SIZE,
+    })
+  }
+
+  // Handshake of a sender: Map a segment created by a listener, and check that
+  // it is ready.
+  fn open(path: &Path) -> io::Result<Self> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    let segment = Self::from_file(&file)?;
+    let header = segment.header();
+    if header.magic.load(Ordering::Acquire) != MAGIC
+      || header.version.load(Ordering::Acquire) != VERSION
+      || header.capacity.load(Ordering::Acquire) as usize != segment.capacity
+    {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "not a compatible segment",
+      ));
+    }
+    if header.state.load(Ordering::Acquire) != STATE_READY {
+      return Err(io::Error::new(
+        io::ErrorKind::NotConnected,
+        "segment is not ready",
+      ));
+    }
+    Ok(segment)
+  }
+
+  fn header(&self) -> &Header {
+    // Safety: The mapping is page-aligned and larger than the header.
+    unsafe { &*(self.base as *const Header) }
+  }
+
+  fn data(&self) -> *mut u8 {
+    // Safety: The mapping is larger than the header.
+    unsafe { self.base.add(HEADER_SIZE) }
+  }
+
+  fn is_ready(&self) -> bool {
+    self.header().state.load(Ordering::Acquire) == STATE_READY
+  }
+
+  // Append a message to the ring buffer. Returns true if the listener is
+  // waiting, and must be woken up.
+  fn push(&self, message: &[u8]) -> io::Result<bool> {
+    let size = record_size(message.len());
+    if size > self.capacity / 2 {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "message too large for shared memory",
+      ));
+    }
+    let header = self.header();
+
+    let pid = std::process::id();
+    let mut attempts = 0;
+    while let Err(holder) =
+      header
+        .writer_lock
+        .compare_exchange_weak(0, pid, Ordering::Acquire, Ordering::Relaxed)
+    {
+      attempts += 1;
+      if attempts >= WRITER_LOCK_ATTEMPTS {
+        // A sender that died while holding the lock has not moved the tail,
+        // so its partial record is simply overwritten.
+        if holder != 0
+          && !process_exists(holder)
+          && header
+            .writer_lock
+            .compare_exchange(holder, pid, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+          warn!(
+            "Took over shared memory writer lock from dead process {}",
+            holder
+          );
+          break;
+        }
+        return Err(io::Error::new(
+          io::ErrorKind::WouldBlock,
+          "shared memory writer lock is busy",
+        ));
+      }
+      thread::yield_now();
+    }
+
+    let head = header.head.load(Ordering::Acquire);
+    let mut tail = header.tail.load(Ordering::Relaxed);
+    let offset = (tail % self.capacity as u64) as usize;
+    let wrap = if self.capacity - offset < size {
+      self.capacity - offset
+    } else {
+      0
+    };
+    if (tail - head) as usize + wrap + size > self.capacity {
+      header.writer_lock.store(0, Ordering::Release);
+      return Err(io::Error::new(
+        io::ErrorKind::WouldBlock,
+        "shared memory ring buffer is full",
+      ));
+    }
+    // Safety: Offsets are within the data area, and the space between tail and
+    // head + capacity is not read by the listener.
+    unsafe {
+      if wrap > 0 {
+        self.write_length(offset, WRAP_MARKER);
+        tail += wrap as u64;
+      }
+      let offset = (tail % self.capacity as u64) as usize;
+      self.write_length(offset, message.len() as u32);
+      ptr::copy_nonoverlapping(
+        message.as_ptr(),
+        self.data().add(offset + LENGTH_SIZE),
+        message.len(),
+      );
+    }
+    header.tail.store(tail + size as u64, Ordering::SeqCst);
+    header.writer_lock.store(0, Ordering::Release);
+
+    Ok(header.reader_waiting.swap(0, Ordering::SeqCst) != 0)
+  }
+
+  // Take all messages from the ring buffer, and declare that we are waiting
+  // for more.
+  fn pop_all(&self) -> Vec<Bytes> {
+    let header = self.header();
+    let mut messages = Vec::new();
+    loop {
+      let tail = header.tail.load(Ordering::Acquire);
+      let mut head = header.head.load(Ordering::Relaxed);
+      while head != tail {
+        let offset = (head % self.capacity as u64) as usize;
+        // Safety: The space between head and tail has been written by senders.
+        let length = unsafe { self.read_length(offset) };
+        if length == WRAP_MARKER {
+          head += (self.capacity - offset) as u64;
+          continue;
+        }
+        let length = length as usize;
+        if offset + LENGTH_SIZE + length > self.capacity {
+          error!("Corrupted shared memory segment. Discarding its contents.");
+          head = tail;
+          break;
+        }
+        let mut message = vec![0; length];
+        // Safety: Checked above that the message is within the data area.
+        unsafe {
+          ptr::copy_nonoverlapping(
+            self.data().add(offset + LENGTH_SIZE),
+            message.as_mut_ptr(),
+            length,
+          );
+        }
+        messages.push(Bytes::from(message));
+        head += record_size(length) as u64;
+      }
+      header.head.store(head, Ordering::Release);
+
+      // Senders wake us up only if we say we are waiting. If a message arrived
+      // after we saw the tail, we must read it now.
+      header.reader_waiting.store(1, Ordering::SeqCst);
+      if header.tail.load(Ordering::SeqCst) == head {
+        return messages;
+      }
+      header.reader_waiting.store(0, Ordering::Relaxed);
+    }
+  }
+
+  unsafe fn write_length(&self, offset: usize, length: u32) {
+    ptr::copy_nonoverlapping(
+      length.to_ne_bytes().as_ptr(),
+      self.data().add(offset),
+      LENGTH_SIZE,
+    );
+  }
+
+  unsafe fn read_length(&self, offset: usize) -> u32 {
+    let mut bytes = [0; LENGTH_SIZE];
+    ptr::copy_nonoverlapping(self.data().add(offset), bytes.as_mut_ptr(), LENGTH_SIZE);
+    u32::from_ne_bytes(bytes)
+  }
+}
+
+// Directory where segments are created. /dev/shm is backed by memory on Linux.
+fn shared_memory_dir() -> PathBuf {
+  let dev_shm = Path::new("/dev/shm");
+  if dev_shm.is_dir() {
+    dev_shm.to_path_buf()
+  } else {
+    env::temp_dir()
+  }
+}
+
+fn segment_path(dir: &Path, segment_id: u64) -> PathBuf {
+  dir.join(format!("{}{:016x}", SEGMENT_FILE_PREFIX, segment_id))
+}
+
+// Read the host id, or create it if we are the first on this host. The file
+// is linked into place only after it is complete, so that others never read a
+// partial id.
+fn host_id(dir: &Path) -> io::Result<[u8; 8]> {
+  let path = dir.join(HOST_ID_FILE);
+  if !path.exists() {
+    let temp_path = dir.join(format!("{}.{}", HOST_ID_FILE, std::process::id()));
+    File::create(&temp_path)?.write_all(&rand::random::<[u8; 8]>())?;
+    let linked = fs::hard_link(&temp_path, &path);
+    fs::remove_file(&temp_path)?;
+    match linked {
+      Ok(()) => (),
+      // Someone else was first.
+      Err(e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+      Err(e) => return Err(e),
+    }
+  }
+  let mut id = [0; 8];
+  File::open(&path)?.read_exact(&mut id)?;
+  Ok(id)
+}
+
+#[cfg(target_os = "linux")]
+fn process_exists(pid: u32) -> bool {
+  Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+// Without a way to check, assume that the process is alive.
+#[cfg(not(target_os = "linux"))]
+fn process_exists(_pid: u32) -> bool {
+  true
+}
+
+// Segments of processes that have crashed are not removed by them. Remove
+// segments whose owner process no longer exists.
+#[cfg(target_os = "linux")]
+fn remove_stale_segments(dir: &Path) {
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(e) => {
+      debug!("Cannot list {:?}: {:?}", dir, e);
+      return;
+    }
+  };
+  for entry in entries.flatten() {
+    let is_segment = entry
+      .file_name()
+      .to_str()
+      .map_or(false, |name| name.starts_with(SEGMENT_FILE_PREFIX))
+      && entry.file_name() != HOST_ID_FILE;
+    if !is_segment {
+      continue;
+    }
+    let mut header = [0; OWNER_PID_OFFSET + 4];
+    let read = File::open(entry.path()).and_then(|mut f| f.read_exact(&mut header));
+    if read.is_err() || header[..4] != MAGIC.to_ne_bytes() {
+      // Not yet initialized, or not ours
+      continue;
+    }
+    let mut pid = [0; 4];
+    pid.copy_from_slice(&header[OWNER_PID_OFFSET..]);
+    let pid = u32::from_ne_bytes(pid);
+    if !process_exists(pid) {
+      info!("Removing stale shared memory segment {:?}", entry.path());
+      fs::remove_file(entry.path()).unwrap_or_else(|e| {
+        debug!("Cannot remove {:?}: {:?}", entry.path(), e);
+      });
+    }
+  }
+}
+
+fn shared_memory_locator(host_id: [u8; 8], segment_id: u64, port: u16) -> Locator {
+  let mut address = [0; 16];
+  address[..8].copy_from_slice(&host_id);
+  address[8..].copy_from_slice(&segment_id.to_be_bytes());
+  Locator::Other {
+    kind: locator::kind::SHARED_MEMORY,
+    port: port.into(),
+    address,
+  }
+}
+
+// Segment id and doorbell port, if the locator is a shared memory locator on
+// this host.
+fn parse_locator(host_id: [u8; 8], locator: &Locator) -> Option<(u64, u16)> {
+  match locator {
+    Locator::Other {
+      kind,
+      port,
+      address,
+    } if *kind == locator::kind::SHARED_MEMORY && address[..8] == host_id => {
+      let mut segment_id = [0; 8];
+      segment_id.copy_from_slice(&address[8..]);
+      Some((u64::from_be_bytes(segment_id), u16::try_from(*port).ok()?))
+    }
+    _ => None,
+  }
+}
+
+// Receiving side: owns a segment, and listens to its doorbell.
+#[derive(Debug)]
+pub struct ShmListener {
+  segment: Segment,
+  path: PathBuf,
+  doorbell: UdpSocket,
+  locator: Locator,
+}
+
+impl ShmListener {
+  pub fn new() -> io::Result<Self> {
+    Self::new_in(&shared_memory_dir())
+  }
+
+  fn new_in(dir: &Path) -> io::Result<Self> {
+    let host_id = host_id(dir)?;
+    #[cfg(target_os = "linux")]
+    remove_stale_segments(dir);
+
+    let segment_id = rand::random::<u64>();
+    let path = segment_path(dir, segment_id);
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create_new(true);
+    // Only processes of the same user may send to us.
+    #[cfg(unix)]
+    options.mode(0o600);
+    let file = options.open(&path)?;
+    file.set_len((HEADER_SIZE + DATA_SIZE) as u64)?;
+    let segment = Segment::from_file(&file)?;
+
+    let header = segment.header();
+    header.magic.store(MAGIC, Ordering::Relaxed);
+    header.version.store(VERSION, Ordering::Relaxed);
+    header.capacity.store(DATA_SIZE as u32, Ordering::Relaxed);
+    header
+      .owner_pid
+      .store(std::process::id(), Ordering::Relaxed);
+    header.reader_waiting.store(1, Ordering::Relaxed);
+    header.state.store(STATE_READY, Ordering::Release);
+
+    let doorbell = UdpSocket::bind(&SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0))?;
+    let locator = shared_memory_locator(host_id, segment_id, doorbell.local_addr()?.port());
+    info!("ShmListener::new() --> {:?} at {:?}", locator, path);
+
+    Ok(Self {
+      segment,
+      path,
+      doorbell,
+      locator,
+    })
+  }
+}
+
+impl Drop for ShmListener {
+  fn drop(&mut self) {
+    self
+      .segment
+      .header()
+      .state
+      .store(STATE_CLOSED, Ordering::Release);
+    fs::remove_file(&self.path).unwrap_or_else(|e| {
+      error!(
+        "Cannot remove shared memory segment {:?}: {:?}",
+        self.path, e
+      );
+    });
+  }
+}
+
+impl TransportListener for ShmListener {
+  fn kind(&self) -> TransportKind {
+    TransportKind::SharedMemory
+  }
+
+  fn evented(&self) -> &dyn Evented {
+    &self.doorbell
+  }
+
+  fn messages(&mut self) -> Vec<Bytes> {
+    // The doorbell datagrams carry no data. Just empty the socket.
+    let mut buf = [0; 16];
+    while self.doorbell.recv_from(&mut buf).is_ok() {}
+    self.segment.pop_all()
+  }
+
+  fn self_locators(&self) -> io::Result<Vec<Locator>> {
+    Ok(vec![self.locator])
+  }
+}
+
+// Sending side: maps the segments of other participants on this host as
+// needed.
+#[derive(Debug)]
+pub struct ShmSender {
+  dir: PathBuf,
+  host_id: [u8; 8],
+  doorbell: std::net::UdpSocket,
+  segments: RefCell<HashMap<u64, Segment>>,
+  max_message_size: usize,
+}
+
+impl ShmSender {
+  pub fn new() -> io::Result<Self> {
+    Self::new_in(shared_memory_dir())
+  }
+
+  #[must_use]
+  pub fn with_max_message_size(mut self, max_message_size: Option<usize>) -> Self {
+    self.max_message_size = max_message_size.unwrap_or(MAX_MESSAGE_SIZE);
+    self
+  }
+
+  fn new_in(dir: PathBuf) -> io::Result<Self> {
+    let host_id = host_id(&dir)?;
+    let doorbell = std::net::UdpSocket::bind(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0))?;
+    doorbell.set_nonblocking(true)?;
+    Ok(Self {
+      dir,
+      host_id,
+      doorbell,
+      segments: RefCell::new(HashMap::new()),
+      max_message_size: MAX_MESSAGE_SIZE,
+    })
+  }
+}
+
+impl Transport for ShmSender {
+  fn kind(&self) -> TransportKind {
+    TransportKind::SharedMemory
+  }
+
+  fn reaches(&self, locator: &Locator) -> bool {
+    parse_locator(self.host_id, locator).is_some()
+  }
+
+  fn send_to_locator(&self, buffer: &[u8], locator: &Locator) {
+    let (segment_id, port) = match parse_locator(self.host_id, locator) {
+      Some(s) => s,
+      None => {
+        error!("ShmSender cannot send to {:?}", locator);
+        return;
+      }
+    };
+    let mut segments = self.segments.borrow_mut();
+    if !segments.get(&segment_id).map_or(false, Segment::is_ready) {
+      // Not yet mapped, or the listener has gone away.
+      segments.remove(&segment_id);
+      match Segment::open(&segment_path(&self.dir, segment_id)) {
+        Ok(segment) => {
+          debug!("Mapped shared memory segment of {:?}", locator);
+          segments.insert(segment_id, segment);
+        }
+        Err(e) => {
+          debug!("Cannot map shared memory of {:?}: {:?}", locator, e);
+          return;
+        }
+      }
+    }
+    let wake_up = match segments.get(&segment_id).map(|s| s.push(buffer)) {
+      Some(Ok(wake_up)) => wake_up,
+      Some(Err(e)) => {
+        debug!("Cannot send to {:?}: {:?}", locator, e);
+        return;
+      }
+      None => return,
+    };
+    if wake_up {
+      let doorbell = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port);
+      if let Err(e) = self.doorbell.send_to(&[0], doorbell) {
+        debug!(
+          "Cannot wake up shared memory listener {:?}: {:?}",
+          locator, e
+        );
+      }
+    }
+  }
+
+  fn max_message_size(&self) -> usize {
+    self.max_message_size
+  }
+
+  // There are no queues to prioritize.
+  fn set_transport_priority(&self, _priority: i32) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn test_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("rustdds-shm-test-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  #[test]
+  fn shm_send_and_receive() {
+    let dir = test_dir("send");
+    let mut listener = ShmListener::new_in(&dir).unwrap();
+    let sender = ShmSender::new_in(dir.clone()).unwrap();
+    let locator = listener.self_locators().unwrap()[0];
+    assert!(sender.reaches(&locator));
+
+    let messages: Vec<Vec<u8>> = vec![vec![1, 2, 3], vec![], vec![4; 1000]];
+    for m in &messages {
+      sender.send_to_locator(m, &locator);
+    }
+    assert_eq!(listener.messages(), messages);
+    assert!(listener.messages().is_empty());
+
+    drop(listener);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn shm_ring_buffer_wraps_around() {
+    let dir = test_dir("wrap");
+    let mut listener = ShmListener::new_in(&dir).unwrap();
+    let sender = ShmSender::new_in(dir.clone()).unwrap();
+    let locator = listener.self_locators().unwrap()[0];
+
+    // Each round fills a bit over a third of the buffer, so that records wrap
+    // around the end.
+    let message = vec![7; DATA_SIZE / 3];
+    for _ in 0..10 {
+      sender.send_to_locator(&message, &locator);
+      assert_eq!(listener.messages(), vec![message.clone()]);
+    }
+
+    drop(listener);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn shm_segment_is_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = test_dir("private");
+    let listener = ShmListener::new_in(&dir).unwrap();
+    let mode = fs::metadata(&listener.path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    drop(listener);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  #[cfg(target_os = "linux")]
+  fn shm_writer_lock_of_dead_process_is_taken_over() {
+    let dir = test_dir("lock");
+    let mut listener = ShmListener::new_in(&dir).unwrap();
+    let sender = ShmSender::new_in(dir.clone()).unwrap();
+    let locator = listener.self_locators().unwrap()[0];
+
+    let mut child = std::process::Command::new("true").spawn().unwrap();
+    let dead_pid = child.id();
+    child.wait().unwrap();
+    let header = listener.segment.header();
+    header.writer_lock.store(dead_pid, Ordering::Release);
+
+    sender.send_to_locator(&[1, 2, 3], &locator);
+    assert_eq!(header.writer_lock.load(Ordering::Acquire), 0);
+    assert_eq!(listener.messages(), vec![vec![1, 2, 3]]);
+
+    drop(listener);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn shm_other_host_is_not_reached() {
+    let dir = test_dir("host");
+    let sender = ShmSender::new_in(dir.clone()).unwrap();
+    let mut other_host_id = sender.host_id;
+    other_host_id[0] ^= 1;
+
+    assert!(!sender.reaches(&shared_memory_locator(other_host_id, 1, 7411)));
+    assert!(!sender.reaches(&Locator::from(SocketAddr::new(
+      Ipv4Addr::LOCALHOST.into(),
+      7411
+    ))));
+    assert!(sender.reaches(&shared_memory_locator(sender.host_id, 1, 7411)));
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
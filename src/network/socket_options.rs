@@ -0,0 +1,78 @@
+use std::{collections::BTreeMap, io};
+
+use socket2::SockRef;
+
+use crate::network::transport::TransportKind;
+
+// Options of the UDP sockets of a DomainParticipant, set with the
+// DomainParticipantBuilder. None means the operating system default.
+//
+// Maximum message sizes are here, too, although shared memory has no sockets.
+// Transports that are not listed use their default.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SocketOptions {
+  pub max_message_size: BTreeMap<TransportKind, usize>,
+  pub send_buffer_size: Option<usize>,
+  pub receive_buffer_size: Option<usize>,
+  pub multicast_ttl: Option<u32>,
+  // We want to hear other DomainParticipants on the same host, so this is on
+  // by default.
+  pub multicast_loopback: bool,
+  pub dscp: Option<u8>,
+}
+
+impl Default for SocketOptions {
+  fn default() -> Self {
+    Self {
+      max_message_size: BTreeMap::new(),
+      send_buffer_size: None,
+      receive_buffer_size: None,
+      multicast_ttl: None,
+      multicast_loopback: true,
+      dscp: None,
+    }
+  }
+}
+
+impl SocketOptions {
+  // DSCP is the upper six bits of the IP TOS octet.
+  pub const DSCP_MAX: u8 = 63;
+
+  // Configured maximum message size, limited to what the transport can carry
+  pub fn max_message_size(&self, kind: TransportKind) -> Option<usize> {
+    self
+      .max_message_size
+      .get(&kind)
+      .map(|size| (*size).min(kind.max_message_size_limit()))
+  }
+
+  pub fn apply_to_sender(&self, socket: &SockRef, ipv6: bool) -> io::Result<()> {
+    if let Some(size) = self.send_buffer_size {
+      socket.set_send_buffer_size(size)?;
+    }
+    if ipv6 {
+      socket.set_multicast_loop_v6(self.multicast_loopback)?;
+      if let Some(ttl) = self.multicast_ttl {
+        socket.set_multicast_hops_v6(ttl)?;
+      }
+      // socket2 does not expose the IPv6 traffic class, so DSCP is not
+      // applied.
+    } else {
+      socket.set_multicast_loop_v4(self.multicast_loopback)?;
+      if let Some(ttl) = self.multicast_ttl {
+        socket.set_multicast_ttl_v4(ttl)?;
+      }
+      if let Some(dscp) = self.dscp {
+        socket.set_tos(u32::from(dscp.min(Self::DSCP_MAX)) << 2)?;
+      }
+    }
+    Ok(())
+  }
+
+  pub fn apply_to_listener(&self, socket: &SockRef) -> io::Result<()> {
+    if let Some(size) = self.receive_buffer_size {
+      socket.set_recv_buffer_size(size)?;
+    }
+    Ok(())
+  }
+}
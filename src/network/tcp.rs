@@ -0,0 +1,540 @@
+use std::{
+  cell::{Cell, RefCell},
+  collections::{hash_map::Entry, HashMap},
+  convert::TryFrom,
+  fmt,
+  io::{self, ErrorKind, Read, Write},
+  net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, TcpStream},
+  thread,
+  time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use mio::{
+  net::{TcpListener, TcpStream as MioTcpStream},
+  Evented, Events, Poll, PollOpt, Ready, Token,
+};
+use mio_extras::channel as mio_channel;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use socket2::SockRef;
+
+use crate::{
+  network::{
+    interface_filter::InterfaceFilter,
+    socket_options::SocketOptions,
+    transport::{Transport, TransportKind, TransportListener},
+    util::get_local_unicast_locators,
+  },
+  structure::locator::Locator,
+};
+
+// RTPS over TCP between RustDDS participants. Only user traffic goes over TCP.
+// Discovery uses UDP, and announces the TCP locators like any other.
+//
+// Each RTPS message is sent as a frame: its length as a big-endian u32, and
+// then the message itself. The TCP transports of other DDS implementations
+// have framing of their own, so our locators are of a RustDDS-specific kind.
+//
+// The sender connects to a remote listener on first use, and keeps the
+// connection. A connection that fails is closed, so that a partially written
+// frame cannot confuse the receiver, and a new one is made for later messages.
+// Messages that cannot be sent are lost, just like UDP datagrams, and reliable
+// Writers repair the loss.
+//
+// The event loop of the participant polls each listener with a single Token,
+// but a TCP listener has a socket for each connection. So the listener has a
+// thread of its own, which accepts connections and reads the frames, and
+// passes the messages on through a channel. The event loop polls the channel.
+
+// Frames larger than this are not accepted, so that a broken or malicious
+// peer cannot make us allocate without limit.
+pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+const FRAME_HEADER_SIZE: usize = 4;
+
+// Sending blocks the event loop, so do not wait long.
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(200);
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+// After failing to connect, messages to the same address are dropped for this
+// long, instead of blocking on each of them.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+// Tokens of the poll of the listener thread
+const STOP_TOKEN: Token = Token(0);
+const ACCEPT_TOKEN: Token = Token(1);
+const FIRST_CONNECTION_TOKEN: usize = 2;
+
+// Receiving side: accepts connections on a port, and delivers the messages
+// received from all of them.
+pub struct TCPListener {
+  messages: mio_channel::Receiver<(Bytes, IpAddr)>,
+  stop_sender: mio_channel::Sender<()>,
+  thread: Option<thread::JoinHandle<()>>,
+  socket: TcpListener,
+  interfaces: InterfaceFilter,
+}
+
+impl TCPListener {
+  // Port 0 lets the operating system choose.
+  pub fn new(port: u16) -> io::Result<Self> {
+    let socket = TcpListener::bind(&SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port))?;
+    let thread_socket = socket.try_clone()?;
+    let (message_sender, messages) = mio_channel::channel();
+    let (stop_sender, stop_receiver) = mio_channel::channel();
+
+    let poll = Poll::new()?;
+    poll.register(
+      &stop_receiver,
+      STOP_TOKEN,
+      Ready::readable(),
+      PollOpt::edge(),
+    )?;
+    poll.register(
+      &thread_socket,
+      ACCEPT_TOKEN,
+      Ready::readable(),
+      PollOpt::edge(),
+    )?;
+    let thread = thread::Builder::new()
+      .name(format!("RustDDS TCP {}", socket.local_addr()?.port()))
+      .spawn(move || {
+        // The receiver must live as long as its registration.
+        let _stop_receiver = stop_receiver;
+        ListenerThread {
+          poll,
+          socket: thread_socket,
+          connections: HashMap::new(),
+          next_token: FIRST_CONNECTION_TOKEN,
+          message_sender,
+        }
+        .run();
+      })?;
+    info!("TCPListener::new() --> {:?}", socket.local_addr());
+
+    Ok(Self {
+      messages,
+      stop_sender,
+      thread: Some(thread),
+      socket,
+      interfaces: InterfaceFilter::default(),
+    })
+  }
+
+  #[must_use]
+  pub fn with_interfaces(mut self, interfaces: InterfaceFilter) -> Self {
+    self.interfaces = interfaces;
+    self
+  }
+
+  pub fn port(&self) -> io::Result<u16> {
+    Ok(self.socket.local_addr()?.port())
+  }
+}
+
+impl fmt::Debug for TCPListener {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("TCPListener")
+      .field("socket", &self.socket)
+      .finish()
+  }
+}
+
+impl Drop for TCPListener {
+  fn drop(&mut self) {
+    // If the thread has already stopped, there is no one to receive.
+    self.stop_sender.send(()).unwrap_or(());
+    if let Some(thread) = self.thread.take() {
+      thread
+        .join()
+        .unwrap_or_else(|e| error!("TCP listener thread panicked: {:?}", e));
+    }
+  }
+}
+
+impl TransportListener for TCPListener {
+  fn kind(&self) -> TransportKind {
+    TransportKind::TcpV4
+  }
+
+  fn evented(&self) -> &dyn Evented {
+    &self.messages
+  }
+
+  fn messages(&mut self) -> Vec<Bytes> {
+    self
+      .messages_with_source()
+      .into_iter()
+      .map(|(message, _source)| message)
+      .collect()
+  }
+
+  fn messages_with_source(&mut self) -> Vec<(Bytes, Option<IpAddr>)> {
+    let mut messages = Vec::new();
+    while let Ok((message, source)) = self.messages.try_recv() {
+      messages.push((message, Some(source)));
+    }
+    messages
+  }
+
+  // The UDPv4 addresses of the interfaces, with our port
+  fn self_locators(&self) -> io::Result<Vec<Locator>> {
+    let port = self.port()?;
+    Ok(
+      get_local_unicast_locators(TransportKind::UdpV4, port, &self.interfaces)
+        .into_iter()
+        .filter_map(|locator| match locator {
+          Locator::UdpV4(socket_address) => Some(Locator::TcpV4(socket_address)),
+          _ => None,
+        })
+        .collect(),
+    )
+  }
+
+  // Accepted connections inherit the buffer size of the listening socket.
+  fn set_socket_options(&self, options: &SocketOptions) -> io::Result<()> {
+    options.apply_to_listener(&SockRef::from(&self.socket))
+  }
+}
+
+// Connection accepted by the listener thread
+struct Connection {
+  stream: MioTcpStream,
+  peer: IpAddr,
+  // Received bytes that do not yet make a complete frame
+  buffer: Vec<u8>,
+}
+
+impl Connection {
+  // Reads what has arrived, and sends the complete messages. Returns false if
+  // the connection should be closed.
+  fn receive(&mut self, message_sender: &mio_channel::Sender<(Bytes, IpAddr)>) -> bool {
+    let mut chunk = vec![0; READ_CHUNK_SIZE];
+    loop {
+      match self.stream.read(&mut chunk) {
+        Ok(0) => return false,
+        Ok(n) => {
+          self.buffer.extend_from_slice(&chunk[..n]);
+          if !self.send_messages(message_sender) {
+            return false;
+          }
+        }
+        Err(e) if e.kind() == ErrorKind::WouldBlock => return true,
+        Err(e) if e.kind() == ErrorKind::Interrupted => {}
+        Err(e) => {
+          debug!("TCP connection from {} failed: {:?}", self.peer, e);
+          return false;
+        }
+      }
+    }
+  }
+
+  // Sends the complete frames in the buffer, and keeps the rest.
+  fn send_messages(&mut self, message_sender: &mio_channel::Sender<(Bytes, IpAddr)>) -> bool {
+    let mut start = 0;
+    while self.buffer.len() - start >= FRAME_HEADER_SIZE {
+      let mut header = [0; FRAME_HEADER_SIZE];
+      header.copy_from_slice(&self.buffer[start..start + FRAME_HEADER_SIZE]);
+      let length = u32::from_be_bytes(header) as usize;
+      if length > MAX_MESSAGE_SIZE {
+        warn!(
+          "TCP connection from {} sent a frame of {} bytes. Closing it.",
+          self.peer, length
+        );
+        return false;
+      }
+      let end = start + FRAME_HEADER_SIZE + length;
+      if self.buffer.len() < end {
+        break;
+      }
+      let message = Bytes::copy_from_slice(&self.buffer[start + FRAME_HEADER_SIZE..end]);
+      if message_sender.send((message, self.peer)).is_err() {
+        // The listener is gone.
+        return false;
+      }
+      start = end;
+    }
+    self.buffer.drain(..start);
+    true
+  }
+}
+
+struct ListenerThread {
+  poll: Poll,
+  socket: TcpListener,
+  connections: HashMap<Token, Connection>,
+  next_token: usize,
+  message_sender: mio_channel::Sender<(Bytes, IpAddr)>,
+}
+
+impl ListenerThread {
+  fn run(mut self) {
+    let mut events = Events::with_capacity(64);
+    loop {
+      if let Err(e) = self.poll.poll(&mut events, None) {
+        if e.kind() == ErrorKind::Interrupted {
+          continue;
+        }
+        error!("TCP listener poll failed: {:?}", e);
+        return;
+      }
+      for event in &events {
+        match event.token() {
+          STOP_TOKEN => return,
+          ACCEPT_TOKEN => self.accept(),
+          token => {
+            let message_sender = &self.message_sender;
+            let open = self
+              .connections
+              .get_mut(&token)
+              .map_or(true, |c| c.receive(message_sender));
+            if !open {
+              if let Some(connection) = self.connections.remove(&token) {
+                debug!("TCP connection from {} closed", connection.peer);
+                self.poll.deregister(&connection.stream).unwrap_or(());
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+
+  fn accept(&mut self) {
+    loop {
+      match self.socket.accept() {
+        Ok((stream, peer)) => {
+          let token = Token(self.next_token);
+          self.next_token += 1;
+          if let Err(e) = self
+            .poll
+            .register(&stream, token, Ready::readable(), PollOpt::edge())
+          {
+            error!("Cannot register TCP connection from {}: {:?}", peer, e);
+            continue;
+          }
+          debug!("TCP connection from {}", peer);
+          self.connections.insert(
+            token,
+            Connection {
+              stream,
+              peer: peer.ip(),
+              buffer: Vec::new(),
+            },
+          );
+        }
+        Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+        Err(e) => {
+          warn!("Cannot accept TCP connection: {:?}", e);
+          return;
+        }
+      }
+    }
+  }
+}
+
+// Sending side: connects to remote listeners as needed.
+#[derive(Debug)]
+pub struct TCPSender {
+  connections: RefCell<HashMap<SocketAddrV4, TcpStream>>,
+  // When connecting to an address last failed
+  failed: RefCell<HashMap<SocketAddrV4, Instant>>,
+  options: SocketOptions,
+  // IP TOS from TRANSPORT_PRIORITY, for the connections made later
+  tos: Cell<Option<u32>>,
+  max_message_size: usize,
+}
+
+impl TCPSender {
+  pub fn new(options: &SocketOptions) -> Self {
+    Self {
+      connections: RefCell::new(HashMap::new()),
+      failed: RefCell::new(HashMap::new()),
+      options: options.clone(),
+      tos: Cell::new(None),
+      max_message_size: options
+        .max_message_size(TransportKind::TcpV4)
+        .unwrap_or(MAX_MESSAGE_SIZE),
+    }
+  }
+
+  fn connect(&self, address: SocketAddrV4) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(&address.into(), CONNECT_TIMEOUT)?;
+    stream.set_nodelay(true)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let socket = SockRef::from(&stream);
+    if let Some(size) = self.options.send_buffer_size {
+      socket.set_send_buffer_size(size)?;
+    }
+    let tos = self.tos.get().or_else(|| {
+      self
+        .options
+        .dscp
+        .map(|dscp| u32::from(dscp.min(SocketOptions::DSCP_MAX)) << 2)
+    });
+    if let Some(tos) = tos {
+      socket.set_tos(tos)?;
+    }
+    Ok(stream)
+  }
+}
+
+impl Transport for TCPSender {
+  fn kind(&self) -> TransportKind {
+    TransportKind::TcpV4
+  }
+
+  fn send_to_locator(&self, buffer: &[u8], locator: &Locator) {
+    let address = match locator {
+      Locator::TcpV4(address) => *address,
+      _ => {
+        error!("TCPSender cannot send to {:?}", locator);
+        return;
+      }
+    };
+    let length = match u32::try_from(buffer.len()) {
+      Ok(length) if buffer.len() <= MAX_MESSAGE_SIZE => length,
+      _ => {
+        error!(
+          "TCPSender: Message of {} bytes is too large to send",
+          buffer.len()
+        );
+        return;
+      }
+    };
+
+    let mut connections = self.connections.borrow_mut();
+    let stream = match connections.entry(address) {
+      Entry::Occupied(entry) => entry.into_mut(),
+      Entry::Vacant(entry) => {
+        let mut failed = self.failed.borrow_mut();
+        if failed
+          .get(&address)
+          .map_or(false, |at| at.elapsed() < RECONNECT_DELAY)
+        {
+          return;
+        }
+        match self.connect(address) {
+          Ok(stream) => {
+            debug!("TCP connection to {}", address);
+            failed.remove(&address);
+            entry.insert(stream)
+          }
+          Err(e) => {
+            debug!("Cannot connect to {}: {:?}", address, e);
+            failed.insert(address, Instant::now());
+            return;
+          }
+        }
+      }
+    };
+
+    let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + buffer.len());
+    frame.extend_from_slice(&length.to_be_bytes());
+    frame.extend_from_slice(buffer);
+    let written = stream.write_all(&frame);
+    if let Err(e) = written {
+      debug!("TCP connection to {} failed: {:?}", address, e);
+      connections.remove(&address);
+    }
+  }
+
+  fn max_message_size(&self) -> usize {
+    self.max_message_size
+  }
+
+  /// Map DDS TRANSPORT_PRIORITY to DSCP, as with UDP. Connections made later
+  /// get it, too.
+  fn set_transport_priority(&self, priority: i32) -> io::Result<()> {
+    let tos = (priority.clamp(0, 63) as u32) << 2;
+    self.tos.set(Some(tos));
+    for stream in self.connections.borrow().values() {
+      SockRef::from(stream).set_tos(tos)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn receive(listener: &mut TCPListener, count: usize) -> Vec<(Bytes, Option<IpAddr>)> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut messages = Vec::new();
+    while messages.len() < count {
+      assert!(Instant::now() < deadline, "Timeout waiting for messages");
+      messages.extend(listener.messages_with_source());
+      thread::sleep(Duration::from_millis(10));
+    }
+    messages
+  }
+
+  #[test]
+  fn tcp_send_and_receive() {
+    let mut listener = TCPListener::new(0).unwrap();
+    let sender = TCPSender::new(&SocketOptions::default());
+    let locator = Locator::TcpV4(SocketAddrV4::new(
+      Ipv4Addr::LOCALHOST,
+      listener.port().unwrap(),
+    ));
+    assert!(sender.reaches(&locator));
+
+    // Larger than a read chunk, so that frames are split between reads.
+    let messages: Vec<Vec<u8>> = vec![vec![1, 2, 3], vec![], vec![4; 100_000], vec![5]];
+    for m in &messages {
+      sender.send_to_locator(m, &locator);
+    }
+    let received = receive(&mut listener, messages.len());
+    assert_eq!(
+      received,
+      messages
+        .into_iter()
+        .map(|m| (Bytes::from(m), Some(IpAddr::from(Ipv4Addr::LOCALHOST))))
+        .collect::<Vec<_>>()
+    );
+  }
+
+  #[test]
+  fn tcp_reconnects_to_new_listener() {
+    let listener = TCPListener::new(0).unwrap();
+    let port = listener.port().unwrap();
+    let locator = Locator::TcpV4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port));
+    let sender = TCPSender::new(&SocketOptions::default());
+    sender.send_to_locator(&[1], &locator);
+    drop(listener);
+
+    // The old connection is found broken when writing, and is then replaced.
+    let mut listener = TCPListener::new(port).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut received = Vec::new();
+    while received.is_empty() {
+      assert!(
+        Instant::now() < deadline,
+        "Timeout waiting for reconnection"
+      );
+      sender.send_to_locator(&[2], &locator);
+      thread::sleep(Duration::from_millis(50));
+      received = listener.messages();
+    }
+    assert_eq!(received[0], Bytes::from_static(&[2]));
+  }
+
+  #[test]
+  fn tcp_oversized_frame_closes_connection() {
+    let mut listener = TCPListener::new(0).unwrap();
+    let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, listener.port().unwrap())).unwrap();
+    stream
+      .write_all(&(MAX_MESSAGE_SIZE as u32 + 1).to_be_bytes())
+      .unwrap();
+
+    stream
+      .set_read_timeout(Some(Duration::from_secs(5)))
+      .unwrap();
+    let mut buf = [0; 1];
+    assert!(matches!(stream.read(&mut buf), Ok(0) | Err(_)));
+    assert!(listener.messages().is_empty());
+  }
+}
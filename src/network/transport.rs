@@ -0,0 +1,415 @@
+use std::{
+  collections::HashMap,
+  fmt::Debug,
+  io,
+  net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+  sync::Arc,
+};
+
+use bytes::Bytes;
+use mio::{Evented, Token};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::{
+  dds::statistics::TrafficCounters,
+  network::{
+    constant::self_locators_token,
+    interface_filter::InterfaceFilter,
+    packet_capture::PacketCapture,
+    shared_memory::{self, ShmSender},
+    socket_options::SocketOptions,
+    tcp::{self, TCPSender},
+    udp_sender::UDPSender,
+  },
+  structure::locator::{self, Locator},
+};
+
+/// A network transport that a DomainParticipant can use for RTPS messages.
+///
+/// Select the transports with
+/// [`DomainParticipantBuilder::transports`](crate::DomainParticipantBuilder::transports).
+/// Locators of kinds that none of the selected transports can reach are
+/// ignored, e.g. remote participants may announce locators of transports that
+/// we do not have. If a remote endpoint can be reached by several of the
+/// selected transports, the one listed first is preferred.
+///
+/// [`SharedMemory`](TransportKind::SharedMemory) is listed first in
+/// [`DEFAULT`](TransportKind::DEFAULT), so participants on the same host
+/// communicate by shared memory automatically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum TransportKind {
+  /// UDP over IPv4, as specified in RTPS spec v2.5 Section "9.6 Mapping to
+  /// UDP/IP". This is needed for discovery with the default settings.
+  UdpV4,
+  /// UDP over IPv6. Uses the multicast group `ff02::ffff:239.255.0.1`, which
+  /// is also the default of some other DDS implementations, e.g. Cyclone DDS.
+  UdpV6,
+  /// Shared memory ring buffers, for user traffic with participants on the
+  /// same host. Those are recognized by the locators that they announce in
+  /// Discovery. Discovery itself always uses UDP.
+  ///
+  /// Only RustDDS participants support this. It is not compatible with the
+  /// shared memory transports of other DDS implementations.
+  SharedMemory,
+  /// TCP over IPv4, for user traffic over networks that pass TCP connections
+  /// more reliably than UDP datagrams. The participant listens on its user
+  /// traffic unicast port, and announces that in Discovery. Discovery itself
+  /// always uses UDP.
+  ///
+  /// Only RustDDS participants support this. It is not compatible with the
+  /// TCP transports of other DDS implementations.
+  TcpV4,
+}
+
+impl TransportKind {
+  /// The transports used if not configured otherwise.
+  pub const DEFAULT: &'static [TransportKind] =
+    &[TransportKind::SharedMemory, TransportKind::UdpV4];
+
+  // Whether this kind of transport sends to locators like this
+  pub(crate) fn reaches(self, locator: &Locator) -> bool {
+    match self {
+      Self::UdpV4 => matches!(locator, Locator::UdpV4(_)),
+      Self::UdpV6 => matches!(locator, Locator::UdpV6(_)),
+      Self::SharedMemory => matches!(
+        locator,
+        Locator::Other { kind, .. } if *kind == locator::kind::SHARED_MEMORY
+      ),
+      Self::TcpV4 => matches!(locator, Locator::TcpV4(_)),
+    }
+  }
+
+  // Address to bind listening sockets to. None if the transport does not use
+  // UDP ports.
+  pub(crate) fn unspecified_address(self) -> Option<&'static str> {
+    match self {
+      Self::UdpV4 => Some("0.0.0.0"),
+      Self::UdpV6 => Some("::"),
+      Self::SharedMemory | Self::TcpV4 => None,
+    }
+  }
+
+  // Largest message that the transport can carry at all
+  pub(crate) fn max_message_size_limit(self) -> usize {
+    match self {
+      // 64 KiB minus IP and UDP headers
+      Self::UdpV4 => 65_507,
+      Self::UdpV6 => 65_527,
+      Self::SharedMemory => shared_memory::MAX_MESSAGE_SIZE,
+      Self::TcpV4 => tcp::MAX_MESSAGE_SIZE,
+    }
+  }
+
+  // Multicast group for discovery and user traffic, if the transport has
+  // multicast
+  pub(crate) fn multicast_group(self) -> Option<IpAddr> {
+    match self {
+      Self::UdpV4 => Some(Ipv4Addr::new(239, 255, 0, 1).into()),
+      Self::UdpV6 => Some(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0xffff, 0xefff, 0x0001).into()),
+      Self::SharedMemory | Self::TcpV4 => None,
+    }
+  }
+}
+
+/// Sending side of a network transport, one of the
+/// [`TransportKind`]s. A DomainParticipant sends each RTPS message with the
+/// first of its transports that [`reaches`](Self::reaches) the destination
+/// locator.
+///
+/// This is implemented by the transports of RustDDS, and is public so that
+/// they can be used, and other transports written, outside of a
+/// DomainParticipant, e.g. for tests and tools. A DomainParticipant uses only
+/// the transports selected with
+/// [`DomainParticipantBuilder::transports`](crate::DomainParticipantBuilder::transports).
+pub trait Transport: Debug {
+  fn kind(&self) -> TransportKind;
+
+  /// Whether this transport can send to the locator. Transports may be more
+  /// picky than their kind.
+  fn reaches(&self, locator: &Locator) -> bool {
+    self.kind().reaches(locator)
+  }
+
+  /// Sends one RTPS message. Errors are logged, not returned: RTPS does not
+  /// expect delivery to succeed, and the caller could not do anything about
+  /// it anyway.
+  fn send_to_locator(&self, buffer: &[u8], locator: &Locator);
+
+  /// Largest RTPS message that should be sent with this transport. Larger
+  /// samples are fragmented.
+  fn max_message_size(&self) -> usize;
+
+  /// Map DDS TRANSPORT_PRIORITY to whatever the transport offers, if
+  /// anything.
+  fn set_transport_priority(&self, priority: i32) -> io::Result<()>;
+
+  /// Record sent messages, if the transport can.
+  #[doc(hidden)]
+  fn set_packet_capture(&mut self, _capture: PacketCapture) {}
+}
+
+/// Receiving side of a network transport. The listeners of a
+/// DomainParticipant are polled in its event loop, each with its own `Token`,
+/// so a listener must be readable through a single [`Evented`].
+pub trait TransportListener: Debug + Send {
+  fn kind(&self) -> TransportKind;
+
+  /// What the event loop polls for the messages of this listener
+  fn evented(&self) -> &dyn Evented;
+
+  /// All messages that have arrived, since the listener is polled
+  /// edge-triggered.
+  fn messages(&mut self) -> Vec<Bytes>;
+
+  /// Same as [`messages`](Self::messages), but also tells the address each
+  /// message came from, if the transport knows it.
+  fn messages_with_source(&mut self) -> Vec<(Bytes, Option<IpAddr>)> {
+    self
+      .messages()
+      .into_iter()
+      .map(|message| (message, None))
+      .collect()
+  }
+
+  /// Locators by which remote participants reach this listener
+  fn self_locators(&self) -> io::Result<Vec<Locator>>;
+
+  /// Local network interfaces have come or gone.
+  fn interfaces_changed(&mut self) {}
+
+  /// Apply the options that concern receiving, if the transport has sockets.
+  #[doc(hidden)]
+  fn set_socket_options(&self, _options: &SocketOptions) -> io::Result<()> {
+    Ok(())
+  }
+
+  /// Record received messages, if the transport can.
+  #[doc(hidden)]
+  fn set_packet_capture(&mut self, _capture: PacketCapture) {}
+}
+
+// Locators by which remote participants reach each listener. Errors are
+// logged, and give no locators.
+pub fn listener_locators(
+  listeners: &HashMap<Token, Box<dyn TransportListener>>,
+) -> HashMap<Token, Vec<Locator>> {
+  listeners
+    .iter()
+    .map(|(token, listener)| {
+      let locators = listener.self_locators().unwrap_or_else(|e| {
+        error!("No local network address for token {:?}: {:?}", token, e);
+        Vec::new()
+      });
+      (*token, locators)
+    })
+    .collect()
+}
+
+// Locators by which remote participants reach us, keyed by the UDPv4 listener
+// Tokens, from the locators of each listener. See self_locators_token().
+pub fn self_locators(
+  listener_locators: &HashMap<Token, Vec<Locator>>,
+) -> HashMap<Token, Vec<Locator>> {
+  let mut self_locators: HashMap<Token, Vec<Locator>> = HashMap::new();
+  for (token, locators) in listener_locators {
+    match self_locators_token(*token) {
+      Some(role) => self_locators.entry(role).or_default().extend(locators),
+      None => error!("Listener has an unknown token {:?}", token),
+    }
+  }
+  // The order must not depend on the HashMap, so that changes can be detected
+  // by comparison.
+  for locators in self_locators.values_mut() {
+    locators.sort();
+  }
+  self_locators
+}
+
+// The senders of all the transports of a DomainParticipant. A message to a
+// locator is sent using the transport that reaches it.
+#[derive(Debug, Default)]
+pub struct Transports {
+  transports: Vec<Box<dyn Transport>>,
+  // Participant statistics of sent messages
+  traffic: Option<Arc<TrafficCounters>>,
+}
+
+impl Transports {
+  pub fn new(
+    kinds: &[TransportKind],
+    interfaces: &InterfaceFilter,
+    options: &SocketOptions,
+  ) -> io::Result<Self> {
+    let mut transports = Self::default();
+    for kind in kinds {
+      match kind {
+        // port number 0 means OS chooses an available port number.
+        TransportKind::UdpV4 => transports.add(Box::new(UDPSender::new_with_interfaces(
+          0, interfaces, options,
+        )?)),
+        TransportKind::UdpV6 => {
+          transports.add(Box::new(UDPSender::new_v6(0, interfaces, options)?));
+        }
+        // Without shared memory, the other transports are used also on the
+        // same host.
+        TransportKind::SharedMemory => match ShmSender::new() {
+          Ok(sender) => transports.add(Box::new(
+            sender.with_max_message_size(options.max_message_size(*kind)),
+          )),
+          Err(e) => warn!("Shared memory transport is not available: {:?}", e),
+        },
+        TransportKind::TcpV4 => transports.add(Box::new(TCPSender::new(options))),
+      }
+    }
+    Ok(transports)
+  }
+
+  pub fn add(&mut self, transport: Box<dyn Transport>) {
+    self.transports.push(transport);
+  }
+
+  pub fn with_traffic_counters(mut self, traffic: Arc<TrafficCounters>) -> Self {
+    self.traffic = Some(traffic);
+    self
+  }
+
+  pub fn with_packet_capture(mut self, capture: &PacketCapture) -> Self {
+    for transport in &mut self.transports {
+      transport.set_packet_capture(capture.clone());
+    }
+    self
+  }
+
+  #[cfg(test)]
+  pub fn kinds(&self) -> Vec<TransportKind> {
+    self.transports.iter().map(|t| t.kind()).collect()
+  }
+
+  // Messages must fit all transports, because the same message is often sent
+  // with several of them.
+  pub fn max_message_size(&self) -> usize {
+    self
+      .transports
+      .iter()
+      .map(|t| t.max_message_size())
+      .min()
+      .unwrap_or(UDPSender::DEFAULT_MAX_MESSAGE_SIZE)
+  }
+
+  pub fn set_transport_priority(&self, priority: i32) -> io::Result<()> {
+    self
+      .transports
+      .iter()
+      .try_for_each(|t| t.set_transport_priority(priority))
+  }
+
+  pub fn send_to_locator_list(&self, buffer: &[u8], ll: &[Locator]) {
+    for loc in self.preferred_locators(ll) {
+      self.send_to_locator(buffer, &loc);
+    }
+  }
+
+  // Locator lists often reach the same participant by several transports, e.g.
+  // both its IPv4 and IPv6 addresses. Then only the unicast locators of the
+  // preferred transport are used, so that the receiver does not get duplicates.
+  // Multicast locators are all used, because the members of a group may each
+  // have different transports.
+  pub fn preferred_locators(&self, ll: &[Locator]) -> Vec<Locator> {
+    let is_multicast = |loc: &Locator| SocketAddr::from(*loc).ip().is_multicast();
+    let preferred = self
+      .transports
+      .iter()
+      .find(|t| ll.iter().any(|loc| !is_multicast(loc) && t.reaches(loc)));
+    ll.iter()
+      .filter(|loc| is_multicast(loc) || preferred.map_or(true, |t| t.reaches(loc)))
+      .copied()
+      .collect()
+  }
+
+  pub fn send_to_locator(&self, buffer: &[u8], locator: &Locator) {
+    match self.transports.iter().find(|t| t.reaches(locator)) {
+      Some(transport) => {
+        transport.send_to_locator(buffer, locator);
+        if let Some(traffic) = &self.traffic {
+          traffic.messages_sent.increment();
+          traffic.bytes_sent.add(buffer.len() as u64);
+        }
+      }
+      None => match locator {
+        Locator::Invalid | Locator::Reserved => {
+          error!("send_to_locator: Cannot send to {:?}", locator);
+        }
+        // This is normal, as other implementations can define their own kinds,
+        // and we get those from Discovery.
+        _ => trace!("send_to_locator: No transport for {:?}", locator),
+      },
+    }
+  }
+}
+
+impl From<UDPSender> for Transports {
+  fn from(udp_sender: UDPSender) -> Self {
+    Self {
+      transports: vec![Box::new(udp_sender)],
+      traffic: None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::network::udp_listener::UDPListener;
+
+  #[test]
+  fn transports_send_by_locator_kind() {
+    let listener = UDPListener::new_unicast("127.0.0.1", 10401).unwrap();
+    let transports = Transports::new(
+      &[TransportKind::UdpV4],
+      &InterfaceFilter::default(),
+      &SocketOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(transports.kinds(), vec![TransportKind::UdpV4]);
+
+    let data: Vec<u8> = vec![1, 2, 3];
+    let ipv4: SocketAddr = "127.0.0.1:10401".parse().unwrap();
+    let ipv6: SocketAddr = "[::1]:10401".parse().unwrap();
+    // No transport for IPv6, so this is silently dropped.
+    transports.send_to_locator_list(&data, &[Locator::from(ipv6), Locator::from(ipv4)]);
+
+    assert_eq!(listener.get_message(), data);
+  }
+
+  #[test]
+  fn transports_prefer_first_kind() {
+    let transports = Transports::new(
+      &[TransportKind::UdpV6, TransportKind::UdpV4],
+      &InterfaceFilter::default(),
+      &SocketOptions::default(),
+    )
+    .unwrap();
+    let ipv4 = Locator::from(SocketAddr::new(Ipv4Addr::new(10, 0, 0, 1).into(), 7411));
+    let ipv6 = Locator::from(SocketAddr::new("fd00::1".parse().unwrap(), 7411));
+    let mc_ipv4 = Locator::from(SocketAddr::new(
+      TransportKind::UdpV4.multicast_group().unwrap(),
+      7401,
+    ));
+    let mc_ipv6 = Locator::from(SocketAddr::new(
+      TransportKind::UdpV6.multicast_group().unwrap(),
+      7401,
+    ));
+
+    assert_eq!(
+      transports.preferred_locators(&[ipv4, ipv6, mc_ipv4, mc_ipv6]),
+      vec![ipv6, mc_ipv4, mc_ipv6]
+    );
+    // IPv4 is used, if that is all there is
+    assert_eq!(
+      transports.preferred_locators(&[ipv4, mc_ipv4]),
+      vec![ipv4, mc_ipv4]
+    );
+  }
+}
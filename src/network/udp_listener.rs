@@ -3,14 +3,18 @@ use std::{
   net::{IpAddr, Ipv4Addr, SocketAddr},
 };
 
-use mio::net::UdpSocket;
-use log::{debug, error, info, trace};
-use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use mio::{net::UdpSocket, Evented};
+use log::{debug, error, info, trace, warn};
+use socket2::{Domain, Protocol, SockAddr, SockRef, Socket, Type};
 use bytes::{Bytes, BytesMut};
 
 use crate::{
-  network::util::{
-    get_local_multicast_ip_addrs, get_local_multicast_locators, get_local_unicast_locators,
+  network::{
+    interface_filter::InterfaceFilter,
+    packet_capture::PacketCapture,
+    socket_options::SocketOptions,
+    transport::{TransportKind, TransportListener},
+    util::{get_local_multicast_ip_addrs, get_local_unicast_locators},
   },
   structure::locator::Locator,
 };
@@ -25,25 +29,45 @@ const MESSAGE_BUFFER_ALLOCATION_CHUNK: usize = 256 * 1024; // must be >= MAX_MES
 pub struct UDPListener {
   socket: UdpSocket,
   receive_buffer: BytesMut,
-  multicast_group: Option<Ipv4Addr>,
+  kind: TransportKind,
+  multicast_group: Option<IpAddr>,
+  interfaces: InterfaceFilter,
+  capture: Option<PacketCapture>,
 }
 
 impl Drop for UDPListener {
   fn drop(&mut self) {
-    if let Some(mcg) = self.multicast_group {
-      self
-        .socket
-        .leave_multicast_v4(&mcg, &Ipv4Addr::UNSPECIFIED)
-        .unwrap_or_else(|e| {
-          error!("leave_multicast_group: {:?}", e);
-        });
-    }
+    let left = match self.multicast_group {
+      Some(IpAddr::V4(mcg)) => self.socket.leave_multicast_v4(&mcg, &Ipv4Addr::UNSPECIFIED),
+      Some(IpAddr::V6(mcg)) => self.socket.leave_multicast_v6(&mcg, 0),
+      None => Ok(()),
+    };
+    left.unwrap_or_else(|e| {
+      error!("leave_multicast_group: {:?}", e);
+    });
   }
 }
 
 impl UDPListener {
   fn new_listening_socket(host: &str, port: u16, reuse_addr: bool) -> io::Result<UdpSocket> {
-    let raw_socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    let address = SocketAddr::new(
+      host
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?,
+      port,
+    );
+
+    let raw_socket = Socket::new(
+      Domain::for_address(address),
+      Type::DGRAM,
+      Some(Protocol::UDP),
+    )?;
+
+    // Otherwise an IPv6 socket may also take the IPv4 port, and then the IPv4
+    // listener of the same participant cannot bind.
+    if address.is_ipv6() {
+      raw_socket.set_only_v6(true)?;
+    }
 
     // We set ReuseAddr so that other DomainParticipants on this host can
     // bind to the same multicast address and port.
@@ -61,13 +85,6 @@ impl UDPListener {
       }
     }
 
-    let address = SocketAddr::new(
-      host
-        .parse()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?,
-      port,
-    );
-
     if let Err(e) = raw_socket.bind(&SockAddr::from(address)) {
       info!("new_socket - cannot bind socket: {:?}", e);
       return Err(e);
@@ -91,8 +108,12 @@ impl UDPListener {
     let local_port = self.socket.local_addr()?.port();
 
     match self.multicast_group {
-      Some(_ipv4_addr) => Ok(get_local_multicast_locators(local_port)),
-      None => Ok(get_local_unicast_locators(local_port)),
+      Some(group) => Ok(vec![Locator::from(SocketAddr::new(group, local_port))]),
+      None => Ok(get_local_unicast_locators(
+        self.kind,
+        local_port,
+        &self.interfaces,
+      )),
     }
   }
 
@@ -100,13 +121,31 @@ impl UDPListener {
     let mio_socket = Self::new_listening_socket(host, port, false)?;
 
     Ok(Self {
+      kind: Self::kind_of(&mio_socket)?,
       socket: mio_socket,
       receive_buffer: BytesMut::with_capacity(MESSAGE_BUFFER_ALLOCATION_CHUNK),
       multicast_group: None,
+      interfaces: InterfaceFilter::default(),
+      capture: None,
     })
   }
 
-  pub fn new_multicast(host: &str, port: u16, multicast_group: Ipv4Addr) -> io::Result<Self> {
+  // Advertise only the addresses of these interfaces. The socket still
+  // receives on all of them.
+  #[must_use]
+  pub fn with_interfaces(mut self, interfaces: InterfaceFilter) -> Self {
+    self.interfaces = interfaces;
+    self
+  }
+
+  // IPv6 groups are joined on the default multicast interface only, because
+  // we do not know the interface indices.
+  pub fn new_multicast(
+    host: &str,
+    port: u16,
+    multicast_group: IpAddr,
+    interfaces: InterfaceFilter,
+  ) -> io::Result<Self> {
     if !multicast_group.is_multicast() {
       return io::Result::Err(io::Error::new(
         io::ErrorKind::Other,
@@ -116,27 +155,40 @@ impl UDPListener {
 
     let mio_socket = Self::new_listening_socket(host, port, true)?;
 
-    for multicast_if_ipaddr in get_local_multicast_ip_addrs()? {
-      match multicast_if_ipaddr {
-        IpAddr::V4(a) => {
-          mio_socket.join_multicast_v4(&multicast_group, &a)?;
+    match multicast_group {
+      IpAddr::V4(group) => {
+        for multicast_if_ipaddr in get_local_multicast_ip_addrs(&interfaces)? {
+          if let IpAddr::V4(a) = multicast_if_ipaddr {
+            mio_socket.join_multicast_v4(&group, &a)?;
+          }
         }
-        IpAddr::V6(_a) => error!("UDPListener::new_multicast() not implemented for IpV6"), // TODO
       }
+      IpAddr::V6(group) => mio_socket.join_multicast_v6(&group, 0)?,
     }
 
     Ok(Self {
+      kind: Self::kind_of(&mio_socket)?,
       socket: mio_socket,
       receive_buffer: BytesMut::with_capacity(MESSAGE_BUFFER_ALLOCATION_CHUNK),
       multicast_group: Some(multicast_group),
+      interfaces,
+      capture: None,
     })
   }
 
+  fn kind_of(socket: &UdpSocket) -> io::Result<TransportKind> {
+    Ok(match socket.local_addr()? {
+      SocketAddr::V4(_) => TransportKind::UdpV4,
+      SocketAddr::V6(_) => TransportKind::UdpV6,
+    })
+  }
+
+  #[cfg(all(test, feature = "discovery"))]
   pub fn mio_socket(&mut self) -> &mut UdpSocket {
     &mut self.socket
   }
 
-  #[cfg(test)]
+  #[cfg(all(test, feature = "discovery"))]
   pub fn port(&self) -> u16 {
     match self.socket.local_addr() {
       Ok(add) => add.port(),
@@ -181,11 +233,20 @@ impl UDPListener {
 
   /// Get all messages waiting in the socket.
   pub fn messages(&mut self) -> Vec<Bytes> {
+    self
+      .messages_with_source()
+      .into_iter()
+      .map(|(message, _source)| message)
+      .collect()
+  }
+
+  /// Get all messages waiting in the socket, with the addresses they came from.
+  pub fn messages_with_source(&mut self) -> Vec<(Bytes, Option<IpAddr>)> {
     // This code may seem slighlty non-sensical, if you do not know
     // how BytesMut works.
     let mut messages = Vec::with_capacity(4); // just a guess, should cover most cases
     self.ensure_receive_buffer_capacity();
-    while let Ok(nbytes) = self.socket.recv(&mut self.receive_buffer) {
+    while let Ok((nbytes, source)) = self.socket.recv_from(&mut self.receive_buffer) {
       self.receive_buffer.truncate(nbytes);
       // Now append some extra data to align the buffer end, so the next piece will
       // be aligned also. This assumes that the initial buffer was aligned to begin
@@ -202,11 +263,26 @@ impl UDPListener {
       let mut message = self.receive_buffer.split_to(self.receive_buffer.len());
       self.ensure_receive_buffer_capacity();
       message.truncate(nbytes); // discard (hide) padding
-      messages.push(Bytes::from(message)); // freeze and push
+      self.capture_received(&message, source);
+      messages.push((Bytes::from(message), Some(source.ip()))); // freeze and push
     }
     messages
   }
 
+  // Multicast messages were sent to the group, others to the address of the
+  // socket, which may be unspecified.
+  fn capture_received(&self, message: &[u8], source: SocketAddr) {
+    if let Some(capture) = self.capture.as_ref().filter(|c| c.is_active()) {
+      if let Ok(local) = self.socket.local_addr() {
+        let destination = match self.multicast_group {
+          Some(group) => SocketAddr::new(group, local.port()),
+          None => local,
+        };
+        capture.record(source, destination, message);
+      }
+    }
+  }
+
   // This function seems not necessary, because multicast join is done
   // at listener creation time.
   //
@@ -236,6 +312,62 @@ impl UDPListener {
   }
 }
 
+impl TransportListener for UDPListener {
+  fn kind(&self) -> TransportKind {
+    self.kind
+  }
+
+  fn evented(&self) -> &dyn Evented {
+    &self.socket
+  }
+
+  fn messages(&mut self) -> Vec<Bytes> {
+    UDPListener::messages(self)
+  }
+
+  fn messages_with_source(&mut self) -> Vec<(Bytes, Option<IpAddr>)> {
+    UDPListener::messages_with_source(self)
+  }
+
+  fn self_locators(&self) -> io::Result<Vec<Locator>> {
+    self.to_locator_address()
+  }
+
+  fn set_socket_options(&self, options: &SocketOptions) -> io::Result<()> {
+    options.apply_to_listener(&SockRef::from(&self.socket))
+  }
+
+  fn set_packet_capture(&mut self, capture: PacketCapture) {
+    self.capture = Some(capture);
+  }
+
+  // Join the IPv4 group also on new interfaces. The interfaces that are gone
+  // have taken their memberships with them.
+  fn interfaces_changed(&mut self) {
+    let group = match self.multicast_group {
+      Some(IpAddr::V4(group)) => group,
+      _ => return,
+    };
+    let if_addrs = match get_local_multicast_ip_addrs(&self.interfaces) {
+      Ok(if_addrs) => if_addrs,
+      Err(e) => {
+        error!("Cannot get local network interfaces: {:?}", e);
+        return;
+      }
+    };
+    for if_addr in if_addrs {
+      if let IpAddr::V4(a) = if_addr {
+        match self.socket.join_multicast_v4(&group, &a) {
+          Ok(()) => info!("Joined multicast group {} on {}", group, a),
+          // already a member
+          Err(e) if e.kind() == io::ErrorKind::AddrInUse => (),
+          Err(e) => warn!("Cannot join multicast group {} on {}: {:?}", group, a, e),
+        }
+      }
+    }
+  }
+}
+
 #[cfg(test)]
 mod tests {
   //use std::os::unix::io::AsRawFd;
@@ -244,7 +376,7 @@ mod tests {
   use std::{thread, time};
 
   use super::*;
-  use crate::network::udp_sender::*;
+  use crate::network::{transport::Transport, udp_sender::*};
 
   #[test]
   fn udpl_single_address() {
@@ -264,8 +396,13 @@ mod tests {
 
   #[test]
   fn udpl_multicast_address() {
-    let listener =
-      UDPListener::new_multicast("0.0.0.0", 10002, Ipv4Addr::new(239, 255, 0, 1)).unwrap();
+    let listener = UDPListener::new_multicast(
+      "0.0.0.0",
+      10002,
+      Ipv4Addr::new(239, 255, 0, 1).into(),
+      InterfaceFilter::default(),
+    )
+    .unwrap();
     let sender = UDPSender::new_with_random_port().unwrap();
 
     //setsockopt(sender.socket.as_raw_fd(), IpMulticastLoop, &true)
@@ -288,4 +425,29 @@ mod tests {
     assert_eq!(rec_data.len(), 3);
     assert_eq!(rec_data, data);
   }
+
+  #[test]
+  fn udpl_ipv6_next_to_ipv4() {
+    // Same port for both, as a participant has
+    let listener_v4 = UDPListener::new_unicast("0.0.0.0", 10004).unwrap();
+    let listener_v6 = UDPListener::new_unicast("::", 10004).unwrap();
+    assert_eq!(TransportListener::kind(&listener_v4), TransportKind::UdpV4);
+    assert_eq!(TransportListener::kind(&listener_v6), TransportKind::UdpV6);
+    assert!(listener_v6
+      .to_locator_address()
+      .unwrap()
+      .iter()
+      .all(|loc| matches!(loc, Locator::UdpV6(_))));
+
+    let sender =
+      UDPSender::new_v6(0, &InterfaceFilter::default(), &SocketOptions::default()).unwrap();
+    let data: Vec<u8> = vec![6, 6, 6];
+    sender.send_to_locator(
+      &data,
+      &Locator::from(SocketAddr::new("::1".parse().unwrap(), 10004)),
+    );
+
+    assert_eq!(listener_v6.get_message(), data);
+    assert!(listener_v4.get_message().is_empty());
+  }
 }
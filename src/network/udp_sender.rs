@@ -1,6 +1,6 @@
 use std::{
   io,
-  net::{IpAddr, SocketAddr},
+  net::{IpAddr, Ipv6Addr, SocketAddr},
 };
 #[cfg(test)]
 use std::net::Ipv4Addr;
@@ -8,22 +8,69 @@ use std::net::Ipv4Addr;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 use mio::net::UdpSocket;
-use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use socket2::{Domain, Protocol, SockAddr, SockRef, Socket, Type};
 #[cfg(windows)]
 use local_ip_address::list_afinet_netifas;
 
-use crate::{network::util::get_local_multicast_ip_addrs, structure::locator::Locator};
+use crate::{
+  network::{
+    interface_filter::InterfaceFilter,
+    packet_capture::PacketCapture,
+    socket_options::SocketOptions,
+    transport::{Transport, TransportKind},
+    util::{get_local_multicast_ip_addrs, get_min_mtu},
+  },
+  structure::locator::Locator,
+};
 
 // We need one multicast sender socket per interface
 
 #[derive(Debug)]
 pub struct UDPSender {
+  kind: TransportKind,
   unicast_socket: UdpSocket,
   multicast_sockets: Vec<UdpSocket>,
+  max_message_size: usize,
+  capture: Option<PacketCapture>,
 }
 
 impl UDPSender {
+  // Fits in an Ethernet frame with the IPv4 and UDP headers, so that messages
+  // are not fragmented on the IP level.
+  pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 1500 - 20 - 8;
+
+  // Configured, or otherwise what fits in the smallest MTU of the interfaces
+  // in use.
+  fn choose_max_message_size(
+    kind: TransportKind,
+    interfaces: &InterfaceFilter,
+    options: &SocketOptions,
+  ) -> usize {
+    let ip_udp_header_size = if kind == TransportKind::UdpV6 { 48 } else { 28 };
+    options.max_message_size(kind).unwrap_or_else(|| {
+      get_min_mtu(interfaces).map_or(Self::DEFAULT_MAX_MESSAGE_SIZE, |mtu| {
+        mtu
+          .saturating_sub(ip_udp_header_size)
+          .min(kind.max_message_size_limit())
+      })
+    })
+  }
+
+  #[cfg(test)]
   pub fn new(sender_port: u16) -> io::Result<Self> {
+    Self::new_with_interfaces(
+      sender_port,
+      &InterfaceFilter::default(),
+      &SocketOptions::default(),
+    )
+  }
+
+  // Multicast is sent from each of the interfaces.
+  pub fn new_with_interfaces(
+    sender_port: u16,
+    interfaces: &InterfaceFilter,
+    options: &SocketOptions,
+  ) -> io::Result<Self> {
     #[cfg(not(windows))]
     let unicast_socket = {
       let saddr: SocketAddr = SocketAddr::new("0.0.0.0".parse().unwrap(), sender_port);
@@ -51,63 +98,83 @@ impl UDPSender {
       UdpSocket::from_socket(std::net::UdpSocket::from(raw_socket))?
     };
 
-    // We set multicasting loop on so that we can hear other DomainParticipant
-    // instances running on the same host.
-    unicast_socket
-      .set_multicast_loop_v4(true)
-      .unwrap_or_else(|e| {
-        error!("Cannot set multicast loop on: {:?}", e);
-      });
+    options.apply_to_sender(&SockRef::from(&unicast_socket), false)?;
 
     let mut multicast_sockets = Vec::with_capacity(1);
-    for multicast_if_ipaddr in get_local_multicast_ip_addrs()? {
+    for multicast_if_ipaddr in get_local_multicast_ip_addrs(interfaces)? {
+      // IPv6 interfaces are for UDPSender::new_v6()
+      let a = match multicast_if_ipaddr {
+        IpAddr::V4(a) => a,
+        IpAddr::V6(_) => continue,
+      };
       let raw_socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
       // beef: specify otput interface
       info!(
         "UDPSender: Multicast sender on interface {:?}",
         multicast_if_ipaddr
       );
-      match multicast_if_ipaddr {
-        IpAddr::V4(a) => {
-          raw_socket.set_multicast_if_v4(&a)?;
-          if cfg!(windows) {
-            raw_socket.set_reuse_address(true)?;
-          } // Necessary? TODO: Check if necessary.
-          raw_socket.bind(&SockAddr::from(SocketAddr::new(multicast_if_ipaddr, 0)))?;
-        }
-        IpAddr::V6(_a) => error!("UDPSender::new() not implemented for IpV6"), // TODO
-      }
+      raw_socket.set_multicast_if_v4(&a)?;
+      if cfg!(windows) {
+        raw_socket.set_reuse_address(true)?;
+      } // Necessary? TODO: Check if necessary.
+      raw_socket.bind(&SockAddr::from(SocketAddr::new(multicast_if_ipaddr, 0)))?;
 
-      let mc_socket = std::net::UdpSocket::from(raw_socket);
-      mc_socket.set_multicast_loop_v4(true).unwrap_or_else(|e| {
-        error!("Cannot set multicast loop on: {:?}", e);
-      });
-      multicast_sockets.push(UdpSocket::from_socket(mc_socket)?);
+      options.apply_to_sender(&SockRef::from(&raw_socket), false)?;
+      multicast_sockets.push(UdpSocket::from_socket(std::net::UdpSocket::from(
+        raw_socket,
+      ))?);
     } // end for
 
     let sender = Self {
+      kind: TransportKind::UdpV4,
       unicast_socket,
       multicast_sockets,
+      max_message_size: Self::choose_max_message_size(TransportKind::UdpV4, interfaces, options),
+      capture: None,
     };
     info!("UDPSender::new() --> {:?}", sender);
     Ok(sender)
   }
 
+  // Multicast is sent from the default multicast interface only, because we do
+  // not know the interface indices that IPv6 uses to select one.
+  pub fn new_v6(
+    sender_port: u16,
+    interfaces: &InterfaceFilter,
+    options: &SocketOptions,
+  ) -> io::Result<Self> {
+    let new_socket = |port: u16| -> io::Result<UdpSocket> {
+      let raw_socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+      raw_socket.set_only_v6(true)?;
+      options.apply_to_sender(&SockRef::from(&raw_socket), true)?;
+      raw_socket.bind(&SockAddr::from(SocketAddr::new(
+        Ipv6Addr::UNSPECIFIED.into(),
+        port,
+      )))?;
+      UdpSocket::from_socket(std::net::UdpSocket::from(raw_socket))
+    };
+
+    let sender = Self {
+      kind: TransportKind::UdpV6,
+      unicast_socket: new_socket(sender_port)?,
+      multicast_sockets: vec![new_socket(0)?],
+      max_message_size: Self::choose_max_message_size(TransportKind::UdpV6, interfaces, options),
+      capture: None,
+    };
+    info!("UDPSender::new_v6() --> {:?}", sender);
+    Ok(sender)
+  }
+
   #[cfg(test)]
   pub fn new_with_random_port() -> io::Result<Self> {
     Self::new(0)
   }
 
-  pub fn send_to_locator_list(&self, buffer: &[u8], ll: &[Locator]) {
-    for loc in ll {
-      self.send_to_locator(buffer, loc);
-    }
-  }
-
   fn send_to_udp_socket(&self, buffer: &[u8], socket: &UdpSocket, addr: &SocketAddr) {
     match socket.send_to(buffer, addr) {
       Ok(bytes_sent) => {
-        if bytes_sent == buffer.len() { // ok
+        if bytes_sent == buffer.len() {
+          self.capture_sent(buffer, socket, addr);
         } else {
           error!(
             "send_to_locator - send_to tried {} bytes, sent only {}",
@@ -122,28 +189,12 @@ impl UDPSender {
     }
   }
 
-  pub fn send_to_locator(&self, buffer: &[u8], locator: &Locator) {
-    let send = |socket_address: SocketAddr| {
-      if socket_address.ip().is_multicast() {
-        for socket in &self.multicast_sockets {
-          self.send_to_udp_socket(buffer, socket, &socket_address);
-        }
-      } else {
-        self.send_to_udp_socket(buffer, &self.unicast_socket, &socket_address);
-      }
-    };
-
-    match locator {
-      Locator::UdpV4(socket_address) => send(SocketAddr::from(*socket_address)),
-      Locator::UdpV6(socket_address) => send(SocketAddr::from(*socket_address)),
-      Locator::Invalid | Locator::Reserved => {
-        error!("send_to_locator: Cannot send to {:?}", locator);
-      }
-      Locator::Other { kind, .. } =>
-      // This is normal, as other implementations can define their own kinds.
-      // We get those from Discovery.
-      {
-        trace!("send_to_locator: Unknown LocatorKind: {:?}", kind);
+  // Sockets are bound to the unspecified address, so that is the source
+  // address in the capture.
+  fn capture_sent(&self, buffer: &[u8], socket: &UdpSocket, addr: &SocketAddr) {
+    if let Some(capture) = self.capture.as_ref().filter(|c| c.is_active()) {
+      if let Ok(source) = socket.local_addr() {
+        capture.record(source, *addr, buffer);
       }
     }
   }
@@ -175,6 +226,61 @@ impl UDPSender {
   }
 }
 
+impl Transport for UDPSender {
+  fn kind(&self) -> TransportKind {
+    self.kind
+  }
+
+  fn send_to_locator(&self, buffer: &[u8], locator: &Locator) {
+    let send = |socket_address: SocketAddr| {
+      if socket_address.ip().is_multicast() {
+        for socket in &self.multicast_sockets {
+          self.send_to_udp_socket(buffer, socket, &socket_address);
+        }
+      } else {
+        self.send_to_udp_socket(buffer, &self.unicast_socket, &socket_address);
+      }
+    };
+
+    // Transports gives us only the locators we reach.
+    match (self.kind, locator) {
+      (TransportKind::UdpV4, Locator::UdpV4(socket_address)) => {
+        send(SocketAddr::from(*socket_address));
+      }
+      (TransportKind::UdpV6, Locator::UdpV6(socket_address)) => {
+        send(SocketAddr::from(*socket_address));
+      }
+      _ => error!("UDPSender: Cannot send to {:?}", locator),
+    }
+  }
+
+  fn max_message_size(&self) -> usize {
+    self.max_message_size
+  }
+
+  /// Map DDS TRANSPORT_PRIORITY to DSCP of outgoing packets.
+  ///
+  /// The DSCP value is the upper six bits of the IP TOS octet, so the priority
+  /// is clamped to 0..=63 and shifted in place. Over IPv6 the priority is not
+  /// applied yet, as socket2 does not expose the traffic class.
+  fn set_transport_priority(&self, priority: i32) -> io::Result<()> {
+    if self.kind == TransportKind::UdpV6 {
+      debug!("set_transport_priority: Not supported over IPv6, ignoring.");
+      return Ok(());
+    }
+    let tos = (priority.clamp(0, 63) as u32) << 2;
+    SockRef::from(&self.unicast_socket).set_tos(tos)?;
+    for socket in &self.multicast_sockets {
+      SockRef::from(socket).set_tos(tos)?;
+    }
+    Ok(())
+  }
+
+  fn set_packet_capture(&mut self, capture: PacketCapture) {
+    self.capture = Some(capture);
+  }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -197,6 +303,58 @@ mod tests {
     assert_eq!(rec_data, data);
   }
 
+  #[test]
+  fn udps_transport_priority_sets_dscp() {
+    let sender = UDPSender::new_with_random_port().expect("failed to create UDPSender");
+    sender.set_transport_priority(46).unwrap(); // DSCP "Expedited Forwarding"
+    assert_eq!(
+      SockRef::from(&sender.unicast_socket).tos().unwrap(),
+      46 << 2
+    );
+    sender.set_transport_priority(1000).unwrap(); // clamped
+    assert_eq!(
+      SockRef::from(&sender.unicast_socket).tos().unwrap(),
+      63 << 2
+    );
+  }
+
+  #[test]
+  fn udps_socket_options() {
+    let options = SocketOptions {
+      multicast_ttl: Some(8),
+      multicast_loopback: false,
+      dscp: Some(10),
+      ..SocketOptions::default()
+    };
+    let sender = UDPSender::new_with_interfaces(0, &InterfaceFilter::default(), &options)
+      .expect("failed to create UDPSender");
+    let socket = SockRef::from(&sender.unicast_socket);
+    assert_eq!(socket.multicast_ttl_v4().unwrap(), 8);
+    assert!(!socket.multicast_loop_v4().unwrap());
+    assert_eq!(socket.tos().unwrap(), 10 << 2);
+  }
+
+  #[test]
+  fn udps_max_message_size() {
+    let mut options = SocketOptions::default();
+    options.max_message_size.insert(TransportKind::UdpV4, 9000);
+    options
+      .max_message_size
+      .insert(TransportKind::UdpV6, 100_000);
+    let sender = UDPSender::new_with_interfaces(0, &InterfaceFilter::default(), &options)
+      .expect("failed to create UDPSender");
+    assert_eq!(sender.max_message_size(), 9000);
+    // limited to what fits in a UDP datagram
+    assert_eq!(
+      UDPSender::choose_max_message_size(
+        TransportKind::UdpV6,
+        &InterfaceFilter::default(),
+        &options
+      ),
+      65_527
+    );
+  }
+
   #[test]
   fn udps_multi_send() {
     let listener_1 = UDPListener::new_unicast("127.0.0.1", 10301).unwrap();
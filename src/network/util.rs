@@ -1,4 +1,5 @@
 use std::{
+  collections::BTreeSet,
   io,
   net::{IpAddr, SocketAddr},
 };
@@ -7,19 +8,28 @@ use if_addrs::Interface;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace};
 
-use crate::structure::locator::Locator;
-
-pub fn get_local_multicast_locators(port: u16) -> Vec<Locator> {
-  let saddr = SocketAddr::new("239.255.0.1".parse().unwrap(), port);
-  vec![Locator::from(saddr)]
-}
+use crate::{
+  network::{interface_filter::InterfaceFilter, transport::TransportKind},
+  structure::locator::Locator,
+};
 
-pub fn get_local_unicast_locators(port: u16) -> Vec<Locator> {
+// Link-local IPv6 addresses are skipped, because they are usable only together
+// with an interface (scope id), and a Locator cannot carry that.
+pub fn get_local_unicast_locators(
+  kind: TransportKind,
+  port: u16,
+  interfaces: &InterfaceFilter,
+) -> Vec<Locator> {
   match if_addrs::get_if_addrs() {
     Ok(ifaces) => ifaces
       .iter()
-      .filter(|ip| !ip.is_loopback())
+      .filter(|iface| interfaces.accepts(iface))
+      .filter(|ip| match ip.ip() {
+        IpAddr::V4(_) => true,
+        IpAddr::V6(a) => a.segments()[0] & 0xffc0 != 0xfe80,
+      })
       .map(|ip| Locator::from(SocketAddr::new(ip.ip(), port)))
+      .filter(|loc| kind.reaches(loc))
       .collect(),
     Err(e) => {
       error!(
@@ -35,15 +45,50 @@ pub fn get_local_unicast_locators(port: u16) -> Vec<Locator> {
 // This is used to set up senders and listeners.
 //
 // TODO: Check that the interface actually has multicast enabled.
-// Now we just skip loopback.
+// Now we just skip loopback, unless it is explicitly allowed.
 // Could use e.g. "interfaces" crate to do this.
-pub fn get_local_multicast_ip_addrs() -> io::Result<Vec<IpAddr>> {
+pub fn get_local_multicast_ip_addrs(interfaces: &InterfaceFilter) -> io::Result<Vec<IpAddr>> {
   let ifs = if_addrs::get_if_addrs()?;
   Ok(
     ifs
       .iter()
-      .filter(|ifaddr| !ifaddr.is_loopback())
+      .filter(|iface| interfaces.accepts(iface))
       .map(Interface::ip)
       .collect(),
   )
 }
+
+// The smallest MTU of the local interfaces in use, if the operating system
+// tells it. Only Linux is supported for now.
+pub fn get_min_mtu(interfaces: &InterfaceFilter) -> Option<usize> {
+  if !cfg!(target_os = "linux") {
+    return None;
+  }
+  let ifaces = if_addrs::get_if_addrs()
+    .map_err(|e| error!("Cannot get local network interfaces: {:?}", e))
+    .ok()?;
+  ifaces
+    .iter()
+    .filter(|iface| interfaces.accepts(iface))
+    .filter_map(|iface| {
+      std::fs::read_to_string(format!("/sys/class/net/{}/mtu", iface.name))
+        .ok()
+        .and_then(|mtu| mtu.trim().parse::<usize>().ok())
+    })
+    .min()
+}
+
+// Addresses of the local interfaces in use, to detect when interfaces come
+// and go.
+pub fn get_local_ip_addrs(interfaces: &InterfaceFilter) -> BTreeSet<IpAddr> {
+  get_local_multicast_ip_addrs(interfaces)
+    .unwrap_or_else(|e| {
+      error!(
+        "Cannot get local network interfaces: get_if_addrs() : {:?}",
+        e
+      );
+      vec![]
+    })
+    .into_iter()
+    .collect()
+}
@@ -7,6 +7,10 @@ use crate::{
 };
 
 /// Analog of DDS GUID in ROS2 builtin datastructures
+///
+/// This is `rmw_dds_common/msg/Gid`: the 16 bytes of the GUID, padded with
+/// zeros to 24 bytes. ROS 2 tools show it as the GID of a publisher or a
+/// subscription.
 #[derive(
   Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, CdrEncodingSize,
 )]
@@ -22,6 +26,28 @@ impl Gid {
     data[15..16].clone_from_slice(&[u8::from(guid.entity_id.entity_kind)]);
     Self { data }
   }
+
+  pub fn to_guid(&self) -> GUID {
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&self.data[..16]);
+    GUID::from_bytes(bytes)
+  }
+
+  pub fn as_bytes(&self) -> &[u8; 24] {
+    &self.data
+  }
+}
+
+impl From<GUID> for Gid {
+  fn from(guid: GUID) -> Self {
+    Self::from_guid(guid)
+  }
+}
+
+impl From<Gid> for GUID {
+  fn from(gid: Gid) -> Self {
+    gid.to_guid()
+  }
 }
 
 impl Key for Gid {}
@@ -33,6 +33,11 @@ impl ROSDiscoveryTopic {
     lifespan: Some(Lifespan {
       duration: Duration::DURATION_INFINITE,
     }),
+    transport_priority: None,
+    user_data: None,
+    topic_data: None,
+    group_data: None,
+    entity_factory: None,
   };
 
   const TOPIC_NAME: &'static str = "ros_discovery_info";
@@ -69,6 +74,11 @@ impl ParameterEventsTopic {
     history: Some(History::KeepLast { depth: 1 }),
     resource_limits: None,
     lifespan: None,
+    transport_priority: None,
+    user_data: None,
+    topic_data: None,
+    group_data: None,
+    entity_factory: None,
   };
 
   const TOPIC_NAME: &'static str = "rt/parameter_events";
@@ -111,6 +121,11 @@ impl RosOutTopic {
     lifespan: Some(Lifespan {
       duration: Duration::from_secs(10),
     }),
+    transport_priority: None,
+    user_data: None,
+    topic_data: None,
+    group_data: None,
+    entity_factory: None,
   };
 
   const TOPIC_NAME: &'static str = "rt/rosout";
@@ -0,0 +1,298 @@
+//! Message types of the ROS 2 `common_interfaces`, with the CDR layout and the
+//! DDS type names ROS 2 uses.
+//!
+//! The DDS type name of each message is given by its
+//! [`DdsType`](crate::DdsType) implementation, e.g.
+//!
+//! ```
+//! use rustdds::{ros2::common_interfaces::geometry_msgs::Twist, DdsType};
+//!
+//! assert_eq!(Twist::type_name(), "geometry_msgs::msg::dds_::Twist_");
+//! ```
+
+// Implements DdsType with the mangled name of a ROS message type.
+macro_rules! ros_message {
+  ($package:ident, $($name:ident),* $(,)?) => {
+    $(
+      impl crate::dds::traits::DdsType for $name {
+        fn type_name() -> &'static str {
+          concat!(stringify!($package), "::msg::dds_::", stringify!($name), "_")
+        }
+      }
+    )*
+  };
+}
+
+/// `builtin_interfaces`: time and duration.
+pub mod builtin_interfaces {
+  use serde::{Deserialize, Serialize};
+
+  use crate::structure::{duration, time::Timestamp};
+
+  /// Time since the Unix epoch.
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+  pub struct Time {
+    pub sec: i32,
+    pub nanosec: u32,
+  }
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+  pub struct Duration {
+    pub sec: i32,
+    pub nanosec: u32,
+  }
+
+  ros_message!(builtin_interfaces, Time, Duration);
+
+  impl From<Timestamp> for Time {
+    fn from(timestamp: Timestamp) -> Self {
+      let ticks = timestamp.to_ticks();
+      Self {
+        sec: (ticks >> 32) as i32,
+        nanosec: (((ticks & 0xFFFF_FFFF) * 1_000_000_000) >> 32) as u32,
+      }
+    }
+  }
+
+  impl From<Time> for Timestamp {
+    fn from(time: Time) -> Self {
+      let fraction = (u64::from(time.nanosec) << 32) / 1_000_000_000;
+      Self::from_ticks(((time.sec as u32 as u64) << 32) + fraction)
+    }
+  }
+
+  impl From<duration::Duration> for Duration {
+    fn from(d: duration::Duration) -> Self {
+      let nanos = d.to_nanoseconds();
+      Self {
+        sec: nanos.div_euclid(1_000_000_000) as i32,
+        nanosec: nanos.rem_euclid(1_000_000_000) as u32,
+      }
+    }
+  }
+
+  impl From<Duration> for duration::Duration {
+    fn from(d: Duration) -> Self {
+      Self::from_nanos(i64::from(d.sec) * 1_000_000_000 + i64::from(d.nanosec))
+    }
+  }
+}
+
+/// `std_msgs`: standard message types.
+pub mod std_msgs {
+  use serde::{Deserialize, Serialize};
+
+  use super::builtin_interfaces::Time;
+
+  #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+  pub struct Header {
+    pub stamp: Time,
+    pub frame_id: std::string::String,
+  }
+
+  /// Empty message. ROS 2 gives it a dummy member, because IDL structures
+  /// must have at least one.
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+  pub struct Empty {
+    pub structure_needs_at_least_one_member: u8,
+  }
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+  pub struct Bool {
+    pub data: bool,
+  }
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+  pub struct Int32 {
+    pub data: i32,
+  }
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+  pub struct Int64 {
+    pub data: i64,
+  }
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+  pub struct UInt32 {
+    pub data: u32,
+  }
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+  pub struct Float32 {
+    pub data: f32,
+  }
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+  pub struct Float64 {
+    pub data: f64,
+  }
+
+  #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+  pub struct String {
+    pub data: std::string::String,
+  }
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+  pub struct ColorRGBA {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+  }
+
+  ros_message!(
+    std_msgs, Header, Empty, Bool, Int32, Int64, UInt32, Float32, Float64, String, ColorRGBA,
+  );
+}
+
+/// `geometry_msgs`: points, vectors and poses.
+pub mod geometry_msgs {
+  use serde::{Deserialize, Serialize};
+
+  use super::std_msgs::Header;
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+  pub struct Vector3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+  }
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+  pub struct Point {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+  }
+
+  /// Rotation as a quaternion. The default is the identity rotation, as in
+  /// ROS.
+  #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+  pub struct Quaternion {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+  }
+
+  impl Default for Quaternion {
+    fn default() -> Self {
+      Self {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+      }
+    }
+  }
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+  pub struct Pose {
+    pub position: Point,
+    pub orientation: Quaternion,
+  }
+
+  #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+  pub struct PoseStamped {
+    pub header: Header,
+    pub pose: Pose,
+  }
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+  pub struct Twist {
+    pub linear: Vector3,
+    pub angular: Vector3,
+  }
+
+  #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+  pub struct TwistStamped {
+    pub header: Header,
+    pub twist: Twist,
+  }
+
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+  pub struct Transform {
+    pub translation: Vector3,
+    pub rotation: Quaternion,
+  }
+
+  #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+  pub struct TransformStamped {
+    pub header: Header,
+    pub child_frame_id: String,
+    pub transform: Transform,
+  }
+
+  ros_message!(
+    geometry_msgs,
+    Vector3,
+    Point,
+    Quaternion,
+    Pose,
+    PoseStamped,
+    Twist,
+    TwistStamped,
+    Transform,
+    TransformStamped,
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{builtin_interfaces::Time, geometry_msgs::*, std_msgs::Header};
+  use crate::{
+    dds::traits::serde_adapters::no_key::SerializerAdapter,
+    ros2::names,
+    serialization::{cdr_deserializer::deserialize_from_little_endian, CDRSerializerAdapter},
+    structure::time::Timestamp,
+    DdsType,
+  };
+
+  #[test]
+  fn transform_stamped_cdr_layout() {
+    let message = TransformStamped {
+      header: Header {
+        stamp: Time { sec: 1, nanosec: 2 },
+        frame_id: "map".to_string(),
+      },
+      child_frame_id: "base".to_string(),
+      transform: Transform {
+        translation: Vector3 {
+          x: 1.0,
+          y: 2.0,
+          z: 3.0,
+        },
+        rotation: Quaternion::default(),
+      },
+    };
+    let bytes = CDRSerializerAdapter::<TransformStamped>::to_bytes(&message).unwrap();
+    #[rustfmt::skip]
+    let expected_start = [
+      1, 0, 0, 0, 2, 0, 0, 0, // stamp
+      4, 0, 0, 0, b'm', b'a', b'p', 0, // frame_id
+      5, 0, 0, 0, b'b', b'a', b's', b'e', 0, // child_frame_id
+      0, 0, 0, 0, 0, 0, 0, // padding to 8
+    ];
+    assert_eq!(bytes[..expected_start.len()], expected_start);
+    assert_eq!(bytes.len(), expected_start.len() + 7 * 8);
+    assert_eq!(
+      deserialize_from_little_endian::<TransformStamped>(&bytes).unwrap(),
+      message
+    );
+
+    assert_eq!(
+      names::demangle_type_name(TransformStamped::type_name()).unwrap(),
+      "geometry_msgs/msg/TransformStamped"
+    );
+  }
+
+  #[test]
+  fn time_conversion() {
+    let time = Time {
+      sec: 1_700_000_000,
+      nanosec: 500_000_000,
+    };
+    let timestamp = Timestamp::from(time);
+    assert_eq!(Time::from(timestamp), time);
+  }
+}
@@ -48,6 +48,8 @@
 pub mod builtin_datatypes;
 /// Some convenience topic infos for ROS2 communication
 pub mod builtin_topics;
+pub mod common_interfaces;
+pub mod names;
 
 pub(crate) mod ros_node;
 
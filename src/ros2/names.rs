@@ -0,0 +1,313 @@
+//! Conversions between ROS 2 names and the DDS names ROS 2 uses for them.
+//!
+//! A ROS 2 topic `/chatter` of type `std_msgs/msg/String` is the DDS topic
+//! `rt/chatter` of type `std_msgs::msg::dds_::String_`. Service `/add` has a
+//! request topic `rq/addRequest` and a response topic `rr/addReply`.
+//!
+//! See [Topic and Service name mapping to DDS](https://design.ros2.org/articles/topic_and_service_names.html).
+
+/// Prefix of the DDS names of ROS topics.
+pub const TOPIC_PREFIX: &str = "rt";
+/// Prefix of the DDS names of service request topics.
+pub const SERVICE_REQUEST_PREFIX: &str = "rq";
+/// Prefix of the DDS names of service response topics.
+pub const SERVICE_RESPONSE_PREFIX: &str = "rr";
+
+const SERVICE_REQUEST_SUFFIX: &str = "Request";
+const SERVICE_RESPONSE_SUFFIX: &str = "Reply";
+
+/// Error from validating or expanding a ROS 2 name.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid ROS 2 name {name:?}: {reason}")]
+pub struct InvalidNameError {
+  pub name: String,
+  pub reason: &'static str,
+}
+
+fn invalid(name: &str, reason: &'static str) -> InvalidNameError {
+  InvalidNameError {
+    name: name.to_string(),
+    reason,
+  }
+}
+
+/// What a DDS topic is used for in ROS 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RosTopicKind {
+  Topic,
+  ServiceRequest,
+  ServiceResponse,
+}
+
+/// Checks a topic or service name against the ROS 2 naming rules. The name
+/// may be relative, private (`~/...`) and contain substitutions (`{node}`).
+pub fn validate_topic_name(name: &str) -> Result<(), InvalidNameError> {
+  if name.is_empty() {
+    return Err(invalid(name, "must not be empty"));
+  }
+  if let Some(c) = name
+    .chars()
+    .find(|c| !(c.is_ascii_alphanumeric() || "_/~{}".contains(*c)))
+  {
+    return Err(InvalidNameError {
+      name: name.to_string(),
+      reason: if c.is_ascii() {
+        "may only contain alphanumerics, '_', '/', '~', '{' and '}'"
+      } else {
+        "must be ASCII"
+      },
+    });
+  }
+  if name.len() > 1 && name.ends_with('/') {
+    return Err(invalid(name, "must not end with '/'"));
+  }
+  if name.contains("//") {
+    return Err(invalid(name, "must not contain repeated '/'"));
+  }
+  if name.contains("__") {
+    return Err(invalid(name, "must not contain repeated '_'"));
+  }
+  if name[1..].contains('~') {
+    return Err(invalid(name, "may only have '~' as the first character"));
+  }
+  if name.starts_with('~') && name.len() > 1 && !name.starts_with("~/") {
+    return Err(invalid(name, "must separate '~' with '/'"));
+  }
+  let mut in_substitution = false;
+  for c in name.chars() {
+    match c {
+      '{' if in_substitution => return Err(invalid(name, "must not nest '{'")),
+      '}' if !in_substitution => return Err(invalid(name, "has unbalanced '}'")),
+      '/' if in_substitution => return Err(invalid(name, "must not have '/' in '{}'")),
+      '{' | '}' => in_substitution = !in_substitution,
+      _ => {}
+    }
+  }
+  if in_substitution {
+    return Err(invalid(name, "has unbalanced '{'"));
+  }
+  if name
+    .split('/')
+    .any(|token| token.starts_with(|c: char| c.is_ascii_digit()))
+  {
+    return Err(invalid(name, "must not start a token with a digit"));
+  }
+  Ok(())
+}
+
+/// Expands a topic or service name of a node to a fully qualified name,
+/// resolving relative and private names and the substitutions `{node}`,
+/// `{ns}` and `{namespace}`.
+///
+/// `namespace` is the fully qualified namespace of the node, e.g. `/` or
+/// `/robot1`.
+pub fn expand_topic_name(
+  name: &str,
+  node_name: &str,
+  namespace: &str,
+) -> Result<String, InvalidNameError> {
+  validate_topic_name(name)?;
+  let namespace = namespace.trim_end_matches('/');
+
+  let mut expanded = String::new();
+  let mut rest = name;
+  while let Some(start) = rest.find('{') {
+    let end = start + rest[start..].find('}').unwrap_or(0);
+    expanded.push_str(&rest[..start]);
+    match &rest[start + 1..end] {
+      "node" => expanded.push_str(node_name),
+      "ns" | "namespace" => expanded.push_str(namespace.trim_start_matches('/')),
+      _ => return Err(invalid(name, "has an unknown substitution")),
+    }
+    rest = &rest[end + 1..];
+  }
+  expanded.push_str(rest);
+
+  let expanded = if let Some(private) = expanded.strip_prefix('~') {
+    format!("{}/{}{}", namespace, node_name, private)
+  } else if expanded.starts_with('/') {
+    expanded
+  } else {
+    format!("{}/{}", namespace, expanded)
+  };
+  validate_topic_name(&expanded)?;
+  Ok(expanded)
+}
+
+fn dds_name(prefix: &str, name: &str, suffix: &str) -> String {
+  // Fully qualified names start with '/'. Relative ones are taken as in the
+  // root namespace.
+  let name = name.strip_prefix('/').unwrap_or(name);
+  format!("{}/{}{}", prefix, name, suffix)
+}
+
+/// DDS topic name of a ROS topic, e.g. `rt/chatter` for `/chatter`.
+pub fn topic_name(ros_name: &str) -> String {
+  dds_name(TOPIC_PREFIX, ros_name, "")
+}
+
+/// DDS topic name of the requests of a ROS service.
+pub fn service_request_topic_name(ros_name: &str) -> String {
+  dds_name(SERVICE_REQUEST_PREFIX, ros_name, SERVICE_REQUEST_SUFFIX)
+}
+
+/// DDS topic name of the responses of a ROS service.
+pub fn service_response_topic_name(ros_name: &str) -> String {
+  dds_name(SERVICE_RESPONSE_PREFIX, ros_name, SERVICE_RESPONSE_SUFFIX)
+}
+
+/// ROS name of a DDS topic, and what the topic is for. Returns `None` for DDS
+/// topics that are not ROS topics or services.
+pub fn demangle_topic_name(dds_name: &str) -> Option<(RosTopicKind, String)> {
+  let (prefix, name) = dds_name.split_once('/')?;
+  let (kind, name) = match prefix {
+    TOPIC_PREFIX => (RosTopicKind::Topic, name),
+    SERVICE_REQUEST_PREFIX => (
+      RosTopicKind::ServiceRequest,
+      name.strip_suffix(SERVICE_REQUEST_SUFFIX)?,
+    ),
+    SERVICE_RESPONSE_PREFIX => (
+      RosTopicKind::ServiceResponse,
+      name.strip_suffix(SERVICE_RESPONSE_SUFFIX)?,
+    ),
+    _ => return None,
+  };
+  if name.is_empty() {
+    return None;
+  }
+  Some((kind, format!("/{}", name)))
+}
+
+/// DDS type name of a ROS interface type, e.g.
+/// `std_msgs::msg::dds_::String_` for `std_msgs/msg/String`. The interface
+/// kind defaults to `msg`, so `std_msgs/String` works too.
+///
+/// The request and response types of service `example_interfaces/srv/AddTwoInts`
+/// are `example_interfaces/srv/AddTwoInts_Request` and
+/// `example_interfaces/srv/AddTwoInts_Response`.
+pub fn type_name(ros_type: &str) -> Option<String> {
+  let parts: Vec<&str> = ros_type.split('/').collect();
+  let (package, kind, name) = match parts[..] {
+    [package, name] => (package, "msg", name),
+    [package, kind, name] => (package, kind, name),
+    _ => return None,
+  };
+  if [package, kind, name].iter().any(|part| part.is_empty()) {
+    return None;
+  }
+  Some(format!("{}::{}::dds_::{}_", package, kind, name))
+}
+
+/// ROS interface type of a DDS type name, the reverse of [`type_name`].
+pub fn demangle_type_name(dds_type: &str) -> Option<String> {
+  let parts: Vec<&str> = dds_type.split("::").collect();
+  match parts[..] {
+    [package, kind, "dds_", name] if !package.is_empty() && !kind.is_empty() => {
+      let name = name.strip_suffix('_').filter(|name| !name.is_empty())?;
+      Some(format!("{}/{}/{}", package, kind, name))
+    }
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn topic_name_validation() {
+    for valid in [
+      "foo",
+      "/foo/bar",
+      "~",
+      "~/foo",
+      "{node}/foo",
+      "/abc_123",
+      "/",
+    ] {
+      assert_eq!(validate_topic_name(valid), Ok(()), "{}", valid);
+    }
+    for invalid in [
+      "",
+      "foo/",
+      "foo//bar",
+      "foo__bar",
+      "~foo",
+      "foo/~",
+      "123abc",
+      "/foo/1bar",
+      "foo-bar",
+      "{node/foo}",
+      "{node",
+      "node}",
+      "{{node}}",
+    ] {
+      assert!(validate_topic_name(invalid).is_err(), "{}", invalid);
+    }
+  }
+
+  #[test]
+  fn topic_name_expansion() {
+    let expand = |name| expand_topic_name(name, "talker", "/robot1");
+    assert_eq!(expand("chatter").unwrap(), "/robot1/chatter");
+    assert_eq!(expand("/chatter").unwrap(), "/chatter");
+    assert_eq!(expand("~/state").unwrap(), "/robot1/talker/state");
+    assert_eq!(expand("~").unwrap(), "/robot1/talker");
+    assert_eq!(expand("{node}/out").unwrap(), "/robot1/talker/out");
+    assert_eq!(expand("/{ns}/out").unwrap(), "/robot1/out");
+    assert!(expand("{unknown}/out").is_err());
+    assert_eq!(
+      expand_topic_name("chatter", "talker", "/").unwrap(),
+      "/chatter"
+    );
+  }
+
+  #[test]
+  fn topic_name_mangling() {
+    assert_eq!(topic_name("/chatter"), "rt/chatter");
+    assert_eq!(topic_name("/robot1/cmd_vel"), "rt/robot1/cmd_vel");
+    assert_eq!(service_request_topic_name("/add"), "rq/addRequest");
+    assert_eq!(service_response_topic_name("/add"), "rr/addReply");
+
+    assert_eq!(
+      demangle_topic_name("rt/robot1/cmd_vel"),
+      Some((RosTopicKind::Topic, "/robot1/cmd_vel".to_string()))
+    );
+    assert_eq!(
+      demangle_topic_name("rq/addRequest"),
+      Some((RosTopicKind::ServiceRequest, "/add".to_string()))
+    );
+    assert_eq!(
+      demangle_topic_name("rr/addReply"),
+      Some((RosTopicKind::ServiceResponse, "/add".to_string()))
+    );
+    assert_eq!(demangle_topic_name("ros_discovery_info"), None);
+    assert_eq!(demangle_topic_name("rq/add"), None);
+    assert_eq!(demangle_topic_name("rt/"), None);
+  }
+
+  #[test]
+  fn type_name_mangling() {
+    assert_eq!(
+      type_name("std_msgs/msg/String").unwrap(),
+      "std_msgs::msg::dds_::String_"
+    );
+    assert_eq!(
+      type_name("geometry_msgs/Twist").unwrap(),
+      "geometry_msgs::msg::dds_::Twist_"
+    );
+    assert_eq!(
+      type_name("example_interfaces/srv/AddTwoInts_Request").unwrap(),
+      "example_interfaces::srv::dds_::AddTwoInts_Request_"
+    );
+    assert_eq!(type_name("String"), None);
+    assert_eq!(type_name("a/b/c/d"), None);
+
+    assert_eq!(
+      demangle_type_name("std_msgs::msg::dds_::String_").unwrap(),
+      "std_msgs/msg/String"
+    );
+    assert_eq!(demangle_type_name("ShapeType"), None);
+    assert_eq!(demangle_type_name("std_msgs::msg::String_"), None);
+  }
+}
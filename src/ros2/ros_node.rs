@@ -28,7 +28,7 @@ use crate::{
 use super::{
   builtin_datatypes::{Gid, Log, NodeInfo, ParameterEvents, ROSParticipantInfo},
   builtin_topics::{ParameterEventsTopic, ROSDiscoveryTopic, RosOutTopic},
-  KeyedRosPublisher, KeyedRosSubscriber, RosPublisher, RosSubscriber,
+  names, KeyedRosPublisher, KeyedRosSubscriber, RosPublisher, RosSubscriber,
 };
 
 // ----------------------------------------------------------------------------------------------------
@@ -495,14 +495,11 @@ impl RosNode {
     qos: &QosPolicies,
     topic_kind: TopicKind,
   ) -> Result<Topic, Error> {
-    if name.is_empty() {
-      return Error::bad_parameter("Topic name must not be empty.");
+    if let Err(e) = names::validate_topic_name(name) {
+      return Error::bad_parameter(e.to_string());
     }
-    // TODO: Implement the rest of the rules.
 
-    let mut oname = "rt/".to_owned();
-    let name_stripped = name.strip_prefix('/').unwrap_or(name); // avoid double slash in name
-    oname.push_str(name_stripped);
+    let oname = names::topic_name(name);
     info!("Creating topic, DDS name: {}", oname);
     let topic = self
       .ros_participant
@@ -0,0 +1,1054 @@
+use std::{
+  collections::BTreeMap,
+  fmt,
+  sync::{Arc, Mutex},
+  time::SystemTime,
+};
+
+use chrono::DateTime;
+use ring::digest::{digest, SHA256};
+
+use crate::{
+  dds::{
+    qos_provider::{xml, Node},
+    values::result::{Error, Result},
+  },
+  security::{
+    authentication::{IdentityCredentials, RemoteCredentials},
+    certificate::{base64_decode, Certificate, Der, TAG_INTEGER, TAG_OID, TAG_SEQUENCE, TAG_SET},
+    plugins::AccessControlPlugin,
+    types::DataHolder,
+    SecurityError, SecurityResult,
+  },
+  structure::guid::{GuidPrefix, GUID},
+};
+
+// Builtin access control plugin "DDS:Access:Permissions", DDS Security spec
+// v1.1 Section "9.4 Builtin Access Control: DDS:Access:Permissions".
+//
+// The governance document tells, for each domain, whether joining the domain
+// is access controlled, and which topics are. The permissions document grants
+// participants, identified by the subject of their identity certificate, the
+// right to join domains and to publish and subscribe topics. Both documents are
+// signed by the permissions CA. Participants send their permissions document to
+// each other in the authentication handshake.
+//
+// Partitions and data tags are not supported: an allow rule that restricts them
+// never applies, and a deny rule applies regardless of them.
+
+const CLASS_ID: &str = "DDS:Access:Permissions:1.0";
+
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_KEY_IDENTIFIER: u8 = 0x80; // [0] IMPLICIT
+const TAG_CONTEXT_0: u8 = 0xa0;
+const TAG_CONTEXT_1: u8 = 0xa1;
+
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const OID_MESSAGE_DIGEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x04];
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+
+/// What a DataWriter or DataReader does with its topic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopicAccess {
+  Publish,
+  Subscribe,
+}
+
+impl fmt::Display for TopicAccess {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Publish => write!(f, "publish"),
+      Self::Subscribe => write!(f, "subscribe"),
+    }
+  }
+}
+
+/// Access control documents of a DomainParticipant for DDS Security: the
+/// permissions CA certificate, and the governance and permissions documents
+/// signed by that CA.
+///
+/// Give this to
+/// [`DomainParticipantBuilder::permissions`](crate::DomainParticipantBuilder::permissions)
+/// to restrict which domains and topics participants may use.
+#[derive(Clone)]
+pub struct PermissionsCredentials {
+  inner: Arc<PermissionsDocuments>,
+}
+
+struct PermissionsDocuments {
+  ca: Certificate,
+  governance: Governance,
+  permissions: Permissions,
+  // As it was given, since the handshake sends the signed document
+  signed_permissions: String,
+}
+
+impl PermissionsCredentials {
+  /// The CA certificate is PEM-encoded. The documents are XML signed with
+  /// S/MIME, as specified in DDS Security spec v1.1 Section "9.4.1
+  /// Configuration", e.g. with `openssl smime -sign -text`. Signatures must
+  /// use SHA-256.
+  pub fn from_smime(permissions_ca: &str, governance: &str, permissions: &str) -> Result<Self> {
+    let ca = Certificate::from_pem(permissions_ca)
+      .or_else(|e| Error::bad_parameter(format!("Permissions CA: {}", e)))?;
+    if !ca.is_valid_at(SystemTime::now()) {
+      return Error::bad_parameter("Permissions CA certificate is not valid now");
+    }
+    let governance = verify_smime(governance, &ca)
+      .and_then(|document| Governance::parse(&document))
+      .or_else(|e| Error::bad_parameter(format!("Governance: {}", e)))?;
+    let parsed_permissions = verify_smime(permissions, &ca)
+      .and_then(|document| Permissions::parse(&document))
+      .or_else(|e| Error::bad_parameter(format!("Permissions: {}", e)))?;
+
+    Ok(Self {
+      inner: Arc::new(PermissionsDocuments {
+        ca,
+        governance,
+        permissions: parsed_permissions,
+        signed_permissions: permissions.to_string(),
+      }),
+    })
+  }
+}
+
+impl fmt::Debug for PermissionsCredentials {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("PermissionsCredentials")
+      .field("ca", &self.inner.ca.subject_name())
+      .finish()
+  }
+}
+
+/// How RTPS messages, submessages or serialized payloads are protected, e.g.
+/// from the *_protection_kind elements of the governance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectionKind {
+  None,
+  Sign,
+  Encrypt,
+}
+
+/// Protection of the submessages and of the serialized payloads of the
+/// endpoints of a topic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TopicProtection {
+  /// Protection of the submessages
+  pub metadata: ProtectionKind,
+  /// Protection of the serialized payloads
+  pub data: ProtectionKind,
+}
+
+impl TopicProtection {
+  /// Nothing is protected.
+  pub const NONE: Self = Self {
+    metadata: ProtectionKind::None,
+    data: ProtectionKind::None,
+  };
+}
+
+// Access control of the local participant in its domain
+#[derive(Debug, Clone)]
+pub(crate) struct AccessControl {
+  credentials: PermissionsCredentials,
+  domain_id: u16,
+  // Indices of the governance rule of the domain, and of our grant in the
+  // permissions document
+  domain_rule: usize,
+  grant: usize,
+}
+
+impl AccessControl {
+  // Check that the local participant may join the domain, see
+  // "check_create_participant" in Section "9.4.3 DDS:Access:Permissions plugin
+  // behavior".
+  pub fn new(
+    credentials: PermissionsCredentials,
+    identity: &IdentityCredentials,
+    domain_id: u16,
+  ) -> Result<Self> {
+    let documents = &credentials.inner;
+    let domain_rule = match documents.governance.domain_rule(domain_id) {
+      Some(index) => index,
+      None => {
+        return Error::permission_denied(format!("Governance has no rule for domain {}", domain_id))
+      }
+    };
+    let subject_name = identity.subject_name();
+    let grant = match documents.permissions.grant(&subject_name) {
+      Some(index) => index,
+      None => return Error::permission_denied(format!("No permissions for {}", subject_name)),
+    };
+
+    let access_control = Self {
+      credentials,
+      domain_id,
+      domain_rule,
+      grant,
+    };
+    if let Err(e) = access_control.check_grant(access_control.grant()) {
+      return Error::permission_denied(e.to_string());
+    }
+    Ok(access_control)
+  }
+
+  fn domain_rule(&self) -> &DomainRule {
+    &self.credentials.inner.governance.domain_rules[self.domain_rule]
+  }
+
+  fn grant(&self) -> &Grant {
+    &self.credentials.inner.permissions.grants[self.grant]
+  }
+
+  pub fn allows_unauthenticated_participants(&self) -> bool {
+    self.domain_rule().allow_unauthenticated_participants
+  }
+
+  // The PermissionsToken that is announced in SPDP
+  pub fn permissions_token(&self) -> DataHolder {
+    let ca = &self.credentials.inner.ca;
+    DataHolder::new(CLASS_ID)
+      .with_property("dds.perm_ca.sn", ca.subject_name())
+      .with_property("dds.perm_ca.algo", ca.key_algorithm.token_name())
+  }
+
+  // The signed permissions document, sent in the handshake as "c.perm"
+  pub fn permissions_document(&self) -> Vec<u8> {
+    self
+      .credentials
+      .inner
+      .signed_permissions
+      .as_bytes()
+      .to_vec()
+  }
+
+  // Check that a local DataWriter or DataReader may be created.
+  pub fn check_local_endpoint(&self, topic_name: &str, access: TopicAccess) -> SecurityResult<()> {
+    self.check_endpoint(Some(self.grant()), topic_name, access)
+  }
+
+  // Validate the permissions document of an authenticated remote participant,
+  // received in the handshake. Returns the grant of the participant, or None
+  // if it has no permissions document and the domain does not require one.
+  pub fn validate_remote_permissions(
+    &self,
+    subject_name: &str,
+    signed_permissions: &[u8],
+  ) -> SecurityResult<Option<Grant>> {
+    if signed_permissions.is_empty() {
+      return if self.domain_rule().join_access_control {
+        Err(SecurityError::new(
+          "Participant has no permissions document",
+        ))
+      } else {
+        Ok(None)
+      };
+    }
+    let document = std::str::from_utf8(signed_permissions)
+      .map_err(|_| SecurityError::new("Permissions document is not text"))?;
+    let permissions = Permissions::parse(&verify_smime(document, &self.credentials.inner.ca)?)?;
+    let grant = permissions
+      .into_grant(subject_name)
+      .ok_or_else(|| SecurityError::new(format!("No permissions for {}", subject_name)))?;
+    self.check_grant(&grant)?;
+    Ok(Some(grant))
+  }
+
+  // Check that a DataWriter or DataReader may use the topic. `grant` is the
+  // grant of its participant, if that has one.
+  pub fn check_endpoint(
+    &self,
+    grant: Option<&Grant>,
+    topic_name: &str,
+    access: TopicAccess,
+  ) -> SecurityResult<()> {
+    let rule = self.domain_rule().topic_rule(topic_name).ok_or_else(|| {
+      SecurityError::new(format!("Governance has no rule for topic {}", topic_name))
+    })?;
+    let access_controlled = match access {
+      TopicAccess::Publish => rule.write_access_control,
+      TopicAccess::Subscribe => rule.read_access_control,
+    };
+    match grant {
+      _ if !access_controlled => Ok(()),
+      Some(grant) if grant.allows(self.domain_id, topic_name, access) => Ok(()),
+      _ => Err(SecurityError::new(format!(
+        "Not permitted to {} topic {}",
+        access, topic_name
+      ))),
+    }
+  }
+
+  // Protection of whole RTPS messages in the domain
+  pub fn rtps_protection(&self) -> ProtectionKind {
+    self.domain_rule().rtps_protection
+  }
+
+  // Protection of the endpoints of a topic. Topics that the governance has no
+  // rule for cannot have endpoints.
+  pub fn topic_protection(&self, topic_name: &str) -> TopicProtection {
+    self
+      .domain_rule()
+      .topic_rule(topic_name)
+      .map_or(TopicProtection::NONE, |rule| rule.protection)
+  }
+
+  // Does the domain need the cryptographic plugin at all?
+  pub fn uses_protection(&self) -> bool {
+    let domain_rule = self.domain_rule();
+    domain_rule.rtps_protection != ProtectionKind::None
+      || domain_rule
+        .topic_rules
+        .iter()
+        .any(|rule| rule.protection != TopicProtection::NONE)
+  }
+
+  fn check_grant(&self, grant: &Grant) -> SecurityResult<()> {
+    if !grant.is_valid_at(SystemTime::now()) {
+      return Err(SecurityError::new(format!(
+        "Permissions of {} are not valid now",
+        grant.subject_name
+      )));
+    }
+    if self.domain_rule().join_access_control && !grant.may_join(self.domain_id) {
+      return Err(SecurityError::new(format!(
+        "{} may not join domain {}",
+        grant.subject_name, self.domain_id
+      )));
+    }
+    Ok(())
+  }
+}
+
+// The builtin access control plugin, which also remembers the grants of
+// authenticated remote participants
+pub(crate) struct BuiltinAccessControl {
+  access_control: AccessControl,
+  remote_grants: Mutex<BTreeMap<GuidPrefix, Grant>>,
+}
+
+impl BuiltinAccessControl {
+  pub fn new(access_control: AccessControl) -> Self {
+    Self {
+      access_control,
+      remote_grants: Mutex::new(BTreeMap::new()),
+    }
+  }
+}
+
+impl AccessControlPlugin for BuiltinAccessControl {
+  fn permissions_token(&self) -> DataHolder {
+    self.access_control.permissions_token()
+  }
+
+  fn permissions_document(&self) -> Vec<u8> {
+    self.access_control.permissions_document()
+  }
+
+  fn allows_unauthenticated_participants(&self) -> bool {
+    self.access_control.allows_unauthenticated_participants()
+  }
+
+  fn check_local_endpoint(&self, topic_name: &str, access: TopicAccess) -> SecurityResult<()> {
+    self.access_control.check_local_endpoint(topic_name, access)
+  }
+
+  fn validate_remote_permissions(
+    &self,
+    guid_prefix: GuidPrefix,
+    credentials: &RemoteCredentials,
+  ) -> SecurityResult<()> {
+    let grant = self
+      .access_control
+      .validate_remote_permissions(&credentials.subject_name, &credentials.permissions)?;
+    let mut remote_grants = self.remote_grants.lock().unwrap();
+    match grant {
+      Some(grant) => remote_grants.insert(guid_prefix, grant),
+      None => remote_grants.remove(&guid_prefix),
+    };
+    Ok(())
+  }
+
+  fn check_remote_endpoint(
+    &self,
+    guid: GUID,
+    topic_name: &str,
+    access: TopicAccess,
+  ) -> SecurityResult<()> {
+    let remote_grants = self.remote_grants.lock().unwrap();
+    self
+      .access_control
+      .check_endpoint(remote_grants.get(&guid.prefix), topic_name, access)
+  }
+
+  fn remove_remote_participant(&self, guid_prefix: GuidPrefix) {
+    self.remote_grants.lock().unwrap().remove(&guid_prefix);
+  }
+
+  fn rtps_protection(&self) -> ProtectionKind {
+    self.access_control.rtps_protection()
+  }
+
+  fn topic_protection(&self, topic_name: &str) -> TopicProtection {
+    self.access_control.topic_protection(topic_name)
+  }
+
+  fn uses_protection(&self) -> bool {
+    self.access_control.uses_protection()
+  }
+}
+
+// Domain ids of <domains>, as inclusive ranges. An <id> is a range of one.
+#[derive(Debug)]
+struct DomainSet {
+  ranges: Vec<(u32, u32)>,
+}
+
+impl DomainSet {
+  fn parse(node: &Node) -> SecurityResult<Self> {
+    let number = |text: &str| {
+      text
+        .parse::<u32>()
+        .map_err(|_| SecurityError::new(format!("Invalid domain id {:?}", text)))
+    };
+    let mut ranges = Vec::new();
+    for child in &node.children {
+      match child.name.as_str() {
+        "id" => {
+          let id = number(&child.text)?;
+          ranges.push((id, id));
+        }
+        // Either end may be left out.
+        "id_range" => ranges.push((
+          child.child_text("min").map_or(Ok(0), number)?,
+          child.child_text("max").map_or(Ok(u32::MAX), number)?,
+        )),
+        other => {
+          return Err(SecurityError::new(format!(
+            "Unexpected <{}> in <domains>",
+            other
+          )))
+        }
+      }
+    }
+    Ok(Self { ranges })
+  }
+
+  fn contains(&self, domain_id: u16) -> bool {
+    let id = u32::from(domain_id);
+    self
+      .ranges
+      .iter()
+      .any(|(min, max)| (*min..=*max).contains(&id))
+  }
+}
+
+// Governance document, Section "9.4.1.2 Domain Governance Document"
+struct Governance {
+  domain_rules: Vec<DomainRule>,
+}
+
+struct DomainRule {
+  domains: DomainSet,
+  allow_unauthenticated_participants: bool,
+  join_access_control: bool,
+  rtps_protection: ProtectionKind,
+  topic_rules: Vec<TopicRule>,
+}
+
+struct TopicRule {
+  topic_expression: String,
+  read_access_control: bool,
+  write_access_control: bool,
+  protection: TopicProtection,
+}
+
+impl Governance {
+  fn parse(document: &str) -> SecurityResult<Self> {
+    let root = parse_xml(document)?;
+    let domain_rules = required_child(&root, "domain_access_rules")?
+      .children_named("domain_rule")
+      .map(DomainRule::parse)
+      .collect::<SecurityResult<_>>()?;
+    Ok(Self { domain_rules })
+  }
+
+  // The first rule that covers the domain applies.
+  fn domain_rule(&self, domain_id: u16) -> Option<usize> {
+    self
+      .domain_rules
+      .iter()
+      .position(|rule| rule.domains.contains(domain_id))
+  }
+}
+
+impl DomainRule {
+  fn parse(node: &Node) -> SecurityResult<Self> {
+    let topic_rules = match node.child("topic_access_rules") {
+      Some(rules) => rules
+        .children_named("topic_rule")
+        .map(TopicRule::parse)
+        .collect::<SecurityResult<_>>()?,
+      None => Vec::new(),
+    };
+    Ok(Self {
+      domains: DomainSet::parse(required_child(node, "domains")?)?,
+      allow_unauthenticated_participants: flag(node, "allow_unauthenticated_participants")?,
+      join_access_control: flag(node, "enable_join_access_control")?,
+      rtps_protection: protection_kind(node, "rtps_protection_kind")?,
+      topic_rules,
+    })
+  }
+
+  // The first rule whose expression matches the topic name applies.
+  fn topic_rule(&self, topic_name: &str) -> Option<&TopicRule> {
+    self
+      .topic_rules
+      .iter()
+      .find(|rule| fnmatch(&rule.topic_expression, topic_name))
+  }
+}
+
+impl TopicRule {
+  fn parse(node: &Node) -> SecurityResult<Self> {
+    Ok(Self {
+      topic_expression: required_child(node, "topic_expression")?.text.clone(),
+      read_access_control: flag(node, "enable_read_access_control")?,
+      write_access_control: flag(node, "enable_write_access_control")?,
+      protection: TopicProtection {
+        metadata: protection_kind(node, "metadata_protection_kind")?,
+        data: protection_kind(node, "data_protection_kind")?,
+      },
+    })
+  }
+}
+
+// Permissions document, Section "9.4.1.3 DomainParticipant Permissions
+// Document"
+struct Permissions {
+  grants: Vec<Grant>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Grant {
+  subject_name: String,
+  // Seconds since the Unix epoch
+  not_before: i64,
+  not_after: i64,
+  rules: Vec<Rule>,
+  default_allow: bool,
+}
+
+#[derive(Debug)]
+struct Rule {
+  allow: bool,
+  domains: DomainSet,
+  publish: Vec<Criteria>,
+  subscribe: Vec<Criteria>,
+}
+
+// <publish> or <subscribe> of a rule
+#[derive(Debug)]
+struct Criteria {
+  topics: Vec<String>,
+  // Has <partitions> or <data_tags>
+  restricted: bool,
+}
+
+impl Permissions {
+  fn parse(document: &str) -> SecurityResult<Self> {
+    let root = parse_xml(document)?;
+    let grants = required_child(&root, "permissions")?
+      .children_named("grant")
+      .map(Grant::parse)
+      .collect::<SecurityResult<_>>()?;
+    Ok(Self { grants })
+  }
+
+  fn grant(&self, subject_name: &str) -> Option<usize> {
+    self
+      .grants
+      .iter()
+      .position(|grant| same_subject(&grant.subject_name, subject_name))
+  }
+
+  fn into_grant(self, subject_name: &str) -> Option<Grant> {
+    self
+      .grants
+      .into_iter()
+      .find(|grant| same_subject(&grant.subject_name, subject_name))
+  }
+}
+
+impl Grant {
+  fn parse(node: &Node) -> SecurityResult<Self> {
+    let validity = required_child(node, "validity")?;
+    let mut rules = Vec::new();
+    for child in &node.children {
+      let allow = match child.name.as_str() {
+        "allow_rule" => true,
+        "deny_rule" => false,
+        _ => continue,
+      };
+      rules.push(Rule {
+        allow,
+        domains: DomainSet::parse(required_child(child, "domains")?)?,
+        publish: child
+          .children_named("publish")
+          .map(Criteria::parse)
+          .collect(),
+        subscribe: child
+          .children_named("subscribe")
+          .map(Criteria::parse)
+          .collect(),
+      });
+    }
+    let default_allow = match node.child_text("default") {
+      Some("ALLOW") => true,
+      Some("DENY") | None => false,
+      Some(other) => {
+        return Err(SecurityError::new(format!(
+          "Invalid default {:?} in grant",
+          other
+        )))
+      }
+    };
+    Ok(Self {
+      subject_name: required_child(node, "subject_name")?.text.clone(),
+      not_before: parse_time(&required_child(validity, "not_before")?.text)?,
+      not_after: parse_time(&required_child(validity, "not_after")?.text)?,
+      rules,
+      default_allow,
+    })
+  }
+
+  fn is_valid_at(&self, time: SystemTime) -> bool {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+      Ok(d) => (self.not_before..=self.not_after).contains(&(d.as_secs() as i64)),
+      Err(_) => false,
+    }
+  }
+
+  // The participant may join a domain that some allow rule is about, or any
+  // domain if the default is to allow.
+  fn may_join(&self, domain_id: u16) -> bool {
+    self.default_allow
+      || self
+        .rules
+        .iter()
+        .any(|rule| rule.allow && rule.domains.contains(domain_id))
+  }
+
+  // The first rule that matches the domain and topic decides. If none does,
+  // the default decides.
+  fn allows(&self, domain_id: u16, topic_name: &str, access: TopicAccess) -> bool {
+    for rule in self.rules.iter().filter(|r| r.domains.contains(domain_id)) {
+      let criteria = match access {
+        TopicAccess::Publish => &rule.publish,
+        TopicAccess::Subscribe => &rule.subscribe,
+      };
+      let matched = criteria
+        .iter()
+        .any(|c| (!rule.allow || !c.restricted) && c.topics.iter().any(|t| fnmatch(t, topic_name)));
+      if matched {
+        return rule.allow;
+      }
+    }
+    self.default_allow
+  }
+}
+
+impl Criteria {
+  fn parse(node: &Node) -> Self {
+    // Without <topics>, the criteria is about all topics.
+    let topics = match node.child("topics") {
+      Some(topics) => topics
+        .children_named("topic")
+        .map(|topic| topic.text.clone())
+        .collect(),
+      None => vec!["*".to_string()],
+    };
+    Self {
+      topics,
+      restricted: node.child("partitions").is_some() || node.child("data_tags").is_some(),
+    }
+  }
+}
+
+fn parse_xml(document: &str) -> SecurityResult<Node> {
+  xml::parse(document).map_err(|e| SecurityError::new(e.to_string()))
+}
+
+fn required_child<'a>(node: &'a Node, name: &str) -> SecurityResult<&'a Node> {
+  node
+    .child(name)
+    .ok_or_else(|| SecurityError::new(format!("<{}> has no <{}>", node.name, name)))
+}
+
+// Missing flags are false.
+fn flag(node: &Node, name: &str) -> SecurityResult<bool> {
+  node
+    .bool(name)
+    .map(Option::unwrap_or_default)
+    .map_err(|e| SecurityError::new(e.to_string()))
+}
+
+// A missing kind is NONE. Origin authentication needs receiver specific keys,
+// which are not supported, so the *_WITH_ORIGIN_AUTHENTICATION kinds are
+// treated as the plain ones.
+fn protection_kind(node: &Node, name: &str) -> SecurityResult<ProtectionKind> {
+  match node.child_text(name) {
+    None | Some("NONE") => Ok(ProtectionKind::None),
+    Some("SIGN") | Some("SIGN_WITH_ORIGIN_AUTHENTICATION") => Ok(ProtectionKind::Sign),
+    Some("ENCRYPT") | Some("ENCRYPT_WITH_ORIGIN_AUTHENTICATION") => Ok(ProtectionKind::Encrypt),
+    Some(other) => Err(SecurityError::new(format!(
+      "Invalid <{}> {:?}",
+      name, other
+    ))),
+  }
+}
+
+// xs:dateTime, e.g. "2020-01-01T00:00:00". Without a time zone, UTC.
+fn parse_time(text: &str) -> SecurityResult<i64> {
+  DateTime::parse_from_rfc3339(text)
+    .or_else(|_| DateTime::parse_from_rfc3339(&format!("{}Z", text)))
+    .map(|time| time.timestamp())
+    .map_err(|_| SecurityError::new(format!("Invalid time {:?}", text)))
+}
+
+// Subject names are compared attribute by attribute, since tools write them in
+// different order and spacing, e.g. "CN=a,O=b" or "O=b, CN=a".
+fn same_subject(a: &str, b: &str) -> bool {
+  let attributes = |name: &str| {
+    let mut attributes: Vec<(String, String)> = name
+      .split(',')
+      .map(|part| {
+        let (key, value) = part.split_once('=').unwrap_or((part, ""));
+        (key.trim().to_ascii_uppercase(), value.trim().to_string())
+      })
+      .collect();
+    attributes.sort();
+    attributes
+  };
+  attributes(a) == attributes(b)
+}
+
+// Topic expressions are POSIX fnmatch() patterns: "*", "?" and bracket
+// expressions like "[a-z]" or "[!0-9]".
+fn fnmatch(pattern: &str, name: &str) -> bool {
+  let pattern: Vec<char> = pattern.chars().collect();
+  let name: Vec<char> = name.chars().collect();
+  fnmatch_chars(&pattern, &name)
+}
+
+fn fnmatch_chars(pattern: &[char], name: &[char]) -> bool {
+  match (pattern.split_first(), name.split_first()) {
+    (None, _) => name.is_empty(),
+    (Some(('*', rest)), _) => (0..=name.len()).any(|i| fnmatch_chars(rest, &name[i..])),
+    (Some(_), None) => false,
+    (Some(('?', rest)), Some((_, name_rest))) => fnmatch_chars(rest, name_rest),
+    (Some(('[', rest)), Some((c, name_rest))) => match bracket(rest, *c) {
+      Some((matched, after)) => matched && fnmatch_chars(after, name_rest),
+      // Not terminated, so "[" is an ordinary character.
+      None => *c == '[' && fnmatch_chars(rest, name_rest),
+    },
+    (Some((p, rest)), Some((c, name_rest))) => p == c && fnmatch_chars(rest, name_rest),
+  }
+}
+
+// Whether the bracket expression that starts after "[" matches `c`, and the
+// rest of the pattern after "]". None if there is no "]".
+fn bracket(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+  let (negate, mut i) = match pattern.first() {
+    Some('!') | Some('^') => (true, 1),
+    _ => (false, 0),
+  };
+  let start = i;
+  let mut matched = false;
+  loop {
+    let first = *pattern.get(i)?;
+    // "]" right after "[" or "[!" is an ordinary character.
+    if first == ']' && i > start {
+      break;
+    }
+    match (pattern.get(i + 1), pattern.get(i + 2)) {
+      (Some('-'), Some(last)) if *last != ']' => {
+        matched |= (first..=*last).contains(&c);
+        i += 3;
+      }
+      _ => {
+        matched |= first == c;
+        i += 1;
+      }
+    }
+  }
+  Some((matched != negate, &pattern[i + 1..]))
+}
+
+// Content of an S/MIME signed document, after checking that it is signed by
+// the CA. The document is "multipart/signed" with a detached PKCS#7 signature
+// (RFC 5652) that has signed attributes, as written by "openssl smime -sign".
+fn verify_smime(document: &str, ca: &Certificate) -> SecurityResult<String> {
+  let invalid = |reason: &str| SecurityError::new(format!("Invalid S/MIME document: {}", reason));
+  let document = document.replace("\r\n", "\n");
+  let (headers, body) = split_headers(&document).ok_or_else(|| invalid("no body"))?;
+  let boundary = mime_boundary(headers).ok_or_else(|| invalid("not multipart"))?;
+
+  // The line break before a delimiter belongs to the delimiter. The parts are
+  // the preamble, the signed content, the signature, and the end.
+  let delimiter = format!("\n--{}", boundary);
+  let body = format!("\n{}", body);
+  let parts: Vec<&str> = body.split(delimiter.as_str()).collect();
+  if parts.len() != 4 || !parts[3].starts_with("--") {
+    return Err(invalid("expected two parts"));
+  }
+  let signed = parts[1].split_once('\n').map_or("", |(_, part)| part);
+  let signature_part = parts[2].split_once('\n').map_or("", |(_, part)| part);
+  let (signature_headers, signature) =
+    split_headers(signature_part).ok_or_else(|| invalid("no signature"))?;
+  if !signature_headers.contains("pkcs7-signature") {
+    return Err(invalid("no signature"));
+  }
+
+  // The signature is over the canonical form, with CRLF line breaks.
+  verify_pkcs7(
+    &base64_decode(signature)?,
+    signed.replace('\n', "\r\n").as_bytes(),
+    ca,
+  )?;
+
+  // The signed content has MIME headers, unless the document was signed
+  // without them.
+  if signed.starts_with('<') {
+    Ok(signed.to_string())
+  } else {
+    let (_, content) = split_headers(signed).ok_or_else(|| invalid("no content"))?;
+    Ok(content.to_string())
+  }
+}
+
+// Headers and body of a MIME entity with "\n" line breaks
+fn split_headers(entity: &str) -> Option<(&str, &str)> {
+  match entity.strip_prefix('\n') {
+    Some(body) => Some(("", body)),
+    None => entity.split_once("\n\n"),
+  }
+}
+
+fn mime_boundary(headers: &str) -> Option<&str> {
+  let start = headers.find("boundary=")? + "boundary=".len();
+  let value = &headers[start..];
+  let boundary = match value.strip_prefix('"') {
+    Some(quoted) => quoted.split('"').next(),
+    None => value.split(|c: char| c == ';' || c.is_whitespace()).next(),
+  };
+  boundary.filter(|b| !b.is_empty())
+}
+
+// Check a detached PKCS#7 SignedData signature of the content. The signer must
+// have used the private key of the CA.
+fn verify_pkcs7(signature: &[u8], content: &[u8], ca: &Certificate) -> SecurityResult<()> {
+  let mut content_info = Der::new(signature).read(TAG_SEQUENCE)?;
+  if content_info.read(TAG_OID)?.input != OID_SIGNED_DATA {
+    return Err(SecurityError::new("Signature is not PKCS#7 SignedData"));
+  }
+  let mut signed_data = content_info.read(TAG_CONTEXT_0)?.read(TAG_SEQUENCE)?;
+  signed_data.read(TAG_INTEGER)?; // version
+  signed_data.read(TAG_SET)?; // digest algorithms
+  signed_data.read(TAG_SEQUENCE)?; // content info, without the content
+  for optional in [TAG_CONTEXT_0, TAG_CONTEXT_1] {
+    // certificates, CRLs
+    if signed_data.peek_tag() == Some(optional) {
+      signed_data.read(optional)?;
+    }
+  }
+
+  let mut signer = signed_data.read(TAG_SET)?.read(TAG_SEQUENCE)?;
+  signer.read(TAG_INTEGER)?; // version
+  match signer.peek_tag() {
+    Some(TAG_KEY_IDENTIFIER) => signer.read(TAG_KEY_IDENTIFIER)?,
+    _ => signer.read(TAG_SEQUENCE)?, // issuer and serial number
+  };
+  if signer.read(TAG_SEQUENCE)?.read(TAG_OID)?.input != OID_SHA256 {
+    return Err(SecurityError::new("Only SHA-256 signatures are supported"));
+  }
+  let signed_attributes = signer.read_raw(TAG_CONTEXT_0)?;
+  signer.read(TAG_SEQUENCE)?; // signature algorithm, which follows from the CA key
+  let signature = signer.read(TAG_OCTET_STRING)?.input;
+
+  let mut attributes = Der::new(signed_attributes).read(TAG_CONTEXT_0)?;
+  let mut message_digest = None;
+  while !attributes.is_empty() {
+    let mut attribute = attributes.read(TAG_SEQUENCE)?;
+    if attribute.read(TAG_OID)?.input == OID_MESSAGE_DIGEST {
+      message_digest = Some(attribute.read(TAG_SET)?.read(TAG_OCTET_STRING)?.input);
+    }
+  }
+  if message_digest != Some(digest(&SHA256, content).as_ref()) {
+    return Err(SecurityError::new("Document does not match its signature"));
+  }
+
+  // The signature is over the attributes, encoded as a SET.
+  let mut signed = signed_attributes.to_vec();
+  signed[0] = TAG_SET;
+  ca.verify_sha256_signature(&signed, signature)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+  use crate::security::certificate::tests::{IDENTITY_CA, PARTICIPANT_A, PARTICIPANT_B};
+
+  // Signed by the identity CA, which is also the permissions CA here
+  const GOVERNANCE: &str = include_str!("../test/security/governance.p7s");
+  const PERMISSIONS: &str = include_str!("../test/security/permissions.p7s");
+
+  fn access_control(certificate: &str, key: &str, domain_id: u16) -> Result<AccessControl> {
+    let identity = IdentityCredentials::from_pem(IDENTITY_CA, certificate, key).unwrap();
+    let credentials =
+      PermissionsCredentials::from_smime(IDENTITY_CA, GOVERNANCE, PERMISSIONS).unwrap();
+    AccessControl::new(credentials, &identity, domain_id)
+  }
+
+  pub(crate) fn participant_a(domain_id: u16) -> Result<AccessControl> {
+    access_control(
+      PARTICIPANT_A,
+      include_str!("../test/security/participant_a_key.pem"),
+      domain_id,
+    )
+  }
+
+  pub(crate) fn participant_b(domain_id: u16) -> Result<AccessControl> {
+    access_control(
+      PARTICIPANT_B,
+      include_str!("../test/security/participant_b_key.pem"),
+      domain_id,
+    )
+  }
+
+  #[test]
+  fn permissions_documents_verify() {
+    assert!(PermissionsCredentials::from_smime(IDENTITY_CA, GOVERNANCE, PERMISSIONS).is_ok());
+    // Signed by another CA
+    assert!(PermissionsCredentials::from_smime(PARTICIPANT_A, GOVERNANCE, PERMISSIONS).is_err());
+    // Modified after signing
+    let tampered = GOVERNANCE.replacen(
+      "<enable_join_access_control>true",
+      "<enable_join_access_control>false",
+      1,
+    );
+    assert_ne!(tampered, GOVERNANCE);
+    assert!(PermissionsCredentials::from_smime(IDENTITY_CA, &tampered, PERMISSIONS).is_err());
+    // Not signed at all
+    assert!(PermissionsCredentials::from_smime(IDENTITY_CA, "<dds/>", PERMISSIONS).is_err());
+  }
+
+  #[test]
+  fn access_control_join() {
+    assert!(participant_a(0).is_ok());
+    assert!(participant_a(15).is_ok());
+    // Participant B is granted only domain 0.
+    assert!(participant_b(0).is_ok());
+    assert!(matches!(
+      participant_b(15),
+      Err(Error::PermissionDenied { .. })
+    ));
+    // The governance has no rule for domain 5.
+    assert!(participant_a(5).is_err());
+  }
+
+  #[test]
+  fn access_control_local_endpoints() {
+    use TopicAccess::{Publish, Subscribe};
+
+    let a = participant_a(0).unwrap();
+    assert!(a.check_local_endpoint("Square", Publish).is_ok());
+    assert!(a.check_local_endpoint("Squares", Publish).is_err());
+    assert!(a.check_local_endpoint("Squares", Subscribe).is_ok());
+    // Topics without access control
+    assert!(a.check_local_endpoint("Open", Publish).is_ok());
+    // Topics that the governance does not cover
+    assert!(a.check_local_endpoint("Circle", Subscribe).is_err());
+
+    // The deny rule comes before the allow rule.
+    let b = participant_b(0).unwrap();
+    assert!(b.check_local_endpoint("Square", Publish).is_err());
+    assert!(b.check_local_endpoint("Squares", Publish).is_ok());
+    assert!(b.check_local_endpoint("Square", Subscribe).is_ok());
+    assert!(b.check_local_endpoint("Squares", Subscribe).is_err());
+  }
+
+  #[test]
+  fn access_control_remote_participants() {
+    let a = participant_a(0).unwrap();
+    let b = participant_b(0).unwrap();
+    let b_subject = "C=FI, O=RustDDS Test, CN=Participant B";
+    let grant = a
+      .validate_remote_permissions(b_subject, &b.permissions_document())
+      .unwrap();
+    assert!(grant.is_some());
+    assert!(a
+      .check_endpoint(grant.as_ref(), "Square", TopicAccess::Publish)
+      .is_err());
+    assert!(a
+      .check_endpoint(grant.as_ref(), "Square", TopicAccess::Subscribe)
+      .is_ok());
+    // Participants without permissions see only topics without access
+    // control.
+    assert!(a
+      .check_endpoint(None, "Square", TopicAccess::Subscribe)
+      .is_err());
+    assert!(a
+      .check_endpoint(None, "Open", TopicAccess::Subscribe)
+      .is_ok());
+
+    // The governance requires permissions for joining.
+    assert!(a.validate_remote_permissions(b_subject, &[]).is_err());
+    assert!(a
+      .validate_remote_permissions("CN=Participant C", &b.permissions_document())
+      .is_err());
+    assert_eq!(a.permissions_token().class_id, CLASS_ID);
+  }
+
+  #[test]
+  fn access_control_protection_kinds() {
+    let a = participant_a(0).unwrap();
+    assert!(a.uses_protection());
+    assert_eq!(a.rtps_protection(), ProtectionKind::Sign);
+    assert_eq!(
+      a.topic_protection("Square"),
+      TopicProtection {
+        metadata: ProtectionKind::Sign,
+        data: ProtectionKind::Encrypt,
+      }
+    );
+    assert_eq!(a.topic_protection("Open"), TopicProtection::NONE);
+    assert_eq!(a.topic_protection("Circle"), TopicProtection::NONE);
+  }
+
+  #[test]
+  fn access_control_subject_names() {
+    assert!(same_subject(
+      "CN=Participant A,O=RustDDS Test,C=FI",
+      "C=FI, O=RustDDS Test, CN=Participant A"
+    ));
+    assert!(!same_subject(
+      "CN=Participant A,O=RustDDS Test",
+      "C=FI, O=RustDDS Test, CN=Participant A"
+    ));
+  }
+
+  #[test]
+  fn access_control_fnmatch() {
+    assert!(fnmatch("*", ""));
+    assert!(fnmatch("Square*", "Square"));
+    assert!(fnmatch("Square*", "Squares"));
+    assert!(!fnmatch("Square", "Squares"));
+    assert!(fnmatch("Sq?are", "Square"));
+    assert!(fnmatch("*/rt/[a-c]*", "ros/rt/chatter"));
+    assert!(!fnmatch("[!a-c]x", "bx"));
+    assert!(fnmatch("[]]", "]"));
+    assert!(fnmatch("a[", "a["));
+    assert!(!fnmatch("?", ""));
+  }
+}
@@ -0,0 +1,1172 @@
+use std::{
+  collections::BTreeMap,
+  fmt,
+  sync::{Arc, Mutex, MutexGuard},
+  time::SystemTime,
+};
+
+use byteorder::BigEndian;
+use log::{debug, info, warn};
+use ring::{
+  agreement::{self, EphemeralPrivateKey},
+  digest::{digest, SHA256},
+  rand::{SecureRandom, SystemRandom},
+  signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+};
+
+use crate::{
+  dds::values::result::{Error, Result},
+  discovery::data_types::spdp_participant_data::SpdpDiscoveredParticipantData,
+  messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
+  security::{
+    certificate::{pem_decode, Certificate, KeyAlgorithm},
+    generic_message::{MessageIdentity, ParticipantGenericMessage},
+    plugins::AuthenticationPlugin,
+    types::{BinaryProperty, DataHolder},
+    SecurityError, SecurityResult,
+  },
+  serialization::{cdr_serializer::to_bytes, pl_cdr_serializer::PlCdrSerialize},
+  structure::guid::{EntityId, GuidPrefix, GUID},
+};
+
+// Builtin authentication plugin "DDS:Auth:PKI-DH", DDS Security spec v1.1
+// Section "9.3 Builtin Authentication: DDS:Auth:PKI-DH".
+//
+// Participants are identified by X.509 certificates issued by a shared
+// identity CA. Two participants authenticate each other with a three-way
+// handshake (request, reply, final) over the ParticipantStatelessMessage
+// topic. The handshake also agrees on a shared secret with ephemeral ECDH.
+
+const CLASS_ID: &str = "DDS:Auth:PKI-DH:1.0";
+const CLASS_ID_REQUEST: &str = "DDS:Auth:PKI-DH:1.0+Req";
+const CLASS_ID_REPLY: &str = "DDS:Auth:PKI-DH:1.0+Reply";
+const CLASS_ID_FINAL: &str = "DDS:Auth:PKI-DH:1.0+Final";
+
+const DSIGN_ALGO: &str = "ECDSA-SHA256";
+const KAGREE_ALGO: &str = "ECDH+prime256v1-CEUM";
+const CHALLENGE_LENGTH: usize = 32;
+
+/// Identity of a DomainParticipant for DDS Security authentication: the
+/// identity CA certificate, the certificate of the participant signed by that
+/// CA, and the private key of the participant.
+///
+/// Give this to
+/// [`DomainParticipantBuilder::identity`](crate::DomainParticipantBuilder::identity)
+/// to make the participant authenticate remote participants.
+#[derive(Clone)]
+pub struct IdentityCredentials {
+  inner: Arc<Identity>,
+}
+
+struct Identity {
+  ca: Certificate,
+  certificate: Certificate,
+  key_pair: EcdsaKeyPair,
+}
+
+impl IdentityCredentials {
+  /// All arguments are PEM-encoded. The private key must be an ECDSA P-256 key
+  /// in PKCS#8 format ("BEGIN PRIVATE KEY"), and the certificate must be
+  /// currently valid and issued by the identity CA.
+  pub fn from_pem(
+    identity_ca: &str,
+    identity_certificate: &str,
+    private_key: &str,
+  ) -> Result<Self> {
+    let rng = SystemRandom::new();
+    let check = |r: SecurityResult<()>| r.or_else(|e| Error::bad_parameter(e.to_string()));
+
+    let ca = Certificate::from_pem(identity_ca)
+      .or_else(|e| Error::bad_parameter(format!("Identity CA: {}", e)))?;
+    let certificate = Certificate::from_pem(identity_certificate)
+      .or_else(|e| Error::bad_parameter(format!("Identity certificate: {}", e)))?;
+    check(certificate.verify(&ca, SystemTime::now()))?;
+    if certificate.key_algorithm != KeyAlgorithm::EcdsaP256 {
+      return Error::bad_parameter("Only ECDSA P-256 identities are supported");
+    }
+
+    let der = pem_decode(private_key, "PRIVATE KEY")
+      .or_else(|e| Error::bad_parameter(format!("Private key: {}", e)))?;
+    let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &der, &rng)
+      .or_else(|e| Error::bad_parameter(format!("Private key: {}", e)))?;
+    if key_pair.public_key().as_ref() != certificate.public_key.as_slice() {
+      return Error::bad_parameter("Private key does not match the identity certificate");
+    }
+
+    Ok(Self {
+      inner: Arc::new(Identity {
+        ca,
+        certificate,
+        key_pair,
+      }),
+    })
+  }
+
+  /// The subject of the identity certificate, e.g. "C=FI, O=Example,
+  /// CN=Participant 1"
+  pub fn subject_name(&self) -> String {
+    self.inner.certificate.subject_name()
+  }
+
+  // The IdentityToken that is announced in SPDP
+  pub(crate) fn identity_token(&self) -> DataHolder {
+    let Identity {
+      ca, certificate, ..
+    } = &*self.inner;
+    DataHolder::new(CLASS_ID)
+      .with_property("dds.cert.sn", certificate.subject_name())
+      .with_property("dds.cert.algo", certificate.key_algorithm.token_name())
+      .with_property("dds.ca.sn", ca.subject_name())
+      .with_property("dds.ca.algo", ca.key_algorithm.token_name())
+  }
+
+  // A secure participant must have a GUID prefix that is derived from its
+  // certificate subject, so that it cannot impersonate others. See DDS
+  // Security spec v1.1 Section "9.3.3 DDS:Auth:PKI-DH plugin behavior".
+  pub(crate) fn adjusted_guid_prefix(&self, candidate: GUID) -> GuidPrefix {
+    let mut bytes = [0; 12];
+    bytes[..6].copy_from_slice(&subject_guid_bytes(&self.inner.certificate.subject));
+    let hash = digest(&SHA256, &candidate.to_bytes());
+    bytes[6..].copy_from_slice(&hash.as_ref()[..6]);
+    GuidPrefix::new(&bytes)
+  }
+}
+
+impl fmt::Debug for IdentityCredentials {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("IdentityCredentials")
+      .field("subject", &self.subject_name())
+      .finish()
+  }
+}
+
+// First bit set, followed by the first 47 bits of the SHA-256 of the subject
+// name.
+fn subject_guid_bytes(subject: &[u8]) -> [u8; 6] {
+  let hash = digest(&SHA256, subject);
+  let hash = hash.as_ref();
+  let mut bytes = [0; 6];
+  bytes[0] = 0x80 | (hash[0] >> 1);
+  for (i, byte) in bytes.iter_mut().enumerate().skip(1) {
+    *byte = (hash[i - 1] << 7) | (hash[i] >> 1);
+  }
+  bytes
+}
+
+/// Secret agreed on in a completed authentication handshake. This is what the
+/// cryptographic plugin derives its key exchange keys from.
+#[derive(Clone)]
+pub struct SharedSecret {
+  /// Challenge of the handshake request
+  pub challenge1: Vec<u8>,
+  /// Challenge of the handshake reply
+  pub challenge2: Vec<u8>,
+  /// The agreed secret, e.g. from Diffie-Hellman
+  pub secret: Vec<u8>,
+}
+
+/// Identity and permissions of an authenticated remote participant, for access
+/// control
+#[derive(Debug, Clone)]
+pub struct RemoteCredentials {
+  /// Subject name of the identity certificate
+  pub subject_name: String,
+  /// The signed permissions document, empty if the participant sent none
+  pub permissions: Vec<u8>,
+}
+
+/// What the Discovery should do after a handshake message was processed
+pub enum HandshakeProgress {
+  /// Send the message and wait for the remote participant to answer.
+  Send(ParticipantGenericMessage),
+  /// The remote participant is authenticated. Send the message, if there is
+  /// one.
+  Completed(Option<ParticipantGenericMessage>),
+  /// E.g. a duplicate message
+  Nothing,
+}
+
+#[allow(clippy::large_enum_variant)] // One per remote participant
+enum HandshakeState {
+  // The remote participant has the smaller GUID, so it sends the request.
+  WaitRequest,
+  // We have sent a request.
+  WaitReply {
+    dh1: EphemeralPrivateKey,
+    dh1_public: Vec<u8>,
+    challenge1: Vec<u8>,
+    hash_c1: Vec<u8>,
+  },
+  // We have replied to a request.
+  WaitFinal {
+    remote_certificate: Certificate,
+    remote_permissions: Vec<u8>,
+    dsign_algo: String,
+    hash_c1: Vec<u8>,
+    hash_c2: Vec<u8>,
+    dh1: Vec<u8>,
+    dh2: Vec<u8>,
+    shared_secret: SharedSecret,
+  },
+  Completed {
+    shared_secret: SharedSecret,
+    remote: RemoteCredentials,
+  },
+}
+
+struct Handshake {
+  remote_guid: GUID,
+  state: HandshakeState,
+  // The stateless messages are sent best-effort, so the last message is resent
+  // until the remote participant answers.
+  last_message: Option<ParticipantGenericMessage>,
+}
+
+// Runs the authentication handshakes of the local participant with remote
+// participants.
+pub(crate) struct Authentication {
+  identity: IdentityCredentials,
+  participant_guid: GUID,
+  rng: SystemRandom,
+  // Local SPDP data, sent in the handshake as "c.pdata"
+  participant_data: Vec<u8>,
+  // Signed permissions document, sent in the handshake as "c.perm"
+  permissions_document: Vec<u8>,
+  sequence_number: i64,
+  handshakes: BTreeMap<GuidPrefix, Handshake>,
+}
+
+impl Authentication {
+  pub fn new(identity: IdentityCredentials, participant_guid: GUID) -> Self {
+    Self {
+      identity,
+      participant_guid,
+      rng: SystemRandom::new(),
+      participant_data: Vec::new(),
+      permissions_document: Vec::new(),
+      sequence_number: 0,
+      handshakes: BTreeMap::new(),
+    }
+  }
+
+  pub fn set_participant_data(&mut self, data: &SpdpDiscoveredParticipantData) {
+    match data.to_pl_cdr_bytes(RepresentationIdentifier::PL_CDR_BE) {
+      Ok(bytes) => {
+        let mut pdata = RepresentationIdentifier::PL_CDR_BE.to_bytes().to_vec();
+        pdata.extend_from_slice(&[0, 0]); // representation options
+        pdata.extend_from_slice(&bytes);
+        self.participant_data = pdata;
+      }
+      Err(e) => warn!(
+        "Cannot serialize participant data for authentication: {:?}",
+        e
+      ),
+    }
+  }
+
+  pub fn set_permissions_document(&mut self, document: Vec<u8>) {
+    self.permissions_document = document;
+  }
+
+  // Start authenticating a newly discovered participant. Returns the handshake
+  // request, if we are the one to send it.
+  pub fn begin(
+    &mut self,
+    remote_guid: GUID,
+    identity_token: Option<&DataHolder>,
+  ) -> SecurityResult<Option<ParticipantGenericMessage>> {
+    if self.handshakes.contains_key(&remote_guid.prefix) {
+      return Ok(None);
+    }
+    match identity_token {
+      Some(token) if token.class_id == CLASS_ID => (),
+      Some(token) => {
+        return Err(SecurityError::new(format!(
+          "Unsupported authentication plugin {}",
+          token.class_id
+        )))
+      }
+      None => return Err(SecurityError::new("Participant has no IdentityToken")),
+    }
+    if (remote_guid.prefix.bytes[0] & 0x80) == 0 {
+      return Err(SecurityError::new("GUID is not derived from a certificate"));
+    }
+
+    if remote_guid.prefix < self.participant_guid.prefix {
+      self.handshakes.insert(
+        remote_guid.prefix,
+        Handshake {
+          remote_guid,
+          state: HandshakeState::WaitRequest,
+          last_message: None,
+        },
+      );
+      return Ok(None);
+    }
+
+    let dh1 = agreement::EphemeralPrivateKey::generate(&agreement::ECDH_P256, &self.rng)
+      .map_err(|_| SecurityError::new("Cannot generate DH key"))?;
+    let dh1_public = dh1
+      .compute_public_key()
+      .map_err(|_| SecurityError::new("Cannot compute DH public key"))?
+      .as_ref()
+      .to_vec();
+    let challenge1 = self.challenge()?;
+    let credentials = self.credential_properties();
+    let hash_c1 = hash(&credentials)?;
+
+    let mut request = DataHolder::new(CLASS_ID_REQUEST);
+    request.binary_properties = credentials;
+    let request = request
+      .with_binary_property("hash_c1", hash_c1.clone())
+      .with_binary_property("dh1", dh1_public.clone())
+      .with_binary_property("challenge1", challenge1.clone());
+
+    let message = self.message(remote_guid, MessageIdentity::default(), request);
+    debug!("Sending authentication request to {:?}", remote_guid.prefix);
+    self.handshakes.insert(
+      remote_guid.prefix,
+      Handshake {
+        remote_guid,
+        state: HandshakeState::WaitReply {
+          dh1,
+          dh1_public,
+          challenge1,
+          hash_c1,
+        },
+        last_message: Some(message.clone()),
+      },
+    );
+    Ok(Some(message))
+  }
+
+  // Process a received handshake message. An error means that the message is
+  // invalid and was dropped.
+  pub fn process(
+    &mut self,
+    message: &ParticipantGenericMessage,
+  ) -> SecurityResult<HandshakeProgress> {
+    if message.destination_participant_guid.prefix != self.participant_guid.prefix
+      || message.message_class_id != ParticipantGenericMessage::AUTH_HANDSHAKE
+    {
+      return Ok(HandshakeProgress::Nothing);
+    }
+    let remote_prefix = message.message_identity.source_guid.prefix;
+    let token = message
+      .message_data
+      .first()
+      .ok_or_else(|| SecurityError::new("Handshake message has no token"))?;
+    let handshake = self.handshakes.remove(&remote_prefix).ok_or_else(|| {
+      SecurityError::new(format!(
+        "Handshake message from unknown participant {:?}",
+        remote_prefix
+      ))
+    })?;
+    let remote_guid = handshake.remote_guid;
+
+    // The handshake state is put back in all cases, also when the message was
+    // invalid.
+    let (handshake, result) = match token.class_id.as_str() {
+      CLASS_ID_REQUEST => self.process_request(handshake, message, token),
+      CLASS_ID_REPLY => self.process_reply(handshake, message, token),
+      CLASS_ID_FINAL => self.process_final(handshake, token),
+      other => (
+        handshake,
+        Err(SecurityError::new(format!(
+          "Unknown handshake message {}",
+          other
+        ))),
+      ),
+    };
+    self.handshakes.insert(remote_guid.prefix, handshake);
+    if let Ok(HandshakeProgress::Completed(_)) = result {
+      info!("Authenticated participant {:?}", remote_guid.prefix);
+    }
+    result
+  }
+
+  fn process_request(
+    &mut self,
+    handshake: Handshake,
+    message: &ParticipantGenericMessage,
+    token: &DataHolder,
+  ) -> (Handshake, SecurityResult<HandshakeProgress>) {
+    match &handshake.state {
+      HandshakeState::WaitRequest => (),
+      HandshakeState::WaitFinal { dh1, .. }
+        if token.binary_property("dh1") == Some(dh1.as_slice()) =>
+      {
+        // Our reply was lost. Send it again.
+        let progress = handshake
+          .last_message
+          .clone()
+          .map_or(HandshakeProgress::Nothing, HandshakeProgress::Send);
+        return (handshake, Ok(progress));
+      }
+      // The remote participant started over.
+      HandshakeState::WaitFinal { .. } => (),
+      _ => {
+        return (
+          handshake,
+          Err(SecurityError::new("Unexpected handshake request")),
+        )
+      }
+    }
+
+    match self.reply(handshake.remote_guid, message, token) {
+      Ok((state, reply)) => (
+        Handshake {
+          remote_guid: handshake.remote_guid,
+          state,
+          last_message: Some(reply.clone()),
+        },
+        Ok(HandshakeProgress::Send(reply)),
+      ),
+      Err(e) => (handshake, Err(e)),
+    }
+  }
+
+  fn reply(
+    &mut self,
+    remote_guid: GUID,
+    message: &ParticipantGenericMessage,
+    request: &DataHolder,
+  ) -> SecurityResult<(HandshakeState, ParticipantGenericMessage)> {
+    let (remote_certificate, dsign_algo) = self.remote_identity(remote_guid, request)?;
+    let hash_c1 = hash(&remote_credential_properties(request)?)?;
+    if let Some(received) = request.binary_property("hash_c1") {
+      if received != hash_c1.as_slice() {
+        return Err(SecurityError::new("hash_c1 does not match"));
+      }
+    }
+    let remote_permissions = request
+      .binary_property("c.perm")
+      .unwrap_or_default()
+      .to_vec();
+    let dh1 = required(request, "dh1")?.to_vec();
+    let challenge1 = required(request, "challenge1")?.to_vec();
+
+    let dh2_private = agreement::EphemeralPrivateKey::generate(&agreement::ECDH_P256, &self.rng)
+      .map_err(|_| SecurityError::new("Cannot generate DH key"))?;
+    let dh2 = dh2_private
+      .compute_public_key()
+      .map_err(|_| SecurityError::new("Cannot compute DH public key"))?
+      .as_ref()
+      .to_vec();
+    let challenge2 = self.challenge()?;
+    let credentials = self.credential_properties();
+    let hash_c2 = hash(&credentials)?;
+    let secret = agree(dh2_private, &dh1)?;
+
+    let signature = self.sign(&[
+      ("hash_c2", &hash_c2),
+      ("challenge2", &challenge2),
+      ("dh2", &dh2),
+      ("challenge1", &challenge1),
+      ("dh1", &dh1),
+      ("hash_c1", &hash_c1),
+    ])?;
+
+    let mut reply = DataHolder::new(CLASS_ID_REPLY);
+    reply.binary_properties = credentials;
+    let reply = reply
+      .with_binary_property("hash_c2", hash_c2.clone())
+      .with_binary_property("dh2", dh2.clone())
+      .with_binary_property("hash_c1", hash_c1.clone())
+      .with_binary_property("dh1", dh1.clone())
+      .with_binary_property("challenge1", challenge1.clone())
+      .with_binary_property("challenge2", challenge2.clone())
+      .with_binary_property("signature", signature);
+
+    let state = HandshakeState::WaitFinal {
+      remote_certificate,
+      remote_permissions,
+      dsign_algo,
+      hash_c1,
+      hash_c2,
+      dh1,
+      dh2,
+      shared_secret: SharedSecret {
+        challenge1,
+        challenge2,
+        secret,
+      },
+    };
+    debug!("Sending authentication reply to {:?}", remote_guid.prefix);
+    let reply = self.message(remote_guid, message.message_identity, reply);
+    Ok((state, reply))
+  }
+
+  fn process_reply(
+    &mut self,
+    handshake: Handshake,
+    message: &ParticipantGenericMessage,
+    token: &DataHolder,
+  ) -> (Handshake, SecurityResult<HandshakeProgress>) {
+    let Handshake {
+      remote_guid,
+      state,
+      last_message,
+    } = handshake;
+    match state {
+      HandshakeState::WaitReply {
+        dh1,
+        dh1_public,
+        challenge1,
+        hash_c1,
+      } => {
+        // Check the reply before the DH private key is consumed.
+        let checked = self.check_reply(remote_guid, token, &dh1_public, &challenge1, &hash_c1);
+        let (hash_c2, dh2, challenge2, remote) = match checked {
+          Ok(values) => values,
+          Err(e) => {
+            let state = HandshakeState::WaitReply {
+              dh1,
+              dh1_public,
+              challenge1,
+              hash_c1,
+            };
+            return (
+              Handshake {
+                remote_guid,
+                state,
+                last_message,
+              },
+              Err(e),
+            );
+          }
+        };
+
+        let secret = agree(dh1, &dh2);
+        let signature = self.sign(&[
+          ("hash_c1", &hash_c1),
+          ("challenge1", &challenge1),
+          ("dh1", &dh1_public),
+          ("challenge2", &challenge2),
+          ("dh2", &dh2),
+          ("hash_c2", &hash_c2),
+        ]);
+        let (secret, signature) = match (secret, signature) {
+          (Ok(secret), Ok(signature)) => (secret, signature),
+          (Err(e), _) | (_, Err(e)) => {
+            // The DH private key is consumed, so this handshake cannot continue.
+            return (
+              Handshake {
+                remote_guid,
+                state: HandshakeState::WaitRequest,
+                last_message: None,
+              },
+              Err(e),
+            );
+          }
+        };
+
+        let final_token = DataHolder::new(CLASS_ID_FINAL)
+          .with_binary_property("hash_c1", hash_c1)
+          .with_binary_property("hash_c2", hash_c2)
+          .with_binary_property("dh1", dh1_public)
+          .with_binary_property("dh2", dh2)
+          .with_binary_property("challenge1", challenge1.clone())
+          .with_binary_property("challenge2", challenge2.clone())
+          .with_binary_property("signature", signature);
+        debug!("Sending authentication final to {:?}", remote_guid.prefix);
+        let final_message = self.message(remote_guid, message.message_identity, final_token);
+        (
+          Handshake {
+            remote_guid,
+            state: HandshakeState::Completed {
+              shared_secret: SharedSecret {
+                challenge1,
+                challenge2,
+                secret,
+              },
+              remote,
+            },
+            last_message: Some(final_message.clone()),
+          },
+          Ok(HandshakeProgress::Completed(Some(final_message))),
+        )
+      }
+      HandshakeState::Completed { .. } => {
+        // Our final message was lost. Send it again.
+        let progress = last_message
+          .clone()
+          .map_or(HandshakeProgress::Nothing, HandshakeProgress::Send);
+        (
+          Handshake {
+            remote_guid,
+            state,
+            last_message,
+          },
+          Ok(progress),
+        )
+      }
+      state => (
+        Handshake {
+          remote_guid,
+          state,
+          last_message,
+        },
+        Err(SecurityError::new("Unexpected handshake reply")),
+      ),
+    }
+  }
+
+  // Verify a reply to our request. Returns hash_c2, dh2, challenge2 and the
+  // credentials of the remote participant.
+  #[allow(clippy::type_complexity)]
+  fn check_reply(
+    &self,
+    remote_guid: GUID,
+    reply: &DataHolder,
+    dh1: &[u8],
+    challenge1: &[u8],
+    hash_c1: &[u8],
+  ) -> SecurityResult<(Vec<u8>, Vec<u8>, Vec<u8>, RemoteCredentials)> {
+    if required(reply, "dh1")? != dh1
+      || required(reply, "challenge1")? != challenge1
+      || matches!(reply.binary_property("hash_c1"), Some(h) if h != hash_c1)
+    {
+      return Err(SecurityError::new("Reply does not match our request"));
+    }
+    let (remote_certificate, dsign_algo) = self.remote_identity(remote_guid, reply)?;
+    let hash_c2 = hash(&remote_credential_properties(reply)?)?;
+    if let Some(received) = reply.binary_property("hash_c2") {
+      if received != hash_c2.as_slice() {
+        return Err(SecurityError::new("hash_c2 does not match"));
+      }
+    }
+    let dh2 = required(reply, "dh2")?.to_vec();
+    let challenge2 = required(reply, "challenge2")?.to_vec();
+
+    let signed = signed_bytes(&[
+      ("hash_c2", &hash_c2),
+      ("challenge2", &challenge2),
+      ("dh2", &dh2),
+      ("challenge1", challenge1),
+      ("dh1", dh1),
+      ("hash_c1", hash_c1),
+    ])?;
+    remote_certificate.verify_signature(&dsign_algo, &signed, required(reply, "signature")?)?;
+    let remote = RemoteCredentials {
+      subject_name: remote_certificate.subject_name(),
+      permissions: reply.binary_property("c.perm").unwrap_or_default().to_vec(),
+    };
+    Ok((hash_c2, dh2, challenge2, remote))
+  }
+
+  fn process_final(
+    &mut self,
+    handshake: Handshake,
+    token: &DataHolder,
+  ) -> (Handshake, SecurityResult<HandshakeProgress>) {
+    let Handshake {
+      remote_guid,
+      state,
+      last_message,
+    } = handshake;
+    let checked = match &state {
+      HandshakeState::WaitFinal {
+        remote_certificate,
+        dsign_algo,
+        hash_c1,
+        hash_c2,
+        dh1,
+        dh2,
+        shared_secret,
+        ..
+      } => check_final(
+        token,
+        remote_certificate,
+        dsign_algo,
+        hash_c1,
+        hash_c2,
+        dh1,
+        dh2,
+        shared_secret,
+      ),
+      // Duplicate
+      HandshakeState::Completed { .. } => {
+        return (
+          Handshake {
+            remote_guid,
+            state,
+            last_message,
+          },
+          Ok(HandshakeProgress::Nothing),
+        )
+      }
+      _ => Err(SecurityError::new("Unexpected handshake final")),
+    };
+
+    match (checked, state) {
+      (
+        Ok(()),
+        HandshakeState::WaitFinal {
+          remote_certificate,
+          remote_permissions,
+          shared_secret,
+          ..
+        },
+      ) => (
+        Handshake {
+          remote_guid,
+          state: HandshakeState::Completed {
+            shared_secret,
+            remote: RemoteCredentials {
+              subject_name: remote_certificate.subject_name(),
+              permissions: remote_permissions,
+            },
+          },
+          last_message: None,
+        },
+        Ok(HandshakeProgress::Completed(None)),
+      ),
+      (checked, state) => (
+        Handshake {
+          remote_guid,
+          state,
+          last_message,
+        },
+        checked.map(|()| HandshakeProgress::Nothing),
+      ),
+    }
+  }
+
+  // Messages of unfinished handshakes, to be sent again
+  pub fn resend(&self) -> Vec<ParticipantGenericMessage> {
+    self
+      .handshakes
+      .values()
+      .filter(|h| !matches!(h.state, HandshakeState::Completed { .. }))
+      .filter_map(|h| h.last_message.clone())
+      .collect()
+  }
+
+  // Credentials of a remote participant that has completed the handshake
+  pub fn remote_credentials(&self, guid_prefix: GuidPrefix) -> Option<&RemoteCredentials> {
+    match &self.handshakes.get(&guid_prefix)?.state {
+      HandshakeState::Completed { remote, .. } => Some(remote),
+      _ => None,
+    }
+  }
+
+  // Secret agreed on with a remote participant that has completed the handshake
+  pub fn shared_secret(&self, guid_prefix: GuidPrefix) -> Option<&SharedSecret> {
+    match &self.handshakes.get(&guid_prefix)?.state {
+      HandshakeState::Completed { shared_secret, .. } => Some(shared_secret),
+      _ => None,
+    }
+  }
+
+  pub fn remove(&mut self, guid_prefix: GuidPrefix) {
+    self.handshakes.remove(&guid_prefix);
+  }
+
+  // Validate the certificate in a request or reply, and check that the remote
+  // GUID is derived from it. Returns the certificate and signature algorithm.
+  fn remote_identity(
+    &self,
+    remote_guid: GUID,
+    token: &DataHolder,
+  ) -> SecurityResult<(Certificate, String)> {
+    let pem = String::from_utf8(required(token, "c.id")?.to_vec())
+      .map_err(|_| SecurityError::new("c.id is not a PEM certificate"))?;
+    let certificate = Certificate::from_pem(&pem)?;
+    certificate.verify(&self.identity.inner.ca, SystemTime::now())?;
+    if remote_guid.prefix.bytes[..6] != subject_guid_bytes(&certificate.subject) {
+      return Err(SecurityError::new(format!(
+        "GUID {:?} does not belong to {}",
+        remote_guid.prefix,
+        certificate.subject_name()
+      )));
+    }
+    if required(token, "c.kagree_algo")? != KAGREE_ALGO.as_bytes() {
+      return Err(SecurityError::new("Unsupported key agreement algorithm"));
+    }
+    let dsign_algo = String::from_utf8(required(token, "c.dsign_algo")?.to_vec())
+      .map_err(|_| SecurityError::new("Invalid c.dsign_algo"))?;
+    Ok((certificate, dsign_algo))
+  }
+
+  // c.id, c.perm, c.pdata, c.dsign_algo and c.kagree_algo of the local
+  // participant
+  fn credential_properties(&self) -> Vec<BinaryProperty> {
+    let property = |name: &str, value: &[u8]| BinaryProperty {
+      name: name.to_string(),
+      value: value.to_vec(),
+    };
+    vec![
+      property("c.id", self.identity.inner.certificate.pem.as_bytes()),
+      property("c.perm", &self.permissions_document),
+      property("c.pdata", &self.participant_data),
+      property("c.dsign_algo", DSIGN_ALGO.as_bytes()),
+      property("c.kagree_algo", KAGREE_ALGO.as_bytes()),
+    ]
+  }
+
+  fn challenge(&self) -> SecurityResult<Vec<u8>> {
+    let mut challenge = vec![0; CHALLENGE_LENGTH];
+    self
+      .rng
+      .fill(&mut challenge)
+      .map_err(|_| SecurityError::new("Cannot generate challenge"))?;
+    Ok(challenge)
+  }
+
+  fn sign(&self, properties: &[(&str, &[u8])]) -> SecurityResult<Vec<u8>> {
+    let signed = signed_bytes(properties)?;
+    self
+      .identity
+      .inner
+      .key_pair
+      .sign(&self.rng, &signed)
+      .map(|signature| signature.as_ref().to_vec())
+      .map_err(|_| SecurityError::new("Signing failed"))
+  }
+
+  fn message(
+    &mut self,
+    remote_guid: GUID,
+    related_message_identity: MessageIdentity,
+    token: DataHolder,
+  ) -> ParticipantGenericMessage {
+    self.sequence_number += 1;
+    ParticipantGenericMessage {
+      message_identity: MessageIdentity {
+        source_guid: self
+          .participant_guid
+          .from_prefix(EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_WRITER),
+        sequence_number: self.sequence_number,
+      },
+      related_message_identity,
+      destination_participant_guid: remote_guid,
+      destination_endpoint_guid: GUID::GUID_UNKNOWN,
+      source_endpoint_guid: GUID::GUID_UNKNOWN,
+      message_class_id: ParticipantGenericMessage::AUTH_HANDSHAKE.to_string(),
+      message_data: vec![token],
+    }
+  }
+}
+
+// The builtin authentication plugin, shared by the participant and Discovery
+pub(crate) struct BuiltinAuthentication {
+  identity: IdentityCredentials,
+  inner: Mutex<Authentication>,
+}
+
+impl BuiltinAuthentication {
+  pub fn new(identity: IdentityCredentials, participant_guid: GUID) -> Self {
+    Self {
+      inner: Mutex::new(Authentication::new(identity.clone(), participant_guid)),
+      identity,
+    }
+  }
+
+  fn lock(&self) -> MutexGuard<'_, Authentication> {
+    self.inner.lock().unwrap()
+  }
+}
+
+impl AuthenticationPlugin for BuiltinAuthentication {
+  fn adjusted_guid_prefix(&self, candidate: GUID) -> GuidPrefix {
+    self.identity.adjusted_guid_prefix(candidate)
+  }
+
+  fn identity_token(&self) -> DataHolder {
+    self.identity.identity_token()
+  }
+
+  fn set_participant_data(&self, data: &SpdpDiscoveredParticipantData) {
+    self.lock().set_participant_data(data);
+  }
+
+  fn set_permissions_document(&self, document: Vec<u8>) {
+    self.lock().set_permissions_document(document);
+  }
+
+  fn begin(
+    &self,
+    remote_guid: GUID,
+    identity_token: Option<&DataHolder>,
+  ) -> SecurityResult<Option<ParticipantGenericMessage>> {
+    self.lock().begin(remote_guid, identity_token)
+  }
+
+  fn process(&self, message: &ParticipantGenericMessage) -> SecurityResult<HandshakeProgress> {
+    self.lock().process(message)
+  }
+
+  fn resend(&self) -> Vec<ParticipantGenericMessage> {
+    self.lock().resend()
+  }
+
+  fn remote_credentials(&self, guid_prefix: GuidPrefix) -> Option<RemoteCredentials> {
+    self.lock().remote_credentials(guid_prefix).cloned()
+  }
+
+  fn shared_secret(&self, guid_prefix: GuidPrefix) -> Option<SharedSecret> {
+    self.lock().shared_secret(guid_prefix).cloned()
+  }
+
+  fn remove(&self, guid_prefix: GuidPrefix) {
+    self.lock().remove(guid_prefix);
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_final(
+  token: &DataHolder,
+  remote_certificate: &Certificate,
+  dsign_algo: &str,
+  hash_c1: &[u8],
+  hash_c2: &[u8],
+  dh1: &[u8],
+  dh2: &[u8],
+  shared_secret: &SharedSecret,
+) -> SecurityResult<()> {
+  let SharedSecret {
+    challenge1,
+    challenge2,
+    ..
+  } = shared_secret;
+  if required(token, "challenge1")? != challenge1.as_slice()
+    || required(token, "challenge2")? != challenge2.as_slice()
+  {
+    return Err(SecurityError::new("Final does not match our reply"));
+  }
+  let signed = signed_bytes(&[
+    ("hash_c1", hash_c1),
+    ("challenge1", challenge1),
+    ("dh1", dh1),
+    ("challenge2", challenge2),
+    ("dh2", dh2),
+    ("hash_c2", hash_c2),
+  ])?;
+  remote_certificate.verify_signature(dsign_algo, &signed, required(token, "signature")?)
+}
+
+// c.* properties of a remote participant, in the order that they are hashed
+fn remote_credential_properties(token: &DataHolder) -> SecurityResult<Vec<BinaryProperty>> {
+  ["c.id", "c.perm", "c.pdata", "c.dsign_algo", "c.kagree_algo"]
+    .iter()
+    .map(|name| -> SecurityResult<BinaryProperty> {
+      // c.perm and c.pdata may be missing from a peer without access control.
+      let value = match *name {
+        "c.perm" | "c.pdata" => token.binary_property(name).unwrap_or_default(),
+        _ => required(token, name)?,
+      };
+      Ok(BinaryProperty {
+        name: name.to_string(),
+        value: value.to_vec(),
+      })
+    })
+    .collect()
+}
+
+fn required<'a>(token: &'a DataHolder, name: &str) -> SecurityResult<&'a [u8]> {
+  token
+    .binary_property(name)
+    .ok_or_else(|| SecurityError::new(format!("Handshake message has no {}", name)))
+}
+
+// Hashes and signatures are computed over a CDR big-endian BinaryPropertySeq.
+fn signed_bytes(properties: &[(&str, &[u8])]) -> SecurityResult<Vec<u8>> {
+  let properties: Vec<BinaryProperty> = properties
+    .iter()
+    .map(|(name, value)| BinaryProperty {
+      name: name.to_string(),
+      value: value.to_vec(),
+    })
+    .collect();
+  to_bytes::<Vec<BinaryProperty>, BigEndian>(&properties)
+    .map_err(|e| SecurityError::new(format!("Serialization failed: {:?}", e)))
+}
+
+fn hash(properties: &[BinaryProperty]) -> SecurityResult<Vec<u8>> {
+  let bytes = to_bytes::<Vec<BinaryProperty>, BigEndian>(&properties.to_vec())
+    .map_err(|e| SecurityError::new(format!("Serialization failed: {:?}", e)))?;
+  Ok(digest(&SHA256, &bytes).as_ref().to_vec())
+}
+
+fn agree(private_key: EphemeralPrivateKey, peer_public_key: &[u8]) -> SecurityResult<Vec<u8>> {
+  let peer_public_key = agreement::UnparsedPublicKey::new(&agreement::ECDH_P256, peer_public_key);
+  agreement::agree_ephemeral(private_key, &peer_public_key, |secret| secret.to_vec())
+    .map_err(|_| SecurityError::new("Key agreement failed"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::security::certificate::tests::{IDENTITY_CA, PARTICIPANT_A, PARTICIPANT_B};
+
+  const PARTICIPANT_A_KEY: &str = include_str!("../test/security/participant_a_key.pem");
+  const PARTICIPANT_B_KEY: &str = include_str!("../test/security/participant_b_key.pem");
+
+  fn participant(certificate: &str, key: &str) -> Authentication {
+    let identity = IdentityCredentials::from_pem(IDENTITY_CA, certificate, key).unwrap();
+    let prefix = identity.adjusted_guid_prefix(GUID::new_participant_guid());
+    Authentication::new(identity, GUID::new(prefix, EntityId::PARTICIPANT))
+  }
+
+  // Returns (initiator, responder)
+  fn participants() -> (Authentication, Authentication) {
+    let a = participant(PARTICIPANT_A, PARTICIPANT_A_KEY);
+    let b = participant(PARTICIPANT_B, PARTICIPANT_B_KEY);
+    if a.participant_guid < b.participant_guid {
+      (a, b)
+    } else {
+      (b, a)
+    }
+  }
+
+  fn sent(progress: SecurityResult<HandshakeProgress>) -> ParticipantGenericMessage {
+    match progress.unwrap() {
+      HandshakeProgress::Send(m) | HandshakeProgress::Completed(Some(m)) => m,
+      _ => panic!("Expected a message"),
+    }
+  }
+
+  #[test]
+  fn credentials() {
+    let identity = IdentityCredentials::from_pem(IDENTITY_CA, PARTICIPANT_A, PARTICIPANT_A_KEY);
+    assert_eq!(
+      identity.unwrap().subject_name(),
+      "C=FI, O=RustDDS Test, CN=Participant A"
+    );
+    // Key of another participant
+    assert!(IdentityCredentials::from_pem(IDENTITY_CA, PARTICIPANT_A, PARTICIPANT_B_KEY).is_err());
+    // Not issued by the CA
+    assert!(
+      IdentityCredentials::from_pem(PARTICIPANT_B, PARTICIPANT_A, PARTICIPANT_A_KEY).is_err()
+    );
+  }
+
+  #[test]
+  fn adjusted_guid() {
+    let identity =
+      IdentityCredentials::from_pem(IDENTITY_CA, PARTICIPANT_A, PARTICIPANT_A_KEY).unwrap();
+    let candidate = GUID::new_participant_guid();
+    let prefix = identity.adjusted_guid_prefix(candidate);
+    assert_eq!(prefix.bytes[0] & 0x80, 0x80);
+    assert_eq!(
+      prefix.bytes[..6],
+      subject_guid_bytes(&identity.inner.certificate.subject)
+    );
+    assert_eq!(prefix, identity.adjusted_guid_prefix(candidate));
+    assert_ne!(
+      prefix,
+      identity.adjusted_guid_prefix(GUID::new_participant_guid())
+    );
+  }
+
+  #[test]
+  fn handshake() {
+    let (mut initiator, mut responder) = participants();
+    let initiator_guid = initiator.participant_guid;
+    let responder_guid = responder.participant_guid;
+    initiator.set_permissions_document(b"initiator permissions".to_vec());
+
+    assert!(responder
+      .begin(initiator_guid, Some(&initiator.identity.identity_token()))
+      .unwrap()
+      .is_none());
+    let request = initiator
+      .begin(responder_guid, Some(&responder.identity.identity_token()))
+      .unwrap()
+      .unwrap();
+    assert_eq!(initiator.resend(), vec![request.clone()]);
+
+    let reply = sent(responder.process(&request));
+    assert_eq!(reply.related_message_identity, request.message_identity);
+    // A resent request gets the same reply.
+    assert_eq!(sent(responder.process(&request)), reply);
+
+    let final_message = match initiator.process(&reply).unwrap() {
+      HandshakeProgress::Completed(Some(m)) => m,
+      _ => panic!("Initiator did not complete"),
+    };
+    assert!(initiator.resend().is_empty());
+    // A resent reply gets the same final.
+    assert_eq!(sent(initiator.process(&reply)), final_message);
+
+    assert!(matches!(
+      responder.process(&final_message),
+      Ok(HandshakeProgress::Completed(None))
+    ));
+    assert!(responder.resend().is_empty());
+
+    let secret = |a: &Authentication, remote: GUID| match &a.handshakes[&remote.prefix].state {
+      HandshakeState::Completed { shared_secret, .. } => shared_secret.secret.clone(),
+      _ => panic!("Not completed"),
+    };
+    assert_eq!(
+      secret(&initiator, responder_guid),
+      secret(&responder, initiator_guid)
+    );
+
+    let remote = responder.remote_credentials(initiator_guid.prefix).unwrap();
+    assert_eq!(remote.subject_name, initiator.identity.subject_name());
+    assert_eq!(remote.permissions, b"initiator permissions");
+    let remote = initiator.remote_credentials(responder_guid.prefix).unwrap();
+    assert_eq!(remote.subject_name, responder.identity.subject_name());
+    assert!(remote.permissions.is_empty());
+  }
+
+  #[test]
+  fn handshake_rejects_tampering() {
+    let (mut initiator, mut responder) = participants();
+    responder
+      .begin(
+        initiator.participant_guid,
+        Some(&initiator.identity.identity_token()),
+      )
+      .unwrap();
+    let request = initiator
+      .begin(
+        responder.participant_guid,
+        Some(&responder.identity.identity_token()),
+      )
+      .unwrap()
+      .unwrap();
+    let mut reply = sent(responder.process(&request));
+    for property in &mut reply.message_data[0].binary_properties {
+      if property.name == "challenge2" {
+        property.value[0] ^= 1;
+      }
+    }
+    assert!(initiator.process(&reply).is_err());
+    // Still waiting for a valid reply
+    assert_eq!(initiator.resend(), vec![request]);
+  }
+
+  #[test]
+  fn handshake_rejects_wrong_guid() {
+    let (mut initiator, mut responder) = participants();
+    // The GUID prefix does not match the certificate.
+    let mut forged = initiator.participant_guid;
+    forged.prefix.bytes[5] ^= 1;
+    initiator.participant_guid = forged;
+    responder
+      .begin(forged, Some(&initiator.identity.identity_token()))
+      .unwrap();
+    let request = initiator
+      .begin(
+        responder.participant_guid,
+        Some(&responder.identity.identity_token()),
+      )
+      .unwrap()
+      .unwrap();
+    assert!(responder.process(&request).is_err());
+  }
+
+  #[test]
+  fn begin_requires_identity_token() {
+    let (mut initiator, responder) = participants();
+    assert!(initiator.begin(responder.participant_guid, None).is_err());
+    let other = DataHolder::new("DDS:Auth:Other");
+    assert!(initiator
+      .begin(responder.participant_guid, Some(&other))
+      .is_err());
+  }
+}
@@ -0,0 +1,472 @@
+use std::{
+  fmt::Write,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use ring::signature::{self, UnparsedPublicKey};
+
+use crate::security::{SecurityError, SecurityResult};
+
+// Just enough of PEM, DER and X.509 (RFC 5280) for DDS Security identities:
+// reading a certificate, checking that it is issued by a CA, and getting its
+// subject and public key. Only ECDSA P-256 and RSA keys with SHA-256 are
+// supported.
+
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_SHA256_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+
+pub(crate) const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+pub(crate) const TAG_OID: u8 = 0x06;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub(crate) const TAG_SEQUENCE: u8 = 0x30;
+pub(crate) const TAG_SET: u8 = 0x31;
+const TAG_VERSION: u8 = 0xa0; // [0] EXPLICIT
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeyAlgorithm {
+  EcdsaP256,
+  Rsa,
+}
+
+impl KeyAlgorithm {
+  // Names used in the IdentityToken
+  pub fn token_name(self) -> &'static str {
+    match self {
+      Self::EcdsaP256 => "EC-prime256v1",
+      Self::Rsa => "RSA-2048",
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Certificate {
+  // As it was given, since the handshake sends the certificate in PEM form
+  pub pem: String,
+  tbs: Vec<u8>,
+  signature_algorithm: Vec<u8>,
+  signature: Vec<u8>,
+  issuer: Vec<u8>,
+  // DER encoding of the subject Name
+  pub subject: Vec<u8>,
+  not_before: i64,
+  not_after: i64,
+  pub key_algorithm: KeyAlgorithm,
+  // subjectPublicKey contents: an uncompressed EC point or an RSAPublicKey
+  pub public_key: Vec<u8>,
+}
+
+impl Certificate {
+  pub fn from_pem(pem: &str) -> SecurityResult<Self> {
+    let der = pem_decode(pem, "CERTIFICATE")?;
+    let mut cert = Self::from_der(&der)?;
+    cert.pem = pem.to_string();
+    Ok(cert)
+  }
+
+  fn from_der(der: &[u8]) -> SecurityResult<Self> {
+    let mut outer = Der::new(der).read(TAG_SEQUENCE)?;
+    let tbs = outer.read_raw(TAG_SEQUENCE)?;
+    let signature_algorithm = read_algorithm(&mut outer.read(TAG_SEQUENCE)?)?;
+    let signature = read_bit_string(&mut outer)?;
+
+    let mut t = Der::new(tbs).read(TAG_SEQUENCE)?;
+    if t.peek_tag() == Some(TAG_VERSION) {
+      t.read(TAG_VERSION)?;
+    }
+    t.read(TAG_INTEGER)?; // serial number
+    t.read(TAG_SEQUENCE)?; // signature algorithm again
+    let issuer = t.read_raw(TAG_SEQUENCE)?.to_vec();
+    let mut validity = t.read(TAG_SEQUENCE)?;
+    let not_before = read_time(&mut validity)?;
+    let not_after = read_time(&mut validity)?;
+    let subject = t.read_raw(TAG_SEQUENCE)?.to_vec();
+    let mut spki = t.read(TAG_SEQUENCE)?;
+    let mut key_algorithm_id = spki.read(TAG_SEQUENCE)?;
+    let key_algorithm = match key_algorithm_id.read(TAG_OID)?.input {
+      OID_EC_PUBLIC_KEY if key_algorithm_id.read(TAG_OID)?.input == OID_PRIME256V1 => {
+        KeyAlgorithm::EcdsaP256
+      }
+      OID_RSA_ENCRYPTION => KeyAlgorithm::Rsa,
+      _ => return Err(SecurityError::new("Unsupported public key algorithm")),
+    };
+    let public_key = read_bit_string(&mut spki)?;
+
+    Ok(Self {
+      pem: String::new(),
+      tbs: tbs.to_vec(),
+      signature_algorithm,
+      signature,
+      issuer,
+      subject,
+      not_before,
+      not_after,
+      key_algorithm,
+      public_key,
+    })
+  }
+
+  // Check that the certificate is signed by the CA, and that both are valid
+  // now. The CA must have issued the certificate directly: there are no
+  // intermediate CAs.
+  pub fn verify(&self, ca: &Certificate, now: SystemTime) -> SecurityResult<()> {
+    if self.issuer != ca.subject {
+      return Err(SecurityError::new(format!(
+        "Certificate {} is not issued by {}",
+        self.subject_name(),
+        ca.subject_name()
+      )));
+    }
+    for cert in [self, ca] {
+      if !cert.is_valid_at(now) {
+        return Err(SecurityError::new(format!(
+          "Certificate {} is not valid now",
+          cert.subject_name()
+        )));
+      }
+    }
+    let algorithm: &'static dyn signature::VerificationAlgorithm =
+      match (self.signature_algorithm.as_slice(), ca.key_algorithm) {
+        (OID_ECDSA_WITH_SHA256, KeyAlgorithm::EcdsaP256) => &signature::ECDSA_P256_SHA256_ASN1,
+        (OID_SHA256_WITH_RSA, KeyAlgorithm::Rsa) => &signature::RSA_PKCS1_2048_8192_SHA256,
+        _ => {
+          return Err(SecurityError::new(
+            "Unsupported certificate signature algorithm",
+          ))
+        }
+      };
+    UnparsedPublicKey::new(algorithm, &ca.public_key)
+      .verify(&self.tbs, &self.signature)
+      .map_err(|_| {
+        SecurityError::new(format!(
+          "Signature of certificate {} does not verify",
+          self.subject_name()
+        ))
+      })
+  }
+
+  pub fn is_valid_at(&self, time: SystemTime) -> bool {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+      Ok(d) => d.as_secs() as i64,
+      Err(_) => return false,
+    };
+    (self.not_before..=self.not_after).contains(&secs)
+  }
+
+  // Verify a signature made with the private key of this certificate.
+  // `dsign_algo` is the algorithm name of the handshake, "ECDSA-SHA256" (ASN.1
+  // encoded signature) or "RSASSA-PSS-SHA256".
+  pub fn verify_signature(
+    &self,
+    dsign_algo: &str,
+    message: &[u8],
+    signature: &[u8],
+  ) -> SecurityResult<()> {
+    let algorithm: &'static dyn signature::VerificationAlgorithm =
+      match (self.key_algorithm, dsign_algo) {
+        (KeyAlgorithm::EcdsaP256, "ECDSA-SHA256") => &signature::ECDSA_P256_SHA256_ASN1,
+        (KeyAlgorithm::Rsa, "RSASSA-PSS-SHA256") => &signature::RSA_PSS_2048_8192_SHA256,
+        _ => {
+          return Err(SecurityError::new(format!(
+            "Signature algorithm {} does not match the certificate",
+            dsign_algo
+          )))
+        }
+      };
+    UnparsedPublicKey::new(algorithm, &self.public_key)
+      .verify(message, signature)
+      .map_err(|_| SecurityError::new("Signature does not verify"))
+  }
+
+  // Verify a SHA-256 signature made with the private key of this certificate,
+  // such as a CA signature of a signed document. The algorithms are the same as
+  // in certificates: ECDSA, or RSA with PKCS#1 v1.5 padding.
+  pub fn verify_sha256_signature(&self, message: &[u8], signature: &[u8]) -> SecurityResult<()> {
+    let algorithm: &'static dyn signature::VerificationAlgorithm = match self.key_algorithm {
+      KeyAlgorithm::EcdsaP256 => &signature::ECDSA_P256_SHA256_ASN1,
+      KeyAlgorithm::Rsa => &signature::RSA_PKCS1_2048_8192_SHA256,
+    };
+    UnparsedPublicKey::new(algorithm, &self.public_key)
+      .verify(message, signature)
+      .map_err(|_| SecurityError::new("Signature does not verify"))
+  }
+
+  // The subject as text, e.g. "C=FI, O=Example, CN=Participant 1"
+  pub fn subject_name(&self) -> String {
+    name_to_string(&self.subject).unwrap_or_else(|_| "(invalid name)".to_string())
+  }
+
+  #[cfg(test)]
+  pub fn issuer_name(&self) -> String {
+    name_to_string(&self.issuer).unwrap_or_else(|_| "(invalid name)".to_string())
+  }
+}
+
+// Contents of the PEM block with the given label, e.g. "CERTIFICATE"
+pub(crate) fn pem_decode(pem: &str, label: &str) -> SecurityResult<Vec<u8>> {
+  let begin = format!("-----BEGIN {}-----", label);
+  let end = format!("-----END {}-----", label);
+  let start = pem
+    .find(&begin)
+    .ok_or_else(|| SecurityError::new(format!("No {} in PEM", label)))?
+    + begin.len();
+  let stop = pem[start..]
+    .find(&end)
+    .ok_or_else(|| SecurityError::new(format!("Unterminated {} in PEM", label)))?
+    + start;
+  base64_decode(&pem[start..stop])
+}
+
+pub(crate) fn base64_decode(text: &str) -> SecurityResult<Vec<u8>> {
+  let mut out = Vec::with_capacity(text.len() * 3 / 4);
+  let mut acc: u32 = 0;
+  let mut bits = 0;
+  for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+    let v = match c {
+      b'A'..=b'Z' => c - b'A',
+      b'a'..=b'z' => c - b'a' + 26,
+      b'0'..=b'9' => c - b'0' + 52,
+      b'+' => 62,
+      b'/' => 63,
+      b'=' => break,
+      _ => return Err(SecurityError::new("Invalid base64")),
+    };
+    acc = (acc << 6) | u32::from(v);
+    bits += 6;
+    if bits >= 8 {
+      bits -= 8;
+      out.push((acc >> bits) as u8);
+    }
+  }
+  Ok(out)
+}
+
+// DER reader over the contents of one constructed value
+pub(crate) struct Der<'a> {
+  pub input: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+  pub fn new(input: &'a [u8]) -> Self {
+    Self { input }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.input.is_empty()
+  }
+
+  pub fn peek_tag(&self) -> Option<u8> {
+    self.input.first().copied()
+  }
+
+  // Next value with its tag and length, and its contents
+  fn read_tlv(&mut self, tag: u8) -> SecurityResult<(&'a [u8], &'a [u8])> {
+    let err = || SecurityError::new("Malformed DER");
+    if self.peek_tag() != Some(tag) {
+      return Err(SecurityError::new(format!(
+        "Expected DER tag {:#04x}, found {:x?}",
+        tag,
+        self.peek_tag()
+      )));
+    }
+    let first = *self.input.get(1).ok_or_else(err)?;
+    let (length, header) = if first < 0x80 {
+      (usize::from(first), 2)
+    } else {
+      let count = usize::from(first & 0x7f);
+      if count == 0 || count > 4 {
+        return Err(err());
+      }
+      let bytes = self.input.get(2..2 + count).ok_or_else(err)?;
+      let length = bytes
+        .iter()
+        .fold(0usize, |acc, b| (acc << 8) | usize::from(*b));
+      (length, 2 + count)
+    };
+    let end = header.checked_add(length).ok_or_else(err)?;
+    let whole = self.input.get(..end).ok_or_else(err)?;
+    self.input = &self.input[end..];
+    Ok((whole, &whole[header..]))
+  }
+
+  // Contents of the next value
+  pub fn read(&mut self, tag: u8) -> SecurityResult<Der<'a>> {
+    self.read_tlv(tag).map(|(_, contents)| Der::new(contents))
+  }
+
+  // The next value including its tag and length
+  pub fn read_raw(&mut self, tag: u8) -> SecurityResult<&'a [u8]> {
+    self.read_tlv(tag).map(|(whole, _)| whole)
+  }
+}
+
+// OID of an AlgorithmIdentifier. Parameters are ignored.
+fn read_algorithm(algorithm: &mut Der) -> SecurityResult<Vec<u8>> {
+  Ok(algorithm.read(TAG_OID)?.input.to_vec())
+}
+
+fn read_bit_string(der: &mut Der) -> SecurityResult<Vec<u8>> {
+  match der.read(TAG_BIT_STRING)?.input.split_first() {
+    Some((0, bits)) => Ok(bits.to_vec()),
+    _ => Err(SecurityError::new("Unsupported BIT STRING")),
+  }
+}
+
+// UTCTime or GeneralizedTime, as seconds since the Unix epoch
+fn read_time(der: &mut Der) -> SecurityResult<i64> {
+  let (text, year) = match der.peek_tag() {
+    Some(TAG_UTC_TIME) => {
+      let text = der.read(TAG_UTC_TIME)?.input;
+      let yy = digits(text.get(0..2))?;
+      // RFC 5280 Section 4.1.2.5.1
+      (&text[2..], if yy >= 50 { 1900 + yy } else { 2000 + yy })
+    }
+    Some(TAG_GENERALIZED_TIME) => {
+      let text = der.read(TAG_GENERALIZED_TIME)?.input;
+      (text.get(4..).unwrap_or_default(), digits(text.get(0..4))?)
+    }
+    _ => return Err(SecurityError::new("Expected a time in certificate")),
+  };
+  // MMDDHHMMSSZ
+  if text.len() != 11 || text[10] != b'Z' {
+    return Err(SecurityError::new("Unsupported time format in certificate"));
+  }
+  let month = digits(text.get(0..2))?;
+  let day = digits(text.get(2..4))?;
+  let seconds =
+    digits(text.get(4..6))? * 3600 + digits(text.get(6..8))? * 60 + digits(text.get(8..10))?;
+  Ok(days_from_civil(year, month, day) * 86400 + seconds)
+}
+
+fn digits(text: Option<&[u8]>) -> SecurityResult<i64> {
+  let text = text.ok_or_else(|| SecurityError::new("Truncated time in certificate"))?;
+  text.iter().try_fold(0, |acc, c| {
+    if c.is_ascii_digit() {
+      Ok(acc * 10 + i64::from(c - b'0'))
+    } else {
+      Err(SecurityError::new("Invalid time in certificate"))
+    }
+  })
+}
+
+// Days since 1970-01-01 in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+  let y = if month <= 2 { year - 1 } else { year };
+  let era = y.div_euclid(400);
+  let yoe = y - era * 400;
+  let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+  let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+  era * 146_097 + doe - 719_468
+}
+
+// Distinguished name as text, attributes in the order they are encoded
+fn name_to_string(name: &[u8]) -> SecurityResult<String> {
+  let mut rdns = Der::new(name).read(TAG_SEQUENCE)?;
+  let mut parts = Vec::new();
+  while !rdns.is_empty() {
+    let mut rdn = rdns.read(TAG_SET)?;
+    while !rdn.is_empty() {
+      let mut attribute = rdn.read(TAG_SEQUENCE)?;
+      let oid = attribute.read(TAG_OID)?.input;
+      let value = match attribute.peek_tag() {
+        Some(tag) => attribute.read(tag)?.input,
+        None => return Err(SecurityError::new("Malformed name")),
+      };
+      let value = String::from_utf8_lossy(value);
+      let part = match attribute_name(oid) {
+        Some(short) => format!("{}={}", short, value),
+        None => format!("{}={}", oid_to_string(oid), value),
+      };
+      parts.push(part);
+    }
+  }
+  Ok(parts.join(", "))
+}
+
+fn attribute_name(oid: &[u8]) -> Option<&'static str> {
+  match oid {
+    [0x55, 0x04, 0x03] => Some("CN"),
+    [0x55, 0x04, 0x06] => Some("C"),
+    [0x55, 0x04, 0x07] => Some("L"),
+    [0x55, 0x04, 0x08] => Some("ST"),
+    [0x55, 0x04, 0x0a] => Some("O"),
+    [0x55, 0x04, 0x0b] => Some("OU"),
+    [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01] => Some("emailAddress"),
+    _ => None,
+  }
+}
+
+fn oid_to_string(oid: &[u8]) -> String {
+  let mut text = String::new();
+  let mut value: u64 = 0;
+  for byte in oid {
+    value = (value << 7) | u64::from(byte & 0x7f);
+    if byte & 0x80 == 0 {
+      if text.is_empty() {
+        let first = (value / 40).min(2);
+        write!(text, "{}.{}", first, value - first * 40).unwrap();
+      } else {
+        write!(text, ".{}", value).unwrap();
+      }
+      value = 0;
+    }
+  }
+  text
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use std::time::Duration;
+
+  use super::*;
+
+  pub(crate) const IDENTITY_CA: &str = include_str!("../test/security/identity_ca.pem");
+  pub(crate) const PARTICIPANT_A: &str = include_str!("../test/security/participant_a.pem");
+  pub(crate) const PARTICIPANT_B: &str = include_str!("../test/security/participant_b.pem");
+
+  #[test]
+  fn certificate_parse_and_verify() {
+    let ca = Certificate::from_pem(IDENTITY_CA).unwrap();
+    let a = Certificate::from_pem(PARTICIPANT_A).unwrap();
+    let b = Certificate::from_pem(PARTICIPANT_B).unwrap();
+    assert_eq!(a.subject_name(), "C=FI, O=RustDDS Test, CN=Participant A");
+    assert_eq!(a.issuer_name(), ca.subject_name());
+    assert_eq!(a.key_algorithm, KeyAlgorithm::EcdsaP256);
+    assert_eq!(a.public_key.len(), 65);
+
+    let now = SystemTime::now();
+    a.verify(&ca, now).unwrap();
+    b.verify(&ca, now).unwrap();
+    // The CA is self-signed.
+    ca.verify(&ca, now).unwrap();
+    assert!(a.verify(&b, now).is_err());
+    assert!(a.verify(&ca, UNIX_EPOCH + Duration::from_secs(1)).is_err());
+  }
+
+  #[test]
+  fn certificate_rejects_garbage() {
+    assert!(Certificate::from_pem("no PEM here").is_err());
+    let lines: Vec<&str> = PARTICIPANT_A.lines().collect();
+    let truncated = [&lines[..4], &lines[lines.len() - 1..]].concat().join("\n");
+    assert!(Certificate::from_pem(&truncated).is_err());
+  }
+
+  #[test]
+  fn certificate_times() {
+    assert_eq!(days_from_civil(1970, 1, 1), 0);
+    assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+    let mut der = Der::new(b"\x17\x0d991231235959Z");
+    assert_eq!(read_time(&mut der).unwrap(), 946_684_799);
+    let mut der = Der::new(b"\x18\x0f21260922090353Z");
+    assert_eq!(read_time(&mut der).unwrap(), 4_945_741_433);
+  }
+
+  #[test]
+  fn certificate_oid_names() {
+    assert_eq!(oid_to_string(OID_PRIME256V1), "1.2.840.10045.3.1.7");
+  }
+}
@@ -0,0 +1,1655 @@
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  convert::TryFrom,
+  fmt,
+  sync::{Arc, Mutex, MutexGuard},
+  time::{Duration, Instant},
+};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use log::debug;
+use ring::{
+  aead::{self, Aad, LessSafeKey, Nonce, UnboundKey},
+  digest::{digest, SHA256},
+  hmac,
+  rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  dds::values::result::{Error, Result},
+  messages::submessages::submessage_kind::SubmessageKind,
+  security::{
+    access_control::{ProtectionKind, TopicProtection},
+    authentication::SharedSecret,
+    plugins::{AccessControlHandle, CryptographicPlugin},
+    types::DataHolder,
+    SecurityError, SecurityResult,
+  },
+  serialization::{
+    cdr_deserializer::deserialize_from_big_endian, cdr_serializer::to_bytes,
+    RTPS_MESSAGE_HEADER_SIZE,
+  },
+  structure::guid::{EntityId, GuidPrefix, GUID},
+};
+
+// Builtin cryptographic plugin "DDS:Crypto:AES-GCM-GMAC", DDS Security spec
+// v1.1 Section "9.5 Builtin Crypto: DDS:Crypto:AES-GCM-GMAC".
+//
+// The local participant and each of its protected endpoints have randomly
+// generated key material. It is sent to every authenticated remote participant
+// in crypto tokens over the ParticipantVolatileMessageSecure topic. That topic
+// is in turn protected with key material that both participants derive from
+// the shared secret of the authentication handshake.
+//
+// The governance tells what is protected: whole RTPS messages, the submessages
+// of the endpoints of a topic, or their serialized payloads. Protected data is
+// either encrypted with AES-GCM, or signed with AES-GMAC.
+//
+// Master keys are replaced as the KeyRotationPolicy says, and the new ones are
+// sent in crypto tokens like the first ones.
+//
+// Receiver specific keys, and thus origin authentication, are not supported.
+
+const CLASS_ID: &str = "DDS:Crypto:AES_GCM_GMAC";
+const KEY_MATERIAL_PROPERTY: &str = "dds.cryp.keymat";
+
+// CryptoTransformKind, Section "9.5.2.1.1 KeyMaterial_AES_GCM_GMAC structure"
+const AES128_GMAC: [u8; 4] = [0, 0, 0, 1];
+const AES128_GCM: [u8; 4] = [0, 0, 0, 2];
+const AES256_GMAC: [u8; 4] = [0, 0, 0, 3];
+const AES256_GCM: [u8; 4] = [0, 0, 0, 4];
+
+// The key id of the key material derived from the shared secret. Generated key
+// material never has this id.
+const KX_KEY_ID: [u8; 4] = [0; 4];
+
+const CRYPTO_HEADER_LENGTH: usize = 20;
+const MAC_LENGTH: usize = 16;
+// Common MAC, and the number of receiver specific MACs, which is zero
+const CRYPTO_FOOTER_LENGTH: usize = MAC_LENGTH + 4;
+
+// Session keys are changed after this many messages, so that long-lived
+// sessions do not use one key for too much data.
+const MESSAGES_PER_SESSION: u64 = 1 << 20;
+
+// How much protection may grow an RTPS message: SRTPS_PREFIX, SEC_BODY and
+// SRTPS_POSTFIX around the message, with an INFO_SRC in front of the
+// submessages, SEC_PREFIX, SEC_BODY and SEC_POSTFIX around a submessage, and
+// the header and footer of a payload. Writers leave this much room when
+// they decide whether to fragment.
+const PROTECTION_OVERHEAD: usize = 192;
+
+// Security infrastructure endpoints, whose messages are never protected as a
+// whole
+const RTPS_UNPROTECTED_ENDPOINTS: [EntityId; 6] = [
+  EntityId::SPDP_BUILTIN_PARTICIPANT_WRITER,
+  EntityId::SPDP_BUILTIN_PARTICIPANT_READER,
+  EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_WRITER,
+  EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_READER,
+  EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER,
+  EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_READER,
+];
+
+/// When the builtin cryptographic plugin replaces the master keys of the
+/// participant and of its protected DataWriters and DataReaders, see
+/// [`DomainParticipantBuilder::key_rotation`](crate::DomainParticipantBuilder::key_rotation).
+///
+/// New keys are sent to the authenticated participants in crypto tokens over
+/// the ParticipantVolatileMessageSecure topic. The old keys stay in use for
+/// the overlap window, so that the new keys reach the other participants
+/// before anything is protected with them. Receivers accept the old keys for
+/// the overlap window after the new keys have come into use, so that
+/// reordered and retransmitted messages are not lost. Whether keys are due
+/// for rotation is checked every couple of seconds.
+///
+/// By default master keys are never replaced. The session keys derived from
+/// them change after every 2^20 messages in any case.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rustdds::KeyRotationPolicy;
+///
+/// let policy = KeyRotationPolicy::new()
+///   .period(Duration::from_secs(3600))
+///   .messages(10_000_000)
+///   .overlap(Duration::from_secs(30));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyRotationPolicy {
+  period: Option<Duration>,
+  messages: Option<u64>,
+  overlap: Duration,
+}
+
+impl Default for KeyRotationPolicy {
+  fn default() -> Self {
+    Self {
+      period: None,
+      messages: None,
+      overlap: Self::DEFAULT_OVERLAP,
+    }
+  }
+}
+
+impl KeyRotationPolicy {
+  /// Overlap window, unless set with [`overlap`](Self::overlap)
+  pub const DEFAULT_OVERLAP: Duration = Duration::from_secs(10);
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Replace keys that have been in use this long. Must be longer than the
+  /// overlap window.
+  #[must_use]
+  pub fn period(mut self, period: Duration) -> Self {
+    self.period = Some(period);
+    self
+  }
+
+  /// Replace keys that have protected this many messages, submessages or
+  /// payloads.
+  #[must_use]
+  pub fn messages(mut self, messages: u64) -> Self {
+    self.messages = Some(messages);
+    self
+  }
+
+  /// How long old and new keys are both valid after a rotation. This applies
+  /// also to the keys that remote participants rotate.
+  #[must_use]
+  pub fn overlap(mut self, overlap: Duration) -> Self {
+    self.overlap = overlap;
+    self
+  }
+
+  pub(crate) fn validate(&self) -> Result<()> {
+    if self.messages == Some(0) {
+      return Error::bad_parameter("Key rotation message count must not be zero");
+    }
+    match self.period {
+      Some(period) if period <= self.overlap => Error::bad_parameter(format!(
+        "Key rotation period {:?} must be longer than the overlap window {:?}",
+        period, self.overlap
+      )),
+      _ => Ok(()),
+    }
+  }
+
+  fn is_due(&self, sender: &Sender) -> bool {
+    self
+      .messages
+      .map_or(false, |messages| sender.encoded >= messages)
+      || self
+        .period
+        .map_or(false, |period| sender.created.elapsed() >= period)
+  }
+}
+
+fn is_volatile_endpoint(entity_id: EntityId) -> bool {
+  entity_id == EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER
+    || entity_id == EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_READER
+}
+
+// KeyMaterial_AES_GCM_GMAC. This is sent in crypto tokens as CDR. Receiver
+// specific keys are not used, so those fields stay empty.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct KeyMaterial {
+  transformation_kind: [u8; 4],
+  master_salt: Vec<u8>,
+  sender_key_id: [u8; 4],
+  master_sender_key: Vec<u8>,
+  receiver_specific_key_id: [u8; 4],
+  master_receiver_specific_key: Vec<u8>,
+}
+
+impl KeyMaterial {
+  fn generate(rng: &SystemRandom) -> SecurityResult<Self> {
+    let mut sender_key_id = KX_KEY_ID;
+    while sender_key_id == KX_KEY_ID {
+      fill_random(rng, &mut sender_key_id)?;
+    }
+    let mut master_salt = vec![0; 32];
+    fill_random(rng, &mut master_salt)?;
+    let mut master_sender_key = vec![0; 32];
+    fill_random(rng, &mut master_sender_key)?;
+    Ok(Self {
+      transformation_kind: AES256_GCM,
+      master_salt,
+      sender_key_id,
+      master_sender_key,
+      receiver_specific_key_id: [0; 4],
+      master_receiver_specific_key: Vec::new(),
+    })
+  }
+
+  // KxKeyMaterial, Section "9.5.2.1.2 Key exchange key material". Both
+  // participants of a handshake derive the same key material.
+  fn key_exchange(shared_secret: &SharedSecret) -> Self {
+    let derive = |first: &[u8], cookie: &[u8], second: &[u8]| {
+      let hmac_key = digest(&SHA256, &[first, cookie, second].concat());
+      let hmac_key = hmac::Key::new(hmac::HMAC_SHA256, hmac_key.as_ref());
+      hmac::sign(&hmac_key, &shared_secret.secret)
+        .as_ref()
+        .to_vec()
+    };
+    let challenge1 = shared_secret.challenge1.as_slice();
+    let challenge2 = shared_secret.challenge2.as_slice();
+    Self {
+      transformation_kind: AES256_GCM,
+      master_salt: derive(challenge1, b"keyexchange salt", challenge2),
+      sender_key_id: KX_KEY_ID,
+      master_sender_key: derive(challenge2, b"keyexchange key", challenge1),
+      receiver_specific_key_id: [0; 4],
+      master_receiver_specific_key: Vec::new(),
+    }
+  }
+
+  fn to_token(&self) -> SecurityResult<DataHolder> {
+    let bytes = to_bytes::<Self, BigEndian>(self)
+      .map_err(|e| SecurityError::new(format!("Cannot serialize key material: {}", e)))?;
+    Ok(DataHolder::new(CLASS_ID).with_binary_property(KEY_MATERIAL_PROPERTY, bytes))
+  }
+
+  // The first token of the right class
+  fn from_tokens(tokens: &[DataHolder]) -> SecurityResult<Self> {
+    let bytes = tokens
+      .iter()
+      .find(|token| token.class_id == CLASS_ID)
+      .and_then(|token| token.binary_property(KEY_MATERIAL_PROPERTY))
+      .ok_or_else(|| SecurityError::new("No key material in crypto tokens"))?;
+    let key_material = deserialize_from_big_endian::<Self>(bytes)
+      .map_err(|e| SecurityError::new(format!("Invalid key material: {}", e)))?;
+    match key_length(key_material.transformation_kind) {
+      Some(length)
+        if key_material.master_sender_key.len() == length
+          && !key_material.master_salt.is_empty() =>
+      {
+        Ok(key_material)
+      }
+      _ => Err(SecurityError::new("Unsupported key material")),
+    }
+  }
+
+  fn key_length(&self) -> usize {
+    // Checked when the key material was created
+    key_length(self.transformation_kind).unwrap_or(32)
+  }
+
+  // SessionKey, Section "9.5.3.3.1 Key computation"
+  fn session_key(&self, session_id: [u8; 4]) -> SecurityResult<LessSafeKey> {
+    let hmac_key = hmac::Key::new(hmac::HMAC_SHA256, &self.master_sender_key);
+    let mut context = hmac::Context::with_key(&hmac_key);
+    context.update(b"SessionKey");
+    context.update(&self.master_salt);
+    context.update(&session_id);
+    let key = context.sign();
+    let length = self.key_length();
+    let algorithm = if length == 16 {
+      &aead::AES_128_GCM
+    } else {
+      &aead::AES_256_GCM
+    };
+    UnboundKey::new(algorithm, &key.as_ref()[..length])
+      .map(LessSafeKey::new)
+      .map_err(|_| SecurityError::new("Cannot create session key"))
+  }
+}
+
+// Do not show the keys in logs.
+impl fmt::Debug for KeyMaterial {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("KeyMaterial")
+      .field("transformation_kind", &self.transformation_kind)
+      .field("sender_key_id", &self.sender_key_id)
+      .finish()
+  }
+}
+
+fn key_length(transformation_kind: [u8; 4]) -> Option<usize> {
+  match transformation_kind {
+    AES128_GMAC | AES128_GCM => Some(16),
+    AES256_GMAC | AES256_GCM => Some(32),
+    _ => None,
+  }
+}
+
+fn fill_random(rng: &SystemRandom, bytes: &mut [u8]) -> SecurityResult<()> {
+  rng
+    .fill(bytes)
+    .map_err(|_| SecurityError::new("Cannot generate random key material"))
+}
+
+// CryptoHeader, Section "9.5.2.3 CryptoHeader"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CryptoHeader {
+  transformation_kind: [u8; 4],
+  key_id: [u8; 4],
+  session_id: [u8; 4],
+  iv_suffix: [u8; 8],
+}
+
+impl CryptoHeader {
+  fn read(bytes: &[u8]) -> SecurityResult<Self> {
+    if bytes.len() < CRYPTO_HEADER_LENGTH {
+      return Err(SecurityError::new("Truncated CryptoHeader"));
+    }
+    let mut header = Self {
+      transformation_kind: [0; 4],
+      key_id: [0; 4],
+      session_id: [0; 4],
+      iv_suffix: [0; 8],
+    };
+    header.transformation_kind.copy_from_slice(&bytes[0..4]);
+    header.key_id.copy_from_slice(&bytes[4..8]);
+    header.session_id.copy_from_slice(&bytes[8..12]);
+    header.iv_suffix.copy_from_slice(&bytes[12..20]);
+    Ok(header)
+  }
+
+  fn to_bytes(self) -> Vec<u8> {
+    [
+      &self.transformation_kind[..],
+      &self.key_id,
+      &self.session_id,
+      &self.iv_suffix,
+    ]
+    .concat()
+  }
+
+  // The initialization vector is the session id followed by the suffix.
+  fn nonce(&self) -> Nonce {
+    let mut iv = [0; aead::NONCE_LEN];
+    iv[..4].copy_from_slice(&self.session_id);
+    iv[4..].copy_from_slice(&self.iv_suffix);
+    Nonce::assume_unique_for_key(iv)
+  }
+
+  fn encrypts(&self) -> bool {
+    self.transformation_kind == AES128_GCM || self.transformation_kind == AES256_GCM
+  }
+}
+
+// Encrypts or signs with the key material of a local participant or endpoint.
+//
+// Both participants of a handshake send with the same key exchange keys, so
+// the session id and IV suffix start from random values, which makes it
+// unlikely that they ever use the same initialization vector.
+struct Sender {
+  key_material: KeyMaterial,
+  session_id: u32,
+  session_key: LessSafeKey,
+  iv_suffix: u64,
+  // Messages encoded in this session
+  messages: u64,
+  // For the rotation policy: when the key material was taken into use, and
+  // how many messages have been encoded with it
+  created: Instant,
+  encoded: u64,
+}
+
+impl Sender {
+  fn new(key_material: KeyMaterial, rng: &SystemRandom) -> SecurityResult<Self> {
+    let mut session_id = [0; 4];
+    fill_random(rng, &mut session_id)?;
+    let mut iv_suffix = [0; 8];
+    fill_random(rng, &mut iv_suffix)?;
+    Ok(Self {
+      session_key: key_material.session_key(session_id)?,
+      key_material,
+      session_id: u32::from_be_bytes(session_id),
+      iv_suffix: u64::from_be_bytes(iv_suffix),
+      messages: 0,
+      created: Instant::now(),
+      encoded: 0,
+    })
+  }
+
+  // Returns the CryptoHeader, the ciphertext if encrypting, and the MAC.
+  fn encode(
+    &mut self,
+    data: &[u8],
+    encrypt: bool,
+  ) -> SecurityResult<(CryptoHeader, Option<Vec<u8>>, [u8; MAC_LENGTH])> {
+    if self.messages >= MESSAGES_PER_SESSION {
+      self.session_id = self.session_id.wrapping_add(1);
+      self.session_key = self
+        .key_material
+        .session_key(self.session_id.to_be_bytes())?;
+      self.messages = 0;
+    }
+    let aes128 = self.key_material.key_length() == 16;
+    let header = CryptoHeader {
+      transformation_kind: match (aes128, encrypt) {
+        (true, true) => AES128_GCM,
+        (true, false) => AES128_GMAC,
+        (false, true) => AES256_GCM,
+        (false, false) => AES256_GMAC,
+      },
+      key_id: self.key_material.sender_key_id,
+      session_id: self.session_id.to_be_bytes(),
+      iv_suffix: self.iv_suffix.to_be_bytes(),
+    };
+    self.iv_suffix = self.iv_suffix.wrapping_add(1);
+    self.messages += 1;
+    self.encoded += 1;
+
+    // GMAC is GCM with nothing to encrypt and the data as additional
+    // authenticated data.
+    let (ciphertext, tag) = if encrypt {
+      let mut ciphertext = data.to_vec();
+      let tag =
+        self
+          .session_key
+          .seal_in_place_separate_tag(header.nonce(), Aad::empty(), &mut ciphertext);
+      (Some(ciphertext), tag)
+    } else {
+      let tag =
+        self
+          .session_key
+          .seal_in_place_separate_tag(header.nonce(), Aad::from(data), &mut []);
+      (None, tag)
+    };
+    let tag = tag.map_err(|_| SecurityError::new("Encoding failed"))?;
+    let mut mac = [0; MAC_LENGTH];
+    mac.copy_from_slice(tag.as_ref());
+    Ok((header, ciphertext, mac))
+  }
+
+  fn encode_payload(&mut self, payload: &[u8], encrypt: bool) -> SecurityResult<Vec<u8>> {
+    let (header, ciphertext, mac) = self.encode(payload, encrypt)?;
+    let mut encoded = header.to_bytes();
+    match ciphertext {
+      Some(ciphertext) => write_crypto_content(&mut encoded, &ciphertext),
+      None => encoded.extend_from_slice(payload),
+    }
+    encoded.extend_from_slice(&crypto_footer(mac));
+    Ok(encoded)
+  }
+
+  // SEC_PREFIX, SEC_BODY or the submessage itself, and SEC_POSTFIX
+  fn encode_submessage(
+    &mut self,
+    submessage: &[u8],
+    encrypt: bool,
+    output: &mut Vec<u8>,
+  ) -> SecurityResult<()> {
+    let (header, ciphertext, mac) = self.encode(submessage, encrypt)?;
+    write_submessage(output, SubmessageKind::SEC_PREFIX, &header.to_bytes())?;
+    match ciphertext {
+      Some(ciphertext) => write_sec_body(output, &ciphertext)?,
+      None => output.extend_from_slice(submessage),
+    }
+    write_submessage(output, SubmessageKind::SEC_POSTFIX, &crypto_footer(mac))
+  }
+}
+
+// The keys that a local participant or endpoint sends with. After a rotation
+// the new keys are distributed, but the old ones are used until the overlap
+// window has passed.
+struct SendingKeys {
+  sender: Sender,
+  // The new keys, and when they are taken into use
+  next: Option<(Sender, Instant)>,
+}
+
+impl SendingKeys {
+  fn new(key_material: KeyMaterial, rng: &SystemRandom) -> SecurityResult<Self> {
+    Ok(Self {
+      sender: Sender::new(key_material, rng)?,
+      next: None,
+    })
+  }
+
+  // The keys to send with now
+  fn sender(&mut self) -> &mut Sender {
+    if matches!(&self.next, Some((_, switch_time)) if *switch_time <= Instant::now()) {
+      if let Some((mut next, _)) = self.next.take() {
+        next.created = Instant::now();
+        self.sender = next;
+      }
+    }
+    &mut self.sender
+  }
+
+  // Generate new keys, if the current ones are due for rotation and a rotation
+  // is not in progress already. Returns the new key material.
+  fn rotate(
+    &mut self,
+    policy: &KeyRotationPolicy,
+    rng: &SystemRandom,
+  ) -> SecurityResult<Option<KeyMaterial>> {
+    if self.next.is_some() || !policy.is_due(&self.sender) {
+      return Ok(None);
+    }
+    let key_material = KeyMaterial::generate(rng)?;
+    let switch_time = Instant::now() + policy.overlap;
+    self.next = Some((Sender::new(key_material.clone(), rng)?, switch_time));
+    Ok(Some(key_material))
+  }
+
+  // Crypto token of the newest keys
+  fn to_token(&self) -> SecurityResult<DataHolder> {
+    match &self.next {
+      Some((next, _)) => next.key_material.to_token(),
+      None => self.sender.key_material.to_token(),
+    }
+  }
+}
+
+// Decrypts or verifies with the key material received from a remote
+// participant.
+struct Receiver {
+  key_material: KeyMaterial,
+  // The session key of the latest session
+  session: Option<([u8; 4], LessSafeKey)>,
+}
+
+impl Receiver {
+  fn new(key_material: KeyMaterial) -> Self {
+    Self {
+      key_material,
+      session: None,
+    }
+  }
+
+  fn key_id(&self) -> [u8; 4] {
+    self.key_material.sender_key_id
+  }
+
+  // `content` is the ciphertext, or the signed data.
+  fn decode(
+    &mut self,
+    header: &CryptoHeader,
+    content: &[u8],
+    mac: &[u8],
+  ) -> SecurityResult<Vec<u8>> {
+    if key_length(header.transformation_kind) != Some(self.key_material.key_length()) {
+      return Err(SecurityError::new(format!(
+        "Transformation kind {:?} does not match the key",
+        header.transformation_kind
+      )));
+    }
+    if self.session.as_ref().map(|(session_id, _)| *session_id) != Some(header.session_id) {
+      let key = self.key_material.session_key(header.session_id)?;
+      self.session = Some((header.session_id, key));
+    }
+    let session_key = match &self.session {
+      Some((_, key)) => key,
+      None => return Err(SecurityError::new("No session key")),
+    };
+
+    let (mut buffer, aad) = if header.encrypts() {
+      ([content, mac].concat(), &[][..])
+    } else {
+      (mac.to_vec(), content)
+    };
+    let plaintext_length = session_key
+      .open_in_place(header.nonce(), Aad::from(aad), &mut buffer)
+      .map_err(|_| SecurityError::new("Decoding failed"))?
+      .len();
+    if header.encrypts() {
+      buffer.truncate(plaintext_length);
+      Ok(buffer)
+    } else {
+      Ok(content.to_vec())
+    }
+  }
+
+  fn decode_payload(&mut self, encoded: &[u8]) -> SecurityResult<Vec<u8>> {
+    if encoded.len() < CRYPTO_HEADER_LENGTH + CRYPTO_FOOTER_LENGTH {
+      return Err(SecurityError::new("Truncated protected payload"));
+    }
+    let header = CryptoHeader::read(encoded)?;
+    let footer = &encoded[encoded.len() - CRYPTO_FOOTER_LENGTH..];
+    let content = &encoded[CRYPTO_HEADER_LENGTH..encoded.len() - CRYPTO_FOOTER_LENGTH];
+    let content = if header.encrypts() {
+      read_crypto_content(content)?
+    } else {
+      content
+    };
+    self.decode(&header, content, &footer[..MAC_LENGTH])
+  }
+}
+
+// The keys that a participant or endpoint has sent, or is going to send, with.
+// After a rotation, the old keys are accepted for the overlap window after the
+// new ones have come into use, since messages may be reordered, and
+// retransmissions may have been protected with the old keys.
+struct KeyRing {
+  // Oldest first, with the time when retired keys expire
+  receivers: Vec<(Receiver, Option<Instant>)>,
+  overlap: Duration,
+}
+
+impl KeyRing {
+  // Keys of a sender that does not use the new ones yet, e.g. a local endpoint
+  // whose messages do not come back, are dropped after this many rotations.
+  const MAX_KEYS: usize = 3;
+
+  fn new(key_material: KeyMaterial, overlap: Duration) -> Self {
+    Self {
+      receivers: vec![(Receiver::new(key_material), None)],
+      overlap,
+    }
+  }
+
+  fn contains(&self, key_id: [u8; 4]) -> bool {
+    self
+      .receivers
+      .iter()
+      .any(|(receiver, _)| receiver.key_id() == key_id)
+  }
+
+  // New keys from crypto tokens. Tokens may be sent again, so known keys are
+  // ignored.
+  fn insert(&mut self, key_material: KeyMaterial) {
+    if self
+      .receivers
+      .iter()
+      .any(|(receiver, _)| receiver.key_material == key_material)
+    {
+      return;
+    }
+    self
+      .receivers
+      .retain(|(receiver, _)| receiver.key_id() != key_material.sender_key_id);
+    self.receivers.push((Receiver::new(key_material), None));
+    if self.receivers.len() > Self::MAX_KEYS {
+      self.receivers.remove(0);
+    }
+  }
+
+  // Decode with the keys of `key_id`. Once newer keys have decoded something,
+  // the older ones expire after the overlap window.
+  fn decode_with<T>(
+    &mut self,
+    key_id: [u8; 4],
+    decode: impl FnOnce(&mut Receiver) -> SecurityResult<T>,
+  ) -> SecurityResult<T> {
+    let now = Instant::now();
+    self
+      .receivers
+      .retain(|(_, expiry)| expiry.map_or(true, |expiry| now < expiry));
+    let index = self
+      .receivers
+      .iter()
+      .position(|(receiver, _)| receiver.key_id() == key_id)
+      .ok_or_else(|| SecurityError::new(format!("Key {:?} has expired", key_id)))?;
+    let decoded = decode(&mut self.receivers[index].0)?;
+    let expiry = now + self.overlap;
+    for (_, retired) in &mut self.receivers[..index] {
+      retired.get_or_insert(expiry);
+    }
+    Ok(decoded)
+  }
+
+  fn decode(
+    &mut self,
+    header: &CryptoHeader,
+    content: &[u8],
+    mac: &[u8],
+  ) -> SecurityResult<Vec<u8>> {
+    self.decode_with(header.key_id, |receiver| {
+      receiver.decode(header, content, mac)
+    })
+  }
+
+  fn decode_payload(&mut self, encoded: &[u8]) -> SecurityResult<Vec<u8>> {
+    let key_id = CryptoHeader::read(encoded)?.key_id;
+    self.decode_with(key_id, |receiver| receiver.decode_payload(encoded))
+  }
+}
+
+// Keys of a protected local endpoint
+struct LocalEndpoint {
+  protection: TopicProtection,
+  keys: SendingKeys,
+  // For what the endpoint has sent, when it comes back over multicast loopback
+  receivers: KeyRing,
+}
+
+struct RemoteParticipant {
+  // Derived from the shared secret of the handshake
+  kx_sender: Sender,
+  kx_receivers: KeyRing,
+  // From the participant crypto tokens
+  receivers: Option<KeyRing>,
+}
+
+// The protection of a remote endpoint is known from its topic, once Discovery
+// has seen it, and the keys once it has sent its crypto tokens. These come in
+// any order.
+#[derive(Default)]
+struct RemoteEndpoint {
+  protection: Option<TopicProtection>,
+  receivers: Option<KeyRing>,
+}
+
+// Keys from crypto tokens, which may replace earlier ones
+fn insert_keys(receivers: &mut Option<KeyRing>, key_material: KeyMaterial, overlap: Duration) {
+  match receivers {
+    Some(receivers) => receivers.insert(key_material),
+    None => *receivers = Some(KeyRing::new(key_material, overlap)),
+  }
+}
+
+struct CryptoState {
+  participant_guid_prefix: GuidPrefix,
+  access_control: AccessControlHandle,
+  key_rotation: KeyRotationPolicy,
+  rng: SystemRandom,
+  participant_keys: SendingKeys,
+  participant_receivers: KeyRing,
+  local_endpoints: BTreeMap<EntityId, LocalEndpoint>,
+  remote_participants: BTreeMap<GuidPrefix, RemoteParticipant>,
+  remote_endpoints: BTreeMap<GUID, RemoteEndpoint>,
+  // Pairs of local and remote endpoints that the local endpoint has sent its
+  // crypto tokens to
+  tokens_sent: BTreeSet<(EntityId, GUID)>,
+}
+
+// Cryptographic plugin of a participant. This is shared by Discovery, which
+// exchanges the keys, and the event loop, which encodes and decodes.
+#[derive(Clone)]
+pub(crate) struct Cryptography {
+  inner: Arc<Mutex<CryptoState>>,
+}
+
+impl fmt::Debug for Cryptography {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("Cryptography")
+  }
+}
+
+impl Cryptography {
+  pub fn new(
+    participant_guid_prefix: GuidPrefix,
+    access_control: AccessControlHandle,
+    key_rotation: KeyRotationPolicy,
+  ) -> SecurityResult<Self> {
+    let rng = SystemRandom::new();
+    let key_material = KeyMaterial::generate(&rng)?;
+    let state = CryptoState {
+      participant_guid_prefix,
+      access_control,
+      key_rotation,
+      participant_receivers: KeyRing::new(key_material.clone(), key_rotation.overlap),
+      participant_keys: SendingKeys::new(key_material, &rng)?,
+      rng,
+      local_endpoints: BTreeMap::new(),
+      remote_participants: BTreeMap::new(),
+      remote_endpoints: BTreeMap::new(),
+      tokens_sent: BTreeSet::new(),
+    };
+    Ok(Self {
+      inner: Arc::new(Mutex::new(state)),
+    })
+  }
+
+  fn state(&self) -> MutexGuard<'_, CryptoState> {
+    self.inner.lock().unwrap()
+  }
+}
+
+impl CryptographicPlugin for Cryptography {
+  fn protection_overhead(&self) -> usize {
+    PROTECTION_OVERHEAD
+  }
+
+  // Generate keys for a local user-defined Writer or Reader, if its topic is
+  // protected.
+  fn register_local_endpoint(&self, guid: GUID, topic_name: &str) -> SecurityResult<()> {
+    if !guid.entity_id.kind().is_user_defined() {
+      return Ok(());
+    }
+    let mut state = self.state();
+    let protection = state.access_control.topic_protection(topic_name);
+    if protection == TopicProtection::NONE {
+      return Ok(());
+    }
+    let key_material = KeyMaterial::generate(&state.rng)?;
+    let endpoint = LocalEndpoint {
+      protection,
+      receivers: KeyRing::new(key_material.clone(), state.key_rotation.overlap),
+      keys: SendingKeys::new(key_material, &state.rng)?,
+    };
+    state.local_endpoints.insert(guid.entity_id, endpoint);
+    Ok(())
+  }
+
+  fn unregister_local_endpoint(&self, entity_id: EntityId) {
+    let mut state = self.state();
+    state.local_endpoints.remove(&entity_id);
+    state.tokens_sent.retain(|(local, _)| *local != entity_id);
+  }
+
+  // Derive the key exchange keys from the shared secret of a completed
+  // handshake.
+  fn register_remote_participant(
+    &self,
+    guid_prefix: GuidPrefix,
+    shared_secret: &SharedSecret,
+  ) -> SecurityResult<()> {
+    let key_material = KeyMaterial::key_exchange(shared_secret);
+    let mut state = self.state();
+    let participant = RemoteParticipant {
+      kx_receivers: KeyRing::new(key_material.clone(), state.key_rotation.overlap),
+      kx_sender: Sender::new(key_material, &state.rng)?,
+      receivers: None,
+    };
+    state.remote_participants.insert(guid_prefix, participant);
+    Ok(())
+  }
+
+  fn remove_remote_participant(&self, guid_prefix: GuidPrefix) {
+    let mut state = self.state();
+    state.remote_participants.remove(&guid_prefix);
+    state
+      .remote_endpoints
+      .retain(|guid, _| guid.prefix != guid_prefix);
+    state
+      .tokens_sent
+      .retain(|(_, remote)| remote.prefix != guid_prefix);
+  }
+
+  // ParticipantCryptoTokens of the local participant
+  fn participant_crypto_tokens(&self) -> SecurityResult<Vec<DataHolder>> {
+    Ok(vec![self.state().participant_keys.to_token()?])
+  }
+
+  fn set_remote_participant_tokens(
+    &self,
+    guid_prefix: GuidPrefix,
+    tokens: &[DataHolder],
+  ) -> SecurityResult<()> {
+    let key_material = KeyMaterial::from_tokens(tokens)?;
+    let mut state = self.state();
+    let overlap = state.key_rotation.overlap;
+    match state.remote_participants.get_mut(&guid_prefix) {
+      Some(participant) => {
+        insert_keys(&mut participant.receivers, key_material, overlap);
+        Ok(())
+      }
+      None => Err(SecurityError::new(format!(
+        "Crypto tokens from unauthenticated participant {:?}",
+        guid_prefix
+      ))),
+    }
+  }
+
+  // A remote Writer or Reader was discovered.
+  fn register_remote_endpoint(&self, guid: GUID, topic_name: &str) {
+    let mut state = self.state();
+    let protection = state.access_control.topic_protection(topic_name);
+    state.remote_endpoints.entry(guid).or_default().protection = Some(protection);
+  }
+
+  fn set_remote_endpoint_tokens(&self, guid: GUID, tokens: &[DataHolder]) -> SecurityResult<()> {
+    let key_material = KeyMaterial::from_tokens(tokens)?;
+    let mut state = self.state();
+    if !state.remote_participants.contains_key(&guid.prefix) {
+      return Err(SecurityError::new(format!(
+        "Crypto tokens from unauthenticated participant {:?}",
+        guid.prefix
+      )));
+    }
+    let overlap = state.key_rotation.overlap;
+    let endpoint = state.remote_endpoints.entry(guid).or_default();
+    insert_keys(&mut endpoint.receivers, key_material, overlap);
+    Ok(())
+  }
+
+  // DatawriterCryptoTokens or DatareaderCryptoTokens of a local endpoint, if
+  // they should be sent to the remote endpoint now: the local endpoint is
+  // protected, the remote participant is authenticated, and the tokens have
+  // not been sent yet.
+  fn endpoint_tokens_to_send(
+    &self,
+    local: EntityId,
+    remote: GUID,
+  ) -> SecurityResult<Option<Vec<DataHolder>>> {
+    let mut state = self.state();
+    if !state.remote_participants.contains_key(&remote.prefix)
+      || state.tokens_sent.contains(&(local, remote))
+    {
+      return Ok(None);
+    }
+    let token = match state.local_endpoints.get(&local) {
+      Some(endpoint) => endpoint.keys.to_token()?,
+      None => return Ok(None),
+    };
+    state.tokens_sent.insert((local, remote));
+    Ok(Some(vec![token]))
+  }
+
+  // Protect an outgoing RTPS message as the governance requires. Submessages
+  // of the ParticipantVolatileMessageSecure writer and reader are protected
+  // with the key exchange keys of the destination participant.
+  fn encode_message(
+    &self,
+    message: Vec<u8>,
+    destination: Option<GuidPrefix>,
+  ) -> SecurityResult<Vec<u8>> {
+    if message.len() < RTPS_MESSAGE_HEADER_SIZE {
+      return Ok(message);
+    }
+    let mut state = self.state();
+    let state = &mut *state;
+    let (header, submessages) = message.split_at(RTPS_MESSAGE_HEADER_SIZE);
+    let submessages = split_submessages(submessages)?;
+
+    let mut body = Vec::with_capacity(message.len());
+    for submessage in &submessages {
+      match sending_endpoint(submessage) {
+        Some(entity_id) if is_volatile_endpoint(entity_id) => {
+          let participant = destination
+            .and_then(|prefix| state.remote_participants.get_mut(&prefix))
+            .ok_or_else(|| SecurityError::new("No key exchange keys for the destination"))?;
+          participant
+            .kx_sender
+            .encode_submessage(submessage, true, &mut body)?;
+        }
+        Some(entity_id) => match state.local_endpoints.get_mut(&entity_id) {
+          Some(endpoint) if endpoint.protection.metadata != ProtectionKind::None => {
+            let encrypt = endpoint.protection.metadata == ProtectionKind::Encrypt;
+            endpoint
+              .keys
+              .sender()
+              .encode_submessage(submessage, encrypt, &mut body)?;
+          }
+          _ => body.extend_from_slice(submessage),
+        },
+        None => body.extend_from_slice(submessage),
+      }
+    }
+
+    let mut encoded = header.to_vec();
+    let rtps_protection = state.access_control.rtps_protection();
+    if rtps_protection == ProtectionKind::None || rtps_unprotected(&submessages) {
+      encoded.extend_from_slice(&body);
+      return Ok(encoded);
+    }
+    // The RTPS header is protected as an INFO_SRC in front of the submessages.
+    let mut plaintext = Vec::with_capacity(body.len() + 24);
+    write_submessage(
+      &mut plaintext,
+      SubmessageKind::INFO_SRC,
+      &[&[0; 4][..], &header[4..]].concat(),
+    )?;
+    plaintext.extend_from_slice(&body);
+    let encrypt = rtps_protection == ProtectionKind::Encrypt;
+    let (crypto_header, ciphertext, mac) = state
+      .participant_keys
+      .sender()
+      .encode(&plaintext, encrypt)?;
+    write_submessage(
+      &mut encoded,
+      SubmessageKind::SRTPS_PREFIX,
+      &crypto_header.to_bytes(),
+    )?;
+    match ciphertext {
+      Some(ciphertext) => write_sec_body(&mut encoded, &ciphertext)?,
+      None => encoded.extend_from_slice(&plaintext),
+    }
+    write_submessage(
+      &mut encoded,
+      SubmessageKind::SRTPS_POSTFIX,
+      &crypto_footer(mac),
+    )?;
+    Ok(encoded)
+  }
+
+  // Decode a received RTPS message. Unprotected submessages of endpoints that
+  // should protect them are dropped. A message that cannot be decoded, or
+  // should have been protected as a whole, is an error.
+  fn decode_message(&self, message: &[u8]) -> SecurityResult<Vec<u8>> {
+    if message.len() < RTPS_MESSAGE_HEADER_SIZE {
+      return Ok(message.to_vec());
+    }
+    let mut state = self.state();
+    let state = &mut *state;
+    let source = GuidPrefix::new(&message[8..RTPS_MESSAGE_HEADER_SIZE]);
+    let (header, submessages) = message.split_at(RTPS_MESSAGE_HEADER_SIZE);
+    let submessages = split_submessages(submessages)?;
+
+    let rtps_plaintext;
+    let submessages = if submessages.len() >= 2
+      && submessages[0][0] == u8::from(SubmessageKind::SRTPS_PREFIX)
+    {
+      let prefix = submessages[0];
+      let postfix = submessages[submessages.len() - 1];
+      if postfix[0] != u8::from(SubmessageKind::SRTPS_POSTFIX) {
+        return Err(SecurityError::new("SRTPS_PREFIX without SRTPS_POSTFIX"));
+      }
+      let crypto_header = CryptoHeader::read(&prefix[4..])?;
+      let mac = read_mac(postfix)?;
+      let inner = &message[RTPS_MESSAGE_HEADER_SIZE + prefix.len()..message.len() - postfix.len()];
+      let content = if crypto_header.encrypts() {
+        sec_body_content(inner)?
+      } else {
+        inner
+      };
+      rtps_plaintext =
+        state
+          .receivers(source, crypto_header.key_id)?
+          .decode(&crypto_header, content, mac)?;
+      split_submessages(&rtps_plaintext)?
+    } else if state.access_control.rtps_protection() != ProtectionKind::None
+      && !rtps_unprotected(&submessages)
+    {
+      return Err(SecurityError::new(format!(
+        "Unprotected RTPS message from {:?}",
+        source
+      )));
+    } else {
+      submessages
+    };
+
+    let mut decoded = header.to_vec();
+    let mut current_source = source;
+    let mut rest = &submessages[..];
+    while let Some((submessage, tail)) = rest.split_first() {
+      rest = tail;
+      let kind = SubmessageKind::from(submessage[0]);
+      if kind == SubmessageKind::SEC_PREFIX {
+        let (body, postfix) = match tail {
+          [body, postfix, ..] if postfix[0] == u8::from(SubmessageKind::SEC_POSTFIX) => {
+            (*body, *postfix)
+          }
+          _ => return Err(SecurityError::new("SEC_PREFIX without SEC_POSTFIX")),
+        };
+        rest = &tail[2..];
+        let crypto_header = CryptoHeader::read(&submessage[4..])?;
+        let mac = read_mac(postfix)?;
+        let content = if crypto_header.encrypts() {
+          sec_body_content(body)?
+        } else {
+          body
+        };
+        let plaintext = state
+          .receivers(current_source, crypto_header.key_id)?
+          .decode(&crypto_header, content, mac)?;
+        decoded.extend_from_slice(&plaintext);
+        continue;
+      }
+      if kind == SubmessageKind::INFO_SRC && submessage.len() >= 24 {
+        current_source = GuidPrefix::new(&submessage[12..24]);
+      }
+      match sending_endpoint(submessage) {
+        Some(entity_id)
+          if state.requires_submessage_protection(GUID::new(current_source, entity_id)) =>
+        {
+          debug!(
+            "Dropping unprotected {:?} from {:?}",
+            kind,
+            GUID::new(current_source, entity_id)
+          );
+        }
+        _ => decoded.extend_from_slice(submessage),
+      }
+    }
+    Ok(decoded)
+  }
+
+  // Protect the serialized payload of a sample written by a local Writer, if
+  // the topic requires it.
+  fn encode_payload(&self, writer: EntityId, payload: &[u8]) -> SecurityResult<Option<Vec<u8>>> {
+    let mut state = self.state();
+    match state.local_endpoints.get_mut(&writer) {
+      Some(endpoint) if endpoint.protection.data != ProtectionKind::None => {
+        let encrypt = endpoint.protection.data == ProtectionKind::Encrypt;
+        endpoint
+          .keys
+          .sender()
+          .encode_payload(payload, encrypt)
+          .map(Some)
+      }
+      _ => Ok(None),
+    }
+  }
+
+  // Decode the serialized payload of a sample from a Writer whose topic
+  // protects data.
+  fn decode_payload(&self, writer: GUID, payload: &[u8]) -> SecurityResult<Option<Vec<u8>>> {
+    let mut state = self.state();
+    let state = &mut *state;
+    let (protection, receivers) = if writer.prefix == state.participant_guid_prefix {
+      match state.local_endpoints.get_mut(&writer.entity_id) {
+        Some(endpoint) => (Some(endpoint.protection), Some(&mut endpoint.receivers)),
+        None => (None, None),
+      }
+    } else {
+      match state.remote_endpoints.get_mut(&writer) {
+        Some(endpoint) => (endpoint.protection, endpoint.receivers.as_mut()),
+        None => (None, None),
+      }
+    };
+    match protection {
+      Some(protection) if protection.data != ProtectionKind::None => (),
+      _ => return Ok(None),
+    }
+    let receivers =
+      receivers.ok_or_else(|| SecurityError::new(format!("No crypto tokens from {:?}", writer)))?;
+    receivers.decode_payload(payload).map(Some)
+  }
+
+  // Generate new keys for the local participant and endpoints whose keys are
+  // due for rotation. New endpoint keys are sent to the remote endpoints again
+  // like the first ones.
+  fn rotate_keys(&self) -> SecurityResult<Vec<GuidPrefix>> {
+    let mut state = self.state();
+    let state = &mut *state;
+    let policy = state.key_rotation;
+    for (entity_id, endpoint) in &mut state.local_endpoints {
+      if let Some(key_material) = endpoint.keys.rotate(&policy, &state.rng)? {
+        debug!("Rotating the keys of {:?}", entity_id);
+        endpoint.receivers.insert(key_material);
+        state.tokens_sent.retain(|(local, _)| local != entity_id);
+      }
+    }
+    match state.participant_keys.rotate(&policy, &state.rng)? {
+      Some(key_material) => {
+        debug!("Rotating the participant keys");
+        state.participant_receivers.insert(key_material);
+        Ok(state.remote_participants.keys().copied().collect())
+      }
+      None => Ok(Vec::new()),
+    }
+  }
+}
+
+impl CryptoState {
+  // Find the keys that a participant has sent with.
+  fn receivers(&mut self, source: GuidPrefix, key_id: [u8; 4]) -> SecurityResult<&mut KeyRing> {
+    let no_keys = || SecurityError::new(format!("No key {:?} for {:?}", key_id, source));
+    if source == self.participant_guid_prefix {
+      if self.participant_receivers.contains(key_id) {
+        return Ok(&mut self.participant_receivers);
+      }
+      return self
+        .local_endpoints
+        .values_mut()
+        .map(|endpoint| &mut endpoint.receivers)
+        .find(|receivers| receivers.contains(key_id))
+        .ok_or_else(no_keys);
+    }
+
+    let participant = self
+      .remote_participants
+      .get_mut(&source)
+      .ok_or_else(no_keys)?;
+    if key_id == KX_KEY_ID {
+      return Ok(&mut participant.kx_receivers);
+    }
+    if participant
+      .receivers
+      .as_ref()
+      .map_or(false, |receivers| receivers.contains(key_id))
+    {
+      return participant.receivers.as_mut().ok_or_else(no_keys);
+    }
+    self
+      .remote_endpoints
+      .range_mut(source.range())
+      .filter_map(|(_, endpoint)| endpoint.receivers.as_mut())
+      .find(|receivers| receivers.contains(key_id))
+      .ok_or_else(no_keys)
+  }
+
+  fn requires_submessage_protection(&self, endpoint: GUID) -> bool {
+    if is_volatile_endpoint(endpoint.entity_id) {
+      return true;
+    }
+    let protection = if endpoint.prefix == self.participant_guid_prefix {
+      self
+        .local_endpoints
+        .get(&endpoint.entity_id)
+        .map(|endpoint| endpoint.protection)
+    } else {
+      self
+        .remote_endpoints
+        .get(&endpoint)
+        .and_then(|endpoint| endpoint.protection)
+    };
+    protection.map_or(false, |protection| {
+      protection.metadata != ProtectionKind::None
+    })
+  }
+}
+
+// Split the submessages of an RTPS message, each with its header.
+fn split_submessages(mut bytes: &[u8]) -> SecurityResult<Vec<&[u8]>> {
+  let mut submessages = Vec::new();
+  while !bytes.is_empty() {
+    if bytes.len() < 4 {
+      return Err(SecurityError::new("Truncated submessage header"));
+    }
+    let length = if bytes[1] & 0x01 != 0 {
+      LittleEndian::read_u16(&bytes[2..4])
+    } else {
+      BigEndian::read_u16(&bytes[2..4])
+    } as usize;
+    let kind = SubmessageKind::from(bytes[0]);
+    // Length zero means that the submessage extends to the end of the message.
+    let end = if length == 0 && kind != SubmessageKind::PAD && kind != SubmessageKind::INFO_TS {
+      bytes.len()
+    } else {
+      4 + length
+    };
+    if end > bytes.len() {
+      return Err(SecurityError::new("Truncated submessage"));
+    }
+    let (submessage, rest) = bytes.split_at(end);
+    submessages.push(submessage);
+    bytes = rest;
+  }
+  Ok(submessages)
+}
+
+// The local endpoint that sent a submessage, if it is an Entity Submessage
+fn sending_endpoint(submessage: &[u8]) -> Option<EntityId> {
+  let offset = match SubmessageKind::from(submessage[0]) {
+    // writerId after extraFlags, octetsToInlineQos and readerId
+    SubmessageKind::DATA | SubmessageKind::DATA_FRAG => 8,
+    // writerId after readerId
+    SubmessageKind::HEARTBEAT | SubmessageKind::GAP | SubmessageKind::HEARTBEAT_FRAG => 4,
+    // readerId
+    SubmessageKind::ACKNACK | SubmessageKind::NACK_FRAG => 0,
+    _ => return None,
+  };
+  let bytes = submessage.get(4 + offset..4 + offset + 4)?;
+  Some(EntityId::from_slice([
+    bytes[0], bytes[1], bytes[2], bytes[3],
+  ]))
+}
+
+// Messages of the security infrastructure are not protected as a whole, since
+// they are needed to get the keys. Submessages that are protected on their
+// own are let through.
+fn rtps_unprotected(submessages: &[&[u8]]) -> bool {
+  submessages.iter().all(|submessage| {
+    let kind = SubmessageKind::from(submessage[0]);
+    kind == SubmessageKind::SEC_PREFIX
+      || kind == SubmessageKind::SEC_BODY
+      || kind == SubmessageKind::SEC_POSTFIX
+      || sending_endpoint(submessage).map_or(true, |entity_id| {
+        RTPS_UNPROTECTED_ENDPOINTS.contains(&entity_id)
+      })
+  })
+}
+
+// Submessages are written little-endian, like the rest of RustDDS.
+fn write_submessage(output: &mut Vec<u8>, kind: SubmessageKind, body: &[u8]) -> SecurityResult<()> {
+  let length =
+    u16::try_from(body.len()).map_err(|_| SecurityError::new("Protected submessage too long"))?;
+  output.push(kind.into());
+  output.push(0x01);
+  output.extend_from_slice(&length.to_le_bytes());
+  output.extend_from_slice(body);
+  Ok(())
+}
+
+// CryptoContent: the length of the ciphertext and the ciphertext
+fn write_crypto_content(output: &mut Vec<u8>, ciphertext: &[u8]) {
+  output.extend_from_slice(&(ciphertext.len() as u32).to_be_bytes());
+  output.extend_from_slice(ciphertext);
+}
+
+fn read_crypto_content(bytes: &[u8]) -> SecurityResult<&[u8]> {
+  if bytes.len() < 4 {
+    return Err(SecurityError::new("Truncated CryptoContent"));
+  }
+  let length = BigEndian::read_u32(&bytes[..4]) as usize;
+  bytes
+    .get(4..4 + length)
+    .ok_or_else(|| SecurityError::new("Truncated CryptoContent"))
+}
+
+// SEC_BODY, padded to a multiple of four bytes
+fn write_sec_body(output: &mut Vec<u8>, ciphertext: &[u8]) -> SecurityResult<()> {
+  let mut body = Vec::with_capacity(ciphertext.len() + 8);
+  write_crypto_content(&mut body, ciphertext);
+  body.resize((body.len() + 3) & !3, 0);
+  write_submessage(output, SubmessageKind::SEC_BODY, &body)
+}
+
+// The CryptoContent of a SEC_BODY submessage, with its header
+fn sec_body_content(submessage: &[u8]) -> SecurityResult<&[u8]> {
+  if submessage.len() < 4 || submessage[0] != u8::from(SubmessageKind::SEC_BODY) {
+    return Err(SecurityError::new("Expected SEC_BODY"));
+  }
+  read_crypto_content(&submessage[4..])
+}
+
+fn crypto_footer(mac: [u8; MAC_LENGTH]) -> Vec<u8> {
+  [&mac[..], &[0; 4]].concat()
+}
+
+// The common MAC of a SEC_POSTFIX or SRTPS_POSTFIX, with its header
+fn read_mac(postfix: &[u8]) -> SecurityResult<&[u8]> {
+  postfix
+    .get(4..4 + MAC_LENGTH)
+    .ok_or_else(|| SecurityError::new("Truncated CryptoFooter"))
+}
+
+// A protected payload does not start with a representation identifier, but
+// SerializedPayload splits the first four bytes like one.
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    security::access_control::{
+      tests::{participant_a, participant_b},
+      BuiltinAccessControl,
+    },
+    structure::guid::EntityKind,
+  };
+
+  fn shared_secret() -> SharedSecret {
+    SharedSecret {
+      challenge1: vec![1; 32],
+      challenge2: vec![2; 32],
+      secret: vec![3; 32],
+    }
+  }
+
+  // An RTPS message with a DATA submessage from a Writer
+  fn data_message(source: GuidPrefix, writer: EntityId) -> Vec<u8> {
+    let mut message = b"RTPS\x02\x04\x01\x0f".to_vec();
+    message.extend_from_slice(source.as_ref());
+    let mut data = vec![0, 0, 16, 0]; // extraFlags, octetsToInlineQos
+    data.extend_from_slice(&EntityId::UNKNOWN.to_slice());
+    data.extend_from_slice(&writer.to_slice());
+    data.extend_from_slice(&[0, 0, 0, 0, 1, 0, 0, 0]); // sequence number
+    data.extend_from_slice(&[0, 1, 0, 0, 1, 2, 3, 4]); // serialized payload
+    write_submessage(&mut message, SubmessageKind::DATA, &data).unwrap();
+    message
+  }
+
+  // Two participants that have authenticated each other and exchanged
+  // participant crypto tokens
+  fn participants() -> (Cryptography, Cryptography) {
+    participants_with(KeyRotationPolicy::default())
+  }
+
+  fn participants_with(key_rotation: KeyRotationPolicy) -> (Cryptography, Cryptography) {
+    let access_control =
+      |access_control| AccessControlHandle::new(BuiltinAccessControl::new(access_control));
+    let a = Cryptography::new(
+      GuidPrefix::new(&[1; 12]),
+      access_control(participant_a(0).unwrap()),
+      key_rotation,
+    )
+    .unwrap();
+    let b = Cryptography::new(
+      GuidPrefix::new(&[2; 12]),
+      access_control(participant_b(0).unwrap()),
+      key_rotation,
+    )
+    .unwrap();
+    for (local, remote) in [(&a, &b), (&b, &a)] {
+      let remote_prefix = remote.state().participant_guid_prefix;
+      local
+        .register_remote_participant(remote_prefix, &shared_secret())
+        .unwrap();
+    }
+    a.set_remote_participant_tokens(
+      GuidPrefix::new(&[2; 12]),
+      &b.participant_crypto_tokens().unwrap(),
+    )
+    .unwrap();
+    b.set_remote_participant_tokens(
+      GuidPrefix::new(&[1; 12]),
+      &a.participant_crypto_tokens().unwrap(),
+    )
+    .unwrap();
+    (a, b)
+  }
+
+  #[test]
+  fn key_material_tokens() {
+    let key_material = KeyMaterial::generate(&SystemRandom::new()).unwrap();
+    assert_ne!(key_material.sender_key_id, KX_KEY_ID);
+    let token = key_material.to_token().unwrap();
+    assert_eq!(token.class_id, CLASS_ID);
+    assert!(KeyMaterial::from_tokens(&[token]).unwrap() == key_material);
+    assert!(KeyMaterial::from_tokens(&[DataHolder::new(CLASS_ID)]).is_err());
+
+    // Both sides of a handshake derive the same key exchange keys.
+    let kx = KeyMaterial::key_exchange(&shared_secret());
+    assert!(kx == KeyMaterial::key_exchange(&shared_secret()));
+    assert_eq!(kx.sender_key_id, KX_KEY_ID);
+    assert_ne!(kx.master_salt, kx.master_sender_key);
+  }
+
+  #[test]
+  fn encode_and_decode() {
+    let key_material = KeyMaterial::generate(&SystemRandom::new()).unwrap();
+    let mut sender = Sender::new(key_material.clone(), &SystemRandom::new()).unwrap();
+    let mut receiver = Receiver::new(key_material);
+    let data = b"Hello, World!";
+
+    for encrypt in [true, false] {
+      let encoded = sender.encode_payload(data, encrypt).unwrap();
+      assert_eq!(
+        encoded.windows(data.len()).any(|w| w == data),
+        !encrypt,
+        "plaintext visible: {}",
+        !encrypt
+      );
+      assert_eq!(receiver.decode_payload(&encoded).unwrap(), data);
+      // Any change is detected.
+      for i in [0, 10, CRYPTO_HEADER_LENGTH + 5, encoded.len() - 5] {
+        let mut tampered = encoded.clone();
+        tampered[i] ^= 1;
+        assert!(receiver.decode_payload(&tampered).is_err());
+      }
+    }
+
+    // A new session starts with a new key.
+    sender.messages = MESSAGES_PER_SESSION;
+    let session_id = sender.session_id;
+    let encoded = sender.encode_payload(data, true).unwrap();
+    assert_eq!(&encoded[8..12], &session_id.wrapping_add(1).to_be_bytes());
+    assert_eq!(receiver.decode_payload(&encoded).unwrap(), data);
+  }
+
+  #[test]
+  fn message_protection() {
+    let (a, b) = participants();
+    let a_prefix = GuidPrefix::new(&[1; 12]);
+    let writer = EntityId::new([0, 0, 1], EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    let message = data_message(a_prefix, writer);
+
+    // The governance signs RTPS messages.
+    let encoded = a.encode_message(message.clone(), None).unwrap();
+    assert_eq!(encoded[20], u8::from(SubmessageKind::SRTPS_PREFIX));
+    let decoded = b.decode_message(&encoded.clone()).unwrap();
+    // The RTPS header is repeated in an INFO_SRC.
+    assert_eq!(&decoded[..20], &message[..20]);
+    assert_eq!(decoded[20], u8::from(SubmessageKind::INFO_SRC));
+    assert_eq!(&decoded[44..], &message[20..]);
+
+    let mut tampered = encoded;
+    let last = tampered.len() - 30;
+    tampered[last] ^= 1;
+    assert!(b.decode_message(&tampered).is_err());
+    // Unprotected messages are rejected.
+    assert!(b.decode_message(&message).is_err());
+    // Except SPDP
+    let spdp = data_message(a_prefix, EntityId::SPDP_BUILTIN_PARTICIPANT_WRITER);
+    assert_eq!(a.encode_message(spdp.clone(), None).unwrap(), spdp);
+    assert_eq!(b.decode_message(&spdp.clone()).unwrap(), spdp);
+  }
+
+  #[test]
+  fn submessage_and_payload_protection() {
+    let (a, b) = participants();
+    let a_prefix = GuidPrefix::new(&[1; 12]);
+    let b_prefix = GuidPrefix::new(&[2; 12]);
+    let writer = GUID::new(
+      a_prefix,
+      EntityId::new([0, 0, 1], EntityKind::WRITER_NO_KEY_USER_DEFINED),
+    );
+    let reader = GUID::new(
+      b_prefix,
+      EntityId::new([0, 0, 2], EntityKind::READER_NO_KEY_USER_DEFINED),
+    );
+    // Topic "Square" signs submessages and encrypts payloads.
+    a.register_local_endpoint(writer, "Square").unwrap();
+    b.register_remote_endpoint(writer, "Square");
+    let tokens = a
+      .endpoint_tokens_to_send(writer.entity_id, reader)
+      .unwrap()
+      .unwrap();
+    assert_eq!(
+      a.endpoint_tokens_to_send(writer.entity_id, reader).unwrap(),
+      None
+    );
+    b.set_remote_endpoint_tokens(writer, &tokens).unwrap();
+
+    let message = data_message(a_prefix, writer.entity_id);
+    let encoded = a.encode_message(message.clone(), None).unwrap();
+    let decoded = b.decode_message(&encoded).unwrap();
+    assert_eq!(&decoded[44..], &message[20..]);
+
+    let data = vec![0, 1, 0, 0, 1, 2, 3, 4];
+    let encoded = a.encode_payload(writer.entity_id, &data).unwrap().unwrap();
+    assert_ne!(encoded, data);
+    assert_eq!(
+      b.decode_payload(writer, &encoded).unwrap(),
+      Some(data.clone())
+    );
+    // An unprotected payload does not decode.
+    assert!(b.decode_payload(writer, &data).is_err());
+
+    // Topic "Open" has no protection.
+    let open_writer = GUID::new(
+      a_prefix,
+      EntityId::new([0, 0, 3], EntityKind::WRITER_NO_KEY_USER_DEFINED),
+    );
+    a.register_local_endpoint(open_writer, "Open").unwrap();
+    assert_eq!(
+      a.endpoint_tokens_to_send(open_writer.entity_id, reader)
+        .unwrap(),
+      None
+    );
+  }
+
+  // A protected Writer of participant a, and a Reader of participant b that
+  // has discovered it
+  fn writer_and_reader(a: &Cryptography, b: &Cryptography) -> (GUID, GUID) {
+    let writer = GUID::new(
+      GuidPrefix::new(&[1; 12]),
+      EntityId::new([0, 0, 1], EntityKind::WRITER_NO_KEY_USER_DEFINED),
+    );
+    let reader = GUID::new(
+      GuidPrefix::new(&[2; 12]),
+      EntityId::new([0, 0, 2], EntityKind::READER_NO_KEY_USER_DEFINED),
+    );
+    a.register_local_endpoint(writer, "Square").unwrap();
+    b.register_remote_endpoint(writer, "Square");
+    (writer, reader)
+  }
+
+  // What Discovery does periodically: rotate keys, and send the new ones.
+  fn rotate_and_send_tokens(a: &Cryptography, b: &Cryptography, writer: GUID, reader: GUID) {
+    for guid_prefix in a.rotate_keys().unwrap() {
+      assert_eq!(guid_prefix, reader.prefix);
+      b.set_remote_participant_tokens(writer.prefix, &a.participant_crypto_tokens().unwrap())
+        .unwrap();
+    }
+    if let Some(tokens) = a.endpoint_tokens_to_send(writer.entity_id, reader).unwrap() {
+      b.set_remote_endpoint_tokens(writer, &tokens).unwrap();
+    }
+  }
+
+  #[test]
+  fn key_rotation_mid_stream() {
+    assert!(KeyRotationPolicy::new().messages(0).validate().is_err());
+    assert!(KeyRotationPolicy::new()
+      .period(Duration::from_secs(5))
+      .validate()
+      .is_err());
+    assert!(KeyRotationPolicy::new()
+      .period(Duration::from_secs(60))
+      .messages(1000)
+      .validate()
+      .is_ok());
+
+    // Without an overlap window the new keys are taken into use at once, and
+    // the old ones expire as soon as the new ones have decoded something.
+    let (a, b) = participants_with(KeyRotationPolicy::new().messages(4).overlap(Duration::ZERO));
+    let (writer, reader) = writer_and_reader(&a, &b);
+    let mut payload_keys = BTreeSet::new();
+    let mut message_keys = BTreeSet::new();
+    for i in 0..40 {
+      if i % 3 == 0 {
+        rotate_and_send_tokens(&a, &b, writer, reader);
+      }
+      // No sample is lost.
+      let data = vec![0, 1, 0, 0, i, i, i, i];
+      let encoded = a.encode_payload(writer.entity_id, &data).unwrap().unwrap();
+      payload_keys.insert(CryptoHeader::read(&encoded).unwrap().key_id);
+      assert_eq!(b.decode_payload(writer, &encoded).unwrap(), Some(data));
+
+      let message = data_message(writer.prefix, writer.entity_id);
+      let encoded = a.encode_message(message.clone(), None).unwrap();
+      message_keys.insert(CryptoHeader::read(&encoded[24..]).unwrap().key_id);
+      assert_eq!(&b.decode_message(&encoded).unwrap()[44..], &message[20..]);
+    }
+    // The endpoint keys were replaced at every check, and the participant
+    // keys at every other.
+    assert_eq!(payload_keys.len(), 14);
+    assert_eq!(message_keys.len(), 7);
+
+    // With an overlap window, the old keys stay in use while the new ones are
+    // distributed. A retransmission protected with the old keys is accepted
+    // for the overlap window after the new keys have come into use.
+    let overlap = Duration::from_millis(200);
+    let (a, b) = participants_with(KeyRotationPolicy::new().messages(1).overlap(overlap));
+    let (writer, reader) = writer_and_reader(&a, &b);
+    rotate_and_send_tokens(&a, &b, writer, reader);
+    let data = vec![0, 1, 0, 0, 1, 2, 3, 4];
+    let old = a.encode_payload(writer.entity_id, &data).unwrap().unwrap();
+    let key_id = |encoded: &[u8]| CryptoHeader::read(encoded).unwrap().key_id;
+
+    rotate_and_send_tokens(&a, &b, writer, reader);
+    let during_overlap = a.encode_payload(writer.entity_id, &data).unwrap().unwrap();
+    assert_eq!(key_id(&during_overlap), key_id(&old));
+    std::thread::sleep(overlap + Duration::from_millis(50));
+    let new = a.encode_payload(writer.entity_id, &data).unwrap().unwrap();
+    assert_ne!(key_id(&new), key_id(&old));
+    for encoded in [&during_overlap, &new, &old] {
+      assert_eq!(
+        b.decode_payload(writer, encoded).unwrap(),
+        Some(data.clone())
+      );
+    }
+    std::thread::sleep(overlap + Duration::from_millis(50));
+    assert!(b.decode_payload(writer, &old).is_err());
+    assert_eq!(b.decode_payload(writer, &new).unwrap(), Some(data));
+  }
+
+  #[test]
+  fn volatile_messages_use_key_exchange_keys() {
+    let (a, b) = participants();
+    let a_prefix = GuidPrefix::new(&[1; 12]);
+    let b_prefix = GuidPrefix::new(&[2; 12]);
+    let message = data_message(
+      a_prefix,
+      EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER,
+    );
+
+    assert!(a.encode_message(message.clone(), None).is_err());
+    let encoded = a.encode_message(message.clone(), Some(b_prefix)).unwrap();
+    // Not protected as a whole, but the submessage is encrypted.
+    assert_eq!(encoded[20], u8::from(SubmessageKind::SEC_PREFIX));
+    assert_eq!(b.decode_message(&encoded).unwrap(), message);
+    // Unprotected volatile submessages are dropped.
+    assert_eq!(b.decode_message(&message).unwrap(), message[..20].to_vec());
+  }
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{security::types::DataHolder, structure::guid::GUID};
+
+// DDS Security spec v1.1 Section "7.4.3.3 ParticipantGenericMessage". This is
+// the data type of the builtin ParticipantStatelessMessage topic, which
+// carries the authentication handshake, and of the builtin
+// ParticipantVolatileMessageSecure topic, which carries the crypto tokens.
+
+/// Identity of a [`ParticipantGenericMessage`]: the GUID of the writer that
+/// sent it, and a sequence number that the writer increments for each message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct MessageIdentity {
+  pub source_guid: GUID,
+  // IDL long long, not an RTPS SequenceNumber
+  pub sequence_number: i64,
+}
+
+/// Message of the builtin ParticipantStatelessMessage and
+/// ParticipantVolatileMessageSecure topics, e.g. an authentication handshake
+/// message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParticipantGenericMessage {
+  pub message_identity: MessageIdentity,
+  pub related_message_identity: MessageIdentity,
+  pub destination_participant_guid: GUID,
+  pub destination_endpoint_guid: GUID,
+  pub source_endpoint_guid: GUID,
+  pub message_class_id: String,
+  pub message_data: Vec<DataHolder>,
+}
+
+impl ParticipantGenericMessage {
+  /// message_class_id of handshake messages
+  pub const AUTH_HANDSHAKE: &'static str = "dds.sec.auth";
+  /// message_class_id of crypto token messages
+  pub const PARTICIPANT_CRYPTO_TOKENS: &'static str = "dds.sec.participant_crypto_tokens";
+  pub const DATAWRITER_CRYPTO_TOKENS: &'static str = "dds.sec.datawriter_crypto_tokens";
+  pub const DATAREADER_CRYPTO_TOKENS: &'static str = "dds.sec.datareader_crypto_tokens";
+}
+
+#[cfg(test)]
+mod tests {
+  use byteorder::LittleEndian;
+
+  use super::*;
+  use crate::{
+    serialization::{cdr_deserializer::deserialize_from_little_endian, cdr_serializer::to_bytes},
+    structure::guid::{EntityId, GuidPrefix},
+  };
+
+  #[test]
+  fn generic_message_cdr() {
+    let source = GUID::new(
+      GuidPrefix::new(&[1; 12]),
+      EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_WRITER,
+    );
+    let message = ParticipantGenericMessage {
+      message_identity: MessageIdentity {
+        source_guid: source,
+        sequence_number: 7,
+      },
+      related_message_identity: MessageIdentity::default(),
+      destination_participant_guid: GUID::new(GuidPrefix::new(&[2; 12]), EntityId::PARTICIPANT),
+      destination_endpoint_guid: GUID::GUID_UNKNOWN,
+      source_endpoint_guid: source,
+      message_class_id: ParticipantGenericMessage::AUTH_HANDSHAKE.to_string(),
+      message_data: vec![DataHolder::new("DDS:Auth:PKI-DH:1.0+Req")],
+    };
+    let bytes = to_bytes::<ParticipantGenericMessage, LittleEndian>(&message).unwrap();
+    // GUID and sequence number
+    assert_eq!(&bytes[..16], &source.to_bytes());
+    assert_eq!(&bytes[16..24], &7i64.to_le_bytes());
+    assert_eq!(
+      deserialize_from_little_endian::<ParticipantGenericMessage>(&bytes).unwrap(),
+      message
+    );
+  }
+}
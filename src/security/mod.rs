@@ -0,0 +1,132 @@
+// DDS Security, OMG DDS Security spec v1.1.
+//
+// The data types that secure participants announce in Discovery are always
+// available, so that such participants can be recognized. The plugins need the
+// "security" feature.
+
+#[cfg(feature = "security")]
+use crate::{
+  dds::values::result::{Error, Result},
+  log_and_err_internal,
+  security::plugins::{AccessControlHandle, AuthenticationHandle, CryptographyHandle},
+  structure::guid::{GuidPrefix, GUID},
+};
+
+#[cfg(feature = "security")]
+pub(crate) mod access_control;
+#[cfg(feature = "security")]
+pub(crate) mod authentication;
+#[cfg(feature = "security")]
+pub(crate) mod certificate;
+#[cfg(feature = "security")]
+pub(crate) mod cryptographic;
+#[cfg(feature = "security")]
+pub(crate) mod generic_message;
+#[cfg(feature = "security")]
+pub(crate) mod plugins;
+pub(crate) mod types;
+
+// Security settings of a participant. This exists also without the "security"
+// feature, so that it can be passed on unconditionally.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SecurityConfig {
+  #[cfg(feature = "security")]
+  pub identity: Option<authentication::IdentityCredentials>,
+  #[cfg(feature = "security")]
+  pub permissions: Option<access_control::PermissionsCredentials>,
+  // The plugins are registered by the application, or set up from the
+  // credentials when the participant is created.
+  #[cfg(feature = "security")]
+  pub authentication: Option<AuthenticationHandle>,
+  #[cfg(feature = "security")]
+  pub access_control: Option<AccessControlHandle>,
+  // Set up with access control, if the governance protects anything
+  #[cfg(feature = "security")]
+  pub cryptography: Option<CryptographyHandle>,
+  // When the builtin cryptographic plugin replaces its keys
+  #[cfg(feature = "security")]
+  pub key_rotation: cryptographic::KeyRotationPolicy,
+  // Anti-spoofing: accept submessages only from the addresses that the sending
+  // participant announced.
+  pub validate_source_addresses: bool,
+}
+
+#[cfg(feature = "security")]
+impl SecurityConfig {
+  // Authentication may decide the GUID prefix of the participant.
+  pub fn participant_guid_prefix(&self, candidate: GUID) -> GuidPrefix {
+    match (&self.authentication, &self.identity) {
+      (Some(authentication), _) => authentication.adjusted_guid_prefix(candidate),
+      (None, Some(identity)) => identity.adjusted_guid_prefix(candidate),
+      (None, None) => candidate.prefix,
+    }
+  }
+
+  // Set up the builtin plugins that the application did not replace. Checks
+  // that the participant may join the domain, and generates its keys if the
+  // governance requires protection.
+  pub fn enable_plugins(&mut self, domain_id: u16, participant_guid: GUID) -> Result<()> {
+    self.key_rotation.validate()?;
+    if self.authentication.is_none() {
+      if let Some(identity) = &self.identity {
+        self.authentication = Some(AuthenticationHandle::new(
+          authentication::BuiltinAuthentication::new(identity.clone(), participant_guid),
+        ));
+      }
+    }
+    if self.access_control.is_none() {
+      if let Some(permissions) = &self.permissions {
+        let identity = match &self.identity {
+          Some(identity) => identity,
+          None => return Error::bad_parameter("Access control requires an identity"),
+        };
+        let access_control =
+          access_control::AccessControl::new(permissions.clone(), identity, domain_id)?;
+        self.access_control = Some(AccessControlHandle::new(
+          access_control::BuiltinAccessControl::new(access_control),
+        ));
+      }
+    }
+    if self.authentication.is_none()
+      && (self.access_control.is_some() || self.cryptography.is_some())
+    {
+      return Error::bad_parameter("Access control and cryptography require authentication");
+    }
+    if self.cryptography.is_none() {
+      if let Some(access_control) = &self.access_control {
+        if access_control.uses_protection() {
+          match cryptographic::Cryptography::new(
+            participant_guid.prefix,
+            access_control.clone(),
+            self.key_rotation,
+          ) {
+            Ok(cryptography) => self.cryptography = Some(CryptographyHandle::new(cryptography)),
+            Err(e) => return log_and_err_internal!("Cannot generate keys: {}", e),
+          }
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Error from the security plugins, e.g. an invalid certificate, or a
+/// handshake message that does not verify.
+#[cfg(feature = "security")]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{message}")]
+pub struct SecurityError {
+  message: String,
+}
+
+#[cfg(feature = "security")]
+impl SecurityError {
+  pub fn new(message: impl Into<String>) -> Self {
+    Self {
+      message: message.into(),
+    }
+  }
+}
+
+#[cfg(feature = "security")]
+pub(crate) type SecurityResult<T> = std::result::Result<T, SecurityError>;
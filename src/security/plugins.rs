@@ -0,0 +1,340 @@
+use std::{fmt, ops::Deref, sync::Arc};
+
+use bytes::Bytes;
+
+use crate::{
+  dds::ddsdata::DDSData,
+  discovery::data_types::spdp_participant_data::SpdpDiscoveredParticipantData,
+  messages::submessages::submessage_elements::serialized_payload::SerializedPayload,
+  security::{
+    access_control::{ProtectionKind, TopicAccess, TopicProtection},
+    authentication::{HandshakeProgress, RemoteCredentials, SharedSecret},
+    generic_message::ParticipantGenericMessage,
+    types::DataHolder,
+    SecurityError, SecurityResult,
+  },
+  structure::guid::{EntityId, GuidPrefix, GUID},
+};
+
+// Service plugin interfaces of DDS Security spec v1.1 Section "8 Plugin
+// Architecture", simplified to what RustDDS needs. The builtin PKI-DH,
+// Permissions and AES-GCM-GMAC plugins implement these, and applications can
+// register their own instead with the DomainParticipantBuilder.
+
+/// Authentication plugin: announces the identity of the local participant and
+/// runs the authentication handshake with remote participants.
+///
+/// The builtin implementation is DDS:Auth:PKI-DH, which is used when
+/// [`DomainParticipantBuilder::identity`](crate::DomainParticipantBuilder::identity)
+/// is given. Register a custom one with
+/// [`DomainParticipantBuilder::authentication_plugin`](crate::DomainParticipantBuilder::authentication_plugin).
+///
+/// Handshake messages are sent best-effort on the builtin
+/// ParticipantStatelessMessage topic. The methods are called from the
+/// discovery thread, so they should be quick.
+pub trait AuthenticationPlugin: Send + Sync {
+  /// GUID prefix of the local participant, given the one it would have
+  /// without authentication. PKI-DH derives the prefix from the certificate
+  /// subject, so that participants cannot impersonate each other.
+  fn adjusted_guid_prefix(&self, candidate: GUID) -> GuidPrefix {
+    candidate.prefix
+  }
+
+  /// IdentityToken that the local participant announces in SPDP
+  fn identity_token(&self) -> DataHolder;
+
+  /// SPDP data of the local participant, given before any handshake and again
+  /// whenever it is announced.
+  fn set_participant_data(&self, data: &SpdpDiscoveredParticipantData);
+
+  /// Signed permissions document of the local participant, from the access
+  /// control plugin, to be sent in the handshake.
+  fn set_permissions_document(&self, document: Vec<u8>);
+
+  /// Start authenticating a newly discovered participant. Returns the first
+  /// handshake message, if the local participant is the one to send it. An
+  /// error means that the participant is ignored.
+  fn begin(
+    &self,
+    remote_guid: GUID,
+    identity_token: Option<&DataHolder>,
+  ) -> SecurityResult<Option<ParticipantGenericMessage>>;
+
+  /// Process a received handshake message. An error means that the message
+  /// was dropped.
+  fn process(&self, message: &ParticipantGenericMessage) -> SecurityResult<HandshakeProgress>;
+
+  /// Messages of unfinished handshakes, to be sent again, since lost messages
+  /// are not repaired
+  fn resend(&self) -> Vec<ParticipantGenericMessage>;
+
+  /// Identity and permissions of a remote participant that has completed the
+  /// handshake
+  fn remote_credentials(&self, guid_prefix: GuidPrefix) -> Option<RemoteCredentials>;
+
+  /// Secret agreed on with a remote participant that has completed the
+  /// handshake
+  fn shared_secret(&self, guid_prefix: GuidPrefix) -> Option<SharedSecret>;
+
+  /// Forget a remote participant, e.g. because it was lost.
+  fn remove(&self, guid_prefix: GuidPrefix);
+}
+
+/// Access control plugin: decides whether participants may use the domain and
+/// its topics, and how much protection the topics need.
+///
+/// The builtin implementation is DDS:Access:Permissions, which is used when
+/// [`DomainParticipantBuilder::permissions`](crate::DomainParticipantBuilder::permissions)
+/// is given. Register a custom one with
+/// [`DomainParticipantBuilder::access_control_plugin`](crate::DomainParticipantBuilder::access_control_plugin).
+/// Access control needs an authentication plugin, since remote participants
+/// are identified by their credentials.
+pub trait AccessControlPlugin: Send + Sync {
+  /// PermissionsToken that the local participant announces in SPDP
+  fn permissions_token(&self) -> DataHolder;
+
+  /// Permissions document of the local participant, sent to remote
+  /// participants in the authentication handshake
+  fn permissions_document(&self) -> Vec<u8>;
+
+  /// May participants without DDS Security join the domain?
+  fn allows_unauthenticated_participants(&self) -> bool;
+
+  /// May the local participant create a DataWriter or DataReader on the topic?
+  fn check_local_endpoint(&self, topic_name: &str, access: TopicAccess) -> SecurityResult<()>;
+
+  /// May an authenticated remote participant join the domain? The decision is
+  /// remembered for [`check_remote_endpoint`](Self::check_remote_endpoint).
+  fn validate_remote_permissions(
+    &self,
+    guid_prefix: GuidPrefix,
+    credentials: &RemoteCredentials,
+  ) -> SecurityResult<()>;
+
+  /// May a remote DataWriter or DataReader be matched?
+  fn check_remote_endpoint(
+    &self,
+    guid: GUID,
+    topic_name: &str,
+    access: TopicAccess,
+  ) -> SecurityResult<()>;
+
+  /// Forget a remote participant, e.g. because it was lost.
+  fn remove_remote_participant(&self, guid_prefix: GuidPrefix);
+
+  /// Protection of whole RTPS messages in the domain
+  fn rtps_protection(&self) -> ProtectionKind;
+
+  /// Protection of the endpoints of a topic
+  fn topic_protection(&self, topic_name: &str) -> TopicProtection;
+
+  /// Does the domain need a cryptographic plugin at all?
+  fn uses_protection(&self) -> bool;
+}
+
+/// Cryptographic plugin: protects RTPS messages, submessages and serialized
+/// payloads, and exchanges keys with authenticated participants.
+///
+/// The builtin implementation is DDS:Crypto:AES-GCM-GMAC, which is used when
+/// the access control plugin asks for protection. Register a custom one with
+/// [`DomainParticipantBuilder::cryptographic_plugin`](crate::DomainParticipantBuilder::cryptographic_plugin).
+///
+/// Crypto tokens are sent reliably on the builtin
+/// ParticipantVolatileMessageSecure topic, whose submessages the plugin
+/// protects with keys derived from the shared secret of the handshake. The
+/// methods are called from the discovery and event loop threads.
+pub trait CryptographicPlugin: Send + Sync {
+  /// How much protection may grow an RTPS message. Writers leave this much
+  /// room when they decide whether to fragment.
+  fn protection_overhead(&self) -> usize;
+
+  /// A local DataWriter or DataReader was created.
+  fn register_local_endpoint(&self, guid: GUID, topic_name: &str) -> SecurityResult<()>;
+
+  /// A local DataWriter or DataReader was deleted.
+  fn unregister_local_endpoint(&self, entity_id: EntityId);
+
+  /// A remote participant completed the authentication handshake.
+  fn register_remote_participant(
+    &self,
+    guid_prefix: GuidPrefix,
+    shared_secret: &SharedSecret,
+  ) -> SecurityResult<()>;
+
+  /// A remote participant was lost.
+  fn remove_remote_participant(&self, guid_prefix: GuidPrefix);
+
+  /// ParticipantCryptoTokens of the local participant, sent to each
+  /// authenticated participant
+  fn participant_crypto_tokens(&self) -> SecurityResult<Vec<DataHolder>>;
+
+  /// ParticipantCryptoTokens were received from a remote participant.
+  fn set_remote_participant_tokens(
+    &self,
+    guid_prefix: GuidPrefix,
+    tokens: &[DataHolder],
+  ) -> SecurityResult<()>;
+
+  /// A remote DataWriter or DataReader was discovered.
+  fn register_remote_endpoint(&self, guid: GUID, topic_name: &str);
+
+  /// DatawriterCryptoTokens or DatareaderCryptoTokens were received from a
+  /// remote endpoint.
+  fn set_remote_endpoint_tokens(&self, guid: GUID, tokens: &[DataHolder]) -> SecurityResult<()>;
+
+  /// Crypto tokens of a local endpoint, if they should be sent to the remote
+  /// endpoint now. This is asked repeatedly, so tokens that have been sent
+  /// already should not be returned again.
+  fn endpoint_tokens_to_send(
+    &self,
+    local: EntityId,
+    remote: GUID,
+  ) -> SecurityResult<Option<Vec<DataHolder>>>;
+
+  /// Protect an outgoing RTPS message. The destination participant is known
+  /// for messages that are sent to a single participant.
+  fn encode_message(
+    &self,
+    message: Vec<u8>,
+    destination: Option<GuidPrefix>,
+  ) -> SecurityResult<Vec<u8>>;
+
+  /// Decode a received RTPS message. An error means that the message is
+  /// dropped.
+  fn decode_message(&self, message: &[u8]) -> SecurityResult<Vec<u8>>;
+
+  /// Protect the serialized payload, including its encapsulation header, of a
+  /// sample written by a local DataWriter. Returns `None` if the payload is
+  /// sent as it is.
+  fn encode_payload(&self, writer: EntityId, payload: &[u8]) -> SecurityResult<Option<Vec<u8>>>;
+
+  /// Decode the serialized payload of a sample from a DataWriter. Returns
+  /// `None` if the payload was not protected.
+  fn decode_payload(&self, writer: GUID, payload: &[u8]) -> SecurityResult<Option<Vec<u8>>>;
+
+  /// Replace the keys that are due for rotation. Returns the remote
+  /// participants that must be sent new ParticipantCryptoTokens. New keys of
+  /// local endpoints should be returned from
+  /// [`endpoint_tokens_to_send`](Self::endpoint_tokens_to_send) again. This
+  /// is called periodically from the discovery thread. By default keys are
+  /// not rotated.
+  fn rotate_keys(&self) -> SecurityResult<Vec<GuidPrefix>> {
+    Ok(Vec::new())
+  }
+}
+
+// Shareable handles to the plugins, so that the participant builder and the
+// security configuration stay Clone and Debug.
+
+#[derive(Clone)]
+pub(crate) struct AuthenticationHandle(Arc<dyn AuthenticationPlugin>);
+
+impl AuthenticationHandle {
+  pub fn new(plugin: impl AuthenticationPlugin + 'static) -> Self {
+    Self(Arc::new(plugin))
+  }
+}
+
+impl Deref for AuthenticationHandle {
+  type Target = dyn AuthenticationPlugin;
+  fn deref(&self) -> &Self::Target {
+    &*self.0
+  }
+}
+
+impl fmt::Debug for AuthenticationHandle {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("AuthenticationPlugin")
+  }
+}
+
+#[derive(Clone)]
+pub(crate) struct AccessControlHandle(Arc<dyn AccessControlPlugin>);
+
+impl AccessControlHandle {
+  pub fn new(plugin: impl AccessControlPlugin + 'static) -> Self {
+    Self(Arc::new(plugin))
+  }
+}
+
+impl Deref for AccessControlHandle {
+  type Target = dyn AccessControlPlugin;
+  fn deref(&self) -> &Self::Target {
+    &*self.0
+  }
+}
+
+impl fmt::Debug for AccessControlHandle {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("AccessControlPlugin")
+  }
+}
+
+#[derive(Clone)]
+pub(crate) struct CryptographyHandle(Arc<dyn CryptographicPlugin>);
+
+impl CryptographyHandle {
+  pub fn new(plugin: impl CryptographicPlugin + 'static) -> Self {
+    Self(Arc::new(plugin))
+  }
+}
+
+impl Deref for CryptographyHandle {
+  type Target = dyn CryptographicPlugin;
+  fn deref(&self) -> &Self::Target {
+    &*self.0
+  }
+}
+
+impl fmt::Debug for CryptographyHandle {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("CryptographicPlugin")
+  }
+}
+
+impl CryptographyHandle {
+  // Protect the payload of a sample written by a local Writer, if its topic
+  // requires it. Disposes carry only the key, so they are not protected.
+  pub fn encode_data(&self, writer: EntityId, data: &DDSData) -> SecurityResult<Option<DDSData>> {
+    match data {
+      DDSData::Data {
+        serialized_payload,
+        key_hash,
+        ..
+      } => {
+        let payload = serialized_payload.bytes_slice(0, serialized_payload.len_serialized());
+        match self.encode_payload(writer, &payload)? {
+          Some(encoded) => Ok(Some(
+            DDSData::new(to_serialized_payload(encoded)?).with_key_hash(*key_hash),
+          )),
+          None => Ok(None),
+        }
+      }
+      _ => Ok(None),
+    }
+  }
+
+  // Decode the payload of a sample, if its Writer protects it.
+  pub fn decode_data(&self, writer: GUID, data: DDSData) -> SecurityResult<DDSData> {
+    let payload = match &data {
+      DDSData::Data {
+        serialized_payload, ..
+      } => {
+        let encoded = serialized_payload.bytes_slice(0, serialized_payload.len_serialized());
+        self.decode_payload(writer, &encoded)?
+      }
+      _ => None,
+    };
+    match (payload, data) {
+      (Some(payload), DDSData::Data { key_hash, .. }) => {
+        Ok(DDSData::new(to_serialized_payload(payload)?).with_key_hash(key_hash))
+      }
+      (_, data) => Ok(data),
+    }
+  }
+}
+
+fn to_serialized_payload(bytes: Vec<u8>) -> SecurityResult<SerializedPayload> {
+  SerializedPayload::from_bytes(&Bytes::from(bytes))
+    .map_err(|e| SecurityError::new(format!("Invalid payload: {}", e)))
+}
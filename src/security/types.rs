@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+
+// Data types of DDS Security spec v1.1 Section "7.2 Common Types". These are
+// sent in SPDP also by participants that have no security plugins, so they are
+// available without the "security" feature.
+
+/// Name and value of a property in a [`DataHolder`].
+///
+/// Only properties that are propagated are sent on the wire, so there is no
+/// `propagate` flag.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Property {
+  pub name: String,
+  pub value: String,
+}
+
+/// Name and binary value of a property in a [`DataHolder`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinaryProperty {
+  pub name: String,
+  pub value: Vec<u8>,
+}
+
+/// Container for security information, e.g. the IdentityToken that a secure
+/// participant announces in SPDP, and the messages of the authentication
+/// handshake. See DDS Security spec v1.1 Section "7.2.3 DataHolder".
+///
+/// The `class_id` tells which plugin the information is for, e.g.
+/// `"DDS:Auth:PKI-DH:1.0"`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DataHolder {
+  pub class_id: String,
+  pub properties: Vec<Property>,
+  pub binary_properties: Vec<BinaryProperty>,
+}
+
+impl DataHolder {
+  pub fn new(class_id: impl Into<String>) -> Self {
+    Self {
+      class_id: class_id.into(),
+      properties: Vec::new(),
+      binary_properties: Vec::new(),
+    }
+  }
+
+  #[must_use]
+  pub fn with_property(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+    self.properties.push(Property {
+      name: name.into(),
+      value: value.into(),
+    });
+    self
+  }
+
+  #[must_use]
+  pub fn with_binary_property(
+    mut self,
+    name: impl Into<String>,
+    value: impl Into<Vec<u8>>,
+  ) -> Self {
+    self.binary_properties.push(BinaryProperty {
+      name: name.into(),
+      value: value.into(),
+    });
+    self
+  }
+
+  /// Value of the first property with this name
+  pub fn property(&self, name: &str) -> Option<&str> {
+    self
+      .properties
+      .iter()
+      .find(|p| p.name == name)
+      .map(|p| p.value.as_str())
+  }
+
+  /// Value of the first binary property with this name
+  pub fn binary_property(&self, name: &str) -> Option<&[u8]> {
+    self
+      .binary_properties
+      .iter()
+      .find(|p| p.name == name)
+      .map(|p| p.value.as_slice())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use byteorder::BigEndian;
+
+  use super::*;
+  use crate::serialization::{cdr_deserializer::deserialize_from_big_endian, cdr_serializer::to_bytes};
+
+  #[test]
+  fn data_holder_cdr() {
+    let token = DataHolder::new("DDS:Auth:PKI-DH:1.0")
+      .with_property("dds.cert.sn", "CN=a")
+      .with_binary_property("c", vec![1, 2, 3]);
+    let bytes = to_bytes::<DataHolder, BigEndian>(&token).unwrap();
+    #[rustfmt::skip]
+    let expected: Vec<u8> = vec![
+      0, 0, 0, 20, b'D', b'D', b'S', b':', b'A', b'u', b't', b'h', b':', b'P', b'K', b'I',
+      b'-', b'D', b'H', b':', b'1', b'.', b'0', 0,
+      0, 0, 0, 1, // properties
+      0, 0, 0, 12, b'd', b'd', b's', b'.', b'c', b'e', b'r', b't', b'.', b's', b'n', 0,
+      0, 0, 0, 5, b'C', b'N', b'=', b'a', 0, 0, 0, 0,
+      0, 0, 0, 1, // binary properties
+      0, 0, 0, 2, b'c', 0, 0, 0,
+      0, 0, 0, 3, 1, 2, 3,
+    ];
+    assert_eq!(bytes, expected);
+    assert_eq!(
+      deserialize_from_big_endian::<DataHolder>(&bytes).unwrap(),
+      token
+    );
+    assert_eq!(token.property("dds.cert.sn"), Some("CN=a"));
+    assert_eq!(token.binary_property("c"), Some(&[1, 2, 3][..]));
+    assert_eq!(token.property("c"), None);
+  }
+}
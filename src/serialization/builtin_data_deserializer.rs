@@ -8,8 +8,9 @@ use crate::{
   dds::{
     qos::{
       policy::{
-        Deadline, DestinationOrder, Durability, History, LatencyBudget, Lifespan, Liveliness,
-        Ownership, Presentation, Reliability, ResourceLimits, TimeBasedFilter,
+        Deadline, DestinationOrder, Durability, GroupData, History, LatencyBudget, Lifespan,
+        Liveliness, Ownership, Presentation, Reliability, ResourceLimits, TimeBasedFilter,
+        TopicData, UserData,
       },
       QosPolicyBuilder,
     },
@@ -31,6 +32,7 @@ use crate::{
     submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
     vendor_id::VendorId,
   },
+  security::types::DataHolder,
   serialization::error::Error,
   structure::{
     builtin_endpoint::{BuiltinEndpointQos, BuiltinEndpointSet},
@@ -59,7 +61,14 @@ pub struct BuiltinDataDeserializer {
   pub manual_liveliness_count: Option<i32>,
   pub builtin_endpoint_qos: Option<BuiltinEndpointQos>,
   pub entity_name: Option<String>,
+  pub user_data: Option<UserData>,
+  pub domain_tag: Option<String>,
+  pub identity_token: Option<DataHolder>,
+  pub permissions_token: Option<DataHolder>,
   pub sentinel: Option<u32>,
+  // Unknown parameter with the must-understand flag. The whole list is
+  // rejected.
+  pub not_understood: Option<ParameterId>,
 
   pub endpoint_guid: Option<GUID>,
 
@@ -85,6 +94,8 @@ pub struct BuiltinDataDeserializer {
   pub lifespan: Option<Lifespan>,
   pub history: Option<History>,
   pub resource_limits: Option<ResourceLimits>,
+  pub topic_data: Option<TopicData>,
+  pub group_data: Option<GroupData>,
 
   pub content_filter_property: Option<ContentFilterProperty>,
 
@@ -136,6 +147,10 @@ impl BuiltinDataDeserializer {
       manual_liveliness_count: self.manual_liveliness_count.unwrap_or(0),
       builtin_endpoint_qos: self.builtin_endpoint_qos,
       entity_name: self.entity_name.clone(),
+      user_data: self.user_data.clone(),
+      domain_tag: self.domain_tag.clone().unwrap_or_default(),
+      identity_token: self.identity_token.clone(),
+      permissions_token: self.permissions_token.clone(),
     })
   }
 
@@ -228,6 +243,21 @@ impl BuiltinDataDeserializer {
       None => qos,
     };
 
+    let qos = match &self.user_data {
+      Some(ud) => qos.user_data(ud.clone()),
+      None => qos,
+    };
+
+    let qos = match &self.topic_data {
+      Some(td) => qos.topic_data(td.clone()),
+      None => qos,
+    };
+
+    let qos = match &self.group_data {
+      Some(gd) => qos.group_data(gd.clone()),
+      None => qos,
+    };
+
     let qos = qos.build();
 
     let key = match self.endpoint_guid {
@@ -282,6 +312,9 @@ impl BuiltinDataDeserializer {
       ownership: self.ownership,
       destination_order: self.destination_order,
       presentation: self.presentation,
+      user_data: self.user_data.clone(),
+      topic_data: self.topic_data.clone(),
+      group_data: self.group_data.clone(),
       service_instance_name: self.service_instance_name.clone(),
       related_datareader_key: self.related_datareader_key,
       topic_aliases: self.topic_aliases.clone(),
@@ -308,6 +341,7 @@ impl BuiltinDataDeserializer {
       history: self.history,
       resource_limits: self.resource_limits,
       ownership: self.ownership,
+      topic_data: self.topic_data,
     })
   }
 
@@ -335,26 +369,46 @@ impl BuiltinDataDeserializer {
     })
   }
 
-  pub fn parse_data_little_endian(self, buffer: &[u8]) -> Self {
-    self.parse_data(buffer, RepresentationIdentifier::CDR_LE)
+  pub fn parse_data_little_endian(self, buffer: &[u8]) -> Result<Self, Error> {
+    self.parse_data(buffer, RepresentationIdentifier::PL_CDR_LE)
   }
 
-  pub fn parse_data_big_endian(self, buffer: &[u8]) -> Self {
-    self.parse_data(buffer, RepresentationIdentifier::CDR_BE)
+  pub fn parse_data_big_endian(self, buffer: &[u8]) -> Result<Self, Error> {
+    self.parse_data(buffer, RepresentationIdentifier::PL_CDR_BE)
   }
 
-  pub fn parse_data(mut self, buffer: &[u8], rep: RepresentationIdentifier) -> Self {
+  /// Parses a parameter list up to PID_SENTINEL, or the end of the buffer if
+  /// the sentinel is missing. Unknown parameters are skipped, unless they
+  /// must be understood (RTPS spec v2.5 Section 9.6.2.2.1), in which case the
+  /// whole list is rejected.
+  pub fn parse_data(mut self, buffer: &[u8], rep: RepresentationIdentifier) -> Result<Self, Error> {
     let mut buffer = buffer.to_vec();
-    while self.sentinel.is_none() && !buffer.is_empty() {
+    while self.sentinel.is_none() && self.not_understood.is_none() && !buffer.is_empty() {
       self = self.read_next(&mut buffer, rep);
     }
 
-    self
+    match self.not_understood {
+      Some(pid) => Err(log_and_err_discovery!(
+        "Parameter list has parameter {:?}, which must be understood, but is not known.",
+        pid
+      )),
+      None => Ok(self),
+    }
   }
 
   pub fn read_next(mut self, buffer: &mut Vec<u8>, rep: RepresentationIdentifier) -> Self {
-    let parameter_id = Self::read_parameter_id(buffer, rep).unwrap();
-    let mut parameter_length: usize = Self::read_parameter_length(buffer, rep).unwrap() as usize;
+    let (parameter_id, parameter_length) = match (
+      Self::read_parameter_id(buffer, rep),
+      Self::read_parameter_length(buffer, rep),
+    ) {
+      (Some(id), Some(length)) if buffer.len() >= 4 => (id, length),
+      _ => {
+        warn!("Truncated parameter header in parameter list: {:?}", buffer);
+        buffer.clear();
+        return self;
+      }
+    };
+    let mut parameter_length = usize::from(parameter_length);
 
     if (parameter_length + 4) > buffer.len() {
       parameter_length = buffer.len() - 4;
@@ -478,6 +532,33 @@ impl BuiltinDataDeserializer {
           return self;
         }
       }
+      ParameterId::PID_DOMAIN_TAG => {
+        let tag: Result<String, Error> =
+          CDRDeserializerAdapter::from_bytes(&buffer[4..4 + parameter_length], rep);
+        if let Ok(t) = tag {
+          self.domain_tag = Some(t);
+          buffer.drain(..4 + parameter_length);
+          return self;
+        }
+      }
+      ParameterId::PID_IDENTITY_TOKEN => {
+        let token: Result<DataHolder, Error> =
+          CDRDeserializerAdapter::from_bytes(&buffer[4..4 + parameter_length], rep);
+        if let Ok(t) = token {
+          self.identity_token = Some(t);
+          buffer.drain(..4 + parameter_length);
+          return self;
+        }
+      }
+      ParameterId::PID_PERMISSIONS_TOKEN => {
+        let token: Result<DataHolder, Error> =
+          CDRDeserializerAdapter::from_bytes(&buffer[4..4 + parameter_length], rep);
+        if let Ok(t) = token {
+          self.permissions_token = Some(t);
+          buffer.drain(..4 + parameter_length);
+          return self;
+        }
+      }
       ParameterId::PID_ENDPOINT_GUID => {
         let guid: Result<GUID, Error> =
           CDRDeserializerAdapter::from_bytes(&buffer[4..4 + parameter_length], rep);
@@ -682,6 +763,33 @@ impl BuiltinDataDeserializer {
           return self;
         }
       }
+      ParameterId::PID_USER_DATA => {
+        let user_data: Result<UserData, Error> =
+          CDRDeserializerAdapter::from_bytes(&buffer[4..4 + parameter_length], rep);
+        if let Ok(ud) = user_data {
+          self.user_data = Some(ud);
+          buffer.drain(..4 + parameter_length);
+          return self;
+        }
+      }
+      ParameterId::PID_TOPIC_DATA => {
+        let topic_data: Result<TopicData, Error> =
+          CDRDeserializerAdapter::from_bytes(&buffer[4..4 + parameter_length], rep);
+        if let Ok(td) = topic_data {
+          self.topic_data = Some(td);
+          buffer.drain(..4 + parameter_length);
+          return self;
+        }
+      }
+      ParameterId::PID_GROUP_DATA => {
+        let group_data: Result<GroupData, Error> =
+          CDRDeserializerAdapter::from_bytes(&buffer[4..4 + parameter_length], rep);
+        if let Ok(gd) = group_data {
+          self.group_data = Some(gd);
+          buffer.drain(..4 + parameter_length);
+          return self;
+        }
+      }
       ParameterId::PID_CONTENT_FILTER_PROPERTY => {
         let content_filter: Result<ContentFilterProperty, Error> =
           CDRDeserializerAdapter::from_bytes(&buffer[4..4 + parameter_length], rep);
@@ -774,6 +882,11 @@ impl BuiltinDataDeserializer {
         buffer.drain(..4 + parameter_length);
         return self;
       }
+      unknown if unknown.must_understand() => {
+        self.not_understood = Some(unknown);
+        buffer.clear();
+        return self;
+      }
       _ => (), /* TODO: Add some logging. But not much, since there may
                 * be some legitimate cases where we encounter paraneters that we do not
                 * know. */
@@ -784,6 +897,9 @@ impl BuiltinDataDeserializer {
   }
 
   pub fn read_parameter_id(buffer: &[u8], rep: RepresentationIdentifier) -> Option<ParameterId> {
+    if buffer.len() < 2 {
+      return None;
+    }
     let par: Result<ParameterId, Error> = CDRDeserializerAdapter::from_bytes(&buffer[..2], rep);
     match par {
       Ok(val) => Some(val),
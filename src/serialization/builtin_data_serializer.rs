@@ -4,12 +4,16 @@ use bytes::Bytes;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 use byteorder::{BigEndian, LittleEndian};
-use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+use serde::{
+  ser::{SerializeStruct, SerializeTuple},
+  Deserialize, Serialize, Serializer,
+};
 
 use crate::{
   dds::qos::policy::{
-    Deadline, DestinationOrder, Durability, History, LatencyBudget, Lifespan, Liveliness,
-    Ownership, Presentation, QosData, Reliability, ResourceLimits, TimeBasedFilter,
+    Deadline, DestinationOrder, Durability, GroupData, History, LatencyBudget, Lifespan,
+    Liveliness, Ownership, Presentation, QosData, Reliability, ResourceLimits, TimeBasedFilter,
+    TopicData, UserData,
   },
   discovery::{
     content_filter_property::{ContentFilterProperty, ContentFilterPropertyData},
@@ -26,7 +30,12 @@ use crate::{
     submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
     vendor_id::{VendorId, VendorIdData},
   },
-  serialization::{cdr_serializer::CdrSerializer, error as ser, error::Result},
+  security::types::DataHolder,
+  serialization::{
+    cdr_serializer::{to_bytes, CdrSerializer},
+    error as ser,
+    error::Result,
+  },
   structure::{
     builtin_endpoint::{
       BuiltinEndpointQos, BuiltinEndpointQosData, BuiltinEndpointSet, BuiltinEndpointSetData,
@@ -113,6 +122,71 @@ struct EntityName {
   entity_name: String,
 }
 
+#[derive(Serialize)]
+struct DomainTag<'a> {
+  parameter_id: ParameterId,
+  parameter_length: u16,
+  domain_tag: &'a str,
+}
+
+// Zero octets that pad a parameter to a multiple of 4, when its contents do
+// not end aligned.
+struct Padding(usize);
+
+impl Serialize for Padding {
+  fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    let mut tuple = serializer.serialize_tuple(self.0)?;
+    for _ in 0..self.0 {
+      tuple.serialize_element(&0u8)?;
+    }
+    tuple.end()
+  }
+}
+
+// Security tokens in CDR
+#[derive(Serialize)]
+struct TokenData<'a> {
+  parameter_id: ParameterId,
+  parameter_length: u16,
+  token: &'a DataHolder,
+  padding: Padding,
+}
+
+impl<'a> TokenData<'a> {
+  pub fn new(parameter_id: ParameterId, token: &'a DataHolder) -> Result<Self> {
+    // Contents are at most 4-aligned, so the length does not depend on where
+    // the parameter is.
+    let length = to_bytes::<DataHolder, LittleEndian>(token)?.len();
+    let padded_length = (length + 3) & !3;
+    Ok(Self {
+      parameter_id,
+      parameter_length: padded_length as u16,
+      token,
+      padding: Padding(padded_length - length),
+    })
+  }
+}
+
+// USER_DATA, TOPIC_DATA and GROUP_DATA are all sequence<octet>
+#[derive(Serialize)]
+struct OctetSeqData<'a> {
+  parameter_id: ParameterId,
+  parameter_length: u16,
+  value: &'a [u8],
+}
+
+impl<'a> OctetSeqData<'a> {
+  pub fn new(parameter_id: ParameterId, value: &'a [u8]) -> Self {
+    // sequence length + octets, padded to multiple of 4
+    let parameter_length = 4 + ((value.len() as u16 + 3) & !3);
+    Self {
+      parameter_id,
+      parameter_length,
+      value,
+    }
+  }
+}
+
 #[derive(Default)]
 pub struct BuiltinDataSerializer<'a> {
   // Participant Data
@@ -129,6 +203,10 @@ pub struct BuiltinDataSerializer<'a> {
   pub manual_liveliness_count: Option<i32>,
   pub builtin_endpoint_qos: Option<BuiltinEndpointQos>,
   pub entity_name: Option<&'a String>,
+  pub user_data: Option<&'a UserData>,
+  pub domain_tag: Option<&'a String>,
+  pub identity_token: Option<&'a DataHolder>,
+  pub permissions_token: Option<&'a DataHolder>,
 
   pub endpoint_guid: Option<GUID>,
 
@@ -154,6 +232,8 @@ pub struct BuiltinDataSerializer<'a> {
   pub lifespan: Option<Lifespan>,
   pub history: Option<History>,
   pub resource_limits: Option<ResourceLimits>,
+  pub topic_data: Option<&'a TopicData>,
+  pub group_data: Option<&'a GroupData>,
 
   pub content_filter_property: Option<&'a ContentFilterProperty>,
 }
@@ -179,6 +259,10 @@ impl<'a> BuiltinDataSerializer<'a> {
     merge_field!(manual_liveliness_count);
     merge_field!(builtin_endpoint_qos);
     merge_field!(entity_name);
+    merge_field!(user_data);
+    merge_field!(domain_tag);
+    merge_field!(identity_token);
+    merge_field!(permissions_token);
     merge_field!(endpoint_guid);
     merge_field!(multicast_locator_list);
     merge_field!(data_max_size_serialized);
@@ -196,6 +280,8 @@ impl<'a> BuiltinDataSerializer<'a> {
     merge_field!(lifespan);
     merge_field!(history);
     merge_field!(resource_limits);
+    merge_field!(topic_data);
+    merge_field!(group_data);
     merge_field!(content_filter_property);
 
     self
@@ -218,6 +304,11 @@ impl<'a> BuiltinDataSerializer<'a> {
       manual_liveliness_count: Some(participant_data.manual_liveliness_count),
       builtin_endpoint_qos: participant_data.builtin_endpoint_qos,
       entity_name: participant_data.entity_name.as_ref(),
+      user_data: participant_data.user_data.as_ref(),
+      // The default (empty) tag is not sent.
+      domain_tag: Some(&participant_data.domain_tag).filter(|tag| !tag.is_empty()),
+      identity_token: participant_data.identity_token.as_ref(),
+      permissions_token: participant_data.permissions_token.as_ref(),
       ..BuiltinDataSerializer::default()
     }
   }
@@ -275,6 +366,9 @@ impl<'a> BuiltinDataSerializer<'a> {
       time_based_filter: qos.time_based_filter(),
       presentation: qos.presentation(),
       lifespan: qos.lifespan(),
+      user_data: subscription_topic_data.user_data(),
+      topic_data: subscription_topic_data.topic_data(),
+      group_data: subscription_topic_data.group_data(),
       ..BuiltinDataSerializer::default()
     }
   }
@@ -297,6 +391,9 @@ impl<'a> BuiltinDataSerializer<'a> {
       time_based_filter: publication_topic_data.time_based_filter,
       presentation: publication_topic_data.presentation,
       lifespan: publication_topic_data.lifespan,
+      user_data: publication_topic_data.user_data.as_ref(),
+      topic_data: publication_topic_data.topic_data.as_ref(),
+      group_data: publication_topic_data.group_data.as_ref(),
       ..BuiltinDataSerializer::default()
     }
   }
@@ -317,6 +414,7 @@ impl<'a> BuiltinDataSerializer<'a> {
       lifespan: topic_data.lifespan,
       history: topic_data.history,
       resource_limits: topic_data.resource_limits,
+      topic_data: topic_data.topic_data.as_ref(),
       ..BuiltinDataSerializer::default()
     }
   }
@@ -396,6 +494,10 @@ impl<'a> BuiltinDataSerializer<'a> {
     self.add_manual_liveliness_count::<S>(&mut s);
     self.add_builtin_endpoint_qos::<S>(&mut s);
     self.add_entity_name::<S>(&mut s);
+    self.add_user_data::<S>(&mut s);
+    self.add_domain_tag::<S>(&mut s);
+    self.add_identity_token::<S>(&mut s);
+    self.add_permissions_token::<S>(&mut s);
 
     self.add_endpoint_guid::<S>(&mut s);
     self.add_unicast_locator_list::<S>(&mut s);
@@ -417,11 +519,15 @@ impl<'a> BuiltinDataSerializer<'a> {
     self.add_lifespan::<S>(&mut s);
     self.add_history::<S>(&mut s);
     self.add_resource_limits::<S>(&mut s);
+    self.add_topic_data::<S>(&mut s);
+    self.add_group_data::<S>(&mut s);
 
     self.add_content_filter_property::<S>(&mut s);
 
     if add_sentinel {
-      s.serialize_field("sentinel", &1_u32).unwrap();
+      // PID_SENTINEL and zero length, in the byte order of the list
+      s.serialize_field("sentinel", &(ParameterId::PID_SENTINEL, 0_u16))
+        .unwrap();
     }
 
     s.end()
@@ -447,6 +553,10 @@ impl<'a> BuiltinDataSerializer<'a> {
     count += usize::from(self.manual_liveliness_count.is_some());
     count += usize::from(self.builtin_endpoint_qos.is_some());
     count += usize::from(self.entity_name.is_some());
+    count += usize::from(self.user_data.is_some());
+    count += usize::from(self.domain_tag.is_some());
+    count += usize::from(self.identity_token.is_some());
+    count += usize::from(self.permissions_token.is_some());
 
     count += usize::from(self.endpoint_guid.is_some());
     count += self.unicast_locator_list.unwrap_or(&empty_ll).len();
@@ -468,6 +578,8 @@ impl<'a> BuiltinDataSerializer<'a> {
     count += usize::from(self.lifespan.is_some());
     count += usize::from(self.history.is_some());
     count += usize::from(self.resource_limits.is_some());
+    count += usize::from(self.topic_data.is_some());
+    count += usize::from(self.group_data.is_some());
 
     count += usize::from(self.content_filter_property.is_some());
 
@@ -620,6 +732,46 @@ impl<'a> BuiltinDataSerializer<'a> {
     }
   }
 
+  fn add_user_data<S: Serializer>(&self, s: &mut S::SerializeStruct) {
+    if let Some(ud) = self.user_data {
+      s.serialize_field(
+        "user_data",
+        &OctetSeqData::new(ParameterId::PID_USER_DATA, &ud.value),
+      )
+      .unwrap();
+    }
+  }
+
+  fn add_domain_tag<S: Serializer>(&self, s: &mut S::SerializeStruct) {
+    if let Some(tag) = self.domain_tag {
+      let domain_tag = DomainTag {
+        parameter_id: ParameterId::PID_DOMAIN_TAG,
+        // string length, characters and terminating NUL, padded to multiple of 4
+        parameter_length: 4 + ((tag.len() as u16 + 1 + 3) & !3),
+        domain_tag: tag,
+      };
+      s.serialize_field("domain_tag", &domain_tag).unwrap();
+    }
+  }
+
+  fn add_identity_token<S: Serializer>(&self, s: &mut S::SerializeStruct) {
+    if let Some(token) = self.identity_token {
+      match TokenData::new(ParameterId::PID_IDENTITY_TOKEN, token) {
+        Ok(token_data) => s.serialize_field("identity_token", &token_data).unwrap(),
+        Err(e) => error!("Cannot serialize IdentityToken: {:?}", e),
+      }
+    }
+  }
+
+  fn add_permissions_token<S: Serializer>(&self, s: &mut S::SerializeStruct) {
+    if let Some(token) = self.permissions_token {
+      match TokenData::new(ParameterId::PID_PERMISSIONS_TOKEN, token) {
+        Ok(token_data) => s.serialize_field("permissions_token", &token_data).unwrap(),
+        Err(e) => error!("Cannot serialize PermissionsToken: {:?}", e),
+      }
+    }
+  }
+
   fn add_endpoint_guid<S: Serializer>(&self, s: &mut S::SerializeStruct) {
     if let Some(guid) = self.endpoint_guid {
       s.serialize_field(
@@ -912,6 +1064,26 @@ impl<'a> BuiltinDataSerializer<'a> {
     }
   }
 
+  fn add_topic_data<S: Serializer>(&self, s: &mut S::SerializeStruct) {
+    if let Some(td) = self.topic_data {
+      s.serialize_field(
+        "topic_data",
+        &OctetSeqData::new(ParameterId::PID_TOPIC_DATA, &td.value),
+      )
+      .unwrap();
+    }
+  }
+
+  fn add_group_data<S: Serializer>(&self, s: &mut S::SerializeStruct) {
+    if let Some(gd) = self.group_data {
+      s.serialize_field(
+        "group_data",
+        &OctetSeqData::new(ParameterId::PID_GROUP_DATA, &gd.value),
+      )
+      .unwrap();
+    }
+  }
+
   fn add_content_filter_property<S: Serializer>(&self, s: &mut S::SerializeStruct) {
     if let Some(cfp) = self.content_filter_property {
       s.serialize_field(
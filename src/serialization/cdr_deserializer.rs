@@ -20,6 +20,11 @@ use crate::{
 /// work as a [`with_key::DeserializerAdapter`] and
 /// [`no_key::DeserializerAdapter`].
 ///
+/// Types may evolve by appending members. Data written with an older version
+/// of the type then ends before all members are read. The missing members are
+/// filled in from `#[serde(default)]`, and deserialization fails only if
+/// there is no default. Extra data at the end is ignored.
+///
 /// CdrDeserializer cannot directly implement
 /// the trait itself, because CdrDeserializer has the type parameter BO open,
 /// and the adapter needs to be bi-endian.
@@ -28,9 +33,10 @@ pub struct CDRDeserializerAdapter<D> {
   // no-one home
 }
 
-const REPR_IDS: [RepresentationIdentifier; 3] = [
+const REPR_IDS: [RepresentationIdentifier; 4] = [
   RepresentationIdentifier::CDR_BE,
   RepresentationIdentifier::CDR_LE,
+  RepresentationIdentifier::PL_CDR_BE,
   RepresentationIdentifier::PL_CDR_LE,
 ];
 
@@ -47,7 +53,9 @@ where
       RepresentationIdentifier::CDR_LE | RepresentationIdentifier::PL_CDR_LE => {
         deserialize_from_little_endian(input_bytes)
       }
-      RepresentationIdentifier::CDR_BE => deserialize_from_big_endian(input_bytes),
+      RepresentationIdentifier::CDR_BE | RepresentationIdentifier::PL_CDR_BE => {
+        deserialize_from_big_endian(input_bytes)
+      }
       repr_id => Err(Error::Message(format!(
         "Unknown representaiton identifier {:?}.",
         repr_id
@@ -66,7 +74,9 @@ where
       RepresentationIdentifier::CDR_LE | RepresentationIdentifier::PL_CDR_LE => {
         deserialize_from_little_endian(input_bytes)
       }
-      RepresentationIdentifier::CDR_BE => deserialize_from_big_endian(input_bytes),
+      RepresentationIdentifier::CDR_BE | RepresentationIdentifier::PL_CDR_BE => {
+        deserialize_from_big_endian(input_bytes)
+      }
       repr_id => Err(Error::Message(format!(
         "Unknown representaiton identifier {:?}.",
         repr_id
@@ -374,7 +384,7 @@ where
   where
     V: Visitor<'de>,
   {
-    visitor.visit_seq(SequenceHelper::new(self, fields.len()))
+    visitor.visit_seq(SequenceHelper::new_struct(self, fields.len()))
   }
 
   ///Enum values are encoded as unsigned longs. (u32)
@@ -488,6 +498,7 @@ struct SequenceHelper<'a, 'de: 'a, BO> {
   de: &'a mut CdrDeserializer<'de, BO>,
   element_counter: usize,
   expected_count: usize,
+  struct_members: bool,
 }
 
 impl<'a, 'de, BO> SequenceHelper<'a, 'de, BO> {
@@ -496,6 +507,18 @@ impl<'a, 'de, BO> SequenceHelper<'a, 'de, BO> {
       de,
       element_counter: 0,
       expected_count,
+      struct_members: false,
+    }
+  }
+
+  // Struct members may run out before all fields are read, if the data was
+  // written using an older version of an appendable type. Then we report the
+  // end of members, and serde fills in the rest from #[serde(default)], or
+  // fails if there is no default.
+  fn new_struct(de: &'a mut CdrDeserializer<'de, BO>, expected_count: usize) -> Self {
+    SequenceHelper {
+      struct_members: true,
+      ..Self::new(de, expected_count)
     }
   }
 }
@@ -516,7 +539,15 @@ where
       Ok(None)
     } else {
       self.element_counter += 1;
-      seed.deserialize(&mut *self.de).map(Some)
+      // Less than 4 bytes left can only be end padding.
+      let at_end = self.struct_members && self.de.input.len() < 4;
+      match seed.deserialize(&mut *self.de) {
+        Err(Error::Eof) if at_end => {
+          self.expected_count = self.element_counter - 1;
+          Ok(None)
+        }
+        result => result.map(Some),
+      }
     }
   }
 }
@@ -988,6 +1019,57 @@ mod tests {
     assert_eq!(input, deserialized);
   }
 
+  #[test]
+  fn cdr_deserialization_appended_members() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct V1 {
+      a: u16,
+    }
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct V2 {
+      a: u16,
+      #[serde(default)]
+      b: u32,
+      #[serde(default)]
+      c: Option<String>,
+    }
+
+    // older writer: missing members get defaults
+    let v1_bytes = to_bytes::<V1, LittleEndian>(&V1 { a: 3 }).unwrap();
+    let v2: V2 = deserialize_from_little_endian(&v1_bytes).unwrap();
+    assert_eq!(
+      v2,
+      V2 {
+        a: 3,
+        b: 0,
+        c: None
+      }
+    );
+    // also with padding at the end
+    let mut padded = v1_bytes.clone();
+    padded.extend_from_slice(&[0, 0]);
+    let v2_padded: V2 = deserialize_from_little_endian(&padded).unwrap();
+    assert_eq!(v2_padded, v2);
+
+    // newer writer: extra members are ignored
+    let v2_bytes = to_bytes::<V2, LittleEndian>(&V2 {
+      a: 4,
+      b: 5,
+      c: Some("c".to_string()),
+    })
+    .unwrap();
+    let v1: V1 = deserialize_from_little_endian(&v2_bytes).unwrap();
+    assert_eq!(v1, V1 { a: 4 });
+
+    // missing member without default is still an error
+    #[derive(Deserialize, Debug)]
+    struct Strict {
+      _a: u16,
+      _b: u32,
+    }
+    assert!(deserialize_from_little_endian::<Strict>(&v1_bytes).is_err());
+  }
+
   /*
   #[test]
   fn cdr_deserialization_bytes(){
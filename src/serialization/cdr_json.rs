@@ -0,0 +1,363 @@
+//! Transcoding CDR-encoded samples to JSON, driven by a run-time schema.
+//!
+//! Generic tools, such as data recorders or inspectors, do not have the data
+//! types of the Topics they handle compiled in. A [`CdrSchema`] describes the
+//! layout of a data type, with member names, so that serialized samples can
+//! be rendered as [`serde_json::Value`] without a Rust type.
+//!
+//! Decoding is done by the regular [`CdrDeserializer`], so the result is
+//! exactly what a typed DataReader would see.
+
+use std::{fmt, marker::PhantomData, sync::Arc};
+
+use byteorder::{BigEndian, LittleEndian};
+use serde::de::{self, DeserializeSeed, Deserializer, SeqAccess, Visitor};
+use serde_json::{Map, Number, Value};
+
+use crate::{
+  dds::traits::serde_adapters::no_key,
+  messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
+  serialization::{
+    cdr_deserializer::CdrDeserializer,
+    error::{Error, Result},
+  },
+};
+
+/// Layout description of a CDR-encoded data type, with member names.
+///
+/// The members are named, so that decoded samples can be rendered as JSON
+/// objects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CdrSchema {
+  Bool,
+  I8,
+  U8,
+  I16,
+  U16,
+  I32,
+  U32,
+  I64,
+  U64,
+  F32,
+  F64,
+  /// Rendered as a JSON string of one character.
+  Char,
+  String,
+  /// Rendered as a JSON object with the given member names.
+  Struct(Vec<(String, CdrSchema)>),
+  /// Fixed-length array, rendered as a JSON array.
+  Array(Box<CdrSchema>, usize),
+  /// Variable-length sequence, rendered as a JSON array.
+  Sequence(Box<CdrSchema>),
+  /// Optional value, as serialized from a Rust `Option`. Rendered as `null`
+  /// if absent.
+  Optional(Box<CdrSchema>),
+  /// Enumeration, encoded as u32. Rendered as the name of the enumerator.
+  Enum(Vec<String>),
+}
+
+impl CdrSchema {
+  /// Convenience constructor for a struct schema.
+  pub fn structure<S: Into<String>>(members: impl IntoIterator<Item = (S, CdrSchema)>) -> Self {
+    Self::Struct(
+      members
+        .into_iter()
+        .map(|(name, schema)| (name.into(), schema))
+        .collect(),
+    )
+  }
+
+  /// Decodes a serialized sample of this type into JSON.
+  ///
+  /// Supported encodings are `CDR_LE`, `CDR_BE` and the corresponding
+  /// `PL_CDR` identifiers, like in
+  /// [`CDRDeserializerAdapter`](super::CDRDeserializerAdapter).
+  pub fn to_json(&self, input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<Value> {
+    match encoding {
+      RepresentationIdentifier::CDR_LE | RepresentationIdentifier::PL_CDR_LE => {
+        self.deserialize(&mut CdrDeserializer::<LittleEndian>::new(input_bytes))
+      }
+      RepresentationIdentifier::CDR_BE | RepresentationIdentifier::PL_CDR_BE => {
+        self.deserialize(&mut CdrDeserializer::<BigEndian>::new(input_bytes))
+      }
+      repr_id => Err(Error::Message(format!(
+        "Unknown representaiton identifier {:?}.",
+        repr_id
+      ))),
+    }
+  }
+}
+
+fn float_value(f: f64) -> Value {
+  // JSON has no representation for NaN or infinities
+  Number::from_f64(f).map_or(Value::Null, Value::Number)
+}
+
+impl<'de> DeserializeSeed<'de> for &CdrSchema {
+  type Value = Value;
+
+  fn deserialize<D>(self, deserializer: D) -> std::result::Result<Value, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    use serde::Deserialize;
+
+    match self {
+      CdrSchema::Bool => bool::deserialize(deserializer).map(Value::from),
+      CdrSchema::I8 => i8::deserialize(deserializer).map(Value::from),
+      CdrSchema::U8 => u8::deserialize(deserializer).map(Value::from),
+      CdrSchema::I16 => i16::deserialize(deserializer).map(Value::from),
+      CdrSchema::U16 => u16::deserialize(deserializer).map(Value::from),
+      CdrSchema::I32 => i32::deserialize(deserializer).map(Value::from),
+      CdrSchema::U32 => u32::deserialize(deserializer).map(Value::from),
+      CdrSchema::I64 => i64::deserialize(deserializer).map(Value::from),
+      CdrSchema::U64 => u64::deserialize(deserializer).map(Value::from),
+      CdrSchema::F32 => f32::deserialize(deserializer).map(|f| float_value(f.into())),
+      CdrSchema::F64 => f64::deserialize(deserializer).map(float_value),
+      CdrSchema::Char => char::deserialize(deserializer).map(|c| Value::String(c.to_string())),
+      CdrSchema::String => String::deserialize(deserializer).map(Value::String),
+      CdrSchema::Struct(members) => {
+        deserializer.deserialize_tuple(members.len(), StructVisitor { members })
+      }
+      CdrSchema::Array(elem, len) => deserializer.deserialize_tuple(*len, ArrayVisitor { elem }),
+      CdrSchema::Sequence(elem) => deserializer.deserialize_seq(ArrayVisitor { elem }),
+      CdrSchema::Optional(inner) => deserializer.deserialize_option(OptionVisitor { inner }),
+      CdrSchema::Enum(enumerators) => {
+        let discriminant = u32::deserialize(deserializer)?;
+        enumerators
+          .get(discriminant as usize)
+          .map(|name| Value::String(name.clone()))
+          .ok_or_else(|| {
+            de::Error::custom(format!("Enum discriminant {} out of range", discriminant))
+          })
+      }
+    }
+  }
+}
+
+struct StructVisitor<'a> {
+  members: &'a [(String, CdrSchema)],
+}
+
+impl<'de> Visitor<'de> for StructVisitor<'_> {
+  type Value = Value;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    write!(formatter, "struct with {} members", self.members.len())
+  }
+
+  fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Value, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let mut object = Map::with_capacity(self.members.len());
+    for (name, schema) in self.members {
+      let value = seq
+        .next_element_seed(schema)?
+        .ok_or_else(|| de::Error::custom(format!("Missing struct member {}", name)))?;
+      object.insert(name.clone(), value);
+    }
+    Ok(Value::Object(object))
+  }
+}
+
+struct ArrayVisitor<'a> {
+  elem: &'a CdrSchema,
+}
+
+impl<'de> Visitor<'de> for ArrayVisitor<'_> {
+  type Value = Value;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.write_str("array or sequence")
+  }
+
+  fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Value, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let mut elements = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+    while let Some(value) = seq.next_element_seed(self.elem)? {
+      elements.push(value);
+    }
+    Ok(Value::Array(elements))
+  }
+}
+
+struct OptionVisitor<'a> {
+  inner: &'a CdrSchema,
+}
+
+impl<'de> Visitor<'de> for OptionVisitor<'_> {
+  type Value = Value;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.write_str("optional value")
+  }
+
+  fn visit_none<E>(self) -> std::result::Result<Value, E>
+  where
+    E: de::Error,
+  {
+    Ok(Value::Null)
+  }
+
+  fn visit_some<D>(self, deserializer: D) -> std::result::Result<Value, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    self.inner.deserialize(deserializer)
+  }
+}
+
+/// Supplies the [`CdrSchema`] for a [`CdrJsonDeserializerAdapter`].
+///
+/// DeserializerAdapters have no state, so the schema is given through a type.
+/// The schema may still be decided at run time, e.g. by returning a schema
+/// stored in a global variable.
+pub trait CdrSchemaSource {
+  fn schema() -> Arc<CdrSchema>;
+}
+
+/// DeserializerAdapter that decodes CDR samples into [`serde_json::Value`]
+/// according to the schema given by `S`.
+///
+/// This allows creating a no_key DataReader of type `serde_json::Value` for
+/// any Topic whose data layout is known at run time.
+pub struct CdrJsonDeserializerAdapter<S> {
+  phantom: PhantomData<S>,
+}
+
+const REPR_IDS: [RepresentationIdentifier; 3] = [
+  RepresentationIdentifier::CDR_BE,
+  RepresentationIdentifier::CDR_LE,
+  RepresentationIdentifier::PL_CDR_LE,
+];
+
+impl<S> no_key::DeserializerAdapter<Value> for CdrJsonDeserializerAdapter<S>
+where
+  S: CdrSchemaSource,
+{
+  fn supported_encodings() -> &'static [RepresentationIdentifier] {
+    &REPR_IDS
+  }
+
+  fn from_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<Value> {
+    S::schema().to_json(input_bytes, encoding)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde::Serialize;
+  use serde_json::json;
+
+  use super::*;
+  use crate::serialization::cdr_serializer::to_bytes;
+
+  #[derive(Serialize)]
+  enum Color {
+    Red,
+    Green,
+  }
+
+  #[derive(Serialize)]
+  struct Inner {
+    flag: bool,
+    letter: char,
+  }
+
+  #[derive(Serialize)]
+  struct Sample {
+    id: u16,
+    position: [f64; 2],
+    inner: Inner,
+    values: Vec<i32>,
+    name: String,
+    colors: [Color; 2],
+    note: Option<String>,
+    missing: Option<u8>,
+  }
+
+  fn schema() -> CdrSchema {
+    CdrSchema::structure(vec![
+      ("id", CdrSchema::U16),
+      ("position", CdrSchema::Array(Box::new(CdrSchema::F64), 2)),
+      (
+        "inner",
+        CdrSchema::structure(vec![("flag", CdrSchema::Bool), ("letter", CdrSchema::Char)]),
+      ),
+      ("values", CdrSchema::Sequence(Box::new(CdrSchema::I32))),
+      ("name", CdrSchema::String),
+      (
+        "colors",
+        CdrSchema::Array(
+          Box::new(CdrSchema::Enum(vec![
+            "Red".to_string(),
+            "Green".to_string(),
+          ])),
+          2,
+        ),
+      ),
+      ("note", CdrSchema::Optional(Box::new(CdrSchema::String))),
+      ("missing", CdrSchema::Optional(Box::new(CdrSchema::U8))),
+    ])
+  }
+
+  struct TestSchema;
+
+  impl CdrSchemaSource for TestSchema {
+    fn schema() -> Arc<CdrSchema> {
+      Arc::new(schema())
+    }
+  }
+
+  #[test]
+  fn cdr_json_transcoding() {
+    let sample = Sample {
+      id: 7,
+      position: [1.5, -2.5],
+      inner: Inner {
+        flag: true,
+        letter: 'x',
+      },
+      values: vec![1, -2, 3],
+      name: "name".to_string(),
+      colors: [Color::Green, Color::Red],
+      note: Some("note".to_string()),
+      missing: None,
+    };
+    let expected = json!({
+      "id": 7,
+      "position": [1.5, -2.5],
+      "inner": { "flag": true, "letter": "x" },
+      "values": [1, -2, 3],
+      "name": "name",
+      "colors": ["Green", "Red"],
+      "note": "note",
+      "missing": null,
+    });
+
+    let le = to_bytes::<Sample, LittleEndian>(&sample).unwrap();
+    let be = to_bytes::<Sample, BigEndian>(&sample).unwrap();
+    assert_eq!(
+      schema()
+        .to_json(&le, RepresentationIdentifier::CDR_LE)
+        .unwrap(),
+      expected
+    );
+    assert_eq!(
+      <CdrJsonDeserializerAdapter<TestSchema> as no_key::DeserializerAdapter<Value>>::from_bytes(
+        &be,
+        RepresentationIdentifier::CDR_BE
+      )
+      .unwrap(),
+      expected
+    );
+
+    // truncated input
+    assert!(schema()
+      .to_json(&le[..10], RepresentationIdentifier::CDR_LE)
+      .is_err());
+  }
+}
@@ -0,0 +1,732 @@
+//! Partial decoding of CDR-encoded samples.
+//!
+//! A [`CdrProjection`] is built once from the [`DynamicType`] of the samples
+//! and a list of member paths. It can then extract those (primitive) members
+//! from serialized samples without deserializing the rest of the sample. This
+//! is used e.g. for evaluating content filters and extracting keys from large
+//! samples.
+//!
+//! Offsets of fields that are preceded only by fixed-size fields are computed
+//! already when the projection is created, so they are read directly.
+//! Fields located after variable-size fields (strings, sequences, unions) are
+//! found by skipping over the preceding data, which is still much cheaper than
+//! full deserialization.
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use cdr_encoding_size::CdrEncodingMaxSize;
+use serde::{Serialize, Serializer};
+
+use crate::{
+  dds::dynamic_data::DynamicType,
+  messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
+  serialization::error::{Error, Result},
+};
+
+// Layout of a CDR-encoded field, as far as projection is concerned. Made from
+// a DynamicType, but without the names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CdrFieldKind {
+  Bool,
+  I8,
+  U8,
+  I16,
+  U16,
+  I32,
+  U32,
+  I64,
+  U64,
+  F32,
+  F64,
+  Char,
+  String,
+  Struct(Vec<CdrFieldKind>),
+  Array(Box<CdrFieldKind>, usize),
+  // Sequence contents cannot be projected, but sequences can be skipped over.
+  Sequence(Box<CdrFieldKind>),
+  // Encoded as u32, projected as the name of the enumerator
+  Enum(Vec<String>),
+  // Cases by discriminator. Unions can only be skipped over.
+  Union(Vec<(i32, CdrFieldKind)>),
+}
+
+/// Primitive value extracted by a [`CdrProjection`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum CdrValue {
+  Bool(bool),
+  I8(i8),
+  U8(u8),
+  I16(i16),
+  U16(u16),
+  I32(i32),
+  U32(u32),
+  I64(i64),
+  U64(u64),
+  F32(f32),
+  F64(f64),
+  Char(char),
+  String(String),
+  /// Enumerator, with its encoded value and its name
+  Enum {
+    value: u32,
+    name: String,
+  },
+}
+
+/// Serializes the value as the member it was extracted from was serialized,
+/// so that e.g. projected key members can be encoded again.
+impl Serialize for CdrValue {
+  fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    match self {
+      Self::Bool(v) => serializer.serialize_bool(*v),
+      Self::I8(v) => serializer.serialize_i8(*v),
+      Self::U8(v) => serializer.serialize_u8(*v),
+      Self::I16(v) => serializer.serialize_i16(*v),
+      Self::U16(v) => serializer.serialize_u16(*v),
+      Self::I32(v) => serializer.serialize_i32(*v),
+      Self::U32(v) => serializer.serialize_u32(*v),
+      Self::I64(v) => serializer.serialize_i64(*v),
+      Self::U64(v) => serializer.serialize_u64(*v),
+      Self::F32(v) => serializer.serialize_f32(*v),
+      Self::F64(v) => serializer.serialize_f64(*v),
+      Self::Char(v) => serializer.serialize_char(*v),
+      Self::String(v) => serializer.serialize_str(v),
+      Self::Enum { value, .. } => serializer.serialize_u32(*value),
+    }
+  }
+}
+
+impl From<&DynamicType> for CdrFieldKind {
+  fn from(dynamic_type: &DynamicType) -> Self {
+    match dynamic_type {
+      DynamicType::Bool => Self::Bool,
+      DynamicType::I8 => Self::I8,
+      DynamicType::U8 => Self::U8,
+      DynamicType::I16 => Self::I16,
+      DynamicType::U16 => Self::U16,
+      DynamicType::I32 => Self::I32,
+      DynamicType::U32 => Self::U32,
+      DynamicType::I64 => Self::I64,
+      DynamicType::U64 => Self::U64,
+      DynamicType::F32 => Self::F32,
+      DynamicType::F64 => Self::F64,
+      DynamicType::Char => Self::Char,
+      DynamicType::String => Self::String,
+      DynamicType::Struct { members, .. } => {
+        Self::Struct(members.iter().map(|m| Self::from(&m.member_type)).collect())
+      }
+      DynamicType::Array(elem, len) => Self::Array(Box::new(Self::from(&**elem)), *len),
+      DynamicType::Sequence(elem) => Self::Sequence(Box::new(Self::from(&**elem))),
+      DynamicType::Enum { enumerators, .. } => Self::Enum(enumerators.clone()),
+      DynamicType::Union { cases, .. } => Self::Union(
+        cases
+          .iter()
+          .map(|c| (c.label, Self::from(&c.member_type)))
+          .collect(),
+      ),
+    }
+  }
+}
+
+// Flattened leaf field. Structs and arrays do not have alignment requirements
+// of their own in CDR, so they can be flattened to a sequence of leaves.
+#[derive(Debug, Clone)]
+enum Leaf {
+  Primitive(CdrFieldKind),
+  Sequence(CdrFieldKind),
+}
+
+impl CdrFieldKind {
+  // Size of primitive kinds. In CDR this is also their alignment.
+  fn primitive_size(&self) -> Option<usize> {
+    match self {
+      Self::Bool | Self::I8 | Self::U8 => Some(1),
+      Self::I16 | Self::U16 => Some(2),
+      Self::I32 | Self::U32 | Self::F32 | Self::Char | Self::Enum(_) => Some(4),
+      Self::I64 | Self::U64 | Self::F64 => Some(8),
+      _ => None,
+    }
+  }
+
+  // Serialized size, without alignment, as in CdrEncodingSize
+  fn max_size(&self) -> CdrEncodingMaxSize {
+    match self.primitive_size() {
+      Some(size) => CdrEncodingMaxSize::Bytes(size),
+      None => CdrEncodingMaxSize::Unbounded,
+    }
+  }
+
+  fn flatten_into(&self, leaves: &mut Vec<Leaf>) {
+    match self {
+      Self::Struct(fields) => fields.iter().for_each(|f| f.flatten_into(leaves)),
+      Self::Array(elem, len) => (0..*len).for_each(|_| elem.flatten_into(leaves)),
+      Self::Sequence(elem) => leaves.push(Leaf::Sequence((**elem).clone())),
+      other => leaves.push(Leaf::Primitive(other.clone())),
+    }
+  }
+
+  fn leaf_count(&self) -> usize {
+    match self {
+      Self::Struct(fields) => fields.iter().map(Self::leaf_count).sum(),
+      Self::Array(elem, len) => elem.leaf_count() * len,
+      _ => 1,
+    }
+  }
+}
+
+// Where to find a projected field.
+#[derive(Debug, Clone, Copy)]
+enum FieldLocation {
+  // Byte offset is known in advance.
+  Fixed(usize),
+  // Must skip from the first variable-size leaf up to this leaf index.
+  Walk(usize),
+}
+
+/// Precomputed projection of selected primitive members out of CDR data.
+///
+/// Member paths are member names, dotted for members of nested structs, e.g.
+/// `"position.x"`. Array elements are addressed by index, e.g.
+/// `"corners.2.x"`. Enums are projected as their enumerators.
+///
+/// # Example
+///
+/// ```
+/// use rustdds::{
+///   serialization::{CdrProjection, CdrValue},
+///   *,
+/// };
+///
+/// let point = DynamicType::structure(
+///   "Point",
+///   vec![
+///     DynamicMember::new("x", DynamicType::I32),
+///     DynamicMember::new("y", DynamicType::I32),
+///   ],
+/// );
+/// let shape = DynamicType::structure(
+///   "Shape",
+///   vec![
+///     DynamicMember::new("name", DynamicType::String),
+///     DynamicMember::new("position", point),
+///   ],
+/// );
+/// let projection = CdrProjection::new(&shape, &["position.y"]).unwrap();
+///
+/// // "ab" and (3, 4), little-endian
+/// let bytes = [3, 0, 0, 0, b'a', b'b', 0, 0, 3, 0, 0, 0, 4, 0, 0, 0];
+/// assert_eq!(
+///   projection.project(&bytes, RepresentationIdentifier::CDR_LE).unwrap(),
+///   vec![CdrValue::I32(4)]
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct CdrProjection {
+  leaves: Vec<Leaf>,
+  first_variable_leaf: usize, // index of first leaf with variable size
+  fixed_prefix_len: usize,    // byte offset of the first variable-size leaf
+  targets: Vec<(FieldLocation, CdrFieldKind)>,
+}
+
+impl CdrProjection {
+  /// Creates a projection of the members `paths` of samples of the struct
+  /// type `data_type`. Fails if a path does not lead to a primitive, string
+  /// or enum member.
+  pub fn new(data_type: &DynamicType, paths: &[&str]) -> Result<Self> {
+    let layout = match CdrFieldKind::from(data_type) {
+      CdrFieldKind::Struct(layout) => layout,
+      _ => {
+        return Err(Error::Message(format!(
+          "Cannot project members of non-struct type {}",
+          data_type.type_name()
+        )))
+      }
+    };
+    let index_paths = paths
+      .iter()
+      .map(|path| index_path(data_type, path))
+      .collect::<Result<Vec<_>>>()?;
+    Self::from_layout(&layout, &index_paths)
+  }
+
+  fn from_layout(layout: &[CdrFieldKind], paths: &[Vec<usize>]) -> Result<Self> {
+    let mut leaves = Vec::new();
+    layout.iter().for_each(|f| f.flatten_into(&mut leaves));
+
+    // compute fixed offsets for the leading fixed-size leaves
+    let mut fixed_offsets = Vec::new();
+    let mut offset = 0;
+    for leaf in &leaves {
+      match leaf {
+        Leaf::Primitive(kind) => match kind.primitive_size() {
+          Some(size) => {
+            offset = align(offset, size);
+            fixed_offsets.push(offset);
+            offset += size;
+          }
+          None => {
+            // String: its own start is still fixed, but nothing after it.
+            fixed_offsets.push(align(offset, 4));
+            break;
+          }
+        },
+        Leaf::Sequence(_) => break,
+      }
+    }
+    let first_variable_leaf = leaves
+      .iter()
+      .position(|leaf| !matches!(leaf, Leaf::Primitive(k) if k.primitive_size().is_some()))
+      .unwrap_or(leaves.len());
+
+    let targets = paths
+      .iter()
+      .map(|path| {
+        let (leaf_index, kind) = resolve_path(layout, path)?;
+        let location = match fixed_offsets.get(leaf_index) {
+          Some(offset) => FieldLocation::Fixed(*offset),
+          None => FieldLocation::Walk(leaf_index),
+        };
+        Ok((location, kind))
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+    Ok(Self {
+      leaves,
+      first_variable_leaf,
+      fixed_prefix_len: offset,
+      targets,
+    })
+  }
+
+  /// The maximum serialized size of the projected members together, without
+  /// alignment, as [`CdrEncodingSize`](crate::CdrEncodingSize) counts it.
+  pub(crate) fn max_size(&self) -> CdrEncodingMaxSize {
+    self
+      .targets
+      .iter()
+      .fold(CdrEncodingMaxSize::Bytes(0), |size, (_, kind)| {
+        size + kind.max_size()
+      })
+  }
+
+  /// Extracts the projected members from serialized data, in the same order
+  /// as the paths were given to [`CdrProjection::new`].
+  pub fn project(&self, input: &[u8], encoding: RepresentationIdentifier) -> Result<Vec<CdrValue>> {
+    match encoding {
+      RepresentationIdentifier::CDR_LE | RepresentationIdentifier::PL_CDR_LE => {
+        self.project_bo::<LittleEndian>(input)
+      }
+      RepresentationIdentifier::CDR_BE | RepresentationIdentifier::PL_CDR_BE => {
+        self.project_bo::<BigEndian>(input)
+      }
+      repr_id => Err(Error::Message(format!(
+        "Unknown representaiton identifier {:?}.",
+        repr_id
+      ))),
+    }
+  }
+
+  fn project_bo<BO: ByteOrder>(&self, input: &[u8]) -> Result<Vec<CdrValue>> {
+    // Offsets of leaves that needed walking, computed lazily and only once.
+    let mut walked_offsets: Vec<usize> = Vec::new();
+    let mut walk_pos = self.fixed_prefix_len;
+
+    self
+      .targets
+      .iter()
+      .map(|(location, kind)| {
+        let offset = match *location {
+          FieldLocation::Fixed(offset) => offset,
+          FieldLocation::Walk(leaf_index) => {
+            let walk_index = leaf_index - self.first_variable_leaf;
+            while walked_offsets.len() <= walk_index {
+              let leaf = &self.leaves[self.first_variable_leaf + walked_offsets.len()];
+              let start = leaf_start(leaf, walk_pos);
+              walked_offsets.push(start);
+              walk_pos = skip_leaf::<BO>(leaf, input, walk_pos)?;
+            }
+            walked_offsets[walk_index]
+          }
+        };
+        read_value::<BO>(kind, input, offset)
+      })
+      .collect()
+  }
+}
+
+fn align(pos: usize, alignment: usize) -> usize {
+  let modulo = pos % alignment;
+  if modulo == 0 {
+    pos
+  } else {
+    pos + alignment - modulo
+  }
+}
+
+fn leaf_start(leaf: &Leaf, pos: usize) -> usize {
+  match leaf {
+    Leaf::Primitive(kind) => align(pos, kind.primitive_size().unwrap_or(4)),
+    Leaf::Sequence(_) => align(pos, 4),
+  }
+}
+
+fn bytes_at(input: &[u8], offset: usize, count: usize) -> Result<&[u8]> {
+  input.get(offset..offset + count).ok_or(Error::Eof)
+}
+
+fn read_u32_at<BO: ByteOrder>(input: &[u8], offset: usize) -> Result<u32> {
+  Ok(BO::read_u32(bytes_at(input, offset, 4)?))
+}
+
+// Skips over a field starting at (unaligned) position pos, returns the
+// position after it.
+fn skip_kind<BO: ByteOrder>(kind: &CdrFieldKind, input: &[u8], pos: usize) -> Result<usize> {
+  if let Some(size) = kind.primitive_size() {
+    let start = align(pos, size);
+    bytes_at(input, start, size)?;
+    return Ok(start + size);
+  }
+  match kind {
+    CdrFieldKind::String => {
+      let start = align(pos, 4);
+      let len = read_u32_at::<BO>(input, start)? as usize;
+      bytes_at(input, start + 4, len)?;
+      Ok(start + 4 + len)
+    }
+    CdrFieldKind::Struct(fields) => fields
+      .iter()
+      .try_fold(pos, |pos, f| skip_kind::<BO>(f, input, pos)),
+    CdrFieldKind::Array(elem, len) => {
+      (0..*len).try_fold(pos, |pos, _| skip_kind::<BO>(elem, input, pos))
+    }
+    CdrFieldKind::Sequence(elem) => skip_sequence::<BO>(elem, input, pos),
+    CdrFieldKind::Union(cases) => {
+      let start = align(pos, 4);
+      let discriminator = BO::read_i32(bytes_at(input, start, 4)?);
+      match cases.iter().find(|(label, _)| *label == discriminator) {
+        Some((_, case)) => skip_kind::<BO>(case, input, start + 4),
+        None => Ok(start + 4),
+      }
+    }
+    _ => unreachable!("primitives handled above"),
+  }
+}
+
+fn skip_sequence<BO: ByteOrder>(elem: &CdrFieldKind, input: &[u8], pos: usize) -> Result<usize> {
+  let start = align(pos, 4);
+  let count = read_u32_at::<BO>(input, start)? as usize;
+  let pos = start + 4;
+  match elem.primitive_size() {
+    // fast path: no need to iterate over primitive elements
+    Some(size) if count > 0 => {
+      let begin = align(pos, size);
+      let len = count.checked_mul(size).ok_or(Error::Eof)?;
+      bytes_at(input, begin, len)?;
+      Ok(begin + len)
+    }
+    _ => (0..count).try_fold(pos, |pos, _| skip_kind::<BO>(elem, input, pos)),
+  }
+}
+
+fn skip_leaf<BO: ByteOrder>(leaf: &Leaf, input: &[u8], pos: usize) -> Result<usize> {
+  match leaf {
+    Leaf::Primitive(kind) => skip_kind::<BO>(kind, input, pos),
+    Leaf::Sequence(elem) => skip_sequence::<BO>(elem, input, pos),
+  }
+}
+
+// offset is already aligned
+fn read_value<BO: ByteOrder>(kind: &CdrFieldKind, input: &[u8], offset: usize) -> Result<CdrValue> {
+  let size = kind.primitive_size().unwrap_or(4);
+  let b = bytes_at(input, offset, size)?;
+  Ok(match kind {
+    CdrFieldKind::Bool => match b[0] {
+      0 => CdrValue::Bool(false),
+      1 => CdrValue::Bool(true),
+      x => return Err(Error::BadBoolean(x)),
+    },
+    CdrFieldKind::I8 => CdrValue::I8(b[0] as i8),
+    CdrFieldKind::U8 => CdrValue::U8(b[0]),
+    CdrFieldKind::I16 => CdrValue::I16(BO::read_i16(b)),
+    CdrFieldKind::U16 => CdrValue::U16(BO::read_u16(b)),
+    CdrFieldKind::I32 => CdrValue::I32(BO::read_i32(b)),
+    CdrFieldKind::U32 => CdrValue::U32(BO::read_u32(b)),
+    CdrFieldKind::I64 => CdrValue::I64(BO::read_i64(b)),
+    CdrFieldKind::U64 => CdrValue::U64(BO::read_u64(b)),
+    CdrFieldKind::F32 => CdrValue::F32(BO::read_f32(b)),
+    CdrFieldKind::F64 => CdrValue::F64(BO::read_f64(b)),
+    CdrFieldKind::Char => {
+      let codepoint = BO::read_u32(b);
+      CdrValue::Char(std::char::from_u32(codepoint).ok_or(Error::BadChar(codepoint))?)
+    }
+    CdrFieldKind::Enum(enumerators) => {
+      let value = BO::read_u32(b);
+      match enumerators.get(value as usize) {
+        Some(name) => CdrValue::Enum {
+          value,
+          name: name.clone(),
+        },
+        None => return Err(Error::Message(format!("Enumerator {} out of range", value))),
+      }
+    }
+    CdrFieldKind::String => {
+      let len = BO::read_u32(b) as usize;
+      let bytes = bytes_at(input, offset + 4, len)?;
+      // length includes null terminator
+      let bytes_without_null = &bytes[..len.saturating_sub(1)];
+      match std::str::from_utf8(bytes_without_null) {
+        Ok(s) => CdrValue::String(s.to_string()),
+        Err(utf8_err) => return Err(Error::BadString(utf8_err)),
+      }
+    }
+    other => {
+      return Err(Error::Message(format!(
+        "Cannot project non-primitive field {:?}",
+        other
+      )))
+    }
+  })
+}
+
+// Maps a field path to (flattened leaf index, field kind)
+fn resolve_path(layout: &[CdrFieldKind], path: &[usize]) -> Result<(usize, CdrFieldKind)> {
+  let bad_path = || Error::Message(format!("Invalid field path {:?}", path));
+  let (&first, rest) = path.split_first().ok_or_else(bad_path)?;
+  let field = layout.get(first).ok_or_else(bad_path)?;
+  let preceding: usize = layout[..first].iter().map(CdrFieldKind::leaf_count).sum();
+
+  match (field, rest.is_empty()) {
+    (CdrFieldKind::Struct(fields), false) => {
+      let (index, kind) = resolve_path(fields, rest)?;
+      Ok((preceding + index, kind))
+    }
+    (CdrFieldKind::Array(elem, len), false) => {
+      let (&elem_index, elem_rest) = rest.split_first().ok_or_else(bad_path)?;
+      if elem_index >= *len {
+        return Err(bad_path());
+      }
+      let elem_base = preceding + elem_index * elem.leaf_count();
+      if elem_rest.is_empty() {
+        match **elem {
+          CdrFieldKind::Struct(_)
+          | CdrFieldKind::Array(..)
+          | CdrFieldKind::Sequence(_)
+          | CdrFieldKind::Union(_) => Err(bad_path()),
+          ref kind => Ok((elem_base, kind.clone())),
+        }
+      } else {
+        let (index, kind) = resolve_path(std::slice::from_ref(&**elem), &prepend(0, elem_rest))?;
+        Ok((elem_base + index, kind))
+      }
+    }
+    (CdrFieldKind::Struct(_), true)
+    | (CdrFieldKind::Array(..), true)
+    | (CdrFieldKind::Sequence(_), _)
+    | (CdrFieldKind::Union(_), _) => Err(bad_path()),
+    (kind, true) => Ok((preceding, kind.clone())),
+    (_, false) => Err(bad_path()),
+  }
+}
+
+fn prepend(first: usize, rest: &[usize]) -> Vec<usize> {
+  std::iter::once(first).chain(rest.iter().copied()).collect()
+}
+
+// Maps a dotted member path to the indices of the members and array elements
+// along it.
+fn index_path(data_type: &DynamicType, path: &str) -> Result<Vec<usize>> {
+  let bad_path = || Error::Message(format!("No member {:?} in {}", path, data_type.type_name()));
+  let mut current = data_type;
+  let mut indices = Vec::new();
+  for name in path.split('.') {
+    let index = match current {
+      DynamicType::Struct { members, .. } => {
+        let index = members
+          .iter()
+          .position(|m| m.name == name)
+          .ok_or_else(bad_path)?;
+        current = &members[index].member_type;
+        index
+      }
+      DynamicType::Array(elem, len) => {
+        let index = name
+          .parse::<usize>()
+          .ok()
+          .filter(|index| index < len)
+          .ok_or_else(bad_path)?;
+        current = elem;
+        index
+      }
+      _ => return Err(bad_path()),
+    };
+    indices.push(index);
+  }
+  Ok(indices)
+}
+
+#[cfg(test)]
+mod tests {
+  use serde::Serialize;
+
+  use super::*;
+  use crate::{
+    dds::dynamic_data::{DynamicMember, DynamicUnionCase},
+    serialization::cdr_serializer::to_bytes,
+  };
+
+  #[derive(Serialize)]
+  struct Inner {
+    x: u8,
+    name: String,
+  }
+
+  #[derive(Serialize)]
+  #[allow(dead_code)] // Square is not constructed
+  enum Kind {
+    Circle,
+    Square,
+  }
+
+  #[derive(Serialize)]
+  #[allow(dead_code)] // Radius is not constructed
+  enum Extent {
+    Radius(f32),
+    Label(String),
+  }
+
+  #[derive(Serialize)]
+  struct Sample {
+    id: u16,
+    position: [f64; 2],
+    inner: Inner,
+    payload: Vec<u32>,
+    extent: Extent,
+    kind: Kind,
+    label: String,
+    tail: i64,
+  }
+
+  fn sample_type() -> DynamicType {
+    DynamicType::structure(
+      "Sample",
+      vec![
+        DynamicMember::new("id", DynamicType::U16),
+        DynamicMember::new("position", DynamicType::array(DynamicType::F64, 2)),
+        DynamicMember::new(
+          "inner",
+          DynamicType::structure(
+            "Inner",
+            vec![
+              DynamicMember::new("x", DynamicType::U8),
+              DynamicMember::new("name", DynamicType::String),
+            ],
+          ),
+        ),
+        DynamicMember::new("payload", DynamicType::sequence(DynamicType::U32)),
+        DynamicMember::new(
+          "extent",
+          DynamicType::union(
+            "Extent",
+            vec![
+              DynamicUnionCase::new(0, "radius", DynamicType::F32),
+              DynamicUnionCase::new(1, "label", DynamicType::String),
+            ],
+          ),
+        ),
+        DynamicMember::new(
+          "kind",
+          DynamicType::enumeration("Kind", ["Circle", "Square"]),
+        ),
+        DynamicMember::new("label", DynamicType::String),
+        DynamicMember::new("tail", DynamicType::I64),
+      ],
+    )
+  }
+
+  fn sample() -> Sample {
+    Sample {
+      id: 7,
+      position: [1.5, -2.5],
+      inner: Inner {
+        x: 3,
+        name: "inner".to_string(),
+      },
+      payload: vec![1, 2, 3, 4, 5],
+      extent: Extent::Label("wide".to_string()),
+      kind: Kind::Circle,
+      label: "label".to_string(),
+      tail: -42,
+    }
+  }
+
+  #[test]
+  fn cdr_projection_fixed_and_walked_fields() {
+    let projection = CdrProjection::new(
+      &sample_type(),
+      &[
+        "tail",
+        "id",
+        "position.1",
+        "inner.name",
+        "kind",
+        "label",
+        "inner.x",
+      ],
+    )
+    .unwrap();
+
+    let expected = vec![
+      CdrValue::I64(-42),
+      CdrValue::U16(7),
+      CdrValue::F64(-2.5),
+      CdrValue::String("inner".to_string()),
+      CdrValue::Enum {
+        value: 0,
+        name: "Circle".to_string(),
+      },
+      CdrValue::String("label".to_string()),
+      CdrValue::U8(3),
+    ];
+
+    let le = to_bytes::<Sample, LittleEndian>(&sample()).unwrap();
+    assert_eq!(
+      projection
+        .project(&le, RepresentationIdentifier::CDR_LE)
+        .unwrap(),
+      expected
+    );
+    let be = to_bytes::<Sample, BigEndian>(&sample()).unwrap();
+    assert_eq!(
+      projection
+        .project(&be, RepresentationIdentifier::CDR_BE)
+        .unwrap(),
+      expected
+    );
+    assert_eq!(projection.max_size(), CdrEncodingMaxSize::Unbounded);
+    let fixed = CdrProjection::new(&sample_type(), &["id", "kind", "tail"]).unwrap();
+    assert_eq!(fixed.max_size(), CdrEncodingMaxSize::Bytes(14));
+  }
+
+  #[test]
+  fn cdr_projection_errors() {
+    let t = sample_type();
+    for path in [
+      "payload",
+      "inner",
+      "position.2",
+      "extent",
+      "extent.label",
+      "nothing",
+      "id.x",
+    ] {
+      assert!(CdrProjection::new(&t, &[path]).is_err(), "{}", path);
+    }
+    assert!(CdrProjection::new(&DynamicType::I32, &["x"]).is_err());
+
+    let projection = CdrProjection::new(&t, &["tail"]).unwrap();
+    let le = to_bytes::<Sample, LittleEndian>(&sample()).unwrap();
+    assert!(matches!(
+      projection.project(&le[..le.len() - 1], RepresentationIdentifier::CDR_LE),
+      Err(Error::Eof)
+    ));
+  }
+}
@@ -828,4 +828,244 @@ mod tests {
     ];
     assert_eq!(expected, sarjallistettu);
   }
+
+  #[cfg(feature = "derive")]
+  #[test]
+  fn cdr_serialize_enum_with_explicit_values() {
+    use crate::{serialization::cdr_deserializer::deserialize_from_big_endian, DdsEnum};
+
+    #[derive(Debug, PartialEq, DdsEnum)]
+    enum Color {
+      Red = 1,
+      Green,
+      Blue = 40,
+    }
+
+    assert_eq!(
+      to_little_endian_binary(&Color::Red).unwrap(),
+      vec![1, 0, 0, 0]
+    );
+    assert_eq!(
+      to_little_endian_binary(&Color::Green).unwrap(),
+      vec![2, 0, 0, 0]
+    );
+    assert_eq!(
+      to_big_endian_binary(&Color::Blue).unwrap(),
+      vec![0, 0, 0, 40]
+    );
+
+    let blue: Color = deserialize_from_big_endian(&[0, 0, 0, 40]).unwrap();
+    assert_eq!(blue, Color::Blue);
+    let green: Color = deserialize_from_little_endian(&[2, 0, 0, 0]).unwrap();
+    assert_eq!(green, Color::Green);
+    // No enumerator has value 3
+    assert!(deserialize_from_little_endian::<Color>(&[3, 0, 0, 0]).is_err());
+  }
+
+  #[cfg(feature = "derive")]
+  #[test]
+  fn cdr_serialize_union() {
+    use crate::{DdsEnum, DdsUnion};
+
+    #[derive(Debug, PartialEq, DdsUnion)]
+    #[dds_union(discriminator = "i16")]
+    enum Shape {
+      #[dds_case(1, 2)]
+      Radius(f64),
+      #[dds_case(3)]
+      Name(String),
+      #[dds_case(4)]
+      Empty,
+      #[dds_default(0)]
+      Other(u8),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Holder {
+      flag: u8,
+      shape: Shape,
+    }
+
+    // Discriminator is aligned to 2, the f64 member to 8.
+    let radius = Holder {
+      flag: 1,
+      shape: Shape::Radius(1.5),
+    };
+    let serialized = to_little_endian_binary(&radius).unwrap();
+    assert_eq!(
+      serialized,
+      vec![1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xf8, 0x3f]
+    );
+    assert_eq!(
+      deserialize_from_little_endian::<Holder>(&serialized).unwrap(),
+      radius
+    );
+
+    let name = to_big_endian_binary(&Shape::Name("ab".to_string())).unwrap();
+    assert_eq!(name, vec![0, 3, 0, 0, 0, 0, 0, 3, b'a', b'b', 0]);
+    let empty = to_little_endian_binary(&Shape::Empty).unwrap();
+    assert_eq!(empty, vec![4, 0]);
+    assert_eq!(
+      deserialize_from_little_endian::<Shape>(&empty).unwrap(),
+      Shape::Empty
+    );
+
+    // Second label of a case
+    let radius: Shape =
+      deserialize_from_little_endian(&[2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f])
+        .unwrap();
+    assert_eq!(radius, Shape::Radius(1.0));
+    // Unlisted discriminators select the default case
+    let other: Shape = deserialize_from_little_endian(&[9, 0, 7]).unwrap();
+    assert_eq!(other, Shape::Other(7));
+    assert_eq!(to_little_endian_binary(&other).unwrap(), vec![0, 0, 7]);
+
+    // Enum discriminator, no default case
+    #[derive(Debug, PartialEq, DdsEnum)]
+    enum Kind {
+      Count = 10,
+      Flag = 20,
+    }
+
+    #[derive(Debug, PartialEq, DdsUnion)]
+    #[dds_union(discriminator = "Kind")]
+    enum Value {
+      #[dds_case(Kind::Count)]
+      Count(u16),
+      #[dds_case(Kind::Flag)]
+      Flag(bool),
+    }
+
+    let count = to_little_endian_binary(&Value::Count(300)).unwrap();
+    assert_eq!(count, vec![10, 0, 0, 0, 0x2c, 0x01]);
+    assert_eq!(
+      deserialize_from_little_endian::<Value>(&count).unwrap(),
+      Value::Count(300)
+    );
+    let flag: Value = deserialize_from_little_endian(&[20, 0, 0, 0, 1]).unwrap();
+    assert_eq!(flag, Value::Flag(true));
+    assert!(deserialize_from_little_endian::<Value>(&[30, 0, 0, 0, 1]).is_err());
+  }
+
+  #[cfg(feature = "derive")]
+  #[test]
+  fn cdr_serialize_idl_bitmask_and_bitset() {
+    use crate::{DdsBitmask, DdsBitset};
+
+    // As generated by IdlCodegen from
+    // @bit_bound(12) bitmask Permissions { READ, WRITE, @position(11) EXECUTE };
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, DdsBitmask)]
+    #[dds_bitmask(bit_bound = 12)]
+    pub struct Permissions(pub u16);
+
+    impl Permissions {
+      pub const READ: Self = Self(1 << 0);
+      pub const WRITE: Self = Self(1 << 1);
+      pub const EXECUTE: Self = Self(1 << 11);
+    }
+
+    // bitset Header {
+    //   bitfield<3> version; bitfield<1> urgent; bitfield<2>;
+    //   bitfield<10, short> delta; bitfield<4>;
+    // };
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, DdsBitset)]
+    #[dds_bitset(bits = 20)]
+    pub struct Header {
+      #[dds_bitfield(3)]
+      pub version: u8,
+      #[dds_bitfield(1)]
+      pub urgent: bool,
+      #[dds_bitfield(10, skip = 2)]
+      pub delta: i16,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+      id: u8,
+      permissions: Permissions,
+      header: Header,
+    }
+
+    let permissions = Permissions::READ | Permissions::EXECUTE;
+    assert!(permissions.contains(Permissions::EXECUTE));
+    assert!(!permissions.contains(Permissions::WRITE | Permissions::READ));
+    assert_eq!(permissions & Permissions::READ, Permissions::READ);
+
+    // A 12-bit bitmask is a u16, and a 20-bit bitset is a u32.
+    let message = Message {
+      id: 5,
+      permissions,
+      header: Header {
+        version: 5,
+        urgent: true,
+        delta: -2,
+      },
+    };
+    let serialized = to_little_endian_binary(&message).unwrap();
+    // delta is 0b11_1111_1110 at bit 6
+    assert_eq!(serialized, vec![5, 0, 0x01, 0x08, 0x8d, 0xff, 0x00, 0x00]);
+    assert_eq!(
+      deserialize_from_little_endian::<Message>(&serialized).unwrap(),
+      message
+    );
+    assert_eq!(
+      to_big_endian_binary(&permissions).unwrap(),
+      vec![0x08, 0x01]
+    );
+
+    // Bit 12 is above the bit bound.
+    assert!(deserialize_from_little_endian::<Permissions>(&[0x00, 0x10]).is_err());
+    assert!(to_little_endian_binary(&Permissions(0x1000)).is_err());
+    // Unused bits are ignored, but values must fit in their bit fields.
+    let header: Header = deserialize_from_little_endian(&[0x30, 0x00, 0xf0, 0x00]).unwrap();
+    assert_eq!(header, Header::default());
+    let too_large = Header {
+      version: 8,
+      ..Header::default()
+    };
+    assert!(to_little_endian_binary(&too_large).is_err());
+    let too_small = Header {
+      delta: -513,
+      ..Header::default()
+    };
+    assert!(to_little_endian_binary(&too_small).is_err());
+  }
+
+  #[test]
+  fn cdr_serialize_bitmask() {
+    use enumflags2::{bitflags, BitFlags};
+
+    // IDL: @bit_bound(16) bitmask Permissions { READ, WRITE, @position(7) EXECUTE };
+    #[bitflags]
+    #[repr(u16)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Permission {
+      Read = 0x01,
+      Write = 0x02,
+      Execute = 0x80,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct File {
+      id: u8,
+      permissions: BitFlags<Permission>,
+    }
+
+    let file = File {
+      id: 5,
+      permissions: Permission::Read | Permission::Execute,
+    };
+    let serialized = to_little_endian_binary(&file).unwrap();
+    assert_eq!(serialized, vec![5, 0, 0x81, 0]);
+    assert_eq!(
+      deserialize_from_little_endian::<File>(&serialized).unwrap(),
+      file
+    );
+    assert_eq!(
+      to_big_endian_binary(&file.permissions).unwrap(),
+      vec![0, 0x81]
+    );
+    // Bit 2 is not defined
+    assert!(deserialize_from_little_endian::<File>(&[5, 0, 0x04, 0]).is_err());
+  }
 }
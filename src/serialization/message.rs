@@ -20,19 +20,22 @@ use crate::{
       },
       submessages::{SubmessageKind, *},
     },
+    validity_trait::Validity,
     vendor_id::VendorId,
   },
   serialization::submessage::{SubMessage, SubmessageBody},
   structure::{
-    cache_change::CacheChange,
+    cache_change::{CacheChange, ChangeKind},
     entity::RTPSEntity,
     guid::{EntityId, EntityKind, GuidPrefix, GUID},
     parameter_id::ParameterId,
-    sequence_number::{FragmentNumber, SequenceNumber, SequenceNumberSet},
+    sequence_number::{FragmentNumber, SequenceNumber, SequenceNumberRange, SequenceNumberSet},
     time::Timestamp,
   },
 };
 
+pub(crate) const RTPS_MESSAGE_HEADER_SIZE: usize = 20;
+
 #[derive(Debug, Clone)]
 pub(crate) struct Message {
   pub header: Header,
@@ -57,19 +60,30 @@ impl Message {
   // We implement this instead of Speedy trait Readable, because
   // we need to run-time decide which endianness we input. Speedy requires the
   // top level to fix that. And there seems to be no reasonable way to change
-  // endianness. TODO: The error type should be something better
-  pub fn read_from_buffer(buffer: &Bytes) -> io::Result<Self> {
+  // endianness.
+  //
+  // The buffer comes from the network, so anything may be in it. Malformed
+  // messages must result in an error, never in a panic.
+  pub fn read_from_buffer(buffer: &Bytes) -> Result<Self, MessageError> {
+    if buffer.len() < RTPS_MESSAGE_HEADER_SIZE {
+      return Err(MessageError::TooShort(buffer.len()));
+    }
     // The Header deserializes the same
-    let rtps_header =
-      Header::read_from_buffer(buffer).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let rtps_header = Header::read_from_buffer(buffer).map_err(MessageError::Header)?;
+    // RTPS spec v2.5 Section "8.3.6.3 Rules Followed by the Message Receiver"
+    if !rtps_header.valid() {
+      return Err(MessageError::InvalidHeader(rtps_header));
+    }
     let mut message = Self::new(rtps_header);
-    let mut submessages_left: Bytes = buffer.slice(20..); // header is 20 bytes
-                                                          // submessage loop
+    let mut submessages_left: Bytes = buffer.slice(RTPS_MESSAGE_HEADER_SIZE..);
+    // submessage loop
     while !submessages_left.is_empty() {
+      let offset = buffer.len() - submessages_left.len();
       let sub_header = SubmessageHeader::read_from_buffer(&submessages_left)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        .map_err(|_| MessageError::TruncatedSubmessageHeader { offset })?;
       // Try to figure out how large this submessage is.
       let sub_header_length = 4; // 4 bytes
+      let available = submessages_left.len() - sub_header_length;
       let sub_content_length = if sub_header.content_length == 0 {
         // RTPS spec 2.3, section 9.4.5.1.3:
         //           In case octetsToNextHeader==0 and the kind of Submessage is
@@ -83,152 +97,217 @@ impl Message {
         // in the Message.
         match sub_header.kind {
           SubmessageKind::PAD | SubmessageKind::INFO_TS => 0,
-          _not_pad_or_info_ts => submessages_left.len() - sub_header_length,
+          _not_pad_or_info_ts => available,
         }
       } else {
         sub_header.content_length as usize
       };
+      // Section 8.3.4.1: octetsToNextHeader pointing past the end of the Message
+      // makes the whole Message invalid.
+      if sub_content_length > available {
+        return Err(MessageError::SubmessageLength {
+          kind: sub_header.kind,
+          offset,
+          length: sub_content_length,
+          available,
+        });
+      }
 
-      // we have to use temporary variable new_submessages_left to avoid creating
-      // another submessages_left
-      // let (sub_buffer, new_submessages_left) =
-      //   submessages_left.split_at(sub_header_length + sub_content_length);
-      // submessages_left = new_submessages_left;
-      // split fisrt buters to new buffer
+      // split first bytes to new buffer
       let mut sub_buffer = submessages_left.split_to(sub_header_length + sub_content_length);
       // split tail part (content) to new buffer
       let sub_content_buffer = sub_buffer.split_off(sub_header_length);
 
-      let e = endianness_flag(sub_header.flags);
-      let mk_e_subm = move |s: EntitySubmessage| {
-        Ok(SubMessage {
-          header: sub_header,
-          body: SubmessageBody::Entity(s),
-        })
-      };
-      let mk_i_subm = move |s: InterpreterSubmessage| {
-        Ok(SubMessage {
-          header: sub_header,
-          body: SubmessageBody::Interpreter(s),
-        })
-      };
-
-      let new_submessage_result: io::Result<SubMessage> = match sub_header.kind {
-        SubmessageKind::DATA => {
-          // Manually implemented deserialization for DATA. Speedy does not quite cut it.
-          let f = BitFlags::<DATA_Flags>::from_bits_truncate(sub_header.flags);
-          mk_e_subm(EntitySubmessage::Data(
-            Data::deserialize_data(&sub_content_buffer, f)?,
-            f,
-          ))
+      match Self::read_submessage(sub_header, &sub_content_buffer) {
+        Ok(Some(submessage)) => message.submessages.push(submessage),
+        Ok(None) => (), // skipped
+        Err(source) => {
+          return Err(MessageError::Submessage {
+            kind: sub_header.kind,
+            offset,
+            source,
+          })
         }
+      }
+    } // loop
 
-        SubmessageKind::DATA_FRAG => {
-          // Manually implemented deserialization for DATA. Speedy does not quite cut it.
-          let f = BitFlags::<DATAFRAG_Flags>::from_bits_truncate(sub_header.flags);
-          mk_e_subm(EntitySubmessage::DataFrag(
-            DataFrag::deserialize(&sub_content_buffer, f)?,
-            f,
-          ))
-        }
+    Ok(message)
+  }
 
-        SubmessageKind::GAP => {
-          let f = BitFlags::<GAP_Flags>::from_bits_truncate(sub_header.flags);
-          mk_e_subm(EntitySubmessage::Gap(
-            Gap::read_from_buffer_with_ctx(e, &sub_content_buffer)?,
-            f,
-          ))
-        }
+  // Deserialize submessage contents. None means that the submessage is to be
+  // skipped, e.g. PAD or an unknown kind.
+  fn read_submessage(
+    sub_header: SubmessageHeader,
+    sub_content_buffer: &Bytes,
+  ) -> io::Result<Option<SubMessage>> {
+    let e = endianness_flag(sub_header.flags);
+    let mk_e_subm = move |s: EntitySubmessage| {
+      Ok(Some(SubMessage {
+        header: sub_header,
+        body: SubmessageBody::Entity(s),
+      }))
+    };
+    let mk_i_subm = move |s: InterpreterSubmessage| {
+      Ok(Some(SubMessage {
+        header: sub_header,
+        body: SubmessageBody::Interpreter(s),
+      }))
+    };
 
-        SubmessageKind::ACKNACK => {
-          let f = BitFlags::<ACKNACK_Flags>::from_bits_truncate(sub_header.flags);
-          mk_e_subm(EntitySubmessage::AckNack(
-            AckNack::read_from_buffer_with_ctx(e, &sub_content_buffer)?,
-            f,
-          ))
-        }
+    match sub_header.kind {
+      SubmessageKind::DATA => {
+        // Manually implemented deserialization for DATA. Speedy does not quite cut it.
+        let f = BitFlags::<DATA_Flags>::from_bits_truncate(sub_header.flags);
+        mk_e_subm(EntitySubmessage::Data(
+          Data::deserialize_data(sub_content_buffer, f)?,
+          f,
+        ))
+      }
 
-        SubmessageKind::NACK_FRAG => {
-          let f = BitFlags::<NACKFRAG_Flags>::from_bits_truncate(sub_header.flags);
-          mk_e_subm(EntitySubmessage::NackFrag(
-            NackFrag::read_from_buffer_with_ctx(e, &sub_content_buffer)?,
-            f,
-          ))
-        }
+      SubmessageKind::DATA_FRAG => {
+        // Manually implemented deserialization for DATA. Speedy does not quite cut it.
+        let f = BitFlags::<DATAFRAG_Flags>::from_bits_truncate(sub_header.flags);
+        mk_e_subm(EntitySubmessage::DataFrag(
+          DataFrag::deserialize(sub_content_buffer, f)?,
+          f,
+        ))
+      }
 
-        SubmessageKind::HEARTBEAT => {
-          let f = BitFlags::<HEARTBEAT_Flags>::from_bits_truncate(sub_header.flags);
-          mk_e_subm(EntitySubmessage::Heartbeat(
-            Heartbeat::read_from_buffer_with_ctx(e, &sub_content_buffer)?,
-            f,
-          ))
-        }
+      SubmessageKind::GAP => {
+        let f = BitFlags::<GAP_Flags>::from_bits_truncate(sub_header.flags);
+        mk_e_subm(EntitySubmessage::Gap(
+          Gap::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
 
-        // interpreter submessages
-        SubmessageKind::INFO_DST => {
-          let f = BitFlags::<INFODESTINATION_Flags>::from_bits_truncate(sub_header.flags);
-          mk_i_subm(InterpreterSubmessage::InfoDestination(
-            InfoDestination::read_from_buffer_with_ctx(e, &sub_content_buffer)?,
-            f,
-          ))
-        }
-        SubmessageKind::INFO_SRC => {
-          let f = BitFlags::<INFOSOURCE_Flags>::from_bits_truncate(sub_header.flags);
-          mk_i_subm(InterpreterSubmessage::InfoSource(
-            InfoSource::read_from_buffer_with_ctx(e, &sub_content_buffer)?,
-            f,
-          ))
-        }
-        SubmessageKind::INFO_TS => {
-          let f = BitFlags::<INFOTIMESTAMP_Flags>::from_bits_truncate(sub_header.flags);
-          let tso = if f.contains(INFOTIMESTAMP_Flags::Invalidate) {
-            None
-          } else {
-            Some(Timestamp::read_from_buffer_with_ctx(
-              e,
-              &sub_content_buffer,
-            )?)
-          };
-          mk_i_subm(InterpreterSubmessage::InfoTimestamp(
-            InfoTimestamp { timestamp: tso },
-            f,
-          ))
-        }
-        SubmessageKind::INFO_REPLY => {
-          let f = BitFlags::<INFOREPLY_Flags>::from_bits_truncate(sub_header.flags);
-          mk_i_subm(InterpreterSubmessage::InfoReply(
-            InfoReply::read_from_buffer_with_ctx(e, &sub_content_buffer)?,
-            f,
-          ))
-        }
-        SubmessageKind::PAD => {
-          continue; // nothing to do here
-        }
-        unknown_kind => {
-          let kind = u8::from(unknown_kind);
-          if kind >= 0x80 {
-            // Kinds 0x80 - 0xFF are vendor-specific.
-            trace!(
-              "Received vendor-specific submessage kind {:?}",
-              unknown_kind
-            );
-            trace!("Submessage was {:?}", &sub_buffer);
-          } else {
-            // Kind is 0x00 - 0x7F, is should be in the standard.
-            error!("Received unknown submessage kind {:?}", unknown_kind);
-            debug!("Submessage was {:?}", &sub_buffer);
-          }
-          continue;
-        }
-      }; // match
+      SubmessageKind::ACKNACK => {
+        let f = BitFlags::<ACKNACK_Flags>::from_bits_truncate(sub_header.flags);
+        mk_e_subm(EntitySubmessage::AckNack(
+          AckNack::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
 
-      message.submessages.push(new_submessage_result?);
-    } // loop
+      SubmessageKind::NACK_FRAG => {
+        let f = BitFlags::<NACKFRAG_Flags>::from_bits_truncate(sub_header.flags);
+        mk_e_subm(EntitySubmessage::NackFrag(
+          NackFrag::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
 
-    Ok(message)
+      SubmessageKind::HEARTBEAT => {
+        let f = BitFlags::<HEARTBEAT_Flags>::from_bits_truncate(sub_header.flags);
+        mk_e_subm(EntitySubmessage::Heartbeat(
+          Heartbeat::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
+
+      // interpreter submessages
+      SubmessageKind::INFO_DST => {
+        let f = BitFlags::<INFODESTINATION_Flags>::from_bits_truncate(sub_header.flags);
+        mk_i_subm(InterpreterSubmessage::InfoDestination(
+          InfoDestination::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
+      SubmessageKind::INFO_SRC => {
+        let f = BitFlags::<INFOSOURCE_Flags>::from_bits_truncate(sub_header.flags);
+        mk_i_subm(InterpreterSubmessage::InfoSource(
+          InfoSource::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
+      SubmessageKind::INFO_TS => {
+        let f = BitFlags::<INFOTIMESTAMP_Flags>::from_bits_truncate(sub_header.flags);
+        let tso = if f.contains(INFOTIMESTAMP_Flags::Invalidate) {
+          None
+        } else {
+          Some(Timestamp::read_from_buffer_with_ctx(e, sub_content_buffer)?)
+        };
+        mk_i_subm(InterpreterSubmessage::InfoTimestamp(
+          InfoTimestamp { timestamp: tso },
+          f,
+        ))
+      }
+      SubmessageKind::INFO_REPLY => {
+        let f = BitFlags::<INFOREPLY_Flags>::from_bits_truncate(sub_header.flags);
+        mk_i_subm(InterpreterSubmessage::InfoReply(
+          InfoReply::deserialize(sub_content_buffer, f)?,
+          f,
+        ))
+      }
+      SubmessageKind::PAD => Ok(None), // nothing to do here
+      // Protected submessages that were not decoded, because the participant
+      // has no cryptographic plugin or no keys for the sender
+      SubmessageKind::SEC_BODY
+      | SubmessageKind::SEC_PREFIX
+      | SubmessageKind::SEC_POSTFIX
+      | SubmessageKind::SRTPS_PREFIX
+      | SubmessageKind::SRTPS_POSTFIX => {
+        debug!("Cannot decode protected submessage {:?}", sub_header.kind);
+        Ok(None)
+      }
+      unknown_kind => {
+        let kind = u8::from(unknown_kind);
+        if kind >= 0x80 {
+          // Kinds 0x80 - 0xFF are vendor-specific.
+          trace!(
+            "Received vendor-specific submessage kind {:?}",
+            unknown_kind
+          );
+          trace!("Submessage was {:?}", sub_content_buffer);
+        } else {
+          // Kind is 0x00 - 0x7F, is should be in the standard.
+          error!("Received unknown submessage kind {:?}", unknown_kind);
+          debug!("Submessage was {:?}", sub_content_buffer);
+        }
+        Ok(None)
+      }
+    } // match
   }
 }
 
+/// Parse an RTPS message as if it was received from the network, and return
+/// the number of submessages in it.
+///
+/// For fuzzing the message parser. Requires feature `testing`.
+#[cfg(feature = "testing")]
+pub fn parse_rtps_message(bytes: &[u8]) -> Result<usize, MessageError> {
+  Message::read_from_buffer(&Bytes::copy_from_slice(bytes)).map(|m| m.submessages.len())
+}
+
+/// Why a received RTPS message was rejected as malformed.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum MessageError {
+  #[error("Message of {0} bytes is shorter than RTPS header")]
+  TooShort(usize),
+  #[error("Cannot read RTPS header: {0}")]
+  Header(speedy::Error),
+  #[error("Not RTPS, or unsupported protocol version: {0:?}")]
+  InvalidHeader(Header),
+  #[error("Truncated submessage header at offset {offset}")]
+  TruncatedSubmessageHeader { offset: usize },
+  #[error(
+    "Submessage {kind:?} at offset {offset} has length {length}, but only {available} bytes follow"
+  )]
+  SubmessageLength {
+    kind: SubmessageKind,
+    offset: usize,
+    length: usize,
+    available: usize,
+  },
+  #[error("Malformed submessage {kind:?} at offset {offset}: {source}")]
+  Submessage {
+    kind: SubmessageKind,
+    offset: usize,
+    source: io::Error,
+  },
+}
+
 impl Message {
   pub fn new(header: Header) -> Self {
     Self {
@@ -326,35 +405,7 @@ impl MessageBuilder {
     writer_entity_id: EntityId,
     endianness: Endianness,
   ) -> Self {
-    let mut param_list = ParameterList::new(); // inline QoS goes here
-
-    // Check if we are disposing by key hash
-    match cache_change.data_value {
-      DDSData::Data { .. } | DDSData::DisposeByKey { .. } => (), // no
-      DDSData::DisposeByKeyHash { key_hash, .. } => {
-        // yes, insert to inline QoS
-        // insert key hash
-        param_list.parameters.push(Parameter {
-          parameter_id: ParameterId::PID_KEY_HASH,
-          value: key_hash.to_vec(),
-        });
-
-        // ... and tell what the key_hash means
-        let status_info = Parameter::create_pid_status_info_parameter(
-          /* disposed */ true, /* unregisterd */ true, /* filtered */ false,
-        );
-        param_list.parameters.push(status_info);
-      }
-    }
-
-    // If we are sending related sample identity, then insert that.
-    if let Some(si) = cache_change.write_options.related_sample_identity {
-      let related_sample_identity_serialized = si.write_to_vec_with_ctx(endianness).unwrap();
-      param_list.parameters.push(Parameter {
-        parameter_id: ParameterId::PID_RELATED_SAMPLE_IDENTITY,
-        value: related_sample_identity_serialized,
-      });
-    }
+    let param_list = inline_qos(cache_change, endianness);
 
     let have_inline_qos = !param_list.is_empty(); // we need this later also
     let inline_qos = if have_inline_qos {
@@ -371,6 +422,7 @@ impl MessageBuilder {
       serialized_payload: match cache_change.data_value {
         DDSData::Data {
           ref serialized_payload,
+          ..
         } => Some(serialized_payload.clone()), // contents is Bytes
         DDSData::DisposeByKey { ref key, .. } => Some(key.clone()),
         DDSData::DisposeByKeyHash { .. } => None,
@@ -427,8 +479,6 @@ impl MessageBuilder {
     sample_size: u32, // all fragments together
     endianness: Endianness,
   ) -> Self {
-    let mut param_list = ParameterList::new(); // inline QoS goes here
-
     // Check if we are disposing by key hash
     match cache_change.data_value {
       DDSData::Data { .. } | DDSData::DisposeByKey { .. } => (), // no => ok
@@ -444,14 +494,7 @@ impl MessageBuilder {
       }
     }
 
-    // If we are sending related sample identity, then insert that.
-    if let Some(si) = cache_change.write_options.related_sample_identity {
-      let related_sample_identity_serialized = si.write_to_vec_with_ctx(endianness).unwrap();
-      param_list.parameters.push(Parameter {
-        parameter_id: ParameterId::PID_RELATED_SAMPLE_IDENTITY,
-        value: related_sample_identity_serialized,
-      });
-    }
+    let param_list = inline_qos(cache_change, endianness);
 
     let have_inline_qos = !param_list.is_empty(); // we need this later also
 
@@ -537,23 +580,45 @@ impl MessageBuilder {
       irrelevant_sns.iter().next_back(),
     ) {
       (Some(&base), Some(&_top)) => {
-        let gap_list = SequenceNumberSet::from_base_and_set(base, irrelevant_sns);
-        let gap = Gap {
-          reader_id: reader_guid.entity_id,
-          writer_id: writer.entity_id(),
-          gap_start: base,
-          gap_list,
-        };
+        // A gap_list spans at most 256 SNs, so there may be several GAPs.
         let gap_flags = BitFlags::<GAP_Flags>::from_endianness(writer.endianness);
-        gap
-          .create_submessage(gap_flags)
-          .map(|s| self.submessages.push(s));
+        for gap_list in SequenceNumberSet::sets_from_base_and_set(base, irrelevant_sns) {
+          let gap = Gap {
+            reader_id: reader_guid.entity_id,
+            writer_id: writer.entity_id(),
+            gap_start: gap_list.base(),
+            gap_list,
+          };
+          gap
+            .create_submessage(gap_flags)
+            .map(|s| self.submessages.push(s));
+        }
       }
       (_, _) => error!("gap_msg called with empty SN set. Skipping GAP submessage"),
     }
     self
   }
 
+  // GAP for a contiguous range of sequence numbers
+  pub fn gap_range_msg(
+    mut self,
+    range: SequenceNumberRange,
+    writer: &RtpsWriter,
+    reader_entity_id: EntityId,
+  ) -> Self {
+    let gap = Gap {
+      reader_id: reader_entity_id,
+      writer_id: writer.entity_id(),
+      gap_start: range.begin(),
+      gap_list: SequenceNumberSet::new_empty(range.end() + SequenceNumber::from(1)),
+    };
+    let gap_flags = BitFlags::<GAP_Flags>::from_endianness(writer.endianness);
+    gap
+      .create_submessage(gap_flags)
+      .map(|s| self.submessages.push(s));
+    self
+  }
+
   pub fn heartbeat_msg(
     mut self,
     writer: &RtpsWriter,
@@ -602,6 +667,63 @@ impl MessageBuilder {
   }
 }
 
+// Inline QoS of DATA and DATA_FRAG submessages
+fn inline_qos(cache_change: &CacheChange, endianness: Endianness) -> ParameterList {
+  let mut param_list = ParameterList::new();
+
+  // Key hash, and if this is a dispose or unregister, what the key or key
+  // hash means.
+  add_key_hash_and_status_info(&cache_change.data_value, &mut param_list);
+
+  let write_options = &cache_change.write_options;
+  // If we are sending related sample identity, then insert that.
+  if let Some(si) = write_options.related_sample_identity {
+    param_list.parameters.push(Parameter {
+      parameter_id: ParameterId::PID_RELATED_SAMPLE_IDENTITY,
+      value: si.write_to_vec_with_ctx(endianness).unwrap(),
+    });
+  }
+  if let Some(strength) = write_options.ownership_strength {
+    param_list.parameters.push(Parameter {
+      parameter_id: ParameterId::PID_OWNERSHIP_STRENGTH,
+      value: strength.write_to_vec_with_ctx(endianness).unwrap(),
+    });
+  }
+  if let Some(coherent_set) = write_options.coherent_set {
+    param_list.parameters.push(Parameter {
+      parameter_id: ParameterId::PID_COHERENT_SET,
+      value: coherent_set.write_to_vec_with_ctx(endianness).unwrap(),
+    });
+  }
+
+  param_list
+}
+
+// Inline QoS for the key hash (RTPS spec v2.5 Section 9.6.4.8) and, for
+// disposes and unregisters, StatusInfo (Section 9.6.4.9).
+fn add_key_hash_and_status_info(data_value: &DDSData, param_list: &mut ParameterList) {
+  if let Some(key_hash) = data_value.key_hash() {
+    param_list.parameters.push(Parameter {
+      parameter_id: ParameterId::PID_KEY_HASH,
+      value: key_hash.to_vec(),
+    });
+  }
+  let disposed_unregistered = match data_value.change_kind() {
+    ChangeKind::Alive => None,
+    ChangeKind::NotAliveDisposed => Some((true, false)),
+    ChangeKind::NotAliveUnregistered => Some((false, true)),
+  };
+  if let Some((disposed, unregistered)) = disposed_unregistered {
+    param_list
+      .parameters
+      .push(Parameter::create_pid_status_info_parameter(
+        disposed,
+        unregistered,
+        /* filtered */ false,
+      ));
+  }
+}
+
 #[cfg(test)]
 mod tests {
   #![allow(non_snake_case)]
@@ -825,6 +947,208 @@ mod tests {
     assert_eq!(bits1, serialized);
   }
 
+  #[test]
+  fn data_msg_dispose_has_key_hash_and_status_info() {
+    use crate::{
+      dds::{qos::InlineQos, traits::key::KeyHash, with_key::datawriter::WriteOptions},
+      messages::submessages::submessage_elements::serialized_payload::SerializedPayload,
+    };
+
+    let key_hash = KeyHash::from_cdr_bytes((1..=16).collect()).unwrap();
+    let dispose = DDSData::new_disposed_by_key(
+      ChangeKind::NotAliveDisposed,
+      SerializedPayload::new_from_bytes(
+        RepresentationIdentifier::CDR_LE,
+        Bytes::from_static(&[1, 0, 0, 0]),
+      ),
+    )
+    .with_key_hash(Some(key_hash));
+    let cache_change = CacheChange::new(
+      GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED),
+      SequenceNumber::new(1),
+      WriteOptions::default(),
+      dispose,
+    );
+    let message = MessageBuilder::new()
+      .data_msg(
+        &cache_change,
+        EntityId::UNKNOWN,
+        EntityId::UNKNOWN,
+        Endianness::LittleEndian,
+      )
+      .add_header_and_build(GuidPrefix::UNKNOWN);
+
+    let (data, flags) = match &message.submessages[0].body {
+      SubmessageBody::Entity(EntitySubmessage::Data(d, flags)) => (d, flags),
+      wtf => panic!("Unexpected message structure {:?}", wtf),
+    };
+    assert!(flags.contains(DATA_Flags::Key));
+    assert!(flags.contains(DATA_Flags::InlineQos));
+    // Submessage length must cover the whole inline QoS, sentinel included
+    assert_eq!(
+      usize::from(message.submessages[0].header.content_length),
+      data
+        .write_to_vec_with_ctx(Endianness::LittleEndian)
+        .unwrap()
+        .len()
+    );
+    let inline_qos = data.inline_qos.as_ref().unwrap();
+    assert_eq!(InlineQos::key_hash(inline_qos).unwrap(), Some(key_hash));
+    assert_eq!(
+      InlineQos::status_info(inline_qos, RepresentationIdentifier::CDR_LE)
+        .unwrap()
+        .change_kind(),
+      ChangeKind::NotAliveDisposed
+    );
+  }
+
+  #[test]
+  fn data_and_data_frag_inline_qos_both_endians() {
+    use crate::{
+      dds::{
+        qos::InlineQos,
+        with_key::datawriter::{WriteOptions, WriteOptionsBuilder},
+      },
+      messages::submessages::submessage_elements::serialized_payload::SerializedPayload,
+      structure::rpc::SampleIdentity,
+    };
+
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED);
+    let related_sample_identity = SampleIdentity {
+      writer_guid,
+      sequence_number: SequenceNumber::new(0x1_0000_0002),
+    };
+    let write_options: WriteOptions = WriteOptionsBuilder::new()
+      .related_sample_identity(related_sample_identity)
+      .ownership_strength(-3)
+      .coherent_set(SequenceNumber::new(5))
+      .build();
+    let cache_change = CacheChange::new(
+      writer_guid,
+      SequenceNumber::new(7),
+      write_options,
+      DDSData::new(SerializedPayload::new_from_bytes(
+        RepresentationIdentifier::CDR_LE,
+        Bytes::from_static(&[1, 2, 3, 4]),
+      )),
+    );
+
+    for endianness in [Endianness::LittleEndian, Endianness::BigEndian] {
+      let message = MessageBuilder::new()
+        .data_msg(
+          &cache_change,
+          EntityId::UNKNOWN,
+          writer_guid.entity_id,
+          endianness,
+        )
+        .data_frag_msg(
+          &cache_change,
+          EntityId::UNKNOWN,
+          writer_guid.entity_id,
+          FragmentNumber::new(1),
+          1024,
+          cache_change.data_value.payload_size() as u32,
+          endianness,
+        )
+        .add_header_and_build(GuidPrefix::UNKNOWN);
+      let bytes = Bytes::from(message.write_to_vec_with_ctx(endianness).unwrap());
+      let message = Message::read_from_buffer(&bytes).unwrap();
+      assert_eq!(message.submessages.len(), 2);
+
+      for submessage in &message.submessages {
+        let (inline_qos, ri) = match &submessage.body {
+          SubmessageBody::Entity(EntitySubmessage::Data(d, flags)) => (
+            &d.inline_qos,
+            DATA_Flags::cdr_representation_identifier(*flags),
+          ),
+          SubmessageBody::Entity(EntitySubmessage::DataFrag(df, flags)) => (
+            &df.inline_qos,
+            DATAFRAG_Flags::cdr_representation_identifier(*flags),
+          ),
+          wtf => panic!("Unexpected message structure {:?}", wtf),
+        };
+        let inline_qos = inline_qos.as_ref().unwrap();
+        assert_eq!(
+          InlineQos::related_sample_identity(inline_qos, ri).unwrap(),
+          Some(related_sample_identity)
+        );
+        assert_eq!(
+          InlineQos::ownership_strength(inline_qos, ri).unwrap(),
+          Some(-3)
+        );
+        assert_eq!(
+          InlineQos::coherent_set(inline_qos, ri).unwrap(),
+          Some(SequenceNumber::new(5))
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn rtps_message_rejects_malformed() {
+    // packet with INFO_DST, INFO_TS, DATA, HEARTBEAT
+    let bits = Bytes::from_static(&[
+      0x52, 0x54, 0x50, 0x53, 0x02, 0x03, 0x01, 0x0f, 0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00,
+      0x00, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x01, 0x0c, 0x00, 0x01, 0x03, 0x00, 0x0c, 0x29, 0x2d,
+      0x31, 0xa2, 0x28, 0x20, 0x02, 0x08, 0x09, 0x01, 0x08, 0x00, 0x1a, 0x15, 0xf3, 0x5e, 0x00,
+      0xcc, 0xfb, 0x13, 0x15, 0x05, 0x2c, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x07,
+      0x00, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x5b, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+      0x00, 0x04, 0x00, 0x00, 0x00, 0x52, 0x45, 0x44, 0x00, 0x69, 0x00, 0x00, 0x00, 0x17, 0x00,
+      0x00, 0x00, 0x1e, 0x00, 0x00, 0x00, 0x07, 0x01, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00,
+      0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x5b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+      0x5b, 0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00,
+    ]);
+    let modified = |index: usize, value: u8| {
+      let mut v = bits.to_vec();
+      v[index] = value;
+      Bytes::from(v)
+    };
+
+    assert!(matches!(
+      Message::read_from_buffer(&bits.slice(..10)),
+      Err(MessageError::TooShort(10))
+    ));
+    assert!(matches!(
+      Message::read_from_buffer(&modified(0, b'X')),
+      Err(MessageError::InvalidHeader(_))
+    ));
+    assert!(matches!(
+      Message::read_from_buffer(&bits.slice(..22)),
+      Err(MessageError::TruncatedSubmessageHeader { offset: 20 })
+    ));
+    // HEARTBEAT claims more than there is
+    assert!(matches!(
+      Message::read_from_buffer(&bits.slice(..bits.len() - 4)),
+      Err(MessageError::SubmessageLength {
+        kind: SubmessageKind::HEARTBEAT,
+        length: 28,
+        available: 24,
+        ..
+      })
+    ));
+    // DATA octetsToInlineQos too small, and too large
+    for octets_to_inline_qos in [4, 0xff] {
+      assert!(matches!(
+        Message::read_from_buffer(&modified(54, octets_to_inline_qos)),
+        Err(MessageError::Submessage {
+          kind: SubmessageKind::DATA,
+          offset: 48,
+          ..
+        })
+      ));
+    }
+
+    // Anything else must not panic either.
+    for len in 0..bits.len() {
+      let _ = Message::read_from_buffer(&bits.slice(..len));
+    }
+    for index in 0..bits.len() {
+      for value in [0x00, 0x80, 0xff] {
+        let _ = Message::read_from_buffer(&modified(index, value));
+      }
+    }
+  }
+
   // removed case test_RTPS_submessage_flags_helper , as it was cut-and-paste
   // from submessage_flag module - and obsoleted there.
 }
@@ -1,11 +1,19 @@
 pub(crate) mod builtin_data_deserializer;
+#[cfg(feature = "discovery")]
 pub(crate) mod builtin_data_serializer;
 pub(crate) mod cdr_deserializer;
+#[cfg(feature = "json")]
+pub(crate) mod cdr_json;
+pub(crate) mod cdr_projection;
 pub(crate) mod cdr_serializer;
 pub(crate) mod error;
 pub(crate) mod pl_cdr_deserializer;
+#[cfg(feature = "discovery")]
 pub(crate) mod pl_cdr_serializer;
+pub(crate) mod raw;
 pub(crate) mod visitors;
+pub(crate) mod xcdr2_deserializer;
+pub(crate) mod xcdr2_serializer;
 
 pub(crate) mod message;
 pub(crate) mod submessage;
@@ -16,6 +24,17 @@ pub(crate) use submessage::*;
 // public exports
 pub use cdr_serializer::{CDRSerializerAdapter, CdrSerializer};
 pub use cdr_deserializer::{CDRDeserializerAdapter, CdrDeserializer};
+pub use xcdr2_serializer::{
+  Appendable, Extensibility, ExtensibilityKind, Final, Mutable, Xcdr2Compound, Xcdr2Serializer,
+  Xcdr2SerializerAdapter,
+};
+pub use xcdr2_deserializer::{Xcdr2Deserializer, Xcdr2DeserializerAdapter};
+pub use cdr_projection::{CdrProjection, CdrValue};
+pub use raw::{RawDeserializerAdapter, RawSample, RawSerializerAdapter, SerializedSample};
+#[cfg(feature = "json")]
+pub use cdr_json::{CdrJsonDeserializerAdapter, CdrSchema, CdrSchemaSource};
 pub use byteorder::{BigEndian, LittleEndian};
+#[cfg(feature = "testing")]
+pub use message::{parse_rtps_message, MessageError};
 
 pub use crate::dds::traits::serde_adapters::{no_key, with_key};
@@ -0,0 +1,145 @@
+//! Receiving and writing samples without (de)serializing them.
+//!
+//! Generic tools, such as inspectors or recorders, may not know the data type
+//! of a Topic at all. A no_key DataReader of [`RawSample`] with
+//! [`RawDeserializerAdapter`] gives them the serialized payloads as they were
+//! written, and a no_key DataWriter with [`RawSerializerAdapter`] writes them
+//! again as they are.
+//!
+//! A DataReader of any type can also give its samples undecoded, as
+//! [`SerializedSample`]s, with `take_serialized`.
+
+use bytes::Bytes;
+use serde::{
+  de::{self, Deserializer},
+  ser::{self, Serializer},
+  Deserialize, Serialize,
+};
+
+use crate::{
+  dds::{sampleinfo::SampleInfo, traits::serde_adapters::no_key},
+  messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
+  serialization::error::Result,
+};
+
+/// Serialized payload of a sample, with the data representation it was
+/// written in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawSample {
+  pub encoding: RepresentationIdentifier,
+  pub bytes: Vec<u8>,
+}
+
+/// A received sample that has not been deserialized, with its
+/// [`SampleInfo`]. See
+/// [`with_key::DataReader::take_serialized`](crate::with_key::DataReader::take_serialized).
+#[derive(Debug, Clone)]
+pub struct SerializedSample {
+  pub(crate) sample_info: SampleInfo,
+  pub(crate) encoding: RepresentationIdentifier,
+  pub(crate) bytes: Bytes,
+}
+
+impl SerializedSample {
+  pub fn sample_info(&self) -> &SampleInfo {
+    &self.sample_info
+  }
+
+  /// Data representation of the serialized bytes.
+  pub fn encoding(&self) -> RepresentationIdentifier {
+    self.encoding
+  }
+
+  /// The serialized data. For a sample without data, i.e. when
+  /// [`SampleInfo::valid_data`] is false, this is the serialized key of the
+  /// instance, or empty if the writer identified the instance only by its key
+  /// hash.
+  pub fn bytes(&self) -> &Bytes {
+    &self.bytes
+  }
+
+  /// The serialized data as a [`RawSample`], e.g. for writing it again with a
+  /// DataWriter of [`RawSerializerAdapter`].
+  pub fn to_raw_sample(&self) -> RawSample {
+    RawSample {
+      encoding: self.encoding,
+      bytes: self.bytes.to_vec(),
+    }
+  }
+}
+
+// DataReaders require Deserialize, but the bytes are not decoded.
+impl<'de> Deserialize<'de> for RawSample {
+  fn deserialize<D>(_deserializer: D) -> std::result::Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Err(de::Error::custom(
+      "RawSample can only be deserialized with RawDeserializerAdapter",
+    ))
+  }
+}
+
+// DataWriters require Serialize, but the bytes are written as they are.
+impl Serialize for RawSample {
+  fn serialize<S>(&self, _serializer: S) -> std::result::Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    Err(ser::Error::custom(
+      "RawSample can only be serialized with RawSerializerAdapter",
+    ))
+  }
+}
+
+/// DeserializerAdapter that accepts samples in any known data representation,
+/// and returns them as [`RawSample`].
+pub struct RawDeserializerAdapter {}
+
+const REPR_IDS: [RepresentationIdentifier; 13] = [
+  RepresentationIdentifier::CDR_BE,
+  RepresentationIdentifier::CDR_LE,
+  RepresentationIdentifier::PL_CDR_BE,
+  RepresentationIdentifier::PL_CDR_LE,
+  RepresentationIdentifier::CDR2_BE,
+  RepresentationIdentifier::CDR2_LE,
+  RepresentationIdentifier::D_CDR2_BE,
+  RepresentationIdentifier::D_CDR2_LE,
+  RepresentationIdentifier::PL_CDR2_BE,
+  RepresentationIdentifier::PL_CDR2_LE,
+  RepresentationIdentifier::D_CDR_BE,
+  RepresentationIdentifier::D_CDR_LE,
+  RepresentationIdentifier::XML,
+];
+
+impl no_key::DeserializerAdapter<RawSample> for RawDeserializerAdapter {
+  fn supported_encodings() -> &'static [RepresentationIdentifier] {
+    &REPR_IDS
+  }
+
+  fn from_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<RawSample> {
+    Ok(RawSample {
+      encoding,
+      bytes: input_bytes.to_vec(),
+    })
+  }
+}
+
+/// SerializerAdapter that writes the bytes of a [`RawSample`] in the data
+/// representation of the sample.
+pub struct RawSerializerAdapter {}
+
+impl no_key::SerializerAdapter<RawSample> for RawSerializerAdapter {
+  // Only used if the sample is not at hand.
+  fn output_encoding() -> RepresentationIdentifier {
+    RepresentationIdentifier::CDR_LE
+  }
+
+  fn output_encoding_of(value: &RawSample) -> RepresentationIdentifier {
+    value.encoding
+  }
+
+  fn to_bytes(value: &RawSample) -> Result<Bytes> {
+    Ok(Bytes::copy_from_slice(&value.bytes))
+  }
+}
@@ -14,8 +14,12 @@ impl<'de> Visitor<'de> for BuiltinDataDeserializer {
     E: Error,
   {
     match RepresentationIdentifier::from_bytes(&v[..2]) {
-      Ok(RepresentationIdentifier::PL_CDR_LE) => Ok(self.parse_data_little_endian(&v[2..])),
-      Ok(RepresentationIdentifier::PL_CDR_BE) => Ok(self.parse_data_big_endian(&v[2..])),
+      Ok(RepresentationIdentifier::PL_CDR_LE) => {
+        self.parse_data_little_endian(&v[2..]).map_err(E::custom)
+      }
+      Ok(RepresentationIdentifier::PL_CDR_BE) => {
+        self.parse_data_big_endian(&v[2..]).map_err(E::custom)
+      }
       _ => Err(E::missing_field("representation identifier")),
     }
   }
@@ -0,0 +1,1004 @@
+use std::marker::PhantomData;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use serde::de::{
+  self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+  VariantAccess, Visitor,
+};
+
+use crate::{
+  dds::traits::{
+    key::Keyed,
+    serde_adapters::{no_key, with_key},
+  },
+  messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
+  serialization::{
+    error::{Error, Result},
+    xcdr2_serializer::{Extensibility, LC_NEXTINT, XCDR2_MAX_ALIGN},
+  },
+};
+
+/// This type adapts [`Xcdr2Deserializer`] to work as a
+/// [`no_key::DeserializerAdapter`] and [`with_key::DeserializerAdapter`].
+///
+/// All XCDR2 encodings are supported. The extensibility of the received data
+/// is decided by the representation identifier, so the writer may use any
+/// of the [`Xcdr2SerializerAdapter`](crate::serialization::Xcdr2SerializerAdapter)
+/// variants.
+pub struct Xcdr2DeserializerAdapter<D> {
+  phantom: PhantomData<D>,
+}
+
+const REPR_IDS: [RepresentationIdentifier; 6] = [
+  RepresentationIdentifier::CDR2_LE,
+  RepresentationIdentifier::CDR2_BE,
+  RepresentationIdentifier::D_CDR2_LE,
+  RepresentationIdentifier::D_CDR2_BE,
+  RepresentationIdentifier::PL_CDR2_LE,
+  RepresentationIdentifier::PL_CDR2_BE,
+];
+
+impl<D> no_key::DeserializerAdapter<D> for Xcdr2DeserializerAdapter<D>
+where
+  D: DeserializeOwned,
+{
+  fn supported_encodings() -> &'static [RepresentationIdentifier] {
+    &REPR_IDS
+  }
+
+  fn from_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<D> {
+    from_bytes(input_bytes, encoding)
+  }
+}
+
+impl<D> with_key::DeserializerAdapter<D> for Xcdr2DeserializerAdapter<D>
+where
+  D: Keyed + DeserializeOwned,
+  <D as Keyed>::K: DeserializeOwned,
+{
+  fn key_from_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<D::K> {
+    from_bytes(input_bytes, encoding)
+  }
+}
+
+pub(crate) fn from_bytes<T>(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<T>
+where
+  T: DeserializeOwned,
+{
+  match Extensibility::from_representation_identifier(encoding) {
+    Some((extensibility, true)) => T::deserialize(&mut Xcdr2Deserializer::<LittleEndian>::new(
+      input_bytes,
+      extensibility,
+    )),
+    Some((extensibility, false)) => T::deserialize(&mut Xcdr2Deserializer::<BigEndian>::new(
+      input_bytes,
+      extensibility,
+    )),
+    None => Err(Error::Message(format!(
+      "Unknown representaiton identifier {:?}.",
+      encoding
+    ))),
+  }
+}
+
+// A sequence, array or map that has not yet seen its first element. The first
+// element tells if the collection is delimited, i.e. if the u32 we read as
+// sequence length was actually a DHEADER.
+enum Pending {
+  Sequence { index: usize },
+  Array,
+}
+
+/// An XCDR2 deserializer implementation. See
+/// [`Xcdr2Serializer`](crate::serialization::Xcdr2Serializer) for how Rust
+/// types map to XCDR2.
+///
+/// Appendable structures may carry members unknown to the reader at their
+/// end. These are skipped. If members are missing from the end, because the
+/// writer has an older version of the type, they are filled in from
+/// `#[serde(default)]`. Mutable structures may carry unknown members
+/// anywhere, and they are skipped as well. Members missing from a mutable
+/// structure are reported to serde as missing fields, which is fine for
+/// `Option` members.
+pub struct Xcdr2Deserializer<'de, BO> {
+  input: &'de [u8],
+  position: usize,
+  extensibility: Extensibility,
+  pending: Option<Pending>,
+  sequence_lengths: Vec<Option<usize>>, // resolved lengths of delimited sequences
+  empty_sequence: bool,                 // first element of an empty delimited sequence
+  member_value: bool,                   // next value is a member of a mutable struct
+  phantom: PhantomData<BO>,
+}
+
+impl<'de, BO> Xcdr2Deserializer<'de, BO>
+where
+  BO: ByteOrder,
+{
+  pub fn new(input: &'de [u8], extensibility: Extensibility) -> Self {
+    Self {
+      input,
+      position: 0,
+      extensibility,
+      pending: None,
+      sequence_lengths: Vec::new(),
+      empty_sequence: false,
+      member_value: false,
+      phantom: PhantomData,
+    }
+  }
+
+  fn next_bytes(&mut self, count: usize) -> Result<&'de [u8]> {
+    let end = self.position.checked_add(count).ok_or(Error::Eof)?;
+    if end <= self.input.len() {
+      let bytes = &self.input[self.position..end];
+      self.position = end;
+      Ok(bytes)
+    } else {
+      Err(Error::Eof)
+    }
+  }
+
+  fn align(&mut self, alignment: usize) -> Result<()> {
+    let alignment = alignment.min(XCDR2_MAX_ALIGN);
+    let modulo = self.position % alignment;
+    if modulo != 0 {
+      self.next_bytes(alignment - modulo)?;
+    }
+    Ok(())
+  }
+
+  fn read_u32(&mut self) -> Result<u32> {
+    self.align(4)?;
+    Ok(BO::read_u32(self.next_bytes(4)?))
+  }
+
+  // Read a DHEADER and return the position where the delimited data ends.
+  fn read_dheader(&mut self) -> Result<usize> {
+    let length = self.read_u32()? as usize;
+    let end = self.position.checked_add(length).ok_or(Error::Eof)?;
+    if end <= self.input.len() {
+      Ok(end)
+    } else {
+      Err(Error::Eof)
+    }
+  }
+
+  fn skip_to(&mut self, end: usize) -> Result<()> {
+    if self.position <= end {
+      self.position = end;
+      Ok(())
+    } else {
+      Err(Error::Message(format!(
+        "XCDR2: Read past end of delimited data by {} bytes.",
+        self.position - end
+      )))
+    }
+  }
+
+  // Counterpart of Xcdr2Serializer::begin_value
+  fn begin_value(&mut self, compound: bool) -> Result<()> {
+    self.member_value = false;
+    match self.pending.take() {
+      Some(Pending::Sequence { index }) if compound => {
+        // What we read as the length was the DHEADER. The length follows.
+        let length = self.read_u32()? as usize;
+        if length == 0 {
+          // We are already deserializing the first element, but there is
+          // none. Abort it. SequenceHelper recognizes this.
+          self.empty_sequence = true;
+          return Err(Error::Message(
+            "XCDR2: empty delimited sequence".to_string(),
+          ));
+        }
+        self.sequence_lengths[index] = Some(length);
+      }
+      Some(Pending::Array) if compound => {
+        self.read_dheader()?;
+      }
+      _ => (),
+    }
+    Ok(())
+  }
+
+  fn read_number(&mut self, size: usize) -> Result<&'de [u8]> {
+    self.begin_value(false)?;
+    self.align(size)?;
+    self.next_bytes(size)
+  }
+
+  fn read_presence_flag(&mut self) -> Result<bool> {
+    match self.next_bytes(1)?[0] {
+      0 => Ok(false),
+      1 => Ok(true),
+      x => Err(Error::BadBoolean(x)),
+    }
+  }
+
+  fn deserialize_collection<V>(&mut self, visitor: V, kind: Collection) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.begin_value(true)?;
+    let (expected_count, index) = match kind {
+      Collection::Array(0) => (0, None),
+      Collection::Array(count) => {
+        self.pending = Some(Pending::Array);
+        (count, None)
+      }
+      Collection::Sequence | Collection::Map => match self.read_u32()? as usize {
+        0 => (0, None),
+        count => {
+          let index = self.sequence_lengths.len();
+          self.sequence_lengths.push(None);
+          self.pending = Some(Pending::Sequence { index });
+          (count, Some(index))
+        }
+      },
+    };
+    let helper = SequenceHelper {
+      de: &mut *self,
+      element_counter: 0,
+      expected_count,
+      index,
+      struct_members: false,
+    };
+    let result = match kind {
+      Collection::Map => visitor.visit_map(helper),
+      _ => visitor.visit_seq(helper),
+    };
+    self.pending = None;
+    if index.is_some() {
+      self.sequence_lengths.pop();
+    }
+    result
+  }
+}
+
+#[derive(Clone, Copy)]
+enum Collection {
+  Sequence,
+  Map,
+  Array(usize),
+}
+
+macro_rules! deserialize_number {
+  ($deserialize:ident, $visit:ident, $read:ident, $size:expr) => {
+    fn $deserialize<V>(self, visitor: V) -> Result<V::Value>
+    where
+      V: Visitor<'de>,
+    {
+      let bytes = self.read_number($size)?;
+      visitor.$visit(BO::$read(bytes))
+    }
+  };
+}
+
+impl<'de, BO> de::Deserializer<'de> for &mut Xcdr2Deserializer<'de, BO>
+where
+  BO: ByteOrder,
+{
+  type Error = Error;
+
+  /// XCDR2 is not a self-describing data format, so we cannot implement
+  /// this.
+  fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    Err(Error::Message(
+      "xcdr2_deserializer: Cannot deserialize \"any\" type. ".to_string(),
+    ))
+  }
+
+  fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.begin_value(false)?;
+    match self.next_bytes(1)?[0] {
+      0 => visitor.visit_bool(false),
+      1 => visitor.visit_bool(true),
+      x => Err(Error::BadBoolean(x)),
+    }
+  }
+
+  deserialize_number!(deserialize_i16, visit_i16, read_i16, 2);
+  deserialize_number!(deserialize_i32, visit_i32, read_i32, 4);
+  deserialize_number!(deserialize_i64, visit_i64, read_i64, 8);
+  deserialize_number!(deserialize_i128, visit_i128, read_i128, 16);
+
+  deserialize_number!(deserialize_u16, visit_u16, read_u16, 2);
+  deserialize_number!(deserialize_u32, visit_u32, read_u32, 4);
+  deserialize_number!(deserialize_u64, visit_u64, read_u64, 8);
+  deserialize_number!(deserialize_u128, visit_u128, read_u128, 16);
+
+  deserialize_number!(deserialize_f32, visit_f32, read_f32, 4);
+  deserialize_number!(deserialize_f64, visit_f64, read_f64, 8);
+
+  fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    visitor.visit_i8(self.read_number(1)?[0] as i8)
+  }
+
+  fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    visitor.visit_u8(self.read_number(1)?[0])
+  }
+
+  fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    let codepoint = BO::read_u32(self.read_number(4)?);
+    match std::char::from_u32(codepoint) {
+      Some(c) => visitor.visit_char(c),
+      None => Err(Error::BadChar(codepoint)),
+    }
+  }
+
+  fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.begin_value(true)?;
+    let bytes_len = self.read_u32()? as usize;
+    let bytes = self.next_bytes(bytes_len)?; // length includes null terminator
+    let bytes_without_null = match bytes.split_last() {
+      Some((0, content)) => content,
+      _ => bytes,
+    };
+    match std::str::from_utf8(bytes_without_null) {
+      Ok(s) => visitor.visit_str(s),
+      Err(utf8_err) => Err(Error::BadString(utf8_err)),
+    }
+  }
+
+  fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_str(visitor)
+  }
+
+  fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.begin_value(true)?;
+    let bytes_len = self.read_u32()? as usize;
+    visitor.visit_borrowed_bytes(self.next_bytes(bytes_len)?)
+  }
+
+  fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_bytes(visitor)
+  }
+
+  fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    if self.member_value {
+      // A mutable struct member that is present at all is Some.
+      self.member_value = false;
+      return visitor.visit_some(self);
+    }
+    self.begin_value(true)?;
+    if self.read_presence_flag()? {
+      visitor.visit_some(self)
+    } else {
+      visitor.visit_none()
+    }
+  }
+
+  fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.begin_value(false)?;
+    visitor.visit_unit()
+  }
+
+  fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_unit(visitor)
+  }
+
+  fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    visitor.visit_newtype_struct(self)
+  }
+
+  fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_collection(visitor, Collection::Sequence)
+  }
+
+  fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_collection(visitor, Collection::Array(len))
+  }
+
+  fn deserialize_tuple_struct<V>(
+    self,
+    _name: &'static str,
+    len: usize,
+    visitor: V,
+  ) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_tuple(len, visitor)
+  }
+
+  fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_collection(visitor, Collection::Map)
+  }
+
+  fn deserialize_struct<V>(
+    self,
+    _name: &'static str,
+    fields: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.begin_value(true)?;
+    match self.extensibility {
+      Extensibility::Final => visitor.visit_seq(SequenceHelper {
+        de: self,
+        element_counter: 0,
+        expected_count: fields.len(),
+        index: None,
+        struct_members: false,
+      }),
+      Extensibility::Appendable => {
+        let end = self.read_dheader()?;
+        // Limit input to the struct, so that members missing at the end,
+        // because of an older version of the type, are noticed.
+        let input = self.input;
+        self.input = &input[..end];
+        let value = visitor.visit_seq(SequenceHelper {
+          de: &mut *self,
+          element_counter: 0,
+          expected_count: fields.len(),
+          index: None,
+          struct_members: true,
+        });
+        self.input = input;
+        let value = value?;
+        // Skip members appended in a newer version of the type.
+        self.skip_to(end)?;
+        Ok(value)
+      }
+      Extensibility::Mutable => {
+        let end = self.read_dheader()?;
+        let value = visitor.visit_map(MemberHelper {
+          de: &mut *self,
+          end,
+          member_end: end,
+        })?;
+        self.skip_to(end)?;
+        Ok(value)
+      }
+    }
+  }
+
+  fn deserialize_enum<V>(
+    self,
+    _name: &'static str,
+    _variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.begin_value(false)?;
+    visitor.visit_enum(EnumerationHelper { de: self })
+  }
+
+  fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_u32(visitor)
+  }
+
+  // Only unknown members of mutable structs are ignored. MemberHelper skips
+  // over them.
+  fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    if self.member_value {
+      self.member_value = false;
+      visitor.visit_unit()
+    } else {
+      self.deserialize_any(visitor)
+    }
+  }
+}
+
+// ----------------------------------------------------------
+
+struct EnumerationHelper<'a, 'de: 'a, BO> {
+  de: &'a mut Xcdr2Deserializer<'de, BO>,
+}
+
+impl<'de, BO> EnumAccess<'de> for EnumerationHelper<'_, 'de, BO>
+where
+  BO: ByteOrder,
+{
+  type Error = Error;
+  type Variant = Self;
+
+  fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+  where
+    V: DeserializeSeed<'de>,
+  {
+    let discriminator = self.de.read_u32()?;
+    let val: Result<_> = seed.deserialize(discriminator.into_deserializer());
+    Ok((val?, self))
+  }
+}
+
+impl<'de, BO> VariantAccess<'de> for EnumerationHelper<'_, 'de, BO>
+where
+  BO: ByteOrder,
+{
+  type Error = Error;
+
+  fn unit_variant(self) -> Result<()> {
+    Ok(())
+  }
+
+  fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+  where
+    T: DeserializeSeed<'de>,
+  {
+    seed.deserialize(self.de)
+  }
+
+  fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    visitor.visit_seq(SequenceHelper {
+      de: self.de,
+      element_counter: 0,
+      expected_count: len,
+      index: None,
+      struct_members: false,
+    })
+  }
+
+  fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.tuple_variant(fields.len(), visitor)
+  }
+}
+
+// ----------------------------------------------------------
+
+struct SequenceHelper<'a, 'de: 'a, BO> {
+  de: &'a mut Xcdr2Deserializer<'de, BO>,
+  element_counter: usize,
+  expected_count: usize,
+  index: Option<usize>, // to sequence_lengths, if length may change
+  struct_members: bool, // elements are members of an appendable struct
+}
+
+impl<'de, BO> SequenceHelper<'_, 'de, BO>
+where
+  BO: ByteOrder,
+{
+  fn next<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+  where
+    T: DeserializeSeed<'de>,
+  {
+    if self.element_counter == self.expected_count {
+      return Ok(None);
+    }
+    self.element_counter += 1;
+    // Members missing from the end of an appendable struct are filled in by
+    // serde from #[serde(default)].
+    let at_end = self.struct_members && self.de.input.len() == self.de.position;
+    let result = match seed.deserialize(&mut *self.de) {
+      Err(Error::Eof) if at_end => {
+        self.expected_count = self.element_counter - 1;
+        return Ok(None);
+      }
+      result => result,
+    };
+    if self.element_counter == 1 {
+      if let Some(index) = self.index {
+        if self.de.empty_sequence {
+          self.de.empty_sequence = false;
+          self.expected_count = 0;
+          return Ok(None);
+        }
+        if let Some(length) = self.de.sequence_lengths[index] {
+          self.expected_count = length;
+        }
+      }
+    }
+    result.map(Some)
+  }
+}
+
+impl<'de, BO> SeqAccess<'de> for SequenceHelper<'_, 'de, BO>
+where
+  BO: ByteOrder,
+{
+  type Error = Error;
+
+  fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+  where
+    T: DeserializeSeed<'de>,
+  {
+    self.next(seed)
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    // The length may still change, if the sequence turns out to be delimited.
+    if self.index.is_some() && self.element_counter == 0 {
+      None
+    } else {
+      Some(self.expected_count - self.element_counter)
+    }
+  }
+}
+
+impl<'de, BO> MapAccess<'de> for SequenceHelper<'_, 'de, BO>
+where
+  BO: ByteOrder,
+{
+  type Error = Error;
+
+  fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+  where
+    K: DeserializeSeed<'de>,
+  {
+    self.next(seed)
+  }
+
+  fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+  where
+    V: DeserializeSeed<'de>,
+  {
+    seed.deserialize(&mut *self.de)
+  }
+}
+
+// ----------------------------------------------------------
+
+// Presents the members of a mutable struct to serde as a map from member id
+// to member value.
+struct MemberHelper<'a, 'de: 'a, BO> {
+  de: &'a mut Xcdr2Deserializer<'de, BO>,
+  end: usize,
+  member_end: usize,
+}
+
+impl<'de, BO> MapAccess<'de> for MemberHelper<'_, 'de, BO>
+where
+  BO: ByteOrder,
+{
+  type Error = Error;
+
+  fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+  where
+    K: DeserializeSeed<'de>,
+  {
+    let padding = (4 - self.de.position % 4) % 4;
+    if self.de.position + padding >= self.end {
+      return Ok(None);
+    }
+    // EMHEADER1, DDS-XTypes v1.3 Section 7.4.3.5.3
+    let emheader = self.de.read_u32()?;
+    let length_code = (emheader >> 28) & 0x7;
+    let member_id = emheader & 0x0fff_ffff;
+    let length = match length_code {
+      0..=3 => 1 << length_code,
+      LC_NEXTINT => self.de.read_u32()? as usize,
+      _ => {
+        // NEXTINT is also the first word of the member value.
+        let next_int = BO::read_u32(
+          self
+            .de
+            .input
+            .get(self.de.position..self.de.position + 4)
+            .ok_or(Error::Eof)?,
+        ) as usize;
+        let multiplier = match length_code {
+          5 => 1,
+          6 => 4,
+          _ => 8,
+        };
+        next_int
+          .checked_mul(multiplier)
+          .and_then(|l| l.checked_add(4))
+          .ok_or(Error::Eof)?
+      }
+    };
+    self.member_end = self.de.position.checked_add(length).ok_or(Error::Eof)?;
+    if self.member_end > self.end {
+      return Err(Error::Message(format!(
+        "XCDR2: Member {} overruns its struct.",
+        member_id
+      )));
+    }
+    seed.deserialize(member_id.into_deserializer()).map(Some)
+  }
+
+  fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+  where
+    V: DeserializeSeed<'de>,
+  {
+    self.de.member_value = true;
+    let value = seed.deserialize(&mut *self.de);
+    self.de.member_value = false;
+    let value = value?;
+    self.de.skip_to(self.member_end)?;
+    Ok(value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use byteorder::{BigEndian, LittleEndian};
+  use serde::{Deserialize, Serialize};
+
+  use super::*;
+  use crate::serialization::xcdr2_serializer::to_bytes;
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+  struct Inner {
+    a: u16,
+    s: String,
+  }
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+  enum Shape {
+    Empty,
+    Circle(f64),
+    Rectangle { w: u32, h: u32 },
+  }
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+  struct Everything {
+    flag: bool,
+    small: i8,
+    big: u64,
+    real: f64,
+    text: String,
+    numbers: Vec<i32>,
+    inners: Vec<Inner>,
+    no_inners: Vec<Inner>,
+    array: [Inner; 2],
+    nested: Vec<Vec<u8>>,
+    maybe: Option<Inner>,
+    maybe_not: Option<u32>,
+    shapes: Vec<Shape>,
+    last: char,
+  }
+
+  fn everything() -> Everything {
+    let inner = |a: u16| Inner {
+      a,
+      s: format!("inner {}", a),
+    };
+    Everything {
+      flag: true,
+      small: -3,
+      big: 1 << 40,
+      real: -2.5,
+      text: "text".to_string(),
+      numbers: vec![1, -2, 3],
+      inners: vec![inner(1), inner(2)],
+      no_inners: vec![],
+      array: [inner(3), inner(4)],
+      nested: vec![vec![1, 2, 3], vec![]],
+      maybe: Some(inner(5)),
+      maybe_not: None,
+      shapes: vec![
+        Shape::Empty,
+        Shape::Circle(1.5),
+        Shape::Rectangle { w: 2, h: 3 },
+      ],
+      last: 'ä',
+    }
+  }
+
+  #[test]
+  fn xcdr2_round_trip() {
+    for &extensibility in &[
+      Extensibility::Final,
+      Extensibility::Appendable,
+      Extensibility::Mutable,
+    ] {
+      let value = everything();
+      let repr_id = extensibility.representation_identifier(true);
+      let bytes = to_bytes::<_, LittleEndian>(&value, extensibility).unwrap();
+      let decoded: Everything = from_bytes(&bytes, repr_id).unwrap();
+      assert_eq!(decoded, value, "{:?}", extensibility);
+
+      let repr_id = extensibility.representation_identifier(false);
+      let bytes = to_bytes::<_, BigEndian>(&value, extensibility).unwrap();
+      let decoded: Everything = from_bytes(&bytes, repr_id).unwrap();
+      assert_eq!(decoded, value, "{:?}", extensibility);
+    }
+  }
+
+  #[test]
+  fn xcdr2_empty_delimited_sequence() {
+    // Written by an implementation that knows the element type: DHEADER 4,
+    // length 0.
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Holder {
+      inners: Vec<Inner>,
+      after: u8,
+    }
+    let bytes = [4, 0, 0, 0, 0, 0, 0, 0, 9];
+    let decoded: Holder = from_bytes(&bytes, RepresentationIdentifier::CDR2_LE).unwrap();
+    assert_eq!(
+      decoded,
+      Holder {
+        inners: vec![],
+        after: 9
+      }
+    );
+  }
+
+  #[test]
+  fn xcdr2_appendable_evolution() {
+    #[derive(Serialize)]
+    struct V2 {
+      a: u16,
+      b: u32,
+    }
+    #[derive(Serialize)]
+    struct Outer2 {
+      inner: V2,
+      tail: u8,
+    }
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct V1 {
+      a: u16,
+    }
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Outer1 {
+      inner: V1,
+      tail: u8,
+    }
+    let bytes = to_bytes::<_, LittleEndian>(
+      &Outer2 {
+        inner: V2 { a: 5, b: 6 },
+        tail: 7,
+      },
+      Extensibility::Appendable,
+    )
+    .unwrap();
+    let decoded: Outer1 = from_bytes(&bytes, RepresentationIdentifier::D_CDR2_LE).unwrap();
+    assert_eq!(
+      decoded,
+      Outer1 {
+        inner: V1 { a: 5 },
+        tail: 7
+      }
+    );
+  }
+
+  #[test]
+  fn xcdr2_mutable_evolution() {
+    #[derive(Serialize)]
+    struct M2 {
+      x: i32,
+      y: Option<u16>,
+      z: Vec<Inner>,
+    }
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct M1 {
+      x: i32,
+      y: Option<u16>,
+    }
+    let m2 = M2 {
+      x: 1,
+      y: None,
+      z: vec![Inner {
+        a: 2,
+        s: "unknown".to_string(),
+      }],
+    };
+    // Unknown member z is skipped, absent member y is None.
+    let bytes = to_bytes::<_, LittleEndian>(&m2, Extensibility::Mutable).unwrap();
+    let decoded: M1 = from_bytes(&bytes, RepresentationIdentifier::PL_CDR2_LE).unwrap();
+    assert_eq!(decoded, M1 { x: 1, y: None });
+  }
+
+  #[test]
+  fn xcdr2_appendable_missing_members() {
+    #[derive(Serialize)]
+    struct V1 {
+      a: u16,
+    }
+    #[derive(Serialize)]
+    struct Outer1 {
+      inner: V1,
+      tail: u8,
+    }
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct V2 {
+      a: u16,
+      #[serde(default)]
+      b: Vec<u32>,
+    }
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Outer2 {
+      inner: V2,
+      tail: u8,
+    }
+    let bytes = to_bytes::<_, LittleEndian>(
+      &Outer1 {
+        inner: V1 { a: 5 },
+        tail: 7,
+      },
+      Extensibility::Appendable,
+    )
+    .unwrap();
+    let decoded: Outer2 = from_bytes(&bytes, RepresentationIdentifier::D_CDR2_LE).unwrap();
+    assert_eq!(
+      decoded,
+      Outer2 {
+        inner: V2 { a: 5, b: vec![] },
+        tail: 7
+      }
+    );
+  }
+
+  #[test]
+  fn xcdr2_mutable_length_codes() {
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct M {
+      x: i32,
+      s: String,
+    }
+    #[rustfmt::skip]
+    let bytes = [
+      0x14, 0x00, 0x00, 0x00, // DHEADER
+      // member 1 first, LC 5: NEXTINT is the string length
+      0x01, 0x00, 0x00, 0x50, 0x03, 0x00, 0x00, 0x00, b'a', b'b', 0x00, 0x00,
+      // member 0, LC 2: 4 bytes
+      0x00, 0x00, 0x00, 0x20, 0x05, 0x00, 0x00, 0x00,
+    ];
+    let decoded: M = from_bytes(&bytes, RepresentationIdentifier::PL_CDR2_LE).unwrap();
+    assert_eq!(
+      decoded,
+      M {
+        x: 5,
+        s: "ab".to_string()
+      }
+    );
+  }
+}
@@ -0,0 +1,881 @@
+use std::marker::PhantomData;
+
+use serde::{ser, Serialize};
+use bytes::Bytes;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+  dds::traits::{
+    key::Keyed,
+    serde_adapters::{no_key, with_key},
+  },
+  messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
+  serialization::error::{Error, Result},
+};
+
+/// Extensibility kind of a data type, as defined in DDS-XTypes v1.3 Section
+/// 7.2.2.4.4.
+///
+/// The extensibility kind decides how structures are encapsulated in XCDR2:
+/// * `Final` structures are encoded as plain member sequences (`CDR2`
+///   representation),
+/// * `Appendable` structures are prefixed by a DHEADER giving their length in
+///   bytes, so that readers can skip members appended in later versions of
+///   the type (`D_CDR2` representation),
+/// * `Mutable` structures are prefixed by a DHEADER and each member is prefixed
+///   by an EMHEADER carrying its member id and length, so that members may be
+///   added, removed or reordered (`PL_CDR2` representation).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extensibility {
+  Final,
+  Appendable,
+  Mutable,
+}
+
+impl Extensibility {
+  /// The representation identifier used in the encapsulation header of data
+  /// encoded with this extensibility.
+  pub fn representation_identifier(self, little_endian: bool) -> RepresentationIdentifier {
+    match (self, little_endian) {
+      (Self::Final, true) => RepresentationIdentifier::CDR2_LE,
+      (Self::Final, false) => RepresentationIdentifier::CDR2_BE,
+      (Self::Appendable, true) => RepresentationIdentifier::D_CDR2_LE,
+      (Self::Appendable, false) => RepresentationIdentifier::D_CDR2_BE,
+      (Self::Mutable, true) => RepresentationIdentifier::PL_CDR2_LE,
+      (Self::Mutable, false) => RepresentationIdentifier::PL_CDR2_BE,
+    }
+  }
+
+  /// Inverse of [`representation_identifier`](Self::representation_identifier):
+  /// Returns the extensibility and little-endianness of an XCDR2
+  /// representation, or `None` if `repr_id` is not XCDR2.
+  pub fn from_representation_identifier(repr_id: RepresentationIdentifier) -> Option<(Self, bool)> {
+    match repr_id {
+      RepresentationIdentifier::CDR2_LE => Some((Self::Final, true)),
+      RepresentationIdentifier::CDR2_BE => Some((Self::Final, false)),
+      RepresentationIdentifier::D_CDR2_LE => Some((Self::Appendable, true)),
+      RepresentationIdentifier::D_CDR2_BE => Some((Self::Appendable, false)),
+      RepresentationIdentifier::PL_CDR2_LE => Some((Self::Mutable, true)),
+      RepresentationIdentifier::PL_CDR2_BE => Some((Self::Mutable, false)),
+      _ => None,
+    }
+  }
+}
+
+/// Type-level selection of [`Extensibility`] for
+/// [`Xcdr2SerializerAdapter`].
+pub trait ExtensibilityKind {
+  const EXTENSIBILITY: Extensibility;
+}
+
+/// Selects [`Extensibility::Final`] in [`Xcdr2SerializerAdapter`].
+pub struct Final;
+/// Selects [`Extensibility::Appendable`] in [`Xcdr2SerializerAdapter`].
+pub struct Appendable;
+/// Selects [`Extensibility::Mutable`] in [`Xcdr2SerializerAdapter`].
+pub struct Mutable;
+
+impl ExtensibilityKind for Final {
+  const EXTENSIBILITY: Extensibility = Extensibility::Final;
+}
+impl ExtensibilityKind for Appendable {
+  const EXTENSIBILITY: Extensibility = Extensibility::Appendable;
+}
+impl ExtensibilityKind for Mutable {
+  const EXTENSIBILITY: Extensibility = Extensibility::Mutable;
+}
+
+// XCDR2 aligns nothing to more than 4 octets. DDS-XTypes v1.3 Section 7.4.2.
+pub(crate) const XCDR2_MAX_ALIGN: usize = 4;
+
+// EMHEADER1 length code meaning "NEXTINT follows and gives member length".
+pub(crate) const LC_NEXTINT: u32 = 4;
+
+pub(crate) fn is_little_endian<BO: ByteOrder>() -> bool {
+  let mut buf = [0u8; 2];
+  BO::write_u16(&mut buf, 1);
+  buf[0] == 1
+}
+
+// ---------------------------------------------------------------------------------
+// ---------------------------------------------------------------------------------
+
+/// This type adapts [`Xcdr2Serializer`] to work as a
+/// [`no_key::SerializerAdapter`] and [`with_key::SerializerAdapter`].
+///
+/// Type parameter `E` selects the extensibility of the serialized
+/// structures, and thereby the output encoding: [`Final`] produces `CDR2`,
+/// [`Appendable`] produces `D_CDR2` and [`Mutable`] produces `PL_CDR2`.
+/// `BO` is the byte order.
+///
+/// Use [`Xcdr2DeserializerAdapter`](crate::serialization::Xcdr2DeserializerAdapter)
+/// on the reading side. It accepts all of these encodings.
+pub struct Xcdr2SerializerAdapter<D, E = Final, BO = LittleEndian>
+where
+  E: ExtensibilityKind,
+  BO: ByteOrder,
+{
+  phantom: PhantomData<D>,
+  extensibility: PhantomData<E>,
+  ghost: PhantomData<BO>,
+}
+
+impl<D, E, BO> no_key::SerializerAdapter<D> for Xcdr2SerializerAdapter<D, E, BO>
+where
+  D: Serialize,
+  E: ExtensibilityKind,
+  BO: ByteOrder,
+{
+  fn output_encoding() -> RepresentationIdentifier {
+    E::EXTENSIBILITY.representation_identifier(is_little_endian::<BO>())
+  }
+
+  fn to_bytes(value: &D) -> Result<Bytes> {
+    to_bytes::<D, BO>(value, E::EXTENSIBILITY).map(Bytes::from)
+  }
+}
+
+impl<D, E, BO> with_key::SerializerAdapter<D> for Xcdr2SerializerAdapter<D, E, BO>
+where
+  D: Keyed + Serialize,
+  <D as Keyed>::K: Serialize,
+  E: ExtensibilityKind,
+  BO: ByteOrder,
+{
+  fn key_to_bytes(value: &D::K) -> Result<Bytes> {
+    to_bytes::<D::K, BO>(value, E::EXTENSIBILITY).map(Bytes::from)
+  }
+}
+
+pub(crate) fn to_bytes<T, BO>(value: &T, extensibility: Extensibility) -> Result<Vec<u8>>
+where
+  T: ?Sized + Serialize,
+  BO: ByteOrder,
+{
+  let mut serializer = Xcdr2Serializer::<BO>::new(extensibility);
+  value.serialize(&mut serializer)?;
+  Ok(serializer.into_bytes())
+}
+
+// ---------------------------------------------------------------------------------
+// ---------------------------------------------------------------------------------
+
+// Sequences, arrays and maps get a DHEADER if their elements are not of a
+// primitive type. Serde does not tell us the element type up front, so the
+// decision is made when the first element starts serializing.
+enum Delimiter {
+  // No element seen yet. Sequences and maps remember where their length is,
+  // because the DHEADER must go in front of it.
+  Undecided { length_position: Option<usize> },
+  Delimited { dheader_position: usize },
+  Plain,
+}
+
+/// An XCDR2 serializer implementation, as specified in DDS-XTypes v1.3
+/// Section 7.4.3.
+///
+/// All structures are encoded with the same [`Extensibility`]. Enums are
+/// encoded as in [`CdrSerializer`](crate::serialization::CdrSerializer),
+/// i.e. as a `u32` discriminator followed by the variant data, and are
+/// treated as final regardless of the extensibility. Tuple structs are
+/// encoded like arrays.
+///
+/// `Option` is encoded as an optional member: In final and appendable
+/// structures it is prefixed by a boolean presence flag. A `None` member of a
+/// mutable structure is left out altogether.
+///
+/// Empty sequences are never delimited, because their element type cannot be
+/// determined from serde data model.
+pub struct Xcdr2Serializer<BO> {
+  buffer: Vec<u8>,
+  extensibility: Extensibility,
+  delimiters: Vec<Delimiter>,
+  pending: Option<usize>, // index to delimiters, waiting for first element
+  member_value: bool,     // next value is a member of a mutable struct
+  omit_member: bool,      // member value turned out to be None
+  phantom: PhantomData<BO>,
+}
+
+impl<BO> Xcdr2Serializer<BO>
+where
+  BO: ByteOrder,
+{
+  pub fn new(extensibility: Extensibility) -> Self {
+    Self {
+      buffer: Vec::new(),
+      extensibility,
+      delimiters: Vec::new(),
+      pending: None,
+      member_value: false,
+      omit_member: false,
+      phantom: PhantomData,
+    }
+  }
+
+  pub fn into_bytes(self) -> Vec<u8> {
+    self.buffer
+  }
+
+  fn align(&mut self, alignment: usize) {
+    let alignment = alignment.min(XCDR2_MAX_ALIGN);
+    let modulo = self.buffer.len() % alignment;
+    if modulo != 0 {
+      self
+        .buffer
+        .resize(self.buffer.len() + alignment - modulo, 0);
+    }
+  }
+
+  fn write_u32_at(&mut self, position: usize, value: u32) {
+    BO::write_u32(&mut self.buffer[position..position + 4], value);
+  }
+
+  // Reserve space for a length field and return its position.
+  fn begin_length(&mut self) -> usize {
+    self.align(4);
+    let position = self.buffer.len();
+    self.buffer.extend_from_slice(&[0; 4]);
+    position
+  }
+
+  // Backpatch the number of bytes written after the length field.
+  fn end_length(&mut self, position: usize) -> Result<()> {
+    let length = self.buffer.len() - position - 4;
+    if length > u32::MAX as usize {
+      return Err(Error::Message(
+        "XCDR2: Delimited data too long.".to_string(),
+      ));
+    }
+    self.write_u32_at(position, length as u32);
+    Ok(())
+  }
+
+  // Every value calls this before writing anything. This resolves whether a
+  // pending sequence or array needs a DHEADER, and ends the special treatment
+  // of Option as a mutable struct member.
+  fn begin_value(&mut self, compound: bool) {
+    self.member_value = false;
+    if let Some(index) = self.pending.take() {
+      let delimiter = if compound {
+        let dheader_position = match self.delimiters[index] {
+          Delimiter::Undecided {
+            length_position: Some(length_position),
+          } => {
+            // Nothing has been written after the length yet, and it is
+            // 4-aligned, so the insertion does not disturb alignment.
+            self
+              .buffer
+              .splice(length_position..length_position, [0; 4].iter().cloned());
+            length_position
+          }
+          _ => self.begin_length(),
+        };
+        Delimiter::Delimited { dheader_position }
+      } else {
+        Delimiter::Plain
+      };
+      self.delimiters[index] = delimiter;
+    }
+  }
+
+  fn write_number<F>(&mut self, size: usize, write: F) -> Result<()>
+  where
+    F: FnOnce(&mut [u8]),
+  {
+    self.begin_value(false);
+    self.align(size);
+    let position = self.buffer.len();
+    self.buffer.resize(position + size, 0);
+    write(&mut self.buffer[position..]);
+    Ok(())
+  }
+
+  fn begin_collection(&mut self, length: Option<usize>) -> Result<Xcdr2Compound<'_, BO>> {
+    let length_position = match length {
+      None => return Err(Error::SequenceLengthUnknown),
+      Some(0) => {
+        self.begin_value(true);
+        self.begin_length() // zero
+      }
+      Some(count) => {
+        self.begin_value(true);
+        let position = self.begin_length();
+        self.write_u32_at(position, count as u32);
+        position
+      }
+    };
+    Ok(self.begin_delimited(Some(length_position), length == Some(0)))
+  }
+
+  fn begin_delimited(
+    &mut self,
+    length_position: Option<usize>,
+    empty: bool,
+  ) -> Xcdr2Compound<'_, BO> {
+    let index = self.delimiters.len();
+    if empty {
+      self.delimiters.push(Delimiter::Plain);
+    } else {
+      self
+        .delimiters
+        .push(Delimiter::Undecided { length_position });
+      self.pending = Some(index);
+    }
+    Xcdr2Compound {
+      ser: self,
+      kind: CompoundKind::Delimited { index },
+    }
+  }
+
+  fn end_delimited(&mut self, index: usize) -> Result<()> {
+    if self.pending == Some(index) {
+      self.pending = None;
+    }
+    match self.delimiters.pop() {
+      Some(Delimiter::Delimited { dheader_position }) => self.end_length(dheader_position),
+      _ => Ok(()),
+    }
+  }
+
+  fn begin_union(&mut self, variant_index: u32) -> Result<()> {
+    self.write_number(4, |b| BO::write_u32(b, variant_index))
+  }
+}
+
+enum CompoundKind {
+  Delimited {
+    index: usize,
+  },
+  Struct {
+    dheader_position: Option<usize>,
+    member_id: u32,
+  },
+  Plain,
+}
+
+/// State for serializing compound types
+pub struct Xcdr2Compound<'a, BO> {
+  ser: &'a mut Xcdr2Serializer<BO>,
+  kind: CompoundKind,
+}
+
+impl<BO> Xcdr2Compound<'_, BO>
+where
+  BO: ByteOrder,
+{
+  fn element<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    value.serialize(&mut *self.ser)
+  }
+
+  fn field<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    match self.kind {
+      CompoundKind::Struct {
+        dheader_position: Some(_),
+        ref mut member_id,
+      } if self.ser.extensibility == Extensibility::Mutable => {
+        let ser = &mut *self.ser;
+        let member_start = ser.buffer.len();
+        // EMHEADER1: M_FLAG=0, LC=4, member id. NEXTINT gives the length.
+        ser.align(4);
+        let mut emheader = [0; 4];
+        BO::write_u32(&mut emheader, (LC_NEXTINT << 28) | *member_id);
+        ser.buffer.extend_from_slice(&emheader);
+        let nextint_position = ser.begin_length();
+        *member_id += 1;
+
+        ser.member_value = true;
+        ser.omit_member = false;
+        value.serialize(&mut *ser)?;
+        ser.member_value = false;
+        if ser.omit_member {
+          ser.omit_member = false;
+          ser.buffer.truncate(member_start);
+          Ok(())
+        } else {
+          ser.end_length(nextint_position)
+        }
+      }
+      _ => value.serialize(&mut *self.ser),
+    }
+  }
+
+  fn end(self) -> Result<()> {
+    match self.kind {
+      CompoundKind::Delimited { index } => self.ser.end_delimited(index),
+      CompoundKind::Struct {
+        dheader_position: Some(position),
+        ..
+      } => self.ser.end_length(position),
+      _ => Ok(()),
+    }
+  }
+}
+
+// ----------------------------------------------------------
+// ----------------------------------------------------------
+
+impl<'a, BO> ser::Serializer for &'a mut Xcdr2Serializer<BO>
+where
+  BO: ByteOrder,
+{
+  type Ok = ();
+  type Error = Error;
+
+  type SerializeSeq = Xcdr2Compound<'a, BO>;
+  type SerializeTuple = Xcdr2Compound<'a, BO>;
+  type SerializeTupleStruct = Xcdr2Compound<'a, BO>;
+  type SerializeTupleVariant = Xcdr2Compound<'a, BO>;
+  type SerializeMap = Xcdr2Compound<'a, BO>;
+  type SerializeStruct = Xcdr2Compound<'a, BO>;
+  type SerializeStructVariant = Xcdr2Compound<'a, BO>;
+
+  fn serialize_bool(self, v: bool) -> Result<()> {
+    self.serialize_u8(u8::from(v))
+  }
+
+  fn serialize_u8(self, v: u8) -> Result<()> {
+    self.begin_value(false);
+    self.buffer.push(v);
+    Ok(())
+  }
+
+  fn serialize_u16(self, v: u16) -> Result<()> {
+    self.write_number(2, |b| BO::write_u16(b, v))
+  }
+
+  fn serialize_u32(self, v: u32) -> Result<()> {
+    self.write_number(4, |b| BO::write_u32(b, v))
+  }
+
+  fn serialize_u64(self, v: u64) -> Result<()> {
+    self.write_number(8, |b| BO::write_u64(b, v))
+  }
+
+  fn serialize_u128(self, v: u128) -> Result<()> {
+    self.write_number(16, |b| BO::write_u128(b, v))
+  }
+
+  fn serialize_i8(self, v: i8) -> Result<()> {
+    self.serialize_u8(v as u8)
+  }
+
+  fn serialize_i16(self, v: i16) -> Result<()> {
+    self.write_number(2, |b| BO::write_i16(b, v))
+  }
+
+  fn serialize_i32(self, v: i32) -> Result<()> {
+    self.write_number(4, |b| BO::write_i32(b, v))
+  }
+
+  fn serialize_i64(self, v: i64) -> Result<()> {
+    self.write_number(8, |b| BO::write_i64(b, v))
+  }
+
+  fn serialize_i128(self, v: i128) -> Result<()> {
+    self.write_number(16, |b| BO::write_i128(b, v))
+  }
+
+  fn serialize_f32(self, v: f32) -> Result<()> {
+    self.write_number(4, |b| BO::write_f32(b, v))
+  }
+
+  fn serialize_f64(self, v: f64) -> Result<()> {
+    self.write_number(8, |b| BO::write_f64(b, v))
+  }
+
+  // Rust "char" is a 32-bit Unicode code point. Serialized as in CdrSerializer.
+  fn serialize_char(self, v: char) -> Result<()> {
+    self.serialize_u32(v as u32)
+  }
+
+  // Strings are not primitive types, so a sequence of strings is delimited.
+  fn serialize_str(self, v: &str) -> Result<()> {
+    self.begin_value(true);
+    let length_position = self.begin_length();
+    self.write_u32_at(length_position, v.len() as u32 + 1); // +1 for terminator
+    self.buffer.extend_from_slice(v.as_bytes());
+    self.buffer.push(0);
+    Ok(())
+  }
+
+  fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+    self.begin_value(true);
+    let length_position = self.begin_length();
+    self.write_u32_at(length_position, v.len() as u32);
+    self.buffer.extend_from_slice(v);
+    Ok(())
+  }
+
+  fn serialize_none(self) -> Result<()> {
+    if self.member_value {
+      self.member_value = false;
+      self.omit_member = true;
+      Ok(())
+    } else {
+      self.begin_value(true);
+      self.buffer.push(0);
+      Ok(())
+    }
+  }
+
+  fn serialize_some<T>(self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    if self.member_value {
+      // Presence of a mutable member is indicated by its EMHEADER
+      self.member_value = false;
+    } else {
+      self.begin_value(true);
+      self.buffer.push(1);
+    }
+    value.serialize(self)
+  }
+
+  fn serialize_unit(self) -> Result<()> {
+    self.begin_value(false);
+    Ok(())
+  }
+
+  fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+    self.serialize_unit()
+  }
+
+  fn serialize_unit_variant(
+    self,
+    _name: &'static str,
+    variant_index: u32,
+    _variant: &'static str,
+  ) -> Result<()> {
+    self.begin_union(variant_index)
+  }
+
+  fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    value.serialize(self)
+  }
+
+  fn serialize_newtype_variant<T>(
+    self,
+    _name: &'static str,
+    variant_index: u32,
+    _variant: &'static str,
+    value: &T,
+  ) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.begin_union(variant_index)?;
+    value.serialize(self)
+  }
+
+  fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+    self.begin_collection(len)
+  }
+
+  // Arrays have no length field, but are delimited like sequences.
+  fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+    self.begin_value(true);
+    Ok(self.begin_delimited(None, len == 0))
+  }
+
+  fn serialize_tuple_struct(
+    self,
+    _name: &'static str,
+    len: usize,
+  ) -> Result<Self::SerializeTupleStruct> {
+    self.serialize_tuple(len)
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    variant_index: u32,
+    _variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeTupleVariant> {
+    self.begin_union(variant_index)?;
+    Ok(Xcdr2Compound {
+      ser: self,
+      kind: CompoundKind::Plain,
+    })
+  }
+
+  // Maps are encoded like sequences of (key,value)-pairs.
+  fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+    self.begin_collection(len)
+  }
+
+  fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+    self.begin_value(true);
+    let dheader_position = match self.extensibility {
+      Extensibility::Final => None,
+      Extensibility::Appendable | Extensibility::Mutable => Some(self.begin_length()),
+    };
+    Ok(Xcdr2Compound {
+      ser: self,
+      kind: CompoundKind::Struct {
+        dheader_position,
+        member_id: 0,
+      },
+    })
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    variant_index: u32,
+    _variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeStructVariant> {
+    self.begin_union(variant_index)?;
+    Ok(Xcdr2Compound {
+      ser: self,
+      kind: CompoundKind::Plain,
+    })
+  }
+}
+
+impl<BO: ByteOrder> ser::SerializeSeq for Xcdr2Compound<'_, BO> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.element(value)
+  }
+
+  fn end(self) -> Result<()> {
+    Xcdr2Compound::end(self)
+  }
+}
+
+impl<BO: ByteOrder> ser::SerializeTuple for Xcdr2Compound<'_, BO> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.element(value)
+  }
+
+  fn end(self) -> Result<()> {
+    Xcdr2Compound::end(self)
+  }
+}
+
+impl<BO: ByteOrder> ser::SerializeTupleStruct for Xcdr2Compound<'_, BO> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.element(value)
+  }
+
+  fn end(self) -> Result<()> {
+    Xcdr2Compound::end(self)
+  }
+}
+
+impl<BO: ByteOrder> ser::SerializeTupleVariant for Xcdr2Compound<'_, BO> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.element(value)
+  }
+
+  fn end(self) -> Result<()> {
+    Xcdr2Compound::end(self)
+  }
+}
+
+impl<BO: ByteOrder> ser::SerializeMap for Xcdr2Compound<'_, BO> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.element(key)
+  }
+
+  fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.element(value)
+  }
+
+  fn end(self) -> Result<()> {
+    Xcdr2Compound::end(self)
+  }
+}
+
+impl<BO: ByteOrder> ser::SerializeStruct for Xcdr2Compound<'_, BO> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.field(value)
+  }
+
+  fn end(self) -> Result<()> {
+    Xcdr2Compound::end(self)
+  }
+}
+
+impl<BO: ByteOrder> ser::SerializeStructVariant for Xcdr2Compound<'_, BO> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.element(value)
+  }
+
+  fn end(self) -> Result<()> {
+    Xcdr2Compound::end(self)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use byteorder::{BigEndian, LittleEndian};
+  use serde::Serialize;
+
+  use super::*;
+
+  #[derive(Serialize)]
+  struct Basic {
+    a: u8,
+    b: u64,
+    c: String,
+  }
+
+  fn basic() -> Basic {
+    Basic {
+      a: 1,
+      b: 0x0102_0304_0506_0708,
+      c: "hi".to_string(),
+    }
+  }
+
+  const BASIC_FINAL_LE: [u8; 19] = [
+    0x01, 0x00, 0x00, 0x00, // a + padding
+    0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, // b, aligned only to 4
+    0x03, 0x00, 0x00, 0x00, b'h', b'i', 0x00, // c
+  ];
+
+  #[test]
+  fn xcdr2_final_struct() {
+    let bytes = to_bytes::<_, LittleEndian>(&basic(), Extensibility::Final).unwrap();
+    assert_eq!(bytes, BASIC_FINAL_LE);
+  }
+
+  #[test]
+  fn xcdr2_appendable_struct() {
+    let bytes = to_bytes::<_, LittleEndian>(&basic(), Extensibility::Appendable).unwrap();
+    assert_eq!(bytes[..4], [19, 0, 0, 0]);
+    assert_eq!(bytes[4..], BASIC_FINAL_LE);
+
+    let bytes = to_bytes::<_, BigEndian>(&basic(), Extensibility::Appendable).unwrap();
+    assert_eq!(bytes[..4], [0, 0, 0, 19]);
+  }
+
+  #[test]
+  fn xcdr2_mutable_struct() {
+    #[derive(Serialize)]
+    struct Mut {
+      x: i32,
+      y: Option<u16>,
+      z: u8,
+    }
+    let bytes = to_bytes::<_, LittleEndian>(
+      &Mut {
+        x: -1,
+        y: None,
+        z: 7,
+      },
+      Extensibility::Mutable,
+    )
+    .unwrap();
+    #[rustfmt::skip]
+    let expected = [
+      0x15, 0x00, 0x00, 0x00, // DHEADER
+      0x00, 0x00, 0x00, 0x40, 0x04, 0x00, 0x00, 0x00, // EMHEADER id 0, LC 4, NEXTINT
+      0xff, 0xff, 0xff, 0xff,
+      // member id 1 is None, and therefore absent
+      0x02, 0x00, 0x00, 0x40, 0x01, 0x00, 0x00, 0x00, // EMHEADER id 2, LC 4, NEXTINT
+      0x07,
+    ];
+    assert_eq!(bytes, expected);
+  }
+
+  #[test]
+  fn xcdr2_sequences() {
+    #[derive(Serialize)]
+    struct Inner {
+      a: u16,
+    }
+    // Primitive elements: no DHEADER
+    let bytes = to_bytes::<_, LittleEndian>(&vec![1u16, 2], Extensibility::Final).unwrap();
+    assert_eq!(bytes, [2, 0, 0, 0, 1, 0, 2, 0]);
+
+    // Non-primitive elements: DHEADER before the length
+    let bytes =
+      to_bytes::<_, LittleEndian>(&vec![Inner { a: 1 }, Inner { a: 2 }], Extensibility::Final)
+        .unwrap();
+    assert_eq!(bytes, [8, 0, 0, 0, 2, 0, 0, 0, 1, 0, 2, 0]);
+
+    let bytes = to_bytes::<_, LittleEndian>(&vec!["a".to_string()], Extensibility::Final).unwrap();
+    assert_eq!(bytes, [10, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, b'a', 0]);
+
+    // Arrays have no length, but are delimited just the same.
+    let bytes =
+      to_bytes::<_, LittleEndian>(&[Inner { a: 3 }, Inner { a: 4 }], Extensibility::Final).unwrap();
+    assert_eq!(bytes, [4, 0, 0, 0, 3, 0, 4, 0]);
+  }
+
+  #[test]
+  fn xcdr2_adapter_encodings() {
+    use crate::dds::traits::serde_adapters::no_key::SerializerAdapter;
+
+    assert_eq!(
+      <Xcdr2SerializerAdapter<Basic> as SerializerAdapter<Basic>>::output_encoding(),
+      RepresentationIdentifier::CDR2_LE
+    );
+    assert_eq!(
+      <Xcdr2SerializerAdapter<Basic, Appendable> as SerializerAdapter<Basic>>::output_encoding(),
+      RepresentationIdentifier::D_CDR2_LE
+    );
+    assert_eq!(
+      <Xcdr2SerializerAdapter<Basic, Mutable, BigEndian> as SerializerAdapter<Basic>>::output_encoding(),
+      RepresentationIdentifier::PL_CDR2_BE
+    );
+  }
+}
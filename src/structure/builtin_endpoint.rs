@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "discovery")]
 use super::parameter_id::ParameterId;
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Copy, Clone)]
 pub struct BuiltinEndpointSet {
@@ -17,6 +18,17 @@ impl BuiltinEndpointSet {
   pub const BUILTIN_ENDPOINT_PARTICIPANT_MESSAGE_DATA_WRITER: u32 = 0x00000400;
   pub const BUILTIN_ENDPOINT_PARTICIPANT_MESSAGE_DATA_READER: u32 = 0x00000800;
 
+  pub const BUILTIN_ENDPOINT_TL_SVC_REQUEST_DATA_WRITER: u32 = 0x00001000;
+  pub const BUILTIN_ENDPOINT_TL_SVC_REQUEST_DATA_READER: u32 = 0x00002000;
+  pub const BUILTIN_ENDPOINT_TL_SVC_REPLY_DATA_WRITER: u32 = 0x00004000;
+  pub const BUILTIN_ENDPOINT_TL_SVC_REPLY_DATA_READER: u32 = 0x00008000;
+
+  // DDS Security spec v1.1
+  pub const BUILTIN_PARTICIPANT_STATELESS_MESSAGE_WRITER: u32 = 0x00400000;
+  pub const BUILTIN_PARTICIPANT_STATELESS_MESSAGE_READER: u32 = 0x00800000;
+  pub const BUILTIN_PARTICIPANT_VOLATILE_MESSAGE_SECURE_WRITER: u32 = 0x01000000;
+  pub const BUILTIN_PARTICIPANT_VOLATILE_MESSAGE_SECURE_READER: u32 = 0x02000000;
+
   pub const DISC_BUILTIN_ENDPOINT_TOPICS_ANNOUNCER: u32 = 0x08000000;
   pub const DISC_BUILTIN_ENDPOINT_TOPICS_DETECTOR: u32 = 0x10000000;
 
@@ -27,8 +39,20 @@ impl BuiltinEndpointSet {
   pub fn contains(&self, other: u32) -> bool {
     (self.value & other) == other
   }
+
+  #[must_use]
+  pub fn without(self, other: u32) -> Self {
+    Self::from_u32(self.value & !other)
+  }
+
+  #[cfg(feature = "security")]
+  #[must_use]
+  pub fn with(self, other: u32) -> Self {
+    Self::from_u32(self.value | other)
+  }
 }
 
+#[cfg(feature = "discovery")]
 #[derive(Copy, Clone, Serialize, Deserialize)]
 pub struct BuiltinEndpointSetData {
   parameter_id: ParameterId,
@@ -36,6 +60,7 @@ pub struct BuiltinEndpointSetData {
   builtin_endpoint_set: BuiltinEndpointSet,
 }
 
+#[cfg(feature = "discovery")]
 impl BuiltinEndpointSetData {
   pub fn from(builtin_endpoint_set: BuiltinEndpointSet, parameter_id: ParameterId) -> Self {
     Self {
@@ -59,6 +84,7 @@ impl BuiltinEndpointQos {
   }
 }
 
+#[cfg(feature = "discovery")]
 #[derive(Serialize, Deserialize)]
 pub struct BuiltinEndpointQosData {
   parameter_id: ParameterId,
@@ -66,6 +92,7 @@ pub struct BuiltinEndpointQosData {
   builtin_endpoint_qos: BuiltinEndpointQos,
 }
 
+#[cfg(feature = "discovery")]
 impl BuiltinEndpointQosData {
   pub fn from(builtin_endpoint_qos: BuiltinEndpointQos) -> Self {
     Self {
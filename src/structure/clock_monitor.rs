@@ -0,0 +1,104 @@
+use std::time::{Duration as StdDuration, Instant};
+
+use crate::{
+  dds::statusevents::ClockJumpKind,
+  structure::{duration::Duration, time::Timestamp},
+};
+
+/// Detects clock anomalies, such as wall clock steps (NTP, manual setting) and
+/// system suspend/resume.
+///
+/// The monitor is checked periodically. It compares how much the monotonic
+/// clock (`Instant`) and the wall clock (`Timestamp`) have advanced since the
+/// previous check, and how this relates to the expected check period.
+pub(crate) struct ClockMonitor {
+  last_instant: Instant,
+  last_wall: Timestamp,
+  expected_period: StdDuration,
+  tolerance: StdDuration,
+}
+
+impl ClockMonitor {
+  pub fn new(expected_period: StdDuration, tolerance: StdDuration) -> Self {
+    Self::starting_from(Instant::now(), Timestamp::now(), expected_period, tolerance)
+  }
+
+  fn starting_from(
+    instant: Instant,
+    wall: Timestamp,
+    expected_period: StdDuration,
+    tolerance: StdDuration,
+  ) -> Self {
+    Self {
+      last_instant: instant,
+      last_wall: wall,
+      expected_period,
+      tolerance,
+    }
+  }
+
+  /// Check for clock anomalies since the previous check.
+  pub fn check(&mut self) -> Option<(ClockJumpKind, Duration)> {
+    self.check_at(Instant::now(), Timestamp::now())
+  }
+
+  fn check_at(&mut self, instant: Instant, wall: Timestamp) -> Option<(ClockJumpKind, Duration)> {
+    let monotonic_elapsed = instant.duration_since(self.last_instant);
+    let wall_elapsed = wall.duration_since(self.last_wall);
+    self.last_instant = instant;
+    self.last_wall = wall;
+
+    // Compare in Duration ticks, so that negative steps are exact.
+    let tolerance = Duration::from_std(self.tolerance).to_ticks();
+    let step = wall_elapsed.to_ticks() - Duration::from_std(monotonic_elapsed).to_ticks();
+
+    if step.abs() > tolerance {
+      Some((ClockJumpKind::WallClockStep, Duration::from_ticks(step)))
+    } else if monotonic_elapsed > self.expected_period + self.tolerance {
+      Some((
+        ClockJumpKind::Stall,
+        Duration::from_std(monotonic_elapsed - self.expected_period),
+      ))
+    } else {
+      None
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn clock_monitor_detects_jumps() {
+    let period = StdDuration::from_secs(2);
+    let tolerance = StdDuration::from_secs(5);
+    let mut instant = Instant::now();
+    let mut wall = Timestamp::now();
+    let mut monitor = ClockMonitor::starting_from(instant, wall, period, tolerance);
+
+    // normal tick
+    instant += period;
+    wall = wall + Duration::from(period);
+    assert_eq!(monitor.check_at(instant, wall), None);
+
+    // wall clock stepped backwards
+    instant += period;
+    wall = wall + Duration::from(period) - Duration::from_secs(60);
+    let (kind, amount) = monitor.check_at(instant, wall).unwrap();
+    assert_eq!(kind, ClockJumpKind::WallClockStep);
+    assert_eq!(amount.to_nanoseconds(), -60_000_000_000);
+
+    // monotonic clock advanced much more than expected, wall clock agrees
+    instant += StdDuration::from_secs(100);
+    wall = wall + Duration::from_secs(100);
+    let (kind, amount) = monitor.check_at(instant, wall).unwrap();
+    assert_eq!(kind, ClockJumpKind::Stall);
+    assert_eq!(amount.to_std(), StdDuration::from_secs(98));
+
+    // back to normal
+    instant += period;
+    wall = wall + Duration::from(period);
+    assert_eq!(monitor.check_at(instant, wall), None);
+  }
+}
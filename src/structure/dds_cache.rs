@@ -1,19 +1,23 @@
 use std::{
   cmp::max,
-  collections::{BTreeMap, HashMap},
+  collections::{BTreeMap, BTreeSet, HashMap},
+  fmt,
   ops::Bound::{Excluded, Included},
+  time::Instant,
 };
 
 #[allow(unused_imports)]
 use log::{debug, error, info, trace};
+use mio_extras::channel as mio_channel;
 
 use crate::{
   dds::{
     data_types::GUID,
+    helpers::WakerSlot,
     qos::{policy::ResourceLimits, QosPolicies, QosPolicyBuilder},
     typedesc::TypeDesc,
   },
-  structure::{sequence_number::SequenceNumber, time::Timestamp},
+  structure::{duration::Duration, sequence_number::SequenceNumber, time::Timestamp},
 };
 use super::cache_change::CacheChange;
 
@@ -100,9 +104,98 @@ impl DDSCache {
     }
   }
 
+  /// Records that the RTPS Reader side has lost (unmatched) a remote writer on
+  /// this topic. DataReaders pick these events up in timestamp order together
+  /// with cache changes, so that instance states and per-writer sequence number
+  /// tracking can be reset before the writer is possibly re-discovered.
+  pub fn topic_writer_lost(&mut self, topic_name: &str, instant: Timestamp, writer_guid: GUID) {
+    match self.topic_caches.get_mut(topic_name) {
+      Some(tc) => tc.writer_lost(instant, writer_guid),
+      None => {
+        error!(
+          "topic_writer_lost: Topic: {:?} is not in DDSCache",
+          topic_name
+        );
+      }
+    }
+  }
+
+  /// Registers a channel that is notified whenever the topic gets a new cache
+  /// change, and the waker slot of async tasks waiting for the notification.
+  /// This is used by DataReaders that have no RTPS Reader of their own, i.e.
+  /// the builtin topic readers. The registration is dropped when the
+  /// receiving end is dropped.
+  pub fn topic_add_listener(
+    &mut self,
+    topic_name: &str,
+    listener: mio_channel::SyncSender<()>,
+    waker: WakerSlot,
+  ) {
+    match self.topic_caches.get_mut(topic_name) {
+      Some(tc) => tc.listeners.0.push((listener, waker)),
+      None => {
+        error!(
+          "topic_add_listener: Topic: {:?} is not in DDSCache",
+          topic_name
+        );
+      }
+    }
+  }
+
+  /// Removes the changes whose lifespan has expired by `now`, and returns
+  /// when the next one expires.
+  pub fn topic_remove_expired(&mut self, topic_name: &str, now: Instant) -> Option<Instant> {
+    match self.topic_caches.get_mut(topic_name) {
+      Some(tc) => tc.remove_expired(now),
+      None => {
+        error!(
+          "topic_remove_expired: Topic: {:?} is not in DDSCache",
+          topic_name
+        );
+        None
+      }
+    }
+  }
+
+  /// Instants of changes removed due to lifespan expiry, in the order they
+  /// were removed, after the given position. Also returns the position to
+  /// continue from. DataReaders use these to remove the samples from their
+  /// own caches.
+  pub fn topic_get_expired_since(&self, topic_name: &str, position: u64) -> (Vec<Timestamp>, u64) {
+    match self.topic_caches.get(topic_name) {
+      Some(tc) => tc.get_expired_since(position),
+      None => (vec![], position),
+    }
+  }
+
+  pub fn topic_get_writers_lost_in_range(
+    &self,
+    topic_name: &str,
+    start_instant: &Timestamp,
+    end_instant: &Timestamp,
+  ) -> Vec<(Timestamp, GUID)> {
+    match self.topic_caches.get(topic_name) {
+      Some(tc) => tc.get_writers_lost_in_range(start_instant, end_instant),
+      None => vec![],
+    }
+  }
+
   pub fn add_change(&mut self, topic_name: &str, instant: &Timestamp, cache_change: CacheChange) {
+    self.add_expiring_change(topic_name, instant, cache_change, None);
+  }
+
+  /// Adds a change that is removed when its lifespan expires, at `expiry` on
+  /// the monotonic clock. Expired changes are removed by
+  /// [`topic_remove_expired`](Self::topic_remove_expired).
+  pub fn add_expiring_change(
+    &mut self,
+    topic_name: &str,
+    instant: &Timestamp,
+    cache_change: CacheChange,
+    expiry: Option<Instant>,
+  ) {
     match self.topic_caches.get_mut(topic_name) {
-      Some(tc) => tc.add_change(instant, cache_change),
+      Some(tc) => tc.add_change(instant, cache_change, expiry),
       None => {
         error!(
           "to_topic_add_change: Topic: {:?} is not in DDSCache",
@@ -111,6 +204,16 @@ impl DDSCache {
       }
     }
   }
+
+  // Number of CacheChanges in all topics
+  #[cfg(any(test, feature = "statistics"))]
+  pub fn change_count(&self) -> usize {
+    self
+      .topic_caches
+      .values()
+      .map(|tc| tc.history_cache.changes.len())
+      .sum()
+  }
 }
 
 #[derive(Debug)]
@@ -120,6 +223,43 @@ pub struct TopicCache {
   topic_data_type: TypeDesc,
   topic_qos: QosPolicies,
   history_cache: DDSHistoryCache,
+  // Times at which a remote writer was unmatched, e.g. due to lease expiry.
+  lost_writers: BTreeMap<Timestamp, GUID>,
+  // Lifespan expiry of changes, on the monotonic clock, so that wall clock
+  // jumps do not expire them early or keep them too long.
+  expiries: BTreeSet<(Instant, Timestamp)>,
+  // Changes removed due to lifespan expiry, numbered in removal order
+  expired_changes: BTreeMap<u64, Timestamp>,
+  expired_count: u64,
+  listeners: CacheListeners,
+}
+
+// Notification channels of DataReaders that read directly from the cache,
+// and the wakers of their async tasks.
+#[derive(Default)]
+struct CacheListeners(Vec<(mio_channel::SyncSender<()>, WakerSlot)>);
+
+impl CacheListeners {
+  fn notify(&mut self) {
+    // A full channel already has a pending notification, so it is kept. Only
+    // disconnected ones are removed.
+    self.0.retain(|(listener, waker)| {
+      let connected = !matches!(
+        listener.try_send(()),
+        Err(mio_channel::TrySendError::Disconnected(_))
+      );
+      if connected {
+        waker.wake();
+      }
+      connected
+    });
+  }
+}
+
+impl fmt::Debug for CacheListeners {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "CacheListeners({})", self.0.len())
+  }
 }
 
 impl TopicCache {
@@ -129,23 +269,66 @@ impl TopicCache {
       topic_data_type,
       topic_qos: QosPolicyBuilder::new().build(),
       history_cache: DDSHistoryCache::new(),
+      lost_writers: BTreeMap::new(),
+      expiries: BTreeSet::new(),
+      expired_changes: BTreeMap::new(),
+      expired_count: 0,
+      listeners: CacheListeners::default(),
     }
   }
 
+  // How many removals of expired changes are remembered for DataReaders that
+  // have not yet caught up
+  const EXPIRED_CHANGES_KEPT: u64 = 1024;
+
   pub fn get_change(&self, instant: &Timestamp) -> Option<&CacheChange> {
     self.history_cache.get_change(instant)
   }
 
-  pub fn add_change(&mut self, instant: &Timestamp, cache_change: CacheChange) {
-    self
-      .history_cache
-      .add_change(instant, cache_change)
-      .map(|cc_back| {
-        debug!(
-          "DDSCache insert failed topic={:?} cache_change={:?}",
-          self.topic_name, cc_back
-        );
-      });
+  pub fn add_change(
+    &mut self,
+    instant: &Timestamp,
+    cache_change: CacheChange,
+    expiry: Option<Instant>,
+  ) {
+    match self.history_cache.add_change(instant, cache_change) {
+      Some(cc_back) => debug!(
+        "DDSCache insert failed topic={:?} cache_change={:?}",
+        self.topic_name, cc_back
+      ),
+      None => {
+        if let Some(expiry) = expiry {
+          self.expiries.insert((expiry, *instant));
+        }
+        self.listeners.notify();
+      }
+    }
+  }
+
+  pub fn remove_expired(&mut self, now: Instant) -> Option<Instant> {
+    let not_expired = self.expiries.split_off(&(now, Timestamp::INVALID));
+    let expired = std::mem::replace(&mut self.expiries, not_expired);
+    for (_expiry, instant) in expired {
+      // The change may have been removed already for other reasons.
+      if self.history_cache.remove_change(&instant).is_some() {
+        self.expired_count += 1;
+        self.expired_changes.insert(self.expired_count, instant);
+      }
+    }
+    let first_kept = self
+      .expired_count
+      .saturating_sub(Self::EXPIRED_CHANGES_KEPT);
+    self.expired_changes = self.expired_changes.split_off(&first_kept);
+    self.expiries.iter().next().map(|(expiry, _)| *expiry)
+  }
+
+  pub fn get_expired_since(&self, position: u64) -> (Vec<Timestamp>, u64) {
+    let expired = self
+      .expired_changes
+      .range((Excluded(position), Included(self.expired_count)))
+      .map(|(_, i)| *i)
+      .collect();
+    (expired, self.expired_count)
   }
 
   pub fn get_changes_in_range(
@@ -163,6 +346,29 @@ impl TopicCache {
     self.history_cache.remove_change(instant)
   }
 
+  pub fn writer_lost(&mut self, instant: Timestamp, writer_guid: GUID) {
+    // Timestamps are used as keys, so make sure we do not overwrite an earlier
+    // event recorded at the exact same instant.
+    let mut key = instant;
+    while self.lost_writers.contains_key(&key) {
+      key = key + Duration::from_nanos(1);
+    }
+    self.lost_writers.insert(key, writer_guid);
+    self.listeners.notify();
+  }
+
+  pub fn get_writers_lost_in_range(
+    &self,
+    start_instant: &Timestamp,
+    end_instant: &Timestamp,
+  ) -> Vec<(Timestamp, GUID)> {
+    self
+      .lost_writers
+      .range((Excluded(start_instant), Included(end_instant)))
+      .map(|(i, g)| (*i, *g))
+      .collect()
+  }
+
   pub fn remove_changes_before(&mut self, instant: Timestamp) {
     // Look up some Topic-specific resource limit
     // and remove earliest samples until we are within limit.
@@ -187,6 +393,7 @@ impl TopicCache {
       .last()
       .map_or(&instant, |lim| max(lim, &instant));
     self.history_cache.remove_changes_before(split_key);
+    self.lost_writers = self.lost_writers.split_off(&instant);
   }
 }
 
@@ -307,7 +514,9 @@ mod tests {
 
   use super::DDSCache;
   use crate::{
-    dds::{ddsdata::DDSData, typedesc::TypeDesc, with_key::datawriter::WriteOptions},
+    dds::{
+      ddsdata::DDSData, helpers::WakerSlot, typedesc::TypeDesc, with_key::datawriter::WriteOptions,
+    },
     messages::submessages::submessage_elements::serialized_payload::SerializedPayload,
     structure::{cache_change::CacheChange, guid::GUID, sequence_number::SequenceNumber},
   };
@@ -386,4 +595,75 @@ mod tests {
     //   )
     // );
   }
+
+  #[test]
+  fn dds_cache_writers_lost() {
+    let mut cache = DDSCache::new();
+    let topic_name = "LostWriters";
+    cache.add_new_topic(topic_name.to_string(), TypeDesc::new("Foo".to_string()));
+
+    let start = crate::Timestamp::now();
+    let writer_a = GUID::from_bytes([1; 16]);
+    let writer_b = GUID::from_bytes([2; 16]);
+    let lost_at = start + crate::Duration::from_millis(10);
+    cache.topic_writer_lost(topic_name, lost_at, writer_a);
+    // same instant must not overwrite the previous event
+    cache.topic_writer_lost(topic_name, lost_at, writer_b);
+
+    let end = start + crate::Duration::from_secs(1);
+    let lost = cache.topic_get_writers_lost_in_range(topic_name, &start, &end);
+    assert_eq!(
+      lost.iter().map(|(_, g)| *g).collect::<Vec<_>>(),
+      vec![writer_a, writer_b]
+    );
+    // range start is exclusive
+    assert_eq!(
+      cache
+        .topic_get_writers_lost_in_range(topic_name, &lost_at, &end)
+        .len(),
+      1
+    );
+
+    cache.topic_remove_before(topic_name, end);
+    assert!(cache
+      .topic_get_writers_lost_in_range(topic_name, &start, &end)
+      .is_empty());
+  }
+
+  #[test]
+  fn dds_cache_listeners() {
+    use mio_extras::channel as mio_channel;
+
+    let mut cache = DDSCache::new();
+    let topic_name = "Listened";
+    cache.add_new_topic(topic_name.to_string(), TypeDesc::new("Foo".to_string()));
+
+    let (sender, receiver) = mio_channel::sync_channel(1);
+    cache.topic_add_listener(topic_name, sender, WakerSlot::default());
+    let (dropped_sender, dropped_receiver) = mio_channel::sync_channel(1);
+    cache.topic_add_listener(topic_name, dropped_sender, WakerSlot::default());
+    drop(dropped_receiver);
+
+    let change = |sn| {
+      CacheChange::new(
+        GUID::GUID_UNKNOWN,
+        SequenceNumber::new(sn),
+        WriteOptions::default(),
+        DDSData::new(SerializedPayload::default()),
+      )
+    };
+    cache.add_change(topic_name, &crate::Timestamp::now(), change(1));
+    cache.add_change(topic_name, &crate::Timestamp::now(), change(2));
+    // The channel is full after the first change, but it is not removed.
+    assert!(receiver.try_recv().is_ok());
+    assert!(receiver.try_recv().is_err());
+
+    // A duplicate is discarded, so there is nothing new to read.
+    cache.add_change(topic_name, &crate::Timestamp::now(), change(2));
+    assert!(receiver.try_recv().is_err());
+
+    cache.add_change(topic_name, &crate::Timestamp::now(), change(3));
+    assert!(receiver.try_recv().is_ok());
+    assert_eq!(cache.topic_caches[topic_name].listeners.0.len(), 1);
+  }
 }
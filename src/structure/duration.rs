@@ -3,6 +3,7 @@ use std::{convert::TryFrom, ops::Div};
 use speedy::{Readable, Writable};
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "discovery")]
 use super::parameter_id::ParameterId;
 
 #[derive(
@@ -150,6 +151,7 @@ impl std::ops::Mul<Duration> for f64 {
   }
 }
 
+#[cfg(feature = "discovery")]
 #[derive(Serialize, Deserialize)]
 pub struct DurationData {
   parameter_id: ParameterId,
@@ -157,6 +159,7 @@ pub struct DurationData {
   duration: Duration,
 }
 
+#[cfg(feature = "discovery")]
 impl DurationData {
   pub fn from(duration: Duration) -> Self {
     Self {
@@ -1,4 +1,4 @@
-use std::{fmt, hash::Hash, ops::RangeBounds};
+use std::{fmt, hash::Hash, ops::RangeBounds, str::FromStr};
 
 use speedy::{Context, Readable, Reader, Writable, Writer};
 use serde::{Deserialize, Serialize};
@@ -7,6 +7,7 @@ use mio::Token;
 use log::warn;
 use static_assertions as sa;
 
+#[cfg(feature = "discovery")]
 use super::parameter_id::ParameterId;
 use crate::dds::traits::key::Key;
 
@@ -67,6 +68,23 @@ impl fmt::Debug for GuidPrefix {
   }
 }
 
+/// Formats as 24 hexadecimal digits, e.g. `010f4a7b2c3d4e5f60718293`.
+impl fmt::Display for GuidPrefix {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write_hex(f, &self.bytes)
+  }
+}
+
+impl FromStr for GuidPrefix {
+  type Err = ParseGuidError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Ok(Self {
+      bytes: parse_hex(s)?,
+    })
+  }
+}
+
 impl Default for GuidPrefix {
   fn default() -> Self {
     Self::UNKNOWN
@@ -273,6 +291,41 @@ impl EntityId {
     entity_key: [0x00, 0x02, 0x00],
     entity_kind: EntityKind::READER_WITH_KEY_BUILT_IN,
   };
+  // XTypes 1.3 Section 7.6.3.3.4 TypeLookup service
+  pub const TL_SVC_REQ_WRITER: Self = Self {
+    entity_key: [0x00, 0x03, 0x00],
+    entity_kind: EntityKind::WRITER_NO_KEY_BUILT_IN,
+  };
+  pub const TL_SVC_REQ_READER: Self = Self {
+    entity_key: [0x00, 0x03, 0x00],
+    entity_kind: EntityKind::READER_NO_KEY_BUILT_IN,
+  };
+  pub const TL_SVC_REPLY_WRITER: Self = Self {
+    entity_key: [0x00, 0x03, 0x01],
+    entity_kind: EntityKind::WRITER_NO_KEY_BUILT_IN,
+  };
+  pub const TL_SVC_REPLY_READER: Self = Self {
+    entity_key: [0x00, 0x03, 0x01],
+    entity_kind: EntityKind::READER_NO_KEY_BUILT_IN,
+  };
+  // DDS Security spec v1.1: authentication handshake
+  pub const P2P_BUILTIN_PARTICIPANT_STATELESS_WRITER: Self = Self {
+    entity_key: [0x00, 0x02, 0x01],
+    entity_kind: EntityKind::WRITER_NO_KEY_BUILT_IN,
+  };
+  pub const P2P_BUILTIN_PARTICIPANT_STATELESS_READER: Self = Self {
+    entity_key: [0x00, 0x02, 0x01],
+    entity_kind: EntityKind::READER_NO_KEY_BUILT_IN,
+  };
+  // DDS Security spec v1.1: exchange of crypto tokens
+  pub const P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER: Self = Self {
+    entity_key: [0xff, 0x02, 0x02],
+    entity_kind: EntityKind::WRITER_NO_KEY_BUILT_IN,
+  };
+  pub const P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_READER: Self = Self {
+    entity_key: [0xff, 0x02, 0x02],
+    entity_kind: EntityKind::READER_NO_KEY_BUILT_IN,
+  };
 
   pub const MIN: Self = Self {
     entity_key: [0x00; 3],
@@ -380,6 +433,22 @@ impl EntityId {
   }
 }
 
+/// Formats as 8 hexadecimal digits: three bytes of entity key followed by the
+/// entity kind, e.g. `000001c1` for `EntityId::PARTICIPANT`.
+impl fmt::Display for EntityId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write_hex(f, &self.to_slice())
+  }
+}
+
+impl FromStr for EntityId {
+  type Err = ParseGuidError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Ok(Self::from_slice(parse_hex(s)?))
+  }
+}
+
 impl Default for EntityId {
   fn default() -> Self {
     Self::UNKNOWN
@@ -417,6 +486,22 @@ impl fmt::Debug for EntityId {
       Self::P2P_BUILTIN_PARTICIPANT_MESSAGE_READER => {
         f.write_str("EntityId::P2P_BUILTIN_PARTICIPANT_MESSAGE_READER")
       }
+      Self::TL_SVC_REQ_WRITER => f.write_str("EntityId::TL_SVC_REQ_WRITER"),
+      Self::TL_SVC_REQ_READER => f.write_str("EntityId::TL_SVC_REQ_READER"),
+      Self::TL_SVC_REPLY_WRITER => f.write_str("EntityId::TL_SVC_REPLY_WRITER"),
+      Self::TL_SVC_REPLY_READER => f.write_str("EntityId::TL_SVC_REPLY_READER"),
+      Self::P2P_BUILTIN_PARTICIPANT_STATELESS_WRITER => {
+        f.write_str("EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_WRITER")
+      }
+      Self::P2P_BUILTIN_PARTICIPANT_STATELESS_READER => {
+        f.write_str("EntityId::P2P_BUILTIN_PARTICIPANT_STATELESS_READER")
+      }
+      Self::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER => {
+        f.write_str("EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER")
+      }
+      Self::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_READER => {
+        f.write_str("EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_READER")
+      }
       _ => {
         f.write_str("EntityId {")?;
         self.entity_key.fmt(f)?;
@@ -567,6 +652,59 @@ impl fmt::Debug for GUID {
   }
 }
 
+/// Formats the 16 bytes of the GUID in the usual GUID/UUID text format of
+/// 8-4-4-4-12 hexadecimal digits, e.g.
+/// `010f4a7b-2c3d-4e5f-6071-8293000001c1`. The GuidPrefix is the first 24
+/// digits and the EntityId the last 8.
+impl fmt::Display for GUID {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let bytes = self.to_bytes();
+    write_hex(f, &bytes[0..4])?;
+    for group in [&bytes[4..6], &bytes[6..8], &bytes[8..10], &bytes[10..16]] {
+      f.write_str("-")?;
+      write_hex(f, group)?;
+    }
+    Ok(())
+  }
+}
+
+/// Parses the format produced by `Display`. The hyphens are optional.
+impl FromStr for GUID {
+  type Err = ParseGuidError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let digits: String = match s.len() {
+      32 => s.to_string(),
+      36 if [8, 13, 18, 23].iter().all(|&i| s.as_bytes()[i] == b'-') => {
+        s.chars().filter(|c| *c != '-').collect()
+      }
+      _ => return Err(ParseGuidError),
+    };
+    Ok(Self::from_bytes(parse_hex(&digits)?))
+  }
+}
+
+/// Error from parsing [`GUID`], [`GuidPrefix`], or [`EntityId`] from text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("invalid GUID text format")]
+pub struct ParseGuidError;
+
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+  bytes.iter().try_for_each(|b| write!(f, "{:02x}", b))
+}
+
+fn parse_hex<const N: usize>(s: &str) -> Result<[u8; N], ParseGuidError> {
+  if s.len() != 2 * N || !s.is_ascii() {
+    return Err(ParseGuidError);
+  }
+  let mut bytes = [0; N];
+  for (i, byte) in bytes.iter_mut().enumerate() {
+    *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|_| ParseGuidError)?;
+  }
+  Ok(bytes)
+}
+
+#[cfg(feature = "discovery")]
 #[derive(Serialize, Deserialize)]
 pub(crate) struct GUIDData {
   parameter_id: ParameterId,
@@ -574,6 +712,7 @@ pub(crate) struct GUIDData {
   guid: GUID,
 }
 
+#[cfg(feature = "discovery")]
 impl GUIDData {
   pub fn from(guid: GUID, parameter_id: ParameterId) -> Self {
     Self {
@@ -593,6 +732,27 @@ mod tests {
 
   use super::*;
 
+  #[test]
+  fn guid_text_format() {
+    let guid = GUID::from_bytes([
+      0x01, 0x0f, 0x4a, 0x7b, 0x2c, 0x3d, 0x4e, 0x5f, 0x60, 0x71, 0x82, 0x93, 0x00, 0x00, 0x01,
+      0xc1,
+    ]);
+    let text = guid.to_string();
+    assert_eq!(text, "010f4a7b-2c3d-4e5f-6071-8293000001c1");
+    assert_eq!(text.parse::<GUID>(), Ok(guid));
+    assert_eq!("010F4A7B2C3D4E5F60718293000001C1".parse::<GUID>(), Ok(guid));
+
+    assert_eq!(guid.prefix.to_string(), "010f4a7b2c3d4e5f60718293");
+    assert_eq!("010f4a7b2c3d4e5f60718293".parse(), Ok(guid.prefix));
+    assert_eq!(EntityId::PARTICIPANT.to_string(), "000001c1");
+    assert_eq!("000001c1".parse(), Ok(EntityId::PARTICIPANT));
+
+    assert_eq!("010f4a7b-2c3d".parse::<GUID>(), Err(ParseGuidError));
+    assert_eq!("0x0001c1".parse::<EntityId>(), Err(ParseGuidError));
+    assert_eq!("000001c1ff".parse::<EntityId>(), Err(ParseGuidError));
+  }
+
   #[test]
   fn serde_test() {
     use crate::serialization::{
@@ -1,24 +1,34 @@
 use std::{
-  convert::TryInto,
+  convert::TryFrom,
   net::{SocketAddrV4, SocketAddrV6},
 };
-pub use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+pub use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 
 use speedy::{Context, Readable, Reader, Writable, Writer};
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "discovery")]
 use super::parameter_id::ParameterId;
 
-mod kind {
+pub(crate) mod kind {
   pub const INVALID: i32 = -1;
   pub const RESERVED: i32 = 0;
   pub const UDP_V4: i32 = 1;
   pub const UDP_V6: i32 = 2;
+  // Vendor-specific kind of the RustDDS shared memory transport. Shared memory
+  // transports of other implementations are not compatible with it.
+  pub const SHARED_MEMORY: i32 = 0x0100_0001;
+  // Vendor-specific kind of the RustDDS TCP transport. TCP transports of other
+  // implementations frame the messages differently, so they do not get this.
+  pub const TCP_V4: i32 = 0x0100_0002;
 }
 
 const INVALID_PORT: u16 = 0;
 const INVALID_ADDRESS: [u8; 16] = [0; 16];
 
+/// Address where an RTPS endpoint receives messages, `Locator_t` in RTPS spec
+/// v2.5 Section "8.3.2 Type Definitions". The kind tells which transport
+/// reaches it.
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
 #[serde(into = "repr::Locator", from = "repr::Locator")]
 pub enum Locator {
@@ -26,6 +36,11 @@ pub enum Locator {
   Reserved,
   UdpV4(SocketAddrV4),
   UdpV6(SocketAddrV6),
+  /// RustDDS TCP transport, see
+  /// [`TransportKind::TcpV4`](crate::TransportKind::TcpV4)
+  TcpV4(SocketAddrV4),
+  /// Kinds that RustDDS has no variant for, e.g. vendor-specific kinds of
+  /// other implementations, and the RustDDS shared memory transport.
   Other {
     kind: i32,
     port: u32,
@@ -44,6 +59,7 @@ impl From<Locator> for SocketAddr {
     match locator {
       Locator::UdpV4(socket_address) => socket_address.into(),
       Locator::UdpV6(socket_address) => socket_address.into(),
+      Locator::TcpV4(socket_address) => socket_address.into(),
       Locator::Invalid | Locator::Reserved | Locator::Other { .. } => {
         let ip = Ipv6Addr::from(INVALID_ADDRESS).into();
         Self::new(ip, INVALID_PORT)
@@ -78,6 +94,7 @@ impl<C: Context> Writable<C> for Locator {
   }
 }
 
+#[cfg(feature = "discovery")]
 #[derive(Serialize, Deserialize)]
 pub struct Data {
   parameter_id: ParameterId,
@@ -85,6 +102,7 @@ pub struct Data {
   locator: Locator,
 }
 
+#[cfg(feature = "discovery")]
 impl Data {
   pub fn from(locator: Locator, parameter_id: ParameterId) -> Self {
     Self {
@@ -97,23 +115,32 @@ impl Data {
 
 impl From<repr::Locator> for Locator {
   fn from(repr: repr::Locator) -> Self {
+    // UDP and TCP ports are 16 bits, but the field is 32. Anything larger is
+    // garbage.
+    let ip_port = u16::try_from(repr.port);
+    let ipv4 = Ipv4Addr::new(
+      repr.address[12],
+      repr.address[13],
+      repr.address[14],
+      repr.address[15],
+    );
     match repr.kind {
       kind::INVALID => Self::Invalid,
       kind::RESERVED => Self::Reserved,
+      kind::UDP_V4 | kind::UDP_V6 | kind::TCP_V4 if ip_port.is_err() => Self::Invalid,
       kind::UDP_V4 => {
-        let ip = Ipv4Addr::new(
-          repr.address[12],
-          repr.address[13],
-          repr.address[14],
-          repr.address[15],
-        );
-        let socket_address = SocketAddrV4::new(ip, repr.port.try_into().unwrap());
+        let socket_address = SocketAddrV4::new(ipv4, ip_port.unwrap_or_default());
 
         Self::UdpV4(socket_address)
       }
+      kind::TCP_V4 => {
+        let socket_address = SocketAddrV4::new(ipv4, ip_port.unwrap_or_default());
+
+        Self::TcpV4(socket_address)
+      }
       kind::UDP_V6 => {
         let ip = Ipv6Addr::from(repr.address);
-        let socket_address = SocketAddrV6::new(ip, repr.port.try_into().unwrap(), 0, 0);
+        let socket_address = SocketAddrV6::new(ip, ip_port.unwrap_or_default(), 0, 0);
 
         Self::UdpV6(socket_address)
       }
@@ -137,6 +164,12 @@ impl From<Locator> for repr::Locator {
         let address = socket_address.ip().to_ipv6_compatible().octets();
         (kind, port.into(), address)
       }
+      Locator::TcpV4(socket_address) => {
+        let kind = kind::TCP_V4;
+        let port = socket_address.port();
+        let address = socket_address.ip().to_ipv6_compatible().octets();
+        (kind, port.into(), address)
+      }
       Locator::UdpV6(socket_address) => {
         let kind = kind::UDP_V6;
         let port = socket_address.port();
@@ -174,7 +207,7 @@ mod repr {
 mod tests {
   use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 
-  use speedy::{Endianness, Writable};
+  use speedy::{Endianness, Readable, Writable};
   use test_case::test_case;
 
   use super::Locator;
@@ -264,6 +297,26 @@ mod tests {
     ]
     ; "IPv6"
   )]
+  #[test_case(
+    Locator::TcpV4("127.0.0.1:8080".parse().unwrap()),
+    [
+      0x01, 0x00, 0x00, 0x02,  // RustDDS TCP_V4
+      0x00, 0x00, 0x1F, 0x90,  // Locator_t::port(8080),
+      0x00, 0x00, 0x00, 0x00,  // Locator_t::address[0:3]
+      0x00, 0x00, 0x00, 0x00,  // Locator_t::address[4:7]
+      0x00, 0x00, 0x00, 0x00,  // Locator_t::address[8:11]
+      0x7F, 0x00, 0x00, 0x01   // Locator_t::address[12:15]
+    ],
+    [
+      0x02, 0x00, 0x00, 0x01,  // RustDDS TCP_V4
+      0x90, 0x1F, 0x00, 0x00,  // Locator_t::port(8080),
+      0x00, 0x00, 0x00, 0x00,  // Locator_t::address[0:3]
+      0x00, 0x00, 0x00, 0x00,  // Locator_t::address[4:7]
+      0x00, 0x00, 0x00, 0x00,  // Locator_t::address[8:11]
+      0x7F, 0x00, 0x00, 0x01   // Locator_t::address[12:15]
+    ]
+    ; "TCP IPv4"
+  )]
   fn serialization(locator: Locator, big_endian: [u8; 24], little_endian: [u8; 24]) {
     assert_eq!(
       locator
@@ -277,5 +330,25 @@ mod tests {
         .unwrap(),
       little_endian
     );
+    assert_eq!(
+      Locator::read_from_buffer_with_ctx(Endianness::BigEndian, &big_endian).unwrap(),
+      locator
+    );
+    assert_eq!(
+      Locator::read_from_buffer_with_ctx(Endianness::LittleEndian, &little_endian).unwrap(),
+      locator
+    );
+  }
+
+  #[test]
+  fn port_out_of_range_is_invalid() {
+    let mut bytes = Locator::from(SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 7400))
+      .write_to_vec_with_ctx(Endianness::BigEndian)
+      .unwrap();
+    bytes[4] = 0x01; // port 0x01001CE8
+    assert_eq!(
+      Locator::read_from_buffer_with_ctx(Endianness::BigEndian, &bytes).unwrap(),
+      Locator::Invalid
+    );
   }
 }
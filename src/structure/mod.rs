@@ -1,5 +1,6 @@
 pub mod builtin_endpoint;
 pub mod cache_change;
+pub(crate) mod clock_monitor;
 pub mod dds_cache;
 pub mod duration;
 pub mod endpoint;
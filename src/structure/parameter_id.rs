@@ -62,6 +62,14 @@ impl ParameterId {
   pub const PID_ENTITY_NAME: Self = Self { value: 0x0062 };
   pub const PID_KEY_HASH: Self = Self { value: 0x0070 };
   pub const PID_STATUS_INFO: Self = Self { value: 0x0071 };
+  pub const PID_COHERENT_SET: Self = Self { value: 0x0056 };
+  // RTPS spec v2.4 Table 9.12. Must be understood, so that implementations
+  // without domain tag support do not join a tagged domain.
+  pub const PID_DOMAIN_TAG: Self = Self { value: 0x4014 };
+
+  // DDS Security spec v1.1
+  pub const PID_IDENTITY_TOKEN: Self = Self { value: 0x1001 };
+  pub const PID_PERMISSIONS_TOKEN: Self = Self { value: 0x1002 };
 
   // From Specification "Remote Procedure Calls over DDS v1.0"
   // Section 7.6.2.1.1 Extended PublicationBuiltin TopicData and
@@ -78,6 +86,28 @@ impl ParameterId {
   // 0x0083 commented out.
   // Wireshark calls this "PID_RELATED_ORIGINAL_WRITER_INFO".
   pub const PID_RELATED_SAMPLE_IDENTITY: Self = Self { value: /*0x0083*/ 0x800f };
+  // The one in the specification. Received samples that use it are read as
+  // PID_RELATED_SAMPLE_IDENTITY, see VendorWorkaround::RelatedSampleIdentityPid.
+  pub const PID_RELATED_SAMPLE_IDENTITY_STANDARD: Self = Self { value: 0x0083 };
+}
+
+impl ParameterId {
+  // RTPS spec v2.5 Section 9.6.2.2.1 "ParameterId space"
+  const MUST_UNDERSTAND_FLAG: u16 = 0x4000;
+  const VENDOR_SPECIFIC_FLAG: u16 = 0x8000;
+
+  /// A parameter with a vendor-specific id is interpreted only by the vendor
+  /// that defined it, and ignored by everyone else.
+  pub fn is_vendor_specific(self) -> bool {
+    self.value & Self::VENDOR_SPECIFIC_FLAG != 0
+  }
+
+  /// An unknown parameter with this flag must not be skipped: the receiver
+  /// must drop the whole parameter list instead. Vendor-specific ids from
+  /// other vendors are ignored regardless of the flag.
+  pub fn must_understand(self) -> bool {
+    self.value & Self::MUST_UNDERSTAND_FLAG != 0 && !self.is_vendor_specific()
+  }
 }
 
 #[cfg(test)]
@@ -396,5 +426,25 @@ mod tests {
       ParameterId::PID_STATUS_INFO,
       le = [0x71, 0x00],
       be = [0x00, 0x71]
+  },
+  {
+      pid_coherent_set,
+      ParameterId::PID_COHERENT_SET,
+      le = [0x56, 0x00],
+      be = [0x00, 0x56]
+  },
+  {
+      pid_domain_tag,
+      ParameterId::PID_DOMAIN_TAG,
+      le = [0x14, 0x40],
+      be = [0x40, 0x14]
   });
+
+  #[test]
+  fn must_understand_flag() {
+    assert!(ParameterId::PID_DOMAIN_TAG.must_understand());
+    assert!(!ParameterId::PID_TOPIC_NAME.must_understand());
+    assert!(ParameterId::PID_RELATED_SAMPLE_IDENTITY.is_vendor_specific());
+    assert!(!ParameterId { value: 0xc001 }.must_understand());
+  }
 }
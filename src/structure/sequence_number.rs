@@ -1,9 +1,12 @@
 use std::{
   cmp::min,
+  fmt,
   fmt::Debug,
   hash::Hash,
   mem::size_of,
-  ops::{Bound, RangeBounds},
+  num::ParseIntError,
+  ops::{Add, AddAssign, Bound, RangeBounds, Sub, SubAssign},
+  str::FromStr,
 };
 //use crate::messages::fragment_number::FragmentNumber;
 use std::collections::BTreeSet;
@@ -35,6 +38,9 @@ pub struct SequenceNumber(i64);
 
 impl SequenceNumber {
   pub const SEQUENCENUMBER_UNKNOWN: Self = Self((std::u32::MAX as i64) << 32);
+  /// The largest sequence number that can be sent: high word `i32::MAX`, low
+  /// word `u32::MAX`.
+  pub const MAX: Self = Self(i64::MAX);
 
   pub fn new(value: i64) -> Self {
     Self::from(value)
@@ -75,6 +81,50 @@ impl From<SequenceNumber> for i64 {
   }
 }
 
+// Offsetting by a plain integer, e.g. `sn + 1`
+//
+// Sequence numbers received from the network can be anything, e.g.
+// SequenceNumber::MAX in a HEARTBEAT, so offsetting saturates instead of
+// overflowing.
+impl Add<i64> for SequenceNumber {
+  type Output = Self;
+  fn add(self, rhs: i64) -> Self {
+    Self(self.0.saturating_add(rhs))
+  }
+}
+
+impl Sub<i64> for SequenceNumber {
+  type Output = Self;
+  fn sub(self, rhs: i64) -> Self {
+    Self(self.0.saturating_sub(rhs))
+  }
+}
+
+impl AddAssign<i64> for SequenceNumber {
+  fn add_assign(&mut self, rhs: i64) {
+    *self = *self + rhs;
+  }
+}
+
+impl SubAssign<i64> for SequenceNumber {
+  fn sub_assign(&mut self, rhs: i64) {
+    *self = *self - rhs;
+  }
+}
+
+impl fmt::Display for SequenceNumber {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl FromStr for SequenceNumber {
+  type Err = ParseIntError;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    s.parse().map(Self)
+  }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct SequenceNumberRange {
   begin: SequenceNumber,
@@ -93,6 +143,20 @@ impl SequenceNumberRange {
   pub fn end(&self) -> SequenceNumber {
     self.end
   }
+
+  /// Number of sequence numbers remaining in the range.
+  pub fn len(&self) -> u64 {
+    if self.begin > self.end {
+      0
+    } else {
+      // The difference fits in u64, even if not in i64.
+      (i64::from(self.end).wrapping_sub(i64::from(self.begin)) as u64).saturating_add(1)
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.begin > self.end
+  }
 }
 
 impl Iterator for SequenceNumberRange {
@@ -102,7 +166,11 @@ impl Iterator for SequenceNumberRange {
       None
     } else {
       let b = self.begin;
-      self.begin = b + SequenceNumber::new(1);
+      match i64::from(b).checked_add(1) {
+        Some(next) => self.begin = SequenceNumber::new(next),
+        // b is SequenceNumber::MAX, so the range ends here.
+        None => self.end = b - 1,
+      }
       Some(b)
     }
   }
@@ -306,6 +374,10 @@ where
   N: Clone + Copy + Debug + Hash + PartialEq + Eq + NumOps + From<i64> + Ord + PartialOrd,
   i64: From<N>,
 {
+  // RTPS v2.5 spec Section "8.3.5.5 SequenceNumberSet":
+  // maximum(SequenceNumberSet) - minimum(SequenceNumberSet) < 256
+  pub const MAX_BITS: u32 = 256;
+
   // Construct an empy set from given base number
   pub fn new(bitmap_base: N, num_bits: u32) -> Self {
     let word_count = (num_bits + 31) / 32;
@@ -324,7 +396,6 @@ where
     Self::new(bitmap_base, 0)
   }
 
-  #[allow(dead_code)] // Is this really unneccessary?
   pub fn is_empty(&self) -> bool {
     self.num_bits == 0 || self.iter().next().is_none()
   }
@@ -337,7 +408,7 @@ where
   fn insert(&mut self, sn: N) {
     if sn < self.bitmap_base
       || self.num_bits == 0
-      || sn >= self.bitmap_base + N::from(self.num_bits as i64)
+      || i64::from(sn).saturating_sub(i64::from(self.bitmap_base)) >= self.num_bits as i64
     {
       error!("out of bounds .insert({:?}) to {:?}", sn, self);
     } else {
@@ -381,10 +452,8 @@ where
           return Self::new_empty(N::from(1));
         }
         // start <= end, because BTreeSet properties.
-        let end = if i64::from(end) - i64::from(base) >= 256 {
-          // RTPS v2.5 spec Section "8.3.5.5 SequenceNumberSet":
-          // maximum(SequenceNumberSet) - minimum(SequenceNumberSet) < 256
-          let truncated_end = base + N::from(255);
+        let end = if i64::from(end).saturating_sub(i64::from(base)) >= (Self::MAX_BITS as i64) {
+          let truncated_end = base + N::from((Self::MAX_BITS as i64) - 1);
           error!("from_base_and_set : max size (256) exceeded, base = {:?}, start = {:?} end = {:?}. Truncating end to {:?}",
               base, start, end, truncated_end );
           truncated_end
@@ -392,8 +461,8 @@ where
           end
         };
         // sanity ok. Now do the actual work.
-        //let num_bits = i64::from( end - base + N::from(1) );
-        let mut sns = Self::new(base, i64::from(end) as u32);
+        let num_bits = (i64::from(end) - i64::from(base) + 1) as u32;
+        let mut sns = Self::new(base, num_bits);
         for s in set.iter().filter(|s| base <= **s && **s <= end) {
           sns.insert(*s);
         }
@@ -403,6 +472,26 @@ where
     }
   }
 
+  /// Like [`from_base_and_set`](Self::from_base_and_set), but when `set`
+  /// does not fit in one NumberSet, splits it into as many as needed, in
+  /// increasing order. The first one has the given base, and the others the
+  /// smallest number in them.
+  pub fn sets_from_base_and_set(base: N, set: &BTreeSet<N>) -> Vec<Self> {
+    let mut sets = Vec::new();
+    let mut window_base = base;
+    let mut window = BTreeSet::new();
+    for &n in set {
+      if i64::from(n).saturating_sub(i64::from(window_base)) >= (Self::MAX_BITS as i64) {
+        sets.push(Self::from_base_and_set(window_base, &window));
+        window.clear();
+        window_base = n;
+      }
+      window.insert(n);
+    }
+    sets.push(Self::from_base_and_set(window_base, &window));
+    sets
+  }
+
   pub fn iter(&self) -> NumberSetIter<N> {
     NumberSetIter::<N> {
       seq: self,
@@ -428,6 +517,23 @@ where
   fn read_from<R: Reader<'a, C>>(reader: &mut R) -> Result<Self, C::Error> {
     let bitmap_base: N = reader.read_value()?;
     let num_bits: u32 = reader.read_value()?;
+    // RTPS spec v2.5 Section "9.4.2.6 SequenceNumberSet": numBits is at most
+    // 256. Larger values would also make us allocate whatever the sender asks.
+    if num_bits > 256 {
+      return Err(speedy::Error::custom(format!("NumberSet has {} bits", num_bits)).into());
+    }
+    // The numbers in the set must be representable, e.g. a base close to
+    // SequenceNumber::MAX can have only a few bits.
+    let top = i64::from(bitmap_base.clone()).checked_add(num_bits.saturating_sub(1) as i64);
+    if top.map_or(true, |top| i64::from(N::from(top)) != top) {
+      return Err(
+        speedy::Error::custom(format!(
+          "NumberSet base {:?} with {} bits overflows",
+          bitmap_base, num_bits
+        ))
+        .into(),
+      );
+    }
     let word_count = (num_bits + 31) / 32;
     let mut bitmap: Vec<u32> = Vec::with_capacity(word_count as usize);
     for _ in 0..word_count {
@@ -526,13 +632,207 @@ where
 
 #[cfg(test)]
 mod tests {
+  use speedy::Endianness;
+  use proptest::prelude::*;
+
   use super::*;
 
+  #[test]
+  fn sequence_number_arithmetic_and_text() {
+    let mut sn = SequenceNumber::new(41);
+    sn += 1;
+    assert_eq!(sn, SequenceNumber::new(42));
+    assert_eq!(sn - 2, SequenceNumber::new(40));
+    assert_eq!(sn + 8, SequenceNumber::new(50));
+    assert_eq!(sn.to_string(), "42");
+    assert_eq!("42".parse::<SequenceNumber>(), Ok(sn));
+    assert!("x".parse::<SequenceNumber>().is_err());
+
+    let range = SequenceNumber::range_inclusive(sn, sn + 9);
+    assert_eq!(range.len(), 10);
+    assert!(range.contains(&(sn + 9)));
+    assert!(!range.contains(&(sn + 10)));
+    assert!(SequenceNumber::range_inclusive(sn, sn - 1).is_empty());
+  }
+
   #[test]
   fn sequence_number_starts_by_default_from_one() {
     assert_eq!(SequenceNumber::from(1), SequenceNumber::default());
   }
 
+  #[test]
+  fn sequence_number_set_bit_count_is_limited() {
+    let mut bytes = SequenceNumberSet::new_empty(SequenceNumber::new(1))
+      .write_to_vec_with_ctx(Endianness::LittleEndian)
+      .unwrap();
+    // numBits follows the 8-byte base
+    bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(
+      SequenceNumberSet::read_from_buffer_with_ctx(Endianness::LittleEndian, &bytes).is_err()
+    );
+  }
+
+  #[test]
+  fn sequence_number_offset_saturates() {
+    assert_eq!(SequenceNumber::MAX + 1, SequenceNumber::MAX);
+    assert_eq!(
+      SequenceNumber::new(i64::MIN) - 1,
+      SequenceNumber::new(i64::MIN)
+    );
+
+    let range = SequenceNumber::range_inclusive(SequenceNumber::MAX - 2, SequenceNumber::MAX);
+    assert_eq!(range.len(), 3);
+    assert_eq!(
+      range.collect::<Vec<_>>(),
+      vec![
+        SequenceNumber::MAX - 2,
+        SequenceNumber::MAX - 1,
+        SequenceNumber::MAX
+      ]
+    );
+  }
+
+  #[test]
+  fn sequence_number_set_from_base_and_set() {
+    let base = SequenceNumber::new(1000);
+    let set: BTreeSet<_> = [1000, 1003, 1010]
+      .iter()
+      .map(|sn| base + (sn - 1000))
+      .collect();
+    let sns = SequenceNumberSet::from_base_and_set(base, &set);
+    assert_eq!(sns.base(), base);
+    assert_eq!(sns.iter().collect::<BTreeSet<_>>(), set);
+    // 11 bits in one word
+    assert_eq!(sns.len_serialized(), 8 + 4 + 4);
+
+    // Not more than 256 bits
+    let set: BTreeSet<_> = (0..300).map(|i| base + i).collect();
+    let sns = SequenceNumberSet::from_base_and_set(base, &set);
+    assert_eq!(sns.iter().count(), 256);
+  }
+
+  #[test]
+  fn sequence_number_sets_from_base_and_set() {
+    let set: BTreeSet<_> = (1..=600).map(SequenceNumber::new).collect();
+    let sets = SequenceNumberSet::sets_from_base_and_set(SequenceNumber::new(1), &set);
+    assert_eq!(
+      sets.iter().map(NumberSet::base).collect::<Vec<_>>(),
+      vec![
+        SequenceNumber::new(1),
+        SequenceNumber::new(257),
+        SequenceNumber::new(513)
+      ]
+    );
+    assert_eq!(
+      sets
+        .iter()
+        .flat_map(NumberSet::iter)
+        .collect::<BTreeSet<_>>(),
+      set
+    );
+
+    let sets = SequenceNumberSet::sets_from_base_and_set(SequenceNumber::new(7), &BTreeSet::new());
+    assert_eq!(
+      sets,
+      vec![SequenceNumberSet::new_empty(SequenceNumber::new(7))]
+    );
+  }
+
+  #[test]
+  fn sequence_number_set_must_not_overflow() {
+    let mut set = SequenceNumberSet::new(SequenceNumber::MAX - 31, 32);
+    set.insert(SequenceNumber::MAX);
+    let bytes = set.write_to_vec_with_ctx(Endianness::BigEndian).unwrap();
+    let read_set =
+      SequenceNumberSet::read_from_buffer_with_ctx(Endianness::BigEndian, &bytes).unwrap();
+    assert_eq!(
+      read_set.iter().collect::<Vec<_>>(),
+      vec![SequenceNumber::MAX]
+    );
+
+    let set = SequenceNumberSet::new(SequenceNumber::MAX - 30, 32);
+    let bytes = set.write_to_vec_with_ctx(Endianness::BigEndian).unwrap();
+    assert!(SequenceNumberSet::read_from_buffer_with_ctx(Endianness::BigEndian, &bytes).is_err());
+
+    let set = FragmentNumberSet::new(FragmentNumber::new(u32::MAX - 30), 32);
+    let bytes = set.write_to_vec_with_ctx(Endianness::BigEndian).unwrap();
+    assert!(FragmentNumberSet::read_from_buffer_with_ctx(Endianness::BigEndian, &bytes).is_err());
+  }
+
+  fn endianness(big: bool) -> Endianness {
+    if big {
+      Endianness::BigEndian
+    } else {
+      Endianness::LittleEndian
+    }
+  }
+
+  proptest! {
+    #[test]
+    fn sequence_number_codec(value in any::<i64>(), big in any::<bool>()) {
+      let sn = SequenceNumber::new(value);
+      let bytes = sn.write_to_vec_with_ctx(endianness(big)).unwrap();
+      prop_assert_eq!(bytes.len(), 8);
+      prop_assert_eq!(
+        SequenceNumber::read_from_buffer_with_ctx(endianness(big), &bytes).unwrap(),
+        sn
+      );
+    }
+
+    #[test]
+    fn sequence_number_set_codec(
+      base in 1..i64::MAX - 255,
+      offsets in prop::collection::btree_set(0..256i64, 0..64),
+      big in any::<bool>(),
+    ) {
+      let base = SequenceNumber::new(base);
+      let set: BTreeSet<_> = offsets.into_iter().map(|offset| base + offset).collect();
+      let sns = SequenceNumberSet::from_base_and_set(base, &set);
+      let bytes = sns.write_to_vec_with_ctx(endianness(big)).unwrap();
+      prop_assert_eq!(bytes.len(), sns.len_serialized());
+
+      let read_sns = SequenceNumberSet::read_from_buffer_with_ctx(endianness(big), &bytes).unwrap();
+      prop_assert_eq!(read_sns.base(), base);
+      prop_assert_eq!(read_sns.iter().collect::<BTreeSet<_>>(), set.clone());
+      prop_assert_eq!(read_sns.iter().rev().collect::<BTreeSet<_>>(), set);
+    }
+
+    #[test]
+    fn sequence_number_sets_cover_the_set(
+      base in 1..i64::MAX / 2,
+      offsets in prop::collection::btree_set(0..2000i64, 0..300),
+    ) {
+      let base = SequenceNumber::new(base);
+      let set: BTreeSet<_> = offsets.into_iter().map(|offset| base + offset).collect();
+      let sets = SequenceNumberSet::sets_from_base_and_set(base, &set);
+      prop_assert_eq!(sets[0].base(), base);
+      for pair in sets.windows(2) {
+        prop_assert!(pair[0].base() < pair[1].base());
+        // Sets do not overlap
+        prop_assert!(pair[0].iter().all(|sn| sn < pair[1].base()));
+      }
+      for sns in &sets {
+        prop_assert!(sns.len_serialized() <= 8 + 4 + 32);
+      }
+      prop_assert_eq!(sets.iter().flat_map(NumberSet::iter).collect::<BTreeSet<_>>(), set);
+    }
+
+    #[test]
+    fn sequence_number_set_reads_any_bytes(
+      bytes in prop::collection::vec(any::<u8>(), 0..64),
+      big in any::<bool>(),
+    ) {
+      // Whatever is received, reading and iterating must not panic.
+      if let Ok(sns) = SequenceNumberSet::read_from_buffer_with_ctx(endianness(big), &bytes) {
+        let _ = sns.iter().count();
+        let _ = sns.iter().rev().count();
+      }
+      if let Ok(fns) = FragmentNumberSet::read_from_buffer_with_ctx(endianness(big), &bytes) {
+        let _ = fns.iter().count();
+      }
+    }
+  }
+
   #[test]
   fn fragment_number_starts_by_default_from_one() {
     assert_eq!(FragmentNumber::from(1u32), FragmentNumber::default());
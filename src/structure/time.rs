@@ -55,11 +55,11 @@ impl Timestamp {
     Self::from_nanos(chrono::Utc::now().timestamp_nanos() as u64)
   }
 
-  fn to_ticks(self) -> u64 {
+  pub(crate) fn to_ticks(self) -> u64 {
     (u64::from(self.seconds) << 32) + u64::from(self.fraction)
   }
 
-  fn from_ticks(ticks: u64) -> Self {
+  pub(crate) fn from_ticks(ticks: u64) -> Self {
     Self {
       seconds: (ticks >> 32) as u32,
       fraction: ticks as u32,
@@ -0,0 +1,20 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::dds::participant::{DomainParticipant, DomainParticipantBuilder};
+
+// DomainParticipants that discover each other by unicast to the initial peer
+// localhost, without multicast. Tests that use these work on hosts without
+// multicast routes, and do not discover participants of other tests, as long
+// as each test uses a domain id of its own. Domain ids 200..=232 are reserved
+// for these tests.
+pub(crate) fn loopback_participant_builder(domain_id: u16) -> DomainParticipantBuilder {
+  DomainParticipantBuilder::new(domain_id)
+    .initial_peer(IpAddr::V4(Ipv4Addr::LOCALHOST))
+    .multicast_discovery(false)
+}
+
+pub(crate) fn loopback_participant(domain_id: u16) -> DomainParticipant {
+  loopback_participant_builder(domain_id)
+    .build()
+    .expect("Failed to create participant")
+}
@@ -1,7 +1,11 @@
 #![cfg(test)]
 
 pub(crate) mod datareader_util;
+pub(crate) mod loopback;
 pub(crate) mod random_data;
+// Samples of discovery traffic, used mostly by the discovery tests
+#[cfg_attr(not(feature = "discovery"), allow(dead_code))]
 pub(crate) mod shape_type;
+#[cfg_attr(not(feature = "discovery"), allow(dead_code))]
 pub(crate) mod test_data;
 pub(crate) mod test_properties;
@@ -86,7 +86,7 @@ use crate::{
       policy::{
         Deadline, DestinationOrder, Durability, History, LatencyBudget, Lifespan, Liveliness,
         Ownership, Presentation, PresentationAccessScope, Reliability, ResourceLimits,
-        TimeBasedFilter,
+        TimeBasedFilter, GroupData, TopicData, UserData,
       },
       QosPolicyBuilder,
     },
@@ -265,6 +265,15 @@ pub(crate) fn subscription_builtin_topic_data() -> Option<SubscriptionBuiltinTop
     .lifespan(Lifespan {
       duration: Duration::from(StdDuration::from_secs(6 * 60)),
     })
+    .user_data(UserData {
+      value: vec![0xde, 0xad, 0xbe, 0xef, 0x01],
+    })
+    .topic_data(TopicData {
+      value: b"ab".to_vec(),
+    })
+    .group_data(GroupData {
+      value: b"group".to_vec(),
+    })
     .build();
 
   let sub_topic_data = SubscriptionBuiltinTopicData::new(
@@ -306,6 +315,13 @@ pub(crate) fn publication_builtin_topic_data() -> Option<PublicationBuiltinTopic
       coherent_access: true,
       ordered_access: false,
     }),
+    user_data: Some(UserData {
+      value: b"some user data".to_vec(),
+    }),
+    topic_data: Some(TopicData {
+      value: vec![1, 2, 3],
+    }),
+    group_data: Some(GroupData { value: vec![] }),
     related_datareader_key: None,
     service_instance_name: None,
     topic_aliases: None,
@@ -338,6 +354,9 @@ pub(crate) fn topic_data() -> Option<TopicBuiltinTopicData> {
       ordered_access: true,
     }),
     history: Some(History::KeepLast { depth: 25 }),
+    topic_data: Some(TopicData {
+      value: b"topic".to_vec(),
+    }),
     resource_limits: Some(ResourceLimits {
       max_samples: 5,
       max_instances: 10,
@@ -0,0 +1,367 @@
+//! Interoperability tests against CycloneDDS and FastDDS.
+//!
+//! The peers run the OMG interoperability test "shape" application
+//! (<https://github.com/omg-dds/dds-rtps>) in containers, see
+//! `tests/interop/docker/README.md` for building the images. The tests need
+//! docker and multicast on the host network, so they are behind the
+//! `interop_tests` feature:
+//!
+//! ```text
+//! cargo test --features interop_tests --test interop
+//! ```
+
+mod peer;
+mod shape;
+
+use std::{thread, time::Duration};
+
+use serde::de::DeserializeOwned;
+use rustdds::{
+  policy, with_key, DomainParticipant, Keyed, QosPolicies, QosPolicyBuilder, StatusEvented,
+  TopicKind,
+};
+use rustdds::dds::statusevents::DataReaderStatus;
+
+use crate::{
+  peer::{next_domain_id, parse_shape, Peer, Vendor},
+  shape::{ShapeType, ShapeTypeWithPayload, TYPE_NAME},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const TOPIC_NAME: &str = "Square";
+const PAYLOAD_SIZE: usize = 100_000;
+
+fn qos(reliable: bool) -> QosPolicies {
+  QosPolicyBuilder::new()
+    .reliability(if reliable {
+      policy::Reliability::Reliable {
+        max_blocking_time: rustdds::Duration::from_millis(100),
+      }
+    } else {
+      policy::Reliability::BestEffort
+    })
+    .durability(policy::Durability::Volatile)
+    .history(policy::History::KeepLast { depth: 100 })
+    .build()
+}
+
+struct Local {
+  participant: DomainParticipant,
+  qos: QosPolicies,
+}
+
+impl Local {
+  fn new(domain_id: u16, reliable: bool) -> Self {
+    Self {
+      participant: DomainParticipant::new(domain_id).unwrap(),
+      qos: qos(reliable),
+    }
+  }
+
+  fn topic(&self) -> rustdds::Topic {
+    self
+      .participant
+      .create_topic(
+        TOPIC_NAME.to_string(),
+        TYPE_NAME.to_string(),
+        &self.qos,
+        TopicKind::WithKey,
+      )
+      .unwrap()
+  }
+
+  fn reader<D>(&self) -> with_key::DataReader<D>
+  where
+    D: DeserializeOwned + Keyed + 'static,
+    D::K: rustdds::Key,
+  {
+    self
+      .participant
+      .create_subscriber(&self.qos)
+      .unwrap()
+      .create_datareader_cdr::<D>(&self.topic(), None)
+      .unwrap()
+  }
+
+  fn writer<D>(&self) -> with_key::DataWriter<D>
+  where
+    D: serde::Serialize + Keyed,
+    D::K: rustdds::Key,
+  {
+    self
+      .participant
+      .create_publisher(&self.qos)
+      .unwrap()
+      .create_datawriter_cdr::<D>(&self.topic(), None)
+      .unwrap()
+  }
+}
+
+// Takes samples until `done` returns true for the samples taken so far.
+fn take_until<D>(reader: &mut with_key::DataReader<D>, mut done: impl FnMut(&[D]) -> bool) -> Vec<D>
+where
+  D: DeserializeOwned + Keyed + 'static,
+  D::K: rustdds::Key,
+{
+  let mut samples = Vec::new();
+  for _ in 0..(TIMEOUT.as_millis() / POLL_INTERVAL.as_millis()) {
+    while let Some(sample) = reader.take_next_sample().unwrap() {
+      if let Ok(value) = sample.into_value() {
+        samples.push(value);
+      }
+    }
+    if done(&samples) {
+      break;
+    }
+    thread::sleep(POLL_INTERVAL);
+  }
+  samples
+}
+
+fn shape(color: &str, x: i32) -> ShapeType {
+  ShapeType {
+    color: color.to_string(),
+    x,
+    y: 50,
+    shapesize: 30,
+  }
+}
+
+fn discovery(vendor: Vendor) {
+  let domain_id = next_domain_id();
+  let _peer = Peer::start(vendor, domain_id, &["-S", "-t", TOPIC_NAME]);
+  let participant = DomainParticipant::new(domain_id).unwrap();
+
+  let mut participants = participant.participant_builtin_reader().unwrap();
+  let mut found_participant = false;
+  let mut subscriptions = participant.subscription_builtin_reader().unwrap();
+  let mut found_subscription = false;
+  for _ in 0..(TIMEOUT.as_millis() / POLL_INTERVAL.as_millis()) {
+    while let Some(sample) = participants.take_next_sample().unwrap() {
+      if let Ok(data) = sample.value() {
+        found_participant |= data.vendor_id.vendor_id == vendor.vendor_id();
+      }
+    }
+    while let Some(sample) = subscriptions.take_next_sample().unwrap() {
+      if let Ok(data) = sample.value() {
+        let topic_data = &data.subscription_topic_data;
+        found_subscription |=
+          topic_data.topic_name() == TOPIC_NAME && topic_data.type_name() == TYPE_NAME;
+      }
+    }
+    if found_participant && found_subscription {
+      break;
+    }
+    thread::sleep(POLL_INTERVAL);
+  }
+  assert!(found_participant, "Peer participant not discovered");
+  assert!(found_subscription, "Peer subscription not discovered");
+}
+
+fn reliable_delivery_from_peer(vendor: Vendor) {
+  let domain_id = next_domain_id();
+  let local = Local::new(domain_id, true);
+  let mut reader = local.reader::<ShapeType>();
+  let _peer = Peer::start(
+    vendor,
+    domain_id,
+    &["-P", "-t", TOPIC_NAME, "-c", "BLUE", "-r"],
+  );
+
+  let samples = take_until(&mut reader, |samples| samples.len() >= 10);
+  assert!(
+    samples.len() >= 10,
+    "Received only {} samples",
+    samples.len()
+  );
+  assert!(samples.iter().all(|s| s.color == "BLUE" && s.shapesize > 0));
+}
+
+fn reliable_delivery_to_peer(vendor: Vendor) {
+  let domain_id = next_domain_id();
+  let local = Local::new(domain_id, true);
+  let writer = local.writer::<ShapeType>();
+  let peer = Peer::start(vendor, domain_id, &["-S", "-t", TOPIC_NAME, "-r"]);
+
+  // Samples written before matching may be lost, but after the first one
+  // the peer receives there must be no gaps.
+  let mut received = Vec::new();
+  for x in 0..200 {
+    writer.write(shape("GREEN", x), None).unwrap();
+    while let Some(printed) = peer.wait_for(POLL_INTERVAL, parse_shape) {
+      received.push(printed.x);
+    }
+    if received.len() >= 20 {
+      break;
+    }
+  }
+  assert!(received.len() >= 20, "Peer received {:?}", received);
+  let first = received[0];
+  assert_eq!(
+    received,
+    (first..first + received.len() as i32).collect::<Vec<_>>()
+  );
+}
+
+fn fragmentation(vendor: Vendor) {
+  let domain_id = next_domain_id();
+  let local = Local::new(domain_id, true);
+  let payload_size = PAYLOAD_SIZE.to_string();
+
+  // Peer to RustDDS
+  let mut reader = local.reader::<ShapeTypeWithPayload>();
+  let publisher = Peer::start(
+    vendor,
+    domain_id,
+    &[
+      "-P",
+      "-t",
+      TOPIC_NAME,
+      "-c",
+      "RED",
+      "-r",
+      "--additional-payload-size",
+      &payload_size,
+    ],
+  );
+  let samples = take_until(&mut reader, |samples| !samples.is_empty());
+  assert!(!samples.is_empty(), "No fragmented samples received");
+  assert_eq!(samples[0].additional_payload.len(), PAYLOAD_SIZE);
+  drop(publisher);
+
+  // RustDDS to peer
+  let writer = local.writer::<ShapeTypeWithPayload>();
+  let subscriber = Peer::start(
+    vendor,
+    domain_id,
+    &[
+      "-S",
+      "-t",
+      TOPIC_NAME,
+      "-r",
+      "--additional-payload-size",
+      &payload_size,
+    ],
+  );
+  let mut received = None;
+  for x in 0..100 {
+    let sample = ShapeTypeWithPayload {
+      color: "YELLOW".to_string(),
+      x,
+      y: 50,
+      shapesize: 30,
+      additional_payload: vec![0xA5; PAYLOAD_SIZE],
+    };
+    writer.write(sample, None).unwrap();
+    received = subscriber.wait_for(Duration::from_millis(200), |line| {
+      parse_shape(line).filter(|shape| shape.color == "YELLOW")
+    });
+    if received.is_some() {
+      break;
+    }
+  }
+  assert!(received.is_some(), "Peer received no fragmented samples");
+}
+
+fn keyed_dispose(vendor: Vendor) {
+  let domain_id = next_domain_id();
+  let local = Local::new(domain_id, true);
+  let writer = local.writer::<ShapeType>();
+  let peer = Peer::start(vendor, domain_id, &["-S", "-t", TOPIC_NAME, "-r"]);
+
+  let mut alive = false;
+  for x in 0..100 {
+    writer.write(shape("ORANGE", x), None).unwrap();
+    if peer
+      .wait_for(Duration::from_millis(200), parse_shape)
+      .is_some()
+    {
+      alive = true;
+      break;
+    }
+  }
+  assert!(alive, "Peer received no samples");
+
+  writer.dispose(&"ORANGE".to_string(), None).unwrap();
+  let disposed = peer.wait_for(TIMEOUT, |line| {
+    (line.contains("ORANGE") && line.contains("NOT_ALIVE_DISPOSED")).then(|| ())
+  });
+  assert!(disposed.is_some(), "Peer did not see the dispose");
+}
+
+fn qos_mismatch(vendor: Vendor) {
+  let domain_id = next_domain_id();
+  let local = Local::new(domain_id, true);
+  let mut reader = local.reader::<ShapeType>();
+  // Best effort writer cannot match a reliable reader.
+  let _peer = Peer::start(
+    vendor,
+    domain_id,
+    &["-P", "-t", TOPIC_NAME, "-c", "BLUE", "-b"],
+  );
+
+  let mut incompatible = false;
+  for _ in 0..(TIMEOUT.as_millis() / POLL_INTERVAL.as_millis()) {
+    while let Some(status) = reader.try_recv_status() {
+      if let DataReaderStatus::RequestedIncompatibleQos { .. } = status {
+        incompatible = true;
+      }
+    }
+    if incompatible {
+      break;
+    }
+    thread::sleep(POLL_INTERVAL);
+  }
+  assert!(incompatible, "No RequestedIncompatibleQos status");
+  assert!(
+    reader.take_next_sample().unwrap().is_none(),
+    "Received samples from an incompatible writer"
+  );
+}
+
+// The same tests against each peer implementation
+macro_rules! interop_tests {
+  ($($module:ident => $vendor:expr),* $(,)?) => {
+    $(
+      mod $module {
+        use super::*;
+
+        #[test]
+        fn discovery() {
+          super::discovery($vendor)
+        }
+
+        #[test]
+        fn reliable_delivery_from_peer() {
+          super::reliable_delivery_from_peer($vendor)
+        }
+
+        #[test]
+        fn reliable_delivery_to_peer() {
+          super::reliable_delivery_to_peer($vendor)
+        }
+
+        #[test]
+        fn fragmentation() {
+          super::fragmentation($vendor)
+        }
+
+        #[test]
+        fn keyed_dispose() {
+          super::keyed_dispose($vendor)
+        }
+
+        #[test]
+        fn qos_mismatch() {
+          super::qos_mismatch($vendor)
+        }
+      }
+    )*
+  };
+}
+
+interop_tests!(
+  cyclonedds => Vendor::CycloneDds,
+  fastdds => Vendor::FastDds,
+);
@@ -0,0 +1,149 @@
+// A peer DDS implementation running the OMG interoperability "shape"
+// application in a container.
+
+use std::{
+  io::{BufRead, BufReader},
+  process::{Child, Command, Stdio},
+  sync::{
+    atomic::{AtomicU16, Ordering},
+    mpsc::{self, Receiver, RecvTimeoutError},
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vendor {
+  CycloneDds,
+  FastDds,
+}
+
+impl Vendor {
+  // The image runs the shape application as its entrypoint, so that container
+  // arguments are shape application arguments.
+  fn image(self) -> String {
+    let (variable, default) = match self {
+      Self::CycloneDds => (
+        "RUSTDDS_INTEROP_CYCLONEDDS_IMAGE",
+        "rustdds-interop-cyclonedds",
+      ),
+      Self::FastDds => ("RUSTDDS_INTEROP_FASTDDS_IMAGE", "rustdds-interop-fastdds"),
+    };
+    std::env::var(variable).unwrap_or_else(|_| default.to_string())
+  }
+
+  pub fn vendor_id(self) -> [u8; 2] {
+    match self {
+      Self::CycloneDds => [0x01, 0x10],
+      Self::FastDds => [0x01, 0x0F],
+    }
+  }
+}
+
+// Each test uses a domain of its own, so that tests can run in parallel.
+pub fn next_domain_id() -> u16 {
+  static NEXT: AtomicU16 = AtomicU16::new(50);
+  NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+pub struct Peer {
+  container: String,
+  child: Child,
+  lines: Receiver<String>,
+}
+
+impl Peer {
+  /// Starts the shape application with the given arguments, in addition to the
+  /// domain id and XCDR1 data representation.
+  pub fn start(vendor: Vendor, domain_id: u16, args: &[&str]) -> Self {
+    let container = format!("rustdds-interop-{}-{}", std::process::id(), domain_id);
+    let domain_id = domain_id.to_string();
+    // Host networking, so that multicast discovery reaches the test process.
+    let mut child = Command::new("docker")
+      .args(["run", "--rm", "--network", "host", "--name", &container])
+      .arg(vendor.image())
+      .args(["-d", &domain_id, "-x", "1"])
+      .args(args)
+      .stdout(Stdio::piped())
+      .stderr(Stdio::inherit())
+      .spawn()
+      .expect("Cannot run docker. The interop tests need docker and the peer images.");
+
+    let stdout = child.stdout.take().unwrap();
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+      for line in BufReader::new(stdout).lines() {
+        match line {
+          Ok(line) => {
+            if sender.send(line).is_err() {
+              break;
+            }
+          }
+          Err(_) => break,
+        }
+      }
+    });
+
+    Self {
+      container,
+      child,
+      lines,
+    }
+  }
+
+  /// Waits for a line of output for which `matches` returns `Some`.
+  pub fn wait_for<T>(
+    &self,
+    timeout: Duration,
+    mut matches: impl FnMut(&str) -> Option<T>,
+  ) -> Option<T> {
+    let deadline = Instant::now() + timeout;
+    loop {
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      match self.lines.recv_timeout(remaining) {
+        Ok(line) => {
+          if let Some(found) = matches(&line) {
+            return Some(found);
+          }
+        }
+        Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => return None,
+      }
+    }
+  }
+}
+
+impl Drop for Peer {
+  fn drop(&mut self) {
+    let _ = Command::new("docker")
+      .args(["rm", "--force", &self.container])
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .status();
+    let _ = self.child.wait();
+  }
+}
+
+/// A sample printed by a subscribing shape application, e.g.
+/// `Square     BLUE       010 020 [30]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrintedShape {
+  pub topic: String,
+  pub color: String,
+  pub x: i32,
+  pub y: i32,
+  pub shapesize: i32,
+}
+
+pub fn parse_shape(line: &str) -> Option<PrintedShape> {
+  let tokens: Vec<&str> = line.split_whitespace().collect();
+  match tokens[..] {
+    [topic, color, x, y, size, ..] => Some(PrintedShape {
+      topic: topic.to_string(),
+      color: color.to_string(),
+      x: x.parse().ok()?,
+      y: y.parse().ok()?,
+      shapesize: size.strip_prefix('[')?.strip_suffix(']')?.parse().ok()?,
+    }),
+    _ => None,
+  }
+}
@@ -0,0 +1,39 @@
+// The data types of the OMG interoperability shape application.
+
+use serde::{Deserialize, Serialize};
+use rustdds::Keyed;
+
+pub const TYPE_NAME: &str = "ShapeType";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShapeType {
+  pub color: String,
+  pub x: i32,
+  pub y: i32,
+  pub shapesize: i32,
+}
+
+impl Keyed for ShapeType {
+  type K = String;
+  fn key(&self) -> String {
+    self.color.clone()
+  }
+}
+
+// ShapeType with the additional payload of the shape application, used to
+// make samples large enough to be fragmented.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShapeTypeWithPayload {
+  pub color: String,
+  pub x: i32,
+  pub y: i32,
+  pub shapesize: i32,
+  pub additional_payload: Vec<u8>,
+}
+
+impl Keyed for ShapeTypeWithPayload {
+  type K = String;
+  fn key(&self) -> String {
+    self.color.clone()
+  }
+}